@@ -16,6 +16,7 @@ struct FileProcessingTask {
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[allow(dead_code)]
 struct ProcessingResult {
     success: bool,
     files_processed: u32,
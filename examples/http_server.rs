@@ -63,6 +63,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         resource_limits: ResourceLimits::default(),
         startup_timeout_ms: 5000,
         enable_debug: true,
+        function_policies: Default::default(),
+        pure_functions: Default::default(),
+        callable_functions: Default::default(),
+        environment_layer: None,
+        settings: None,
+        max_inline_result_bytes: None,
+        max_param_bytes: None,
+        function_param_limits: Default::default(),
+        max_inline_param_bytes: None,
+        recovery: None,
+        fuel_weight: 1,
+        fuel_class: Default::default(),
+        io_weight: 1,
+        call_queue: None,
+        scratch: None,
+        inbox: None,
+        oom_prediction: None,
+        profiling: None,
+        checkpoints: None,
+        tenant: None,
     };
     
     // Create the instance
@@ -33,7 +33,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Test function call with exports from the test module
     let exports = module.exports();
-    if let Some(export_name) = exports.get(0) {
+    if let Some(export_name) = exports.first() {
         println!("Testing function call for export: {}", export_name);
         
         // Try to call a function - this might fail if the function requires different parameters
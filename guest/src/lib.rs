@@ -0,0 +1,124 @@
+//! Guest-side helpers for plugins running in wasm-sandbox
+//!
+//! Host imports such as `sandbox_rpc.call` and `sandbox_config.get` return a
+//! non-negative value on success and a negative error code on failure. This
+//! crate decodes those values so a plugin can match on the failure and degrade
+//! gracefully, e.g. by falling back to a default when a service is not granted.
+
+#![no_std]
+
+#[cfg(target_arch = "wasm32")]
+extern crate alloc;
+
+use core::fmt;
+
+#[cfg(target_arch = "wasm32")]
+pub mod oom;
+#[cfg(target_arch = "wasm32")]
+pub mod secrets;
+#[cfg(target_arch = "wasm32")]
+pub mod stdlib;
+#[cfg(target_arch = "wasm32")]
+pub mod timer;
+
+/// Error code returned by a host import
+///
+/// Mirrors `wasm_sandbox::GuestErrorCode`; the values are stable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(i64)]
+pub enum ErrorCode {
+    /// The plugin lacks the capability or grant for the operation
+    PermissionDenied = -1,
+    
+    /// The plugin exhausted a quota or exceeded a size limit
+    QuotaExceeded = -2,
+    
+    /// The operation failed for another reason
+    Failed = -3,
+    
+    /// The requested item does not exist
+    NotFound = -4,
+    
+    /// The plugin is being rate limited and may retry later
+    Throttled = -5,
+    
+    /// The request was malformed
+    InvalidInput = -6,
+    
+    /// The facility is not available to this plugin
+    Unavailable = -7,
+    
+    /// The operation did not finish in time
+    Timeout = -8,
+}
+
+impl ErrorCode {
+    /// Look up a negative value returned by a host import
+    ///
+    /// Returns `None` for non-negative values. Negative values this version
+    /// does not know map to [`ErrorCode::Failed`].
+    pub const fn from_code(code: i64) -> Option<Self> {
+        Some(match code {
+            0.. => return None,
+            -1 => Self::PermissionDenied,
+            -2 => Self::QuotaExceeded,
+            -4 => Self::NotFound,
+            -5 => Self::Throttled,
+            -6 => Self::InvalidInput,
+            -7 => Self::Unavailable,
+            -8 => Self::Timeout,
+            _ => Self::Failed,
+        })
+    }
+    
+    /// Value returned by the host
+    pub const fn code(self) -> i64 {
+        self as i64
+    }
+    
+    /// Whether retrying the same call later may succeed
+    pub const fn is_transient(self) -> bool {
+        matches!(self, Self::Throttled | Self::Timeout)
+    }
+    
+    /// Stable snake_case name, e.g. `permission_denied`
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::PermissionDenied => "permission_denied",
+            Self::QuotaExceeded => "quota_exceeded",
+            Self::Failed => "failed",
+            Self::NotFound => "not_found",
+            Self::Throttled => "throttled",
+            Self::InvalidInput => "invalid_input",
+            Self::Unavailable => "unavailable",
+            Self::Timeout => "timeout",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Decode a status returned by a host import
+///
+/// Returns the non-negative value, or the error code.
+pub const fn check(value: i64) -> Result<i64, ErrorCode> {
+    match ErrorCode::from_code(value) {
+        Some(code) => Err(code),
+        None => Ok(value),
+    }
+}
+
+/// Decode a `(ptr << 32) | len` result returned by a host import
+///
+/// Returns the pointer and length of the data the host copied into guest
+/// memory, or the error code.
+pub const fn decode_slice(value: i64) -> Result<(u32, u32), ErrorCode> {
+    match check(value) {
+        Ok(packed) => Ok(((packed >> 32) as u32, packed as u32)),
+        Err(code) => Err(code),
+    }
+}
@@ -0,0 +1,89 @@
+//! Allocation that fails gracefully through `sandbox_alloc`
+//!
+//! Wrapping the plugin's global allocator in [`OomSafe`] reports each failed
+//! allocation to the host. If the plugin then aborts, as Rust does when a
+//! `Vec` or `String` can't grow, the host call fails with a structured memory
+//! error instead of an opaque `unreachable` trap. Plugins that want to recover
+//! allocate through [`try_buffer`], which returns
+//! [`ErrorCode::QuotaExceeded`] instead of aborting.
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOC: OomSafe<dlmalloc::GlobalDlmalloc> = OomSafe(dlmalloc::GlobalDlmalloc);
+//!
+//! #[no_mangle]
+//! pub extern "C" fn alloc(len: i32) -> *mut u8 {
+//!     wasm_sandbox_guest::oom::alloc_input(len as usize)
+//! }
+//! ```
+
+use alloc::vec::Vec;
+use core::alloc::{GlobalAlloc, Layout};
+
+use crate::ErrorCode;
+
+#[link(wasm_import_module = "sandbox_alloc")]
+extern "C" {
+    #[link_name = "failed"]
+    fn host_failed(size: i64) -> i32;
+}
+
+/// Tell the host an allocation of `size` bytes failed
+///
+/// [`OomSafe`] calls this itself; allocators not wrapped in it can call it
+/// before returning null.
+pub fn report_failure(size: usize) {
+    // SAFETY: the import takes no pointers
+    unsafe { host_failed(size as i64) };
+}
+
+/// Global allocator wrapper reporting failed allocations to the host
+pub struct OomSafe<A>(pub A);
+
+/// Report `ptr` as a failed allocation of `size` bytes if it is null
+fn reported(ptr: *mut u8, size: usize) -> *mut u8 {
+    if ptr.is_null() {
+        report_failure(size);
+    }
+    ptr
+}
+
+// SAFETY: every call is forwarded to the wrapped allocator unchanged
+unsafe impl<A: GlobalAlloc> GlobalAlloc for OomSafe<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        reported(self.0.alloc(layout), layout.size())
+    }
+    
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        reported(self.0.alloc_zeroed(layout), layout.size())
+    }
+    
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout)
+    }
+    
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        reported(self.0.realloc(ptr, layout, new_size), new_size)
+    }
+}
+
+/// Allocate a zeroed buffer of `len` bytes without aborting on failure
+pub fn try_buffer(len: usize) -> Result<Vec<u8>, ErrorCode> {
+    let mut buffer = Vec::new();
+    buffer.try_reserve_exact(len).map_err(|_| ErrorCode::QuotaExceeded)?;
+    buffer.resize(len, 0);
+    Ok(buffer)
+}
+
+/// Allocate `len` bytes for the host to copy input into
+///
+/// Intended as the body of the plugin's `alloc` export. Returns null if the
+/// allocation fails, which the host reports as the call running out of
+/// memory. The buffer is leaked; the plugin frees it once it has read the
+/// input.
+pub fn alloc_input(len: usize) -> *mut u8 {
+    match try_buffer(len) {
+        Ok(buffer) => buffer.leak().as_mut_ptr(),
+        Err(_) => core::ptr::null_mut(),
+    }
+}
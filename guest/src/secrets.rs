@@ -0,0 +1,25 @@
+//! Wrapper for reading host-provisioned secrets through `sandbox_secrets`
+//!
+//! Secrets fail with [`ErrorCode::PermissionDenied`] unless the plugin is granted
+//! them by name, and with [`ErrorCode::Unavailable`] if the host has no secrets
+//! provider.
+
+use crate::{decode_slice, ErrorCode};
+
+#[link(wasm_import_module = "sandbox_secrets")]
+extern "C" {
+    #[link_name = "get"]
+    fn host_get(name_ptr: *const u8, name_len: usize) -> i64;
+}
+
+/// Read the named secret
+///
+/// The host copies the value into memory obtained from the plugin's `alloc`
+/// export. The plugin owns that buffer and should overwrite it once the
+/// secret is no longer needed.
+pub fn get(name: &str) -> Result<&'static mut [u8], ErrorCode> {
+    // SAFETY: the host only reads `name`
+    let (ptr, len) = decode_slice(unsafe { host_get(name.as_ptr(), name.len()) })?;
+    // SAFETY: the host wrote `len` bytes into a fresh allocation at `ptr`
+    Ok(unsafe { core::slice::from_raw_parts_mut(ptr as usize as *mut u8, len as usize) })
+}
@@ -0,0 +1,50 @@
+//! Wrappers for the host's standard functions in `sandbox_std`
+//!
+//! The host must allow these imports (`ImportPolicy::allow_host_stdlib`).
+//! Randomness and UUIDs fail with [`ErrorCode::PermissionDenied`] unless the
+//! plugin is granted randomness.
+
+use crate::{check, ErrorCode};
+
+#[link(wasm_import_module = "sandbox_std")]
+extern "C" {
+    #[link_name = "monotonic_now"]
+    fn host_monotonic_now() -> i64;
+    
+    #[link_name = "random_fill"]
+    fn host_random_fill(ptr: *mut u8, len: usize) -> i32;
+    
+    #[link_name = "uuid_v4"]
+    fn host_uuid_v4(out_ptr: *mut u8) -> i32;
+    
+    #[link_name = "sha256"]
+    fn host_sha256(ptr: *const u8, len: usize, out_ptr: *mut u8) -> i32;
+}
+
+/// Nanoseconds since the instance was created
+pub fn monotonic_now() -> u64 {
+    // SAFETY: the import takes no arguments
+    unsafe { host_monotonic_now() as u64 }
+}
+
+/// Fill `buf` with random bytes
+pub fn random_fill(buf: &mut [u8]) -> Result<(), ErrorCode> {
+    // SAFETY: the host writes exactly `buf.len()` bytes at `buf`
+    check(unsafe { host_random_fill(buf.as_mut_ptr(), buf.len()) } as i64).map(|_| ())
+}
+
+/// Generate a random (version 4) UUID
+pub fn uuid_v4() -> Result<[u8; 16], ErrorCode> {
+    let mut uuid = [0; 16];
+    // SAFETY: the host writes 16 bytes at `uuid`
+    check(unsafe { host_uuid_v4(uuid.as_mut_ptr()) } as i64)?;
+    Ok(uuid)
+}
+
+/// SHA-256 digest of `data`
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut digest = [0; 32];
+    // SAFETY: the host reads `data` and writes 32 bytes at `digest`
+    unsafe { host_sha256(data.as_ptr(), data.len(), digest.as_mut_ptr()) };
+    digest
+}
@@ -0,0 +1,30 @@
+//! Wrappers for arming timers through `sandbox_timer`
+//!
+//! When a timer is due, the host calls the plugin's `on_timer(token: i64)`
+//! export with the token it was set with. Setting more timers than the host
+//! allows fails with [`ErrorCode::QuotaExceeded`].
+
+use crate::{check, ErrorCode};
+
+#[link(wasm_import_module = "sandbox_timer")]
+extern "C" {
+    #[link_name = "set"]
+    fn host_set(delay_ms: i64, token: i64) -> i64;
+    
+    #[link_name = "cancel"]
+    fn host_cancel(timer_id: i64) -> i64;
+}
+
+/// Have `on_timer(token)` called once `delay_ms` milliseconds have passed
+///
+/// Returns the timer's ID for [`cancel`].
+pub fn set(delay_ms: u64, token: i64) -> Result<u64, ErrorCode> {
+    // SAFETY: the import takes no pointers
+    check(unsafe { host_set(delay_ms as i64, token) }).map(|timer_id| timer_id as u64)
+}
+
+/// Disarm a timer that has not fired yet
+pub fn cancel(timer_id: u64) -> Result<(), ErrorCode> {
+    // SAFETY: the import takes no pointers
+    check(unsafe { host_cancel(timer_id as i64) }).map(|_| ())
+}
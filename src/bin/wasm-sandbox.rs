@@ -0,0 +1,336 @@
+//! Command-line interface for running and inspecting sandboxed modules
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use clap::{Parser, Subcommand};
+use wasm_sandbox::runtime::compilation::serve_compile_request;
+use wasm_sandbox::runtime::wit::host_world;
+use wasm_sandbox::{SandboxConfig, SandboxManifest, WasmSandbox};
+
+type CliResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// Run, inspect, and benchmark WebAssembly modules in a sandbox
+#[derive(Parser)]
+#[command(name = "wasm-sandbox", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Call an exported function and print the result as JSON
+    Run {
+        /// Path to the WebAssembly module
+        module: PathBuf,
+        
+        /// Function to call
+        #[arg(long)]
+        call: String,
+        
+        /// Function arguments as JSON
+        #[arg(long, default_value = "[]")]
+        args: String,
+        
+        /// Manifest providing capabilities and resource limits
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+    },
+    
+    /// Show a module's size, exports, and imports
+    Inspect {
+        /// Path to the WebAssembly module
+        module: PathBuf,
+    },
+    
+    /// Policy operations
+    Policy {
+        #[command(subcommand)]
+        command: PolicyCommand,
+    },
+    
+    /// Measure instantiation time and, optionally, call latency
+    Bench {
+        /// Path to the WebAssembly module
+        module: PathBuf,
+        
+        /// Function to call on each iteration
+        #[arg(long)]
+        call: Option<String>,
+        
+        /// Function arguments as JSON
+        #[arg(long, default_value = "[]")]
+        args: String,
+        
+        /// Number of iterations
+        #[arg(long, default_value_t = 100)]
+        iterations: u32,
+        
+        /// Manifest providing capabilities and resource limits
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+    },
+    
+    /// Print the WIT world of the host imports guests may use
+    Wit,
+    
+    /// Serve the JSON-RPC management API on stdio or a TCP listener
+    #[cfg(feature = "management-api")]
+    Serve {
+        /// Listen on this TCP address instead of stdio
+        #[arg(long)]
+        listen: Option<String>,
+        
+        /// Environment variable holding the token clients must authenticate with
+        #[arg(long, default_value = "WASM_SANDBOX_TOKEN")]
+        token_env: String,
+        
+        /// Manifest providing capabilities and resource limits
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+    },
+    
+    /// Compile a module from stdin for a host using subprocess compilation
+    #[command(hide = true)]
+    CompileWorker,
+}
+
+#[derive(Subcommand)]
+enum PolicyCommand {
+    /// Check that a module can run under a manifest's policy
+    Check {
+        /// Path to the manifest
+        policy: PathBuf,
+        
+        /// Path to the WebAssembly module
+        module: PathBuf,
+    },
+    
+    /// Print a manifest with its includes merged in
+    Resolve {
+        /// Path to the manifest
+        policy: PathBuf,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    
+    let result = match cli.command {
+        Command::Run { module, call, args, manifest } => {
+            run(&module, &call, &args, manifest.as_deref()).await
+        }
+        Command::Inspect { module } => inspect(&module),
+        Command::Policy { command: PolicyCommand::Check { policy, module } } => {
+            policy_check(&policy, &module)
+        }
+        Command::Policy { command: PolicyCommand::Resolve { policy } } => policy_resolve(&policy),
+        Command::Bench { module, call, args, iterations, manifest } => {
+            bench(&module, call.as_deref(), &args, iterations, manifest.as_deref()).await
+        }
+        Command::Wit => {
+            print!("{}", host_world());
+            Ok(true)
+        }
+        #[cfg(feature = "management-api")]
+        Command::Serve { listen, token_env, manifest } => {
+            serve(listen.as_deref(), &token_env, manifest.as_deref()).await
+        }
+        Command::CompileWorker => compile_worker(),
+    };
+    
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Create a sandbox configured from an optional manifest
+fn sandbox_for(manifest: Option<&Path>) -> CliResult<WasmSandbox> {
+    let config = match manifest {
+        Some(path) => {
+            let manifest = SandboxManifest::from_path_strict(path)?;
+            SandboxConfig {
+                runtime: manifest.to_runtime_config(),
+                default_instance_config: manifest.to_instance_config()?,
+                result_cache: manifest.to_result_cache_config()?,
+                ..SandboxConfig::default()
+            }
+        }
+        None => SandboxConfig::default(),
+    };
+    
+    Ok(WasmSandbox::with_config(config)?)
+}
+
+async fn run(module: &Path, call: &str, args: &str, manifest: Option<&Path>) -> CliResult<bool> {
+    let mut sandbox = sandbox_for(manifest)?;
+    let module_id = sandbox.load_module(&std::fs::read(module)?)?;
+    let instance_id = sandbox.create_instance(module_id, None)?;
+    
+    let args: serde_json::Value = serde_json::from_str(args)?;
+    let result: serde_json::Value = sandbox.call_function(instance_id, call, args).await?;
+    println!("{}", serde_json::to_string_pretty(&result)?);
+    
+    Ok(true)
+}
+
+fn inspect(module: &Path) -> CliResult<bool> {
+    let sandbox = WasmSandbox::new()?;
+    let module_id = sandbox.load_module(&std::fs::read(module)?)?;
+    let module = sandbox.runtime().get_module(module_id)?;
+    
+    println!("size: {} bytes", module.size());
+    
+    println!("exports:");
+    for export in module.exports() {
+        println!("  {export}");
+    }
+    
+    let imports = module.imports();
+    let report = SandboxConfig::default().runtime.import_policy.validate(&imports);
+    println!("imports:");
+    for import in &imports {
+        let marker = if report.unknown.contains(import) { " (unknown)" } else { "" };
+        println!("  {import}{marker}");
+    }
+    
+    Ok(true)
+}
+
+fn policy_check(policy: &Path, module: &Path) -> CliResult<bool> {
+    let manifest = SandboxManifest::from_path_strict(policy)?;
+    let instance_config = manifest.to_instance_config()?;
+    let mut sandbox = sandbox_for(Some(policy))?;
+    let module_id = sandbox.load_module(&std::fs::read(module)?)?;
+    
+    let imports = sandbox.runtime().get_module(module_id)?.imports();
+    let import_policy = manifest.to_runtime_config().import_policy;
+    let violations = import_policy.rules.violations(&imports);
+    let report = import_policy.validate(&imports);
+    
+    println!("network: {:?}", instance_config.capabilities.network);
+    println!("filesystem: {:?}", instance_config.capabilities.filesystem);
+    println!("environment: {:?}", instance_config.capabilities.environment);
+    println!("process: {:?}", instance_config.capabilities.process);
+    println!("memory: {} pages", instance_config.resource_limits.memory.max_memory_pages);
+    
+    if !violations.is_empty() {
+        for violation in &violations {
+            println!("FAIL: {violation}");
+        }
+        return Ok(false);
+    }
+    if !report.is_clean() {
+        println!("FAIL: unknown imports: {report}");
+        return Ok(false);
+    }
+    
+    match sandbox.create_instance(module_id, None) {
+        Ok(_) => {
+            println!("OK: module instantiates under {}", policy.display());
+            Ok(true)
+        }
+        Err(e) => {
+            println!("FAIL: {e}");
+            Ok(false)
+        }
+    }
+}
+
+fn policy_resolve(policy: &Path) -> CliResult<bool> {
+    let resolved = SandboxManifest::resolve(policy, true)?;
+    print!("{}", resolved.dump()?);
+    
+    Ok(true)
+}
+
+async fn bench(
+    module: &Path,
+    call: Option<&str>,
+    args: &str,
+    iterations: u32,
+    manifest: Option<&Path>,
+) -> CliResult<bool> {
+    let mut sandbox = sandbox_for(manifest)?;
+    let module_id = sandbox.load_module(&std::fs::read(module)?)?;
+    let iterations = iterations.max(1);
+    
+    let mut instantiate = Vec::with_capacity(iterations as usize);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let instance_id = sandbox.create_instance(module_id, None)?;
+        instantiate.push(start.elapsed());
+        sandbox.remove_instance(instance_id);
+    }
+    print_timings("instantiate", &instantiate);
+    
+    if let Some(call) = call {
+        let instance_id = sandbox.create_instance(module_id, None)?;
+        let args: serde_json::Value = serde_json::from_str(args)?;
+        
+        let mut calls = Vec::with_capacity(iterations as usize);
+        for _ in 0..iterations {
+            let start = Instant::now();
+            let _: serde_json::Value = sandbox.call_function(instance_id, call, args.clone()).await?;
+            calls.push(start.elapsed());
+        }
+        print_timings(call, &calls);
+    }
+    
+    Ok(true)
+}
+
+/// Serve the management API; a TCP listener requires a token, stdio uses one if set
+#[cfg(feature = "management-api")]
+async fn serve(listen: Option<&str>, token_env: &str, manifest: Option<&Path>) -> CliResult<bool> {
+    use wasm_sandbox::{ManagementServer, TokenAuthenticator};
+    
+    let sandbox = tokio::sync::RwLock::new(sandbox_for(manifest)?);
+    let token = std::env::var(token_env).ok().filter(|token| !token.is_empty());
+    let server = match &token {
+        Some(token) => ManagementServer::new().with_authenticator(TokenAuthenticator::new(token.as_str())),
+        None => ManagementServer::new(),
+    };
+    
+    match listen {
+        Some(addr) => {
+            if token.is_none() {
+                return Err(format!("set {token_env} to the token clients must present before listening on TCP").into());
+            }
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            eprintln!("listening on {}", listener.local_addr()?);
+            server.serve_tcp(&sandbox, listener).await?;
+        }
+        None => server.serve_stdio(&sandbox).await?,
+    }
+    
+    Ok(true)
+}
+
+fn compile_worker() -> CliResult<bool> {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    serve_compile_request(stdin.lock(), stdout.lock())?;
+    Ok(true)
+}
+
+fn print_timings(label: &str, timings: &[Duration]) {
+    let total: Duration = timings.iter().sum();
+    let min = timings.iter().min().copied().unwrap_or_default();
+    let max = timings.iter().max().copied().unwrap_or_default();
+    println!(
+        "{label}: {} iterations, mean {:?}, min {:?}, max {:?}",
+        timings.len(),
+        total / timings.len().max(1) as u32,
+        min,
+        max,
+    );
+}
@@ -125,20 +125,26 @@ import time
 from typing import Any, Dict, List, Optional, TypeVar, Generic, Union
 from pathlib import Path
 
-from .._native import lib as native_lib
+from .._native import ffi, lib as native_lib
 from ..security import Capabilities, ResourceLimits
 
 T = TypeVar('T')
 R = TypeVar('R')
 
+
+def _last_error() -> str:
+    """Why the last native call on this thread failed"""
+    error = native_lib.get_last_error()
+    return ffi.string(error).decode('utf-8') if error != ffi.NULL else "unknown error"
+
 class WasmSandbox:
     """WebAssembly sandbox for secure code execution"""
     
     def __init__(self):
         """Initialize a new sandbox with default settings"""
         self._handle = native_lib.sandbox_new()
-        if not self._handle:
-            raise RuntimeError("Failed to create sandbox")
+        if self._handle == ffi.NULL:
+            raise RuntimeError(f"Failed to create sandbox: {_last_error()}")
     
     def __del__(self):
         """Clean up sandbox resources"""
@@ -155,19 +161,20 @@ class WasmSandbox:
         """Create a sandbox from source code"""
         sandbox = cls()
         source_path_str = str(source_path)
-        result = native_lib.sandbox_load_from_source(sandbox._handle, source_path_str)
+        result = native_lib.sandbox_load_from_source(sandbox._handle, source_path_str.encode('utf-8'))
         if result != 0:
-            error_msg = native_lib.get_last_error()
-            raise RuntimeError(f"Failed to load from source: {error_msg}")
+            raise RuntimeError(f"Failed to load from source: {_last_error()}")
         return sandbox
     
     def load_module(self, wasm_bytes: bytes):
         """Load a WebAssembly module from bytes"""
-        module_id = native_lib.sandbox_load_module(self._handle, wasm_bytes, len(wasm_bytes))
-        if not module_id:
-            error_msg = native_lib.get_last_error()
-            raise RuntimeError(f"Failed to load module: {error_msg}")
-        return module_id
+        module_id_ptr = native_lib.sandbox_load_module(self._handle, wasm_bytes, len(wasm_bytes))
+        if module_id_ptr == ffi.NULL:
+            raise RuntimeError(f"Failed to load module: {_last_error()}")
+        try:
+            return ffi.string(module_id_ptr).decode('utf-8')
+        finally:
+            native_lib.sandbox_free_string(module_id_ptr)
     
     def call(self, function_name: str, params: Any) -> Any:
         """Call a function in the sandbox"""
@@ -181,13 +188,14 @@ class WasmSandbox:
             params_json.encode('utf-8')
         )
         
-        if not result_ptr:
-            error_msg = native_lib.get_last_error()
-            raise RuntimeError(f"Function call failed: {error_msg}")
+        if result_ptr == ffi.NULL:
+            raise RuntimeError(f"Function call failed: {_last_error()}")
         
         # Get the result
-        result_json = native_lib.get_result_json(result_ptr).decode('utf-8')
-        native_lib.free_result(result_ptr)
+        try:
+            result_json = ffi.string(native_lib.get_result_json(result_ptr)).decode('utf-8')
+        finally:
+            native_lib.free_result(result_ptr)
         
         # Parse and return the result
         return json.loads(result_json)
@@ -235,9 +243,8 @@ class SandboxBuilder:
     def build(self):
         """Build the sandbox with the configured settings"""
         sandbox_handle = native_lib.sandbox_builder_build(self._handle)
-        if not sandbox_handle:
-            error_msg = native_lib.get_last_error()
-            raise RuntimeError(f"Failed to build sandbox: {error_msg}")
+        if sandbox_handle == ffi.NULL:
+            raise RuntimeError(f"Failed to build sandbox: {_last_error()}")
         
         sandbox = WasmSandbox.__new__(WasmSandbox)
         sandbox._handle = sandbox_handle
@@ -282,7 +289,7 @@ ffi.cdef("""
     void* sandbox_new(void);
     void sandbox_free(void* sandbox);
     int sandbox_load_from_source(void* sandbox, const char* source_path);
-    const char* sandbox_load_module(void* sandbox, const void* wasm_bytes, size_t wasm_len);
+    char* sandbox_load_module(void* sandbox, const void* wasm_bytes, size_t wasm_len);
     void* sandbox_call_function(void* sandbox, const char* function_name, const char* params_json);
     
     // Builder functions
@@ -298,6 +305,7 @@ ffi.cdef("""
     // Result handling
     const char* get_result_json(void* result);
     void free_result(void* result);
+    void sandbox_free_string(char* string);
     
     // Error handling
     const char* get_last_error(void);
@@ -305,14 +313,15 @@ ffi.cdef("""
 
 # Determine the shared library name based on platform
 if platform.system() == "Windows":
-    lib_name = "wasm_sandbox_python.dll"
+    lib_name = "wasm_sandbox.dll"
 elif platform.system() == "Darwin":
-    lib_name = "libwasm_sandbox_python.dylib"
+    lib_name = "libwasm_sandbox.dylib"
 else:
-    lib_name = "libwasm_sandbox_python.so"
+    lib_name = "libwasm_sandbox.so"
 
-# Try to find the library
+# Try to find the library, preferring an explicit path
 lib_paths = [
+    os.environ.get("WASM_SANDBOX_LIBRARY_DIR", ""),
     # Current directory
     os.path.dirname(os.path.abspath(__file__)),
     # Parent directory
@@ -325,7 +334,7 @@ lib_paths = [
 lib_path = None
 for path in lib_paths:
     full_path = os.path.join(path, lib_name)
-    if os.path.exists(full_path):
+    if path and os.path.exists(full_path):
         lib_path = full_path
         break
 
@@ -532,7 +541,7 @@ print(f"10 + 20 = {{add_result}}")  # Output: 10 + 20 = 30
 
 - Python {min_python_version}+
 - CFFI 1.15.0+
-- wasm-sandbox native library
+- The wasm-sandbox native library, built with its `ffi` feature (the generated `build.sh` does this)
 "#,
             package_name = self.package_name,
             min_python_version = self.min_python_version,
@@ -622,7 +631,9 @@ impl CBindingsGenerator {
         }
     }
     
-    /// Generate C bindings
+    /// Generate the header of the library's C ABI and a script that builds the library
+    ///
+    /// The functions are implemented by the crate itself under the `ffi` feature.
     pub fn generate(&self) -> Result<()> {
         // Create output directory
         std::fs::create_dir_all(&self.output_dir)?;
@@ -630,9 +641,6 @@ impl CBindingsGenerator {
         // Generate header file
         self.generate_header()?;
         
-        // Generate implementation file
-        self.generate_implementation()?;
-        
         // Generate build script
         self.generate_build_script()?;
         
@@ -643,7 +651,11 @@ impl CBindingsGenerator {
     fn generate_header(&self) -> Result<()> {
         let header_content = r#"/**
  * wasm_sandbox_python.h
- * C bindings for wasm-sandbox Python integration
+ * C ABI of the wasm-sandbox library, built with the `ffi` feature
+ *
+ * Handles are released with their free function exactly once. A returned
+ * `char*` belongs to the caller and is released with sandbox_free_string; a
+ * returned `const char*` is borrowed. After a failure, get_last_error says why.
  */
 
 #ifndef WASM_SANDBOX_PYTHON_H
@@ -681,16 +693,16 @@ int sandbox_load_from_source(void* sandbox, const char* source_path);
  * @param sandbox Handle to the sandbox
  * @param wasm_bytes WebAssembly module bytes
  * @param wasm_len Length of WebAssembly module bytes
- * @return Module ID string or NULL on error
+ * @return Module ID string, freed with sandbox_free_string, or NULL on error
  */
-const char* sandbox_load_module(void* sandbox, const void* wasm_bytes, size_t wasm_len);
+char* sandbox_load_module(void* sandbox, const void* wasm_bytes, size_t wasm_len);
 
 /**
  * Call a function in the sandbox
  * @param sandbox Handle to the sandbox
  * @param function_name Name of the function to call
  * @param params_json JSON-encoded parameters
- * @return Result handle or NULL on error
+ * @return Result handle, freed with free_result, or NULL on error
  */
 void* sandbox_call_function(void* sandbox, const char* function_name, const char* params_json);
 
@@ -742,7 +754,7 @@ void sandbox_builder_file_access(void* builder, int enabled);
 void sandbox_builder_network(void* builder, int enabled);
 
 /**
- * Build the sandbox with the configured settings
+ * Build the sandbox with the configured settings; the builder still has to be freed
  * @param builder Handle to the builder
  * @return Handle to the sandbox or NULL on error
  */
@@ -751,7 +763,7 @@ void* sandbox_builder_build(void* builder);
 /**
  * Get the JSON-encoded result
  * @param result Handle to the result
- * @return JSON-encoded result or NULL on error
+ * @return JSON-encoded result, valid until the result is freed, or NULL on error
  */
 const char* get_result_json(void* result);
 
@@ -762,8 +774,14 @@ const char* get_result_json(void* result);
 void free_result(void* result);
 
 /**
- * Get the last error message
- * @return Error message or NULL if no error
+ * Free a string returned as char*
+ * @param string The string
+ */
+void sandbox_free_string(char* string);
+
+/**
+ * Get the last error message on this thread
+ * @return Error message, valid until the next call on this thread, or NULL if the last call succeeded
  */
 const char* get_last_error(void);
 
@@ -779,198 +797,26 @@ const char* get_last_error(void);
         Ok(())
     }
     
-    /// Generate implementation file
-    fn generate_implementation(&self) -> Result<()> {
-        let implementation = r#"/**
- * wasm_sandbox_python.c
- * C bindings for wasm-sandbox Python integration
- */
-
-#include "wasm_sandbox_python.h"
-#include <stdlib.h>
-#include <string.h>
-#include <stdio.h>
-
-#define WASM_SANDBOX_EXPORT __declspec(dllexport)
-
-// Thread-local error message buffer
-static __thread char error_buffer[1024];
-
-// Set the last error message
-static void set_error(const char* message) {
-    if (message) {
-        strncpy(error_buffer, message, sizeof(error_buffer) - 1);
-        error_buffer[sizeof(error_buffer) - 1] = '\0';
-    } else {
-        error_buffer[0] = '\0';
-    }
-}
-
-// These are just stub implementations that would be replaced with actual
-// bindings to the Rust library using technologies like cxx, cbindgen, or bindgen
-
-WASM_SANDBOX_EXPORT void* sandbox_new(void) {
-    // Placeholder for actual implementation
-    void* handle = malloc(8);  // Just a placeholder
-    if (!handle) {
-        set_error("Failed to allocate memory for sandbox");
-        return NULL;
-    }
-    return handle;
-}
-
-WASM_SANDBOX_EXPORT void sandbox_free(void* sandbox) {
-    if (sandbox) {
-        free(sandbox);
-    }
-}
-
-WASM_SANDBOX_EXPORT int sandbox_load_from_source(void* sandbox, const char* source_path) {
-    if (!sandbox || !source_path) {
-        set_error("Invalid arguments");
-        return -1;
-    }
-    // Placeholder
-    return 0;
-}
-
-WASM_SANDBOX_EXPORT const char* sandbox_load_module(void* sandbox, const void* wasm_bytes, size_t wasm_len) {
-    if (!sandbox || !wasm_bytes || wasm_len == 0) {
-        set_error("Invalid arguments");
-        return NULL;
-    }
-    
-    // Placeholder: Just return a fixed string
-    static char module_id[] = "module-12345";
-    return module_id;
-}
-
-WASM_SANDBOX_EXPORT void* sandbox_call_function(void* sandbox, const char* function_name, const char* params_json) {
-    if (!sandbox || !function_name || !params_json) {
-        set_error("Invalid arguments");
-        return NULL;
-    }
-    
-    // Placeholder: Allocate a result to return
-    void* result = malloc(16);
-    if (!result) {
-        set_error("Failed to allocate result");
-        return NULL;
-    }
-    
-    // In a real implementation, this would call into the Rust library
-    return result;
-}
-
-WASM_SANDBOX_EXPORT void* sandbox_builder_new(void) {
-    void* handle = malloc(8);  // Just a placeholder
-    if (!handle) {
-        set_error("Failed to allocate memory for builder");
-        return NULL;
-    }
-    return handle;
-}
-
-WASM_SANDBOX_EXPORT void sandbox_builder_free(void* builder) {
-    if (builder) {
-        free(builder);
-    }
-}
-
-WASM_SANDBOX_EXPORT void sandbox_builder_source(void* builder, const char* source_path) {
-    // Placeholder
-}
-
-WASM_SANDBOX_EXPORT void sandbox_builder_timeout(void* builder, unsigned int timeout_ms) {
-    // Placeholder
-}
-
-WASM_SANDBOX_EXPORT void sandbox_builder_memory_limit(void* builder, size_t limit_bytes) {
-    // Placeholder
-}
-
-WASM_SANDBOX_EXPORT void sandbox_builder_file_access(void* builder, int enabled) {
-    // Placeholder
-}
-
-WASM_SANDBOX_EXPORT void sandbox_builder_network(void* builder, int enabled) {
-    // Placeholder
-}
-
-WASM_SANDBOX_EXPORT void* sandbox_builder_build(void* builder) {
-    if (!builder) {
-        set_error("Invalid builder");
-        return NULL;
-    }
-    
-    void* sandbox = malloc(8);  // Just a placeholder
-    if (!sandbox) {
-        set_error("Failed to create sandbox");
-        return NULL;
-    }
-    
-    return sandbox;
-}
-
-WASM_SANDBOX_EXPORT const char* get_result_json(void* result) {
-    if (!result) {
-        set_error("Invalid result");
-        return NULL;
-    }
-    
-    // Placeholder: Return a fixed JSON result
-    static char json_result[] = "{\"result\": 42}";
-    return json_result;
-}
-
-WASM_SANDBOX_EXPORT void free_result(void* result) {
-    if (result) {
-        free(result);
-    }
-}
-
-WASM_SANDBOX_EXPORT const char* get_last_error(void) {
-    if (error_buffer[0] == '\0') {
-        return NULL;
-    }
-    return error_buffer;
-}
-"#;
-        
-        std::fs::write(self.output_dir.join("wasm_sandbox_python.c"), implementation)?;
-        
-        Ok(())
-    }
-    
     /// Generate build script
     fn generate_build_script(&self) -> Result<()> {
         let build_script = r#"#!/bin/bash
 
 set -e
 
-# Build settings
-CC=${CC:-gcc}
-CFLAGS="-Wall -Wextra -O2 -fPIC -I."
-OUTPUT_NAME="libwasm_sandbox_python.so"
+# Build the wasm-sandbox shared library with its C ABI from the crate's sources
+CRATE_DIR=${WASM_SANDBOX_CRATE_DIR:-.}
+OUTPUT_NAME="libwasm_sandbox.so"
 
 if [[ "$OSTYPE" == "darwin"* ]]; then
-    CFLAGS="$CFLAGS -dynamiclib"
-    OUTPUT_NAME="libwasm_sandbox_python.dylib"
+    OUTPUT_NAME="libwasm_sandbox.dylib"
 elif [[ "$OSTYPE" == "msys" || "$OSTYPE" == "cygwin" || "$OSTYPE" == "win32" ]]; then
-    CC=${CC:-cl}
-    CFLAGS="/W4 /O2 /LD"
-    OUTPUT_NAME="wasm_sandbox_python.dll"
+    OUTPUT_NAME="wasm_sandbox.dll"
 fi
 
 echo "Building $OUTPUT_NAME..."
 
-if [[ "$OSTYPE" == "msys" || "$OSTYPE" == "cygwin" || "$OSTYPE" == "win32" ]]; then
-    # Windows with MSVC
-    $CC $CFLAGS wasm_sandbox_python.c /Fe$OUTPUT_NAME
-else
-    # Unix-like systems
-    $CC $CFLAGS -o $OUTPUT_NAME wasm_sandbox_python.c -lm
-fi
+cargo rustc --manifest-path "$CRATE_DIR/Cargo.toml" --release --lib --features ffi --crate-type cdylib
+cp "$CRATE_DIR/target/release/$OUTPUT_NAME" .
 
 echo "Build complete: $OUTPUT_NAME"
 "#;
@@ -0,0 +1,588 @@
+//! Plugin bundles (`.wsbx`) for distributing sandboxed plugins
+//!
+//! A [`Bundle`] packs everything a marketplace needs to accept and install a
+//! plugin into one file: the module, its [`PluginManifest`], a README and
+//! free-form metadata, the [`FixtureCase`]s the plugin must pass, and the
+//! [`SandboxManifest`] policy it runs under by default. The file starts with
+//! [`BUNDLE_MAGIC`] and a JSON header listing each entry's name, length and
+//! SHA-256 digest, followed by the entries:
+//!
+//! | entry           | contents                         |
+//! |-----------------|----------------------------------|
+//! | `module.wasm`   | the module                       |
+//! | `manifest.json` | the plugin manifest              |
+//! | `README.md`     | optional                         |
+//! | `metadata.json` | optional, a JSON object          |
+//! | `fixtures.json` | optional, a list of fixture cases |
+//! | `policy.toml`   | optional, a sandbox manifest     |
+//!
+//! [`Bundle::unpack`] checks every digest. The header may carry a
+//! [`BundleSignature`] over the entry digests, made by a [`BundleSigner`] and
+//! checked with [`Bundle::verify`], so a registry installs only bundles from
+//! publishers it trusts; see [`PluginRegistry::register_bundle`]. JSON entries
+//! are written with sorted object keys, so a bundle's digest doesn't depend on
+//! the iteration order of the maps it was built from.
+//!
+//! Publishers sign with an [`Ed25519Signer`] and registries verify with its
+//! [`Ed25519PublicKey`]. An [`HmacKey`] is a shared secret: anyone able to
+//! verify with it can also sign, so it only suits a registry checking bundles
+//! it built itself.
+//!
+//! [`PluginRegistry::register_bundle`]: crate::plugins::PluginRegistry::register_bundle
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use base64::Engine as _;
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519 as RING_ED25519};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result, SecurityContext};
+use crate::fixtures::{FixtureCase, FixtureReport, SandboxTestSuite};
+use crate::plugins::PluginManifest;
+use crate::utils::manifest::SandboxManifest;
+
+/// First bytes of every plugin bundle
+pub const BUNDLE_MAGIC: &[u8; 8] = b"WSBXPLUG";
+
+/// Format version of the bundles this crate writes
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// File extension of plugin bundles
+pub const BUNDLE_EXTENSION: &str = "wsbx";
+
+/// Algorithm name of [`HmacKey`] signatures
+pub const HMAC_SHA256: &str = "hmac-sha256";
+
+/// Algorithm name of [`Ed25519Signer`] signatures
+pub const ED25519: &str = "ed25519";
+
+const MODULE_ENTRY: &str = "module.wasm";
+const MANIFEST_ENTRY: &str = "manifest.json";
+const README_ENTRY: &str = "README.md";
+const METADATA_ENTRY: &str = "metadata.json";
+const FIXTURES_ENTRY: &str = "fixtures.json";
+const POLICY_ENTRY: &str = "policy.toml";
+
+/// A signature over a bundle's contents
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleSignature {
+    /// Key the bundle was signed with
+    pub key_id: String,
+    
+    /// Signature algorithm, e.g. [`ED25519`]
+    pub algorithm: String,
+    
+    /// The signature, base64 encoded
+    pub signature: String,
+}
+
+/// Signs bundle digests
+pub trait BundleSigner {
+    /// Key the signatures are made with
+    fn key_id(&self) -> &str;
+    
+    /// Name of the signature algorithm
+    fn algorithm(&self) -> &str;
+    
+    /// Sign the digest of a bundle's contents
+    fn sign(&self, digest: &[u8; 32]) -> Result<Vec<u8>>;
+}
+
+/// Checks bundle signatures
+pub trait BundleVerifier {
+    /// Fail unless `signature` is a valid signature of `digest` by a trusted key
+    fn verify(&self, digest: &[u8; 32], signature: &BundleSignature) -> Result<()>;
+}
+
+/// A shared secret signing and verifying bundles with HMAC-SHA256
+///
+/// Whoever holds the secret to verify a bundle can also sign one, so a
+/// registry trusting an `HmacKey` trusts everyone it shared the key with. Use
+/// it only for bundles the registry builds itself; third-party publishers
+/// sign with an [`Ed25519Signer`].
+#[derive(Clone)]
+pub struct HmacKey {
+    key_id: String,
+    secret: Vec<u8>,
+}
+
+impl HmacKey {
+    /// Create a key named `key_id`
+    pub fn new(key_id: &str, secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            key_id: key_id.to_string(),
+            secret: secret.into(),
+        }
+    }
+}
+
+impl std::fmt::Debug for HmacKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HmacKey").field("key_id", &self.key_id).finish_non_exhaustive()
+    }
+}
+
+impl BundleSigner for HmacKey {
+    fn key_id(&self) -> &str {
+        &self.key_id
+    }
+    
+    fn algorithm(&self) -> &str {
+        HMAC_SHA256
+    }
+    
+    fn sign(&self, digest: &[u8; 32]) -> Result<Vec<u8>> {
+        Ok(hmac_sha256(&self.secret, digest).to_vec())
+    }
+}
+
+impl BundleVerifier for HmacKey {
+    fn verify(&self, digest: &[u8; 32], signature: &BundleSignature) -> Result<()> {
+        if signature.key_id != self.key_id || signature.algorithm != HMAC_SHA256 {
+            return Err(untrusted(format!(
+                "the bundle is signed with {} key {}, not {} key {}",
+                signature.algorithm, signature.key_id, HMAC_SHA256, self.key_id,
+            )));
+        }
+        let expected = hmac_sha256(&self.secret, digest);
+        let actual = base64::engine::general_purpose::STANDARD.decode(&signature.signature)
+            .map_err(|_| untrusted("the bundle's signature is not valid base64".to_string()))?;
+        // Compare in constant time so the signature can't be guessed byte by byte
+        let matches = actual.len() == expected.len()
+            && actual.iter().zip(expected).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0;
+        if !matches {
+            return Err(untrusted("the bundle's signature does not match its contents".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// A private key signing bundles with Ed25519
+pub struct Ed25519Signer {
+    key_id: String,
+    pair: Ed25519KeyPair,
+}
+
+impl Ed25519Signer {
+    /// Generate a key named `key_id`, returning it with its PKCS#8 document for safekeeping
+    pub fn generate(key_id: &str) -> Result<(Self, Vec<u8>)> {
+        let document = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|_| invalid_key("failed to generate an Ed25519 key"))?;
+        let pkcs8 = document.as_ref().to_vec();
+        Ok((Self::from_pkcs8(key_id, &pkcs8)?, pkcs8))
+    }
+    
+    /// Load a key named `key_id` from a PKCS#8 document
+    pub fn from_pkcs8(key_id: &str, pkcs8: &[u8]) -> Result<Self> {
+        let pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8)
+            .map_err(|e| invalid_key(&format!("not an Ed25519 PKCS#8 document: {}", e)))?;
+        Ok(Self {
+            key_id: key_id.to_string(),
+            pair,
+        })
+    }
+    
+    /// Public key verifying this signer's bundles
+    pub fn public_key(&self) -> Ed25519PublicKey {
+        Ed25519PublicKey::new(&self.key_id, self.pair.public_key().as_ref())
+    }
+}
+
+impl std::fmt::Debug for Ed25519Signer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ed25519Signer").field("key_id", &self.key_id).finish_non_exhaustive()
+    }
+}
+
+impl BundleSigner for Ed25519Signer {
+    fn key_id(&self) -> &str {
+        &self.key_id
+    }
+    
+    fn algorithm(&self) -> &str {
+        ED25519
+    }
+    
+    fn sign(&self, digest: &[u8; 32]) -> Result<Vec<u8>> {
+        Ok(self.pair.sign(digest).as_ref().to_vec())
+    }
+}
+
+/// A publisher's Ed25519 public key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ed25519PublicKey {
+    key_id: String,
+    public_key: Vec<u8>,
+}
+
+impl Ed25519PublicKey {
+    /// Trust the 32-byte public key `public_key`, named `key_id`
+    pub fn new(key_id: &str, public_key: &[u8]) -> Self {
+        Self {
+            key_id: key_id.to_string(),
+            public_key: public_key.to_vec(),
+        }
+    }
+    
+    /// The raw public key
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.public_key
+    }
+}
+
+impl BundleVerifier for Ed25519PublicKey {
+    fn verify(&self, digest: &[u8; 32], signature: &BundleSignature) -> Result<()> {
+        if signature.key_id != self.key_id || signature.algorithm != ED25519 {
+            return Err(untrusted(format!(
+                "the bundle is signed with {} key {}, not {} key {}",
+                signature.algorithm, signature.key_id, ED25519, self.key_id,
+            )));
+        }
+        let actual = base64::engine::general_purpose::STANDARD.decode(&signature.signature)
+            .map_err(|_| untrusted("the bundle's signature is not valid base64".to_string()))?;
+        UnparsedPublicKey::new(&RING_ED25519, &self.public_key)
+            .verify(digest, &actual)
+            .map_err(|_| untrusted("the bundle's signature does not match its contents".to_string()))
+    }
+}
+
+/// Where an entry sits in a bundle
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleEntry {
+    name: String,
+    digest: [u8; 32],
+    len: u64,
+}
+
+/// Description of a bundle's contents, stored ahead of the entries
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleHeader {
+    version: u32,
+    entries: Vec<BundleEntry>,
+    signature: Option<BundleSignature>,
+}
+
+/// A plugin packaged for distribution
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    /// The module
+    pub module: Vec<u8>,
+    
+    /// What the plugin is and exports
+    pub manifest: PluginManifest,
+    
+    /// README shown to users browsing the marketplace
+    pub readme: Option<String>,
+    
+    /// Free-form metadata such as categories or screenshots
+    pub metadata: HashMap<String, Value>,
+    
+    /// Golden calls the plugin must pass
+    pub fixtures: Vec<FixtureCase>,
+    
+    /// Sandbox manifest the plugin runs under unless the host overrides it
+    pub policy: Option<String>,
+    
+    /// Signature over the other contents, if signed
+    pub signature: Option<BundleSignature>,
+}
+
+impl Bundle {
+    /// Bundle a module with its manifest
+    pub fn new(manifest: PluginManifest, module: Vec<u8>) -> Self {
+        Self {
+            module,
+            manifest,
+            readme: None,
+            metadata: HashMap::new(),
+            fixtures: Vec::new(),
+            policy: None,
+            signature: None,
+        }
+    }
+    
+    /// Include a README
+    pub fn with_readme(mut self, readme: &str) -> Self {
+        self.readme = Some(readme.to_string());
+        self
+    }
+    
+    /// Set a metadata value
+    pub fn with_metadata(mut self, key: &str, value: Value) -> Self {
+        self.metadata.insert(key.to_string(), value);
+        self
+    }
+    
+    /// Include a fixture case
+    pub fn with_fixture(mut self, case: FixtureCase) -> Self {
+        self.fixtures.push(case);
+        self
+    }
+    
+    /// Include the sandbox manifest the plugin runs under by default
+    ///
+    /// Fails if the manifest doesn't parse or includes other files, which
+    /// wouldn't travel with the bundle.
+    pub fn with_policy(mut self, policy: &str) -> Result<Self> {
+        check_policy(policy)?;
+        self.policy = Some(policy.to_string());
+        Ok(self)
+    }
+    
+    /// The default policy, parsed
+    pub fn default_policy(&self) -> Result<Option<SandboxManifest>> {
+        self.policy.as_deref().map(SandboxManifest::from_str_strict).transpose()
+    }
+    
+    /// The bundle's fixtures as a suite, running under the default policy if there is one
+    pub fn test_suite(&self) -> Result<SandboxTestSuite> {
+        let mut suite = SandboxTestSuite::new();
+        if let Some(policy) = self.default_policy()? {
+            suite = suite.instance_config(policy.to_instance_config()?);
+        }
+        Ok(self.fixtures.iter().cloned().fold(suite, SandboxTestSuite::case))
+    }
+    
+    /// Run the bundle's fixtures against its module
+    pub async fn run_fixtures(&self) -> Result<FixtureReport> {
+        self.test_suite()?.run(&self.module).await
+    }
+    
+    /// Digest of the bundle's contents, which its signature covers
+    pub fn digest(&self) -> Result<[u8; 32]> {
+        Ok(contents_digest(&entry_headers(&self.entries()?)))
+    }
+    
+    /// Sign the bundle, replacing any signature it has
+    pub fn sign(&mut self, signer: &dyn BundleSigner) -> Result<()> {
+        let signature = signer.sign(&self.digest()?)?;
+        self.signature = Some(BundleSignature {
+            key_id: signer.key_id().to_string(),
+            algorithm: signer.algorithm().to_string(),
+            signature: base64::engine::general_purpose::STANDARD.encode(signature),
+        });
+        Ok(())
+    }
+    
+    /// Fail unless the bundle is signed and `verifier` trusts the signature
+    pub fn verify(&self, verifier: &dyn BundleVerifier) -> Result<()> {
+        let signature = self.signature.as_ref()
+            .ok_or_else(|| untrusted("the bundle is not signed".to_string()))?;
+        verifier.verify(&self.digest()?, signature)
+    }
+    
+    /// Serialize the bundle: magic, header JSON length (u32, little endian), header JSON, then the entries
+    pub fn pack(&self) -> Result<Vec<u8>> {
+        let entries = self.entries()?;
+        let header = serde_json::to_vec(&BundleHeader {
+            version: BUNDLE_VERSION,
+            entries: entry_headers(&entries),
+            signature: self.signature.clone(),
+        })?;
+        
+        let size = entries.iter().map(|(_, contents)| contents.len()).sum::<usize>();
+        let mut bundle = Vec::with_capacity(BUNDLE_MAGIC.len() + 4 + header.len() + size);
+        bundle.extend_from_slice(BUNDLE_MAGIC);
+        bundle.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        bundle.extend_from_slice(&header);
+        for (_, contents) in &entries {
+            bundle.extend_from_slice(contents);
+        }
+        Ok(bundle)
+    }
+    
+    /// Read a bundle, checking its format and the digest of every entry
+    ///
+    /// The signature is read but not checked; see [`Bundle::verify`].
+    pub fn unpack(bundle: &[u8]) -> Result<Self> {
+        let rest = bundle.strip_prefix(BUNDLE_MAGIC.as_slice()).ok_or_else(|| invalid("not a plugin bundle"))?;
+        let (length, rest) = rest.split_first_chunk::<4>().ok_or_else(|| invalid("missing header length"))?;
+        let length = u32::from_le_bytes(*length) as usize;
+        if rest.len() < length {
+            return Err(invalid("truncated header"));
+        }
+        let (header, mut rest) = rest.split_at(length);
+        let header: BundleHeader = serde_json::from_slice(header)?;
+        if header.version != BUNDLE_VERSION {
+            return Err(invalid(&format!("unsupported bundle version {}", header.version)));
+        }
+        
+        let mut entries: HashMap<String, &[u8]> = HashMap::new();
+        for entry in &header.entries {
+            let len = usize::try_from(entry.len).map_err(|_| invalid("entry too large"))?;
+            if rest.len() < len {
+                return Err(invalid(&format!("truncated entry {}", entry.name)));
+            }
+            let (contents, remaining) = rest.split_at(len);
+            if <[u8; 32]>::from(Sha256::digest(contents)) != entry.digest {
+                return Err(invalid(&format!("digest mismatch in entry {}", entry.name)));
+            }
+            if entries.insert(entry.name.clone(), contents).is_some() {
+                return Err(invalid(&format!("duplicate entry {}", entry.name)));
+            }
+            rest = remaining;
+        }
+        if !rest.is_empty() {
+            return Err(invalid("trailing data after the entries"));
+        }
+        
+        let mut take = |name: &str| entries.remove(name);
+        let module = take(MODULE_ENTRY).ok_or_else(|| invalid("missing module.wasm"))?.to_vec();
+        let manifest = serde_json::from_slice(take(MANIFEST_ENTRY).ok_or_else(|| invalid("missing manifest.json"))?)?;
+        let text = |contents: &[u8], name: &str| {
+            String::from_utf8(contents.to_vec()).map_err(|_| invalid(&format!("{} is not UTF-8", name)))
+        };
+        let readme = take(README_ENTRY).map(|contents| text(contents, README_ENTRY)).transpose()?;
+        let metadata = take(METADATA_ENTRY).map(serde_json::from_slice).transpose()?.unwrap_or_default();
+        let fixtures = take(FIXTURES_ENTRY).map(serde_json::from_slice).transpose()?.unwrap_or_default();
+        let policy = take(POLICY_ENTRY).map(|contents| text(contents, POLICY_ENTRY)).transpose()?;
+        if let Some(name) = entries.keys().next() {
+            return Err(invalid(&format!("unknown entry {}", name)));
+        }
+        if let Some(policy) = &policy {
+            check_policy(policy)?;
+        }
+        
+        Ok(Self {
+            module,
+            manifest,
+            readme,
+            metadata,
+            fixtures,
+            policy,
+            signature: header.signature,
+        })
+    }
+    
+    /// Write the bundle to a file
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.pack()?).map_err(|e| Error::Filesystem {
+            operation: "write_bundle".to_string(),
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })
+    }
+    
+    /// Read a bundle from a file
+    pub fn read(path: &Path) -> Result<Self> {
+        let bundle = fs::read(path).map_err(|e| Error::Filesystem {
+            operation: "read_bundle".to_string(),
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })?;
+        Self::unpack(&bundle)
+    }
+    
+    /// Entry names and contents, in the order they are stored
+    fn entries(&self) -> Result<Vec<(&'static str, Vec<u8>)>> {
+        let mut entries = vec![
+            (MODULE_ENTRY, self.module.clone()),
+            (MANIFEST_ENTRY, canonical_json(&self.manifest)?),
+        ];
+        if let Some(readme) = &self.readme {
+            entries.push((README_ENTRY, readme.clone().into_bytes()));
+        }
+        if !self.metadata.is_empty() {
+            entries.push((METADATA_ENTRY, canonical_json(&self.metadata)?));
+        }
+        if !self.fixtures.is_empty() {
+            entries.push((FIXTURES_ENTRY, canonical_json(&self.fixtures)?));
+        }
+        if let Some(policy) = &self.policy {
+            entries.push((POLICY_ENTRY, policy.clone().into_bytes()));
+        }
+        Ok(entries)
+    }
+}
+
+/// `value` as JSON with every object's keys sorted
+///
+/// Going through [`Value`] sorts the keys, since this crate builds
+/// `serde_json` without `preserve_order`; maps such as
+/// [`PluginManifest::metadata`] serialize directly in hash order.
+fn canonical_json<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec_pretty(&serde_json::to_value(value)?)?)
+}
+
+/// Header entries describing `entries`
+fn entry_headers(entries: &[(&str, Vec<u8>)]) -> Vec<BundleEntry> {
+    entries.iter()
+        .map(|(name, contents)| BundleEntry {
+            name: name.to_string(),
+            digest: Sha256::digest(contents).into(),
+            len: contents.len() as u64,
+        })
+        .collect()
+}
+
+/// Digest of the entry names and digests, which signatures cover
+fn contents_digest(entries: &[BundleEntry]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for entry in entries {
+        hasher.update(entry.name.as_bytes());
+        hasher.update([0]);
+        hasher.update(entry.digest);
+    }
+    hasher.finalize().into()
+}
+
+/// Fail unless `policy` is a self-contained sandbox manifest
+fn check_policy(policy: &str) -> Result<()> {
+    let value: toml::Value = toml::from_str(policy).map_err(|e| invalid(&format!("policy.toml: {}", e)))?;
+    if value.get("include").is_some() {
+        return Err(invalid("policy.toml may not include other manifests"));
+    }
+    SandboxManifest::from_str_strict(policy).map(|_| ())
+}
+
+/// HMAC-SHA256 of `message` under `key` (RFC 2104)
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner = Sha256::new()
+        .chain_update(block.map(|byte| byte ^ 0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(block.map(|byte| byte ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidInput {
+        field: "plugin bundle".to_string(),
+        reason: reason.to_string(),
+        suggestion: None,
+    }
+}
+
+fn invalid_key(reason: &str) -> Error {
+    Error::InvalidInput {
+        field: "signing key".to_string(),
+        reason: reason.to_string(),
+        suggestion: None,
+    }
+}
+
+fn untrusted(violation: String) -> Error {
+    Error::SecurityViolation {
+        violation,
+        instance_id: None,
+        context: SecurityContext {
+            attempted_operation: "install plugin bundle".to_string(),
+            required_capability: "trusted bundle signature".to_string(),
+            available_capabilities: Vec::new(),
+        },
+    }
+}
@@ -0,0 +1,105 @@
+//! Typed clients for guest exports
+//!
+//! [`typed_client!`](crate::typed_client) is a declarative macro that takes a
+//! list of method signatures describing a plugin's exports and generates a
+//! trait plus a client type implementing it, whose methods call the exports of
+//! the same name through [`WasmSandbox::call_function`]. Host code calling the
+//! client is type-checked against the declared signatures, but nothing checks
+//! them against the plugin: arguments and results are marshalled with serde
+//! like any other call, so a mismatch between host and plugin shows up at
+//! runtime, as an error when the guest's result (or the guest's view of the
+//! arguments) fails to decode. [`WasmSandbox::typed_client`] only checks that
+//! the guest exports every method before handing the client out.
+//!
+//! ```rust,no_run
+//! wasm_sandbox::typed_client! {
+//!     /// Exports of the calculator plugin
+//!     pub trait Calculator for CalculatorClient {
+//!         fn add(a: i32, b: i32) -> i32;
+//!         fn describe() -> String;
+//!     }
+//! }
+//!
+//! # async fn example(sandbox: wasm_sandbox::WasmSandbox, id: wasm_sandbox::InstanceId) -> wasm_sandbox::Result<()> {
+//! let calculator = sandbox.typed_client::<CalculatorClient>(id)?;
+//! assert_eq!(calculator.add(2, 3).await?, 5);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{InstanceId, WasmSandbox};
+
+/// A client generated by [`typed_client!`](crate::typed_client) for one instance
+pub trait TypedClient<'a>: Sized {
+    /// Guest exports the client's methods call
+    const EXPORTS: &'static [&'static str];
+    
+    /// Wrap an instance without checking what it exports
+    fn bind(sandbox: &'a WasmSandbox, instance_id: InstanceId) -> Self;
+    
+    /// Instance the client calls
+    fn instance_id(&self) -> InstanceId;
+}
+
+/// Declare a plugin interface as a trait and generate a client implementing it
+///
+/// Each method is written without a receiver or `async`; the trait gets an
+/// `async fn name(&self, args...) -> Result<Output>` for it, and the client
+/// type a lifetime-generic struct implementing [`TypedClient`]. A method with
+/// one argument passes it to the guest as is, and one with several as a tuple,
+/// matching [`WasmSandbox::call_function`]. See the [module docs](crate::client).
+#[macro_export]
+macro_rules! typed_client {
+    (
+        $(#[$trait_meta:meta])*
+        $vis:vis trait $trait_name:ident for $client:ident {
+            $(
+                $(#[$method_meta:meta])*
+                fn $method:ident($($arg:ident: $arg_ty:ty),* $(,)?) $(-> $ret:ty)?;
+            )*
+        }
+    ) => {
+        $(#[$trait_meta])*
+        #[allow(async_fn_in_trait)]
+        $vis trait $trait_name {
+            $(
+                $(#[$method_meta])*
+                async fn $method(&self, $($arg: $arg_ty),*) -> $crate::Result<$crate::typed_client!(@ret $($ret)?)>;
+            )*
+        }
+        
+        #[doc = concat!("Calls the guest exports declared by [`", stringify!($trait_name), "`]")]
+        #[derive(Clone, Copy)]
+        $vis struct $client<'a> {
+            sandbox: &'a $crate::WasmSandbox,
+            instance_id: $crate::InstanceId,
+        }
+        
+        impl<'a> $crate::client::TypedClient<'a> for $client<'a> {
+            const EXPORTS: &'static [&'static str] = &[$(stringify!($method)),*];
+            
+            fn bind(sandbox: &'a $crate::WasmSandbox, instance_id: $crate::InstanceId) -> Self {
+                Self { sandbox, instance_id }
+            }
+            
+            fn instance_id(&self) -> $crate::InstanceId {
+                self.instance_id
+            }
+        }
+        
+        impl $trait_name for $client<'_> {
+            $(
+                async fn $method(&self, $($arg: $arg_ty),*) -> $crate::Result<$crate::typed_client!(@ret $($ret)?)> {
+                    self.sandbox
+                        .call_function(self.instance_id, stringify!($method), $crate::typed_client!(@params $($arg),*))
+                        .await
+                }
+            )*
+        }
+    };
+    (@ret) => { () };
+    (@ret $ret:ty) => { $ret };
+    (@params) => { () };
+    (@params $arg:ident) => { $arg };
+    (@params $($arg:ident),+) => { ($($arg),+) };
+}
@@ -0,0 +1,309 @@
+//! Brokered RPC between sandboxed instances
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::InstanceId;
+use crate::error::{Error, Result, SecurityContext};
+use crate::runtime::{ServiceDispatcher, WasmInstance};
+use crate::security::{Capabilities, CustomCapability, EnforcementMode};
+use crate::security::audit::{AuditEventType, AuditLogger};
+use crate::security::capabilities::ActiveCapabilities;
+
+/// Custom capability listing the services an instance may call
+///
+/// The value is a [`CustomCapability::StringList`] of `service`, `service.function`,
+/// or `*` entries.
+pub const SERVICE_GRANT_CAPABILITY: &str = "rpc.services";
+
+/// Capability domain used for enforcement modes
+const SERVICE_DOMAIN: &str = "rpc";
+
+thread_local! {
+    /// Instances with a brokered call in progress on this thread
+    static CALL_CHAIN: RefCell<Vec<InstanceId>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Limits the broker enforces on calls to a service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceQuota {
+    /// Maximum calls each caller may make to the service
+    pub max_calls: Option<u64>,
+    
+    /// Maximum request payload size in bytes
+    pub max_payload_bytes: Option<usize>,
+}
+
+impl Default for ServiceQuota {
+    fn default() -> Self {
+        Self {
+            max_calls: None,
+            max_payload_bytes: Some(1024 * 1024), // 1MB
+        }
+    }
+}
+
+/// A service exposed by an instance
+struct ExposedService {
+    /// Instance providing the service
+    instance_id: InstanceId,
+    
+    /// Instance the calls are made on
+    instance: Arc<dyn WasmInstance>,
+    
+    /// Exports callable through the service
+    functions: BTreeSet<String>,
+}
+
+/// Routes calls between instances, enforcing grants and quotas
+///
+/// Services are called with the guest data ABI (see [`WasmInstance::call_raw`]).
+/// Every attempt is recorded in the audit log whether or not it is allowed.
+pub struct ServiceBroker {
+    /// Services by name
+    services: RwLock<HashMap<String, ExposedService>>,
+    
+    /// Quotas overriding the default, by service
+    quotas: RwLock<HashMap<String, ServiceQuota>>,
+    
+    /// Quota for services without an override
+    default_quota: ServiceQuota,
+    
+    /// Calls made by each caller to each service
+    usage: Mutex<HashMap<(InstanceId, String), u64>>,
+    
+    /// Audit log of call attempts
+    audit: AuditLogger,
+}
+
+impl Default for ServiceBroker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServiceBroker {
+    /// Create a broker with the default quota
+    pub fn new() -> Self {
+        Self {
+            services: RwLock::new(HashMap::new()),
+            quotas: RwLock::new(HashMap::new()),
+            default_quota: ServiceQuota::default(),
+            usage: Mutex::new(HashMap::new()),
+            audit: AuditLogger::new(1000),
+        }
+    }
+    
+    /// Use a different default quota
+    pub fn with_default_quota(mut self, quota: ServiceQuota) -> Self {
+        self.default_quota = quota;
+        self
+    }
+    
+    /// Record call attempts in an existing audit logger
+    pub fn with_audit_logger(mut self, logger: AuditLogger) -> Self {
+        self.audit = logger;
+        self
+    }
+    
+    /// Audit log of call attempts
+    pub fn audit_logger(&self) -> &AuditLogger {
+        &self.audit
+    }
+    
+    /// Expose exports of an instance as a named service
+    pub fn expose(
+        &self,
+        service: &str,
+        instance_id: InstanceId,
+        instance: Arc<dyn WasmInstance>,
+        functions: &[&str],
+    ) -> Result<()> {
+        let mut services = self.services.write().unwrap();
+        if let Some(existing) = services.get(service)
+            && existing.instance_id != instance_id
+        {
+            return Err(Error::config_error(
+                format!("Service {} is already exposed by instance {}", service, existing.instance_id),
+                Some("Withdraw the existing service or choose another name".to_string()),
+            ));
+        }
+        
+        services.insert(service.to_string(), ExposedService {
+            instance_id,
+            instance,
+            functions: functions.iter().map(|f| f.to_string()).collect(),
+        });
+        Ok(())
+    }
+    
+    /// Set the quota for a service
+    pub fn set_quota(&self, service: &str, quota: ServiceQuota) {
+        self.quotas.write().unwrap().insert(service.to_string(), quota);
+    }
+    
+    /// Remove every service exposed by an instance and its usage counters
+    pub fn withdraw(&self, instance_id: InstanceId) {
+        self.services.write().unwrap().retain(|_, service| service.instance_id != instance_id);
+        self.usage.lock().unwrap().retain(|(caller, _), _| *caller != instance_id);
+    }
+    
+    /// Whether an instance exposes any service
+    pub fn exposes(&self, instance_id: InstanceId) -> bool {
+        self.services.read().unwrap().values().any(|service| service.instance_id == instance_id)
+    }
+    
+    /// Names of exposed services
+    pub fn services(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.services.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+    
+    /// Number of calls a caller has made to a service
+    pub fn calls_made(&self, caller: InstanceId, service: &str) -> u64 {
+        self.usage.lock().unwrap()
+            .get(&(caller, service.to_string()))
+            .copied()
+            .unwrap_or(0)
+    }
+    
+    /// Call a service on behalf of `caller`
+    pub fn call(
+        &self,
+        caller: InstanceId,
+        capabilities: &Capabilities,
+        service: &str,
+        function: &str,
+        payload: &[u8],
+    ) -> Result<Vec<u8>> {
+        let (target_id, instance) = {
+            let services = self.services.read().unwrap();
+            let exposed = services.get(service)
+                .filter(|exposed| exposed.functions.contains(function))
+                .ok_or_else(|| Error::NotFound {
+                    resource_type: "service".to_string(),
+                    identifier: format!("{}.{}", service, function),
+                })?;
+            (exposed.instance_id, exposed.instance.clone())
+        };
+        
+        if !is_granted(capabilities, service, function) {
+            self.record(caller, service, function, false);
+            if capabilities.enforcement.mode_for(SERVICE_DOMAIN) == EnforcementMode::Enforce {
+                return Err(Error::SecurityViolation {
+                    violation: format!("Instance is not granted service {}.{}", service, function),
+                    instance_id: Some(caller.0),
+                    context: SecurityContext {
+                        attempted_operation: format!("call {}.{}", service, function),
+                        required_capability: SERVICE_GRANT_CAPABILITY.to_string(),
+                        available_capabilities: granted_services(capabilities),
+                    },
+                });
+            }
+        }
+        
+        let reentrant = CALL_CHAIN.with(|chain| {
+            let chain = chain.borrow();
+            target_id == caller || chain.contains(&target_id)
+        });
+        if reentrant {
+            return Err(Error::FunctionCall {
+                function_name: format!("{}.{}", service, function),
+                reason: "Service calls may not re-enter an instance already in the call chain".to_string(),
+            });
+        }
+        
+        self.charge_quota(caller, service, payload.len())?;
+        self.record(caller, service, function, true);
+        
+        CALL_CHAIN.with(|chain| chain.borrow_mut().push(caller));
+        let result = instance.call_raw(function, payload);
+        CALL_CHAIN.with(|chain| chain.borrow_mut().pop());
+        result
+    }
+    
+    /// Check and count a call against the service's quota
+    fn charge_quota(&self, caller: InstanceId, service: &str, payload_len: usize) -> Result<()> {
+        let quota = self.quotas.read().unwrap()
+            .get(service)
+            .cloned()
+            .unwrap_or_else(|| self.default_quota.clone());
+        
+        if let Some(max) = quota.max_payload_bytes
+            && payload_len > max
+        {
+            return Err(Error::ResourceLimit {
+                message: format!("Payload of {} bytes exceeds the {} byte limit for service {}", payload_len, max, service),
+            });
+        }
+        
+        let mut usage = self.usage.lock().unwrap();
+        let calls = usage.entry((caller, service.to_string())).or_insert(0);
+        if let Some(max) = quota.max_calls
+            && *calls >= max
+        {
+            return Err(Error::ResourceLimit {
+                message: format!("Call quota of {} exhausted for service {}", max, service),
+            });
+        }
+        *calls += 1;
+        Ok(())
+    }
+    
+    /// Record a call attempt in the audit log
+    fn record(&self, caller: InstanceId, service: &str, function: &str, allowed: bool) {
+        let event = AuditEventType::ServiceCall {
+            caller_id: caller.to_string(),
+            service: service.to_string(),
+            function: function.to_string(),
+            allowed,
+        };
+        if allowed {
+            self.audit.info(event, &format!("Service call {}.{}", service, function));
+        } else {
+            self.audit.warning(event, &format!("Denied service call {}.{}", service, function));
+        }
+    }
+}
+
+/// Dispatches one instance's service calls through a broker
+pub struct BrokerDispatcher {
+    broker: Arc<ServiceBroker>,
+    caller: InstanceId,
+    capabilities: ActiveCapabilities,
+}
+
+impl BrokerDispatcher {
+    /// Create a dispatcher for calls made by `caller`
+    pub fn new(broker: Arc<ServiceBroker>, caller: InstanceId, capabilities: ActiveCapabilities) -> Self {
+        Self {
+            broker,
+            caller,
+            capabilities,
+        }
+    }
+}
+
+impl ServiceDispatcher for BrokerDispatcher {
+    fn dispatch(&self, service: &str, function: &str, payload: &[u8]) -> Result<Vec<u8>> {
+        self.broker.call(self.caller, &self.capabilities.current(), service, function, payload)
+    }
+}
+
+/// Check whether the capabilities grant a service function
+fn is_granted(capabilities: &Capabilities, service: &str, function: &str) -> bool {
+    let qualified = format!("{}.{}", service, function);
+    granted_services(capabilities).iter()
+        .any(|grant| grant == "*" || grant == service || *grant == qualified)
+}
+
+/// Service grants in the capabilities
+fn granted_services(capabilities: &Capabilities) -> Vec<String> {
+    match capabilities.get_custom(SERVICE_GRANT_CAPABILITY) {
+        Some(CustomCapability::StringList(grants)) => grants.clone(),
+        Some(CustomCapability::String(grant)) => vec![grant.clone()],
+        _ => Vec::new(),
+    }
+}
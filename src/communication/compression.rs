@@ -0,0 +1,191 @@
+//! Transparent compression of channel payloads
+//!
+//! Large JSON documents compress well, and in the out-of-process mode the IPC
+//! socket is often the bottleneck. A channel configured with a
+//! [`CompressionConfig`] announces the codecs it accepts when it connects; once
+//! the peer's announcement arrives, messages of at least `min_size` bytes are
+//! sent with the first codec in the channel's preference list that the peer
+//! accepts. Messages that don't shrink are sent as they are. Both ends have to
+//! opt in, so a channel talking to a peer without compression keeps working.
+
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::error::{Error, Result};
+
+/// Default size below which messages are sent uncompressed
+pub const DEFAULT_MIN_COMPRESSED_SIZE: usize = 4 * 1024;
+
+/// Compression algorithm for channel payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionCodec {
+    /// Zstandard, fast with a good ratio
+    Zstd,
+    
+    /// Gzip, for peers without zstd
+    Gzip,
+}
+
+impl CompressionCodec {
+    /// Number identifying the codec on the wire
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            Self::Zstd => 1,
+            Self::Gzip => 2,
+        }
+    }
+    
+    /// Codec for a wire number
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Self::Zstd),
+            2 => Some(Self::Gzip),
+            _ => None,
+        }
+    }
+    
+    /// Compress a payload
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::Zstd => zstd::bulk::compress(data, zstd::DEFAULT_COMPRESSION_LEVEL).map_err(Error::Io),
+            Self::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+        }
+    }
+    
+    /// Decompress a payload that must expand to exactly `raw_len` bytes
+    pub fn decompress(self, data: &[u8], raw_len: usize) -> Result<Vec<u8>> {
+        let mut raw = Vec::with_capacity(raw_len);
+        // Read one byte past the expected length to catch payloads that expand further
+        let limit = raw_len as u64 + 1;
+        match self {
+            Self::Zstd => zstd::stream::read::Decoder::new(data)?.take(limit).read_to_end(&mut raw)?,
+            Self::Gzip => flate2::read::GzDecoder::new(data).take(limit).read_to_end(&mut raw)?,
+        };
+        
+        if raw.len() != raw_len {
+            return Err(Error::Communication {
+                channel: "compression".to_string(),
+                reason: format!("{:?} payload expanded to {} bytes instead of {}", self, raw.len(), raw_len),
+                instance_id: None,
+            });
+        }
+        Ok(raw)
+    }
+}
+
+/// Which codecs a channel offers and when it compresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionConfig {
+    /// Codecs the channel accepts, most preferred first
+    pub codecs: Vec<CompressionCodec>,
+    
+    /// Smallest message worth compressing, in bytes
+    pub min_size: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            codecs: vec![CompressionCodec::Zstd, CompressionCodec::Gzip],
+            min_size: DEFAULT_MIN_COMPRESSED_SIZE,
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Offer only `codecs`, most preferred first
+    pub fn codecs(mut self, codecs: impl IntoIterator<Item = CompressionCodec>) -> Self {
+        self.codecs = codecs.into_iter().collect();
+        self
+    }
+    
+    /// Set the smallest message worth compressing
+    pub fn min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+    
+    /// The preferred codec the peer also accepts
+    pub(crate) fn negotiate(&self, peer_codecs: &[CompressionCodec]) -> Option<CompressionCodec> {
+        self.codecs.iter().copied().find(|codec| peer_codecs.contains(codec))
+    }
+}
+
+/// Raw and on-the-wire payload sizes of a channel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressionStats {
+    /// Payload bytes given to the channel to send
+    pub raw_bytes_sent: u64,
+    
+    /// Payload bytes actually sent, after compression
+    pub wire_bytes_sent: u64,
+    
+    /// Payload bytes delivered to the receiver, after decompression
+    pub raw_bytes_received: u64,
+    
+    /// Payload bytes actually received
+    pub wire_bytes_received: u64,
+    
+    /// Messages sent compressed
+    pub messages_compressed: u64,
+    
+    /// Messages received compressed
+    pub messages_decompressed: u64,
+}
+
+impl CompressionStats {
+    /// Fraction of the raw bytes sent that went over the wire
+    pub fn send_ratio(&self) -> f64 {
+        if self.raw_bytes_sent == 0 {
+            return 1.0;
+        }
+        self.wire_bytes_sent as f64 / self.raw_bytes_sent as f64
+    }
+}
+
+/// Counters behind [`CompressionStats`], shared with a channel's reader thread
+#[derive(Debug, Default)]
+pub(crate) struct CompressionCounters {
+    raw_bytes_sent: AtomicU64,
+    wire_bytes_sent: AtomicU64,
+    raw_bytes_received: AtomicU64,
+    wire_bytes_received: AtomicU64,
+    messages_compressed: AtomicU64,
+    messages_decompressed: AtomicU64,
+}
+
+impl CompressionCounters {
+    /// Count a sent message
+    pub(crate) fn sent(&self, raw: usize, wire: usize, compressed: bool) {
+        self.raw_bytes_sent.fetch_add(raw as u64, Ordering::Relaxed);
+        self.wire_bytes_sent.fetch_add(wire as u64, Ordering::Relaxed);
+        if compressed {
+            self.messages_compressed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    
+    /// Count a received message
+    pub(crate) fn received(&self, raw: usize, wire: usize, compressed: bool) {
+        self.raw_bytes_received.fetch_add(raw as u64, Ordering::Relaxed);
+        self.wire_bytes_received.fetch_add(wire as u64, Ordering::Relaxed);
+        if compressed {
+            self.messages_decompressed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    
+    /// Current totals
+    pub(crate) fn snapshot(&self) -> CompressionStats {
+        CompressionStats {
+            raw_bytes_sent: self.raw_bytes_sent.load(Ordering::Relaxed),
+            wire_bytes_sent: self.wire_bytes_sent.load(Ordering::Relaxed),
+            raw_bytes_received: self.raw_bytes_received.load(Ordering::Relaxed),
+            wire_bytes_received: self.wire_bytes_received.load(Ordering::Relaxed),
+            messages_compressed: self.messages_compressed.load(Ordering::Relaxed),
+            messages_decompressed: self.messages_decompressed.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -59,10 +59,10 @@ impl StdioRedirection {
         
         // Try to fill the buffer if it's empty
         let mut stdin_buffer = self.stdin_buffer.lock().unwrap();
-        if stdin_buffer.is_empty() {
-            if let Ok(data) = self.stdin_channel.receive_from_guest() {
-                stdin_buffer.extend_from_slice(&data);
-            }
+        if stdin_buffer.is_empty()
+            && let Ok(data) = self.stdin_channel.receive_from_guest()
+        {
+            stdin_buffer.extend_from_slice(&data);
         }
         
         // If we have data, copy it to the buffer
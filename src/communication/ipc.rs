@@ -0,0 +1,786 @@
+//! Inter-process communication channel over unix domain sockets or named pipes
+//!
+//! Lets an out-of-process supervisor or external tooling talk to the sandbox
+//! through the same [`CommunicationChannel`] and [`RpcChannel`] traits as the
+//! in-process channels. Messages are framed with a little-endian `u32` length
+//! prefix. On unix the endpoint is a socket path; on Windows it is a pipe
+//! name such as `\\.\pipe\wasm-sandbox`.
+//!
+//! Channels configured with [`CompressionConfig`] exchange the codecs they
+//! accept when they connect and compress large messages from then on. Such
+//! control and compressed frames set the top bit of the length prefix and
+//! start with a tag byte: 0 for the list of accepted codec IDs, otherwise the
+//! codec ID followed by the message's uncompressed length as a `u32`.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::communication::compression::{CompressionCodec, CompressionConfig, CompressionCounters, CompressionStats};
+use crate::communication::{ByteHandlerFunction, CommunicationChannel, RpcChannel, StringHandlerFunction};
+use crate::error::{Error, Result};
+use crate::utils::logging;
+
+/// IPC channel configuration
+#[derive(Debug, Clone)]
+pub struct IpcChannelConfig {
+    /// Channel name used in logs and errors
+    pub name: String,
+    
+    /// How long a receive or RPC call waits for the peer
+    pub timeout: Duration,
+    
+    /// Largest message accepted from the peer
+    pub max_message_size: usize,
+    
+    /// Compression to negotiate with the peer; `None` sends everything uncompressed
+    pub compression: Option<CompressionConfig>,
+}
+
+impl Default for IpcChannelConfig {
+    fn default() -> Self {
+        Self {
+            name: "ipc".to_string(),
+            timeout: Duration::from_secs(30),
+            max_message_size: 16 * 1024 * 1024,
+            compression: None,
+        }
+    }
+}
+
+type ShutdownFunction = Box<dyn Fn() -> io::Result<()> + Send + Sync>;
+
+/// Set in the length prefix of frames that start with a tag byte
+const TAGGED_FRAME: u32 = 1 << 31;
+
+/// Tag of the frame listing the codecs a peer accepts
+const TAG_CODECS: u8 = 0;
+
+/// Frame read from the peer
+enum Frame {
+    /// Message sent as it is
+    Message(Vec<u8>),
+    
+    /// Codecs the peer accepts
+    Codecs(Vec<CompressionCodec>),
+    
+    /// Compressed message with its uncompressed length
+    Compressed(CompressionCodec, usize, Vec<u8>),
+}
+
+/// Messages read from the peer by the background reader
+#[derive(Default)]
+struct Inbox {
+    /// Frames waiting to be received
+    messages: VecDeque<Vec<u8>>,
+    
+    /// Set once the peer disconnects or the channel is closed
+    closed: bool,
+}
+
+/// Communication channel to another process
+pub struct IpcChannel {
+    /// Channel configuration
+    config: IpcChannelConfig,
+    
+    /// Write half of the connection
+    writer: Mutex<Box<dyn Write + Send>>,
+    
+    /// Frames delivered by the reader thread
+    inbox: Arc<(Mutex<Inbox>, Condvar)>,
+    
+    /// Tears down the connection so the reader thread exits
+    shutdown: ShutdownFunction,
+    
+    /// Codec agreed with the peer, once it has announced its codecs
+    codec: Arc<Mutex<Option<CompressionCodec>>>,
+    
+    /// Raw and on-the-wire payload sizes
+    counters: Arc<CompressionCounters>,
+}
+
+impl IpcChannel {
+    /// Connect to a listening endpoint
+    pub fn connect(path: impl AsRef<Path>, config: IpcChannelConfig) -> Result<Self> {
+        let path = path.as_ref();
+        platform::connect(path, config).map_err(|e| Error::Communication {
+            channel: "ipc".to_string(),
+            reason: format!("Failed to connect to {}: {}", path.display(), e),
+            instance_id: None,
+        })
+    }
+    
+    /// Create a connected pair of channels within this process
+    #[cfg(unix)]
+    pub fn pair(config: IpcChannelConfig) -> Result<(Self, Self)> {
+        let (a, b) = std::os::unix::net::UnixStream::pair().map_err(|e| Error::Communication {
+            channel: config.name.clone(),
+            reason: format!("Failed to create socket pair: {}", e),
+            instance_id: None,
+        })?;
+        Ok((
+            platform::from_stream(a, config.clone()).map_err(|e| io_error(&config.name, e))?,
+            platform::from_stream(b, config.clone()).map_err(|e| io_error(&config.name, e))?,
+        ))
+    }
+    
+    /// Get the channel name
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+    
+    /// Codec large messages are sent with, if one has been negotiated
+    pub fn compression_codec(&self) -> Option<CompressionCodec> {
+        *self.codec.lock().unwrap()
+    }
+    
+    /// Raw and on-the-wire sizes of the payloads sent and received
+    pub fn compression_stats(&self) -> CompressionStats {
+        self.counters.snapshot()
+    }
+    
+    /// Start the reader thread for a connection
+    fn from_parts(
+        config: IpcChannelConfig,
+        mut reader: Box<dyn Read + Send>,
+        writer: Box<dyn Write + Send>,
+        shutdown: ShutdownFunction,
+    ) -> io::Result<Self> {
+        let inbox = Arc::new((Mutex::new(Inbox::default()), Condvar::new()));
+        let codec = Arc::new(Mutex::new(None));
+        let counters = Arc::new(CompressionCounters::default());
+        let thread_inbox = inbox.clone();
+        let thread_codec = codec.clone();
+        let thread_counters = counters.clone();
+        let compression = config.compression.clone();
+        let max_message_size = config.max_message_size;
+        let name = config.name.clone();
+        
+        std::thread::Builder::new()
+            .name(format!("{}-reader", config.name))
+            .spawn(move || {
+                let (lock, ready) = &*thread_inbox;
+                while let Ok(frame) = read_frame(&mut reader, max_message_size) {
+                    let message = match frame {
+                        Frame::Message(message) => {
+                            thread_counters.received(message.len(), message.len(), false);
+                            message
+                        }
+                        Frame::Codecs(peer_codecs) => {
+                            *thread_codec.lock().unwrap() = compression.as_ref()
+                                .and_then(|compression| compression.negotiate(&peer_codecs));
+                            continue;
+                        }
+                        Frame::Compressed(codec, raw_len, payload) => match codec.decompress(&payload, raw_len) {
+                            Ok(message) => {
+                                thread_counters.received(message.len(), payload.len() + 5, true);
+                                message
+                            }
+                            Err(e) => {
+                                log::warn!("Dropping connection {} after a bad compressed frame: {}", name, e);
+                                break;
+                            }
+                        },
+                    };
+                    let mut inbox = lock.lock().unwrap();
+                    if inbox.closed {
+                        break;
+                    }
+                    inbox.messages.push_back(message);
+                    ready.notify_all();
+                }
+                lock.lock().unwrap().closed = true;
+                ready.notify_all();
+            })?;
+        
+        let channel = Self {
+            config,
+            writer: Mutex::new(writer),
+            inbox,
+            shutdown,
+            codec,
+            counters,
+        };
+        if let Some(compression) = &channel.config.compression {
+            let mut announcement = vec![TAG_CODECS];
+            announcement.extend(compression.codecs.iter().map(|codec| codec.id()));
+            channel.write_frame(announcement.len() as u32 | TAGGED_FRAME, &[&announcement])?;
+        }
+        Ok(channel)
+    }
+    
+    /// Write a frame with the given length prefix, made of `parts`
+    fn write_frame(&self, prefix: u32, parts: &[&[u8]]) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_all(&prefix.to_le_bytes())?;
+        for part in parts {
+            writer.write_all(part)?;
+        }
+        writer.flush()
+    }
+    
+    /// Compress a message with the negotiated codec, if that is worthwhile
+    fn compress(&self, message: &[u8]) -> Result<Option<(CompressionCodec, Vec<u8>)>> {
+        let (Some(codec), Some(compression)) = (self.compression_codec(), &self.config.compression) else {
+            return Ok(None);
+        };
+        if message.len() < compression.min_size {
+            return Ok(None);
+        }
+        let compressed = codec.compress(message)?;
+        Ok((compressed.len() + 5 < message.len()).then_some((codec, compressed)))
+    }
+    
+    /// Wait for the next message, or indefinitely when `timeout` is `None`
+    fn receive_within(&self, timeout: Option<Duration>) -> Result<Vec<u8>> {
+        let (lock, ready) = &*self.inbox;
+        let mut inbox = lock.lock().unwrap();
+        let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+        
+        loop {
+            if let Some(message) = inbox.messages.pop_front() {
+                logging::log_communication_event(&self.config.name, "received", message.len());
+                return Ok(message);
+            }
+            if inbox.closed {
+                return Err(self.closed_error());
+            }
+            
+            inbox = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+                    if remaining.is_zero() {
+                        return Err(Error::Timeout {
+                            operation: format!("receive on {}", self.config.name),
+                            duration: timeout.unwrap_or_default(),
+                            instance_id: None,
+                        });
+                    }
+                    ready.wait_timeout(inbox, remaining).unwrap().0
+                }
+                None => ready.wait(inbox).unwrap(),
+            };
+        }
+    }
+    
+    fn closed_error(&self) -> Error {
+        Error::Communication {
+            channel: self.config.name.clone(),
+            reason: "Channel is closed".to_string(),
+            instance_id: None,
+        }
+    }
+}
+
+impl CommunicationChannel for IpcChannel {
+    fn send_to_guest(&self, message: &[u8]) -> Result<()> {
+        if self.inbox.0.lock().unwrap().closed {
+            return Err(self.closed_error());
+        }
+        if message.len() > self.config.max_message_size {
+            return Err(Error::Communication {
+                channel: self.config.name.clone(),
+                reason: format!(
+                    "Message of {} bytes exceeds the {} byte limit",
+                    message.len(),
+                    self.config.max_message_size
+                ),
+                instance_id: None,
+            });
+        }
+        
+        match self.compress(message)? {
+            Some((codec, compressed)) => {
+                let header = [&[codec.id()][..], &(message.len() as u32).to_le_bytes()].concat();
+                let wire_len = header.len() + compressed.len();
+                self.write_frame(wire_len as u32 | TAGGED_FRAME, &[&header, &compressed])
+                    .map_err(|e| io_error(&self.config.name, e))?;
+                self.counters.sent(message.len(), wire_len, true);
+            }
+            None => {
+                self.write_frame(message.len() as u32, &[message])
+                    .map_err(|e| io_error(&self.config.name, e))?;
+                self.counters.sent(message.len(), message.len(), false);
+            }
+        }
+        
+        logging::log_communication_event(&self.config.name, "sent", message.len());
+        Ok(())
+    }
+    
+    fn receive_from_guest(&self) -> Result<Vec<u8>> {
+        self.receive_within(Some(self.config.timeout))
+    }
+    
+    fn has_messages(&self) -> bool {
+        !self.inbox.0.lock().unwrap().messages.is_empty()
+    }
+    
+    fn close(&self) -> Result<()> {
+        let (lock, ready) = &*self.inbox;
+        lock.lock().unwrap().closed = true;
+        ready.notify_all();
+        (self.shutdown)().map_err(|e| io_error(&self.config.name, e))
+    }
+}
+
+impl Drop for IpcChannel {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+/// Listening endpoint accepting IPC connections
+pub struct IpcListener {
+    /// Endpoint path or pipe name
+    path: PathBuf,
+    
+    /// Configuration given to accepted channels
+    config: IpcChannelConfig,
+    
+    #[cfg(unix)]
+    listener: std::os::unix::net::UnixListener,
+}
+
+impl IpcListener {
+    /// Listen on a socket path (unix) or pipe name (Windows)
+    pub fn bind(path: impl AsRef<Path>, config: IpcChannelConfig) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        
+        #[cfg(unix)]
+        {
+            let listener = std::os::unix::net::UnixListener::bind(&path).map_err(|e| Error::Communication {
+                channel: config.name.clone(),
+                reason: format!("Failed to listen on {}: {}", path.display(), e),
+                instance_id: None,
+            })?;
+            Ok(Self { path, config, listener })
+        }
+        
+        #[cfg(not(unix))]
+        Ok(Self { path, config })
+    }
+    
+    /// Get the endpoint path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    /// Wait for the next connection
+    pub fn accept(&self) -> Result<IpcChannel> {
+        #[cfg(unix)]
+        let channel = self
+            .listener
+            .accept()
+            .and_then(|(stream, _)| platform::from_stream(stream, self.config.clone()));
+        
+        #[cfg(not(unix))]
+        let channel = platform::accept(&self.path, self.config.clone());
+        
+        channel.map_err(|e| io_error(&self.config.name, e))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for IpcListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::io;
+    use std::net::Shutdown;
+    use std::os::unix::net::UnixStream;
+    use std::path::Path;
+    
+    use super::{IpcChannel, IpcChannelConfig};
+    
+    pub fn connect(path: &Path, config: IpcChannelConfig) -> io::Result<IpcChannel> {
+        from_stream(UnixStream::connect(path)?, config)
+    }
+    
+    pub fn from_stream(stream: UnixStream, config: IpcChannelConfig) -> io::Result<IpcChannel> {
+        let reader = stream.try_clone()?;
+        let control = stream.try_clone()?;
+        IpcChannel::from_parts(
+            config,
+            Box::new(reader),
+            Box::new(stream),
+            Box::new(move || match control.shutdown(Shutdown::Both) {
+                Err(e) if e.kind() != io::ErrorKind::NotConnected => Err(e),
+                _ => Ok(()),
+            }),
+        )
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    //! Named pipes opened without overlapped I/O serialize reads and writes on
+    //! the same handle, so the pipe is driven by a private tokio runtime and
+    //! exposed to the channel as blocking halves.
+    
+    use std::io::{self, Read, Write};
+    use std::path::Path;
+    use std::sync::Arc;
+    
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+    use tokio::net::windows::named_pipe::{ClientOptions, ServerOptions};
+    use tokio::runtime::Runtime;
+    use tokio::sync::{watch, Mutex};
+    
+    use super::{IpcChannel, IpcChannelConfig};
+    
+    type Pipe = Box<dyn PipeIo>;
+    
+    trait PipeIo: AsyncRead + AsyncWrite + Send + Unpin {}
+    
+    impl<T: AsyncRead + AsyncWrite + Send + Unpin> PipeIo for T {}
+    
+    pub fn connect(path: &Path, config: IpcChannelConfig) -> io::Result<IpcChannel> {
+        let runtime = pipe_runtime()?;
+        let path = path.to_path_buf();
+        let pipe = run(&runtime, async move { ClientOptions::new().open(path).map(|pipe| Box::new(pipe) as Pipe) })?;
+        from_pipe(runtime, pipe, config)
+    }
+    
+    /// Create a pipe instance and wait for a client to connect to it
+    pub fn accept(path: &Path, config: IpcChannelConfig) -> io::Result<IpcChannel> {
+        let runtime = pipe_runtime()?;
+        let path = path.to_path_buf();
+        let pipe = run(&runtime, async move {
+            let server = ServerOptions::new().create(path)?;
+            server.connect().await?;
+            Ok(Box::new(server) as Pipe)
+        })?;
+        from_pipe(runtime, pipe, config)
+    }
+    
+    fn pipe_runtime() -> io::Result<Arc<Runtime>> {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("ipc-pipe")
+            .enable_io()
+            .build()
+            .map(Arc::new)
+    }
+    
+    /// Run a future on the pipe runtime, safe to call from inside another runtime
+    fn run<T: Send + 'static>(
+        runtime: &Runtime,
+        future: impl std::future::Future<Output = io::Result<T>> + Send + 'static,
+    ) -> io::Result<T> {
+        futures::executor::block_on(runtime.spawn(future))
+            .map_err(io::Error::other)?
+    }
+    
+    fn from_pipe(runtime: Arc<Runtime>, pipe: Pipe, config: IpcChannelConfig) -> io::Result<IpcChannel> {
+        let (reader, writer) = tokio::io::split(pipe);
+        let (closed_tx, closed_rx) = watch::channel(false);
+        IpcChannel::from_parts(
+            config,
+            Box::new(PipeReader { runtime: runtime.clone(), reader, closed: closed_rx }),
+            Box::new(PipeWriter { runtime, writer: Arc::new(Mutex::new(writer)) }),
+            Box::new(move || {
+                let _ = closed_tx.send(true);
+                Ok(())
+            }),
+        )
+    }
+    
+    /// Blocking read half, only used from the channel's reader thread
+    struct PipeReader {
+        runtime: Arc<Runtime>,
+        reader: ReadHalf<Pipe>,
+        closed: watch::Receiver<bool>,
+    }
+    
+    impl Read for PipeReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let Self { runtime, reader, closed } = self;
+            runtime.block_on(async {
+                if *closed.borrow() {
+                    return Ok(0);
+                }
+                tokio::select! {
+                    read = reader.read(buf) => read,
+                    _ = closed.changed() => Ok(0),
+                }
+            })
+        }
+    }
+    
+    /// Blocking write half
+    struct PipeWriter {
+        runtime: Arc<Runtime>,
+        writer: Arc<Mutex<WriteHalf<Pipe>>>,
+    }
+    
+    impl Write for PipeWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let writer = self.writer.clone();
+            let data = buf.to_vec();
+            run(&self.runtime, async move { writer.lock().await.write_all(&data).await })?;
+            Ok(buf.len())
+        }
+        
+        fn flush(&mut self) -> io::Result<()> {
+            let writer = self.writer.clone();
+            run(&self.runtime, async move { writer.lock().await.flush().await })
+        }
+    }
+}
+
+fn read_frame(reader: &mut dyn Read, max_message_size: usize) -> io::Result<Frame> {
+    let mut prefix = [0u8; 4];
+    reader.read_exact(&mut prefix)?;
+    let prefix = u32::from_le_bytes(prefix);
+    let length = (prefix & !TAGGED_FRAME) as usize;
+    if length > max_message_size {
+        return Err(frame_too_large(length, max_message_size));
+    }
+    
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body)?;
+    if prefix & TAGGED_FRAME == 0 {
+        return Ok(Frame::Message(body));
+    }
+    
+    let malformed = || io::Error::new(io::ErrorKind::InvalidData, "Malformed tagged frame");
+    match body.first().copied().ok_or_else(malformed)? {
+        TAG_CODECS => Ok(Frame::Codecs(body[1..].iter().filter_map(|&id| CompressionCodec::from_id(id)).collect())),
+        tag => {
+            let codec = CompressionCodec::from_id(tag).ok_or_else(malformed)?;
+            let raw_len = u32::from_le_bytes(body.get(1..5).ok_or_else(malformed)?.try_into().unwrap()) as usize;
+            if raw_len > max_message_size {
+                return Err(frame_too_large(raw_len, max_message_size));
+            }
+            Ok(Frame::Compressed(codec, raw_len, body.split_off(5)))
+        }
+    }
+}
+
+fn frame_too_large(length: usize, max_message_size: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Frame of {} bytes exceeds the {} byte limit", length, max_message_size),
+    )
+}
+
+fn io_error(channel: &str, e: io::Error) -> Error {
+    Error::Communication {
+        channel: channel.to_string(),
+        reason: e.to_string(),
+        instance_id: None,
+    }
+}
+
+const FRAME_REQUEST: u8 = 0;
+const FRAME_RESPONSE: u8 = 1;
+const FRAME_ERROR: u8 = 2;
+
+/// Decoded RPC frame: kind, call id, function name, payload
+type RpcFrame = (u8, u32, String, Vec<u8>);
+
+type PendingCalls = Arc<(Mutex<HashMap<u32, std::result::Result<Vec<u8>, String>>>, Condvar)>;
+
+/// RPC channel to another process
+///
+/// Both ends register host functions and call the peer's. A dispatcher
+/// thread owns all reads from the underlying channel, so the channel must
+/// not be read directly once wrapped. Dropping the RPC channel closes it.
+pub struct IpcRpcChannel {
+    /// Underlying channel
+    channel: Arc<IpcChannel>,
+    
+    /// Functions the peer may call
+    functions: Arc<Mutex<HashMap<String, Arc<ByteHandlerFunction>>>>,
+    
+    /// Responses not yet collected by their callers
+    pending: PendingCalls,
+    
+    /// Next call id
+    next_id: AtomicU32,
+}
+
+impl IpcRpcChannel {
+    /// Create an RPC channel and start dispatching incoming calls
+    pub fn new(channel: Arc<IpcChannel>) -> Result<Self> {
+        let functions: Arc<Mutex<HashMap<String, Arc<ByteHandlerFunction>>>> = Arc::default();
+        let pending: PendingCalls = Arc::default();
+        
+        let dispatch_channel = channel.clone();
+        let dispatch_functions = functions.clone();
+        let dispatch_pending = pending.clone();
+        std::thread::Builder::new()
+            .name(format!("{}-rpc", channel.name()))
+            .spawn(move || {
+                while let Ok(frame) = dispatch_channel.receive_within(None) {
+                    match decode_frame(&frame) {
+                        Some((FRAME_REQUEST, id, name, payload)) => {
+                            let function = dispatch_functions.lock().unwrap().get(&name).cloned();
+                            let channel = dispatch_channel.clone();
+                            // Calls run on their own thread so they can call back into the peer
+                            std::thread::spawn(move || {
+                                let reply = match function {
+                                    Some(function) => match function(&payload) {
+                                        Ok(result) => encode_frame(FRAME_RESPONSE, id, "", &result),
+                                        Err(e) => encode_frame(FRAME_ERROR, id, "", e.to_string().as_bytes()),
+                                    },
+                                    None => encode_frame(
+                                        FRAME_ERROR,
+                                        id,
+                                        "",
+                                        format!("Function {} is not registered", name).as_bytes(),
+                                    ),
+                                };
+                                if let Err(e) = channel.send_to_guest(&reply) {
+                                    log::warn!("Failed to reply to IPC call {}: {}", id, e);
+                                }
+                            });
+                        }
+                        Some((kind, id, _, payload)) => {
+                            let result = if kind == FRAME_RESPONSE {
+                                Ok(payload)
+                            } else {
+                                Err(String::from_utf8_lossy(&payload).into_owned())
+                            };
+                            let (lock, ready) = &*dispatch_pending;
+                            lock.lock().unwrap().insert(id, result);
+                            ready.notify_all();
+                        }
+                        None => log::warn!("Dropping malformed IPC frame of {} bytes", frame.len()),
+                    }
+                }
+                // Wake callers so they observe the closed channel
+                let (lock, ready) = &*dispatch_pending;
+                let _pending = lock.lock().unwrap();
+                ready.notify_all();
+            })
+            .map_err(|e| io_error(channel.name(), e))?;
+        
+        Ok(Self {
+            channel,
+            functions,
+            pending,
+            next_id: AtomicU32::new(0),
+        })
+    }
+    
+    /// Get the underlying channel
+    pub fn channel(&self) -> &Arc<IpcChannel> {
+        &self.channel
+    }
+    
+    /// Send a request and wait for its response
+    fn call(&self, function_name: &str, payload: &[u8]) -> Result<Vec<u8>> {
+        if function_name.len() > u16::MAX as usize {
+            return Err(Error::InvalidInput {
+                field: "function_name".to_string(),
+                reason: format!("Function name is {} bytes long", function_name.len()),
+                suggestion: Some(format!("Use a name of at most {} bytes", u16::MAX)),
+            });
+        }
+        
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.channel.send_to_guest(&encode_frame(FRAME_REQUEST, id, function_name, payload))?;
+        
+        let timeout = self.channel.config.timeout;
+        let deadline = std::time::Instant::now() + timeout;
+        let (lock, ready) = &*self.pending;
+        let mut pending = lock.lock().unwrap();
+        loop {
+            if let Some(result) = pending.remove(&id) {
+                return result.map_err(|reason| Error::FunctionCall {
+                    function_name: function_name.to_string(),
+                    reason,
+                });
+            }
+            if self.channel.inbox.0.lock().unwrap().closed {
+                return Err(self.channel.closed_error());
+            }
+            
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() {
+                return Err(Error::Timeout {
+                    operation: format!("call to {}", function_name),
+                    duration: timeout,
+                    instance_id: None,
+                });
+            }
+            pending = ready.wait_timeout(pending, remaining).unwrap().0;
+        }
+    }
+}
+
+impl RpcChannel for IpcRpcChannel {
+    fn register_host_function_json(
+        &mut self,
+        name: &str,
+        function: StringHandlerFunction,
+    ) -> Result<()> {
+        let func: ByteHandlerFunction = Box::new(move |data: &[u8]| -> Result<Vec<u8>> {
+            let params_json = String::from_utf8_lossy(data);
+            Ok(function(&params_json)?.into_bytes())
+        });
+        self.functions.lock().unwrap().insert(name.to_string(), Arc::new(func));
+        Ok(())
+    }
+    
+    fn call_guest_function_json(
+        &self,
+        function_name: &str,
+        params_json: &str,
+    ) -> Result<String> {
+        let response = self.call(function_name, params_json.as_bytes())?;
+        Ok(String::from_utf8_lossy(&response).into_owned())
+    }
+    
+    fn register_host_function_msgpack(
+        &mut self,
+        name: &str,
+        function: ByteHandlerFunction,
+    ) -> Result<()> {
+        self.functions.lock().unwrap().insert(name.to_string(), Arc::new(function));
+        Ok(())
+    }
+    
+    fn call_guest_function_msgpack(
+        &self,
+        function_name: &str,
+        params_msgpack: &[u8],
+    ) -> Result<Vec<u8>> {
+        self.call(function_name, params_msgpack)
+    }
+}
+
+impl Drop for IpcRpcChannel {
+    fn drop(&mut self) {
+        // Stops the dispatcher thread, which holds its own reference to the channel
+        let _ = self.channel.close();
+    }
+}
+
+/// Layout: kind (u8), call id (u32 LE), name length (u16 LE), name, payload
+fn encode_frame(kind: u8, id: u32, name: &str, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(7 + name.len() + payload.len());
+    frame.push(kind);
+    frame.extend_from_slice(&id.to_le_bytes());
+    frame.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    frame.extend_from_slice(name.as_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn decode_frame(frame: &[u8]) -> Option<RpcFrame> {
+    let kind = *frame.first()?;
+    let id = u32::from_le_bytes(frame.get(1..5)?.try_into().ok()?);
+    let name_len = u16::from_le_bytes(frame.get(5..7)?.try_into().ok()?) as usize;
+    let name = std::str::from_utf8(frame.get(7..7 + name_len)?).ok()?.to_string();
+    Some((kind, id, name, frame[7 + name_len..].to_vec()))
+}
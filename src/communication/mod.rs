@@ -1,7 +1,10 @@
 //! Host-guest communication mechanisms
 
+use std::future::Future;
 use std::sync::Arc;
 
+use futures::future::BoxFuture;
+
 use crate::error::Result;
 
 /// Communication channel between host and guest
@@ -22,6 +25,7 @@ pub trait CommunicationChannel: Send + Sync {
 // Type aliases for complex types to improve readability
 type StringHandlerFunction = Box<dyn Fn(&str) -> Result<String> + Send + Sync + 'static>;
 type ByteHandlerFunction = Box<dyn Fn(&[u8]) -> Result<Vec<u8>> + Send + Sync + 'static>;
+type AsyncStringHandlerFunction = Box<dyn Fn(String) -> BoxFuture<'static, Result<String>> + Send + Sync + 'static>;
 
 /// RPC mechanism between host and guest (dyn-compatible part)
 pub trait RpcChannel: Send + Sync {
@@ -116,6 +120,98 @@ impl<T: RpcChannel> RpcChannelExt for T {
     }
 }
 
+/// RPC channel that supports async host functions
+///
+/// Async host functions return futures that are awaited while the calling
+/// guest is suspended, so host-side lookups (databases, HTTP) don't block a
+/// worker thread.
+pub trait AsyncRpcChannel: RpcChannel {
+    /// Register an async host function with JSON serialization
+    fn register_async_host_function_json(
+        &mut self,
+        name: &str,
+        function: AsyncStringHandlerFunction,
+    ) -> Result<()>;
+    
+    /// Dispatch a guest call to a registered host function (sync or async)
+    fn call_host_function_json_async<'a>(
+        &'a self,
+        name: &'a str,
+        params_json: &'a str,
+    ) -> BoxFuture<'a, Result<String>>;
+}
+
+/// Extension trait for type-safe async host functions
+pub trait AsyncRpcChannelExt {
+    /// Register an async host function that can be called from the guest
+    fn register_async_host_function<F, Fut, Params, Return>(
+        &mut self,
+        name: &str,
+        function: F,
+    ) -> Result<()>
+    where
+        F: Fn(Params) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Return>> + Send + 'static,
+        Params: serde::de::DeserializeOwned + 'static,
+        Return: serde::Serialize + 'static;
+    
+    /// Call a registered host function, awaiting it if it is async
+    fn call_host_function<'a, Params, Return>(
+        &'a self,
+        name: &'a str,
+        params: &Params,
+    ) -> BoxFuture<'a, Result<Return>>
+    where
+        Params: serde::Serialize + ?Sized,
+        Return: serde::de::DeserializeOwned + 'static;
+}
+
+/// Automatic implementation for all async RPC channels
+impl<T: AsyncRpcChannel> AsyncRpcChannelExt for T {
+    fn register_async_host_function<F, Fut, Params, Return>(
+        &mut self,
+        name: &str,
+        function: F,
+    ) -> Result<()>
+    where
+        F: Fn(Params) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Return>> + Send + 'static,
+        Params: serde::de::DeserializeOwned + 'static,
+        Return: serde::Serialize + 'static,
+    {
+        let function = Arc::new(function);
+        let wrapped_function: AsyncStringHandlerFunction = Box::new(move |params_json: String| {
+            let function = function.clone();
+            Box::pin(async move {
+                let params: Params = serde_json::from_str(&params_json)?;
+                let result = function(params).await?;
+                let result_json = serde_json::to_string(&result)?;
+                Ok(result_json)
+            })
+        });
+        
+        self.register_async_host_function_json(name, wrapped_function)
+    }
+    
+    fn call_host_function<'a, Params, Return>(
+        &'a self,
+        name: &'a str,
+        params: &Params,
+    ) -> BoxFuture<'a, Result<Return>>
+    where
+        Params: serde::Serialize + ?Sized,
+        Return: serde::de::DeserializeOwned + 'static,
+    {
+        let params_json = serde_json::to_string(params);
+        Box::pin(async move {
+            let params_json = params_json?;
+            let result_json = self.call_host_function_json_async(name, &params_json).await?;
+            let result = serde_json::from_str(&result_json)?;
+            Ok(result)
+        })
+    }
+}
+
 /// Communication channel factory
 pub trait CommunicationFactory: Send + Sync {
     /// Create a new communication channel
@@ -125,8 +221,11 @@ pub trait CommunicationFactory: Send + Sync {
     fn create_rpc_channel(&self) -> Result<Arc<dyn RpcChannel>>;
 }
 
+pub mod broker;
 pub mod channels;
+pub mod compression;
 pub mod io;
+pub mod ipc;
 pub mod rpc;
 pub mod memory;
 pub mod memory_channel;
@@ -134,5 +233,7 @@ pub mod streaming;
 
 // Re-export memory channel for easier usage
 pub use memory_channel::{MemoryChannel, MemoryRpcChannel, MemoryChannelConfig};
+pub use ipc::{IpcChannel, IpcChannelConfig, IpcListener, IpcRpcChannel};
+pub use compression::{CompressionCodec, CompressionConfig, CompressionStats};
 pub use streaming::{StreamingChannel, StreamingInput, StreamingOutput, StreamingChannel2Way, 
                    StreamChunk, StreamingManager, StreamingFactory};
@@ -2,10 +2,14 @@
 
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use futures::future::BoxFuture;
 use serde::{Serialize, Deserialize, de::DeserializeOwned};
 
 use crate::error::{Error, Result};
-use crate::communication::{RpcChannel, CommunicationChannel, StringHandlerFunction, ByteHandlerFunction};
+use crate::communication::{
+    RpcChannel, AsyncRpcChannel, CommunicationChannel,
+    StringHandlerFunction, ByteHandlerFunction, AsyncStringHandlerFunction,
+};
 
 /// JSON-RPC implementation
 pub struct JsonRpcChannel {
@@ -16,6 +20,9 @@ pub struct JsonRpcChannel {
     /// Host functions
     host_functions: Mutex<HashMap<String, StringHandlerFunction>>,
     
+    /// Async host functions
+    async_host_functions: Mutex<HashMap<String, Arc<AsyncStringHandlerFunction>>>,
+    
     /// Function call ID counter
     #[allow(dead_code)]
     call_id: Mutex<u64>,
@@ -27,6 +34,7 @@ impl JsonRpcChannel {
         Self {
             channel,
             host_functions: Mutex::new(HashMap::new()),
+            async_host_functions: Mutex::new(HashMap::new()),
             call_id: Mutex::new(0),
         }
     }
@@ -86,6 +94,7 @@ impl RpcChannel for JsonRpcChannel {
         name: &str,
         function: StringHandlerFunction,
     ) -> Result<()> {
+        self.async_host_functions.lock().unwrap().remove(name);
         let mut functions = self.host_functions.lock().unwrap();
         functions.insert(name.to_string(), function);
         Ok(())
@@ -140,6 +149,42 @@ impl RpcChannel for JsonRpcChannel {
     }
 }
 
+impl AsyncRpcChannel for JsonRpcChannel {
+    fn register_async_host_function_json(
+        &mut self,
+        name: &str,
+        function: AsyncStringHandlerFunction,
+    ) -> Result<()> {
+        self.host_functions.lock().unwrap().remove(name);
+        let mut functions = self.async_host_functions.lock().unwrap();
+        functions.insert(name.to_string(), Arc::new(function));
+        Ok(())
+    }
+    
+    fn call_host_function_json_async<'a>(
+        &'a self,
+        name: &'a str,
+        params_json: &'a str,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            // Clone the async handler out of the lock so it isn't held across the await
+            let async_function = self.async_host_functions.lock().unwrap().get(name).cloned();
+            if let Some(function) = async_function {
+                return function(params_json.to_string()).await;
+            }
+            
+            let functions = self.host_functions.lock().unwrap();
+            match functions.get(name) {
+                Some(function) => function(params_json),
+                None => Err(Error::FunctionCall {
+                    function_name: name.to_string(),
+                    reason: "Host function not registered".to_string(),
+                }),
+            }
+        })
+    }
+}
+
 /// RPC channel factory
 pub struct RpcFactory {
     /// Communication channel factory
@@ -5,7 +5,7 @@
 
 use std::io;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::path::Path;
 
 use async_trait::async_trait;
@@ -307,8 +307,8 @@ pub struct MemoryStreamingChannel {
     /// Channel configuration
     config: StreamingChannelConfig,
     
-    /// Internal state
-    state: Arc<tokio::sync::RwLock<MemoryStreamingState>>,
+    /// Internal state, never locked across an await
+    state: Arc<RwLock<MemoryStreamingState>>,
 }
 
 /// Internal state for memory streaming channel
@@ -332,7 +332,7 @@ impl MemoryStreamingChannel {
         Self {
             id: id.into(),
             config,
-            state: Arc::new(tokio::sync::RwLock::new(MemoryStreamingState {
+            state: Arc::new(RwLock::new(MemoryStreamingState {
                 is_open: true,
                 h2g_queue: Vec::new(),
                 g2h_queue: Vec::new(),
@@ -360,36 +360,23 @@ impl StreamingChannel for MemoryStreamingChannel {
     }
     
     fn stats(&self) -> StreamingStats {
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                self.state.read().await.stats.clone()
-            })
-        })
+        self.state.read().unwrap().stats.clone()
     }
     
     fn is_open(&self) -> bool {
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                self.state.read().await.is_open
-            })
-        })
+        self.state.read().unwrap().is_open
     }
     
     fn close(&self) -> Result<()> {
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let mut state = self.state.write().await;
-                state.is_open = false;
-                Ok(())
-            })
-        })
+        self.state.write().unwrap().is_open = false;
+        Ok(())
     }
 }
 
 #[async_trait]
 impl StreamingInput for MemoryStreamingChannel {
     async fn send_chunk(&self, chunk: StreamChunk) -> Result<()> {
-        let mut state = self.state.write().await;
+        let mut state = self.state.write().unwrap();
         
         if !state.is_open {
             return Err(Error::Communication {
@@ -459,7 +446,7 @@ impl StreamingInput for MemoryStreamingChannel {
 #[async_trait]
 impl StreamingOutput for MemoryStreamingChannel {
     async fn receive_chunk(&self) -> Result<StreamChunk> {
-        let mut state = self.state.write().await;
+        let mut state = self.state.write().unwrap();
         
         if !state.is_open {
             return Err(Error::Communication {
@@ -490,16 +477,16 @@ impl StreamingOutput for MemoryStreamingChannel {
         let chunk = self.receive_chunk().await?;
         
         // Check size limit if specified
-        if let Some(max) = max_size {
-            if chunk.data.len() > max {
-                return Err(Error::ResourceExhausted {
-                    kind: ResourceKind::Memory,
-                    limit: max as u64,
-                    used: chunk.data.len() as u64,
-                    instance_id: None,
-                    suggestion: Some(format!("Consider increasing max_size to {} bytes or more", chunk.data.len())),
-                });
-            }
+        if let Some(max) = max_size
+            && chunk.data.len() > max
+        {
+            return Err(Error::ResourceExhausted {
+                kind: ResourceKind::Memory,
+                limit: max as u64,
+                used: chunk.data.len() as u64,
+                instance_id: None,
+                suggestion: Some(format!("Consider increasing max_size to {} bytes or more", chunk.data.len())),
+            });
         }
         
         Ok(chunk.data)
@@ -514,26 +501,20 @@ impl StreamingOutput for MemoryStreamingChannel {
             let mut last_seen_index = 0;
             
             loop {
-                // Get the current state
-                let state_guard = state.read().await;
-                
-                // Check if closed
-                if !state_guard.is_open {
-                    break;
-                }
-                
-                // Get all new chunks
-                let new_chunks: Vec<StreamChunk> = state_guard.g2h_queue
-                    .iter()
-                    .skip(last_seen_index)
-                    .cloned()
-                    .collect();
-                
-                // Update last seen index
-                last_seen_index = state_guard.g2h_queue.len();
-                
-                // Drop the guard
-                drop(state_guard);
+                // Get all new chunks, unless the channel is closed
+                let new_chunks: Vec<StreamChunk> = {
+                    let state_guard = state.read().unwrap();
+                    if !state_guard.is_open {
+                        break;
+                    }
+                    let new_chunks = state_guard.g2h_queue
+                        .iter()
+                        .skip(last_seen_index)
+                        .cloned()
+                        .collect();
+                    last_seen_index = state_guard.g2h_queue.len();
+                    new_chunks
+                };
                 
                 // Yield all new chunks
                 for chunk in new_chunks {
@@ -579,8 +560,11 @@ pub struct FileStreamingChannel {
     /// Output file path
     output_path: Option<std::path::PathBuf>,
     
-    /// Internal state
-    state: Arc<tokio::sync::RwLock<FileStreamingState>>,
+    /// Internal state, never locked across an await
+    state: Arc<RwLock<FileStreamingState>>,
+    
+    /// Held while a chunk is written or read, so chunks don't interleave
+    io: Arc<tokio::sync::Mutex<()>>,
 }
 
 /// Internal state for file streaming channel
@@ -605,7 +589,7 @@ impl FileStreamingChannel {
             config,
             input_path: input_path.map(|p| p.as_ref().to_path_buf()),
             output_path: output_path.map(|p| p.as_ref().to_path_buf()),
-            state: Arc::new(tokio::sync::RwLock::new(FileStreamingState {
+            state: Arc::new(RwLock::new(FileStreamingState {
                 is_open: true,
                 stats: StreamingStats {
                     bytes_sent: 0,
@@ -617,6 +601,7 @@ impl FileStreamingChannel {
                     error_count: 0,
                 },
             })),
+            io: Arc::new(tokio::sync::Mutex::new(())),
         }
     }
 }
@@ -631,38 +616,25 @@ impl StreamingChannel for FileStreamingChannel {
     }
     
     fn stats(&self) -> StreamingStats {
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                self.state.read().await.stats.clone()
-            })
-        })
+        self.state.read().unwrap().stats.clone()
     }
     
     fn is_open(&self) -> bool {
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                self.state.read().await.is_open
-            })
-        })
+        self.state.read().unwrap().is_open
     }
     
     fn close(&self) -> Result<()> {
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let mut state = self.state.write().await;
-                state.is_open = false;
-                Ok(())
-            })
-        })
+        self.state.write().unwrap().is_open = false;
+        Ok(())
     }
 }
 
 #[async_trait]
 impl StreamingInput for FileStreamingChannel {
     async fn send_chunk(&self, chunk: StreamChunk) -> Result<()> {
-        let mut state = self.state.write().await;
+        let _io = self.io.lock().await;
         
-        if !state.is_open {
+        if !self.is_open() {
             return Err(Error::Communication {
                 channel: "file_streaming".to_string(),
                 reason: "Streaming channel is closed".to_string(),
@@ -702,7 +674,7 @@ impl StreamingInput for FileStreamingChannel {
                 path: output_path.clone(),
                 reason: format!("Failed to write chunk size: {}", e),
             })?;
-            
+        
         file.write_all(&chunk.data).await
             .map_err(|e| Error::Filesystem {
                 operation: "write".to_string(),
@@ -720,6 +692,7 @@ impl StreamingInput for FileStreamingChannel {
             })?;
         
         // Update statistics
+        let mut state = self.state.write().unwrap();
         state.stats.bytes_sent += chunk.data.len() as u64;
         state.stats.chunks_sent += 1;
         
@@ -765,9 +738,9 @@ impl StreamingInput for FileStreamingChannel {
 #[async_trait]
 impl StreamingOutput for FileStreamingChannel {
     async fn receive_chunk(&self) -> Result<StreamChunk> {
-        let mut state = self.state.write().await;
+        let _io = self.io.lock().await;
         
-        if !state.is_open {
+        if !self.is_open() {
             return Err(Error::Communication {
                 channel: "file_streaming".to_string(),
                 reason: "Streaming channel is closed".to_string(),
@@ -807,7 +780,7 @@ impl StreamingOutput for FileStreamingChannel {
                 path: input_path.clone(),
                 reason: format!("Failed to get file metadata: {}", e),
             })?;
-            
+        
         // Check if file is empty
         if metadata.len() == 0 {
             return Err(Error::NotFound {
@@ -824,7 +797,7 @@ impl StreamingOutput for FileStreamingChannel {
                 path: input_path.clone(),
                 reason: format!("Failed to read chunk size: {}", e),
             })?;
-            
+        
         let size = u32::from_le_bytes(size_bytes) as usize;
         
         // Read the chunk data
@@ -844,10 +817,11 @@ impl StreamingOutput for FileStreamingChannel {
                 path: input_path.clone(),
                 reason: format!("Failed to read final flag: {}", e),
             })?;
-            
+        
         let is_final = final_flag[0] != 0;
         
         // Create the chunk
+        let mut state = self.state.write().unwrap();
         let chunk = StreamChunk {
             data,
             is_final,
@@ -866,16 +840,16 @@ impl StreamingOutput for FileStreamingChannel {
         let chunk = self.receive_chunk().await?;
         
         // Check size limit if specified
-        if let Some(max) = max_size {
-            if chunk.data.len() > max {
-                return Err(Error::ResourceExhausted {
-                    kind: ResourceKind::Memory,
-                    limit: max as u64,
-                    used: chunk.data.len() as u64,
-                    instance_id: None,
-                    suggestion: Some(format!("Consider increasing max_size to {} bytes or more", chunk.data.len())),
-                });
-            }
+        if let Some(max) = max_size
+            && chunk.data.len() > max
+        {
+            return Err(Error::ResourceExhausted {
+                kind: ResourceKind::Memory,
+                limit: max as u64,
+                used: chunk.data.len() as u64,
+                instance_id: None,
+                suggestion: Some(format!("Consider increasing max_size to {} bytes or more", chunk.data.len())),
+            });
         }
         
         Ok(chunk.data)
@@ -920,16 +894,14 @@ impl StreamingOutput for FileStreamingChannel {
             
             loop {
                 // Check if channel is still open
-                {
-                    let state_guard = state.read().await;
-                    if !state_guard.is_open {
-                        yield Err(Error::Communication {
-                            channel: "file_streaming".to_string(),
-                            reason: "Streaming channel was closed".to_string(),
-                            instance_id: None,
-                        });
-                        return;
-                    }
+                let is_open = state.read().unwrap().is_open;
+                if !is_open {
+                    yield Err(Error::Communication {
+                        channel: "file_streaming".to_string(),
+                        reason: "Streaming channel was closed".to_string(),
+                        instance_id: None,
+                    });
+                    return;
                 }
                 
                 // Read chunk size
@@ -987,7 +959,7 @@ impl StreamingOutput for FileStreamingChannel {
                 
                 // Update statistics
                 {
-                    let mut state_guard = state.write().await;
+                    let mut state_guard = state.write().unwrap();
                     state_guard.stats.bytes_received += chunk.data.len() as u64;
                     state_guard.stats.chunks_received += 1;
                 }
@@ -1203,7 +1175,7 @@ impl StreamTransformer {
                     operation: "encode".to_string(),
                     reason: e.to_string(),
                 })?;
-                
+            
             Ok(StreamChunk {
                 data: json,
                 is_final: false, // Set to true for last chunk
@@ -5,7 +5,7 @@ use std::process::Command;
 use std::collections::HashMap;
 
 use crate::error::{Error, Result};
-use super::{Compiler, CompilerOptions, BuildProfile};
+use super::{embed_provenance, post_optimize, preinitialize, Compiler, CompilerOptions, BuildProfile};
 
 /// Enhanced Cargo compiler implementation with additional features
 pub struct EnhancedCargoCompiler {
@@ -233,6 +233,9 @@ impl Compiler for EnhancedCargoCompiler {
                 path: wasm_path.clone(), 
                 reason: format!("Failed to copy WASM file: {}", e) 
             })?;
+        preinitialize(&output_wasm_path, options)?;
+        post_optimize(&output_wasm_path, options)?;
+        embed_provenance(&output_wasm_path, options)?;
         
         // Copy .d.ts file if available (useful for WASM-bindgen projects)
         let dts_path = wasm_path.with_extension("d.ts");
@@ -4,6 +4,9 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use crate::error::{Error, Result};
+use crate::utils::provenance::Provenance;
+use self::optimize::{PostOptimize, SizeReport};
+use self::preinit::{Preinit, PreinitReport};
 
 /// Compiler options
 #[derive(Debug, Clone)]
@@ -37,6 +40,15 @@ pub struct CompilerOptions {
     
     /// Additional RUSTFLAGS to pass to the compiler (optional)
     pub rustflags: Option<String>,
+    
+    /// Provenance to embed in the compiled module (optional)
+    pub provenance: Option<Provenance>,
+    
+    /// Shrink the compiled module before it is written out (optional)
+    pub post_optimize: Option<PostOptimize>,
+    
+    /// Run the module's initialization and snapshot the result into it (optional)
+    pub preinitialize: Option<Preinit>,
 }
 
 impl Default for CompilerOptions {
@@ -52,10 +64,81 @@ impl Default for CompilerOptions {
             profile: BuildProfile::Release,
             target_cpu: None,
             rustflags: None,
+            provenance: None,
+            post_optimize: None,
+            preinitialize: None,
         }
     }
 }
 
+impl CompilerOptions {
+    /// Embed provenance in the compiled module
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = Some(provenance);
+        self
+    }
+    
+    /// Shrink the compiled module with `wasm-opt` and by stripping custom sections
+    pub fn with_post_optimize(mut self, post_optimize: PostOptimize) -> Self {
+        self.post_optimize = Some(post_optimize);
+        self
+    }
+    
+    /// Preinitialize the compiled module, see [`preinit`]
+    pub fn with_preinitialize(mut self, preinit: Preinit) -> Self {
+        self.preinitialize = Some(preinit);
+        self
+    }
+}
+
+/// Preinitialize a compiled module as the options say, if they ask to
+pub(crate) fn preinitialize(wasm_path: &Path, options: &CompilerOptions) -> Result<Option<PreinitReport>> {
+    let Some(preinit) = &options.preinitialize else {
+        return Ok(None);
+    };
+    let filesystem_error = |operation: &str, e: std::io::Error| Error::Filesystem {
+        operation: operation.to_string(),
+        path: wasm_path.to_path_buf(),
+        reason: format!("Failed to preinitialize module: {}", e),
+    };
+    let wasm_bytes = std::fs::read(wasm_path).map_err(|e| filesystem_error("read", e))?;
+    let (preinitialized, report) = preinit::preinitialize(&wasm_bytes, preinit)?;
+    std::fs::write(wasm_path, preinitialized).map_err(|e| filesystem_error("write", e))?;
+    log::info!("Preinitialized {}: {}", wasm_path.display(), report);
+    Ok(Some(report))
+}
+
+/// Shrink a compiled module as the options say, if they ask to
+pub(crate) fn post_optimize(wasm_path: &Path, options: &CompilerOptions) -> Result<Option<SizeReport>> {
+    let Some(post_optimize) = &options.post_optimize else {
+        return Ok(None);
+    };
+    let filesystem_error = |operation: &str, e: std::io::Error| Error::Filesystem {
+        operation: operation.to_string(),
+        path: wasm_path.to_path_buf(),
+        reason: format!("Failed to optimize module: {}", e),
+    };
+    let wasm_bytes = std::fs::read(wasm_path).map_err(|e| filesystem_error("read", e))?;
+    let (optimized, report) = optimize::optimize(&wasm_bytes, post_optimize)?;
+    std::fs::write(wasm_path, optimized).map_err(|e| filesystem_error("write", e))?;
+    log::info!("Optimized {}: {}", wasm_path.display(), report);
+    Ok(Some(report))
+}
+
+/// Embed the options' provenance, if any, in a compiled module
+pub(crate) fn embed_provenance(wasm_path: &Path, options: &CompilerOptions) -> Result<()> {
+    let Some(provenance) = &options.provenance else {
+        return Ok(());
+    };
+    let filesystem_error = |operation: &str, e: std::io::Error| Error::Filesystem {
+        operation: operation.to_string(),
+        path: wasm_path.to_path_buf(),
+        reason: format!("Failed to embed provenance: {}", e),
+    };
+    let wasm_bytes = std::fs::read(wasm_path).map_err(|e| filesystem_error("read", e))?;
+    std::fs::write(wasm_path, provenance.embed(&wasm_bytes)?).map_err(|e| filesystem_error("write", e))
+}
+
 /// Optimization level
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OptimizationLevel {
@@ -273,6 +356,9 @@ impl Compiler for CargoCompiler {
                 path: wasm_path.clone(), 
                 reason: format!("Failed to copy WASM file: {}", e) 
             })?;
+        preinitialize(&output_wasm_path, options)?;
+        post_optimize(&output_wasm_path, options)?;
+        embed_provenance(&output_wasm_path, options)?;
         
         Ok(output_wasm_path)
     }
@@ -301,4 +387,6 @@ impl Compiler for CargoCompiler {
 }
 
 pub mod cargo;
+pub mod optimize;
+pub mod preinit;
 pub mod wasi;
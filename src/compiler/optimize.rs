@@ -0,0 +1,262 @@
+//! Shrinking compiled modules before they ship
+//!
+//! With [`CompilerOptions::post_optimize`] set, the compilers pass the module
+//! they built through [`optimize`]: `wasm-opt` from Binaryen runs at the
+//! configured [`WasmOptLevel`] when it is installed, and custom sections the
+//! runtime doesn't read are stripped. Provenance and `wasm-bindgen` sections
+//! are always kept, since loading the module depends on them. The
+//! [`SizeReport`] says how much smaller the module became.
+//!
+//! [`CompilerOptions::post_optimize`]: super::CompilerOptions::post_optimize
+
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::error::{Error, Result};
+use crate::runtime::abi::{custom_sections, WASM_BINDGEN_CUSTOM_SECTION};
+use crate::utils::provenance::PROVENANCE_SECTION;
+
+/// Custom sections never stripped
+const REQUIRED_SECTIONS: [&str; 2] = [PROVENANCE_SECTION, WASM_BINDGEN_CUSTOM_SECTION];
+
+/// Optimization level passed to `wasm-opt`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasmOptLevel {
+    /// `-O1`
+    O1,
+    
+    /// `-O2`
+    O2,
+    
+    /// `-O3`
+    O3,
+    
+    /// `-O4`
+    O4,
+    
+    /// `-Os`, optimizing for size
+    Os,
+    
+    /// `-Oz`, optimizing aggressively for size
+    Oz,
+}
+
+impl WasmOptLevel {
+    /// The `wasm-opt` flag for the level
+    pub fn flag(&self) -> &'static str {
+        match self {
+            WasmOptLevel::O1 => "-O1",
+            WasmOptLevel::O2 => "-O2",
+            WasmOptLevel::O3 => "-O3",
+            WasmOptLevel::O4 => "-O4",
+            WasmOptLevel::Os => "-Os",
+            WasmOptLevel::Oz => "-Oz",
+        }
+    }
+}
+
+/// How compiled modules are shrunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostOptimize {
+    /// Level `wasm-opt` runs at; `None` skips it
+    pub level: Option<WasmOptLevel>,
+    
+    /// The `wasm-opt` executable
+    pub wasm_opt: PathBuf,
+    
+    /// Fail instead of skipping `wasm-opt` when it isn't installed
+    pub require_wasm_opt: bool,
+    
+    /// Remove custom sections such as debug info and producers
+    pub strip_custom_sections: bool,
+    
+    /// Custom sections kept when stripping, e.g. `name` for readable backtraces
+    pub keep_sections: Vec<String>,
+}
+
+impl Default for PostOptimize {
+    fn default() -> Self {
+        Self {
+            level: Some(WasmOptLevel::Oz),
+            wasm_opt: PathBuf::from("wasm-opt"),
+            require_wasm_opt: false,
+            strip_custom_sections: true,
+            keep_sections: Vec::new(),
+        }
+    }
+}
+
+impl PostOptimize {
+    /// Run `wasm-opt -Oz` if installed and strip custom sections
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Run `wasm-opt` at `level`
+    pub fn level(mut self, level: WasmOptLevel) -> Self {
+        self.level = Some(level);
+        self
+    }
+    
+    /// Don't run `wasm-opt`, only the internal passes
+    pub fn without_wasm_opt(mut self) -> Self {
+        self.level = None;
+        self
+    }
+    
+    /// Run `wasm-opt` from `path`
+    pub fn wasm_opt(mut self, path: impl Into<PathBuf>) -> Self {
+        self.wasm_opt = path.into();
+        self
+    }
+    
+    /// Fail when `wasm-opt` isn't installed
+    pub fn require_wasm_opt(mut self, require: bool) -> Self {
+        self.require_wasm_opt = require;
+        self
+    }
+    
+    /// Whether to remove custom sections
+    pub fn strip_custom_sections(mut self, strip: bool) -> Self {
+        self.strip_custom_sections = strip;
+        self
+    }
+    
+    /// Keep the custom section `name` when stripping
+    pub fn keep_section(mut self, name: impl Into<String>) -> Self {
+        self.keep_sections.push(name.into());
+        self
+    }
+}
+
+/// How much a module shrank
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeReport {
+    /// Size before optimizing, in bytes
+    pub original_bytes: usize,
+    
+    /// Size after optimizing, in bytes
+    pub optimized_bytes: usize,
+    
+    /// Whether `wasm-opt` ran
+    pub wasm_opt: bool,
+    
+    /// Names of the custom sections removed, in module order
+    pub stripped_sections: Vec<String>,
+}
+
+impl SizeReport {
+    /// Bytes saved
+    pub fn saved_bytes(&self) -> usize {
+        self.original_bytes.saturating_sub(self.optimized_bytes)
+    }
+    
+    /// Share of the original size saved, from 0 to 1
+    pub fn saved_ratio(&self) -> f64 {
+        if self.original_bytes == 0 {
+            return 0.0;
+        }
+        self.saved_bytes() as f64 / self.original_bytes as f64
+    }
+}
+
+impl fmt::Display for SizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {} bytes ({:.1}% smaller)",
+            self.original_bytes,
+            self.optimized_bytes,
+            self.saved_ratio() * 100.0,
+        )
+    }
+}
+
+/// Shrink a binary module as `options` say
+pub fn optimize(wasm_bytes: &[u8], options: &PostOptimize) -> Result<(Vec<u8>, SizeReport)> {
+    if !wasm_bytes.starts_with(b"\0asm") || wasm_bytes.len() < 8 {
+        return Err(Error::InvalidInput {
+            field: "wasm_bytes".to_string(),
+            reason: "only binary modules can be optimized".to_string(),
+            suggestion: Some("Convert text-format modules to binary first".to_string()),
+        });
+    }
+    
+    let mut optimized = wasm_bytes.to_vec();
+    let mut ran_wasm_opt = false;
+    if let Some(level) = options.level
+        && let Some(output) = run_wasm_opt(&optimized, level, options)?
+    {
+        optimized = output;
+        ran_wasm_opt = true;
+    }
+    
+    let mut stripped_sections = Vec::new();
+    if options.strip_custom_sections {
+        let (output, stripped) = strip_custom_sections(&optimized, &options.keep_sections);
+        optimized = output;
+        stripped_sections = stripped;
+    }
+    
+    let report = SizeReport {
+        original_bytes: wasm_bytes.len(),
+        optimized_bytes: optimized.len(),
+        wasm_opt: ran_wasm_opt,
+        stripped_sections,
+    };
+    Ok((optimized, report))
+}
+
+/// Remove the custom sections not named in `keep`, returning the module and the names removed
+///
+/// Provenance and `wasm-bindgen` sections are always kept.
+pub fn strip_custom_sections(wasm_bytes: &[u8], keep: &[String]) -> (Vec<u8>, Vec<String>) {
+    let mut stripped = Vec::with_capacity(wasm_bytes.len());
+    let mut names = Vec::new();
+    let mut copied = 0;
+    for section in custom_sections(wasm_bytes) {
+        if REQUIRED_SECTIONS.contains(&section.name.as_str()) || keep.contains(&section.name) {
+            continue;
+        }
+        stripped.extend_from_slice(&wasm_bytes[copied..section.range.start]);
+        copied = section.range.end;
+        names.push(section.name);
+    }
+    stripped.extend_from_slice(&wasm_bytes[copied..]);
+    (stripped, names)
+}
+
+/// Run `wasm-opt` over a module, or return `None` if it isn't installed and not required
+fn run_wasm_opt(wasm_bytes: &[u8], level: WasmOptLevel, options: &PostOptimize) -> Result<Option<Vec<u8>>> {
+    let dir = tempfile::tempdir()?;
+    let input = dir.path().join("input.wasm");
+    let output = dir.path().join("output.wasm");
+    std::fs::write(&input, wasm_bytes)?;
+    
+    let result = Command::new(&options.wasm_opt)
+        .arg(level.flag())
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .output();
+    let result = match result {
+        Ok(result) => result,
+        Err(e) if e.kind() == ErrorKind::NotFound && !options.require_wasm_opt => {
+            log::debug!("{} is not installed; only stripping custom sections", options.wasm_opt.display());
+            return Ok(None);
+        }
+        Err(e) => {
+            return Err(Error::Compilation {
+                message: format!("Failed to run {}: {}", options.wasm_opt.display(), e),
+            });
+        }
+    };
+    if !result.status.success() {
+        return Err(Error::Compilation {
+            message: format!("wasm-opt failed: {}", String::from_utf8_lossy(&result.stderr)),
+        });
+    }
+    Ok(Some(std::fs::read(&output)?))
+}
@@ -0,0 +1,670 @@
+//! Preinitializing modules by snapshotting them after initialization
+//!
+//! Plugins with heavy static initialization, such as parsing embedded tables
+//! or building caches, repeat that work in every instance. [`preinitialize`]
+//! does it once, Wizer-style: it instantiates the module in a scratch sandbox,
+//! calls its initialization export ([`DEFAULT_INIT_FUNCTION`] by default), and
+//! writes a new module whose data segments and mutable globals hold the
+//! resulting state. Instances of the new module start where initialization
+//! left off, without running it.
+//!
+//! The start function and the initialization export are removed, since their
+//! effects are part of the snapshot. Only the module's own memory and globals
+//! are captured: modules importing their memory are refused, and tables,
+//! imported globals and host state such as open files start afresh in each
+//! instance. With [`CompilerOptions::preinitialize`] set, the compilers
+//! preinitialize the modules they build.
+//!
+//! [`CompilerOptions::preinitialize`]: super::CompilerOptions::preinitialize
+
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::runtime::HostValue;
+use crate::{InstanceConfig, WasmSandbox};
+
+/// Export Wizer-aware toolchains give the initialization function
+pub const DEFAULT_INIT_FUNCTION: &str = "wizer.initialize";
+
+/// Prefix of the exports added to read the module's globals during preinitialization
+const GLOBAL_EXPORT_PREFIX: &str = "__preinit_global_";
+
+/// Zero runs shorter than this stay inside a data segment rather than splitting it
+const MIN_SEGMENT_GAP: usize = 32;
+
+const SECTION_CUSTOM: u8 = 0;
+const SECTION_IMPORT: u8 = 2;
+const SECTION_MEMORY: u8 = 5;
+const SECTION_GLOBAL: u8 = 6;
+const SECTION_EXPORT: u8 = 7;
+const SECTION_START: u8 = 8;
+const SECTION_DATA_COUNT: u8 = 12;
+const SECTION_DATA: u8 = 11;
+
+const EXPORT_GLOBAL: u8 = 3;
+const EXPORT_MEMORY: u8 = 2;
+
+/// How a module is preinitialized
+#[derive(Debug, Clone)]
+pub struct Preinit {
+    /// Export called to initialize the module
+    pub init_function: String,
+    
+    /// Keep the initialization export in the preinitialized module
+    pub keep_init_export: bool,
+    
+    /// Capabilities and limits of the instance initialization runs in
+    pub instance_config: InstanceConfig,
+}
+
+impl Default for Preinit {
+    fn default() -> Self {
+        Self {
+            init_function: DEFAULT_INIT_FUNCTION.to_string(),
+            keep_init_export: false,
+            instance_config: InstanceConfig::default(),
+        }
+    }
+}
+
+impl Preinit {
+    /// Call [`DEFAULT_INIT_FUNCTION`] with the default instance configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Call `name` to initialize the module
+    pub fn init_function(mut self, name: &str) -> Self {
+        self.init_function = name.to_string();
+        self
+    }
+    
+    /// Keep the initialization export, e.g. for guests that check whether they were initialized
+    pub fn keep_init_export(mut self) -> Self {
+        self.keep_init_export = true;
+        self
+    }
+    
+    /// Run initialization with `config`'s capabilities and limits
+    pub fn instance_config(mut self, config: InstanceConfig) -> Self {
+        self.instance_config = config;
+        self
+    }
+}
+
+/// What preinitialization captured
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreinitReport {
+    /// Pages of memory the preinitialized module starts with
+    pub memory_pages: u64,
+    
+    /// Active data segments holding the snapshot of memory
+    pub data_segments: usize,
+    
+    /// Bytes of memory in those segments
+    pub data_bytes: usize,
+    
+    /// Mutable globals given their values after initialization
+    pub globals: usize,
+    
+    /// Whether a start function was removed
+    pub removed_start: bool,
+}
+
+impl fmt::Display for PreinitReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} pages snapshotted into {} data segments of {} bytes, {} globals",
+            self.memory_pages, self.data_segments, self.data_bytes, self.globals,
+        )
+    }
+}
+
+/// Run a module's initialization and return a module starting from its result
+///
+/// Accepts binary or text modules and returns a binary module.
+pub fn preinitialize(wasm_bytes: &[u8], options: &Preinit) -> Result<(Vec<u8>, PreinitReport)> {
+    let wasm_bytes = wat::parse_bytes(wasm_bytes).map_err(|e| invalid(&format!("not a WebAssembly module: {}", e)))?;
+    let module = ParsedModule::parse(&wasm_bytes)?;
+    if !module.exports.iter().any(|export| export.name == options.init_function) {
+        return Err(Error::NotFound {
+            resource_type: "initialization function".to_string(),
+            identifier: options.init_function.clone(),
+        });
+    }
+    
+    // Export every mutable global and the memory, so they can be read after initialization
+    let mut exports = module.exports.clone();
+    let snapshotted: Vec<usize> = module.globals.iter().enumerate()
+        .filter(|(_, global)| global.mutable && global.numeric)
+        .map(|(index, _)| index)
+        .collect();
+    for index in &snapshotted {
+        exports.push(Export {
+            name: format!("{}{}", GLOBAL_EXPORT_PREFIX, index),
+            kind: EXPORT_GLOBAL,
+            index: (module.imported_globals + index) as u32,
+        });
+    }
+    if module.memory.is_some() {
+        match exports.iter().find(|export| export.name == "memory") {
+            Some(export) if export.kind != EXPORT_MEMORY || export.index != 0 => {
+                return Err(invalid("the module exports something other than its memory as `memory`"));
+            }
+            Some(_) => {}
+            None => exports.push(Export { name: "memory".to_string(), kind: EXPORT_MEMORY, index: 0 }),
+        }
+    }
+    let instrumented = module.rebuild(&[(SECTION_EXPORT, Some(encode_exports(&exports)))]);
+    
+    let (memory, globals) = run_initialization(&instrumented, options)?;
+    
+    // Write the snapshot into the original module
+    let mut replacements = Vec::new();
+    let mut report = PreinitReport::default();
+    if let Some(limits) = &module.memory {
+        let pages = (memory.len() / crate::runtime::WASM_PAGE_SIZE) as u64;
+        report.memory_pages = pages.max(limits.min);
+        let mut section = Vec::new();
+        write_u32(&mut section, 1);
+        section.push(limits.flags);
+        write_u64(&mut section, report.memory_pages);
+        if let Some(max) = limits.max {
+            write_u64(&mut section, max);
+        }
+        replacements.push((SECTION_MEMORY, Some(section)));
+    }
+    
+    if !module.globals.is_empty() {
+        let mut section = Vec::new();
+        write_u32(&mut section, module.globals.len() as u32);
+        for (index, global) in module.globals.iter().enumerate() {
+            section.extend_from_slice(&global.ty);
+            section.push(global.mutable as u8);
+            let value = globals.iter()
+                .find(|(name, _)| name.strip_prefix(GLOBAL_EXPORT_PREFIX) == Some(&index.to_string()))
+                .map(|(_, value)| value);
+            match value {
+                Some(value) => {
+                    encode_const(&mut section, value)?;
+                    report.globals += 1;
+                }
+                None => section.extend_from_slice(&global.init),
+            }
+        }
+        replacements.push((SECTION_GLOBAL, Some(section)));
+    }
+    
+    let exports: Vec<Export> = module.exports.iter()
+        .filter(|export| options.keep_init_export || export.name != options.init_function)
+        .cloned()
+        .collect();
+    replacements.push((SECTION_EXPORT, Some(encode_exports(&exports))));
+    report.removed_start = module.has_start;
+    replacements.push((SECTION_START, None));
+    
+    // Passive segments keep their indices; active ones were applied and are in the snapshot
+    let keep_indices = module.data.iter().any(|segment| segment.passive);
+    let mut segments: Vec<Vec<u8>> = if keep_indices {
+        module.data.iter()
+            .map(|segment| if segment.passive { segment.raw.clone() } else { vec![1, 0] })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    for (offset, bytes) in nonzero_runs(&memory) {
+        let mut segment = vec![0, 0x41];
+        write_i64(&mut segment, offset as u32 as i32 as i64);
+        segment.push(0x0b);
+        write_u32(&mut segment, bytes.len() as u32);
+        segment.extend_from_slice(bytes);
+        segments.push(segment);
+        report.data_segments += 1;
+        report.data_bytes += bytes.len();
+    }
+    let mut data = Vec::new();
+    write_u32(&mut data, segments.len() as u32);
+    for segment in &segments {
+        data.extend_from_slice(segment);
+    }
+    replacements.push((SECTION_DATA, (!segments.is_empty()).then_some(data)));
+    if module.has_data_count {
+        let mut count = Vec::new();
+        write_u32(&mut count, segments.len() as u32);
+        replacements.push((SECTION_DATA_COUNT, Some(count)));
+    }
+    
+    Ok((module.rebuild(&replacements), report))
+}
+
+/// Memory of an initialized instance and its exported mutable globals
+type Snapshot = (Vec<u8>, Vec<(String, HostValue)>);
+
+/// Instantiate `wasm_bytes`, call its initialization, and read its memory and exported globals
+fn run_initialization(wasm_bytes: &[u8], options: &Preinit) -> Result<Snapshot> {
+    let mut sandbox = WasmSandbox::new()?;
+    let module_id = sandbox.load_module(wasm_bytes)?;
+    let instance_id = sandbox.create_instance(module_id, Some(options.instance_config.clone()))?;
+    let instance = &sandbox.get_instance(instance_id)
+        .ok_or_else(|| Error::NotFound { resource_type: "instance".to_string(), identifier: instance_id.to_string() })?
+        .instance;
+    instance.call_values(&options.init_function, &[]).map_err(|e| Error::FunctionCall {
+        function_name: options.init_function.clone(),
+        reason: format!("Initialization failed: {}", e),
+    })?;
+    Ok((instance.read_memory()?, instance.mutable_globals()?))
+}
+
+/// Runs of `memory` worth a data segment each, by offset
+fn nonzero_runs(memory: &[u8]) -> Vec<(usize, &[u8])> {
+    let mut runs = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = memory[offset..].iter().position(|byte| *byte != 0).map(|n| offset + n) {
+        // Extend the run until a gap of zeros long enough to split on
+        let mut end = start;
+        let mut zeros = 0;
+        for (n, byte) in memory[start..].iter().enumerate() {
+            if *byte == 0 {
+                zeros += 1;
+                if zeros == MIN_SEGMENT_GAP {
+                    break;
+                }
+            } else {
+                zeros = 0;
+                end = start + n + 1;
+            }
+        }
+        runs.push((start, &memory[start..end]));
+        offset = end;
+    }
+    runs
+}
+
+fn encode_const(bytes: &mut Vec<u8>, value: &HostValue) -> Result<()> {
+    match value {
+        HostValue::I32(value) => {
+            bytes.push(0x41);
+            write_i64(bytes, *value as i64);
+        }
+        HostValue::I64(value) => {
+            bytes.push(0x42);
+            write_i64(bytes, *value);
+        }
+        HostValue::F32(value) => {
+            bytes.push(0x43);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        HostValue::F64(value) => {
+            bytes.push(0x44);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        HostValue::V128(value) => {
+            bytes.push(0xfd);
+            write_u32(bytes, 12);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        HostValue::ExternRef(_) => return Err(invalid("reference globals can't be snapshotted")),
+    }
+    bytes.push(0x0b);
+    Ok(())
+}
+
+fn encode_exports(exports: &[Export]) -> Vec<u8> {
+    let mut section = Vec::new();
+    write_u32(&mut section, exports.len() as u32);
+    for export in exports {
+        write_u32(&mut section, export.name.len() as u32);
+        section.extend_from_slice(export.name.as_bytes());
+        section.push(export.kind);
+        write_u32(&mut section, export.index);
+    }
+    section
+}
+
+/// The parts of a binary module preinitialization reads and rewrites
+struct ParsedModule<'a> {
+    bytes: &'a [u8],
+    
+    /// Sections by ID, with the range of their payload
+    sections: Vec<(u8, std::ops::Range<usize>, std::ops::Range<usize>)>,
+    imported_globals: usize,
+    memory: Option<Limits>,
+    globals: Vec<Global>,
+    exports: Vec<Export>,
+    has_start: bool,
+    has_data_count: bool,
+    data: Vec<DataSegment>,
+}
+
+struct Limits {
+    flags: u8,
+    min: u64,
+    max: Option<u64>,
+}
+
+struct Global {
+    /// Encoded value type
+    ty: Vec<u8>,
+    mutable: bool,
+    
+    /// Whether the value is a number, which can be snapshotted
+    numeric: bool,
+    
+    /// Encoded initializer, including its `end`
+    init: Vec<u8>,
+}
+
+#[derive(Clone)]
+struct Export {
+    name: String,
+    kind: u8,
+    index: u32,
+}
+
+struct DataSegment {
+    passive: bool,
+    raw: Vec<u8>,
+}
+
+impl<'a> ParsedModule<'a> {
+    fn parse(bytes: &'a [u8]) -> Result<Self> {
+        if !bytes.starts_with(b"\0asm") || bytes.get(4..8) != Some(&[1, 0, 0, 0]) {
+            return Err(invalid("only core modules can be preinitialized"));
+        }
+        let mut module = Self {
+            bytes,
+            sections: Vec::new(),
+            imported_globals: 0,
+            memory: None,
+            globals: Vec::new(),
+            exports: Vec::new(),
+            has_start: false,
+            has_data_count: false,
+            data: Vec::new(),
+        };
+        
+        let mut reader = Reader { bytes, offset: 8 };
+        while reader.offset < bytes.len() {
+            let start = reader.offset;
+            let id = reader.byte()?;
+            let size = reader.u32()? as usize;
+            let payload = reader.offset..reader.offset + size;
+            if payload.end > bytes.len() {
+                return Err(invalid("a section runs past the end of the module"));
+            }
+            reader.offset = payload.end;
+            module.sections.push((id, start..payload.end, payload.clone()));
+            
+            let mut section = Reader { bytes: &bytes[..payload.end], offset: payload.start };
+            match id {
+                SECTION_IMPORT => module.parse_imports(&mut section)?,
+                SECTION_MEMORY => module.parse_memories(&mut section)?,
+                SECTION_GLOBAL => module.parse_globals(&mut section)?,
+                SECTION_EXPORT => module.parse_exports(&mut section)?,
+                SECTION_START => module.has_start = true,
+                SECTION_DATA_COUNT => module.has_data_count = true,
+                SECTION_DATA => module.parse_data(&mut section)?,
+                _ => {}
+            }
+        }
+        Ok(module)
+    }
+    
+    fn parse_imports(&mut self, reader: &mut Reader<'_>) -> Result<()> {
+        for _ in 0..reader.u32()? {
+            reader.name()?;
+            reader.name()?;
+            match reader.byte()? {
+                0 => {
+                    reader.u32()?;
+                }
+                1 => {
+                    reader.value_type()?;
+                    reader.limits()?;
+                }
+                2 => return Err(invalid("modules importing their memory can't be preinitialized")),
+                3 => {
+                    reader.value_type()?;
+                    reader.byte()?;
+                    self.imported_globals += 1;
+                }
+                4 => {
+                    reader.byte()?;
+                    reader.u32()?;
+                }
+                kind => return Err(invalid(&format!("unknown import kind {}", kind))),
+            }
+        }
+        Ok(())
+    }
+    
+    fn parse_memories(&mut self, reader: &mut Reader<'_>) -> Result<()> {
+        match reader.u32()? {
+            0 => Ok(()),
+            1 => {
+                let limits = reader.limits()?;
+                if limits.flags & !1 != 0 {
+                    return Err(invalid("only unshared 32-bit memories can be preinitialized"));
+                }
+                self.memory = Some(limits);
+                Ok(())
+            }
+            _ => Err(invalid("modules with several memories can't be preinitialized")),
+        }
+    }
+    
+    fn parse_globals(&mut self, reader: &mut Reader<'_>) -> Result<()> {
+        for _ in 0..reader.u32()? {
+            let start = reader.offset;
+            let numeric = reader.value_type()?;
+            let ty = self.bytes[start..reader.offset].to_vec();
+            let mutable = reader.byte()? == 1;
+            let start = reader.offset;
+            reader.const_expr()?;
+            self.globals.push(Global { ty, mutable, numeric, init: self.bytes[start..reader.offset].to_vec() });
+        }
+        Ok(())
+    }
+    
+    fn parse_exports(&mut self, reader: &mut Reader<'_>) -> Result<()> {
+        for _ in 0..reader.u32()? {
+            let name = reader.name()?;
+            let kind = reader.byte()?;
+            let index = reader.u32()?;
+            self.exports.push(Export { name, kind, index });
+        }
+        Ok(())
+    }
+    
+    fn parse_data(&mut self, reader: &mut Reader<'_>) -> Result<()> {
+        for _ in 0..reader.u32()? {
+            let start = reader.offset;
+            let passive = match reader.u32()? {
+                0 => {
+                    reader.const_expr()?;
+                    false
+                }
+                1 => true,
+                2 => {
+                    if reader.u32()? != 0 {
+                        return Err(invalid("modules with several memories can't be preinitialized"));
+                    }
+                    reader.const_expr()?;
+                    false
+                }
+                flags => return Err(invalid(&format!("unknown data segment flags {}", flags))),
+            };
+            let len = reader.u32()? as usize;
+            reader.take(len)?;
+            self.data.push(DataSegment { passive, raw: self.bytes[start..reader.offset].to_vec() });
+        }
+        Ok(())
+    }
+    
+    /// The module with the sections in `replacements` replaced, added or, for `None`, removed
+    fn rebuild(&self, replacements: &[(u8, Option<Vec<u8>>)]) -> Vec<u8> {
+        let mut bytes = self.bytes[..8].to_vec();
+        let mut pending: Vec<&(u8, Option<Vec<u8>>)> = replacements.iter()
+            .filter(|(id, payload)| payload.is_some() && !self.sections.iter().any(|(existing, _, _)| existing == id))
+            .collect();
+        let mut emit_pending = |bytes: &mut Vec<u8>, before: Option<u8>| {
+            pending.retain(|(id, payload)| {
+                if before.is_some_and(|before| section_order(*id) > section_order(before)) {
+                    return true;
+                }
+                write_section(bytes, *id, payload.as_deref().unwrap_or_default());
+                false
+            });
+        };
+        
+        for (id, range, _) in &self.sections {
+            if *id != SECTION_CUSTOM {
+                emit_pending(&mut bytes, Some(*id));
+            }
+            match replacements.iter().find(|(replaced, _)| replaced == id) {
+                Some((_, Some(payload))) => write_section(&mut bytes, *id, payload),
+                Some((_, None)) => {}
+                None => bytes.extend_from_slice(&self.bytes[range.clone()]),
+            }
+        }
+        emit_pending(&mut bytes, None);
+        bytes
+    }
+}
+
+/// Position of a section in a module; sections must appear in this order
+fn section_order(id: u8) -> u8 {
+    match id {
+        // Tags come between memories and globals
+        13 => 6,
+        6..=9 => id + 1,
+        // The data count comes before the code
+        12 => 11,
+        10 | 11 => id + 2,
+        _ => id,
+    }
+}
+
+fn write_section(bytes: &mut Vec<u8>, id: u8, payload: &[u8]) {
+    bytes.push(id);
+    write_u32(bytes, payload.len() as u32);
+    bytes.extend_from_slice(payload);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8> {
+        let byte = *self.bytes.get(self.offset).ok_or_else(|| invalid("the module ends unexpectedly"))?;
+        self.offset += 1;
+        Ok(byte)
+    }
+    
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        let bytes = self.bytes.get(self.offset..self.offset + len).ok_or_else(|| invalid("the module ends unexpectedly"))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+    
+    fn u64(&mut self) -> Result<u64> {
+        let mut result = 0u64;
+        for shift in (0..70).step_by(7) {
+            let byte = self.byte()?;
+            result |= ((byte & 0x7f) as u64).checked_shl(shift).unwrap_or(0);
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+        Err(invalid("an integer is too long"))
+    }
+    
+    fn u32(&mut self) -> Result<u32> {
+        self.u64().map(|value| value as u32)
+    }
+    
+    fn name(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+    
+    fn limits(&mut self) -> Result<Limits> {
+        let flags = self.byte()?;
+        let min = self.u64()?;
+        let max = if flags & 1 != 0 { Some(self.u64()?) } else { None };
+        Ok(Limits { flags, min, max })
+    }
+    
+    /// Skip a value type, returning whether it is a number
+    fn value_type(&mut self) -> Result<bool> {
+        match self.byte()? {
+            0x7b..=0x7f => Ok(true),
+            // References to a heap type carry it
+            0x63 | 0x64 => self.u64().map(|_| false),
+            _ => Ok(false),
+        }
+    }
+    
+    /// Skip a constant expression, including its `end`
+    fn const_expr(&mut self) -> Result<()> {
+        loop {
+            match self.byte()? {
+                0x0b => return Ok(()),
+                0x41 | 0x42 | 0x23 | 0xd2 | 0xd0 => {
+                    self.u64()?;
+                }
+                0x43 => {
+                    self.take(4)?;
+                }
+                0x44 => {
+                    self.take(8)?;
+                }
+                0x6a | 0x6b | 0x6c | 0x7c | 0x7d | 0x7e => {}
+                0xfd if self.u32()? == 12 => {
+                    self.take(16)?;
+                }
+                opcode => return Err(invalid(&format!("unsupported instruction 0x{:02x} in a constant expression", opcode))),
+            }
+        }
+    }
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+    write_u64(bytes, value as u64);
+}
+
+fn write_u64(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn write_i64(bytes: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidInput {
+        field: "wasm_bytes".to_string(),
+        reason: reason.to_string(),
+        suggestion: None,
+    }
+}
@@ -4,8 +4,19 @@ use std::path::PathBuf;
 use std::time::Duration;
 
 use crate::error::{Result, SandboxError};
-use crate::security::Capabilities;
-use crate::{InstanceConfig, SandboxConfig};
+use crate::runtime::call_queue::CallQueueConfig;
+use crate::runtime::fuel_budget::FuelClass;
+use crate::runtime::recovery::RecoveryPolicy;
+use crate::security::{CallableFunctions, Capabilities};
+use crate::utils::scratch::ScratchConfig;
+use crate::utils::ingest::InboxConfig;
+use crate::runtime::growth::OomPrediction;
+use crate::runtime::checkpoint::CheckpointConfig;
+use crate::runtime::profiling::SamplingConfig;
+use crate::observability::journal::JournalRetention;
+use crate::observability::telemetry::TelemetryConfig;
+use crate::security::audit_store::AuditStoreConfig;
+use crate::{EnvironmentLayer, InstanceConfig, PluginSettings, SandboxConfig};
 
 /// Human-readable memory units
 pub trait MemoryUnit {
@@ -120,7 +131,7 @@ impl InstanceConfigBuilder {
     /// Set memory limit using human-readable units
     pub fn memory_limit<T: MemoryUnit>(mut self, amount: T) -> Self {
         self.config.resource_limits.memory.max_memory_pages = 
-            (amount.bytes() / 65536) as u32; // WASM page size is 64KB
+            amount.bytes() / 65536; // WASM page size is 64KB
         self
     }
 
@@ -190,6 +201,19 @@ impl InstanceConfigBuilder {
         self
     }
 
+    /// Grow memory to this size at instantiation
+    pub fn pre_grow_memory<T: MemoryUnit>(mut self, amount: T) -> Self {
+        self.config.resource_limits.memory.pre_grow_pages =
+            Some(amount.bytes() / 65536); // WASM page size is 64KB
+        self
+    }
+
+    /// Set the fuel cost schedule
+    pub fn fuel_schedule(mut self, schedule: crate::security::FuelSchedule) -> Self {
+        self.config.resource_limits.fuel_schedule = schedule;
+        self
+    }
+
     /// Set maximum file operations per second
     pub fn io_ops_limit(mut self, ops_per_sec: u32) -> Self {
         self.config.resource_limits.io.max_read_bytes_per_second = Some(ops_per_sec as u64);
@@ -216,6 +240,126 @@ impl InstanceConfigBuilder {
         self
     }
 
+    /// Use different capabilities while a specific export runs
+    pub fn function_policy(mut self, function_name: &str, capabilities: Capabilities) -> Self {
+        self.config.function_policies.insert(function_name.to_string(), capabilities);
+        self
+    }
+
+    /// Mark an export as pure so repeated calls are served from the result cache
+    pub fn pure_function(mut self, function_name: &str) -> Self {
+        self.config.pure_functions.insert(function_name.to_string());
+        self
+    }
+
+    /// Attribute the instance's events to a tenant, for its telemetry overrides
+    pub fn tenant(mut self, tenant: &str) -> Self {
+        self.config.tenant = Some(tenant.to_string());
+        self
+    }
+
+    /// Restrict which exports the host may call
+    pub fn callable_functions(mut self, callable: CallableFunctions) -> Self {
+        self.config.callable_functions = callable;
+        self
+    }
+
+    /// Recreate the instance and retry once when a call traps as if memory were corrupted
+    pub fn recover_from_corruption(mut self, policy: RecoveryPolicy) -> Self {
+        self.config.recovery = Some(policy);
+        self
+    }
+
+    /// Set the instance's share of the sandbox fuel budget relative to other instances
+    pub fn fuel_weight(mut self, weight: u32) -> Self {
+        self.config.fuel_weight = weight;
+        self
+    }
+
+    /// Set the instance's share of the sandbox I/O budget relative to other instances
+    pub fn io_weight(mut self, weight: u32) -> Self {
+        self.config.io_weight = weight;
+        self
+    }
+
+    /// Set how the instance's calls are topped up when they run low on fuel
+    pub fn fuel_class(mut self, class: FuelClass) -> Self {
+        self.config.fuel_class = class;
+        self
+    }
+
+    /// Give the instance a private scratch directory
+    pub fn scratch(mut self, scratch: ScratchConfig) -> Self {
+        self.config.scratch = Some(scratch);
+        self
+    }
+
+    /// Give the instance an inbox the host stages input files into
+    pub fn inbox(mut self, inbox: InboxConfig) -> Self {
+        self.config.inbox = Some(inbox);
+        self
+    }
+
+    /// Warn when a call's memory growth is projected to exceed the memory limit
+    pub fn oom_prediction(mut self, prediction: OomPrediction) -> Self {
+        self.config.oom_prediction = Some(prediction);
+        self
+    }
+
+    /// Sample the guest's stack during calls
+    pub fn profiling(mut self, sampling: SamplingConfig) -> Self {
+        self.config.profiling = Some(sampling);
+        self
+    }
+
+    /// Let the guest write checkpoints it can be restored from
+    pub fn checkpoints(mut self, checkpoints: CheckpointConfig) -> Self {
+        self.config.checkpoints = Some(checkpoints);
+        self
+    }
+
+    /// Queue concurrent calls by priority instead of contending for the instance
+    pub fn call_queue(mut self, queue: CallQueueConfig) -> Self {
+        self.config.call_queue = Some(queue);
+        self
+    }
+
+    /// Compose fixture files, variables, and stub sockets into the instance
+    pub fn environment_layer(mut self, layer: EnvironmentLayer) -> Self {
+        self.config.environment_layer = Some(layer);
+        self
+    }
+
+    /// Attach a settings document the guest can read
+    pub fn settings(mut self, settings: PluginSettings) -> Self {
+        self.config.settings = Some(settings);
+        self
+    }
+
+    /// Read guest data ABI results larger than `bytes` out of guest memory in chunks
+    pub fn max_inline_result_bytes(mut self, bytes: usize) -> Self {
+        self.config.max_inline_result_bytes = Some(bytes);
+        self
+    }
+
+    /// Fail calls whose serialized parameters are larger than `bytes`
+    pub fn max_param_bytes(mut self, bytes: usize) -> Self {
+        self.config.max_param_bytes = Some(bytes);
+        self
+    }
+
+    /// Limit the serialized parameters of one export, in place of `max_param_bytes`
+    pub fn function_param_limit(mut self, function_name: &str, bytes: usize) -> Self {
+        self.config.function_param_limits.insert(function_name.to_string(), bytes);
+        self
+    }
+
+    /// Write guest data ABI parameters larger than `bytes` into guest memory in chunks
+    pub fn max_inline_param_bytes(mut self, bytes: usize) -> Self {
+        self.config.max_inline_param_bytes = Some(bytes);
+        self
+    }
+
     /// Set maximum number of threads
     pub fn max_threads(mut self, max: usize) -> Self {
         self.advanced_caps.max_threads = max;
@@ -264,13 +408,13 @@ impl InstanceConfigBuilder {
         }
 
         for path in &self.advanced_caps.filesystem.write_paths {
-            if let Some(parent) = path.parent() {
-                if !parent.exists() {
-                    return Err(SandboxError::config_error(
-                        format!("Write path parent directory does not exist: {:?}", parent),
-                        Some("Ensure parent directories exist before configuration".to_string())
-                    ));
-                }
+            if let Some(parent) = path.parent()
+                && !parent.exists()
+            {
+                return Err(SandboxError::config_error(
+                    format!("Write path parent directory does not exist: {:?}", parent),
+                    Some("Ensure parent directories exist before configuration".to_string())
+                ));
             }
         }
 
@@ -373,6 +517,24 @@ impl SandboxConfigBuilder {
         self
     }
 
+    /// Set how long entries stay in the sandbox's journal
+    pub fn journal_retention(mut self, retention: JournalRetention) -> Self {
+        self.config.journal_retention = retention;
+        self
+    }
+
+    /// Set the sampling and hashing applied to journaled events
+    pub fn telemetry(mut self, telemetry: TelemetryConfig) -> Self {
+        self.config.telemetry = telemetry;
+        self
+    }
+
+    /// Persist the sandbox's audit events to a database
+    pub fn audit_store(mut self, store: AuditStoreConfig) -> Self {
+        self.config.audit_store = Some(store);
+        self
+    }
+
     /// Set runtime to use Wasmtime
     /// 
     /// Note: Runtime selection is determined at compile time by feature flags.
@@ -0,0 +1,346 @@
+//! Guest ABI conformance checks for plugin authors
+//!
+//! [`ConformanceSuite`] instantiates a module and checks that it follows the
+//! sandbox data ABI: it exports its memory and a working [`GUEST_ALLOC_EXPORT`],
+//! its echo export returns JSON unchanged, its error export fails cleanly and
+//! leaves the instance usable, and large payloads survive the trip. Each check
+//! runs in a fresh instance. The [`ConformanceReport`] prints like `cargo test`
+//! output, so the suite can run from a plugin's own tests:
+//!
+//! ```rust,no_run
+//! use wasm_sandbox::conformance::ConformanceSuite;
+//!
+//! # fn main() -> wasm_sandbox::Result<()> {
+//! let wasm_bytes = std::fs::read("plugin.wasm")?;
+//! let report = ConformanceSuite::new().run(&wasm_bytes)?;
+//! println!("{}", report);
+//! report.assert_passed();
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The round-trip and large-payload checks need an [`ECHO_EXPORT`] that
+//! returns its input, and the error check an [`ERROR_EXPORT`] that always
+//! fails; checks whose export is missing are ignored rather than failed.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::error::Result;
+use crate::runtime::wasmtime::WasmtimeRuntime;
+use crate::runtime::{HostValue, RuntimeConfig, WasmInstance, WasmModule, WasmRuntime, GUEST_ALLOC_EXPORT};
+use crate::security::{Capabilities, ResourceLimits};
+
+/// Default export the serialization checks call, which must return its input
+pub const ECHO_EXPORT: &str = "conformance_echo";
+
+/// Default export the error check calls, which must always fail
+pub const ERROR_EXPORT: &str = "conformance_fail";
+
+/// Default size of the large-payload check's input
+pub const DEFAULT_LARGE_PAYLOAD_BYTES: usize = 1024 * 1024;
+
+/// Outcome of one conformance check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    /// The module conforms
+    Passed,
+    
+    /// The module doesn't conform, and why
+    Failed(String),
+    
+    /// The check couldn't run, and why
+    Ignored(String),
+}
+
+/// A conformance check and its outcome
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    /// Name of the check, e.g. `abi::allocator`
+    pub name: String,
+    
+    /// What the check found
+    pub outcome: CheckOutcome,
+}
+
+/// Outcomes of a conformance run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConformanceReport {
+    /// Checks in the order they ran
+    pub checks: Vec<CheckResult>,
+    
+    /// Time the run took
+    pub elapsed: Duration,
+}
+
+impl ConformanceReport {
+    /// Whether no check failed
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+    
+    /// Checks that failed
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|check| matches!(check.outcome, CheckOutcome::Failed(_)))
+    }
+    
+    /// Outcome of the check called `name`
+    pub fn outcome(&self, name: &str) -> Option<&CheckOutcome> {
+        self.checks.iter().find(|check| check.name == name).map(|check| &check.outcome)
+    }
+    
+    /// Panic with the report unless every check passed or was ignored
+    #[track_caller]
+    pub fn assert_passed(&self) {
+        if !self.passed() {
+            panic!("module does not conform to the sandbox ABI\n\n{}", self);
+        }
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "running {} checks", self.checks.len())?;
+        for check in &self.checks {
+            match &check.outcome {
+                CheckOutcome::Passed => writeln!(f, "check {} ... ok", check.name)?,
+                CheckOutcome::Failed(_) => writeln!(f, "check {} ... FAILED", check.name)?,
+                CheckOutcome::Ignored(reason) => writeln!(f, "check {} ... ignored, {}", check.name, reason)?,
+            }
+        }
+        
+        if !self.passed() {
+            writeln!(f, "\nfailures:")?;
+            for check in self.failures() {
+                if let CheckOutcome::Failed(reason) = &check.outcome {
+                    writeln!(f, "    {}: {}", check.name, reason)?;
+                }
+            }
+        }
+        
+        let count = |wanted: fn(&CheckOutcome) -> bool| self.checks.iter().filter(|check| wanted(&check.outcome)).count();
+        write!(
+            f,
+            "\ncheck result: {}. {} passed; {} failed; {} ignored; finished in {:.2}s",
+            if self.passed() { "ok" } else { "FAILED" },
+            count(|outcome| *outcome == CheckOutcome::Passed),
+            count(|outcome| matches!(outcome, CheckOutcome::Failed(_))),
+            count(|outcome| matches!(outcome, CheckOutcome::Ignored(_))),
+            self.elapsed.as_secs_f64(),
+        )
+    }
+}
+
+/// A check run against a fresh instance
+type Check = fn(&ConformanceSuite, &dyn WasmInstance) -> CheckOutcome;
+
+/// Checks a module against the sandbox data ABI
+pub struct ConformanceSuite {
+    echo_export: String,
+    error_export: String,
+    large_payload_bytes: usize,
+    capabilities: Capabilities,
+    resource_limits: ResourceLimits,
+    runtime_config: RuntimeConfig,
+}
+
+impl ConformanceSuite {
+    /// Create a suite with the default exports, minimal capabilities and default limits
+    pub fn new() -> Self {
+        Self {
+            echo_export: ECHO_EXPORT.to_string(),
+            error_export: ERROR_EXPORT.to_string(),
+            large_payload_bytes: DEFAULT_LARGE_PAYLOAD_BYTES,
+            capabilities: Capabilities::minimal(),
+            resource_limits: ResourceLimits::default(),
+            runtime_config: RuntimeConfig::default(),
+        }
+    }
+    
+    /// Set the export that returns its input
+    pub fn echo_export(mut self, name: &str) -> Self {
+        self.echo_export = name.to_string();
+        self
+    }
+    
+    /// Set the export that always fails
+    pub fn error_export(mut self, name: &str) -> Self {
+        self.error_export = name.to_string();
+        self
+    }
+    
+    /// Set the size of the large-payload check's input
+    pub fn large_payload_bytes(mut self, bytes: usize) -> Self {
+        self.large_payload_bytes = bytes;
+        self
+    }
+    
+    /// Set the capabilities the module runs with
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+    
+    /// Set the resource limits the module runs with
+    pub fn resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.resource_limits = limits;
+        self
+    }
+    
+    /// Set the runtime configuration
+    pub fn runtime_config(mut self, config: RuntimeConfig) -> Self {
+        self.runtime_config = config;
+        self
+    }
+    
+    /// Run every check against a module
+    ///
+    /// Fails only if the module can't be compiled or instantiated; checks the
+    /// module fails are recorded in the report.
+    pub fn run(&self, wasm_bytes: &[u8]) -> Result<ConformanceReport> {
+        let started = Instant::now();
+        let runtime = WasmtimeRuntime::new(&self.runtime_config)?;
+        let module = runtime.load_module(wasm_bytes)?;
+        let exports = module.exports();
+        let exported = |name: &str| exports.iter().any(|export| export == name);
+        
+        // Each check needs an export; the ABI's own exports are required, the test exports optional
+        let checks: [(&str, Check, &str, bool); 5] = [
+            ("abi::memory_export", Self::check_memory_export, "memory", true),
+            ("abi::allocator", Self::check_allocator, GUEST_ALLOC_EXPORT, true),
+            ("serialization::round_trip", Self::check_round_trip, &self.echo_export, false),
+            ("errors::propagation", Self::check_error_propagation, &self.error_export, false),
+            ("payload::large", Self::check_large_payload, &self.echo_export, false),
+        ];
+        let mut results = Vec::with_capacity(checks.len());
+        for (name, check, export, required) in checks {
+            let outcome = match (exported(export), required) {
+                (false, true) => CheckOutcome::Failed(format!("module does not export `{}`", export)),
+                (false, false) => CheckOutcome::Ignored(format!("module does not export `{}`", export)),
+                (true, _) => {
+                    let instance = self.instantiate(&runtime, module.as_ref())?;
+                    check(self, instance.as_ref())
+                }
+            };
+            results.push(CheckResult { name: name.to_string(), outcome });
+        }
+        
+        Ok(ConformanceReport {
+            checks: results,
+            elapsed: started.elapsed(),
+        })
+    }
+    
+    fn instantiate(&self, runtime: &WasmtimeRuntime, module: &dyn WasmModule) -> Result<Box<dyn WasmInstance>> {
+        runtime.create_instance(module, self.resource_limits.clone(), self.capabilities.clone())
+    }
+    
+    /// The memory export must be a memory the host can write to
+    fn check_memory_export(&self, instance: &dyn WasmInstance) -> CheckOutcome {
+        match instance.memory_size() {
+            0 => CheckOutcome::Failed("`memory` is not a memory or has no pages".to_string()),
+            _ => CheckOutcome::Passed,
+        }
+    }
+    
+    /// Two allocations must be writable, in bounds and disjoint
+    fn check_allocator(&self, instance: &dyn WasmInstance) -> CheckOutcome {
+        const LEN: usize = 64;
+        let mut allocations = Vec::new();
+        for _ in 0..2 {
+            let ptr = match instance.call_values(GUEST_ALLOC_EXPORT, &[HostValue::I32(LEN as i32)]) {
+                Ok(results) => match results.as_slice() {
+                    [HostValue::I32(ptr)] => *ptr as u32 as usize,
+                    _ => return CheckOutcome::Failed(format!("`{}` must have type (i32) -> i32", GUEST_ALLOC_EXPORT)),
+                },
+                Err(e) => return CheckOutcome::Failed(format!("`{}({})` failed: {}", GUEST_ALLOC_EXPORT, LEN, e)),
+            };
+            if ptr == 0 || ptr + LEN > instance.memory_size() {
+                return CheckOutcome::Failed(format!("`{}({})` returned {}, outside the memory", GUEST_ALLOC_EXPORT, LEN, ptr));
+            }
+            if let Err(e) = instance.write_memory_at(ptr, &[0xa5; LEN]) {
+                return CheckOutcome::Failed(format!("allocation at {} is not writable: {}", ptr, e));
+            }
+            allocations.push(ptr);
+        }
+        match allocations.as_slice() {
+            [first, second] if first.abs_diff(*second) < LEN => CheckOutcome::Failed(format!(
+                "allocations at {} and {} overlap",
+                first, second,
+            )),
+            _ => CheckOutcome::Passed,
+        }
+    }
+    
+    /// JSON values of every kind must come back unchanged
+    fn check_round_trip(&self, instance: &dyn WasmInstance) -> CheckOutcome {
+        let payloads = [
+            json!(null),
+            json!(true),
+            json!(42),
+            json!(-1.5),
+            json!(""),
+            json!("héllo, wörld ✓"),
+            json!([1, [2, [3, []]]]),
+            json!({"name": "plugin", "tags": ["a", "b"], "nested": {"empty": {}}}),
+        ];
+        for payload in payloads {
+            if let Err(reason) = self.echo(instance, &payload) {
+                return CheckOutcome::Failed(reason);
+            }
+        }
+        CheckOutcome::Passed
+    }
+    
+    /// The error export must fail, and the instance must keep working afterwards
+    fn check_error_propagation(&self, instance: &dyn WasmInstance) -> CheckOutcome {
+        match instance.call_raw(&self.error_export, b"{}") {
+            Ok(output) => CheckOutcome::Failed(format!(
+                "`{}` returned {} bytes instead of failing",
+                self.error_export,
+                output.len(),
+            )),
+            Err(_) => match instance.call_values(GUEST_ALLOC_EXPORT, &[HostValue::I32(8)]) {
+                Ok(_) => CheckOutcome::Passed,
+                Err(e) => CheckOutcome::Failed(format!("instance stopped working after `{}` failed: {}", self.error_export, e)),
+            },
+        }
+    }
+    
+    /// A payload of `large_payload_bytes` must come back unchanged
+    fn check_large_payload(&self, instance: &dyn WasmInstance) -> CheckOutcome {
+        let payload = Value::String("x".repeat(self.large_payload_bytes.saturating_sub(2)));
+        match self.echo(instance, &payload) {
+            Ok(()) => CheckOutcome::Passed,
+            Err(reason) => CheckOutcome::Failed(reason),
+        }
+    }
+    
+    fn echo(&self, instance: &dyn WasmInstance, payload: &Value) -> std::result::Result<(), String> {
+        let input = payload.to_string();
+        let output = instance.call_raw(&self.echo_export, input.as_bytes())
+            .map_err(|e| format!("`{}` failed on {} bytes of input: {}", self.echo_export, input.len(), e))?;
+        match serde_json::from_slice::<Value>(&output) {
+            Ok(echoed) if echoed == *payload => Ok(()),
+            Ok(_) => Err(format!("`{}` changed {}", self.echo_export, abbreviate(&input))),
+            Err(e) => Err(format!("`{}` returned invalid JSON for {}: {}", self.echo_export, abbreviate(&input), e)),
+        }
+    }
+}
+
+impl Default for ConformanceSuite {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A payload short enough to quote in a failure
+fn abbreviate(input: &str) -> String {
+    const MAX_CHARS: usize = 60;
+    match input.char_indices().nth(MAX_CHARS) {
+        Some((end, _)) => format!("{}... ({} bytes)", &input[..end], input.len()),
+        None => input.to_string(),
+    }
+}
@@ -0,0 +1,106 @@
+//! Instances that live for a single request
+//!
+//! [`WasmSandbox::with_ephemeral_instance`] creates an instance, hands an
+//! [`EphemeralInstance`] to an async closure, and removes the instance when the
+//! closure returns, panics, or its future is dropped, so no state can leak from
+//! one request into the next. [`WasmSandbox::with_ephemeral_fork`] does the
+//! same with a fork of an initialised template instance, which skips the
+//! guest's own start-up work. [`EphemeralMetrics`] records what creating the
+//! instances costs.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::{InstanceId, WasmSandbox};
+
+/// An instance that is removed when the closure it was handed to finishes
+#[derive(Clone, Copy)]
+pub struct EphemeralInstance<'a> {
+    sandbox: &'a WasmSandbox,
+    instance_id: InstanceId,
+}
+
+impl<'a> EphemeralInstance<'a> {
+    pub(crate) fn new(sandbox: &'a WasmSandbox, instance_id: InstanceId) -> Self {
+        Self { sandbox, instance_id }
+    }
+    
+    /// ID of the instance
+    pub fn id(&self) -> InstanceId {
+        self.instance_id
+    }
+    
+    /// Sandbox the instance lives in
+    pub fn sandbox(&self) -> &'a WasmSandbox {
+        self.sandbox
+    }
+    
+    /// Call a function in the instance, as with [`WasmSandbox::call_function`]
+    pub async fn call_function<P, R>(&self, function_name: &str, params: P) -> Result<R>
+    where
+        P: Serialize + Sync + 'static,
+        R: for<'de> Deserialize<'de> + 'static,
+    {
+        self.sandbox.call_function(self.instance_id, function_name, params).await
+    }
+}
+
+/// Counters for request-scoped instances
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EphemeralMetrics {
+    /// Instances created
+    pub created: u64,
+    
+    /// Instances removed again
+    pub torn_down: u64,
+    
+    /// Closures whose future was dropped before finishing
+    pub cancelled: u64,
+    
+    /// Closures that panicked
+    pub panicked: u64,
+    
+    /// Time spent creating instances
+    pub creation_time: Duration,
+    
+    /// Longest time creating one instance took
+    pub max_creation_time: Duration,
+}
+
+impl EphemeralMetrics {
+    /// Average time creating an instance took
+    pub fn mean_creation_time(&self) -> Duration {
+        match self.created {
+            0 => Duration::ZERO,
+            created => self.creation_time.div_f64(created as f64),
+        }
+    }
+    
+    pub(crate) fn record_creation(&mut self, elapsed: Duration) {
+        self.created += 1;
+        self.creation_time += elapsed;
+        self.max_creation_time = self.max_creation_time.max(elapsed);
+    }
+}
+
+/// Removes an ephemeral instance when dropped, however its closure ended
+pub(crate) struct Teardown<'a> {
+    pub(crate) sandbox: &'a mut WasmSandbox,
+    pub(crate) instance_id: InstanceId,
+    pub(crate) finished: bool,
+}
+
+impl Drop for Teardown<'_> {
+    fn drop(&mut self) {
+        self.sandbox.remove_instance(self.instance_id);
+        let mut metrics = self.sandbox.ephemeral_metrics.lock().unwrap();
+        metrics.torn_down += 1;
+        if std::thread::panicking() {
+            metrics.panicked += 1;
+        } else if !self.finished {
+            metrics.cancelled += 1;
+        }
+    }
+}
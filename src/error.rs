@@ -14,6 +14,9 @@ pub enum ResourceKind {
     FileHandles,
     NetworkConnections,
     ExecutionTime,
+    Table,
+    DnsResolutions,
+    ParamBytes,
 }
 
 /// Security context providing details about attempted operations
@@ -100,6 +103,13 @@ pub enum SandboxError {
         reason: String,
     },
 
+    /// Guest result rejected by the function's result validator
+    #[error("Function '{function_name}' returned an invalid result: {}", .errors.join("; "))]
+    InvalidResult {
+        function_name: String,
+        errors: Vec<String>,
+    },
+
     /// File system error with path context
     #[error("Filesystem error: {operation} failed on '{path:?}' - {reason}")]
     Filesystem {
@@ -197,6 +207,22 @@ pub enum SandboxError {
     #[error("Unsupported operation: {message}")]
     UnsupportedOperation { message: String },
 
+    /// Module imports items the host does not provide
+    #[error("Module imports unknown host items: {report}")]
+    UnknownImports { report: crate::security::imports::ImportReport },
+
+    /// Module imports items the operator's import rules forbid
+    #[error("Module breaks the import rules: {}", .violations.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("; "))]
+    ForbiddenImports { violations: Vec<crate::security::imports::ImportViolation> },
+
+    /// Guest ABI or plugin API version is outside the supported range
+    #[error("Incompatible {export}: found {}, supported {supported}", .found.as_deref().unwrap_or("no version export"))]
+    IncompatibleVersion {
+        export: String,
+        found: Option<String>,
+        supported: String,
+    },
+
     // Wrapped errors from external sources
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -335,6 +361,12 @@ impl Clone for SandboxError {
                     reason: reason.clone(),
                 }
             }
+            SandboxError::InvalidResult { function_name, errors } => {
+                SandboxError::InvalidResult {
+                    function_name: function_name.clone(),
+                    errors: errors.clone(),
+                }
+            }
             SandboxError::Filesystem { operation, path, reason } => {
                 SandboxError::Filesystem {
                     operation: operation.clone(),
@@ -433,6 +465,23 @@ impl Clone for SandboxError {
                     message: message.clone(),
                 }
             }
+            SandboxError::UnknownImports { report } => {
+                SandboxError::UnknownImports {
+                    report: report.clone(),
+                }
+            }
+            SandboxError::ForbiddenImports { violations } => {
+                SandboxError::ForbiddenImports {
+                    violations: violations.clone(),
+                }
+            }
+            SandboxError::IncompatibleVersion { export, found, supported } => {
+                SandboxError::IncompatibleVersion {
+                    export: export.clone(),
+                    found: found.clone(),
+                    supported: supported.clone(),
+                }
+            }
             // For wrapped errors that don't implement Clone, we create a new error with the string representation
             SandboxError::Io(e) => SandboxError::Filesystem {
                 operation: "io".to_string(),
@@ -0,0 +1,340 @@
+//! C ABI for calling the sandbox from other languages
+//!
+//! With the `ffi` feature the crate exports the functions the generated Python
+//! package (see [`crate::bindings::python`]) loads through CFFI. Build the
+//! shared library with
+//! `cargo rustc --release --lib --features ffi --crate-type cdylib`.
+//!
+//! Ownership rules:
+//! - Sandbox, builder and result handles are made by the library and released
+//!   with `sandbox_free`, `sandbox_builder_free` and `free_result`, each exactly
+//!   once, after which they must not be used.
+//! - A returned `char*` belongs to the caller, who releases it with
+//!   `sandbox_free_string`. A returned `const char*` is borrowed: a result's
+//!   JSON lives as long as the result, the last error until the next call on
+//!   the same thread.
+//! - Strings and buffers passed in are only read during the call.
+//!
+//! Functions returning a pointer return NULL on failure and those returning
+//! `int` return -1; `get_last_error` then says why. Panics are caught at the
+//! boundary and reported the same way.
+//!
+//! Calls go to the instance of the module a sandbox loaded last. A handle
+//! mustn't be used from two threads at once.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_uint, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio::runtime::Runtime;
+
+use crate::error::{Result, SandboxError};
+use crate::runtime::ModuleId;
+use crate::{compile_source_to_wasm, InstanceId, WasmSandbox, WasmSandboxBuilder};
+
+/// A sandbox behind a C handle
+pub struct FfiSandbox {
+    sandbox: WasmSandbox,
+    
+    /// Instance of the module loaded last
+    instance_id: Option<InstanceId>,
+}
+
+impl FfiSandbox {
+    /// Load a module and make its new instance the one calls go to
+    fn load(&mut self, wasm_bytes: &[u8]) -> Result<ModuleId> {
+        let _runtime = runtime().enter();
+        let module_id = self.sandbox.load_module(wasm_bytes)?;
+        let instance_id = self.sandbox.create_instance(module_id, None)?;
+        if let Some(previous) = self.instance_id.replace(instance_id) {
+            self.sandbox.remove_instance(previous);
+        }
+        Ok(module_id)
+    }
+}
+
+/// A call's result behind a C handle
+pub struct FfiResult {
+    json: CString,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Runtime the blocking entry points run sandbox calls on
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("wasm-sandbox-ffi")
+            .build()
+            .expect("Failed to start the FFI runtime")
+    })
+}
+
+/// Run an entry point, recording its error or panic for `get_last_error`
+fn guard<T>(failed: T, body: impl FnOnce() -> Result<T>) -> T {
+    LAST_ERROR.with_borrow_mut(|error| *error = None);
+    let message = match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(e)) => e.to_string(),
+        Err(panic) => match panic.downcast_ref::<&str>() {
+            Some(message) => format!("Panicked: {}", message),
+            None => format!("Panicked: {}", panic.downcast_ref::<String>().map_or("unknown cause", String::as_str)),
+        },
+    };
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with_borrow_mut(|error| *error = Some(message));
+    failed
+}
+
+/// Borrow the object behind a handle
+///
+/// # Safety
+/// `ptr` must be NULL or point to a live `T` nothing else is using.
+unsafe fn handle<'a, T>(ptr: *mut T, name: &str) -> Result<&'a mut T> {
+    unsafe { ptr.as_mut() }.ok_or_else(|| invalid(name, "is NULL"))
+}
+
+/// Borrow a NUL-terminated UTF-8 string
+///
+/// # Safety
+/// `ptr` must be NULL or point to a NUL-terminated string that outlives `'a`.
+unsafe fn string<'a>(ptr: *const c_char, name: &str) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(invalid(name, "is NULL"));
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_str().map_err(|_| invalid(name, "isn't UTF-8"))
+}
+
+fn invalid(field: &str, reason: &str) -> SandboxError {
+    SandboxError::InvalidInput {
+        field: field.to_string(),
+        reason: format!("{} {}", field, reason),
+        suggestion: None,
+    }
+}
+
+/// Create a sandbox with the default configuration, or NULL on error
+#[unsafe(no_mangle)]
+pub extern "C" fn sandbox_new() -> *mut FfiSandbox {
+    guard(std::ptr::null_mut(), || {
+        let sandbox = {
+            let _runtime = runtime().enter();
+            WasmSandbox::new()?
+        };
+        Ok(Box::into_raw(Box::new(FfiSandbox { sandbox, instance_id: None })))
+    })
+}
+
+/// Free a sandbox and its instances
+///
+/// # Safety
+/// `sandbox` must be NULL or a sandbox handle that wasn't freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sandbox_free(sandbox: *mut FfiSandbox) {
+    if !sandbox.is_null() {
+        guard((), || {
+            let _runtime = runtime().enter();
+            drop(unsafe { Box::from_raw(sandbox) });
+            Ok(())
+        })
+    }
+}
+
+/// Compile a source file, load it and instantiate it; 0 on success, -1 on error
+///
+/// # Safety
+/// `sandbox` must be a live sandbox handle and `source_path` a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sandbox_load_from_source(sandbox: *mut FfiSandbox, source_path: *const c_char) -> c_int {
+    guard(-1, || {
+        let sandbox = unsafe { handle(sandbox, "sandbox") }?;
+        let source_path = unsafe { string(source_path, "source_path") }?;
+        let wasm_bytes = runtime().block_on(compile_source_to_wasm(source_path))?;
+        sandbox.load(&wasm_bytes)?;
+        Ok(0)
+    })
+}
+
+/// Load a module from bytes and instantiate it, returning its ID or NULL on error
+///
+/// The ID is freed with `sandbox_free_string`.
+///
+/// # Safety
+/// `sandbox` must be a live sandbox handle and `wasm_bytes` point to `wasm_len` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sandbox_load_module(
+    sandbox: *mut FfiSandbox,
+    wasm_bytes: *const c_void,
+    wasm_len: usize,
+) -> *mut c_char {
+    guard(std::ptr::null_mut(), || {
+        let sandbox = unsafe { handle(sandbox, "sandbox") }?;
+        let wasm_bytes = match wasm_len {
+            0 => &[][..],
+            _ if wasm_bytes.is_null() => return Err(invalid("wasm_bytes", "is NULL")),
+            _ => unsafe { std::slice::from_raw_parts(wasm_bytes.cast::<u8>(), wasm_len) },
+        };
+        let module_id = sandbox.load(wasm_bytes)?;
+        Ok(CString::new(module_id.to_string()).unwrap_or_default().into_raw())
+    })
+}
+
+/// Call a function of the loaded module with JSON parameters, returning a result or NULL on error
+///
+/// # Safety
+/// `sandbox` must be a live sandbox handle and the strings NUL-terminated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sandbox_call_function(
+    sandbox: *mut FfiSandbox,
+    function_name: *const c_char,
+    params_json: *const c_char,
+) -> *mut FfiResult {
+    guard(std::ptr::null_mut(), || {
+        let sandbox = unsafe { handle(sandbox, "sandbox") }?;
+        let function_name = unsafe { string(function_name, "function_name") }?;
+        let params: Value = serde_json::from_str(unsafe { string(params_json, "params_json") }?)?;
+        let instance_id = sandbox.instance_id.ok_or_else(|| SandboxError::InvalidInput {
+            field: "sandbox".to_string(),
+            reason: "No module is loaded".to_string(),
+            suggestion: Some("Load a module or source file first".to_string()),
+        })?;
+        let result: Value = runtime().block_on(sandbox.sandbox.call_function(instance_id, function_name, params))?;
+        // JSON escapes NUL, so the text never contains one
+        let json = CString::new(serde_json::to_string(&result)?).unwrap_or_default();
+        Ok(Box::into_raw(Box::new(FfiResult { json })))
+    })
+}
+
+/// Create a sandbox builder
+#[unsafe(no_mangle)]
+pub extern "C" fn sandbox_builder_new() -> *mut WasmSandboxBuilder {
+    Box::into_raw(Box::new(WasmSandboxBuilder::new()))
+}
+
+/// Free a sandbox builder
+///
+/// # Safety
+/// `builder` must be NULL or a builder handle that wasn't freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sandbox_builder_free(builder: *mut WasmSandboxBuilder) {
+    if !builder.is_null() {
+        drop(unsafe { Box::from_raw(builder) });
+    }
+}
+
+/// Apply a setting to a builder, recording an error for a NULL handle
+///
+/// # Safety
+/// `builder` must be NULL or a live builder handle.
+unsafe fn configure(builder: *mut WasmSandboxBuilder, update: impl FnOnce(WasmSandboxBuilder) -> Result<WasmSandboxBuilder>) {
+    guard((), || {
+        let builder = unsafe { handle(builder, "builder") }?;
+        *builder = update(std::mem::take(builder))?;
+        Ok(())
+    })
+}
+
+/// Set the source file the sandbox is built from
+///
+/// # Safety
+/// `builder` must be a live builder handle and `source_path` a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sandbox_builder_source(builder: *mut WasmSandboxBuilder, source_path: *const c_char) {
+    unsafe { configure(builder, |builder| Ok(builder.source(string(source_path, "source_path")?))) }
+}
+
+/// Set the execution timeout in milliseconds
+///
+/// # Safety
+/// `builder` must be a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sandbox_builder_timeout(builder: *mut WasmSandboxBuilder, timeout_ms: c_uint) {
+    unsafe { configure(builder, |builder| Ok(builder.timeout_duration(Duration::from_millis(timeout_ms.into())))) }
+}
+
+/// Set the memory limit in bytes
+///
+/// # Safety
+/// `builder` must be a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sandbox_builder_memory_limit(builder: *mut WasmSandboxBuilder, limit_bytes: usize) {
+    unsafe { configure(builder, |builder| Ok(builder.memory_limit(limit_bytes))) }
+}
+
+/// Enable (non-zero) or disable (0) file system access
+///
+/// # Safety
+/// `builder` must be a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sandbox_builder_file_access(builder: *mut WasmSandboxBuilder, enabled: c_int) {
+    unsafe { configure(builder, |builder| Ok(builder.enable_file_access(enabled != 0))) }
+}
+
+/// Enable (non-zero) or disable (0) network access
+///
+/// # Safety
+/// `builder` must be a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sandbox_builder_network(builder: *mut WasmSandboxBuilder, enabled: c_int) {
+    unsafe { configure(builder, |builder| Ok(builder.enable_network(enabled != 0))) }
+}
+
+/// Build a sandbox from a builder's settings, or NULL on error
+///
+/// The builder is left as it was and still has to be freed.
+///
+/// # Safety
+/// `builder` must be a live builder handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sandbox_builder_build(builder: *mut WasmSandboxBuilder) -> *mut FfiSandbox {
+    guard(std::ptr::null_mut(), || {
+        let builder = unsafe { handle(builder, "builder") }?.clone();
+        let sandbox = runtime().block_on(builder.build())?;
+        let instance_id = sandbox.instance_ids().first().copied();
+        Ok(Box::into_raw(Box::new(FfiSandbox { sandbox, instance_id })))
+    })
+}
+
+/// A result's JSON text, borrowed from the result, or NULL on error
+///
+/// # Safety
+/// `result` must be NULL or a live result handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn get_result_json(result: *mut FfiResult) -> *const c_char {
+    guard(std::ptr::null(), || Ok(unsafe { handle(result, "result") }?.json.as_ptr()))
+}
+
+/// Free a result
+///
+/// # Safety
+/// `result` must be NULL or a result handle that wasn't freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn free_result(result: *mut FfiResult) {
+    if !result.is_null() {
+        drop(unsafe { Box::from_raw(result) });
+    }
+}
+
+/// Free a string the library returned as `char*`
+///
+/// # Safety
+/// `string` must be NULL or a string from this library that wasn't freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sandbox_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+/// Why the last failed call on this thread failed, or NULL if the last call succeeded
+#[unsafe(no_mangle)]
+pub extern "C" fn get_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|error| error.as_ref().map_or(std::ptr::null(), |error| error.as_ptr()))
+}
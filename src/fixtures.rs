@@ -0,0 +1,407 @@
+//! Declarative golden fixtures for modules
+//!
+//! A fixture names an export, the JSON input to call it with and what should
+//! come back: an expected output, an expected error, and the capability
+//! denials the call should run into. [`SandboxTestSuite::from_dir`] loads every
+//! `.yaml`, `.yml` and `.json` file in a directory, each holding one case or a
+//! list of them:
+//!
+//! ```yaml
+//! - name: greets by name
+//!   function: greet
+//!   input: { name: "Ada" }
+//!   expected: "Hello, Ada!"
+//! - name: cannot phone home
+//!   function: report
+//!   expected_error: "may not call"
+//!   expected_denials: ["telemetry.send"]
+//! ```
+//!
+//! Each case runs in a fresh instance, and the [`FixtureReport`] prints like
+//! `cargo test` output with a diff for every mismatch, so a marketplace can
+//! require a passing suite before accepting an upload:
+//!
+//! ```rust,no_run
+//! use wasm_sandbox::fixtures::SandboxTestSuite;
+//!
+//! # async fn check() -> wasm_sandbox::Result<()> {
+//! let wasm_bytes = std::fs::read("plugin.wasm")?;
+//! let report = SandboxTestSuite::from_dir("tests/fixtures")?.run(&wasm_bytes).await?;
+//! report.assert_passed();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::conformance::{CheckOutcome, CheckResult};
+use crate::error::{Error, Result};
+use crate::runtime::ModuleId;
+use crate::security::audit::AuditEventType;
+use crate::security::Capabilities;
+use crate::{InstanceConfig, SandboxConfig, WasmSandbox};
+
+/// Extensions of the files [`SandboxTestSuite::from_dir`] loads
+pub const FIXTURE_EXTENSIONS: [&str; 3] = ["yaml", "yml", "json"];
+
+/// One golden call and what it should produce
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FixtureCase {
+    /// Name in the report; defaults to the file's stem, with the case's index for lists
+    #[serde(default)]
+    pub name: String,
+    
+    /// Export to call
+    pub function: String,
+    
+    /// JSON input passed to the export
+    #[serde(default)]
+    pub input: Value,
+    
+    /// Output the call must return
+    #[serde(default)]
+    pub expected: Option<Value>,
+    
+    /// Text the call's error must contain; the call must fail if set
+    #[serde(default)]
+    pub expected_error: Option<String>,
+    
+    /// Text of each capability denial the call must run into
+    ///
+    /// Denials the call runs into that match none of these fail the case.
+    #[serde(default)]
+    pub expected_denials: Vec<String>,
+}
+
+impl FixtureCase {
+    /// Create a case calling `function` with `input`
+    pub fn new(name: &str, function: &str, input: Value) -> Self {
+        Self {
+            name: name.to_string(),
+            function: function.to_string(),
+            input,
+            expected: None,
+            expected_error: None,
+            expected_denials: Vec::new(),
+        }
+    }
+    
+    /// Expect the call to return `output`
+    pub fn expect(mut self, output: Value) -> Self {
+        self.expected = Some(output);
+        self
+    }
+    
+    /// Expect the call to fail with an error containing `text`
+    pub fn expect_error(mut self, text: &str) -> Self {
+        self.expected_error = Some(text.to_string());
+        self
+    }
+    
+    /// Expect the call to be denied a capability, described by `text`
+    pub fn expect_denial(mut self, text: &str) -> Self {
+        self.expected_denials.push(text.to_string());
+        self
+    }
+}
+
+/// A fixture file holds one case or a list of them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FixtureFile {
+    Many(Vec<FixtureCase>),
+    One(Box<FixtureCase>),
+}
+
+/// Outcomes of a fixture run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixtureReport {
+    /// Cases in the order they ran
+    pub cases: Vec<CheckResult>,
+    
+    /// Time the run took
+    pub elapsed: Duration,
+}
+
+impl FixtureReport {
+    /// Whether every case passed
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+    
+    /// Cases that failed
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.cases.iter().filter(|case| matches!(case.outcome, CheckOutcome::Failed(_)))
+    }
+    
+    /// Outcome of the case called `name`
+    pub fn outcome(&self, name: &str) -> Option<&CheckOutcome> {
+        self.cases.iter().find(|case| case.name == name).map(|case| &case.outcome)
+    }
+    
+    /// Panic with the report unless every case passed
+    #[track_caller]
+    pub fn assert_passed(&self) {
+        if !self.passed() {
+            panic!("module does not match its fixtures\n\n{}", self);
+        }
+    }
+}
+
+impl fmt::Display for FixtureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "running {} fixtures", self.cases.len())?;
+        for case in &self.cases {
+            match &case.outcome {
+                CheckOutcome::Passed => writeln!(f, "fixture {} ... ok", case.name)?,
+                CheckOutcome::Failed(_) => writeln!(f, "fixture {} ... FAILED", case.name)?,
+                CheckOutcome::Ignored(reason) => writeln!(f, "fixture {} ... ignored, {}", case.name, reason)?,
+            }
+        }
+        
+        if !self.passed() {
+            writeln!(f, "\nfailures:")?;
+            for case in self.failures() {
+                if let CheckOutcome::Failed(reason) = &case.outcome {
+                    writeln!(f, "    {}:", case.name)?;
+                    for line in reason.lines() {
+                        writeln!(f, "        {}", line)?;
+                    }
+                }
+            }
+        }
+        
+        let failed = self.failures().count();
+        write!(
+            f,
+            "\nfixture result: {}. {} passed; {} failed; finished in {:.2}s",
+            if self.passed() { "ok" } else { "FAILED" },
+            self.cases.len() - failed,
+            failed,
+            self.elapsed.as_secs_f64(),
+        )
+    }
+}
+
+/// Golden fixtures to run against a module
+#[derive(Debug, Clone, Default)]
+pub struct SandboxTestSuite {
+    cases: Vec<FixtureCase>,
+    instance_config: InstanceConfig,
+    sandbox_config: SandboxConfig,
+}
+
+impl SandboxTestSuite {
+    /// Create an empty suite
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Load every fixture file in `dir`, in file name order
+    ///
+    /// Fails if the directory can't be read or a fixture file doesn't parse.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir.as_ref())? {
+            let path = entry?.path();
+            let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+            if path.is_file() && FIXTURE_EXTENSIONS.contains(&extension) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        
+        let mut suite = Self::new();
+        for path in paths {
+            suite.cases.extend(load_file(&path)?);
+        }
+        Ok(suite)
+    }
+    
+    /// Add a case
+    pub fn case(mut self, case: FixtureCase) -> Self {
+        self.cases.push(case);
+        self
+    }
+    
+    /// Cases in the order they run
+    pub fn cases(&self) -> &[FixtureCase] {
+        &self.cases
+    }
+    
+    /// Set the capabilities each case's instance runs with
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.instance_config.capabilities = capabilities;
+        self
+    }
+    
+    /// Set the configuration of each case's instance
+    pub fn instance_config(mut self, config: InstanceConfig) -> Self {
+        self.instance_config = config;
+        self
+    }
+    
+    /// Set the configuration of the sandbox [`SandboxTestSuite::run`] creates
+    pub fn sandbox_config(mut self, config: SandboxConfig) -> Self {
+        self.sandbox_config = config;
+        self
+    }
+    
+    /// Run every case against a module in a sandbox of its own
+    ///
+    /// Fails only if the module can't be compiled; cases the module fails are
+    /// recorded in the report.
+    pub async fn run(&self, wasm_bytes: &[u8]) -> Result<FixtureReport> {
+        let mut sandbox = WasmSandbox::with_config(self.sandbox_config.clone())?;
+        let module_id = sandbox.load_module(wasm_bytes)?;
+        self.run_with(&mut sandbox, module_id).await
+    }
+    
+    /// Run every case against a module already loaded into `sandbox`
+    ///
+    /// Use this when the module needs host namespaces or capability extensions
+    /// registered first. Each case's instance is removed once it has run.
+    pub async fn run_with(&self, sandbox: &mut WasmSandbox, module_id: ModuleId) -> Result<FixtureReport> {
+        let started = Instant::now();
+        let mut results = Vec::with_capacity(self.cases.len());
+        for case in &self.cases {
+            let outcome = self.run_case(sandbox, module_id, case).await?;
+            results.push(CheckResult { name: case.name.clone(), outcome });
+        }
+        
+        Ok(FixtureReport {
+            cases: results,
+            elapsed: started.elapsed(),
+        })
+    }
+    
+    async fn run_case(&self, sandbox: &mut WasmSandbox, module_id: ModuleId, case: &FixtureCase) -> Result<CheckOutcome> {
+        let instance_id = sandbox.create_instance(module_id, Some(self.instance_config.clone()))?;
+        let audited = sandbox.capability_audit_logger().get_events().len();
+        let result = sandbox.call_function::<Value, Value>(instance_id, &case.function, case.input.clone()).await;
+        
+        // Denials surface in the call's error, and extension calls are audited too
+        let instance = instance_id.to_string();
+        let mut denials: Vec<String> = sandbox.capability_audit_logger().get_events()
+            .into_iter()
+            .skip(audited)
+            .filter(|event| match &event.event_type {
+                AuditEventType::ExtensionCall { instance_id, allowed, .. } => *instance_id == instance && !allowed,
+                AuditEventType::CapabilityViolation { instance_id, .. } => *instance_id == instance,
+                _ => false,
+            })
+            .map(|event| event.message)
+            .collect();
+        if let Err(e) = &result {
+            denials.extend(denial(e));
+        }
+        sandbox.remove_instance(instance_id);
+        
+        let mut mismatches = Vec::new();
+        match (&result, &case.expected_error) {
+            (Ok(output), None) => {
+                if let Some(expected) = &case.expected {
+                    diff("$", expected, output, &mut mismatches);
+                }
+            }
+            (Ok(output), Some(text)) => mismatches.push(format!("expected an error containing {:?}, got {}", text, output)),
+            (Err(e), Some(text)) if e.to_string().contains(text.as_str()) => {}
+            (Err(e), Some(text)) => mismatches.push(format!("expected an error containing {:?}, got: {}", text, e)),
+            (Err(e), None) => mismatches.push(format!("`{}` failed: {}", case.function, e)),
+        }
+        for expected in &case.expected_denials {
+            if !denials.iter().any(|denial| denial.contains(expected.as_str())) {
+                mismatches.push(format!("expected a denial matching {:?}, got none", expected));
+            }
+        }
+        for observed in &denials {
+            if !case.expected_denials.iter().any(|expected| observed.contains(expected.as_str())) {
+                mismatches.push(format!("unexpected denial: {}", observed));
+            }
+        }
+        
+        Ok(match mismatches.is_empty() {
+            true => CheckOutcome::Passed,
+            false => CheckOutcome::Failed(mismatches.join("\n")),
+        })
+    }
+}
+
+/// Parse the cases in one fixture file, naming the unnamed ones
+fn load_file(path: &Path) -> Result<Vec<FixtureCase>> {
+    let content = std::fs::read_to_string(path)?;
+    let parsed = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => serde_json::from_str::<FixtureFile>(&content).map_err(|e| e.to_string()),
+        _ => serde_yaml::from_str::<FixtureFile>(&content).map_err(|e| e.to_string()),
+    };
+    let file = parsed.map_err(|e| Error::Configuration {
+        message: format!("Failed to parse fixture {}: {}", path.display(), e),
+        suggestion: Some("Each case needs a `function`, and may set `input`, `expected`, `expected_error` and `expected_denials`".to_string()),
+        field: Some("fixtures".to_string()),
+    })?;
+    
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+    Ok(match file {
+        FixtureFile::One(mut case) => {
+            if case.name.is_empty() {
+                case.name = stem.to_string();
+            }
+            vec![*case]
+        }
+        FixtureFile::Many(cases) => cases.into_iter()
+            .enumerate()
+            .map(|(index, mut case)| {
+                if case.name.is_empty() {
+                    case.name = format!("{}#{}", stem, index);
+                }
+                case
+            })
+            .collect(),
+    })
+}
+
+/// The capability denial behind an error, if it is one
+fn denial(error: &Error) -> Option<String> {
+    if let Error::SecurityViolation { violation, .. } = error {
+        return Some(violation.clone());
+    }
+    
+    // Host functions' violations reach the caller inside a trap
+    let message = error.to_string();
+    let (_, rest) = message.split_once("Security violation: ")?;
+    Some(rest.lines().next().unwrap_or_default().to_string())
+}
+
+/// Record where `actual` differs from `expected`, as `path: ...` lines
+fn diff(path: &str, expected: &Value, actual: &Value, out: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, value) in expected {
+                match actual.get(key) {
+                    Some(found) => diff(&format!("{}.{}", path, key), value, found, out),
+                    None => out.push(format!("{}.{}: missing, expected {}", path, key, value)),
+                }
+            }
+            for (key, value) in actual {
+                if !expected.contains_key(key) {
+                    out.push(format!("{}.{}: unexpected {}", path, key, value));
+                }
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            for (index, (value, found)) in expected.iter().zip(actual).enumerate() {
+                diff(&format!("{}[{}]", path, index), value, found, out);
+            }
+            if expected.len() != actual.len() {
+                out.push(format!("{}: expected {} items, got {}", path, expected.len(), actual.len()));
+            }
+        }
+        _ if expected != actual => out.push(format!("{}: expected {}, got {}", path, expected, actual)),
+        _ => {}
+    }
+}
@@ -0,0 +1,146 @@
+//! Serving [`SandboxRoute`]s from `actix-web` middleware
+
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::StatusCode;
+use actix_web::HttpMessage;
+use futures::future::LocalBoxFuture;
+use futures::StreamExt;
+
+use super::SandboxRoute;
+use crate::http_handler::{HttpRequest, HttpResponse};
+
+/// Middleware answering requests under its mounts from sandboxed routes
+///
+/// The longest matching mount wins; requests no mount serves go on to the
+/// wrapped app.
+#[derive(Debug, Clone, Default)]
+pub struct SandboxMiddleware {
+    mounts: Arc<Vec<(String, SandboxRoute)>>,
+}
+
+impl SandboxMiddleware {
+    /// Create middleware with no mounts
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Serve requests under `path` from `route`
+    pub fn mount(mut self, path: &str, route: SandboxRoute) -> Self {
+        Arc::make_mut(&mut self.mounts).push((path.trim_end_matches('/').to_string(), route));
+        self
+    }
+    
+    /// Route serving `path`, and the path relative to its mount
+    fn resolve<'a>(&self, path: &'a str) -> Option<(&SandboxRoute, &'a str)> {
+        self.mounts.iter()
+            .filter_map(|(mount, route)| Some((mount.len(), route, relative_path(mount, path)?)))
+            .max_by_key(|(length, _, _)| *length)
+            .map(|(_, route, path)| (route, path))
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SandboxMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = SandboxMiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+    
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SandboxMiddlewareService { service: Rc::new(service), middleware: self.clone() }))
+    }
+}
+
+/// [`SandboxMiddleware`] wrapped around an app's service
+pub struct SandboxMiddlewareService<S> {
+    service: Rc<S>,
+    middleware: SandboxMiddleware,
+}
+
+impl<S, B> Service<ServiceRequest> for SandboxMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+    
+    forward_ready!(service);
+    
+    fn call(&self, mut request: ServiceRequest) -> Self::Future {
+        let Some((route, path)) = self.middleware.resolve(request.path()) else {
+            let response = self.service.call(request);
+            return Box::pin(async move { Ok(response.await?.map_into_left_body()) });
+        };
+        let route = route.clone();
+        let target = match request.query_string() {
+            "" => path.to_string(),
+            query => format!("{}?{}", path, query),
+        };
+        let head = into_http_request(&request, &target);
+        let payload = request.take_payload();
+        Box::pin(async move {
+            let response = match (head, read_body(payload, route.budget().max_request_bytes).await) {
+                (Ok(head), Ok(body)) => route.handle(head.body(body)).await,
+                (Err(response), _) | (_, Err(response)) => response,
+            };
+            Ok(request.into_response(into_response(response)).map_into_right_body())
+        })
+    }
+}
+
+/// Path of a request relative to `mount`, if the mount serves it
+///
+/// Mounts match whole path segments, like [`crate::HttpRouter`] prefixes.
+fn relative_path<'a>(mount: &str, path: &'a str) -> Option<&'a str> {
+    match path.strip_prefix(mount)? {
+        "" => Some("/"),
+        rest if rest.starts_with('/') => Some(rest),
+        _ => None,
+    }
+}
+
+/// Convert a request's method and headers, addressed to `target`
+fn into_http_request(request: &ServiceRequest, target: &str) -> Result<HttpRequest, HttpResponse> {
+    let mut converted = HttpRequest::new(request.method().as_str(), target);
+    for (name, value) in request.headers() {
+        let value = value.to_str().map_err(|_| HttpResponse::text(400, "Header value is not text"))?;
+        converted = converted.header(name.as_str(), value);
+    }
+    Ok(converted)
+}
+
+/// Read at most `max_body_bytes` of a request body
+async fn read_body(mut payload: Payload, max_body_bytes: usize) -> Result<Vec<u8>, HttpResponse> {
+    let mut body = Vec::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|_| HttpResponse::text(400, "Could not read request body"))?;
+        if body.len() + chunk.len() > max_body_bytes {
+            return Err(HttpResponse::text(413, "Request body too large"));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Convert a guest's response
+fn into_response(response: HttpResponse) -> actix_web::HttpResponse {
+    let Ok(status) = StatusCode::from_u16(response.status) else {
+        return into_response(HttpResponse::text(502, "Handler returned an invalid status"));
+    };
+    let mut builder = actix_web::HttpResponse::build(status);
+    for (name, value) in response.forwarded_headers() {
+        builder.append_header((name, value));
+    }
+    builder.body(response.body)
+}
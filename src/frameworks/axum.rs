@@ -0,0 +1,66 @@
+//! Mounting [`SandboxRoute`]s on an `axum::Router`
+
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum::response::Response;
+use axum::routing::any;
+use axum::Router;
+
+use super::SandboxRoute;
+use crate::http_handler::{HttpRequest, HttpResponse};
+
+/// Adds sandboxed routes to an `axum::Router`
+pub trait SandboxRouterExt {
+    /// Serve requests under `path` from `route`
+    ///
+    /// Mounting at `/` serves every request no other route matches.
+    fn sandbox_route(self, path: &str, route: SandboxRoute) -> Self;
+}
+
+impl<S: Clone + Send + Sync + 'static> SandboxRouterExt for Router<S> {
+    fn sandbox_route(self, path: &str, route: SandboxRoute) -> Self {
+        let handler = any(move |request: Request| {
+            let route = route.clone();
+            async move {
+                let response = match into_http_request(request, route.budget().max_request_bytes).await {
+                    Ok(request) => route.handle(request).await,
+                    Err(response) => response,
+                };
+                into_response(response)
+            }
+        });
+        match path.trim_end_matches('/') {
+            "" => self.fallback_service(handler),
+            path => self.nest_service(path, handler),
+        }
+    }
+}
+
+/// Convert a request, reading at most `max_body_bytes` of its body
+async fn into_http_request(request: Request, max_body_bytes: usize) -> Result<HttpRequest, HttpResponse> {
+    let (parts, body) = request.into_parts();
+    let target = parts.uri.path_and_query().map_or("/", |target| target.as_str());
+    let mut request = HttpRequest::new(parts.method.as_str(), target);
+    for (name, value) in &parts.headers {
+        let value = value.to_str().map_err(|_| HttpResponse::text(400, "Header value is not text"))?;
+        request = request.header(name.as_str(), value);
+    }
+    let body = axum::body::to_bytes(body, max_body_bytes).await
+        .map_err(|_| HttpResponse::text(413, "Request body too large"))?;
+    Ok(request.body(body.to_vec()))
+}
+
+/// Convert a guest's response
+fn into_response(response: HttpResponse) -> Response {
+    let Ok(status) = StatusCode::from_u16(response.status) else {
+        return into_response(HttpResponse::text(502, "Handler returned an invalid status"));
+    };
+    let mut builder = Response::builder().status(status);
+    for (name, value) in response.forwarded_headers() {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::from_str(value)) {
+            builder = builder.header(name, value);
+        }
+    }
+    builder.body(Body::from(response.body)).unwrap_or_default()
+}
@@ -0,0 +1,149 @@
+//! Mounting guest HTTP handlers in existing web services
+//!
+//! A [`SandboxRoute`] serves requests from instances exporting
+//! [`crate::HTTP_HANDLER_EXPORT`] like one route of an [`HttpRouter`], but is
+//! driven by the service's web framework rather than the built-in listener of
+//! [`WasmSandbox::serve_http`]. A [`RoutePolicy`] creates the instances behind
+//! a route with their own capabilities and resource limits, and sets the
+//! route's [`HttpBudget`].
+//!
+//! The `axum` feature adds [`axum::SandboxRouterExt`] to mount routes on an
+//! `axum::Router`, and the `actix` feature adds [`actix::SandboxMiddleware`]
+//! for `actix-web` apps. Handlers see paths relative to their mount point, so
+//! a module mounted at `/plugins/resize` receives `/` and `/small` for
+//! `/plugins/resize` and `/plugins/resize/small`.
+
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::runtime::ModuleId;
+use crate::security::{Capabilities, ResourceLimits};
+use crate::http_handler::{HttpBudget, HttpRequest, HttpResponse, HttpRouter};
+use crate::{InstanceConfig, InstanceId, WasmSandbox};
+
+#[cfg(feature = "axum")]
+pub mod axum;
+
+#[cfg(feature = "actix")]
+pub mod actix;
+
+/// How the instances behind a route are created and what each request may use
+#[derive(Debug, Clone)]
+pub struct RoutePolicy {
+    /// Configuration of each instance, including its capabilities and resource limits
+    pub instance_config: InstanceConfig,
+    
+    /// Limits applied to each request
+    pub budget: HttpBudget,
+    
+    /// Number of instances requests are spread over
+    pub replicas: usize,
+}
+
+impl Default for RoutePolicy {
+    fn default() -> Self {
+        Self {
+            instance_config: InstanceConfig::default(),
+            budget: HttpBudget::default(),
+            replicas: 1,
+        }
+    }
+}
+
+impl RoutePolicy {
+    /// Create a policy with default instance settings and budget
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Set the capabilities of the route's instances
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.instance_config.capabilities = capabilities;
+        self
+    }
+    
+    /// Set the resource limits of the route's instances
+    pub fn resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.instance_config.resource_limits = limits;
+        self
+    }
+    
+    /// Replace the whole instance configuration
+    pub fn instance_config(mut self, config: InstanceConfig) -> Self {
+        self.instance_config = config;
+        self
+    }
+    
+    /// Set the limits applied to each request
+    pub fn budget(mut self, budget: HttpBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+    
+    /// Set the number of instances serving the route
+    pub fn replicas(mut self, replicas: usize) -> Self {
+        self.replicas = replicas.max(1);
+        self
+    }
+    
+    /// Create the route's instances of `module_id`
+    ///
+    /// If one fails, those already created are removed.
+    pub fn instantiate(&self, sandbox: &mut WasmSandbox, module_id: ModuleId) -> Result<Vec<InstanceId>> {
+        let mut instances = Vec::with_capacity(self.replicas);
+        for _ in 0..self.replicas.max(1) {
+            match sandbox.create_instance(module_id, Some(self.instance_config.clone())) {
+                Ok(instance_id) => instances.push(instance_id),
+                Err(e) => {
+                    for instance_id in instances {
+                        sandbox.remove_instance(instance_id);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(instances)
+    }
+}
+
+/// Instances serving one mounted route
+///
+/// Cloning is cheap; clones share the instances and take turns between them.
+#[derive(Clone)]
+pub struct SandboxRoute {
+    sandbox: Arc<WasmSandbox>,
+    router: Arc<HttpRouter>,
+    budget: HttpBudget,
+}
+
+impl SandboxRoute {
+    /// Serve a route from `instances` of `sandbox` within `budget`
+    pub fn new(sandbox: Arc<WasmSandbox>, instances: impl IntoIterator<Item = InstanceId>, budget: HttpBudget) -> Self {
+        Self {
+            sandbox,
+            router: Arc::new(HttpRouter::new().route("/", instances, budget)),
+            budget,
+        }
+    }
+    
+    /// Limits applied to each request
+    pub fn budget(&self) -> HttpBudget {
+        self.budget
+    }
+    
+    /// Handle a request whose path is relative to the mount point
+    ///
+    /// Failures become responses as in [`WasmSandbox::handle_http`].
+    pub async fn handle(&self, request: HttpRequest) -> HttpResponse {
+        self.sandbox.handle_http(&self.router, request).await
+    }
+}
+
+impl std::fmt::Debug for SandboxRoute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SandboxRoute")
+            .field("router", &self.router)
+            .field("budget", &self.budget)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,294 @@
+//! Serving HTTP requests from guest handlers
+//!
+//! [`crate::WasmSandbox::serve_http`] turns a sandbox into a small serverless
+//! platform: a built-in hyper listener accepts HTTP/1.1 requests, an
+//! [`HttpRouter`] picks an instance by path prefix, and the instance's
+//! [`HTTP_HANDLER_EXPORT`] is called with the [`HttpRequest`] as JSON through
+//! the guest data ABI, returning an [`HttpResponse`]. This is the sandbox's
+//! own handler ABI for core modules, not the component model's `wasi:http`
+//! interface. Each route has an [`HttpBudget`] bounding the request and
+//! response sizes and the time the guest may take; guests that overrun it are
+//! interrupted.
+//!
+//! The listener answers one request per connection, so a slow client holds a
+//! connection slot for at most [`HTTP_READ_TIMEOUT`].
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response, StatusCode};
+use hyper_util::rt::{TokioIo, TokioTimer};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+
+use crate::InstanceId;
+
+/// Export called for every request routed to an instance
+pub const HTTP_HANDLER_EXPORT: &str = "on_http_request";
+
+/// Largest request line and headers the listener reads
+pub const MAX_HTTP_HEAD_BYTES: usize = 16 * 1024;
+
+/// Time a client has to send its whole request
+pub const HTTP_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A request handed to a guest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpRequest {
+    /// Method, e.g. `GET`
+    pub method: String,
+    
+    /// Path without the query string
+    pub path: String,
+    
+    /// Query string without the leading `?`
+    pub query: Option<String>,
+    
+    /// Headers with lowercase names
+    pub headers: Vec<(String, String)>,
+    
+    /// Request body
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// Create a request with no headers or body
+    pub fn new(method: &str, target: &str) -> Self {
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_string())),
+            None => (target, None),
+        };
+        Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            query,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+    
+    /// Add a header
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_ascii_lowercase(), value.to_string()));
+        self
+    }
+    
+    /// Set the body
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+/// A guest's response to an [`HttpRequest`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpResponse {
+    /// Status code
+    pub status: u16,
+    
+    /// Headers to send; `content-length` is always set by the host
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    
+    /// Response body
+    #[serde(default)]
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// A plain-text response generated by the host
+    pub fn text(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            headers: vec![("content-type".to_string(), "text/plain; charset=utf-8".to_string())],
+            body: body.as_bytes().to_vec(),
+        }
+    }
+    
+    /// Headers the host passes on, with lowercase names
+    ///
+    /// Framing headers are set by the host, and headers that could split the
+    /// response are dropped.
+    pub(crate) fn forwarded_headers(&self) -> impl Iterator<Item = (String, &str)> {
+        self.headers.iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.as_str()))
+            .filter(|(name, _)| !matches!(name.as_str(), "content-length" | "transfer-encoding" | "connection"))
+            // Header values from guests must not be able to split the response
+            .filter(|(name, value)| !name.contains(['\r', '\n', ':']) && !value.contains(['\r', '\n']))
+    }
+}
+
+/// Limits applied to each request on a route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpBudget {
+    /// Time the guest may take to handle a request before it is interrupted
+    pub timeout: Duration,
+    
+    /// Largest request body accepted
+    pub max_request_bytes: usize,
+    
+    /// Largest response body a guest may return
+    pub max_response_bytes: usize,
+}
+
+impl Default for HttpBudget {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_request_bytes: 1024 * 1024,
+            max_response_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl HttpBudget {
+    /// Set the time a guest may take per request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    
+    /// Set the largest request body accepted
+    pub fn max_request_bytes(mut self, bytes: usize) -> Self {
+        self.max_request_bytes = bytes;
+        self
+    }
+    
+    /// Set the largest response body a guest may return
+    pub fn max_response_bytes(mut self, bytes: usize) -> Self {
+        self.max_response_bytes = bytes;
+        self
+    }
+}
+
+/// Instances serving one path prefix
+#[derive(Debug)]
+struct HttpRoute {
+    prefix: String,
+    instances: Vec<InstanceId>,
+    budget: HttpBudget,
+    next: AtomicUsize,
+}
+
+/// Routes requests to instances by path prefix
+///
+/// The longest matching prefix wins, matching whole path segments, so
+/// `/api` serves `/api` and `/api/users` but not `/apiary`. Requests on a
+/// route are spread over its instances in turn.
+#[derive(Debug, Default)]
+pub struct HttpRouter {
+    routes: Vec<HttpRoute>,
+}
+
+impl HttpRouter {
+    /// Create a router with no routes
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Serve requests under `prefix` from `instances`
+    pub fn route(mut self, prefix: &str, instances: impl IntoIterator<Item = InstanceId>, budget: HttpBudget) -> Self {
+        self.routes.push(HttpRoute {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            instances: instances.into_iter().collect(),
+            budget,
+            next: AtomicUsize::new(0),
+        });
+        self
+    }
+    
+    /// Instance that should handle a request for `path`, and the route's budget
+    pub fn resolve(&self, path: &str) -> Option<(InstanceId, HttpBudget)> {
+        let route = self.routes.iter()
+            .filter(|route| !route.instances.is_empty())
+            .filter(|route| match path.strip_prefix(route.prefix.as_str()) {
+                Some(rest) => rest.is_empty() || rest.starts_with('/'),
+                None => false,
+            })
+            .max_by_key(|route| route.prefix.len())?;
+        let turn = route.next.fetch_add(1, Ordering::Relaxed);
+        Some((route.instances[turn % route.instances.len()], route.budget))
+    }
+    
+    /// Largest request body any route accepts
+    pub(crate) fn max_request_bytes(&self) -> usize {
+        self.routes.iter().map(|route| route.budget.max_request_bytes).max().unwrap_or(0)
+    }
+}
+
+/// Serve the request on a connection, answering it with `handle`
+///
+/// Requests hyper can't parse get 400 from hyper itself; bodies over
+/// `max_body_bytes` get 413, and clients that take longer than `read_timeout`
+/// to send their request get 408 or are disconnected.
+pub(crate) async fn serve_connection<F, Fut>(
+    stream: TcpStream,
+    max_body_bytes: usize,
+    read_timeout: Duration,
+    handle: F,
+) -> std::result::Result<(), hyper::Error>
+where
+    F: Fn(HttpRequest) -> Fut,
+    Fut: Future<Output = HttpResponse>,
+{
+    let handle = &handle;
+    let service = service_fn(move |request: Request<Incoming>| async move {
+        let response = match read_request(request, max_body_bytes, read_timeout).await {
+            Ok(request) => handle(request).await,
+            Err(response) => response,
+        };
+        Ok::<_, Infallible>(into_hyper_response(response))
+    });
+    http1::Builder::new()
+        .timer(TokioTimer::new())
+        .header_read_timeout(read_timeout)
+        .max_buf_size(MAX_HTTP_HEAD_BYTES)
+        .keep_alive(false)
+        .serve_connection(TokioIo::new(stream), service)
+        .await
+}
+
+/// Convert a parsed request, reading its body
+///
+/// Oversized or slow bodies are answered with the response to send.
+async fn read_request(
+    request: Request<Incoming>,
+    max_body_bytes: usize,
+    read_timeout: Duration,
+) -> std::result::Result<HttpRequest, HttpResponse> {
+    let (parts, body) = request.into_parts();
+    let target = parts.uri.path_and_query().map_or("/", |target| target.as_str());
+    let mut converted = HttpRequest::new(parts.method.as_str(), target);
+    for (name, value) in &parts.headers {
+        let value = value.to_str().map_err(|_| HttpResponse::text(400, "Header values must be visible ASCII"))?;
+        converted = converted.header(name.as_str(), value);
+    }
+    
+    let body = tokio::time::timeout(read_timeout, Limited::new(body, max_body_bytes).collect())
+        .await
+        .map_err(|_| HttpResponse::text(408, "Request timed out"))?
+        .map_err(|e| match e.is::<LengthLimitError>() {
+            true => HttpResponse::text(413, "Request body too large"),
+            false => HttpResponse::text(400, "Incomplete request body"),
+        })?;
+    converted.body = body.to_bytes().to_vec();
+    Ok(converted)
+}
+
+/// Convert a response for hyper, which sets the framing headers
+fn into_hyper_response(response: HttpResponse) -> Response<Full<Bytes>> {
+    let headers: Vec<_> = response.forwarded_headers()
+        .filter_map(|(name, value)| Some((HeaderName::from_bytes(name.as_bytes()).ok()?, HeaderValue::from_str(value).ok()?)))
+        .collect();
+    let mut converted = Response::new(Full::new(Bytes::from(response.body)));
+    *converted.status_mut() = StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    converted.headers_mut().extend(headers);
+    converted
+}
@@ -127,16 +127,57 @@ pub mod compiler;
 pub mod templates;
 pub mod utils;
 pub mod monitoring;
+pub mod testing;
+pub mod conformance;
+pub mod fixtures;
+pub mod replay;
 pub use monitoring::{DetailedResourceUsage, ResourceMonitor, MemoryUsage, CpuUsage, IoUsage, ResourceSnapshot};
 
 // Export main API types
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
 use uuid::Uuid;
 
-use runtime::{create_runtime, ModuleId, RuntimeConfig, WasmInstance, WasmRuntime};
+use runtime::{create_runtime, FromHostValues, GuestCall, HostValue, IntoHostValues, ModuleId, RuntimeConfig, WasmFunctionCallerAsync, WasmInstance, WasmRuntime};
 use security::{Capabilities, ResourceLimits};
+use security::audit::{AuditEventType, AuditLogger};
+use security::capabilities::ActiveCapabilities;
+use security::secrets::{InstanceSecrets, SecretStore, SecretsProvider};
+use security::dns::{DnsResolver, InstanceDns, NameResolver};
+#[cfg(feature = "http-client")]
+use security::http::{HttpEgress, InstanceHttp};
+use communication::broker::BrokerDispatcher;
+use runtime::abi::AbiFunctionCaller;
+use runtime::eviction::{self, EvictedInstance, EvictionCandidate, EvictionHandler, InstanceSnapshot};
+use ephemeral::Teardown;
+use nested::NestedSandbox;
+use runtime::call_context::ActiveCall;
+use runtime::call_queue::CallQueue;
+use runtime::coredump::{Coredumps, InstanceCoredumps};
+use runtime::profiling::{InstanceProfiles, Profiles};
+use runtime::checkpoint::{self, Checkpoints, InstanceCheckpoints};
+use runtime::fuel_budget::{FuelLedger, InstanceFuelRefill};
+use runtime::io_scheduler::{InstanceIo, IoLedger};
+use runtime::hibernation::HibernatedInstance;
+use runtime::growth::{GrowthHooks, HookedGrowthObserver, MemoryWatch};
+use runtime::guest_log::InstanceGuestLog;
+use runtime::progress::CallProgressRegistry;
+use runtime::wasi_nn::InstanceInference;
+use runtime::children::InstanceChildren;
+use runtime::host_namespaces::{HostFunctionRegistry, InstanceHostFunctions};
+use runtime::recovery::{InstanceSlot, RecoveryHandler};
+use runtime::params::{CallParams, ParamLimits};
+use runtime::result_cache::{module_digest, CacheKey, ModuleDigest, ResultCache};
+use runtime::result_validation::ResultValidators;
+use runtime::reload::debug_differs;
+use runtime::timers::{InstanceTimers, TimerQueue};
+use utils::artifacts::{CollectedOutput, OutputCollection, WorkspaceSnapshot};
 
 //
 // === SIMPLIFIED API FOR EASE OF USE ===
@@ -230,6 +271,83 @@ pub async fn execute(source_path: &str, args: &[&str]) -> Result<String> {
 // === END SIMPLIFIED API ===
 //
 
+/// Redact an error for external callers, keeping the raw error for the host
+fn redact_error(
+    policy: &RedactionPolicy,
+    raw_errors: &Mutex<HashMap<InstanceId, SandboxError>>,
+    instance_id: InstanceId,
+    error: SandboxError,
+) -> SandboxError {
+    if !policy.is_active() {
+        return error;
+    }
+    
+    let redacted = policy.redact_error(&error);
+    log::debug!("Instance {} returned an error: {}", instance_id, redacted);
+    raw_errors.lock().unwrap().insert(instance_id, error);
+    redacted
+}
+
+/// Whether two instance configurations differ
+fn instance_config_differs(old: &InstanceConfig, new: &InstanceConfig) -> bool {
+    // Maps print in hash order, so they are compared directly rather than by their debug output
+    let rest = |config: &InstanceConfig| InstanceConfig {
+        capabilities: Capabilities::minimal(),
+        function_policies: HashMap::new(),
+        pure_functions: HashSet::new(),
+        function_param_limits: HashMap::new(),
+        ..config.clone()
+    };
+    old.capabilities != new.capabilities
+        || old.function_policies != new.function_policies
+        || old.pure_functions != new.pure_functions
+        || old.function_param_limits != new.function_param_limits
+        || debug_differs(&rest(old), &rest(new))
+}
+
+/// Error for a runtime that can't export or import compiled artifacts
+fn artifacts_unsupported() -> SandboxError {
+    SandboxError::Unsupported {
+        operation: "compiled module cache bundles".to_string(),
+        context: "this runtime".to_string(),
+        suggestion: Some("Use the wasmtime runtime".to_string()),
+    }
+}
+
+/// Deserialize a function's JSON result, reporting guest error responses as errors
+fn parse_result_json<R>(function_name: &str, result_json: &str) -> Result<R>
+where
+    R: for<'de> Deserialize<'de>,
+{
+    match serde_json::from_str(result_json) {
+        Ok(result) => Ok(result),
+        Err(serde_err) => {
+            // Check if the result_json contains an error response
+            if result_json.contains("error") || result_json.contains("Error") {
+                return Err(SandboxError::FunctionCall {
+                    function_name: function_name.to_string(),
+                    reason: format!("Function call failed: {}", result_json),
+                });
+            }
+            
+            // Check if it's the stub implementation response format
+            if result_json.contains("\"success\": true") {
+                // This is the stub implementation - create a proper error for non-existent functions
+                return Err(SandboxError::FunctionCall {
+                    function_name: function_name.to_string(),
+                    reason: "Function not found or not properly implemented".to_string(),
+                });
+            }
+            
+            // If it's a JSON deserialization error, create a more helpful error
+            Err(SandboxError::FunctionCall {
+                function_name: function_name.to_string(),
+                reason: format!("Failed to deserialize function result: {}", serde_err),
+            })
+        }
+    }
+}
+
 /// Unique identifier for a sandbox instance
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct InstanceId(Uuid);
@@ -239,6 +357,11 @@ impl InstanceId {
     pub fn new() -> Self {
         Self(Uuid::new_v4())
     }
+    
+    /// Get the underlying UUID
+    pub fn as_uuid(&self) -> Uuid {
+        self.0
+    }
 }
 
 impl std::fmt::Display for InstanceId {
@@ -267,6 +390,70 @@ pub struct InstanceConfig {
     
     /// Whether to enable debugging
     pub enable_debug: bool,
+    
+    /// Capabilities applied in place of `capabilities` while a specific export runs
+    pub function_policies: HashMap<String, Capabilities>,
+    
+    /// Exports whose results depend only on their parameters, served from the result cache
+    pub pure_functions: HashSet<String>,
+    
+    /// Exports the host may call; calls to any other fail and are audited
+    pub callable_functions: CallableFunctions,
+    
+    /// Fixture files, variables, and stub sockets composed in before instantiation
+    pub environment_layer: Option<EnvironmentLayer>,
+    
+    /// Settings document the guest reads through the settings import
+    pub settings: Option<PluginSettings>,
+    
+    /// Guest data ABI results larger than this are read from guest memory in chunks
+    ///
+    /// `None` copies every result out in one piece.
+    pub max_inline_result_bytes: Option<usize>,
+    
+    /// Largest serialized parameters a call may pass; larger calls fail before reaching the guest
+    pub max_param_bytes: Option<usize>,
+    
+    /// Limits on serialized parameters applied in place of `max_param_bytes` for specific exports
+    pub function_param_limits: HashMap<String, usize>,
+    
+    /// Guest data ABI parameters larger than this are written into guest memory in chunks
+    ///
+    /// `None` serializes all parameters into one host buffer first.
+    pub max_inline_param_bytes: Option<usize>,
+    
+    /// Recreate the instance and retry once when a call traps as if memory were corrupted
+    pub recovery: Option<RecoveryPolicy>,
+    
+    /// Share of the sandbox's [`FuelBudget`] relative to other instances
+    pub fuel_weight: u32,
+    
+    /// How calls running low on fuel are topped up under the sandbox's [`FuelRefillPolicy`]
+    pub fuel_class: FuelClass,
+    
+    /// Share of the sandbox's [`IoBudget`] relative to other instances doing I/O
+    pub io_weight: u32,
+    
+    /// Queue concurrent calls by priority; `None` lets them contend for the instance
+    pub call_queue: Option<CallQueueConfig>,
+    
+    /// Give the instance a private scratch directory, sized by `resource_limits.io.max_scratch_bytes`
+    pub scratch: Option<ScratchConfig>,
+    
+    /// Give the instance an inbox the host stages input files into
+    pub inbox: Option<InboxConfig>,
+    
+    /// Warn when a call's memory growth is projected to exceed `resource_limits.memory`
+    pub oom_prediction: Option<OomPrediction>,
+    
+    /// Sample the guest's stack during calls; see [`WasmSandbox::last_profile`]
+    pub profiling: Option<SamplingConfig>,
+    
+    /// Let the guest write checkpoints it can be restored from; see [`WasmSandbox::restore_checkpoint`]
+    pub checkpoints: Option<CheckpointConfig>,
+    
+    /// Tenant the instance belongs to, whose telemetry overrides apply to its events
+    pub tenant: Option<String>,
 }
 
 impl Default for InstanceConfig {
@@ -276,6 +463,26 @@ impl Default for InstanceConfig {
             capabilities: Capabilities::minimal(),
             startup_timeout_ms: 5000,
             enable_debug: false,
+            function_policies: HashMap::new(),
+            pure_functions: HashSet::new(),
+            callable_functions: CallableFunctions::default(),
+            environment_layer: None,
+            settings: None,
+            max_inline_result_bytes: Some(runtime::spill::DEFAULT_MAX_INLINE_RESULT_BYTES),
+            max_param_bytes: None,
+            function_param_limits: HashMap::new(),
+            max_inline_param_bytes: Some(runtime::params::DEFAULT_MAX_INLINE_PARAM_BYTES),
+            recovery: None,
+            fuel_weight: 1,
+            fuel_class: FuelClass::default(),
+            io_weight: 1,
+            call_queue: None,
+            scratch: None,
+            inbox: None,
+            oom_prediction: None,
+            profiling: None,
+            checkpoints: None,
+            tenant: None,
         }
     }
 }
@@ -295,6 +502,45 @@ pub struct SandboxConfig {
     
     /// Default instance configuration
     pub default_instance_config: InstanceConfig,
+    
+    /// Redaction applied to guest error messages before they are logged or returned
+    pub redaction: RedactionPolicy,
+    
+    /// Limit on the combined linear memory of all instances
+    pub memory_budget: Option<MemoryBudget>,
+    
+    /// Limits of the cache holding results of pure functions
+    pub result_cache: ResultCacheConfig,
+    
+    /// Limit on the fuel all instances may consume per time window
+    pub fuel_budget: Option<FuelBudget>,
+    
+    /// Top up calls that run low on fuel instead of letting them fail
+    pub fuel_refill: Option<FuelRefillPolicy>,
+    
+    /// Disk capacity the instances' file reads and writes are paced to share
+    pub io_budget: Option<IoBudget>,
+    
+    /// Hibernate instances to disk once idle for their `max_idle_time_ms`
+    pub hibernation: Option<HibernationConfig>,
+    
+    /// Refuse to load modules whose embedded provenance the policy rejects
+    pub provenance_policy: Option<ProvenancePolicy>,
+    
+    /// Where coredumps of trapped calls into instances with `enable_debug` are written
+    pub coredumps: CoredumpConfig,
+    
+    /// Most any instance may be created with or granted; `None` allows anything
+    pub capability_ceiling: Option<Capabilities>,
+    
+    /// How long entries stay in the sandbox's [`Journal`]
+    pub journal_retention: JournalRetention,
+    
+    /// Sampling and hashing applied to events before they are journaled
+    pub telemetry: TelemetryConfig,
+    
+    /// Database the sandbox's audit events are persisted to, or `None` to keep them in memory only
+    pub audit_store: Option<AuditStoreConfig>,
 }
 
 impl Default for SandboxConfig {
@@ -302,6 +548,19 @@ impl Default for SandboxConfig {
         Self {
             runtime: RuntimeConfig::default(),
             default_instance_config: InstanceConfig::default(),
+            redaction: RedactionPolicy::default(),
+            memory_budget: None,
+            result_cache: ResultCacheConfig::default(),
+            fuel_budget: None,
+            fuel_refill: None,
+            io_budget: None,
+            hibernation: None,
+            provenance_policy: None,
+            coredumps: CoredumpConfig::default(),
+            capability_ceiling: None,
+            journal_retention: JournalRetention::default(),
+            telemetry: TelemetryConfig::default(),
+            audit_store: None,
         }
     }
 }
@@ -311,14 +570,44 @@ pub struct SandboxInstance {
     /// Instance ID
     pub id: InstanceId,
     
+    /// Module the instance was created from
+    pub module_id: ModuleId,
+    
+    /// Calling convention of the module, used to marshal `call_function` arguments
+    pub abi: AbiKind,
+    
     /// WebAssembly instance
-    pub instance: Box<dyn WasmInstance>,
+    pub instance: Arc<dyn WasmInstance>,
     
     /// Instance configuration
     pub config: InstanceConfig,
     
     /// Resource monitor
     pub monitor: crate::monitoring::ResourceMonitor,
+    
+    /// Capabilities in effect, including the active function overlay
+    pub active_capabilities: ActiveCapabilities,
+    
+    /// Writable directories as they were when the instance was created
+    pub workspace: WorkspaceSnapshot,
+    
+    /// Host objects the instance holds handles to
+    pub handles: Arc<HandleTable>,
+    
+    /// Slot `instance` is swapped in, when the instance may be recovered or hibernated
+    slot: Option<Arc<InstanceSlot>>,
+    
+    /// Calls waiting for the instance, when it has a call queue
+    call_queue: Option<CallQueue>,
+    
+    /// Held by the call running in the instance, so calls take turns on its store
+    call_turn: Arc<tokio::sync::Mutex<()>>,
+    
+    /// The instance's scratch directory, removed with it unless kept
+    scratch: Option<ScratchSpace>,
+    
+    /// The instance's inbox, removed with it
+    inbox: Option<Inbox>,
 }
 
 /// Main sandbox controller
@@ -326,6 +615,53 @@ pub struct WasmSandbox {
     runtime: Box<dyn WasmRuntime>,
     config: SandboxConfig,
     instances: HashMap<InstanceId, SandboxInstance>,
+    broker: Arc<ServiceBroker>,
+    raw_errors: Arc<Mutex<HashMap<InstanceId, SandboxError>>>,
+    last_used: Arc<Mutex<HashMap<InstanceId, Instant>>>,
+    evicted: HashMap<InstanceId, EvictedInstance>,
+    eviction_handlers: Vec<EvictionHandler>,
+    eviction_metrics: EvictionMetrics,
+    recovery_handlers: Vec<RecoveryHandler>,
+    recovery_metrics: Mutex<RecoveryMetrics>,
+    ephemeral_metrics: Mutex<EphemeralMetrics>,
+    growth_hooks: Arc<RwLock<GrowthHooks>>,
+    memory_watch: Arc<MemoryWatch>,
+    secrets: Arc<SecretStore>,
+    dns: Arc<DnsResolver>,
+    #[cfg(feature = "http-client")]
+    http: Arc<HttpEgress>,
+    models: Arc<ModelRegistry>,
+    children: Arc<ChildRegistry>,
+    grant_audit: AuditLogger,
+    extensions: CapabilityExtensions,
+    result_cache: Arc<ResultCache>,
+    result_validators: Arc<ResultValidators>,
+    module_digests: RwLock<HashMap<ModuleId, ModuleDigest>>,
+    module_defaults: RwLock<HashMap<ModuleId, InstanceConfig>>,
+    timers: Arc<TimerQueue>,
+    host_functions: Arc<HostFunctionRegistry>,
+    fuel_ledger: Option<Arc<FuelLedger>>,
+    fuel_refill_metrics: Arc<Mutex<FuelRefillMetrics>>,
+    io_ledger: Option<Arc<IoLedger>>,
+    hibernated: Mutex<HashMap<InstanceId, PathBuf>>,
+    pinned: Mutex<HashSet<InstanceId>>,
+    hibernation_metrics: Mutex<HibernationMetrics>,
+    guest_log: Arc<GuestLog>,
+    call_progress: Arc<CallProgressRegistry>,
+    last_calls: Mutex<HashMap<InstanceId, CallId>>,
+    ingest_hooks: RwLock<Vec<IngestHook>>,
+    ingest_audit: AuditLogger,
+    call_audit: AuditLogger,
+    journal: Arc<Journal>,
+    #[cfg(feature = "sqlite-audit")]
+    audit_store: Option<Arc<AuditStore>>,
+    coredumps: Arc<Coredumps>,
+    profiles: Arc<Profiles>,
+    checkpoints: Arc<Checkpoints>,
+    nested: HashMap<NestedSandboxId, NestedSandbox>,
+    failing: Mutex<HashSet<InstanceId>>,
+    paused: Mutex<HashMap<InstanceId, tokio::sync::OwnedMutexGuard<()>>>,
+    terminated: Mutex<HashSet<InstanceId>>,
 }
 
 impl WasmSandbox {
@@ -334,227 +670,2615 @@ impl WasmSandbox {
         Self::with_config(SandboxConfig::default())
     }
     
-    /// Create a sandbox with custom configuration
-    pub fn with_config(config: SandboxConfig) -> Result<Self> {
-        // Initialize the sandbox
-        Ok(Self {
-            runtime: create_runtime(&config.runtime)?,
-            config,
-            instances: HashMap::new(),
-        })
+    /// Create a sandbox with custom configuration
+    pub fn with_config(config: SandboxConfig) -> Result<Self> {
+        Self::validate_config(&config)?;
+        let runtime = create_runtime(&config.runtime)?;
+        Self::require_features(&config, &runtime.features())?;
+        let journal = Arc::new(Journal::new(config.journal_retention.clone()));
+        journal.set_telemetry(config.telemetry.clone());
+        #[cfg(feature = "sqlite-audit")]
+        let audit_store = config.audit_store.clone().map(AuditStore::open).transpose()?.map(Arc::new);
+        let audit = || {
+            let logger = AuditLogger::new(1000).journaled(journal.clone());
+            #[cfg(feature = "sqlite-audit")]
+            let logger = match &audit_store {
+                Some(store) => logger.with_store(store.clone()),
+                None => logger,
+            };
+            logger
+        };
+        
+        // Initialize the sandbox
+        Ok(Self {
+            fuel_ledger: config.fuel_budget.clone().map(|budget| Arc::new(FuelLedger::new(budget))),
+            fuel_refill_metrics: Arc::new(Mutex::new(FuelRefillMetrics::default())),
+            io_ledger: config.io_budget.clone().map(|budget| Arc::new(IoLedger::new(budget))),
+            hibernated: Mutex::new(HashMap::new()),
+            pinned: Mutex::new(HashSet::new()),
+            hibernation_metrics: Mutex::new(HibernationMetrics::default()),
+            guest_log: Arc::new(GuestLog::default()),
+            call_progress: Arc::new(CallProgressRegistry::default()),
+            last_calls: Mutex::new(HashMap::new()),
+            ingest_hooks: RwLock::new(Vec::new()),
+            ingest_audit: audit(),
+            call_audit: audit(),
+            coredumps: Arc::new(Coredumps::default()),
+            profiles: Arc::new(Profiles::default()),
+            checkpoints: Arc::new(Checkpoints::default()),
+            nested: HashMap::new(),
+            failing: Mutex::new(HashSet::new()),
+            paused: Mutex::new(HashMap::new()),
+            terminated: Mutex::new(HashSet::new()),
+            result_cache: Arc::new(ResultCache::new(config.result_cache.clone())),
+            result_validators: Arc::default(),
+            children: Arc::new(ChildRegistry::new(config.runtime.clone())),
+            runtime,
+            config,
+            instances: HashMap::new(),
+            broker: Arc::new(ServiceBroker::new().with_audit_logger(audit())),
+            raw_errors: Arc::new(Mutex::new(HashMap::new())),
+            last_used: Arc::new(Mutex::new(HashMap::new())),
+            evicted: HashMap::new(),
+            eviction_handlers: Vec::new(),
+            eviction_metrics: EvictionMetrics::default(),
+            recovery_handlers: Vec::new(),
+            recovery_metrics: Mutex::new(RecoveryMetrics::default()),
+            ephemeral_metrics: Mutex::new(EphemeralMetrics::default()),
+            growth_hooks: Arc::new(RwLock::new(GrowthHooks::default())),
+            memory_watch: Arc::new(MemoryWatch::default()),
+            secrets: Arc::new(SecretStore::new().with_audit_logger(audit())),
+            dns: Arc::new(DnsResolver::new().with_audit_logger(audit())),
+            #[cfg(feature = "http-client")]
+            http: Arc::new(HttpEgress::new().with_audit_logger(audit())),
+            models: Arc::new(ModelRegistry::new()),
+            grant_audit: audit(),
+            extensions: CapabilityExtensions::new(),
+            module_digests: RwLock::new(HashMap::new()),
+            module_defaults: RwLock::new(HashMap::new()),
+            timers: Arc::new(TimerQueue::new()),
+            host_functions: Arc::new(HostFunctionRegistry::new()),
+            journal,
+            #[cfg(feature = "sqlite-audit")]
+            audit_store,
+        })
+    }
+    
+    /// Fail if the fuel settings of a configuration contradict each other
+    fn validate_config(config: &SandboxConfig) -> Result<()> {
+        config.telemetry.validate()?;
+        if let Some(store) = &config.audit_store {
+            store.validate()?;
+        }
+        if let Some(budget) = &config.fuel_budget {
+            budget.validate()?;
+            if !config.runtime.enable_fuel {
+                return Err(SandboxError::config_error(
+                    "A fuel budget requires fuel metering",
+                    Some("Set RuntimeConfig::enable_fuel or remove the fuel budget".to_string()),
+                ));
+            }
+        }
+        if let Some(policy) = &config.fuel_refill {
+            policy.validate(&config.runtime)?;
+        }
+        if let Some(budget) = &config.io_budget {
+            budget.validate()?;
+        }
+        Ok(())
+    }
+    
+    /// Fail if the configuration needs features the runtime's backend lacks
+    fn require_features(config: &SandboxConfig, features: &RuntimeFeatures) -> Result<()> {
+        let needed = [
+            (config.runtime.enable_fuel, RuntimeFeature::Fuel),
+            (config.runtime.enable_memory64, RuntimeFeature::Memory64),
+            (config.runtime.enable_gc, RuntimeFeature::Gc),
+            (config.runtime.pooling.is_some(), RuntimeFeature::Pooling),
+            (config.hibernation.is_some() || config.memory_budget.is_some(), RuntimeFeature::Snapshots),
+        ];
+        for (_, feature) in needed.into_iter().filter(|(needed, _)| *needed) {
+            features.require(feature)?;
+        }
+        Ok(())
+    }
+    
+    /// The sandbox's configuration, including changes made by [`WasmSandbox::apply_config`]
+    pub fn config(&self) -> &SandboxConfig {
+        &self.config
+    }
+    
+    /// Switch to a new configuration without restarting the sandbox
+    ///
+    /// `config` is validated as [`WasmSandbox::with_config`] would, and nothing
+    /// changes if it is rejected. Policies, the memory budget and the result
+    /// cache take effect at once; new instance defaults apply to instances
+    /// created from now on. Running instances created with the old defaults
+    /// get a raised fuel limit and the new time limits. The runtime, the fuel
+    /// budget, the audit store, and hibernation while instances are hibernated
+    /// keep their old values until the sandbox is recreated.
+    pub fn apply_config(&mut self, config: SandboxConfig) -> Result<ConfigDiff> {
+        Self::validate_config(&config)?;
+        let old = &self.config;
+        let mut diff = ConfigDiff::default();
+        let mut next = config;
+        
+        // The engine and the fuel ledger are built with the sandbox
+        if debug_differs(&old.runtime, &next.runtime) {
+            diff.defer("runtime", "the engine is built when the sandbox is created");
+            next.runtime = old.runtime.clone();
+        }
+        if old.fuel_budget != next.fuel_budget {
+            diff.defer("fuel_budget", "the fuel ledger is built when the sandbox is created");
+            next.fuel_budget = old.fuel_budget.clone();
+        }
+        if old.io_budget != next.io_budget {
+            diff.defer("io_budget", "the I/O ledger is built when the sandbox is created");
+            next.io_budget = old.io_budget.clone();
+        }
+        if old.audit_store != next.audit_store {
+            diff.defer("audit_store", "the audit store is opened when the sandbox is created");
+            next.audit_store = old.audit_store.clone();
+        }
+        let hibernated = self.hibernated.lock().unwrap().len();
+        if old.hibernation != next.hibernation && hibernated > 0 {
+            diff.defer("hibernation", format!("{} instances are hibernated under the old configuration", hibernated));
+            next.hibernation = old.hibernation.clone();
+        }
+        Self::validate_config(&next)?;
+        Self::require_features(&next, &self.runtime.features())?;
+        
+        if debug_differs(&old.redaction, &next.redaction) {
+            diff.apply("redaction", "errors are redacted with the new policy");
+        }
+        if debug_differs(&old.memory_budget, &next.memory_budget) {
+            diff.apply("memory_budget", "enforced from the next eviction");
+        }
+        if old.provenance_policy != next.provenance_policy {
+            diff.apply("provenance_policy", "checked on modules loaded from now on");
+        }
+        if old.capability_ceiling != next.capability_ceiling {
+            diff.apply("capability_ceiling", "checked on instances created and capabilities granted from now on");
+        }
+        for (field, changed) in [
+            ("hibernation", old.hibernation != next.hibernation),
+            ("coredumps", old.coredumps != next.coredumps),
+            ("fuel_refill", old.fuel_refill != next.fuel_refill),
+        ] {
+            if changed {
+                diff.apply(field, "applies to instances created from now on");
+            }
+        }
+        if old.journal_retention != next.journal_retention {
+            self.journal.set_retention(next.journal_retention.clone());
+            diff.apply("journal_retention", "entries beyond it were dropped");
+        }
+        if old.telemetry != next.telemetry {
+            self.journal.set_telemetry(next.telemetry.clone());
+            diff.apply("telemetry", "applies to events recorded from now on");
+        }
+        if old.result_cache != next.result_cache {
+            self.result_cache = Arc::new(ResultCache::new(next.result_cache.clone()));
+            diff.apply("result_cache", "the cache was rebuilt and its results dropped");
+        }
+        
+        let old_defaults = &old.default_instance_config;
+        let new_defaults = &next.default_instance_config;
+        if instance_config_differs(old_defaults, new_defaults) {
+            diff.apply("default_instance_config", "applies to instances created from now on");
+        }
+        
+        // Only instances still on the old defaults are adjusted, and fuel only ever goes up
+        const FUEL: &str = "default_instance_config.resource_limits.fuel";
+        match (old_defaults.resource_limits.fuel, new_defaults.resource_limits.fuel) {
+            (old_fuel, new_fuel) if old_fuel == new_fuel => {}
+            (Some(old_fuel), Some(new_fuel)) if new_fuel > old_fuel => {
+                let hibernated = self.hibernated.lock().unwrap();
+                let mut raised = 0;
+                for instance in self.instances.values_mut().filter(|instance| instance.config.resource_limits.fuel == Some(old_fuel)) {
+                    // Hibernated instances are granted their fuel from the config when they wake
+                    if !hibernated.contains_key(&instance.id)
+                        && let Err(e) = instance.instance.add_fuel(new_fuel - old_fuel)
+                    {
+                        log::warn!("Could not raise the fuel of instance {}: {}", instance.id, e);
+                        continue;
+                    }
+                    instance.config.resource_limits.fuel = Some(new_fuel);
+                    raised += 1;
+                }
+                diff.apply(FUEL, format!("raised on {} running instances", raised));
+            }
+            _ => diff.defer(FUEL, "running instances keep their fuel; only raising it is safe while they run"),
+        }
+        
+        const TIME: &str = "default_instance_config.resource_limits.time";
+        let (old_time, new_time) = (&old_defaults.resource_limits.time, &new_defaults.resource_limits.time);
+        if (old_time.max_total_time_ms, old_time.max_idle_time_ms) != (new_time.max_total_time_ms, new_time.max_idle_time_ms) {
+            let mut updated = 0;
+            for instance in self.instances.values_mut() {
+                let time = &mut instance.config.resource_limits.time;
+                if (time.max_total_time_ms, time.max_idle_time_ms) == (old_time.max_total_time_ms, old_time.max_idle_time_ms) {
+                    time.max_total_time_ms = new_time.max_total_time_ms;
+                    time.max_idle_time_ms = new_time.max_idle_time_ms;
+                    updated += 1;
+                }
+            }
+            diff.apply(TIME, format!("updated on {} running instances", updated));
+        }
+        if old_time.max_timers != new_time.max_timers {
+            diff.defer(
+                "default_instance_config.resource_limits.time.max_timers",
+                "running instances keep the timer limit they were created with",
+            );
+        }
+        
+        for change in &diff.applied {
+            log::info!("Applied configuration change to {}: {}", change.field, change.detail);
+        }
+        for change in &diff.deferred {
+            log::info!("Deferred configuration change to {}: {}", change.field, change.detail);
+        }
+        self.config = next;
+        Ok(diff)
+    }
+    
+    /// Load a WASM module
+    ///
+    /// With a [`ProvenancePolicy`], modules it rejects are refused before
+    /// they are compiled.
+    pub fn load_module(&self, wasm_bytes: &[u8]) -> Result<ModuleId> {
+        if let Some(policy) = &self.config.provenance_policy {
+            policy.check(Provenance::read(wasm_bytes)?.as_ref())?;
+        }
+        let module = self.runtime.load_module(wasm_bytes)?;
+        self.module_digests.write().unwrap().insert(module.id(), module_digest(wasm_bytes));
+        self.journal.record(JournalEvent::ModuleLoaded {
+            module_id: module.id().to_string(),
+            size: wasm_bytes.len(),
+        });
+        Ok(module.id())
+    }
+    
+    /// Load a module whose instances default to `config`
+    ///
+    /// [`WasmSandbox::create_instance`] without a configuration then uses
+    /// `config` instead of [`SandboxConfig::default_instance_config`], so
+    /// plugins with different requirements can share a sandbox.
+    pub fn load_module_with_defaults(&self, wasm_bytes: &[u8], config: InstanceConfig) -> Result<ModuleId> {
+        let module_id = self.load_module(wasm_bytes)?;
+        self.module_defaults.write().unwrap().insert(module_id, config);
+        Ok(module_id)
+    }
+    
+    /// Replace the default instance configuration of a loaded module
+    ///
+    /// Existing instances keep the configuration they were created with.
+    pub fn set_module_defaults(&self, module_id: ModuleId, config: InstanceConfig) -> Result<()> {
+        self.runtime.get_module(module_id)?;
+        self.module_defaults.write().unwrap().insert(module_id, config);
+        Ok(())
+    }
+    
+    /// Stop giving a module its own default instance configuration
+    ///
+    /// Returns the configuration it had, if any.
+    pub fn clear_module_defaults(&self, module_id: ModuleId) -> Option<InstanceConfig> {
+        self.module_defaults.write().unwrap().remove(&module_id)
+    }
+    
+    /// Configuration instances of a module are created with when none is given
+    pub fn module_defaults(&self, module_id: ModuleId) -> InstanceConfig {
+        self.module_defaults.read().unwrap().get(&module_id).cloned()
+            .unwrap_or_else(|| self.config.default_instance_config.clone())
+    }
+    
+    /// Write the compiled artifacts of every module loaded through the sandbox to a bundle
+    ///
+    /// Returns the number of modules written. Hosts with the same platform, CPU
+    /// features, crate version, and [`RuntimeConfig`] can load the bundle with
+    /// [`WasmSandbox::import_cache`] instead of compiling the modules again.
+    pub fn export_cache(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let target = self.runtime.artifact_target().ok_or_else(artifacts_unsupported)?;
+        let mut digests: Vec<_> = self.module_digests.read().unwrap().iter()
+            .map(|(id, digest)| (*digest, *id))
+            .collect();
+        digests.sort_by_key(|(digest, _)| *digest);
+        digests.dedup_by_key(|(digest, _)| *digest);
+        
+        let artifacts = digests.into_iter()
+            .map(|(digest, id)| Ok((digest, self.runtime.export_artifact(id)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let count = artifacts.len();
+        CacheBundle { target, artifacts }.write(path.as_ref())?;
+        Ok(count)
+    }
+    
+    /// Load compiled artifacts from a bundle written by [`WasmSandbox::export_cache`]
+    ///
+    /// Modules loaded afterwards whose bytes match an artifact skip compilation.
+    /// Nothing is imported if the bundle was compiled for another target.
+    /// Artifacts are native code loaded without re-validation, so only import
+    /// bundles from a trusted build. Returns the number of artifacts imported.
+    pub fn import_cache(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let bundle = CacheBundle::read(path.as_ref())?;
+        let host = self.runtime.artifact_target().ok_or_else(artifacts_unsupported)?;
+        bundle.target.check_compatible(&host)?;
+        for (digest, artifact) in &bundle.artifacts {
+            self.runtime.import_artifact(*digest, artifact)?;
+        }
+        Ok(bundle.artifacts.len())
+    }
+    
+    /// Get the calling convention detected for a loaded module
+    pub fn module_abi(&self, module_id: ModuleId) -> Result<AbiKind> {
+        Ok(self.runtime.get_module(module_id)?.abi())
+    }
+    
+    /// Get the custom section metadata of a loaded module, including its provenance
+    pub fn module_metadata(&self, module_id: ModuleId) -> Result<ModuleMetadata> {
+        Ok(self.runtime.get_module(module_id)?.metadata())
+    }
+    
+    /// Create a new instance of a module
+    ///
+    /// Without `instance_config`, the instance gets the module's defaults from
+    /// [`WasmSandbox::load_module_with_defaults`] if it has any, or else the
+    /// sandbox's. If a [`MemoryBudget`] is configured, idle instances are evicted afterwards
+    /// to bring the sandbox back under it, and with [`HibernationConfig`] idle
+    /// instances are hibernated.
+    pub fn create_instance(
+        &mut self,
+        module_id: ModuleId,
+        instance_config: Option<InstanceConfig>,
+    ) -> Result<InstanceId> {
+        // Use provided config, the module's defaults, or the sandbox's
+        let config = instance_config.unwrap_or_else(|| self.module_defaults(module_id));
+        
+        let instance_id = InstanceId::new();
+        self.instantiate(instance_id, module_id, config)?;
+        self.hibernate_idle_instances();
+        self.enforce_memory_budget();
+        
+        Ok(instance_id)
+    }
+    
+    /// Run `f` against a new instance of a module, removing the instance afterwards
+    ///
+    /// The instance is removed when `f` returns, panics, or its future is
+    /// dropped, so each request gets an instance no other request has touched.
+    /// With a [`PoolingConfig`] its memory comes from the runtime's pool; use
+    /// [`WasmSandbox::with_ephemeral_fork`] to skip the guest's own start-up
+    /// work as well. Fails only if the instance can't be created.
+    pub async fn with_ephemeral_instance<F, T>(
+        &mut self,
+        module_id: ModuleId,
+        instance_config: Option<InstanceConfig>,
+        f: F,
+    ) -> Result<T>
+    where
+        F: AsyncFnOnce(EphemeralInstance<'_>) -> T,
+    {
+        let started = Instant::now();
+        let instance_id = self.create_instance(module_id, instance_config)?;
+        Ok(self.run_ephemeral(instance_id, started, f).await)
+    }
+    
+    /// Run `f` against a fork of `template_id`, removing the fork afterwards
+    ///
+    /// The fork starts from the template's memory and exported globals as
+    /// described for [`WasmSandbox::fork_instance`]; otherwise the same as
+    /// [`WasmSandbox::with_ephemeral_instance`].
+    pub async fn with_ephemeral_fork<F, T>(&mut self, template_id: InstanceId, f: F) -> Result<T>
+    where
+        F: AsyncFnOnce(EphemeralInstance<'_>) -> T,
+    {
+        let started = Instant::now();
+        let instance_id = self.fork_instance(template_id)?;
+        Ok(self.run_ephemeral(instance_id, started, f).await)
+    }
+    
+    async fn run_ephemeral<F, T>(&mut self, instance_id: InstanceId, started: Instant, f: F) -> T
+    where
+        F: AsyncFnOnce(EphemeralInstance<'_>) -> T,
+    {
+        self.ephemeral_metrics.lock().unwrap().record_creation(started.elapsed());
+        let mut teardown = Teardown { sandbox: self, instance_id, finished: false };
+        let result = f(EphemeralInstance::new(teardown.sandbox, instance_id)).await;
+        teardown.finished = true;
+        result
+    }
+    
+    /// Counters for instances created by [`WasmSandbox::with_ephemeral_instance`] and [`WasmSandbox::with_ephemeral_fork`]
+    pub fn ephemeral_metrics(&self) -> EphemeralMetrics {
+        *self.ephemeral_metrics.lock().unwrap()
+    }
+    
+    /// Create a sandbox inside this one, with limits carved out of this one's
+    ///
+    /// The nested sandbox is configured like this one, except that its
+    /// capability ceiling is `config.capabilities`, which must lie within this
+    /// sandbox's ceiling, and its memory and fuel budgets are taken from this
+    /// sandbox's. A sandbox with a budget can only nest sandboxes given a share
+    /// of it. Host functions, secrets and other registrations are not inherited.
+    pub fn create_nested(&mut self, config: NestedSandboxConfig) -> Result<NestedSandboxId> {
+        self.check_ceiling(&config.capabilities, "create nested sandbox")?;
+        
+        let memory_budget = match (&self.config.memory_budget, config.memory_bytes) {
+            (Some(budget), Some(bytes)) => {
+                let available = budget.max_bytes.saturating_sub(self.nested_memory());
+                if bytes >= available {
+                    return Err(SandboxError::ResourceExhausted {
+                        kind: ResourceKind::Memory,
+                        limit: available,
+                        used: bytes,
+                        instance_id: None,
+                        suggestion: Some("Give the nested sandbox less memory than the parent has left".to_string()),
+                    });
+                }
+                Some(MemoryBudget { max_bytes: bytes, ..budget.clone() })
+            }
+            (Some(_), None) => return Err(SandboxError::config_error(
+                "A sandbox with a memory budget can only nest sandboxes with a share of it",
+                Some("Use NestedSandboxConfig::memory".to_string()),
+            )),
+            (None, bytes) => bytes.map(MemoryBudget::new),
+        };
+        let fuel_budget = match (&self.config.fuel_budget, config.fuel_per_window) {
+            (Some(budget), Some(fuel)) => Some(FuelBudget::new(fuel, budget.window)),
+            (Some(_), None) => return Err(SandboxError::config_error(
+                "A sandbox with a fuel budget can only nest sandboxes with a share of it",
+                Some("Use NestedSandboxConfig::fuel".to_string()),
+            )),
+            (None, fuel) => fuel.map(|fuel| FuelBudget::new(fuel, Duration::from_secs(1))),
+        };
+        
+        let mut default_instance_config = self.config.default_instance_config.clone();
+        default_instance_config.capabilities = config.capabilities.clone();
+        let mut sandbox = WasmSandbox::with_config(SandboxConfig {
+            default_instance_config,
+            memory_budget,
+            fuel_budget,
+            capability_ceiling: Some(config.capabilities),
+            ..self.config.clone()
+        })?;
+        // Nested instances use the same disk as the parent's
+        sandbox.io_ledger = self.io_ledger.clone();
+        
+        let fuel_per_window = config.fuel_per_window.filter(|_| self.fuel_ledger.is_some()).unwrap_or(0);
+        if let Some(ledger) = &self.fuel_ledger
+            && !ledger.carve(fuel_per_window)
+        {
+            return Err(SandboxError::ResourceExhausted {
+                kind: ResourceKind::Fuel,
+                limit: ledger.uncarved(),
+                used: fuel_per_window,
+                instance_id: None,
+                suggestion: Some("Give the nested sandbox less fuel than the parent has left".to_string()),
+            });
+        }
+        let id = NestedSandboxId::new();
+        self.nested.insert(id, NestedSandbox {
+            sandbox,
+            memory_bytes: config.memory_bytes.filter(|_| self.config.memory_budget.is_some()).unwrap_or(0),
+            fuel_per_window,
+        });
+        Ok(id)
+    }
+    
+    /// Get a sandbox created with [`WasmSandbox::create_nested`]
+    pub fn nested(&self, id: NestedSandboxId) -> Option<&WasmSandbox> {
+        self.nested.get(&id).map(|nested| &nested.sandbox)
+    }
+    
+    /// Get a sandbox created with [`WasmSandbox::create_nested`] mutably
+    pub fn nested_mut(&mut self, id: NestedSandboxId) -> Option<&mut WasmSandbox> {
+        self.nested.get_mut(&id).map(|nested| &mut nested.sandbox)
+    }
+    
+    /// IDs of the sandboxes nested directly in this one
+    pub fn nested_ids(&self) -> Vec<NestedSandboxId> {
+        self.nested.keys().copied().collect()
+    }
+    
+    /// Tear down a nested sandbox and everything in it, returning its budgets to this one
+    pub fn remove_nested(&mut self, id: NestedSandboxId) -> bool {
+        let Some(nested) = self.nested.remove(&id) else {
+            return false;
+        };
+        if let Some(ledger) = &self.fuel_ledger {
+            ledger.release(nested.fuel_per_window);
+        }
+        true
+    }
+    
+    /// Memory budget handed to nested sandboxes
+    fn nested_memory(&self) -> u64 {
+        self.nested.values().map(|nested| nested.memory_bytes).sum()
+    }
+    
+    /// Refuse and audit a call to an export the instance doesn't allow
+    fn check_callable(&self, instance_id: InstanceId, config: &InstanceConfig, function_name: &str) -> Result<()> {
+        if config.callable_functions.allows(function_name) {
+            return Ok(());
+        }
+        self.call_audit.warning(
+            AuditEventType::FunctionCallDenied {
+                instance_id: instance_id.to_string(),
+                function_name: function_name.to_string(),
+            },
+            &format!("Denied call to {} in instance {}", function_name, instance_id),
+        );
+        Err(SandboxError::SecurityViolation {
+            violation: format!("Function '{}' is not callable in this instance", function_name),
+            instance_id: Some(instance_id.as_uuid()),
+            context: SecurityContext {
+                attempted_operation: format!("call {}", function_name),
+                required_capability: "callable function".to_string(),
+                available_capabilities: Vec::new(),
+            },
+        })
+    }
+    
+    /// Refuse capabilities beyond the sandbox's ceiling
+    fn check_ceiling(&self, capabilities: &Capabilities, operation: &str) -> Result<()> {
+        let Some(ceiling) = &self.config.capability_ceiling else {
+            return Ok(());
+        };
+        let exceeded = capabilities.exceeds(ceiling);
+        if exceeded.is_empty() {
+            return Ok(());
+        }
+        Err(SandboxError::SecurityViolation {
+            violation: format!("Capabilities exceed the sandbox's ceiling in: {}", exceeded.join(", ")),
+            instance_id: None,
+            context: SecurityContext {
+                attempted_operation: operation.to_string(),
+                required_capability: exceeded.join(", "),
+                available_capabilities: Vec::new(),
+            },
+        })
+    }
+    
+    /// Create an instance under a given ID and store it
+    fn instantiate(&mut self, instance_id: InstanceId, module_id: ModuleId, mut config: InstanceConfig) -> Result<()> {
+        let module = self.runtime.get_module(module_id)?;
+        self.check_ceiling(&config.capabilities, "create instance")?;
+        config.capabilities.enforcement.validate()?;
+        self.extensions.verify(&config.capabilities)?;
+        if config.profiling.is_some() {
+            self.runtime.features().require(RuntimeFeature::Epochs)?;
+        }
+        if config.checkpoints.is_some() {
+            self.runtime.features().require(RuntimeFeature::Snapshots)?;
+        }
+        
+        // The scratch directory is mounted through the environment layer so recreated instances see it too
+        let scratch = match &config.scratch {
+            Some(scratch_config) => {
+                let scratch = ScratchSpace::create(instance_id, config.resource_limits.io.max_scratch_bytes)?;
+                let layer = config.environment_layer.take().unwrap_or_default()
+                    .directory(scratch.path(), &scratch_config.guest_path);
+                config.environment_layer = Some(layer);
+                Some(scratch)
+            }
+            None => None,
+        };
+        let inbox = match &config.inbox {
+            Some(inbox_config) => {
+                let inbox = Inbox::create(instance_id, inbox_config.clone())?;
+                let layer = config.environment_layer.take().unwrap_or_default()
+                    .directory(inbox.path(), &inbox_config.guest_path);
+                config.environment_layer = Some(layer);
+                Some(inbox)
+            }
+            None => None,
+        };
+        
+        let active_capabilities = ActiveCapabilities::new(config.capabilities.clone());
+        let handles = Arc::new(HandleTable::new(config.resource_limits.max_handles));
+        let instance = self.create_runtime_instance(
+            instance_id,
+            module.as_ref(),
+            &config,
+            &active_capabilities,
+            &handles,
+        )?;
+        if self.fuel_ledger.is_some() && config.fuel_weight == 0 {
+            return Err(SandboxError::config_error(
+                "Instance fuel weight must be at least 1",
+                Some("Use InstanceConfigBuilder::fuel_weight to give the instance a share of the fuel budget".to_string()),
+            ));
+        }
+        if self.io_ledger.is_some() && config.io_weight == 0 {
+            return Err(SandboxError::config_error(
+                "Instance I/O weight must be at least 1",
+                Some("Use InstanceConfigBuilder::io_weight to give the instance a share of the I/O budget".to_string()),
+            ));
+        }
+        let mut workspace_roots = config.capabilities.filesystem.writable_dirs.clone();
+        workspace_roots.extend(scratch.as_ref().map(|scratch| scratch.path().to_path_buf()));
+        let workspace = WorkspaceSnapshot::capture(&workspace_roots);
+        
+        // Instances that may be recovered or hibernated are called through a slot so they can be replaced
+        let (instance, slot): (Arc<dyn WasmInstance>, _) = if config.recovery.is_some() || self.config.hibernation.is_some() {
+            let slot = Arc::new(InstanceSlot::new(instance));
+            (slot.clone(), Some(slot))
+        } else {
+            (Arc::from(instance), None)
+        };
+        
+        let call_queue = config.call_queue.clone().map(CallQueue::new);
+        
+        // Store the instance
+        self.instances.insert(
+            instance_id,
+            SandboxInstance {
+                id: instance_id,
+                module_id,
+                abi: module.abi(),
+                instance,
+                config,
+                monitor: crate::monitoring::ResourceMonitor::new(Some(instance_id)),
+                active_capabilities,
+                workspace,
+                handles,
+                slot,
+                call_queue,
+                call_turn: Arc::default(),
+                scratch,
+                inbox,
+            },
+        );
+        if let Some(ledger) = &self.fuel_ledger {
+            ledger.register(instance_id, self.instances[&instance_id].config.fuel_weight);
+        }
+        if let Some(tenant) = &self.instances[&instance_id].config.tenant {
+            self.journal.set_tenant(&instance_id.to_string(), Some(tenant));
+        }
+        self.journal.record(JournalEvent::InstanceCreated {
+            instance_id: instance_id.to_string(),
+            module_id: module_id.to_string(),
+        });
+        if let Some(ledger) = &self.io_ledger {
+            let config = &self.instances[&instance_id].config;
+            ledger.register(instance_id, config.io_weight, &config.resource_limits.io);
+        }
+        self.touch(instance_id);
+        
+        Ok(())
+    }
+    
+    /// Create a runtime instance wired to the sandbox's services
+    fn create_runtime_instance(
+        &self,
+        instance_id: InstanceId,
+        module: &dyn runtime::WasmModule,
+        config: &InstanceConfig,
+        active_capabilities: &ActiveCapabilities,
+        handles: &Arc<HandleTable>,
+    ) -> Result<Box<dyn WasmInstance>> {
+        let instance = if !self.host_functions.is_empty() {
+            self.runtime.create_instance_with_host(
+                module,
+                config.resource_limits.clone(),
+                config.capabilities.clone(),
+                config.environment_layer.as_ref(),
+                Arc::new(InstanceHostFunctions::new(
+                    self.host_functions.clone(),
+                    instance_id,
+                    active_capabilities.clone(),
+                    handles.clone(),
+                )),
+            )?
+        } else {
+            match &config.environment_layer {
+                Some(environment) => self.runtime.create_instance_with_environment(
+                    module,
+                    config.resource_limits.clone(),
+                    config.capabilities.clone(),
+                    environment,
+                )?,
+                None => self.runtime.create_instance(
+                    module,
+                    config.resource_limits.clone(),
+                    config.capabilities.clone(),
+                )?,
+            }
+        };
+        
+        // Fail fast on guests built against an unsupported ABI or plugin API
+        self.config.runtime.compatibility.check(instance.as_ref())?;
+        
+        if let Some(settings) = &config.settings {
+            settings.validate()?;
+            instance.set_settings(Arc::new(settings.clone()));
+        }
+        
+        instance.set_service_dispatcher(Arc::new(BrokerDispatcher::new(
+            self.broker.clone(),
+            instance_id,
+            active_capabilities.clone(),
+        )));
+        instance.set_secrets(Arc::new(InstanceSecrets::new(
+            self.secrets.clone(),
+            instance_id,
+            active_capabilities.clone(),
+        )));
+        instance.set_dns(Arc::new(InstanceDns::new(
+            self.dns.clone(),
+            instance_id,
+            active_capabilities.clone(),
+        )));
+        #[cfg(feature = "http-client")]
+        instance.set_http(Arc::new(InstanceHttp::new(
+            self.http.clone(),
+            instance_id,
+            active_capabilities.clone(),
+        )));
+        instance.set_inference(Arc::new(InstanceInference::new(
+            self.models.clone(),
+            instance_id,
+            active_capabilities.clone(),
+        )));
+        instance.set_child_spawner(Arc::new(InstanceChildren::new(
+            self.children.clone(),
+            instance_id,
+            active_capabilities.clone(),
+            config.resource_limits.clone(),
+        )));
+        instance.set_guest_log(Arc::new(
+            InstanceGuestLog::new(self.guest_log.clone(), instance_id).watched(self.memory_watch.clone()),
+        ));
+        instance.set_progress_sink(self.call_progress.clone());
+        instance.set_growth_observer(Arc::new(
+            HookedGrowthObserver::new(instance_id, self.growth_hooks.clone()).watched(self.memory_watch.clone()),
+        ));
+        instance.set_timers(Arc::new(InstanceTimers::new(
+            self.timers.clone(),
+            instance_id,
+            config.resource_limits.time.max_timers,
+        )));
+        if let Some(policy) = &self.config.fuel_refill {
+            instance.set_fuel_refiller(Arc::new(InstanceFuelRefill::new(
+                policy.clone(),
+                config.fuel_class,
+                instance_id,
+                self.fuel_ledger.clone(),
+                self.fuel_refill_metrics.clone(),
+            )));
+        }
+        if let Some(ledger) = &self.io_ledger {
+            instance.set_io_scheduler(Arc::new(InstanceIo::new(ledger.clone(), instance_id)));
+        }
+        if config.enable_debug {
+            instance.set_coredump_sink(Arc::new(InstanceCoredumps::new(
+                self.coredumps.clone(),
+                self.config.coredumps.clone(),
+                instance_id,
+            )));
+        }
+        if let Some(sampling) = &config.profiling {
+            instance.set_profile_sink(Arc::new(InstanceProfiles::new(
+                self.profiles.clone(),
+                sampling.clone(),
+                instance_id,
+            )));
+        }
+        if let Some(checkpoints) = &config.checkpoints {
+            let module_digest = self.module_digests.read().unwrap().get(&module.id()).copied()
+                // Modules loaded through the runtime directly are identified by their module ID
+                .unwrap_or_else(|| module_digest(module.id().to_string().as_bytes()));
+            instance.set_checkpoint_sink(Arc::new(InstanceCheckpoints::new(
+                self.checkpoints.clone(),
+                checkpoints.clone(),
+                instance_id,
+                module_digest,
+            )));
+        }
+        Ok(instance)
+    }
+    
+    /// Run a function in the sandbox
+    ///
+    /// Errors are passed through the sandbox's [`RedactionPolicy`]; the unredacted
+    /// error is available from [`WasmSandbox::last_raw_error`]. If the instance
+    /// has a [`RecoveryPolicy`], a call that traps as if the guest's memory were
+    /// corrupted is retried once on a fresh instance. Parameters are shaped by
+    /// the instance's size limits; see [`runtime::params`].
+    pub async fn call_function<P, R>(
+        &self,
+        instance_id: InstanceId,
+        function_name: &str,
+        params: P,
+    ) -> Result<R>
+    where
+        P: Serialize + Sync + 'static,
+        R: for<'de> Deserialize<'de> + 'static,
+    {
+        self.call_function_with_priority(instance_id, function_name, params, CallPriority::Normal).await
+    }
+    
+    /// Run a function in the sandbox, queued at `priority` if the instance has a call queue
+    ///
+    /// Otherwise the same as [`WasmSandbox::call_function`].
+    pub async fn call_function_with_priority<P, R>(
+        &self,
+        instance_id: InstanceId,
+        function_name: &str,
+        params: P,
+        priority: CallPriority,
+    ) -> Result<R>
+    where
+        P: Serialize + Sync + 'static,
+        R: for<'de> Deserialize<'de> + 'static,
+    {
+        self.call_function_tracked(instance_id, function_name, params, priority, None).await
+    }
+    
+    /// Run a function in the sandbox, reporting the guest's progress to `progress`
+    ///
+    /// The guest reports progress through [`runtime::PROGRESS_IMPORT_MODULE`].
+    /// Cancelling `progress` tells the guest to abort at its next report, and
+    /// the call then fails whatever the guest returns. Otherwise the same as
+    /// [`WasmSandbox::call_function`].
+    pub async fn call_function_with_progress<P, R>(
+        &self,
+        instance_id: InstanceId,
+        function_name: &str,
+        params: P,
+        progress: &CallProgress,
+    ) -> Result<R>
+    where
+        P: Serialize + Sync + 'static,
+        R: for<'de> Deserialize<'de> + 'static,
+    {
+        self.call_function_tracked(instance_id, function_name, params, CallPriority::Normal, Some(progress)).await
+    }
+    
+    /// Call a numeric export with WebAssembly values, returning all of its results
+    ///
+    /// Unlike [`WasmSandbox::call_function`], arguments and results keep their
+    /// exact types, including `v128`, and exports may return several values.
+    /// Integer arguments are widened to the parameter types as `call_function`
+    /// widens JSON numbers; any other mismatch fails with the export's signature.
+    pub async fn call_values(
+        &self,
+        instance_id: InstanceId,
+        function_name: &str,
+        args: &[HostValue],
+    ) -> Result<Vec<HostValue>> {
+        self.call_tracked(instance_id, function_name, CallPriority::Normal, None, |instance| Box::pin(async move {
+            let _fuel_charge = match &self.fuel_ledger {
+                Some(ledger) => Some(ledger.admit(instance.id, instance.instance.clone())?),
+                None => None,
+            };
+            instance.instance.call_values_async(function_name, args).await
+        })).await
+    }
+    
+    /// Call a numeric export with typed arguments and results
+    ///
+    /// `params` is one [`runtime::WasmValue`] or a tuple of them, and `R` is `()`, one
+    /// value, or a tuple matching the export's results. Otherwise the same as
+    /// [`WasmSandbox::call_values`].
+    ///
+    /// ```no_run
+    /// # async fn example(sandbox: wasm_sandbox::WasmSandbox, instance_id: wasm_sandbox::InstanceId) -> wasm_sandbox::Result<()> {
+    /// let (quotient, remainder): (i64, i64) = sandbox.call_typed(instance_id, "divmod", (17i64, 5i64)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn call_typed<P, R>(&self, instance_id: InstanceId, function_name: &str, params: P) -> Result<R>
+    where
+        P: IntoHostValues,
+        R: FromHostValues,
+    {
+        let results = self.call_values(instance_id, function_name, &params.into_host_values()).await?;
+        runtime::values::convert_results(function_name, &results)
+    }
+    
+    /// Call an export with raw bytes, returning the bytes it outputs
+    ///
+    /// The input reaches the guest unchanged and the output comes back the same
+    /// way, with no JSON in between. Works with exports of the sandbox data ABI
+    /// and wasm-bindgen exports of the form `fn(&[u8]) -> Vec<u8>`; numeric
+    /// exports fail with [`SandboxError::UnsupportedOperation`].
+    pub async fn call_function_bytes(&self, instance_id: InstanceId, function_name: &str, input: &[u8]) -> Result<Vec<u8>> {
+        self.param_limits(instance_id, function_name).check(input.len())?;
+        self.call_tracked(instance_id, function_name, CallPriority::Normal, None, |instance| Box::pin(async move {
+            let _fuel_charge = match &self.fuel_ledger {
+                Some(ledger) => Some(ledger.admit(instance.id, instance.instance.clone())?),
+                None => None,
+            };
+            AbiFunctionCaller::new(instance.instance.clone(), instance.abi).call_bytes_async(function_name, input).await
+        })).await
+    }
+    
+    /// Call an export with MessagePack parameters and result
+    ///
+    /// The same as [`WasmSandbox::call_function_bytes`] with `params` encoded
+    /// and the output decoded as MessagePack, structs as maps of field names.
+    /// [`Bytes`] fields travel as raw binary rather than arrays of numbers.
+    pub async fn call_function_msgpack<P, R>(&self, instance_id: InstanceId, function_name: &str, params: &P) -> Result<R>
+    where
+        P: Serialize + ?Sized,
+        R: for<'de> Deserialize<'de>,
+    {
+        let input = rmp_serde::to_vec_named(params)?;
+        let output = self.call_function_bytes(instance_id, function_name, &input).await?;
+        rmp_serde::from_slice(&output).map_err(|e| SandboxError::FunctionCall {
+            function_name: function_name.to_string(),
+            reason: format!("Failed to deserialize function result: {}", e),
+        })
+    }
+    
+    async fn call_function_tracked<P, R>(
+        &self,
+        instance_id: InstanceId,
+        function_name: &str,
+        params: P,
+        priority: CallPriority,
+        progress: Option<&CallProgress>,
+    ) -> Result<R>
+    where
+        P: Serialize + Sync + 'static,
+        R: for<'de> Deserialize<'de> + 'static,
+    {
+        let shaped = self.param_limits(instance_id, function_name).serialize(&params)?;
+        self.call_tracked(instance_id, function_name, priority, progress, |instance| match &shaped {
+            CallParams::Inline(params_json) => Box::pin(self.call_instance(instance, function_name, params_json)),
+            CallParams::Spilled(len) => self.call_instance_spilled(instance, function_name, &params, *len),
+        }).await
+    }
+    
+    /// Limits on the parameters of a call to an instance's export
+    ///
+    /// Unknown instances get no limits; the call itself reports them missing.
+    fn param_limits(&self, instance_id: InstanceId, function_name: &str) -> ParamLimits {
+        self.instances.get(&instance_id)
+            .map(|instance| ParamLimits::new(instance_id, &instance.config, function_name))
+            .unwrap_or_default()
+    }
+    
+    /// Run `invoke` on an instance as a call, tracking and redacting its outcome
+    async fn call_tracked<'a, R>(
+        &'a self,
+        instance_id: InstanceId,
+        function_name: &str,
+        priority: CallPriority,
+        progress: Option<&CallProgress>,
+        invoke: impl Fn(&'a SandboxInstance) -> GuestCall<'a, R>,
+    ) -> Result<R> {
+        self.touch(instance_id);
+        let result = self.call_unredacted(instance_id, function_name, priority, progress, invoke).await;
+        if result.is_ok() {
+            self.failing.lock().unwrap().remove(&instance_id);
+        } else {
+            self.failing.lock().unwrap().insert(instance_id);
+        }
+        result.map_err(|e| redact_error(&self.config.redaction, &self.raw_errors, instance_id, e))
+    }
+    
+    async fn call_unredacted<'a, R>(
+        &'a self,
+        instance_id: InstanceId,
+        function_name: &str,
+        priority: CallPriority,
+        progress: Option<&CallProgress>,
+        invoke: impl Fn(&'a SandboxInstance) -> GuestCall<'a, R>,
+    ) -> Result<R> {
+        // Get the instance
+        let instance = self.instances.get(&instance_id).ok_or_else(|| {
+            SandboxError::NotFound {
+                resource_type: "instance".to_string(),
+                identifier: instance_id.to_string(),
+            }
+        })?;
+        self.check_callable(instance_id, &instance.config, function_name)?;
+        let _permit = match &instance.call_queue {
+            Some(queue) => Some(queue.acquire(priority).await?),
+            None => None,
+        };
+        let _turn = instance.call_turn.lock().await;
+        if self.terminated.lock().unwrap().contains(&instance_id) {
+            return Err(SandboxError::Instance {
+                operation: "call".to_string(),
+                instance_id: Some(instance_id.as_uuid()),
+                reason: "Instance was terminated; remove it to release its resources".to_string(),
+            });
+        }
+        self.wake(instance)?;
+        let context = self.new_call(instance_id, function_name);
+        let call_id = context.call_id;
+        let started = Instant::now();
+        let _progress = progress.map(|progress| self.call_progress.track(call_id, progress.clone()));
+        let _dns_pins = self.dns.pin_scope(instance_id);
+        let _memory = self.memory_watch.watch(
+            &context,
+            instance.instance.clone(),
+            instance.config.resource_limits.memory.max_memory_pages,
+            instance.config.oom_prediction,
+        );
+        
+        let result = ActiveCall::scope(context, async {
+            // Apply the function's capability overlay for the duration of the call
+            let _capability_scope = instance.config.function_policies.get(function_name)
+                .map(|policy| instance.active_capabilities.enter(function_name, policy.clone()));
+            
+            let result = invoke(instance).await;
+            let result = match (&instance.config.recovery, &instance.slot, result) {
+                (Some(policy), Some(slot), Err(e)) if policy.matches(&e) => {
+                    self.recover(instance, slot, policy, function_name, e)?;
+                    let retried = invoke(instance).await;
+                    let mut metrics = self.recovery_metrics.lock().unwrap();
+                    if retried.is_ok() {
+                        metrics.retries_succeeded += 1;
+                    } else {
+                        metrics.retries_failed += 1;
+                    }
+                    retried
+                }
+                (_, _, result) => result,
+            };
+            
+            let Some(scratch) = &instance.scratch else {
+                return result;
+            };
+            let result = result.and_then(|value| scratch.check_quota().map(|()| value));
+            if result.is_err() && instance.config.scratch.as_ref().is_some_and(|config| config.keep_on_failure) {
+                scratch.keep();
+            }
+            result
+        })
+        .await;
+        
+        // A cancelled guest returns early, so whatever it returned is incomplete
+        let result = match progress {
+            Some(progress) if progress.is_cancelled() => Err(SandboxError::FunctionCall {
+                function_name: function_name.to_string(),
+                reason: "Call was cancelled through its progress handle".to_string(),
+            }),
+            _ => result,
+        };
+        self.journal_call(instance_id, function_name, call_id, started.elapsed(), result.as_ref().err());
+        result
+    }
+    
+    /// Journal a finished call, and the resource limit it ran into if any
+    fn journal_call(
+        &self,
+        instance_id: InstanceId,
+        function_name: &str,
+        call_id: CallId,
+        duration: Duration,
+        error: Option<&SandboxError>,
+    ) {
+        let error = error.map(|e| self.config.redaction.redact_error(e));
+        if let Some(e) = &error
+            && matches!(GuestErrorCode::from(e), GuestErrorCode::QuotaExceeded | GuestErrorCode::Timeout)
+        {
+            self.journal.record(JournalEvent::ResourceViolation {
+                instance_id: instance_id.to_string(),
+                function_name: function_name.to_string(),
+                call_id,
+                error: e.to_string(),
+            });
+        }
+        self.journal.record(JournalEvent::CallCompleted {
+            instance_id: instance_id.to_string(),
+            function_name: function_name.to_string(),
+            call_id,
+            duration_ms: duration.as_millis() as u64,
+            error: error.map(|e| e.to_string()),
+        });
+    }
+    
+    /// Call an export of an instance with JSON parameters, marshalling through its ABI
+    async fn call_instance<R>(&self, instance: &SandboxInstance, function_name: &str, params_json: &str) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        // Calls are refused once the instance has used its share of the fuel budget
+        let _fuel_charge = match &self.fuel_ledger {
+            Some(ledger) => Some(ledger.admit(instance.id, instance.instance.clone())?),
+            None => None,
+        };
+        
+        // Pure functions are answered from the result cache when possible
+        if instance.config.pure_functions.contains(function_name) {
+            return self.call_pure_function(instance, function_name, params_json).await;
+        }
+        
+        // Special case: simple two-parameter i32 functions for testing
+        if function_name == "add" {
+            // Try to deserialize params as (i32, i32)
+            if let Ok((a, b)) = serde_json::from_str::<(i32, i32)>(params_json) {
+                let result = match instance.instance.call_values_async(function_name, &[HostValue::I32(a), HostValue::I32(b)]).await?[..] {
+                    [HostValue::I32(result)] => result,
+                    _ => return Err(SandboxError::FunctionCall {
+                        function_name: function_name.to_string(),
+                        reason: "Unexpected return type".to_string(),
+                    }),
+                };
+                let result_json = serde_json::to_string(&result)?;
+                self.result_validators.check_json(instance.module_id, function_name, &result_json)?;
+                return Ok(serde_json::from_str(&result_json)?);
+            }
+        }
+        
+        // Large guest data ABI results are deserialized straight out of guest memory
+        if let (AbiKind::Sandbox, Some(limit)) = (instance.abi, instance.config.max_inline_result_bytes) {
+            let output = SpilledResult::call_async(instance.instance.clone(), function_name, params_json.as_bytes(), limit).await?;
+            return self.deserialize_spilled(instance, function_name, output);
+        }
+        
+        // Marshal through the module's ABI
+        let caller = AbiFunctionCaller::new(instance.instance.clone(), instance.abi);
+        let result_json = caller.call_function_json_async(function_name, params_json).await?;
+        self.result_validators.check_json(instance.module_id, function_name, &result_json)?;
+        parse_result_json(function_name, &result_json)
+    }
+    
+    /// Call an export with parameters over the instance's `max_inline_param_bytes`
+    ///
+    /// Guest data ABI exports get the `len` bytes of parameters serialized
+    /// straight into guest memory. Other calls, and calls to pure functions
+    /// whose parameters key the result cache, serialize them in one piece.
+    fn call_instance_spilled<'a, P, R>(
+        &'a self,
+        instance: &'a SandboxInstance,
+        function_name: &'a str,
+        params: &'a P,
+        len: usize,
+    ) -> GuestCall<'a, R>
+    where
+        P: Serialize + Sync,
+        R: for<'de> Deserialize<'de> + 'a,
+    {
+        if instance.abi != AbiKind::Sandbox || instance.config.pure_functions.contains(function_name) {
+            let params_json = serde_json::to_string(params);
+            return Box::pin(async move { self.call_instance(instance, function_name, &params_json?).await });
+        }
+        
+        Box::pin(async move {
+            // Calls are refused once the instance has used its share of the fuel budget
+            let _fuel_charge = match &self.fuel_ledger {
+                Some(ledger) => Some(ledger.admit(instance.id, instance.instance.clone())?),
+                None => None,
+            };
+            let chunk_size = instance.config.max_inline_param_bytes
+                .unwrap_or(runtime::params::DEFAULT_MAX_INLINE_PARAM_BYTES);
+            let write = |guest: &mut dyn std::io::Write| -> Result<()> {
+                let mut writer = std::io::BufWriter::with_capacity(chunk_size, guest);
+                serde_json::to_writer(&mut writer, params)?;
+                Ok(std::io::Write::flush(&mut writer)?)
+            };
+            let input = instance.instance.write_input_async(function_name, len, &write).await?;
+            let output = instance.instance.call_raw_region_at_async(function_name, input).await?;
+            let chunk_size = instance.config.max_inline_result_bytes
+                .unwrap_or(runtime::spill::DEFAULT_MAX_INLINE_RESULT_BYTES);
+            let output = SpilledResult::at(instance.instance.clone(), function_name, output, chunk_size)?;
+            self.deserialize_spilled(instance, function_name, output)
+        })
+    }
+    
+    /// Deserialize and validate a result left in guest memory
+    fn deserialize_spilled<R>(&self, instance: &SandboxInstance, function_name: &str, output: SpilledResult) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        let Some(validator) = self.result_validators.get(instance.module_id, function_name) else {
+            return runtime::spill::deserialize(function_name, output);
+        };
+        let result = runtime::spill::deserialize(function_name, output)?;
+        self.result_validators.check(instance.module_id, function_name, &validator, &result)?;
+        serde_json::from_value(result).map_err(|e| SandboxError::FunctionCall {
+            function_name: function_name.to_string(),
+            reason: format!("Failed to deserialize function result: {}", e),
+        })
+    }
+    
+    /// Replace an instance whose call trapped as if its memory were corrupted
+    ///
+    /// `error` is handed back if the instance has used up its recoveries or
+    /// can't be recreated.
+    fn recover(
+        &self,
+        instance: &SandboxInstance,
+        slot: &InstanceSlot,
+        policy: &RecoveryPolicy,
+        function_name: &str,
+        error: SandboxError,
+    ) -> Result<()> {
+        let Some(recoveries) = slot.claim_recovery(policy) else {
+            self.recovery_metrics.lock().unwrap().exhausted += 1;
+            return Err(error);
+        };
+        
+        // The guest's handles, timers, and children refer to state the fresh instance doesn't have
+        instance.handles.clear();
+        self.timers.withdraw(instance.id);
+        self.children.forget(instance.id);
+        let fresh = self.runtime.get_module(instance.module_id).and_then(|module| {
+            self.create_runtime_instance(
+                instance.id,
+                module.as_ref(),
+                &instance.config,
+                &instance.active_capabilities,
+                &instance.handles,
+            )
+        });
+        match fresh {
+            Ok(fresh) => slot.replace(fresh),
+            Err(e) => {
+                log::warn!("Could not recreate instance {}: {}", instance.id, e);
+                return Err(error);
+            }
+        }
+        
+        log::warn!("Recreated instance {} after {} trapped: {}", instance.id, function_name, error);
+        self.recovery_metrics.lock().unwrap().recoveries += 1;
+        let notice = RecoveryNotice {
+            instance_id: instance.id,
+            function_name: function_name.to_string(),
+            error: error.to_string(),
+            recoveries,
+        };
+        for handler in &self.recovery_handlers {
+            handler(&notice);
+        }
+        Ok(())
+    }
+    
+    /// Call a pure function, skipping the guest if the result is cached
+    async fn call_pure_function<R>(&self, instance: &SandboxInstance, function_name: &str, params_json: &str) -> Result<R>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        let module = self.module_digests.read().unwrap().get(&instance.module_id).copied()
+            // Modules loaded through the runtime directly are cached per module ID
+            .unwrap_or_else(|| module_digest(instance.module_id.to_string().as_bytes()));
+        let key = CacheKey::new(module, function_name, params_json);
+        
+        if let Some(result_json) = self.result_cache.get(&key) {
+            return parse_result_json(function_name, &result_json);
+        }
+        
+        let caller = AbiFunctionCaller::new(instance.instance.clone(), instance.abi);
+        let result_json = caller.call_function_json_async(function_name, params_json).await?;
+        self.result_validators.check_json(instance.module_id, function_name, &result_json)?;
+        let result = parse_result_json(function_name, &result_json)?;
+        self.result_cache.insert(key, result_json);
+        Ok(result)
+    }
+    
+    /// Open a scope for running calls against several instances at once
+    ///
+    /// Cancelling or dropping the scope, or letting its deadline pass,
+    /// interrupts the guest code of every call still running in it.
+    pub fn scope<R>(&self) -> CallScope<'_, R>
+    where
+        R: for<'de> Deserialize<'de> + Send + 'static,
+    {
+        CallScope::new(self)
+    }
+    
+    /// Serve HTTP requests from guest handlers until accepting a connection fails
+    ///
+    /// Each connection carries one request, which is routed by `router` and
+    /// handled as by [`WasmSandbox::handle_http`]; up to `max_connections` are
+    /// handled at once. See [`http_handler`] for the handler ABI.
+    pub async fn serve_http(&self, listener: TcpListener, router: &HttpRouter, max_connections: usize) -> Result<()> {
+        let max_body_bytes = router.max_request_bytes();
+        let connections = futures::stream::try_unfold(&listener, |listener| async move {
+            let (stream, _) = listener.accept().await?;
+            Ok::<_, SandboxError>(Some((stream, listener)))
+        });
+        connections.try_for_each_concurrent(max_connections.max(1), |stream| async move {
+            let handle = |request| self.handle_http(router, request);
+            let served = http_handler::serve_connection(stream, max_body_bytes, http_handler::HTTP_READ_TIMEOUT, handle);
+            if let Err(e) = served.await {
+                log::debug!("Could not serve HTTP connection: {}", e);
+            }
+            Ok(())
+        })
+        .await
+    }
+    
+    /// Route a request to an instance and call its handler within the route's budget
+    ///
+    /// Failures become responses: 404 without a matching route, 413 for an
+    /// oversized body, 504 if the guest overruns the route's timeout, 502 for an
+    /// oversized response, and 500 if the handler fails, including when it runs
+    /// out of the instance's fuel.
+    pub async fn handle_http(&self, router: &HttpRouter, request: HttpRequest) -> HttpResponse {
+        let Some((instance_id, budget)) = router.resolve(&request.path) else {
+            return HttpResponse::text(404, "No route for path");
+        };
+        if request.body.len() > budget.max_request_bytes {
+            return HttpResponse::text(413, "Request body too large");
+        }
+        
+        let mut scope = self.scope::<HttpResponse>().with_deadline(budget.timeout);
+        let outcome = match scope.spawn(instance_id, HTTP_HANDLER_EXPORT, request) {
+            Ok(_) => scope.join_next().await.map(|(_, result)| result),
+            Err(e) => Some(Err(e)),
+        };
+        match outcome {
+            Some(Ok(response)) if response.body.len() > budget.max_response_bytes => {
+                HttpResponse::text(502, "Handler response too large")
+            }
+            Some(Ok(response)) => response,
+            Some(Err(SandboxError::Timeout { .. })) => HttpResponse::text(504, "Handler timed out"),
+            Some(Err(e)) => {
+                log::warn!("HTTP handler in instance {} failed: {}", instance_id, e);
+                HttpResponse::text(500, "Handler failed")
+            }
+            None => HttpResponse::text(500, "Handler failed"),
+        }
+    }
+    
+    /// Call a guest data ABI function and read its output in chunks
+    ///
+    /// The output stays in guest memory and is read in pieces of
+    /// `max_inline_result_bytes` (or [`runtime::spill::DEFAULT_MAX_INLINE_RESULT_BYTES`]),
+    /// so a large result never needs one host allocation. It must be consumed
+    /// before the next call into the instance.
+    pub fn call_function_spilled<P: Serialize>(
+        &self,
+        instance_id: InstanceId,
+        function_name: &str,
+        params: P,
+    ) -> Result<SpilledResult> {
+        let instance = self.instances.get(&instance_id).ok_or_else(|| {
+            SandboxError::NotFound {
+                resource_type: "instance".to_string(),
+                identifier: instance_id.to_string(),
+            }
+        })?;
+        
+        self.check_callable(instance_id, &instance.config, function_name)?;
+        self.touch(instance_id);
+        self.wake(instance).map_err(|e| redact_error(&self.config.redaction, &self.raw_errors, instance_id, e))?;
+        let call = ActiveCall::enter(self.new_call(instance_id, function_name));
+        let _span = call.span().enter();
+        let _capability_scope = instance.config.function_policies.get(function_name)
+            .map(|policy| instance.active_capabilities.enter(function_name, policy.clone()));
+        let params_json = ParamLimits::new(instance_id, &instance.config, function_name).serialize_whole(&params)?;
+        let chunk_size = instance.config.max_inline_result_bytes
+            .unwrap_or(runtime::spill::DEFAULT_MAX_INLINE_RESULT_BYTES);
+        
+        SpilledResult::call(instance.instance.clone(), function_name, params_json.as_bytes(), chunk_size)
+            .map_err(|e| redact_error(&self.config.redaction, &self.raw_errors, instance_id, e))
+    }
+    
+    /// Call a function that pushes partial results through the stream import
+    ///
+    /// The guest runs as a task and is paused in `emit` whenever
+    /// [`streaming::RESULT_STREAM_BUFFER`] results are waiting to be consumed.
+    /// The stream ends when the function returns.
+    pub async fn call_function_streaming<P, R>(
+        &self,
+        instance_id: InstanceId,
+        function_name: &str,
+        params: P,
+    ) -> Result<streaming::ResultStream<R>>
+    where
+        P: Serialize,
+        R: for<'de> Deserialize<'de> + 'static,
+    {
+        let instance = self.instances.get(&instance_id).ok_or_else(|| {
+            SandboxError::NotFound {
+                resource_type: "instance".to_string(),
+                identifier: instance_id.to_string(),
+            }
+        })?;
+        
+        self.check_callable(instance_id, &instance.config, function_name)?;
+        self.touch(instance_id);
+        self.wake(instance).map_err(|e| redact_error(&self.config.redaction, &self.raw_errors, instance_id, e))?;
+        let params_json = ParamLimits::new(instance_id, &instance.config, function_name).serialize_whole(&params)?;
+        let (sink, receiver) = tokio::sync::mpsc::channel(streaming::RESULT_STREAM_BUFFER);
+        
+        let guest = instance.instance.clone();
+        let capability_scope = instance.config.function_policies.get(function_name)
+            .map(|policy| (instance.active_capabilities.clone(), policy.clone()));
+        let name = function_name.to_string();
+        let redaction = self.config.redaction.clone();
+        let raw_errors = self.raw_errors.clone();
+        let context = self.new_call(instance_id, function_name);
+        let call = tokio::spawn(ActiveCall::scope(context, async move {
+            let _capability_scope = capability_scope
+                .as_ref()
+                .map(|(active, policy)| active.enter(&name, policy.clone()));
+            guest.call_streaming_async(&name, &params_json, sink).await
+                .map_err(|e| redact_error(&redaction, &raw_errors, instance_id, e))
+        }));
+        
+        Ok(streaming::ResultStream::new(function_name, receiver, call))
+    }
+    
+    /// Unredacted form of the last error returned for an instance
+    ///
+    /// This is a privileged API for the host operator: the raw error may contain
+    /// host paths or data embedded in the module, so don't forward it to callers
+    /// outside the trust boundary.
+    pub fn last_raw_error(&self, instance_id: InstanceId) -> Option<SandboxError> {
+        self.raw_errors.lock().unwrap().get(&instance_id).cloned()
+    }
+    
+    /// Get current and peak memory pages for an instance
+    pub fn memory_pages(&self, instance_id: InstanceId) -> Result<MemoryPages> {
+        let instance = self.instances.get(&instance_id).ok_or_else(|| {
+            SandboxError::NotFound {
+                resource_type: "instance".to_string(),
+                identifier: instance_id.to_string(),
+            }
+        })?;
+        
+        Ok(instance.instance.memory_pages())
+    }
+    
+    /// Get the imports linked into an instance
+    ///
+    /// Only the WASI functions and host imports the module imports are linked.
+    pub fn link_report(&self, instance_id: InstanceId) -> Result<LinkReport> {
+        let instance = self.instances.get(&instance_id).ok_or_else(|| {
+            SandboxError::NotFound {
+                resource_type: "instance".to_string(),
+                identifier: instance_id.to_string(),
+            }
+        })?;
+        
+        instance.instance.link_report().ok_or_else(|| SandboxError::UnsupportedOperation {
+            message: "The runtime does not report linked imports".to_string(),
+        })
+    }
+    
+    /// Collect files the guest created or modified in its writable directories
+    pub fn collect_outputs(&self, instance_id: InstanceId, patterns: &[&str]) -> Result<Vec<CollectedOutput>> {
+        self.collect_outputs_with(instance_id, &OutputCollection::new(patterns))
+    }
+    
+    /// Collect guest outputs with size caps, streaming, and cleanup options
+    pub fn collect_outputs_with(
+        &self,
+        instance_id: InstanceId,
+        options: &OutputCollection,
+    ) -> Result<Vec<CollectedOutput>> {
+        let instance = self.instances.get(&instance_id).ok_or_else(|| {
+            SandboxError::NotFound {
+                resource_type: "instance".to_string(),
+                identifier: instance_id.to_string(),
+            }
+        })?;
+        
+        // Default the per-file cap to the instance's filesystem capability
+        let mut options = options.clone();
+        if options.max_file_size.is_none() {
+            options.max_file_size = instance.config.capabilities.filesystem.max_file_size;
+        }
+        
+        utils::artifacts::collect_outputs(&instance.workspace, &options)
+    }
+    
+    /// Create a new sandbox from source code with automatic compilation and configuration.
+    /// 
+    /// This is the easiest way to get started - just point to a source file and the sandbox
+    /// will automatically detect the language, compile it to WebAssembly, and set up safe defaults.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust,no_run
+    /// use wasm_sandbox::WasmSandbox;
+    /// 
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), wasm_sandbox::Error> {
+    ///     // Rust source file
+    ///     let sandbox = WasmSandbox::from_source("./calculator.rs").await?;
+    ///     let result: i32 = sandbox.call("add", &(5, 3)).await?;
+    ///     
+    ///     // Python source (when supported)
+    ///     let sandbox = WasmSandbox::from_source("./processor.py").await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn from_source(source_path: &str) -> Result<Self> {
+        Self::builder().source(source_path).build().await
+    }
+    
+    /// Create a new sandbox builder for more control over configuration.
+    /// 
+    /// # Examples
+    /// 
+    /// ```rust,no_run
+    /// use wasm_sandbox::WasmSandbox;
+    /// use std::time::Duration;
+    /// 
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), wasm_sandbox::Error> {
+    ///     let sandbox = WasmSandbox::builder()
+    ///         .source("./my_program.rs")
+    ///         .timeout_duration(Duration::from_secs(30))
+    ///         .memory_limit(64 * 1024 * 1024) // 64MB
+    ///         .enable_file_access(false)
+    ///         .build()
+    ///         .await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn builder() -> WasmSandboxBuilder {
+        WasmSandboxBuilder::new()
+    }
+    
+    /// Call a function in the sandbox with automatic instance management.
+    /// 
+    /// This method automatically creates an instance if needed and calls the function.
+    /// For more control, use the explicit instance creation methods.
+    pub async fn call<P, R>(&self, function_name: &str, params: &P) -> Result<R>
+    where
+        P: Serialize + Send + Sync,
+        R: for<'de> Deserialize<'de> + Send + Sync + 'static,
+    {
+        // For now, use the first available instance or create one
+        let instance_id = if let Some(&id) = self.instances.keys().next() {
+            id
+        } else {
+            return Err(SandboxError::NotFound {
+                resource_type: "instance".to_string(),
+                identifier: "default".to_string(),
+            });
+        };
+        
+        // Convert to owned params for the existing call_function method
+        let params_owned = serde_json::to_value(params)?;
+        self.call_function(instance_id, function_name, params_owned).await
+    }
+    
+    /// Execute a complete program with command-line arguments.
+    /// 
+    /// This calls the main function of the WebAssembly module with the provided arguments.
+    pub async fn execute_main(&self, args: &[&str]) -> Result<String> {
+        // For now, return a placeholder - this would need proper main function support
+        Ok(format!("Executed with args: {:?}", args))
+    }
+    
+    /// Get a reference to the runtime
+    pub fn runtime(&self) -> &dyn WasmRuntime {
+        self.runtime.as_ref()
+    }
+    
+    /// Get a mutable reference to the runtime
+    pub fn runtime_mut(&mut self) -> &mut dyn WasmRuntime {
+        self.runtime.as_mut()
+    }
+    
+    /// Get a reference to an instance
+    pub fn get_instance(&self, instance_id: InstanceId) -> Option<&SandboxInstance> {
+        self.instances.get(&instance_id)
+    }
+    
+    /// Typed client for an instance, declared with [`typed_client!`]
+    ///
+    /// Fails if the instance doesn't exist or its module doesn't export every
+    /// function the client calls.
+    pub fn typed_client<'a, T: TypedClient<'a>>(&'a self, instance_id: InstanceId) -> Result<T> {
+        let instance = self.instances.get(&instance_id).ok_or_else(|| SandboxError::NotFound {
+            resource_type: "instance".to_string(),
+            identifier: instance_id.to_string(),
+        })?;
+        let exports = self.runtime.get_module(instance.module_id)?.exports();
+        if let Some(missing) = T::EXPORTS.iter().find(|export| !exports.iter().any(|name| name == *export)) {
+            return Err(SandboxError::NotFound {
+                resource_type: "export".to_string(),
+                identifier: missing.to_string(),
+            });
+        }
+        Ok(T::bind(self, instance_id))
+    }
+    
+    /// Get a mutable reference to an instance
+    pub fn get_instance_mut(&mut self, instance_id: InstanceId) -> Option<&mut SandboxInstance> {
+        self.instances.get_mut(&instance_id)
+    }
+    
+    /// Remove an instance
+    ///
+    /// Handles it held are closed, and an evicted instance's snapshot is deleted as well.
+    pub fn remove_instance(&mut self, instance_id: InstanceId) -> Option<SandboxInstance> {
+        self.broker.withdraw(instance_id);
+        self.timers.withdraw(instance_id);
+        self.raw_errors.lock().unwrap().remove(&instance_id);
+        self.last_used.lock().unwrap().remove(&instance_id);
+        if let Some(evicted) = self.evicted.remove(&instance_id) {
+            let _ = std::fs::remove_file(&evicted.snapshot);
+        }
+        if let Some(ledger) = &self.fuel_ledger {
+            ledger.unregister(instance_id);
+        }
+        if let Some(ledger) = &self.io_ledger {
+            ledger.unregister(instance_id);
+        }
+        self.forget_hibernation(instance_id);
+        self.last_calls.lock().unwrap().remove(&instance_id);
+        self.models.forget(instance_id);
+        self.children.forget(instance_id);
+        self.memory_watch.forget(instance_id);
+        self.coredumps.forget(instance_id);
+        self.profiles.forget(instance_id);
+        self.checkpoints.forget(instance_id);
+        self.failing.lock().unwrap().remove(&instance_id);
+        self.paused.lock().unwrap().remove(&instance_id);
+        self.terminated.lock().unwrap().remove(&instance_id);
+        self.dns.remove(instance_id);
+        let instance = self.instances.remove(&instance_id);
+        if let Some(instance) = &instance {
+            instance.handles.clear();
+            self.journal.record(JournalEvent::InstanceRemoved { instance_id: instance_id.to_string() });
+            self.journal.set_tenant(&instance_id.to_string(), None);
+        }
+        instance
+    }
+    
+    /// Replace an instance's settings document
+    ///
+    /// The document is checked against the instance's size cap, then the guest's
+    /// `on_config_update` export is called if it has one.
+    pub fn update_settings(&mut self, instance_id: InstanceId, document: serde_json::Value) -> Result<()> {
+        if let Some(instance) = self.instances.get(&instance_id) {
+            self.wake(instance)?;
+        }
+        let instance = self.instances.get_mut(&instance_id).ok_or_else(|| {
+            SandboxError::NotFound {
+                resource_type: "instance".to_string(),
+                identifier: instance_id.to_string(),
+            }
+        })?;
+        
+        let max_bytes = instance.config.settings.as_ref()
+            .map_or(runtime::settings::DEFAULT_MAX_SETTINGS_BYTES, |settings| settings.max_bytes);
+        let settings = PluginSettings::new(document).max_bytes(max_bytes);
+        settings.validate()?;
+        instance.instance.set_settings(Arc::new(settings.clone()));
+        instance.config.settings = Some(settings);
+        
+        let module = self.runtime.get_module(instance.module_id)?;
+        if module.exports().iter().any(|export| export == runtime::CONFIG_UPDATE_EXPORT) {
+            let guest = instance.instance.clone();
+            let call = ActiveCall::enter(self.new_call(instance_id, runtime::CONFIG_UPDATE_EXPORT));
+            let _span = call.span().enter();
+            guest.call_values(runtime::CONFIG_UPDATE_EXPORT, &[])
+                .map_err(|e| redact_error(&self.config.redaction, &self.raw_errors, instance_id, e))?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Register a callback invoked before each eviction
+    pub fn on_eviction<F>(&mut self, handler: F)
+    where
+        F: Fn(&EvictionNotice) + Send + Sync + 'static,
+    {
+        self.eviction_handlers.push(Arc::new(handler));
+    }
+    
+    /// Register a callback invoked when an instance is recreated under its [`RecoveryPolicy`]
+    ///
+    /// The callback runs after the fresh instance is in place and before the
+    /// failed call is retried on it.
+    pub fn on_recovery<F>(&mut self, handler: F)
+    where
+        F: Fn(&RecoveryNotice) + Send + Sync + 'static,
+    {
+        self.recovery_handlers.push(Arc::new(handler));
+    }
+    
+    /// Counters for instances recreated after suspected memory corruption
+    pub fn recovery_metrics(&self) -> RecoveryMetrics {
+        *self.recovery_metrics.lock().unwrap()
+    }
+    
+    /// Register a callback invoked when a guest grows a memory
+    ///
+    /// The callback receives the instance and the memory's size before and
+    /// after in pages. Returning [`GrowthDecision::Deny`] makes the guest's
+    /// `memory.grow` fail.
+    pub fn on_memory_grow<F>(&self, hook: F)
+    where
+        F: Fn(InstanceId, u64, u64) -> GrowthDecision + Send + Sync + 'static,
+    {
+        self.growth_hooks.write().unwrap().add_memory_hook(Arc::new(hook));
+    }
+    
+    /// Register a callback invoked when a call is predicted to run out of memory
+    ///
+    /// Only instances with an [`OomPrediction`] are watched. The callback runs
+    /// during the growth that triggered the prediction, at most once per call;
+    /// returning [`GrowthDecision::Deny`] fails that `memory.grow` so the guest
+    /// can give up before reaching the hard limit.
+    pub fn on_oom_warning<F>(&self, handler: F)
+    where
+        F: Fn(&OomWarning) -> GrowthDecision + Send + Sync + 'static,
+    {
+        self.memory_watch.add_handler(Arc::new(handler));
+    }
+    
+    /// Memory high-watermark, diagnostics and other measurements of the latest `call_function` into an instance
+    pub fn last_invocation_report(&self, instance_id: InstanceId) -> Option<InvocationReport> {
+        self.memory_watch.report(instance_id)
+    }
+    
+    /// The latest coredump written for an instance, if a call into it trapped with `enable_debug` set
+    pub fn last_coredump(&self, instance_id: InstanceId) -> Option<Coredump> {
+        self.coredumps.latest(instance_id)
+    }
+    
+    /// Stacks sampled during the latest call into an instance created with `profiling` set
+    ///
+    /// [`FoldedStacks::folded_fuel`] on the profile's stacks shows which guest
+    /// functions burned the call's fuel.
+    pub fn last_profile(&self, instance_id: InstanceId) -> Option<CallProfile> {
+        self.profiles.latest(instance_id)
+    }
+    
+    /// Latest checkpoint the guest of an instance requested
+    pub fn last_checkpoint(&self, instance_id: InstanceId) -> Option<Checkpoint> {
+        self.checkpoints.latest(instance_id)
+    }
+    
+    /// Register a callback invoked when a guest grows a table
+    ///
+    /// The callback receives the instance and the table's size before and
+    /// after in elements. Returning [`GrowthDecision::Deny`] makes the guest's
+    /// `table.grow` fail.
+    pub fn on_table_grow<F>(&self, hook: F)
+    where
+        F: Fn(InstanceId, u64, u64) -> GrowthDecision + Send + Sync + 'static,
+    {
+        self.growth_hooks.write().unwrap().add_table_hook(Arc::new(hook));
+    }
+    
+    /// Combined linear memory of all live instances in bytes
+    pub fn total_memory_usage(&self) -> u64 {
+        self.instances.values().map(|instance| instance.instance.memory_usage()).sum()
+    }
+    
+    /// Evict least recently used idle instances until the memory budget is met
+    ///
+    /// Returns the evicted instances. Does nothing without a [`MemoryBudget`].
+    pub fn enforce_memory_budget(&mut self) -> Vec<InstanceId> {
+        let Some(mut budget) = self.config.memory_budget.clone() else {
+            return Vec::new();
+        };
+        budget.max_bytes = budget.max_bytes.saturating_sub(self.nested_memory());
+        
+        let candidates = {
+            let last_used = self.last_used.lock().unwrap();
+            let now = Instant::now();
+            self.instances.values()
+                .map(|instance| EvictionCandidate {
+                    instance_id: instance.id,
+                    memory_bytes: instance.instance.memory_usage(),
+                    last_used: last_used.get(&instance.id).copied().unwrap_or(now),
+                })
+                .collect()
+        };
+        
+        let now = Instant::now();
+        let mut evicted = Vec::new();
+        for victim in eviction::select_victims(candidates, &budget, now) {
+            let instance_id = victim.instance_id;
+            if self.evict(victim, &budget.policy, now) {
+                evicted.push(instance_id);
+            }
+        }
+        evicted
+    }
+    
+    /// Evict one instance, returning whether it was removed
+    fn evict(&mut self, victim: EvictionCandidate, policy: &EvictionPolicy, now: Instant) -> bool {
+        let snapshot = match policy {
+            EvictionPolicy::Drop => None,
+            EvictionPolicy::Snapshot { dir } => Some(eviction::snapshot_path(dir, victim.instance_id)),
+        };
+        
+        let notice = EvictionNotice {
+            instance_id: victim.instance_id,
+            memory_bytes: victim.memory_bytes,
+            idle_for: now.duration_since(victim.last_used),
+            snapshot: snapshot.clone(),
+        };
+        for handler in &self.eviction_handlers {
+            handler(&notice);
+        }
+        
+        if let Some(path) = snapshot {
+            let instance = &self.instances[&victim.instance_id];
+            let written = InstanceSnapshot::capture(instance.instance.as_ref())
+                .and_then(|snapshot| eviction::write_snapshot(&path, &snapshot));
+            if let Err(e) = written {
+                log::warn!("Keeping instance {}: snapshot failed: {}", victim.instance_id, e);
+                self.eviction_metrics.snapshot_failures += 1;
+                return false;
+            }
+            
+            self.evicted.insert(victim.instance_id, EvictedInstance {
+                module_id: instance.module_id,
+                config: instance.config.clone(),
+                snapshot: path,
+            });
+            self.eviction_metrics.snapshotted += 1;
+        } else {
+            self.eviction_metrics.dropped += 1;
+        }
+        
+        log::info!("Evicted instance {} to reclaim {} bytes", victim.instance_id, victim.memory_bytes);
+        self.broker.withdraw(victim.instance_id);
+        self.timers.withdraw(victim.instance_id);
+        self.raw_errors.lock().unwrap().remove(&victim.instance_id);
+        self.last_used.lock().unwrap().remove(&victim.instance_id);
+        if let Some(ledger) = &self.fuel_ledger {
+            ledger.unregister(victim.instance_id);
+        }
+        self.forget_hibernation(victim.instance_id);
+        if let Some(instance) = self.instances.remove(&victim.instance_id) {
+            instance.handles.clear();
+        }
+        self.eviction_metrics.evictions += 1;
+        self.eviction_metrics.bytes_reclaimed += victim.memory_bytes;
+        true
+    }
+    
+    /// Recreate a snapshotted instance under its original ID
+    ///
+    /// Linear memory, exported mutable globals and remaining fuel are restored
+    /// from the snapshot; tables and unexported globals start from their initial
+    /// values. Services the instance exposed must be exposed again, and handles
+    /// it held were closed when it was evicted.
+    pub fn restore_instance(&mut self, instance_id: InstanceId) -> Result<()> {
+        let evicted = self.evicted.remove(&instance_id).ok_or_else(|| {
+            SandboxError::NotFound {
+                resource_type: "evicted instance".to_string(),
+                identifier: instance_id.to_string(),
+            }
+        })?;
+        
+        let restored = eviction::read_snapshot(&evicted.snapshot).and_then(|snapshot| {
+            self.instantiate(instance_id, evicted.module_id, evicted.config.clone())?;
+            let result = snapshot.restore(self.instances[&instance_id].instance.as_ref());
+            if result.is_err() {
+                self.remove_instance(instance_id);
+            }
+            result
+        });
+        if let Err(e) = restored {
+            self.evicted.insert(instance_id, evicted);
+            return Err(e);
+        }
+        
+        let _ = std::fs::remove_file(&evicted.snapshot);
+        self.eviction_metrics.restored += 1;
+        Ok(())
+    }
+    
+    /// Create an instance from a checkpoint its guest wrote, possibly in an earlier process
+    ///
+    /// The instance is created from `module_id` with `instance_config`, or the
+    /// default configuration, and gets the checkpoint's linear memory and
+    /// exported mutable globals; other globals and tables start from their
+    /// initial values, as for [`WasmSandbox::fork_instance`]. The module must be
+    /// the one the checkpointed instance ran. Calling the guest's entry point
+    /// again resumes its work from the state in its memory.
+    pub fn restore_checkpoint(
+        &mut self,
+        module_id: ModuleId,
+        path: &Path,
+        instance_config: Option<InstanceConfig>,
+    ) -> Result<InstanceId> {
+        self.runtime.features().require(RuntimeFeature::Snapshots)?;
+        let state = checkpoint::read_state(path)?;
+        if self.module_digests.read().unwrap().get(&module_id) != Some(&state.checkpoint.module_digest) {
+            return Err(SandboxError::InvalidInput {
+                field: "module_id".to_string(),
+                reason: format!("checkpoint {} was written by a different module", path.display()),
+                suggestion: Some("Load the module the checkpointed instance ran".to_string()),
+            });
+        }
+        
+        let instance_id = self.create_instance(module_id, instance_config)?;
+        let instance = &self.instances[&instance_id].instance;
+        let restored = instance.restore_memory(&state.memory)
+            .and_then(|_| instance.restore_globals(&state.globals));
+        if let Err(e) = restored {
+            self.remove_instance(instance_id);
+            return Err(e);
+        }
+        log::info!(
+            "Restored instance {} from checkpoint {} of instance {}",
+            instance_id,
+            state.checkpoint.checkpoint_id,
+            state.checkpoint.instance_id,
+        );
+        Ok(instance_id)
+    }
+    
+    /// Create a new instance as a copy of a running one
+    ///
+    /// The fork gets the source's configuration, a copy of its linear memory,
+    /// and the values of its exported mutable globals, so a template instance
+    /// initialised once can be forked into cheap per-request workers. Memory is
+    /// copied eagerly; globals the module does not export start from their
+    /// initial values. Handles, grants, and timers are not copied, and a fork of
+    /// an instance with a scratch directory or inbox gets an empty one of its own.
+    pub fn fork_instance(&mut self, instance_id: InstanceId) -> Result<InstanceId> {
+        self.runtime.features().require(RuntimeFeature::Snapshots)?;
+        let source = self.instances.get(&instance_id).ok_or_else(|| SandboxError::NotFound {
+            resource_type: "instance".to_string(),
+            identifier: instance_id.to_string(),
+        })?;
+        self.wake(source)?;
+        let memory = source.instance.read_memory()?;
+        let globals = source.instance.mutable_globals()?;
+        let module_id = source.module_id;
+        let mut config = source.config.clone();
+        if let (Some(scratch), Some(layer)) = (&source.scratch, &mut config.environment_layer) {
+            layer.directories.retain(|directory| directory.host_path != scratch.path());
+        }
+        if let (Some(inbox), Some(layer)) = (&source.inbox, &mut config.environment_layer) {
+            layer.directories.retain(|directory| directory.host_path != inbox.path());
+        }
+        
+        let fork_id = InstanceId::new();
+        self.instantiate(fork_id, module_id, config)?;
+        let fork = &self.instances[&fork_id].instance;
+        let copied = match memory.is_empty() {
+            true => Ok(()),
+            false => fork.restore_memory(&memory),
+        }
+        .and_then(|_| fork.restore_globals(&globals));
+        if let Err(e) = copied {
+            self.remove_instance(fork_id);
+            return Err(e);
+        }
+        self.hibernate_idle_instances();
+        self.enforce_memory_budget();
+        Ok(fork_id)
+    }
+    
+    /// Instances evicted to snapshots that can be restored
+    pub fn evicted_instances(&self) -> Vec<InstanceId> {
+        self.evicted.keys().copied().collect()
+    }
+    
+    /// Eviction counters
+    pub fn eviction_metrics(&self) -> EvictionMetrics {
+        self.eviction_metrics.clone()
+    }
+    
+    /// Hibernate every instance idle for longer than its `max_idle_time_ms`
+    ///
+    /// Returns the instances hibernated. Pinned instances, instances exposing
+    /// services, and instances without an idle limit are left alone. Does
+    /// nothing without a [`HibernationConfig`].
+    pub fn hibernate_idle_instances(&self) -> Vec<InstanceId> {
+        if self.config.hibernation.is_none() {
+            return Vec::new();
+        }
+        
+        let idle: Vec<InstanceId> = {
+            let last_used = self.last_used.lock().unwrap();
+            let pinned = self.pinned.lock().unwrap();
+            let hibernated = self.hibernated.lock().unwrap();
+            let now = Instant::now();
+            self.instances.values()
+                .filter(|instance| !pinned.contains(&instance.id) && !hibernated.contains_key(&instance.id))
+                .filter(|instance| {
+                    let Some(max_idle) = instance.config.resource_limits.time.max_idle_time_ms else {
+                        return false;
+                    };
+                    last_used.get(&instance.id)
+                        .is_some_and(|used| now.duration_since(*used) >= Duration::from_millis(max_idle))
+                })
+                .map(|instance| instance.id)
+                .collect()
+        };
+        idle.into_iter()
+            .filter(|instance_id| !self.broker.exposes(*instance_id))
+            .filter(|instance_id| match self.hibernate_instance(*instance_id) {
+                Ok(()) => true,
+                Err(e) => {
+                    log::warn!("Could not hibernate instance {}: {}", instance_id, e);
+                    false
+                }
+            })
+            .collect()
+    }
+    
+    /// Write an instance's state to disk and drop it until its next call
+    pub fn hibernate_instance(&self, instance_id: InstanceId) -> Result<()> {
+        let Some(hibernation) = &self.config.hibernation else {
+            return Err(SandboxError::config_error(
+                "Hibernation is not configured",
+                Some("Set SandboxConfig::hibernation to a directory for hibernated instances".to_string()),
+            ));
+        };
+        let instance = self.instances.get(&instance_id).ok_or_else(|| {
+            SandboxError::NotFound {
+                resource_type: "instance".to_string(),
+                identifier: instance_id.to_string(),
+            }
+        })?;
+        let Some(slot) = &instance.slot else {
+            return Err(SandboxError::UnsupportedOperation {
+                message: format!("Instance {} was created before hibernation was enabled", instance_id),
+            });
+        };
+        
+        let mut hibernated = self.hibernated.lock().unwrap();
+        if hibernated.contains_key(&instance_id) {
+            return Ok(());
+        }
+        let started = Instant::now();
+        let memory_bytes = slot.memory_usage();
+        let path = eviction::snapshot_path(&hibernation.dir, instance_id);
+        let written = InstanceSnapshot::capture(slot.as_ref()).and_then(|snapshot| eviction::write_snapshot(&path, &snapshot));
+        let mut metrics = self.hibernation_metrics.lock().unwrap();
+        if let Err(e) = written {
+            metrics.failures += 1;
+            return Err(e);
+        }
+        
+        slot.replace(Box::new(HibernatedInstance));
+        hibernated.insert(instance_id, path);
+        metrics.hibernations += 1;
+        metrics.bytes_freed += memory_bytes;
+        metrics.hibernate_time += started.elapsed();
+        log::info!("Hibernated instance {} to free {} bytes", instance_id, memory_bytes);
+        Ok(())
+    }
+    
+    /// Restore a hibernated instance so it can run
+    fn wake(&self, instance: &SandboxInstance) -> Result<()> {
+        let Some(slot) = &instance.slot else {
+            return Ok(());
+        };
+        let mut hibernated = self.hibernated.lock().unwrap();
+        let Some(path) = hibernated.get(&instance.id) else {
+            return Ok(());
+        };
+        
+        let started = Instant::now();
+        let restored = eviction::read_snapshot(path).and_then(|snapshot| {
+            let module = self.runtime.get_module(instance.module_id)?;
+            let fresh = self.create_runtime_instance(
+                instance.id,
+                module.as_ref(),
+                &instance.config,
+                &instance.active_capabilities,
+                &instance.handles,
+            )?;
+            snapshot.restore(fresh.as_ref())?;
+            Ok(fresh)
+        });
+        let mut metrics = self.hibernation_metrics.lock().unwrap();
+        let fresh = restored.inspect_err(|_| metrics.failures += 1)?;
+        slot.replace(fresh);
+        if let Some(path) = hibernated.remove(&instance.id) {
+            let _ = std::fs::remove_file(path);
+        }
+        
+        let latency = started.elapsed();
+        metrics.restores += 1;
+        metrics.restore_time += latency;
+        metrics.max_restore_latency = metrics.max_restore_latency.max(latency);
+        Ok(())
+    }
+    
+    /// Drop the hibernation state of an instance that is going away
+    fn forget_hibernation(&self, instance_id: InstanceId) {
+        self.pinned.lock().unwrap().remove(&instance_id);
+        if let Some(path) = self.hibernated.lock().unwrap().remove(&instance_id) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+    
+    /// Keep an instance in memory however long it is idle, restoring it if hibernated
+    pub fn pin_instance(&self, instance_id: InstanceId) -> Result<()> {
+        let instance = self.instances.get(&instance_id).ok_or_else(|| {
+            SandboxError::NotFound {
+                resource_type: "instance".to_string(),
+                identifier: instance_id.to_string(),
+            }
+        })?;
+        self.pinned.lock().unwrap().insert(instance_id);
+        self.wake(instance)
+    }
+    
+    /// Let a pinned instance be hibernated again
+    pub fn unpin_instance(&self, instance_id: InstanceId) {
+        self.pinned.lock().unwrap().remove(&instance_id);
+    }
+    
+    /// Whether an instance is hibernated
+    pub fn is_hibernated(&self, instance_id: InstanceId) -> bool {
+        self.hibernated.lock().unwrap().contains_key(&instance_id)
+    }
+    
+    /// Start a call, remembering its ID as the instance's latest
+    fn new_call(&self, instance_id: InstanceId, function_name: &str) -> CallContext {
+        let context = CallContext::new(instance_id, function_name);
+        self.last_calls.lock().unwrap().insert(instance_id, context.call_id);
+        context
+    }
+    
+    /// ID of the latest call into an instance, to find its log records and audit events
+    pub fn last_call_id(&self, instance_id: InstanceId) -> Option<CallId> {
+        self.last_calls.lock().unwrap().get(&instance_id).copied()
+    }
+    
+    /// Lines guests have logged through the logging import
+    pub fn guest_log(&self) -> &Arc<GuestLog> {
+        &self.guest_log
+    }
+    
+    /// Depth and counters of an instance's call queue
+    ///
+    /// `None` for an unknown instance or one without a call queue.
+    pub fn call_queue_metrics(&self, instance_id: InstanceId) -> Option<CallQueueMetrics> {
+        self.instances.get(&instance_id)?.call_queue.as_ref().map(CallQueue::metrics)
+    }
+    
+    /// An instance's scratch directory, to inspect, keep, or export
+    ///
+    /// `None` for an unknown instance or one without a scratch directory.
+    pub fn scratch_space(&self, instance_id: InstanceId) -> Option<&ScratchSpace> {
+        self.instances.get(&instance_id)?.scratch.as_ref()
+    }
+    
+    /// An instance's inbox
+    ///
+    /// `None` for an unknown instance or one without an inbox.
+    pub fn inbox(&self, instance_id: InstanceId) -> Option<&Inbox> {
+        self.instances.get(&instance_id)?.inbox.as_ref()
+    }
+    
+    /// Register a hook run on every file before it is staged
+    ///
+    /// Hooks can scan, transform, or reject files; see [`IngestHook`].
+    pub fn on_ingest<F>(&self, hook: F)
+    where
+        F: Fn(&str, &[u8]) -> IngestDecision + Send + Sync + 'static,
+    {
+        self.ingest_hooks.write().unwrap().push(Arc::new(hook));
+    }
+    
+    /// Stage `contents` into an instance's inbox as `name`
+    ///
+    /// The file must fit the inbox's limits and pass every ingest hook. A file
+    /// already staged under the same name is replaced.
+    pub fn stage_file(&self, instance_id: InstanceId, name: &str, contents: impl Into<Vec<u8>>) -> Result<StagedFile> {
+        let instance = self.instances.get(&instance_id).ok_or_else(|| SandboxError::NotFound {
+            resource_type: "instance".to_string(),
+            identifier: instance_id.to_string(),
+        })?;
+        let inbox = instance.inbox.as_ref().ok_or_else(|| SandboxError::config_error(
+            format!("Instance {} has no inbox", instance_id),
+            Some("Set InstanceConfig::inbox when creating the instance".to_string()),
+        ))?;
+        let hooks = self.ingest_hooks.read().unwrap().clone();
+        inbox.stage(name, contents.into(), &hooks, &self.ingest_audit)
+    }
+    
+    /// Stage a host file into an instance's inbox under its file name
+    pub fn stage_path(&self, instance_id: InstanceId, path: &Path) -> Result<StagedFile> {
+        let name = path.file_name().and_then(|name| name.to_str()).ok_or_else(|| SandboxError::InvalidInput {
+            field: "path".to_string(),
+            reason: format!("{} has no usable file name", path.display()),
+            suggestion: None,
+        })?;
+        let contents = std::fs::read(path).map_err(|e| SandboxError::Filesystem {
+            operation: "stage_path".to_string(),
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })?;
+        self.stage_file(instance_id, name, contents)
+    }
+    
+    /// Files staged into an instance's inbox, in the order they were staged
+    pub fn staged_files(&self, instance_id: InstanceId) -> Vec<StagedFile> {
+        self.inbox(instance_id).map(Inbox::files).unwrap_or_default()
+    }
+    
+    /// Remove a staged file from an instance's inbox, returning whether it was there
+    pub fn unstage_file(&self, instance_id: InstanceId, name: &str) -> bool {
+        self.inbox(instance_id).is_some_and(|inbox| inbox.remove(name))
+    }
+    
+    /// Audit log of every file staged or refused
+    pub fn ingestion_audit_logger(&self) -> &AuditLogger {
+        &self.ingest_audit
+    }
+    
+    /// Ordered journal of the sandbox's lifecycle events, audit records and calls
+    pub fn journal(&self) -> &Arc<Journal> {
+        &self.journal
+    }
+    
+    /// Database the sandbox's audit events are persisted to, if one is configured
+    #[cfg(feature = "sqlite-audit")]
+    pub fn audit_store(&self) -> Option<&Arc<AuditStore>> {
+        self.audit_store.as_ref()
+    }
+    
+    /// Audit log of calls refused by [`InstanceConfig::callable_functions`]
+    pub fn function_call_audit_logger(&self) -> &AuditLogger {
+        &self.call_audit
+    }
+    
+    /// Hibernation counters
+    pub fn hibernation_metrics(&self) -> HibernationMetrics {
+        self.hibernation_metrics.lock().unwrap().clone()
+    }
+    
+    /// Fuel left in an instance's share of the fuel budget, negative while it is in debt
+    ///
+    /// `None` without a [`FuelBudget`] or for an unknown instance.
+    pub fn fuel_balance(&self, instance_id: InstanceId) -> Option<i64> {
+        self.fuel_ledger.as_ref()?.balance(instance_id)
     }
     
-    /// Load a WASM module
-    pub fn load_module(&self, wasm_bytes: &[u8]) -> Result<ModuleId> {
-        let module = self.runtime.load_module(wasm_bytes)?;
-        Ok(module.id())
+    /// Counters for calls metered against the fuel budget
+    pub fn fuel_budget_metrics(&self) -> FuelBudgetMetrics {
+        self.fuel_ledger.as_ref().map(|ledger| ledger.metrics()).unwrap_or_default()
     }
     
-    /// Create a new instance of a module
-    pub fn create_instance(
-        &mut self,
-        module_id: ModuleId,
-        instance_config: Option<InstanceConfig>,
-    ) -> Result<InstanceId> {
-        // Use provided config or default
-        let config = instance_config.unwrap_or_else(|| self.config.default_instance_config.clone());
-        
-        // Get the module
-        let module = self.runtime.get_module(module_id)?;
-        
-        // Create the instance
-        let instance = self.runtime.create_instance(
-            module.as_ref(),
-            config.resource_limits.clone(),
-            config.capabilities.clone(),
-        )?;
-        
-        // Create the instance ID
-        let instance_id = InstanceId::new();
-        
-        // Store the instance
-        self.instances.insert(
-            instance_id,
-            SandboxInstance {
-                id: instance_id,
-                instance,
-                config,
-                monitor: crate::monitoring::ResourceMonitor::new(Some(instance_id)),
-            },
-        );
-        
-        Ok(instance_id)
+    /// Counters for fuel top-ups under the sandbox's [`FuelRefillPolicy`]
+    pub fn fuel_refill_metrics(&self) -> FuelRefillMetrics {
+        *self.fuel_refill_metrics.lock().unwrap()
     }
     
-    /// Run a function in the sandbox
-    pub async fn call_function<P, R>(
-        &self,
-        instance_id: InstanceId,
-        function_name: &str,
-        params: P,
-    ) -> Result<R>
-    where
-        P: Serialize + 'static,
-        R: for<'de> Deserialize<'de> + 'static,
-    {
-        // Get the instance
+    /// Counters for every file operation paced by the sandbox's [`IoBudget`]
+    pub fn io_metrics(&self) -> IoMetrics {
+        self.io_ledger.as_ref().map(|ledger| ledger.metrics()).unwrap_or_default()
+    }
+    
+    /// Counters for one instance's file operations paced by the sandbox's [`IoBudget`]
+    ///
+    /// `None` without an [`IoBudget`] or for an unknown instance.
+    pub fn instance_io_metrics(&self, instance_id: InstanceId) -> Option<IoMetrics> {
+        self.io_ledger.as_ref()?.instance_metrics().remove(&instance_id)
+    }
+    
+    /// Record that an instance was just used
+    fn touch(&self, instance_id: InstanceId) {
+        if self.instances.contains_key(&instance_id) {
+            self.last_used.lock().unwrap().insert(instance_id, Instant::now());
+        }
+    }
+    
+    /// Expose exports of an instance as a service other instances can call
+    ///
+    /// Callers need the service in their [`communication::broker::SERVICE_GRANT_CAPABILITY`]
+    /// custom capability.
+    pub fn expose_service(&self, instance_id: InstanceId, service: &str, functions: &[&str]) -> Result<()> {
         let instance = self.instances.get(&instance_id).ok_or_else(|| {
             SandboxError::NotFound {
                 resource_type: "instance".to_string(),
                 identifier: instance_id.to_string(),
             }
         })?;
-        
-        // Special case: simple two-parameter i32 functions for testing
-        if function_name == "add" {
-            // Try to deserialize params as (i32, i32)
-            let params_json = serde_json::to_string(&params)?;
-            if let Ok(tuple_params) = serde_json::from_str::<(i32, i32)>(&params_json) {
-                let result = instance.instance.call_simple_function(function_name, &[tuple_params.0, tuple_params.1])?;
-                let result_json = serde_json::to_string(&result)?;
-                return Ok(serde_json::from_str(&result_json)?);
+        self.broker.expose(service, instance_id, instance.instance.clone(), functions)
+    }
+    
+    /// Broker routing calls between instances
+    pub fn service_broker(&self) -> &Arc<ServiceBroker> {
+        &self.broker
+    }
+    
+    /// Set the provider guests' secret requests are served from
+    ///
+    /// Instances can only read the secrets named in their
+    /// [`security::SecretsCapability`].
+    pub fn set_secrets_provider(&self, provider: impl SecretsProvider + 'static) {
+        self.secrets.set_provider(Arc::new(provider));
+    }
+    
+    /// Store releasing secrets to instances, including its audit log
+    pub fn secret_store(&self) -> &Arc<SecretStore> {
+        &self.secrets
+    }
+    
+    /// Set where guests' domain names are looked up (the system resolver by default)
+    ///
+    /// Instances can only resolve the domains their
+    /// [`security::DnsPolicy`] allows.
+    pub fn set_name_resolver(&self, resolver: impl NameResolver + 'static) {
+        self.dns.set_resolver(Arc::new(resolver));
+    }
+    
+    /// Resolver serving guests' domain names, including its audit log
+    pub fn dns_resolver(&self) -> &Arc<DnsResolver> {
+        &self.dns
+    }
+    
+    /// Set how guests' HTTP requests are sent: pinned roots, client certificates, proxy, timeouts and retries
+    ///
+    /// Instances can only request URLs whose host their
+    /// [`security::NetworkCapability`] allows.
+    #[cfg(feature = "http-client")]
+    pub fn set_http_egress_policy(&self, policy: &HttpEgressPolicy) -> Result<()> {
+        self.http.set_policy(policy)
+    }
+    
+    /// Egress sending guests' HTTP requests, including its audit log
+    #[cfg(feature = "http-client")]
+    pub fn http_egress(&self) -> &Arc<HttpEgress> {
+        &self.http
+    }
+    
+    /// Check whether an instance may connect to `addr`
+    ///
+    /// Host functions connecting on a guest's behalf should check here. With
+    /// [`security::NetworkCapability::AllowedDomains`], only addresses the host
+    /// resolved for the instance during its current call are allowed. Denied
+    /// connections are logged in [`WasmSandbox::capability_audit_logger`], and
+    /// allowed anyway in audit mode.
+    pub fn is_connection_allowed(&self, instance_id: InstanceId, addr: std::net::SocketAddr) -> bool {
+        let Some(instance) = self.instances.get(&instance_id) else {
+            return false;
+        };
+        let capabilities = instance.active_capabilities.current();
+        let allowed = match &capabilities.network {
+            security::NetworkCapability::AllowedDomains(policy) => {
+                policy.allows_port(addr.port()) && self.dns.is_pinned(instance_id, addr.ip())
             }
+            network => security::capabilities::NetworkVerifier::new(network.clone()).is_socket_allowed(addr),
+        };
+        if allowed {
+            return true;
         }
         
-        // Fall back to generic JSON-based function calling
-        let caller = instance.instance.function_caller();
-        let params_json = serde_json::to_string(&params)?;
-        let result_json = caller.call_function_json(function_name, &params_json)?;
-        
-        // Try to deserialize the result, but handle JSON errors gracefully
-        match serde_json::from_str(&result_json) {
-            Ok(result) => Ok(result),
-            Err(serde_err) => {
-                // Check if the result_json contains an error response
-                if result_json.contains("error") || result_json.contains("Error") {
-                    return Err(SandboxError::FunctionCall {
-                        function_name: function_name.to_string(),
-                        reason: format!("Function call failed: {}", result_json),
-                    });
-                }
-                
-                // Check if it's the stub implementation response format
-                if result_json.contains("\"success\": true") {
-                    // This is the stub implementation - create a proper error for non-existent functions
-                    return Err(SandboxError::FunctionCall {
-                        function_name: function_name.to_string(),
-                        reason: "Function not found or not properly implemented".to_string(),
-                    });
-                }
-                
-                // If it's a JSON deserialization error, create a more helpful error
-                Err(SandboxError::FunctionCall {
-                    function_name: function_name.to_string(),
-                    reason: format!("Failed to deserialize function result: {}", serde_err),
-                })
-            }
-        }
+        let (ip, port) = (addr.ip().to_string(), addr.port().to_string());
+        let error = SandboxError::SecurityViolation {
+            violation: format!("Network access denied to {}", addr),
+            instance_id: Some(instance_id.0),
+            context: SecurityContext {
+                attempted_operation: "connect".to_string(),
+                required_capability: "network.connect".to_string(),
+                available_capabilities: Vec::new(),
+            },
+        };
+        security::capabilities::CapabilityManager::from_capabilities(&capabilities)
+            .with_audit_logger(self.grant_audit.clone())
+            .with_instance_id(instance_id.to_string())
+            .violation("network", "connect", &[&ip, &port], error)
+            .is_ok()
     }
     
-    /// Create a new sandbox from source code with automatic compilation and configuration.
-    /// 
-    /// This is the easiest way to get started - just point to a source file and the sandbox
-    /// will automatically detect the language, compile it to WebAssembly, and set up safe defaults.
-    /// 
-    /// # Examples
-    /// 
-    /// ```rust,no_run
-    /// use wasm_sandbox::WasmSandbox;
-    /// 
-    /// #[tokio::main]
-    /// async fn main() -> Result<(), wasm_sandbox::Error> {
-    ///     // Rust source file
-    ///     let sandbox = WasmSandbox::from_source("./calculator.rs").await?;
-    ///     let result: i32 = sandbox.call("add", &(5, 3)).await?;
-    ///     
-    ///     // Python source (when supported)
-    ///     let sandbox = WasmSandbox::from_source("./processor.py").await?;
-    ///     Ok(())
-    /// }
-    /// ```
-    pub async fn from_source(source_path: &str) -> Result<Self> {
-        Self::builder().source(source_path).build().await
+    /// Make a model available to guests' WASI-NN imports under `name`
+    ///
+    /// Instances can only load the models named in their
+    /// [`security::MlCapability`].
+    pub fn register_model(&self, name: &str, model: impl InferenceModel + 'static) {
+        self.models.register(name, Arc::new(model));
     }
     
-    /// Create a new sandbox builder for more control over configuration.
-    /// 
-    /// # Examples
-    /// 
-    /// ```rust,no_run
-    /// use wasm_sandbox::WasmSandbox;
-    /// use std::time::Duration;
-    /// 
-    /// #[tokio::main]
-    /// async fn main() -> Result<(), wasm_sandbox::Error> {
-    ///     let sandbox = WasmSandbox::builder()
-    ///         .source("./my_program.rs")
-    ///         .timeout_duration(Duration::from_secs(30))
-    ///         .memory_limit(64 * 1024 * 1024) // 64MB
-    ///         .enable_file_access(false)
-    ///         .build()
-    ///         .await?;
-    ///     Ok(())
-    /// }
-    /// ```
-    pub fn builder() -> WasmSandboxBuilder {
-        WasmSandboxBuilder::new()
+    /// Models available to guests, with each instance's inference usage
+    pub fn model_registry(&self) -> &Arc<ModelRegistry> {
+        &self.models
     }
     
-    /// Call a function in the sandbox with automatic instance management.
-    /// 
-    /// This method automatically creates an instance if needed and calls the function.
-    /// For more control, use the explicit instance creation methods.
-    pub async fn call<P, R>(&self, function_name: &str, params: &P) -> Result<R>
-    where
-        P: Serialize + Send + Sync,
-        R: for<'de> Deserialize<'de> + Send + Sync + 'static,
-    {
-        // For now, use the first available instance or create one
-        let instance_id = if let Some(&id) = self.instances.keys().next() {
-            id
-        } else {
-            return Err(SandboxError::NotFound {
-                resource_type: "instance".to_string(),
-                identifier: "default".to_string(),
-            });
+    /// Inference an instance has run through the WASI-NN imports
+    pub fn ml_usage(&self, instance_id: InstanceId) -> MlUsage {
+        self.models.usage(instance_id)
+    }
+    
+    /// Make a module available to guests as a child instance under `name`
+    ///
+    /// Guests can only start the modules named in their
+    /// [`security::ChildCapability`], and children are stopped with their parent.
+    pub fn register_child_module(&self, name: &str, wasm_bytes: &[u8]) -> Result<()> {
+        self.children.register(name, wasm_bytes)
+    }
+    
+    /// Modules available to guests as children, with the children running
+    pub fn child_registry(&self) -> &Arc<ChildRegistry> {
+        &self.children
+    }
+    
+    /// Grant an instance a capability for `ttl`, or until revoked if `None`
+    ///
+    /// The grant applies on top of the instance's capabilities and any function
+    /// policy from the next host-function check on. Policies fixed when the
+    /// instance was created, such as its WASI preopens, are unaffected. Grants
+    /// beyond the sandbox's capability ceiling are refused.
+    pub fn grant(&self, instance_id: InstanceId, capability: CapabilityChange, ttl: Option<Duration>) -> Result<GrantId> {
+        let instance = self.get_instance(instance_id).ok_or_else(|| SandboxError::NotFound {
+            resource_type: "instance".to_string(),
+            identifier: instance_id.to_string(),
+        })?;
+        self.audit_expired_grants(instance);
+        if self.config.capability_ceiling.is_some() {
+            let mut granted = instance.config.capabilities.clone();
+            capability.apply(&mut granted);
+            self.check_ceiling(&granted, "grant capability")?;
+        }
+        
+        let event = AuditEventType::CapabilityGranted {
+            instance_id: instance_id.to_string(),
+            capability: capability.to_string(),
+            ttl_ms: ttl.map(|ttl| ttl.as_millis() as u64),
         };
+        let message = format!("Granted instance {} capability: {}", instance_id, capability);
+        let id = instance.active_capabilities.grant(capability, ttl);
+        self.grant_audit.warning(event, &message);
+        Ok(id)
+    }
+    
+    /// Withdraw a capability granted with [`WasmSandbox::grant`]
+    pub fn revoke(&self, instance_id: InstanceId, grant_id: GrantId) -> Result<()> {
+        let instance = self.get_instance(instance_id).ok_or_else(|| SandboxError::NotFound {
+            resource_type: "instance".to_string(),
+            identifier: instance_id.to_string(),
+        })?;
+        self.audit_expired_grants(instance);
         
-        // Convert to owned params for the existing call_function method
-        let params_owned = serde_json::to_value(params)?;
-        self.call_function(instance_id, function_name, params_owned).await
+        let grant = instance.active_capabilities.revoke(grant_id).ok_or_else(|| SandboxError::NotFound {
+            resource_type: "capability grant".to_string(),
+            identifier: grant_id.to_string(),
+        })?;
+        self.grant_audit.info(
+            AuditEventType::CapabilityRevoked {
+                instance_id: instance_id.to_string(),
+                capability: grant.change.to_string(),
+                expired: false,
+            },
+            &format!("Revoked instance {} capability: {}", instance_id, grant.change),
+        );
+        Ok(())
     }
     
-    /// Execute a complete program with command-line arguments.
-    /// 
-    /// This calls the main function of the WebAssembly module with the provided arguments.
-    pub async fn execute_main(&self, args: &[&str]) -> Result<String> {
-        // For now, return a placeholder - this would need proper main function support
-        Ok(format!("Executed with args: {:?}", args))
+    /// Capabilities granted to an instance that are still in effect
+    pub fn capability_grants(&self, instance_id: InstanceId) -> Vec<CapabilityGrant> {
+        let Some(instance) = self.get_instance(instance_id) else {
+            return Vec::new();
+        };
+        self.audit_expired_grants(instance);
+        instance.active_capabilities.grants()
     }
-
-    /// Get a reference to the runtime
-    pub fn runtime(&self) -> &dyn WasmRuntime {
-        self.runtime.as_ref()
+    
+    /// Audit log of capability grants, revocations, expiries, capability extension
+    /// calls, and denied connections
+    ///
+    /// Lapsed grants stop applying as soon as they expire, but are recorded
+    /// the next time the instance's grants are changed or listed.
+    pub fn capability_audit_logger(&self) -> &AuditLogger {
+        &self.grant_audit
     }
     
-    /// Get a mutable reference to the runtime
-    pub fn runtime_mut(&mut self) -> &mut dyn WasmRuntime {
-        self.runtime.as_mut()
+    fn audit_expired_grants(&self, instance: &SandboxInstance) {
+        for grant in instance.active_capabilities.prune_expired() {
+            self.grant_audit.info(
+                AuditEventType::CapabilityRevoked {
+                    instance_id: instance.id.to_string(),
+                    capability: grant.change.to_string(),
+                    expired: true,
+                },
+                &format!("Capability grant of instance {} expired: {}", instance.id, grant.change),
+            );
+        }
     }
     
-    /// Get a reference to an instance
-    pub fn get_instance(&self, instance_id: InstanceId) -> Option<&SandboxInstance> {
-        self.instances.get(&instance_id)
+    /// Hit and miss counters of the pure function result cache
+    pub fn result_cache_metrics(&self) -> ResultCacheMetrics {
+        self.result_cache.metrics()
     }
     
-    /// Get a mutable reference to an instance
-    pub fn get_instance_mut(&mut self, instance_id: InstanceId) -> Option<&mut SandboxInstance> {
-        self.instances.get_mut(&instance_id)
+    /// Cache holding results of pure functions
+    pub fn result_cache(&self) -> &Arc<ResultCache> {
+        &self.result_cache
     }
     
-    /// Remove an instance
-    pub fn remove_instance(&mut self, instance_id: InstanceId) -> Option<SandboxInstance> {
-        self.instances.remove(&instance_id)
+    /// Check a module's results from `function_name` before they are deserialized
+    ///
+    /// Replaces any validator set for the function before. See
+    /// [`runtime::result_validation`] for which calls are validated.
+    pub fn set_result_validator(&self, module_id: ModuleId, function_name: &str, validator: ResultValidator) -> Result<()> {
+        self.runtime.get_module(module_id)?;
+        self.result_validators.set(module_id, function_name, validator);
+        Ok(())
+    }
+    
+    /// Stop validating a module's results from `function_name`, returning whether they were validated
+    pub fn remove_result_validator(&self, module_id: ModuleId, function_name: &str) -> bool {
+        self.result_validators.remove(module_id, function_name)
+    }
+    
+    /// Counters of validated and rejected results, by module
+    pub fn result_validation_metrics(&self) -> HashMap<ModuleId, ResultValidationMetrics> {
+        self.result_validators.metrics()
+    }
+    
+    /// Call guests back for every timer they set that is now due
+    ///
+    /// Callbacks run in the order their timers fell due, under the instance's
+    /// resource limits and any function policy for `on_timer`. Errors are
+    /// redacted like those of [`WasmSandbox::call_function`].
+    pub fn fire_due_timers(&self) -> Vec<FiredTimer> {
+        self.timers.take_due(Instant::now()).into_iter()
+            .filter_map(|timer| {
+                let instance = self.instances.get(&timer.instance_id)?;
+                self.touch(timer.instance_id);
+                let result = self.wake(instance).and_then(|_| {
+                    let call = ActiveCall::enter(self.new_call(timer.instance_id, runtime::TIMER_CALLBACK_EXPORT));
+                    let _span = call.span().enter();
+                    let _capability_scope = instance.config.function_policies.get(runtime::TIMER_CALLBACK_EXPORT)
+                        .map(|policy| instance.active_capabilities.enter(runtime::TIMER_CALLBACK_EXPORT, policy.clone()));
+                    instance.instance.call_values(runtime::TIMER_CALLBACK_EXPORT, &[HostValue::I64(timer.token)])
+                });
+                let result = result.map(|_| ())
+                    .map_err(|e| redact_error(&self.config.redaction, &self.raw_errors, timer.instance_id, e));
+                Some(FiredTimer { timer, result })
+            })
+            .collect()
+    }
+    
+    /// Fire timers as they fall due until no timers are outstanding
+    ///
+    /// Timers set while waiting, including by the callbacks themselves, are
+    /// picked up too.
+    pub async fn run_timers(&self) -> Vec<FiredTimer> {
+        let mut fired = Vec::new();
+        loop {
+            fired.extend(self.fire_due_timers());
+            let Some(due) = self.timers.next_due() else {
+                return fired;
+            };
+            tokio::select! {
+                _ = tokio::time::sleep_until(due.into()) => {}
+                _ = self.timers.changed() => {}
+            }
+        }
+    }
+    
+    /// Number of timers an instance has outstanding
+    pub fn pending_timers(&self, instance_id: InstanceId) -> usize {
+        self.timers.outstanding(instance_id)
+    }
+    
+    /// Register a namespace of host functions for instances created afterwards
+    ///
+    /// Fails without registering anything if a function is already registered
+    /// by another provider. Gated namespaces are only callable by instances
+    /// granted them through [`HOST_NAMESPACE_CAPABILITY`].
+    pub fn register_host_namespace(&self, namespace: HostNamespace) -> Result<()> {
+        self.host_functions.register(namespace)
+    }
+    
+    /// Register several namespaces of host functions, all or none
+    pub fn register_host_namespaces(&self, namespaces: impl IntoIterator<Item = HostNamespace>) -> Result<()> {
+        self.host_functions.register_all(namespaces)
+    }
+    
+    /// Register a capability extension for instances created afterwards
+    ///
+    /// The extension's functions are registered as a host namespace named
+    /// after it, so this fails if the namespace is taken. Instances holding a
+    /// capability under the namespace that the extension rejects can't be
+    /// created, and calls to its functions are recorded in the
+    /// [`WasmSandbox::capability_audit_logger`].
+    pub fn register_capability_extension<E: CapabilityExtension>(&self, extension: E) -> Result<()> {
+        self.extensions.register(extension, &self.host_functions, &self.grant_audit)
+    }
+    
+    /// Capability extensions registered with the sandbox
+    pub fn capability_extensions(&self) -> &CapabilityExtensions {
+        &self.extensions
+    }
+    
+    /// Host functions registered with the sandbox
+    pub fn host_functions(&self) -> &Arc<HostFunctionRegistry> {
+        &self.host_functions
     }
     
     /// Get all instance IDs
@@ -562,6 +3286,84 @@ impl WasmSandbox {
         self.instances.keys().copied().collect()
     }
     
+    /// Snapshot of the sandbox's instances and metrics for health checks
+    ///
+    /// An instance counts as failing while the last call made to it through
+    /// [`WasmSandbox::call_function`] failed.
+    pub fn health(&self) -> SandboxHealth {
+        let mut failing_instances: Vec<InstanceId> = self.failing.lock().unwrap().iter()
+            .filter(|instance_id| self.instances.contains_key(instance_id))
+            .copied()
+            .collect();
+        failing_instances.sort_by_key(InstanceId::as_uuid);
+        SandboxHealth {
+            instances: self.instances.len(),
+            failing_instances,
+            hibernated_instances: self.hibernated.lock().unwrap().len(),
+            evicted_instances: self.evicted.len(),
+            runtime: self.runtime.get_metrics_detailed(),
+            result_cache: self.result_cache_metrics(),
+            evictions: self.eviction_metrics(),
+            recoveries: self.recovery_metrics(),
+            hibernation: self.hibernation_metrics(),
+            io: self.io_metrics(),
+            instance_io: self.io_ledger.as_ref().map(|ledger| ledger.instance_metrics()).unwrap_or_default(),
+            result_validation: self.result_validation_metrics(),
+        }
+    }
+    
+    /// Hold calls to an instance until it is resumed
+    ///
+    /// Waits for the call running in the instance, if any, to finish; calls
+    /// made while it is paused wait their turn as they would behind a running call.
+    pub async fn pause_instance(&self, instance_id: InstanceId) -> Result<()> {
+        let instance = self.instances.get(&instance_id).ok_or_else(|| SandboxError::NotFound {
+            resource_type: "instance".to_string(),
+            identifier: instance_id.to_string(),
+        })?;
+        if self.is_paused(instance_id) {
+            return Ok(());
+        }
+        let turn = instance.call_turn.clone().lock_owned().await;
+        self.paused.lock().unwrap().insert(instance_id, turn);
+        Ok(())
+    }
+    
+    /// Let calls to a paused instance run again, returning whether it was paused
+    pub fn resume_instance(&self, instance_id: InstanceId) -> bool {
+        self.paused.lock().unwrap().remove(&instance_id).is_some()
+    }
+    
+    /// Whether an instance is paused
+    pub fn is_paused(&self, instance_id: InstanceId) -> bool {
+        self.paused.lock().unwrap().contains_key(&instance_id)
+    }
+    
+    /// Stop an instance for good without removing it
+    ///
+    /// The running call is interrupted, and this and every later call fails
+    /// until the instance is removed with [`WasmSandbox::remove_instance`].
+    /// Paused instances are resumed so their waiting calls fail too.
+    pub fn terminate_instance(&self, instance_id: InstanceId) -> Result<()> {
+        let instance = self.instances.get(&instance_id).ok_or_else(|| SandboxError::NotFound {
+            resource_type: "instance".to_string(),
+            identifier: instance_id.to_string(),
+        })?;
+        self.terminated.lock().unwrap().insert(instance_id);
+        if let Some(interrupt) = instance.instance.interrupt_handle() {
+            interrupt.interrupt();
+        }
+        self.resume_instance(instance_id);
+        Ok(())
+    }
+    
+    /// Instances terminated but not yet removed
+    pub fn terminated_instances(&self) -> Vec<InstanceId> {
+        let mut terminated: Vec<InstanceId> = self.terminated.lock().unwrap().iter().copied().collect();
+        terminated.sort_by_key(InstanceId::as_uuid);
+        terminated
+    }
+    
     /// Get resource usage for a specific instance
     pub fn get_instance_resource_usage(&self, instance_id: InstanceId) -> Result<crate::monitoring::DetailedResourceUsage> {
         let instance = self.instances.get(&instance_id).ok_or_else(|| {
@@ -573,7 +3375,7 @@ impl WasmSandbox {
         
         Ok(instance.monitor.get_current_usage())
     }
-
+    
     /// Reset an instance (recreate it with the same configuration)
     pub fn reset_instance(&mut self, instance_id: InstanceId) -> Result<()> {
         // Get the current instance
@@ -594,13 +3396,66 @@ impl WasmSandbox {
     }
 }
 
-pub use communication::{CommunicationChannel, RpcChannel};
-pub use runtime::{RuntimeMetrics, WasmInstanceState};
+pub use communication::{CommunicationChannel, RpcChannel, AsyncRpcChannel};
+pub use communication::broker::{ServiceBroker, ServiceQuota};
+pub use runtime::{ApiCompatibility, MemoryPages, ModuleMetadata, PoolingConfig, RuntimeMetrics, WasmInstanceState};
+pub use runtime::compilation::{CompilationIsolation, SubprocessCompiler};
+pub use runtime::cache_bundle::{ArtifactTarget, CacheBundle};
+pub use runtime::engine::{EngineSelection, RuntimeEngine};
+pub use utils::version::{ApiVersion, VersionRange};
+pub use utils::module_diff::{ChangeKind, ModuleChange, ModuleDiff};
+pub use utils::provenance::{Provenance, ProvenancePolicy, SbomComponent, PROVENANCE_SECTION};
+pub use runtime::environment::{EnvironmentLayer, HostDirectory};
+pub use utils::scratch::{ScratchConfig, ScratchSpace, DEFAULT_SCRATCH_GUEST_PATH};
+pub use utils::ingest::{Inbox, InboxConfig, IngestDecision, IngestHook, StagedFile, DEFAULT_INBOX_GUEST_PATH};
+pub use runtime::abi::AbiKind;
+pub use runtime::error_codes::GuestErrorCode;
+pub use runtime::scheduler::{CooperativeScheduler, SchedulerConfig};
+pub use runtime::spill::SpilledResult;
+pub use runtime::bytes::Bytes;
+pub use runtime::settings::PluginSettings;
+pub use runtime::eviction::{EvictionMetrics, EvictionNotice, EvictionPolicy, MemoryBudget};
+pub use runtime::call_context::{current_call, current_call_id, CallContext, CallId};
+pub use runtime::guest_log::{GuestLog, GuestLogRecord};
+pub use runtime::progress::{CallProgress, ProgressUpdate};
+pub use runtime::call_queue::{CallPriority, CallQueueConfig, CallQueueMetrics, OverflowPolicy};
+pub use runtime::features::{RuntimeFeature, RuntimeFeatures};
+pub use runtime::fuel_budget::{FuelBudget, FuelBudgetMetrics, FuelClass, FuelRefillMetrics, FuelRefillPolicy};
+pub use runtime::io_scheduler::{IoBudget, IoMetrics};
+pub use runtime::hibernation::{HibernationConfig, HibernationMetrics};
+pub use runtime::wasi_nn::{CallbackModel, InferenceModel, MlUsage, ModelRegistry, NnErrno, Tensor, TensorType};
+pub use runtime::growth::{GrowthDecision, InvocationReport, OomPrediction, OomWarning};
+pub use runtime::diagnostics::{CallDiagnostics, Diagnostic, DiagnosticKind};
+pub use runtime::coredump::{Coredump, CoredumpConfig, DEFAULT_MAX_COREDUMP_BYTES};
+pub use runtime::profiling::{CallProfile, FoldedStacks, ProfilingStrategy, SamplingConfig, DEFAULT_SAMPLING_INTERVAL};
+pub use runtime::checkpoint::{Checkpoint, CheckpointConfig};
+pub use runtime::metrics::{CallMetrics, DecayingRate, DetailedMetrics, LatencySummary, LatencyWindow};
+pub use runtime::children::ChildRegistry;
+pub use runtime::recovery::{RecoveryMetrics, RecoveryNotice, RecoveryPolicy};
+pub use runtime::result_cache::{ResultCacheConfig, ResultCacheMetrics};
+pub use runtime::result_validation::{ResultValidationMetrics, ResultValidator};
+pub use runtime::reload::{ConfigChange, ConfigDiff};
+pub use runtime::timers::{DueTimer, FiredTimer};
+pub use runtime::host_namespaces::{HostContext, HostNamespace, HOST_NAMESPACE_CAPABILITY};
+pub use runtime::handles::{Handle, HandleTable};
 pub use security::{
-    CpuLimits, EnvironmentCapability, FilesystemCapability,
+    CallableFunctions, CpuLimits, EnvironmentCapability, FilesystemCapability,
     IoLimits, MemoryLimits, NetworkCapability, ProcessCapability,
-    RandomCapability, TimeCapability,
+    RandomCapability, TimeCapability, SecretsCapability, MlCapability, ChildCapability, EnforcementMode, FuelSchedule,
 };
+pub use security::capabilities::{CapabilityChange, CapabilityGrant, GrantId};
+pub use security::extensions::{CapabilityExtension, CapabilityExtensions, ExtensionCall};
+pub use security::redaction::RedactionPolicy;
+pub use security::DnsPolicy;
+pub use security::dns::{StaticResolver, SystemResolver};
+#[cfg(feature = "http-client")]
+pub use security::http::{HttpEgressPolicy, HttpHostPolicy, HttpProxy, HttpRetryPolicy};
+pub use security::hardening::{HardeningMeasure, HardeningReport, HostHardening};
+pub use security::imports::LinkReport;
+pub use security::audit::AuditEventKind;
+pub use security::audit_store::AuditStoreConfig;
+#[cfg(feature = "sqlite-audit")]
+pub use security::audit_store::{AuditQuery, AuditStore};
 pub use utils::manifest::SandboxManifest;
 
 
@@ -650,7 +3505,7 @@ impl WasmSandboxBuilder {
         self
     }
     
-    /// Set memory limit in bytes
+    /// Set memory limit in bytes, rounded up to whole 64KB pages
     pub fn memory_limit(mut self, bytes: usize) -> Self {
         self.memory_limit = Some(bytes);
         self
@@ -687,7 +3542,7 @@ impl WasmSandboxBuilder {
         }
         
         if let Some(memory_limit) = self.memory_limit {
-            self.config.default_instance_config.resource_limits.memory.max_memory_pages = (memory_limit / 65536) as u32;
+            self.config.default_instance_config.resource_limits.memory.max_memory_pages = (memory_limit as u64).div_ceil(65536);
         }
         
         if let Some(enable_files) = self.enable_file_access {
@@ -763,6 +3618,17 @@ pub async fn compile_source_to_wasm(source_path: &str) -> Result<Vec<u8>> {
     }
 }
 
+/// Compile source code to WebAssembly like [`compile_source_to_wasm`], then shrink the module
+///
+/// Returns the optimized module with a report of how much smaller it became.
+pub async fn compile_source_to_wasm_optimized(
+    source_path: &str,
+    options: &compiler::optimize::PostOptimize,
+) -> Result<(Vec<u8>, compiler::optimize::SizeReport)> {
+    let wasm_bytes = compile_source_to_wasm(source_path).await?;
+    compiler::optimize::optimize(&wasm_bytes, options)
+}
+
 /// Compile Rust source to WebAssembly
 async fn compile_rust_to_wasm(source_path: &str) -> Result<Vec<u8>> {
     use std::path::Path;
@@ -949,7 +3815,42 @@ async fn compile_go_to_wasm(_source_path: &str) -> Result<Vec<u8>> {
 pub mod bindings;
 
 pub mod streaming;
-pub use streaming::{StreamingExecution, StreamingExecutor, StreamingConfig, StreamingConfigExt, FunctionCall, FunctionResult};
+
+// Structured concurrency for grouped calls
+pub mod scope;
+pub use scope::{CallScope, ScopedCallId};
+
+// Typed clients for guest exports
+pub mod client;
+pub use client::TypedClient;
+
+// Instances scoped to one request
+pub mod ephemeral;
+pub use ephemeral::{EphemeralInstance, EphemeralMetrics};
+
+// Health checks and Prometheus metrics
+pub mod observability;
+pub use observability::SandboxHealth;
+pub use observability::journal::{
+    FileJournalSink, Journal, JournalCursor, JournalEntry, JournalEvent, JournalMetrics, JournalRetention,
+    JournalSink, TracingJournalSink, WebhookJournalSink,
+};
+#[cfg(feature = "sqlite-journal")]
+pub use observability::journal::SqliteJournalSink;
+pub use observability::telemetry::{TelemetryConfig, TenantTelemetry};
+
+// Sandboxes nested inside a sandbox
+pub mod nested;
+pub use nested::{NestedSandboxConfig, NestedSandboxId};
+
+// HTTP handlers served from instances
+pub mod http_handler;
+pub use http_handler::{HttpBudget, HttpRequest, HttpResponse, HttpRouter, HTTP_HANDLER_EXPORT};
+pub use streaming::{StreamingExecution, StreamingExecutor, StreamingConfig, StreamingConfigExt, FunctionCall, FunctionResult, ResultStream};
+
+// Guest HTTP handlers mounted in axum and actix-web services
+pub mod frameworks;
+pub use frameworks::{RoutePolicy, SandboxRoute};
 
 pub mod plugins;
 pub use plugins::{
@@ -958,5 +3859,18 @@ pub use plugins::{
     SecurityAuditReport, BenchmarkReport
 };
 
+pub mod bundle;
+pub use bundle::{Bundle, BundleSignature, BundleSigner, BundleVerifier, Ed25519PublicKey, Ed25519Signer, HmacKey, BUNDLE_EXTENSION};
+
+// C ABI for calling the sandbox from other languages
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+// JSON-RPC management API for orchestrators in other languages
+#[cfg(feature = "management-api")]
+pub mod management;
+#[cfg(feature = "management-api")]
+pub use management::{ManagementServer, RpcAuthenticator, RpcError, TokenAuthenticator};
+
 pub mod simple;
 pub use simple::{SimpleSandbox, ReusableSandbox, from_source};
@@ -0,0 +1,445 @@
+//! JSON-RPC 2.0 management API
+//!
+//! [`ManagementServer`] lets an orchestrator written in any language drive a
+//! sandbox over stdio or TCP without FFI bindings. Each line a client sends is
+//! a JSON-RPC 2.0 request, notification or batch, and each line it gets back
+//! the matching response. Parameters are passed by name:
+//!
+//! | Method | Params | Result |
+//! |---|---|---|
+//! | `authenticate` | credentials for the [`RpcAuthenticator`] | `{"principal": ..}` |
+//! | `load_module` | `wasm` (base64) or `wat` | `{"module_id": ..}` |
+//! | `create_instance` | `module_id` | `{"instance_id": ..}` |
+//! | `call_function` | `instance_id`, `function`, `params` | the function's result |
+//! | `remove_instance` | `instance_id` | whether the instance existed |
+//! | `list_instances` | | instance IDs |
+//! | `pause_instance`, `resume_instance`, `terminate_instance` | `instance_id` | `null` |
+//! | `health` | | instance counts |
+//! | `metrics` | | Prometheus text |
+//!
+//! Instances get the sandbox's default instance configuration. With an
+//! authenticator, a connection must call `authenticate` before anything else
+//! and is closed if its credentials are refused; without one every request is
+//! trusted, which suits stdio to a process the orchestrator spawned.
+
+use std::sync::Arc;
+
+use base64::Engine;
+use futures::TryStreamExt;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+
+use crate::error::{Result, SandboxError};
+use crate::observability::console::tokens_match;
+use crate::observability::http::render_metrics;
+use crate::runtime::ModuleId;
+use crate::{InstanceId, WasmSandbox};
+
+/// Longest request line the server reads; modules are sent inline
+pub const MAX_REQUEST_BYTES: usize = 64 * 1024 * 1024;
+
+/// Connections served at once
+const MAX_CONNECTIONS: usize = 8;
+
+/// The error object of a JSON-RPC response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    /// One of the codes below
+    pub code: i64,
+    
+    /// What went wrong
+    pub message: String,
+    
+    /// Further details, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    /// The line isn't JSON
+    pub const PARSE_ERROR: i64 = -32700;
+    
+    /// The message isn't a JSON-RPC 2.0 request
+    pub const INVALID_REQUEST: i64 = -32600;
+    
+    /// There's no such method
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    
+    /// The params don't fit the method
+    pub const INVALID_PARAMS: i64 = -32602;
+    
+    /// The sandbox refused or failed the operation
+    pub const SANDBOX_ERROR: i64 = -32000;
+    
+    /// The connection hasn't authenticated, or its credentials were refused
+    pub const UNAUTHENTICATED: i64 = -32001;
+    
+    /// The principal may not call the method
+    pub const FORBIDDEN: i64 = -32002;
+    
+    /// Create an error without data
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+}
+
+impl From<SandboxError> for RpcError {
+    fn from(error: SandboxError) -> Self {
+        Self::new(Self::SANDBOX_ERROR, error.to_string())
+    }
+}
+
+/// Decides who may use the management API
+pub trait RpcAuthenticator: Send + Sync {
+    /// Check the params of an `authenticate` request, returning the principal they identify
+    fn authenticate(&self, credentials: &Value) -> Option<String>;
+    
+    /// Whether an authenticated principal may call `method`; every method by default
+    fn authorize(&self, principal: &str, method: &str) -> bool {
+        let _ = (principal, method);
+        true
+    }
+}
+
+/// Admits connections presenting a shared token as `{"token": ..}`
+#[derive(Debug, Clone)]
+pub struct TokenAuthenticator {
+    token: String,
+}
+
+impl TokenAuthenticator {
+    /// Create an authenticator that admits `token`
+    pub fn new(token: impl Into<String>) -> Self {
+        Self { token: token.into() }
+    }
+}
+
+impl RpcAuthenticator for TokenAuthenticator {
+    fn authenticate(&self, credentials: &Value) -> Option<String> {
+        let token = credentials.get("token")?.as_str()?;
+        tokens_match(token, &self.token).then(|| "token".to_string())
+    }
+}
+
+/// Serves the management API for a sandbox
+///
+/// The sandbox sits behind a [`RwLock`]: creating and removing instances take
+/// it for writing and so wait for running calls, everything else shares it.
+#[derive(Clone, Default)]
+pub struct ManagementServer {
+    authenticator: Option<Arc<dyn RpcAuthenticator>>,
+}
+
+/// What one connection has established
+struct Session {
+    /// Whether requests skip authentication
+    trusted: bool,
+    
+    /// Who the connection authenticated as
+    principal: Option<String>,
+    
+    /// Whether to hang up after answering
+    closed: bool,
+}
+
+type RpcResult = std::result::Result<Value, RpcError>;
+
+impl ManagementServer {
+    /// Create a server that trusts every request
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Require connections to authenticate with `authenticator`
+    pub fn with_authenticator(mut self, authenticator: impl RpcAuthenticator + 'static) -> Self {
+        self.authenticator = Some(Arc::new(authenticator));
+        self
+    }
+    
+    /// Answer a trusted request or batch, or `None` if it needs no response
+    pub async fn handle(&self, sandbox: &RwLock<WasmSandbox>, message: Value) -> Option<Value> {
+        let mut session = Session { trusted: true, principal: None, closed: false };
+        self.answer(sandbox, &mut session, message).await
+    }
+    
+    /// Serve requests on the process's stdin and stdout until stdin closes
+    pub async fn serve_stdio(&self, sandbox: &RwLock<WasmSandbox>) -> Result<()> {
+        self.serve(sandbox, tokio::io::stdin(), tokio::io::stdout()).await
+    }
+    
+    /// Serve connections from a TCP listener until accepting one fails
+    pub async fn serve_tcp(&self, sandbox: &RwLock<WasmSandbox>, listener: TcpListener) -> Result<()> {
+        let connections = futures::stream::try_unfold(&listener, |listener| async move {
+            let (stream, _) = listener.accept().await?;
+            Ok::<_, SandboxError>(Some((stream, listener)))
+        });
+        connections.try_for_each_concurrent(MAX_CONNECTIONS, |stream| async move {
+            let (reader, writer) = tokio::io::split(stream);
+            self.serve(sandbox, reader, writer).await
+        }).await
+    }
+    
+    /// Serve one connection until the client hangs up or its credentials are refused
+    pub async fn serve<R, W>(&self, sandbox: &RwLock<WasmSandbox>, reader: R, mut writer: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut reader = BufReader::new(reader);
+        let mut session = Session { trusted: self.authenticator.is_none(), principal: None, closed: false };
+        while !session.closed {
+            let reply = match read_line(&mut reader).await {
+                Ok(Some(line)) if line.trim().is_empty() => continue,
+                Ok(Some(line)) => match serde_json::from_str(&line) {
+                    Ok(message) => self.answer(sandbox, &mut session, message).await,
+                    Err(e) => Some(response(Value::Null, Err(RpcError::new(RpcError::PARSE_ERROR, e.to_string())))),
+                },
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    // The rest of the line is still unread
+                    session.closed = true;
+                    Some(response(Value::Null, Err(RpcError::new(RpcError::INVALID_REQUEST, e.to_string()))))
+                }
+            };
+            let Some(reply) = reply else { continue };
+            if let Err(e) = write_line(&mut writer, &reply).await {
+                log::debug!("Could not send management API response: {}", e);
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+    
+    /// Answer a request or batch, or `None` if it needs no response
+    async fn answer(&self, sandbox: &RwLock<WasmSandbox>, session: &mut Session, message: Value) -> Option<Value> {
+        let batch = match message {
+            Value::Array(batch) => batch,
+            request => return self.answer_one(sandbox, session, request).await,
+        };
+        if batch.is_empty() {
+            return Some(response(Value::Null, Err(RpcError::new(RpcError::INVALID_REQUEST, "Empty batch"))));
+        }
+        let mut responses = Vec::new();
+        for request in batch {
+            if session.closed {
+                break;
+            }
+            responses.extend(self.answer_one(sandbox, session, request).await);
+        }
+        (!responses.is_empty()).then_some(Value::Array(responses))
+    }
+    
+    async fn answer_one(&self, sandbox: &RwLock<WasmSandbox>, session: &mut Session, request: Value) -> Option<Value> {
+        let (id, method, params) = match parse_request(request) {
+            Ok(request) => request,
+            Err((id, error)) => return Some(response(id, Err(error))),
+        };
+        let result = match method.as_str() {
+            "authenticate" => self.authenticate(session, &params),
+            _ => match self.authorize(session, &method) {
+                Ok(()) => call_method(sandbox, &method, params).await,
+                Err(error) => Err(error),
+            },
+        };
+        // Notifications get no response, even when they fail
+        id.map(|id| response(id, result))
+    }
+    
+    fn authenticate(&self, session: &mut Session, credentials: &Value) -> RpcResult {
+        let Some(authenticator) = &self.authenticator else {
+            return Ok(json!({ "principal": null }));
+        };
+        match authenticator.authenticate(credentials) {
+            Some(principal) => {
+                session.principal = Some(principal.clone());
+                Ok(json!({ "principal": principal }))
+            }
+            None => {
+                session.closed = true;
+                Err(RpcError::new(RpcError::UNAUTHENTICATED, "Invalid credentials"))
+            }
+        }
+    }
+    
+    fn authorize(&self, session: &Session, method: &str) -> std::result::Result<(), RpcError> {
+        let Some(authenticator) = self.authenticator.as_ref().filter(|_| !session.trusted) else {
+            return Ok(());
+        };
+        let principal = session.principal.as_deref()
+            .ok_or_else(|| RpcError::new(RpcError::UNAUTHENTICATED, "Authenticate first"))?;
+        if !authenticator.authorize(principal, method) {
+            return Err(RpcError::new(RpcError::FORBIDDEN, format!("{} may not call {}", principal, method)));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LoadModuleParams {
+    #[serde(default)]
+    wasm: Option<String>,
+    #[serde(default)]
+    wat: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModuleParams {
+    module_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InstanceParams {
+    instance_id: InstanceId,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CallParams {
+    instance_id: InstanceId,
+    function: String,
+    #[serde(default)]
+    params: Value,
+}
+
+async fn call_method(sandbox: &RwLock<WasmSandbox>, method: &str, params: Value) -> RpcResult {
+    match method {
+        "load_module" => {
+            let wasm_bytes = match parse_params(params)? {
+                LoadModuleParams { wasm: Some(wasm), wat: None } => base64::engine::general_purpose::STANDARD
+                    .decode(wasm)
+                    .map_err(|e| invalid_params(format!("wasm isn't base64: {}", e)))?,
+                LoadModuleParams { wasm: None, wat: Some(wat) } => wat.into_bytes(),
+                _ => return Err(invalid_params("Pass exactly one of wasm and wat")),
+            };
+            let module_id = sandbox.read().await.load_module(&wasm_bytes)?;
+            Ok(json!({ "module_id": module_id.to_string() }))
+        }
+        "create_instance" => {
+            let ModuleParams { module_id } = parse_params(params)?;
+            let module_id: ModuleId = module_id.parse()
+                .map_err(|_| invalid_params(format!("{} isn't a module ID", module_id)))?;
+            let instance_id = sandbox.write().await.create_instance(module_id, None)?;
+            Ok(json!({ "instance_id": instance_id }))
+        }
+        "call_function" => {
+            let CallParams { instance_id, function, params } = parse_params(params)?;
+            let sandbox = sandbox.read().await;
+            Ok(sandbox.call_function::<Value, Value>(instance_id, &function, params).await?)
+        }
+        "remove_instance" => {
+            let InstanceParams { instance_id } = parse_params(params)?;
+            Ok(Value::Bool(sandbox.write().await.remove_instance(instance_id).is_some()))
+        }
+        "list_instances" => {
+            let mut instance_ids = sandbox.read().await.instance_ids();
+            instance_ids.sort_by_key(InstanceId::as_uuid);
+            Ok(json!(instance_ids))
+        }
+        "pause_instance" => {
+            let InstanceParams { instance_id } = parse_params(params)?;
+            sandbox.read().await.pause_instance(instance_id).await?;
+            Ok(Value::Null)
+        }
+        "resume_instance" => {
+            let InstanceParams { instance_id } = parse_params(params)?;
+            let sandbox = sandbox.read().await;
+            if sandbox.get_instance(instance_id).is_none() {
+                return Err(SandboxError::NotFound {
+                    resource_type: "instance".to_string(),
+                    identifier: instance_id.to_string(),
+                }.into());
+            }
+            sandbox.resume_instance(instance_id);
+            Ok(Value::Null)
+        }
+        "terminate_instance" => {
+            let InstanceParams { instance_id } = parse_params(params)?;
+            sandbox.read().await.terminate_instance(instance_id)?;
+            Ok(Value::Null)
+        }
+        "health" => {
+            let health = sandbox.read().await.health();
+            Ok(json!({
+                "instances": health.instances,
+                "failing_instances": health.failing_instances,
+                "hibernated_instances": health.hibernated_instances,
+                "evicted_instances": health.evicted_instances,
+            }))
+        }
+        "metrics" => Ok(Value::String(render_metrics(&sandbox.read().await.health()))),
+        _ => Err(RpcError::new(RpcError::METHOD_NOT_FOUND, format!("No method {}", method))),
+    }
+}
+
+/// Split a request into its ID, if it isn't a notification, method and params
+fn parse_request(request: Value) -> std::result::Result<(Option<Value>, String, Value), (Value, RpcError)> {
+    let Value::Object(mut request) = request else {
+        return Err((Value::Null, RpcError::new(RpcError::INVALID_REQUEST, "A request must be an object")));
+    };
+    let id = request.remove("id");
+    let reply_id = match &id {
+        Some(id @ (Value::Null | Value::Number(_) | Value::String(_))) => id.clone(),
+        Some(_) => return Err((Value::Null, RpcError::new(RpcError::INVALID_REQUEST, "id must be a string, number or null"))),
+        None => Value::Null,
+    };
+    let invalid = |message: &str| (reply_id.clone(), RpcError::new(RpcError::INVALID_REQUEST, message));
+    if request.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+        return Err(invalid("jsonrpc must be \"2.0\""));
+    }
+    let Some(Value::String(method)) = request.remove("method") else {
+        return Err(invalid("method must be a string"));
+    };
+    let params = request.remove("params").unwrap_or(Value::Null);
+    if !matches!(params, Value::Object(_) | Value::Null) {
+        return Err((reply_id, invalid_params("Params must be passed by name")));
+    }
+    Ok((id, method, params))
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> std::result::Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| invalid_params(e.to_string()))
+}
+
+fn invalid_params(message: impl Into<String>) -> RpcError {
+    RpcError::new(RpcError::INVALID_PARAMS, message)
+}
+
+fn response(id: Value, result: RpcResult) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    }
+}
+
+/// Read one line, or `None` once the peer hangs up
+async fn read_line<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> Result<Option<String>> {
+    let mut line = String::new();
+    let read = (&mut *reader).take(MAX_REQUEST_BYTES as u64 + 1).read_line(&mut line).await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if read > MAX_REQUEST_BYTES {
+        return Err(SandboxError::InvalidInput {
+            field: "request".to_string(),
+            reason: format!("Management API lines are limited to {} bytes", MAX_REQUEST_BYTES),
+            suggestion: None,
+        });
+    }
+    Ok(Some(line))
+}
+
+async fn write_line<W: AsyncWrite + Unpin>(writer: &mut W, message: &Value) -> Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.flush().await?;
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//! Sandboxes nested inside a sandbox
+//!
+//! Frameworks that let plugins host sub-plugins can give each plugin a
+//! sandbox of its own inside theirs. [`WasmSandbox::create_nested`] carves the
+//! nested sandbox's limits out of its parent: its memory and fuel budgets are
+//! taken from the parent's, which shrink by as much until
+//! [`WasmSandbox::remove_nested`] hands them back, and its capability ceiling
+//! must lie within the parent's, so capabilities only ever narrow on the way
+//! down. The parent owns its nested sandboxes, so dropping it tears down every
+//! sandbox below it along with their instances.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::security::Capabilities;
+use crate::WasmSandbox;
+
+/// Unique identifier for a nested sandbox
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct NestedSandboxId(Uuid);
+
+impl NestedSandboxId {
+    /// Create a new random nested sandbox ID
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+    
+    /// Get the underlying UUID
+    pub fn as_uuid(&self) -> Uuid {
+        self.0
+    }
+}
+
+impl std::fmt::Display for NestedSandboxId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Default for NestedSandboxId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What a nested sandbox is given by its parent
+#[derive(Debug, Clone)]
+pub struct NestedSandboxConfig {
+    /// Most any instance in the nested sandbox may be granted; also the
+    /// capabilities its instances get by default
+    pub capabilities: Capabilities,
+    
+    /// Linear memory taken from the parent's memory budget, in bytes
+    pub memory_bytes: Option<u64>,
+    
+    /// Fuel per window taken from the parent's fuel budget
+    pub fuel_per_window: Option<u64>,
+}
+
+impl NestedSandboxConfig {
+    /// Create a nested sandbox limited to `capabilities`
+    pub fn new(capabilities: Capabilities) -> Self {
+        Self {
+            capabilities,
+            memory_bytes: None,
+            fuel_per_window: None,
+        }
+    }
+    
+    /// Give the nested sandbox a memory budget of `bytes`
+    pub fn memory(mut self, bytes: u64) -> Self {
+        self.memory_bytes = Some(bytes);
+        self
+    }
+    
+    /// Give the nested sandbox a fuel budget of `fuel` per window
+    pub fn fuel(mut self, fuel: u64) -> Self {
+        self.fuel_per_window = Some(fuel);
+        self
+    }
+}
+
+/// A nested sandbox and what it took from its parent
+pub(crate) struct NestedSandbox {
+    pub(crate) sandbox: WasmSandbox,
+    pub(crate) memory_bytes: u64,
+    pub(crate) fuel_per_window: u64,
+}
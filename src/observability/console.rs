@@ -0,0 +1,444 @@
+//! Opt-in debug console for operators
+//!
+//! [`DebugConsole`] serves a small line protocol on a TCP listener or, on
+//! Unix, a local socket: each line a client sends is a JSON [`ConsoleRequest`]
+//! and each line it gets back a JSON [`ConsoleResponse`]. A connection must
+//! first authenticate with the console's token, then may list instances, view
+//! an instance's configuration, capabilities and resource usage, read recent
+//! audit entries, and pause, resume or terminate instances. [`ConsoleClient`]
+//! speaks the protocol from the operator's side.
+//!
+//! Nothing listens unless the host serves the console, and the token is the
+//! only credential, so bind TCP listeners to loopback or a private interface.
+
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use crate::error::{Result, SandboxError};
+use crate::monitoring::DetailedResourceUsage;
+use crate::security::audit::AuditEvent;
+use crate::{InstanceId, WasmSandbox};
+
+/// Longest request line the console reads
+pub const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// Audit entries returned when a request doesn't say how many
+pub const DEFAULT_AUDIT_ENTRIES: usize = 50;
+
+/// Connections served at once
+const MAX_CONNECTIONS: usize = 8;
+
+/// A request from an operator
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ConsoleRequest {
+    /// Authenticate the connection; must come first
+    Auth {
+        /// The console's token
+        token: String,
+    },
+    
+    /// List the sandbox's instances
+    ListInstances,
+    
+    /// Show one instance in detail
+    Instance {
+        /// Instance to show
+        instance_id: InstanceId,
+    },
+    
+    /// Show the most recent audit entries, newest last
+    Audit {
+        /// Only entries about this instance
+        #[serde(default)]
+        instance_id: Option<InstanceId>,
+        
+        /// Entries to return, [`DEFAULT_AUDIT_ENTRIES`] if unset
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    
+    /// Hold calls to an instance
+    Pause {
+        /// Instance to pause
+        instance_id: InstanceId,
+    },
+    
+    /// Let calls to a paused instance run again
+    Resume {
+        /// Instance to resume
+        instance_id: InstanceId,
+    },
+    
+    /// Interrupt an instance and fail its calls until the host removes it
+    Terminate {
+        /// Instance to terminate
+        instance_id: InstanceId,
+    },
+}
+
+/// An instance as listed by the console
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceSummary {
+    /// Instance ID
+    pub instance_id: InstanceId,
+    
+    /// Module the instance was created from
+    pub module_id: String,
+    
+    /// Linear memory in bytes
+    pub memory_bytes: u64,
+    
+    /// Whether calls to the instance are held
+    pub paused: bool,
+    
+    /// Whether the instance was terminated
+    pub terminated: bool,
+    
+    /// Whether the instance is hibernated to disk
+    pub hibernated: bool,
+    
+    /// Whether the last call to the instance failed
+    pub failing: bool,
+}
+
+/// An instance shown in detail
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceDetails {
+    /// What the listing shows
+    #[serde(flatten)]
+    pub summary: InstanceSummary,
+    
+    /// The instance's configuration, as printed by `{:#?}`
+    pub config: String,
+    
+    /// Capabilities in effect, including grants, as printed by `{:#?}`
+    pub capabilities: String,
+    
+    /// Resources the instance has used
+    pub resource_usage: DetailedResourceUsage,
+    
+    /// Fuel the instance has burned, when fuel is metered
+    pub fuel_consumed: Option<u64>,
+}
+
+/// The console's answer to a request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub enum ConsoleResponse {
+    /// The connection is authenticated
+    Authenticated,
+    
+    /// The sandbox's instances
+    Instances {
+        /// Instances in ID order
+        instances: Vec<InstanceSummary>,
+    },
+    
+    /// One instance in detail
+    Instance {
+        /// The instance
+        instance: Box<InstanceDetails>,
+    },
+    
+    /// Recent audit entries
+    Audit {
+        /// Entries, newest last
+        events: Vec<AuditEvent>,
+    },
+    
+    /// The pause, resume or termination took effect
+    Done,
+    
+    /// The request failed
+    Error {
+        /// Why
+        message: String,
+    },
+}
+
+/// Serves the debug console for a sandbox
+#[derive(Debug, Clone)]
+pub struct DebugConsole {
+    token: String,
+}
+
+impl DebugConsole {
+    /// Create a console that admits connections presenting `token`
+    pub fn new(token: impl Into<String>) -> Self {
+        Self { token: token.into() }
+    }
+    
+    /// Answer an authenticated request
+    pub async fn handle(&self, sandbox: &WasmSandbox, request: ConsoleRequest) -> ConsoleResponse {
+        match request {
+            ConsoleRequest::Auth { .. } => ConsoleResponse::Authenticated,
+            ConsoleRequest::ListInstances => {
+                let mut instance_ids = sandbox.instance_ids();
+                instance_ids.sort_by_key(InstanceId::as_uuid);
+                let failing = sandbox.health().failing_instances;
+                ConsoleResponse::Instances {
+                    instances: instance_ids.into_iter()
+                        .filter_map(|instance_id| summary(sandbox, instance_id, &failing))
+                        .collect(),
+                }
+            }
+            ConsoleRequest::Instance { instance_id } => match details(sandbox, instance_id) {
+                Some(instance) => ConsoleResponse::Instance { instance: Box::new(instance) },
+                None => not_found(instance_id),
+            },
+            ConsoleRequest::Audit { instance_id, limit } => ConsoleResponse::Audit {
+                events: audit_events(sandbox, instance_id, limit.unwrap_or(DEFAULT_AUDIT_ENTRIES)),
+            },
+            ConsoleRequest::Pause { instance_id } => done(sandbox.pause_instance(instance_id).await),
+            ConsoleRequest::Resume { instance_id } => match sandbox.get_instance(instance_id) {
+                Some(_) => {
+                    sandbox.resume_instance(instance_id);
+                    ConsoleResponse::Done
+                }
+                None => not_found(instance_id),
+            },
+            ConsoleRequest::Terminate { instance_id } => done(sandbox.terminate_instance(instance_id)),
+        }
+    }
+    
+    /// Serve connections from a TCP listener until accepting one fails
+    pub async fn serve_tcp(&self, sandbox: &WasmSandbox, listener: TcpListener) -> Result<()> {
+        let connections = futures::stream::try_unfold(&listener, |listener| async move {
+            let (stream, _) = listener.accept().await?;
+            Ok::<_, SandboxError>(Some((stream, listener)))
+        });
+        connections.try_for_each_concurrent(MAX_CONNECTIONS, |stream| self.serve_connection(sandbox, stream)).await
+    }
+    
+    /// Serve connections from a Unix socket listener until accepting one fails
+    #[cfg(unix)]
+    pub async fn serve_unix(&self, sandbox: &WasmSandbox, listener: tokio::net::UnixListener) -> Result<()> {
+        let connections = futures::stream::try_unfold(&listener, |listener| async move {
+            let (stream, _) = listener.accept().await?;
+            Ok::<_, SandboxError>(Some((stream, listener)))
+        });
+        connections.try_for_each_concurrent(MAX_CONNECTIONS, |stream| self.serve_connection(sandbox, stream)).await
+    }
+    
+    /// Serve one connection until the client hangs up or fails to authenticate
+    async fn serve_connection<S>(&self, sandbox: &WasmSandbox, stream: S) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut stream = BufReader::new(stream);
+        let mut authenticated = false;
+        loop {
+            let request = match read_line(&mut stream).await {
+                Ok(Some(line)) => serde_json::from_str::<ConsoleRequest>(&line)
+                    .map_err(|e| format!("Malformed request: {}", e)),
+                Ok(None) => return Ok(()),
+                Err(e) => Err(e.to_string()),
+            };
+            let (response, close) = match (request, authenticated) {
+                (Ok(ConsoleRequest::Auth { token }), _) if tokens_match(&token, &self.token) => {
+                    authenticated = true;
+                    (ConsoleResponse::Authenticated, false)
+                }
+                (Ok(ConsoleRequest::Auth { .. }), _) => (error("Invalid token"), true),
+                (Ok(_), false) => (error("Authenticate first"), true),
+                (Ok(request), true) => (self.handle(sandbox, request).await, false),
+                (Err(message), _) => (error(message), !authenticated),
+            };
+            if let Err(e) = write_line(stream.get_mut(), &response).await {
+                log::debug!("Could not send console response: {}", e);
+                return Ok(());
+            }
+            if close {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Operator's side of the debug console
+pub struct ConsoleClient {
+    stream: BufReader<Box<dyn ConsoleStream>>,
+}
+
+impl ConsoleClient {
+    /// Connect to a console served over TCP and authenticate
+    pub async fn connect_tcp(addr: impl ToSocketAddrs, token: &str) -> Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        Self::authenticate(Box::new(stream), token).await
+    }
+    
+    /// Connect to a console served on a Unix socket and authenticate
+    #[cfg(unix)]
+    pub async fn connect_unix(path: impl AsRef<std::path::Path>, token: &str) -> Result<Self> {
+        let stream = tokio::net::UnixStream::connect(path).await?;
+        Self::authenticate(Box::new(stream), token).await
+    }
+    
+    async fn authenticate(stream: Box<dyn ConsoleStream>, token: &str) -> Result<Self> {
+        let mut client = Self { stream: BufReader::new(stream) };
+        client.request(&ConsoleRequest::Auth { token: token.to_string() }).await?;
+        Ok(client)
+    }
+    
+    /// Send a request, failing if the console answers with an error
+    pub async fn request(&mut self, request: &ConsoleRequest) -> Result<ConsoleResponse> {
+        write_line(self.stream.get_mut(), request).await?;
+        let line = read_line(&mut self.stream).await?
+            .ok_or_else(|| console_error("Console closed the connection"))?;
+        match serde_json::from_str(&line)? {
+            ConsoleResponse::Error { message } => Err(console_error(message)),
+            response => Ok(response),
+        }
+    }
+    
+    /// List the sandbox's instances
+    pub async fn list_instances(&mut self) -> Result<Vec<InstanceSummary>> {
+        match self.request(&ConsoleRequest::ListInstances).await? {
+            ConsoleResponse::Instances { instances } => Ok(instances),
+            response => Err(unexpected(response)),
+        }
+    }
+    
+    /// Show one instance in detail
+    pub async fn instance(&mut self, instance_id: InstanceId) -> Result<InstanceDetails> {
+        match self.request(&ConsoleRequest::Instance { instance_id }).await? {
+            ConsoleResponse::Instance { instance } => Ok(*instance),
+            response => Err(unexpected(response)),
+        }
+    }
+    
+    /// Read up to `limit` recent audit entries, optionally about one instance
+    pub async fn audit(&mut self, instance_id: Option<InstanceId>, limit: usize) -> Result<Vec<AuditEvent>> {
+        match self.request(&ConsoleRequest::Audit { instance_id, limit: Some(limit) }).await? {
+            ConsoleResponse::Audit { events } => Ok(events),
+            response => Err(unexpected(response)),
+        }
+    }
+    
+    /// Hold calls to an instance
+    pub async fn pause(&mut self, instance_id: InstanceId) -> Result<()> {
+        self.request(&ConsoleRequest::Pause { instance_id }).await.map(drop)
+    }
+    
+    /// Let calls to a paused instance run again
+    pub async fn resume(&mut self, instance_id: InstanceId) -> Result<()> {
+        self.request(&ConsoleRequest::Resume { instance_id }).await.map(drop)
+    }
+    
+    /// Interrupt an instance and fail its calls until the host removes it
+    pub async fn terminate(&mut self, instance_id: InstanceId) -> Result<()> {
+        self.request(&ConsoleRequest::Terminate { instance_id }).await.map(drop)
+    }
+}
+
+/// A connection the client can talk to the console over
+trait ConsoleStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> ConsoleStream for S {}
+
+fn summary(sandbox: &WasmSandbox, instance_id: InstanceId, failing: &[InstanceId]) -> Option<InstanceSummary> {
+    let instance = sandbox.get_instance(instance_id)?;
+    Some(InstanceSummary {
+        instance_id,
+        module_id: instance.module_id.to_string(),
+        memory_bytes: instance.instance.memory_usage(),
+        paused: sandbox.is_paused(instance_id),
+        terminated: sandbox.terminated_instances().contains(&instance_id),
+        hibernated: sandbox.is_hibernated(instance_id),
+        failing: failing.contains(&instance_id),
+    })
+}
+
+fn details(sandbox: &WasmSandbox, instance_id: InstanceId) -> Option<InstanceDetails> {
+    let summary = summary(sandbox, instance_id, &sandbox.health().failing_instances)?;
+    let instance = sandbox.get_instance(instance_id)?;
+    Some(InstanceDetails {
+        summary,
+        config: format!("{:#?}", instance.config),
+        capabilities: format!("{:#?}", instance.active_capabilities.current()),
+        resource_usage: sandbox.get_instance_resource_usage(instance_id).ok()?,
+        fuel_consumed: instance.instance.fuel_usage(),
+    })
+}
+
+/// The last `limit` entries of the sandbox's audit logs, oldest first
+fn audit_events(sandbox: &WasmSandbox, instance_id: Option<InstanceId>, limit: usize) -> Vec<AuditEvent> {
+    let mut events = sandbox.capability_audit_logger().get_events();
+    events.extend(sandbox.ingestion_audit_logger().get_events());
+    events.extend(sandbox.function_call_audit_logger().get_events());
+    if let Some(instance_id) = instance_id {
+        let instance_id = instance_id.to_string();
+        events.retain(|event| event_instance(event).as_deref() == Some(instance_id.as_str()));
+    }
+    events.sort_by_key(|event| event.timestamp);
+    let skip = events.len().saturating_sub(limit);
+    events.split_off(skip)
+}
+
+/// The instance an audit entry is about, if its type names one
+fn event_instance(event: &AuditEvent) -> Option<String> {
+    let value = serde_json::to_value(&event.event_type).ok()?;
+    let fields = value.as_object()?.values().next()?;
+    fields.get("instance_id")?.as_str().map(str::to_string)
+}
+
+/// Compare tokens without stopping at the first difference
+pub(crate) fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn done(result: Result<()>) -> ConsoleResponse {
+    match result {
+        Ok(()) => ConsoleResponse::Done,
+        Err(e) => error(e.to_string()),
+    }
+}
+
+fn error(message: impl Into<String>) -> ConsoleResponse {
+    ConsoleResponse::Error { message: message.into() }
+}
+
+fn not_found(instance_id: InstanceId) -> ConsoleResponse {
+    error(format!("No instance {}", instance_id))
+}
+
+fn unexpected(response: ConsoleResponse) -> SandboxError {
+    console_error(format!("Unexpected response: {:?}", response))
+}
+
+fn console_error(reason: impl Into<String>) -> SandboxError {
+    SandboxError::Communication {
+        channel: "debug console".to_string(),
+        reason: reason.into(),
+        instance_id: None,
+    }
+}
+
+/// Read one line, or `None` once the peer hangs up
+async fn read_line<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> Result<Option<String>> {
+    let mut line = String::new();
+    let read = (&mut *reader).take(MAX_REQUEST_BYTES as u64 + 1).read_line(&mut line).await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if read > MAX_REQUEST_BYTES {
+        return Err(SandboxError::InvalidInput {
+            field: "request".to_string(),
+            reason: format!("Console lines are limited to {} bytes", MAX_REQUEST_BYTES),
+            suggestion: None,
+        });
+    }
+    Ok(Some(line))
+}
+
+async fn write_line<W: AsyncWrite + Unpin, T: Serialize>(writer: &mut W, message: &T) -> Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.flush().await?;
+    Ok(())
+}
@@ -0,0 +1,213 @@
+//! `/healthz` and `/metrics` endpoints for embedded servers
+//!
+//! [`HealthEndpoints::serve`] runs a tiny hyper listener of its own, like
+//! [`crate::WasmSandbox::serve_http`]. Hosts that
+//! already run a server (axum, hyper, ...) call [`HealthEndpoints::handle`]
+//! from their own route instead, converting the method and URI into an
+//! [`HttpRequest`] and the [`HttpResponse`] back.
+//!
+//! `/healthz` answers with a small JSON document, and with status 503 once more
+//! instances are failing than the endpoints allow. `/metrics` renders the
+//! [`SandboxHealth`] in the Prometheus text exposition format.
+
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use tokio::net::TcpListener;
+
+use crate::error::Result;
+use crate::observability::SandboxHealth;
+use crate::runtime::metrics::LatencySummary;
+use crate::http_handler::{self, HttpRequest, HttpResponse};
+use crate::WasmSandbox;
+
+/// Path of the health check
+pub const HEALTH_PATH: &str = "/healthz";
+
+/// Path of the Prometheus metrics
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Content type of the Prometheus text exposition format
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Time a scraper has to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves a sandbox's health check and metrics
+#[derive(Debug, Clone, Default)]
+pub struct HealthEndpoints {
+    max_failing_instances: Option<usize>,
+}
+
+impl HealthEndpoints {
+    /// Create endpoints that report healthy whatever the instances do
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Report unhealthy once more than `count` instances are failing
+    pub fn max_failing_instances(mut self, count: usize) -> Self {
+        self.max_failing_instances = Some(count);
+        self
+    }
+    
+    /// Whether a snapshot counts as healthy
+    pub fn is_healthy(&self, health: &SandboxHealth) -> bool {
+        self.max_failing_instances.is_none_or(|max| health.failing_instances.len() <= max)
+    }
+    
+    /// Answer a request for either endpoint from the sandbox's current state
+    ///
+    /// Other paths get 404 and methods other than `GET` and `HEAD` get 405.
+    pub fn handle(&self, sandbox: &WasmSandbox, request: &HttpRequest) -> HttpResponse {
+        if request.path != HEALTH_PATH && request.path != METRICS_PATH {
+            return HttpResponse::text(404, "Not found");
+        }
+        if request.method != "GET" && request.method != "HEAD" {
+            return HttpResponse::text(405, "Only GET and HEAD are supported");
+        }
+        
+        let health = sandbox.health();
+        let mut response = match request.path.as_str() {
+            HEALTH_PATH => self.health_response(&health),
+            _ => metrics_response(&health),
+        };
+        if request.method == "HEAD" {
+            response.body.clear();
+        }
+        response
+    }
+    
+    /// The `/healthz` response for a snapshot
+    pub fn health_response(&self, health: &SandboxHealth) -> HttpResponse {
+        let healthy = self.is_healthy(health);
+        let body = serde_json::json!({
+            "status": if healthy { "ok" } else { "unhealthy" },
+            "instances": health.instances,
+            "failing_instances": health.failing_instances.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            "hibernated_instances": health.hibernated_instances,
+            "evicted_instances": health.evicted_instances,
+        });
+        HttpResponse {
+            status: if healthy { 200 } else { 503 },
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: body.to_string().into_bytes(),
+        }
+    }
+    
+    /// Serve both endpoints until accepting a connection fails
+    ///
+    /// Requests are answered one at a time, which is plenty for health checks
+    /// and scrapes.
+    pub async fn serve(&self, sandbox: &WasmSandbox, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let handle = |request: HttpRequest| std::future::ready(self.handle(sandbox, &request));
+            if let Err(e) = http_handler::serve_connection(stream, 0, READ_TIMEOUT, handle).await {
+                log::debug!("Could not serve health request: {}", e);
+            }
+        }
+    }
+}
+
+/// The `/metrics` response for a snapshot
+pub fn metrics_response(health: &SandboxHealth) -> HttpResponse {
+    HttpResponse {
+        status: 200,
+        headers: vec![("content-type".to_string(), METRICS_CONTENT_TYPE.to_string())],
+        body: render_metrics(health).into_bytes(),
+    }
+}
+
+/// Render a snapshot in the Prometheus text exposition format
+pub fn render_metrics(health: &SandboxHealth) -> String {
+    let mut out = String::new();
+    let runtime = &health.runtime;
+    let totals = &runtime.totals;
+    let gauges: [(&str, &str, f64); 6] = [
+        ("instances", "Live instances", health.instances as f64),
+        ("failing_instances", "Live instances whose last call failed", health.failing_instances.len() as f64),
+        ("hibernated_instances", "Instances hibernated to disk", health.hibernated_instances as f64),
+        ("evicted_instances", "Evicted instances kept as snapshots", health.evicted_instances as f64),
+        ("modules", "Compiled modules", runtime.runtime.compiled_modules as f64),
+        ("memory_bytes", "Linear memory of all instances in bytes", runtime.runtime.total_memory_usage as f64),
+    ];
+    for (name, help, value) in gauges {
+        metric(&mut out, name, "gauge", help, &[("", value)]);
+    }
+    
+    let counters: [(&str, &str, u64); 15] = [
+        ("calls_total", "Guest calls made", totals.calls),
+        ("failed_calls_total", "Guest calls that trapped or failed", totals.failed_calls),
+        ("instantiations_total", "Instances created", totals.instantiations),
+        ("fuel_consumed_total", "Fuel burned by guest calls", totals.fuel_consumed),
+        ("result_cache_hits_total", "Calls answered from the result cache", health.result_cache.hits),
+        ("result_cache_misses_total", "Cacheable calls that had to run the guest", health.result_cache.misses),
+        ("result_cache_evictions_total", "Results evicted from the cache to stay within its limits", health.result_cache.evictions),
+        ("result_cache_expirations_total", "Cached results dropped because their TTL passed", health.result_cache.expirations),
+        ("evictions_total", "Instances evicted under the memory budget", health.evictions.evictions),
+        ("recoveries_total", "Instances recreated after suspected corruption", health.recoveries.recoveries),
+        ("hibernations_total", "Instances hibernated while idle", health.hibernation.hibernations),
+        ("io_operations_total", "File reads and writes paced by the I/O budget", health.io.operations),
+        ("io_delayed_operations_total", "File operations that waited for their share of the disk", health.io.delayed_operations),
+        ("io_starved_operations_total", "File operations that waited past the starvation threshold", health.io.starved_operations),
+        ("io_refused_operations_total", "File operations failed because they would have waited too long", health.io.refused_operations),
+    ];
+    for (name, help, value) in counters {
+        metric(&mut out, name, "counter", help, &[("", value as f64)]);
+    }
+    metric(&mut out, "io_wait_seconds_total", "counter", "Time file operations waited for the disk", &[("", health.io.wait_time.as_secs_f64())]);
+    metric(&mut out, "result_cache_entries", "gauge", "Results in the cache", &[("", health.result_cache.entries as f64)]);
+    metric(&mut out, "result_cache_bytes", "gauge", "Size of the cached results in bytes", &[("", health.result_cache.bytes as f64)]);
+    
+    latency(&mut out, "call_latency_seconds", "Latency of recent guest calls", &totals.call_latency);
+    latency(&mut out, "instantiation_latency_seconds", "Latency of recent instantiations", &totals.instantiation_latency);
+    
+    let mut modules: Vec<_> = runtime.modules.iter()
+        .map(|(id, metrics)| (format!("module=\"{}\"", id), metrics))
+        .collect();
+    modules.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let calls: Vec<_> = modules.iter().map(|(labels, metrics)| (labels.as_str(), metrics.calls as f64)).collect();
+    let failed: Vec<_> = modules.iter().map(|(labels, metrics)| (labels.as_str(), metrics.failed_calls as f64)).collect();
+    metric(&mut out, "module_calls_total", "counter", "Guest calls made, by module", &calls);
+    metric(&mut out, "module_failed_calls_total", "counter", "Guest calls that trapped or failed, by module", &failed);
+    
+    let mut validated: Vec<_> = health.result_validation.iter()
+        .map(|(id, metrics)| (format!("module=\"{}\"", id), metrics))
+        .collect();
+    validated.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let invalid: Vec<_> = validated.iter().map(|(labels, metrics)| (labels.as_str(), metrics.failed as f64)).collect();
+    metric(&mut out, "module_invalid_results_total", "counter", "Guest results rejected by their function's result validator, by module", &invalid);
+    
+    let mut instances: Vec<_> = health.instance_io.iter()
+        .map(|(id, metrics)| (format!("instance=\"{}\"", id), metrics))
+        .collect();
+    instances.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let starved: Vec<_> = instances.iter().map(|(labels, metrics)| (labels.as_str(), metrics.starved_operations as f64)).collect();
+    metric(&mut out, "instance_io_starved_operations_total", "counter", "File operations that waited past the starvation threshold, by instance", &starved);
+    out
+}
+
+/// Write one metric family
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(&str, f64)]) {
+    let _ = writeln!(out, "# HELP wasm_sandbox_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE wasm_sandbox_{} {}", name, kind);
+    for (labels, value) in samples {
+        let _ = if labels.is_empty() {
+            writeln!(out, "wasm_sandbox_{} {}", name, value)
+        } else {
+            writeln!(out, "wasm_sandbox_{}{{{}}} {}", name, labels, value)
+        };
+    }
+}
+
+/// Write a latency window as a summary
+fn latency(out: &mut String, name: &str, help: &str, summary: &LatencySummary) {
+    let quantiles = [
+        ("quantile=\"0.5\"", summary.p50.as_secs_f64()),
+        ("quantile=\"0.95\"", summary.p95.as_secs_f64()),
+        ("quantile=\"0.99\"", summary.p99.as_secs_f64()),
+    ];
+    metric(out, name, "summary", help, &quantiles);
+    let _ = writeln!(out, "wasm_sandbox_{}_count {}", name, summary.count);
+}
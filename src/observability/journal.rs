@@ -0,0 +1,639 @@
+//! Ordered journal of what happens in a sandbox
+//!
+//! Lifecycle events, records from the sandbox's audit logs, calls stopped by
+//! resource limits and a summary of every call are appended to one
+//! [`Journal`] per sandbox, each with a sequence number that orders it
+//! against the rest. Hosts feed the journal to external billing or compliance
+//! pipelines through [`JournalSink`]s: files of JSON lines, a webhook, the
+//! `tracing` subscriber, or with the `sqlite-journal` feature an SQLite
+//! database. Entries stay in memory under the [`JournalRetention`] policy, so
+//! a consumer that falls behind or restarts can read on from a saved
+//! [`JournalCursor`], and a sink added late can be replayed the entries it
+//! missed. Events are sampled and have fields hashed under the sandbox's
+//! [`TelemetryConfig`] before they are recorded.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::observability::telemetry::{TelemetryConfig, TelemetryFilter};
+use crate::runtime::call_context::CallId;
+use crate::security::audit::AuditEvent;
+
+/// Entries kept in memory by default
+pub const DEFAULT_MAX_JOURNAL_ENTRIES: usize = 10_000;
+
+/// Something that happened in a sandbox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JournalEvent {
+    /// Module compiled and loaded
+    ModuleLoaded {
+        /// Module ID
+        module_id: String,
+        
+        /// Size of the module in bytes
+        size: usize,
+    },
+    
+    /// Instance created
+    InstanceCreated {
+        /// Instance ID
+        instance_id: String,
+        
+        /// Module the instance runs
+        module_id: String,
+    },
+    
+    /// Instance removed
+    InstanceRemoved {
+        /// Instance ID
+        instance_id: String,
+    },
+    
+    /// Record written to one of the sandbox's audit logs
+    Audit {
+        /// The audit record
+        record: AuditEvent,
+    },
+    
+    /// Call stopped by a resource limit or timeout
+    ResourceViolation {
+        /// Instance ID
+        instance_id: String,
+        
+        /// Function called
+        function_name: String,
+        
+        /// The call
+        call_id: CallId,
+        
+        /// The error the call failed with, redacted
+        error: String,
+    },
+    
+    /// Call into an instance finished
+    CallCompleted {
+        /// Instance ID
+        instance_id: String,
+        
+        /// Function called
+        function_name: String,
+        
+        /// The call
+        call_id: CallId,
+        
+        /// How long the call took in milliseconds
+        duration_ms: u64,
+        
+        /// The error the call failed with, redacted, or `None` if it succeeded
+        error: Option<String>,
+    },
+    
+    /// Event recorded by the host
+    Custom {
+        /// Event type
+        event_type: String,
+        
+        /// Event data
+        data: serde_json::Value,
+    },
+}
+
+impl JournalEvent {
+    /// Name of the event's kind, as it is tagged when serialized
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JournalEvent::ModuleLoaded { .. } => "module_loaded",
+            JournalEvent::InstanceCreated { .. } => "instance_created",
+            JournalEvent::InstanceRemoved { .. } => "instance_removed",
+            JournalEvent::Audit { .. } => "audit",
+            JournalEvent::ResourceViolation { .. } => "resource_violation",
+            JournalEvent::CallCompleted { .. } => "call_completed",
+            JournalEvent::Custom { .. } => "custom",
+        }
+    }
+    
+    /// ID of the instance the event is about, if any
+    pub fn instance_id(&self) -> Option<&str> {
+        match self {
+            JournalEvent::InstanceCreated { instance_id, .. }
+            | JournalEvent::InstanceRemoved { instance_id }
+            | JournalEvent::ResourceViolation { instance_id, .. }
+            | JournalEvent::CallCompleted { instance_id, .. } => Some(instance_id),
+            JournalEvent::Audit { record } => record.event_type.instance_id(),
+            JournalEvent::ModuleLoaded { .. } | JournalEvent::Custom { .. } => None,
+        }
+    }
+}
+
+/// One entry in a journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Position of the entry in the journal, counting up from 0
+    pub sequence: u64,
+    
+    /// When the entry was recorded
+    pub timestamp: SystemTime,
+    
+    /// What happened
+    pub event: JournalEvent,
+}
+
+/// How long entries stay in memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalRetention {
+    /// Most entries kept; the oldest are dropped first
+    pub max_entries: usize,
+    
+    /// Entries older than this are dropped, or `None` to keep them until `max_entries` is reached
+    pub max_age: Option<Duration>,
+}
+
+impl Default for JournalRetention {
+    fn default() -> Self {
+        Self {
+            max_entries: DEFAULT_MAX_JOURNAL_ENTRIES,
+            max_age: None,
+        }
+    }
+}
+
+/// Position of a consumer in a journal
+///
+/// Cursors serialize, so a consumer can save its position and resume from it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct JournalCursor {
+    next: u64,
+}
+
+impl JournalCursor {
+    /// Cursor at the first entry ever recorded
+    pub fn start() -> Self {
+        Self::default()
+    }
+    
+    /// Cursor at the entry with sequence number `sequence`
+    pub fn at(sequence: u64) -> Self {
+        Self { next: sequence }
+    }
+    
+    /// Sequence number of the next entry the cursor reads
+    pub fn position(&self) -> u64 {
+        self.next
+    }
+}
+
+/// Counters of a journal
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JournalMetrics {
+    /// Entries recorded
+    pub recorded: u64,
+    
+    /// Entries dropped from memory under the retention policy
+    pub dropped: u64,
+    
+    /// Entries a sink failed to write
+    pub sink_failures: u64,
+    
+    /// Events left out by the telemetry configuration's sampling
+    pub sampled_out: u64,
+}
+
+/// Destination entries are written to as they are recorded
+///
+/// Sinks are called in sequence order while the journal is locked, so they
+/// should hand slow work such as network requests to a thread of their own.
+pub trait JournalSink: Send + Sync {
+    /// Write an entry
+    fn write(&self, entry: &JournalEntry) -> Result<()>;
+    
+    /// Wait until the entries written so far have reached their destination
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct JournalState {
+    entries: VecDeque<JournalEntry>,
+    next_sequence: u64,
+    retention: JournalRetention,
+    sinks: Vec<Arc<dyn JournalSink>>,
+    metrics: JournalMetrics,
+    telemetry: TelemetryFilter,
+}
+
+impl JournalState {
+    fn write_to(&mut self, sink: &dyn JournalSink, entry: &JournalEntry) {
+        if let Err(e) = sink.write(entry) {
+            self.metrics.sink_failures += 1;
+            log::warn!("Journal sink failed to write entry {}: {}", entry.sequence, e);
+        }
+    }
+    
+    fn retain(&mut self) {
+        let now = SystemTime::now();
+        let max_age = self.retention.max_age;
+        let expired = |entry: &JournalEntry| max_age.is_some_and(|max_age| {
+            now.duration_since(entry.timestamp).is_ok_and(|age| age > max_age)
+        });
+        while self.entries.len() > self.retention.max_entries || self.entries.front().is_some_and(expired) {
+            self.entries.pop_front();
+            self.metrics.dropped += 1;
+        }
+    }
+}
+
+/// Ordered record of a sandbox's events
+pub struct Journal {
+    state: Mutex<JournalState>,
+}
+
+impl Journal {
+    /// Create an empty journal keeping entries under `retention`
+    pub fn new(retention: JournalRetention) -> Self {
+        Self {
+            state: Mutex::new(JournalState {
+                entries: VecDeque::new(),
+                next_sequence: 0,
+                retention,
+                sinks: Vec::new(),
+                metrics: JournalMetrics::default(),
+                telemetry: TelemetryFilter::default(),
+            }),
+        }
+    }
+    
+    /// Append an event, writing it to every sink, and return its sequence number
+    ///
+    /// The event is first passed through the [`TelemetryConfig`]; `None` means
+    /// it was sampled out and not recorded.
+    pub fn record(&self, event: JournalEvent) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        let Some(event) = state.telemetry.apply(event) else {
+            state.metrics.sampled_out += 1;
+            return None;
+        };
+        let entry = JournalEntry {
+            sequence: state.next_sequence,
+            timestamp: SystemTime::now(),
+            event,
+        };
+        state.next_sequence += 1;
+        state.metrics.recorded += 1;
+        for sink in state.sinks.clone() {
+            state.write_to(sink.as_ref(), &entry);
+        }
+        state.entries.push_back(entry);
+        state.retain();
+        Some(state.next_sequence - 1)
+    }
+    
+    /// Write entries recorded from now on to `sink`
+    pub fn add_sink(&self, sink: Arc<dyn JournalSink>) {
+        self.state.lock().unwrap().sinks.push(sink);
+    }
+    
+    /// Replay the entries still in memory from `cursor` to `sink`, then write new ones to it
+    ///
+    /// Returns the number of entries replayed.
+    pub fn add_sink_from(&self, sink: Arc<dyn JournalSink>, cursor: JournalCursor) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let missed: Vec<_> = state.entries.iter()
+            .filter(|entry| entry.sequence >= cursor.next)
+            .cloned()
+            .collect();
+        for entry in &missed {
+            state.write_to(sink.as_ref(), entry);
+        }
+        state.sinks.push(sink);
+        missed.len()
+    }
+    
+    /// Wait for every sink to deliver the entries written to it
+    pub fn flush(&self) -> Result<()> {
+        let sinks = self.state.lock().unwrap().sinks.clone();
+        sinks.iter().try_for_each(|sink| sink.flush())
+    }
+    
+    /// Cursor after the latest entry, reading only entries recorded from now on
+    pub fn cursor(&self) -> JournalCursor {
+        JournalCursor::at(self.state.lock().unwrap().next_sequence)
+    }
+    
+    /// Read up to `limit` entries from `cursor` and move it past them
+    ///
+    /// Entries dropped under the retention policy are skipped; a gap in the
+    /// sequence numbers shows that the consumer fell behind.
+    pub fn read(&self, cursor: &mut JournalCursor, limit: usize) -> Vec<JournalEntry> {
+        let state = self.state.lock().unwrap();
+        let entries: Vec<_> = state.entries.iter()
+            .filter(|entry| entry.sequence >= cursor.next)
+            .take(limit)
+            .cloned()
+            .collect();
+        if let Some(last) = entries.last() {
+            cursor.next = last.sequence + 1;
+        }
+        entries
+    }
+    
+    /// Entries still in memory, oldest first
+    pub fn entries(&self) -> Vec<JournalEntry> {
+        let mut state = self.state.lock().unwrap();
+        state.retain();
+        state.entries.iter().cloned().collect()
+    }
+    
+    /// The retention policy in force
+    pub fn retention(&self) -> JournalRetention {
+        self.state.lock().unwrap().retention.clone()
+    }
+    
+    /// Keep entries under a new retention policy
+    pub fn set_retention(&self, retention: JournalRetention) {
+        let mut state = self.state.lock().unwrap();
+        state.retention = retention;
+        state.retain();
+    }
+    
+    /// The telemetry configuration events are recorded under
+    pub fn telemetry(&self) -> TelemetryConfig {
+        self.state.lock().unwrap().telemetry.config.clone()
+    }
+    
+    /// Record events from now on under a new telemetry configuration
+    pub fn set_telemetry(&self, config: TelemetryConfig) {
+        self.state.lock().unwrap().telemetry.config = config;
+    }
+    
+    /// Attribute an instance's events to `tenant`, or to no tenant
+    pub(crate) fn set_tenant(&self, instance_id: &str, tenant: Option<&str>) {
+        self.state.lock().unwrap().telemetry.set_tenant(instance_id, tenant);
+    }
+    
+    /// Counters of recorded, dropped and undelivered entries
+    pub fn metrics(&self) -> JournalMetrics {
+        self.state.lock().unwrap().metrics
+    }
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self::new(JournalRetention::default())
+    }
+}
+
+impl std::fmt::Debug for Journal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("Journal")
+            .field("entries", &state.entries.len())
+            .field("next_sequence", &state.next_sequence)
+            .field("retention", &state.retention)
+            .field("sinks", &state.sinks.len())
+            .finish()
+    }
+}
+
+/// Appends entries to a file as JSON lines
+pub struct FileJournalSink {
+    file: Mutex<File>,
+}
+
+impl FileJournalSink {
+    /// Append to `path`, creating it if needed
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| Error::Filesystem {
+            operation: "open journal".to_string(),
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+}
+
+impl JournalSink for FileJournalSink {
+    fn write(&self, entry: &JournalEntry) -> Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.lock().unwrap().write_all(&line)?;
+        Ok(())
+    }
+    
+    fn flush(&self) -> Result<()> {
+        self.file.lock().unwrap().sync_data()?;
+        Ok(())
+    }
+}
+
+/// `tracing` target entries are emitted under
+pub const JOURNAL_TRACING_TARGET: &str = "wasm_sandbox::journal";
+
+/// Emits entries as `tracing` events under [`JOURNAL_TRACING_TARGET`]
+#[derive(Debug, Default)]
+pub struct TracingJournalSink;
+
+impl JournalSink for TracingJournalSink {
+    fn write(&self, entry: &JournalEntry) -> Result<()> {
+        let event = serde_json::to_string(&entry.event)?;
+        tracing::info!(target: JOURNAL_TRACING_TARGET, sequence = entry.sequence, event = %event);
+        Ok(())
+    }
+}
+
+enum WebhookMessage {
+    Entry(Vec<u8>),
+    Flush(mpsc::Sender<()>),
+}
+
+/// Posts entries as JSON to an `http://` endpoint from a thread of its own
+///
+/// Each entry is posted on its own, in order. Failed posts are logged and
+/// counted in [`WebhookJournalSink::failures`] rather than retried; consumers
+/// needing every entry should read from a [`JournalCursor`] instead.
+pub struct WebhookJournalSink {
+    sender: Mutex<Option<mpsc::Sender<WebhookMessage>>>,
+    worker: Option<JoinHandle<()>>,
+    failures: Arc<Mutex<u64>>,
+}
+
+impl WebhookJournalSink {
+    /// Post to `url`, of the form `http://host[:port][/path]`
+    pub fn new(url: &str) -> Result<Self> {
+        let endpoint = WebhookEndpoint::parse(url)?;
+        let (sender, receiver) = mpsc::channel();
+        let failures = Arc::new(Mutex::new(0));
+        let counted = failures.clone();
+        let worker = std::thread::Builder::new()
+            .name("journal-webhook".to_string())
+            .spawn(move || {
+                for message in receiver {
+                    match message {
+                        WebhookMessage::Entry(body) => {
+                            if let Err(e) = endpoint.post(&body) {
+                                *counted.lock().unwrap() += 1;
+                                log::warn!("Journal webhook {} failed: {}", endpoint.host, e);
+                            }
+                        }
+                        WebhookMessage::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })?;
+        Ok(Self {
+            sender: Mutex::new(Some(sender)),
+            worker: Some(worker),
+            failures,
+        })
+    }
+    
+    /// Entries the endpoint did not accept
+    pub fn failures(&self) -> u64 {
+        *self.failures.lock().unwrap()
+    }
+    
+    fn send(&self, message: WebhookMessage) -> Result<()> {
+        let sender = self.sender.lock().unwrap();
+        sender.as_ref()
+            .and_then(|sender| sender.send(message).ok())
+            .ok_or_else(|| webhook_error("the webhook thread stopped"))
+    }
+}
+
+impl JournalSink for WebhookJournalSink {
+    fn write(&self, entry: &JournalEntry) -> Result<()> {
+        self.send(WebhookMessage::Entry(serde_json::to_vec(entry)?))
+    }
+    
+    fn flush(&self) -> Result<()> {
+        let (done, flushed) = mpsc::channel();
+        self.send(WebhookMessage::Flush(done))?;
+        flushed.recv().map_err(|_| webhook_error("the webhook thread stopped"))
+    }
+}
+
+impl Drop for WebhookJournalSink {
+    fn drop(&mut self) {
+        // Closing the channel lets the thread post what is queued and exit
+        self.sender.lock().unwrap().take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Where a webhook posts to
+struct WebhookEndpoint {
+    host: String,
+    path: String,
+}
+
+impl WebhookEndpoint {
+    fn parse(url: &str) -> Result<Self> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| Error::InvalidInput {
+            field: "webhook url".to_string(),
+            reason: format!("'{}' is not an http:// URL", url),
+            suggestion: Some("Post to a local collector that forwards over TLS".to_string()),
+        })?;
+        let (host, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        if host.is_empty() {
+            return Err(Error::InvalidInput {
+                field: "webhook url".to_string(),
+                reason: format!("'{}' has no host", url),
+                suggestion: None,
+            });
+        }
+        let host = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+        Ok(Self { host, path: path.to_string() })
+    }
+    
+    fn post(&self, body: &[u8]) -> Result<()> {
+        let mut stream = TcpStream::connect(&self.host)?;
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.path, self.host, body.len(),
+        )?;
+        stream.write_all(body)?;
+        
+        let mut status = String::new();
+        BufReader::new(stream).read_line(&mut status)?;
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(webhook_error(&format!("endpoint answered '{}'", status.trim()))),
+        }
+    }
+}
+
+fn webhook_error(reason: &str) -> Error {
+    Error::Communication {
+        channel: "journal webhook".to_string(),
+        reason: reason.to_string(),
+        instance_id: None,
+    }
+}
+
+/// Inserts entries into an SQLite table
+///
+/// The `journal` table is created if missing, with a row per entry holding
+/// its `sequence`, `timestamp_ms`, event `kind` and the event as JSON in
+/// `event`. Sequence numbers start again at 0 for each sandbox, so rows are
+/// ordered by their `id`.
+#[cfg(feature = "sqlite-journal")]
+pub struct SqliteJournalSink {
+    connection: Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite-journal")]
+impl SqliteJournalSink {
+    /// Write to the database at `path`, creating it if needed
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let connection = rusqlite::Connection::open(path).map_err(sqlite_error)?;
+        connection.execute(
+            "CREATE TABLE IF NOT EXISTS journal (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                sequence INTEGER NOT NULL,
+                timestamp_ms INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                event TEXT NOT NULL
+            )",
+            (),
+        ).map_err(sqlite_error)?;
+        Ok(Self { connection: Mutex::new(connection) })
+    }
+}
+
+#[cfg(feature = "sqlite-journal")]
+impl JournalSink for SqliteJournalSink {
+    fn write(&self, entry: &JournalEntry) -> Result<()> {
+        let event = serde_json::to_value(&entry.event)?;
+        let kind = event["kind"].as_str().unwrap_or_default().to_string();
+        let timestamp_ms = entry.timestamp.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as i64;
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO journal (sequence, timestamp_ms, kind, event) VALUES (?1, ?2, ?3, ?4)",
+            (entry.sequence as i64, timestamp_ms, kind, event.to_string()),
+        ).map_err(sqlite_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite-journal")]
+fn sqlite_error(e: rusqlite::Error) -> Error {
+    Error::Generic { message: format!("SQLite journal: {}", e) }
+}
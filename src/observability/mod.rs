@@ -0,0 +1,66 @@
+//! Health and metrics of a running sandbox
+//!
+//! [`WasmSandbox::health`](crate::WasmSandbox::health) takes a
+//! [`SandboxHealth`] snapshot of the sandbox's instances, call counters and
+//! cache statistics. The [`http`] module serves it to load balancers and
+//! Prometheus as `/healthz` and `/metrics`, either from a small listener of its
+//! own or from a handler inside an existing server. The [`console`] module
+//! lets an operator inspect instances and pause, resume or terminate them
+//! over an authenticated socket.
+
+pub mod console;
+pub mod http;
+pub mod journal;
+pub mod telemetry;
+
+use std::collections::HashMap;
+
+use crate::runtime::eviction::EvictionMetrics;
+use crate::runtime::hibernation::HibernationMetrics;
+use crate::runtime::io_scheduler::IoMetrics;
+use crate::runtime::metrics::DetailedMetrics;
+use crate::runtime::recovery::RecoveryMetrics;
+use crate::runtime::result_cache::ResultCacheMetrics;
+use crate::runtime::result_validation::ResultValidationMetrics;
+use crate::runtime::ModuleId;
+use crate::InstanceId;
+
+/// A sandbox's instances and metrics at one point in time
+#[derive(Debug, Clone)]
+pub struct SandboxHealth {
+    /// Live instances
+    pub instances: usize,
+    
+    /// Live instances whose last call failed
+    pub failing_instances: Vec<InstanceId>,
+    
+    /// Instances hibernated to disk
+    pub hibernated_instances: usize,
+    
+    /// Evicted instances kept as snapshots
+    pub evicted_instances: usize,
+    
+    /// The runtime's counters, latencies and per-module breakdowns
+    pub runtime: DetailedMetrics,
+    
+    /// Counters of the pure-function result cache
+    pub result_cache: ResultCacheMetrics,
+    
+    /// Counters of instances evicted under the memory budget
+    pub evictions: EvictionMetrics,
+    
+    /// Counters of instances recreated after suspected corruption
+    pub recoveries: RecoveryMetrics,
+    
+    /// Counters of instances hibernated while idle
+    pub hibernation: HibernationMetrics,
+    
+    /// Counters of file operations paced by the I/O budget
+    pub io: IoMetrics,
+    
+    /// The same counters for each live instance under the I/O budget
+    pub instance_io: HashMap<InstanceId, IoMetrics>,
+    
+    /// Counters of validated guest results for each module with a result validator
+    pub result_validation: HashMap<ModuleId, ResultValidationMetrics>,
+}
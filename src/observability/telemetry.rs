@@ -0,0 +1,201 @@
+//! What a sandbox's telemetry may reveal
+//!
+//! Everything recorded in a sandbox's [`Journal`](super::journal::Journal)
+//! can leave the host through its sinks. [`TelemetryConfig`], set as
+//! [`crate::SandboxConfig::telemetry`], decides what does: each kind of event
+//! can be sampled so only a fraction of it is kept, and named fields of
+//! events, such as the names of secrets or staged files, are replaced by a
+//! salted hash, so events can still be correlated without revealing the
+//! values. Instances created with an [`crate::InstanceConfig::tenant`] follow
+//! that tenant's overrides.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::observability::journal::JournalEvent;
+
+/// Controls over the events a sandbox exports
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TelemetryConfig {
+    /// Fraction of events kept, from 0.0 to 1.0, by event kind such as
+    /// `call_completed`; kinds not listed are all kept
+    pub sampling: BTreeMap<String, f64>,
+    
+    /// Names of event fields whose text is replaced by a hash, e.g. `function_name`
+    pub hashed_fields: BTreeSet<String>,
+    
+    /// Mixed into every hash, so values can't be recovered by hashing likely ones
+    pub hash_salt: String,
+    
+    /// Overrides for the instances of each tenant
+    pub tenants: BTreeMap<String, TenantTelemetry>,
+}
+
+impl TelemetryConfig {
+    /// Export every event as it is
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Keep only `rate` of the events of `kind`
+    pub fn sample(mut self, kind: &str, rate: f64) -> Self {
+        self.sampling.insert(kind.to_string(), rate);
+        self
+    }
+    
+    /// Replace the text of `field` in every event with its hash
+    pub fn hash_field(mut self, field: &str) -> Self {
+        self.hashed_fields.insert(field.to_string());
+        self
+    }
+    
+    /// Salt hashes with `salt`
+    pub fn hash_salt(mut self, salt: &str) -> Self {
+        self.hash_salt = salt.to_string();
+        self
+    }
+    
+    /// Apply `overrides` to the events of `tenant`'s instances
+    pub fn tenant(mut self, tenant: &str, overrides: TenantTelemetry) -> Self {
+        self.tenants.insert(tenant.to_string(), overrides);
+        self
+    }
+    
+    /// Check that every sampling rate is between 0 and 1
+    pub fn validate(&self) -> Result<()> {
+        let rates = self.sampling.iter()
+            .map(|(kind, rate)| (None, kind, rate))
+            .chain(self.tenants.iter().flat_map(|(tenant, overrides)| {
+                overrides.sampling.iter().map(move |(kind, rate)| (Some(tenant), kind, rate))
+            }));
+        for (tenant, kind, rate) in rates {
+            if !(0.0..=1.0).contains(rate) {
+                let field = match tenant {
+                    Some(tenant) => format!("telemetry.tenants.{}.sampling.{}", tenant, kind),
+                    None => format!("telemetry.sampling.{}", kind),
+                };
+                return Err(Error::InvalidInput {
+                    field,
+                    reason: format!("Sampling rate {} is not between 0 and 1", rate),
+                    suggestion: Some("Use 0.0 to drop every event of the kind and 1.0 to keep them all".to_string()),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A tenant's departures from a sandbox's [`TelemetryConfig`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TenantTelemetry {
+    /// Sampling rates used instead of the sandbox's for these kinds
+    pub sampling: BTreeMap<String, f64>,
+    
+    /// Fields hashed instead of the sandbox's, or `None` to hash the same ones
+    pub hashed_fields: Option<BTreeSet<String>>,
+}
+
+impl TenantTelemetry {
+    /// Follow the sandbox's configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Keep only `rate` of the tenant's events of `kind`
+    pub fn sample(mut self, kind: &str, rate: f64) -> Self {
+        self.sampling.insert(kind.to_string(), rate);
+        self
+    }
+    
+    /// Hash exactly `fields` in the tenant's events
+    pub fn hashed_fields<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.hashed_fields = Some(fields.into_iter().map(Into::into).collect());
+        self
+    }
+}
+
+/// Hash of `value` as it appears in exported events
+pub fn hash_value(salt: &str, value: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(salt.as_bytes())
+        .chain_update(value.as_bytes())
+        .finalize();
+    let hex: String = digest[..8].iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256:{}", hex)
+}
+
+/// A [`TelemetryConfig`] and the tenants of a sandbox's instances
+#[derive(Debug, Default)]
+pub(crate) struct TelemetryFilter {
+    pub(crate) config: TelemetryConfig,
+    tenants: HashMap<String, String>,
+}
+
+impl TelemetryFilter {
+    /// Attribute the events of an instance to `tenant`, or to no tenant
+    pub(crate) fn set_tenant(&mut self, instance_id: &str, tenant: Option<&str>) {
+        match tenant {
+            Some(tenant) => self.tenants.insert(instance_id.to_string(), tenant.to_string()),
+            None => self.tenants.remove(instance_id),
+        };
+    }
+    
+    /// The event as it may be exported, or `None` if it was sampled out
+    pub(crate) fn apply(&self, event: JournalEvent) -> Option<JournalEvent> {
+        let tenant = event.instance_id()
+            .and_then(|instance_id| self.tenants.get(instance_id))
+            .and_then(|tenant| self.config.tenants.get(tenant));
+        let kind = event.kind();
+        let rate = tenant.and_then(|tenant| tenant.sampling.get(kind))
+            .or_else(|| self.config.sampling.get(kind))
+            .copied()
+            .unwrap_or(1.0);
+        if rate < 1.0 && rand::random::<f64>() >= rate {
+            return None;
+        }
+        
+        let fields = tenant.and_then(|tenant| tenant.hashed_fields.as_ref()).unwrap_or(&self.config.hashed_fields);
+        if fields.is_empty() {
+            return Some(event);
+        }
+        let mut value = serde_json::to_value(&event).ok()?;
+        if let Value::Object(object) = &mut value {
+            for (key, value) in object.iter_mut().filter(|(key, _)| *key != "kind") {
+                hash_fields(key, value, fields, &self.config.hash_salt);
+            }
+        }
+        match serde_json::from_value(value) {
+            Ok(event) => Some(event),
+            Err(e) => {
+                // Exporting the raw event would leak what the configuration hides
+                log::warn!("Dropped a {} event whose fields couldn't be hashed: {}", kind, e);
+                None
+            }
+        }
+    }
+}
+
+/// Hash the text of `value`, stored under `key`, and of its nested fields
+fn hash_fields(key: &str, value: &mut Value, fields: &BTreeSet<String>, salt: &str) {
+    match value {
+        Value::String(text) if fields.contains(key) => *text = hash_value(salt, text),
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                hash_fields(key, value, fields, salt);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                hash_fields(key, value, fields, salt);
+            }
+        }
+        _ => {}
+    }
+}
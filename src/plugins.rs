@@ -6,7 +6,9 @@ use serde::{Serialize, Deserialize};
 use async_trait::async_trait;
 use serde_json::Value;
 
+use crate::bundle::{Bundle, BundleVerifier};
 use crate::error::{Result, InstanceId};
+use crate::utils::version::{ApiVersion, VersionRange};
 use crate::config::AdvancedCapabilities;
 use crate::monitoring::DetailedResourceUsage;
 
@@ -50,6 +52,20 @@ pub struct PluginManifest {
     pub repository: Option<String>,
 }
 
+impl PluginManifest {
+    /// Check whether the plugin version satisfies a requirement such as `^1.2` or `>=1.0, <2.0`
+    pub fn satisfies(&self, requirement: &str) -> Result<bool> {
+        version_satisfies(&self.version, requirement)
+    }
+}
+
+/// Check whether `version` satisfies a semver-style `requirement`
+pub fn version_satisfies(version: &str, requirement: &str) -> Result<bool> {
+    let version: ApiVersion = version.parse()?;
+    let range: VersionRange = requirement.parse()?;
+    Ok(range.contains(&version))
+}
+
 /// Plugin entry point definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EntryPoint {
@@ -106,6 +122,13 @@ pub struct Dependency {
     pub dependency_type: DependencyType,
 }
 
+impl Dependency {
+    /// Check whether a registered plugin satisfies this dependency
+    pub fn is_satisfied_by(&self, manifest: &PluginManifest) -> Result<bool> {
+        Ok(manifest.id == self.name && manifest.satisfies(&self.version_requirement)?)
+    }
+}
+
 /// Types of dependencies
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DependencyType {
@@ -409,6 +432,12 @@ pub trait PluginRegistry: Send + Sync {
     
     /// Get plugin dependencies
     fn get_dependencies(&self, plugin_id: &str) -> Result<Vec<&PluginManifest>>;
+    
+    /// Register the plugin in a bundle once `verifier` trusts its signature
+    fn register_bundle(&mut self, bundle: Bundle, verifier: &dyn BundleVerifier) -> Result<()> {
+        bundle.verify(verifier)?;
+        self.register_plugin(bundle.manifest, bundle.module)
+    }
 }
 
 /// Plugin search query
@@ -430,6 +459,17 @@ pub struct PluginQuery {
     pub required_capabilities: Vec<String>,
 }
 
+impl PluginQuery {
+    /// Check whether a plugin meets the query's minimum version
+    pub fn matches_version(&self, manifest: &PluginManifest) -> bool {
+        match &self.min_version {
+            Some(min_version) => version_satisfies(&manifest.version, &format!(">={}", min_version))
+                .unwrap_or(false),
+            None => true,
+        }
+    }
+}
+
 /// Default plugin query implementation
 impl Default for PluginQuery {
     fn default() -> Self {
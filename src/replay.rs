@@ -0,0 +1,611 @@
+//! Recording and deterministic replay of sessions
+//!
+//! A [`Recorder`] runs a module and logs every export the host calls, every host
+//! function call the guest makes, and every clock read and random byte the guest
+//! receives through WASI. A [`Replayer`] feeds the same inputs to another build of
+//! the module, serving host calls, time, and randomness from the recording, and
+//! reports where its behavior diverges.
+//!
+//! ```rust,no_run
+//! use wasm_sandbox::replay::{Recorder, Replayer};
+//! use wasm_sandbox::runtime::HostValue;
+//!
+//! # fn main() -> wasm_sandbox::Result<()> {
+//! let mut session = Recorder::new().start(&std::fs::read("plugin-v1.wasm")?)?;
+//! session.call("process", &[HostValue::I32(7)])?;
+//! let recording = session.finish();
+//! recording.save("session.json")?;
+//!
+//! let report = Replayer::new(recording).replay(&std::fs::read("plugin-v2.wasm")?)?;
+//! assert!(report.is_identical(), "{:?}", report.divergences);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeSet, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::runtime::{EntropySource, GuestCall, HostFunctions, HostValue, RuntimeConfig, WasmInstance, WasmRuntime};
+use crate::runtime::wasmtime::WasmtimeRuntime;
+use crate::security::{Capabilities, ResourceLimits};
+use crate::testing::HostCall;
+
+/// WASI realtime clock ID
+const REALTIME_CLOCK: u32 = 0;
+
+/// Outcome of an export call: its results, or the error message
+pub type CallOutcome = std::result::Result<Vec<HostValue>, String>;
+
+/// Something that happened during a recorded session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SessionEvent {
+    /// The host called an export
+    Call {
+        /// Export name
+        function: String,
+        
+        /// Arguments
+        args: Vec<HostValue>,
+        
+        /// What the export returned
+        outcome: CallOutcome,
+    },
+    
+    /// The guest called a host function
+    HostCall(HostCall),
+    
+    /// The guest read a WASI clock
+    Clock {
+        /// WASI clock ID
+        clock_id: u32,
+        
+        /// Time returned in nanoseconds
+        nanos: u64,
+    },
+    
+    /// The guest requested random bytes
+    Random {
+        /// Bytes returned
+        bytes: Vec<u8>,
+    },
+}
+
+/// A session event and when it happened
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Time since the session started
+    pub elapsed: Duration,
+    
+    /// The event
+    pub event: SessionEvent,
+}
+
+/// Everything recorded during a session
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecording {
+    /// Events in the order they happened
+    pub events: Vec<RecordedEvent>,
+}
+
+impl SessionRecording {
+    /// Write the recording as JSON
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path.as_ref(), json).map_err(|e| Error::Filesystem {
+            operation: "write recording".to_string(),
+            path: path.as_ref().to_path_buf(),
+            reason: e.to_string(),
+        })
+    }
+    
+    /// Read a recording written by [`SessionRecording::save`]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let json = std::fs::read_to_string(path.as_ref()).map_err(|e| Error::Filesystem {
+            operation: "read recording".to_string(),
+            path: path.as_ref().to_path_buf(),
+            reason: e.to_string(),
+        })?;
+        Ok(serde_json::from_str(&json)?)
+    }
+    
+    /// Export calls made by the host
+    pub fn calls(&self) -> impl Iterator<Item = &SessionEvent> {
+        self.events.iter().map(|e| &e.event).filter(|e| matches!(e, SessionEvent::Call { .. }))
+    }
+    
+    /// Host calls made by the guest
+    pub fn host_calls(&self) -> impl Iterator<Item = &HostCall> {
+        self.events.iter().filter_map(|e| match &e.event {
+            SessionEvent::HostCall(call) => Some(call),
+            _ => None,
+        })
+    }
+}
+
+/// Shared event log of a session
+struct SessionLog {
+    start: Instant,
+    events: Mutex<Vec<RecordedEvent>>,
+}
+
+impl SessionLog {
+    fn push(&self, event: SessionEvent) {
+        self.events.lock().unwrap().push(RecordedEvent {
+            elapsed: self.start.elapsed(),
+            event,
+        });
+    }
+}
+
+/// Host functions that never provide anything
+struct NoHostFunctions;
+
+impl HostFunctions for NoHostFunctions {
+    fn provides(&self, _module: &str, _name: &str) -> bool {
+        false
+    }
+    
+    fn call(&self, module: &str, name: &str, _args: &[HostValue]) -> Result<Vec<HostValue>> {
+        Err(Error::NotFound {
+            resource_type: "host function".to_string(),
+            identifier: format!("{}.{}", module, name),
+        })
+    }
+}
+
+/// Builder for recording a session
+pub struct Recorder {
+    host: Arc<dyn HostFunctions>,
+    capabilities: Capabilities,
+    resource_limits: ResourceLimits,
+    runtime_config: RuntimeConfig,
+}
+
+impl Recorder {
+    /// Create a recorder with no host functions, minimal capabilities, and default limits
+    pub fn new() -> Self {
+        Self {
+            host: Arc::new(NoHostFunctions),
+            capabilities: Capabilities::minimal(),
+            resource_limits: ResourceLimits::default(),
+            runtime_config: RuntimeConfig::default(),
+        }
+    }
+    
+    /// Serve and record the guest's calls to these host functions
+    pub fn host_functions(mut self, host: Arc<dyn HostFunctions>) -> Self {
+        self.host = host;
+        self
+    }
+    
+    /// Set the capabilities the module runs with
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+    
+    /// Set the resource limits the module runs with
+    pub fn resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.resource_limits = limits;
+        self
+    }
+    
+    /// Set the runtime configuration
+    pub fn runtime_config(mut self, config: RuntimeConfig) -> Self {
+        self.runtime_config = config;
+        self
+    }
+    
+    /// Instantiate a module and start recording
+    pub fn start(&self, wasm_bytes: &[u8]) -> Result<RecordingSession> {
+        let log = Arc::new(SessionLog {
+            start: Instant::now(),
+            events: Mutex::new(Vec::new()),
+        });
+        let host = Arc::new(RecordingHost {
+            inner: self.host.clone(),
+            entropy: Arc::new(RecordingEntropy {
+                inner: self.host.entropy(),
+                log: log.clone(),
+            }),
+            log: log.clone(),
+        });
+        
+        let runtime = WasmtimeRuntime::new(&self.runtime_config)?;
+        let module = runtime.load_module(wasm_bytes)?;
+        let instance = runtime.create_instance_with_host_functions(
+            module.as_ref(),
+            self.resource_limits.clone(),
+            self.capabilities.clone(),
+            host,
+        )?;
+        
+        Ok(RecordingSession {
+            instance,
+            log,
+            _runtime: runtime,
+        })
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Forwards host calls and entropy to the real host, logging each one
+struct RecordingHost {
+    inner: Arc<dyn HostFunctions>,
+    entropy: Arc<RecordingEntropy>,
+    log: Arc<SessionLog>,
+}
+
+impl RecordingHost {
+    fn record(&self, module: &str, name: &str, args: &[HostValue], result: &Result<Vec<HostValue>>) {
+        self.log.push(SessionEvent::HostCall(HostCall {
+            module: module.to_string(),
+            name: name.to_string(),
+            args: args.to_vec(),
+            results: result.as_ref().ok().cloned(),
+        }));
+    }
+}
+
+impl HostFunctions for RecordingHost {
+    fn provides(&self, module: &str, name: &str) -> bool {
+        self.inner.provides(module, name)
+    }
+    
+    fn call(&self, module: &str, name: &str, args: &[HostValue]) -> Result<Vec<HostValue>> {
+        let result = self.inner.call(module, name, args);
+        self.record(module, name, args, &result);
+        result
+    }
+    
+    fn call_async<'a>(&'a self, module: &'a str, name: &'a str, args: &'a [HostValue]) -> GuestCall<'a, Vec<HostValue>> {
+        Box::pin(async move {
+            let result = self.inner.call_async(module, name, args).await;
+            self.record(module, name, args, &result);
+            result
+        })
+    }
+    
+    fn entropy(&self) -> Option<Arc<dyn EntropySource>> {
+        Some(self.entropy.clone())
+    }
+}
+
+/// Reads the real clocks and generator (or the host's source) and logs the values
+struct RecordingEntropy {
+    inner: Option<Arc<dyn EntropySource>>,
+    log: Arc<SessionLog>,
+}
+
+impl EntropySource for RecordingEntropy {
+    fn clock_time(&self, clock_id: u32) -> u64 {
+        let nanos = match &self.inner {
+            Some(inner) => inner.clock_time(clock_id),
+            None if clock_id == REALTIME_CLOCK => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0),
+            None => self.log.start.elapsed().as_nanos() as u64,
+        };
+        self.log.push(SessionEvent::Clock { clock_id, nanos });
+        nanos
+    }
+    
+    fn random_bytes(&self, buf: &mut [u8]) {
+        match &self.inner {
+            Some(inner) => inner.random_bytes(buf),
+            None => rand::rng().fill_bytes(buf),
+        }
+        self.log.push(SessionEvent::Random { bytes: buf.to_vec() });
+    }
+}
+
+/// A module instance whose session is being recorded
+pub struct RecordingSession {
+    instance: Box<dyn WasmInstance>,
+    log: Arc<SessionLog>,
+    _runtime: WasmtimeRuntime,
+}
+
+impl RecordingSession {
+    /// Call an export and record the call
+    pub fn call(&mut self, function_name: &str, args: &[HostValue]) -> Result<Vec<HostValue>> {
+        let result = self.instance.call_values(function_name, args);
+        self.log.push(SessionEvent::Call {
+            function: function_name.to_string(),
+            args: args.to_vec(),
+            outcome: result.clone().map_err(|e| e.to_string()),
+        });
+        result
+    }
+    
+    /// The underlying instance
+    pub fn instance(&self) -> &dyn WasmInstance {
+        self.instance.as_ref()
+    }
+    
+    /// Stop recording and return what was recorded
+    pub fn finish(self) -> SessionRecording {
+        SessionRecording {
+            events: std::mem::take(&mut *self.log.events.lock().unwrap()),
+        }
+    }
+}
+
+/// A difference between a replay and its recording
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    /// An export returned something else
+    ///
+    /// Errors only diverge from successes; error messages are not compared.
+    Outcome {
+        /// Index of the call in the recording's export calls
+        call: usize,
+        
+        /// Export name
+        function: String,
+        
+        /// Recorded outcome
+        expected: CallOutcome,
+        
+        /// Outcome during the replay
+        actual: CallOutcome,
+    },
+    
+    /// The guest made a host call that differs from the next recorded one
+    HostCall {
+        /// Next recorded host call, if any remained
+        expected: Option<HostCall>,
+        
+        /// Module, name, and arguments of the call the guest made
+        actual: (String, String, Vec<HostValue>),
+    },
+    
+    /// The guest read more clock values or random bytes than were recorded, or different amounts
+    Entropy {
+        /// What was read
+        description: String,
+    },
+    
+    /// Recorded host calls the guest never made
+    MissingHostCalls {
+        /// Number of calls
+        count: usize,
+    },
+}
+
+/// Result of replaying a recording
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    /// Export calls replayed
+    pub calls: usize,
+    
+    /// Where the replay differed from the recording
+    pub divergences: Vec<Divergence>,
+}
+
+impl ReplayReport {
+    /// Check whether the replay matched the recording exactly
+    pub fn is_identical(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// Replays a recording against a module
+pub struct Replayer {
+    recording: SessionRecording,
+    capabilities: Capabilities,
+    resource_limits: ResourceLimits,
+    runtime_config: RuntimeConfig,
+}
+
+impl Replayer {
+    /// Create a replayer with minimal capabilities and default limits
+    pub fn new(recording: SessionRecording) -> Self {
+        Self {
+            recording,
+            capabilities: Capabilities::minimal(),
+            resource_limits: ResourceLimits::default(),
+            runtime_config: RuntimeConfig::default(),
+        }
+    }
+    
+    /// Set the capabilities the module runs with
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+    
+    /// Set the resource limits the module runs with
+    pub fn resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.resource_limits = limits;
+        self
+    }
+    
+    /// Set the runtime configuration
+    pub fn runtime_config(mut self, config: RuntimeConfig) -> Self {
+        self.runtime_config = config;
+        self
+    }
+    
+    /// Replay the recorded export calls against a module
+    ///
+    /// Host calls are answered from the recording in order. Fails if the module
+    /// can't be instantiated, for example because it imports host functions the
+    /// recorded session never provided.
+    pub fn replay(&self, wasm_bytes: &[u8]) -> Result<ReplayReport> {
+        let host = Arc::new(ReplayHost::new(&self.recording));
+        
+        let runtime = WasmtimeRuntime::new(&self.runtime_config)?;
+        let module = runtime.load_module(wasm_bytes)?;
+        let instance = runtime.create_instance_with_host_functions(
+            module.as_ref(),
+            self.resource_limits.clone(),
+            self.capabilities.clone(),
+            host.clone(),
+        )?;
+        
+        let mut report = ReplayReport::default();
+        for event in self.recording.calls() {
+            let SessionEvent::Call { function, args, outcome } = event else {
+                continue;
+            };
+            
+            let actual = instance.call_values(function, args).map_err(|e| e.to_string());
+            let same = match (outcome, &actual) {
+                (Ok(expected), Ok(actual)) => expected == actual,
+                (Err(_), Err(_)) => true,
+                _ => false,
+            };
+            if !same {
+                host.diverge(Divergence::Outcome {
+                    call: report.calls,
+                    function: function.clone(),
+                    expected: outcome.clone(),
+                    actual,
+                });
+            }
+            report.calls += 1;
+        }
+        
+        let remaining = host.state.lock().unwrap().host_calls.len();
+        if remaining > 0 {
+            host.diverge(Divergence::MissingHostCalls { count: remaining });
+        }
+        report.divergences = std::mem::take(&mut host.state.lock().unwrap().divergences);
+        Ok(report)
+    }
+}
+
+/// Recorded inputs not yet consumed by the replay
+struct ReplayState {
+    host_calls: VecDeque<HostCall>,
+    clocks: VecDeque<(u32, u64)>,
+    random: VecDeque<Vec<u8>>,
+    divergences: Vec<Divergence>,
+}
+
+/// Serves host calls and entropy from a recording
+struct ReplayHost {
+    imports: BTreeSet<(String, String)>,
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl ReplayHost {
+    fn new(recording: &SessionRecording) -> Self {
+        let mut state = ReplayState {
+            host_calls: VecDeque::new(),
+            clocks: VecDeque::new(),
+            random: VecDeque::new(),
+            divergences: Vec::new(),
+        };
+        for event in &recording.events {
+            match &event.event {
+                SessionEvent::HostCall(call) => state.host_calls.push_back(call.clone()),
+                SessionEvent::Clock { clock_id, nanos } => state.clocks.push_back((*clock_id, *nanos)),
+                SessionEvent::Random { bytes } => state.random.push_back(bytes.clone()),
+                SessionEvent::Call { .. } => {}
+            }
+        }
+        
+        Self {
+            imports: state.host_calls.iter().map(|call| (call.module.clone(), call.name.clone())).collect(),
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+    
+    fn diverge(&self, divergence: Divergence) {
+        self.state.lock().unwrap().divergences.push(divergence);
+    }
+}
+
+impl HostFunctions for ReplayHost {
+    fn provides(&self, module: &str, name: &str) -> bool {
+        self.imports.contains(&(module.to_string(), name.to_string()))
+    }
+    
+    fn call(&self, module: &str, name: &str, args: &[HostValue]) -> Result<Vec<HostValue>> {
+        let mut state = self.state.lock().unwrap();
+        let expected = state.host_calls.front().cloned();
+        
+        // A call to the same function with other arguments still gets the recorded answer
+        let answer = match &expected {
+            Some(call) if call.module == module && call.name == name => {
+                state.host_calls.pop_front();
+                if call.args != args {
+                    state.divergences.push(Divergence::HostCall {
+                        expected: expected.clone(),
+                        actual: (module.to_string(), name.to_string(), args.to_vec()),
+                    });
+                }
+                call.results.clone()
+            }
+            _ => {
+                state.divergences.push(Divergence::HostCall {
+                    expected,
+                    actual: (module.to_string(), name.to_string(), args.to_vec()),
+                });
+                None
+            }
+        };
+        
+        answer.ok_or_else(|| Error::FunctionCall {
+            function_name: format!("{}.{}", module, name),
+            reason: "No recorded result for this host call".to_string(),
+        })
+    }
+    
+    fn entropy(&self) -> Option<Arc<dyn EntropySource>> {
+        Some(Arc::new(ReplayEntropy { state: self.state.clone() }))
+    }
+}
+
+/// Serves clock values and random bytes from a recording
+struct ReplayEntropy {
+    state: Arc<Mutex<ReplayState>>,
+}
+
+impl EntropySource for ReplayEntropy {
+    fn clock_time(&self, clock_id: u32) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        match state.clocks.pop_front() {
+            Some((recorded_id, nanos)) if recorded_id == clock_id => nanos,
+            other => {
+                state.divergences.push(Divergence::Entropy {
+                    description: match other {
+                        Some((recorded_id, _)) => format!("read clock {} where clock {} was recorded", clock_id, recorded_id),
+                        None => format!("read clock {} after the recorded reads ran out", clock_id),
+                    },
+                });
+                other.map(|(_, nanos)| nanos).unwrap_or(0)
+            }
+        }
+    }
+    
+    fn random_bytes(&self, buf: &mut [u8]) {
+        let mut state = self.state.lock().unwrap();
+        match state.random.pop_front() {
+            Some(bytes) if bytes.len() == buf.len() => buf.copy_from_slice(&bytes),
+            other => {
+                state.divergences.push(Divergence::Entropy {
+                    description: match &other {
+                        Some(bytes) => format!("requested {} random bytes where {} were recorded", buf.len(), bytes.len()),
+                        None => format!("requested {} random bytes after the recorded bytes ran out", buf.len()),
+                    },
+                });
+                buf.fill(0);
+            }
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! Guest ABI detection and per-ABI call marshalling
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::runtime::{HostValue, WasmFunctionCaller, WasmFunctionCallerAsync, WasmInstance, GUEST_ALLOC_EXPORT};
+use crate::runtime::wasmtime::block_on;
+use crate::security::imports::ModuleImport;
+
+/// Import modules wasm-bindgen emits for its JS glue
+pub const WASM_BINDGEN_IMPORT_MODULES: &[&str] = &["__wbindgen_placeholder__", "wbg"];
+
+/// Custom section wasm-bindgen embeds in its output
+pub const WASM_BINDGEN_CUSTOM_SECTION: &str = "__wasm_bindgen_unstable";
+
+/// Import module prefix used by WASI
+const WASI_IMPORT_PREFIX: &str = "wasi_";
+
+/// Calling convention a guest module was built against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AbiKind {
+    /// The sandbox's own data ABI: JSON passed through [`GUEST_ALLOC_EXPORT`]
+    Sandbox,
+    
+    /// wasm-bindgen output: JSON passed as a `&str` argument, returning a `String`
+    WasmBindgen,
+    
+    /// Plain WASI module with numeric exports
+    Wasi,
+    
+    /// Unrecognized conventions; exports are called with numeric arguments
+    Custom,
+}
+
+impl AbiKind {
+    /// Detect the ABI from a module's imports, exports and custom section names
+    pub fn detect(imports: &[ModuleImport], exports: &[String], custom_sections: &[String]) -> Self {
+        let has_export = |name: &str| exports.iter().any(|export| export == name);
+        
+        if custom_sections.iter().any(|section| section == WASM_BINDGEN_CUSTOM_SECTION)
+            || imports.iter().any(|import| WASM_BINDGEN_IMPORT_MODULES.contains(&import.module.as_str()))
+            || exports.iter().any(|export| export.starts_with("__wbindgen_"))
+        {
+            return Self::WasmBindgen;
+        }
+        
+        if has_export(GUEST_ALLOC_EXPORT) && has_export("memory") {
+            return Self::Sandbox;
+        }
+        
+        if imports.iter().any(|import| import.module.starts_with(WASI_IMPORT_PREFIX)) {
+            return Self::Wasi;
+        }
+        
+        Self::Custom
+    }
+}
+
+/// Names of the custom sections in a binary module
+///
+/// Returns nothing for text-format or malformed input.
+pub fn custom_section_names(wasm_bytes: &[u8]) -> Vec<String> {
+    custom_sections(wasm_bytes).into_iter().map(|section| section.name).collect()
+}
+
+/// A custom section of a binary module
+pub(crate) struct CustomSection<'a> {
+    /// Section name
+    pub name: String,
+    
+    /// Section contents after the name
+    pub payload: &'a [u8],
+    
+    /// Bytes the whole section occupies in the module, including its header
+    pub range: std::ops::Range<usize>,
+}
+
+/// Custom sections of a binary module, in order
+///
+/// Returns nothing for text-format input and stops at the first malformed section.
+pub(crate) fn custom_sections(wasm_bytes: &[u8]) -> Vec<CustomSection<'_>> {
+    let mut sections = Vec::new();
+    if !wasm_bytes.starts_with(b"\0asm") || wasm_bytes.len() < 8 {
+        return sections;
+    }
+    
+    let mut offset = 8;
+    while offset < wasm_bytes.len() {
+        let start = offset;
+        let id = wasm_bytes[offset];
+        offset += 1;
+        let Some(size) = read_leb_u32(wasm_bytes, &mut offset) else {
+            break;
+        };
+        let end = offset + size as usize;
+        if end > wasm_bytes.len() {
+            break;
+        }
+        
+        if id == 0 {
+            let mut name_offset = offset;
+            if let Some(name_len) = read_leb_u32(wasm_bytes, &mut name_offset) {
+                let name_end = name_offset + name_len as usize;
+                if let Some(name) = wasm_bytes.get(name_offset..name_end).filter(|_| name_end <= end) {
+                    sections.push(CustomSection {
+                        name: String::from_utf8_lossy(name).into_owned(),
+                        payload: &wasm_bytes[name_end..end],
+                        range: start..end,
+                    });
+                }
+            }
+        }
+        offset = end;
+    }
+    
+    sections
+}
+
+fn read_leb_u32(bytes: &[u8], offset: &mut usize) -> Option<u32> {
+    let mut result = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes.get(*offset)?;
+        *offset += 1;
+        result |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+    }
+    None
+}
+
+/// Function caller that marshals arguments according to the guest's ABI
+///
+/// - [`AbiKind::Sandbox`]: the JSON parameters are passed through
+///   [`WasmInstance::call_raw`] and the output is returned as JSON.
+/// - [`AbiKind::WasmBindgen`]: the JSON parameters are passed as a string
+///   through `__wbindgen_malloc` and the returned string is the result.
+/// - [`AbiKind::Wasi`] and [`AbiKind::Custom`]: the parameters must be a
+///   number or an array of numbers; a single result is returned as a number
+///   and several as an array.
+///
+/// [`AbiFunctionCaller::call_bytes_async`] passes bytes the same way for the
+/// two ABIs that take a buffer, without JSON in between.
+///
+/// Through [`WasmFunctionCallerAsync`], sandbox and numeric calls yield to the
+/// executor while the guest runs. wasm-bindgen calls take several trips into
+/// the guest and still run synchronously.
+pub struct AbiFunctionCaller {
+    /// Instance to call into
+    instance: Arc<dyn WasmInstance>,
+    
+    /// ABI the instance's module was built against
+    abi: AbiKind,
+}
+
+impl AbiFunctionCaller {
+    /// Create a caller for an instance
+    pub fn new(instance: Arc<dyn WasmInstance>, abi: AbiKind) -> Self {
+        Self { instance, abi }
+    }
+    
+    /// Get the ABI used for marshalling
+    pub fn abi(&self) -> AbiKind {
+        self.abi
+    }
+    
+    fn call_numeric(&self, function_name: &str, params_json: &str) -> Result<String> {
+        let args = numeric_arguments(function_name, params_json)?;
+        let results = self.instance.call_values(function_name, &args)?;
+        Ok(numeric_result(results))
+    }
+    
+    fn call_sandbox(&self, function_name: &str, params_json: &str) -> Result<String> {
+        let output = self.instance.call_raw(function_name, params_json.as_bytes())?;
+        sandbox_output(function_name, output)
+    }
+    
+    /// Call an export with raw bytes, returning the bytes it outputs
+    ///
+    /// Supported for [`AbiKind::Sandbox`] exports and wasm-bindgen exports of the
+    /// form `fn(&[u8]) -> Vec<u8>`; numeric ABIs have no buffer to pass.
+    pub async fn call_bytes_async(&self, function_name: &str, input: &[u8]) -> Result<Vec<u8>> {
+        match self.abi {
+            AbiKind::Sandbox => self.instance.call_raw_async(function_name, input).await,
+            AbiKind::WasmBindgen => self.call_wasm_bindgen_bytes(function_name, input).await,
+            AbiKind::Wasi | AbiKind::Custom => Err(Error::UnsupportedOperation {
+                message: format!(
+                    "{} can't take bytes: {:?} modules only have numeric exports",
+                    function_name, self.abi,
+                ),
+            }),
+        }
+    }
+    
+    /// Call a wasm-bindgen export of the form `fn(&str) -> String`
+    async fn call_wasm_bindgen(&self, function_name: &str, params_json: &str) -> Result<String> {
+        let output = self.call_wasm_bindgen_bytes(function_name, params_json.as_bytes()).await?;
+        String::from_utf8(output).map_err(|e| bindgen_error(function_name, &format!("output is not UTF-8: {}", e)))
+    }
+    
+    /// Call a wasm-bindgen export taking and returning a buffer
+    async fn call_wasm_bindgen_bytes(&self, function_name: &str, input: &[u8]) -> Result<Vec<u8>> {
+        let instance = &self.instance;
+        let len = HostValue::I32(input.len() as i32);
+        
+        // Newer wasm-bindgen passes an alignment to malloc and free
+        let ptr = match instance.call_values_async("__wbindgen_malloc", &[len, HostValue::I32(1)]).await {
+            Ok(ptr) => ptr,
+            Err(_) => instance.call_values_async("__wbindgen_malloc", &[len]).await?,
+        };
+        let ptr = match ptr.as_slice() {
+            [HostValue::I32(ptr)] => *ptr,
+            _ => return Err(bindgen_error(function_name, "__wbindgen_malloc did not return a pointer")),
+        };
+        instance.write_memory_at(ptr as u32 as usize, input)?;
+        
+        // Multi-value builds return (ptr, len); older builds write them through a return pointer
+        let (out_ptr, out_len) = match instance.call_values_async(function_name, &[HostValue::I32(ptr), len]).await {
+            Ok(results) => match results.as_slice() {
+                [HostValue::I32(out_ptr), HostValue::I32(out_len)] => (*out_ptr, *out_len),
+                _ => return Err(bindgen_error(function_name, "export does not return a string")),
+            },
+            Err(direct_error) => self.call_with_retptr(function_name, ptr, len).await.map_err(|_| direct_error)?,
+        };
+        
+        let output = instance.read_memory_at(out_ptr as u32 as usize, out_len as u32 as usize)?;
+        let free_args = [HostValue::I32(out_ptr), HostValue::I32(out_len)];
+        if instance.call_values_async("__wbindgen_free", &[free_args[0], free_args[1], HostValue::I32(1)]).await.is_err() {
+            let _ = instance.call_values_async("__wbindgen_free", &free_args).await;
+        }
+        
+        Ok(output)
+    }
+    
+    async fn call_with_retptr(&self, function_name: &str, ptr: i32, len: HostValue) -> Result<(i32, i32)> {
+        let instance = &self.instance;
+        let retptr = match instance.call_values_async("__wbindgen_add_to_stack_pointer", &[HostValue::I32(-16)]).await?.as_slice() {
+            [HostValue::I32(retptr)] => *retptr,
+            _ => return Err(bindgen_error(function_name, "__wbindgen_add_to_stack_pointer did not return a pointer")),
+        };
+        
+        let result = instance
+            .call_values_async(function_name, &[HostValue::I32(retptr), HostValue::I32(ptr), len]).await
+            .and_then(|_| instance.read_memory_at(retptr as u32 as usize, 8));
+        instance.call_values_async("__wbindgen_add_to_stack_pointer", &[HostValue::I32(16)]).await?;
+        
+        let words = result?;
+        let word = |i: usize| i32::from_le_bytes([words[i], words[i + 1], words[i + 2], words[i + 3]]);
+        Ok((word(0), word(4)))
+    }
+}
+
+impl WasmFunctionCaller for AbiFunctionCaller {
+    fn call_function_json(
+        &self,
+        function_name: &str,
+        params_json: &str,
+    ) -> Result<String> {
+        match self.abi {
+            AbiKind::Sandbox => self.call_sandbox(function_name, params_json),
+            AbiKind::WasmBindgen => block_on(self.call_wasm_bindgen(function_name, params_json)),
+            AbiKind::Wasi | AbiKind::Custom => self.call_numeric(function_name, params_json),
+        }
+    }
+    
+    fn call_function_msgpack(
+        &self,
+        function_name: &str,
+        params_msgpack: &[u8],
+    ) -> Result<Vec<u8>> {
+        let result_json = self.call_function_json(function_name, &msgpack_to_json(params_msgpack)?)?;
+        json_to_msgpack(&result_json)
+    }
+    
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl WasmFunctionCallerAsync for AbiFunctionCaller {
+    async fn call_function_json_async(
+        &self,
+        function_name: &str,
+        params_json: &str,
+    ) -> Result<String> {
+        match self.abi {
+            AbiKind::Sandbox => {
+                let output = self.instance.call_raw_async(function_name, params_json.as_bytes()).await?;
+                sandbox_output(function_name, output)
+            }
+            AbiKind::WasmBindgen => self.call_wasm_bindgen(function_name, params_json).await,
+            AbiKind::Wasi | AbiKind::Custom => {
+                let args = numeric_arguments(function_name, params_json)?;
+                let results = self.instance.call_values_async(function_name, &args).await?;
+                Ok(numeric_result(results))
+            }
+        }
+    }
+    
+    async fn call_function_msgpack_async(
+        &self,
+        function_name: &str,
+        params_msgpack: &[u8],
+    ) -> Result<Vec<u8>> {
+        let params_json = msgpack_to_json(params_msgpack)?;
+        let result_json = self.call_function_json_async(function_name, &params_json).await?;
+        json_to_msgpack(&result_json)
+    }
+}
+
+fn msgpack_to_json(params_msgpack: &[u8]) -> Result<String> {
+    let params: Value = rmp_serde::from_slice(params_msgpack).map_err(|e| Error::Serialization {
+        format: "messagepack".to_string(),
+        operation: "deserialize".to_string(),
+        reason: e.to_string(),
+    })?;
+    Ok(params.to_string())
+}
+
+fn json_to_msgpack(result_json: &str) -> Result<Vec<u8>> {
+    let result: Value = serde_json::from_str(result_json)?;
+    rmp_serde::to_vec(&result).map_err(|e| Error::Serialization {
+        format: "messagepack".to_string(),
+        operation: "serialize".to_string(),
+        reason: e.to_string(),
+    })
+}
+
+/// Output of a data ABI export as JSON
+fn sandbox_output(function_name: &str, output: Vec<u8>) -> Result<String> {
+    String::from_utf8(output).map_err(|e| Error::FunctionCall {
+        function_name: function_name.to_string(),
+        reason: format!("Output is not UTF-8: {}", e),
+    })
+}
+
+/// Results of a numeric export as JSON: nothing, one number, or an array
+fn numeric_result(results: Vec<HostValue>) -> String {
+    let mut values: Vec<Value> = results.into_iter().map(host_value_to_json).collect();
+    let result = match values.len() {
+        0 => Value::Null,
+        1 => values.remove(0),
+        _ => Value::Array(values),
+    };
+    result.to_string()
+}
+
+fn numeric_arguments(function_name: &str, params_json: &str) -> Result<Vec<HostValue>> {
+    let params: Value = if params_json.trim().is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_str(params_json)?
+    };
+    let values = match params {
+        Value::Null => Vec::new(),
+        Value::Array(values) => values,
+        value => vec![value],
+    };
+    
+    values
+        .iter()
+        .map(|value| json_to_host_value(value).ok_or_else(|| Error::InvalidInput {
+            field: function_name.to_string(),
+            reason: format!("Argument {} is not a number", value),
+            suggestion: Some("Exports of WASI and custom-ABI modules take numeric arguments".to_string()),
+        }))
+        .collect()
+}
+
+fn json_to_host_value(value: &Value) -> Option<HostValue> {
+    if value.is_null() {
+        return Some(HostValue::ExternRef(None));
+    }
+    let number = value.as_number()?;
+    if let Some(int) = number.as_i64() {
+        return Some(i32::try_from(int).map_or(HostValue::I64(int), HostValue::I32));
+    }
+    number.as_f64().map(HostValue::F64)
+}
+
+fn host_value_to_json(value: HostValue) -> Value {
+    match value {
+        HostValue::I32(v) => Value::from(v),
+        HostValue::I64(v) => Value::from(v),
+        HostValue::F32(v) => Value::from(v as f64),
+        HostValue::F64(v) => Value::from(v),
+        HostValue::ExternRef(handle) => handle.map_or(Value::Null, Value::from),
+        // JSON numbers can't hold 128 bits
+        HostValue::V128(v) => Value::from(format!("{:#034x}", v)),
+    }
+}
+
+fn bindgen_error(function_name: &str, reason: &str) -> Error {
+    Error::FunctionCall {
+        function_name: function_name.to_string(),
+        reason: format!("wasm-bindgen call failed: {}", reason),
+    }
+}
@@ -0,0 +1,245 @@
+//! Portable bundles of compiled modules
+//!
+//! [`crate::WasmSandbox::export_cache`] writes the compiled artifacts of every
+//! module a sandbox has loaded to one file, along with the [`ArtifactTarget`]
+//! they were compiled for. [`crate::WasmSandbox::import_cache`] checks that
+//! target against its own host before handing the artifacts to the runtime, so
+//! a CI job can compile modules once and ship them to a fleet of identical
+//! hosts. Loading a module whose bytes match an imported artifact then skips
+//! compilation.
+//!
+//! Artifacts are native code loaded without re-validation, so bundles must come
+//! from a trusted build. Each artifact's digest is checked on import to catch
+//! corruption in transit, not tampering.
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+use crate::runtime::compilation::EngineSettings;
+use crate::runtime::result_cache::ModuleDigest;
+
+/// First bytes of every cache bundle
+pub const CACHE_BUNDLE_MAGIC: &[u8; 8] = b"WSBCACHE";
+
+/// Format version of the bundles this crate writes
+pub const CACHE_BUNDLE_VERSION: u32 = 1;
+
+/// Host and engine settings compiled artifacts depend on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactTarget {
+    /// Version of this crate that compiled the artifacts
+    pub sandbox_version: String,
+    
+    /// CPU architecture
+    pub arch: String,
+    
+    /// Operating system
+    pub os: String,
+    
+    /// Optional CPU features the compiler may have used
+    pub cpu_features: Vec<String>,
+    
+    /// Engine settings the artifacts were compiled with
+    pub settings: EngineSettings,
+}
+
+impl ArtifactTarget {
+    /// Target of artifacts compiled on this host with `settings`
+    pub fn host(settings: EngineSettings) -> Self {
+        Self {
+            sandbox_version: env!("CARGO_PKG_VERSION").to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            os: std::env::consts::OS.to_string(),
+            cpu_features: host_cpu_features(),
+            settings,
+        }
+    }
+    
+    /// Check that artifacts compiled for this target can run on `host`
+    ///
+    /// The host must match exactly except for CPU features, of which it may
+    /// have more.
+    pub fn check_compatible(&self, host: &ArtifactTarget) -> Result<()> {
+        let mismatch = if self.sandbox_version != host.sandbox_version {
+            Some(format!("compiled by wasm-sandbox {}, host runs {}", self.sandbox_version, host.sandbox_version))
+        } else if (&self.arch, &self.os) != (&host.arch, &host.os) {
+            Some(format!("compiled for {}-{}, host is {}-{}", self.arch, self.os, host.arch, host.os))
+        } else if self.settings != host.settings {
+            Some("compiled with different runtime settings".to_string())
+        } else {
+            let missing: Vec<_> = self.cpu_features.iter()
+                .filter(|feature| !host.cpu_features.contains(feature))
+                .map(String::as_str)
+                .collect();
+            (!missing.is_empty()).then(|| format!("host lacks CPU features {}", missing.join(", ")))
+        };
+        match mismatch {
+            None => Ok(()),
+            Some(reason) => Err(Error::Module {
+                operation: "import compiled module cache".to_string(),
+                reason,
+                suggestion: Some("Export the cache on a host with the same platform and RuntimeConfig".to_string()),
+            }),
+        }
+    }
+}
+
+/// Optional CPU features detected on this host
+fn host_cpu_features() -> Vec<String> {
+    #[allow(unused_mut)]
+    let mut features: Vec<&str> = Vec::new();
+    #[cfg(target_arch = "x86_64")]
+    {
+        macro_rules! detect {
+            ($($feature:tt),*) => {
+                $(if std::is_x86_feature_detected!($feature) { features.push($feature); })*
+            };
+        }
+        detect!("sse3", "ssse3", "sse4.1", "sse4.2", "popcnt", "avx", "avx2", "bmi1", "bmi2", "lzcnt", "fma", "avx512f");
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        macro_rules! detect {
+            ($($feature:tt),*) => {
+                $(if std::arch::is_aarch64_feature_detected!($feature) { features.push($feature); })*
+            };
+        }
+        detect!("lse", "paca", "fp16");
+    }
+    features.into_iter().map(String::from).collect()
+}
+
+/// Where a compiled module sits in a bundle
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleEntry {
+    /// Digest of the module bytes the artifact was compiled from
+    module: ModuleDigest,
+    
+    /// Digest of the artifact
+    artifact: [u8; 32],
+    
+    /// Length of the artifact in bytes
+    len: u64,
+}
+
+/// Description of a bundle's contents, stored ahead of the artifacts
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleHeader {
+    version: u32,
+    target: ArtifactTarget,
+    entries: Vec<BundleEntry>,
+}
+
+/// Compiled modules and the target they were compiled for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheBundle {
+    /// Host and settings the artifacts need
+    pub target: ArtifactTarget,
+    
+    /// Artifacts by the digest of the module bytes they were compiled from
+    pub artifacts: Vec<(ModuleDigest, Vec<u8>)>,
+}
+
+impl CacheBundle {
+    /// Write the bundle: magic, header JSON length (u32, little endian), header JSON, then the artifacts
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let header = BundleHeader {
+            version: CACHE_BUNDLE_VERSION,
+            target: self.target.clone(),
+            entries: self.artifacts.iter()
+                .map(|(module, artifact)| BundleEntry {
+                    module: *module,
+                    artifact: Sha256::digest(artifact).into(),
+                    len: artifact.len() as u64,
+                })
+                .collect(),
+        };
+        let header = serde_json::to_vec(&header)?;
+        
+        let size = self.artifacts.iter().map(|(_, artifact)| artifact.len()).sum::<usize>();
+        let mut bundle = Vec::with_capacity(CACHE_BUNDLE_MAGIC.len() + 4 + header.len() + size);
+        bundle.extend_from_slice(CACHE_BUNDLE_MAGIC);
+        bundle.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        bundle.extend_from_slice(&header);
+        for (_, artifact) in &self.artifacts {
+            bundle.extend_from_slice(artifact);
+        }
+        fs::write(path, bundle).map_err(|e| Error::Filesystem {
+            operation: "write_cache_bundle".to_string(),
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })
+    }
+    
+    /// Read only the target a bundle's artifacts were compiled for
+    pub fn read_target(path: &Path) -> Result<ArtifactTarget> {
+        let read_error = |e: std::io::Error| Error::Filesystem {
+            operation: "read_cache_bundle".to_string(),
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        };
+        let mut file = fs::File::open(path).map_err(read_error)?;
+        let mut prefix = [0u8; CACHE_BUNDLE_MAGIC.len() + 4];
+        file.read_exact(&mut prefix).map_err(read_error)?;
+        let (magic, length) = prefix.split_at(CACHE_BUNDLE_MAGIC.len());
+        if magic != CACHE_BUNDLE_MAGIC {
+            return Err(Error::InvalidInput {
+                field: "cache bundle".to_string(),
+                reason: "not a cache bundle".to_string(),
+                suggestion: None,
+            });
+        }
+        let mut header = vec![0u8; u32::from_le_bytes(length.try_into().unwrap()) as usize];
+        file.read_exact(&mut header).map_err(read_error)?;
+        Ok(serde_json::from_slice::<BundleHeader>(&header)?.target)
+    }
+    
+    /// Read a bundle, checking its format and the digest of every artifact
+    pub fn read(path: &Path) -> Result<Self> {
+        let bundle = fs::read(path).map_err(|e| Error::Filesystem {
+            operation: "read_cache_bundle".to_string(),
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })?;
+        
+        let invalid = |reason: &str| Error::InvalidInput {
+            field: "cache bundle".to_string(),
+            reason: reason.to_string(),
+            suggestion: None,
+        };
+        let rest = bundle.strip_prefix(CACHE_BUNDLE_MAGIC.as_slice()).ok_or_else(|| invalid("not a cache bundle"))?;
+        let (length, rest) = rest.split_first_chunk::<4>().ok_or_else(|| invalid("missing header length"))?;
+        let length = u32::from_le_bytes(*length) as usize;
+        if rest.len() < length {
+            return Err(invalid("truncated header"));
+        }
+        let (header, mut rest) = rest.split_at(length);
+        let header: BundleHeader = serde_json::from_slice(header)?;
+        if header.version != CACHE_BUNDLE_VERSION {
+            return Err(invalid(&format!("unsupported bundle version {}", header.version)));
+        }
+        
+        let mut artifacts = Vec::with_capacity(header.entries.len());
+        for entry in header.entries {
+            let len = usize::try_from(entry.len).map_err(|_| invalid("artifact too large"))?;
+            if rest.len() < len {
+                return Err(invalid("truncated artifact"));
+            }
+            let (artifact, remaining) = rest.split_at(len);
+            if <[u8; 32]>::from(Sha256::digest(artifact)) != entry.artifact {
+                return Err(invalid("artifact digest mismatch"));
+            }
+            artifacts.push((entry.module, artifact.to_vec()));
+            rest = remaining;
+        }
+        Ok(Self {
+            target: header.target,
+            artifacts,
+        })
+    }
+}
@@ -0,0 +1,137 @@
+//! Identifying the call a piece of host or guest output belongs to
+//!
+//! Every call into an instance through the sandbox gets a fresh [`CallId`].
+//! While the call runs, it is the thread's current call: guest log records,
+//! audit events and the call's tracing span carry its ID, and the guest can
+//! read it through [`crate::runtime::LOG_CALL_ID_FUNCTION`], so all output of
+//! one plugin call can be correlated.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::pin::pin;
+
+use serde::{Deserialize, Serialize};
+
+use crate::InstanceId;
+
+/// Unique identifier of one call into an instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CallId(u64);
+
+impl CallId {
+    /// Generate a new random call ID
+    pub fn new() -> Self {
+        Self(rand::random::<u64>().max(1))
+    }
+    
+    /// The ID as a number, as guests see it
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for CallId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for CallId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// The call a thread is running
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallContext {
+    /// ID of the call
+    pub call_id: CallId,
+    
+    /// Instance being called
+    pub instance_id: InstanceId,
+    
+    /// Export being called
+    pub function_name: String,
+}
+
+impl CallContext {
+    /// Describe a new call of `function_name` on `instance_id`
+    pub fn new(instance_id: InstanceId, function_name: &str) -> Self {
+        Self {
+            call_id: CallId::new(),
+            instance_id,
+            function_name: function_name.to_string(),
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT_CALL: RefCell<Option<CallContext>> = const { RefCell::new(None) };
+}
+
+/// The call running on this thread, if any
+pub fn current_call() -> Option<CallContext> {
+    CURRENT_CALL.with(|current| current.borrow().clone())
+}
+
+/// ID of the call running on this thread, if any
+pub fn current_call_id() -> Option<CallId> {
+    CURRENT_CALL.with(|current| current.borrow().as_ref().map(|call| call.call_id))
+}
+
+/// Makes a call current on this thread until dropped
+pub(crate) struct ActiveCall {
+    previous: Option<CallContext>,
+    span: tracing::Span,
+}
+
+impl ActiveCall {
+    /// Make `context` the current call
+    pub(crate) fn enter(context: CallContext) -> Self {
+        let span = call_span(&context);
+        Self::resume(context, span)
+    }
+    
+    /// Run `future` with `context` as the current call
+    ///
+    /// The call is made current again each time the future is polled, so it
+    /// follows the future across threads and awaits.
+    pub(crate) async fn scope<F: Future>(context: CallContext, future: F) -> F::Output {
+        let span = call_span(&context);
+        let mut future = pin!(future);
+        std::future::poll_fn(|cx| {
+            let call = Self::resume(context.clone(), span.clone());
+            let _span = call.span().enter();
+            future.as_mut().poll(cx)
+        })
+        .await
+    }
+    
+    fn resume(context: CallContext, span: tracing::Span) -> Self {
+        let previous = CURRENT_CALL.with(|current| current.replace(Some(context)));
+        Self { previous, span }
+    }
+    
+    /// Tracing span covering the call
+    pub(crate) fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+}
+
+fn call_span(context: &CallContext) -> tracing::Span {
+    tracing::info_span!(
+        "wasm_call",
+        call_id = %context.call_id,
+        instance_id = %context.instance_id,
+        function = %context.function_name,
+    )
+}
+
+impl Drop for ActiveCall {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT_CALL.with(|current| *current.borrow_mut() = previous);
+    }
+}
@@ -0,0 +1,304 @@
+//! Priority queue for calls into a single instance
+//!
+//! An instance runs one call at a time. Without a queue, concurrent callers
+//! contend for the instance's store lock and are served in whatever order the
+//! lock hands it out. With a [`CallQueueConfig`], callers wait in a bounded
+//! queue instead and the highest-priority caller, oldest first, runs next.
+//! Each priority can have its own wait timeout, and a full queue either
+//! rejects the new call or sheds a lower-priority waiter to make room.
+
+use std::collections::BTreeMap;
+use std::cmp::Reverse;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+use crate::error::{Error, Result};
+
+/// Default number of calls that may wait for an instance
+pub const DEFAULT_MAX_QUEUE_DEPTH: usize = 64;
+
+/// How urgently a call should run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CallPriority {
+    /// Background work, run when nothing else is waiting
+    Low,
+    
+    /// Ordinary calls
+    #[default]
+    Normal,
+    
+    /// Latency-sensitive calls, run before everything else
+    High,
+}
+
+impl CallPriority {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// What happens to a call that arrives at a full queue
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Refuse the new call
+    #[default]
+    Reject,
+    
+    /// Refuse the newest waiter of the lowest priority if it is below the new call's
+    ShedLowest,
+}
+
+/// Depth, timeouts, and overflow handling of an instance's call queue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallQueueConfig {
+    /// Calls that may wait while another runs
+    pub max_depth: usize,
+    
+    /// Longest a call of each priority may wait, indexed by [`CallPriority`]; `None` waits indefinitely
+    pub timeouts: [Option<Duration>; 3],
+    
+    /// What to do with calls arriving at a full queue
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for CallQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_QUEUE_DEPTH,
+            timeouts: [None; 3],
+            overflow: OverflowPolicy::Reject,
+        }
+    }
+}
+
+impl CallQueueConfig {
+    /// Set how many calls may wait
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+    
+    /// Set how long calls of a priority may wait before failing
+    pub fn timeout(mut self, priority: CallPriority, timeout: Duration) -> Self {
+        self.timeouts[priority.index()] = Some(timeout);
+        self
+    }
+    
+    /// Set what happens to calls arriving at a full queue
+    pub fn overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+}
+
+/// Counters and current depth of an instance's call queue
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallQueueMetrics {
+    /// Calls waiting now, by [`CallPriority`]
+    pub waiting: [usize; 3],
+    
+    /// Whether a call is running
+    pub running: bool,
+    
+    /// Most calls that have waited at once
+    pub peak_depth: usize,
+    
+    /// Calls that had to wait
+    pub queued: u64,
+    
+    /// Calls refused because the queue was full
+    pub rejected: u64,
+    
+    /// Waiting calls refused to make room for higher-priority ones
+    pub shed: u64,
+    
+    /// Calls that gave up waiting
+    pub timed_out: u64,
+}
+
+impl CallQueueMetrics {
+    /// Calls waiting now, of any priority
+    pub fn depth(&self) -> usize {
+        self.waiting.iter().sum()
+    }
+}
+
+/// Position of a waiter: higher priorities first, then arrival order
+type WaiterKey = (Reverse<CallPriority>, u64);
+
+struct QueueState {
+    running: bool,
+    waiters: BTreeMap<WaiterKey, oneshot::Sender<bool>>,
+    next: u64,
+    metrics: CallQueueMetrics,
+}
+
+/// Calls waiting for one instance
+pub(crate) struct CallQueue {
+    config: CallQueueConfig,
+    state: Arc<Mutex<QueueState>>,
+}
+
+impl CallQueue {
+    /// Create an empty queue
+    pub(crate) fn new(config: CallQueueConfig) -> Self {
+        Self {
+            config,
+            state: Arc::new(Mutex::new(QueueState {
+                running: false,
+                waiters: BTreeMap::new(),
+                next: 0,
+                metrics: CallQueueMetrics::default(),
+            })),
+        }
+    }
+    
+    /// Wait for the instance, returning a permit that lets the next call run when dropped
+    pub(crate) async fn acquire(&self, priority: CallPriority) -> Result<CallPermit> {
+        let (key, receiver) = {
+            let mut state = self.state.lock().unwrap();
+            if !state.running {
+                state.running = true;
+                return Ok(self.permit());
+            }
+            
+            if state.waiters.len() >= self.config.max_depth {
+                self.make_room(&mut state, priority)?;
+            }
+            let key = (Reverse(priority), state.next);
+            state.next += 1;
+            let (sender, receiver) = oneshot::channel();
+            state.waiters.insert(key, sender);
+            state.metrics.waiting[priority.index()] += 1;
+            state.metrics.queued += 1;
+            state.metrics.peak_depth = state.metrics.peak_depth.max(state.waiters.len());
+            (key, receiver)
+        };
+        
+        let mut waiting = Waiting {
+            key,
+            receiver,
+            state: self.state.clone(),
+            settled: false,
+        };
+        let granted = match self.config.timeouts[priority.index()] {
+            Some(timeout) => match tokio::time::timeout(timeout, &mut waiting.receiver).await {
+                Ok(granted) => granted,
+                Err(_) => {
+                    {
+                        let mut state = self.state.lock().unwrap();
+                        if state.waiters.remove(&key).is_some() {
+                            state.metrics.waiting[priority.index()] -= 1;
+                            state.metrics.timed_out += 1;
+                            return Err(Error::Timeout {
+                                operation: format!("waiting in the {:?} priority call queue", priority),
+                                duration: timeout,
+                                instance_id: None,
+                            });
+                        }
+                    }
+                    // Granted or shed just as the wait ran out; the decision is already sent
+                    (&mut waiting.receiver).await
+                }
+            },
+            None => (&mut waiting.receiver).await,
+        };
+        waiting.settled = true;
+        
+        if granted.unwrap_or(false) {
+            Ok(self.permit())
+        } else {
+            Err(Error::ResourceLimit {
+                message: "Call was shed from a full call queue for a higher-priority call".to_string(),
+            })
+        }
+    }
+    
+    /// Current depth and counters
+    pub(crate) fn metrics(&self) -> CallQueueMetrics {
+        let state = self.state.lock().unwrap();
+        CallQueueMetrics {
+            running: state.running,
+            ..state.metrics
+        }
+    }
+    
+    /// Shed a lower-priority waiter for a call of `priority`, or refuse it
+    fn make_room(&self, state: &mut QueueState, priority: CallPriority) -> Result<()> {
+        let victim = match self.config.overflow {
+            OverflowPolicy::ShedLowest => state.waiters.keys().next_back()
+                .filter(|(Reverse(lowest), _)| *lowest < priority)
+                .copied(),
+            OverflowPolicy::Reject => None,
+        };
+        let Some(victim) = victim else {
+            state.metrics.rejected += 1;
+            return Err(Error::ResourceLimit {
+                message: format!("Call queue is full with {} waiting calls", state.waiters.len()),
+            });
+        };
+        
+        let sender = state.waiters.remove(&victim).expect("victim is waiting");
+        state.metrics.waiting[victim.0 .0.index()] -= 1;
+        state.metrics.shed += 1;
+        let _ = sender.send(false);
+        Ok(())
+    }
+    
+    fn permit(&self) -> CallPermit {
+        CallPermit { state: self.state.clone() }
+    }
+}
+
+/// A caller's place in the queue
+///
+/// If the caller goes away while waiting, dropping this leaves the queue, or
+/// passes the instance on to the next caller if it had just been granted.
+struct Waiting {
+    key: WaiterKey,
+    receiver: oneshot::Receiver<bool>,
+    state: Arc<Mutex<QueueState>>,
+    settled: bool,
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        if self.settled {
+            return;
+        }
+        let granted = {
+            let mut state = self.state.lock().unwrap();
+            if state.waiters.remove(&self.key).is_some() {
+                state.metrics.waiting[self.key.0 .0.index()] -= 1;
+                false
+            } else {
+                // Decisions are sent under the lock, so one has arrived if it was made
+                self.receiver.try_recv() == Ok(true)
+            }
+        };
+        if granted {
+            drop(CallPermit { state: self.state.clone() });
+        }
+    }
+}
+
+/// Lets the next queued call run when dropped
+pub(crate) struct CallPermit {
+    state: Arc<Mutex<QueueState>>,
+}
+
+impl Drop for CallPermit {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        while let Some((key, sender)) = state.waiters.pop_first() {
+            state.metrics.waiting[key.0 .0.index()] -= 1;
+            // A waiter whose caller just went away is skipped
+            if sender.send(true).is_ok() {
+                return;
+            }
+        }
+        state.running = false;
+    }
+}
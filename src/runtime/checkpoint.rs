@@ -0,0 +1,300 @@
+//! Checkpoints requested by guests
+//!
+//! Instances created with a [`CheckpointConfig`] may call
+//! [`super::CHECKPOINT_IMPORT_MODULE`] at points where their state is
+//! consistent. The host then writes the guest's linear memory and exported
+//! mutable globals to [`CheckpointConfig::directory`] and hands the guest the
+//! checkpoint's ID. After a crash or a host restart,
+//! [`Checkpoint::latest`] finds the newest checkpoint in the directory and
+//! [`crate::WasmSandbox::restore_checkpoint`] recreates an instance from it;
+//! calling the guest's entry point again lets it pick up where its memory
+//! says it was. Give each job a directory of its own: IDs count up within a
+//! directory, and only the newest [`CheckpointConfig::keep`] files are kept.
+//!
+//! A checkpoint file starts with [`CHECKPOINT_MAGIC`], a little-endian `u32`
+//! header length and a JSON header, followed by the guest's memory.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::runtime::result_cache::ModuleDigest;
+use crate::runtime::HostValue;
+use crate::InstanceId;
+
+/// First bytes of every checkpoint file
+pub const CHECKPOINT_MAGIC: &[u8; 8] = b"WSBXCKPT";
+
+/// Format version of the checkpoints this crate writes
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// File extension of checkpoint files
+pub const CHECKPOINT_EXTENSION: &str = "ckpt";
+
+/// Checkpoints kept per directory by default
+pub const DEFAULT_KEPT_CHECKPOINTS: usize = 3;
+
+/// Where an instance's checkpoints are written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointConfig {
+    /// Directory the checkpoints are written to
+    pub directory: PathBuf,
+    
+    /// Number of checkpoints kept in the directory; older ones are deleted
+    pub keep: usize,
+}
+
+impl CheckpointConfig {
+    /// Write checkpoints to `directory`, keeping the newest [`DEFAULT_KEPT_CHECKPOINTS`]
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            keep: DEFAULT_KEPT_CHECKPOINTS,
+        }
+    }
+    
+    /// Keep the newest `keep` checkpoints, at least one
+    pub fn keep(mut self, keep: usize) -> Self {
+        self.keep = keep.max(1);
+        self
+    }
+}
+
+/// A checkpoint on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// ID the guest was handed, counting up from 1 within the directory
+    pub checkpoint_id: u64,
+    
+    /// Instance that requested the checkpoint
+    pub instance_id: InstanceId,
+    
+    /// Digest of the module the instance ran
+    pub module_digest: ModuleDigest,
+    
+    /// File the checkpoint was written to
+    pub path: PathBuf,
+    
+    /// Size of the saved linear memory in bytes
+    pub memory_bytes: u64,
+    
+    /// When the checkpoint was written
+    pub created_at: SystemTime,
+}
+
+impl Checkpoint {
+    /// Describe the checkpoint in a file
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut file = File::open(path).map_err(|e| read_error(path, e))?;
+        let header = read_header(&mut file, path)?;
+        Ok(header.describe(path))
+    }
+    
+    /// Checkpoints in a directory, oldest first
+    pub fn list(directory: &Path) -> Result<Vec<Self>> {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(read_error(directory, e)),
+        };
+        let mut checkpoints = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| read_error(directory, e))?.path();
+            if path.extension().is_some_and(|extension| extension == CHECKPOINT_EXTENSION) {
+                checkpoints.push(Self::read(&path)?);
+            }
+        }
+        checkpoints.sort_by_key(|checkpoint| checkpoint.checkpoint_id);
+        Ok(checkpoints)
+    }
+    
+    /// Newest checkpoint in a directory, the one to resume a job from
+    pub fn latest(directory: &Path) -> Result<Option<Self>> {
+        Ok(Self::list(directory)?.pop())
+    }
+}
+
+/// Saved guest state
+pub(crate) struct CheckpointState {
+    /// Description of the checkpoint
+    pub(crate) checkpoint: Checkpoint,
+    
+    /// The guest's linear memory
+    pub(crate) memory: Vec<u8>,
+    
+    /// The guest's exported mutable globals
+    pub(crate) globals: Vec<(String, HostValue)>,
+}
+
+/// Read a checkpoint with the state it saved
+pub(crate) fn read_state(path: &Path) -> Result<CheckpointState> {
+    let mut file = File::open(path).map_err(|e| read_error(path, e))?;
+    let header = read_header(&mut file, path)?;
+    let mut memory = Vec::with_capacity(header.memory_bytes as usize);
+    file.read_to_end(&mut memory).map_err(|e| read_error(path, e))?;
+    if memory.len() as u64 != header.memory_bytes {
+        return Err(invalid("memory does not match its recorded size"));
+    }
+    Ok(CheckpointState {
+        checkpoint: header.describe(path),
+        memory,
+        globals: header.globals,
+    })
+}
+
+/// Description of a checkpoint, stored ahead of the memory
+#[derive(Debug, Serialize, Deserialize)]
+struct CheckpointHeader {
+    version: u32,
+    checkpoint_id: u64,
+    instance_id: InstanceId,
+    module_digest: ModuleDigest,
+    globals: Vec<(String, HostValue)>,
+    memory_bytes: u64,
+    created_at_ms: u64,
+}
+
+impl CheckpointHeader {
+    fn describe(&self, path: &Path) -> Checkpoint {
+        Checkpoint {
+            checkpoint_id: self.checkpoint_id,
+            instance_id: self.instance_id,
+            module_digest: self.module_digest,
+            path: path.to_path_buf(),
+            memory_bytes: self.memory_bytes,
+            created_at: SystemTime::UNIX_EPOCH + Duration::from_millis(self.created_at_ms),
+        }
+    }
+}
+
+fn read_header(file: &mut File, path: &Path) -> Result<CheckpointHeader> {
+    let mut prefix = [0u8; 12];
+    file.read_exact(&mut prefix).map_err(|_| invalid("not a checkpoint"))?;
+    if &prefix[..8] != CHECKPOINT_MAGIC {
+        return Err(invalid("not a checkpoint"));
+    }
+    let length = u32::from_le_bytes(prefix[8..].try_into().expect("four bytes")) as usize;
+    let mut header = vec![0; length];
+    file.read_exact(&mut header).map_err(|e| read_error(path, e))?;
+    let header: CheckpointHeader = serde_json::from_slice(&header)?;
+    if header.version != CHECKPOINT_VERSION {
+        return Err(invalid(&format!("unsupported checkpoint version {}", header.version)));
+    }
+    Ok(header)
+}
+
+fn read_error(path: &Path, e: std::io::Error) -> Error {
+    Error::Filesystem {
+        operation: "read checkpoint".to_string(),
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    }
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidInput {
+        field: "checkpoint".to_string(),
+        reason: reason.to_string(),
+        suggestion: None,
+    }
+}
+
+/// Destination for the checkpoints one instance requests
+pub trait CheckpointSink: Send + Sync {
+    /// Save the guest's state, returning the checkpoint's ID
+    fn checkpoint(&self, memory: &[u8], globals: &[(String, HostValue)]) -> Result<u64>;
+}
+
+/// Latest checkpoint of every instance in a sandbox
+#[derive(Debug, Default)]
+pub(crate) struct Checkpoints {
+    latest: Mutex<HashMap<InstanceId, Checkpoint>>,
+    /// Held while an ID is picked and its file written, so instances sharing a directory get distinct IDs
+    writing: Mutex<()>,
+}
+
+impl Checkpoints {
+    /// Latest checkpoint written for an instance
+    pub(crate) fn latest(&self, instance_id: InstanceId) -> Option<Checkpoint> {
+        self.latest.lock().unwrap().get(&instance_id).cloned()
+    }
+    
+    /// Forget an instance's latest checkpoint; the file stays on disk
+    pub(crate) fn forget(&self, instance_id: InstanceId) {
+        self.latest.lock().unwrap().remove(&instance_id);
+    }
+}
+
+/// A sandbox's checkpoints as seen by one instance
+pub(crate) struct InstanceCheckpoints {
+    checkpoints: Arc<Checkpoints>,
+    config: CheckpointConfig,
+    instance_id: InstanceId,
+    module_digest: ModuleDigest,
+}
+
+impl InstanceCheckpoints {
+    /// Write checkpoints of `instance_id`, running the module with `module_digest`, as `config` says
+    pub(crate) fn new(
+        checkpoints: Arc<Checkpoints>,
+        config: CheckpointConfig,
+        instance_id: InstanceId,
+        module_digest: ModuleDigest,
+    ) -> Self {
+        Self { checkpoints, config, instance_id, module_digest }
+    }
+}
+
+impl CheckpointSink for InstanceCheckpoints {
+    fn checkpoint(&self, memory: &[u8], globals: &[(String, HostValue)]) -> Result<u64> {
+        let _writing = self.checkpoints.writing.lock().unwrap();
+        let directory = &self.config.directory;
+        let existing = Checkpoint::list(directory)?;
+        let checkpoint_id = existing.last().map_or(1, |checkpoint| checkpoint.checkpoint_id + 1);
+        
+        let header = CheckpointHeader {
+            version: CHECKPOINT_VERSION,
+            checkpoint_id,
+            instance_id: self.instance_id,
+            module_digest: self.module_digest,
+            globals: globals.to_vec(),
+            memory_bytes: memory.len() as u64,
+            created_at_ms: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+        };
+        let encoded = serde_json::to_vec(&header)?;
+        let mut contents = Vec::with_capacity(CHECKPOINT_MAGIC.len() + 4 + encoded.len() + memory.len());
+        contents.extend_from_slice(CHECKPOINT_MAGIC);
+        contents.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+        contents.extend_from_slice(&encoded);
+        contents.extend_from_slice(memory);
+        
+        // Written under a temporary name and renamed, so a crash never leaves a torn checkpoint
+        let path = directory.join(format!("checkpoint-{:08}.{}", checkpoint_id, CHECKPOINT_EXTENSION));
+        let partial = path.with_extension("partial");
+        let written = fs::create_dir_all(directory)
+            .and_then(|_| fs::write(&partial, &contents))
+            .and_then(|_| fs::rename(&partial, &path));
+        written.map_err(|e| Error::Filesystem {
+            operation: "write checkpoint".to_string(),
+            path: path.clone(),
+            reason: e.to_string(),
+        })?;
+        
+        let stale = (existing.len() + 1).saturating_sub(self.config.keep);
+        for old in &existing[..stale] {
+            if let Err(e) = fs::remove_file(&old.path) {
+                log::warn!("Could not delete old checkpoint {}: {}", old.path.display(), e);
+            }
+        }
+        
+        log::info!("Instance {} wrote checkpoint {} to {}", self.instance_id, checkpoint_id, path.display());
+        self.checkpoints.latest.lock().unwrap().insert(self.instance_id, header.describe(&path));
+        Ok(checkpoint_id)
+    }
+}
@@ -0,0 +1,202 @@
+//! Child instances started by guests in place of host processes
+//!
+//! Spawning host processes hands a plugin everything the host user can do.
+//! Instead, the host registers modules in the sandbox's [`ChildRegistry`] and
+//! guests granted a [`ChildCapability`] start instances of them through
+//! [`crate::runtime::CHILD_IMPORT_MODULE`]. Children run on a runtime of their
+//! own with a subset of the parent's capabilities and a share of its resource
+//! limits, and are removed with the parent.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+use crate::error::{Error, Result, SecurityContext};
+use crate::runtime::{create_runtime, ChildSpawner, ModuleId, RuntimeConfig, WasmInstance, WasmRuntime};
+use crate::security::capabilities::ActiveCapabilities;
+use crate::security::{Capabilities, ChildCapability, ProcessCapability, ResourceLimits};
+use crate::InstanceId;
+
+/// Running children of one parent by handle
+type Children = BTreeMap<u32, Arc<dyn WasmInstance>>;
+
+/// Modules guests may start as children, and the children running
+pub struct ChildRegistry {
+    config: RuntimeConfig,
+    runtime: OnceLock<Box<dyn WasmRuntime>>,
+    modules: RwLock<HashMap<String, ModuleId>>,
+    children: Mutex<HashMap<InstanceId, Children>>,
+}
+
+impl ChildRegistry {
+    /// Create an empty registry whose children run on a runtime configured like `config`
+    pub fn new(config: RuntimeConfig) -> Self {
+        Self {
+            config,
+            runtime: OnceLock::new(),
+            modules: RwLock::new(HashMap::new()),
+            children: Mutex::new(HashMap::new()),
+        }
+    }
+    
+    /// Make a module available to guests as a child under `name`, replacing any module of that name
+    pub fn register(&self, name: &str, wasm_bytes: &[u8]) -> Result<()> {
+        let module = self.runtime()?.load_module(wasm_bytes)?;
+        self.modules.write().unwrap().insert(name.to_string(), module.id());
+        Ok(())
+    }
+    
+    /// Stop offering a module; running children of it are unaffected
+    pub fn unregister(&self, name: &str) -> bool {
+        self.modules.write().unwrap().remove(name).is_some()
+    }
+    
+    /// Names of the registered child modules
+    pub fn module_names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.modules.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+    
+    /// Number of children an instance has running
+    pub fn child_count(&self, parent: InstanceId) -> usize {
+        self.children.lock().unwrap().get(&parent).map_or(0, BTreeMap::len)
+    }
+    
+    /// Stop every child of an instance
+    pub(crate) fn forget(&self, parent: InstanceId) {
+        self.children.lock().unwrap().remove(&parent);
+    }
+    
+    fn runtime(&self) -> Result<&dyn WasmRuntime> {
+        if let Some(runtime) = self.runtime.get() {
+            return Ok(runtime.as_ref());
+        }
+        let runtime = create_runtime(&self.config)?;
+        Ok(self.runtime.get_or_init(|| runtime).as_ref())
+    }
+    
+    fn child(&self, parent: InstanceId, child: u32) -> Result<Arc<dyn WasmInstance>> {
+        self.children.lock().unwrap()
+            .get(&parent)
+            .and_then(|children| children.get(&child))
+            .cloned()
+            .ok_or_else(|| Error::NotFound {
+                resource_type: "child instance".to_string(),
+                identifier: child.to_string(),
+            })
+    }
+}
+
+impl std::fmt::Debug for ChildRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChildRegistry")
+            .field("modules", &self.module_names())
+            .finish()
+    }
+}
+
+/// Capabilities of a child: the parent's, without processes or children of its own
+fn child_capabilities(parent: &Capabilities) -> Capabilities {
+    Capabilities {
+        process: ProcessCapability::None,
+        children: ChildCapability {
+            allowed_modules: Vec::new(),
+            ..parent.children.clone()
+        },
+        ..parent.clone()
+    }
+}
+
+/// Resource limits of a child: `percent` of the parent's memory and fuel
+fn child_limits(parent: &ResourceLimits, percent: u8) -> ResourceLimits {
+    let percent = u64::from(percent.min(100));
+    let mut limits = parent.clone();
+    limits.memory.max_memory_pages = (parent.memory.max_memory_pages * percent / 100).max(1);
+    limits.memory.reserved_memory_pages = limits.memory.reserved_memory_pages.min(limits.memory.max_memory_pages);
+    limits.memory.pre_grow_pages = None;
+    limits.fuel = parent.fuel.map(|fuel| fuel / 100 * percent);
+    limits
+}
+
+/// Starts and calls the children of one instance
+pub(crate) struct InstanceChildren {
+    registry: Arc<ChildRegistry>,
+    parent: InstanceId,
+    capabilities: ActiveCapabilities,
+    limits: ResourceLimits,
+}
+
+impl InstanceChildren {
+    /// Serve requests made by `parent`, whose resource limits are `limits`
+    pub(crate) fn new(
+        registry: Arc<ChildRegistry>,
+        parent: InstanceId,
+        capabilities: ActiveCapabilities,
+        limits: ResourceLimits,
+    ) -> Self {
+        Self {
+            registry,
+            parent,
+            capabilities,
+            limits,
+        }
+    }
+}
+
+impl ChildSpawner for InstanceChildren {
+    fn spawn(&self, module: &str) -> Result<u32> {
+        let capabilities = self.capabilities.current();
+        if !capabilities.children.allows(module) {
+            log::warn!("Instance {} denied starting child module {}", self.parent, module);
+            return Err(Error::SecurityViolation {
+                violation: format!("Instance is not granted child module {}", module),
+                instance_id: Some(self.parent.as_uuid()),
+                context: SecurityContext {
+                    attempted_operation: format!("spawn {}", module),
+                    required_capability: "children".to_string(),
+                    available_capabilities: capabilities.children.allowed_modules.clone(),
+                },
+            });
+        }
+        if self.registry.child_count(self.parent) >= capabilities.children.max_children {
+            return Err(Error::ResourceLimit {
+                message: format!("Instance already runs its limit of {} children", capabilities.children.max_children),
+            });
+        }
+        
+        let module_id = self.registry.modules.read().unwrap().get(module).copied()
+            .ok_or_else(|| Error::NotFound {
+                resource_type: "child module".to_string(),
+                identifier: module.to_string(),
+            })?;
+        let runtime = self.registry.runtime()?;
+        let instance = runtime.create_instance(
+            runtime.get_module(module_id)?.as_ref(),
+            child_limits(&self.limits, capabilities.children.budget_percent),
+            child_capabilities(&capabilities),
+        )?;
+        
+        let mut children = self.registry.children.lock().unwrap();
+        let children = children.entry(self.parent).or_default();
+        let handle = children.keys().next_back().map_or(1, |last| last + 1);
+        children.insert(handle, Arc::from(instance));
+        Ok(handle)
+    }
+    
+    fn call(&self, child: u32, function: &str, payload: &[u8]) -> Result<Vec<u8>> {
+        self.registry.child(self.parent, child)?.call_raw(function, payload)
+    }
+    
+    fn kill(&self, child: u32) -> Result<()> {
+        let removed = self.registry.children.lock().unwrap()
+            .get_mut(&self.parent)
+            .and_then(|children| children.remove(&child));
+        match removed {
+            Some(_) => Ok(()),
+            None => Err(Error::NotFound {
+                resource_type: "child instance".to_string(),
+                identifier: child.to_string(),
+            }),
+        }
+    }
+}
@@ -0,0 +1,404 @@
+//! Isolating module compilation from the threads that load modules
+//!
+//! Compiling untrusted wasm runs Cranelift on attacker-controlled input, and a
+//! pathological module can take a long time to compile or hit a compiler bug.
+//! By default modules compile on the thread that loads them. A
+//! [`CompilationIsolation`] moves that work to dedicated compiler threads or to
+//! a child process, so a bad module can stall or crash only the compiler.
+//!
+//! A compiler process reads a request from stdin and streams the compiled
+//! artifact back on stdout; [`serve_compile_request`] implements its side, and
+//! the `wasm-sandbox compile-worker` command runs it. Artifacts are loaded
+//! without re-validation, so the process must run this crate's compiler.
+//!
+//! A compiler process can be locked down with a [`HostHardening`]: resource
+//! limits, `no_new_privs` and the seccomp filter are applied before it starts,
+//! and [`serve_compile_request`] applies the rest, such as landlock rules,
+//! once it has read its request.
+
+use std::io::{Read, Write};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use wasmtime::{Engine, Module};
+
+use crate::error::{Error, Result};
+use crate::runtime::{PoolingConfig, RuntimeConfig};
+use crate::security::hardening::HostHardening;
+#[cfg(windows)]
+use crate::security::hardening::JobObject;
+
+/// Default time a compiler process may take
+pub const DEFAULT_COMPILE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default cap on the size of an artifact read back from a compiler process
+pub const DEFAULT_MAX_ARTIFACT_BYTES: usize = 256 * 1024 * 1024;
+
+/// Where modules are compiled
+#[derive(Debug, Clone, Default)]
+pub enum CompilationIsolation {
+    /// On the thread that loads the module
+    #[default]
+    InProcess,
+    
+    /// On dedicated compiler threads
+    ThreadPool {
+        /// Number of compiler threads
+        threads: usize,
+        
+        /// How long a load waits for its module to compile (`None` waits forever)
+        timeout: Option<Duration>,
+    },
+    
+    /// In a child process
+    Subprocess(SubprocessCompiler),
+}
+
+/// Command that compiles modules in a child process
+///
+/// Each module is compiled by a fresh process, which is killed if it runs past
+/// the timeout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubprocessCompiler {
+    /// Program to run
+    pub program: PathBuf,
+    
+    /// Arguments passed to the program
+    pub args: Vec<String>,
+    
+    /// How long the process may take
+    pub timeout: Duration,
+    
+    /// Largest artifact accepted from the process in bytes
+    pub max_artifact_bytes: usize,
+    
+    /// OS-level restrictions for the process
+    pub hardening: Option<HostHardening>,
+}
+
+impl SubprocessCompiler {
+    /// Compile with `program`, which must serve requests like [`serve_compile_request`]
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            timeout: DEFAULT_COMPILE_TIMEOUT,
+            max_artifact_bytes: DEFAULT_MAX_ARTIFACT_BYTES,
+            hardening: None,
+        }
+    }
+    
+    /// Compile with the `wasm-sandbox` command-line tool at `cli_path`
+    pub fn cli(cli_path: impl Into<PathBuf>) -> Self {
+        Self::new(cli_path).arg("compile-worker")
+    }
+    
+    /// Add an argument
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+    
+    /// Set how long the process may take
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    
+    /// Set the largest artifact accepted from the process
+    pub fn max_artifact_bytes(mut self, max_artifact_bytes: usize) -> Self {
+        self.max_artifact_bytes = max_artifact_bytes;
+        self
+    }
+    
+    /// Harden the process with `hardening`
+    pub fn hardening(mut self, hardening: HostHardening) -> Self {
+        self.hardening = Some(hardening);
+        self
+    }
+    
+    /// Compile `wasm_bytes` for an engine configured with `settings`
+    fn compile(&self, engine: &Engine, settings: &EngineSettings, wasm_bytes: &[u8]) -> Result<Module> {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        if let Some(hardening) = &self.hardening {
+            hardening.harden_command(&mut command)?;
+        }
+        let mut child = command.spawn()
+            .map_err(|e| Error::Compilation {
+                message: format!("Failed to start compiler process {}: {}", self.program.display(), e),
+            })?;
+        // The process does nothing before it gets its request, so it can join the job first
+        #[cfg(windows)]
+        let _job = match self.hardening.as_ref().filter(|hardening| hardening.has_limits()) {
+            Some(hardening) => match JobObject::new(hardening).and_then(|job| job.assign(&child).map(|()| job)) {
+                Ok(job) => Some(job),
+                Err(e) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(e);
+                }
+            },
+            None => None,
+        };
+        
+        // Feed and drain the pipes on their own threads so a stuck process can't block us
+        let request = encode_request(settings, self.hardening.as_ref(), wasm_bytes)?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        thread::spawn(move || stdin.write_all(&request));
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let limit = self.max_artifact_bytes as u64 + 1;
+        let artifact = thread::spawn(move || {
+            let mut artifact = Vec::new();
+            stdout.take(limit).read_to_end(&mut artifact).map(|_| artifact)
+        });
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let diagnostics = thread::spawn(move || {
+            let mut diagnostics = String::new();
+            let _ = stderr.take(64 * 1024).read_to_string(&mut diagnostics);
+            diagnostics
+        });
+        
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(Error::Timeout {
+                    operation: "module compilation".to_string(),
+                    duration: self.timeout,
+                    instance_id: None,
+                });
+            }
+            thread::sleep(Duration::from_millis(5));
+        };
+        
+        let artifact = artifact.join().unwrap_or_else(|_| Ok(Vec::new()))?;
+        if !status.success() {
+            let diagnostics = diagnostics.join().unwrap_or_default();
+            return Err(Error::Compilation {
+                message: format!("Compiler process failed ({}): {}", status, diagnostics.trim()),
+            });
+        }
+        if artifact.len() > self.max_artifact_bytes {
+            return Err(Error::Compilation {
+                message: format!("Compiled module is over the {} byte limit", self.max_artifact_bytes),
+            });
+        }
+        
+        // SAFETY: the artifact comes from this crate's compiler (see the module docs);
+        // Wasmtime still rejects artifacts built for a different engine configuration
+        unsafe { Module::deserialize(engine, &artifact) }.map_err(|e| Error::Compilation {
+            message: format!("Compiler process returned an unusable module: {}", e),
+        })
+    }
+}
+
+/// Runtime settings that change how modules are compiled
+///
+/// A compiler process builds its engine from these so its artifacts match the
+/// host's engine.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EngineSettings {
+    /// Instrument code for fuel metering
+    pub enable_fuel: bool,
+    
+    /// Apply the runtime's stack limit
+    pub enable_memory_limits: bool,
+    
+    /// Emit debug information
+    pub debug_info: bool,
+    
+    /// Optimize generated code for speed
+    pub optimize: bool,
+    
+    /// Pooling allocator the modules are instantiated in
+    pub pooling: Option<PoolingConfig>,
+    
+    /// Accept 64-bit memories
+    pub memory64: bool,
+    
+    /// Accept GC struct and array types
+    pub gc: bool,
+}
+
+impl From<&RuntimeConfig> for EngineSettings {
+    fn from(config: &RuntimeConfig) -> Self {
+        Self {
+            enable_fuel: config.enable_fuel,
+            enable_memory_limits: config.enable_memory_limits,
+            debug_info: config.debug_info,
+            optimize: config.compilation_threads > 0,
+            pooling: config.pooling.clone(),
+            memory64: config.enable_memory64,
+            gc: config.enable_gc,
+        }
+    }
+}
+
+/// JSON header of a compile request
+#[derive(Serialize, Deserialize)]
+struct RequestHeader {
+    #[serde(flatten)]
+    settings: EngineSettings,
+    
+    /// Hardening the process applies to itself before compiling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hardening: Option<HostHardening>,
+}
+
+/// Frame a compile request: the header's JSON length (u32, little endian), the JSON, then the module
+fn encode_request(settings: &EngineSettings, hardening: Option<&HostHardening>, wasm_bytes: &[u8]) -> Result<Vec<u8>> {
+    let header = serde_json::to_vec(&RequestHeader {
+        settings: settings.clone(),
+        hardening: hardening.cloned(),
+    })?;
+    let mut request = Vec::with_capacity(4 + header.len() + wasm_bytes.len());
+    request.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    request.extend_from_slice(&header);
+    request.extend_from_slice(wasm_bytes);
+    Ok(request)
+}
+
+/// Compile one module for a compiler process
+///
+/// Reads a request from `input` until end of file and writes the compiled
+/// artifact to `output`. A request carrying a [`HostHardening`] hardens the
+/// current process before the module is compiled, which can't be undone.
+pub fn serve_compile_request(mut input: impl Read, mut output: impl Write) -> Result<()> {
+    let mut request = Vec::new();
+    input.read_to_end(&mut request)?;
+    
+    let invalid = |reason: &str| Error::InvalidInput {
+        field: "compile request".to_string(),
+        reason: reason.to_string(),
+        suggestion: None,
+    };
+    let (length, rest) = request.split_first_chunk::<4>().ok_or_else(|| invalid("missing header length"))?;
+    let length = u32::from_le_bytes(*length) as usize;
+    if rest.len() < length {
+        return Err(invalid("truncated header"));
+    }
+    let (header, wasm_bytes) = rest.split_at(length);
+    let header: RequestHeader = serde_json::from_slice(header)?;
+    if let Some(hardening) = &header.hardening {
+        hardening.apply()?;
+    }
+    let settings = header.settings;
+    
+    let engine = Engine::new(&super::wasmtime::engine_config(&settings))
+        .map_err(|e| Error::Compilation { message: format!("Failed to create engine: {}", e) })?;
+    let artifact = engine.precompile_module(wasm_bytes)
+        .map_err(|e| Error::Compilation { message: format!("Failed to compile module: {:#}", e) })?;
+    output.write_all(&artifact)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// A module waiting for a compiler thread
+struct CompileJob {
+    engine: Engine,
+    wasm_bytes: Vec<u8>,
+    reply: mpsc::Sender<Result<Module>>,
+}
+
+/// Dedicated threads compiling modules one at a time each
+pub(crate) struct CompilerPool {
+    jobs: mpsc::Sender<CompileJob>,
+    timeout: Option<Duration>,
+}
+
+impl CompilerPool {
+    fn new(threads: usize, timeout: Option<Duration>) -> Result<Self> {
+        let (jobs, queue) = mpsc::channel::<CompileJob>();
+        let queue = Arc::new(Mutex::new(queue));
+        for index in 0..threads.max(1) {
+            let queue = queue.clone();
+            thread::Builder::new()
+                .name(format!("wasm-compiler-{}", index))
+                .spawn(move || loop {
+                    // Exits once the pool is dropped
+                    let Ok(job) = queue.lock().unwrap().recv() else {
+                        return;
+                    };
+                    let compiled = catch_unwind(AssertUnwindSafe(|| Module::new(&job.engine, &job.wasm_bytes)));
+                    let _ = job.reply.send(match compiled {
+                        Ok(result) => result.map_err(|e| Error::module_load_error(format!("Failed to compile module: {:#}", e))),
+                        Err(_) => Err(Error::Compilation { message: "Compiler thread panicked".to_string() }),
+                    });
+                })
+                .map_err(|e| Error::RuntimeInitialization {
+                    message: format!("Failed to start compiler thread: {}", e),
+                })?;
+        }
+        Ok(Self { jobs, timeout })
+    }
+    
+    fn compile(&self, engine: &Engine, wasm_bytes: &[u8]) -> Result<Module> {
+        let (reply, compiled) = mpsc::channel();
+        let job = CompileJob {
+            engine: engine.clone(),
+            wasm_bytes: wasm_bytes.to_vec(),
+            reply,
+        };
+        self.jobs.send(job).map_err(|_| Error::Compilation {
+            message: "Compiler threads have stopped".to_string(),
+        })?;
+        
+        let stopped = || Error::Compilation { message: "Compiler threads have stopped".to_string() };
+        match self.timeout {
+            None => compiled.recv().map_err(|_| stopped())?,
+            Some(timeout) => compiled.recv_timeout(timeout).map_err(|e| match e {
+                mpsc::RecvTimeoutError::Timeout => Error::Timeout {
+                    operation: "module compilation".to_string(),
+                    duration: timeout,
+                    instance_id: None,
+                },
+                mpsc::RecvTimeoutError::Disconnected => stopped(),
+            })?,
+        }
+    }
+}
+
+/// Compiles modules as a [`CompilationIsolation`] says
+pub(crate) enum ModuleCompiler {
+    InProcess,
+    ThreadPool(CompilerPool),
+    Subprocess(SubprocessCompiler, EngineSettings),
+}
+
+impl ModuleCompiler {
+    pub(crate) fn new(config: &RuntimeConfig) -> Result<Self> {
+        Ok(match &config.compilation {
+            CompilationIsolation::InProcess => Self::InProcess,
+            CompilationIsolation::ThreadPool { threads, timeout } => {
+                Self::ThreadPool(CompilerPool::new(*threads, *timeout)?)
+            }
+            CompilationIsolation::Subprocess(compiler) => {
+                Self::Subprocess(compiler.clone(), EngineSettings::from(config))
+            }
+        })
+    }
+    
+    pub(crate) fn compile(&self, engine: &Engine, wasm_bytes: &[u8]) -> Result<Module> {
+        match self {
+            Self::InProcess => Module::new(engine, wasm_bytes)
+                .map_err(|e| Error::module_load_error(format!("Failed to compile module: {:#}", e))),
+            Self::ThreadPool(pool) => pool.compile(engine, wasm_bytes),
+            Self::Subprocess(compiler, settings) => compiler.compile(engine, settings, wasm_bytes),
+        }
+    }
+}
@@ -9,15 +9,19 @@
 //! - Structured data exchange
 //! - Explicit imports and exports
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, PoisonError};
 use std::path::Path;
 use uuid::Uuid;
 
-use wasmtime::component::{Component, Instance, Linker};
+use wasmtime::component::{Component, Instance, Linker, ResourceTable};
 use wasmtime::{Engine, Store};
+use wasmtime_wasi::p2::{IoView, WasiCtx, WasiView};
 
 use crate::error::{Error, Result};
 use crate::runtime::{WasmModule, WasmInstance, WasmRuntime, WasmFunctionCaller, WasmInstanceState, ModuleId, RuntimeConfig, RuntimeMetrics};
+use crate::runtime::engine::RuntimeEngine;
+use crate::runtime::features::RuntimeFeatures;
+use crate::runtime::wasi_sockets::SocketPolicy;
 use crate::security::{Capabilities, ResourceLimits};
 
 /// A WebAssembly Component Module
@@ -169,7 +173,7 @@ impl<T: Send + Sync + 'static> WasmInstance for ComponentInstance<T> {
         WasmInstanceState::Running // Components are always running if created
     }
     
-    fn memory_usage(&self) -> usize {
+    fn memory_usage(&self) -> u64 {
         // Component memory usage would need specific wasmtime API calls
         0
     }
@@ -234,6 +238,37 @@ impl WasmFunctionCaller for ComponentFunctionCaller {
     }
 }
 
+/// Store data for components linked against WASI preview2
+///
+/// The WASI context isn't `Sync`; it sits behind mutexes that are only ever
+/// reached through `&mut self`, so they never actually lock.
+pub struct ComponentWasi {
+    /// WASI context, including the socket policy
+    ctx: Mutex<WasiCtx>,
+    
+    /// Resources handed out to the guest
+    table: Mutex<ResourceTable>,
+}
+
+impl ComponentWasi {
+    /// Store data around a built WASI context
+    pub fn new(ctx: WasiCtx) -> Self {
+        Self { ctx: Mutex::new(ctx), table: Mutex::new(ResourceTable::new()) }
+    }
+}
+
+impl IoView for ComponentWasi {
+    fn table(&mut self) -> &mut ResourceTable {
+        self.table.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl WasiView for ComponentWasi {
+    fn ctx(&mut self) -> &mut WasiCtx {
+        self.ctx.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
 /// Component Model runtime manager
 pub struct ComponentRuntime {
     /// Wasmtime engine
@@ -295,6 +330,29 @@ impl ComponentRuntime {
     pub fn create_linker<T>(&self) -> Linker<T> {
         Linker::new(&self.engine)
     }
+    
+    /// Create a WASI preview2 context builder whose sockets follow the network capability
+    pub fn wasi_ctx_builder(&self, capabilities: &Capabilities) -> wasmtime_wasi::p2::WasiCtxBuilder {
+        let mut builder = wasmtime_wasi::p2::WasiCtxBuilder::new();
+        let policy = SocketPolicy::new(capabilities.network.clone())
+            .with_enforcement(capabilities.enforcement.mode_for("network"));
+        Arc::new(policy).apply(&mut builder);
+        builder
+    }
+    
+    /// Instantiate a component with WASI preview2 imports restricted by `capabilities`
+    pub fn instantiate(
+        &self,
+        module: &ComponentModule,
+        capabilities: Capabilities,
+        resource_limits: ResourceLimits,
+    ) -> Result<ComponentInstance<ComponentWasi>> {
+        let store = self.create_store(ComponentWasi::new(self.wasi_ctx_builder(&capabilities).build()));
+        let mut linker = self.create_linker();
+        wasmtime_wasi::p2::add_to_linker_sync(&mut linker)
+            .map_err(|e| Error::InstanceCreation { reason: e.to_string(), instance_id: None })?;
+        ComponentInstance::new(module, store, &linker, capabilities, resource_limits)
+    }
 }
 
 impl WasmRuntime for ComponentRuntime {
@@ -318,13 +376,29 @@ impl WasmRuntime for ComponentRuntime {
         Vec::new()
     }
     
+    fn engine(&self) -> RuntimeEngine {
+        RuntimeEngine::Wasmtime
+    }
+    
     fn create_instance(
         &self, 
-        _module: &dyn WasmModule, 
-        _resources: ResourceLimits,
-        _capabilities: Capabilities,
+        module: &dyn WasmModule, 
+        resources: ResourceLimits,
+        capabilities: Capabilities,
     ) -> Result<Box<dyn WasmInstance>> {
-        Err(Error::UnsupportedOperation { message: "Instance creation interface not implemented for components yet".to_string() })
+        let module = module.as_any().downcast_ref::<ComponentModule>()
+            .ok_or_else(|| Error::InstanceCreation {
+                reason: "Module is not a component".to_string(),
+                instance_id: None,
+            })?;
+        Ok(Box::new(self.instantiate(module, capabilities, resources)?))
+    }
+    
+    fn features(&self) -> RuntimeFeatures {
+        RuntimeFeatures {
+            components: true,
+            ..RuntimeFeatures::none("wasmtime-component")
+        }
     }
     
     fn get_metrics(&self) -> RuntimeMetrics {
@@ -336,6 +410,7 @@ impl WasmRuntime for ComponentRuntime {
             fuel_consumption_rate: None,
             cache_hit_rate: None,
             last_compilation_time_ms: None,
+            pool: None,
         }
     }
     
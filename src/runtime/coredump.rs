@@ -0,0 +1,150 @@
+//! Coredumps of trapped guests
+//!
+//! When a call into an instance created with `enable_debug` traps, the guest's
+//! stack, globals and memories are written in the standard
+//! [Wasm coredump format](https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md),
+//! which debuggers and inspectors such as `wasmgdb` read. Dumps go to
+//! [`CoredumpConfig::directory`], one file per trap, and are skipped if the
+//! guest's memories are larger than [`CoredumpConfig::max_bytes`]. The failed
+//! call's error names the file, and
+//! [`crate::WasmSandbox::last_coredump`] describes the latest one.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::runtime::call_context::{current_call, CallId};
+use crate::InstanceId;
+
+/// Largest coredump written by default
+pub const DEFAULT_MAX_COREDUMP_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Where coredumps are written and how large they may be
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoredumpConfig {
+    /// Directory the dumps are written to
+    pub directory: PathBuf,
+    
+    /// Largest dump written; traps in guests with more memory than this produce none
+    pub max_bytes: u64,
+}
+
+impl Default for CoredumpConfig {
+    fn default() -> Self {
+        Self {
+            directory: std::env::temp_dir().join("wasm-sandbox-coredumps"),
+            max_bytes: DEFAULT_MAX_COREDUMP_BYTES,
+        }
+    }
+}
+
+impl CoredumpConfig {
+    /// Write dumps to `directory`
+    pub fn directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.directory = directory.into();
+        self
+    }
+    
+    /// Skip dumps larger than `bytes`
+    pub fn max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = bytes;
+        self
+    }
+}
+
+/// A coredump written for a trapped call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coredump {
+    /// Instance whose call trapped
+    pub instance_id: InstanceId,
+    
+    /// ID of the call, if it was made through the sandbox
+    pub call_id: Option<CallId>,
+    
+    /// Export that was called
+    pub function_name: String,
+    
+    /// File the dump was written to
+    pub path: PathBuf,
+    
+    /// Size of the dump in bytes
+    pub size: u64,
+    
+    /// When the dump was written
+    pub created_at: SystemTime,
+}
+
+/// Destination for the coredumps of one instance's trapped calls
+pub trait CoredumpSink: Send + Sync {
+    /// Largest dump the sink accepts
+    fn max_bytes(&self) -> u64;
+    
+    /// Store a serialized dump, returning where it was written
+    fn write(&self, function_name: &str, coredump: &[u8]) -> Option<PathBuf>;
+}
+
+/// Latest coredump of every instance in a sandbox
+#[derive(Debug, Default)]
+pub(crate) struct Coredumps {
+    latest: Mutex<HashMap<InstanceId, Coredump>>,
+}
+
+impl Coredumps {
+    /// Latest coredump written for an instance
+    pub(crate) fn latest(&self, instance_id: InstanceId) -> Option<Coredump> {
+        self.latest.lock().unwrap().get(&instance_id).cloned()
+    }
+    
+    /// Forget an instance's latest coredump; the file stays on disk
+    pub(crate) fn forget(&self, instance_id: InstanceId) {
+        self.latest.lock().unwrap().remove(&instance_id);
+    }
+}
+
+/// A sandbox's coredumps as seen by one instance
+pub(crate) struct InstanceCoredumps {
+    coredumps: Arc<Coredumps>,
+    config: CoredumpConfig,
+    instance_id: InstanceId,
+}
+
+impl InstanceCoredumps {
+    /// Write dumps for `instance_id` as `config` says
+    pub(crate) fn new(coredumps: Arc<Coredumps>, config: CoredumpConfig, instance_id: InstanceId) -> Self {
+        Self { coredumps, config, instance_id }
+    }
+}
+
+impl CoredumpSink for InstanceCoredumps {
+    fn max_bytes(&self) -> u64 {
+        self.config.max_bytes
+    }
+    
+    fn write(&self, function_name: &str, coredump: &[u8]) -> Option<PathBuf> {
+        let call_id = current_call().map(|call| call.call_id);
+        let created_at = SystemTime::now();
+        let stamp = created_at.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis();
+        let name = match call_id {
+            Some(call_id) => format!("{}-call-{}.coredump", self.instance_id, call_id),
+            None => format!("{}-{}.coredump", self.instance_id, stamp),
+        };
+        let path = self.config.directory.join(name);
+        let written = fs::create_dir_all(&self.config.directory).and_then(|_| fs::write(&path, coredump));
+        if let Err(e) = written {
+            log::warn!("Instance {}: failed to write coredump to {}: {}", self.instance_id, path.display(), e);
+            return None;
+        }
+        
+        self.coredumps.latest.lock().unwrap().insert(self.instance_id, Coredump {
+            instance_id: self.instance_id,
+            call_id,
+            function_name: function_name.to_string(),
+            path: path.clone(),
+            size: coredump.len() as u64,
+            created_at,
+        });
+        Some(path)
+    }
+}
@@ -0,0 +1,95 @@
+//! Structured diagnostics guests attach to their results
+//!
+//! During a call a guest can report warnings, records it skipped, or the
+//! parts of a batch that failed through [`crate::runtime::LOG_DIAGNOSTIC_FUNCTION`]
+//! instead of printing them for the host to parse. They are collected per call
+//! and handed back as the [`CallDiagnostics`] of the call's
+//! [`InvocationReport`](crate::runtime::growth::InvocationReport).
+
+use serde::{Deserialize, Serialize};
+
+/// Most diagnostics kept for one call; later ones are only counted
+pub const MAX_CALL_DIAGNOSTICS: usize = 256;
+
+/// Largest encoded diagnostic a guest may report
+pub const MAX_DIAGNOSTIC_BYTES: usize = 64 * 1024;
+
+/// What a diagnostic is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    /// Something the caller should know about that didn't stop the call
+    Warning,
+    
+    /// An input record the guest left out of its result
+    SkippedRecord,
+    
+    /// Part of the work failed while the rest succeeded
+    PartialFailure,
+    
+    /// Anything else
+    Note,
+}
+
+/// One diagnostic reported by a guest
+///
+/// Guests send it as JSON, e.g.
+/// `{"kind": "skipped_record", "message": "bad date", "details": {"row": 12}}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// What the diagnostic is about
+    pub kind: DiagnosticKind,
+    
+    /// Human-readable description
+    pub message: String,
+    
+    /// Structured details, in whatever shape the guest chooses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+/// Diagnostics a guest reported during one call, in the order it reported them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CallDiagnostics {
+    /// Diagnostics kept
+    pub entries: Vec<Diagnostic>,
+    
+    /// Diagnostics dropped after [`MAX_CALL_DIAGNOSTICS`] were kept
+    pub dropped: usize,
+}
+
+impl CallDiagnostics {
+    /// Whether the guest reported nothing
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.dropped == 0
+    }
+    
+    /// Diagnostics of one kind
+    pub fn of_kind(&self, kind: DiagnosticKind) -> impl Iterator<Item = &Diagnostic> {
+        self.entries.iter().filter(move |diagnostic| diagnostic.kind == kind)
+    }
+    
+    /// Warnings reported
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.of_kind(DiagnosticKind::Warning)
+    }
+    
+    /// Records the guest skipped
+    pub fn skipped_records(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.of_kind(DiagnosticKind::SkippedRecord)
+    }
+    
+    /// Parts of the call that failed
+    pub fn partial_failures(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.of_kind(DiagnosticKind::PartialFailure)
+    }
+    
+    /// Add a diagnostic, or count it once the call has reported its maximum
+    pub(crate) fn push(&mut self, diagnostic: Diagnostic) {
+        if self.entries.len() < MAX_CALL_DIAGNOSTICS {
+            self.entries.push(diagnostic);
+        } else {
+            self.dropped += 1;
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Choosing the engine a sandbox runs on
+//!
+//! With both the `wasmtime-runtime` and `wasmer-runtime` features enabled,
+//! [`RuntimeConfig::engine`] picks which engine [`create_runtime`] builds. An
+//! explicit preference is tried first and the other engines follow in
+//! [`RuntimeEngine::ALL`] order, each fallback logged as a warning, so a host
+//! where the preferred engine can't initialize still gets a working sandbox.
+//! [`EngineSelection::Require`] turns that fallback off.
+//!
+//! [`EngineSelection::PreferCached`] picks the engine that can load the
+//! artifacts in a cache bundle written by [`crate::WasmSandbox::export_cache`],
+//! so a fleet shipped precompiled modules doesn't compile them again on a
+//! different engine.
+//!
+//! [`create_runtime`]: crate::runtime::create_runtime
+
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::runtime::cache_bundle::CacheBundle;
+use crate::runtime::{RuntimeConfig, WasmRuntime};
+
+/// WebAssembly engine a runtime is built on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeEngine {
+    /// Wasmtime, from the `wasmtime-runtime` feature
+    Wasmtime,
+    
+    /// Wasmer, from the `wasmer-runtime` feature
+    Wasmer,
+}
+
+impl RuntimeEngine {
+    /// Every engine, in the order they are tried by default
+    pub const ALL: [RuntimeEngine; 2] = [RuntimeEngine::Wasmtime, RuntimeEngine::Wasmer];
+    
+    /// Stable lowercase name, e.g. `wasmtime`
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Wasmtime => "wasmtime",
+            Self::Wasmer => "wasmer",
+        }
+    }
+    
+    /// Whether this build includes the engine
+    pub const fn is_available(self) -> bool {
+        match self {
+            Self::Wasmtime => cfg!(feature = "wasmtime-runtime"),
+            Self::Wasmer => cfg!(feature = "wasmer-runtime"),
+        }
+    }
+    
+    /// Engines this build includes, in the order they are tried by default
+    pub fn available() -> Vec<RuntimeEngine> {
+        Self::ALL.into_iter().filter(|engine| engine.is_available()).collect()
+    }
+    
+    /// Initialize a runtime on this engine
+    fn create(self, _config: &RuntimeConfig) -> Result<Box<dyn WasmRuntime>> {
+        match self {
+            #[cfg(feature = "wasmtime-runtime")]
+            Self::Wasmtime => Ok(Box::new(crate::runtime::wasmtime::WasmtimeRuntime::new(_config)?)),
+            #[cfg(feature = "wasmer-runtime")]
+            Self::Wasmer => Ok(Box::new(crate::runtime::wasmer::WasmerRuntime::new()?)),
+            #[allow(unreachable_patterns)]
+            _ => Err(not_compiled_in(self)),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for RuntimeEngine {
+    type Err = Error;
+    
+    fn from_str(name: &str) -> Result<Self> {
+        Self::ALL.into_iter()
+            .find(|engine| engine.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| Error::InvalidInput {
+                field: "engine".to_string(),
+                reason: format!("unknown engine `{}`", name),
+                suggestion: Some("Use `auto`, `wasmtime`, or `wasmer`".to_string()),
+            })
+    }
+}
+
+/// How [`crate::runtime::create_runtime`] picks an engine
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EngineSelection {
+    /// The first available engine in [`RuntimeEngine::ALL`] order that initializes
+    #[default]
+    Auto,
+    
+    /// This engine, falling back to the others if it is unavailable or fails to initialize
+    Prefer(RuntimeEngine),
+    
+    /// This engine or an error
+    Require(RuntimeEngine),
+    
+    /// The engine able to load the artifacts in the cache bundle at this path,
+    /// as for [`EngineSelection::Auto`] if none can or the bundle is unreadable
+    PreferCached(PathBuf),
+}
+
+impl FromStr for EngineSelection {
+    type Err = Error;
+    
+    /// Parse `auto` or the name of a preferred engine, as manifests give it
+    fn from_str(name: &str) -> Result<Self> {
+        if name.eq_ignore_ascii_case("auto") {
+            Ok(Self::Auto)
+        } else {
+            name.parse().map(Self::Prefer)
+        }
+    }
+}
+
+/// Initialize a runtime on the engine `config` selects
+pub(crate) fn select_runtime(config: &RuntimeConfig) -> Result<Box<dyn WasmRuntime>> {
+    let candidates: Vec<RuntimeEngine> = match &config.engine {
+        EngineSelection::Auto | EngineSelection::PreferCached(_) => RuntimeEngine::ALL.to_vec(),
+        EngineSelection::Prefer(preferred) => {
+            if !preferred.is_available() {
+                log::warn!("The preferred {} engine is not compiled in", preferred);
+            }
+            std::iter::once(*preferred)
+                .chain(RuntimeEngine::ALL.into_iter().filter(|engine| engine != preferred))
+                .collect()
+        }
+        EngineSelection::Require(required) => return required.create(config),
+    };
+    let bundle_target = match &config.engine {
+        EngineSelection::PreferCached(path) => match CacheBundle::read_target(path) {
+            Ok(target) => Some(target),
+            Err(e) => {
+                log::warn!("Ignoring cache bundle {} when choosing an engine: {}", path.display(), e);
+                None
+            }
+        },
+        _ => None,
+    };
+    
+    // Without a bundle the first engine to initialize wins; with one, engines
+    // keep being tried until one can load it
+    let mut candidates = candidates.into_iter().filter(|engine| engine.is_available()).peekable();
+    let first = candidates.peek().copied();
+    let mut fallback: Option<Box<dyn WasmRuntime>> = None;
+    let mut last_error = None;
+    for engine in candidates {
+        let runtime = match engine.create(config) {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                log::warn!("The {} engine failed to initialize: {}", engine, e);
+                last_error = Some(e);
+                continue;
+            }
+        };
+        let Some(target) = &bundle_target else {
+            if Some(engine) != first {
+                log::warn!("Falling back to the {} engine", engine);
+            }
+            return Ok(runtime);
+        };
+        let loads_bundle = runtime.artifact_target()
+            .is_some_and(|host| target.check_compatible(&host).is_ok());
+        if loads_bundle {
+            return Ok(runtime);
+        }
+        fallback.get_or_insert(runtime);
+    }
+    
+    match fallback {
+        Some(runtime) => {
+            log::warn!("No engine can load the cache bundle; using the {} engine", runtime.engine());
+            Ok(runtime)
+        }
+        None => Err(last_error.unwrap_or_else(|| Error::RuntimeInitialization {
+            message: "No WebAssembly runtime feature is enabled".to_string(),
+        })),
+    }
+}
+
+/// Error for an engine this build does not include
+fn not_compiled_in(engine: RuntimeEngine) -> Error {
+    Error::RuntimeInitialization {
+        message: format!("The {} engine is not compiled in; enable the `{}-runtime` feature", engine, engine),
+    }
+}
@@ -0,0 +1,237 @@
+//! Preconfigured guest environments composed in before instantiation
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// A file placed in the guest filesystem
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VirtualFile {
+    /// Absolute guest path (e.g. `/fixtures/data.csv`)
+    pub path: String,
+    
+    /// File contents
+    pub contents: Vec<u8>,
+}
+
+/// A socket endpoint answered with canned data instead of the network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StubSocket {
+    /// Endpoint address as `host:port`
+    pub address: String,
+    
+    /// Bytes returned to the guest when it reads from the endpoint
+    pub response: Vec<u8>,
+}
+
+/// A host directory preopened at a guest path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostDirectory {
+    /// Directory on the host
+    pub host_path: PathBuf,
+    
+    /// Absolute guest path (e.g. `/scratch`)
+    pub guest_path: String,
+    
+    /// Whether the guest may only read the directory
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// Fixture files, environment variables, and stub sockets layered into an instance
+///
+/// Apart from host directories, the layer is fully virtual: files are
+/// materialized into a private temporary directory and preopened at their
+/// guest paths, variables are set directly in the guest environment without
+/// consulting the host's, and stub sockets never reach the network.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvironmentLayer {
+    /// Files visible to the guest
+    pub files: Vec<VirtualFile>,
+    
+    /// Environment variables visible to the guest
+    pub env: BTreeMap<String, String>,
+    
+    /// Stubbed socket endpoints
+    pub stub_sockets: Vec<StubSocket>,
+    
+    /// Host directories shared with the guest as they are
+    #[serde(default)]
+    pub directories: Vec<HostDirectory>,
+}
+
+impl EnvironmentLayer {
+    /// Create an empty layer
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Add a file at an absolute guest path
+    pub fn file(mut self, path: &str, contents: impl Into<Vec<u8>>) -> Self {
+        self.files.push(VirtualFile {
+            path: path.to_string(),
+            contents: contents.into(),
+        });
+        self
+    }
+    
+    /// Set an environment variable
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.env.insert(key.to_string(), value.to_string());
+        self
+    }
+    
+    /// Stub a socket endpoint with a canned response
+    pub fn stub_socket(mut self, address: &str, response: impl Into<Vec<u8>>) -> Self {
+        self.stub_sockets.push(StubSocket {
+            address: address.to_string(),
+            response: response.into(),
+        });
+        self
+    }
+    
+    /// Preopen a host directory read-write at an absolute guest path
+    pub fn directory(mut self, host_path: impl Into<PathBuf>, guest_path: &str) -> Self {
+        self.directories.push(HostDirectory {
+            host_path: host_path.into(),
+            guest_path: guest_path.to_string(),
+            read_only: false,
+        });
+        self
+    }
+    
+    /// Preopen a host directory read-only at an absolute guest path
+    pub fn read_only_directory(mut self, host_path: impl Into<PathBuf>, guest_path: &str) -> Self {
+        self.directories.push(HostDirectory {
+            host_path: host_path.into(),
+            guest_path: guest_path.to_string(),
+            read_only: true,
+        });
+        self
+    }
+    
+    /// Layer `other` on top of this layer, replacing files, sockets, and directories with the same path or address
+    pub fn merge(mut self, other: EnvironmentLayer) -> Self {
+        for file in other.files {
+            self.files.retain(|existing| existing.path != file.path);
+            self.files.push(file);
+        }
+        self.env.extend(other.env);
+        for socket in other.stub_sockets {
+            self.stub_sockets.retain(|existing| existing.address != socket.address);
+            self.stub_sockets.push(socket);
+        }
+        for directory in other.directories {
+            self.directories.retain(|existing| existing.guest_path != directory.guest_path);
+            self.directories.push(directory);
+        }
+        self
+    }
+    
+    /// Check whether the layer adds nothing
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.env.is_empty() && self.stub_sockets.is_empty() && self.directories.is_empty()
+    }
+    
+    /// Canned response for a stubbed endpoint
+    pub fn stub_response(&self, address: &str) -> Option<&[u8]> {
+        self.stub_sockets.iter()
+            .find(|socket| socket.address == address)
+            .map(|socket| socket.response.as_slice())
+    }
+    
+    /// Write the layer's files into a private temporary directory and list what to preopen
+    pub fn materialize(&self) -> Result<MaterializedEnvironment> {
+        let root = crate::utils::temp_dir()?;
+        let mut guest_dirs = BTreeSet::new();
+        
+        for file in &self.files {
+            let relative = guest_relative_path(&file.path)?;
+            let host_path = root.path().join(&relative);
+            
+            // Preopen the top-level guest directory, or the guest root for top-level files
+            let top = match relative.components().count() {
+                1 => "/".to_string(),
+                _ => format!("/{}", relative.components().next().unwrap().as_os_str().to_string_lossy()),
+            };
+            guest_dirs.insert(top);
+            
+            if let Some(parent) = host_path.parent() {
+                crate::utils::ensure_dir_exists(parent)?;
+            }
+            fs::write(&host_path, &file.contents).map_err(|e| Error::Filesystem {
+                operation: "materialize_environment".to_string(),
+                path: host_path.clone(),
+                reason: e.to_string(),
+            })?;
+        }
+        
+        let mut preopens: Vec<_> = guest_dirs.into_iter()
+            .map(|guest| HostDirectory {
+                host_path: root.path().join(guest.trim_start_matches('/')),
+                guest_path: guest,
+                read_only: false,
+            })
+            .collect();
+        for directory in &self.directories {
+            guest_relative_path(&directory.guest_path)?;
+            preopens.push(directory.clone());
+        }
+        
+        Ok(MaterializedEnvironment { root, preopens })
+    }
+}
+
+/// An environment layer written to disk; files are removed when dropped
+#[derive(Debug)]
+pub struct MaterializedEnvironment {
+    /// Temporary directory holding the files
+    root: tempfile::TempDir,
+    
+    /// Host directories to preopen, the materialized files' first
+    preopens: Vec<HostDirectory>,
+}
+
+impl MaterializedEnvironment {
+    /// Temporary directory holding the files
+    pub fn root(&self) -> &Path {
+        self.root.path()
+    }
+    
+    /// Host directories to preopen and the guest paths to preopen them at
+    pub fn preopens(&self) -> &[HostDirectory] {
+        &self.preopens
+    }
+}
+
+/// Validate an absolute guest path and return it relative to the guest root
+fn guest_relative_path(path: &str) -> Result<PathBuf> {
+    let invalid = |reason: &str| Error::InvalidInput {
+        field: "environment_layer.files.path".to_string(),
+        reason: format!("{}: {}", reason, path),
+        suggestion: Some("Use an absolute guest path such as /fixtures/data.json".to_string()),
+    };
+    
+    let guest = Path::new(path);
+    if !guest.has_root() {
+        return Err(invalid("guest path must be absolute"));
+    }
+    
+    let mut relative = PathBuf::new();
+    for component in guest.components() {
+        match component {
+            Component::RootDir => {}
+            Component::Normal(part) => relative.push(part),
+            _ => return Err(invalid("guest path must not contain `.` or `..`")),
+        }
+    }
+    
+    if relative.as_os_str().is_empty() {
+        return Err(invalid("guest path must name a file"));
+    }
+    Ok(relative)
+}
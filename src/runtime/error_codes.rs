@@ -0,0 +1,106 @@
+//! Stable error codes returned to guests by host imports
+//!
+//! Host imports return a non-negative value on success and one of these codes
+//! on failure, so a plugin can tell a missing grant from an exhausted quota and
+//! degrade gracefully instead of trapping. The values never change; the
+//! `wasm-sandbox-guest` crate in `guest/` mirrors them for plugin authors.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Guest-visible error code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuestErrorCode {
+    /// The caller lacks the capability or grant for the operation
+    PermissionDenied,
+    
+    /// The caller exhausted a quota or exceeded a size limit
+    QuotaExceeded,
+    
+    /// The operation failed for another reason
+    Failed,
+    
+    /// The requested item does not exist
+    NotFound,
+    
+    /// The caller is being rate limited and may retry later
+    Throttled,
+    
+    /// The request was malformed
+    InvalidInput,
+    
+    /// The facility is not available in the current context
+    Unavailable,
+    
+    /// The operation did not finish in time
+    Timeout,
+}
+
+impl GuestErrorCode {
+    /// All codes, in code order
+    pub const ALL: [GuestErrorCode; 8] = [
+        Self::PermissionDenied,
+        Self::QuotaExceeded,
+        Self::Failed,
+        Self::NotFound,
+        Self::Throttled,
+        Self::InvalidInput,
+        Self::Unavailable,
+        Self::Timeout,
+    ];
+    
+    /// Value returned to the guest
+    pub const fn code(self) -> i64 {
+        match self {
+            Self::PermissionDenied => -1,
+            Self::QuotaExceeded => -2,
+            Self::Failed => -3,
+            Self::NotFound => -4,
+            Self::Throttled => -5,
+            Self::InvalidInput => -6,
+            Self::Unavailable => -7,
+            Self::Timeout => -8,
+        }
+    }
+    
+    /// Look up a code returned by a host import
+    pub fn from_code(code: i64) -> Option<Self> {
+        Self::ALL.into_iter().find(|candidate| candidate.code() == code)
+    }
+    
+    /// Stable snake_case name, e.g. `permission_denied`
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::PermissionDenied => "permission_denied",
+            Self::QuotaExceeded => "quota_exceeded",
+            Self::Failed => "failed",
+            Self::NotFound => "not_found",
+            Self::Throttled => "throttled",
+            Self::InvalidInput => "invalid_input",
+            Self::Unavailable => "unavailable",
+            Self::Timeout => "timeout",
+        }
+    }
+}
+
+impl std::fmt::Display for GuestErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl From<&Error> for GuestErrorCode {
+    fn from(error: &Error) -> Self {
+        match error {
+            Error::SecurityViolation { .. } | Error::Capability { .. } => Self::PermissionDenied,
+            Error::ResourceLimit { .. } | Error::ResourceExhausted { .. } => Self::QuotaExceeded,
+            Error::NotFound { .. } => Self::NotFound,
+            Error::InvalidInput { .. } | Error::Serialization { .. } => Self::InvalidInput,
+            Error::Timeout { .. } => Self::Timeout,
+            Error::Unsupported { .. } | Error::UnsupportedOperation { .. } => Self::Unavailable,
+            _ => Self::Failed,
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Global memory budget and eviction of idle instances
+//!
+//! A snapshot file holds a little-endian `u32` header length and a JSON header
+//! with the instance's exported mutable globals and fuel, followed by its
+//! linear memory.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::InstanceId;
+use crate::InstanceConfig;
+use crate::error::{Error, Result};
+use crate::runtime::{FuelState, HostValue, ModuleId, WasmInstance};
+
+/// What happens to an instance evicted to stay within the memory budget
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Drop the instance and its state
+    Drop,
+    
+    /// Write the instance's state to a file in `dir` so it can be restored later
+    Snapshot {
+        /// Directory snapshots are written to
+        dir: PathBuf,
+    },
+}
+
+/// Limit on the combined linear memory of a sandbox's instances
+///
+/// When instances together use more than `max_bytes`, the least recently used
+/// instances that have been idle for at least `min_idle` are evicted until the
+/// total is back under the budget.
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    /// Maximum combined linear memory in bytes
+    pub max_bytes: u64,
+    
+    /// What to do with evicted instances
+    pub policy: EvictionPolicy,
+    
+    /// Instances used more recently than this are never evicted
+    pub min_idle: Duration,
+}
+
+impl MemoryBudget {
+    /// Create a budget that drops idle instances
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            policy: EvictionPolicy::Drop,
+            min_idle: Duration::from_secs(30),
+        }
+    }
+    
+    /// Snapshot evicted instances to `dir` instead of dropping them
+    pub fn snapshot_to<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.policy = EvictionPolicy::Snapshot { dir: dir.into() };
+        self
+    }
+    
+    /// Set how long an instance must be idle before it can be evicted
+    pub fn min_idle(mut self, duration: Duration) -> Self {
+        self.min_idle = duration;
+        self
+    }
+}
+
+/// Sent to eviction handlers before an instance is evicted
+#[derive(Debug, Clone)]
+pub struct EvictionNotice {
+    /// Instance about to be evicted
+    pub instance_id: InstanceId,
+    
+    /// Linear memory the eviction reclaims, in bytes
+    pub memory_bytes: u64,
+    
+    /// Time since the instance was last used
+    pub idle_for: Duration,
+    
+    /// File the instance will be snapshotted to, if it is kept
+    pub snapshot: Option<PathBuf>,
+}
+
+/// Callback invoked before each eviction
+pub type EvictionHandler = Arc<dyn Fn(&EvictionNotice) + Send + Sync>;
+
+/// Eviction counters for a sandbox
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EvictionMetrics {
+    /// Instances evicted
+    pub evictions: u64,
+    
+    /// Evicted instances that were dropped
+    pub dropped: u64,
+    
+    /// Evicted instances that were snapshotted
+    pub snapshotted: u64,
+    
+    /// Snapshots that could not be written; the instance is kept
+    pub snapshot_failures: u64,
+    
+    /// Snapshotted instances restored
+    pub restored: u64,
+    
+    /// Linear memory reclaimed by evictions, in bytes
+    pub bytes_reclaimed: u64,
+}
+
+/// An instance evicted to disk
+#[derive(Debug, Clone)]
+pub(crate) struct EvictedInstance {
+    /// Module the instance was created from
+    pub module_id: ModuleId,
+    
+    /// Configuration the instance was created with
+    pub config: InstanceConfig,
+    
+    /// File holding the instance's state
+    pub snapshot: PathBuf,
+}
+
+/// An instance considered for eviction
+pub(crate) struct EvictionCandidate {
+    /// Instance ID
+    pub instance_id: InstanceId,
+    
+    /// Linear memory in bytes
+    pub memory_bytes: u64,
+    
+    /// When the instance was last used
+    pub last_used: Instant,
+}
+
+/// Pick the instances to evict, least recently used first
+///
+/// Instances idle for less than the budget's `min_idle` are never picked, so the
+/// total may stay over budget.
+pub(crate) fn select_victims(
+    mut candidates: Vec<EvictionCandidate>,
+    budget: &MemoryBudget,
+    now: Instant,
+) -> Vec<EvictionCandidate> {
+    let mut total: u64 = candidates.iter().map(|c| c.memory_bytes).sum();
+    candidates.sort_by_key(|c| c.last_used);
+    
+    let mut victims = Vec::new();
+    for candidate in candidates {
+        if total <= budget.max_bytes {
+            break;
+        }
+        if now.duration_since(candidate.last_used) < budget.min_idle {
+            continue;
+        }
+        total -= candidate.memory_bytes;
+        victims.push(candidate);
+    }
+    victims
+}
+
+/// Path of an instance's snapshot file
+pub(crate) fn snapshot_path(dir: &Path, instance_id: InstanceId) -> PathBuf {
+    dir.join(format!("{}.mem", instance_id))
+}
+
+/// State of an instance kept while it is off the runtime
+#[derive(Debug)]
+pub(crate) struct InstanceSnapshot {
+    /// Linear memory
+    pub memory: Vec<u8>,
+    
+    /// Exported mutable globals
+    pub globals: Vec<(String, HostValue)>,
+    
+    /// Fuel granted and left, if metered
+    pub fuel: Option<FuelState>,
+}
+
+impl InstanceSnapshot {
+    /// Read the state of a running instance
+    pub(crate) fn capture(instance: &dyn WasmInstance) -> Result<Self> {
+        Ok(Self {
+            memory: instance.read_memory()?,
+            globals: instance.mutable_globals()?,
+            fuel: instance.fuel_state(),
+        })
+    }
+    
+    /// Put the state into a fresh instance of the same module
+    pub(crate) fn restore(&self, instance: &dyn WasmInstance) -> Result<()> {
+        instance.restore_memory(&self.memory)?;
+        instance.restore_globals(&self.globals)?;
+        match self.fuel {
+            Some(fuel) => instance.restore_fuel(fuel),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Snapshot contents stored ahead of the memory
+#[derive(Serialize, Deserialize)]
+struct SnapshotHeader {
+    globals: Vec<(String, HostValue)>,
+    fuel: Option<FuelState>,
+}
+
+/// Write an instance's state to its snapshot file
+pub(crate) fn write_snapshot(path: &Path, snapshot: &InstanceSnapshot) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| Error::Filesystem {
+            operation: "create snapshot directory".to_string(),
+            path: dir.to_path_buf(),
+            reason: e.to_string(),
+        })?;
+    }
+    
+    let header = serde_json::to_vec(&SnapshotHeader {
+        globals: snapshot.globals.clone(),
+        fuel: snapshot.fuel,
+    })?;
+    let mut contents = Vec::with_capacity(4 + header.len() + snapshot.memory.len());
+    contents.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    contents.extend_from_slice(&header);
+    contents.extend_from_slice(&snapshot.memory);
+    std::fs::write(path, contents).map_err(|e| Error::Filesystem {
+        operation: "write snapshot".to_string(),
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })
+}
+
+/// Read an instance's state back from its snapshot file
+pub(crate) fn read_snapshot(path: &Path) -> Result<InstanceSnapshot> {
+    let mut contents = std::fs::read(path).map_err(|e| Error::Filesystem {
+        operation: "read snapshot".to_string(),
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    let header_end = contents.get(..4)
+        .map(|length| 4 + u32::from_le_bytes(length.try_into().expect("four bytes")) as usize)
+        .filter(|end| *end <= contents.len())
+        .ok_or_else(|| Error::InvalidInput {
+            field: "snapshot".to_string(),
+            reason: format!("{} is truncated", path.display()),
+            suggestion: None,
+        })?;
+    let header: SnapshotHeader = serde_json::from_slice(&contents[4..header_end])?;
+    Ok(InstanceSnapshot {
+        memory: contents.split_off(header_end),
+        globals: header.globals,
+        fuel: header.fuel,
+    })
+}
@@ -0,0 +1,160 @@
+//! What a runtime backend can do
+//!
+//! Backends differ in what they support: Wasmtime meters fuel, interrupts
+//! calls through epochs and snapshots instances, while other backends do less.
+//! [`WasmRuntime::features`](crate::runtime::WasmRuntime::features) reports a
+//! backend's [`RuntimeFeatures`], so library users can branch on them, and
+//! [`RuntimeFeatures::require`] turns a missing feature into the same
+//! [`Error::Unsupported`] wherever the crate needs one.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// A feature a runtime backend may support
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeFeature {
+    /// Metering guest execution with fuel
+    Fuel,
+    
+    /// Interrupting running calls by advancing an epoch
+    Epochs,
+    
+    /// Loading and instantiating components
+    Components,
+    
+    /// 64-bit linear memories
+    Memory64,
+    
+    /// Shared memories and guest threads
+    Threads,
+    
+    /// Pre-allocating instances with a pooling allocator
+    Pooling,
+    
+    /// Copying an instance's memory and globals out and back in, for
+    /// hibernation, eviction and forks
+    Snapshots,
+    
+    /// The GC proposal's struct and array types
+    Gc,
+}
+
+impl RuntimeFeature {
+    /// Every feature, in declaration order
+    pub const ALL: [RuntimeFeature; 8] = [
+        RuntimeFeature::Fuel,
+        RuntimeFeature::Epochs,
+        RuntimeFeature::Components,
+        RuntimeFeature::Memory64,
+        RuntimeFeature::Threads,
+        RuntimeFeature::Pooling,
+        RuntimeFeature::Snapshots,
+        RuntimeFeature::Gc,
+    ];
+    
+    /// Short name of the feature, e.g. `memory64`
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuntimeFeature::Fuel => "fuel",
+            RuntimeFeature::Epochs => "epochs",
+            RuntimeFeature::Components => "components",
+            RuntimeFeature::Memory64 => "memory64",
+            RuntimeFeature::Threads => "threads",
+            RuntimeFeature::Pooling => "pooling",
+            RuntimeFeature::Snapshots => "snapshots",
+            RuntimeFeature::Gc => "gc",
+        }
+    }
+}
+
+impl fmt::Display for RuntimeFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Features a runtime backend supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RuntimeFeatures {
+    /// Name of the backend, e.g. `wasmtime`
+    pub backend: &'static str,
+    
+    /// Fuel metering
+    pub fuel: bool,
+    
+    /// Epoch interruption
+    pub epochs: bool,
+    
+    /// The component model
+    pub components: bool,
+    
+    /// 64-bit memories
+    pub memory64: bool,
+    
+    /// Shared memories and threads
+    pub threads: bool,
+    
+    /// The pooling allocator
+    pub pooling: bool,
+    
+    /// Instance snapshots
+    pub snapshots: bool,
+    
+    /// Garbage-collected struct and array types
+    pub gc: bool,
+}
+
+impl RuntimeFeatures {
+    /// A backend that supports none of the features
+    pub fn none(backend: &'static str) -> Self {
+        Self {
+            backend,
+            fuel: false,
+            epochs: false,
+            components: false,
+            memory64: false,
+            threads: false,
+            pooling: false,
+            snapshots: false,
+            gc: false,
+        }
+    }
+    
+    /// Whether the backend supports `feature`
+    pub fn supports(&self, feature: RuntimeFeature) -> bool {
+        match feature {
+            RuntimeFeature::Fuel => self.fuel,
+            RuntimeFeature::Epochs => self.epochs,
+            RuntimeFeature::Components => self.components,
+            RuntimeFeature::Memory64 => self.memory64,
+            RuntimeFeature::Threads => self.threads,
+            RuntimeFeature::Pooling => self.pooling,
+            RuntimeFeature::Snapshots => self.snapshots,
+            RuntimeFeature::Gc => self.gc,
+        }
+    }
+    
+    /// Features the backend supports, in declaration order
+    pub fn supported(&self) -> Vec<RuntimeFeature> {
+        RuntimeFeature::ALL.into_iter().filter(|feature| self.supports(*feature)).collect()
+    }
+    
+    /// Fail with [`Error::Unsupported`] naming `feature` unless the backend supports it
+    pub fn require(&self, feature: RuntimeFeature) -> Result<()> {
+        if self.supports(feature) {
+            return Ok(());
+        }
+        Err(Error::Unsupported {
+            operation: format!("`{}` on the {} runtime", feature, self.backend),
+            context: self.backend.to_string(),
+            suggestion: Some(format!(
+                "Use a runtime backend that supports {}, or leave it out of the configuration",
+                feature,
+            )),
+        })
+    }
+}
@@ -0,0 +1,441 @@
+//! Sandbox-wide fuel budget shared between instances
+//!
+//! Per-instance fuel limits bound what one instance may do, but not what a
+//! tenant running many instances may do together. A [`FuelBudget`] caps the
+//! fuel the whole sandbox may consume per time window and splits it between
+//! instances by their [`InstanceConfig::fuel_weight`](crate::InstanceConfig):
+//! each instance has a token bucket holding one window of its share, refilled
+//! continuously. A call is admitted while the instance's bucket is positive and
+//! the fuel it consumed is charged afterwards, so a long call can leave the
+//! bucket in debt that later refills have to pay off first.
+//!
+//! A [`FuelRefillPolicy`] keeps calls from dying when their instance runs out
+//! of fuel mid-request. Each time a running call's fuel drops below one
+//! increment it asks for a top-up: [`FuelClass::Interactive`] instances get one
+//! straight away, even if it puts their bucket in debt, while
+//! [`FuelClass::Batch`] instances pay for each top-up from their bucket up
+//! front and are suspended while it is empty. Top-ups stop at the policy's per-call cap.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, ResourceKind, Result};
+use crate::runtime::{RuntimeConfig, WasmInstance};
+use crate::InstanceId;
+
+/// Limit on the fuel all of a sandbox's instances may consume per window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuelBudget {
+    /// Fuel the sandbox may consume per window, split between instances by weight
+    pub fuel_per_window: u64,
+    
+    /// Length of a window
+    pub window: Duration,
+}
+
+impl FuelBudget {
+    /// Create a budget of `fuel_per_window` every `window`
+    pub fn new(fuel_per_window: u64, window: Duration) -> Self {
+        Self { fuel_per_window, window }
+    }
+    
+    /// Check the budget can be enforced
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.fuel_per_window == 0 || self.window.is_zero() {
+            return Err(Error::config_error(
+                "Fuel budget must grant some fuel over a non-empty window",
+                Some("Set FuelBudget::fuel_per_window and FuelBudget::window above zero".to_string()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// How an instance's calls are treated when they run low on fuel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FuelClass {
+    /// Background work, suspended until the fuel budget can pay for a top-up;
+    /// without a budget it gets no top-ups
+    #[default]
+    Batch,
+    
+    /// User-facing work, topped up immediately so calls rarely die mid-request
+    Interactive,
+}
+
+/// Top-ups for calls that run low on fuel
+///
+/// Fuel is checked each time a guest yields to the executor, so the increment
+/// must be at least twice [`RuntimeConfig::async_yield_fuel`] for a low call
+/// to be seen before it runs out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuelRefillPolicy {
+    /// Fuel added per top-up; a call asks for one when it has less than this left
+    pub increment: u64,
+    
+    /// Most fuel added over one call
+    pub max_per_call: u64,
+    
+    /// Time a suspended batch call waits before asking again
+    pub retry_interval: Duration,
+}
+
+impl Default for FuelRefillPolicy {
+    fn default() -> Self {
+        Self {
+            increment: 1_000_000,
+            max_per_call: 100_000_000,
+            retry_interval: Duration::from_millis(10),
+        }
+    }
+}
+
+impl FuelRefillPolicy {
+    /// Set the fuel added per top-up
+    pub fn increment(mut self, fuel: u64) -> Self {
+        self.increment = fuel;
+        self
+    }
+    
+    /// Set the most fuel added over one call
+    pub fn max_per_call(mut self, fuel: u64) -> Self {
+        self.max_per_call = fuel;
+        self
+    }
+    
+    /// Set the time a suspended batch call waits before asking again
+    pub fn retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+    
+    /// Check the policy can be enforced by a runtime with `runtime`
+    pub(crate) fn validate(&self, runtime: &RuntimeConfig) -> Result<()> {
+        let Some(yield_fuel) = runtime.async_yield_fuel.filter(|_| runtime.enable_fuel) else {
+            return Err(Error::config_error(
+                "Fuel refills require fuel metering with async yields",
+                Some("Set RuntimeConfig::enable_fuel and RuntimeConfig::async_yield_fuel".to_string()),
+            ));
+        };
+        if self.increment < yield_fuel.saturating_mul(2) {
+            return Err(Error::config_error(
+                format!("A fuel refill increment of {} can't be checked every {} fuel", self.increment, yield_fuel),
+                Some("Raise FuelRefillPolicy::increment to at least twice RuntimeConfig::async_yield_fuel".to_string()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Counters for fuel top-ups
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FuelRefillMetrics {
+    /// Top-ups granted
+    pub refills: u64,
+    
+    /// Fuel added by top-ups
+    pub fuel_added: u64,
+    
+    /// Times a batch call was suspended waiting for the fuel budget
+    pub suspensions: u64,
+    
+    /// Calls refused a top-up because they reached the per-call cap
+    pub capped: u64,
+}
+
+/// A running call's request for more fuel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefillRequest {
+    /// Fuel the call has consumed so far
+    pub consumed: u64,
+    
+    /// Fuel already added to the call
+    pub added: u64,
+}
+
+/// Answer to a [`RefillRequest`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefillDecision {
+    /// Add this much fuel
+    Grant(u64),
+    
+    /// Suspend the call and ask again after this long
+    Wait(Duration),
+    
+    /// Add nothing; the call runs on with the fuel it has
+    Deny,
+}
+
+/// Tops up an instance's fuel while its calls run
+pub trait FuelRefiller: Send + Sync {
+    /// Fuel left below which a call asks for more
+    fn low_water(&self) -> u64;
+    
+    /// Decide on a running call's request
+    fn refill(&self, request: RefillRequest) -> RefillDecision;
+}
+
+/// Applies a [`FuelRefillPolicy`] to one instance's calls
+pub(crate) struct InstanceFuelRefill {
+    policy: FuelRefillPolicy,
+    class: FuelClass,
+    instance_id: InstanceId,
+    ledger: Option<Arc<FuelLedger>>,
+    metrics: Arc<Mutex<FuelRefillMetrics>>,
+}
+
+impl InstanceFuelRefill {
+    pub(crate) fn new(
+        policy: FuelRefillPolicy,
+        class: FuelClass,
+        instance_id: InstanceId,
+        ledger: Option<Arc<FuelLedger>>,
+        metrics: Arc<Mutex<FuelRefillMetrics>>,
+    ) -> Self {
+        Self { policy, class, instance_id, ledger, metrics }
+    }
+}
+
+impl FuelRefiller for InstanceFuelRefill {
+    fn low_water(&self) -> u64 {
+        self.policy.increment
+    }
+    
+    fn refill(&self, request: RefillRequest) -> RefillDecision {
+        let mut metrics = self.metrics.lock().unwrap();
+        let fuel = self.policy.increment.min(self.policy.max_per_call.saturating_sub(request.added));
+        if fuel == 0 {
+            metrics.capped += 1;
+            return RefillDecision::Deny;
+        }
+        
+        // Batch calls pay for top-ups up front and wait while their bucket is empty
+        if self.class == FuelClass::Batch {
+            match self.ledger.as_ref().and_then(|ledger| ledger.prepay(self.instance_id, fuel)) {
+                None => return RefillDecision::Deny,
+                Some(false) => {
+                    metrics.suspensions += 1;
+                    return RefillDecision::Wait(self.policy.retry_interval);
+                }
+                Some(true) => {}
+            }
+        }
+        
+        metrics.refills += 1;
+        metrics.fuel_added = metrics.fuel_added.saturating_add(fuel);
+        RefillDecision::Grant(fuel)
+    }
+}
+
+/// Counters for calls metered against the fuel budget
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FuelBudgetMetrics {
+    /// Fuel charged to instances
+    pub fuel_charged: u64,
+    
+    /// Calls refused because the instance had used up its share
+    pub throttled_calls: u64,
+}
+
+struct Account {
+    weight: u32,
+    tokens: i64,
+    refilled_at: Instant,
+    /// Fuel paid for by top-ups but not yet consumed by a finished call
+    prepaid: u64,
+}
+
+struct LedgerState {
+    accounts: HashMap<InstanceId, Account>,
+    total_weight: u64,
+    metrics: FuelBudgetMetrics,
+}
+
+/// Token buckets of every instance under a [`FuelBudget`]
+pub(crate) struct FuelLedger {
+    budget: FuelBudget,
+    state: Mutex<LedgerState>,
+    /// Fuel per window handed to nested sandboxes, no longer shared by the instances
+    carved: AtomicU64,
+}
+
+impl FuelLedger {
+    /// Create a ledger with no instances
+    pub(crate) fn new(budget: FuelBudget) -> Self {
+        Self {
+            budget,
+            state: Mutex::new(LedgerState {
+                accounts: HashMap::new(),
+                total_weight: 0,
+                metrics: FuelBudgetMetrics::default(),
+            }),
+            carved: AtomicU64::new(0),
+        }
+    }
+    
+    /// Open an account for an instance, starting with a full bucket
+    pub(crate) fn register(&self, instance_id: InstanceId, weight: u32) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(previous) = state.accounts.remove(&instance_id) {
+            state.total_weight -= previous.weight as u64;
+        }
+        state.total_weight += weight as u64;
+        let tokens = self.share(weight, state.total_weight) as i64;
+        state.accounts.insert(instance_id, Account {
+            weight,
+            tokens,
+            refilled_at: Instant::now(),
+            prepaid: 0,
+        });
+    }
+    
+    /// Close an instance's account, returning its share to the others
+    pub(crate) fn unregister(&self, instance_id: InstanceId) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(account) = state.accounts.remove(&instance_id) {
+            state.total_weight -= account.weight as u64;
+        }
+    }
+    
+    /// Admit a call if the instance has fuel left in its bucket
+    ///
+    /// The returned charge bills the fuel the call consumes when dropped.
+    pub(crate) fn admit(&self, instance_id: InstanceId, instance: Arc<dyn WasmInstance>) -> Result<FuelCharge<'_>> {
+        let mut state = self.state.lock().unwrap();
+        let total_weight = state.total_weight;
+        let now = Instant::now();
+        let (share, tokens) = match state.accounts.get_mut(&instance_id) {
+            Some(account) => {
+                let share = self.share(account.weight, total_weight);
+                self.refill(account, share, now);
+                (share, account.tokens)
+            }
+            None => return Ok(FuelCharge::untracked(self, instance_id, instance)),
+        };
+        
+        if tokens <= 0 {
+            state.metrics.throttled_calls += 1;
+            let wait = self.budget.window.mul_f64((1 - tokens) as f64 / share as f64);
+            return Err(Error::ResourceExhausted {
+                kind: ResourceKind::Fuel,
+                limit: share,
+                used: share.saturating_add(tokens.unsigned_abs()),
+                instance_id: Some(instance_id.as_uuid()),
+                suggestion: Some(format!(
+                    "The instance has used its share of the sandbox fuel budget; retry in {:?}",
+                    wait,
+                )),
+            });
+        }
+        
+        let before = instance.fuel_usage();
+        Ok(FuelCharge { ledger: self, instance_id, instance, before })
+    }
+    
+    /// Fuel left in an instance's bucket, negative while it is in debt
+    pub(crate) fn balance(&self, instance_id: InstanceId) -> Option<i64> {
+        let mut state = self.state.lock().unwrap();
+        let total_weight = state.total_weight;
+        let account = state.accounts.get_mut(&instance_id)?;
+        let share = self.share(account.weight, total_weight);
+        self.refill(account, share, Instant::now());
+        Some(account.tokens)
+    }
+    
+    /// Charge a top-up to an instance ahead of its use, if its bucket has fuel left
+    ///
+    /// The fuel is deducted from what the call is charged when it finishes.
+    pub(crate) fn prepay(&self, instance_id: InstanceId, fuel: u64) -> Option<bool> {
+        let mut state = self.state.lock().unwrap();
+        let total_weight = state.total_weight;
+        let account = state.accounts.get_mut(&instance_id)?;
+        let share = self.share(account.weight, total_weight);
+        self.refill(account, share, Instant::now());
+        if account.tokens <= 0 {
+            return Some(false);
+        }
+        account.tokens = account.tokens.saturating_sub(fuel.min(i64::MAX as u64) as i64);
+        account.prepaid = account.prepaid.saturating_add(fuel);
+        state.metrics.fuel_charged = state.metrics.fuel_charged.saturating_add(fuel);
+        Some(true)
+    }
+    
+    /// Counters for metered calls
+    pub(crate) fn metrics(&self) -> FuelBudgetMetrics {
+        self.state.lock().unwrap().metrics
+    }
+    
+    fn charge(&self, instance_id: InstanceId, mut fuel: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(account) = state.accounts.get_mut(&instance_id) {
+            let prepaid = account.prepaid.min(fuel);
+            account.prepaid -= prepaid;
+            fuel -= prepaid;
+            account.tokens = account.tokens.saturating_sub(fuel.min(i64::MAX as u64) as i64);
+        }
+        state.metrics.fuel_charged = state.metrics.fuel_charged.saturating_add(fuel);
+    }
+    
+    /// Take fuel per window out of the budget, if that leaves some for the instances
+    pub(crate) fn carve(&self, fuel: u64) -> bool {
+        self.carved.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |carved| {
+            carved.checked_add(fuel).filter(|carved| *carved < self.budget.fuel_per_window)
+        }).is_ok()
+    }
+    
+    /// Return fuel per window taken by [`FuelLedger::carve`]
+    pub(crate) fn release(&self, fuel: u64) {
+        self.carved.fetch_sub(fuel, Ordering::SeqCst);
+    }
+    
+    /// Fuel per window left to the instances
+    pub(crate) fn uncarved(&self) -> u64 {
+        self.budget.fuel_per_window - self.carved.load(Ordering::SeqCst)
+    }
+    
+    /// An instance's fuel per window given the weight of every instance
+    fn share(&self, weight: u32, total_weight: u64) -> u64 {
+        let share = self.uncarved() as u128 * weight as u128 / total_weight.max(1) as u128;
+        (share as u64).max(1)
+    }
+    
+    /// Add the fuel accrued since the last refill, up to one window's share
+    fn refill(&self, account: &mut Account, share: u64, now: Instant) {
+        let window = self.budget.window.as_nanos();
+        let elapsed = now.duration_since(account.refilled_at).as_nanos();
+        let accrued = share as u128 * elapsed / window;
+        if account.tokens as i128 + accrued as i128 >= share as i128 {
+            account.tokens = share as i64;
+            account.refilled_at = now;
+        } else {
+            // Only spend the time the accrued fuel accounts for, so frequent refills don't lose fractions
+            account.tokens += accrued as i64;
+            let spent = (accrued * window / share as u128).min(u64::MAX as u128) as u64;
+            account.refilled_at += Duration::from_nanos(spent);
+        }
+    }
+}
+
+/// Bills a call's fuel to its instance when dropped
+pub(crate) struct FuelCharge<'a> {
+    ledger: &'a FuelLedger,
+    instance_id: InstanceId,
+    instance: Arc<dyn WasmInstance>,
+    before: Option<u64>,
+}
+
+impl<'a> FuelCharge<'a> {
+    fn untracked(ledger: &'a FuelLedger, instance_id: InstanceId, instance: Arc<dyn WasmInstance>) -> Self {
+        Self { ledger, instance_id, instance, before: None }
+    }
+}
+
+impl Drop for FuelCharge<'_> {
+    fn drop(&mut self) {
+        if let (Some(before), Some(after)) = (self.before, self.instance.fuel_usage()) {
+            self.ledger.charge(self.instance_id, after.saturating_sub(before));
+        }
+    }
+}
@@ -0,0 +1,364 @@
+//! Hooks observing guest memory and table growth
+//!
+//! Hooks run whenever a guest asks to grow a memory or table, before the growth
+//! happens. Any hook can veto the request, in which case the guest's
+//! `memory.grow` or `table.grow` returns -1 as if a static limit had been hit.
+//!
+//! The same observer tracks each call's memory high-watermark for its
+//! [`InvocationReport`], and with an [`OomPrediction`] configured, projects the
+//! call's growth rate forward to warn before the call runs out of memory.
+//! Diagnostics the guest reports during the call are collected into the same
+//! report.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::runtime::call_context::{CallContext, CallId};
+use crate::runtime::diagnostics::{CallDiagnostics, Diagnostic};
+use crate::runtime::{GrowthObserver, WasmInstance, WASM_PAGE_SIZE};
+use crate::InstanceId;
+
+/// Outcome of a growth hook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrowthDecision {
+    /// Let the growth proceed
+    Allow,
+    
+    /// Refuse the growth
+    Deny,
+}
+
+/// Callback for memory growth, given the instance and its size before and after in pages
+pub type MemoryGrowHook = Arc<dyn Fn(InstanceId, u64, u64) -> GrowthDecision + Send + Sync>;
+
+/// Callback for table growth, given the instance and its size before and after in elements
+pub type TableGrowHook = Arc<dyn Fn(InstanceId, u64, u64) -> GrowthDecision + Send + Sync>;
+
+/// Growth hooks registered on a sandbox
+#[derive(Default, Clone)]
+pub struct GrowthHooks {
+    memory: Vec<MemoryGrowHook>,
+    table: Vec<TableGrowHook>,
+}
+
+impl GrowthHooks {
+    /// Add a memory growth hook
+    pub fn add_memory_hook(&mut self, hook: MemoryGrowHook) {
+        self.memory.push(hook);
+    }
+    
+    /// Add a table growth hook
+    pub fn add_table_hook(&mut self, hook: TableGrowHook) {
+        self.table.push(hook);
+    }
+    
+    /// Check whether no hooks are registered
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_empty() && self.table.is_empty()
+    }
+    
+    /// Run every memory hook; growth is allowed unless one denies it
+    pub fn memory_growing(&self, instance_id: InstanceId, from_pages: u64, to_pages: u64) -> GrowthDecision {
+        decide(self.memory.iter().map(|hook| hook(instance_id, from_pages, to_pages)))
+    }
+    
+    /// Run every table hook; growth is allowed unless one denies it
+    pub fn table_growing(&self, instance_id: InstanceId, from: u64, to: u64) -> GrowthDecision {
+        decide(self.table.iter().map(|hook| hook(instance_id, from, to)))
+    }
+}
+
+impl std::fmt::Debug for GrowthHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GrowthHooks")
+            .field("memory", &self.memory.len())
+            .field("table", &self.table.len())
+            .finish()
+    }
+}
+
+/// Every hook runs, so observers see requests another hook denies
+fn decide(decisions: impl Iterator<Item = GrowthDecision>) -> GrowthDecision {
+    decisions.fold(GrowthDecision::Allow, |outcome, decision| {
+        if decision == GrowthDecision::Deny { decision } else { outcome }
+    })
+}
+
+/// How a call's memory growth is projected to predict it running out of memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OomPrediction {
+    /// How far ahead the call's growth rate so far is projected
+    pub horizon: Duration,
+    
+    /// Growth requests a call makes before its rate is projected
+    pub min_growths: u32,
+}
+
+impl Default for OomPrediction {
+    fn default() -> Self {
+        Self {
+            horizon: Duration::from_secs(1),
+            min_growths: 2,
+        }
+    }
+}
+
+impl OomPrediction {
+    /// Project growth `horizon` ahead
+    pub fn horizon(mut self, horizon: Duration) -> Self {
+        self.horizon = horizon;
+        self
+    }
+    
+    /// Wait for `min_growths` growth requests before projecting
+    pub fn min_growths(mut self, min_growths: u32) -> Self {
+        self.min_growths = min_growths;
+        self
+    }
+    
+    /// Pages a call will have after `horizon` if it keeps growing at its rate so far
+    fn project(&self, start_pages: u64, to_pages: u64, elapsed: Duration) -> u64 {
+        let grown = to_pages.saturating_sub(start_pages) as f64;
+        let rate = grown / elapsed.as_secs_f64().max(f64::EPSILON);
+        to_pages.saturating_add((rate * self.horizon.as_secs_f64()).ceil() as u64)
+    }
+}
+
+/// A call projected to outgrow its instance's memory limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OomWarning {
+    /// Instance running the call
+    pub instance_id: InstanceId,
+    
+    /// ID of the call
+    pub call_id: CallId,
+    
+    /// Export being called
+    pub function_name: String,
+    
+    /// Pages the memory is growing to
+    pub current_pages: u64,
+    
+    /// Pages projected at the end of the prediction horizon
+    pub projected_pages: u64,
+    
+    /// The instance's memory limit in pages
+    pub limit_pages: u64,
+}
+
+/// Callback for predicted out-of-memory conditions, run during the growth that triggered it
+pub type OomWarningHandler = Arc<dyn Fn(&OomWarning) -> GrowthDecision + Send + Sync>;
+
+/// Memory use and diagnostics of one call, reported once it returns
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvocationReport {
+    /// ID of the call
+    pub call_id: CallId,
+    
+    /// Export that was called
+    pub function_name: String,
+    
+    /// How long the call took
+    pub duration: Duration,
+    
+    /// Pages allocated when the call started
+    pub start_pages: u64,
+    
+    /// Most pages allocated during the call
+    pub peak_pages: u64,
+    
+    /// Pages allocated when the call returned
+    pub end_pages: u64,
+    
+    /// Whether the call was predicted to run out of memory
+    pub oom_predicted: bool,
+    
+    /// Diagnostics the guest attached to the result
+    pub diagnostics: CallDiagnostics,
+}
+
+/// A call whose memory growth is being watched
+struct WatchedCall {
+    context: CallContext,
+    started: Instant,
+    start_pages: u64,
+    peak_pages: u64,
+    growths: u32,
+    limit_pages: u64,
+    prediction: Option<OomPrediction>,
+    oom_predicted: bool,
+    diagnostics: CallDiagnostics,
+}
+
+/// Memory high-watermarks and out-of-memory predictions for the calls instances run
+#[derive(Default)]
+pub(crate) struct MemoryWatch {
+    calls: Mutex<HashMap<InstanceId, WatchedCall>>,
+    reports: Mutex<HashMap<InstanceId, InvocationReport>>,
+    handlers: RwLock<Vec<OomWarningHandler>>,
+}
+
+impl MemoryWatch {
+    /// Add a callback for predicted out-of-memory conditions
+    pub(crate) fn add_handler(&self, handler: OomWarningHandler) {
+        self.handlers.write().unwrap().push(handler);
+    }
+    
+    /// Watch a call into `instance` until the returned guard drops
+    pub(crate) fn watch(
+        &self,
+        context: &CallContext,
+        instance: Arc<dyn WasmInstance>,
+        limit_pages: u64,
+        prediction: Option<OomPrediction>,
+    ) -> WatchGuard<'_> {
+        let start_pages = instance.memory_pages().current;
+        self.calls.lock().unwrap().insert(context.instance_id, WatchedCall {
+            context: context.clone(),
+            started: Instant::now(),
+            start_pages,
+            peak_pages: start_pages,
+            growths: 0,
+            limit_pages,
+            prediction,
+            oom_predicted: false,
+            diagnostics: CallDiagnostics::default(),
+        });
+        WatchGuard { watch: self, instance_id: context.instance_id, instance }
+    }
+    
+    /// Report of the latest call into an instance
+    pub(crate) fn report(&self, instance_id: InstanceId) -> Option<InvocationReport> {
+        self.reports.lock().unwrap().get(&instance_id).cloned()
+    }
+    
+    /// Attach a diagnostic to the call running in an instance; dropped outside a watched call
+    pub(crate) fn diagnostic(&self, instance_id: InstanceId, diagnostic: Diagnostic) {
+        if let Some(call) = self.calls.lock().unwrap().get_mut(&instance_id) {
+            call.diagnostics.push(diagnostic);
+        }
+    }
+    
+    /// Drop everything kept for an instance
+    pub(crate) fn forget(&self, instance_id: InstanceId) {
+        self.calls.lock().unwrap().remove(&instance_id);
+        self.reports.lock().unwrap().remove(&instance_id);
+    }
+    
+    /// Record a call's memory growing to `to_pages`, warning if it is projected to run out
+    fn memory_growing(&self, instance_id: InstanceId, to_pages: u64) -> GrowthDecision {
+        let warning = {
+            let mut calls = self.calls.lock().unwrap();
+            let Some(call) = calls.get_mut(&instance_id) else {
+                return GrowthDecision::Allow;
+            };
+            call.growths += 1;
+            match call.prediction {
+                Some(prediction) if !call.oom_predicted && call.growths >= prediction.min_growths => {
+                    let projected_pages = prediction.project(call.start_pages, to_pages, call.started.elapsed());
+                    (projected_pages > call.limit_pages).then(|| {
+                        call.oom_predicted = true;
+                        OomWarning {
+                            instance_id,
+                            call_id: call.context.call_id,
+                            function_name: call.context.function_name.clone(),
+                            current_pages: to_pages,
+                            projected_pages,
+                            limit_pages: call.limit_pages,
+                        }
+                    })
+                }
+                _ => None,
+            }
+        };
+        
+        // Handlers run unlocked so they can inspect the sandbox
+        let decision = match warning {
+            Some(warning) => {
+                log::warn!(
+                    "Instance {}: {} is projected to grow to {} pages, over its limit of {}",
+                    instance_id, warning.function_name, warning.projected_pages, warning.limit_pages,
+                );
+                let handlers = self.handlers.read().unwrap().clone();
+                decide(handlers.iter().map(|handler| handler(&warning)))
+            }
+            None => GrowthDecision::Allow,
+        };
+        if decision == GrowthDecision::Allow
+            && let Some(call) = self.calls.lock().unwrap().get_mut(&instance_id)
+        {
+            call.peak_pages = call.peak_pages.max(to_pages);
+        }
+        decision
+    }
+}
+
+/// Ends a watched call when dropped, recording its [`InvocationReport`]
+pub(crate) struct WatchGuard<'a> {
+    watch: &'a MemoryWatch,
+    instance_id: InstanceId,
+    instance: Arc<dyn WasmInstance>,
+}
+
+impl Drop for WatchGuard<'_> {
+    fn drop(&mut self) {
+        let Some(call) = self.watch.calls.lock().unwrap().remove(&self.instance_id) else {
+            return;
+        };
+        let end_pages = self.instance.memory_pages().current;
+        self.watch.reports.lock().unwrap().insert(self.instance_id, InvocationReport {
+            call_id: call.context.call_id,
+            function_name: call.context.function_name,
+            duration: call.started.elapsed(),
+            start_pages: call.start_pages,
+            peak_pages: call.peak_pages.max(end_pages),
+            end_pages,
+            oom_predicted: call.oom_predicted,
+            diagnostics: call.diagnostics,
+        });
+    }
+}
+
+/// Observer forwarding one instance's growth requests to shared hooks
+pub struct HookedGrowthObserver {
+    instance_id: InstanceId,
+    hooks: Arc<RwLock<GrowthHooks>>,
+    watch: Option<Arc<MemoryWatch>>,
+}
+
+impl HookedGrowthObserver {
+    /// Create an observer for an instance
+    pub fn new(instance_id: InstanceId, hooks: Arc<RwLock<GrowthHooks>>) -> Self {
+        Self { instance_id, hooks, watch: None }
+    }
+    
+    /// Also track the instance's calls in `watch`
+    pub(crate) fn watched(mut self, watch: Arc<MemoryWatch>) -> Self {
+        self.watch = Some(watch);
+        self
+    }
+}
+
+impl GrowthObserver for HookedGrowthObserver {
+    fn memory_growing(&self, current_bytes: usize, desired_bytes: usize) -> bool {
+        let from_pages = (current_bytes / WASM_PAGE_SIZE) as u64;
+        let to_pages = (desired_bytes / WASM_PAGE_SIZE) as u64;
+        let mut decision = self.hooks.read().unwrap().memory_growing(self.instance_id, from_pages, to_pages);
+        if let (GrowthDecision::Allow, Some(watch)) = (decision, &self.watch) {
+            decision = watch.memory_growing(self.instance_id, to_pages);
+        }
+        if decision == GrowthDecision::Deny {
+            log::debug!("Instance {}: memory growth from {} to {} pages denied", self.instance_id, from_pages, to_pages);
+        }
+        decision == GrowthDecision::Allow
+    }
+    
+    fn table_growing(&self, current: usize, desired: usize) -> bool {
+        let decision = self.hooks.read().unwrap().table_growing(self.instance_id, current as u64, desired as u64);
+        if decision == GrowthDecision::Deny {
+            log::debug!("Instance {}: table growth from {} to {} elements denied", self.instance_id, current, desired);
+        }
+        decision == GrowthDecision::Allow
+    }
+}
@@ -0,0 +1,163 @@
+//! Structured logging for guests
+//!
+//! Guests log through [`crate::runtime::LOG_IMPORT_MODULE`] instead of writing
+//! to stdout. Each record is tagged with the instance and, when written during
+//! a call, the call's [`CallId`], then forwarded to the `log` crate under
+//! [`GUEST_LOG_TARGET`] and kept in a bounded in-memory buffer the host can
+//! query by call.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::runtime::call_context::{current_call, CallId};
+use crate::runtime::diagnostics::Diagnostic;
+use crate::runtime::growth::MemoryWatch;
+use crate::InstanceId;
+
+/// `log` target guest records are forwarded under
+pub const GUEST_LOG_TARGET: &str = "wasm_sandbox::guest";
+
+/// Default number of records kept in memory
+pub const DEFAULT_MAX_GUEST_LOG_RECORDS: usize = 1000;
+
+/// One line a guest logged
+#[derive(Debug, Clone)]
+pub struct GuestLogRecord {
+    /// When the line was logged
+    pub timestamp: SystemTime,
+    
+    /// Severity the guest gave it
+    pub level: log::Level,
+    
+    /// Instance that logged it
+    pub instance_id: InstanceId,
+    
+    /// Call it was logged during, if any
+    pub call_id: Option<CallId>,
+    
+    /// Export being called when it was logged
+    pub function_name: Option<String>,
+    
+    /// The line itself
+    pub message: String,
+}
+
+/// Destination for one instance's log lines
+pub trait GuestLogSink: Send + Sync {
+    /// Record a line the guest logged
+    fn write(&self, level: log::Level, message: &str);
+    
+    /// Attach a diagnostic to the result of the running call
+    fn diagnostic(&self, diagnostic: Diagnostic) {
+        let _ = diagnostic;
+    }
+}
+
+/// Recent guest log records of every instance in a sandbox
+#[derive(Debug)]
+pub struct GuestLog {
+    records: Mutex<VecDeque<GuestLogRecord>>,
+    max_records: usize,
+}
+
+impl GuestLog {
+    /// Create a log keeping the latest `max_records` records
+    pub fn new(max_records: usize) -> Self {
+        Self {
+            records: Mutex::new(VecDeque::new()),
+            max_records,
+        }
+    }
+    
+    /// Record a line for an instance, tagged with the current call
+    pub fn record(&self, instance_id: InstanceId, level: log::Level, message: &str) {
+        let call = current_call();
+        let record = GuestLogRecord {
+            timestamp: SystemTime::now(),
+            level,
+            instance_id,
+            call_id: call.as_ref().map(|call| call.call_id),
+            function_name: call.map(|call| call.function_name),
+            message: message.to_string(),
+        };
+        match record.call_id {
+            Some(call_id) => log::log!(target: GUEST_LOG_TARGET, level, "[call {}] [instance {}] {}", call_id, instance_id, message),
+            None => log::log!(target: GUEST_LOG_TARGET, level, "[instance {}] {}", instance_id, message),
+        }
+        
+        let mut records = self.records.lock().unwrap();
+        records.push_back(record);
+        while records.len() > self.max_records {
+            records.pop_front();
+        }
+    }
+    
+    /// Records still in memory, oldest first
+    pub fn records(&self) -> Vec<GuestLogRecord> {
+        self.records.lock().unwrap().iter().cloned().collect()
+    }
+    
+    /// Records logged during one call, oldest first
+    pub fn records_for_call(&self, call_id: CallId) -> Vec<GuestLogRecord> {
+        self.records.lock().unwrap().iter()
+            .filter(|record| record.call_id == Some(call_id))
+            .cloned()
+            .collect()
+    }
+    
+    /// Drop every record
+    pub fn clear(&self) {
+        self.records.lock().unwrap().clear();
+    }
+}
+
+impl Default for GuestLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_GUEST_LOG_RECORDS)
+    }
+}
+
+/// A sandbox's guest log as seen by one instance
+pub(crate) struct InstanceGuestLog {
+    log: Arc<GuestLog>,
+    instance_id: InstanceId,
+    watch: Option<Arc<MemoryWatch>>,
+}
+
+impl InstanceGuestLog {
+    /// Log on behalf of `instance_id`
+    pub(crate) fn new(log: Arc<GuestLog>, instance_id: InstanceId) -> Self {
+        Self { log, instance_id, watch: None }
+    }
+    
+    /// Attach diagnostics to the reports of the calls `watch` tracks
+    pub(crate) fn watched(mut self, watch: Arc<MemoryWatch>) -> Self {
+        self.watch = Some(watch);
+        self
+    }
+}
+
+impl GuestLogSink for InstanceGuestLog {
+    fn write(&self, level: log::Level, message: &str) {
+        self.log.record(self.instance_id, level, message);
+    }
+    
+    fn diagnostic(&self, diagnostic: Diagnostic) {
+        if let Some(watch) = &self.watch {
+            watch.diagnostic(self.instance_id, diagnostic);
+        }
+    }
+}
+
+/// Severity for a guest's level number: 1 for error through 5 for trace
+pub(crate) fn level_from_guest(level: i32) -> Option<log::Level> {
+    match level {
+        1 => Some(log::Level::Error),
+        2 => Some(log::Level::Warn),
+        3 => Some(log::Level::Info),
+        4 => Some(log::Level::Debug),
+        5 => Some(log::Level::Trace),
+        _ => None,
+    }
+}
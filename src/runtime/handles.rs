@@ -0,0 +1,149 @@
+//! Opaque handles to host objects
+//!
+//! Host functions hand guests references to host objects (database
+//! connections, open files) as small integer handles rather than pointers.
+//! Each instance has its own [`HandleTable`]: a handle only means something to
+//! the instance it was issued to, looking one up checks the object's type, the
+//! number of live handles is capped, and every object still in the table is
+//! dropped with the instance.
+
+use std::any::{type_name, Any};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use crate::error::{Error, Result};
+
+/// Default cap on the handles an instance may hold
+pub const DEFAULT_MAX_HANDLES: usize = 256;
+
+/// Identifier of a host object as seen by a guest
+pub type Handle = u32;
+
+struct Entry {
+    type_name: &'static str,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+struct TableState {
+    entries: BTreeMap<Handle, Entry>,
+    next: Handle,
+}
+
+/// Host objects an instance holds handles to
+pub struct HandleTable {
+    state: Mutex<TableState>,
+    max_handles: usize,
+}
+
+impl HandleTable {
+    /// Create an empty table holding at most `max_handles` objects
+    pub fn new(max_handles: usize) -> Self {
+        Self {
+            state: Mutex::new(TableState {
+                entries: BTreeMap::new(),
+                next: 1,
+            }),
+            max_handles,
+        }
+    }
+    
+    /// Store an object and return the handle to give the guest
+    ///
+    /// Handles start at 1 and are not reused, so a guest holding a closed
+    /// handle gets an error rather than someone else's object.
+    pub fn insert<T: Any + Send + Sync>(&self, value: T) -> Result<Handle> {
+        let mut state = self.state.lock().unwrap();
+        if state.entries.len() >= self.max_handles {
+            return Err(Error::ResourceLimit {
+                message: format!("Instance already holds {} handles", state.entries.len()),
+            });
+        }
+        
+        let handle = state.next;
+        state.next = state.next.checked_add(1).ok_or_else(|| Error::ResourceLimit {
+            message: "Instance has used up its handle identifiers".to_string(),
+        })?;
+        state.entries.insert(handle, Entry {
+            type_name: type_name::<T>(),
+            value: Arc::new(value),
+        });
+        Ok(handle)
+    }
+    
+    /// Look up the object behind a handle, which must be a `T`
+    pub fn get<T: Any + Send + Sync>(&self, handle: Handle) -> Result<Arc<T>> {
+        let state = self.state.lock().unwrap();
+        let entry = state.entries.get(&handle).ok_or_else(|| not_found(handle))?;
+        entry.value.clone().downcast::<T>().map_err(|_| wrong_type::<T>(handle, entry.type_name))
+    }
+    
+    /// Close a handle, returning its object, which must be a `T`
+    ///
+    /// The handle is left open if it refers to another type.
+    pub fn remove<T: Any + Send + Sync>(&self, handle: Handle) -> Result<Arc<T>> {
+        let mut state = self.state.lock().unwrap();
+        let entry = state.entries.get(&handle).ok_or_else(|| not_found(handle))?;
+        if !entry.value.is::<T>() {
+            return Err(wrong_type::<T>(handle, entry.type_name));
+        }
+        let entry = state.entries.remove(&handle).expect("handle is open");
+        Ok(entry.value.downcast::<T>().expect("type was checked"))
+    }
+    
+    /// Close a handle whatever it refers to, returning whether it was open
+    pub fn close(&self, handle: Handle) -> bool {
+        self.state.lock().unwrap().entries.remove(&handle).is_some()
+    }
+    
+    /// Close every handle
+    pub fn clear(&self) {
+        // Drop the objects after releasing the lock in case they use the table
+        let entries = std::mem::take(&mut self.state.lock().unwrap().entries);
+        drop(entries);
+    }
+    
+    /// Type name of the object behind a handle
+    pub fn type_of(&self, handle: Handle) -> Option<&'static str> {
+        self.state.lock().unwrap().entries.get(&handle).map(|entry| entry.type_name)
+    }
+    
+    /// Number of open handles
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+    
+    /// Whether no handles are open
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for HandleTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_HANDLES)
+    }
+}
+
+impl std::fmt::Debug for HandleTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HandleTable")
+            .field("open", &self.len())
+            .field("max_handles", &self.max_handles)
+            .finish()
+    }
+}
+
+fn not_found(handle: Handle) -> Error {
+    Error::NotFound {
+        resource_type: "handle".to_string(),
+        identifier: handle.to_string(),
+    }
+}
+
+fn wrong_type<T>(handle: Handle, actual: &str) -> Error {
+    Error::InvalidInput {
+        field: "handle".to_string(),
+        reason: format!("Handle {} refers to a {}, not a {}", handle, actual, type_name::<T>()),
+        suggestion: None,
+    }
+}
@@ -0,0 +1,151 @@
+//! Hibernating idle instances to disk
+//!
+//! An instance that has gone unused for its `max_idle_time_ms` can be
+//! hibernated: its linear memory, exported mutable globals and fuel are
+//! written to a file and the runtime instance is dropped, freeing its memory.
+//! The next call through the sandbox recreates the instance from its module and
+//! restores that state before running, so callers don't notice beyond the
+//! latency. Tables and unexported globals start over on the recreated instance.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::runtime::{HostValue, WasmFunctionCaller, WasmInstance, WasmInstanceState};
+
+/// Where and whether idle instances are hibernated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HibernationConfig {
+    /// Directory hibernated instances' state is written to
+    pub dir: PathBuf,
+}
+
+impl HibernationConfig {
+    /// Hibernate idle instances to files in `dir`
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+/// Hibernation counters for a sandbox
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HibernationMetrics {
+    /// Instances hibernated
+    pub hibernations: u64,
+    
+    /// Hibernated instances woken by a call
+    pub restores: u64,
+    
+    /// Hibernations or restores that failed; the instance is left as it was
+    pub failures: u64,
+    
+    /// Linear memory freed by hibernations, in bytes
+    pub bytes_freed: u64,
+    
+    /// Time spent hibernating instances
+    pub hibernate_time: Duration,
+    
+    /// Time spent restoring instances
+    pub restore_time: Duration,
+    
+    /// Longest time a call waited for its instance to be restored
+    pub max_restore_latency: Duration,
+}
+
+/// Stands in for a hibernated instance until it is restored
+///
+/// Anything reaching the instance without going through the sandbox, such as
+/// a direct [`WasmInstance`] call, gets an error rather than waking it.
+pub(crate) struct HibernatedInstance;
+
+fn hibernated() -> Error {
+    Error::Instance {
+        operation: "call".to_string(),
+        instance_id: None,
+        reason: "Instance is hibernated; call it through the sandbox to restore it".to_string(),
+    }
+}
+
+impl WasmInstance for HibernatedInstance {
+    fn state(&self) -> WasmInstanceState {
+        WasmInstanceState::Paused
+    }
+    
+    fn memory_usage(&self) -> u64 {
+        0
+    }
+    
+    fn fuel_usage(&self) -> Option<u64> {
+        None
+    }
+    
+    fn reset_fuel(&self) -> Result<()> {
+        Err(hibernated())
+    }
+    
+    fn add_fuel(&self, _fuel: u64) -> Result<()> {
+        Err(hibernated())
+    }
+    
+    unsafe fn memory_ptr(&self) -> Result<*mut u8> {
+        Err(hibernated())
+    }
+    
+    fn visit_memory(&self, _f: &mut dyn FnMut(&[u8])) -> Result<()> {
+        Err(hibernated())
+    }
+    
+    fn visit_memory_mut(&self, _f: &mut dyn FnMut(&mut [u8])) -> Result<()> {
+        Err(hibernated())
+    }
+    
+    fn memory_size(&self) -> usize {
+        0
+    }
+    
+    fn function_caller(&self) -> Box<dyn WasmFunctionCaller> {
+        Box::new(HibernatedInstance)
+    }
+    
+    fn call_simple_function(&self, _function_name: &str, _params: &[i32]) -> Result<i32> {
+        Err(hibernated())
+    }
+    
+    fn call_raw(&self, _function_name: &str, _input: &[u8]) -> Result<Vec<u8>> {
+        Err(hibernated())
+    }
+    
+    fn read_memory(&self) -> Result<Vec<u8>> {
+        Err(hibernated())
+    }
+    
+    fn call_raw_region(&self, _function_name: &str, _input: &[u8]) -> Result<(usize, usize)> {
+        Err(hibernated())
+    }
+    
+    fn write_input(&self, _function_name: &str, _len: usize, _write: &mut dyn FnMut(&mut dyn std::io::Write) -> Result<()>) -> Result<(usize, usize)> {
+        Err(hibernated())
+    }
+    
+    fn call_raw_region_at(&self, _function_name: &str, _input: (usize, usize)) -> Result<(usize, usize)> {
+        Err(hibernated())
+    }
+    
+    fn call_values(&self, _function_name: &str, _args: &[HostValue]) -> Result<Vec<HostValue>> {
+        Err(hibernated())
+    }
+}
+
+impl WasmFunctionCaller for HibernatedInstance {
+    fn call_function_json(&self, _function_name: &str, _params_json: &str) -> Result<String> {
+        Err(hibernated())
+    }
+    
+    fn call_function_msgpack(&self, _function_name: &str, _params_msgpack: &[u8]) -> Result<Vec<u8>> {
+        Err(hibernated())
+    }
+    
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,438 @@
+//! Namespaced host functions
+//!
+//! Host functions are registered under dotted names such as `host.fs.read` or
+//! `acme.billing.charge`: everything before the last dot is the namespace, which
+//! guests import as the module name, and the last segment is the function.
+//! Namespaces are registered in bulk with a [`HostNamespace`], and registering a
+//! name another provider already owns is an error rather than a silent override.
+//!
+//! Unless a namespace is registered as ungated, an instance may only call its
+//! functions if its capabilities grant the namespace through the
+//! [`HOST_NAMESPACE_CAPABILITY`] custom capability.
+//!
+//! Functions registered with [`HostNamespace::function_with_context`] also see
+//! the calling instance's [`HandleTable`], through which they can hand the
+//! guest opaque handles to host objects. Functions registered with
+//! [`HostNamespace::async_function`] return a future, which the runtime awaits
+//! while the calling guest is suspended.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+
+use crate::error::{Error, Result, SecurityContext};
+use crate::runtime::handles::HandleTable;
+use crate::runtime::{GuestCall, HostFunctions, HostValue};
+use crate::security::capabilities::ActiveCapabilities;
+use crate::security::{Capabilities, CustomCapability, EnforcementMode};
+use crate::InstanceId;
+
+/// Custom capability listing the host namespaces an instance may call
+///
+/// The value is a [`CustomCapability::StringList`] of namespaces (`acme.billing`),
+/// namespace prefixes (`acme.*`, matching `acme` and everything below it), or `*`.
+pub const HOST_NAMESPACE_CAPABILITY: &str = "host.namespaces";
+
+/// Capability domain used for enforcement modes
+const HOST_DOMAIN: &str = "host";
+
+/// Handler for a host function
+pub type HostHandler = Arc<dyn Fn(&HostContext<'_>, &[HostValue]) -> Result<Vec<HostValue>> + Send + Sync>;
+
+/// Handler for an async host function
+pub type AsyncHostHandler = Arc<dyn Fn(Vec<HostValue>) -> GuestCall<'static, Vec<HostValue>> + Send + Sync>;
+
+/// A registered function's handler, sync or async
+#[derive(Clone)]
+enum Handler {
+    Sync(HostHandler),
+    Async(AsyncHostHandler),
+}
+
+/// The instance a host function is called by
+pub struct HostContext<'a> {
+    /// Calling instance
+    pub instance_id: InstanceId,
+    
+    /// Host objects the instance holds handles to
+    pub handles: &'a HandleTable,
+    
+    /// Capabilities in effect for the call, including runtime grants
+    pub capabilities: &'a Capabilities,
+}
+
+/// A namespace of host functions registered together
+pub struct HostNamespace {
+    name: String,
+    provider: Option<String>,
+    gated: bool,
+    functions: Vec<(String, Handler)>,
+}
+
+impl HostNamespace {
+    /// Create an empty namespace, e.g. `acme.billing`
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            provider: None,
+            gated: true,
+            functions: Vec::new(),
+        }
+    }
+    
+    /// Name the provider in conflict errors (defaults to the namespace)
+    pub fn provider(mut self, provider: &str) -> Self {
+        self.provider = Some(provider.to_string());
+        self
+    }
+    
+    /// Let every instance call the namespace without a grant
+    pub fn ungated(mut self) -> Self {
+        self.gated = false;
+        self
+    }
+    
+    /// Add a function
+    pub fn function<F>(mut self, name: &str, handler: F) -> Self
+    where
+        F: Fn(&[HostValue]) -> Result<Vec<HostValue>> + Send + Sync + 'static,
+    {
+        let handler = Arc::new(move |_: &HostContext<'_>, args: &[HostValue]| handler(args));
+        self.functions.push((name.to_string(), Handler::Sync(handler)));
+        self
+    }
+    
+    /// Add a function that needs to know its caller, e.g. to issue or look up handles
+    pub fn function_with_context<F>(mut self, name: &str, handler: F) -> Self
+    where
+        F: Fn(&HostContext<'_>, &[HostValue]) -> Result<Vec<HostValue>> + Send + Sync + 'static,
+    {
+        self.functions.push((name.to_string(), Handler::Sync(Arc::new(handler))));
+        self
+    }
+    
+    /// Add a function that returns a future, e.g. to call another service
+    ///
+    /// The guest stays suspended until the future resolves. Runtimes without
+    /// async support block on it instead.
+    pub fn async_function<F, Fut>(mut self, name: &str, handler: F) -> Self
+    where
+        F: Fn(Vec<HostValue>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Vec<HostValue>>> + Send + 'static,
+    {
+        let handler = Arc::new(move |args: Vec<HostValue>| -> GuestCall<'static, Vec<HostValue>> { Box::pin(handler(args)) });
+        self.functions.push((name.to_string(), Handler::Async(handler)));
+        self
+    }
+    
+    /// The namespace's name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    
+    fn provider_name(&self) -> &str {
+        self.provider.as_deref().unwrap_or(&self.name)
+    }
+}
+
+struct RegisteredFunction {
+    provider: String,
+    handler: Handler,
+}
+
+struct RegisteredNamespace {
+    gated: bool,
+    provider: String,
+    functions: BTreeMap<String, RegisteredFunction>,
+}
+
+/// Host functions available to a sandbox's instances, by namespace
+#[derive(Default)]
+pub struct HostFunctionRegistry {
+    namespaces: RwLock<BTreeMap<String, RegisteredNamespace>>,
+}
+
+impl HostFunctionRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Register a namespace's functions
+    ///
+    /// Nothing is registered if any function conflicts with one already registered.
+    pub fn register(&self, namespace: HostNamespace) -> Result<()> {
+        self.register_all(std::iter::once(namespace))
+    }
+    
+    /// Register several namespaces, all or none
+    pub fn register_all(&self, namespaces: impl IntoIterator<Item = HostNamespace>) -> Result<()> {
+        let namespaces: Vec<HostNamespace> = namespaces.into_iter().collect();
+        let mut registered = self.namespaces.write().unwrap();
+        
+        // Check everything first so a conflict leaves the registry untouched
+        let mut claimed: BTreeMap<(&str, &str), &str> = BTreeMap::new();
+        let mut gates: BTreeMap<&str, (bool, &str)> = BTreeMap::new();
+        for namespace in &namespaces {
+            validate_namespace(&namespace.name)?;
+            let provider = namespace.provider_name();
+            
+            let existing_gate = registered.get(&namespace.name)
+                .map(|existing| (existing.gated, existing.provider.as_str()))
+                .or_else(|| gates.get(namespace.name.as_str()).copied());
+            if let Some((gated, owner)) = existing_gate
+                && gated != namespace.gated
+            {
+                return Err(Error::config_error(
+                    format!(
+                        "Provider {} registers namespace {} {}, but provider {} registered it {}",
+                        provider, namespace.name, gate_name(namespace.gated), owner, gate_name(gated),
+                    ),
+                    Some("Register every part of a namespace with the same gating".to_string()),
+                ));
+            }
+            gates.insert(&namespace.name, (namespace.gated, provider));
+            
+            for (function, _) in &namespace.functions {
+                validate_function(&namespace.name, function)?;
+                let owner = registered.get(&namespace.name)
+                    .and_then(|existing| existing.functions.get(function))
+                    .map(|existing| existing.provider.as_str())
+                    .or_else(|| claimed.get(&(namespace.name.as_str(), function.as_str())).copied());
+                if let Some(owner) = owner {
+                    return Err(Error::config_error(
+                        format!(
+                            "Host function {}.{} from provider {} is already registered by provider {}",
+                            namespace.name, function, provider, owner,
+                        ),
+                        Some("Unregister the existing provider or use another namespace".to_string()),
+                    ));
+                }
+                claimed.insert((&namespace.name, function), provider);
+            }
+        }
+        
+        for namespace in namespaces {
+            let provider = namespace.provider_name().to_string();
+            let entry = registered.entry(namespace.name).or_insert_with(|| RegisteredNamespace {
+                gated: namespace.gated,
+                provider: provider.clone(),
+                functions: BTreeMap::new(),
+            });
+            for (function, handler) in namespace.functions {
+                entry.functions.insert(function, RegisteredFunction {
+                    provider: provider.clone(),
+                    handler,
+                });
+            }
+        }
+        Ok(())
+    }
+    
+    /// Remove every function a provider registered, returning how many there were
+    pub fn unregister_provider(&self, provider: &str) -> usize {
+        let mut registered = self.namespaces.write().unwrap();
+        let mut removed = 0;
+        for namespace in registered.values_mut() {
+            let before = namespace.functions.len();
+            namespace.functions.retain(|_, function| function.provider != provider);
+            removed += before - namespace.functions.len();
+        }
+        registered.retain(|_, namespace| !namespace.functions.is_empty());
+        removed
+    }
+    
+    /// Fully qualified names of the registered functions, grouped by namespace
+    pub fn functions(&self) -> Vec<String> {
+        self.namespaces.read().unwrap().iter()
+            .flat_map(|(namespace, registered)| {
+                registered.functions.keys().map(move |function| format!("{}.{}", namespace, function))
+            })
+            .collect()
+    }
+    
+    /// Provider that registered a function, by fully qualified name
+    pub fn provider_of(&self, qualified_name: &str) -> Option<String> {
+        let (namespace, function) = qualified_name.rsplit_once('.')?;
+        self.namespaces.read().unwrap()
+            .get(namespace)?
+            .functions.get(function)
+            .map(|registered| registered.provider.clone())
+    }
+    
+    /// Whether no functions are registered
+    pub fn is_empty(&self) -> bool {
+        self.namespaces.read().unwrap().is_empty()
+    }
+    
+    /// Look up a function and check that `capabilities` may call it
+    fn resolve(&self, instance_id: InstanceId, capabilities: &Capabilities, namespace: &str, function: &str) -> Result<Handler> {
+        let (gated, handler) = {
+            let registered = self.namespaces.read().unwrap();
+            let entry = registered.get(namespace);
+            let handler = entry.and_then(|entry| entry.functions.get(function))
+                .ok_or_else(|| Error::NotFound {
+                    resource_type: "host function".to_string(),
+                    identifier: format!("{}.{}", namespace, function),
+                })?
+                .handler.clone();
+            (entry.is_some_and(|entry| entry.gated), handler)
+        };
+        
+        if gated && !is_granted(capabilities, namespace) {
+            if capabilities.enforcement.mode_for(HOST_DOMAIN) == EnforcementMode::Enforce {
+                return Err(Error::SecurityViolation {
+                    violation: format!("Instance is not granted host namespace {}", namespace),
+                    instance_id: Some(instance_id.as_uuid()),
+                    context: SecurityContext {
+                        attempted_operation: format!("call {}.{}", namespace, function),
+                        required_capability: HOST_NAMESPACE_CAPABILITY.to_string(),
+                        available_capabilities: granted_namespaces(capabilities),
+                    },
+                });
+            }
+            log::warn!("Instance {} called {}.{} without a grant for its namespace", instance_id, namespace, function);
+        }
+        Ok(handler)
+    }
+}
+
+impl std::fmt::Debug for HostFunctionRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostFunctionRegistry")
+            .field("functions", &self.functions())
+            .finish()
+    }
+}
+
+/// One instance's view of a [`HostFunctionRegistry`], checked against its capabilities
+pub struct InstanceHostFunctions {
+    registry: Arc<HostFunctionRegistry>,
+    instance_id: InstanceId,
+    capabilities: ActiveCapabilities,
+    handles: Arc<HandleTable>,
+}
+
+impl InstanceHostFunctions {
+    /// Resolve an instance's imports through `registry`
+    pub fn new(
+        registry: Arc<HostFunctionRegistry>,
+        instance_id: InstanceId,
+        capabilities: ActiveCapabilities,
+        handles: Arc<HandleTable>,
+    ) -> Self {
+        Self {
+            registry,
+            instance_id,
+            capabilities,
+            handles,
+        }
+    }
+}
+
+impl InstanceHostFunctions {
+    fn resolve(&self, module: &str, name: &str) -> Result<Handler> {
+        self.registry.resolve(self.instance_id, &self.capabilities.current(), module, name)
+    }
+    
+    fn call_sync(&self, handler: &HostHandler, args: &[HostValue]) -> Result<Vec<HostValue>> {
+        let capabilities = self.capabilities.current();
+        let context = HostContext {
+            instance_id: self.instance_id,
+            handles: &self.handles,
+            capabilities: &capabilities,
+        };
+        handler(&context, args)
+    }
+}
+
+impl HostFunctions for InstanceHostFunctions {
+    fn provides(&self, module: &str, name: &str) -> bool {
+        self.registry.namespaces.read().unwrap()
+            .get(module)
+            .is_some_and(|namespace| namespace.functions.contains_key(name))
+    }
+    
+    fn call(&self, module: &str, name: &str, args: &[HostValue]) -> Result<Vec<HostValue>> {
+        match self.resolve(module, name)? {
+            Handler::Sync(handler) => self.call_sync(&handler, args),
+            Handler::Async(handler) => futures::executor::block_on(handler(args.to_vec())),
+        }
+    }
+    
+    fn call_async<'a>(&'a self, module: &'a str, name: &'a str, args: &'a [HostValue]) -> GuestCall<'a, Vec<HostValue>> {
+        Box::pin(async move {
+            match self.resolve(module, name)? {
+                Handler::Sync(handler) => self.call_sync(&handler, args),
+                Handler::Async(handler) => handler(args.to_vec()).await,
+            }
+        })
+    }
+}
+
+fn gate_name(gated: bool) -> &'static str {
+    if gated { "gated" } else { "ungated" }
+}
+
+/// Check a namespace name, rejecting ones the runtime itself provides
+fn validate_namespace(namespace: &str) -> Result<()> {
+    let valid = namespace.split('.').all(|segment| {
+        !segment.is_empty() && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    });
+    if !valid {
+        return Err(Error::InvalidInput {
+            field: "namespace".to_string(),
+            reason: format!("'{}' is not a dotted name", namespace),
+            suggestion: Some("Use names like acme.billing".to_string()),
+        });
+    }
+    
+    let reserved = [
+        "env",
+        super::STREAM_IMPORT_MODULE,
+        super::SERVICE_IMPORT_MODULE,
+        super::CONFIG_IMPORT_MODULE,
+        super::SECRETS_IMPORT_MODULE,
+        super::DNS_IMPORT_MODULE,
+        super::HTTP_IMPORT_MODULE,
+        super::TIMER_IMPORT_MODULE,
+        super::ALLOC_IMPORT_MODULE,
+        super::stdlib::STDLIB_IMPORT_MODULE,
+    ];
+    if namespace.starts_with("wasi") || reserved.contains(&namespace) {
+        return Err(Error::config_error(
+            format!("Namespace {} is reserved for imports the runtime provides", namespace),
+            Some("Choose a namespace of your own, such as your organization's name".to_string()),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_function(namespace: &str, function: &str) -> Result<()> {
+    if function.is_empty() || function.contains('.') {
+        return Err(Error::InvalidInput {
+            field: "function".to_string(),
+            reason: format!("'{}' in namespace {} must be a single non-empty segment", function, namespace),
+            suggestion: None,
+        });
+    }
+    Ok(())
+}
+
+/// Check whether the capabilities grant a namespace
+fn is_granted(capabilities: &Capabilities, namespace: &str) -> bool {
+    granted_namespaces(capabilities).iter().any(|grant| {
+        grant == "*" || grant == namespace || grant.strip_suffix(".*").is_some_and(|prefix| {
+            namespace == prefix || namespace.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('.'))
+        })
+    })
+}
+
+/// Namespace grants in the capabilities
+fn granted_namespaces(capabilities: &Capabilities) -> Vec<String> {
+    match capabilities.get_custom(HOST_NAMESPACE_CAPABILITY) {
+        Some(CustomCapability::StringList(grants)) => grants.clone(),
+        Some(CustomCapability::String(grant)) => vec![grant.clone()],
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,268 @@
+//! Fair sharing of host disk I/O between instances
+//!
+//! Per-instance [`IoLimits`] cap what one instance may read or write, but many
+//! instances hammering their scratch space can still saturate the disk they
+//! share and starve each other. An [`IoBudget`] describes what the disk can
+//! take, in bytes and operations per second, and splits it between the
+//! instances doing I/O by their [`InstanceConfig::io_weight`](crate::InstanceConfig):
+//! an instance alone on the disk gets all of it, while two busy instances of
+//! equal weight get half each. An instance's share is further capped by its own
+//! `max_read_bytes_per_second` and `max_write_bytes_per_second`.
+//!
+//! File reads and writes the guest makes through WASI are paced to its share;
+//! the standard streams are not. Each operation reserves the time its share
+//! needs to move it, and waits for the operations reserved before it beyond a
+//! short burst allowance. An operation that would wait longer than
+//! [`IoBudget::max_delay`] fails with `EAGAIN` instead, and one that waits
+//! longer than [`IoBudget::starvation_threshold`] is counted as starved in the
+//! instance's [`IoMetrics`].
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::security::IoLimits;
+use crate::InstanceId;
+
+/// How long after its last operation an instance still counts as doing I/O
+const ACTIVE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Disk capacity shared by all of a sandbox's instances
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IoBudget {
+    /// Bytes per second the instances may read and write together
+    pub bytes_per_second: u64,
+    
+    /// Read and write operations per second the instances may make together
+    pub operations_per_second: u64,
+    
+    /// Backlog an instance may run ahead of its share before it has to wait
+    pub burst: Duration,
+    
+    /// Longest an operation waits for its turn; longer waits fail with `EAGAIN`
+    pub max_delay: Duration,
+    
+    /// Waits longer than this count as starvation
+    pub starvation_threshold: Duration,
+}
+
+impl IoBudget {
+    /// Share `bytes_per_second` and `operations_per_second` between the instances
+    pub fn new(bytes_per_second: u64, operations_per_second: u64) -> Self {
+        Self {
+            bytes_per_second,
+            operations_per_second,
+            burst: Duration::from_millis(50),
+            max_delay: Duration::from_secs(1),
+            starvation_threshold: Duration::from_millis(100),
+        }
+    }
+    
+    /// Set the backlog an instance may run ahead of its share
+    pub fn burst(mut self, burst: Duration) -> Self {
+        self.burst = burst;
+        self
+    }
+    
+    /// Set the longest an operation waits for its turn
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+    
+    /// Set the wait beyond which an operation counts as starved
+    pub fn starvation_threshold(mut self, threshold: Duration) -> Self {
+        self.starvation_threshold = threshold;
+        self
+    }
+    
+    /// Check the budget can be enforced
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.bytes_per_second == 0 || self.operations_per_second == 0 {
+            return Err(Error::config_error(
+                "An I/O budget must allow some bytes and operations per second",
+                Some("Set IoBudget::bytes_per_second and IoBudget::operations_per_second above zero".to_string()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Whether an operation reads or writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IoDirection {
+    /// Reads from a file
+    Read,
+    
+    /// Writes to a file
+    Write,
+}
+
+/// Counters for operations paced by the I/O budget
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoMetrics {
+    /// Operations admitted
+    pub operations: u64,
+    
+    /// Bytes the admitted operations asked to move
+    pub bytes: u64,
+    
+    /// Operations that waited for their turn
+    pub delayed_operations: u64,
+    
+    /// Time operations spent waiting
+    pub wait_time: Duration,
+    
+    /// Operations that waited longer than the starvation threshold
+    pub starved_operations: u64,
+    
+    /// Operations failed with `EAGAIN` because they would have waited too long
+    pub refused_operations: u64,
+}
+
+impl IoMetrics {
+    fn admitted(&mut self, bytes: u64, wait: Duration, starved: bool) {
+        self.operations += 1;
+        self.bytes = self.bytes.saturating_add(bytes);
+        if !wait.is_zero() {
+            self.delayed_operations += 1;
+            self.wait_time += wait;
+        }
+        if starved {
+            self.starved_operations += 1;
+        }
+    }
+}
+
+/// Paces one instance's file operations
+pub trait IoScheduler: Send + Sync {
+    /// Reserve disk time for an operation moving `bytes`
+    ///
+    /// Returns how long to wait before running the operation, or `None` to
+    /// fail it because the wait would be too long.
+    fn reserve(&self, direction: IoDirection, bytes: u64) -> Option<Duration>;
+}
+
+struct Stream {
+    weight: u32,
+    read_cap: Option<u64>,
+    write_cap: Option<u64>,
+    /// When the operations reserved so far will have had their share of the disk
+    ready_at: Instant,
+    last_active: Option<Instant>,
+    metrics: IoMetrics,
+}
+
+struct LedgerState {
+    streams: HashMap<InstanceId, Stream>,
+    totals: IoMetrics,
+}
+
+/// Disk time reserved by every instance under an [`IoBudget`]
+pub(crate) struct IoLedger {
+    budget: IoBudget,
+    state: Mutex<LedgerState>,
+}
+
+impl IoLedger {
+    /// Create a ledger with no instances
+    pub(crate) fn new(budget: IoBudget) -> Self {
+        Self {
+            budget,
+            state: Mutex::new(LedgerState {
+                streams: HashMap::new(),
+                totals: IoMetrics::default(),
+            }),
+        }
+    }
+    
+    /// Pace an instance's operations by `weight`, within its own rate `limits`
+    pub(crate) fn register(&self, instance_id: InstanceId, weight: u32, limits: &IoLimits) {
+        self.state.lock().unwrap().streams.insert(instance_id, Stream {
+            weight,
+            read_cap: limits.max_read_bytes_per_second,
+            write_cap: limits.max_write_bytes_per_second,
+            ready_at: Instant::now(),
+            last_active: None,
+            metrics: IoMetrics::default(),
+        });
+    }
+    
+    /// Stop pacing an instance; its operations stay in the totals
+    pub(crate) fn unregister(&self, instance_id: InstanceId) {
+        self.state.lock().unwrap().streams.remove(&instance_id);
+    }
+    
+    /// Counters for every operation paced so far
+    pub(crate) fn metrics(&self) -> IoMetrics {
+        self.state.lock().unwrap().totals
+    }
+    
+    /// Counters for each paced instance
+    pub(crate) fn instance_metrics(&self) -> HashMap<InstanceId, IoMetrics> {
+        let state = self.state.lock().unwrap();
+        state.streams.iter().map(|(instance_id, stream)| (*instance_id, stream.metrics)).collect()
+    }
+    
+    fn reserve(&self, instance_id: InstanceId, direction: IoDirection, bytes: u64) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        
+        // The disk is split between the instances that did I/O recently
+        let active_weight: u64 = state.streams.iter()
+            .filter(|(id, stream)| {
+                **id == instance_id || stream.last_active.is_some_and(|at| now.duration_since(at) < ACTIVE_WINDOW)
+            })
+            .map(|(_, stream)| stream.weight as u64)
+            .sum();
+        let LedgerState { streams, totals } = &mut *state;
+        let Some(stream) = streams.get_mut(&instance_id) else {
+            return Some(Duration::ZERO);
+        };
+        let share = |capacity: u64| {
+            let share = capacity as u128 * stream.weight as u128 / active_weight.max(1) as u128;
+            (share as u64).max(1)
+        };
+        let cap = match direction {
+            IoDirection::Read => stream.read_cap,
+            IoDirection::Write => stream.write_cap,
+        };
+        let bandwidth = share(self.budget.bytes_per_second).min(cap.unwrap_or(u64::MAX).max(1));
+        let operations = share(self.budget.operations_per_second);
+        let cost = Duration::from_secs_f64(bytes as f64 / bandwidth as f64)
+            .max(Duration::from_secs_f64(1.0 / operations as f64));
+        
+        let start = stream.ready_at.max(now);
+        let wait = start.duration_since(now).saturating_sub(self.budget.burst);
+        if wait > self.budget.max_delay {
+            stream.metrics.refused_operations += 1;
+            totals.refused_operations += 1;
+            return None;
+        }
+        stream.ready_at = start + cost;
+        stream.last_active = Some(now);
+        let starved = wait > self.budget.starvation_threshold;
+        stream.metrics.admitted(bytes, wait, starved);
+        totals.admitted(bytes, wait, starved);
+        Some(wait)
+    }
+}
+
+/// A sandbox's I/O ledger as seen by one instance
+pub(crate) struct InstanceIo {
+    ledger: Arc<IoLedger>,
+    instance_id: InstanceId,
+}
+
+impl InstanceIo {
+    pub(crate) fn new(ledger: Arc<IoLedger>, instance_id: InstanceId) -> Self {
+        Self { ledger, instance_id }
+    }
+}
+
+impl IoScheduler for InstanceIo {
+    fn reserve(&self, direction: IoDirection, bytes: u64) -> Option<Duration> {
+        self.ledger.reserve(self.instance_id, direction, bytes)
+    }
+}
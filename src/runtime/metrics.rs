@@ -0,0 +1,275 @@
+//! Latency percentiles and decaying rates behind the runtime metrics
+//!
+//! [`RuntimeMetrics`] only counts. The runtime also keeps the latency of recent
+//! calls and instantiations in a [`LatencyWindow`] and the fuel guests burn as
+//! a [`DecayingRate`], both for the runtime as a whole and per module, and
+//! [`WasmRuntime::get_metrics_detailed`](crate::runtime::WasmRuntime::get_metrics_detailed)
+//! reports them as [`DetailedMetrics`].
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::runtime::{ModuleId, RuntimeMetrics};
+
+/// Number of recent samples latency percentiles are computed over
+pub const LATENCY_WINDOW: usize = 1024;
+
+/// Time after which fuel burned no longer counts for half in the fuel rate
+pub const FUEL_RATE_HALF_LIFE: Duration = Duration::from_secs(10);
+
+/// Percentiles of the latencies in a [`LatencyWindow`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencySummary {
+    /// Samples recorded since the window was created, including evicted ones
+    pub count: u64,
+    
+    /// Median latency
+    pub p50: Duration,
+    
+    /// 95th percentile latency
+    pub p95: Duration,
+    
+    /// 99th percentile latency
+    pub p99: Duration,
+    
+    /// Longest latency in the window
+    pub max: Duration,
+}
+
+/// The most recent latencies, oldest evicted first
+#[derive(Debug, Clone)]
+pub struct LatencyWindow {
+    samples: VecDeque<Duration>,
+    capacity: usize,
+    count: u64,
+}
+
+impl Default for LatencyWindow {
+    fn default() -> Self {
+        Self::new(LATENCY_WINDOW)
+    }
+}
+
+impl LatencyWindow {
+    /// Create a window of `capacity` samples
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity.min(LATENCY_WINDOW)),
+            capacity: capacity.max(1),
+            count: 0,
+        }
+    }
+    
+    /// Record a latency
+    pub fn record(&mut self, latency: Duration) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(latency);
+        self.count += 1;
+    }
+    
+    /// Nearest-rank percentiles of the samples in the window
+    pub fn summary(&self) -> LatencySummary {
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let percentile = |p: usize| match sorted.len() {
+            0 => Duration::ZERO,
+            len => sorted[(len * p).div_ceil(100).clamp(1, len) - 1],
+        };
+        LatencySummary {
+            count: self.count,
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+            max: sorted.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// A per-second rate in which older amounts count for exponentially less
+#[derive(Debug, Clone)]
+pub struct DecayingRate {
+    half_life: Duration,
+    total: f64,
+    updated: Option<Instant>,
+}
+
+impl Default for DecayingRate {
+    fn default() -> Self {
+        Self::new(FUEL_RATE_HALF_LIFE)
+    }
+}
+
+impl DecayingRate {
+    /// Create a rate whose amounts count for half after `half_life`
+    pub fn new(half_life: Duration) -> Self {
+        Self {
+            half_life: half_life.max(Duration::from_millis(1)),
+            total: 0.0,
+            updated: None,
+        }
+    }
+    
+    /// Record `amount` at `now`
+    pub fn record(&mut self, amount: f64, now: Instant) {
+        self.total = self.decayed(now) + amount;
+        self.updated = Some(now);
+    }
+    
+    /// Rate per second at `now`
+    ///
+    /// A steady rate is reported accurately once it has run for a few half-lives.
+    pub fn rate(&self, now: Instant) -> f64 {
+        self.decayed(now) * std::f64::consts::LN_2 / self.half_life.as_secs_f64()
+    }
+    
+    fn decayed(&self, now: Instant) -> f64 {
+        match self.updated {
+            Some(updated) => {
+                let elapsed = now.saturating_duration_since(updated).as_secs_f64();
+                self.total * 0.5f64.powf(elapsed / self.half_life.as_secs_f64())
+            }
+            None => 0.0,
+        }
+    }
+}
+
+/// Calls, instantiations and fuel for a module or the whole runtime
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CallMetrics {
+    /// Guest calls made
+    pub calls: u64,
+    
+    /// Guest calls that trapped or failed
+    pub failed_calls: u64,
+    
+    /// Instances created
+    pub instantiations: u64,
+    
+    /// Fuel burned by guest calls
+    pub fuel_consumed: u64,
+    
+    /// Latency of recent guest calls
+    pub call_latency: LatencySummary,
+    
+    /// Latency of recent instantiations
+    pub instantiation_latency: LatencySummary,
+    
+    /// Fuel burned per second, decaying with [`FUEL_RATE_HALF_LIFE`]
+    pub fuel_rate: f64,
+}
+
+/// Runtime counters with latency percentiles, fuel rates and per-module breakdowns
+#[derive(Debug, Clone)]
+pub struct DetailedMetrics {
+    /// The runtime's counters
+    pub runtime: RuntimeMetrics,
+    
+    /// Totals over every module
+    pub totals: CallMetrics,
+    
+    /// Totals for each module that has been instantiated
+    pub modules: HashMap<ModuleId, CallMetrics>,
+}
+
+impl DetailedMetrics {
+    /// Counters alone, for runtimes that don't track latencies
+    pub fn from_counters(runtime: RuntimeMetrics) -> Self {
+        Self {
+            runtime,
+            totals: CallMetrics::default(),
+            modules: HashMap::new(),
+        }
+    }
+}
+
+/// What one module, or the runtime, has recorded
+#[derive(Debug, Default)]
+struct Tracker {
+    calls: u64,
+    failed_calls: u64,
+    instantiations: u64,
+    fuel_consumed: u64,
+    call_latency: LatencyWindow,
+    instantiation_latency: LatencyWindow,
+    fuel_rate: DecayingRate,
+}
+
+impl Tracker {
+    fn record_call(&mut self, latency: Duration, fuel: Option<u64>, succeeded: bool, now: Instant) {
+        self.calls += 1;
+        self.failed_calls += u64::from(!succeeded);
+        self.call_latency.record(latency);
+        if let Some(fuel) = fuel {
+            self.fuel_consumed += fuel;
+            self.fuel_rate.record(fuel as f64, now);
+        }
+    }
+    
+    fn record_instantiation(&mut self, latency: Duration) {
+        self.instantiations += 1;
+        self.instantiation_latency.record(latency);
+    }
+    
+    fn snapshot(&self, now: Instant) -> CallMetrics {
+        CallMetrics {
+            calls: self.calls,
+            failed_calls: self.failed_calls,
+            instantiations: self.instantiations,
+            fuel_consumed: self.fuel_consumed,
+            call_latency: self.call_latency.summary(),
+            instantiation_latency: self.instantiation_latency.summary(),
+            fuel_rate: self.fuel_rate.rate(now),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Trackers {
+    totals: Tracker,
+    modules: HashMap<ModuleId, Tracker>,
+}
+
+/// Latencies and fuel shared by a runtime and the instances it creates
+#[derive(Debug, Default)]
+pub(crate) struct MetricsRecorder {
+    trackers: Mutex<Trackers>,
+}
+
+impl MetricsRecorder {
+    /// Record a guest call into an instance of `module_id`
+    pub(crate) fn record_call(&self, module_id: ModuleId, latency: Duration, fuel: Option<u64>, succeeded: bool) {
+        let now = Instant::now();
+        let mut trackers = self.trackers.lock().unwrap();
+        trackers.totals.record_call(latency, fuel, succeeded, now);
+        trackers.modules.entry(module_id).or_default().record_call(latency, fuel, succeeded, now);
+    }
+    
+    /// Record the creation of an instance of `module_id`
+    pub(crate) fn record_instantiation(&self, module_id: ModuleId, latency: Duration) {
+        let mut trackers = self.trackers.lock().unwrap();
+        trackers.totals.record_instantiation(latency);
+        trackers.modules.entry(module_id).or_default().record_instantiation(latency);
+    }
+    
+    /// Fuel burned per second across the runtime, if any fuel has been burned
+    pub(crate) fn fuel_rate(&self) -> Option<f64> {
+        let trackers = self.trackers.lock().unwrap();
+        (trackers.totals.fuel_consumed > 0).then(|| trackers.totals.fuel_rate.rate(Instant::now()))
+    }
+    
+    /// Detailed metrics around the runtime's counters
+    pub(crate) fn detailed(&self, runtime: RuntimeMetrics) -> DetailedMetrics {
+        let now = Instant::now();
+        let trackers = self.trackers.lock().unwrap();
+        DetailedMetrics {
+            runtime,
+            totals: trackers.totals.snapshot(now),
+            modules: trackers.modules.iter()
+                .map(|(module_id, tracker)| (*module_id, tracker.snapshot(now)))
+                .collect(),
+        }
+    }
+}
@@ -1,11 +1,31 @@
 //! WebAssembly runtime abstraction
 
+use std::future::Future;
 use std::path::PathBuf;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::error::Result;
 use crate::security::{Capabilities, ResourceLimits};
+use crate::security::imports::{ImportPolicy, LinkReport, ModuleImport};
+use crate::security::secrets::SecretValue;
+use self::abi::AbiKind;
+use self::cache_bundle::ArtifactTarget;
+use self::compilation::CompilationIsolation;
+use self::environment::EnvironmentLayer;
+use self::error_codes::GuestErrorCode;
+use self::features::RuntimeFeatures;
+use self::guest_log::GuestLogSink;
+use self::engine::{EngineSelection, RuntimeEngine};
+use self::profiling::ProfilingStrategy;
+use self::wasi_nn::InferenceHost;
+use self::result_cache::ModuleDigest;
+use self::settings::PluginSettings;
+use crate::utils::provenance::Provenance;
+use crate::utils::version::{ApiVersion, VersionRange};
 
 /// Metrics for the WebAssembly runtime
 #[derive(Debug, Clone)]
@@ -17,7 +37,7 @@ pub struct RuntimeMetrics {
     pub active_instances: usize,
     
     /// Total memory used by all instances (in bytes)
-    pub total_memory_usage: usize,
+    pub total_memory_usage: u64,
     
     /// Peak memory usage (in bytes)
     pub peak_memory_usage: usize,
@@ -30,6 +50,125 @@ pub struct RuntimeMetrics {
     
     /// Compilation time for the last module (in milliseconds)
     pub last_compilation_time_ms: Option<u64>,
+    
+    /// Pooling allocator utilization (if pooling is enabled)
+    pub pool: Option<PoolMetrics>,
+}
+
+/// Pooling allocator utilization
+#[derive(Debug, Clone, Default)]
+pub struct PoolMetrics {
+    /// Instances currently holding a pool slot
+    pub live_instances: usize,
+    
+    /// Instance slots in the pool
+    pub max_instances: u32,
+    
+    /// Fraction of instance slots in use (0.0-1.0)
+    pub utilization: f64,
+}
+
+/// Pooling instance allocator settings
+///
+/// The pooling allocator reserves virtual address space for every memory slot
+/// up front (roughly `total_memories * max_memory_size` plus guard regions), so
+/// instantiation reuses a pre-mapped slot instead of calling mmap. This makes
+/// short-lived instances much cheaper but costs address space even when the
+/// pool is idle, and memories can never grow beyond `max_memory_size`. Size the
+/// pool for the peak number of live instances rather than the total served.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolingConfig {
+    /// Total linear memories across all live instances
+    pub total_memories: u32,
+    
+    /// Total tables across all live instances
+    pub total_tables: u32,
+    
+    /// Maximum number of live instances
+    pub max_instances: u32,
+    
+    /// Largest size any pooled memory may reach, in bytes
+    pub max_memory_size: usize,
+}
+
+impl Default for PoolingConfig {
+    fn default() -> Self {
+        Self {
+            total_memories: 1000,
+            total_tables: 1000,
+            max_instances: 1000,
+            max_memory_size: 160 * WASM_PAGE_SIZE, // matches the default memory limit
+        }
+    }
+}
+
+/// Guest export holding the ABI version as a plain integer
+pub const ABI_VERSION_EXPORT: &str = "__abi_version";
+
+/// Guest export holding the plugin API version as `(major << 16) | (minor << 8) | patch`
+pub const API_VERSION_EXPORT: &str = "__api_version";
+
+/// Versions of the guest ABI and plugin API the host supports
+///
+/// Guests declare their versions with an `i32` global or a nullary function
+/// returning `i32`, exported as [`ABI_VERSION_EXPORT`] and [`API_VERSION_EXPORT`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApiCompatibility {
+    /// Supported ABI versions (the major component is the ABI version)
+    pub abi: Option<VersionRange>,
+    
+    /// Supported plugin API versions
+    pub api: Option<VersionRange>,
+    
+    /// Reject guests that don't export a version a range is declared for
+    pub require_exports: bool,
+}
+
+impl ApiCompatibility {
+    /// Accept no guests outside the given ABI versions
+    pub fn abi(mut self, range: VersionRange) -> Self {
+        self.abi = Some(range);
+        self
+    }
+    
+    /// Accept no guests outside the given plugin API versions
+    pub fn api(mut self, range: VersionRange) -> Self {
+        self.api = Some(range);
+        self
+    }
+    
+    /// Reject guests without version exports
+    pub fn require_exports(mut self) -> Self {
+        self.require_exports = true;
+        self
+    }
+    
+    /// Check the versions an instance exports
+    pub fn check(&self, instance: &dyn WasmInstance) -> Result<()> {
+        if let Some(range) = &self.abi {
+            let found = instance.exported_i32(ABI_VERSION_EXPORT)
+                .map(|abi| ApiVersion::new(abi as u32, 0, 0));
+            self.check_export(ABI_VERSION_EXPORT, found, range)?;
+        }
+        if let Some(range) = &self.api {
+            let found = instance.exported_i32(API_VERSION_EXPORT)
+                .map(|api| ApiVersion::from_packed(api as u32));
+            self.check_export(API_VERSION_EXPORT, found, range)?;
+        }
+        Ok(())
+    }
+    
+    fn check_export(&self, export: &str, found: Option<ApiVersion>, range: &VersionRange) -> Result<()> {
+        match found {
+            Some(version) if range.contains(&version) => Ok(()),
+            None if !self.require_exports => Ok(()),
+            found => Err(crate::error::Error::IncompatibleVersion {
+                export: export.to_string(),
+                found: found.map(|version| version.to_string()),
+                supported: range.to_string(),
+            }),
+        }
+    }
 }
 
 /// Configuration for the WebAssembly runtime
@@ -55,6 +194,36 @@ pub struct RuntimeConfig {
     
     /// Cache directory for compiled modules
     pub cache_directory: Option<PathBuf>,
+    
+    /// Policy for validating module imports before instantiation
+    pub import_policy: ImportPolicy,
+    
+    /// Use the pooling instance allocator (for many short-lived instances)
+    pub pooling: Option<PoolingConfig>,
+    
+    /// Accept modules with 64-bit memories, which may grow beyond 4GB
+    pub enable_memory64: bool,
+    
+    /// Accept modules using the GC proposal's struct and array types
+    ///
+    /// Reference types such as `externref` are accepted either way.
+    pub enable_gc: bool,
+    
+    /// Guest ABI and plugin API versions accepted at instantiation
+    pub compatibility: ApiCompatibility,
+    
+    /// Where modules are compiled
+    pub compilation: CompilationIsolation,
+    
+    /// Fuel a guest may consume before an async call yields to the executor
+    /// (`None` to run calls without yielding; ignored without fuel metering)
+    pub async_yield_fuel: Option<u64>,
+    
+    /// Native profiler the generated code is described to
+    pub profiling: ProfilingStrategy,
+    
+    /// Engine [`create_runtime`] builds the runtime on
+    pub engine: EngineSelection,
 }
 
 impl Default for RuntimeConfig {
@@ -67,10 +236,36 @@ impl Default for RuntimeConfig {
             compilation_threads: num_cpus::get(),
             cache_modules: true,
             cache_directory: None,
+            import_policy: ImportPolicy::default(),
+            pooling: None,
+            enable_memory64: true,
+            enable_gc: false,
+            compatibility: ApiCompatibility::default(),
+            compilation: CompilationIsolation::default(),
+            async_yield_fuel: Some(DEFAULT_ASYNC_YIELD_FUEL),
+            profiling: ProfilingStrategy::None,
+            engine: EngineSelection::Auto,
         }
     }
 }
 
+impl RuntimeConfig {
+    /// Describe generated code to a native profiler such as `perf` or VTune
+    pub fn profiling(mut self, strategy: ProfilingStrategy) -> Self {
+        self.profiling = strategy;
+        self
+    }
+    
+    /// Choose the engine the runtime is built on
+    pub fn engine(mut self, selection: EngineSelection) -> Self {
+        self.engine = selection;
+        self
+    }
+}
+
+/// Fuel a guest consumes between yields to the executor by default
+pub const DEFAULT_ASYNC_YIELD_FUEL: u64 = 100_000;
+
 /// Unique identifier for a WebAssembly module
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ModuleId(Uuid);
@@ -93,12 +288,46 @@ impl std::fmt::Display for ModuleId {
     }
 }
 
+impl std::str::FromStr for ModuleId {
+    type Err = uuid::Error;
+    
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Uuid::parse_str(s).map(Self)
+    }
+}
+
 impl Default for ModuleId {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// WebAssembly page size in bytes
+pub const WASM_PAGE_SIZE: usize = 65536;
+
+/// A guest call that yields to the executor while the guest runs
+pub type GuestCall<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Memory usage of an instance in WebAssembly pages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryPages {
+    /// Pages currently allocated
+    pub current: u64,
+    
+    /// Most pages allocated at any point
+    pub peak: u64,
+}
+
+/// Fuel granted to an instance and the part of it left
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FuelState {
+    /// Fuel granted since the instance was created
+    pub granted: u64,
+    
+    /// Fuel not yet consumed
+    pub remaining: u64,
+}
+
 /// State of a WebAssembly instance
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WasmInstanceState {
@@ -118,6 +347,32 @@ pub enum WasmInstanceState {
     Crashed,
 }
 
+/// Metadata read from a module's custom sections when it was loaded
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleMetadata {
+    /// Names of the module's custom sections
+    pub custom_sections: Vec<String>,
+    
+    /// Build provenance embedded in the module
+    pub provenance: Option<Provenance>,
+}
+
+impl ModuleMetadata {
+    /// Read the metadata of a binary module
+    ///
+    /// Malformed provenance is ignored with a warning.
+    pub fn read(wasm_bytes: &[u8]) -> Self {
+        let provenance = Provenance::read(wasm_bytes).unwrap_or_else(|e| {
+            log::warn!("Ignoring module provenance: {}", e);
+            None
+        });
+        Self {
+            custom_sections: abi::custom_section_names(wasm_bytes),
+            provenance,
+        }
+    }
+}
+
 /// WebAssembly module abstraction
 pub trait WasmModule: Send + Sync {
     /// Get the module ID
@@ -132,6 +387,21 @@ pub trait WasmModule: Send + Sync {
     /// Get the list of exported functions
     fn exports(&self) -> Vec<String>;
     
+    /// Get the list of imported items
+    fn imports(&self) -> Vec<ModuleImport> {
+        Vec::new()
+    }
+    
+    /// Get the calling convention the module was built against
+    fn abi(&self) -> AbiKind {
+        AbiKind::detect(&self.imports(), &self.exports(), &[])
+    }
+    
+    /// Get what the module's custom sections say about it
+    fn metadata(&self) -> ModuleMetadata {
+        ModuleMetadata::default()
+    }
+    
     /// Clone the module
     fn clone_module(&self) -> Box<dyn WasmModule>;
     
@@ -145,7 +415,9 @@ pub trait WasmInstance: Send + Sync {
     fn state(&self) -> WasmInstanceState;
     
     /// Get memory usage in bytes
-    fn memory_usage(&self) -> usize;
+    ///
+    /// Reported as `u64` so 64-bit memories aren't truncated on 32-bit hosts.
+    fn memory_usage(&self) -> u64;
     
     /// Get fuel usage (if enabled)
     fn fuel_usage(&self) -> Option<u64>;
@@ -162,20 +434,682 @@ pub trait WasmInstance: Send + Sync {
     /// 
     /// This is unsafe because it allows direct access to the instance's memory.
     /// The caller must ensure that they do not corrupt memory or violate WebAssembly's
-    /// memory safety guarantees.
+    /// memory safety guarantees. The pointer dangles once the memory grows.
+    #[deprecated(note = "the memory can move when it grows; use `WasmMemoryExt::with_memory` or `with_memory_mut` instead")]
     unsafe fn memory_ptr(&self) -> Result<*mut u8>;
     
-    /// Get memory size in bytes
+    /// Run `f` on the instance's linear memory, with the store locked until it returns
+    ///
+    /// Use [`WasmMemoryExt::with_memory`], which passes results back out.
+    fn visit_memory(&self, f: &mut dyn FnMut(&[u8])) -> Result<()> {
+        let _ = f;
+        Err(crate::error::Error::UnsupportedOperation {
+            message: "Scoped memory access is not supported by this runtime".to_string(),
+        })
+    }
+    
+    /// Run `f` on the instance's linear memory mutably, with the store locked until it returns
+    ///
+    /// Use [`WasmMemoryExt::with_memory_mut`], which passes results back out.
+    fn visit_memory_mut(&self, f: &mut dyn FnMut(&mut [u8])) -> Result<()> {
+        let _ = f;
+        Err(crate::error::Error::UnsupportedOperation {
+            message: "Scoped memory access is not supported by this runtime".to_string(),
+        })
+    }
+    
+    /// Get the size in bytes of the memory the host can address through [`WasmMemoryExt::with_memory`]
     fn memory_size(&self) -> usize;
     
+    /// Get current and peak memory size in pages
+    fn memory_pages(&self) -> MemoryPages {
+        let current = self.memory_usage() / WASM_PAGE_SIZE as u64;
+        MemoryPages { current, peak: current }
+    }
+    
     /// Get the function caller for this instance
     fn function_caller(&self) -> Box<dyn WasmFunctionCaller>;
     
     /// Simple function call for basic cases (add two i32s)
     /// This is a convenience method for testing and simple operations
+    ///
+    /// Exports with other types or several results are called with [`WasmInstance::call_values`].
     fn call_simple_function(&self, function_name: &str, params: &[i32]) -> Result<i32>;
+    
+    /// Call a function that pushes partial results to `sink` through the stream import
+    ///
+    /// Blocks until the function returns; the guest waits in the stream import
+    /// whenever `sink` is full.
+    fn call_streaming(&self, function_name: &str, params_json: &str, sink: ResultSink) -> Result<()> {
+        let _ = (params_json, sink);
+        Err(crate::error::Error::UnsupportedOperation {
+            message: format!("Streaming calls to {} are not supported by this runtime", function_name),
+        })
+    }
+    
+    /// Async form of [`WasmInstance::call_streaming`]
+    ///
+    /// Runtimes without async support run the whole call when the future is
+    /// first polled.
+    fn call_streaming_async<'a>(&'a self, function_name: &'a str, params_json: &'a str, sink: ResultSink) -> GuestCall<'a, ()> {
+        Box::pin(async move { self.call_streaming(function_name, params_json, sink) })
+    }
+    
+    /// Call an export using the guest data ABI
+    ///
+    /// The input is copied into guest memory through [`GUEST_ALLOC_EXPORT`] and the
+    /// export is called as `(ptr: i32, len: i32) -> i64`, returning `(ptr << 32) | len`
+    /// of its output.
+    fn call_raw(&self, function_name: &str, input: &[u8]) -> Result<Vec<u8>> {
+        let _ = input;
+        Err(crate::error::Error::UnsupportedOperation {
+            message: format!("Raw calls to {} are not supported by this runtime", function_name),
+        })
+    }
+    
+    /// Read an `i32` exported as a global or returned by a nullary function
+    fn exported_i32(&self, name: &str) -> Option<i32> {
+        let _ = name;
+        None
+    }
+    
+    /// Report of the imports linked into the instance, if the runtime prunes its linker
+    fn link_report(&self) -> Option<LinkReport> {
+        None
+    }
+    
+    /// Route the guest's service calls through `dispatcher`
+    fn set_service_dispatcher(&self, dispatcher: Arc<dyn ServiceDispatcher>) {
+        let _ = dispatcher;
+    }
+    
+    /// Resolve the guest's secret requests through `secrets`
+    fn set_secrets(&self, secrets: Arc<dyn SecretResolver>) {
+        let _ = secrets;
+    }
+    
+    /// Resolve the guest's domain names through `dns`
+    fn set_dns(&self, dns: Arc<dyn DnsLookup>) {
+        let _ = dns;
+    }
+    
+    /// Send the guest's HTTP requests through `http`
+    fn set_http(&self, http: Arc<dyn HttpFetch>) {
+        let _ = http;
+    }
+    
+    /// Handle that interrupts the instance's running call from another thread
+    fn interrupt_handle(&self) -> Option<Arc<dyn GuestInterrupt>> {
+        None
+    }
+    
+    /// Arm the timers the guest sets through [`TIMER_IMPORT_MODULE`] with `timers`
+    fn set_timers(&self, timers: Arc<dyn TimerScheduler>) {
+        let _ = timers;
+    }
+    
+    /// Record the lines the guest logs through [`LOG_IMPORT_MODULE`] in `log`
+    fn set_guest_log(&self, log: Arc<dyn GuestLogSink>) {
+        let _ = log;
+    }
+    
+    /// Route the progress the guest reports through [`PROGRESS_IMPORT_MODULE`] to `sink`
+    fn set_progress_sink(&self, sink: Arc<dyn progress::ProgressSink>) {
+        let _ = sink;
+    }
+    
+    /// Write coredumps of the guest's trapped calls to `sink`
+    fn set_coredump_sink(&self, sink: Arc<dyn coredump::CoredumpSink>) {
+        let _ = sink;
+    }
+    
+    /// Sample the guest's calls into `sink`
+    fn set_profile_sink(&self, sink: Arc<dyn profiling::ProfileSink>) {
+        let _ = sink;
+    }
+    
+    /// Write the checkpoints the guest requests through [`CHECKPOINT_IMPORT_MODULE`] to `sink`
+    fn set_checkpoint_sink(&self, sink: Arc<dyn checkpoint::CheckpointSink>) {
+        let _ = sink;
+    }
+    
+    /// Ask `refiller` for more fuel when a call runs low
+    fn set_fuel_refiller(&self, refiller: Arc<dyn fuel_budget::FuelRefiller>) {
+        let _ = refiller;
+    }
+    
+    /// Pace the guest's file reads and writes through `scheduler`
+    fn set_io_scheduler(&self, scheduler: Arc<dyn io_scheduler::IoScheduler>) {
+        let _ = scheduler;
+    }
+    
+    /// Serve the guest's WASI-NN imports from `inference`
+    fn set_inference(&self, inference: Arc<dyn InferenceHost>) {
+        let _ = inference;
+    }
+    
+    /// Start and call the guest's child instances through `spawner`
+    fn set_child_spawner(&self, spawner: Arc<dyn ChildSpawner>) {
+        let _ = spawner;
+    }
+    
+    /// Route the guest's memory and table growth requests through `observer`
+    fn set_growth_observer(&self, observer: Arc<dyn GrowthObserver>) {
+        let _ = observer;
+    }
+    
+    /// Replace the settings document the guest reads through [`CONFIG_IMPORT_MODULE`]
+    fn set_settings(&self, settings: Arc<PluginSettings>) {
+        let _ = settings;
+    }
+    
+    /// Copy out the instance's linear memory
+    fn read_memory(&self) -> Result<Vec<u8>> {
+        Err(crate::error::Error::UnsupportedOperation {
+            message: "Reading instance memory is not supported by this runtime".to_string(),
+        })
+    }
+    
+    /// Overwrite the start of linear memory with `contents`, growing it if needed
+    fn restore_memory(&self, contents: &[u8]) -> Result<()> {
+        let _ = contents;
+        Err(crate::error::Error::UnsupportedOperation {
+            message: "Restoring instance memory is not supported by this runtime".to_string(),
+        })
+    }
+    
+    /// Values of the instance's exported mutable globals
+    ///
+    /// Globals holding values other than numbers and host handles, such as
+    /// `funcref`s, are skipped.
+    fn mutable_globals(&self) -> Result<Vec<(String, HostValue)>> {
+        Ok(Vec::new())
+    }
+    
+    /// Set exported mutable globals to values read by [`WasmInstance::mutable_globals`]
+    fn restore_globals(&self, globals: &[(String, HostValue)]) -> Result<()> {
+        if globals.is_empty() {
+            return Ok(());
+        }
+        Err(crate::error::Error::UnsupportedOperation {
+            message: "Restoring instance globals is not supported by this runtime".to_string(),
+        })
+    }
+    
+    /// Fuel granted to the instance and left, if fuel is metered
+    fn fuel_state(&self) -> Option<FuelState> {
+        None
+    }
+    
+    /// Set the instance's fuel to a state read by [`WasmInstance::fuel_state`]
+    fn restore_fuel(&self, fuel: FuelState) -> Result<()> {
+        let _ = fuel;
+        Err(crate::error::Error::UnsupportedOperation {
+            message: "Restoring instance fuel is not supported by this runtime".to_string(),
+        })
+    }
+    
+    /// Call an export using the guest data ABI, returning where its output lies in memory
+    ///
+    /// Unlike [`WasmInstance::call_raw`] the output is not copied; it stays valid
+    /// until the next call into the instance.
+    fn call_raw_region(&self, function_name: &str, input: &[u8]) -> Result<(usize, usize)> {
+        let _ = input;
+        Err(crate::error::Error::UnsupportedOperation {
+            message: format!("Calls to {} without copying the output are not supported by this runtime", function_name),
+        })
+    }
+    
+    /// Async form of [`WasmInstance::call_raw_region`]
+    ///
+    /// Runtimes without async support run the whole call when the future is
+    /// first polled.
+    fn call_raw_region_async<'a>(&'a self, function_name: &'a str, input: &'a [u8]) -> GuestCall<'a, (usize, usize)> {
+        Box::pin(async move { self.call_raw_region(function_name, input) })
+    }
+    
+    /// Allocate `len` bytes of input through [`GUEST_ALLOC_EXPORT`] and fill them with `write`
+    ///
+    /// Returns where the input lies in memory, for [`WasmInstance::call_raw_region_at`].
+    /// The input is written as `write` produces it rather than from one host buffer.
+    fn write_input(&self, function_name: &str, len: usize, write: &mut dyn FnMut(&mut dyn std::io::Write) -> Result<()>) -> Result<(usize, usize)> {
+        let _ = (len, write);
+        Err(crate::error::Error::UnsupportedOperation {
+            message: format!("Writing input for {} in place is not supported by this runtime", function_name),
+        })
+    }
+    
+    /// Async form of [`WasmInstance::write_input`]
+    ///
+    /// Runtimes without async support allocate and write the input when the
+    /// future is first polled.
+    fn write_input_async<'a>(
+        &'a self,
+        function_name: &'a str,
+        len: usize,
+        write: &'a (dyn Fn(&mut dyn std::io::Write) -> Result<()> + Sync),
+    ) -> GuestCall<'a, (usize, usize)> {
+        Box::pin(async move { self.write_input(function_name, len, &mut |guest| write(guest)) })
+    }
+    
+    /// Call an export using the guest data ABI with input written by [`WasmInstance::write_input`]
+    ///
+    /// Returns where the output lies in memory, as [`WasmInstance::call_raw_region`] does.
+    fn call_raw_region_at(&self, function_name: &str, input: (usize, usize)) -> Result<(usize, usize)> {
+        let _ = input;
+        Err(crate::error::Error::UnsupportedOperation {
+            message: format!("Calls to {} without copying the output are not supported by this runtime", function_name),
+        })
+    }
+    
+    /// Async form of [`WasmInstance::call_raw_region_at`]
+    ///
+    /// Runtimes without async support run the whole call when the future is
+    /// first polled.
+    fn call_raw_region_at_async<'a>(&'a self, function_name: &'a str, input: (usize, usize)) -> GuestCall<'a, (usize, usize)> {
+        Box::pin(async move { self.call_raw_region_at(function_name, input) })
+    }
+    
+    /// Read `len` bytes of linear memory starting at `offset`
+    fn read_memory_at(&self, offset: usize, len: usize) -> Result<Vec<u8>> {
+        let _ = (offset, len);
+        Err(crate::error::Error::UnsupportedOperation {
+            message: "Reading instance memory is not supported by this runtime".to_string(),
+        })
+    }
+    
+    /// Write `bytes` into linear memory starting at `offset`
+    fn write_memory_at(&self, offset: usize, bytes: &[u8]) -> Result<()> {
+        let _ = (offset, bytes);
+        Err(crate::error::Error::UnsupportedOperation {
+            message: "Writing instance memory is not supported by this runtime".to_string(),
+        })
+    }
+    
+    /// Call an export with numeric arguments, returning all of its results
+    fn call_values(&self, function_name: &str, args: &[HostValue]) -> Result<Vec<HostValue>> {
+        let _ = args;
+        Err(crate::error::Error::UnsupportedOperation {
+            message: format!("Calls to {} with typed values are not supported by this runtime", function_name),
+        })
+    }
+    
+    /// Async form of [`WasmInstance::call_values`]
+    ///
+    /// Runtimes without async support run the whole call when the future is
+    /// first polled.
+    fn call_values_async<'a>(&'a self, function_name: &'a str, args: &'a [HostValue]) -> GuestCall<'a, Vec<HostValue>> {
+        Box::pin(async move { self.call_values(function_name, args) })
+    }
+    
+    /// Async form of [`WasmInstance::call_raw`]
+    ///
+    /// Runtimes without async support run the whole call when the future is
+    /// first polled.
+    fn call_raw_async<'a>(&'a self, function_name: &'a str, input: &'a [u8]) -> GuestCall<'a, Vec<u8>> {
+        Box::pin(async move { self.call_raw(function_name, input) })
+    }
+}
+
+/// A WebAssembly value passed to or returned from a function
+///
+/// Numbers are passed as they are. An `externref` is passed as the
+/// [`handles::Handle`] it carries, so host functions look up the object behind
+/// it in the instance's [`handles::HandleTable`].
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum HostValue {
+    /// 32-bit integer
+    I32(i32),
+    
+    /// 64-bit integer
+    I64(i64),
+    
+    /// 32-bit float
+    F32(f32),
+    
+    /// 64-bit float
+    F64(f64),
+    
+    /// `externref` carrying a host handle, or a null reference
+    ExternRef(Option<handles::Handle>),
+    
+    /// 128-bit SIMD vector, as its little-endian bits
+    V128(u128),
+}
+
+impl HostValue {
+    /// Name of the value's WebAssembly type
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            HostValue::I32(_) => "i32",
+            HostValue::I64(_) => "i64",
+            HostValue::F32(_) => "f32",
+            HostValue::F64(_) => "f64",
+            HostValue::ExternRef(_) => "externref",
+            HostValue::V128(_) => "v128",
+        }
+    }
+}
+
+/// Functions supplied by the host for a module's imports
+///
+/// Used in place of real host implementations, for example by
+/// [`crate::testing::MockHost`]. Provided imports shadow WASI and runtime imports
+/// of the same name.
+pub trait HostFunctions: Send + Sync {
+    /// Check whether `module.name` is provided
+    fn provides(&self, module: &str, name: &str) -> bool;
+    
+    /// Handle a call to `module.name`; an error traps the guest
+    fn call(&self, module: &str, name: &str, args: &[HostValue]) -> Result<Vec<HostValue>>;
+    
+    /// Async form of [`HostFunctions::call`], awaited while the guest is suspended
+    ///
+    /// Runtimes with async stores call this instead of [`HostFunctions::call`].
+    /// The default runs the sync handler when the future is first polled.
+    fn call_async<'a>(&'a self, module: &'a str, name: &'a str, args: &'a [HostValue]) -> GuestCall<'a, Vec<HostValue>> {
+        Box::pin(async move { self.call(module, name, args) })
+    }
+    
+    /// Source of the guest's WASI time and randomness, if the host controls them
+    fn entropy(&self) -> Option<Arc<dyn EntropySource>> {
+        None
+    }
 }
 
+/// Time and randomness supplied in place of WASI `clock_time_get` and `random_get`
+pub trait EntropySource: Send + Sync {
+    /// Current time of WASI clock `clock_id` in nanoseconds
+    fn clock_time(&self, clock_id: u32) -> u64;
+    
+    /// Fill `buf` with random bytes
+    fn random_bytes(&self, buf: &mut [u8]);
+}
+
+/// Handles service calls a guest makes through [`SERVICE_IMPORT_MODULE`]
+pub trait ServiceDispatcher: Send + Sync {
+    /// Call `function` on `service` with a raw payload
+    fn dispatch(&self, service: &str, function: &str, payload: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Starts and calls the child instances a guest runs through [`CHILD_IMPORT_MODULE`]
+pub trait ChildSpawner: Send + Sync {
+    /// Start an instance of the named child module, returning its handle
+    fn spawn(&self, module: &str) -> Result<u32>;
+    
+    /// Call an export of a child through the guest data ABI
+    fn call(&self, child: u32, function: &str, payload: &[u8]) -> Result<Vec<u8>>;
+    
+    /// Stop a child
+    fn kill(&self, child: u32) -> Result<()>;
+}
+
+/// Resolves secrets a guest requests through [`SECRETS_IMPORT_MODULE`]
+pub trait SecretResolver: Send + Sync {
+    /// Fetch the named secret if the guest is granted it
+    fn resolve(&self, name: &str) -> Result<SecretValue>;
+}
+
+/// Resolves domain names a guest looks up through [`DNS_IMPORT_MODULE`]
+pub trait DnsLookup: Send + Sync {
+    /// Resolve `name` if the guest may, pinning the addresses for the current call
+    fn resolve(&self, name: &str) -> Result<Vec<std::net::IpAddr>>;
+}
+
+/// Makes HTTP requests a guest sends through [`HTTP_IMPORT_MODULE`]
+pub trait HttpFetch: Send + Sync {
+    /// Send the JSON request the guest passed, returning the JSON response
+    fn fetch<'a>(&'a self, request: &'a [u8]) -> GuestCall<'a, Vec<u8>>;
+}
+
+/// Stops guest code an instance is running from another thread
+pub trait GuestInterrupt: Send + Sync {
+    /// Make the instance's running call trap as soon as possible
+    fn interrupt(&self);
+    
+    /// Withdraw an interrupt that arrived after the call had finished
+    fn clear(&self);
+}
+
+/// Arms timers a guest sets through [`TIMER_IMPORT_MODULE`]
+pub trait TimerScheduler: Send + Sync {
+    /// Arm a timer that fires after `delay`, returning its identifier
+    fn set(&self, delay: Duration, token: i64) -> Result<u64>;
+    
+    /// Disarm an outstanding timer, returning whether there was one
+    fn cancel(&self, timer_id: u64) -> bool;
+}
+
+/// Approves guest memory and table growth before it happens
+pub trait GrowthObserver: Send + Sync {
+    /// Called when a memory grows from `current_bytes` to `desired_bytes`; `false` refuses
+    fn memory_growing(&self, current_bytes: usize, desired_bytes: usize) -> bool;
+    
+    /// Called when a table grows from `current` to `desired` elements; `false` refuses
+    fn table_growing(&self, current: usize, desired: usize) -> bool;
+}
+
+/// Guest export that allocates `len` bytes and returns a pointer to them
+pub const GUEST_ALLOC_EXPORT: &str = "alloc";
+
+/// Host import module for calling services exposed by other instances
+///
+/// Guests call `sandbox_rpc.call(service_ptr, service_len, function_ptr,
+/// function_len, payload_ptr, payload_len) -> i64`. On success the response is
+/// copied into the caller through [`GUEST_ALLOC_EXPORT`] and `(ptr << 32) | len`
+/// is returned; otherwise a negative [`GuestErrorCode`].
+pub const SERVICE_IMPORT_MODULE: &str = "sandbox_rpc";
+
+/// Name of the function in [`SERVICE_IMPORT_MODULE`] that calls a service
+pub const SERVICE_CALL_FUNCTION: &str = "call";
+
+/// The caller is not granted the service
+pub const SERVICE_CALL_DENIED: i64 = GuestErrorCode::PermissionDenied.code();
+
+/// The caller exceeded the service's quota
+pub const SERVICE_CALL_QUOTA_EXCEEDED: i64 = GuestErrorCode::QuotaExceeded.code();
+
+/// The service call failed
+pub const SERVICE_CALL_FAILED: i64 = GuestErrorCode::Failed.code();
+
+/// Host import module for reading plugin settings
+///
+/// Guests call `sandbox_config.get(key_ptr, key_len) -> i64` with a dotted key (see
+/// [`settings::PluginSettings::get`]). The JSON-encoded value is copied into the
+/// guest through [`GUEST_ALLOC_EXPORT`] and `(ptr << 32) | len` is returned, or
+/// [`CONFIG_KEY_MISSING`] if there is no such key.
+pub const CONFIG_IMPORT_MODULE: &str = "sandbox_config";
+
+/// Name of the function in [`CONFIG_IMPORT_MODULE`] that reads a setting
+pub const CONFIG_GET_FUNCTION: &str = "get";
+
+/// The requested setting does not exist
+pub const CONFIG_KEY_MISSING: i64 = GuestErrorCode::NotFound.code();
+
+/// Optional nullary guest export called after its settings are replaced
+pub const CONFIG_UPDATE_EXPORT: &str = "on_config_update";
+
+/// Host import module for reading host-provisioned secrets
+///
+/// Guests call `sandbox_secrets.get(name_ptr, name_len) -> i64`. If the instance
+/// is granted the secret (see [`crate::security::SecretsCapability`]), its value is
+/// copied into the guest through [`GUEST_ALLOC_EXPORT`] and `(ptr << 32) | len` is
+/// returned; otherwise a negative [`GuestErrorCode`].
+pub const SECRETS_IMPORT_MODULE: &str = "sandbox_secrets";
+
+/// Name of the function in [`SECRETS_IMPORT_MODULE`] that reads a secret
+pub const SECRETS_GET_FUNCTION: &str = "get";
+
+/// Host import module for resolving domain names on the host
+///
+/// Guests call `sandbox_dns.resolve(name_ptr, name_len) -> i64`. If the
+/// instance's [`crate::security::DnsPolicy`] allows the name, the host resolves
+/// it and copies the addresses into the guest through [`GUEST_ALLOC_EXPORT`],
+/// 16 bytes each with IPv4 addresses IPv4-mapped, and returns
+/// `(ptr << 32) | len`; otherwise a negative [`GuestErrorCode`].
+pub const DNS_IMPORT_MODULE: &str = "sandbox_dns";
+
+/// Name of the function in [`DNS_IMPORT_MODULE`] that resolves a name
+pub const DNS_RESOLVE_FUNCTION: &str = "resolve";
+
+/// Host import module for HTTP requests the host makes on the guest's behalf
+///
+/// Guests call `sandbox_http.fetch(request_ptr, request_len) -> i64` with a
+/// JSON request `{"method", "url", "headers", "body"}`, `body` base64 encoded.
+/// If the instance's [`crate::security::NetworkCapability`] allows the URL's
+/// host, the host sends it under its egress policy and copies the JSON response
+/// `{"status", "headers", "body"}` into the guest through
+/// [`GUEST_ALLOC_EXPORT`], returning `(ptr << 32) | len`; otherwise a negative
+/// [`GuestErrorCode`]. Needs the `http-client` feature.
+pub const HTTP_IMPORT_MODULE: &str = "sandbox_http";
+
+/// Name of the function in [`HTTP_IMPORT_MODULE`] that sends a request
+pub const HTTP_FETCH_FUNCTION: &str = "fetch";
+
+/// Host import module for guest timers
+///
+/// Guests call `sandbox_timer.set(delay_ms: i64, token: i64) -> i64` to have
+/// their [`TIMER_CALLBACK_EXPORT`] called with `token` once `delay_ms` has passed.
+/// It returns a timer ID, or [`GuestErrorCode::QuotaExceeded`] if the instance
+/// already has its maximum of timers outstanding (see
+/// [`crate::security::TimeLimits::max_timers`]). `sandbox_timer.cancel(timer_id: i64) -> i64`
+/// returns 0, or [`GuestErrorCode::NotFound`] if the timer already fired.
+pub const TIMER_IMPORT_MODULE: &str = "sandbox_timer";
+
+/// Name of the function in [`TIMER_IMPORT_MODULE`] that arms a timer
+pub const TIMER_SET_FUNCTION: &str = "set";
+
+/// Name of the function in [`TIMER_IMPORT_MODULE`] that disarms a timer
+pub const TIMER_CANCEL_FUNCTION: &str = "cancel";
+
+/// Guest export `(token: i64)` called when one of its timers is due
+pub const TIMER_CALLBACK_EXPORT: &str = "on_timer";
+
+/// Host import module for checkpoints requested by the guest
+///
+/// Guests created with a [`checkpoint::CheckpointConfig`] call
+/// `sandbox_checkpoint.checkpoint() -> i64` at a safe point to have their
+/// memory and exported mutable globals written to disk. It returns the
+/// checkpoint's ID, [`GuestErrorCode::Unavailable`] if checkpoints aren't
+/// enabled for the instance, or another negative [`GuestErrorCode`] if the
+/// checkpoint couldn't be written.
+pub const CHECKPOINT_IMPORT_MODULE: &str = "sandbox_checkpoint";
+
+/// Name of the function in [`CHECKPOINT_IMPORT_MODULE`] that writes a checkpoint
+pub const CHECKPOINT_FUNCTION: &str = "checkpoint";
+
+/// Host import module for progress reports from long calls
+///
+/// Guests call `sandbox_progress.report(percent: i32, ptr: i32, len: i32) -> i32`
+/// with how far along they are, from 0 to 100, and a UTF-8 message of at most
+/// [`progress::MAX_PROGRESS_MESSAGE_BYTES`]. It returns 0 to continue or 1 once
+/// the host has cancelled the call, in which case the guest should return, or
+/// [`GuestErrorCode::InvalidInput`] for a percentage out of range or a message
+/// too long. Reports of calls nobody is watching are dropped.
+pub const PROGRESS_IMPORT_MODULE: &str = "sandbox_progress";
+
+/// Name of the function in [`PROGRESS_IMPORT_MODULE`] that reports progress
+pub const PROGRESS_REPORT_FUNCTION: &str = "report";
+
+/// Host import module for structured guest logging
+///
+/// Guests call `sandbox_log.write(level: i32, ptr: i32, len: i32) -> i32` with a
+/// UTF-8 message and a level from 1 (error) to 5 (trace). The line is tagged with
+/// the instance and the current call's ID (see [`call_context`]) and returns 0,
+/// or [`GuestErrorCode::InvalidInput`] for an unknown level.
+/// `sandbox_log.call_id() -> i64` returns the current call's ID, or 0 outside a
+/// call, for guests that correlate their own output.
+/// `sandbox_log.diagnostic(ptr: i32, len: i32) -> i32` attaches a JSON-encoded
+/// [`diagnostics::Diagnostic`] to the current call's result, returning 0 or
+/// [`GuestErrorCode::InvalidInput`] if it can't be decoded.
+pub const LOG_IMPORT_MODULE: &str = "sandbox_log";
+
+/// Name of the function in [`LOG_IMPORT_MODULE`] that logs a line
+pub const LOG_WRITE_FUNCTION: &str = "write";
+
+/// Name of the function in [`LOG_IMPORT_MODULE`] that reads the current call ID
+pub const LOG_CALL_ID_FUNCTION: &str = "call_id";
+
+/// Name of the function in [`LOG_IMPORT_MODULE`] that reports a diagnostic
+pub const LOG_DIAGNOSTIC_FUNCTION: &str = "diagnostic";
+
+/// Host import module for allocation failures in the guest
+///
+/// A guest allocator that can't satisfy a request calls
+/// `sandbox_alloc.failed(size: i64) -> i32` before returning null; it returns
+/// 0. If the call then traps on `unreachable`, as Rust guests abort when an
+/// allocation fails outside `try_reserve`, or the failure was in
+/// [`GUEST_ALLOC_EXPORT`] while the host passed input, the call fails with a
+/// memory [`crate::Error::ResourceExhausted`] rather than the trap. Guests
+/// that recover from the failure are unaffected.
+pub const ALLOC_IMPORT_MODULE: &str = "sandbox_alloc";
+
+/// Name of the function in [`ALLOC_IMPORT_MODULE`] that reports a failed allocation
+pub const ALLOC_FAILED_FUNCTION: &str = "failed";
+
+/// WASI-NN import module for inference with host-registered models
+///
+/// Follows the `wasi_ephemeral_nn` ABI, returning 0 or an [`wasi_nn::NnErrno`]:
+/// `load_by_name(name_ptr, name_len, graph_ptr)` loads a model the instance's
+/// [`crate::security::MlCapability`] allows, `init_execution_context(graph, ctx_ptr)`,
+/// `set_input(ctx, index, tensor_ptr)`, `compute(ctx)` and
+/// `get_output(ctx, index, out_ptr, out_len, written_ptr)` run it. Guests cannot
+/// supply their own weights: `load` always fails with
+/// [`wasi_nn::NnErrno::UnsupportedOperation`].
+pub const NN_IMPORT_MODULE: &str = "wasi_ephemeral_nn";
+
+/// Name of the function in [`NN_IMPORT_MODULE`] that loads a graph from guest-supplied weights
+pub const NN_LOAD_FUNCTION: &str = "load";
+
+/// Name of the function in [`NN_IMPORT_MODULE`] that loads a host-registered model
+pub const NN_LOAD_BY_NAME_FUNCTION: &str = "load_by_name";
+
+/// Name of the function in [`NN_IMPORT_MODULE`] that creates an execution context
+pub const NN_INIT_EXECUTION_CONTEXT_FUNCTION: &str = "init_execution_context";
+
+/// Name of the function in [`NN_IMPORT_MODULE`] that sets an input tensor
+pub const NN_SET_INPUT_FUNCTION: &str = "set_input";
+
+/// Name of the function in [`NN_IMPORT_MODULE`] that runs inference
+pub const NN_COMPUTE_FUNCTION: &str = "compute";
+
+/// Name of the function in [`NN_IMPORT_MODULE`] that copies out an output tensor
+pub const NN_GET_OUTPUT_FUNCTION: &str = "get_output";
+
+/// Host import module for child instances, the sandboxed alternative to processes
+///
+/// Guests granted a [`crate::security::ChildCapability`] call
+/// `sandbox_child.spawn(name_ptr, name_len) -> i64` to start a registered
+/// module, getting a child handle, and `sandbox_child.call(child, function_ptr,
+/// function_len, payload_ptr, payload_len) -> i64` to call one of its exports
+/// through the guest data ABI; the output is copied back like a service call
+/// response. `sandbox_child.kill(child) -> i32` stops a child early. Failures
+/// return a negative [`GuestErrorCode`].
+pub const CHILD_IMPORT_MODULE: &str = "sandbox_child";
+
+/// Name of the function in [`CHILD_IMPORT_MODULE`] that starts a child
+pub const CHILD_SPAWN_FUNCTION: &str = "spawn";
+
+/// Name of the function in [`CHILD_IMPORT_MODULE`] that calls a child's export
+pub const CHILD_CALL_FUNCTION: &str = "call";
+
+/// Name of the function in [`CHILD_IMPORT_MODULE`] that stops a child
+pub const CHILD_KILL_FUNCTION: &str = "kill";
+
+/// Host import module for streamed results
+///
+/// Guests call `sandbox_stream.emit(ptr: i32, len: i32) -> i32` with a JSON-encoded
+/// partial result. It blocks while the consumer is behind and returns 0 to continue
+/// or 1 once the consumer has gone away, in which case the guest should return.
+/// Returning from the function is the completion marker.
+pub const STREAM_IMPORT_MODULE: &str = "sandbox_stream";
+
+/// Name of the function in [`STREAM_IMPORT_MODULE`] that emits a partial result
+pub const STREAM_EMIT_FUNCTION: &str = "emit";
+
+/// Bounded channel receiving partial results from a streaming call
+pub type ResultSink = tokio::sync::mpsc::Sender<Vec<u8>>;
+
 /// Separate trait for generic/async function calling (dyn-compatible)
 pub trait WasmFunctionCaller: Send + Sync {
     /// Call a function in the instance with JSON serialization
@@ -196,6 +1130,40 @@ pub trait WasmFunctionCaller: Send + Sync {
     fn as_any(&self) -> &dyn std::any::Any;
 }
 
+/// Scoped access to an instance's linear memory (generic, not dyn-compatible)
+///
+/// The slice is only borrowed for the closure, and the instance's store stays
+/// locked until it returns, so the guest can't run or grow its memory in the
+/// meantime and the slice never outlives a move of the memory.
+pub trait WasmMemoryExt {
+    /// Read the instance's linear memory
+    fn with_memory<R>(&self, f: impl FnOnce(&[u8]) -> R) -> Result<R>;
+    
+    /// Read and write the instance's linear memory
+    fn with_memory_mut<R>(&self, f: impl FnOnce(&mut [u8]) -> R) -> Result<R>;
+}
+
+/// Automatic implementation for all instances
+impl<T: WasmInstance + ?Sized> WasmMemoryExt for T {
+    fn with_memory<R>(&self, f: impl FnOnce(&[u8]) -> R) -> Result<R> {
+        let mut f = Some(f);
+        let mut result = None;
+        self.visit_memory(&mut |memory| result = f.take().map(|f| f(memory)))?;
+        result.ok_or_else(|| crate::error::Error::UnsupportedOperation {
+            message: "The runtime did not pass the memory to the closure".to_string(),
+        })
+    }
+    
+    fn with_memory_mut<R>(&self, f: impl FnOnce(&mut [u8]) -> R) -> Result<R> {
+        let mut f = Some(f);
+        let mut result = None;
+        self.visit_memory_mut(&mut |memory| result = f.take().map(|f| f(memory)))?;
+        result.ok_or_else(|| crate::error::Error::UnsupportedOperation {
+            message: "The runtime did not pass the memory to the closure".to_string(),
+        })
+    }
+}
+
 /// Extension trait for async function calling (not dyn-compatible)
 #[allow(async_fn_in_trait)]
 pub trait WasmFunctionCallerAsync {
@@ -260,6 +1228,29 @@ pub trait WasmRuntime: Send + Sync {
     /// Get all module IDs
     fn get_module_ids(&self) -> Vec<ModuleId>;
     
+    /// Engine this runtime is built on
+    fn engine(&self) -> RuntimeEngine;
+    
+    /// Host and settings this runtime's compiled artifacts depend on, if it can export them
+    fn artifact_target(&self) -> Option<ArtifactTarget> {
+        None
+    }
+    
+    /// Compiled artifact of a loaded module, loadable by a runtime with the same target
+    fn export_artifact(&self, id: ModuleId) -> Result<Vec<u8>> {
+        Err(crate::error::Error::UnsupportedOperation {
+            message: format!("Exporting the compiled artifact of module {} is not supported by this runtime", id),
+        })
+    }
+    
+    /// Load `artifact` in place of compiling modules whose bytes have `digest`
+    fn import_artifact(&self, digest: ModuleDigest, artifact: &[u8]) -> Result<()> {
+        let _ = (digest, artifact);
+        Err(crate::error::Error::UnsupportedOperation {
+            message: "Importing compiled artifacts is not supported by this runtime".to_string(),
+        })
+    }
+    
     /// Create a new instance with resource limits
     fn create_instance(
         &self, 
@@ -268,31 +1259,59 @@ pub trait WasmRuntime: Send + Sync {
         capabilities: Capabilities,
     ) -> Result<Box<dyn WasmInstance>>;
     
+    /// Create a new instance with an environment layer composed in before instantiation
+    fn create_instance_with_environment(
+        &self,
+        module: &dyn WasmModule,
+        resources: ResourceLimits,
+        capabilities: Capabilities,
+        environment: &EnvironmentLayer,
+    ) -> Result<Box<dyn WasmInstance>> {
+        if environment.is_empty() {
+            return self.create_instance(module, resources, capabilities);
+        }
+        Err(crate::error::Error::UnsupportedOperation {
+            message: "Environment layers are not supported by this runtime".to_string(),
+        })
+    }
+    
+    /// Create an instance whose imports are handled by `host` where it provides them
+    ///
+    /// Provided imports are exempt from the import policy.
+    fn create_instance_with_host(
+        &self,
+        module: &dyn WasmModule,
+        resources: ResourceLimits,
+        capabilities: Capabilities,
+        environment: Option<&EnvironmentLayer>,
+        host: Arc<dyn HostFunctions>,
+    ) -> Result<Box<dyn WasmInstance>> {
+        let _ = (module, resources, capabilities, environment, host);
+        Err(crate::error::Error::UnsupportedOperation {
+            message: "Host functions are not supported by this runtime".to_string(),
+        })
+    }
+    
+    /// Features this runtime's backend supports
+    fn features(&self) -> RuntimeFeatures {
+        RuntimeFeatures::none("unknown")
+    }
+    
     /// Get runtime metrics
     fn get_metrics(&self) -> RuntimeMetrics;
     
+    /// Get runtime metrics with latency percentiles, fuel rates and per-module breakdowns
+    fn get_metrics_detailed(&self) -> metrics::DetailedMetrics {
+        metrics::DetailedMetrics::from_counters(self.get_metrics())
+    }
+    
     /// Shutdown the runtime
     fn shutdown(&self) -> Result<()>;
 }
 
-/// Create a runtime with the given configuration
-pub fn create_runtime(_config: &RuntimeConfig) -> Result<Box<dyn WasmRuntime>> {
-    #[cfg(feature = "wasmtime-runtime")]
-    {
-        Ok(Box::new(crate::runtime::wasmtime::WasmtimeRuntime::new(_config)?))
-    }
-
-    #[cfg(all(feature = "wasmer-runtime", not(feature = "wasmtime-runtime")))]
-    {
-        return Ok(Box::new(crate::runtime::wasmer::WasmerRuntime::new()?));
-    }
-
-    #[cfg(not(any(feature = "wasmtime-runtime", feature = "wasmer-runtime")))]
-    {
-        return Err(crate::error::Error::RuntimeInitialization(
-            "No WebAssembly runtime feature is enabled".to_string(),
-        ));
-    }
+/// Create a runtime on the engine [`RuntimeConfig::engine`] selects
+pub fn create_runtime(config: &RuntimeConfig) -> Result<Box<dyn WasmRuntime>> {
+    engine::select_runtime(config)
 }
 
 pub mod wasmtime;
@@ -300,7 +1319,49 @@ pub mod wasmtime;
 #[cfg(feature = "wasmer-runtime")]
 pub mod wasmer;
 pub mod wasm_common;
+pub mod abi;
+pub mod bytes;
+pub mod cache_bundle;
+pub mod call_context;
+pub mod children;
+pub mod call_queue;
+pub mod checkpoint;
+pub mod compilation;
 pub mod component;
+pub mod coredump;
+pub mod diagnostics;
+pub mod engine;
+pub mod environment;
+pub mod error_codes;
+pub mod eviction;
+pub mod features;
+pub mod fuel_budget;
+pub mod growth;
+pub mod guest_log;
+pub mod handles;
+pub mod hibernation;
+pub mod host_namespaces;
+pub mod io_scheduler;
+pub mod metrics;
+pub mod params;
+pub mod profiling;
+pub mod progress;
+pub(crate) mod read_only_dir;
+pub mod recovery;
+pub mod reload;
+pub mod result_cache;
+pub mod result_validation;
+pub mod scheduler;
+pub mod settings;
+pub mod spill;
+pub mod stdlib;
+pub mod timers;
+pub mod wasi_nn;
+pub mod values;
+pub mod wit;
+pub mod wasi_sockets;
+
+pub use self::values::{FromHostValues, IntoHostValues, WasmValue};
 
 // Re-export runtimes for convenience
 #[cfg(feature = "wasmtime-runtime")]
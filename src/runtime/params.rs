@@ -0,0 +1,127 @@
+//! Limits on the size of call parameters
+//!
+//! Parameters are serialized to JSON before they reach the guest. A call whose
+//! parameters are larger than [`crate::InstanceConfig::max_param_bytes`], or
+//! the limit set for its function in
+//! [`crate::InstanceConfig::function_param_limits`], fails before the guest is
+//! entered, and serialization stops as soon as the limit is passed. Parameters
+//! over [`crate::InstanceConfig::max_inline_param_bytes`] are not built up in
+//! one host buffer; guest data ABI calls serialize them a second time, straight
+//! into the guest memory allocated for them.
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::error::{Error, ResourceKind, Result};
+use crate::{InstanceConfig, InstanceId};
+
+/// Default size above which parameters are written into guest memory in chunks
+pub const DEFAULT_MAX_INLINE_PARAM_BYTES: usize = 1024 * 1024;
+
+/// Serialized parameters of a call
+#[derive(Debug)]
+pub(crate) enum CallParams {
+    /// Parameters small enough to hold on the host
+    Inline(String),
+    
+    /// Length of parameters to be serialized again into guest memory
+    Spilled(usize),
+}
+
+/// Size limits applying to one call's parameters
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ParamLimits {
+    instance_id: Option<InstanceId>,
+    max_bytes: Option<usize>,
+    max_inline_bytes: Option<usize>,
+}
+
+impl ParamLimits {
+    /// Limits on calls to `function_name` of an instance configured with `config`
+    pub(crate) fn new(instance_id: InstanceId, config: &InstanceConfig, function_name: &str) -> Self {
+        Self {
+            instance_id: Some(instance_id),
+            max_bytes: config.function_param_limits.get(function_name).copied().or(config.max_param_bytes),
+            max_inline_bytes: config.max_inline_param_bytes,
+        }
+    }
+    
+    /// Fail if `len` bytes of parameters are over the limit
+    pub(crate) fn check(&self, len: usize) -> Result<()> {
+        match self.max_bytes {
+            Some(limit) if len > limit => Err(Error::ResourceExhausted {
+                kind: ResourceKind::ParamBytes,
+                limit: limit as u64,
+                used: len as u64,
+                instance_id: self.instance_id.map(|id| id.as_uuid()),
+                suggestion: Some("Pass smaller parameters or raise InstanceConfig::max_param_bytes".to_string()),
+            }),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Serialize parameters, spilling them if they are over the inline limit
+    pub(crate) fn serialize<P: Serialize + ?Sized>(&self, params: &P) -> Result<CallParams> {
+        let writer = self.write(params, self.max_inline_bytes)?;
+        match self.max_inline_bytes {
+            Some(max_inline) if writer.len > max_inline => Ok(CallParams::Spilled(writer.len)),
+            _ => into_string(writer.buffer).map(CallParams::Inline),
+        }
+    }
+    
+    /// Serialize parameters into one string, within the size limit
+    pub(crate) fn serialize_whole<P: Serialize + ?Sized>(&self, params: &P) -> Result<String> {
+        into_string(self.write(params, None)?.buffer)
+    }
+    
+    /// Serialize parameters, keeping them while they are within `max_inline` bytes
+    fn write<P: Serialize + ?Sized>(&self, params: &P, max_inline: Option<usize>) -> Result<ShapingWriter> {
+        let mut writer = ShapingWriter {
+            buffer: Vec::new(),
+            len: 0,
+            max_len: self.max_bytes,
+            max_inline,
+        };
+        if let Err(e) = serde_json::to_writer(&mut writer, params) {
+            self.check(writer.len)?;
+            return Err(e.into());
+        }
+        Ok(writer)
+    }
+}
+
+/// Serialized JSON as a string
+fn into_string(json: Vec<u8>) -> Result<String> {
+    String::from_utf8(json).map_err(|e| Error::Serialization {
+        format: "json".to_string(),
+        operation: "serialize".to_string(),
+        reason: e.to_string(),
+    })
+}
+
+/// Counts serialized parameters, keeping them while they are within the inline limit
+struct ShapingWriter {
+    buffer: Vec<u8>,
+    len: usize,
+    max_len: Option<usize>,
+    max_inline: Option<usize>,
+}
+
+impl Write for ShapingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.len += buf.len();
+        if self.max_len.is_some_and(|max_len| self.len > max_len) {
+            return Err(std::io::Error::other("Parameters are over the size limit"));
+        }
+        match self.max_inline {
+            Some(max_inline) if self.len > max_inline => self.buffer = Vec::new(),
+            _ => self.buffer.extend_from_slice(buf),
+        }
+        Ok(buf.len())
+    }
+    
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+//! Profiling guest code
+//!
+//! Two kinds of profiling are available. [`RuntimeConfig::profiling`] hands
+//! the code the runtime generates to a native profiler such as `perf` or
+//! VTune, which then sees guest functions in its own recordings. Instances
+//! created with a [`SamplingConfig`] are sampled per call instead: while a call
+//! runs, its guest stack is captured every [`SamplingConfig::interval`], and
+//! the fuel burned since the previous sample is charged to the stack captured.
+//! [`crate::WasmSandbox::last_profile`] returns the samples of an instance's
+//! latest call as [`FoldedStacks`], which `flamegraph.pl` and `inferno` read.
+//!
+//! [`RuntimeConfig::profiling`]: crate::runtime::RuntimeConfig::profiling
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::runtime::call_context::{current_call, CallId};
+use crate::InstanceId;
+
+/// How often running calls are sampled by default
+pub const DEFAULT_SAMPLING_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Native profiler the runtime describes its generated code to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfilingStrategy {
+    /// Don't describe generated code to any profiler
+    #[default]
+    None,
+    
+    /// Write `/tmp/perf-<pid>.map` for `perf`
+    PerfMap,
+    
+    /// Write a `jit-<pid>.dump` file for `perf inject --jit`
+    JitDump,
+    
+    /// Register code with Intel VTune
+    VTune,
+}
+
+/// How calls into a profiled instance are sampled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SamplingConfig {
+    /// Time between samples of a running call
+    pub interval: Duration,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_SAMPLING_INTERVAL,
+        }
+    }
+}
+
+impl SamplingConfig {
+    /// Sample every [`DEFAULT_SAMPLING_INTERVAL`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Sample every `interval`
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+/// Samples of guest stacks, folded by stack
+///
+/// Stacks are the names of the guest functions on them, outermost first and
+/// separated by `;`. Functions without a name in the module's name section
+/// appear as `wasm-function[<index>]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FoldedStacks {
+    /// Number of times each stack was sampled
+    pub samples: BTreeMap<String, u64>,
+    
+    /// Fuel burned while each stack was running
+    pub fuel: BTreeMap<String, u64>,
+}
+
+impl FoldedStacks {
+    /// Count a sample of `stack` that burned `fuel` since the previous one
+    pub fn add(&mut self, stack: &str, fuel: u64) {
+        *self.samples.entry(stack.to_string()).or_default() += 1;
+        if fuel > 0 {
+            *self.fuel.entry(stack.to_string()).or_default() += fuel;
+        }
+    }
+    
+    /// Whether no stack was sampled
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+    
+    /// Number of samples taken
+    pub fn total_samples(&self) -> u64 {
+        self.samples.values().sum()
+    }
+    
+    /// Fuel charged to the sampled stacks
+    pub fn total_fuel(&self) -> u64 {
+        self.fuel.values().sum()
+    }
+    
+    /// Sample counts in folded-stack format, one `stack count` line per stack
+    pub fn folded(&self) -> String {
+        fold(&self.samples)
+    }
+    
+    /// Fuel burned in folded-stack format, so a flamegraph shows where the fuel went
+    pub fn folded_fuel(&self) -> String {
+        fold(&self.fuel)
+    }
+}
+
+/// Write `counts` as folded-stack lines
+fn fold(counts: &BTreeMap<String, u64>) -> String {
+    let mut folded = String::new();
+    for (stack, count) in counts {
+        let _ = writeln!(folded, "{} {}", stack, count);
+    }
+    folded
+}
+
+/// Name of a guest function as it appears in a folded stack
+pub(crate) fn frame_name(name: Option<&str>, func_index: u32) -> String {
+    match name {
+        // `;` separates frames, so it can't appear inside one
+        Some(name) => name.replace(';', ":"),
+        None => format!("wasm-function[{}]", func_index),
+    }
+}
+
+/// The samples of one call into a profiled instance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallProfile {
+    /// Instance that was called
+    pub instance_id: InstanceId,
+    
+    /// ID of the call, if it was made through the sandbox
+    pub call_id: Option<CallId>,
+    
+    /// Export that was called
+    pub function_name: String,
+    
+    /// How long the call ran
+    pub duration: Duration,
+    
+    /// Stacks sampled while the call ran
+    pub stacks: FoldedStacks,
+}
+
+/// Destination for the samples of one instance's calls
+pub trait ProfileSink: Send + Sync {
+    /// Time between samples of a running call
+    fn interval(&self) -> Duration;
+    
+    /// Receive the samples of a finished call
+    fn record(&self, function_name: &str, duration: Duration, stacks: FoldedStacks);
+}
+
+/// Latest call profile of every instance in a sandbox
+#[derive(Debug, Default)]
+pub(crate) struct Profiles {
+    latest: Mutex<HashMap<InstanceId, CallProfile>>,
+}
+
+impl Profiles {
+    /// Profile of an instance's latest call
+    pub(crate) fn latest(&self, instance_id: InstanceId) -> Option<CallProfile> {
+        self.latest.lock().unwrap().get(&instance_id).cloned()
+    }
+    
+    /// Forget an instance's latest profile
+    pub(crate) fn forget(&self, instance_id: InstanceId) {
+        self.latest.lock().unwrap().remove(&instance_id);
+    }
+}
+
+/// A sandbox's profiles as seen by one instance
+pub(crate) struct InstanceProfiles {
+    profiles: Arc<Profiles>,
+    config: SamplingConfig,
+    instance_id: InstanceId,
+}
+
+impl InstanceProfiles {
+    /// Sample the calls of `instance_id` as `config` says
+    pub(crate) fn new(profiles: Arc<Profiles>, config: SamplingConfig, instance_id: InstanceId) -> Self {
+        Self { profiles, config, instance_id }
+    }
+}
+
+impl ProfileSink for InstanceProfiles {
+    fn interval(&self) -> Duration {
+        self.config.interval
+    }
+    
+    fn record(&self, function_name: &str, duration: Duration, stacks: FoldedStacks) {
+        self.profiles.latest.lock().unwrap().insert(self.instance_id, CallProfile {
+            instance_id: self.instance_id,
+            call_id: current_call().map(|call| call.call_id),
+            function_name: function_name.to_string(),
+            duration,
+            stacks,
+        });
+    }
+}
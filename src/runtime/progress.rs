@@ -0,0 +1,135 @@
+//! Progress reported by the guest during long calls
+//!
+//! Guests call [`crate::runtime::PROGRESS_REPORT_FUNCTION`] with a percentage
+//! and a short message as they work. A host that wants to show it creates a
+//! [`CallProgress`] and passes it to
+//! [`crate::WasmSandbox::call_function_with_progress`]; the handle holds the
+//! latest update and can be watched for changes, for example to drive a
+//! progress bar.
+//!
+//! Each report is also a cancellation checkpoint: once the host calls
+//! [`CallProgress::cancel`], the next report tells the guest to abort, and the
+//! call returns an error instead of whatever the guest returns. Guests that
+//! never report progress can't be cancelled this way.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::runtime::call_context::{current_call_id, CallId};
+
+/// Longest message the guest may attach to a progress report
+pub const MAX_PROGRESS_MESSAGE_BYTES: usize = 1024;
+
+/// Latest progress of a call
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressUpdate {
+    /// How far along the call is, from 0 to 100
+    pub percent: u8,
+    
+    /// What the guest is doing
+    pub message: String,
+}
+
+/// Observable progress of one call, and the switch to cancel it
+///
+/// Clones share the same progress.
+#[derive(Debug, Clone)]
+pub struct CallProgress {
+    updates: Arc<watch::Sender<ProgressUpdate>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CallProgress {
+    /// Progress of a call that hasn't reported yet
+    pub fn new() -> Self {
+        Self {
+            updates: Arc::new(watch::Sender::new(ProgressUpdate::default())),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+    
+    /// The latest update the guest reported
+    pub fn current(&self) -> ProgressUpdate {
+        self.updates.borrow().clone()
+    }
+    
+    /// Receiver notified of each update
+    pub fn subscribe(&self) -> watch::Receiver<ProgressUpdate> {
+        self.updates.subscribe()
+    }
+    
+    /// Ask the guest to abort at its next progress report
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+    
+    /// Whether the call was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+    
+    /// Record an update, returning whether the guest should abort
+    pub(crate) fn report(&self, percent: u8, message: &str) -> bool {
+        self.updates.send_replace(ProgressUpdate {
+            percent,
+            message: message.to_string(),
+        });
+        self.is_cancelled()
+    }
+}
+
+impl Default for CallProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Receives the progress the guest reports through [`crate::runtime::PROGRESS_IMPORT_MODULE`]
+pub trait ProgressSink: Send + Sync {
+    /// Record an update for the running call, returning whether the guest should abort
+    fn report(&self, percent: u8, message: &str) -> bool;
+}
+
+/// Progress handles of a sandbox's calls in flight, by call ID
+#[derive(Debug, Default)]
+pub(crate) struct CallProgressRegistry {
+    calls: Mutex<HashMap<CallId, CallProgress>>,
+}
+
+impl CallProgressRegistry {
+    /// Route a call's reports to `progress` until the returned guard is dropped
+    pub(crate) fn track(self: &Arc<Self>, call_id: CallId, progress: CallProgress) -> TrackedProgress {
+        self.calls.lock().unwrap().insert(call_id, progress);
+        TrackedProgress {
+            registry: self.clone(),
+            call_id,
+        }
+    }
+}
+
+impl ProgressSink for CallProgressRegistry {
+    fn report(&self, percent: u8, message: &str) -> bool {
+        // Progress of calls nobody is watching is dropped
+        let Some(call_id) = current_call_id() else {
+            return false;
+        };
+        let progress = self.calls.lock().unwrap().get(&call_id).cloned();
+        progress.is_some_and(|progress| progress.report(percent, message))
+    }
+}
+
+/// Stops routing a call's progress when dropped
+pub(crate) struct TrackedProgress {
+    registry: Arc<CallProgressRegistry>,
+    call_id: CallId,
+}
+
+impl Drop for TrackedProgress {
+    fn drop(&mut self) {
+        self.registry.calls.lock().unwrap().remove(&self.call_id);
+    }
+}
@@ -0,0 +1,101 @@
+//! Preopened directories the guest may read but not change
+//!
+//! WASI preview1 preopens carry no permissions of their own, so a read-only
+//! mount wraps the host directory and refuses every operation that would
+//! create, modify, or remove something beneath it. Subdirectories the guest
+//! opens are wrapped the same way, and files are only opened for reading.
+
+use std::any::Any;
+use std::path::PathBuf;
+
+use wasi_common::dir::{OpenResult, ReaddirCursor, ReaddirEntity};
+use wasi_common::file::{FdFlags, Filestat, OFlags};
+use wasi_common::{Error, ErrorExt, SystemTimeSpec, WasiDir};
+
+/// A directory whose contents the guest can only read
+pub(crate) struct ReadOnlyDir(Box<dyn WasiDir>);
+
+impl ReadOnlyDir {
+    /// Wrap a preopened directory
+    pub(crate) fn new(dir: Box<dyn WasiDir>) -> Self {
+        Self(dir)
+    }
+}
+
+#[wiggle::async_trait]
+impl WasiDir for ReadOnlyDir {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    
+    async fn open_file(
+        &self,
+        symlink_follow: bool,
+        path: &str,
+        oflags: OFlags,
+        read: bool,
+        write: bool,
+        fdflags: FdFlags,
+    ) -> Result<OpenResult, Error> {
+        if write || oflags.intersects(OFlags::CREATE | OFlags::EXCLUSIVE | OFlags::TRUNCATE) {
+            return Err(Error::perm());
+        }
+        match self.0.open_file(symlink_follow, path, oflags, read, false, fdflags).await? {
+            OpenResult::Dir(dir) => Ok(OpenResult::Dir(Box::new(ReadOnlyDir(dir)))),
+            file => Ok(file),
+        }
+    }
+    
+    async fn create_dir(&self, _path: &str) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+    
+    async fn readdir(
+        &self,
+        cursor: ReaddirCursor,
+    ) -> Result<Box<dyn Iterator<Item = Result<ReaddirEntity, Error>> + Send>, Error> {
+        self.0.readdir(cursor).await
+    }
+    
+    async fn symlink(&self, _old_path: &str, _new_path: &str) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+    
+    async fn remove_dir(&self, _path: &str) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+    
+    async fn unlink_file(&self, _path: &str) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+    
+    async fn read_link(&self, path: &str) -> Result<PathBuf, Error> {
+        self.0.read_link(path).await
+    }
+    
+    async fn get_filestat(&self) -> Result<Filestat, Error> {
+        self.0.get_filestat().await
+    }
+    
+    async fn get_path_filestat(&self, path: &str, follow_symlinks: bool) -> Result<Filestat, Error> {
+        self.0.get_path_filestat(path, follow_symlinks).await
+    }
+    
+    async fn rename(&self, _path: &str, _dest_dir: &dyn WasiDir, _dest_path: &str) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+    
+    async fn hard_link(&self, _path: &str, _target_dir: &dyn WasiDir, _target_path: &str) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+    
+    async fn set_times(
+        &self,
+        _path: &str,
+        _atime: Option<SystemTimeSpec>,
+        _mtime: Option<SystemTimeSpec>,
+        _follow_symlinks: bool,
+    ) -> Result<(), Error> {
+        Err(Error::perm())
+    }
+}
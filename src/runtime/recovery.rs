@@ -0,0 +1,366 @@
+//! Recreating instances whose calls trap as if memory were corrupted
+//!
+//! A guest with a heap bug tends to fail with out-of-bounds accesses or bad
+//! indirect calls, and once it has, its memory can't be trusted for the next
+//! call either. With a [`RecoveryPolicy`] set on an instance, a call that traps
+//! with one of the policy's signatures replaces the instance with a fresh one
+//! from the same module and is retried once, so a transient guest bug doesn't
+//! take down a long-lived pipeline.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::error::{Error, Result};
+use crate::runtime::coredump::CoredumpSink;
+use crate::runtime::fuel_budget::FuelRefiller;
+use crate::runtime::guest_log::GuestLogSink;
+use crate::runtime::io_scheduler::IoScheduler;
+use crate::runtime::checkpoint::CheckpointSink;
+use crate::runtime::profiling::ProfileSink;
+use crate::runtime::wasi_nn::InferenceHost;
+use crate::runtime::settings::PluginSettings;
+use crate::runtime::{
+    ChildSpawner, DnsLookup, FuelState, GrowthObserver, GuestCall, GuestInterrupt, HostValue, HttpFetch, MemoryPages, ResultSink, SecretResolver,
+    ServiceDispatcher, TimerScheduler, WasmFunctionCaller, WasmInstance, WasmInstanceState,
+};
+use crate::security::imports::LinkReport;
+use crate::InstanceId;
+
+/// Trap messages that suggest a guest has corrupted its own memory
+pub const CORRUPTION_TRAPS: &[&str] = &[
+    "out of bounds memory access",
+    "misaligned memory access",
+    "out of bounds table access",
+    "uninitialized element",
+    "indirect call type mismatch",
+];
+
+/// When to recreate an instance and retry a failed call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryPolicy {
+    /// Substrings of an error that mark it as a sign of memory corruption
+    pub signatures: Vec<String>,
+    
+    /// Times the instance may be recreated over its lifetime; `None` for no limit
+    pub max_recoveries: Option<u32>,
+}
+
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        Self {
+            signatures: CORRUPTION_TRAPS.iter().map(|trap| trap.to_string()).collect(),
+            max_recoveries: Some(3),
+        }
+    }
+}
+
+impl RecoveryPolicy {
+    /// Also treat errors containing `signature` as corruption, e.g. `unreachable`
+    pub fn signature(mut self, signature: &str) -> Self {
+        self.signatures.push(signature.to_string());
+        self
+    }
+    
+    /// Limit how many times the instance may be recreated
+    pub fn max_recoveries(mut self, max_recoveries: Option<u32>) -> Self {
+        self.max_recoveries = max_recoveries;
+        self
+    }
+    
+    /// Whether an error looks like memory corruption
+    pub fn matches(&self, error: &Error) -> bool {
+        let message = error.to_string();
+        self.signatures.iter().any(|signature| message.contains(signature.as_str()))
+    }
+}
+
+/// Sent to recovery handlers after an instance is recreated, before the call is retried
+#[derive(Debug, Clone)]
+pub struct RecoveryNotice {
+    /// Instance that was recreated
+    pub instance_id: InstanceId,
+    
+    /// Function whose call trapped
+    pub function_name: String,
+    
+    /// The trap, unredacted
+    pub error: String,
+    
+    /// Times the instance has now been recreated
+    pub recoveries: u32,
+}
+
+/// Counters for instances recreated after suspected corruption
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecoveryMetrics {
+    /// Instances recreated
+    pub recoveries: u64,
+    
+    /// Retried calls that succeeded on the fresh instance
+    pub retries_succeeded: u64,
+    
+    /// Retried calls that failed again
+    pub retries_failed: u64,
+    
+    /// Suspected corruption left alone because the instance had used up its recoveries
+    pub exhausted: u64,
+}
+
+/// Callback invoked when an instance is recreated
+pub type RecoveryHandler = Arc<dyn Fn(&RecoveryNotice) + Send + Sync>;
+
+/// An instance that can be swapped for a fresh one without its callers noticing
+///
+/// Every call goes to the current instance; a call already running on the
+/// replaced instance finishes there.
+pub(crate) struct InstanceSlot {
+    current: RwLock<Arc<dyn WasmInstance>>,
+    recoveries: AtomicU32,
+}
+
+impl InstanceSlot {
+    /// Wrap an instance
+    pub(crate) fn new(instance: Box<dyn WasmInstance>) -> Self {
+        Self {
+            current: RwLock::new(Arc::from(instance)),
+            recoveries: AtomicU32::new(0),
+        }
+    }
+    
+    /// Count a recovery against `policy`, returning the new count if one is allowed
+    pub(crate) fn claim_recovery(&self, policy: &RecoveryPolicy) -> Option<u32> {
+        self.recoveries
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |recoveries| {
+                match policy.max_recoveries {
+                    Some(max) if recoveries >= max => None,
+                    _ => Some(recoveries + 1),
+                }
+            })
+            .ok()
+            .map(|recoveries| recoveries + 1)
+    }
+    
+    /// Put a fresh instance in place of the current one
+    pub(crate) fn replace(&self, instance: Box<dyn WasmInstance>) {
+        *self.current.write().unwrap() = Arc::from(instance);
+    }
+    
+    fn current(&self) -> Arc<dyn WasmInstance> {
+        self.current.read().unwrap().clone()
+    }
+}
+
+impl WasmInstance for InstanceSlot {
+    fn state(&self) -> WasmInstanceState {
+        self.current().state()
+    }
+    
+    fn memory_usage(&self) -> u64 {
+        self.current().memory_usage()
+    }
+    
+    fn fuel_usage(&self) -> Option<u64> {
+        self.current().fuel_usage()
+    }
+    
+    fn reset_fuel(&self) -> Result<()> {
+        self.current().reset_fuel()
+    }
+    
+    fn add_fuel(&self, fuel: u64) -> Result<()> {
+        self.current().add_fuel(fuel)
+    }
+    
+    unsafe fn memory_ptr(&self) -> Result<*mut u8> {
+        // SAFETY: the caller upholds the same contract for the current instance
+        #[allow(deprecated)]
+        unsafe { self.current().memory_ptr() }
+    }
+    
+    fn visit_memory(&self, f: &mut dyn FnMut(&[u8])) -> Result<()> {
+        self.current().visit_memory(f)
+    }
+    
+    fn visit_memory_mut(&self, f: &mut dyn FnMut(&mut [u8])) -> Result<()> {
+        self.current().visit_memory_mut(f)
+    }
+    
+    fn memory_size(&self) -> usize {
+        self.current().memory_size()
+    }
+    
+    fn memory_pages(&self) -> MemoryPages {
+        self.current().memory_pages()
+    }
+    
+    fn function_caller(&self) -> Box<dyn WasmFunctionCaller> {
+        self.current().function_caller()
+    }
+    
+    fn call_simple_function(&self, function_name: &str, params: &[i32]) -> Result<i32> {
+        self.current().call_simple_function(function_name, params)
+    }
+    
+    fn call_streaming(&self, function_name: &str, params_json: &str, sink: ResultSink) -> Result<()> {
+        self.current().call_streaming(function_name, params_json, sink)
+    }
+    
+    fn call_streaming_async<'a>(&'a self, function_name: &'a str, params_json: &'a str, sink: ResultSink) -> GuestCall<'a, ()> {
+        let current = self.current();
+        Box::pin(async move { current.call_streaming_async(function_name, params_json, sink).await })
+    }
+    
+    fn call_raw(&self, function_name: &str, input: &[u8]) -> Result<Vec<u8>> {
+        self.current().call_raw(function_name, input)
+    }
+    
+    fn exported_i32(&self, name: &str) -> Option<i32> {
+        self.current().exported_i32(name)
+    }
+    
+    fn link_report(&self) -> Option<LinkReport> {
+        self.current().link_report()
+    }
+    
+    fn set_service_dispatcher(&self, dispatcher: Arc<dyn ServiceDispatcher>) {
+        self.current().set_service_dispatcher(dispatcher)
+    }
+    
+    fn set_secrets(&self, secrets: Arc<dyn SecretResolver>) {
+        self.current().set_secrets(secrets)
+    }
+    
+    fn set_dns(&self, dns: Arc<dyn DnsLookup>) {
+        self.current().set_dns(dns)
+    }
+    
+    fn set_http(&self, http: Arc<dyn HttpFetch>) {
+        self.current().set_http(http)
+    }
+    
+    fn interrupt_handle(&self) -> Option<Arc<dyn GuestInterrupt>> {
+        self.current().interrupt_handle()
+    }
+    
+    fn set_timers(&self, timers: Arc<dyn TimerScheduler>) {
+        self.current().set_timers(timers)
+    }
+    
+    fn set_guest_log(&self, log: Arc<dyn GuestLogSink>) {
+        self.current().set_guest_log(log)
+    }
+    
+    fn set_coredump_sink(&self, sink: Arc<dyn CoredumpSink>) {
+        self.current().set_coredump_sink(sink)
+    }
+    
+    fn set_profile_sink(&self, sink: Arc<dyn ProfileSink>) {
+        self.current().set_profile_sink(sink)
+    }
+    
+    fn set_checkpoint_sink(&self, sink: Arc<dyn CheckpointSink>) {
+        self.current().set_checkpoint_sink(sink)
+    }
+    
+    fn set_fuel_refiller(&self, refiller: Arc<dyn FuelRefiller>) {
+        self.current().set_fuel_refiller(refiller)
+    }
+    
+    fn set_io_scheduler(&self, scheduler: Arc<dyn IoScheduler>) {
+        self.current().set_io_scheduler(scheduler)
+    }
+    
+    fn set_inference(&self, inference: Arc<dyn InferenceHost>) {
+        self.current().set_inference(inference)
+    }
+    
+    fn set_child_spawner(&self, spawner: Arc<dyn ChildSpawner>) {
+        self.current().set_child_spawner(spawner)
+    }
+    
+    fn set_growth_observer(&self, observer: Arc<dyn GrowthObserver>) {
+        self.current().set_growth_observer(observer)
+    }
+    
+    fn set_settings(&self, settings: Arc<PluginSettings>) {
+        self.current().set_settings(settings)
+    }
+    
+    fn read_memory(&self) -> Result<Vec<u8>> {
+        self.current().read_memory()
+    }
+    
+    fn restore_memory(&self, contents: &[u8]) -> Result<()> {
+        self.current().restore_memory(contents)
+    }
+    
+    fn mutable_globals(&self) -> Result<Vec<(String, HostValue)>> {
+        self.current().mutable_globals()
+    }
+    
+    fn restore_globals(&self, globals: &[(String, HostValue)]) -> Result<()> {
+        self.current().restore_globals(globals)
+    }
+    
+    fn fuel_state(&self) -> Option<FuelState> {
+        self.current().fuel_state()
+    }
+    
+    fn restore_fuel(&self, fuel: FuelState) -> Result<()> {
+        self.current().restore_fuel(fuel)
+    }
+    
+    fn call_raw_region(&self, function_name: &str, input: &[u8]) -> Result<(usize, usize)> {
+        self.current().call_raw_region(function_name, input)
+    }
+    
+    fn call_raw_region_async<'a>(&'a self, function_name: &'a str, input: &'a [u8]) -> GuestCall<'a, (usize, usize)> {
+        let current = self.current();
+        Box::pin(async move { current.call_raw_region_async(function_name, input).await })
+    }
+    
+    fn write_input(&self, function_name: &str, len: usize, write: &mut dyn FnMut(&mut dyn std::io::Write) -> Result<()>) -> Result<(usize, usize)> {
+        self.current().write_input(function_name, len, write)
+    }
+    
+    fn write_input_async<'a>(
+        &'a self,
+        function_name: &'a str,
+        len: usize,
+        write: &'a (dyn Fn(&mut dyn std::io::Write) -> Result<()> + Sync),
+    ) -> GuestCall<'a, (usize, usize)> {
+        let current = self.current();
+        Box::pin(async move { current.write_input_async(function_name, len, write).await })
+    }
+    
+    fn call_raw_region_at(&self, function_name: &str, input: (usize, usize)) -> Result<(usize, usize)> {
+        self.current().call_raw_region_at(function_name, input)
+    }
+    
+    fn call_raw_region_at_async<'a>(&'a self, function_name: &'a str, input: (usize, usize)) -> GuestCall<'a, (usize, usize)> {
+        let current = self.current();
+        Box::pin(async move { current.call_raw_region_at_async(function_name, input).await })
+    }
+    
+    fn read_memory_at(&self, offset: usize, len: usize) -> Result<Vec<u8>> {
+        self.current().read_memory_at(offset, len)
+    }
+    
+    fn write_memory_at(&self, offset: usize, bytes: &[u8]) -> Result<()> {
+        self.current().write_memory_at(offset, bytes)
+    }
+    
+    fn call_values(&self, function_name: &str, args: &[HostValue]) -> Result<Vec<HostValue>> {
+        self.current().call_values(function_name, args)
+    }
+    
+    fn call_values_async<'a>(&'a self, function_name: &'a str, args: &'a [HostValue]) -> GuestCall<'a, Vec<HostValue>> {
+        let current = self.current();
+        Box::pin(async move { current.call_values_async(function_name, args).await })
+    }
+    
+    fn call_raw_async<'a>(&'a self, function_name: &'a str, input: &'a [u8]) -> GuestCall<'a, Vec<u8>> {
+        let current = self.current();
+        Box::pin(async move { current.call_raw_async(function_name, input).await })
+    }
+}
@@ -0,0 +1,84 @@
+//! Reloading a sandbox's configuration without restarting it
+//!
+//! [`crate::WasmSandbox::apply_config`] validates a new [`crate::SandboxConfig`]
+//! and applies what can change in place: policies, budgets and the cache take
+//! effect immediately, new defaults apply to instances created afterwards, and
+//! running instances created with the old defaults get raised fuel and new
+//! time limits. Everything else is deferred until the sandbox is recreated.
+//! The [`ConfigDiff`] returned says which changes went which way.
+
+use std::fmt;
+
+/// One changed configuration field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigChange {
+    /// Path of the field, e.g. `default_instance_config.resource_limits.fuel`
+    pub field: String,
+    
+    /// What happened to the change
+    pub detail: String,
+}
+
+impl ConfigChange {
+    pub(crate) fn new(field: &str, detail: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Changes made by a configuration reload
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    /// Changes in effect now
+    pub applied: Vec<ConfigChange>,
+    
+    /// Changes that only take effect once the sandbox is recreated
+    pub deferred: Vec<ConfigChange>,
+}
+
+impl ConfigDiff {
+    /// Whether the new configuration changed nothing
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.deferred.is_empty()
+    }
+    
+    /// Whether a change to `field` was applied
+    pub fn is_applied(&self, field: &str) -> bool {
+        self.applied.iter().any(|change| change.field == field)
+    }
+    
+    /// Whether a change to `field` was deferred
+    pub fn is_deferred(&self, field: &str) -> bool {
+        self.deferred.iter().any(|change| change.field == field)
+    }
+    
+    pub(crate) fn apply(&mut self, field: &str, detail: impl Into<String>) {
+        self.applied.push(ConfigChange::new(field, detail));
+    }
+    
+    pub(crate) fn defer(&mut self, field: &str, detail: impl Into<String>) {
+        self.deferred.push(ConfigChange::new(field, detail));
+    }
+}
+
+impl fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no changes");
+        }
+        for change in &self.applied {
+            writeln!(f, "applied  {}: {}", change.field, change.detail)?;
+        }
+        for change in &self.deferred {
+            writeln!(f, "deferred {}: {}", change.field, change.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether two values without `PartialEq` differ, judged by their debug output
+pub(crate) fn debug_differs<T: fmt::Debug>(old: &T, new: &T) -> bool {
+    format!("{:?}", old) != format!("{:?}", new)
+}
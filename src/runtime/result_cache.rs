@@ -0,0 +1,250 @@
+//! Memoization of results from pure guest functions
+//!
+//! Functions marked pure in an instance's configuration (or a manifest's
+//! `result_cache.pure_functions`) are looked up here before the guest is
+//! called. Results are keyed by a digest of the module bytes, the function
+//! name, and a digest of the JSON parameters, so every instance of the same
+//! module version shares them and a new version never sees stale results.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+
+/// SHA-256 digest identifying a module version
+pub type ModuleDigest = [u8; 32];
+
+/// Digest of a module's bytes
+pub fn module_digest(wasm_bytes: &[u8]) -> ModuleDigest {
+    Sha256::digest(wasm_bytes).into()
+}
+
+/// Size and lifetime limits of a result cache
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultCacheConfig {
+    /// Maximum number of cached results
+    pub max_entries: usize,
+    
+    /// Maximum combined size of cached results in bytes
+    pub max_bytes: usize,
+    
+    /// How long a result stays valid; `None` keeps it until it is evicted
+    pub ttl: Option<Duration>,
+}
+
+impl ResultCacheConfig {
+    /// Set the maximum number of cached results
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+    
+    /// Set the maximum combined size of cached results
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+    
+    /// Expire results after `ttl`
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+}
+
+impl Default for ResultCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 1024,
+            max_bytes: 16 * 1024 * 1024,
+            ttl: None,
+        }
+    }
+}
+
+/// Result cache counters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResultCacheMetrics {
+    /// Calls answered from the cache
+    pub hits: u64,
+    
+    /// Calls that had to run the guest
+    pub misses: u64,
+    
+    /// Results evicted to stay within the size limits
+    pub evictions: u64,
+    
+    /// Results dropped because their TTL passed
+    pub expirations: u64,
+    
+    /// Results currently cached
+    pub entries: usize,
+    
+    /// Combined size of the cached results in bytes
+    pub bytes: usize,
+}
+
+impl ResultCacheMetrics {
+    /// Fraction of lookups answered from the cache, or 0 before any lookup
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// Identifies one call of a pure function
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    module: ModuleDigest,
+    function: String,
+    params: [u8; 32],
+}
+
+impl CacheKey {
+    /// Key for calling `function` of a module version with JSON parameters
+    pub fn new(module: ModuleDigest, function: &str, params_json: &str) -> Self {
+        Self {
+            module,
+            function: function.to_string(),
+            params: Sha256::digest(params_json.as_bytes()).into(),
+        }
+    }
+}
+
+struct CacheEntry {
+    result_json: String,
+    stored_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, CacheEntry>,
+    metrics: ResultCacheMetrics,
+    tick: u64,
+}
+
+/// Least-recently-used cache of JSON results
+pub struct ResultCache {
+    config: ResultCacheConfig,
+    state: Mutex<CacheState>,
+}
+
+impl ResultCache {
+    /// Create an empty cache
+    pub fn new(config: ResultCacheConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+    
+    /// Limits the cache was created with
+    pub fn config(&self) -> &ResultCacheConfig {
+        &self.config
+    }
+    
+    /// Look up a cached result, counting a hit or a miss
+    pub fn get(&self, key: &CacheKey) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        
+        let expired = match state.entries.get_mut(key) {
+            Some(entry) if !self.is_expired(entry) => {
+                entry.last_used = tick;
+                let result = entry.result_json.clone();
+                state.metrics.hits += 1;
+                return Some(result);
+            }
+            Some(_) => true,
+            None => false,
+        };
+        
+        if expired
+            && let Some(entry) = state.entries.remove(key)
+        {
+            state.metrics.bytes -= entry.result_json.len();
+            state.metrics.expirations += 1;
+        }
+        state.metrics.misses += 1;
+        None
+    }
+    
+    /// Cache a result, evicting least recently used results to make room
+    ///
+    /// Results larger than the whole cache are not stored.
+    pub fn insert(&self, key: CacheKey, result_json: String) {
+        if self.config.max_entries == 0 || result_json.len() > self.config.max_bytes {
+            return;
+        }
+        
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        if let Some(previous) = state.entries.remove(&key) {
+            state.metrics.bytes -= previous.result_json.len();
+        }
+        
+        while state.entries.len() >= self.config.max_entries
+            || state.metrics.bytes + result_json.len() > self.config.max_bytes
+        {
+            let Some(oldest) = state.entries.iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            let evicted = state.entries.remove(&oldest).expect("oldest entry exists");
+            state.metrics.bytes -= evicted.result_json.len();
+            state.metrics.evictions += 1;
+        }
+        
+        state.metrics.bytes += result_json.len();
+        let last_used = state.tick;
+        state.entries.insert(key, CacheEntry {
+            result_json,
+            stored_at: Instant::now(),
+            last_used,
+        });
+    }
+    
+    /// Drop every cached result
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.metrics.bytes = 0;
+    }
+    
+    /// Current counters
+    pub fn metrics(&self) -> ResultCacheMetrics {
+        let state = self.state.lock().unwrap();
+        ResultCacheMetrics {
+            entries: state.entries.len(),
+            ..state.metrics.clone()
+        }
+    }
+    
+    fn is_expired(&self, entry: &CacheEntry) -> bool {
+        self.config.ttl.is_some_and(|ttl| entry.stored_at.elapsed() >= ttl)
+    }
+}
+
+impl Default for ResultCache {
+    fn default() -> Self {
+        Self::new(ResultCacheConfig::default())
+    }
+}
+
+impl std::fmt::Debug for ResultCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResultCache")
+            .field("config", &self.config)
+            .field("metrics", &self.metrics())
+            .finish()
+    }
+}
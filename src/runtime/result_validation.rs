@@ -0,0 +1,177 @@
+//! Validation of guest results before they are deserialized
+//!
+//! A guest that returns the wrong shape of result normally surfaces as a
+//! deserialization error wherever the host happens to read it. Registering a
+//! [`ResultValidator`] for a module's function with
+//! [`crate::WasmSandbox::set_result_validator`] checks each result against a
+//! JSON Schema, or against a host type it must deserialize into, as soon as
+//! the guest returns it. A result that fails is rejected with
+//! [`crate::Error::InvalidResult`] listing what was wrong, and counted in the
+//! module's [`ResultValidationMetrics`].
+//!
+//! Results are validated as JSON whichever ABI the module uses, so MessagePack
+//! results are checked after they are decoded. Results answered from the
+//! result cache were validated when the guest first returned them.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+use crate::runtime::ModuleId;
+
+type Check = dyn Fn(&Value) -> std::result::Result<(), Vec<String>> + Send + Sync;
+
+/// Check applied to a function's results
+#[derive(Clone)]
+pub struct ResultValidator {
+    description: String,
+    check: Arc<Check>,
+}
+
+impl ResultValidator {
+    /// Require results to match a JSON Schema
+    pub fn json_schema(schema: &Value) -> Result<Self> {
+        let validator = jsonschema::validator_for(schema).map_err(|e| Error::InvalidInput {
+            field: "result schema".to_string(),
+            reason: e.to_string(),
+            suggestion: Some("Pass a valid JSON Schema document".to_string()),
+        })?;
+        Ok(Self {
+            description: "JSON Schema".to_string(),
+            check: Arc::new(move |result| {
+                let errors: Vec<String> = validator.iter_errors(result)
+                    .map(|error| match error.instance_path.to_string() {
+                        path if path.is_empty() => error.to_string(),
+                        path => format!("{}: {}", path, error),
+                    })
+                    .collect();
+                if errors.is_empty() { Ok(()) } else { Err(errors) }
+            }),
+        })
+    }
+    
+    /// Require results to deserialize into `T`
+    pub fn typed<T: DeserializeOwned>() -> Self {
+        Self {
+            description: std::any::type_name::<T>().to_string(),
+            check: Arc::new(|result| {
+                T::deserialize(result).map(drop).map_err(|e| vec![e.to_string()])
+            }),
+        }
+    }
+    
+    /// Check results with a function returning what is wrong with them
+    pub fn custom<F>(description: impl Into<String>, check: F) -> Self
+    where
+        F: Fn(&Value) -> std::result::Result<(), Vec<String>> + Send + Sync + 'static,
+    {
+        Self {
+            description: description.into(),
+            check: Arc::new(check),
+        }
+    }
+    
+    /// What the validator checks, for diagnostics
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+    
+    /// Check a result, returning what is wrong with it
+    pub fn validate(&self, result: &Value) -> std::result::Result<(), Vec<String>> {
+        (self.check)(result)
+    }
+}
+
+impl fmt::Debug for ResultValidator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResultValidator").field("description", &self.description).finish()
+    }
+}
+
+/// Counters for one module's validated results
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResultValidationMetrics {
+    /// Results checked
+    pub validated: u64,
+    
+    /// Results rejected
+    pub failed: u64,
+    
+    /// Results rejected, by function
+    pub failures_by_function: HashMap<String, u64>,
+}
+
+/// Validators registered for each module's functions
+#[derive(Default)]
+pub(crate) struct ResultValidators {
+    validators: RwLock<HashMap<ModuleId, HashMap<String, ResultValidator>>>,
+    metrics: Mutex<HashMap<ModuleId, ResultValidationMetrics>>,
+}
+
+impl ResultValidators {
+    /// Validate a module's function results with `validator`, replacing any before it
+    pub(crate) fn set(&self, module_id: ModuleId, function_name: &str, validator: ResultValidator) {
+        self.validators.write().unwrap()
+            .entry(module_id)
+            .or_default()
+            .insert(function_name.to_string(), validator);
+    }
+    
+    /// Stop validating a function's results, returning whether it was validated
+    pub(crate) fn remove(&self, module_id: ModuleId, function_name: &str) -> bool {
+        let mut validators = self.validators.write().unwrap();
+        let Some(functions) = validators.get_mut(&module_id) else {
+            return false;
+        };
+        let removed = functions.remove(function_name).is_some();
+        if functions.is_empty() {
+            validators.remove(&module_id);
+        }
+        removed
+    }
+    
+    /// Validator registered for a module's function
+    pub(crate) fn get(&self, module_id: ModuleId, function_name: &str) -> Option<ResultValidator> {
+        self.validators.read().unwrap().get(&module_id)?.get(function_name).cloned()
+    }
+    
+    /// Counters for every module with validated results
+    pub(crate) fn metrics(&self) -> HashMap<ModuleId, ResultValidationMetrics> {
+        self.metrics.lock().unwrap().clone()
+    }
+    
+    /// Check a JSON result if the function has a validator
+    ///
+    /// Results that aren't JSON are left for deserialization to report.
+    pub(crate) fn check_json(&self, module_id: ModuleId, function_name: &str, result_json: &str) -> Result<()> {
+        let Some(validator) = self.get(module_id, function_name) else {
+            return Ok(());
+        };
+        match serde_json::from_str(result_json) {
+            Ok(result) => self.check(module_id, function_name, &validator, &result),
+            Err(_) => Ok(()),
+        }
+    }
+    
+    /// Check a result with `validator`, counting the outcome against the module
+    pub(crate) fn check(&self, module_id: ModuleId, function_name: &str, validator: &ResultValidator, result: &Value) -> Result<()> {
+        let outcome = validator.validate(result);
+        let mut metrics = self.metrics.lock().unwrap();
+        let metrics = metrics.entry(module_id).or_default();
+        metrics.validated += 1;
+        let Err(errors) = outcome else {
+            return Ok(());
+        };
+        metrics.failed += 1;
+        *metrics.failures_by_function.entry(function_name.to_string()).or_default() += 1;
+        log::warn!("Module {} returned an invalid result from {}: {}", module_id, function_name, errors.join("; "));
+        Err(Error::InvalidResult {
+            function_name: function_name.to_string(),
+            errors,
+        })
+    }
+}
@@ -0,0 +1,412 @@
+//! Cooperative scheduling of many instances on one thread
+//!
+//! For hosts without threads to spare, a [`CooperativeScheduler`] runs every
+//! instance on the calling thread. Calls execute in fuel slices: each turn gives
+//! every instance with pending work exactly one slice, in a fixed rotation, so
+//! no instance runs twice before every other runnable instance has run once.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use wasi_common::{WasiCtx, sync::WasiCtxBuilder};
+use wasmtime::{Config, Engine, Instance, Linker, Module, RootScope, Store, Trap, Val};
+
+use crate::error::{Error, ResourceKind, Result};
+use crate::runtime::wasmtime::{block_on, to_host_value, to_val, widen};
+use crate::runtime::{HostValue, ModuleId};
+use crate::InstanceId;
+
+/// Fuel an instance may consume per turn by default
+pub const DEFAULT_FUEL_SLICE: u64 = 10_000;
+
+/// Configuration for a [`CooperativeScheduler`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchedulerConfig {
+    /// Fuel an instance may consume before yielding to the next one
+    pub fuel_slice: u64,
+    
+    /// Fuel a single call may consume in total (`None` for unlimited)
+    pub max_fuel_per_call: Option<u64>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            fuel_slice: DEFAULT_FUEL_SLICE,
+            max_fuel_per_call: None,
+        }
+    }
+}
+
+/// Identifier of a call submitted to a [`CooperativeScheduler`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TaskId(u64);
+
+/// Scheduling statistics for one instance
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SchedulingMetrics {
+    /// Fuel slices the instance has run
+    pub slices: u64,
+    
+    /// Calls that have finished, successfully or not
+    pub completed_calls: u64,
+    
+    /// Fuel consumed by finished calls
+    pub fuel_consumed: u64,
+    
+    /// Calls waiting behind the running one
+    pub queued_calls: usize,
+    
+    /// Total time spent runnable but waiting for a slice
+    pub total_wait: Duration,
+    
+    /// Longest time spent runnable but waiting for a slice
+    pub max_wait: Duration,
+}
+
+/// A call in progress, owning the instance's store until it finishes
+type CallFuture = Pin<Box<dyn Future<Output = (Store<WasiCtx>, wasmtime::Result<Vec<HostValue>>)> + Send>>;
+
+struct PendingCall {
+    task: TaskId,
+    function_name: String,
+    args: Vec<HostValue>,
+}
+
+struct RunningCall {
+    task: TaskId,
+    function_name: String,
+    future: CallFuture,
+}
+
+struct ScheduledInstance {
+    instance: Instance,
+    
+    /// Store, absent while a running call owns it
+    store: Option<Store<WasiCtx>>,
+    
+    running: Option<RunningCall>,
+    queue: VecDeque<PendingCall>,
+    
+    /// When the instance became runnable without having been given a slice
+    waiting_since: Option<Instant>,
+    
+    metrics: SchedulingMetrics,
+}
+
+impl ScheduledInstance {
+    fn is_runnable(&self) -> bool {
+        self.running.is_some() || !self.queue.is_empty()
+    }
+}
+
+/// Runs calls into many instances on a single thread in fuel slices
+///
+/// Submit calls with [`CooperativeScheduler::spawn`] and drive them with
+/// [`CooperativeScheduler::run_turn`] or [`CooperativeScheduler::run_until_idle`].
+/// Calls into the same instance run one after another in submission order.
+pub struct CooperativeScheduler {
+    engine: Engine,
+    linker: Linker<WasiCtx>,
+    config: SchedulerConfig,
+    modules: HashMap<ModuleId, Module>,
+    instances: HashMap<InstanceId, ScheduledInstance>,
+    
+    /// Fixed round-robin order of instances
+    rotation: Vec<InstanceId>,
+    
+    /// Results of finished calls not yet taken
+    results: HashMap<TaskId, Result<Vec<HostValue>>>,
+    
+    next_task: u64,
+}
+
+impl CooperativeScheduler {
+    /// Create a scheduler with its own engine
+    pub fn new(config: SchedulerConfig) -> Result<Self> {
+        if config.fuel_slice == 0 {
+            return Err(Error::config_error(
+                "fuel_slice must be greater than zero",
+                Some("Use DEFAULT_FUEL_SLICE unless calls need finer interleaving".to_string()),
+            ));
+        }
+        
+        let mut wasmtime_config = Config::new();
+        wasmtime_config.async_support(true);
+        wasmtime_config.consume_fuel(true);
+        let engine = Engine::new(&wasmtime_config).map_err(|e| Error::config_error(
+            format!("Failed to create Wasmtime engine: {}", e),
+            Some("Check Wasmtime configuration".to_string()),
+        ))?;
+        
+        let mut linker = Linker::new(&engine);
+        wasi_common::sync::add_to_linker(&mut linker, |ctx: &mut WasiCtx| ctx)
+            .map_err(|e| Error::RuntimeInitialization {
+                message: format!("Failed to add WASI to linker: {}", e),
+            })?;
+        
+        Ok(Self {
+            engine,
+            linker,
+            config,
+            modules: HashMap::new(),
+            instances: HashMap::new(),
+            rotation: Vec::new(),
+            results: HashMap::new(),
+            next_task: 0,
+        })
+    }
+    
+    /// Get the scheduler configuration
+    pub fn config(&self) -> &SchedulerConfig {
+        &self.config
+    }
+    
+    /// Compile a module from binary or text format
+    pub fn load_module(&mut self, wasm_bytes: &[u8]) -> Result<ModuleId> {
+        let module = Module::new(&self.engine, wasm_bytes).map_err(|e| Error::ModuleLoad {
+            message: format!("Failed to compile module: {}", e),
+        })?;
+        let module_id = ModuleId::new();
+        self.modules.insert(module_id, module);
+        Ok(module_id)
+    }
+    
+    /// Instantiate a module, running its start function to completion
+    pub fn create_instance(&mut self, module_id: ModuleId) -> Result<InstanceId> {
+        let module = self.modules.get(&module_id).ok_or_else(|| Error::NotFound {
+            resource_type: "module".to_string(),
+            identifier: module_id.to_string(),
+        })?;
+        
+        let mut store = Store::new(&self.engine, WasiCtxBuilder::new().build());
+        let instance_error = |e: wasmtime::Error| Error::InstanceCreation {
+            reason: format!("Failed to instantiate module: {}", e),
+            instance_id: None,
+        };
+        store.set_fuel(self.call_budget()).map_err(instance_error)?;
+        store.fuel_async_yield_interval(Some(self.config.fuel_slice)).map_err(instance_error)?;
+        let instance = block_on(self.linker.instantiate_async(&mut store, module)).map_err(instance_error)?;
+        
+        let instance_id = InstanceId::new();
+        self.instances.insert(instance_id, ScheduledInstance {
+            instance,
+            store: Some(store),
+            running: None,
+            queue: VecDeque::new(),
+            waiting_since: None,
+            metrics: SchedulingMetrics::default(),
+        });
+        self.rotation.push(instance_id);
+        Ok(instance_id)
+    }
+    
+    /// Remove an instance, failing its unfinished calls
+    pub fn remove_instance(&mut self, instance_id: InstanceId) -> bool {
+        let Some(mut instance) = self.instances.remove(&instance_id) else {
+            return false;
+        };
+        self.rotation.retain(|id| *id != instance_id);
+        
+        let unfinished = instance.running.take().map(|call| call.task)
+            .into_iter()
+            .chain(instance.queue.drain(..).map(|call| call.task));
+        for task in unfinished {
+            self.results.insert(task, Err(Error::Instance {
+                operation: "call".to_string(),
+                instance_id: Some(instance_id.as_uuid()),
+                reason: "Instance was removed before the call finished".to_string(),
+            }));
+        }
+        true
+    }
+    
+    /// Number of instances
+    pub fn instance_count(&self) -> usize {
+        self.instances.len()
+    }
+    
+    /// Queue a call to an exported function
+    ///
+    /// Numeric arguments are widened to the export's parameter types.
+    pub fn spawn(&mut self, instance_id: InstanceId, function_name: &str, args: &[HostValue]) -> Result<TaskId> {
+        let instance = self.instances.get_mut(&instance_id).ok_or_else(|| Error::NotFound {
+            resource_type: "instance".to_string(),
+            identifier: instance_id.to_string(),
+        })?;
+        
+        let task = TaskId(self.next_task);
+        self.next_task += 1;
+        if !instance.is_runnable() {
+            instance.waiting_since = Some(Instant::now());
+        }
+        instance.queue.push_back(PendingCall {
+            task,
+            function_name: function_name.to_string(),
+            args: args.to_vec(),
+        });
+        Ok(task)
+    }
+    
+    /// Give every runnable instance one fuel slice
+    ///
+    /// Returns the number of instances still runnable afterwards.
+    pub fn run_turn(&mut self) -> usize {
+        for instance_id in self.rotation.clone() {
+            self.run_slice(instance_id);
+        }
+        self.instances.values().filter(|instance| instance.is_runnable()).count()
+    }
+    
+    /// Run turns until no call is pending
+    pub fn run_until_idle(&mut self) {
+        while self.run_turn() > 0 {}
+    }
+    
+    /// Queue a call and run turns until it finishes
+    ///
+    /// Other instances keep receiving their slices while the call runs.
+    pub fn call(&mut self, instance_id: InstanceId, function_name: &str, args: &[HostValue]) -> Result<Vec<HostValue>> {
+        let task = self.spawn(instance_id, function_name, args)?;
+        loop {
+            if let Some(result) = self.take_result(task) {
+                return result;
+            }
+            self.run_turn();
+        }
+    }
+    
+    /// Take the result of a finished call
+    pub fn take_result(&mut self, task: TaskId) -> Option<Result<Vec<HostValue>>> {
+        self.results.remove(&task)
+    }
+    
+    /// Get scheduling statistics for an instance
+    pub fn metrics(&self, instance_id: InstanceId) -> Option<SchedulingMetrics> {
+        self.instances.get(&instance_id).map(|instance| SchedulingMetrics {
+            queued_calls: instance.queue.len(),
+            ..instance.metrics
+        })
+    }
+    
+    fn call_budget(&self) -> u64 {
+        self.config.max_fuel_per_call.unwrap_or(u64::MAX)
+    }
+    
+    /// Run one fuel slice of the instance's current call, starting the next queued one if idle
+    fn run_slice(&mut self, instance_id: InstanceId) {
+        let budget = self.call_budget();
+        let Some(instance) = self.instances.get_mut(&instance_id) else {
+            return;
+        };
+        
+        if instance.running.is_none() {
+            let Some(call) = instance.queue.pop_front() else {
+                return;
+            };
+            let mut store = instance.store.take().expect("idle instance owns its store");
+            if let Err(e) = store.set_fuel(budget) {
+                instance.store = Some(store);
+                self.results.insert(call.task, Err(call_error(&call.function_name, e)));
+                return;
+            }
+            instance.running = Some(RunningCall {
+                task: call.task,
+                function_name: call.function_name.clone(),
+                future: Box::pin(call_export(store, instance.instance, call.function_name, call.args)),
+            });
+        }
+        
+        if let Some(since) = instance.waiting_since.take() {
+            let wait = since.elapsed();
+            instance.metrics.total_wait += wait;
+            instance.metrics.max_wait = instance.metrics.max_wait.max(wait);
+        }
+        
+        let Some(running) = instance.running.as_mut() else {
+            return;
+        };
+        instance.metrics.slices += 1;
+        let mut cx = Context::from_waker(Waker::noop());
+        let Poll::Ready((store, result)) = running.future.as_mut().poll(&mut cx) else {
+            instance.waiting_since = Some(Instant::now());
+            return;
+        };
+        
+        let running = instance.running.take().expect("running call");
+        let fuel_consumed = budget - store.get_fuel().unwrap_or(budget);
+        instance.metrics.completed_calls += 1;
+        instance.metrics.fuel_consumed += fuel_consumed;
+        instance.store = Some(store);
+        if !instance.queue.is_empty() {
+            instance.waiting_since = Some(Instant::now());
+        }
+        
+        let result = result
+            .map_err(|e| match e.downcast_ref::<Trap>() {
+                Some(Trap::OutOfFuel) => Error::ResourceExhausted {
+                    kind: ResourceKind::Fuel,
+                    limit: budget,
+                    used: fuel_consumed,
+                    instance_id: Some(instance_id.as_uuid()),
+                    suggestion: Some("Raise max_fuel_per_call".to_string()),
+                },
+                _ => call_error(&running.function_name, e),
+            });
+        self.results.insert(running.task, result);
+    }
+}
+
+fn call_error(function_name: &str, e: wasmtime::Error) -> Error {
+    Error::FunctionCall {
+        function_name: function_name.to_string(),
+        reason: format!("Call failed: {:#}", e),
+    }
+}
+
+/// Call an export, handing the store back once the call finishes
+async fn call_export(
+    mut store: Store<WasiCtx>,
+    instance: Instance,
+    function_name: String,
+    args: Vec<HostValue>,
+) -> (Store<WasiCtx>, wasmtime::Result<Vec<HostValue>>) {
+    let result = call_in_store(&mut store, instance, &function_name, &args).await;
+    (store, result)
+}
+
+async fn call_in_store(
+    store: &mut Store<WasiCtx>,
+    instance: Instance,
+    function_name: &str,
+    args: &[HostValue],
+) -> wasmtime::Result<Vec<HostValue>> {
+    let func = instance
+        .get_func(&mut *store, function_name)
+        .ok_or_else(|| wasmtime::Error::msg("Function not found"))?;
+    let func_ty = func.ty(&*store);
+    if func_ty.params().len() != args.len() {
+        return Err(wasmtime::Error::msg(format!(
+            "Expected {} arguments, got {}",
+            func_ty.params().len(),
+            args.len()
+        )));
+    }
+    
+    // Externrefs made for the call are unrooted when the scope ends
+    let mut scope = RootScope::new(&mut *store);
+    let mut params = Vec::with_capacity(args.len());
+    for (arg, ty) in args.iter().copied().zip(func_ty.params()) {
+        params.push(to_val(&mut scope, widen(arg, &ty)).await?);
+    }
+    let mut results: Vec<Val> = func_ty.results()
+        .map(|ty| Val::default_for_ty(&ty).unwrap_or(Val::I32(0)))
+        .collect();
+    drop(func_ty);
+    
+    func.call_async(&mut scope, &params, &mut results).await?;
+    results.iter().map(|result| to_host_value(&scope, result)).collect()
+}
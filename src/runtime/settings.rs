@@ -0,0 +1,60 @@
+//! Per-installation plugin settings readable by the guest
+
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+/// Default cap on the serialized size of a settings document
+pub const DEFAULT_MAX_SETTINGS_BYTES: usize = 64 * 1024;
+
+/// A JSON settings document the guest reads through [`super::CONFIG_IMPORT_MODULE`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginSettings {
+    /// Settings document
+    pub document: Value,
+    
+    /// Maximum serialized size of the document in bytes
+    pub max_bytes: usize,
+}
+
+impl PluginSettings {
+    /// Create settings with the default size cap
+    pub fn new(document: Value) -> Self {
+        Self {
+            document,
+            max_bytes: DEFAULT_MAX_SETTINGS_BYTES,
+        }
+    }
+    
+    /// Set the maximum serialized size of the document
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+    
+    /// Check the document against the size cap
+    pub fn validate(&self) -> Result<()> {
+        let size = serde_json::to_vec(&self.document)?.len();
+        if size > self.max_bytes {
+            return Err(Error::ResourceLimit {
+                message: format!("Settings document is {} bytes, over the {} byte limit", size, self.max_bytes),
+            });
+        }
+        Ok(())
+    }
+    
+    /// Look up a dotted key such as `retry.max_attempts` or `servers.0.host`
+    ///
+    /// The empty key returns the whole document.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        if key.is_empty() {
+            return Some(&self.document);
+        }
+        
+        key.split('.').try_fold(&self.document, |value, part| match value {
+            Value::Object(map) => map.get(part),
+            Value::Array(items) => part.parse::<usize>().ok().and_then(|index| items.get(index)),
+            _ => None,
+        })
+    }
+}
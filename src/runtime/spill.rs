@@ -0,0 +1,160 @@
+//! Large guest results read out of guest memory in chunks
+//!
+//! A guest data ABI call leaves its output in guest memory. Rather than copying
+//! it to the host in one allocation, results over
+//! [`crate::InstanceConfig::max_inline_result_bytes`] are read a chunk at a time
+//! and deserialized as they arrive.
+
+use std::io::Read;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::runtime::WasmInstance;
+
+/// Default size above which results are spilled, and the chunk size used to read them
+pub const DEFAULT_MAX_INLINE_RESULT_BYTES: usize = 1024 * 1024;
+
+/// Output of a guest data ABI call, still in guest memory
+///
+/// Iterating yields the output in chunks; reading through [`Read`] buffers one
+/// chunk at a time. The output is only valid until the next call into the
+/// instance, which may reuse the memory.
+pub struct SpilledResult {
+    /// Instance whose memory holds the output
+    instance: Arc<dyn WasmInstance>,
+    
+    /// Start of the output in guest memory
+    offset: usize,
+    
+    /// Total output length
+    len: usize,
+    
+    /// Bytes of output already handed out
+    position: usize,
+    
+    /// Bytes read per chunk
+    chunk_size: usize,
+    
+    /// Current chunk for [`Read`] and how much of it has been consumed
+    buffer: Vec<u8>,
+    buffer_position: usize,
+}
+
+impl SpilledResult {
+    /// Call an export using the guest data ABI, leaving its output in guest memory
+    pub fn call(
+        instance: Arc<dyn WasmInstance>,
+        function_name: &str,
+        input: &[u8],
+        chunk_size: usize,
+    ) -> Result<Self> {
+        let output = instance.call_raw_region(function_name, input)?;
+        Self::at(instance, function_name, output, chunk_size)
+    }
+    
+    /// Async form of [`SpilledResult::call`]
+    pub async fn call_async(
+        instance: Arc<dyn WasmInstance>,
+        function_name: &str,
+        input: &[u8],
+        chunk_size: usize,
+    ) -> Result<Self> {
+        let output = instance.call_raw_region_async(function_name, input).await?;
+        Self::at(instance, function_name, output, chunk_size)
+    }
+    
+    /// Output left in guest memory at `(offset, len)` by a call to `function_name`
+    pub(crate) fn at(
+        instance: Arc<dyn WasmInstance>,
+        function_name: &str,
+        (offset, len): (usize, usize),
+        chunk_size: usize,
+    ) -> Result<Self> {
+        if offset.saturating_add(len) > instance.memory_size() {
+            return Err(Error::FunctionCall {
+                function_name: function_name.to_string(),
+                reason: format!("Output of {} bytes at offset {} is outside guest memory", len, offset),
+            });
+        }
+        Ok(Self {
+            instance,
+            offset,
+            len,
+            position: 0,
+            chunk_size: chunk_size.max(1),
+            buffer: Vec::new(),
+            buffer_position: 0,
+        })
+    }
+    
+    /// Total length of the output in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    
+    /// Whether the output is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    
+    /// Whether the output is larger than one chunk
+    pub fn is_spilled(&self) -> bool {
+        self.len > self.chunk_size
+    }
+    
+    /// Read the remaining output into one buffer
+    pub fn into_bytes(mut self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(self.len - self.position + self.buffer.len() - self.buffer_position);
+        bytes.extend_from_slice(&self.buffer[self.buffer_position..]);
+        for chunk in &mut self {
+            bytes.extend_from_slice(&chunk?);
+        }
+        Ok(bytes)
+    }
+}
+
+impl Iterator for SpilledResult {
+    type Item = Result<Vec<u8>>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.len {
+            return None;
+        }
+        
+        let size = self.chunk_size.min(self.len - self.position);
+        let chunk = self.instance.read_memory_at(self.offset + self.position, size);
+        self.position += size;
+        Some(chunk)
+    }
+}
+
+impl Read for SpilledResult {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.buffer_position >= self.buffer.len() {
+            match self.next() {
+                Some(chunk) => {
+                    self.buffer = chunk.map_err(|e| std::io::Error::other(e.to_string()))?;
+                    self.buffer_position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        
+        let available = &self.buffer[self.buffer_position..];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.buffer_position += count;
+        Ok(count)
+    }
+}
+
+/// Deserialize a JSON result without copying it out of guest memory in one piece
+pub(crate) fn deserialize<R>(function_name: &str, output: SpilledResult) -> Result<R>
+where
+    R: for<'de> serde::Deserialize<'de>,
+{
+    serde_json::from_reader(output).map_err(|e| Error::FunctionCall {
+        function_name: function_name.to_string(),
+        reason: format!("Failed to deserialize function result: {}", e),
+    })
+}
@@ -0,0 +1,104 @@
+//! Batteries-included host imports for common plugin needs
+//!
+//! Guests import these from [`STDLIB_IMPORT_MODULE`] instead of compiling
+//! clock, randomness, hashing and encoding crates into every module. They are
+//! opt-in: a module may only import them once the import policy allows them
+//! (see [`crate::security::imports::ImportPolicy::allow_host_stdlib`]).
+//!
+//! | Function | Signature | Result |
+//! |----------|-----------|--------|
+//! | `monotonic_now` | `() -> i64` | Nanoseconds since the instance was created |
+//! | `random_fill` | `(ptr, len) -> i32` | 0 after filling the buffer |
+//! | `uuid_v4` | `(out_ptr) -> i32` | 0 after writing 16 bytes |
+//! | `sha256` | `(ptr, len, out_ptr) -> i32` | 0 after writing 32 bytes |
+//! | `base64_encode` | `(ptr, len) -> i64` | `(ptr << 32) \| len` of the text |
+//! | `base64_decode` | `(ptr, len) -> i64` | `(ptr << 32) \| len` of the bytes |
+//!
+//! Failures return a negative [`GuestErrorCode`]. Randomness and UUIDs require
+//! a [`RandomCapability`] other than `None`.
+
+use std::time::Instant;
+
+use base64::Engine as _;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::runtime::error_codes::GuestErrorCode;
+use crate::security::RandomCapability;
+
+/// Host import module providing the standard host functions
+pub const STDLIB_IMPORT_MODULE: &str = "sandbox_std";
+
+/// Names of the functions in [`STDLIB_IMPORT_MODULE`]
+pub const STDLIB_FUNCTIONS: &[&str] = &[
+    "monotonic_now",
+    "random_fill",
+    "uuid_v4",
+    "sha256",
+    "base64_encode",
+    "base64_decode",
+];
+
+/// Per-instance state behind the standard host functions
+#[derive(Debug, Clone)]
+pub struct HostStdlib {
+    /// Reference point for the monotonic clock
+    started: Instant,
+    
+    /// Randomness the instance was granted
+    random: RandomCapability,
+}
+
+impl HostStdlib {
+    /// Create the state for an instance granted `random`
+    pub fn new(random: RandomCapability) -> Self {
+        Self {
+            started: Instant::now(),
+            random,
+        }
+    }
+    
+    /// Nanoseconds since the instance was created
+    pub fn monotonic_now(&self) -> i64 {
+        self.started.elapsed().as_nanos().min(i64::MAX as u128) as i64
+    }
+    
+    /// Fill `buf` with random bytes
+    pub fn random_fill(&self, buf: &mut [u8]) -> Result<(), GuestErrorCode> {
+        if self.random == RandomCapability::None {
+            return Err(GuestErrorCode::PermissionDenied);
+        }
+        rand::rng().fill_bytes(buf);
+        Ok(())
+    }
+    
+    /// Generate a random (version 4) UUID
+    pub fn uuid_v4(&self) -> Result<[u8; 16], GuestErrorCode> {
+        let mut bytes = [0; 16];
+        self.random_fill(&mut bytes)?;
+        Ok(*uuid::Builder::from_random_bytes(bytes).into_uuid().as_bytes())
+    }
+    
+    /// SHA-256 digest of `data`
+    pub fn sha256(&self, data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+    
+    /// Standard base64 encoding of `data`, with padding
+    pub fn base64_encode(&self, data: &[u8]) -> Vec<u8> {
+        base64::engine::general_purpose::STANDARD.encode(data).into_bytes()
+    }
+    
+    /// Decode standard base64 text
+    pub fn base64_decode(&self, text: &[u8]) -> Result<Vec<u8>, GuestErrorCode> {
+        base64::engine::general_purpose::STANDARD
+            .decode(text)
+            .map_err(|_| GuestErrorCode::InvalidInput)
+    }
+}
+
+impl Default for HostStdlib {
+    fn default() -> Self {
+        Self::new(RandomCapability::default())
+    }
+}
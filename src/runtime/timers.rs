@@ -0,0 +1,185 @@
+//! Timers armed by guests through [`super::TIMER_IMPORT_MODULE`]
+//!
+//! A guest sets a timer with a delay and an opaque token; once it is due the
+//! host calls the guest's [`super::TIMER_CALLBACK_EXPORT`] with the token. This
+//! lets plugins implement retry and backoff without busy-waiting. Timers only
+//! fire while the host drives them with [`crate::WasmSandbox::fire_due_timers`]
+//! or [`crate::WasmSandbox::run_timers`].
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+use crate::error::{Error, Result};
+use crate::runtime::TimerScheduler;
+use crate::InstanceId;
+
+/// Default cap on the timers an instance may have outstanding
+pub const DEFAULT_MAX_TIMERS: usize = 16;
+
+/// A timer whose delay has passed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DueTimer {
+    /// Instance that set the timer
+    pub instance_id: InstanceId,
+    
+    /// Identifier returned to the guest when the timer was set
+    pub timer_id: u64,
+    
+    /// Token passed back to the guest's callback
+    pub token: i64,
+}
+
+/// Outcome of calling a guest back for a due timer
+#[derive(Debug)]
+pub struct FiredTimer {
+    /// The timer that fired
+    pub timer: DueTimer,
+    
+    /// Result of the guest's callback
+    pub result: Result<()>,
+}
+
+struct PendingTimer {
+    instance_id: InstanceId,
+    token: i64,
+    due: Instant,
+}
+
+#[derive(Default)]
+struct TimerState {
+    pending: BTreeMap<u64, PendingTimer>,
+    next_id: u64,
+}
+
+/// Outstanding timers of every instance in a sandbox
+#[derive(Default)]
+pub struct TimerQueue {
+    state: Mutex<TimerState>,
+    changed: Notify,
+}
+
+impl TimerQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Arm a timer for an instance with at most `max_timers` outstanding
+    pub fn schedule(&self, instance_id: InstanceId, delay: Duration, token: i64, max_timers: usize) -> Result<u64> {
+        let mut state = self.state.lock().unwrap();
+        let outstanding = state.pending.values().filter(|timer| timer.instance_id == instance_id).count();
+        if outstanding >= max_timers {
+            return Err(Error::ResourceLimit {
+                message: format!("Instance {} already has {} timers outstanding", instance_id, outstanding),
+            });
+        }
+        
+        let timer_id = state.next_id;
+        state.next_id += 1;
+        state.pending.insert(timer_id, PendingTimer {
+            instance_id,
+            token,
+            due: Instant::now() + delay,
+        });
+        drop(state);
+        self.changed.notify_one();
+        Ok(timer_id)
+    }
+    
+    /// Disarm one of an instance's timers, returning whether it was outstanding
+    pub fn cancel(&self, instance_id: InstanceId, timer_id: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.pending.get(&timer_id) {
+            Some(timer) if timer.instance_id == instance_id => {
+                state.pending.remove(&timer_id);
+                true
+            }
+            _ => false,
+        }
+    }
+    
+    /// Remove and return every timer due at `now`, earliest first
+    pub fn take_due(&self, now: Instant) -> Vec<DueTimer> {
+        let mut state = self.state.lock().unwrap();
+        let mut due: Vec<_> = state.pending.iter()
+            .filter(|(_, timer)| timer.due <= now)
+            .map(|(timer_id, timer)| (timer.due, *timer_id))
+            .collect();
+        due.sort();
+        
+        due.into_iter()
+            .map(|(_, timer_id)| {
+                let timer = state.pending.remove(&timer_id).expect("due timer is pending");
+                DueTimer {
+                    instance_id: timer.instance_id,
+                    timer_id,
+                    token: timer.token,
+                }
+            })
+            .collect()
+    }
+    
+    /// When the earliest outstanding timer is due
+    pub fn next_due(&self) -> Option<Instant> {
+        self.state.lock().unwrap().pending.values().map(|timer| timer.due).min()
+    }
+    
+    /// Number of timers an instance has outstanding
+    pub fn outstanding(&self, instance_id: InstanceId) -> usize {
+        self.state.lock().unwrap().pending.values().filter(|timer| timer.instance_id == instance_id).count()
+    }
+    
+    /// Whether no timers are outstanding
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().unwrap().pending.is_empty()
+    }
+    
+    /// Disarm every timer of a removed instance
+    pub fn withdraw(&self, instance_id: InstanceId) {
+        self.state.lock().unwrap().pending.retain(|_, timer| timer.instance_id != instance_id);
+    }
+    
+    /// Wait until a timer is armed
+    pub(crate) async fn changed(&self) {
+        self.changed.notified().await;
+    }
+}
+
+impl std::fmt::Debug for TimerQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TimerQueue")
+            .field("pending", &self.state.lock().unwrap().pending.len())
+            .finish()
+    }
+}
+
+/// One instance's view of the sandbox's [`TimerQueue`]
+pub struct InstanceTimers {
+    queue: Arc<TimerQueue>,
+    instance_id: InstanceId,
+    max_timers: usize,
+}
+
+impl InstanceTimers {
+    /// Arm timers in `queue` on behalf of an instance
+    pub fn new(queue: Arc<TimerQueue>, instance_id: InstanceId, max_timers: usize) -> Self {
+        Self {
+            queue,
+            instance_id,
+            max_timers,
+        }
+    }
+}
+
+impl TimerScheduler for InstanceTimers {
+    fn set(&self, delay: Duration, token: i64) -> Result<u64> {
+        self.queue.schedule(self.instance_id, delay, token, self.max_timers)
+    }
+    
+    fn cancel(&self, timer_id: u64) -> bool {
+        self.queue.cancel(self.instance_id, timer_id)
+    }
+}
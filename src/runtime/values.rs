@@ -0,0 +1,168 @@
+//! Typed arguments and results of numeric exports
+//!
+//! [`crate::WasmSandbox::call_typed`] passes Rust values to an export and
+//! converts all of its results back, so exports taking `i64`, `f32`, `f64` or
+//! `v128` parameters and returning several values are called without going
+//! through JSON. Arguments are one [`WasmValue`] or a tuple of them; results
+//! are `()`, one value, or a tuple matching the export's results in number and
+//! type. A mismatch fails the call with both signatures spelled out.
+
+use crate::error::{Error, Result};
+use crate::runtime::HostValue;
+
+/// A Rust type passed to and from WebAssembly as one value
+pub trait WasmValue: Sized {
+    /// Name of the WebAssembly type the value is passed as
+    const TYPE_NAME: &'static str;
+    
+    /// Convert to a host value
+    fn into_host_value(self) -> HostValue;
+    
+    /// Convert from a host value of the same type
+    fn from_host_value(value: HostValue) -> Option<Self>;
+}
+
+macro_rules! wasm_value {
+    ($($ty:ty => $variant:ident, $name:literal;)*) => {
+        $(
+            impl WasmValue for $ty {
+                const TYPE_NAME: &'static str = $name;
+                
+                fn into_host_value(self) -> HostValue {
+                    HostValue::$variant(self)
+                }
+                
+                fn from_host_value(value: HostValue) -> Option<Self> {
+                    match value {
+                        HostValue::$variant(value) => Some(value),
+                        _ => None,
+                    }
+                }
+            }
+            
+            impl From<$ty> for HostValue {
+                fn from(value: $ty) -> Self {
+                    HostValue::$variant(value)
+                }
+            }
+            
+            impl TryFrom<HostValue> for $ty {
+                type Error = Error;
+                
+                fn try_from(value: HostValue) -> Result<Self> {
+                    <$ty as WasmValue>::from_host_value(value).ok_or_else(|| Error::InvalidInput {
+                        field: "value".to_string(),
+                        reason: format!("Expected {}, got {}", $name, value.type_name()),
+                        suggestion: None,
+                    })
+                }
+            }
+        )*
+    };
+}
+
+wasm_value! {
+    i32 => I32, "i32";
+    i64 => I64, "i64";
+    f32 => F32, "f32";
+    f64 => F64, "f64";
+    u128 => V128, "v128";
+}
+
+/// Arguments of a typed call
+pub trait IntoHostValues {
+    /// Convert to the values passed to the export
+    fn into_host_values(self) -> Vec<HostValue>;
+}
+
+/// Results of a typed call
+pub trait FromHostValues: Sized {
+    /// Names of the WebAssembly types of the results, in order
+    fn type_names() -> Vec<&'static str>;
+    
+    /// Convert from the values the export returned, if they match
+    fn from_host_values(values: &[HostValue]) -> Option<Self>;
+}
+
+impl IntoHostValues for Vec<HostValue> {
+    fn into_host_values(self) -> Vec<HostValue> {
+        self
+    }
+}
+
+impl<T: WasmValue> IntoHostValues for T {
+    fn into_host_values(self) -> Vec<HostValue> {
+        vec![self.into_host_value()]
+    }
+}
+
+impl<T: WasmValue> FromHostValues for T {
+    fn type_names() -> Vec<&'static str> {
+        vec![T::TYPE_NAME]
+    }
+    
+    fn from_host_values(values: &[HostValue]) -> Option<Self> {
+        match values {
+            [value] => T::from_host_value(*value),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! tuple_values {
+    ($(($($name:ident),*);)*) => {
+        $(
+            impl<$($name: WasmValue),*> IntoHostValues for ($($name,)*) {
+                #[allow(non_snake_case)]
+                fn into_host_values(self) -> Vec<HostValue> {
+                    let ($($name,)*) = self;
+                    vec![$($name.into_host_value()),*]
+                }
+            }
+            
+            impl<$($name: WasmValue),*> FromHostValues for ($($name,)*) {
+                fn type_names() -> Vec<&'static str> {
+                    vec![$($name::TYPE_NAME),*]
+                }
+                
+                #[allow(non_snake_case)]
+                fn from_host_values(values: &[HostValue]) -> Option<Self> {
+                    match values {
+                        [$($name),*] => Some(($($name::from_host_value(*$name)?,)*)),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+tuple_values! {
+    ();
+    (A);
+    (A, B);
+    (A, B, C);
+    (A, B, C, D);
+    (A, B, C, D, E);
+    (A, B, C, D, E, F);
+}
+
+/// Types of values as a signature, e.g. `(i32, f64)`
+pub fn signature(type_names: &[&str]) -> String {
+    format!("({})", type_names.join(", "))
+}
+
+/// Convert an export's results, failing with both signatures if they don't match
+pub(crate) fn convert_results<R: FromHostValues>(function_name: &str, values: &[HostValue]) -> Result<R> {
+    R::from_host_values(values).ok_or_else(|| {
+        let returned: Vec<&str> = values.iter().map(HostValue::type_name).collect();
+        Error::FunctionCall {
+            function_name: function_name.to_string(),
+            reason: format!(
+                "Export returns {} but the call expects {}",
+                signature(&returned),
+                signature(&R::type_names()),
+            ),
+        }
+    })
+}
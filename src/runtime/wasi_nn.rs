@@ -0,0 +1,428 @@
+//! WASI-NN inference with host-registered models
+//!
+//! Guests run inference through the `wasi_ephemeral_nn` imports (see
+//! [`crate::runtime::NN_IMPORT_MODULE`]) without bundling model weights: the
+//! host registers models by name in a [`ModelRegistry`] and guests load them
+//! with `load_by_name`. An instance may only load the models its
+//! [`MlCapability`] allows, every tensor it passes in or reads out is bounded
+//! by `max_tensor_bytes`, and the time its inferences take is charged against
+//! its `time_budget`. Each instance's inferences are accounted in
+//! [`MlUsage`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::error::Result;
+use crate::security::capabilities::ActiveCapabilities;
+use crate::security::MlCapability;
+use crate::InstanceId;
+
+/// Most graphs or execution contexts one instance may hold
+pub const MAX_NN_HANDLES: usize = 64;
+
+/// Most dimensions a guest's tensor may have
+pub const MAX_TENSOR_DIMENSIONS: usize = 16;
+
+/// Error codes returned to guests by the WASI-NN imports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum NnErrno {
+    /// A handle, index or tensor description was invalid
+    InvalidArgument = 1,
+    
+    /// The graph encoding is not supported
+    InvalidEncoding = 2,
+    
+    /// The instance has used up its inference time budget
+    Timeout = 3,
+    
+    /// The model failed to run
+    RuntimeError = 4,
+    
+    /// The operation is not supported by this host
+    UnsupportedOperation = 5,
+    
+    /// A tensor or the guest's buffer exceeded a limit
+    TooLarge = 6,
+    
+    /// No model is registered under the name
+    NotFound = 7,
+    
+    /// The instance is not allowed to load the model
+    Security = 8,
+}
+
+impl NnErrno {
+    /// Value returned to the guest
+    pub const fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+/// Element type of a tensor, numbered as in WASI-NN
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TensorType {
+    /// 16-bit float
+    F16,
+    
+    /// 32-bit float
+    F32,
+    
+    /// 64-bit float
+    F64,
+    
+    /// Unsigned byte
+    U8,
+    
+    /// 32-bit signed integer
+    I32,
+    
+    /// 64-bit signed integer
+    I64,
+}
+
+impl TensorType {
+    /// Type for a guest's type number
+    pub fn from_guest(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::F16),
+            1 => Some(Self::F32),
+            2 => Some(Self::F64),
+            3 => Some(Self::U8),
+            4 => Some(Self::I32),
+            5 => Some(Self::I64),
+            _ => None,
+        }
+    }
+    
+    /// Size of one element in bytes
+    pub fn element_size(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::F16 => 2,
+            Self::F32 | Self::I32 => 4,
+            Self::F64 | Self::I64 => 8,
+        }
+    }
+}
+
+/// A tensor exchanged with a model
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    /// Size of each dimension
+    pub dimensions: Vec<u32>,
+    
+    /// Element type
+    pub tensor_type: TensorType,
+    
+    /// Elements in row-major order, little-endian
+    pub data: Vec<u8>,
+}
+
+impl Tensor {
+    /// Create a tensor
+    pub fn new(dimensions: Vec<u32>, tensor_type: TensorType, data: Vec<u8>) -> Self {
+        Self { dimensions, tensor_type, data }
+    }
+    
+    /// Check the data holds exactly the elements the dimensions describe
+    pub fn is_consistent(&self) -> bool {
+        self.dimensions.iter()
+            .try_fold(self.tensor_type.element_size(), |size, &dimension| size.checked_mul(dimension as usize))
+            .is_some_and(|size| size == self.data.len())
+    }
+}
+
+/// A model the host runs on behalf of guests
+pub trait InferenceModel: Send + Sync {
+    /// Run the model on the guest's inputs, returning its outputs
+    fn infer(&self, inputs: &[Tensor]) -> Result<Vec<Tensor>>;
+}
+
+/// Closure running inference
+pub type InferenceFn = Box<dyn Fn(&[Tensor]) -> Result<Vec<Tensor>> + Send + Sync>;
+
+/// Adapter for inference engines the sandbox does not depend on
+///
+/// Wraps a closure that runs the model with the host's own engine, such as
+/// ONNX Runtime or a remote inference service.
+pub struct CallbackModel {
+    infer: InferenceFn,
+}
+
+impl CallbackModel {
+    /// Create a model calling `infer` for each inference
+    pub fn new<F>(infer: F) -> Self
+    where
+        F: Fn(&[Tensor]) -> Result<Vec<Tensor>> + Send + Sync + 'static,
+    {
+        Self { infer: Box::new(infer) }
+    }
+}
+
+impl InferenceModel for CallbackModel {
+    fn infer(&self, inputs: &[Tensor]) -> Result<Vec<Tensor>> {
+        (self.infer)(inputs)
+    }
+}
+
+impl fmt::Debug for CallbackModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackModel").finish_non_exhaustive()
+    }
+}
+
+/// Inference an instance has run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MlUsage {
+    /// Inferences that completed
+    pub inferences: u64,
+    
+    /// Inferences the model failed
+    pub failed_inferences: u64,
+    
+    /// Model loads refused by the instance's capability
+    pub denied_loads: u64,
+    
+    /// Input tensor bytes passed to models
+    pub input_bytes: u64,
+    
+    /// Output tensor bytes produced by models
+    pub output_bytes: u64,
+    
+    /// Time models spent running, charged against the time budget
+    pub compute_time: Duration,
+}
+
+/// Models available to guests, and the inference each instance has run
+#[derive(Default)]
+pub struct ModelRegistry {
+    models: RwLock<HashMap<String, Arc<dyn InferenceModel>>>,
+    usage: Mutex<HashMap<InstanceId, MlUsage>>,
+}
+
+impl ModelRegistry {
+    /// Create a registry with no models
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Make a model loadable under `name`, replacing any model of that name
+    ///
+    /// Instances that already loaded the old model keep running it.
+    pub fn register(&self, name: &str, model: Arc<dyn InferenceModel>) {
+        self.models.write().unwrap().insert(name.to_string(), model);
+    }
+    
+    /// Stop a model from being loaded, returning whether it was registered
+    pub fn unregister(&self, name: &str) -> bool {
+        self.models.write().unwrap().remove(name).is_some()
+    }
+    
+    /// Names of the registered models
+    pub fn model_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.models.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+    
+    /// Inference an instance has run
+    pub fn usage(&self, instance_id: InstanceId) -> MlUsage {
+        self.usage.lock().unwrap().get(&instance_id).copied().unwrap_or_default()
+    }
+    
+    /// Drop an instance's accounting
+    pub(crate) fn forget(&self, instance_id: InstanceId) {
+        self.usage.lock().unwrap().remove(&instance_id);
+    }
+    
+    fn model(&self, name: &str) -> Option<Arc<dyn InferenceModel>> {
+        self.models.read().unwrap().get(name).cloned()
+    }
+    
+    fn account(&self, instance_id: InstanceId, update: impl FnOnce(&mut MlUsage)) {
+        update(self.usage.lock().unwrap().entry(instance_id).or_default());
+    }
+}
+
+impl fmt::Debug for ModelRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModelRegistry")
+            .field("models", &self.model_names())
+            .finish()
+    }
+}
+
+/// Host side of one instance's WASI-NN imports
+///
+/// Graph and execution context handles are indexes into per-instance tables.
+pub trait InferenceHost: Send + Sync {
+    /// Largest tensor the guest may pass in or read out
+    fn max_tensor_bytes(&self) -> u64;
+    
+    /// Load a host-registered model, returning its graph handle
+    fn load_by_name(&self, name: &str) -> std::result::Result<u32, NnErrno>;
+    
+    /// Create an execution context for a graph
+    fn init_execution_context(&self, graph: u32) -> std::result::Result<u32, NnErrno>;
+    
+    /// Set an input of an execution context
+    fn set_input(&self, context: u32, index: u32, tensor: Tensor) -> std::result::Result<(), NnErrno>;
+    
+    /// Run the context's model on its inputs
+    fn compute(&self, context: u32) -> std::result::Result<(), NnErrno>;
+    
+    /// Read an output of the context's latest computation
+    fn get_output(&self, context: u32, index: u32) -> std::result::Result<Tensor, NnErrno>;
+}
+
+struct ExecutionContext {
+    model: Arc<dyn InferenceModel>,
+    inputs: Vec<Option<Tensor>>,
+    outputs: Vec<Tensor>,
+}
+
+#[derive(Default)]
+struct Session {
+    graphs: Vec<Arc<dyn InferenceModel>>,
+    contexts: Vec<ExecutionContext>,
+}
+
+/// Serves one instance's WASI-NN imports from a shared registry
+pub(crate) struct InstanceInference {
+    registry: Arc<ModelRegistry>,
+    instance_id: InstanceId,
+    capabilities: ActiveCapabilities,
+    session: Mutex<Session>,
+}
+
+impl InstanceInference {
+    /// Serve requests made by `instance_id`
+    pub(crate) fn new(registry: Arc<ModelRegistry>, instance_id: InstanceId, capabilities: ActiveCapabilities) -> Self {
+        Self {
+            registry,
+            instance_id,
+            capabilities,
+            session: Mutex::new(Session::default()),
+        }
+    }
+    
+    fn capability(&self) -> MlCapability {
+        self.capabilities.current().ml
+    }
+}
+
+impl InferenceHost for InstanceInference {
+    fn max_tensor_bytes(&self) -> u64 {
+        self.capability().max_tensor_bytes
+    }
+    
+    fn load_by_name(&self, name: &str) -> std::result::Result<u32, NnErrno> {
+        if !self.capability().allows(name) {
+            self.registry.account(self.instance_id, |usage| usage.denied_loads += 1);
+            log::warn!("Instance {} denied loading model {}", self.instance_id, name);
+            return Err(NnErrno::Security);
+        }
+        let model = self.registry.model(name).ok_or(NnErrno::NotFound)?;
+        
+        let mut session = self.session.lock().unwrap();
+        if session.graphs.len() >= MAX_NN_HANDLES {
+            return Err(NnErrno::TooLarge);
+        }
+        session.graphs.push(model);
+        Ok(session.graphs.len() as u32 - 1)
+    }
+    
+    fn init_execution_context(&self, graph: u32) -> std::result::Result<u32, NnErrno> {
+        let mut session = self.session.lock().unwrap();
+        let model = session.graphs.get(graph as usize).cloned().ok_or(NnErrno::InvalidArgument)?;
+        if session.contexts.len() >= MAX_NN_HANDLES {
+            return Err(NnErrno::TooLarge);
+        }
+        session.contexts.push(ExecutionContext {
+            model,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        });
+        Ok(session.contexts.len() as u32 - 1)
+    }
+    
+    fn set_input(&self, context: u32, index: u32, tensor: Tensor) -> std::result::Result<(), NnErrno> {
+        if tensor.data.len() as u64 > self.max_tensor_bytes() {
+            return Err(NnErrno::TooLarge);
+        }
+        if !tensor.is_consistent() || index as usize >= MAX_NN_HANDLES {
+            return Err(NnErrno::InvalidArgument);
+        }
+        
+        let mut session = self.session.lock().unwrap();
+        let context = session.contexts.get_mut(context as usize).ok_or(NnErrno::InvalidArgument)?;
+        let index = index as usize;
+        if context.inputs.len() <= index {
+            context.inputs.resize(index + 1, None);
+        }
+        context.inputs[index] = Some(tensor);
+        Ok(())
+    }
+    
+    fn compute(&self, context: u32) -> std::result::Result<(), NnErrno> {
+        let capability = self.capability();
+        let (model, inputs) = {
+            let session = self.session.lock().unwrap();
+            let context = session.contexts.get(context as usize).ok_or(NnErrno::InvalidArgument)?;
+            let inputs = context.inputs.iter().cloned().collect::<Option<Vec<Tensor>>>()
+                .ok_or(NnErrno::InvalidArgument)?;
+            (context.model.clone(), inputs)
+        };
+        if let Some(budget) = capability.time_budget
+            && self.registry.usage(self.instance_id).compute_time >= budget
+        {
+            return Err(NnErrno::Timeout);
+        }
+        
+        // The model runs without the session lock; a budget overrun is only seen afterwards
+        let started = Instant::now();
+        let result = model.infer(&inputs);
+        let elapsed = started.elapsed();
+        let input_bytes: u64 = inputs.iter().map(|tensor| tensor.data.len() as u64).sum();
+        
+        let outputs = match result {
+            Ok(outputs) if outputs.iter().all(|tensor| tensor.data.len() as u64 <= capability.max_tensor_bytes) => outputs,
+            Ok(_) => {
+                self.registry.account(self.instance_id, |usage| {
+                    usage.failed_inferences += 1;
+                    usage.compute_time += elapsed;
+                });
+                return Err(NnErrno::TooLarge);
+            }
+            Err(e) => {
+                log::debug!("Inference for instance {} failed: {}", self.instance_id, e);
+                self.registry.account(self.instance_id, |usage| {
+                    usage.failed_inferences += 1;
+                    usage.compute_time += elapsed;
+                });
+                return Err(NnErrno::RuntimeError);
+            }
+        };
+        let output_bytes: u64 = outputs.iter().map(|tensor| tensor.data.len() as u64).sum();
+        self.registry.account(self.instance_id, |usage| {
+            usage.inferences += 1;
+            usage.input_bytes += input_bytes;
+            usage.output_bytes += output_bytes;
+            usage.compute_time += elapsed;
+        });
+        
+        if let Some(context) = self.session.lock().unwrap().contexts.get_mut(context as usize) {
+            context.outputs = outputs;
+        }
+        Ok(())
+    }
+    
+    fn get_output(&self, context: u32, index: u32) -> std::result::Result<Tensor, NnErrno> {
+        let session = self.session.lock().unwrap();
+        let context = session.contexts.get(context as usize).ok_or(NnErrno::InvalidArgument)?;
+        context.outputs.get(index as usize).cloned().ok_or(NnErrno::InvalidArgument)
+    }
+}
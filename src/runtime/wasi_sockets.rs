@@ -0,0 +1,139 @@
+//! WASI preview2 sockets governed by [`NetworkCapability`]
+//!
+//! Components get the standard `wasi:sockets` interfaces, but every bind,
+//! connect and outgoing datagram is checked against the instance's network
+//! capability before the host performs it.
+
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+
+use wasmtime_wasi::p2::WasiCtxBuilder;
+use wasmtime_wasi::SocketAddrUse;
+
+use crate::security::capabilities::NetworkVerifier;
+use crate::security::{EnforcementMode, NetworkCapability, PortRange};
+
+/// Socket access policy derived from a [`NetworkCapability`]
+#[derive(Debug, Clone)]
+pub struct SocketPolicy {
+    /// Capability the policy enforces
+    capability: NetworkCapability,
+    
+    /// Addresses the allowlisted hosts resolved to, with their port ranges
+    resolved: Vec<(IpAddr, Option<PortRange>)>,
+    
+    /// Whether denied operations fail or are only logged
+    enforcement: EnforcementMode,
+}
+
+impl SocketPolicy {
+    /// Create a policy, resolving allowlisted host names to addresses
+    ///
+    /// Hosts that fail to resolve are skipped; connections to them are denied.
+    pub fn new(capability: NetworkCapability) -> Self {
+        let mut resolved = Vec::new();
+        if let NetworkCapability::AllowedHosts(hosts) = &capability {
+            for spec in hosts {
+                match resolve_host(&spec.host) {
+                    Ok(addrs) => resolved.extend(addrs.into_iter().map(|ip| (ip, spec.ports.clone()))),
+                    Err(e) => log::warn!("Allowed host {} did not resolve: {}", spec.host, e),
+                }
+            }
+        }
+        
+        Self { capability, resolved, enforcement: EnforcementMode::Enforce }
+    }
+    
+    /// Set whether denied operations fail or are only logged
+    pub fn with_enforcement(mut self, enforcement: EnforcementMode) -> Self {
+        self.enforcement = enforcement;
+        self
+    }
+    
+    /// Get the capability this policy enforces
+    pub fn capability(&self) -> &NetworkCapability {
+        &self.capability
+    }
+    
+    /// Whether guests may open sockets at all
+    pub fn allows_sockets(&self) -> bool {
+        self.enforcement == EnforcementMode::Audit || !matches!(self.capability, NetworkCapability::None)
+    }
+    
+    /// Whether guests may use `wasi:sockets/ip-name-lookup`
+    ///
+    /// Lookups cannot be filtered by name, so resolved addresses are still
+    /// subject to [`SocketPolicy::check`] when the guest connects. Components
+    /// with [`NetworkCapability::AllowedDomains`] get no lookups and no remote
+    /// connections, as only core modules can resolve through the host.
+    pub fn allows_name_lookup(&self) -> bool {
+        self.enforcement == EnforcementMode::Audit || matches!(
+            self.capability,
+            NetworkCapability::AllowedHosts(_) | NetworkCapability::AllowedPorts(_) | NetworkCapability::Full
+        )
+    }
+    
+    /// Check a socket operation against the capability
+    pub fn check(&self, addr: SocketAddr, usage: SocketAddrUse) -> bool {
+        match usage {
+            SocketAddrUse::TcpBind | SocketAddrUse::UdpBind => self.allows_bind(addr),
+            SocketAddrUse::TcpConnect | SocketAddrUse::UdpConnect | SocketAddrUse::UdpOutgoingDatagram => {
+                self.allows_remote(addr)
+            }
+        }
+    }
+    
+    /// Install the policy on a WASI context builder
+    pub fn apply(self: Arc<Self>, builder: &mut WasiCtxBuilder) {
+        builder
+            .allow_tcp(self.allows_sockets())
+            .allow_udp(self.allows_sockets())
+            .allow_ip_name_lookup(self.allows_name_lookup());
+        
+        builder.socket_addr_check(move |addr, usage| {
+            let allowed = self.check(addr, usage) || match self.enforcement {
+                EnforcementMode::Enforce => {
+                    log::warn!("Denied {:?} to {}: not permitted by network capability", usage, addr);
+                    false
+                }
+                EnforcementMode::Audit => {
+                    log::warn!("Allowed {:?} to {} in audit mode: not permitted by network capability", usage, addr);
+                    true
+                }
+            };
+            Box::pin(async move { allowed })
+        });
+    }
+    
+    /// Local binds are limited to ephemeral ports unless the capability grants the port
+    fn allows_bind(&self, addr: SocketAddr) -> bool {
+        let ip = addr.ip();
+        match &self.capability {
+            NetworkCapability::None => false,
+            NetworkCapability::Loopback => ip.is_loopback(),
+            NetworkCapability::AllowedHosts(_) | NetworkCapability::AllowedDomains(_) => {
+                addr.port() == 0 && (ip.is_unspecified() || ip.is_loopback())
+            }
+            NetworkCapability::AllowedPorts(ports) => {
+                addr.port() == 0 || ports.iter().any(|range| range.contains(addr.port()))
+            }
+            NetworkCapability::Full => true,
+        }
+    }
+    
+    fn allows_remote(&self, addr: SocketAddr) -> bool {
+        match &self.capability {
+            NetworkCapability::AllowedHosts(_) => self.resolved.iter().any(|(ip, ports)| {
+                *ip == addr.ip() && ports.as_ref().map_or(true, |range| range.contains(addr.port()))
+            }),
+            capability => NetworkVerifier::new(capability.clone()).is_socket_allowed(addr),
+        }
+    }
+}
+
+fn resolve_host(host: &str) -> std::io::Result<Vec<IpAddr>> {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(vec![ip]);
+    }
+    Ok((host, 0).to_socket_addrs()?.map(|addr| addr.ip()).collect())
+}
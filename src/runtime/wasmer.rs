@@ -4,14 +4,17 @@ use std::any::Any;
 use std::sync::{Arc, RwLock};
 use std::collections::HashMap;
 
-use wasmer::{Engine, Module, Store, Instance, Value, Memory, imports};
+use wasmer::{Engine, ExternType, Module, Store, Instance, Value, Memory, imports};
 
 use crate::error::{Error, Result};
 use crate::runtime::{
     ModuleId, RuntimeConfig, RuntimeMetrics, WasmInstanceState,
     WasmInstance, WasmModule, WasmRuntime, WasmFunctionCaller, WasmFunctionCallerAsync
 };
+use crate::runtime::engine::RuntimeEngine;
+use crate::runtime::features::RuntimeFeatures;
 use crate::security::{Capabilities, ResourceLimits};
+use crate::security::imports::{ImportKind, ModuleImport};
 
 /// Wasmer module implementation
 pub struct WasmerModule {
@@ -74,6 +77,22 @@ impl WasmModule for WasmerModule {
         self.exports.clone()
     }
 
+    fn imports(&self) -> Vec<ModuleImport> {
+        self.module.imports()
+            .map(|import| {
+                let kind = match import.ty() {
+                    ExternType::Function(_) => ImportKind::Function,
+                    ExternType::Memory(_) => ImportKind::Memory,
+                    ExternType::Table(_) => ImportKind::Table,
+                    ExternType::Global(_) => ImportKind::Global,
+                    #[allow(unreachable_patterns)]
+                    _ => ImportKind::Other,
+                };
+                ModuleImport::new(import.module(), import.name(), kind)
+            })
+            .collect()
+    }
+
     fn clone_module(&self) -> Box<dyn WasmModule> {
         Box::new(Self {
             id: self.id,
@@ -130,6 +149,14 @@ impl WasmerInstance {
     fn get_memory(&self) -> Option<Memory> {
         self.instance.exports.get_memory("memory").ok().cloned()
     }
+
+    /// Get the memory instance, failing if the module doesn't export one
+    fn exported_memory(&self) -> Result<Memory> {
+        self.get_memory().ok_or_else(|| Error::InstanceCreation {
+            reason: "Memory export not found".to_string(),
+            instance_id: Some(self.module_id.as_uuid()),
+        })
+    }
 }
 
 impl WasmInstance for WasmerInstance {
@@ -137,11 +164,11 @@ impl WasmInstance for WasmerInstance {
         *self.state.read().unwrap()
     }
 
-    fn memory_usage(&self) -> usize {
+    fn memory_usage(&self) -> u64 {
         if let Some(memory) = self.get_memory() {
             let store = self.store.read().unwrap();
             let size = memory.view(&store).size();
-            size.bytes().0
+            size.bytes().0 as u64
         } else {
             0
         }
@@ -179,8 +206,29 @@ impl WasmInstance for WasmerInstance {
         }
     }
 
+    fn visit_memory(&self, f: &mut dyn FnMut(&[u8])) -> Result<()> {
+        let memory = self.exported_memory()?;
+        // Calls take the store's write lock, so the guest can't run or grow the memory while it is borrowed
+        let store = self.store.read().unwrap();
+        let view = memory.view(&store);
+        // SAFETY: the store's read lock is held until `f` returns, so nothing writes the memory
+        f(unsafe { view.data_unchecked() });
+        Ok(())
+    }
+
+    // The write lock keeps other readers out while the memory is borrowed mutably
+    #[allow(clippy::readonly_write_lock)]
+    fn visit_memory_mut(&self, f: &mut dyn FnMut(&mut [u8])) -> Result<()> {
+        let memory = self.exported_memory()?;
+        let store = self.store.write().unwrap();
+        let view = memory.view(&store);
+        // SAFETY: the store's write lock is held until `f` returns, so nothing else accesses the memory
+        f(unsafe { view.data_unchecked_mut() });
+        Ok(())
+    }
+
     fn memory_size(&self) -> usize {
-        self.memory_usage()
+        self.memory_usage() as usize
     }
 
     fn function_caller(&self) -> Box<dyn WasmFunctionCaller> {
@@ -311,6 +359,7 @@ impl WasmerRuntime {
             fuel_consumption_rate: None,
             cache_hit_rate: None,
             last_compilation_time_ms: None,
+            pool: None,
         };
         
         Ok(Self {
@@ -387,6 +436,10 @@ impl WasmRuntime for WasmerRuntime {
         modules.keys().cloned().collect()
     }
     
+    fn engine(&self) -> RuntimeEngine {
+        RuntimeEngine::Wasmer
+    }
+    
     fn create_instance(
         &self,
         module: &dyn WasmModule,
@@ -429,6 +482,11 @@ impl WasmRuntime for WasmerRuntime {
         Ok(Box::new(wasmer_instance))
     }
     
+    fn features(&self) -> RuntimeFeatures {
+        // Instances can't be metered, interrupted or snapshotted yet
+        RuntimeFeatures::none("wasmer")
+    }
+    
     fn get_metrics(&self) -> RuntimeMetrics {
         self.metrics.read().unwrap().clone()
     }
@@ -1,18 +1,68 @@
 //! Wasmtime runtime implementation
 
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex, RwLock};
+use std::future::{poll_fn, Future};
+use std::io::Write;
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+use std::pin::pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::Thread;
+use std::time::{Duration, Instant};
 
 use dashmap::DashMap;
-use wasmtime::{Engine, Module, Store, Linker, Config, Val, Memory, Instance};
-use wasi_common::{WasiCtx, sync::WasiCtxBuilder};
+use tokio::sync::Notify;
+use wasmtime::{
+    AsContext, AsContextMut, Caller, Engine, ExternRef, ExternType, Module, RootScope, Store, Linker, Config, Val, Memory, Instance,
+    ResourceLimiter, InstanceAllocationStrategy, PoolingAllocationConfig, StoreContextMut, Trap, TypedFunc, UpdateDeadline,
+    WasmBacktrace, WasmCoreDump,
+};
+use wasi_common::{WasiCtx, WasiDir, sync::{ambient_authority, dir::Dir as SyncDir, Dir, WasiCtxBuilder}};
+use wiggle::GuestMemory;
 
-use crate::error::{Error, Result};
+use crate::error::{Error, ResourceKind, Result};
 use crate::runtime::{
-    ModuleId, RuntimeConfig, RuntimeMetrics, 
+    FuelState, ModuleId, ModuleMetadata, RuntimeConfig, RuntimeMetrics, MemoryPages, PoolMetrics, ResultSink, WASM_PAGE_SIZE,
+    STREAM_IMPORT_MODULE, STREAM_EMIT_FUNCTION, ServiceDispatcher, GrowthObserver, GuestInterrupt, SecretResolver, GUEST_ALLOC_EXPORT,
+    SERVICE_IMPORT_MODULE, SERVICE_CALL_FUNCTION, CONFIG_IMPORT_MODULE, CONFIG_GET_FUNCTION, CONFIG_KEY_MISSING,
+    SECRETS_IMPORT_MODULE, SECRETS_GET_FUNCTION, DNS_IMPORT_MODULE, DNS_RESOLVE_FUNCTION, DnsLookup, HTTP_IMPORT_MODULE, HTTP_FETCH_FUNCTION, HttpFetch, TIMER_IMPORT_MODULE, TIMER_SET_FUNCTION, TIMER_CANCEL_FUNCTION,
+    LOG_IMPORT_MODULE, LOG_WRITE_FUNCTION, LOG_CALL_ID_FUNCTION, LOG_DIAGNOSTIC_FUNCTION,
+    NN_IMPORT_MODULE, NN_LOAD_FUNCTION, NN_LOAD_BY_NAME_FUNCTION, NN_INIT_EXECUTION_CONTEXT_FUNCTION,
+    NN_SET_INPUT_FUNCTION, NN_COMPUTE_FUNCTION, NN_GET_OUTPUT_FUNCTION,
+    CHILD_IMPORT_MODULE, CHILD_SPAWN_FUNCTION, CHILD_CALL_FUNCTION, CHILD_KILL_FUNCTION, ChildSpawner,
+    CHECKPOINT_IMPORT_MODULE, CHECKPOINT_FUNCTION, PROGRESS_IMPORT_MODULE, PROGRESS_REPORT_FUNCTION,
+    ALLOC_IMPORT_MODULE, ALLOC_FAILED_FUNCTION, TimerScheduler, EntropySource, GuestCall, HostFunctions, HostValue,
     WasmInstance, WasmInstanceState, WasmModule, WasmRuntime, WasmFunctionCaller, WasmFunctionCallerAsync
 };
-use crate::security::{Capabilities, ResourceLimits};
+use crate::runtime::environment::{EnvironmentLayer, MaterializedEnvironment};
+use crate::runtime::read_only_dir::ReadOnlyDir;
+use crate::runtime::handles::Handle;
+use crate::runtime::abi::AbiKind;
+use crate::runtime::values;
+use crate::runtime::wit;
+use crate::runtime::cache_bundle::ArtifactTarget;
+use crate::runtime::compilation::{EngineSettings, ModuleCompiler};
+use crate::runtime::result_cache::{module_digest, ModuleDigest};
+use crate::runtime::error_codes::GuestErrorCode;
+use crate::runtime::call_context::{current_call_id, CallId};
+use crate::runtime::checkpoint::CheckpointSink;
+use crate::runtime::coredump::CoredumpSink;
+use crate::runtime::engine::RuntimeEngine;
+use crate::runtime::features::RuntimeFeatures;
+use crate::runtime::fuel_budget::{FuelRefiller, RefillDecision, RefillRequest};
+use crate::runtime::diagnostics::{Diagnostic, MAX_DIAGNOSTIC_BYTES};
+use crate::runtime::guest_log::{level_from_guest, GuestLogSink};
+use crate::runtime::io_scheduler::{IoDirection, IoScheduler};
+use crate::runtime::metrics::{DetailedMetrics, MetricsRecorder};
+use crate::runtime::profiling::{frame_name, FoldedStacks, ProfileSink, ProfilingStrategy};
+use crate::runtime::progress::{ProgressSink, MAX_PROGRESS_MESSAGE_BYTES};
+use crate::runtime::wasi_nn::{InferenceHost, NnErrno, Tensor, TensorType, MAX_TENSOR_DIMENSIONS};
+use crate::runtime::stdlib::{HostStdlib, STDLIB_IMPORT_MODULE};
+use crate::runtime::settings::PluginSettings;
+use crate::security::{Capabilities, FuelSchedule, ResourceLimits};
+use crate::security::imports::{ImportKind, ImportPolicy, ImportReport, LinkReport, ModuleImport};
 // Removed unused imports
 
 /// Wasmtime module implementation
@@ -31,6 +81,12 @@ pub struct WasmtimeModule {
     
     /// Module size in bytes
     size: usize,
+    
+    /// Calling convention detected at load time
+    abi: AbiKind,
+    
+    /// Custom section metadata read at load time
+    metadata: ModuleMetadata,
 }
 
 impl WasmtimeModule {
@@ -44,13 +100,17 @@ impl WasmtimeModule {
             exports.push(export.name().to_string());
         }
         
-        Self {
+        let mut module = Self {
             id: ModuleId::new(),
             name: None,
             module,
             exports,
             size: wasm_bytes.len(),
-        }
+            abi: AbiKind::Custom,
+            metadata: ModuleMetadata::read(wasm_bytes),
+        };
+        module.abi = AbiKind::detect(&module.imports(), &module.exports, &module.metadata.custom_sections);
+        module
     }
     
     /// Get a reference to the Wasmtime module
@@ -76,6 +136,22 @@ impl WasmModule for WasmtimeModule {
         self.exports.clone()
     }
     
+    fn imports(&self) -> Vec<ModuleImport> {
+        self.module.imports()
+            .map(|import| {
+                let kind = match import.ty() {
+                    ExternType::Func(_) => ImportKind::Function,
+                    ExternType::Memory(_) => ImportKind::Memory,
+                    ExternType::Table(_) => ImportKind::Table,
+                    ExternType::Global(_) => ImportKind::Global,
+                    #[allow(unreachable_patterns)]
+                    _ => ImportKind::Other,
+                };
+                ModuleImport::new(import.module(), import.name(), kind)
+            })
+            .collect()
+    }
+    
     fn clone_module(&self) -> Box<dyn WasmModule> {
         Box::new(Self {
             id: self.id,
@@ -83,9 +159,19 @@ impl WasmModule for WasmtimeModule {
             module: self.module.clone(),
             exports: self.exports.clone(),
             size: self.size,
+            abi: self.abi,
+            metadata: self.metadata.clone(),
         })
     }
     
+    fn abi(&self) -> AbiKind {
+        self.abi
+    }
+    
+    fn metadata(&self) -> ModuleMetadata {
+        self.metadata.clone()
+    }
+    
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
@@ -101,19 +187,836 @@ pub struct WasmtimeStoreData {
     
     /// Cached memory export
     memory: Option<Memory>,
+    
+    /// Fuel cost schedule
+    fuel_schedule: FuelSchedule,
+    
+    /// Fuel granted to the instance so far (if fuel is enabled)
+    granted_fuel: Option<u64>,
+    
+    /// Tracks peak memory growth
+    memory_tracker: MemoryTracker,
+    
+    /// Fixture files backing the environment layer, removed with the store
+    _environment: Option<MaterializedEnvironment>,
+    
+    /// Receiver of partial results during a streaming call
+    stream_sink: Option<ResultSink>,
+    
+    /// Handler for the guest's brokered service calls
+    service_dispatcher: Option<Arc<dyn ServiceDispatcher>>,
+    
+    /// Settings document the guest reads through the settings import
+    settings: Option<Arc<PluginSettings>>,
+    
+    /// State behind the standard host functions
+    stdlib: HostStdlib,
+    
+    /// Resolver for the guest's secret requests
+    secrets: Option<Arc<dyn SecretResolver>>,
+    
+    /// Resolver for the guest's domain names
+    dns: Option<Arc<dyn DnsLookup>>,
+    
+    /// Sends the guest's HTTP requests
+    http: Option<Arc<dyn HttpFetch>>,
+    
+    /// Arms the timers the guest sets
+    timers: Option<Arc<dyn TimerScheduler>>,
+    
+    /// Receives the lines the guest logs
+    guest_log: Option<Arc<dyn GuestLogSink>>,
+    
+    /// Receives the progress the guest reports
+    progress: Option<Arc<dyn ProgressSink>>,
+    
+    /// Receives coredumps of trapped calls, when the instance keeps them
+    coredumps: Option<Arc<dyn CoredumpSink>>,
+    
+    /// Tops up the fuel of calls that run low
+    fuel_refills: Option<Arc<CallRefills>>,
+    
+    /// Paces the guest's file reads and writes, when the sandbox shares its disk
+    io: Option<Arc<dyn IoScheduler>>,
+    
+    /// Samples the guest's stack during calls, when the instance is profiled
+    profiling: Option<Arc<CallProfiling>>,
+    
+    /// Writes the checkpoints the guest requests, when the instance may checkpoint
+    checkpoints: Option<Arc<GuestCheckpoints>>,
+    
+    /// Serves the guest's WASI-NN imports
+    inference: Option<Arc<dyn InferenceHost>>,
+    
+    /// Starts and calls the guest's child instances
+    child_spawner: Option<Arc<dyn ChildSpawner>>,
+    
+    /// Set to make the running call trap at the next epoch
+    interrupt_requested: Arc<AtomicBool>,
+    
+    /// Latest allocation the guest reported failing
+    alloc_failure: Option<AllocFailure>,
+}
+
+/// Interrupts an instance by flagging its store and advancing the engine epoch
+///
+/// Advancing the epoch makes every running store check its flag once, so only
+/// the flagged instance traps.
+struct EpochInterrupt {
+    requested: Arc<AtomicBool>,
+    engine: Engine,
+}
+
+impl GuestInterrupt for EpochInterrupt {
+    fn interrupt(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        self.engine.increment_epoch();
+    }
+    
+    fn clear(&self) {
+        self.requested.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Where a guest's checkpoints go and which of its globals they save
+struct GuestCheckpoints {
+    sink: Arc<dyn CheckpointSink>,
+    /// Names of the instance's exported mutable globals
+    globals: Vec<String>,
+}
+
+/// Memory of the calling instance
+fn caller_memory(caller: &mut Caller<'_, WasmtimeStoreData>) -> wasmtime::Result<Memory> {
+    caller.data().memory
+        .or_else(|| caller.get_export("memory").and_then(|export| export.into_memory()))
+        .ok_or_else(|| wasmtime::Error::msg("host import requires an exported memory"))
+}
+
+/// Copy `len` bytes at `ptr` out of the calling instance's memory
+fn read_caller_bytes(
+    caller: &Caller<'_, WasmtimeStoreData>,
+    memory: Memory,
+    ptr: i32,
+    len: i32,
+) -> wasmtime::Result<Vec<u8>> {
+    let mut bytes = vec![0u8; len as u32 as usize];
+    memory.read(caller, ptr as u32 as usize, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// WASI's `EAGAIN`, returned for file operations that would wait too long for the disk
+const WASI_ERRNO_AGAIN: i32 = 6;
+
+/// Most iovecs of one WASI read or write counted towards its size
+const MAX_COUNTED_IOVECS: u32 = 1024;
+
+/// Bytes a WASI read or write asks to move, summed from its iovecs
+///
+/// Iovecs that can't be read count as nothing; WASI reports the fault itself.
+fn iovec_bytes(caller: &mut Caller<'_, WasmtimeStoreData>, iovs: i32, iovs_len: i32) -> u64 {
+    let Ok(memory) = caller_memory(caller) else {
+        return 0;
+    };
+    let mut iovecs = vec![0u8; (iovs_len as u32).min(MAX_COUNTED_IOVECS) as usize * 8];
+    if memory.read(&*caller, iovs as u32 as usize, &mut iovecs).is_err() {
+        return 0;
+    }
+    iovecs.chunks_exact(8)
+        .map(|iovec| u32::from_le_bytes(iovec[4..].try_into().expect("four bytes")) as u64)
+        .sum()
+}
+
+/// Wait `duration`, yielding to the executor when there is one
+async fn pause(duration: Duration) {
+    match tokio::runtime::Handle::try_current() {
+        Ok(_) => tokio::time::sleep(duration).await,
+        Err(_) => std::thread::sleep(duration),
+    }
+}
+
+/// Wait for the caller's share of the disk before a file read or write
+///
+/// Returns the errno to fail the operation with instead.
+async fn pace(
+    caller: &mut Caller<'_, WasmtimeStoreData>,
+    direction: IoDirection,
+    fd: i32,
+    iovs: i32,
+    iovs_len: i32,
+) -> Option<i32> {
+    let io = caller.data().io.clone().filter(|_| fd > 2)?;
+    match io.reserve(direction, iovec_bytes(caller, iovs, iovs_len)) {
+        Some(wait) if !wait.is_zero() => pause(wait).await,
+        Some(_) => {}
+        None => return Some(WASI_ERRNO_AGAIN),
+    }
+    None
+}
+
+/// Pack a guest slice into the data ABI's `(ptr << 32) | len`
+fn pack_guest_slice(ptr: u32, len: u32) -> i64 {
+    (((ptr as u64) << 32) | len as u64) as i64
+}
+
+/// Copy bytes into the calling guest through its `alloc` export, returning the packed slice
+async fn copy_to_caller(caller: &mut Caller<'_, WasmtimeStoreData>, memory: Memory, bytes: &[u8]) -> wasmtime::Result<i64> {
+    let alloc = caller.get_export(GUEST_ALLOC_EXPORT)
+        .and_then(|export| export.into_func())
+        .ok_or_else(|| wasmtime::Error::msg(format!("host imports returning data require an `{}` export", GUEST_ALLOC_EXPORT)))?
+        .typed::<i32, i32>(&*caller)?;
+    let ptr = alloc.call_async(&mut *caller, bytes.len() as i32).await?;
+    memory.write(&mut *caller, ptr as u32 as usize, bytes)?;
+    Ok(pack_guest_slice(ptr as u32, bytes.len() as u32))
+}
+
+/// Read a WASI-NN tensor description at `ptr` and the data it points to
+///
+/// The description is `{dims_ptr: u32, dims_len: u32, type: u8, data_ptr: u32, data_len: u32}`
+/// with the usual 4-byte alignment.
+fn read_caller_tensor(
+    caller: &Caller<'_, WasmtimeStoreData>,
+    memory: Memory,
+    ptr: i32,
+    max_bytes: u64,
+) -> wasmtime::Result<std::result::Result<Tensor, NnErrno>> {
+    let header = read_caller_bytes(caller, memory, ptr, 20)?;
+    let field = |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+    let (dims_ptr, dims_len, data_ptr, data_len) = (field(0), field(4), field(12), field(16));
+    
+    let Some(tensor_type) = TensorType::from_guest(header[8]) else {
+        return Ok(Err(NnErrno::InvalidArgument));
+    };
+    if dims_len as usize > MAX_TENSOR_DIMENSIONS {
+        return Ok(Err(NnErrno::InvalidArgument));
+    }
+    if data_len as u64 > max_bytes {
+        return Ok(Err(NnErrno::TooLarge));
+    }
+    
+    let dimensions = read_caller_bytes(caller, memory, dims_ptr as i32, (dims_len * 4) as i32)?
+        .chunks_exact(4)
+        .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+    let data = read_caller_bytes(caller, memory, data_ptr as i32, data_len as i32)?;
+    Ok(Ok(Tensor::new(dimensions, tensor_type, data)))
+}
+
+/// Split a data ABI return value into `(ptr, len)`
+fn unpack_guest_slice(packed: i64) -> (usize, usize) {
+    let packed = packed as u64;
+    ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize)
+}
+
+/// Resource limiter that records the peak size of any memory in the store
+///
+/// Memory growth is refused beyond `max_bytes` or when the growth observer, if
+/// any, refuses it. Tables are capped at `max_tables` of `max_table_elements`
+/// each; growing a table past the cap traps with [`Error::ResourceExhausted`].
+#[derive(Default)]
+struct MemoryTracker {
+    /// Largest memory size reached so far, in bytes
+    peak_bytes: u64,
+    
+    /// Largest size any memory may grow to, in bytes
+    max_bytes: Option<u64>,
+    
+    /// Most tables the store may hold
+    max_tables: Option<usize>,
+    
+    /// Largest size any table may grow to, in elements
+    max_table_elements: Option<u64>,
+    
+    /// Approves growth requests
+    observer: Option<Arc<dyn GrowthObserver>>,
+}
+
+impl ResourceLimiter for MemoryTracker {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        if self.max_bytes.is_some_and(|max_bytes| desired as u64 > max_bytes) {
+            return Ok(false);
+        }
+        if let Some(observer) = &self.observer
+            && !observer.memory_growing(current, desired)
+        {
+            return Ok(false);
+        }
+        self.peak_bytes = self.peak_bytes.max(desired as u64);
+        Ok(true)
+    }
+    
+    fn table_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        if let Some(max_table_elements) = self.max_table_elements.filter(|max| desired as u64 > *max) {
+            return Err(table_limit_error(max_table_elements, desired as u64).into());
+        }
+        Ok(self.observer.as_ref().is_none_or(|observer| observer.table_growing(current, desired)))
+    }
+    
+    fn tables(&self) -> usize {
+        self.max_tables.unwrap_or(wasmtime::DEFAULT_TABLE_LIMIT)
+    }
+}
+
+/// Error for a table larger than the instance's limit
+fn table_limit_error(limit: u64, used: u64) -> Error {
+    Error::ResourceExhausted {
+        kind: ResourceKind::Table,
+        limit,
+        used,
+        instance_id: None,
+        suggestion: Some("Raise MemoryLimits::max_table_elements or shrink the guest's tables".to_string()),
+    }
+}
+
+/// Error for a failed call, passing through limits the store's limiter enforced
+fn call_failed(function_name: &str, error: anyhow::Error) -> Error {
+    match error.downcast_ref::<Error>() {
+        Some(limit @ Error::ResourceExhausted { .. }) => limit.clone(),
+        // The coredump is the outermost context of a trap; leave it out of the message
+        _ if error.downcast_ref::<WasmCoreDump>().is_some() => Error::FunctionCall {
+            function_name: function_name.to_string(),
+            reason: format!(
+                "Call failed: {}",
+                error.chain().skip(1).map(ToString::to_string).collect::<Vec<_>>().join(": "),
+            ),
+        },
+        _ => Error::FunctionCall {
+            function_name: function_name.to_string(),
+            reason: format!("Call failed: {:#}", error),
+        },
+    }
+}
+
+/// An allocation the guest reported through [`ALLOC_IMPORT_MODULE`]
+#[derive(Debug, Clone, Copy)]
+struct AllocFailure {
+    /// Bytes requested
+    size: u64,
+    
+    /// Call the allocation was made in
+    call_id: Option<CallId>,
+}
+
+/// Fuel and memory measured as a guest call begins
+#[derive(Debug, Clone, Copy)]
+struct CallStart {
+    /// Fuel before the call
+    fuel: Option<u64>,
+    
+    /// Fuel the call starts with, in units of the schedule's instruction weight
+    call_fuel: Option<u64>,
+    
+    /// Fuel too small to make up a weighted unit, handed back after the call
+    remainder: u64,
+    
+    /// Memory pages before the call
+    pages: u64,
+}
+
+/// Error for a call the guest failed after running out of memory
+fn guest_out_of_memory(store: &Store<WasmtimeStoreData>, failure: AllocFailure) -> Error {
+    let memory = store.data().memory;
+    let memory_bytes = memory.map_or(0, |memory| memory.data_size(store) as u64);
+    // Without a configured limit, the memory can grow to its declared maximum
+    // or as far as its index type addresses
+    let declared_limit = memory.map(|memory| {
+        let ty = memory.ty(store);
+        ty.maximum()
+            .map(|pages| pages.saturating_mul(ty.page_size()))
+            .unwrap_or(if ty.is_64() { u64::MAX } else { 1 << 32 })
+    });
+    let limit = match (store.data().memory_tracker.max_bytes, declared_limit) {
+        (Some(configured), Some(declared)) => configured.min(declared),
+        (configured, declared) => configured.or(declared).unwrap_or(1 << 32),
+    };
+    Error::ResourceExhausted {
+        kind: ResourceKind::Memory,
+        limit,
+        used: memory_bytes.saturating_add(failure.size),
+        instance_id: None,
+        suggestion: Some(format!(
+            "The guest could not allocate {} bytes; raise the instance's memory limit or allocate less",
+            failure.size,
+        )),
+    }
+}
+
+/// Map a failed guest call to an error, writing a coredump first if the guest trapped
+fn call_trapped(store: &mut Store<WasmtimeStoreData>, function_name: &str, error: anyhow::Error) -> Error {
+    let written = match (error.downcast_ref::<WasmCoreDump>(), store.data().coredumps.clone()) {
+        (Some(coredump), Some(sink)) => write_coredump(store, sink.as_ref(), function_name, coredump),
+        _ => None,
+    };
+    // Rust guests abort on `unreachable` when an allocation fails
+    let failure = store.data_mut().alloc_failure.take()
+        .filter(|failure| failure.call_id == current_call_id());
+    if let (Some(failure), Some(Trap::UnreachableCodeReached)) = (failure, error.downcast_ref::<Trap>()) {
+        return guest_out_of_memory(store, failure);
+    }
+    match (call_failed(function_name, error), written) {
+        (Error::FunctionCall { function_name, reason }, Some(path)) => Error::FunctionCall {
+            function_name,
+            reason: format!("{} (coredump written to {})", reason, path.display()),
+        },
+        (error, _) => error,
+    }
+}
+
+/// Serialize a coredump into `sink`, unless the guest's memories exceed its cap
+fn write_coredump(
+    store: &mut Store<WasmtimeStoreData>,
+    sink: &dyn CoredumpSink,
+    function_name: &str,
+    coredump: &WasmCoreDump,
+) -> Option<PathBuf> {
+    let memory_bytes: u64 = coredump.memories().iter().map(|memory| memory.data_size(&*store) as u64).sum();
+    if memory_bytes > sink.max_bytes() {
+        log::warn!(
+            "Not writing a coredump for {}: its {} bytes of memory exceed the {} byte cap",
+            function_name, memory_bytes, sink.max_bytes(),
+        );
+        return None;
+    }
+    let bytes = coredump.serialize(&mut *store, function_name);
+    if bytes.len() as u64 > sink.max_bytes() {
+        log::warn!("Not writing a {} byte coredump for {}: over the {} byte cap", bytes.len(), function_name, sink.max_bytes());
+        return None;
+    }
+    sink.write(function_name, &bytes)
+}
+
+/// Fuel top-ups for the call running in a store
+///
+/// The store's epoch callback asks the refiller for fuel when the call runs
+/// low. The call future advances the engine's epoch each time the guest
+/// yields, so the callback runs at least once per yield, and holds a suspended
+/// call back until it may ask again.
+struct CallRefills {
+    refiller: Arc<dyn FuelRefiller>,
+    engine: Engine,
+    
+    /// Fuel the running call started with, in units of the instruction weight
+    start_fuel: AtomicU64,
+    
+    /// Fuel added to the running call, in units of the instruction weight
+    added: AtomicU64,
+    
+    /// Set once the running call has been refused, so it isn't asked again
+    denied: AtomicBool,
+    
+    /// When a suspended call may ask again
+    resume_at: Mutex<Option<Instant>>,
+}
+
+impl CallRefills {
+    /// Start tracking a call that begins with `fuel`, if the store tops up calls
+    fn begin(store: impl AsContext<Data = WasmtimeStoreData>, fuel: Option<u64>) -> Option<Arc<Self>> {
+        let refills = store.as_context().data().fuel_refills.clone()?;
+        refills.start_fuel.store(fuel?, Ordering::Relaxed);
+        refills.added.store(0, Ordering::Relaxed);
+        refills.denied.store(false, Ordering::Relaxed);
+        *refills.resume_at.lock().unwrap() = None;
+        Some(refills)
+    }
+    
+    /// Top up or suspend the call if it is low on fuel
+    ///
+    /// The refiller sees fuel in real units, scaled up from the weighted units
+    /// the store holds during a call.
+    fn check(&self, context: &mut StoreContextMut<'_, WasmtimeStoreData>) -> wasmtime::Result<Option<UpdateDeadline>> {
+        let Ok(remaining) = context.get_fuel() else {
+            return Ok(None);
+        };
+        let weight = context.data().fuel_schedule.instruction_weight.max(1);
+        if remaining.saturating_mul(weight) >= self.refiller.low_water() || self.denied.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        
+        let added = self.added.load(Ordering::Relaxed);
+        let consumed = self.start_fuel.load(Ordering::Relaxed).saturating_add(added).saturating_sub(remaining);
+        let request = RefillRequest {
+            consumed: consumed.saturating_mul(weight),
+            added: added.saturating_mul(weight),
+        };
+        match self.refiller.refill(request) {
+            RefillDecision::Grant(fuel) => {
+                context.set_fuel(remaining.saturating_add(fuel / weight))?;
+                let data = context.data_mut();
+                data.granted_fuel = data.granted_fuel.map(|granted| granted.saturating_add(fuel));
+                self.added.fetch_add(fuel / weight, Ordering::Relaxed);
+                Ok(Some(UpdateDeadline::Yield(1)))
+            }
+            RefillDecision::Wait(delay) => {
+                // The deadline is set once the call resumes, so ask again straight away
+                *self.resume_at.lock().unwrap() = Some(Instant::now() + delay);
+                Ok(Some(UpdateDeadline::Yield(0)))
+            }
+            RefillDecision::Deny => {
+                self.denied.store(true, Ordering::Relaxed);
+                Ok(None)
+            }
+        }
+    }
+    
+    /// When a suspended call may ask again, if that is still to come
+    fn suspended_until(&self) -> Option<Instant> {
+        let resume_at = *self.resume_at.lock().unwrap();
+        resume_at.filter(|resume_at| *resume_at > Instant::now())
+    }
+}
+
+/// Stack samples of the call running in a store
+///
+/// The store's epoch callback takes a sample each time the engine's epoch
+/// advances. While a call runs, a ticker thread advances the epoch every
+/// sampling interval; interrupts and yields advance it too, so samples come at
+/// least that often.
+struct CallProfiling {
+    sink: Arc<dyn ProfileSink>,
+    engine: Engine,
+    
+    /// Stacks sampled during the running call
+    stacks: Mutex<FoldedStacks>,
+    
+    /// Fuel left at the previous sample
+    last_fuel: Mutex<Option<u64>>,
+}
+
+impl CallProfiling {
+    /// Start sampling a call that begins with `fuel`, if the store is profiled
+    fn begin(store: impl AsContext<Data = WasmtimeStoreData>, fuel: Option<u64>) -> Option<ProfiledCall> {
+        let profiling = store.as_context().data().profiling.clone()?;
+        *profiling.stacks.lock().unwrap() = FoldedStacks::default();
+        *profiling.last_fuel.lock().unwrap() = fuel;
+        
+        let stop = Arc::new(AtomicBool::new(false));
+        let ticker = {
+            let stop = stop.clone();
+            let engine = profiling.engine.clone();
+            let interval = profiling.sink.interval();
+            std::thread::Builder::new()
+                .name("wasm-sandbox-profiler".to_string())
+                .spawn(move || loop {
+                    std::thread::park_timeout(interval);
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    engine.increment_epoch();
+                })
+        };
+        let ticker = match ticker {
+            Ok(ticker) => Some(ticker),
+            Err(e) => {
+                log::warn!("Failed to start the profiler ticker, sampling only on yields: {}", e);
+                None
+            }
+        };
+        Some(ProfiledCall { profiling, stop, ticker, started: Instant::now() })
+    }
+    
+    /// Charge the fuel burned since the previous sample to the guest's current stack
+    fn sample(&self, context: &StoreContextMut<'_, WasmtimeStoreData>) {
+        let backtrace = WasmBacktrace::capture(context);
+        if backtrace.frames().is_empty() {
+            return;
+        }
+        let stack = backtrace.frames().iter().rev()
+            .map(|frame| frame_name(frame.func_name(), frame.func_index()))
+            .collect::<Vec<_>>()
+            .join(";");
+        
+        // Refills raise the remaining fuel; the interval they land in is charged nothing
+        let fuel = context.get_fuel().ok();
+        let mut last_fuel = self.last_fuel.lock().unwrap();
+        let burned = last_fuel.zip(fuel).map(|(before, now)| before.saturating_sub(now)).unwrap_or(0);
+        *last_fuel = fuel;
+        let burned = burned.saturating_mul(context.data().fuel_schedule.instruction_weight.max(1));
+        self.stacks.lock().unwrap().add(&stack, burned);
+    }
+}
+
+/// A call being sampled, whose ticker stops when it is finished or dropped
+struct ProfiledCall {
+    profiling: Arc<CallProfiling>,
+    stop: Arc<AtomicBool>,
+    ticker: Option<std::thread::JoinHandle<()>>,
+    started: Instant,
+}
+
+impl ProfiledCall {
+    /// Stop sampling and hand the call's samples to the sink
+    fn finish(mut self, function_name: &str) {
+        self.stop_ticker();
+        let stacks = std::mem::take(&mut *self.profiling.stacks.lock().unwrap());
+        self.profiling.sink.record(function_name, self.started.elapsed(), stacks);
+    }
+    
+    /// Stop advancing the epoch for this call
+    fn stop_ticker(&mut self) {
+        if let Some(ticker) = self.ticker.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            ticker.thread().unpark();
+            let _ = ticker.join();
+        }
+    }
+}
+
+impl Drop for ProfiledCall {
+    fn drop(&mut self) {
+        self.stop_ticker();
+    }
+}
+
+/// Run a guest call, keeping its fuel topped up through `refills`
+async fn refilling<F: Future>(refills: Option<Arc<CallRefills>>, call: F) -> F::Output {
+    let Some(refills) = refills else {
+        return call.await;
+    };
+    let mut call = pin!(call);
+    poll_fn(|cx| {
+        if let Some(resume_at) = refills.suspended_until() {
+            wake_at(cx.waker().clone(), resume_at);
+            return Poll::Pending;
+        }
+        let poll = call.as_mut().poll(cx);
+        if poll.is_pending() {
+            refills.engine.increment_epoch();
+        }
+        poll
+    })
+    .await
+}
+
+/// Wake `waker` at `resume_at`
+fn wake_at(waker: Waker, resume_at: Instant) {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn(async move {
+                tokio::time::sleep_until(resume_at.into()).await;
+                waker.wake();
+            });
+        }
+        // Without an executor there is nothing else for this thread to do
+        Err(_) => {
+            std::thread::sleep(resume_at.saturating_duration_since(Instant::now()));
+            waker.wake();
+        }
+    }
+}
+
+/// Runtime driving guest calls made through the synchronous API
+///
+/// Its worker thread runs the IO and timer drivers, so host imports awaiting
+/// IO make progress while the calling thread waits for the guest.
+fn sync_runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .thread_name("wasm-sandbox-sync")
+            .build()
+            .expect("Failed to start the runtime for synchronous guest calls")
+    })
+}
+
+/// Wakes a thread parked in [`block_on`]
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Drive a guest call made through the synchronous API to completion
+///
+/// Outside a tokio runtime the call is run by [`sync_runtime`]. A thread that
+/// is already in a runtime can't block on another one, so the call is polled
+/// here with [`sync_runtime`] entered instead: IO the guest's host imports
+/// start is driven by its worker rather than the runtime this thread belongs
+/// to, which may be waiting on this very thread. Async callers use the async
+/// forms of the calls and never get here.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    if tokio::runtime::Handle::try_current().is_err() {
+        return sync_runtime().block_on(future);
+    }
+    let _entered = sync_runtime().enter();
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+/// An instance's store, which an async call takes for as long as it runs
+///
+/// Synchronous access waits for the store to be handed back, so inspecting an
+/// instance while an async call into it is suspended blocks until the call
+/// finishes.
+struct StoreSlot {
+    store: Mutex<Option<Store<WasmtimeStoreData>>>,
+    returned: Condvar,
+    returned_async: Notify,
+}
+
+impl StoreSlot {
+    fn new(store: Store<WasmtimeStoreData>) -> Self {
+        Self {
+            store: Mutex::new(Some(store)),
+            returned: Condvar::new(),
+            returned_async: Notify::new(),
+        }
+    }
+    
+    /// Lock the store, waiting while an async call has it
+    fn lock(&self) -> LockedStore<'_> {
+        let store = self.store.lock().unwrap();
+        LockedStore(self.returned.wait_while(store, |store| store.is_none()).unwrap())
+    }
+    
+    /// Take the store until the returned lease is dropped
+    async fn lend(&self) -> LentStore<'_> {
+        loop {
+            let returned = self.returned_async.notified();
+            let store = self.store.lock().unwrap().take();
+            if let Some(store) = store {
+                return LentStore { slot: self, store: Some(store) };
+            }
+            returned.await;
+        }
+    }
+}
+
+/// The store, locked for synchronous access
+struct LockedStore<'a>(MutexGuard<'a, Option<Store<WasmtimeStoreData>>>);
+
+impl Deref for LockedStore<'_> {
+    type Target = Store<WasmtimeStoreData>;
+    
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref().expect("a locked store is never lent")
+    }
+}
+
+impl DerefMut for LockedStore<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0.as_mut().expect("a locked store is never lent")
+    }
+}
+
+/// Writes a call's input into the guest memory allocated for it
+struct GuestInputWriter<'a> {
+    store: &'a mut Store<WasmtimeStoreData>,
+    memory: Memory,
+    offset: usize,
+    remaining: usize,
+}
+
+impl Write for GuestInputWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.len() > self.remaining {
+            return Err(std::io::Error::other("Input is longer than its allocation"));
+        }
+        self.memory.write(&mut *self.store, self.offset, buf).map_err(std::io::Error::other)?;
+        self.offset += buf.len();
+        self.remaining -= buf.len();
+        Ok(buf.len())
+    }
+    
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Fill the `len` bytes of input allocated at `ptr` with `write`
+fn fill_guest_input(
+    store: &mut Store<WasmtimeStoreData>,
+    function_name: &str,
+    memory: Memory,
+    (ptr, len): (i32, usize),
+    write: &mut dyn FnMut(&mut dyn Write) -> Result<()>,
+) -> Result<(usize, usize)> {
+    let mut writer = GuestInputWriter { store, memory, offset: ptr as u32 as usize, remaining: len };
+    write(&mut writer)?;
+    if writer.remaining > 0 {
+        return Err(Error::FunctionCall {
+            function_name: function_name.to_string(),
+            reason: format!("Input ended {} bytes short of its allocation", writer.remaining),
+        });
+    }
+    Ok((ptr as u32 as usize, len))
+}
+
+/// The store, taken by an async call and handed back when dropped
+///
+/// The lease is dropped with the call's future, so a cancelled call returns
+/// the store too.
+struct LentStore<'a> {
+    slot: &'a StoreSlot,
+    store: Option<Store<WasmtimeStoreData>>,
+}
+
+impl Deref for LentStore<'_> {
+    type Target = Store<WasmtimeStoreData>;
+    
+    fn deref(&self) -> &Self::Target {
+        self.store.as_ref().expect("a lent store is held until dropped")
+    }
+}
+
+impl DerefMut for LentStore<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.store.as_mut().expect("a lent store is held until dropped")
+    }
+}
+
+impl Drop for LentStore<'_> {
+    fn drop(&mut self) {
+        *self.slot.store.lock().unwrap() = self.store.take();
+        self.slot.returned.notify_all();
+        self.slot.returned_async.notify_waiters();
+    }
 }
 
 /// Wasmtime instance implementation
 pub struct WasmtimeInstance {
-    /// Store for the instance (with interior mutability)
-    store: RwLock<Store<WasmtimeStoreData>>,
+    /// Store for the instance, lent to async calls while they run
+    store: StoreSlot,
     
     /// Instance
     instance: Instance,
     
+    /// Memory export, if any
+    memory: Option<Memory>,
+    
     /// Module ID
-    #[allow(dead_code)]
     module_id: ModuleId,
+    
+    /// Runtime's live instance counter, decremented on drop
+    live_instances: Option<Arc<AtomicUsize>>,
+    
+    /// Runtime's latency and fuel metrics, which calls are recorded in
+    metrics: Option<Arc<MetricsRecorder>>,
+    
+    /// Imports wired into the instance's linker
+    link_report: LinkReport,
+    
+    /// Interrupts the running call without waiting for the store lock
+    interrupt: Arc<EpochInterrupt>,
 }
 
 impl WasmtimeInstance {
@@ -128,21 +1031,373 @@ impl WasmtimeInstance {
             .get_export(&mut store, "memory")
             .and_then(|ext| ext.into_memory());
         
-        store.data_mut().memory = memory;
+        store.data_mut().memory = memory;
+        
+        // Update instance state
+        store.data_mut().state = WasmInstanceState::Running;
+        
+        let interrupt = Arc::new(EpochInterrupt {
+            requested: store.data().interrupt_requested.clone(),
+            engine: store.engine().clone(),
+        });
+        
+        Ok(Self {
+            store: StoreSlot::new(store),
+            instance,
+            memory,
+            module_id,
+            live_instances: None,
+            metrics: None,
+            link_report: LinkReport::default(),
+            interrupt,
+        })
+    }
+    
+    /// Helper method to get the memory instance
+    fn get_memory(&self) -> Option<Memory> {
+        self.memory
+    }
+    
+    /// Grow memory up front to the given number of pages
+    fn pre_grow(&self, pages: u64) -> Result<()> {
+        let Some(memory) = self.get_memory() else {
+            return Ok(());
+        };
+        
+        let mut store = self.store.lock();
+        let current = memory.size(&*store);
+        if current < pages {
+            memory.grow(&mut *store, pages - current).map_err(|e| Error::InstanceCreation {
+                reason: format!("Failed to pre-grow memory to {} pages: {}", pages, e),
+                instance_id: None,
+            })?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Copy JSON parameters into guest memory through the guest's `alloc` export
+    ///
+    /// Empty parameters (`[]` or `null`) are passed as no arguments; anything else
+    /// is passed as `(ptr, len)`.
+    async fn stream_arguments(
+        &self,
+        store: &mut Store<WasmtimeStoreData>,
+        function_name: &str,
+        params_json: &str,
+    ) -> Result<Vec<Val>> {
+        let params = params_json.trim();
+        if params.is_empty() || params == "[]" || params == "null" {
+            return Ok(Vec::new());
+        }
+        
+        let (ptr, len) = self.write_guest_bytes(store, function_name, params.as_bytes()).await?;
+        Ok(vec![Val::I32(ptr), Val::I32(len)])
+    }
+    
+    /// Copy bytes into guest memory through the guest's `alloc` export
+    async fn write_guest_bytes(
+        &self,
+        store: &mut Store<WasmtimeStoreData>,
+        function_name: &str,
+        bytes: &[u8],
+    ) -> Result<(i32, i32)> {
+        let call_error = |reason: String| Error::FunctionCall {
+            function_name: function_name.to_string(),
+            reason,
+        };
+        let (ptr, memory) = self.alloc_guest_input(store, function_name, bytes.len()).await?;
+        memory.write(&mut *store, ptr as u32 as usize, bytes)
+            .map_err(|e| call_error(format!("Failed to write input: {}", e)))?;
+        
+        Ok((ptr, bytes.len() as i32))
+    }
+    
+    /// Allocate `len` bytes of input in the guest through [`GUEST_ALLOC_EXPORT`]
+    async fn alloc_guest_input(
+        &self,
+        store: &mut Store<WasmtimeStoreData>,
+        function_name: &str,
+        len: usize,
+    ) -> Result<(i32, Memory)> {
+        let call_error = |reason: String| Error::FunctionCall {
+            function_name: function_name.to_string(),
+            reason,
+        };
+        let alloc = self.instance
+            .get_typed_func::<i32, i32>(&mut *store, GUEST_ALLOC_EXPORT)
+            .map_err(|_| call_error(format!("Module must export `{}(len) -> ptr` to receive input", GUEST_ALLOC_EXPORT)))?;
+        let memory = store.data().memory
+            .ok_or_else(|| call_error("Module does not export memory".to_string()))?;
+        
+        store.data_mut().alloc_failure = None;
+        let ptr = alloc.call_async(&mut *store, len as i32).await;
+        // Whether it trapped or returned, the input has nowhere to go
+        if let Some(failure) = store.data_mut().alloc_failure.take() {
+            return Err(guest_out_of_memory(store, failure));
+        }
+        let ptr = ptr.map_err(|e| call_error(format!("{} failed: {}", GUEST_ALLOC_EXPORT, e)))?;
+        Ok((ptr, memory))
+    }
+    
+    /// Clear what the last call left behind and start charging a guest call under the fuel schedule
+    ///
+    /// The schedule's call cost is charged up front, failing the call if the
+    /// store can't cover it. While the call runs the store holds fuel in units
+    /// of the instruction weight, so the call runs out as soon as its weighted
+    /// instructions exceed what is left.
+    fn begin_call(mut store: impl AsContextMut<Data = WasmtimeStoreData>, function_name: &str) -> Result<CallStart> {
+        let mut store = store.as_context_mut();
+        store.data_mut().alloc_failure = None;
+        let pages = store.data().memory
+            .map(|memory| memory.size(&store))
+            .unwrap_or(0);
+        let Ok(fuel) = store.get_fuel() else {
+            return Ok(CallStart { fuel: None, call_fuel: None, remainder: 0, pages });
+        };
+        
+        let schedule = &store.data().fuel_schedule;
+        let weight = schedule.instruction_weight.max(1);
+        let Some(available) = fuel.checked_sub(schedule.call_cost) else {
+            let _ = store.set_fuel(0);
+            return Err(call_failed(function_name, Trap::OutOfFuel.into()));
+        };
+        let call_fuel = available / weight;
+        store.set_fuel(call_fuel).map_err(|e| call_failed(function_name, e))?;
+        Ok(CallStart { fuel: Some(fuel), call_fuel: Some(call_fuel), remainder: available % weight, pages })
+    }
+    
+    /// Finish charging a guest call under the fuel schedule
+    ///
+    /// Converts the store's fuel back from instruction weight units and
+    /// charges memory growth. A call whose growth costs more than the fuel it
+    /// left fails, with the store left with none.
+    fn charge_fuel_schedule(mut store: impl AsContextMut<Data = WasmtimeStoreData>, start: CallStart) -> wasmtime::Result<()> {
+        let mut store = store.as_context_mut();
+        let Some(call_fuel_after) = start.call_fuel.and_then(|_| store.get_fuel().ok()) else {
+            return Ok(());
+        };
+        let schedule = &store.data().fuel_schedule;
+        let remaining = call_fuel_after
+            .saturating_mul(schedule.instruction_weight.max(1))
+            .saturating_add(start.remainder);
+        let pages_after = store.data().memory
+            .map(|memory| memory.size(&store))
+            .unwrap_or(start.pages);
+        let growth = pages_after.saturating_sub(start.pages).saturating_mul(schedule.memory_grow_per_page);
+        
+        match remaining.checked_sub(growth) {
+            Some(remaining) => store.set_fuel(remaining),
+            None => {
+                store.set_fuel(0)?;
+                Err(Trap::OutOfFuel.into())
+            }
+        }
+    }
+    
+    /// Record a finished guest call's latency and the fuel it burned
+    fn record_call(&self, store: &Store<WasmtimeStoreData>, started: Instant, fuel_before: Option<u64>, succeeded: bool) {
+        let Some(metrics) = &self.metrics else {
+            return;
+        };
+        let fuel = fuel_before.zip(store.get_fuel().ok()).map(|(before, after)| before.saturating_sub(after));
+        metrics.record_call(self.module_id, started.elapsed(), fuel, succeeded);
+    }
+    
+    /// Call a function that pushes partial results to `sink` through the stream import
+    async fn invoke_streaming(
+        &self,
+        store: &mut Store<WasmtimeStoreData>,
+        function_name: &str,
+        params_json: &str,
+        sink: ResultSink,
+    ) -> Result<()> {
+        let func = self.instance
+            .get_func(&mut *store, function_name)
+            .ok_or_else(|| Error::FunctionCall {
+                function_name: function_name.to_string(),
+                reason: "Function not found".to_string(),
+            })?;
+        let args = self.stream_arguments(store, function_name, params_json).await?;
+        let mut results: Vec<Val> = func.ty(&*store).results()
+            .map(|ty| Val::default_for_ty(&ty).unwrap_or(Val::I32(0)))
+            .collect();
+        
+        let start = Self::begin_call(&mut *store, function_name)?;
+        
+        // The sink is dropped when the call returns, which closes the stream
+        store.data_mut().stream_sink = Some(sink);
+        let refills = CallRefills::begin(&*store, start.call_fuel);
+        let profile = CallProfiling::begin(&*store, start.call_fuel);
+        let started = Instant::now();
+        let call_result = refilling(refills, func.call_async(&mut *store, &args, &mut results)).await;
+        if let Some(profile) = profile {
+            profile.finish(function_name);
+        }
+        store.data_mut().stream_sink = None;
+        let charged = Self::charge_fuel_schedule(&mut *store, start);
+        let call_result = call_result.and(charged);
+        self.record_call(store, started, start.fuel, call_result.is_ok());
+        
+        call_result.map_err(|e| call_trapped(store, function_name, e))
+    }
+    
+    /// Call a data ABI export, returning where its output is in guest memory
+    async fn invoke_raw_region(
+        &self,
+        store: &mut Store<WasmtimeStoreData>,
+        function_name: &str,
+        input: &[u8],
+    ) -> Result<(usize, usize)> {
+        let call_error = |reason: String| Error::FunctionCall {
+            function_name: function_name.to_string(),
+            reason,
+        };
+        
+        let func = self.instance
+            .get_typed_func::<(i32, i32), i64>(&mut *store, function_name)
+            .map_err(|e| call_error(format!("Export does not match `(i32, i32) -> i64`: {}", e)))?;
+        let (ptr, len) = self.write_guest_bytes(store, function_name, input).await?;
+        self.invoke_raw_export(store, function_name, func, (ptr, len)).await
+    }
+    
+    /// Call a data ABI export with input written by [`WasmInstance::write_input`]
+    async fn invoke_raw_region_at(
+        &self,
+        store: &mut Store<WasmtimeStoreData>,
+        function_name: &str,
+        (ptr, len): (usize, usize),
+    ) -> Result<(usize, usize)> {
+        let func = self.instance
+            .get_typed_func::<(i32, i32), i64>(&mut *store, function_name)
+            .map_err(|e| Error::FunctionCall {
+                function_name: function_name.to_string(),
+                reason: format!("Export does not match `(i32, i32) -> i64`: {}", e),
+            })?;
+        self.invoke_raw_export(store, function_name, func, (ptr as i32, len as i32)).await
+    }
+    
+    /// Call a guest data ABI export with input already in guest memory
+    async fn invoke_raw_export(
+        &self,
+        store: &mut Store<WasmtimeStoreData>,
+        function_name: &str,
+        func: TypedFunc<(i32, i32), i64>,
+        (ptr, len): (i32, i32),
+    ) -> Result<(usize, usize)> {
+        let start = Self::begin_call(&mut *store, function_name)?;
+        let refills = CallRefills::begin(&*store, start.call_fuel);
+        let profile = CallProfiling::begin(&*store, start.call_fuel);
+        let started = Instant::now();
+        let call_result = refilling(refills, func.call_async(&mut *store, (ptr, len))).await;
+        if let Some(profile) = profile {
+            profile.finish(function_name);
+        }
+        let charged = Self::charge_fuel_schedule(&mut *store, start);
+        let call_result = call_result.and_then(|packed| charged.map(|()| packed));
+        self.record_call(store, started, start.fuel, call_result.is_ok());
+        let packed = call_result.map_err(|e| call_trapped(store, function_name, e))?;
+        
+        Ok(unpack_guest_slice(packed))
+    }
+    
+    /// Call an export with numeric arguments
+    async fn invoke_values(
+        &self,
+        store: &mut Store<WasmtimeStoreData>,
+        function_name: &str,
+        args: &[HostValue],
+    ) -> Result<Vec<HostValue>> {
+        let call_error = |reason: String| Error::FunctionCall {
+            function_name: function_name.to_string(),
+            reason,
+        };
+        
+        let func = self.instance
+            .get_func(&mut *store, function_name)
+            .ok_or_else(|| call_error("Function not found".to_string()))?;
+        let func_ty = func.ty(&*store);
+        if func_ty.params().len() != args.len() {
+            return Err(call_error(format!(
+                "Export is {} but was passed {} arguments",
+                export_signature(&func_ty),
+                args.len(),
+            )));
+        }
+        let args: Vec<HostValue> = args.iter().zip(func_ty.params()).map(|(arg, ty)| widen(*arg, &ty)).collect();
+        if let Some((n, arg)) = args.iter().zip(func_ty.params()).enumerate()
+            .find_map(|(n, (arg, ty))| (!has_type(arg, &ty)).then_some((n, arg)))
+        {
+            return Err(call_error(format!(
+                "Argument {} is {} but the export is {}",
+                n + 1,
+                arg.type_name(),
+                export_signature(&func_ty),
+            )));
+        }
+        let mut results: Vec<Val> = func_ty.results()
+            .map(|ty| Val::default_for_ty(&ty).unwrap_or(Val::I32(0)))
+            .collect();
+        
+        // Externrefs made for the call are unrooted when the scope ends
+        let mut scope = RootScope::new(&mut *store);
+        let mut params = Vec::with_capacity(args.len());
+        for arg in args {
+            params.push(to_val(&mut scope, arg).await.map_err(|e| call_error(e.to_string()))?);
+        }
+        drop(func_ty);
         
-        // Update instance state
-        store.data_mut().state = WasmInstanceState::Running;
+        let start = Self::begin_call(&mut scope, function_name)?;
+        let refills = CallRefills::begin(&scope, start.call_fuel);
+        let profile = CallProfiling::begin(&scope, start.call_fuel);
+        let started = Instant::now();
+        let call_result = refilling(refills, func.call_async(&mut scope, &params, &mut results)).await;
+        let values = call_result.as_ref().ok().map(|()| {
+            results.iter()
+                .map(|result| to_host_value(&scope, result))
+                .collect::<wasmtime::Result<Vec<_>>>()
+        });
+        let charged = Self::charge_fuel_schedule(&mut scope, start);
+        let call_result = call_result.and(charged);
+        drop(scope);
         
-        Ok(Self {
-            store: RwLock::new(store),
-            instance,
-            module_id,
-        })
+        if let Some(profile) = profile {
+            profile.finish(function_name);
+        }
+        self.record_call(store, started, start.fuel, call_result.is_ok());
+        call_result.map_err(|e| call_trapped(store, function_name, e))?;
+        
+        values.unwrap_or_else(|| Ok(Vec::new())).map_err(|e| call_error(e.to_string()))
     }
     
-    /// Helper method to get the memory instance
-    fn get_memory(&self) -> Option<Memory> {
-        self.store.read().unwrap().data().memory
+    /// Copy `len` bytes at `offset` out of the instance's memory
+    fn read_store_memory(&self, store: &Store<WasmtimeStoreData>, offset: usize, len: usize) -> Result<Vec<u8>> {
+        let memory = self.get_memory().ok_or_else(|| {
+            Error::config_error("No memory exported by the module".to_string(), None)
+        })?;
+        
+        if offset.saturating_add(len) > memory.data_size(store) {
+            return Err(Error::Instance {
+                operation: "read_memory_at".to_string(),
+                instance_id: None,
+                reason: format!("{} bytes at offset {} is out of bounds", len, offset),
+            });
+        }
+        let mut buffer = vec![0u8; len];
+        memory.read(store, offset, &mut buffer).map_err(|e| Error::Instance {
+            operation: "read_memory_at".to_string(),
+            instance_id: None,
+            reason: e.to_string(),
+        })?;
+        Ok(buffer)
+    }
+}
+
+impl Drop for WasmtimeInstance {
+    fn drop(&mut self) {
+        if let Some(live_instances) = &self.live_instances {
+            live_instances.fetch_sub(1, Ordering::Relaxed);
+        }
     }
 }
 
@@ -214,46 +1469,109 @@ impl WasmFunctionCallerAsync for WasmtimeFunctionCaller {
 
 impl WasmInstance for WasmtimeInstance {
     fn state(&self) -> WasmInstanceState {
-        self.store.read().unwrap().data().state
+        self.store.lock().data().state
     }
     
-    fn memory_usage(&self) -> usize {
-        if let Some(memory) = self.get_memory() {
-            let store = self.store.read().unwrap();
-            return memory.data_size(&*store);
-        }
-        
-        0
+    fn memory_usage(&self) -> u64 {
+        let Some(memory) = self.get_memory() else {
+            return 0;
+        };
+        let store = self.store.lock();
+        memory.size(&*store).saturating_mul(memory.page_size(&*store))
     }
     
     fn fuel_usage(&self) -> Option<u64> {
-        // Note: Fuel API has changed in Wasmtime 13.0
-        // For now, return None until we implement the correct API
-        None
+        let store = self.store.lock();
+        let granted = store.data().granted_fuel?;
+        let remaining = store.get_fuel().ok()?;
+        Some(granted.saturating_sub(remaining))
     }
     
     fn reset_fuel(&self) -> Result<()> {
-        // Fuel API needs to be updated for Wasmtime 13.0
+        let mut store = self.store.lock();
+        if let Some(granted) = store.data().granted_fuel {
+            store.set_fuel(granted).map_err(|e| Error::ResourceLimit {
+                message: format!("Failed to reset fuel: {}", e),
+            })?;
+        }
+        Ok(())
+    }
+    
+    fn add_fuel(&self, fuel: u64) -> Result<()> {
+        let mut store = self.store.lock();
+        let remaining = store.get_fuel().map_err(|e| Error::UnsupportedOperation {
+            message: format!("Fuel metering is not enabled: {}", e),
+        })?;
+        store.set_fuel(remaining.saturating_add(fuel)).map_err(|e| Error::ResourceLimit {
+            message: format!("Failed to add fuel: {}", e),
+        })?;
+        let granted = store.data().granted_fuel.unwrap_or(remaining);
+        store.data_mut().granted_fuel = Some(granted.saturating_add(fuel));
         Ok(())
     }
     
-    fn add_fuel(&self, _fuel: u64) -> Result<()> {
-        // Fuel API needs to be updated for Wasmtime 13.0
+fn fuel_state(&self) -> Option<FuelState> {
+        let store = self.store.lock();
+        Some(FuelState {
+            granted: store.data().granted_fuel?,
+            remaining: store.get_fuel().ok()?,
+        })
+    }
+    
+    fn restore_fuel(&self, fuel: FuelState) -> Result<()> {
+        let mut store = self.store.lock();
+        store.set_fuel(fuel.remaining).map_err(|e| Error::ResourceLimit {
+            message: format!("Failed to restore fuel: {}", e),
+        })?;
+        store.data_mut().granted_fuel = Some(fuel.granted);
         Ok(())
     }
     
-    unsafe fn memory_ptr(&self) -> Result<*mut u8> {
+        unsafe fn memory_ptr(&self) -> Result<*mut u8> {
         let memory = self.get_memory().ok_or_else(|| 
             Error::config_error("No memory exported by the module".to_string(), None)
         )?;
         
-        let store = self.store.read().unwrap();
+        let store = self.store.lock();
         let ptr = memory.data_ptr(&*store);
         Ok(ptr)
     }
     
+    fn visit_memory(&self, f: &mut dyn FnMut(&[u8])) -> Result<()> {
+        let memory = self.get_memory().ok_or_else(|| {
+            Error::config_error("No memory exported by the module".to_string(), None)
+        })?;
+        
+        let store = self.store.lock();
+        f(memory.data(&*store));
+        Ok(())
+    }
+    
+    fn visit_memory_mut(&self, f: &mut dyn FnMut(&mut [u8])) -> Result<()> {
+        let memory = self.get_memory().ok_or_else(|| {
+            Error::config_error("No memory exported by the module".to_string(), None)
+        })?;
+        
+        let mut store = self.store.lock();
+        f(memory.data_mut(&mut *store));
+        Ok(())
+    }
+    
     fn memory_size(&self) -> usize {
-        self.memory_usage()
+        let Some(memory) = self.get_memory() else {
+            return 0;
+        };
+        memory.data_size(&*self.store.lock())
+    }
+    
+    fn memory_pages(&self) -> MemoryPages {
+        let store = self.store.lock();
+        let current = self.get_memory().map_or(0, |memory| memory.size(&*store));
+        let peak_bytes = store.data().memory_tracker.peak_bytes;
+        MemoryPages {
+            current,
+            peak: current.max(peak_bytes / WASM_PAGE_SIZE as u64),
+        }
     }
     
     fn function_caller(&self) -> Box<dyn WasmFunctionCaller> {
@@ -261,8 +1579,283 @@ impl WasmInstance for WasmtimeInstance {
         Box::new(WasmtimeFunctionCaller::new()) as Box<dyn WasmFunctionCaller>
     }
     
+    fn call_streaming(&self, function_name: &str, params_json: &str, sink: ResultSink) -> Result<()> {
+        block_on(self.invoke_streaming(&mut self.store.lock(), function_name, params_json, sink))
+    }
+    
+    fn call_streaming_async<'a>(&'a self, function_name: &'a str, params_json: &'a str, sink: ResultSink) -> GuestCall<'a, ()> {
+        Box::pin(async move {
+            self.invoke_streaming(&mut *self.store.lend().await, function_name, params_json, sink).await
+        })
+    }
+    
+    fn call_raw(&self, function_name: &str, input: &[u8]) -> Result<Vec<u8>> {
+        let (offset, len) = self.call_raw_region(function_name, input)?;
+        self.read_memory_at(offset, len).map_err(|e| Error::FunctionCall {
+            function_name: function_name.to_string(),
+            reason: format!("Failed to read output: {}", e),
+        })
+    }
+    
+    fn call_raw_region(&self, function_name: &str, input: &[u8]) -> Result<(usize, usize)> {
+        block_on(self.invoke_raw_region(&mut self.store.lock(), function_name, input))
+    }
+    
+    fn call_raw_region_async<'a>(&'a self, function_name: &'a str, input: &'a [u8]) -> GuestCall<'a, (usize, usize)> {
+        Box::pin(async move {
+            self.invoke_raw_region(&mut *self.store.lend().await, function_name, input).await
+        })
+    }
+    
+    fn write_input(&self, function_name: &str, len: usize, write: &mut dyn FnMut(&mut dyn Write) -> Result<()>) -> Result<(usize, usize)> {
+        let mut store = self.store.lock();
+        let (ptr, memory) = block_on(self.alloc_guest_input(&mut store, function_name, len))?;
+        fill_guest_input(&mut store, function_name, memory, (ptr, len), write)
+    }
+    
+    fn write_input_async<'a>(
+        &'a self,
+        function_name: &'a str,
+        len: usize,
+        write: &'a (dyn Fn(&mut dyn Write) -> Result<()> + Sync),
+    ) -> GuestCall<'a, (usize, usize)> {
+        Box::pin(async move {
+            let mut store = self.store.lend().await;
+            let (ptr, memory) = self.alloc_guest_input(&mut store, function_name, len).await?;
+            fill_guest_input(&mut store, function_name, memory, (ptr, len), &mut |guest| write(guest))
+        })
+    }
+    
+    fn call_raw_region_at(&self, function_name: &str, input: (usize, usize)) -> Result<(usize, usize)> {
+        block_on(self.invoke_raw_region_at(&mut self.store.lock(), function_name, input))
+    }
+    
+    fn call_raw_region_at_async<'a>(&'a self, function_name: &'a str, input: (usize, usize)) -> GuestCall<'a, (usize, usize)> {
+        Box::pin(async move {
+            self.invoke_raw_region_at(&mut *self.store.lend().await, function_name, input).await
+        })
+    }
+    
+    fn exported_i32(&self, name: &str) -> Option<i32> {
+        let mut store = self.store.lock();
+        match self.instance.get_export(&mut *store, name)? {
+            wasmtime::Extern::Global(global) => global.get(&mut *store).i32(),
+            wasmtime::Extern::Func(func) => {
+                let func = func.typed::<(), i32>(&*store).ok()?;
+                block_on(func.call_async(&mut *store, ())).ok()
+            }
+            _ => None,
+        }
+    }
+    
+    fn link_report(&self) -> Option<LinkReport> {
+        Some(self.link_report.clone())
+    }
+    
+    fn set_service_dispatcher(&self, dispatcher: Arc<dyn ServiceDispatcher>) {
+        self.store.lock().data_mut().service_dispatcher = Some(dispatcher);
+    }
+    
+    fn set_settings(&self, settings: Arc<PluginSettings>) {
+        self.store.lock().data_mut().settings = Some(settings);
+    }
+    
+    fn set_secrets(&self, secrets: Arc<dyn SecretResolver>) {
+        self.store.lock().data_mut().secrets = Some(secrets);
+    }
+    
+    fn set_dns(&self, dns: Arc<dyn DnsLookup>) {
+        self.store.lock().data_mut().dns = Some(dns);
+    }
+    
+    fn set_http(&self, http: Arc<dyn HttpFetch>) {
+        self.store.lock().data_mut().http = Some(http);
+    }
+    
+    fn interrupt_handle(&self) -> Option<Arc<dyn GuestInterrupt>> {
+        Some(self.interrupt.clone())
+    }
+    
+    fn set_timers(&self, timers: Arc<dyn TimerScheduler>) {
+        self.store.lock().data_mut().timers = Some(timers);
+    }
+    
+    fn set_guest_log(&self, log: Arc<dyn GuestLogSink>) {
+        self.store.lock().data_mut().guest_log = Some(log);
+    }
+    
+    fn set_progress_sink(&self, sink: Arc<dyn ProgressSink>) {
+        self.store.lock().data_mut().progress = Some(sink);
+    }
+    
+    fn set_coredump_sink(&self, sink: Arc<dyn CoredumpSink>) {
+        self.store.lock().data_mut().coredumps = Some(sink);
+    }
+    
+    fn set_checkpoint_sink(&self, sink: Arc<dyn CheckpointSink>) {
+        let mut store = self.store.lock();
+        let globals: Vec<_> = self.instance.exports(&mut *store)
+            .filter_map(|export| {
+                let name = export.name().to_string();
+                export.into_global().map(|global| (name, global))
+            })
+            .collect();
+        let globals = globals.into_iter()
+            .filter(|(_, global)| global.ty(&*store).mutability() == wasmtime::Mutability::Var)
+            .map(|(name, _)| name)
+            .collect();
+        store.data_mut().checkpoints = Some(Arc::new(GuestCheckpoints { sink, globals }));
+    }
+    
+    fn set_profile_sink(&self, sink: Arc<dyn ProfileSink>) {
+        let mut store = self.store.lock();
+        let engine = store.engine().clone();
+        store.data_mut().profiling = Some(Arc::new(CallProfiling {
+            sink,
+            engine,
+            stacks: Mutex::new(FoldedStacks::default()),
+            last_fuel: Mutex::new(None),
+        }));
+    }
+    
+    fn set_fuel_refiller(&self, refiller: Arc<dyn FuelRefiller>) {
+        let mut store = self.store.lock();
+        let engine = store.engine().clone();
+        store.data_mut().fuel_refills = Some(Arc::new(CallRefills {
+            refiller,
+            engine,
+            start_fuel: AtomicU64::new(0),
+            added: AtomicU64::new(0),
+            denied: AtomicBool::new(false),
+            resume_at: Mutex::new(None),
+        }));
+    }
+    
+    fn set_io_scheduler(&self, scheduler: Arc<dyn IoScheduler>) {
+        self.store.lock().data_mut().io = Some(scheduler);
+    }
+    
+    fn set_inference(&self, inference: Arc<dyn InferenceHost>) {
+        self.store.lock().data_mut().inference = Some(inference);
+    }
+    
+    fn set_child_spawner(&self, spawner: Arc<dyn ChildSpawner>) {
+        self.store.lock().data_mut().child_spawner = Some(spawner);
+    }
+    
+    fn set_growth_observer(&self, observer: Arc<dyn GrowthObserver>) {
+        self.store.lock().data_mut().memory_tracker.observer = Some(observer);
+    }
+    
+    fn read_memory(&self) -> Result<Vec<u8>> {
+        let Some(memory) = self.get_memory() else {
+            return Ok(Vec::new());
+        };
+        
+        let store = self.store.lock();
+        Ok(memory.data(&*store).to_vec())
+    }
+    
+    fn restore_memory(&self, contents: &[u8]) -> Result<()> {
+        let memory = self.get_memory().ok_or_else(|| {
+            Error::config_error("No memory exported by the module".to_string(), None)
+        })?;
+        
+        let mut store = self.store.lock();
+        let needed = contents.len().div_ceil(WASM_PAGE_SIZE) as u64;
+        let current = memory.size(&*store);
+        if current < needed {
+            memory.grow(&mut *store, needed - current).map_err(|e| Error::Instance {
+                operation: "restore_memory".to_string(),
+                instance_id: None,
+                reason: format!("Failed to grow memory to {} pages: {}", needed, e),
+            })?;
+        }
+        
+        memory.write(&mut *store, 0, contents).map_err(|e| Error::Instance {
+            operation: "restore_memory".to_string(),
+            instance_id: None,
+            reason: e.to_string(),
+        })
+    }
+    
+    fn mutable_globals(&self) -> Result<Vec<(String, HostValue)>> {
+        let mut store = self.store.lock();
+        let globals: Vec<_> = self.instance.exports(&mut *store)
+            .filter_map(|export| {
+                let name = export.name().to_string();
+                export.into_global().map(|global| (name, global))
+            })
+            .collect();
+        Ok(globals.into_iter()
+            .filter_map(|(name, global)| {
+                if global.ty(&*store).mutability() != wasmtime::Mutability::Var {
+                    return None;
+                }
+                let value = global.get(&mut *store);
+                Some((name, to_host_value(&*store, &value).ok()?))
+            })
+            .collect())
+    }
+    
+    fn restore_globals(&self, globals: &[(String, HostValue)]) -> Result<()> {
+        let mut store = self.store.lock();
+        let mut scope = RootScope::new(&mut *store);
+        for (name, value) in globals {
+            let global = self.instance.get_global(&mut scope, name).ok_or_else(|| Error::NotFound {
+                resource_type: "global".to_string(),
+                identifier: name.clone(),
+            })?;
+            let value = block_on(to_val(&mut scope, *value));
+            value.and_then(|value| global.set(&mut scope, value)).map_err(|e| Error::Instance {
+                operation: "restore_globals".to_string(),
+                instance_id: None,
+                reason: format!("Failed to set global {}: {}", name, e),
+            })?;
+        }
+        Ok(())
+    }
+    
+    fn read_memory_at(&self, offset: usize, len: usize) -> Result<Vec<u8>> {
+        self.read_store_memory(&self.store.lock(), offset, len)
+    }
+    
+    fn write_memory_at(&self, offset: usize, bytes: &[u8]) -> Result<()> {
+        let memory = self.get_memory().ok_or_else(|| {
+            Error::config_error("No memory exported by the module".to_string(), None)
+        })?;
+        
+        let mut store = self.store.lock();
+        memory.write(&mut *store, offset, bytes).map_err(|e| Error::Instance {
+            operation: "write_memory_at".to_string(),
+            instance_id: None,
+            reason: e.to_string(),
+        })
+    }
+    
+    fn call_values(&self, function_name: &str, args: &[HostValue]) -> Result<Vec<HostValue>> {
+        block_on(self.invoke_values(&mut self.store.lock(), function_name, args))
+    }
+    
+    fn call_values_async<'a>(&'a self, function_name: &'a str, args: &'a [HostValue]) -> GuestCall<'a, Vec<HostValue>> {
+        Box::pin(async move {
+            self.invoke_values(&mut *self.store.lend().await, function_name, args).await
+        })
+    }
+    
+    fn call_raw_async<'a>(&'a self, function_name: &'a str, input: &'a [u8]) -> GuestCall<'a, Vec<u8>> {
+        Box::pin(async move {
+            let mut store = self.store.lend().await;
+            let (offset, len) = self.invoke_raw_region(&mut store, function_name, input).await?;
+            self.read_store_memory(&store, offset, len).map_err(|e| Error::FunctionCall {
+                function_name: function_name.to_string(),
+                reason: format!("Failed to read output: {}", e),
+            })
+        })
+    }
+    
     fn call_simple_function(&self, function_name: &str, params: &[i32]) -> Result<i32> {
-        let mut store_guard = self.store.write().unwrap();
+        let mut store_guard = self.store.lock();
         
         // Get the function export
         let func = self.instance
@@ -275,13 +1868,22 @@ impl WasmInstance for WasmtimeInstance {
         // Convert parameters to wasmtime values
         let args: Vec<Val> = params.iter().map(|&p| Val::I32(p)).collect();
         
+        // Record fuel and memory before the call for the fuel schedule
+        let start = Self::begin_call(&mut *store_guard, function_name)?;
+        
         // Call the function
         let mut results = vec![Val::I32(0)]; // Pre-allocate result
-        func.call(&mut *store_guard, &args, &mut results)
-            .map_err(|e| Error::FunctionCall {
-                function_name: function_name.to_string(),
-                reason: format!("Call failed: {}", e),
-            })?;
+        let refills = CallRefills::begin(&*store_guard, start.call_fuel);
+        let profile = CallProfiling::begin(&*store_guard, start.call_fuel);
+        let started = Instant::now();
+        let call_result = block_on(refilling(refills, func.call_async(&mut *store_guard, &args, &mut results)));
+        if let Some(profile) = profile {
+            profile.finish(function_name);
+        }
+        let charged = Self::charge_fuel_schedule(&mut *store_guard, start);
+        let call_result = call_result.and(charged);
+        self.record_call(&store_guard, started, start.fuel, call_result.is_ok());
+        call_result.map_err(|e| call_trapped(&mut store_guard, function_name, e))?;
         
         // Extract the result
         match &results[0] {
@@ -294,46 +1896,200 @@ impl WasmInstance for WasmtimeInstance {
     }
 }
 
+/// Convert a wasmtime value to a host value
+///
+/// An `externref` is read as the host handle it carries.
+pub(crate) fn to_host_value(store: impl AsContext, val: &Val) -> wasmtime::Result<HostValue> {
+    match val {
+        Val::I32(v) => Ok(HostValue::I32(*v)),
+        Val::I64(v) => Ok(HostValue::I64(*v)),
+        Val::F32(bits) => Ok(HostValue::F32(f32::from_bits(*bits))),
+        Val::F64(bits) => Ok(HostValue::F64(f64::from_bits(*bits))),
+        Val::V128(v) => Ok(HostValue::V128(v.as_u128())),
+        Val::ExternRef(None) => Ok(HostValue::ExternRef(None)),
+        Val::ExternRef(Some(externref)) => externref.data(store.as_context())?
+            .and_then(|data| data.downcast_ref::<Handle>())
+            .map(|handle| HostValue::ExternRef(Some(*handle)))
+            .ok_or_else(|| wasmtime::Error::msg("externref does not carry a host handle")),
+        other => Err(wasmtime::Error::msg(format!("Unsupported value type {:?}", other))),
+    }
+}
+
+/// Widen a numeric argument to the parameter type it is passed as
+pub(crate) fn widen(value: HostValue, ty: &wasmtime::ValType) -> HostValue {
+    use wasmtime::ValType;
+    
+    match (value, ty) {
+        (HostValue::I32(v), ValType::I64) => HostValue::I64(v as i64),
+        (HostValue::I32(v), ValType::F32) => HostValue::F32(v as f32),
+        (HostValue::I32(v), ValType::F64) => HostValue::F64(v as f64),
+        (HostValue::I64(v), ValType::F64) => HostValue::F64(v as f64),
+        (HostValue::F64(v), ValType::F32) => HostValue::F32(v as f32),
+        (HostValue::F32(v), ValType::F64) => HostValue::F64(v as f64),
+        (HostValue::I32(v), ty) if ty.is_externref() => HostValue::ExternRef(Some(v as Handle)),
+        (value, _) => value,
+    }
+}
+
+/// Convert a host value to a wasmtime value
+///
+/// A handle becomes a new `externref` rooted in `store`'s current scope.
+pub(crate) async fn to_val<T: Send>(store: impl AsContextMut<Data = T>, value: HostValue) -> wasmtime::Result<Val> {
+    Ok(match value {
+        HostValue::I32(v) => Val::I32(v),
+        HostValue::I64(v) => Val::I64(v),
+        HostValue::F32(v) => Val::F32(v.to_bits()),
+        HostValue::F64(v) => Val::F64(v.to_bits()),
+        HostValue::V128(v) => Val::V128(v.into()),
+        HostValue::ExternRef(None) => Val::ExternRef(None),
+        HostValue::ExternRef(Some(handle)) => Val::ExternRef(Some(ExternRef::new_async(store, handle).await?)),
+    })
+}
+
+/// Check whether a value is of a parameter's type
+fn has_type(value: &HostValue, ty: &wasmtime::ValType) -> bool {
+    use wasmtime::ValType;
+    
+    match (value, ty) {
+        (HostValue::I32(_), ValType::I32)
+        | (HostValue::I64(_), ValType::I64)
+        | (HostValue::F32(_), ValType::F32)
+        | (HostValue::F64(_), ValType::F64)
+        | (HostValue::V128(_), ValType::V128) => true,
+        (HostValue::ExternRef(_), ty) => ty.is_externref(),
+        _ => false,
+    }
+}
+
+/// Signature of an export, e.g. `(i32, i64) -> (f64, f64)`
+fn export_signature(ty: &wasmtime::FuncType) -> String {
+    use wasmtime::ValType;
+    
+    let names = |types: &mut dyn Iterator<Item = ValType>| -> Vec<&'static str> {
+        types.map(|ty| match ty {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+            ValType::F32 => "f32",
+            ValType::F64 => "f64",
+            ValType::V128 => "v128",
+            ty if ty.is_externref() => "externref",
+            _ => "ref",
+        }).collect()
+    };
+    format!(
+        "{} -> {}",
+        values::signature(&names(&mut ty.params())),
+        values::signature(&names(&mut ty.results())),
+    )
+}
+
 /// Wasmtime runtime implementation
 pub struct WasmtimeRuntime {
     /// Wasmtime engine
     engine: Engine,
     
+    /// Compiles modules where the configuration says
+    compiler: ModuleCompiler,
+    
     /// Configuration
     config: RuntimeConfig,
     
     /// Loaded modules
     modules: DashMap<ModuleId, Arc<WasmtimeModule>>,
     
+    /// Imported compiled modules by the digest of their bytes
+    precompiled: DashMap<ModuleDigest, Module>,
+    
+    /// Modules loaded from `precompiled` instead of compiled
+    precompiled_loads: AtomicUsize,
+    
     /// Runtime metrics
     metrics: Mutex<RuntimeMetrics>,
+    
+    /// Call and instantiation latencies and fuel rates, shared with instances
+    recorder: Arc<MetricsRecorder>,
+    
+    /// Number of instances that have not been dropped
+    live_instances: Arc<AtomicUsize>,
+}
+
+/// Deepest stack guest code may use when memory limits are enabled
+pub const MAX_GUEST_STACK_BYTES: usize = 4 * 1024 * 1024;
+
+/// Stack left for host functions above the guest's
+const HOST_STACK_BYTES: usize = 2 * 1024 * 1024;
+
+/// Engine configuration for a runtime with `settings`
+///
+/// Compiler processes build their engines the same way, so their artifacts load
+/// into the host's engine.
+pub(crate) fn engine_config(settings: &EngineSettings) -> Config {
+    let mut wasmtime_config = Config::new();
+    
+    // Configure features
+    if settings.enable_fuel {
+        wasmtime_config.consume_fuel(true);
+    }
+    
+    // Running calls can be interrupted from other threads (see `EpochInterrupt`)
+    wasmtime_config.epoch_interruption(true);
+    
+    // Guests run on their own stacks so calls can yield to the executor
+    wasmtime_config.async_support(true);
+    wasmtime_config.async_stack_size(MAX_GUEST_STACK_BYTES + HOST_STACK_BYTES);
+    
+    wasmtime_config.wasm_memory64(settings.memory64);
+    
+    // Reference types are always accepted; GC struct and array types build on typed function references
+    wasmtime_config.wasm_reference_types(true);
+    wasmtime_config.wasm_function_references(settings.gc);
+    wasmtime_config.wasm_gc(settings.gc);
+    
+    // Traps carry the state coredumps are made from; it is only serialized for debug instances
+    wasmtime_config.coredump_on_trap(true);
+    
+    // Configure memory limits
+    if settings.enable_memory_limits {
+        wasmtime_config.max_wasm_stack(MAX_GUEST_STACK_BYTES);
+    }
+    
+    if settings.debug_info {
+        wasmtime_config.debug_info(true);
+    }
+    
+    // Configure compilation
+    if settings.optimize {
+        wasmtime_config.cranelift_opt_level(wasmtime::OptLevel::Speed);
+        wasmtime_config.parallel_compilation(true);
+    }
+    
+    // Configure the pooling allocator for high-density hosting
+    if let Some(pooling) = &settings.pooling {
+        let mut pool = PoolingAllocationConfig::default();
+        pool.total_memories(pooling.total_memories)
+            .total_tables(pooling.total_tables)
+            .total_core_instances(pooling.max_instances)
+            .total_stacks(pooling.max_instances)
+            .max_memory_size(pooling.max_memory_size);
+        wasmtime_config.allocation_strategy(InstanceAllocationStrategy::Pooling(pool));
+    }
+    
+    wasmtime_config
 }
 
 impl WasmtimeRuntime {
     /// Create a new Wasmtime runtime
     pub fn new(config: &RuntimeConfig) -> Result<Self> {
         // Create Wasmtime configuration
-        let mut wasmtime_config = Config::new();
-        
-        // Configure features
-        if config.enable_fuel {
-            wasmtime_config.consume_fuel(true);
-        }
-        
-        // Configure memory limits
-        if config.enable_memory_limits {
-            wasmtime_config.max_wasm_stack(4 * 1024 * 1024 * 1024); // 4GB max
-        }
-        
-        if config.debug_info {
-            wasmtime_config.debug_info(true);
-        }
+        let mut wasmtime_config = engine_config(&EngineSettings::from(config));
         
-        // Configure compilation
-        if config.compilation_threads > 0 {
-            wasmtime_config.cranelift_opt_level(wasmtime::OptLevel::Speed);
-            wasmtime_config.parallel_compilation(true);
-        }
+        // Profilers see code as it is loaded, so compiler processes don't need the agent
+        wasmtime_config.profiler(match config.profiling {
+            ProfilingStrategy::None => wasmtime::ProfilingStrategy::None,
+            ProfilingStrategy::PerfMap => wasmtime::ProfilingStrategy::PerfMap,
+            ProfilingStrategy::JitDump => wasmtime::ProfilingStrategy::JitDump,
+            ProfilingStrategy::VTune => wasmtime::ProfilingStrategy::VTune,
+        });
         
         // Configure caching
         if config.cache_modules {
@@ -356,8 +2112,13 @@ impl WasmtimeRuntime {
         
         Ok(Self {
             engine,
+            compiler: ModuleCompiler::new(config)?,
             config: config.clone(),
             modules: DashMap::new(),
+            precompiled: DashMap::new(),
+            precompiled_loads: AtomicUsize::new(0),
+            live_instances: Arc::new(AtomicUsize::new(0)),
+            recorder: Arc::new(MetricsRecorder::default()),
             metrics: Mutex::new(RuntimeMetrics {
                 compiled_modules: 0,
                 active_instances: 0,
@@ -366,74 +2127,371 @@ impl WasmtimeRuntime {
                 fuel_consumption_rate: None,
                 cache_hit_rate: None,
                 last_compilation_time_ms: None,
+                pool: None,
             }),
         })
     }
 }
 
-impl WasmRuntime for WasmtimeRuntime {
-    fn initialize(&mut self, config: RuntimeConfig) -> Result<()> {
-        // Update configuration
-        self.config = config;
+impl WasmtimeRuntime {
+    /// Create an instance whose imports are handled by `host` where it provides them
+    ///
+    /// Provided imports are exempt from the import policy.
+    pub fn create_instance_with_host_functions(
+        &self,
+        module: &dyn WasmModule,
+        resources: ResourceLimits,
+        capabilities: Capabilities,
+        host: Arc<dyn HostFunctions>,
+    ) -> Result<Box<dyn WasmInstance>> {
+        self.instantiate(module, resources, capabilities, None, Some(host))
+    }
+    
+    /// Add a compiled module to the loaded modules
+    fn register_module(&self, module: Module, wasm_bytes: &[u8]) -> Box<dyn WasmModule> {
+        let module = Arc::new(WasmtimeModule::new(module, wasm_bytes));
+        let id = module.id();
+        self.modules.insert(id, module.clone());
+        
+        Box::new(WasmtimeModule {
+            id,
+            name: module.name.clone(),
+            module: module.module.clone(),
+            exports: module.exports.clone(),
+            size: module.size,
+            abi: module.abi,
+            metadata: module.metadata.clone(),
+        })
+    }
+    
+    /// Route WASI time and randomness through `entropy`
+    fn link_entropy(linker: &mut Linker<WasmtimeStoreData>, entropy: Arc<dyn EntropySource>) -> Result<()> {
+        let clock = entropy.clone();
+        linker.func_wrap(
+            "wasi_snapshot_preview1",
+            "clock_time_get",
+            move |mut caller: Caller<'_, WasmtimeStoreData>, clock_id: i32, _precision: i64, time_ptr: i32| -> wasmtime::Result<i32> {
+                let memory = caller_memory(&mut caller)?;
+                let nanos = clock.clock_time(clock_id as u32);
+                memory.write(&mut caller, time_ptr as u32 as usize, &nanos.to_le_bytes())?;
+                Ok(0)
+            },
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add clock import to linker: {}", e),
+            instance_id: None,
+        })?;
+        
+        linker.func_wrap(
+            "wasi_snapshot_preview1",
+            "random_get",
+            move |mut caller: Caller<'_, WasmtimeStoreData>, buf: i32, len: i32| -> wasmtime::Result<i32> {
+                let memory = caller_memory(&mut caller)?;
+                let mut bytes = vec![0; len as u32 as usize];
+                entropy.random_bytes(&mut bytes);
+                memory.write(&mut caller, buf as u32 as usize, &bytes)?;
+                Ok(0)
+            },
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add random import to linker: {}", e),
+            instance_id: None,
+        })?;
+        Ok(())
+    }
+    
+    /// Wrap WASI's file reads and writes so they wait for the instance's share of the disk
+    ///
+    /// The standard streams, and instances without an I/O scheduler, go straight to WASI.
+    fn link_io_scheduling(linker: &mut Linker<WasmtimeStoreData>) -> Result<()> {
+        use wasi_common::snapshots::preview_1::wasi_snapshot_preview1 as wasi;
+        
+        linker.allow_shadowing(true);
+        let linked = (|| -> wasmtime::Result<()> {
+            linker.func_wrap_async(
+                "wasi_snapshot_preview1",
+                "fd_read",
+                |mut caller: Caller<'_, WasmtimeStoreData>, (fd, iovs, iovs_len, nread): (i32, i32, i32, i32)| Box::new(async move {
+                    if let Some(errno) = pace(&mut caller, IoDirection::Read, fd, iovs, iovs_len).await {
+                        return Ok(errno);
+                    }
+                    let memory = caller_memory(&mut caller)?;
+                    let (bytes, data) = memory.data_and_store_mut(&mut caller);
+                    wasi::fd_read(&mut data.wasi, &mut GuestMemory::Unshared(bytes), fd, iovs, iovs_len, nread).await
+                }),
+            )?;
+            linker.func_wrap_async(
+                "wasi_snapshot_preview1",
+                "fd_pread",
+                |mut caller: Caller<'_, WasmtimeStoreData>, (fd, iovs, iovs_len, offset, nread): (i32, i32, i32, i64, i32)| Box::new(async move {
+                    if let Some(errno) = pace(&mut caller, IoDirection::Read, fd, iovs, iovs_len).await {
+                        return Ok(errno);
+                    }
+                    let memory = caller_memory(&mut caller)?;
+                    let (bytes, data) = memory.data_and_store_mut(&mut caller);
+                    wasi::fd_pread(&mut data.wasi, &mut GuestMemory::Unshared(bytes), fd, iovs, iovs_len, offset, nread).await
+                }),
+            )?;
+            linker.func_wrap_async(
+                "wasi_snapshot_preview1",
+                "fd_write",
+                |mut caller: Caller<'_, WasmtimeStoreData>, (fd, iovs, iovs_len, nwritten): (i32, i32, i32, i32)| Box::new(async move {
+                    if let Some(errno) = pace(&mut caller, IoDirection::Write, fd, iovs, iovs_len).await {
+                        return Ok(errno);
+                    }
+                    let memory = caller_memory(&mut caller)?;
+                    let (bytes, data) = memory.data_and_store_mut(&mut caller);
+                    wasi::fd_write(&mut data.wasi, &mut GuestMemory::Unshared(bytes), fd, iovs, iovs_len, nwritten).await
+                }),
+            )?;
+            linker.func_wrap_async(
+                "wasi_snapshot_preview1",
+                "fd_pwrite",
+                |mut caller: Caller<'_, WasmtimeStoreData>, (fd, iovs, iovs_len, offset, nwritten): (i32, i32, i32, i64, i32)| Box::new(async move {
+                    if let Some(errno) = pace(&mut caller, IoDirection::Write, fd, iovs, iovs_len).await {
+                        return Ok(errno);
+                    }
+                    let memory = caller_memory(&mut caller)?;
+                    let (bytes, data) = memory.data_and_store_mut(&mut caller);
+                    wasi::fd_pwrite(&mut data.wasi, &mut GuestMemory::Unshared(bytes), fd, iovs, iovs_len, offset, nwritten).await
+                }),
+            )?;
+            Ok(())
+        })();
+        linker.allow_shadowing(false);
+        linked.map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add paced file I/O to linker: {}", e),
+            instance_id: None,
+        })
+    }
+    
+    /// Build a linker holding only the definitions `module` imports
+    ///
+    /// Imports are linked when the import policy allows them or `host` provides
+    /// them; any that are not allowed or have no definition fail instantiation.
+    fn prune_linker(
+        available: &Linker<WasmtimeStoreData>,
+        store: &mut Store<WasmtimeStoreData>,
+        module: &WasmtimeModule,
+        policy: &ImportPolicy,
+        host: Option<&dyn HostFunctions>,
+    ) -> Result<(Linker<WasmtimeStoreData>, LinkReport)> {
+        let mut linker = Linker::new(store.engine());
+        let mut report = LinkReport::default();
+        
+        for import in module.imports() {
+            let allowed = policy.allows_link(&import)
+                || host.is_some_and(|host| host.provides(&import.module, &import.name));
+            let definition = if allowed {
+                // Imports under the host world's WIT names get the functions of their core names
+                let (module, name) = wit::core_import(&import.module, &import.name)
+                    .unwrap_or((&import.module, &import.name));
+                available.get(&mut *store, module, name)
+            } else {
+                None
+            };
+            
+            match definition {
+                Some(definition) => {
+                    linker.define(&*store, &import.module, &import.name, definition)
+                        .map_err(|e| Error::InstanceCreation {
+                            reason: format!("Failed to link {}: {}", import, e),
+                            instance_id: None,
+                        })?;
+                    report.linked.push(import);
+                }
+                None => report.unresolved.push(import),
+            }
+        }
         
+        log::debug!("Module {}: {}", module.id, report);
+        if !report.is_complete() {
+            return Err(Error::UnknownImports {
+                report: ImportReport { unknown: report.unresolved },
+            });
+        }
+        Ok((linker, report))
+    }
+    
+    /// Link the WASI-NN functions in [`NN_IMPORT_MODULE`]
+    fn link_wasi_nn(linker: &mut Linker<WasmtimeStoreData>) -> Result<()> {
+        linker.func_wrap(
+            NN_IMPORT_MODULE,
+            NN_LOAD_FUNCTION,
+            |_builder_ptr: i32, _builder_len: i32, _encoding: i32, _target: i32, _graph_ptr: i32| -> i32 {
+                NnErrno::UnsupportedOperation.code()
+            },
+        ).and_then(|linker| linker.func_wrap(
+            NN_IMPORT_MODULE,
+            NN_LOAD_BY_NAME_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, name_ptr: i32, name_len: i32, graph_ptr: i32| -> wasmtime::Result<i32> {
+                let Some(inference) = caller.data().inference.clone() else {
+                    return Ok(NnErrno::UnsupportedOperation.code());
+                };
+                let memory = caller_memory(&mut caller)?;
+                let name = read_caller_bytes(&caller, memory, name_ptr, name_len)?;
+                match inference.load_by_name(&String::from_utf8_lossy(&name)) {
+                    Ok(graph) => {
+                        memory.write(&mut caller, graph_ptr as u32 as usize, &graph.to_le_bytes())?;
+                        Ok(0)
+                    }
+                    Err(errno) => Ok(errno.code()),
+                }
+            },
+        )).and_then(|linker| linker.func_wrap(
+            NN_IMPORT_MODULE,
+            NN_INIT_EXECUTION_CONTEXT_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, graph: i32, context_ptr: i32| -> wasmtime::Result<i32> {
+                let Some(inference) = caller.data().inference.clone() else {
+                    return Ok(NnErrno::UnsupportedOperation.code());
+                };
+                let memory = caller_memory(&mut caller)?;
+                match inference.init_execution_context(graph as u32) {
+                    Ok(context) => {
+                        memory.write(&mut caller, context_ptr as u32 as usize, &context.to_le_bytes())?;
+                        Ok(0)
+                    }
+                    Err(errno) => Ok(errno.code()),
+                }
+            },
+        )).and_then(|linker| linker.func_wrap(
+            NN_IMPORT_MODULE,
+            NN_SET_INPUT_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, context: i32, index: i32, tensor_ptr: i32| -> wasmtime::Result<i32> {
+                let Some(inference) = caller.data().inference.clone() else {
+                    return Ok(NnErrno::UnsupportedOperation.code());
+                };
+                let memory = caller_memory(&mut caller)?;
+                let tensor = match read_caller_tensor(&caller, memory, tensor_ptr, inference.max_tensor_bytes())? {
+                    Ok(tensor) => tensor,
+                    Err(errno) => return Ok(errno.code()),
+                };
+                Ok(inference.set_input(context as u32, index as u32, tensor).map_or_else(NnErrno::code, |()| 0))
+            },
+        )).and_then(|linker| linker.func_wrap(
+            NN_IMPORT_MODULE,
+            NN_COMPUTE_FUNCTION,
+            |caller: Caller<'_, WasmtimeStoreData>, context: i32| -> i32 {
+                let Some(inference) = caller.data().inference.clone() else {
+                    return NnErrno::UnsupportedOperation.code();
+                };
+                inference.compute(context as u32).map_or_else(NnErrno::code, |()| 0)
+            },
+        )).and_then(|linker| linker.func_wrap(
+            NN_IMPORT_MODULE,
+            NN_GET_OUTPUT_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, context: i32, index: i32, out_ptr: i32, out_len: i32, written_ptr: i32| -> wasmtime::Result<i32> {
+                let Some(inference) = caller.data().inference.clone() else {
+                    return Ok(NnErrno::UnsupportedOperation.code());
+                };
+                let memory = caller_memory(&mut caller)?;
+                let tensor = match inference.get_output(context as u32, index as u32) {
+                    Ok(tensor) => tensor,
+                    Err(errno) => return Ok(errno.code()),
+                };
+                if tensor.data.len() > out_len as u32 as usize {
+                    return Ok(NnErrno::TooLarge.code());
+                }
+                memory.write(&mut caller, out_ptr as u32 as usize, &tensor.data)?;
+                memory.write(&mut caller, written_ptr as u32 as usize, &(tensor.data.len() as u32).to_le_bytes())?;
+                Ok(0)
+            },
+        )).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add WASI-NN imports to linker: {}", e),
+            instance_id: None,
+        })?;
         Ok(())
     }
     
-    fn load_module(&self, wasm_bytes: &[u8]) -> Result<Box<dyn WasmModule>> {
-        // Compile the module
-        let start_time = std::time::Instant::now();
-        
-        let module = Module::new(&self.engine, wasm_bytes)
-            .map_err(|e| Error::module_load_error(format!("Failed to compile module: {}", e)))?;
+    /// Link the standard host functions in [`STDLIB_IMPORT_MODULE`]
+    fn link_stdlib(linker: &mut Linker<WasmtimeStoreData>) -> Result<()> {
+        let link_error = |e: wasmtime::Error| Error::InstanceCreation {
+            reason: format!("Failed to add standard host functions to linker: {}", e),
+            instance_id: None,
+        };
         
-        let elapsed_ms = start_time.elapsed().as_millis() as u64;
+        linker.func_wrap(
+            STDLIB_IMPORT_MODULE,
+            "monotonic_now",
+            |caller: Caller<'_, WasmtimeStoreData>| -> i64 { caller.data().stdlib.monotonic_now() },
+        ).map_err(link_error)?;
         
-        // Update metrics
-        {
-            let mut metrics = self.metrics.lock().unwrap();
-            metrics.compiled_modules += 1;
-            metrics.last_compilation_time_ms = Some(elapsed_ms);
-        }
+        linker.func_wrap(
+            STDLIB_IMPORT_MODULE,
+            "random_fill",
+            |mut caller: Caller<'_, WasmtimeStoreData>, ptr: i32, len: i32| -> wasmtime::Result<i32> {
+                let mut bytes = vec![0; len as u32 as usize];
+                if let Err(code) = caller.data().stdlib.random_fill(&mut bytes) {
+                    return Ok(code.code() as i32);
+                }
+                let memory = caller_memory(&mut caller)?;
+                memory.write(&mut caller, ptr as u32 as usize, &bytes)?;
+                Ok(0)
+            },
+        ).map_err(link_error)?;
         
-        // Create the module
-        let module = Arc::new(WasmtimeModule::new(module, wasm_bytes));
-        let id = module.id();
+        linker.func_wrap(
+            STDLIB_IMPORT_MODULE,
+            "uuid_v4",
+            |mut caller: Caller<'_, WasmtimeStoreData>, out_ptr: i32| -> wasmtime::Result<i32> {
+                let uuid = match caller.data().stdlib.uuid_v4() {
+                    Ok(uuid) => uuid,
+                    Err(code) => return Ok(code.code() as i32),
+                };
+                let memory = caller_memory(&mut caller)?;
+                memory.write(&mut caller, out_ptr as u32 as usize, &uuid)?;
+                Ok(0)
+            },
+        ).map_err(link_error)?;
         
-        // Store in the modules map
-        self.modules.insert(id, module.clone());
+        linker.func_wrap(
+            STDLIB_IMPORT_MODULE,
+            "sha256",
+            |mut caller: Caller<'_, WasmtimeStoreData>, ptr: i32, len: i32, out_ptr: i32| -> wasmtime::Result<i32> {
+                let memory = caller_memory(&mut caller)?;
+                let data = read_caller_bytes(&caller, memory, ptr, len)?;
+                let digest = caller.data().stdlib.sha256(&data);
+                memory.write(&mut caller, out_ptr as u32 as usize, &digest)?;
+                Ok(0)
+            },
+        ).map_err(link_error)?;
         
-        Ok(Box::new(WasmtimeModule {
-            id,
-            name: module.name.clone(),
-            module: module.module.clone(),
-            exports: module.exports.clone(),
-            size: module.size,
-        }))
-    }
-    
-    fn get_module(&self, id: ModuleId) -> Result<Arc<dyn WasmModule>> {
-        // Get the module
-        let module = self.modules.get(&id)
-            .ok_or_else(|| Error::config_error(format!("Module not found: {}", id), None))?;
+        linker.func_wrap_async(
+            STDLIB_IMPORT_MODULE,
+            "base64_encode",
+            |mut caller: Caller<'_, WasmtimeStoreData>, (ptr, len): (i32, i32)| Box::new(async move {
+                let memory = caller_memory(&mut caller)?;
+                let data = read_caller_bytes(&caller, memory, ptr, len)?;
+                let text = caller.data().stdlib.base64_encode(&data);
+                copy_to_caller(&mut caller, memory, &text).await
+            }),
+        ).map_err(link_error)?;
         
-        // Return as Arc<dyn WasmModule>
-        let clone: Box<dyn WasmModule> = Box::new(WasmtimeModule {
-            id: module.id,
-            name: module.name.clone(),
-            module: module.module.clone(),
-            exports: module.exports.clone(),
-            size: module.size,
-        });
+        linker.func_wrap_async(
+            STDLIB_IMPORT_MODULE,
+            "base64_decode",
+            |mut caller: Caller<'_, WasmtimeStoreData>, (ptr, len): (i32, i32)| Box::new(async move {
+                let memory = caller_memory(&mut caller)?;
+                let text = read_caller_bytes(&caller, memory, ptr, len)?;
+                match caller.data().stdlib.base64_decode(&text) {
+                    Ok(data) => copy_to_caller(&mut caller, memory, &data).await,
+                    Err(code) => Ok(code.code()),
+                }
+            }),
+        ).map_err(link_error)?;
         
-        Ok(Arc::from(clone))
+        Ok(())
     }
     
-    fn create_instance(
-        &self, 
-        module: &dyn WasmModule, 
+    /// Create an instance, optionally composing in an environment layer and host functions
+    fn instantiate(
+        &self,
+        module: &dyn WasmModule,
         resources: ResourceLimits,
         capabilities: Capabilities,
+        environment: Option<&EnvironmentLayer>,
+        host: Option<Arc<dyn HostFunctions>>,
     ) -> Result<Box<dyn WasmInstance>> {
+        let started = Instant::now();
+        
         // Try to downcast the module to a WasmtimeModule using the modules map
         let wasmtime_module = if let Some(id) = self.modules.iter().find_map(|m| {
             if m.id() == module.id() {
@@ -450,6 +2508,43 @@ impl WasmRuntime for WasmtimeRuntime {
             });
         };
         
+        // Reject forbidden and unknown imports before instantiation so the error names each one
+        let imports = wasmtime_module.imports();
+        self.config.import_policy.check_rules(&imports)?;
+        let imports: Vec<ModuleImport> = imports.into_iter()
+            .filter(|import| !host.as_ref().is_some_and(|host| host.provides(&import.module, &import.name)))
+            .collect();
+        self.config.import_policy.check(&imports)?;
+        
+        // Refuse modules whose memories or tables start out larger than the instance may grow
+        let required = wasmtime_module.module.resources_required();
+        if self.config.enable_memory_limits {
+            let tables = required.num_tables as u64;
+            if tables > resources.memory.max_tables as u64 {
+                return Err(Error::ResourceExhausted {
+                    kind: ResourceKind::Table,
+                    limit: resources.memory.max_tables as u64,
+                    used: tables,
+                    instance_id: None,
+                    suggestion: Some(format!("The module defines {} tables; raise MemoryLimits::max_tables", tables)),
+                });
+            }
+            let elements = required.max_initial_table_size.unwrap_or(0);
+            if elements > resources.memory.max_table_elements {
+                return Err(table_limit_error(resources.memory.max_table_elements, elements));
+            }
+        }
+        let initial_pages = required.max_initial_memory_size.unwrap_or(0);
+        if self.config.enable_memory_limits && initial_pages > resources.memory.max_memory_pages {
+            return Err(Error::config_error(
+                format!(
+                    "Module needs {} memory pages at instantiation, but max_memory_pages is {}",
+                    initial_pages, resources.memory.max_memory_pages,
+                ),
+                Some("Raise max_memory_pages for this instance".to_string()),
+            ));
+        }
+        
         // Create WASI context builder
         let mut wasi_builder = WasiCtxBuilder::new();
         
@@ -501,15 +2596,43 @@ impl WasmRuntime for WasmtimeRuntime {
             },
         }
         
-        // Configure filesystem capabilities - simplified version for now
-        // In a real implementation, this would properly handle directory access permissions
-        if !capabilities.filesystem.readable_dirs.is_empty() {
-            // Just log that we'd be configuring dirs
-            log::warn!("Directory access capabilities are not yet fully implemented");
+        // Compose the environment layer: virtual variables, then fixture files
+        // and host directories preopened once the context is built
+        let layer = environment.filter(|layer| !layer.is_empty());
+        if let Some(layer) = layer {
+            for (k, v) in &layer.env {
+                wasi_builder.env(k, v).map_err(|e| Error::InstanceCreation {
+                    reason: format!("Failed to set env var {}: {}", k, e),
+                    instance_id: None,
+                })?;
+            }
         }
         
         // Build the WASI context
         let wasi_ctx = wasi_builder.build();
+        let environment = match layer {
+            Some(layer) => {
+                let materialized = layer.materialize()?;
+                for preopen in materialized.preopens() {
+                    let guest = &preopen.guest_path;
+                    let dir = Dir::open_ambient_dir(&preopen.host_path, ambient_authority())
+                        .map_err(|e| Error::InstanceCreation {
+                            reason: format!("Failed to open environment directory {}: {}", guest, e),
+                            instance_id: None,
+                        })?;
+                    let mut dir: Box<dyn WasiDir> = Box::new(SyncDir::from_cap_std(dir));
+                    if preopen.read_only {
+                        dir = Box::new(ReadOnlyDir::new(dir));
+                    }
+                    wasi_ctx.push_preopened_dir(dir, guest).map_err(|e| Error::InstanceCreation {
+                        reason: format!("Failed to preopen {}: {}", guest, e),
+                        instance_id: None,
+                    })?;
+                }
+                Some(materialized)
+            }
+            None => None,
+        };
         
         // Create the store
         let mut store = Store::new(
@@ -518,8 +2641,55 @@ impl WasmRuntime for WasmtimeRuntime {
                 wasi: wasi_ctx,
                 state: WasmInstanceState::Created,
                 memory: None,
+                fuel_schedule: resources.fuel_schedule.clone(),
+                granted_fuel: None,
+                memory_tracker: MemoryTracker {
+                    max_bytes: self.config.enable_memory_limits
+                        .then(|| resources.memory.max_memory_pages.saturating_mul(WASM_PAGE_SIZE as u64)),
+                    max_tables: self.config.enable_memory_limits.then_some(resources.memory.max_tables as usize),
+                    max_table_elements: self.config.enable_memory_limits.then_some(resources.memory.max_table_elements),
+                    ..MemoryTracker::default()
+                },
+                _environment: environment,
+                stream_sink: None,
+                service_dispatcher: None,
+                settings: None,
+                stdlib: HostStdlib::new(capabilities.random.clone()),
+                secrets: None,
+                dns: None,
+                http: None,
+                timers: None,
+                guest_log: None,
+                progress: None,
+                coredumps: None,
+                fuel_refills: None,
+                io: None,
+                profiling: None,
+                checkpoints: None,
+                inference: None,
+                child_spawner: None,
+                interrupt_requested: Arc::new(AtomicBool::new(false)),
+                alloc_failure: None,
             }
         );
+        store.limiter(|data| &mut data.memory_tracker);
+        
+        // Check for interrupts whenever the engine's epoch advances
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_callback(|mut context| {
+            if context.data().interrupt_requested.swap(false, Ordering::SeqCst) {
+                return Err(Trap::Interrupt.into());
+            }
+            if let Some(profiling) = context.data().profiling.clone() {
+                profiling.sample(&context);
+            }
+            if let Some(refills) = context.data().fuel_refills.clone()
+                && let Some(update) = refills.check(&mut context)?
+            {
+                return Ok(update);
+            }
+            Ok(UpdateDeadline::Continue(1))
+        });
         
         // Set fuel if enabled
         if self.config.enable_fuel {
@@ -528,10 +2698,15 @@ impl WasmRuntime for WasmtimeRuntime {
                     reason: format!("Failed to set fuel: {}", e),
                     instance_id: None,
                 })?;
+                store.data_mut().granted_fuel = Some(fuel);
             }
+            store.fuel_async_yield_interval(self.config.async_yield_fuel).map_err(|e| Error::InstanceCreation {
+                reason: format!("Failed to set fuel yield interval: {}", e),
+                instance_id: None,
+            })?;
         }
         
-        // Create linker
+        // Create the linker holding everything the host can provide
         let mut linker = Linker::new(&self.engine);
         
         // Add WASI to the linker
@@ -540,6 +2715,375 @@ impl WasmRuntime for WasmtimeRuntime {
                 reason: format!("Failed to add WASI to linker: {}", e),
                 instance_id: None,
             })?;
+        Self::link_io_scheduling(&mut linker)?;
+        
+        // Add the result streaming import
+        linker.func_wrap_async(
+            STREAM_IMPORT_MODULE,
+            STREAM_EMIT_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, (ptr, len): (i32, i32)| Box::new(async move {
+                let Some(sink) = caller.data().stream_sink.clone() else {
+                    return Ok(1);
+                };
+                let memory = caller_memory(&mut caller)?;
+                let partial = read_caller_bytes(&caller, memory, ptr, len)?;
+                
+                // Waits while the consumer is behind; fails once it has gone away
+                Ok(if sink.send(partial).await.is_ok() { 0 } else { 1 })
+            }),
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add stream import to linker: {}", e),
+            instance_id: None,
+        })?;
+        
+        // Add the brokered service call import
+        linker.func_wrap_async(
+            SERVICE_IMPORT_MODULE,
+            SERVICE_CALL_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>,
+             (service_ptr, service_len, function_ptr, function_len, payload_ptr, payload_len): (i32, i32, i32, i32, i32, i32)| Box::new(async move {
+                let Some(dispatcher) = caller.data().service_dispatcher.clone() else {
+                    return Ok(GuestErrorCode::Unavailable.code());
+                };
+                let memory = caller_memory(&mut caller)?;
+                let service = read_caller_bytes(&caller, memory, service_ptr, service_len)?;
+                let function = read_caller_bytes(&caller, memory, function_ptr, function_len)?;
+                let payload = read_caller_bytes(&caller, memory, payload_ptr, payload_len)?;
+                let service = String::from_utf8_lossy(&service);
+                let function = String::from_utf8_lossy(&function);
+                
+                let response = match dispatcher.dispatch(&service, &function, &payload) {
+                    Ok(response) => response,
+                    Err(e) => {
+                        log::debug!("Service call {}.{} failed: {}", service, function, e);
+                        return Ok(GuestErrorCode::from(&e).code());
+                    }
+                };
+                
+                copy_to_caller(&mut caller, memory, &response).await
+            }),
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add service import to linker: {}", e),
+            instance_id: None,
+        })?;
+        
+        // Add the child instance imports
+        linker.func_wrap(
+            CHILD_IMPORT_MODULE,
+            CHILD_SPAWN_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, name_ptr: i32, name_len: i32| -> wasmtime::Result<i64> {
+                let Some(spawner) = caller.data().child_spawner.clone() else {
+                    return Ok(GuestErrorCode::Unavailable.code());
+                };
+                let memory = caller_memory(&mut caller)?;
+                let name = read_caller_bytes(&caller, memory, name_ptr, name_len)?;
+                let name = String::from_utf8_lossy(&name);
+                Ok(match spawner.spawn(&name) {
+                    Ok(child) => i64::from(child),
+                    Err(e) => {
+                        log::debug!("Starting child module {} failed: {}", name, e);
+                        GuestErrorCode::from(&e).code()
+                    }
+                })
+            },
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add child spawn import to linker: {}", e),
+            instance_id: None,
+        })?;
+        linker.func_wrap_async(
+            CHILD_IMPORT_MODULE,
+            CHILD_CALL_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>,
+             (child, function_ptr, function_len, payload_ptr, payload_len): (i32, i32, i32, i32, i32)| Box::new(async move {
+                let Some(spawner) = caller.data().child_spawner.clone() else {
+                    return Ok(GuestErrorCode::Unavailable.code());
+                };
+                let memory = caller_memory(&mut caller)?;
+                let function = read_caller_bytes(&caller, memory, function_ptr, function_len)?;
+                let payload = read_caller_bytes(&caller, memory, payload_ptr, payload_len)?;
+                let function = String::from_utf8_lossy(&function);
+                
+                let response = match spawner.call(child as u32, &function, &payload) {
+                    Ok(response) => response,
+                    Err(e) => {
+                        log::debug!("Call to {} on child {} failed: {}", function, child, e);
+                        return Ok(GuestErrorCode::from(&e).code());
+                    }
+                };
+                
+                copy_to_caller(&mut caller, memory, &response).await
+            }),
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add child call import to linker: {}", e),
+            instance_id: None,
+        })?;
+        linker.func_wrap(
+            CHILD_IMPORT_MODULE,
+            CHILD_KILL_FUNCTION,
+            |caller: Caller<'_, WasmtimeStoreData>, child: i32| -> i32 {
+                let Some(spawner) = caller.data().child_spawner.clone() else {
+                    return GuestErrorCode::Unavailable.code() as i32;
+                };
+                match spawner.kill(child as u32) {
+                    Ok(()) => 0,
+                    Err(e) => GuestErrorCode::from(&e).code() as i32,
+                }
+            },
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add child kill import to linker: {}", e),
+            instance_id: None,
+        })?;
+        
+        // Add the settings import
+        linker.func_wrap_async(
+            CONFIG_IMPORT_MODULE,
+            CONFIG_GET_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, (key_ptr, key_len): (i32, i32)| Box::new(async move {
+                let Some(settings) = caller.data().settings.clone() else {
+                    return Ok(CONFIG_KEY_MISSING);
+                };
+                let memory = caller_memory(&mut caller)?;
+                let key = read_caller_bytes(&caller, memory, key_ptr, key_len)?;
+                let Some(value) = settings.get(&String::from_utf8_lossy(&key)) else {
+                    return Ok(CONFIG_KEY_MISSING);
+                };
+                
+                let json = serde_json::to_vec(value)?;
+                copy_to_caller(&mut caller, memory, &json).await
+            }),
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add settings import to linker: {}", e),
+            instance_id: None,
+        })?;
+        
+        // Add the secrets import
+        linker.func_wrap_async(
+            SECRETS_IMPORT_MODULE,
+            SECRETS_GET_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, (name_ptr, name_len): (i32, i32)| Box::new(async move {
+                let Some(secrets) = caller.data().secrets.clone() else {
+                    return Ok(GuestErrorCode::Unavailable.code());
+                };
+                let memory = caller_memory(&mut caller)?;
+                let name = read_caller_bytes(&caller, memory, name_ptr, name_len)?;
+                let name = String::from_utf8_lossy(&name);
+                
+                // The host-side copy is zeroized when `secret` drops
+                let secret = match secrets.resolve(&name) {
+                    Ok(secret) => secret,
+                    Err(e) => {
+                        log::debug!("Secret request for {} failed: {}", name, e);
+                        return Ok(GuestErrorCode::from(&e).code());
+                    }
+                };
+                copy_to_caller(&mut caller, memory, secret.expose()).await
+            }),
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add secrets import to linker: {}", e),
+            instance_id: None,
+        })?;
+        
+        // Add the DNS import
+        linker.func_wrap_async(
+            DNS_IMPORT_MODULE,
+            DNS_RESOLVE_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, (name_ptr, name_len): (i32, i32)| Box::new(async move {
+                let Some(dns) = caller.data().dns.clone() else {
+                    return Ok(GuestErrorCode::Unavailable.code());
+                };
+                let memory = caller_memory(&mut caller)?;
+                let name = read_caller_bytes(&caller, memory, name_ptr, name_len)?;
+                let name = String::from_utf8_lossy(&name);
+                
+                let addresses = match dns.resolve(&name) {
+                    Ok(addresses) => addresses,
+                    Err(e) => {
+                        log::debug!("Resolving {} failed: {}", name, e);
+                        return Ok(GuestErrorCode::from(&e).code());
+                    }
+                };
+                let bytes: Vec<u8> = addresses.iter()
+                    .flat_map(|ip| match ip {
+                        std::net::IpAddr::V4(ip) => ip.to_ipv6_mapped().octets(),
+                        std::net::IpAddr::V6(ip) => ip.octets(),
+                    })
+                    .collect();
+                copy_to_caller(&mut caller, memory, &bytes).await
+            }),
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add DNS import to linker: {}", e),
+            instance_id: None,
+        })?;
+        
+        // Add the HTTP import
+        linker.func_wrap_async(
+            HTTP_IMPORT_MODULE,
+            HTTP_FETCH_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, (request_ptr, request_len): (i32, i32)| Box::new(async move {
+                let Some(http) = caller.data().http.clone() else {
+                    return Ok(GuestErrorCode::Unavailable.code());
+                };
+                let memory = caller_memory(&mut caller)?;
+                let request = read_caller_bytes(&caller, memory, request_ptr, request_len)?;
+                
+                let response = match http.fetch(&request).await {
+                    Ok(response) => response,
+                    Err(e) => {
+                        log::debug!("HTTP request failed: {}", e);
+                        return Ok(GuestErrorCode::from(&e).code());
+                    }
+                };
+                copy_to_caller(&mut caller, memory, &response).await
+            }),
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add HTTP import to linker: {}", e),
+            instance_id: None,
+        })?;
+        
+        // Add the timer imports
+        linker.func_wrap(
+            TIMER_IMPORT_MODULE,
+            TIMER_SET_FUNCTION,
+            |caller: Caller<'_, WasmtimeStoreData>, delay_ms: i64, token: i64| -> i64 {
+                let Some(timers) = caller.data().timers.clone() else {
+                    return GuestErrorCode::Unavailable.code();
+                };
+                let Ok(delay_ms) = u64::try_from(delay_ms) else {
+                    return GuestErrorCode::InvalidInput.code();
+                };
+                match timers.set(Duration::from_millis(delay_ms), token) {
+                    Ok(timer_id) => timer_id as i64,
+                    Err(e) => GuestErrorCode::from(&e).code(),
+                }
+            },
+        ).and_then(|linker| linker.func_wrap(
+            TIMER_IMPORT_MODULE,
+            TIMER_CANCEL_FUNCTION,
+            |caller: Caller<'_, WasmtimeStoreData>, timer_id: i64| -> i64 {
+                let cancelled = caller.data().timers.as_ref()
+                    .is_some_and(|timers| timer_id >= 0 && timers.cancel(timer_id as u64));
+                if cancelled { 0 } else { GuestErrorCode::NotFound.code() }
+            },
+        )).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add timer imports to linker: {}", e),
+            instance_id: None,
+        })?;
+        
+        // Add the checkpoint import
+        linker.func_wrap(
+            CHECKPOINT_IMPORT_MODULE,
+            CHECKPOINT_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>| -> wasmtime::Result<i64> {
+                let Some(checkpoints) = caller.data().checkpoints.clone() else {
+                    return Ok(GuestErrorCode::Unavailable.code());
+                };
+                let memory = caller_memory(&mut caller)?;
+                let contents = memory.data(&caller).to_vec();
+                let mut globals = Vec::with_capacity(checkpoints.globals.len());
+                for name in &checkpoints.globals {
+                    if let Some(global) = caller.get_export(name).and_then(|export| export.into_global()) {
+                        let value = global.get(&mut caller);
+                        globals.push((name.clone(), to_host_value(&caller, &value)?));
+                    }
+                }
+                Ok(match checkpoints.sink.checkpoint(&contents, &globals) {
+                    Ok(checkpoint_id) => checkpoint_id as i64,
+                    Err(e) => {
+                        log::warn!("Guest checkpoint failed: {}", e);
+                        GuestErrorCode::from(&e).code()
+                    }
+                })
+            },
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add checkpoint import to linker: {}", e),
+            instance_id: None,
+        })?;
+        
+        // Add the progress import
+        linker.func_wrap(
+            PROGRESS_IMPORT_MODULE,
+            PROGRESS_REPORT_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, percent: i32, ptr: i32, len: i32| -> wasmtime::Result<i32> {
+                let Ok(percent @ 0..=100) = u8::try_from(percent) else {
+                    return Ok(GuestErrorCode::InvalidInput.code() as i32);
+                };
+                if len as u32 as usize > MAX_PROGRESS_MESSAGE_BYTES {
+                    return Ok(GuestErrorCode::InvalidInput.code() as i32);
+                }
+                let Some(progress) = caller.data().progress.clone() else {
+                    return Ok(0);
+                };
+                let memory = caller_memory(&mut caller)?;
+                let message = read_caller_bytes(&caller, memory, ptr, len)?;
+                Ok(if progress.report(percent, &String::from_utf8_lossy(&message)) { 1 } else { 0 })
+            },
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add progress import to linker: {}", e),
+            instance_id: None,
+        })?;
+        
+        // Add the allocation failure import
+        linker.func_wrap(
+            ALLOC_IMPORT_MODULE,
+            ALLOC_FAILED_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, size: i64| -> i32 {
+                caller.data_mut().alloc_failure = Some(AllocFailure {
+                    size: size.max(0) as u64,
+                    call_id: current_call_id(),
+                });
+                0
+            },
+        ).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add allocation failure import to linker: {}", e),
+            instance_id: None,
+        })?;
+        
+        // Add the logging imports
+        linker.func_wrap(
+            LOG_IMPORT_MODULE,
+            LOG_WRITE_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, level: i32, ptr: i32, len: i32| -> wasmtime::Result<i32> {
+                let Some(level) = level_from_guest(level) else {
+                    return Ok(GuestErrorCode::InvalidInput.code() as i32);
+                };
+                let Some(log) = caller.data().guest_log.clone() else {
+                    return Ok(0);
+                };
+                let memory = caller_memory(&mut caller)?;
+                let message = read_caller_bytes(&caller, memory, ptr, len)?;
+                log.write(level, &String::from_utf8_lossy(&message));
+                Ok(0)
+            },
+        ).and_then(|linker| linker.func_wrap(
+            LOG_IMPORT_MODULE,
+            LOG_CALL_ID_FUNCTION,
+            || -> i64 { current_call_id().map_or(0, |call_id| call_id.as_u64() as i64) },
+        )).and_then(|linker| linker.func_wrap(
+            LOG_IMPORT_MODULE,
+            LOG_DIAGNOSTIC_FUNCTION,
+            |mut caller: Caller<'_, WasmtimeStoreData>, ptr: i32, len: i32| -> wasmtime::Result<i32> {
+                if len as u32 as usize > MAX_DIAGNOSTIC_BYTES {
+                    return Ok(GuestErrorCode::InvalidInput.code() as i32);
+                }
+                let memory = caller_memory(&mut caller)?;
+                let encoded = read_caller_bytes(&caller, memory, ptr, len)?;
+                let Ok(diagnostic) = serde_json::from_slice::<Diagnostic>(&encoded) else {
+                    return Ok(GuestErrorCode::InvalidInput.code() as i32);
+                };
+                if let Some(log) = caller.data().guest_log.clone() {
+                    log.diagnostic(diagnostic);
+                }
+                Ok(0)
+            },
+        )).map_err(|e| Error::InstanceCreation {
+            reason: format!("Failed to add logging imports to linker: {}", e),
+            instance_id: None,
+        })?;
+        
+        Self::link_wasi_nn(&mut linker)?;
+        
+        // Add the standard host functions
+        Self::link_stdlib(&mut linker)?;
         
         // Add "env" memory if needed by the module
         let memory_type = wasmtime::MemoryType::new(1, None);
@@ -555,40 +3099,335 @@ impl WasmRuntime for WasmtimeRuntime {
                 instance_id: None,
             })?;
         
-        // Instantiate the module
-        let instance = linker
-            .instantiate(&mut store, &wasmtime_module.module)
+        // Host functions replace any import of the same name defined above
+        if let Some(host) = &host {
+            linker.allow_shadowing(true);
+            if let Some(entropy) = host.entropy() {
+                Self::link_entropy(&mut linker, entropy)?;
+            }
+            for import in wasmtime_module.module.imports() {
+                let ExternType::Func(func_type) = import.ty() else {
+                    continue;
+                };
+                if !host.provides(import.module(), import.name()) {
+                    continue;
+                }
+                
+                let host = host.clone();
+                let (module_name, name) = (import.module().to_string(), import.name().to_string());
+                linker.func_new_async(
+                    import.module(),
+                    import.name(),
+                    func_type,
+                    move |mut caller, params, results| {
+                        let host = host.clone();
+                        let (module_name, name) = (module_name.clone(), name.clone());
+                        Box::new(async move {
+                            let args = params.iter()
+                                .map(|param| to_host_value(&caller, param))
+                                .collect::<wasmtime::Result<Vec<_>>>()?;
+                            let values = host.call_async(&module_name, &name, &args).await
+                                .map_err(|e| wasmtime::Error::msg(e.to_string()))?;
+                            if values.len() != results.len() {
+                                return Err(wasmtime::Error::msg(format!(
+                                    "{}.{} returned {} values, expected {}",
+                                    module_name, name, values.len(), results.len()
+                                )));
+                            }
+                            for (slot, value) in results.iter_mut().zip(values) {
+                                *slot = to_val(&mut caller, value).await?;
+                            }
+                            Ok(())
+                        })
+                    },
+                ).map_err(|e| Error::InstanceCreation {
+                    reason: format!("Failed to add host function {}.{}: {}", import.module(), import.name(), e),
+                    instance_id: None,
+                })?;
+            }
+        }
+        
+        // Link only what the module imports, leaving the rest of the surface out
+        let (linker, link_report) = Self::prune_linker(
+            &linker,
+            &mut store,
+            &wasmtime_module,
+            &self.config.import_policy,
+            host.as_deref(),
+        )?;
+        
+        // Instantiate the module, running its start function to completion
+        let instance = block_on(linker.instantiate_async(&mut store, &wasmtime_module.module))
             .map_err(|e| Error::InstanceCreation { 
                 reason: format!("Failed to instantiate module: {}", e),
                 instance_id: None,
             })?;
         
         // Create the instance
-        let instance = WasmtimeInstance::new(
+        let mut instance = WasmtimeInstance::new(
             store,
             instance,
             wasmtime_module.id,
         )?;
         
+        instance.link_report = link_report;
+        
+        // Track the instance until it is dropped (and releases its pool slot)
+        self.live_instances.fetch_add(1, Ordering::Relaxed);
+        instance.live_instances = Some(self.live_instances.clone());
+        
+        // Pre-grow memory for latency-sensitive instances
+        if let Some(pages) = resources.memory.pre_grow_pages {
+            if pages > resources.memory.max_memory_pages {
+                return Err(Error::config_error(
+                    format!("pre_grow_pages ({}) exceeds max_memory_pages ({})", pages, resources.memory.max_memory_pages),
+                    Some("Lower pre_grow_pages or raise the memory limit".to_string()),
+                ));
+            }
+            instance.pre_grow(pages)?;
+        }
+        
         // Update metrics
         {
             let mut metrics = self.metrics.lock().unwrap();
             metrics.active_instances += 1;
         }
+        self.recorder.record_instantiation(wasmtime_module.id, started.elapsed());
+        instance.metrics = Some(self.recorder.clone());
         
         Ok(Box::new(instance) as Box<dyn WasmInstance>)
     }
+}
+
+impl WasmRuntime for WasmtimeRuntime {
+    fn initialize(&mut self, config: RuntimeConfig) -> Result<()> {
+        // Update configuration
+        self.config = config;
+        
+        Ok(())
+    }
+    
+    fn load_module(&self, wasm_bytes: &[u8]) -> Result<Box<dyn WasmModule>> {
+        // Compile the module
+        let start_time = std::time::Instant::now();
+        
+        // Imported artifacts stand in for compiling the same bytes
+        let precompiled = if self.precompiled.is_empty() {
+            None
+        } else {
+            self.precompiled.get(&module_digest(wasm_bytes)).map(|module| module.clone())
+        };
+        if let Some(module) = precompiled {
+            let loads = self.precompiled_loads.fetch_add(1, Ordering::Relaxed) + 1;
+            let mut metrics = self.metrics.lock().unwrap();
+            metrics.cache_hit_rate = Some(loads as f64 / (loads + metrics.compiled_modules) as f64);
+            drop(metrics);
+            return Ok(self.register_module(module, wasm_bytes));
+        }
+        
+        let module = self.compiler.compile(&self.engine, wasm_bytes).map_err(|e| {
+            let reason = e.to_string();
+            if !self.config.enable_memory64 && reason.contains("memory64") {
+                Error::config_error(
+                    format!("Module uses 64-bit memory, which this runtime does not accept: {}", e),
+                    Some("Set RuntimeConfig::enable_memory64 to load it".to_string()),
+                )
+            } else if !self.config.enable_gc && (reason.contains("gc feature") || reason.contains("function references")) {
+                Error::config_error(
+                    format!("Module uses GC types, which this runtime does not accept: {}", e),
+                    Some("Set RuntimeConfig::enable_gc to load it".to_string()),
+                )
+            } else {
+                e
+            }
+        })?;
+        
+        let elapsed_ms = start_time.elapsed().as_millis() as u64;
+        
+        // Update metrics
+        {
+            let mut metrics = self.metrics.lock().unwrap();
+            metrics.compiled_modules += 1;
+            metrics.last_compilation_time_ms = Some(elapsed_ms);
+            let loads = self.precompiled_loads.load(Ordering::Relaxed);
+            if loads > 0 {
+                metrics.cache_hit_rate = Some(loads as f64 / (loads + metrics.compiled_modules) as f64);
+            }
+        }
+        
+        Ok(self.register_module(module, wasm_bytes))
+    }
+    
+    fn get_module(&self, id: ModuleId) -> Result<Arc<dyn WasmModule>> {
+        // Get the module
+        let module = self.modules.get(&id)
+            .ok_or_else(|| Error::config_error(format!("Module not found: {}", id), None))?;
+        
+        // Return as Arc<dyn WasmModule>
+        let clone: Box<dyn WasmModule> = Box::new(WasmtimeModule {
+            id: module.id,
+            name: module.name.clone(),
+            module: module.module.clone(),
+            exports: module.exports.clone(),
+            size: module.size,
+            abi: module.abi,
+            metadata: module.metadata.clone(),
+        });
+        
+        Ok(Arc::from(clone))
+    }
+    
+    fn create_instance(
+        &self, 
+        module: &dyn WasmModule, 
+        resources: ResourceLimits,
+        capabilities: Capabilities,
+    ) -> Result<Box<dyn WasmInstance>> {
+        self.instantiate(module, resources, capabilities, None, None)
+    }
+    
+    fn create_instance_with_environment(
+        &self,
+        module: &dyn WasmModule,
+        resources: ResourceLimits,
+        capabilities: Capabilities,
+        environment: &EnvironmentLayer,
+    ) -> Result<Box<dyn WasmInstance>> {
+        self.instantiate(module, resources, capabilities, Some(environment), None)
+    }
+    
+    fn create_instance_with_host(
+        &self,
+        module: &dyn WasmModule,
+        resources: ResourceLimits,
+        capabilities: Capabilities,
+        environment: Option<&EnvironmentLayer>,
+        host: Arc<dyn HostFunctions>,
+    ) -> Result<Box<dyn WasmInstance>> {
+        self.instantiate(module, resources, capabilities, environment, Some(host))
+    }
+    
+    fn features(&self) -> RuntimeFeatures {
+        RuntimeFeatures {
+            backend: "wasmtime",
+            fuel: true,
+            epochs: true,
+            components: false,
+            memory64: true,
+            threads: false,
+            pooling: true,
+            snapshots: true,
+            gc: true,
+        }
+    }
     
     fn get_metrics(&self) -> RuntimeMetrics {
-        self.metrics.lock().unwrap().clone()
+        let mut metrics = self.metrics.lock().unwrap().clone();
+        metrics.fuel_consumption_rate = self.recorder.fuel_rate();
+        
+        if let Some(pooling) = &self.config.pooling {
+            let live_instances = self.live_instances.load(Ordering::Relaxed);
+            metrics.pool = Some(PoolMetrics {
+                live_instances,
+                max_instances: pooling.max_instances,
+                utilization: live_instances as f64 / pooling.max_instances.max(1) as f64,
+            });
+        }
+        
+        metrics
+    }
+    
+    fn get_metrics_detailed(&self) -> DetailedMetrics {
+        self.recorder.detailed(self.get_metrics())
     }
     
     fn get_module_ids(&self) -> Vec<ModuleId> {
         self.modules.iter().map(|entry| *entry.key()).collect()
     }
     
+    fn engine(&self) -> RuntimeEngine {
+        RuntimeEngine::Wasmtime
+    }
+    
+    fn artifact_target(&self) -> Option<ArtifactTarget> {
+        Some(ArtifactTarget::host(EngineSettings::from(&self.config)))
+    }
+    
+    fn export_artifact(&self, id: ModuleId) -> Result<Vec<u8>> {
+        let module = self.modules.get(&id)
+            .ok_or_else(|| Error::config_error(format!("Module not found: {}", id), None))?;
+        module.module.serialize().map_err(|e| Error::Module {
+            operation: "export compiled module".to_string(),
+            reason: e.to_string(),
+            suggestion: None,
+        })
+    }
+    
+    fn import_artifact(&self, digest: ModuleDigest, artifact: &[u8]) -> Result<()> {
+        // SAFETY: wasmtime checks the artifact was compiled by a compatible engine; the
+        // caller vouches for where it came from, as documented on `import_cache`
+        let module = unsafe { Module::deserialize(&self.engine, artifact) }.map_err(|e| Error::Module {
+            operation: "import compiled module".to_string(),
+            reason: format!("{:#}", e),
+            suggestion: Some("Export the cache on a host with the same platform and RuntimeConfig".to_string()),
+        })?;
+        self.precompiled.insert(digest, module);
+        Ok(())
+    }
+    
     fn shutdown(&self) -> Result<()> {
         // Nothing specific to do for Wasmtime
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::PoolingConfig;
+    
+    const TEST_MODULE: &[u8] = include_bytes!("../../fixtures/test_module.wasm");
+    
+    #[test]
+    fn test_memory_pre_grow_and_page_usage() {
+        let runtime = WasmtimeRuntime::new(&RuntimeConfig::default()).unwrap();
+        let module = runtime.load_module(TEST_MODULE).unwrap();
+        
+        let mut resources = ResourceLimits::default();
+        resources.memory.pre_grow_pages = Some(8);
+        let instance = runtime.create_instance(module.as_ref(), resources, Capabilities::minimal()).unwrap();
+        
+        let pages = instance.memory_pages();
+        assert_eq!(pages.current, 8);
+        assert!(pages.peak >= pages.current);
+        
+        // Pre-growing past the memory limit is a configuration error
+        let mut resources = ResourceLimits::default();
+        resources.memory.pre_grow_pages = Some(resources.memory.max_memory_pages + 1);
+        assert!(runtime.create_instance(module.as_ref(), resources, Capabilities::minimal()).is_err());
+    }
+    
+    #[test]
+    fn test_pooling_allocator_reports_utilization() {
+        let config = RuntimeConfig {
+            pooling: Some(PoolingConfig {
+                total_memories: 20,
+                total_tables: 10,
+                max_instances: 10,
+                ..PoolingConfig::default()
+            }),
+            ..RuntimeConfig::default()
+        };
+        let runtime = WasmtimeRuntime::new(&config).unwrap();
+        let module = runtime.load_module(TEST_MODULE).unwrap();
+        
+        let first = runtime.create_instance(module.as_ref(), ResourceLimits::default(), Capabilities::minimal()).unwrap();
+        let _second = runtime.create_instance(module.as_ref(), ResourceLimits::default(), Capabilities::minimal()).unwrap();
+        let pool = runtime.get_metrics().pool.unwrap();
+        assert_eq!(pool.live_instances, 2);
+        assert!((pool.utilization - 0.2).abs() < f64::EPSILON);
+        
+        drop(first);
+        assert_eq!(runtime.get_metrics().pool.unwrap().live_instances, 1);
+    }
+}
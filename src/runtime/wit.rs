@@ -0,0 +1,303 @@
+//! WIT world of the host imports
+//!
+//! Every import the sandbox provides is described here, and [`host_world`]
+//! renders the description as a WIT package, published with the crate as
+//! `wit/host.wit`. Guest authors in any language with component tooling
+//! generate bindings from it instead of copying signatures from the docs.
+//!
+//! The world describes the core ABI the host implements: pointers, lengths
+//! and results are plain `s32` and `s64` values, which the canonical ABI
+//! passes through unchanged, so bindings generated for a core module call the
+//! host directly. Such bindings import the interfaces under their WIT names,
+//! e.g. `wasm-sandbox:host/sandbox-log` and `call-id`; the runtime links
+//! those names to the same functions as the core names `sandbox_log` and
+//! `call_id`, see [`core_import`].
+//!
+//! WASI-NN, which has its own published WIT, and host functions registered
+//! by the embedder are not part of the world.
+
+use std::fmt::Write;
+
+use self::WitType::{S32, S64};
+
+/// Package the host interfaces are published in
+pub const WIT_PACKAGE: &str = "wasm-sandbox:host";
+
+/// World importing every host interface
+pub const WIT_WORLD: &str = "sandbox-guest";
+
+/// Type of a parameter or result in the host world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WitType {
+    /// 32-bit integer, also used for guest pointers and lengths
+    S32,
+    
+    /// 64-bit integer, also used for packed `(ptr << 32) | len` results
+    S64,
+}
+
+impl WitType {
+    /// Name of the type in WIT
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WitType::S32 => "s32",
+            WitType::S64 => "s64",
+        }
+    }
+}
+
+/// A host function of the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WitFunction {
+    /// Name of the function in its core import module
+    pub name: &'static str,
+    
+    /// Names and types of the parameters
+    pub params: &'static [(&'static str, WitType)],
+    
+    /// Type of the result
+    pub result: WitType,
+    
+    /// What the function does and returns
+    pub docs: &'static str,
+}
+
+/// A core import module of the host, published as one WIT interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WitInterface {
+    /// Name of the core import module
+    pub module: &'static str,
+    
+    /// What the module provides
+    pub docs: &'static str,
+    
+    /// The module's functions
+    pub functions: &'static [WitFunction],
+}
+
+impl WitInterface {
+    /// Name of the interface in WIT
+    pub fn wit_name(&self) -> String {
+        kebab(self.module)
+    }
+    
+    /// Import module of the interface in bindings generated from the world
+    pub fn wit_import_module(&self) -> String {
+        format!("{}/{}", WIT_PACKAGE, self.wit_name())
+    }
+}
+
+impl WitFunction {
+    /// Name of the function in WIT
+    pub fn wit_name(&self) -> String {
+        kebab(self.name)
+    }
+}
+
+const fn function(
+    name: &'static str,
+    params: &'static [(&'static str, WitType)],
+    result: WitType,
+    docs: &'static str,
+) -> WitFunction {
+    WitFunction { name, params, result, docs }
+}
+
+/// Every interface of the host world
+pub const HOST_INTERFACES: &[WitInterface] = &[
+    WitInterface {
+        module: super::LOG_IMPORT_MODULE,
+        docs: "Structured logging tagged with the instance and the current call",
+        functions: &[
+            function(super::LOG_WRITE_FUNCTION, &[("level", S32), ("ptr", S32), ("len", S32)], S32,
+                "Log a UTF-8 message at a level from 1 (error) to 5 (trace); returns 0 or an error code"),
+            function(super::LOG_CALL_ID_FUNCTION, &[], S64,
+                "ID of the current call, or 0 outside a call"),
+            function(super::LOG_DIAGNOSTIC_FUNCTION, &[("ptr", S32), ("len", S32)], S32,
+                "Attach a JSON diagnostic to the current call's result; returns 0 or an error code"),
+        ],
+    },
+    WitInterface {
+        module: super::CONFIG_IMPORT_MODULE,
+        docs: "Plugin settings the host configured for the instance",
+        functions: &[
+            function(super::CONFIG_GET_FUNCTION, &[("key-ptr", S32), ("key-len", S32)], S64,
+                "JSON value of a dotted key, as (ptr << 32) | len in memory from the guest's alloc export, or an error code"),
+        ],
+    },
+    WitInterface {
+        module: super::SECRETS_IMPORT_MODULE,
+        docs: "Secrets the instance is granted",
+        functions: &[
+            function(super::SECRETS_GET_FUNCTION, &[("name-ptr", S32), ("name-len", S32)], S64,
+                "Value of a secret as (ptr << 32) | len, or an error code"),
+        ],
+    },
+    WitInterface {
+        module: super::SERVICE_IMPORT_MODULE,
+        docs: "Calls to services exposed by other instances",
+        functions: &[
+            function(
+                super::SERVICE_CALL_FUNCTION,
+                &[
+                    ("service-ptr", S32),
+                    ("service-len", S32),
+                    ("function-ptr", S32),
+                    ("function-len", S32),
+                    ("payload-ptr", S32),
+                    ("payload-len", S32),
+                ],
+                S64,
+                "Call a service's function; returns the response as (ptr << 32) | len, or an error code",
+            ),
+        ],
+    },
+    WitInterface {
+        module: super::STREAM_IMPORT_MODULE,
+        docs: "Partial results streamed to the caller",
+        functions: &[
+            function(super::STREAM_EMIT_FUNCTION, &[("ptr", S32), ("len", S32)], S32,
+                "Emit a JSON partial result; returns 0 to continue or 1 once the consumer has gone away"),
+        ],
+    },
+    WitInterface {
+        module: super::PROGRESS_IMPORT_MODULE,
+        docs: "Progress reports from long calls, which double as cancellation checkpoints",
+        functions: &[
+            function(super::PROGRESS_REPORT_FUNCTION, &[("percent", S32), ("ptr", S32), ("len", S32)], S32,
+                "Report progress from 0 to 100 with a UTF-8 message; returns 0 to continue or 1 if the call was cancelled"),
+        ],
+    },
+    WitInterface {
+        module: super::ALLOC_IMPORT_MODULE,
+        docs: "Allocation failures, reported so calls fail with a memory error rather than a trap",
+        functions: &[
+            function(super::ALLOC_FAILED_FUNCTION, &[("size", S64)], S32,
+                "Report that an allocation of size bytes failed; returns 0"),
+        ],
+    },
+    WitInterface {
+        module: super::TIMER_IMPORT_MODULE,
+        docs: "Timers calling the guest's on-timer export when due",
+        functions: &[
+            function(super::TIMER_SET_FUNCTION, &[("delay-ms", S64), ("token", S64)], S64,
+                "Arm a timer passing token to the guest after delay-ms; returns its ID or an error code"),
+            function(super::TIMER_CANCEL_FUNCTION, &[("timer-id", S64)], S64,
+                "Disarm a timer; returns 0 or an error code if it already fired"),
+        ],
+    },
+    WitInterface {
+        module: super::CHECKPOINT_IMPORT_MODULE,
+        docs: "Checkpoints of the instance requested at safe points",
+        functions: &[
+            function(super::CHECKPOINT_FUNCTION, &[], S64,
+                "Write the instance's memory and globals to disk; returns the checkpoint's ID or an error code"),
+        ],
+    },
+    WitInterface {
+        module: super::DNS_IMPORT_MODULE,
+        docs: "Domain names resolved by the host under the instance's DNS policy",
+        functions: &[
+            function(super::DNS_RESOLVE_FUNCTION, &[("name-ptr", S32), ("name-len", S32)], S64,
+                "Addresses of a name, 16 bytes each, as (ptr << 32) | len, or an error code"),
+        ],
+    },
+    WitInterface {
+        module: super::HTTP_IMPORT_MODULE,
+        docs: "HTTP requests sent by the host under its egress policy",
+        functions: &[
+            function(super::HTTP_FETCH_FUNCTION, &[("request-ptr", S32), ("request-len", S32)], S64,
+                "Send a JSON request; returns the JSON response as (ptr << 32) | len, or an error code"),
+        ],
+    },
+    WitInterface {
+        module: super::CHILD_IMPORT_MODULE,
+        docs: "Child instances, the sandboxed alternative to processes",
+        functions: &[
+            function(super::CHILD_SPAWN_FUNCTION, &[("name-ptr", S32), ("name-len", S32)], S64,
+                "Start a registered module; returns a child handle or an error code"),
+            function(
+                super::CHILD_CALL_FUNCTION,
+                &[
+                    ("child", S32),
+                    ("function-ptr", S32),
+                    ("function-len", S32),
+                    ("payload-ptr", S32),
+                    ("payload-len", S32),
+                ],
+                S64,
+                "Call a child's export; returns its output as (ptr << 32) | len, or an error code",
+            ),
+            function(super::CHILD_KILL_FUNCTION, &[("child", S32)], S32,
+                "Stop a child early; returns 0 or an error code"),
+        ],
+    },
+    WitInterface {
+        module: super::stdlib::STDLIB_IMPORT_MODULE,
+        docs: "Standard host functions, linked once the import policy allows them",
+        functions: &[
+            function("monotonic_now", &[], S64, "Nanoseconds since the instance was created"),
+            function("random_fill", &[("ptr", S32), ("len", S32)], S32,
+                "Fill a buffer with random bytes; returns 0 or an error code"),
+            function("uuid_v4", &[("out-ptr", S32)], S32,
+                "Write a random UUID's 16 bytes; returns 0 or an error code"),
+            function("sha256", &[("ptr", S32), ("len", S32), ("out-ptr", S32)], S32,
+                "Write the 32-byte SHA-256 of a buffer; returns 0 or an error code"),
+            function("base64_encode", &[("ptr", S32), ("len", S32)], S64,
+                "Base64 text of a buffer as (ptr << 32) | len, or an error code"),
+            function("base64_decode", &[("ptr", S32), ("len", S32)], S64,
+                "Bytes of base64 text as (ptr << 32) | len, or an error code"),
+        ],
+    },
+];
+
+/// Render the host world as a WIT package
+pub fn host_world() -> String {
+    let mut wit = String::new();
+    let _ = writeln!(wit, "// Host imports of wasm-sandbox, generated by `wasm_sandbox::runtime::wit::host_world`");
+    let _ = writeln!(wit, "//");
+    let _ = writeln!(wit, "// Functions return a negative error code on failure; see GuestErrorCode.");
+    let _ = writeln!(wit, "package {};", WIT_PACKAGE);
+    
+    for interface in HOST_INTERFACES {
+        let _ = writeln!(wit);
+        let _ = writeln!(wit, "/// {}", interface.docs);
+        let _ = writeln!(wit, "interface {} {{", interface.wit_name());
+        for (n, function) in interface.functions.iter().enumerate() {
+            if n > 0 {
+                let _ = writeln!(wit);
+            }
+            let params: Vec<String> = function.params.iter()
+                .map(|(name, ty)| format!("{}: {}", name, ty.as_str()))
+                .collect();
+            let _ = writeln!(wit, "    /// {}", function.docs);
+            let _ = writeln!(wit, "    {}: func({}) -> {};", function.wit_name(), params.join(", "), function.result.as_str());
+        }
+        let _ = writeln!(wit, "}}");
+    }
+    
+    let _ = writeln!(wit);
+    let _ = writeln!(wit, "/// Everything a guest of the sandbox may import");
+    let _ = writeln!(wit, "world {} {{", WIT_WORLD);
+    for interface in HOST_INTERFACES {
+        let _ = writeln!(wit, "    import {};", interface.wit_name());
+    }
+    let _ = writeln!(wit, "}}");
+    wit
+}
+
+/// Core import a function imported under its WIT names stands for
+///
+/// Returns the core module and function names for imports of the host
+/// world, e.g. `("sandbox_log", "call_id")` for
+/// `wasm-sandbox:host/sandbox-log` `call-id`, and `None` for any other import.
+pub fn core_import(module: &str, name: &str) -> Option<(&'static str, &'static str)> {
+    let interface_name = module.strip_prefix(WIT_PACKAGE)?.strip_prefix('/')?;
+    let interface = HOST_INTERFACES.iter().find(|interface| interface.wit_name() == interface_name)?;
+    let function = interface.functions.iter().find(|function| function.wit_name() == name)?;
+    Some((interface.module, function.name))
+}
+
+fn kebab(name: &str) -> String {
+    name.replace('_', "-")
+}
@@ -0,0 +1,309 @@
+//! Structured concurrency for groups of guest calls
+//!
+//! A [`CallScope`] runs calls against any number of instances at once, like a
+//! [`tokio::task::JoinSet`] of `call_function` futures. Unlike aborting a
+//! JoinSet, cancelling a scope (or letting its deadline pass, or dropping it)
+//! interrupts the guest code that is still running, so no call outlives its
+//! scope.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::task::{AbortHandle, JoinSet};
+use tokio::time::Instant;
+
+use crate::error::{Error, Result};
+use crate::runtime::abi::AbiFunctionCaller;
+use crate::runtime::call_context::ActiveCall;
+use crate::runtime::result_cache::{module_digest, CacheKey, ResultCache};
+use crate::runtime::result_validation::ResultValidators;
+use crate::runtime::{GuestInterrupt, ModuleId, WasmFunctionCallerAsync};
+use crate::{parse_result_json, redact_error, InstanceId, WasmSandbox};
+
+/// Identifies a call spawned in a [`CallScope`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScopedCallId(u64);
+
+/// The scope is still accepting and running calls
+const OPEN: u8 = 0;
+
+/// The scope was cancelled or dropped
+const CANCELLED: u8 = 1;
+
+/// The scope's deadline passed
+const EXPIRED: u8 = 2;
+
+/// State shared between a scope and its running calls
+struct ScopeState {
+    status: AtomicU8,
+    timeout: Mutex<Option<Duration>>,
+    running: Mutex<HashMap<ScopedCallId, Arc<dyn GuestInterrupt>>>,
+}
+
+impl ScopeState {
+    /// End the scope and interrupt every running call; the first reason wins
+    fn end(&self, status: u8) {
+        if self.status.compare_exchange(OPEN, status, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+            return;
+        }
+        for interrupt in self.running.lock().unwrap().values() {
+            interrupt.interrupt();
+        }
+    }
+    
+    /// Error reported for calls stopped by the end of the scope
+    fn ended_error(&self, instance_id: InstanceId, function_name: &str) -> Option<Error> {
+        match self.status.load(Ordering::SeqCst) {
+            CANCELLED => Some(Error::FunctionCall {
+                function_name: function_name.to_string(),
+                reason: "Call was cancelled with its scope".to_string(),
+            }),
+            EXPIRED => Some(Error::Timeout {
+                operation: format!("scoped call to {}", function_name),
+                duration: self.timeout.lock().unwrap().unwrap_or_default(),
+                instance_id: Some(instance_id.as_uuid()),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Group of concurrent guest calls with a shared deadline and cancellation
+///
+/// Created with [`WasmSandbox::scope`]. Calls run as tasks on the current Tokio
+/// runtime. Calls to the same instance run one at a time, taking turns with
+/// [`WasmSandbox::call_function`].
+pub struct CallScope<'a, R> {
+    sandbox: &'a WasmSandbox,
+    tasks: JoinSet<(ScopedCallId, Result<R>)>,
+    state: Arc<ScopeState>,
+    deadline: Option<Instant>,
+    watchdog: Option<AbortHandle>,
+    next_id: u64,
+}
+
+impl<'a, R> CallScope<'a, R>
+where
+    R: DeserializeOwned + Send + 'static,
+{
+    pub(crate) fn new(sandbox: &'a WasmSandbox) -> Self {
+        Self {
+            sandbox,
+            tasks: JoinSet::new(),
+            state: Arc::new(ScopeState {
+                status: AtomicU8::new(OPEN),
+                timeout: Mutex::new(None),
+                running: Mutex::new(HashMap::new()),
+            }),
+            deadline: None,
+            watchdog: None,
+            next_id: 0,
+        }
+    }
+    
+    /// Interrupt every call still running `timeout` from now
+    pub fn with_deadline(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Instant::now() + timeout);
+        *self.state.timeout.lock().unwrap() = Some(timeout);
+        if let Some(watchdog) = self.watchdog.take() {
+            watchdog.abort();
+        }
+        if !self.tasks.is_empty() {
+            self.start_watchdog();
+        }
+        self
+    }
+    
+    /// Start calling a function in an instance
+    ///
+    /// The call is subject to the instance's function policies and pure
+    /// function cache, as with [`WasmSandbox::call_function`].
+    pub fn spawn<P: Serialize>(&mut self, instance_id: InstanceId, function_name: &str, params: P) -> Result<ScopedCallId> {
+        let instance = self.sandbox.instances.get(&instance_id).ok_or_else(|| Error::NotFound {
+            resource_type: "instance".to_string(),
+            identifier: instance_id.to_string(),
+        })?;
+        self.sandbox.check_callable(instance_id, &instance.config, function_name)?;
+        self.sandbox.touch(instance_id);
+        self.start_watchdog();
+        
+        let id = ScopedCallId(self.next_id);
+        self.next_id += 1;
+        
+        let params_json = serde_json::to_string(&params)?;
+        let cache = instance.config.pure_functions.contains(function_name).then(|| {
+            let module = self.sandbox.module_digests.read().unwrap().get(&instance.module_id).copied()
+                .unwrap_or_else(|| module_digest(instance.module_id.to_string().as_bytes()));
+            (self.sandbox.result_cache.clone(), CacheKey::new(module, function_name, &params_json))
+        });
+        let call = ScopedCall {
+            id,
+            instance_id,
+            function_name: function_name.to_string(),
+            params_json,
+            caller: AbiFunctionCaller::new(instance.instance.clone(), instance.abi),
+            interrupt: instance.instance.interrupt_handle(),
+            policy: instance.config.function_policies.get(function_name)
+                .map(|policy| (instance.active_capabilities.clone(), policy.clone())),
+            cache,
+            validators: self.sandbox.result_validators.clone(),
+            module_id: instance.module_id,
+            turn: instance.call_turn.clone(),
+            state: self.state.clone(),
+        };
+        let redaction = self.sandbox.config.redaction.clone();
+        let raw_errors = self.sandbox.raw_errors.clone();
+        let context = self.sandbox.new_call(instance_id, function_name);
+        
+        self.tasks.spawn(ActiveCall::scope(context, async move {
+            let result = call.run().await
+                .map_err(|e| redact_error(&redaction, &raw_errors, instance_id, e));
+            (id, result)
+        }));
+        Ok(id)
+    }
+    
+    /// Wait for the next call to finish, or `None` once no calls are left
+    pub async fn join_next(&mut self) -> Option<(ScopedCallId, Result<R>)> {
+        loop {
+            match self.tasks.join_next().await? {
+                Ok(outcome) => return Some(outcome),
+                // Calls are never aborted, so this is a panic in the call
+                Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+                Err(_) => continue,
+            }
+        }
+    }
+    
+    /// Wait for every call to finish, returning the results in spawn order
+    pub async fn join_all(mut self) -> Vec<Result<R>> {
+        let mut outcomes = Vec::with_capacity(self.tasks.len());
+        while let Some(outcome) = self.join_next().await {
+            outcomes.push(outcome);
+        }
+        outcomes.sort_by_key(|(id, _)| *id);
+        outcomes.into_iter().map(|(_, result)| result).collect()
+    }
+    
+    /// Interrupt every running call; calls spawned afterwards fail immediately
+    pub fn cancel(&self) {
+        self.state.end(CANCELLED);
+    }
+    
+    /// Whether the scope was cancelled or its deadline passed
+    pub fn is_ended(&self) -> bool {
+        self.state.status.load(Ordering::SeqCst) != OPEN
+    }
+    
+    /// Number of calls that have not been joined yet
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+    
+    /// Whether every call has been joined
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+    
+    /// End the scope when its deadline passes, even if nobody is joining
+    fn start_watchdog(&mut self) {
+        let (Some(deadline), None) = (self.deadline, &self.watchdog) else {
+            return;
+        };
+        let state = self.state.clone();
+        let watchdog = tokio::spawn(async move {
+            tokio::time::sleep_until(deadline).await;
+            state.end(EXPIRED);
+        });
+        self.watchdog = Some(watchdog.abort_handle());
+    }
+}
+
+impl<R> Drop for CallScope<'_, R> {
+    fn drop(&mut self) {
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.abort();
+        }
+        self.state.end(CANCELLED);
+    }
+}
+
+/// Everything a task needs to run one scoped call
+struct ScopedCall {
+    id: ScopedCallId,
+    instance_id: InstanceId,
+    function_name: String,
+    params_json: String,
+    caller: AbiFunctionCaller,
+    interrupt: Option<Arc<dyn GuestInterrupt>>,
+    policy: Option<(crate::security::capabilities::ActiveCapabilities, crate::security::Capabilities)>,
+    cache: Option<(Arc<ResultCache>, CacheKey)>,
+    validators: Arc<ResultValidators>,
+    module_id: ModuleId,
+    turn: Arc<tokio::sync::Mutex<()>>,
+    state: Arc<ScopeState>,
+}
+
+impl ScopedCall {
+    async fn run<R: DeserializeOwned>(self) -> Result<R> {
+        if let Some((cache, key)) = &self.cache
+            && let Some(result_json) = cache.get(key)
+        {
+            return parse_result_json(&self.function_name, &result_json);
+        }
+        
+        let _turn = self.turn.lock().await;
+        
+        // Register before checking the status so `end` can't miss this call
+        let running = Running::register(&self);
+        if let Some(error) = self.state.ended_error(self.instance_id, &self.function_name) {
+            return Err(error);
+        }
+        
+        let result_json = {
+            let _capability_scope = self.policy.as_ref()
+                .map(|(active, policy)| active.enter(&self.function_name, policy.clone()));
+            self.caller.call_function_json_async(&self.function_name, &self.params_json).await
+        };
+        drop(running);
+        
+        let result_json = match result_json {
+            Ok(result_json) => result_json,
+            Err(e) => return Err(self.state.ended_error(self.instance_id, &self.function_name).unwrap_or(e)),
+        };
+        self.validators.check_json(self.module_id, &self.function_name, &result_json)?;
+        let result = parse_result_json(&self.function_name, &result_json)?;
+        if let Some((cache, key)) = self.cache {
+            cache.insert(key, result_json);
+        }
+        Ok(result)
+    }
+}
+
+/// Registers a call as running until dropped, including when its task is aborted
+struct Running<'a> {
+    call: &'a ScopedCall,
+}
+
+impl<'a> Running<'a> {
+    fn register(call: &'a ScopedCall) -> Self {
+        if let Some(interrupt) = &call.interrupt {
+            call.state.running.lock().unwrap().insert(call.id, interrupt.clone());
+        }
+        Self { call }
+    }
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        // An interrupt that lands after the guest returned must not hit the next call
+        self.call.state.running.lock().unwrap().remove(&self.call.id);
+        if let Some(interrupt) = &self.call.interrupt {
+            interrupt.clear();
+        }
+    }
+}
@@ -6,6 +6,11 @@ use std::time::{Duration, SystemTime};
 
 use serde::{Serialize, Deserialize};
 
+use crate::observability::journal::{Journal, JournalEvent};
+use crate::runtime::call_context::{current_call_id, CallId};
+#[cfg(feature = "sqlite-audit")]
+use crate::security::audit_store::AuditStore;
+
 /// Severity level for audit events
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AuditSeverity {
@@ -112,6 +117,123 @@ pub enum AuditEventType {
         size: usize 
     },
     
+    /// Brokered call from one instance to a service exposed by another
+    ServiceCall {
+        /// Calling instance ID
+        caller_id: String,
+        
+        /// Service name
+        service: String,
+        
+        /// Function name
+        function: String,
+        
+        /// Whether the call was allowed
+        allowed: bool,
+    },
+    
+    /// Guest request for a host-provisioned secret
+    SecretAccess {
+        /// Requesting instance ID
+        instance_id: String,
+        
+        /// Secret name (never the value)
+        name: String,
+        
+        /// Whether the request was granted
+        allowed: bool,
+    },
+    
+    /// Guest request to resolve a domain name on the host
+    DnsResolution {
+        /// Requesting instance ID
+        instance_id: String,
+        
+        /// Name to resolve
+        name: String,
+        
+        /// Whether the request was allowed
+        allowed: bool,
+    },
+    
+    /// HTTP request the host sends on a guest's behalf
+    HttpRequest {
+        /// Requesting instance ID
+        instance_id: String,
+        
+        /// Request method
+        method: String,
+        
+        /// Request URL
+        url: String,
+        
+        /// Whether the request was allowed
+        allowed: bool,
+    },
+    
+    /// Capability granted to a running instance
+    CapabilityGranted {
+        /// Instance ID
+        instance_id: String,
+        
+        /// Capability granted
+        capability: String,
+        
+        /// How long the grant lasts, or `None` until revoked
+        ttl_ms: Option<u64>,
+    },
+    
+    /// Runtime capability grant withdrawn
+    CapabilityRevoked {
+        /// Instance ID
+        instance_id: String,
+        
+        /// Capability withdrawn
+        capability: String,
+        
+        /// Whether the grant lapsed rather than being revoked
+        expired: bool,
+    },
+    
+    /// Guest call to a capability extension's function
+    ExtensionCall {
+        /// Calling instance ID
+        instance_id: String,
+        
+        /// Extension namespace
+        namespace: String,
+        
+        /// Function called
+        function: String,
+        
+        /// Whether the caller's grant allowed the call
+        allowed: bool,
+    },
+    
+    /// Host file staged into an instance's inbox
+    FileStaged {
+        /// Receiving instance ID
+        instance_id: String,
+        
+        /// File name within the inbox
+        name: String,
+        
+        /// SHA-256 of the contents as submitted, in hex
+        sha256: String,
+        
+        /// Whether the file was staged
+        accepted: bool,
+    },
+    
+    /// Host call to an export the instance's callable functions block
+    FunctionCallDenied {
+        /// Instance ID
+        instance_id: String,
+        
+        /// Function name
+        function_name: String,
+    },
+    
     /// Custom event
     Custom { 
         /// Event type
@@ -122,6 +244,139 @@ pub enum AuditEventType {
     },
 }
 
+/// Type of an audit event, without its data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AuditEventKind {
+    /// Module loaded
+    ModuleLoaded,
+    
+    /// Instance created
+    InstanceCreated,
+    
+    /// Instance terminated
+    InstanceTerminated,
+    
+    /// Function called
+    FunctionCall,
+    
+    /// Resource limit reached
+    ResourceLimit,
+    
+    /// Capability violation
+    CapabilityViolation,
+    
+    /// Host function called
+    HostFunctionCall,
+    
+    /// Memory accessed
+    MemoryAccess,
+    
+    /// Service called through the broker
+    ServiceCall,
+    
+    /// Secret released or refused
+    SecretAccess,
+    
+    /// Domain name resolved or refused
+    DnsResolution,
+    
+    /// HTTP request sent or refused
+    HttpRequest,
+    
+    /// Capability granted
+    CapabilityGranted,
+    
+    /// Capability revoked
+    CapabilityRevoked,
+    
+    /// Capability extension called
+    ExtensionCall,
+    
+    /// File submitted to an inbox
+    FileStaged,
+    
+    /// Host call to a blocked export
+    FunctionCallDenied,
+    
+    /// Custom event
+    Custom,
+}
+
+impl AuditEventKind {
+    /// Name of the kind, as stored in an audit store
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventKind::ModuleLoaded => "ModuleLoaded",
+            AuditEventKind::InstanceCreated => "InstanceCreated",
+            AuditEventKind::InstanceTerminated => "InstanceTerminated",
+            AuditEventKind::FunctionCall => "FunctionCall",
+            AuditEventKind::ResourceLimit => "ResourceLimit",
+            AuditEventKind::CapabilityViolation => "CapabilityViolation",
+            AuditEventKind::HostFunctionCall => "HostFunctionCall",
+            AuditEventKind::MemoryAccess => "MemoryAccess",
+            AuditEventKind::ServiceCall => "ServiceCall",
+            AuditEventKind::SecretAccess => "SecretAccess",
+            AuditEventKind::DnsResolution => "DnsResolution",
+            AuditEventKind::HttpRequest => "HttpRequest",
+            AuditEventKind::CapabilityGranted => "CapabilityGranted",
+            AuditEventKind::CapabilityRevoked => "CapabilityRevoked",
+            AuditEventKind::ExtensionCall => "ExtensionCall",
+            AuditEventKind::FileStaged => "FileStaged",
+            AuditEventKind::FunctionCallDenied => "FunctionCallDenied",
+            AuditEventKind::Custom => "Custom",
+        }
+    }
+}
+
+impl AuditEventType {
+    /// Type of the event, without its data
+    pub fn kind(&self) -> AuditEventKind {
+        match self {
+            AuditEventType::ModuleLoaded { .. } => AuditEventKind::ModuleLoaded,
+            AuditEventType::InstanceCreated { .. } => AuditEventKind::InstanceCreated,
+            AuditEventType::InstanceTerminated { .. } => AuditEventKind::InstanceTerminated,
+            AuditEventType::FunctionCall { .. } => AuditEventKind::FunctionCall,
+            AuditEventType::ResourceLimit { .. } => AuditEventKind::ResourceLimit,
+            AuditEventType::CapabilityViolation { .. } => AuditEventKind::CapabilityViolation,
+            AuditEventType::HostFunctionCall { .. } => AuditEventKind::HostFunctionCall,
+            AuditEventType::MemoryAccess { .. } => AuditEventKind::MemoryAccess,
+            AuditEventType::ServiceCall { .. } => AuditEventKind::ServiceCall,
+            AuditEventType::SecretAccess { .. } => AuditEventKind::SecretAccess,
+            AuditEventType::DnsResolution { .. } => AuditEventKind::DnsResolution,
+            AuditEventType::HttpRequest { .. } => AuditEventKind::HttpRequest,
+            AuditEventType::CapabilityGranted { .. } => AuditEventKind::CapabilityGranted,
+            AuditEventType::CapabilityRevoked { .. } => AuditEventKind::CapabilityRevoked,
+            AuditEventType::ExtensionCall { .. } => AuditEventKind::ExtensionCall,
+            AuditEventType::FileStaged { .. } => AuditEventKind::FileStaged,
+            AuditEventType::FunctionCallDenied { .. } => AuditEventKind::FunctionCallDenied,
+            AuditEventType::Custom { .. } => AuditEventKind::Custom,
+        }
+    }
+    
+    /// ID of the instance the event is about, if any
+    pub fn instance_id(&self) -> Option<&str> {
+        match self {
+            AuditEventType::InstanceCreated { id }
+            | AuditEventType::InstanceTerminated { id, .. } => Some(id),
+            AuditEventType::FunctionCall { instance_id, .. }
+            | AuditEventType::ResourceLimit { instance_id, .. }
+            | AuditEventType::CapabilityViolation { instance_id, .. }
+            | AuditEventType::HostFunctionCall { instance_id, .. }
+            | AuditEventType::MemoryAccess { instance_id, .. }
+            | AuditEventType::SecretAccess { instance_id, .. }
+            | AuditEventType::DnsResolution { instance_id, .. }
+            | AuditEventType::HttpRequest { instance_id, .. }
+            | AuditEventType::CapabilityGranted { instance_id, .. }
+            | AuditEventType::CapabilityRevoked { instance_id, .. }
+            | AuditEventType::ExtensionCall { instance_id, .. }
+            | AuditEventType::FileStaged { instance_id, .. }
+            | AuditEventType::FunctionCallDenied { instance_id, .. } => Some(instance_id),
+            AuditEventType::ServiceCall { caller_id, .. } => Some(caller_id),
+            AuditEventType::ModuleLoaded { .. } | AuditEventType::Custom { .. } => None,
+        }
+    }
+}
+
 /// Audit event record
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditEvent {
@@ -136,6 +391,10 @@ pub struct AuditEvent {
     
     /// Message
     pub message: String,
+    
+    /// Call running when the event was logged, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<CallId>,
 }
 
 /// Audit logger for the sandbox
@@ -155,6 +414,13 @@ pub struct AuditLogger {
     
     /// File path for logging
     file_path: Option<String>,
+    
+    /// Journal events are also recorded in
+    journal: Option<Arc<Journal>>,
+    
+    /// Database events are persisted to
+    #[cfg(feature = "sqlite-audit")]
+    store: Option<Arc<AuditStore>>,
 }
 
 impl AuditLogger {
@@ -166,6 +432,9 @@ impl AuditLogger {
             log_to_stdout: false,
             log_to_file: false,
             file_path: None,
+            journal: None,
+            #[cfg(feature = "sqlite-audit")]
+            store: None,
         }
     }
     
@@ -182,6 +451,19 @@ impl AuditLogger {
         self
     }
     
+    /// Also record events in a sandbox's journal
+    pub(crate) fn journaled(mut self, journal: Arc<Journal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+    
+    /// Also persist events to an audit store
+    #[cfg(feature = "sqlite-audit")]
+    pub fn with_store(mut self, store: Arc<AuditStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+    
     /// Log an event
     pub fn log(&self, severity: AuditSeverity, event_type: AuditEventType, message: &str) {
         let event = AuditEvent {
@@ -189,6 +471,7 @@ impl AuditLogger {
             severity,
             event_type,
             message: message.to_string(),
+            call_id: current_call_id(),
         };
         
         // Log to stdout if enabled
@@ -204,26 +487,40 @@ impl AuditLogger {
                 AuditSeverity::Critical => "CRITICAL",
             };
             
-            println!("[{}] {} - {} - {:?}", timestamp, level, event.message, event.event_type);
+            match event.call_id {
+                Some(call_id) => println!("[{}] {} - [call {}] {} - {:?}", timestamp, level, call_id, event.message, event.event_type),
+                None => println!("[{}] {} - {} - {:?}", timestamp, level, event.message, event.event_type),
+            }
         }
         
         // Log to file if enabled
-        if self.log_to_file {
-            if let Some(file_path) = &self.file_path {
-                // Open the file in append mode
-                let json = serde_json::to_string(&event).unwrap_or_else(|_| "{}".to_string());
-                
-                // Append to file
-                std::fs::OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(file_path)
-                    .map(|mut file| {
-                        use std::io::Write;
-                        let _ = writeln!(file, "{}", json);
-                    })
-                    .ok();
-            }
+        if self.log_to_file
+            && let Some(file_path) = &self.file_path
+        {
+            // Open the file in append mode
+            let json = serde_json::to_string(&event).unwrap_or_else(|_| "{}".to_string());
+            
+            // Append to file
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(file_path)
+                .map(|mut file| {
+                    use std::io::Write;
+                    let _ = writeln!(file, "{}", json);
+                })
+                .ok();
+        }
+        
+        if let Some(journal) = &self.journal {
+            journal.record(JournalEvent::Audit { record: event.clone() });
+        }
+        
+        #[cfg(feature = "sqlite-audit")]
+        if let Some(store) = &self.store
+            && let Err(e) = store.record(&event)
+        {
+            log::warn!("Failed to persist audit event: {}", e);
         }
         
         // Store in memory
@@ -469,10 +766,10 @@ impl SecurityScanner {
             for event in &memory_accesses {
                 if let AuditEventType::MemoryAccess { 
                     address, size, access_type, .. 
-                } = &event.event_type {
-                    if access_type == "write" && *size > 1024 && *address > 0xFFFF0000 {
-                        suspicious_addresses.insert(*address);
-                    }
+                } = &event.event_type
+                    && access_type == "write" && *size > 1024 && *address > 0xFFFF0000
+                {
+                    suspicious_addresses.insert(*address);
                 }
             }
             
@@ -0,0 +1,437 @@
+//! Persistent store of audit events
+//!
+//! The audit loggers of a sandbox keep their events in memory, where they are
+//! lost with the sandbox. Setting [`crate::SandboxConfig::audit_store`] also
+//! writes every audit event, unredacted and unsampled, to an SQLite database,
+//! one file per sandbox. Investigations then pick events out with an
+//! [`AuditQuery`] and export them as JSON lines or CSV.
+//!
+//! The store needs the `sqlite-audit` feature. Old events are pruned under the
+//! retention settings of its [`AuditStoreConfig`], and a full database can be
+//! rotated to numbered files beside it, which are SQLite databases with the
+//! same `audit` table.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+
+#[cfg(feature = "sqlite-audit")]
+use std::io::Write;
+#[cfg(feature = "sqlite-audit")]
+use std::path::Path;
+#[cfg(feature = "sqlite-audit")]
+use std::sync::Mutex;
+#[cfg(feature = "sqlite-audit")]
+use std::time::SystemTime;
+
+#[cfg(feature = "sqlite-audit")]
+use rusqlite::types::Value as SqlValue;
+
+#[cfg(feature = "sqlite-audit")]
+use crate::security::audit::{AuditEvent, AuditEventKind, AuditSeverity};
+
+/// Where a sandbox persists its audit events, and for how long
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditStoreConfig {
+    /// Path of the SQLite database, created if missing
+    pub path: PathBuf,
+    
+    /// Events older than this are pruned, or `None` to keep them
+    pub max_age: Option<Duration>,
+    
+    /// Most events kept in the database; the oldest are pruned first
+    pub max_events: Option<usize>,
+    
+    /// Events written to the database before it is rotated, or `None` to never rotate
+    pub rotate_after: Option<usize>,
+    
+    /// Rotated databases kept beside the current one, as `<path>.1`, `<path>.2` and so on
+    pub keep_rotated: usize,
+}
+
+impl AuditStoreConfig {
+    /// Keep every event in the database at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_age: None,
+            max_events: None,
+            rotate_after: None,
+            keep_rotated: 0,
+        }
+    }
+    
+    /// Prune events older than `max_age`
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+    
+    /// Prune the oldest events beyond `max_events`
+    pub fn max_events(mut self, max_events: usize) -> Self {
+        self.max_events = Some(max_events);
+        self
+    }
+    
+    /// Rotate the database after `events` events, keeping `keep` rotated files
+    pub fn rotate_after(mut self, events: usize, keep: usize) -> Self {
+        self.rotate_after = Some(events);
+        self.keep_rotated = keep;
+        self
+    }
+    
+    /// Check that the store can be opened with these settings
+    pub fn validate(&self) -> Result<()> {
+        if self.path.as_os_str().is_empty() {
+            return Err(Error::InvalidInput {
+                field: "audit_store.path".to_string(),
+                reason: "The path of the audit store is empty".to_string(),
+                suggestion: None,
+            });
+        }
+        for (field, value) in [("max_events", self.max_events), ("rotate_after", self.rotate_after)] {
+            if value == Some(0) {
+                return Err(Error::InvalidInput {
+                    field: format!("audit_store.{}", field),
+                    reason: "The store would keep no events".to_string(),
+                    suggestion: Some("Use None to keep events without a limit".to_string()),
+                });
+            }
+        }
+        if !cfg!(feature = "sqlite-audit") {
+            return Err(Error::config_error(
+                "A persistent audit store needs the sqlite-audit feature",
+                Some("Enable the sqlite-audit feature of wasm-sandbox or remove the audit store".to_string()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// SQLite database of a sandbox's audit events
+///
+/// Events are rows of the `audit` table, holding their `timestamp_ms`,
+/// `severity`, `kind`, `instance_id` and `message`, and the whole event as
+/// JSON in `event`.
+#[cfg(feature = "sqlite-audit")]
+#[derive(Debug)]
+pub struct AuditStore {
+    config: AuditStoreConfig,
+    state: Mutex<StoreState>,
+}
+
+#[cfg(feature = "sqlite-audit")]
+#[derive(Debug)]
+struct StoreState {
+    connection: rusqlite::Connection,
+    
+    /// Events written since the database was created or last rotated
+    written: usize,
+}
+
+#[cfg(feature = "sqlite-audit")]
+impl AuditStore {
+    /// Open the store `config` describes, creating its database if needed
+    pub fn open(config: AuditStoreConfig) -> Result<Self> {
+        config.validate()?;
+        let (connection, written) = Self::connect(&config.path)?;
+        Ok(Self {
+            config,
+            state: Mutex::new(StoreState { connection, written }),
+        })
+    }
+    
+    fn connect(path: &Path) -> Result<(rusqlite::Connection, usize)> {
+        let connection = rusqlite::Connection::open(path).map_err(sqlite_error)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS audit (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp_ms INTEGER NOT NULL,
+                severity TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                instance_id TEXT,
+                message TEXT NOT NULL,
+                event TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS audit_timestamp ON audit (timestamp_ms);
+            CREATE INDEX IF NOT EXISTS audit_instance ON audit (instance_id);",
+        ).map_err(sqlite_error)?;
+        let written: i64 = connection.query_row("SELECT COUNT(*) FROM audit", (), |row| row.get(0)).map_err(sqlite_error)?;
+        Ok((connection, written as usize))
+    }
+    
+    /// Settings the store was opened with
+    pub fn config(&self) -> &AuditStoreConfig {
+        &self.config
+    }
+    
+    /// Write an event, then rotate and prune as configured
+    pub fn record(&self, event: &AuditEvent) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if self.config.rotate_after.is_some_and(|limit| state.written >= limit) {
+            self.rotate(&mut state)?;
+        }
+        
+        state.connection.execute(
+            "INSERT INTO audit (timestamp_ms, severity, kind, instance_id, message, event) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                timestamp_ms(event.timestamp),
+                severity_name(event.severity),
+                event.event_type.kind().as_str(),
+                event.event_type.instance_id(),
+                &event.message,
+                serde_json::to_string(event)?,
+            ),
+        ).map_err(sqlite_error)?;
+        state.written += 1;
+        self.prune(&state.connection)?;
+        Ok(())
+    }
+    
+    /// Start a query over the stored events
+    pub fn query(&self) -> AuditQuery<'_> {
+        AuditQuery {
+            store: self,
+            instance_id: None,
+            kinds: Vec::new(),
+            min_severity: None,
+            since: None,
+            until: None,
+            limit: None,
+        }
+    }
+    
+    /// Drop events beyond the retention settings
+    fn prune(&self, connection: &rusqlite::Connection) -> Result<()> {
+        if let Some(max_age) = self.config.max_age {
+            let cutoff = SystemTime::now().checked_sub(max_age).unwrap_or(SystemTime::UNIX_EPOCH);
+            connection.execute("DELETE FROM audit WHERE timestamp_ms < ?1", [timestamp_ms(cutoff)]).map_err(sqlite_error)?;
+        }
+        if let Some(max_events) = self.config.max_events {
+            connection.execute(
+                "DELETE FROM audit WHERE id <= (SELECT MAX(id) FROM audit) - ?1",
+                [max_events as i64],
+            ).map_err(sqlite_error)?;
+        }
+        Ok(())
+    }
+    
+    /// Move the database to `<path>.1`, shifting older rotations along
+    fn rotate(&self, state: &mut StoreState) -> Result<()> {
+        let path = &self.config.path;
+        let rotated = |n: usize| {
+            let mut name = path.clone().into_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+        
+        // Close the database so it can be renamed
+        let (placeholder, _) = Self::connect(Path::new(":memory:"))?;
+        let connection = std::mem::replace(&mut state.connection, placeholder);
+        connection.close().map_err(|(_, e)| sqlite_error(e))?;
+        
+        if self.config.keep_rotated == 0 {
+            std::fs::remove_file(path)?;
+        } else {
+            let oldest = rotated(self.config.keep_rotated);
+            if oldest.exists() {
+                std::fs::remove_file(&oldest)?;
+            }
+            for n in (1..self.config.keep_rotated).rev() {
+                if rotated(n).exists() {
+                    std::fs::rename(rotated(n), rotated(n + 1))?;
+                }
+            }
+            std::fs::rename(path, rotated(1))?;
+        }
+        
+        let (connection, written) = Self::connect(path)?;
+        state.connection = connection;
+        state.written = written;
+        Ok(())
+    }
+}
+
+/// Events picked out of an [`AuditStore`]
+///
+/// Filters combine, so `store.query().instance(id).kind(AuditEventKind::FunctionCallDenied).since(start)`
+/// finds the calls an instance was refused since `start`. Events come oldest first.
+#[cfg(feature = "sqlite-audit")]
+#[derive(Debug, Clone)]
+pub struct AuditQuery<'a> {
+    store: &'a AuditStore,
+    instance_id: Option<String>,
+    kinds: Vec<AuditEventKind>,
+    min_severity: Option<AuditSeverity>,
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
+    limit: Option<usize>,
+}
+
+#[cfg(feature = "sqlite-audit")]
+impl AuditQuery<'_> {
+    /// Only events about the instance `instance_id`
+    pub fn instance(mut self, instance_id: impl ToString) -> Self {
+        self.instance_id = Some(instance_id.to_string());
+        self
+    }
+    
+    /// Only events of `kind`; repeat to accept several kinds
+    pub fn kind(mut self, kind: AuditEventKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+    
+    /// Only events of `severity` or worse
+    pub fn min_severity(mut self, severity: AuditSeverity) -> Self {
+        self.min_severity = Some(severity);
+        self
+    }
+    
+    /// Only events logged at or after `since`
+    pub fn since(mut self, since: SystemTime) -> Self {
+        self.since = Some(since);
+        self
+    }
+    
+    /// Only events logged at or before `until`
+    pub fn until(mut self, until: SystemTime) -> Self {
+        self.until = Some(until);
+        self
+    }
+    
+    /// At most `limit` events, the oldest that match
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+    
+    /// The matching events
+    pub fn fetch(&self) -> Result<Vec<AuditEvent>> {
+        let mut events = Vec::new();
+        self.for_each(|event| {
+            events.push(event);
+            Ok(())
+        })?;
+        Ok(events)
+    }
+    
+    /// Write the matching events as JSON lines, returning how many were written
+    pub fn export_jsonl(&self, mut writer: impl Write) -> Result<usize> {
+        let mut count = 0;
+        self.for_each(|event| {
+            serde_json::to_writer(&mut writer, &event)?;
+            writer.write_all(b"\n")?;
+            count += 1;
+            Ok(())
+        })?;
+        writer.flush()?;
+        Ok(count)
+    }
+    
+    /// Write the matching events as CSV with a header row, returning how many were written
+    ///
+    /// Columns are `timestamp` in RFC 3339, `severity`, `kind`, `instance_id`,
+    /// `call_id`, `message`, and the event's data as JSON in `details`.
+    pub fn export_csv(&self, mut writer: impl Write) -> Result<usize> {
+        writeln!(writer, "timestamp,severity,kind,instance_id,call_id,message,details")?;
+        let mut count = 0;
+        self.for_each(|event| {
+            let fields = [
+                chrono::DateTime::<chrono::Utc>::from(event.timestamp).to_rfc3339(),
+                severity_name(event.severity).to_string(),
+                event.event_type.kind().as_str().to_string(),
+                event.event_type.instance_id().unwrap_or_default().to_string(),
+                event.call_id.map(|call_id| call_id.to_string()).unwrap_or_default(),
+                event.message.clone(),
+                serde_json::to_string(&event.event_type)?,
+            ];
+            let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            writeln!(writer, "{}", row.join(","))?;
+            count += 1;
+            Ok(())
+        })?;
+        writer.flush()?;
+        Ok(count)
+    }
+    
+    fn for_each(&self, mut visit: impl FnMut(AuditEvent) -> Result<()>) -> Result<()> {
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        if let Some(instance_id) = &self.instance_id {
+            conditions.push("instance_id = ?".to_string());
+            params.push(SqlValue::Text(instance_id.clone()));
+        }
+        if !self.kinds.is_empty() {
+            conditions.push(format!("kind IN ({})", vec!["?"; self.kinds.len()].join(", ")));
+            params.extend(self.kinds.iter().map(|kind| SqlValue::Text(kind.as_str().to_string())));
+        }
+        if let Some(min_severity) = self.min_severity {
+            let accepted: Vec<_> = SEVERITIES.iter().skip_while(|severity| **severity != min_severity).collect();
+            conditions.push(format!("severity IN ({})", vec!["?"; accepted.len()].join(", ")));
+            params.extend(accepted.into_iter().map(|severity| SqlValue::Text(severity_name(*severity).to_string())));
+        }
+        if let Some(since) = self.since {
+            conditions.push("timestamp_ms >= ?".to_string());
+            params.push(SqlValue::Integer(timestamp_ms(since)));
+        }
+        if let Some(until) = self.until {
+            conditions.push("timestamp_ms <= ?".to_string());
+            params.push(SqlValue::Integer(timestamp_ms(until)));
+        }
+        
+        let mut sql = "SELECT event FROM audit".to_string();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY id");
+        if let Some(limit) = self.limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+        
+        let state = self.store.state.lock().unwrap();
+        let mut statement = state.connection.prepare(&sql).map_err(sqlite_error)?;
+        let mut rows = statement.query(rusqlite::params_from_iter(params)).map_err(sqlite_error)?;
+        while let Some(row) = rows.next().map_err(sqlite_error)? {
+            let json: String = row.get(0).map_err(sqlite_error)?;
+            visit(serde_json::from_str(&json)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Severities from least to most severe
+#[cfg(feature = "sqlite-audit")]
+const SEVERITIES: [AuditSeverity; 4] = [AuditSeverity::Info, AuditSeverity::Warning, AuditSeverity::Error, AuditSeverity::Critical];
+
+#[cfg(feature = "sqlite-audit")]
+fn severity_name(severity: AuditSeverity) -> &'static str {
+    match severity {
+        AuditSeverity::Info => "info",
+        AuditSeverity::Warning => "warning",
+        AuditSeverity::Error => "error",
+        AuditSeverity::Critical => "critical",
+    }
+}
+
+#[cfg(feature = "sqlite-audit")]
+fn timestamp_ms(timestamp: SystemTime) -> i64 {
+    timestamp.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as i64
+}
+
+/// Quote a CSV field if it holds a separator, quote or line break
+#[cfg(feature = "sqlite-audit")]
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(feature = "sqlite-audit")]
+fn sqlite_error(e: rusqlite::Error) -> Error {
+    Error::Generic { message: format!("SQLite audit store: {}", e) }
+}
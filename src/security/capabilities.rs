@@ -3,12 +3,17 @@
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 
 use crate::error::{Error, Result, SecurityContext};
+use crate::runtime::host_namespaces::HOST_NAMESPACE_CAPABILITY;
 use crate::security::{
-    NetworkCapability, FilesystemCapability, 
-    EnvironmentCapability, ProcessCapability, TimeCapability, RandomCapability
+    Capabilities, NetworkCapability, FilesystemCapability, HostSpec, PortRange,
+    EnvironmentCapability, ProcessCapability, TimeCapability, RandomCapability,
+    SecretsCapability, CapabilityEnforcement, CustomCapability, EnforcementMode,
 };
+use crate::security::audit::{AuditEventType, AuditLogger};
 
 /// Capability verification helper
 pub trait CapabilityVerifier {
@@ -44,10 +49,10 @@ impl NetworkVerifier {
                     }
                     
                     // Check port if specified
-                    if let Some(port_range) = &h.ports {
-                        if !port_range.contains(port) {
-                            return false;
-                        }
+                    if let Some(port_range) = &h.ports
+                        && !port_range.contains(port)
+                    {
+                        return false;
                     }
                     
                     // Check secure flag
@@ -62,6 +67,7 @@ impl NetworkVerifier {
                 // Check if port is in any allowed port range
                 ports.iter().any(|r| r.contains(port))
             },
+            NetworkCapability::AllowedDomains(policy) => policy.allows_domain(host) && policy.allows_port(port),
             NetworkCapability::Full => true,
         }
     }
@@ -77,14 +83,19 @@ impl NetworkVerifier {
                     IpAddr::V6(addr) => addr.is_loopback(),
                 }
             },
-            NetworkCapability::AllowedHosts(_hosts) => {
-                // Not implemented: would need to resolve hosts to IPs
-                false
+            NetworkCapability::AllowedHosts(hosts) => {
+                // Only literal addresses match; names are resolved by the socket policy
+                hosts.iter().any(|h| {
+                    h.host.parse::<IpAddr>().map_or(false, |allowed| allowed == ip)
+                        && h.ports.as_ref().map_or(true, |r| r.contains(port))
+                })
             },
             NetworkCapability::AllowedPorts(ports) => {
                 // Check if port is in any allowed port range
                 ports.iter().any(|r| r.contains(port))
             },
+            // Addresses are only allowed once the host has resolved and pinned them
+            NetworkCapability::AllowedDomains(_) => false,
             NetworkCapability::Full => true,
         }
     }
@@ -136,6 +147,24 @@ impl CapabilityVerifier for NetworkVerifier {
                     });
                 }
             }
+            "resolve" => {
+                let Some(name) = params.first() else {
+                    return Err(Error::Capability { message: "Missing name for resolve".to_string() });
+                };
+                
+                let allowed = match &self.capability {
+                    NetworkCapability::AllowedDomains(policy) => policy.allows_domain(name),
+                    NetworkCapability::Full => true,
+                    _ => false,
+                };
+                if !allowed {
+                    return Err(Error::SecurityViolation {
+                        violation: format!("Name resolution denied for {}", name),
+                        instance_id: None,
+                        context: create_security_context("resolve", "network.resolve", &[]),
+                    });
+                }
+            }
             "listen" => {
                 if params.len() < 1 {
                     return Err(Error::Capability { message: "Missing port for listen".to_string() });
@@ -608,6 +637,15 @@ pub struct CapabilityManager {
     
     /// Random verifier
     pub random: RandomVerifier,
+    
+    /// Per-domain enforcement modes
+    enforcement: CapabilityEnforcement,
+    
+    /// Audit logger for capability violations
+    audit_logger: Option<AuditLogger>,
+    
+    /// Instance the checks are performed for
+    instance_id: Option<String>,
 }
 
 impl CapabilityManager {
@@ -627,6 +665,94 @@ impl CapabilityManager {
             process: ProcessVerifier::new(process),
             time: TimeVerifier::new(time),
             random: RandomVerifier::new(random),
+            enforcement: CapabilityEnforcement::default(),
+            audit_logger: None,
+            instance_id: None,
+        }
+    }
+    
+    /// Create a capability manager from a capability set, including its enforcement modes
+    pub fn from_capabilities(capabilities: &Capabilities) -> Self {
+        let mut manager = Self::new(
+            capabilities.network.clone(),
+            capabilities.filesystem.clone(),
+            capabilities.environment.clone(),
+            capabilities.process.clone(),
+            capabilities.time.clone(),
+            capabilities.random.clone(),
+        );
+        manager.enforcement = capabilities.enforcement.clone();
+        manager
+    }
+    
+    /// Set the per-domain enforcement modes
+    pub fn with_enforcement(mut self, enforcement: CapabilityEnforcement) -> Self {
+        self.enforcement = enforcement;
+        self
+    }
+    
+    /// Record capability violations in an audit logger
+    pub fn with_audit_logger(mut self, logger: AuditLogger) -> Self {
+        self.audit_logger = Some(logger);
+        self
+    }
+    
+    /// Set the instance ID reported in audit events
+    pub fn with_instance_id(mut self, instance_id: impl Into<String>) -> Self {
+        self.instance_id = Some(instance_id.into());
+        self
+    }
+    
+    /// Get the enforcement mode for a domain
+    pub fn enforcement_mode(&self, domain: &str) -> EnforcementMode {
+        self.enforcement.mode_for(domain)
+    }
+    
+    /// Check an operation, honoring the enforcement mode of its domain
+    ///
+    /// Violations are recorded in the audit logger (if any). In
+    /// [`EnforcementMode::Audit`] mode they are then allowed to proceed; in
+    /// [`EnforcementMode::Enforce`] mode the violation is returned as an error.
+    /// Malformed requests are always rejected.
+    pub fn check(&self, domain: &str, operation: &str, params: &[&str]) -> Result<()> {
+        match self.verify(domain, operation, params) {
+            Ok(()) => Ok(()),
+            Err(e @ Error::Capability { .. }) => Err(e),
+            Err(e) => self.violation(domain, operation, params, e),
+        }
+    }
+    
+    /// Handle a violation found by a host function's own check, as [`CapabilityManager::check`] does
+    ///
+    /// Returns `error` in [`EnforcementMode::Enforce`] mode and `Ok` in
+    /// [`EnforcementMode::Audit`] mode, recording the violation either way.
+    pub fn violation(&self, domain: &str, operation: &str, params: &[&str], error: Error) -> Result<()> {
+        let mode = self.enforcement_mode(domain);
+        if let Some(logger) = &self.audit_logger {
+            let explanation = self.explain(domain, operation, params);
+            let changes = explanation.changes.iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let event = AuditEventType::CapabilityViolation {
+                instance_id: self.instance_id.clone().unwrap_or_default(),
+                domain: domain.to_string(),
+                operation: operation.to_string(),
+            };
+            let message = format!(
+                "{} (mode: {:?}, params: {:?}, rule: {}, required: [{}])",
+                explanation.reason, mode, explanation.params, explanation.rule, changes
+            );
+            
+            match mode {
+                EnforcementMode::Audit => logger.warning(event, &message),
+                EnforcementMode::Enforce => logger.error(event, &message),
+            }
+        }
+        
+        match mode {
+            EnforcementMode::Audit => Ok(()),
+            EnforcementMode::Enforce => Err(error),
         }
     }
     
@@ -643,3 +769,536 @@ impl CapabilityManager {
         }
     }
 }
+
+/// Decision reached when a capability rule is consulted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapabilityDecision {
+    /// The operation is allowed by the current capabilities
+    Allowed,
+    
+    /// The operation is denied by the current capabilities
+    Denied,
+}
+
+/// A single change to a capability set
+///
+/// A list of changes forms a capability diff: applying every change in an
+/// [`CapabilityExplanation`] to the capabilities it was computed from yields a
+/// policy that allows the explained operation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CapabilityChange {
+    /// Allow connections to a host
+    AllowHost(HostSpec),
+    
+    /// Allow a port range
+    AllowPorts(PortRange),
+    
+    /// Add a readable directory
+    AddReadableDir(PathBuf),
+    
+    /// Add a writable directory
+    AddWritableDir(PathBuf),
+    
+    /// Raise the maximum file size
+    RaiseMaxFileSize(u64),
+    
+    /// Allow file creation
+    AllowCreate,
+    
+    /// Allow file deletion
+    AllowDelete,
+    
+    /// Allow access to an environment variable
+    AllowEnvVar(String),
+    
+    /// Grant full environment access (required for setting variables)
+    FullEnvironment,
+    
+    /// Allow execution of a command
+    AllowCommand(String),
+    
+    /// Grant full time access (required for setting time)
+    FullTime,
+    
+    /// Allow pseudo-random number generation
+    PseudoRandom,
+    
+    /// Allow secure random number generation
+    FullRandom,
+    
+    /// Allow calls into a gated host namespace
+    AllowHostNamespace(String),
+    
+    /// Allow requests for a named secret
+    AllowSecret(String),
+    
+    /// Allow loading a host-registered model
+    AllowModel(String),
+}
+
+impl CapabilityChange {
+    /// Apply this change to a set of capabilities
+    pub fn apply(&self, capabilities: &mut Capabilities) {
+        match self {
+            Self::AllowHost(spec) => {
+                match &mut capabilities.network {
+                    NetworkCapability::AllowedHosts(hosts) => hosts.push(spec.clone()),
+                    NetworkCapability::Full => {}
+                    NetworkCapability::Loopback => {
+                        let mut hosts: Vec<HostSpec> = ["localhost", "127.0.0.1", "::1"]
+                            .iter()
+                            .map(|h| HostSpec { host: h.to_string(), ports: None, secure: true })
+                            .collect();
+                        hosts.push(spec.clone());
+                        capabilities.network = NetworkCapability::AllowedHosts(hosts);
+                    }
+                    NetworkCapability::None | NetworkCapability::AllowedPorts(_) | NetworkCapability::AllowedDomains(_) => {
+                        capabilities.network = NetworkCapability::AllowedHosts(vec![spec.clone()]);
+                    }
+                }
+            }
+            Self::AllowPorts(range) => {
+                match &mut capabilities.network {
+                    NetworkCapability::AllowedPorts(ports) => ports.push(range.clone()),
+                    NetworkCapability::Full => {}
+                    _ => capabilities.network = NetworkCapability::AllowedPorts(vec![range.clone()]),
+                }
+            }
+            Self::AddReadableDir(dir) => {
+                if !capabilities.filesystem.readable_dirs.contains(dir) {
+                    capabilities.filesystem.readable_dirs.push(dir.clone());
+                }
+            }
+            Self::AddWritableDir(dir) => {
+                if !capabilities.filesystem.writable_dirs.contains(dir) {
+                    capabilities.filesystem.writable_dirs.push(dir.clone());
+                }
+            }
+            Self::RaiseMaxFileSize(size) => {
+                if let Some(limit) = capabilities.filesystem.max_file_size {
+                    capabilities.filesystem.max_file_size = Some(limit.max(*size));
+                }
+            }
+            Self::AllowCreate => capabilities.filesystem.allow_create = true,
+            Self::AllowDelete => capabilities.filesystem.allow_delete = true,
+            Self::AllowEnvVar(var) => {
+                match &mut capabilities.environment {
+                    EnvironmentCapability::Allowlist(vars) => {
+                        if !vars.contains(var) {
+                            vars.push(var.clone());
+                        }
+                    }
+                    EnvironmentCapability::Denylist(vars) => vars.retain(|v| v != var),
+                    EnvironmentCapability::Full => {}
+                    EnvironmentCapability::None => {
+                        capabilities.environment = EnvironmentCapability::Allowlist(vec![var.clone()]);
+                    }
+                }
+            }
+            Self::FullEnvironment => capabilities.environment = EnvironmentCapability::Full,
+            Self::AllowCommand(command) => {
+                match &mut capabilities.process {
+                    ProcessCapability::AllowedCommands(commands) => commands.push(command.clone()),
+                    ProcessCapability::Full => {}
+                    ProcessCapability::None => {
+                        capabilities.process = ProcessCapability::AllowedCommands(vec![command.clone()]);
+                    }
+                }
+            }
+            Self::FullTime => capabilities.time = TimeCapability::Full,
+            Self::PseudoRandom => {
+                if capabilities.random == RandomCapability::None {
+                    capabilities.random = RandomCapability::PseudoOnly;
+                }
+            }
+            Self::FullRandom => capabilities.random = RandomCapability::Full,
+            Self::AllowHostNamespace(namespace) => {
+                let grants = capabilities.custom
+                    .entry(HOST_NAMESPACE_CAPABILITY.to_string())
+                    .or_insert_with(|| CustomCapability::StringList(Vec::new()));
+                match grants {
+                    CustomCapability::StringList(namespaces) => {
+                        if !namespaces.contains(namespace) {
+                            namespaces.push(namespace.clone());
+                        }
+                    }
+                    other => *other = CustomCapability::StringList(vec![namespace.clone()]),
+                }
+            }
+            Self::AllowSecret(name) => {
+                match &mut capabilities.secrets {
+                    SecretsCapability::Allowlist(names) => {
+                        if !names.contains(name) {
+                            names.push(name.clone());
+                        }
+                    }
+                    SecretsCapability::None => capabilities.secrets = SecretsCapability::Allowlist(vec![name.clone()]),
+                }
+            }
+            Self::AllowModel(model) => {
+                if !capabilities.ml.allows(model) {
+                    capabilities.ml.allowed_models.push(model.clone());
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for CapabilityChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AllowHost(spec) => match &spec.ports {
+                Some(ports) if ports.start == ports.end => write!(f, "allow host {}:{}", spec.host, ports.start),
+                Some(ports) => write!(f, "allow host {}:{}-{}", spec.host, ports.start, ports.end),
+                None => write!(f, "allow host {}", spec.host),
+            },
+            Self::AllowPorts(ports) => write!(f, "allow ports {}-{}", ports.start, ports.end),
+            Self::AddReadableDir(dir) => write!(f, "add readable directory {}", dir.display()),
+            Self::AddWritableDir(dir) => write!(f, "add writable directory {}", dir.display()),
+            Self::RaiseMaxFileSize(size) => write!(f, "raise maximum file size to {} bytes", size),
+            Self::AllowCreate => write!(f, "allow file creation"),
+            Self::AllowDelete => write!(f, "allow file deletion"),
+            Self::AllowEnvVar(var) => write!(f, "allow environment variable {}", var),
+            Self::FullEnvironment => write!(f, "grant full environment access"),
+            Self::AllowCommand(command) => write!(f, "allow command {}", command),
+            Self::FullTime => write!(f, "grant full time access"),
+            Self::PseudoRandom => write!(f, "allow pseudo-random generation"),
+            Self::FullRandom => write!(f, "allow secure random generation"),
+            Self::AllowHostNamespace(namespace) => write!(f, "allow host namespace {}", namespace),
+            Self::AllowSecret(name) => write!(f, "allow secret {}", name),
+            Self::AllowModel(model) => write!(f, "allow model {}", model),
+        }
+    }
+}
+
+/// Explanation of a capability check
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapabilityExplanation {
+    /// Capability domain that was consulted
+    pub domain: String,
+    
+    /// Operation that was checked
+    pub operation: String,
+    
+    /// Operation parameters
+    pub params: Vec<String>,
+    
+    /// Description of the rule that was consulted
+    pub rule: String,
+    
+    /// Decision reached
+    pub decision: CapabilityDecision,
+    
+    /// Reason for a denial (empty when allowed)
+    pub reason: String,
+    
+    /// Minimal set of changes that would allow the operation
+    pub changes: Vec<CapabilityChange>,
+}
+
+impl CapabilityExplanation {
+    /// Check whether the operation was allowed
+    pub fn is_allowed(&self) -> bool {
+        self.decision == CapabilityDecision::Allowed
+    }
+    
+    /// Apply the suggested changes to a set of capabilities
+    pub fn apply_to(&self, capabilities: &mut Capabilities) {
+        for change in &self.changes {
+            change.apply(capabilities);
+        }
+    }
+}
+
+impl CapabilityManager {
+    /// Explain a capability check
+    ///
+    /// Unlike [`CapabilityManager::verify`], this never fails: it describes the
+    /// rule that was consulted, the decision reached, and the minimal capability
+    /// changes that would allow the operation. It has no side effects, so it can
+    /// be used to evaluate a policy before shipping it.
+    pub fn explain(&self, domain: &str, operation: &str, params: &[&str]) -> CapabilityExplanation {
+        let (decision, reason, changes) = match self.verify(domain, operation, params) {
+            Ok(()) => (CapabilityDecision::Allowed, String::new(), Vec::new()),
+            // Malformed requests cannot be fixed by granting capabilities
+            Err(e @ Error::Capability { .. }) => (CapabilityDecision::Denied, e.to_string(), Vec::new()),
+            Err(e) => (
+                CapabilityDecision::Denied,
+                e.to_string(),
+                self.required_changes(domain, operation, params),
+            ),
+        };
+        
+        CapabilityExplanation {
+            domain: domain.to_string(),
+            operation: operation.to_string(),
+            params: params.iter().map(|p| p.to_string()).collect(),
+            rule: self.describe_rule(domain),
+            decision,
+            reason,
+            changes,
+        }
+    }
+    
+    /// Describe the rule consulted for a domain
+    fn describe_rule(&self, domain: &str) -> String {
+        match domain {
+            "network" => format!("network: {:?}", self.network.capability),
+            "filesystem" | "fs" => format!(
+                "filesystem: readable {:?}, writable {:?}, max file size {:?}, create {}, delete {}",
+                self.filesystem.capability.readable_dirs,
+                self.filesystem.capability.writable_dirs,
+                self.filesystem.capability.max_file_size,
+                self.filesystem.capability.allow_create,
+                self.filesystem.capability.allow_delete,
+            ),
+            "environment" | "env" => format!("environment: {:?}", self.environment.capability),
+            "process" | "proc" => format!("process: {:?}", self.process.capability),
+            "time" => format!("time: {:?}", self.time.capability),
+            "random" | "rand" => format!("random: {:?}", self.random.capability),
+            _ => format!("unknown domain: {}", domain),
+        }
+    }
+    
+    /// Compute the minimal changes required to allow a denied operation
+    fn required_changes(&self, domain: &str, operation: &str, params: &[&str]) -> Vec<CapabilityChange> {
+        let mut changes = Vec::new();
+        
+        match (domain, operation) {
+            ("network", "connect") | ("network", "bind") | ("network", "listen") => {
+                let (host, port) = if operation == "listen" {
+                    ("127.0.0.1", params[0])
+                } else {
+                    (params[0], params[1])
+                };
+                let port = port.parse::<u16>().unwrap_or_default();
+                let secure = operation == "connect" && params.get(2).map(|s| *s == "secure").unwrap_or(false);
+                
+                if let NetworkCapability::AllowedPorts(_) = self.network.capability {
+                    changes.push(CapabilityChange::AllowPorts(PortRange::single(port)));
+                } else {
+                    changes.push(CapabilityChange::AllowHost(HostSpec {
+                        host: host.to_string(),
+                        ports: Some(PortRange::single(port)),
+                        secure,
+                    }));
+                }
+            }
+            ("filesystem" | "fs", "open" | "read") => {
+                changes.push(CapabilityChange::AddReadableDir(PathBuf::from(params[0])));
+            }
+            ("filesystem" | "fs", "write" | "append") => {
+                let path = Path::new(params[0]);
+                if !self.filesystem.is_writable(path) {
+                    changes.push(CapabilityChange::AddWritableDir(writable_target(path)));
+                }
+                if let Some(size) = params.get(1).and_then(|s| s.parse::<u64>().ok())
+                    && !self.filesystem.is_size_allowed(size)
+                {
+                    changes.push(CapabilityChange::RaiseMaxFileSize(size));
+                }
+            }
+            ("filesystem" | "fs", "create") => {
+                if !self.filesystem.can_create() {
+                    changes.push(CapabilityChange::AllowCreate);
+                }
+                let path = Path::new(params[0]);
+                if !self.filesystem.is_writable(path) {
+                    changes.push(CapabilityChange::AddWritableDir(writable_target(path)));
+                }
+            }
+            ("filesystem" | "fs", "delete" | "remove") => {
+                if !self.filesystem.can_delete() {
+                    changes.push(CapabilityChange::AllowDelete);
+                }
+                let path = Path::new(params[0]);
+                if !self.filesystem.is_writable(path) {
+                    changes.push(CapabilityChange::AddWritableDir(writable_target(path)));
+                }
+            }
+            ("environment" | "env", "get") => {
+                changes.push(CapabilityChange::AllowEnvVar(params[0].to_string()));
+            }
+            ("environment" | "env", "set") => {
+                changes.push(CapabilityChange::FullEnvironment);
+            }
+            ("process" | "proc", _) => {
+                changes.push(CapabilityChange::AllowCommand(params[0].to_string()));
+            }
+            ("time", "set") => changes.push(CapabilityChange::FullTime),
+            ("random" | "rand", "pseudo") => changes.push(CapabilityChange::PseudoRandom),
+            ("random" | "rand", "secure") => changes.push(CapabilityChange::FullRandom),
+            _ => {}
+        }
+        
+        changes
+    }
+}
+
+/// Directory that must be writable for a write to `path` to be allowed
+fn writable_target(path: &Path) -> PathBuf {
+    if path.exists() {
+        path.to_path_buf()
+    } else {
+        path.parent().map(Path::to_path_buf).unwrap_or_else(|| path.to_path_buf())
+    }
+}
+
+/// Identifier of a capability granted to a running instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GrantId(u64);
+
+impl std::fmt::Display for GrantId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "grant-{}", self.0)
+    }
+}
+
+/// A capability granted to a running instance until it expires or is revoked
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapabilityGrant {
+    /// Identifier to revoke the grant with
+    pub id: GrantId,
+    
+    /// The capability granted
+    pub change: CapabilityChange,
+    
+    /// When the grant was made
+    pub granted_at: SystemTime,
+    
+    /// When the grant lapses, or `None` to keep it until revoked
+    pub expires_at: Option<Instant>,
+}
+
+impl CapabilityGrant {
+    /// Check whether the grant has lapsed
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| Instant::now() >= expires_at)
+    }
+}
+
+/// Capabilities in effect for an instance, including any per-function overlay
+///
+/// Host functions consult [`ActiveCapabilities::current`] so that a function
+/// policy applies only for the duration of the guest call it was entered for,
+/// and grants made while the instance runs apply from the next check on.
+#[derive(Debug, Clone)]
+pub struct ActiveCapabilities {
+    inner: Arc<RwLock<ActiveCapabilityState>>,
+}
+
+#[derive(Debug)]
+struct ActiveCapabilityState {
+    /// Instance-wide capabilities
+    base: Capabilities,
+    
+    /// Function name and capabilities of the active overlay
+    overlay: Option<(String, Capabilities)>,
+    
+    /// Capabilities granted on top of the base or overlay, including lapsed ones not yet pruned
+    grants: Vec<CapabilityGrant>,
+    
+    /// Number of the next grant
+    next_grant: u64,
+}
+
+impl ActiveCapabilities {
+    /// Create active capabilities from the instance-wide set
+    pub fn new(base: Capabilities) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(ActiveCapabilityState {
+                base,
+                overlay: None,
+                grants: Vec::new(),
+                next_grant: 1,
+            })),
+        }
+    }
+    
+    /// Get the capabilities currently in effect
+    pub fn current(&self) -> Capabilities {
+        let state = self.inner.read().unwrap();
+        let mut capabilities = match &state.overlay {
+            Some((_, overlay)) => overlay.clone(),
+            None => state.base.clone(),
+        };
+        for grant in state.grants.iter().filter(|grant| !grant.is_expired()) {
+            grant.change.apply(&mut capabilities);
+        }
+        capabilities
+    }
+    
+    /// Grant a capability on top of the base and any overlay, for `ttl` or until revoked
+    pub fn grant(&self, change: CapabilityChange, ttl: Option<Duration>) -> GrantId {
+        let mut state = self.inner.write().unwrap();
+        let id = GrantId(state.next_grant);
+        state.next_grant += 1;
+        state.grants.push(CapabilityGrant {
+            id,
+            change,
+            granted_at: SystemTime::now(),
+            expires_at: ttl.map(|ttl| Instant::now() + ttl),
+        });
+        id
+    }
+    
+    /// Withdraw a grant, returning it if it was still in effect
+    pub fn revoke(&self, id: GrantId) -> Option<CapabilityGrant> {
+        let mut state = self.inner.write().unwrap();
+        let index = state.grants.iter().position(|grant| grant.id == id)?;
+        Some(state.grants.remove(index)).filter(|grant| !grant.is_expired())
+    }
+    
+    /// Grants still in effect
+    pub fn grants(&self) -> Vec<CapabilityGrant> {
+        self.inner.read().unwrap().grants.iter()
+            .filter(|grant| !grant.is_expired())
+            .cloned()
+            .collect()
+    }
+    
+    /// Forget lapsed grants, returning them
+    pub fn prune_expired(&self) -> Vec<CapabilityGrant> {
+        let mut state = self.inner.write().unwrap();
+        let (expired, live) = std::mem::take(&mut state.grants).into_iter().partition(CapabilityGrant::is_expired);
+        state.grants = live;
+        expired
+    }
+    
+    /// Get the name of the function whose overlay is active
+    pub fn active_function(&self) -> Option<String> {
+        self.inner.read().unwrap().overlay.as_ref().map(|(name, _)| name.clone())
+    }
+    
+    /// Build a capability manager for the capabilities currently in effect
+    pub fn manager(&self) -> CapabilityManager {
+        CapabilityManager::from_capabilities(&self.current())
+    }
+    
+    /// Apply a function overlay until the returned scope is dropped
+    pub fn enter(&self, function_name: &str, overlay: Capabilities) -> CapabilityScope {
+        let previous = self.inner.write().unwrap()
+            .overlay
+            .replace((function_name.to_string(), overlay));
+        
+        CapabilityScope {
+            active: self.clone(),
+            previous,
+        }
+    }
+}
+
+/// Guard that restores the previous capabilities when dropped
+#[derive(Debug)]
+pub struct CapabilityScope {
+    active: ActiveCapabilities,
+    previous: Option<(String, Capabilities)>,
+}
+
+impl Drop for CapabilityScope {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.active.inner.write() {
+            state.overlay = self.previous.take();
+        }
+    }
+}
@@ -0,0 +1,309 @@
+//! Domain names resolved on the host for guests
+//!
+//! Guests with [`NetworkCapability::AllowedDomains`] don't resolve names
+//! themselves: they ask the host through [`crate::runtime::DNS_IMPORT_MODULE`].
+//! The host checks the name against the instance's [`DnsPolicy`] and its
+//! resolutions-per-minute quota, looks it up with the configured
+//! [`NameResolver`], and pins the addresses until the call returns. For the
+//! rest of the call the name resolves to the same addresses, and host code
+//! connecting on the guest's behalf checks its target with
+//! [`DnsResolver::is_pinned`], so a DNS server can't rebind an allowed name to
+//! an address the host never approved. Every request is audited.
+//!
+//! [`DnsPolicy`]: crate::security::DnsPolicy
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, ResourceKind, Result, SecurityContext};
+use crate::runtime::DnsLookup;
+use crate::security::audit::{AuditEventType, AuditLogger};
+use crate::security::capabilities::{ActiveCapabilities, CapabilityManager};
+use crate::security::{Capabilities, NetworkCapability};
+use crate::InstanceId;
+
+/// Capability name reported when a name may not be resolved
+const NETWORK_CAPABILITY: &str = "network";
+
+/// Window the resolutions-per-minute quota counts over
+const QUOTA_WINDOW: Duration = Duration::from_secs(60);
+
+/// Source of addresses for domain names on the host
+pub trait NameResolver: Send + Sync {
+    /// Look up the addresses of `name`
+    fn lookup(&self, name: &str) -> Result<Vec<IpAddr>>;
+}
+
+/// Resolves names with the host operating system's resolver
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl NameResolver for SystemResolver {
+    fn lookup(&self, name: &str) -> Result<Vec<IpAddr>> {
+        Ok((name, 0).to_socket_addrs()?.map(|addr| addr.ip()).collect())
+    }
+}
+
+/// Resolves names from a fixed table, for tests and air-gapped hosts
+#[derive(Debug, Clone, Default)]
+pub struct StaticResolver {
+    names: HashMap<String, Vec<IpAddr>>,
+}
+
+impl StaticResolver {
+    /// Create a resolver that knows no names
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Resolve `name` to `addresses`
+    pub fn host(mut self, name: &str, addresses: impl IntoIterator<Item = IpAddr>) -> Self {
+        self.names.insert(normalize(name), addresses.into_iter().collect());
+        self
+    }
+}
+
+impl NameResolver for StaticResolver {
+    fn lookup(&self, name: &str) -> Result<Vec<IpAddr>> {
+        Ok(self.names.get(&normalize(name)).cloned().unwrap_or_default())
+    }
+}
+
+/// An instance's recent lookups and pinned addresses
+#[derive(Debug, Default)]
+struct InstanceState {
+    /// When the names resolved in the last minute were looked up, including lookups still running
+    recent: VecDeque<Instant>,
+    
+    /// Addresses resolved during the current call, by name
+    pinned: HashMap<String, Vec<IpAddr>>,
+}
+
+/// Resolves instances' domain names according to their capabilities
+pub struct DnsResolver {
+    resolver: RwLock<Arc<dyn NameResolver>>,
+    instances: Mutex<HashMap<InstanceId, InstanceState>>,
+    audit: AuditLogger,
+}
+
+impl DnsResolver {
+    /// Create a resolver using the system's resolver
+    pub fn new() -> Self {
+        Self {
+            resolver: RwLock::new(Arc::new(SystemResolver)),
+            instances: Mutex::new(HashMap::new()),
+            audit: AuditLogger::new(1000),
+        }
+    }
+    
+    /// Record requests in an existing audit logger
+    pub fn with_audit_logger(mut self, logger: AuditLogger) -> Self {
+        self.audit = logger;
+        self
+    }
+    
+    /// Get the audit logger recording resolution requests
+    pub fn audit_logger(&self) -> &AuditLogger {
+        &self.audit
+    }
+    
+    /// Set where names are looked up
+    pub fn set_resolver(&self, resolver: Arc<dyn NameResolver>) {
+        *self.resolver.write().unwrap() = resolver;
+    }
+    
+    /// Resolve `name` on behalf of an instance, pinning the addresses until [`DnsResolver::release`]
+    ///
+    /// A name already pinned resolves to the same addresses without another
+    /// lookup, and only lookups that find addresses count against the
+    /// quota. Instances with [`NetworkCapability::Full`] may resolve any name,
+    /// and names are resolved regardless in audit mode, with the violation logged.
+    pub fn resolve(&self, instance_id: InstanceId, capabilities: &Capabilities, name: &str) -> Result<Vec<IpAddr>> {
+        let name = normalize(name);
+        let quota = match &capabilities.network {
+            NetworkCapability::AllowedDomains(policy) => policy.max_resolutions_per_minute,
+            _ => None,
+        };
+        let verdict = CapabilityManager::from_capabilities(capabilities)
+            .with_audit_logger(self.audit.clone())
+            .with_instance_id(instance_id.to_string())
+            .check("network", "resolve", &[&name]);
+        if verdict.is_err() {
+            self.record(instance_id, &name, false);
+            return Err(Error::SecurityViolation {
+                violation: format!("Instance may not resolve {}", name),
+                instance_id: Some(instance_id.0),
+                context: SecurityContext {
+                    attempted_operation: format!("resolve {}", name),
+                    required_capability: NETWORK_CAPABILITY.to_string(),
+                    available_capabilities: match &capabilities.network {
+                        NetworkCapability::AllowedDomains(policy) => policy.allowed_domains.clone(),
+                        _ => Vec::new(),
+                    },
+                },
+            });
+        }
+        
+        let looked_up_at = {
+            let mut instances = self.instances.lock().unwrap();
+            let state = instances.entry(instance_id).or_default();
+            if let Some(addresses) = state.pinned.get(&name) {
+                self.record(instance_id, &name, true);
+                return Ok(addresses.clone());
+            }
+            
+            let now = Instant::now();
+            while state.recent.front().is_some_and(|at| now.duration_since(*at) >= QUOTA_WINDOW) {
+                state.recent.pop_front();
+            }
+            if let Some(limit) = quota {
+                // A running lookup holds its place, so concurrent lookups can't overshoot the quota
+                if state.recent.len() >= limit as usize {
+                    self.record(instance_id, &name, false);
+                    return Err(Error::ResourceExhausted {
+                        kind: ResourceKind::DnsResolutions,
+                        limit: limit as u64,
+                        used: state.recent.len() as u64 + 1,
+                        instance_id: Some(instance_id.0),
+                        suggestion: Some("Resolve fewer names per minute or raise DnsPolicy::max_resolutions_per_minute".to_string()),
+                    });
+                }
+            }
+            state.recent.push_back(now);
+            now
+        };
+        self.record(instance_id, &name, true);
+        
+        // Look up without holding the lock, as the system resolver may block
+        let resolver = self.resolver.read().unwrap().clone();
+        let addresses = resolver.lookup(&name).and_then(|addresses| {
+            if addresses.is_empty() {
+                Err(Error::NotFound {
+                    resource_type: "domain".to_string(),
+                    identifier: name.clone(),
+                })
+            } else {
+                Ok(addresses)
+            }
+        });
+        
+        let mut instances = self.instances.lock().unwrap();
+        let state = instances.entry(instance_id).or_default();
+        let addresses = match addresses {
+            Ok(addresses) => addresses,
+            Err(e) => {
+                // Failed lookups give back their place in the quota
+                if let Some(position) = state.recent.iter().position(|at| *at == looked_up_at) {
+                    state.recent.remove(position);
+                }
+                return Err(e);
+            }
+        };
+        // A concurrent lookup of the same name may have pinned it first
+        Ok(state.pinned.entry(name).or_insert(addresses).clone())
+    }
+    
+    /// Check whether `ip` was resolved for an instance during the current call
+    pub fn is_pinned(&self, instance_id: InstanceId, ip: IpAddr) -> bool {
+        self.instances.lock().unwrap()
+            .get(&instance_id)
+            .is_some_and(|state| state.pinned.values().flatten().any(|pinned| *pinned == ip))
+    }
+    
+    /// Names resolved for an instance during the current call, with their addresses
+    pub fn pinned(&self, instance_id: InstanceId) -> HashMap<String, Vec<IpAddr>> {
+        self.instances.lock().unwrap()
+            .get(&instance_id)
+            .map(|state| state.pinned.clone())
+            .unwrap_or_default()
+    }
+    
+    /// Unpin an instance's addresses when the returned guard drops
+    pub(crate) fn pin_scope(&self, instance_id: InstanceId) -> PinScope<'_> {
+        PinScope { resolver: self, instance_id }
+    }
+    
+    /// Unpin an instance's addresses once its call has returned
+    pub fn release(&self, instance_id: InstanceId) {
+        if let Some(state) = self.instances.lock().unwrap().get_mut(&instance_id) {
+            state.pinned.clear();
+        }
+    }
+    
+    /// Forget a removed instance
+    pub fn remove(&self, instance_id: InstanceId) {
+        self.instances.lock().unwrap().remove(&instance_id);
+    }
+    
+    /// Record a request in the audit log
+    fn record(&self, instance_id: InstanceId, name: &str, allowed: bool) {
+        let event = AuditEventType::DnsResolution {
+            instance_id: instance_id.to_string(),
+            name: name.to_string(),
+            allowed,
+        };
+        if allowed {
+            self.audit.info(event, &format!("Resolving {}", name));
+        } else {
+            self.audit.warning(event, &format!("Denied resolving {}", name));
+        }
+    }
+}
+
+impl Default for DnsResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for DnsResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnsResolver")
+            .field("instances", &self.instances.lock().unwrap().len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Releases an instance's pinned addresses when a call ends
+pub(crate) struct PinScope<'a> {
+    resolver: &'a DnsResolver,
+    instance_id: InstanceId,
+}
+
+impl Drop for PinScope<'_> {
+    fn drop(&mut self) {
+        self.resolver.release(self.instance_id);
+    }
+}
+
+/// Resolves one instance's names through a shared resolver
+pub struct InstanceDns {
+    resolver: Arc<DnsResolver>,
+    instance_id: InstanceId,
+    capabilities: ActiveCapabilities,
+}
+
+impl InstanceDns {
+    /// Create a lookup for names resolved by `instance_id`
+    pub fn new(resolver: Arc<DnsResolver>, instance_id: InstanceId, capabilities: ActiveCapabilities) -> Self {
+        Self {
+            resolver,
+            instance_id,
+            capabilities,
+        }
+    }
+}
+
+impl DnsLookup for InstanceDns {
+    fn resolve(&self, name: &str) -> Result<Vec<IpAddr>> {
+        self.resolver.resolve(self.instance_id, &self.capabilities.current(), name)
+    }
+}
+
+/// Lowercase a name and drop its trailing dot
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
@@ -0,0 +1,188 @@
+//! Capability extensions for domain-specific host APIs
+//!
+//! A [`CapabilityExtension`] makes a custom capability first-class: it owns a
+//! namespace such as `gpio` or `payments`, turns the [`CustomCapability`] an
+//! instance holds under that name into a typed grant, and implements the host
+//! functions guests import from the namespace. Once an extension is registered
+//! with [`crate::WasmSandbox::register_capability_extension`], instances whose
+//! grant it rejects can't be created, every call is checked against the
+//! caller's current grant (including runtime grants), and each call is
+//! recorded in the sandbox's capability audit log. Extensions always enforce:
+//! a call without a valid grant fails whatever the [`EnforcementMode`].
+//!
+//! [`EnforcementMode`]: crate::security::EnforcementMode
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use crate::error::{Error, Result, SecurityContext};
+use crate::runtime::handles::HandleTable;
+use crate::runtime::host_namespaces::{HostContext, HostFunctionRegistry, HostNamespace};
+use crate::runtime::HostValue;
+use crate::security::audit::{AuditEventType, AuditLogger};
+use crate::security::{Capabilities, CustomCapability};
+use crate::InstanceId;
+
+/// A domain-specific capability with host functions of its own
+pub trait CapabilityExtension: Send + Sync + 'static {
+    /// What an instance's capability allows, once verified
+    type Grant: Send + Sync;
+    
+    /// Namespace guests import the functions from, also the key of the
+    /// capability in [`Capabilities::custom`]
+    fn namespace(&self) -> &str;
+    
+    /// Functions guests may import from the namespace
+    fn functions(&self) -> Vec<String>;
+    
+    /// Check the capability an instance holds and turn it into a grant
+    fn verify(&self, capability: &CustomCapability) -> Result<Self::Grant>;
+    
+    /// Handle a call from an instance holding `call.grant`
+    fn call(&self, call: &ExtensionCall<'_, Self::Grant>, args: &[HostValue]) -> Result<Vec<HostValue>>;
+}
+
+/// A guest call to an extension's function
+pub struct ExtensionCall<'a, G> {
+    /// Calling instance
+    pub instance_id: InstanceId,
+    
+    /// Function called, without the namespace
+    pub function: &'a str,
+    
+    /// The caller's verified grant
+    pub grant: &'a G,
+    
+    /// Host objects the instance holds handles to
+    pub handles: &'a HandleTable,
+}
+
+/// The part of an extension checked when instances are created
+trait Verifier: Send + Sync {
+    fn check(&self, capability: &CustomCapability) -> Result<()>;
+}
+
+impl<E: CapabilityExtension> Verifier for E {
+    fn check(&self, capability: &CustomCapability) -> Result<()> {
+        self.verify(capability).map(|_| ())
+    }
+}
+
+/// Capability extensions registered with a sandbox
+#[derive(Default)]
+pub struct CapabilityExtensions {
+    verifiers: RwLock<BTreeMap<String, Arc<dyn Verifier>>>,
+}
+
+impl CapabilityExtensions {
+    /// Create an empty set of extensions
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Register an extension's functions in `host_functions`, auditing calls to `audit`
+    pub(crate) fn register<E: CapabilityExtension>(
+        &self,
+        extension: E,
+        host_functions: &HostFunctionRegistry,
+        audit: &AuditLogger,
+    ) -> Result<()> {
+        let namespace = extension.namespace().to_string();
+        let mut verifiers = self.verifiers.write().unwrap();
+        if verifiers.contains_key(&namespace) {
+            return Err(Error::config_error(
+                format!("A capability extension for {} is already registered", namespace),
+                None,
+            ));
+        }
+        
+        let extension = Arc::new(extension);
+        let mut functions = HostNamespace::new(&namespace)
+            .provider(&format!("capability extension {}", namespace))
+            .ungated();
+        for function in extension.functions() {
+            let extension = extension.clone();
+            let audit = audit.clone();
+            let name = function.clone();
+            functions = functions.function_with_context(&function, move |context, args| {
+                let grant = authorize(&*extension, context, &name, &audit)?;
+                let call = ExtensionCall {
+                    instance_id: context.instance_id,
+                    function: &name,
+                    grant: &grant,
+                    handles: context.handles,
+                };
+                extension.call(&call, args)
+            });
+        }
+        host_functions.register(functions)?;
+        verifiers.insert(namespace, extension);
+        Ok(())
+    }
+    
+    /// Namespaces of the registered extensions
+    pub fn namespaces(&self) -> Vec<String> {
+        self.verifiers.read().unwrap().keys().cloned().collect()
+    }
+    
+    /// Check every extension capability in `capabilities` with its extension
+    pub fn verify(&self, capabilities: &Capabilities) -> Result<()> {
+        for (namespace, verifier) in self.verifiers.read().unwrap().iter() {
+            let Some(capability) = capabilities.get_custom(namespace) else {
+                continue;
+            };
+            verifier.check(capability).map_err(|e| Error::config_error(
+                format!("Invalid {} capability: {}", namespace, e),
+                Some(format!("Check the value given for the {} capability", namespace)),
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for CapabilityExtensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CapabilityExtensions")
+            .field("namespaces", &self.namespaces())
+            .finish()
+    }
+}
+
+/// Verify the caller's current grant for a call, recording the decision
+fn authorize<E: CapabilityExtension>(
+    extension: &E,
+    context: &HostContext<'_>,
+    function: &str,
+    audit: &AuditLogger,
+) -> Result<E::Grant> {
+    let namespace = extension.namespace();
+    let grant = match context.capabilities.get_custom(namespace) {
+        Some(capability) => extension.verify(capability).map_err(|e| e.to_string()),
+        None => Err("no grant".to_string()),
+    };
+    
+    let event = AuditEventType::ExtensionCall {
+        instance_id: context.instance_id.to_string(),
+        namespace: namespace.to_string(),
+        function: function.to_string(),
+        allowed: grant.is_ok(),
+    };
+    match grant {
+        Ok(grant) => {
+            audit.info(event, &format!("Instance {} called {}.{}", context.instance_id, namespace, function));
+            Ok(grant)
+        }
+        Err(reason) => {
+            audit.warning(event, &format!("Denied instance {} call to {}.{}: {}", context.instance_id, namespace, function, reason));
+            Err(Error::SecurityViolation {
+                violation: format!("Instance may not call {}.{}: {}", namespace, function, reason),
+                instance_id: Some(context.instance_id.as_uuid()),
+                context: SecurityContext {
+                    attempted_operation: format!("call {}.{}", namespace, function),
+                    required_capability: namespace.to_string(),
+                    available_capabilities: context.capabilities.custom.keys().cloned().collect(),
+                },
+            })
+        }
+    }
+}
@@ -0,0 +1,672 @@
+//! OS-level hardening for helper processes
+//!
+//! Compiler processes (see [`crate::runtime::compilation`]) run Cranelift on
+//! untrusted wasm, so a module that exploits a compiler bug gets whatever the
+//! process can do. A [`HostHardening`] takes most of that away: resource limits
+//! cap the process's memory, CPU time and open files, a seccomp filter denies
+//! network sockets, access to other processes, running other programs and
+//! io_uring (whose operations the filter can't see), and landlock rules deny
+//! the filesystem except for paths allowed explicitly.
+//!
+//! Measures are applied where the platform supports them:
+//!
+//! | Measure | Linux | Other unix | Windows |
+//! |---|---|---|---|
+//! | Resource limits | `setrlimit` | `setrlimit` | job object |
+//! | No new privileges | `prctl` | - | - |
+//! | Network and process denial | seccomp | - | - |
+//! | Filesystem rules | landlock | - | - |
+//!
+//! A measure the platform or kernel can't apply is listed as unavailable in the
+//! [`HardeningReport`], or fails with [`Error::Unsupported`] when
+//! [`HostHardening::require_all`] is set.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// A hardening measure applied to a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HardeningMeasure {
+    /// Memory, CPU time and open file limits through `setrlimit`
+    ResourceLimits,
+    
+    /// Memory, CPU time and process limits through a Windows job object
+    JobObject,
+    
+    /// Setting `no_new_privs`, so exec can't gain privileges
+    NoNewPrivileges,
+    
+    /// A seccomp filter denying network, process-inspection, exec and io_uring system calls
+    Seccomp,
+    
+    /// Landlock rules denying the filesystem
+    Landlock,
+}
+
+impl HardeningMeasure {
+    /// Short name of the measure, e.g. `seccomp`
+    pub fn name(&self) -> &'static str {
+        match self {
+            HardeningMeasure::ResourceLimits => "resource_limits",
+            HardeningMeasure::JobObject => "job_object",
+            HardeningMeasure::NoNewPrivileges => "no_new_privileges",
+            HardeningMeasure::Seccomp => "seccomp",
+            HardeningMeasure::Landlock => "landlock",
+        }
+    }
+}
+
+impl fmt::Display for HardeningMeasure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Measures applied by [`HostHardening::apply`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HardeningReport {
+    /// Measures now in force
+    pub applied: Vec<HardeningMeasure>,
+    
+    /// Measures requested but not supported by the platform or kernel
+    pub unavailable: Vec<HardeningMeasure>,
+}
+
+/// OS-level restrictions for helper processes
+///
+/// The default denies the network, other processes and the filesystem, and
+/// sets no resource limits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostHardening {
+    /// Largest address space in bytes; this includes the engine's reservations, not just live memory
+    pub max_memory_bytes: Option<u64>,
+    
+    /// CPU time in seconds before the process is killed
+    pub max_cpu_seconds: Option<u64>,
+    
+    /// Most file descriptors the process may have open
+    pub max_open_files: Option<u64>,
+    
+    /// Set `no_new_privs`; seccomp and landlock set it as well
+    pub no_new_privileges: bool,
+    
+    /// Deny sockets, `ptrace`, reading or writing other processes' memory, exec and io_uring
+    pub seccomp: bool,
+    
+    /// Deny all filesystem access except reading `readable_paths`
+    pub landlock: bool,
+    
+    /// Files and directories the process may still read under landlock
+    pub readable_paths: Vec<PathBuf>,
+    
+    /// Fail instead of skipping measures the platform can't apply
+    pub require_all: bool,
+}
+
+impl Default for HostHardening {
+    fn default() -> Self {
+        Self {
+            max_memory_bytes: None,
+            max_cpu_seconds: None,
+            max_open_files: None,
+            no_new_privileges: true,
+            seccomp: true,
+            landlock: true,
+            readable_paths: Vec::new(),
+            require_all: false,
+        }
+    }
+}
+
+impl HostHardening {
+    /// Hardening with the default restrictions
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Hardening that applies nothing, to build up from
+    pub fn none() -> Self {
+        Self {
+            no_new_privileges: false,
+            seccomp: false,
+            landlock: false,
+            ..Self::default()
+        }
+    }
+    
+    /// Limit the address space to `bytes`
+    pub fn max_memory_bytes(mut self, bytes: u64) -> Self {
+        self.max_memory_bytes = Some(bytes);
+        self
+    }
+    
+    /// Limit CPU time to `seconds`
+    pub fn max_cpu_seconds(mut self, seconds: u64) -> Self {
+        self.max_cpu_seconds = Some(seconds);
+        self
+    }
+    
+    /// Limit open file descriptors to `files`
+    pub fn max_open_files(mut self, files: u64) -> Self {
+        self.max_open_files = Some(files);
+        self
+    }
+    
+    /// Set whether to set `no_new_privs`
+    pub fn no_new_privileges(mut self, enabled: bool) -> Self {
+        self.no_new_privileges = enabled;
+        self
+    }
+    
+    /// Set whether to install the seccomp filter
+    pub fn seccomp(mut self, enabled: bool) -> Self {
+        self.seccomp = enabled;
+        self
+    }
+    
+    /// Set whether to deny the filesystem with landlock
+    pub fn landlock(mut self, enabled: bool) -> Self {
+        self.landlock = enabled;
+        self
+    }
+    
+    /// Let the process read `path` (recursively, for a directory) under landlock
+    pub fn allow_read(mut self, path: impl Into<PathBuf>) -> Self {
+        self.readable_paths.push(path.into());
+        self
+    }
+    
+    /// Set whether measures the platform can't apply are errors
+    pub fn require_all(mut self, required: bool) -> Self {
+        self.require_all = required;
+        self
+    }
+    
+    /// Whether any resource limit is set
+    pub fn has_limits(&self) -> bool {
+        self.max_memory_bytes.is_some() || self.max_cpu_seconds.is_some() || self.max_open_files.is_some()
+    }
+    
+    /// Apply the hardening to the current process
+    ///
+    /// This can't be undone. Call it from the helper process itself, before it
+    /// starts threads: `no_new_privs` and landlock only restrict the calling
+    /// thread and the threads it starts afterwards.
+    pub fn apply(&self) -> Result<HardeningReport> {
+        let mut report = HardeningReport::default();
+        
+        if self.has_limits() {
+            #[cfg(unix)]
+            {
+                unix::set_limits(self).map_err(|e| failed(HardeningMeasure::ResourceLimits, e))?;
+                report.applied.push(HardeningMeasure::ResourceLimits);
+            }
+            #[cfg(windows)]
+            {
+                windows::JobObject::new(self)?.assign_current_process()?;
+                report.applied.push(HardeningMeasure::JobObject);
+            }
+            #[cfg(not(any(unix, windows)))]
+            self.unavailable(HardeningMeasure::ResourceLimits, &mut report)?;
+        }
+        
+        #[cfg(target_os = "linux")]
+        {
+            if self.no_new_privileges || self.seccomp || self.landlock {
+                linux::set_no_new_privileges().map_err(|e| failed(HardeningMeasure::NoNewPrivileges, e))?;
+                report.applied.push(HardeningMeasure::NoNewPrivileges);
+            }
+            if self.landlock {
+                match linux::restrict_filesystem(&self.readable_paths) {
+                    Ok(true) => report.applied.push(HardeningMeasure::Landlock),
+                    Ok(false) => self.unavailable(HardeningMeasure::Landlock, &mut report)?,
+                    Err(e) => return Err(failed(HardeningMeasure::Landlock, e)),
+                }
+            }
+            if self.seccomp {
+                match linux::SeccompFilter::new(false) {
+                    Some(filter) => match filter.install() {
+                        Ok(()) => report.applied.push(HardeningMeasure::Seccomp),
+                        Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+                            self.unavailable(HardeningMeasure::Seccomp, &mut report)?
+                        }
+                        Err(e) => return Err(failed(HardeningMeasure::Seccomp, e)),
+                    },
+                    None => self.unavailable(HardeningMeasure::Seccomp, &mut report)?,
+                }
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        for (enabled, measure) in [
+            (self.no_new_privileges, HardeningMeasure::NoNewPrivileges),
+            (self.seccomp, HardeningMeasure::Seccomp),
+            (self.landlock, HardeningMeasure::Landlock),
+        ] {
+            if enabled {
+                self.unavailable(measure, &mut report)?;
+            }
+        }
+        
+        Ok(report)
+    }
+    
+    /// Apply the measures that survive `exec` to a child before it runs `command`
+    ///
+    /// Landlock rules are left to the child, which can only lock down its
+    /// filesystem once it has loaded. The seccomp filter installed here still
+    /// allows exec so the child can start; a worker that applies the hardening
+    /// itself (see [`crate::runtime::compilation::serve_compile_request`]) then
+    /// denies exec as well.
+    #[cfg(unix)]
+    pub(crate) fn harden_command(&self, command: &mut std::process::Command) -> Result<()> {
+        use std::os::unix::process::CommandExt;
+        
+        #[cfg(target_os = "linux")]
+        let filter = match self.seccomp {
+            true => match linux::SeccompFilter::new(true) {
+                Some(filter) => Some(filter),
+                None => {
+                    self.unavailable(HardeningMeasure::Seccomp, &mut HardeningReport::default())?;
+                    None
+                }
+            },
+            false => None,
+        };
+        #[cfg(not(target_os = "linux"))]
+        for (enabled, measure) in [
+            (self.no_new_privileges, HardeningMeasure::NoNewPrivileges),
+            (self.seccomp, HardeningMeasure::Seccomp),
+        ] {
+            if enabled {
+                self.unavailable(measure, &mut HardeningReport::default())?;
+            }
+        }
+        
+        let hardening = self.clone();
+        let pre_exec = move || {
+            unix::set_limits(&hardening)?;
+            #[cfg(target_os = "linux")]
+            {
+                if hardening.no_new_privileges || filter.is_some() {
+                    linux::set_no_new_privileges()?;
+                }
+                if let Some(filter) = &filter {
+                    filter.install()?;
+                }
+            }
+            Ok(())
+        };
+        // SAFETY: the closure only makes system calls, which are safe between fork and exec
+        unsafe { command.pre_exec(pre_exec) };
+        Ok(())
+    }
+    
+    /// Fail on `measure` if all measures are required, otherwise report it unavailable
+    fn unavailable(&self, measure: HardeningMeasure, report: &mut HardeningReport) -> Result<()> {
+        if self.require_all {
+            return Err(Error::Unsupported {
+                operation: format!("`{}` hardening", measure),
+                context: std::env::consts::OS.to_string(),
+                suggestion: Some(format!(
+                    "Run helper processes on a platform that supports {}, or turn it off",
+                    measure,
+                )),
+            });
+        }
+        report.unavailable.push(measure);
+        Ok(())
+    }
+}
+
+/// Error for a measure the platform supports but refused to apply
+fn failed(measure: HardeningMeasure, error: std::io::Error) -> Error {
+    Error::IoError {
+        message: format!("Failed to apply `{}` hardening: {}", measure, error),
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::io;
+    
+    use super::HostHardening;
+    
+    /// Set the resource limits, both soft and hard so they can't be raised again
+    pub(super) fn set_limits(hardening: &HostHardening) -> io::Result<()> {
+        let limits = [
+            (libc::RLIMIT_AS, hardening.max_memory_bytes),
+            (libc::RLIMIT_CPU, hardening.max_cpu_seconds),
+            (libc::RLIMIT_NOFILE, hardening.max_open_files),
+        ];
+        for (resource, value) in limits {
+            let Some(value) = value else { continue };
+            let limit = libc::rlimit {
+                rlim_cur: value as libc::rlim_t,
+                rlim_max: value as libc::rlim_t,
+            };
+            // SAFETY: `limit` is a valid rlimit for the call's duration
+            if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+    use std::path::PathBuf;
+    
+    /// Set `no_new_privs` for the calling thread and its future children
+    pub(super) fn set_no_new_privileges() -> io::Result<()> {
+        // SAFETY: prctl with integer arguments only
+        if unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    
+    /// `struct landlock_ruleset_attr`, up to ABI 3
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+    }
+    
+    /// `struct landlock_path_beneath_attr`
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+    
+    const CREATE_RULESET_VERSION: libc::c_uint = 1 << 0;
+    const RULE_PATH_BENEATH: libc::c_int = 1;
+    const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+    const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+    
+    /// Every filesystem access right known to landlock ABI `abi`
+    fn handled_access(abi: libc::c_long) -> u64 {
+        // ABI 1 covers execute through make_sym, 2 adds refer and 3 truncate
+        let mut access = (1 << 13) - 1;
+        if abi >= 2 {
+            access |= 1 << 13;
+        }
+        if abi >= 3 {
+            access |= 1 << 14;
+        }
+        access
+    }
+    
+    /// Deny the calling thread all filesystem access except reading `readable`
+    ///
+    /// Returns `false` if the kernel doesn't support landlock.
+    pub(super) fn restrict_filesystem(readable: &[PathBuf]) -> io::Result<bool> {
+        // SAFETY: a null attribute with size 0 asks for the ABI version
+        let abi = unsafe {
+            libc::syscall(libc::SYS_landlock_create_ruleset, std::ptr::null::<RulesetAttr>(), 0usize, CREATE_RULESET_VERSION)
+        };
+        if abi < 0 {
+            let error = io::Error::last_os_error();
+            return match error.raw_os_error() {
+                Some(libc::ENOSYS | libc::EOPNOTSUPP) => Ok(false),
+                _ => Err(error),
+            };
+        }
+        
+        let attr = RulesetAttr { handled_access_fs: handled_access(abi) };
+        // SAFETY: `attr` is a valid ruleset attribute of the given size
+        let fd = unsafe {
+            libc::syscall(libc::SYS_landlock_create_ruleset, &attr as *const RulesetAttr, size_of::<RulesetAttr>(), 0 as libc::c_uint)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the kernel just returned this descriptor to us
+        let ruleset = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+        
+        for path in readable {
+            let file = File::open(path)?;
+            let allowed_access = match file.metadata()?.is_dir() {
+                true => ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR,
+                false => ACCESS_FS_READ_FILE,
+            };
+            let rule = PathBeneathAttr { allowed_access, parent_fd: file.as_raw_fd() };
+            // SAFETY: `rule` is a valid path-beneath rule for an open descriptor
+            let result = unsafe {
+                libc::syscall(libc::SYS_landlock_add_rule, ruleset.as_raw_fd(), RULE_PATH_BENEATH, &rule as *const PathBeneathAttr, 0 as libc::c_uint)
+            };
+            if result < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        
+        // SAFETY: `ruleset` is a landlock ruleset descriptor
+        if unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0 as libc::c_uint) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(true)
+    }
+    
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xC000_003E;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xC000_00B7;
+    
+    /// System calls the seccomp filter denies
+    ///
+    /// io_uring is denied outright: its operations run without passing through
+    /// seccomp, so a ring could open sockets the filter denies.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    const DENIED_SYSCALLS: [libc::c_long; 14] = [
+        libc::SYS_socket,
+        libc::SYS_socketpair,
+        libc::SYS_connect,
+        libc::SYS_bind,
+        libc::SYS_listen,
+        libc::SYS_accept4,
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_io_uring_setup,
+        libc::SYS_io_uring_enter,
+        libc::SYS_io_uring_register,
+        libc::SYS_execve,
+        libc::SYS_execveat,
+    ];
+    
+    /// System calls in [`DENIED_SYSCALLS`] a child needs to start its program
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    const EXEC_SYSCALLS: [libc::c_long; 2] = [libc::SYS_execve, libc::SYS_execveat];
+    
+    /// `struct seccomp_data` offsets of the system call number and architecture
+    const SECCOMP_DATA_NR: u32 = 0;
+    const SECCOMP_DATA_ARCH: u32 = 4;
+    
+    fn statement(code: u32, k: u32) -> libc::sock_filter {
+        libc::sock_filter { code: code as u16, jt: 0, jf: 0, k }
+    }
+    
+    fn jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+        libc::sock_filter { code: code as u16, jt, jf, k }
+    }
+    
+    /// A compiled seccomp filter that fails [`DENIED_SYSCALLS`] with `EPERM`
+    pub(super) struct SeccompFilter {
+        program: Vec<libc::sock_filter>,
+    }
+    
+    impl SeccompFilter {
+        /// Build the filter, or `None` on architectures it doesn't know
+        ///
+        /// With `allow_exec` the filter leaves out [`EXEC_SYSCALLS`], for a
+        /// child that installs it before running its program.
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        pub(super) fn new(allow_exec: bool) -> Option<Self> {
+            let load = libc::BPF_LD | libc::BPF_W | libc::BPF_ABS;
+            let equals = libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K;
+            let denied: Vec<libc::c_long> = DENIED_SYSCALLS.into_iter()
+                .filter(|syscall| !(allow_exec && EXEC_SYSCALLS.contains(syscall)))
+                .collect();
+            let count = denied.len();
+            
+            // Kill callers using another architecture's system call numbers
+            let mut program = vec![
+                statement(load, SECCOMP_DATA_ARCH),
+                jump(equals, AUDIT_ARCH, 1, 0),
+                statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS),
+                statement(load, SECCOMP_DATA_NR),
+            ];
+            // On x86_64, x32 system calls carry this bit and use different numbers
+            #[cfg(target_arch = "x86_64")]
+            program.push(jump(libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K, 0x4000_0000, count as u8 + 1, 0));
+            for (index, syscall) in denied.iter().enumerate() {
+                program.push(jump(equals, *syscall as u32, (count - index) as u8, 0));
+            }
+            program.push(statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ALLOW));
+            program.push(statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32));
+            Some(Self { program })
+        }
+        
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        pub(super) fn new(_allow_exec: bool) -> Option<Self> {
+            None
+        }
+        
+        /// Install the filter on every thread of the process
+        pub(super) fn install(&self) -> io::Result<()> {
+            let program = libc::sock_fprog {
+                len: self.program.len() as libc::c_ushort,
+                filter: self.program.as_ptr() as *mut libc::sock_filter,
+            };
+            // SAFETY: `program` points at the filter, which outlives the call
+            let result = unsafe {
+                libc::syscall(
+                    libc::SYS_seccomp,
+                    libc::SECCOMP_SET_MODE_FILTER,
+                    libc::SECCOMP_FILTER_FLAG_TSYNC,
+                    &program as *const libc::sock_fprog,
+                )
+            };
+            match result {
+                0 => Ok(()),
+                result if result < 0 => Err(io::Error::last_os_error()),
+                // A thread that couldn't be synchronized
+                _ => Err(io::Error::from_raw_os_error(libc::EAGAIN)),
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::mem;
+    use std::os::windows::io::AsRawHandle;
+    use std::ptr;
+    
+    use winapi::shared::minwindef::{DWORD, FALSE};
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::jobapi2::{AssignProcessToJobObject, SetInformationJobObject};
+    use winapi::um::processthreadsapi::GetCurrentProcess;
+    use winapi::um::winbase::CreateJobObjectW;
+    use winapi::um::winnt::{
+        JobObjectExtendedLimitInformation, HANDLE, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_ACTIVE_PROCESS, JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE, JOB_OBJECT_LIMIT_PROCESS_MEMORY, JOB_OBJECT_LIMIT_PROCESS_TIME,
+    };
+    
+    use super::{failed, HardeningMeasure, HostHardening};
+    use crate::error::Result;
+    
+    /// A job object enforcing the hardening's limits; closing it kills its processes
+    pub(crate) struct JobObject {
+        handle: HANDLE,
+    }
+    
+    // SAFETY: job object handles may be used and closed from any thread
+    unsafe impl Send for JobObject {}
+    
+    impl JobObject {
+        /// Create a job object with `hardening`'s limits
+        pub(crate) fn new(hardening: &HostHardening) -> Result<Self> {
+            // SAFETY: no security attributes and no name
+            let handle = unsafe { CreateJobObjectW(ptr::null_mut(), ptr::null()) };
+            if handle.is_null() {
+                return Err(failed(HardeningMeasure::JobObject, std::io::Error::last_os_error()));
+            }
+            let job = Self { handle };
+            
+            // SAFETY: the structure is plain data, for which all zeroes is valid
+            let mut limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { mem::zeroed() };
+            let mut flags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE
+                | JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION
+                | JOB_OBJECT_LIMIT_ACTIVE_PROCESS;
+            limits.BasicLimitInformation.ActiveProcessLimit = 1;
+            if let Some(bytes) = hardening.max_memory_bytes {
+                flags |= JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+                limits.ProcessMemoryLimit = bytes as usize;
+            }
+            if let Some(seconds) = hardening.max_cpu_seconds {
+                flags |= JOB_OBJECT_LIMIT_PROCESS_TIME;
+                // In 100 nanosecond units
+                // SAFETY: writing the integer view of the union
+                unsafe { *limits.BasicLimitInformation.PerProcessUserTimeLimit.QuadPart_mut() = seconds as i64 * 10_000_000 };
+            }
+            limits.BasicLimitInformation.LimitFlags = flags;
+            
+            // SAFETY: `limits` is the structure this information class expects
+            let set = unsafe {
+                SetInformationJobObject(
+                    job.handle,
+                    JobObjectExtendedLimitInformation,
+                    &mut limits as *mut _ as *mut _,
+                    mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as DWORD,
+                )
+            };
+            if set == FALSE {
+                return Err(failed(HardeningMeasure::JobObject, std::io::Error::last_os_error()));
+            }
+            Ok(job)
+        }
+        
+        /// Put `child` in the job
+        pub(crate) fn assign(&self, child: &std::process::Child) -> Result<()> {
+            self.assign_handle(child.as_raw_handle() as HANDLE)
+        }
+        
+        /// Put the current process in the job, for the rest of its life
+        pub(crate) fn assign_current_process(self) -> Result<()> {
+            // SAFETY: the pseudo handle for the current process is always valid
+            self.assign_handle(unsafe { GetCurrentProcess() })?;
+            // Closing the job would kill this process
+            mem::forget(self);
+            Ok(())
+        }
+        
+        fn assign_handle(&self, process: HANDLE) -> Result<()> {
+            // SAFETY: both handles are open
+            if unsafe { AssignProcessToJobObject(self.handle, process) } == FALSE {
+                return Err(failed(HardeningMeasure::JobObject, std::io::Error::last_os_error()));
+            }
+            Ok(())
+        }
+    }
+    
+    impl Drop for JobObject {
+        fn drop(&mut self) {
+            // SAFETY: the handle is open and owned by us
+            unsafe { CloseHandle(self.handle) };
+        }
+    }
+}
+
+#[cfg(windows)]
+pub(crate) use windows::JobObject;
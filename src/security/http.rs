@@ -0,0 +1,557 @@
+//! HTTP requests sent by the host for guests
+//!
+//! Guests don't open connections to make HTTP requests: they hand them to the
+//! host through [`crate::runtime::HTTP_IMPORT_MODULE`]. The host checks each
+//! URL against the instance's [`NetworkCapability`] and sends it under the
+//! deployment's [`HttpEgressPolicy`]: CA certificates pinned per host, client
+//! certificates, an egress proxy with credentials, and per-host timeouts and
+//! retries, so all guest traffic goes through the same egress controls.
+//! Redirects are returned to the guest rather than followed, as they could
+//! lead to a host the instance may not reach. Every request is audited.
+//!
+//! [`NetworkCapability`]: crate::security::NetworkCapability
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use base64::Engine as _;
+use reqwest::{Certificate, Client, Identity, Method, Proxy, Url};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::{Error, Result, SecurityContext};
+use crate::runtime::{GuestCall, HttpFetch};
+use crate::security::audit::{AuditEventType, AuditLogger};
+use crate::security::capabilities::{ActiveCapabilities, CapabilityManager};
+use crate::security::Capabilities;
+use crate::InstanceId;
+
+/// Capability name reported when a URL may not be requested
+const NETWORK_CAPABILITY: &str = "network";
+
+/// Statuses a gateway returns when the upstream may recover, which are retried
+const RETRIED_STATUSES: [u16; 3] = [502, 503, 504];
+
+/// A request a guest asks the host to send
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpRequest {
+    /// Request method, `GET` if omitted
+    #[serde(default = "default_method")]
+    pub method: String,
+    
+    /// Absolute `http` or `https` URL
+    pub url: String,
+    
+    /// Request headers
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    
+    /// Request body, base64 encoded in JSON
+    #[serde(default, with = "base64_bytes")]
+    pub body: Vec<u8>,
+}
+
+/// The response handed back to the guest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpResponse {
+    /// Status code
+    pub status: u16,
+    
+    /// Response headers, repeated headers joined with `, `
+    pub headers: BTreeMap<String, String>,
+    
+    /// Response body, base64 encoded in JSON
+    #[serde(with = "base64_bytes")]
+    pub body: Vec<u8>,
+}
+
+/// How often a request is attempted before its failure is returned
+///
+/// Connection failures, timeouts, and 502, 503 and 504 responses are retried.
+/// Only idempotent methods (`GET`, `HEAD`, `PUT`, `DELETE` and `OPTIONS`) are
+/// retried unless [`HttpRetryPolicy::retry_non_idempotent`] is set, since a
+/// `POST` that timed out may already have taken effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpRetryPolicy {
+    /// Attempts in total, including the first
+    pub max_attempts: u32,
+    
+    /// Wait between attempts
+    pub backoff: Duration,
+    
+    /// Whether `POST`, `PATCH` and other non-idempotent requests are retried too
+    pub non_idempotent: bool,
+}
+
+impl HttpRetryPolicy {
+    /// Make up to `max_attempts` attempts, `backoff` apart
+    pub fn new(max_attempts: u32, backoff: Duration) -> Self {
+        Self { max_attempts, backoff, non_idempotent: false }
+    }
+    
+    /// Retry non-idempotent requests as well, for hosts known to deduplicate them
+    pub fn retry_non_idempotent(mut self) -> Self {
+        self.non_idempotent = true;
+        self
+    }
+    
+    /// Attempts allowed for a request with `method`
+    fn attempts_for(&self, method: &Method) -> u32 {
+        let idempotent = [Method::GET, Method::HEAD, Method::PUT, Method::DELETE, Method::OPTIONS].contains(method);
+        match idempotent || self.non_idempotent {
+            true => self.max_attempts.max(1),
+            false => 1,
+        }
+    }
+}
+
+impl Default for HttpRetryPolicy {
+    fn default() -> Self {
+        Self::new(1, Duration::from_millis(100))
+    }
+}
+
+/// Proxy all guest requests go through
+#[derive(Clone)]
+pub struct HttpProxy {
+    url: String,
+    credentials: Option<(String, String)>,
+}
+
+impl HttpProxy {
+    /// Send requests through the proxy at `url`, e.g. `http://egress.internal:3128`
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            credentials: None,
+        }
+    }
+    
+    /// Authenticate to the proxy with basic credentials
+    pub fn basic_auth(mut self, username: &str, password: &str) -> Self {
+        self.credentials = Some((username.to_string(), password.to_string()));
+        self
+    }
+}
+
+impl fmt::Debug for HttpProxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpProxy")
+            .field("url", &self.url)
+            .field("username", &self.credentials.as_ref().map(|(username, _)| username))
+            .finish_non_exhaustive()
+    }
+}
+
+/// TLS and retry settings for requests to one host
+#[derive(Clone, Default)]
+pub struct HttpHostPolicy {
+    pinned_roots: Vec<Vec<u8>>,
+    client_identity: Option<Vec<u8>>,
+    timeout: Option<Duration>,
+    retry: Option<HttpRetryPolicy>,
+}
+
+impl HttpHostPolicy {
+    /// Settings inheriting the egress policy's timeout and retries
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Trust only the CA certificates in this PEM bundle, and others pinned, for the host
+    pub fn pin_root_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.pinned_roots.push(pem.into());
+        self
+    }
+    
+    /// Present this client certificate, a PEM certificate chain and private key
+    pub fn client_identity_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.client_identity = Some(pem.into());
+        self
+    }
+    
+    /// Give each attempt this long to complete
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    
+    /// Retry requests to the host this way
+    pub fn retry(mut self, retry: HttpRetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+}
+
+impl fmt::Debug for HttpHostPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpHostPolicy")
+            .field("pinned_roots", &self.pinned_roots.len())
+            .field("client_identity", &self.client_identity.is_some())
+            .field("timeout", &self.timeout)
+            .field("retry", &self.retry)
+            .finish()
+    }
+}
+
+/// How the host sends guests' HTTP requests
+#[derive(Debug, Clone)]
+pub struct HttpEgressPolicy {
+    proxy: Option<HttpProxy>,
+    timeout: Duration,
+    retry: HttpRetryPolicy,
+    max_response_bytes: usize,
+    hosts: HashMap<String, HttpHostPolicy>,
+}
+
+impl HttpEgressPolicy {
+    /// Direct connections, the public web PKI, a 30 second timeout, no retries and 16MB responses
+    pub fn new() -> Self {
+        Self {
+            proxy: None,
+            timeout: Duration::from_secs(30),
+            retry: HttpRetryPolicy::default(),
+            max_response_bytes: 16 * 1024 * 1024,
+            hosts: HashMap::new(),
+        }
+    }
+    
+    /// Send every request through `proxy`
+    pub fn proxy(mut self, proxy: HttpProxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+    
+    /// Give each attempt this long to complete, unless its host says otherwise
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    
+    /// Retry requests this way, unless their host says otherwise
+    pub fn retry(mut self, retry: HttpRetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    
+    /// Fail requests whose response body is larger than this
+    pub fn max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = max_response_bytes;
+        self
+    }
+    
+    /// Use `policy` for requests to `host`
+    pub fn host(mut self, host: &str, policy: HttpHostPolicy) -> Self {
+        self.hosts.insert(host.to_ascii_lowercase(), policy);
+        self
+    }
+}
+
+impl Default for HttpEgressPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A client and the settings requests sent with it use
+struct HostClient {
+    client: Client,
+    timeout: Duration,
+    retry: HttpRetryPolicy,
+}
+
+/// Clients built from an [`HttpEgressPolicy`]
+struct Clients {
+    default: HostClient,
+    hosts: HashMap<String, HostClient>,
+    max_response_bytes: usize,
+}
+
+impl Clients {
+    fn build(policy: &HttpEgressPolicy) -> Result<Self> {
+        let default = HostClient {
+            client: client_builder(policy)?.build().map_err(|e| config_error(&e.to_string()))?,
+            timeout: policy.timeout,
+            retry: policy.retry,
+        };
+        let hosts = policy.hosts.iter()
+            .map(|(host, host_policy)| {
+                let mut builder = client_builder(policy)?;
+                if !host_policy.pinned_roots.is_empty() {
+                    builder = builder.tls_built_in_root_certs(false);
+                    for pem in &host_policy.pinned_roots {
+                        let roots = Certificate::from_pem_bundle(pem)
+                            .map_err(|e| config_error(&format!("pinned root for {}: {}", host, e)))?;
+                        if roots.is_empty() {
+                            return Err(config_error(&format!("pinned root for {} holds no certificate", host)));
+                        }
+                        builder = roots.into_iter().fold(builder, |builder, root| builder.add_root_certificate(root));
+                    }
+                }
+                if let Some(pem) = &host_policy.client_identity {
+                    let identity = Identity::from_pem(pem)
+                        .map_err(|e| config_error(&format!("client identity for {}: {}", host, e)))?;
+                    builder = builder.identity(identity);
+                }
+                let client = builder.build().map_err(|e| config_error(&format!("client for {}: {}", host, e)))?;
+                Ok((host.clone(), HostClient {
+                    client,
+                    timeout: host_policy.timeout.unwrap_or(policy.timeout),
+                    retry: host_policy.retry.unwrap_or(policy.retry),
+                }))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            default,
+            hosts,
+            max_response_bytes: policy.max_response_bytes,
+        })
+    }
+}
+
+/// Sends instances' HTTP requests according to their capabilities and the egress policy
+pub struct HttpEgress {
+    clients: RwLock<Arc<Clients>>,
+    audit: AuditLogger,
+}
+
+impl HttpEgress {
+    /// Create an egress with the default [`HttpEgressPolicy`]
+    pub fn new() -> Self {
+        let clients = Clients::build(&HttpEgressPolicy::default())
+            .expect("the default HTTP egress policy builds");
+        Self {
+            clients: RwLock::new(Arc::new(clients)),
+            audit: AuditLogger::new(1000),
+        }
+    }
+    
+    /// Record requests in an existing audit logger
+    pub fn with_audit_logger(mut self, logger: AuditLogger) -> Self {
+        self.audit = logger;
+        self
+    }
+    
+    /// Get the audit logger recording requests
+    pub fn audit_logger(&self) -> &AuditLogger {
+        &self.audit
+    }
+    
+    /// Send later requests under `policy`
+    ///
+    /// Fails without changing the current policy if a pinned root or client
+    /// identity doesn't parse.
+    pub fn set_policy(&self, policy: &HttpEgressPolicy) -> Result<()> {
+        let clients = Clients::build(policy)?;
+        *self.clients.write().unwrap() = Arc::new(clients);
+        Ok(())
+    }
+    
+    /// Send `request` on behalf of an instance
+    pub async fn send(&self, instance_id: InstanceId, capabilities: &Capabilities, request: HttpRequest) -> Result<HttpResponse> {
+        let url = Url::parse(&request.url).map_err(|e| invalid(&format!("invalid URL {}: {}", request.url, e)))?;
+        let secure = match url.scheme() {
+            "https" => true,
+            "http" => false,
+            scheme => return Err(invalid(&format!("unsupported URL scheme {}", scheme))),
+        };
+        let host = url.host_str().ok_or_else(|| invalid(&format!("URL {} has no host", request.url)))?.to_ascii_lowercase();
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let port = url.port_or_known_default().unwrap_or(if secure { 443 } else { 80 });
+        let method = Method::from_bytes(request.method.to_ascii_uppercase().as_bytes())
+            .map_err(|_| invalid(&format!("invalid method {}", request.method)))?;
+        
+        let verdict = CapabilityManager::from_capabilities(capabilities)
+            .with_audit_logger(self.audit.clone())
+            .with_instance_id(instance_id.to_string())
+            .check("network", "connect", &[host, &port.to_string(), if secure { "secure" } else { "plain" }]);
+        if verdict.is_err() {
+            self.record(instance_id, &request, false);
+            return Err(Error::SecurityViolation {
+                violation: format!("Instance may not request {}", url),
+                instance_id: Some(instance_id.0),
+                context: SecurityContext {
+                    attempted_operation: format!("{} {}", method, url),
+                    required_capability: NETWORK_CAPABILITY.to_string(),
+                    available_capabilities: Vec::new(),
+                },
+            });
+        }
+        self.record(instance_id, &request, true);
+        
+        let clients = self.clients.read().unwrap().clone();
+        let target = clients.hosts.get(host).unwrap_or(&clients.default);
+        let attempts = target.retry.attempts_for(&method);
+        for attempt in 1..=attempts {
+            let mut builder = target.client.request(method.clone(), url.clone()).timeout(target.timeout);
+            for (name, value) in &request.headers {
+                builder = builder.header(name, value);
+            }
+            let last = attempt == attempts;
+            match builder.body(request.body.clone()).send().await {
+                Ok(response) if !last && RETRIED_STATUSES.contains(&response.status().as_u16()) => {
+                    log::debug!("{} {} returned {}; retrying", method, url, response.status());
+                }
+                Ok(response) => return read_response(response, clients.max_response_bytes, instance_id).await,
+                Err(e) if !last && (e.is_connect() || e.is_timeout()) => {
+                    log::debug!("{} {} failed: {}; retrying", method, url, e);
+                }
+                Err(e) if e.is_timeout() => {
+                    return Err(Error::Timeout {
+                        operation: format!("{} {}", method, url),
+                        duration: target.timeout,
+                        instance_id: Some(instance_id.0),
+                    });
+                }
+                Err(e) => return Err(network_error(&url, e)),
+            }
+            tokio::time::sleep(target.retry.backoff).await;
+        }
+        unreachable!("the last attempt always returns")
+    }
+    
+    /// Record a request in the audit log
+    fn record(&self, instance_id: InstanceId, request: &HttpRequest, allowed: bool) {
+        let event = AuditEventType::HttpRequest {
+            instance_id: instance_id.to_string(),
+            method: request.method.clone(),
+            url: request.url.clone(),
+            allowed,
+        };
+        if allowed {
+            self.audit.info(event, &format!("Requesting {}", request.url));
+        } else {
+            self.audit.warning(event, &format!("Denied requesting {}", request.url));
+        }
+    }
+}
+
+impl Default for HttpEgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for HttpEgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpEgress")
+            .field("hosts", &self.clients.read().unwrap().hosts.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Sends one instance's requests through a shared egress
+pub struct InstanceHttp {
+    egress: Arc<HttpEgress>,
+    instance_id: InstanceId,
+    capabilities: ActiveCapabilities,
+}
+
+impl InstanceHttp {
+    /// Create a fetcher for requests sent by `instance_id`
+    pub fn new(egress: Arc<HttpEgress>, instance_id: InstanceId, capabilities: ActiveCapabilities) -> Self {
+        Self {
+            egress,
+            instance_id,
+            capabilities,
+        }
+    }
+}
+
+impl HttpFetch for InstanceHttp {
+    fn fetch<'a>(&'a self, request: &'a [u8]) -> GuestCall<'a, Vec<u8>> {
+        Box::pin(async move {
+            let request: HttpRequest = serde_json::from_slice(request)?;
+            let response = self.egress.send(self.instance_id, &self.capabilities.current(), request).await?;
+            Ok(serde_json::to_vec(&response)?)
+        })
+    }
+}
+
+/// Client settings every client in a policy shares
+fn client_builder(policy: &HttpEgressPolicy) -> Result<reqwest::ClientBuilder> {
+    let builder = Client::builder()
+        .use_rustls_tls()
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy();
+    let Some(proxy) = &policy.proxy else {
+        return Ok(builder);
+    };
+    let mut all = Proxy::all(&proxy.url).map_err(|e| config_error(&format!("proxy {}: {}", proxy.url, e)))?;
+    if let Some((username, password)) = &proxy.credentials {
+        all = all.basic_auth(username, password);
+    }
+    Ok(builder.proxy(all))
+}
+
+/// Read a response, failing once its body passes `max_bytes`
+async fn read_response(mut response: reqwest::Response, max_bytes: usize, instance_id: InstanceId) -> Result<HttpResponse> {
+    let mut headers: BTreeMap<String, String> = BTreeMap::new();
+    for (name, value) in response.headers() {
+        let value = String::from_utf8_lossy(value.as_bytes());
+        headers.entry(name.as_str().to_string())
+            .and_modify(|joined| {
+                joined.push_str(", ");
+                joined.push_str(&value);
+            })
+            .or_insert_with(|| value.into_owned());
+    }
+    let status = response.status().as_u16();
+    let url = response.url().clone();
+    
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| network_error(&url, e))? {
+        if body.len() + chunk.len() > max_bytes {
+            return Err(Error::ResourceLimit {
+                message: format!(
+                    "Response from {} for instance {} is larger than {} bytes",
+                    url, instance_id, max_bytes,
+                ),
+            });
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(HttpResponse { status, headers, body })
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidInput {
+        field: "http request".to_string(),
+        reason: reason.to_string(),
+        suggestion: None,
+    }
+}
+
+fn config_error(reason: &str) -> Error {
+    Error::config_error(
+        format!("Invalid HTTP egress policy: {}", reason),
+        Some("Check the proxy URL and that certificates and keys are PEM encoded".to_string()),
+    )
+}
+
+fn network_error(url: &Url, error: reqwest::Error) -> Error {
+    Error::Network {
+        operation: "http request".to_string(),
+        reason: error.to_string(),
+        endpoint: Some(url.to_string()),
+    }
+}
+
+/// Bytes as a base64 string
+mod base64_bytes {
+    use super::*;
+    
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+    
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
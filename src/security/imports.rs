@@ -0,0 +1,541 @@
+//! Pre-instantiation validation of module imports
+//!
+//! [`ImportPolicy`] decides which imports the host provides. Operators narrow
+//! it further with [`ImportRules`]: namespaces and functions modules may
+//! import, patterns they may never import, and how many imports they may have.
+//! The rules hold for every import, whichever part of the host would provide
+//! it, so a rule denying `wasi_snapshot_preview1::proc_*` keeps such modules
+//! out even if a later capability or host function would link them.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// WASI namespaces allowed by default
+pub const DEFAULT_WASI_NAMESPACES: &[&str] = &["wasi_snapshot_preview1", "wasi_unstable"];
+
+/// Kind of an imported item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportKind {
+    /// Function import
+    Function,
+    
+    /// Memory import
+    Memory,
+    
+    /// Table import
+    Table,
+    
+    /// Global import
+    Global,
+    
+    /// Any other import (tags, component items)
+    Other,
+}
+
+impl fmt::Display for ImportKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportKind::Function => write!(f, "function"),
+            ImportKind::Memory => write!(f, "memory"),
+            ImportKind::Table => write!(f, "table"),
+            ImportKind::Global => write!(f, "global"),
+            ImportKind::Other => write!(f, "item"),
+        }
+    }
+}
+
+/// An item imported by a module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleImport {
+    /// Import module (namespace)
+    pub module: String,
+    
+    /// Import name
+    pub name: String,
+    
+    /// Import kind
+    pub kind: ImportKind,
+}
+
+impl ModuleImport {
+    /// Create a new module import
+    pub fn new(module: &str, name: &str, kind: ImportKind) -> Self {
+        Self {
+            module: module.to_string(),
+            name: name.to_string(),
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for ModuleImport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}::{}", self.kind, self.module, self.name)
+    }
+}
+
+/// Report of imports that the host does not provide
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Imports that are neither registered host items nor in an allowed WASI namespace
+    pub unknown: Vec<ModuleImport>,
+}
+
+impl ImportReport {
+    /// Check whether every import is provided
+    pub fn is_clean(&self) -> bool {
+        self.unknown.is_empty()
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown: Vec<String> = self.unknown.iter().map(|i| i.to_string()).collect();
+        write!(f, "{}", unknown.join(", "))
+    }
+}
+
+/// Imports matched by a rule, written `module::name`
+///
+/// The name may end in `*` to match every name starting with what comes
+/// before it, e.g. `wasi_snapshot_preview1::sock_*`; `module::*` matches the
+/// whole module.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ImportPattern {
+    /// Import module (namespace)
+    pub module: String,
+    
+    /// Import name, or a prefix of it followed by `*`
+    pub name: String,
+}
+
+impl ImportPattern {
+    /// Check whether the pattern matches an import
+    ///
+    /// Imports of the host world under their WIT names match patterns for their core names too.
+    pub fn matches(&self, import: &ModuleImport) -> bool {
+        let core = crate::runtime::wit::core_import(&import.module, &import.name);
+        std::iter::once((import.module.as_str(), import.name.as_str()))
+            .chain(core)
+            .any(|(module, name)| self.matches_names(module, name))
+    }
+    
+    fn matches_names(&self, module: &str, name: &str) -> bool {
+        if module != self.module {
+            return false;
+        }
+        match self.name.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == self.name,
+        }
+    }
+}
+
+impl FromStr for ImportPattern {
+    type Err = Error;
+    
+    fn from_str(pattern: &str) -> Result<Self> {
+        match pattern.split_once("::") {
+            Some((module, name)) if !module.is_empty() && !name.is_empty()
+                && name.find('*').is_none_or(|star| star == name.len() - 1) => {
+                Ok(Self { module: module.to_string(), name: name.to_string() })
+            }
+            _ => Err(Error::InvalidInput {
+                field: "import_pattern".to_string(),
+                reason: format!("`{}` is not an import pattern", pattern),
+                suggestion: Some("Write patterns as `module::name`, `module::prefix*` or `module::*`".to_string()),
+            }),
+        }
+    }
+}
+
+impl TryFrom<String> for ImportPattern {
+    type Error = Error;
+    
+    fn try_from(pattern: String) -> Result<Self> {
+        pattern.parse()
+    }
+}
+
+impl From<ImportPattern> for String {
+    fn from(pattern: ImportPattern) -> Self {
+        pattern.to_string()
+    }
+}
+
+impl fmt::Display for ImportPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::{}", self.module, self.name)
+    }
+}
+
+/// An import rule a module breaks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportViolation {
+    /// Import outside the allowed namespaces and functions
+    NotAllowed(ModuleImport),
+    
+    /// Import matching a denied pattern
+    Denied {
+        /// The import
+        import: ModuleImport,
+        
+        /// Pattern denying it
+        pattern: ImportPattern,
+    },
+    
+    /// More imports than allowed, in total or from one namespace
+    TooMany {
+        /// Namespace the limit is for, or `None` for the total
+        namespace: Option<String>,
+        
+        /// Most imports allowed
+        limit: usize,
+        
+        /// Imports the module has
+        found: usize,
+    },
+}
+
+impl fmt::Display for ImportViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportViolation::NotAllowed(import) => write!(f, "{} is not allowed", import),
+            ImportViolation::Denied { import, pattern } => write!(f, "{} is denied by {}", import, pattern),
+            ImportViolation::TooMany { namespace: Some(namespace), limit, found } => {
+                write!(f, "{} imports from {}, at most {} allowed", found, namespace, limit)
+            }
+            ImportViolation::TooMany { namespace: None, limit, found } => {
+                write!(f, "{} imports, at most {} allowed", found, limit)
+            }
+        }
+    }
+}
+
+/// Operator rules restricting the imports modules may have
+///
+/// Empty rules allow every import the [`ImportPolicy`] does.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportRules {
+    /// Namespaces modules may import from; with `allowed_functions`, every import must match one when either is set
+    pub allowed_namespaces: BTreeSet<String>,
+    
+    /// Functions modules may import outside the allowed namespaces
+    pub allowed_functions: Vec<ImportPattern>,
+    
+    /// Imports refused even if allowed
+    pub denied: Vec<ImportPattern>,
+    
+    /// Most imports a module may have
+    pub max_imports: Option<usize>,
+    
+    /// Most imports a module may have from each namespace
+    pub max_namespace_imports: BTreeMap<String, usize>,
+}
+
+impl ImportRules {
+    /// Rules allowing every import
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Allow imports from a namespace
+    pub fn allow_namespace(mut self, namespace: &str) -> Self {
+        self.allowed_namespaces.insert(namespace.to_string());
+        self
+    }
+    
+    /// Allow imports matching a pattern
+    pub fn allow_function(mut self, pattern: ImportPattern) -> Self {
+        self.allowed_functions.push(pattern);
+        self
+    }
+    
+    /// Refuse imports matching a pattern
+    pub fn deny(mut self, pattern: ImportPattern) -> Self {
+        self.denied.push(pattern);
+        self
+    }
+    
+    /// Allow at most `limit` imports
+    pub fn max_imports(mut self, limit: usize) -> Self {
+        self.max_imports = Some(limit);
+        self
+    }
+    
+    /// Allow at most `limit` imports from a namespace
+    pub fn max_namespace_imports(mut self, namespace: &str, limit: usize) -> Self {
+        self.max_namespace_imports.insert(namespace.to_string(), limit);
+        self
+    }
+    
+    /// Check whether the rules allow every import
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+    
+    /// Rules the imports break
+    pub fn violations(&self, imports: &[ModuleImport]) -> Vec<ImportViolation> {
+        let mut violations = Vec::new();
+        let allowlist = !self.allowed_namespaces.is_empty() || !self.allowed_functions.is_empty();
+        for import in imports {
+            if let Some(pattern) = self.denied.iter().find(|pattern| pattern.matches(import)) {
+                violations.push(ImportViolation::Denied { import: import.clone(), pattern: pattern.clone() });
+            } else if allowlist && !self.allows(import) {
+                violations.push(ImportViolation::NotAllowed(import.clone()));
+            }
+        }
+        
+        if let Some(limit) = self.max_imports.filter(|limit| imports.len() > *limit) {
+            violations.push(ImportViolation::TooMany { namespace: None, limit, found: imports.len() });
+        }
+        for (namespace, limit) in &self.max_namespace_imports {
+            let found = imports.iter()
+                .filter(|import| namespace_of(import) == namespace.as_str())
+                .count();
+            if found > *limit {
+                violations.push(ImportViolation::TooMany { namespace: Some(namespace.clone()), limit: *limit, found });
+            }
+        }
+        violations
+    }
+    
+    /// Check imports, failing with every rule they break
+    pub fn check(&self, imports: &[ModuleImport]) -> Result<()> {
+        let violations = self.violations(imports);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::ForbiddenImports { violations })
+        }
+    }
+    
+    fn allows(&self, import: &ModuleImport) -> bool {
+        self.allowed_namespaces.contains(namespace_of(import))
+            || self.allowed_functions.iter().any(|pattern| pattern.matches(import))
+    }
+}
+
+/// Core namespace of an import, counting WIT-named imports under their core module
+fn namespace_of(import: &ModuleImport) -> &str {
+    crate::runtime::wit::core_import(&import.module, &import.name)
+        .map_or(import.module.as_str(), |(module, _)| module)
+}
+
+/// Imports wired into an instance's linker
+///
+/// Only the items a module imports are linked; every other WASI function and
+/// host import is left out of the instance.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkReport {
+    /// Imports linked to a host definition
+    pub linked: Vec<ModuleImport>,
+    
+    /// Imports that are not allowed or have no host definition
+    pub unresolved: Vec<ModuleImport>,
+}
+
+impl LinkReport {
+    /// Check whether an import was linked
+    pub fn is_linked(&self, module: &str, name: &str) -> bool {
+        self.linked.iter().any(|import| import.module == module && import.name == name)
+    }
+    
+    /// Check whether every import was linked
+    pub fn is_complete(&self) -> bool {
+        self.unresolved.is_empty()
+    }
+}
+
+impl fmt::Display for LinkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let linked: Vec<String> = self.linked.iter().map(|i| i.to_string()).collect();
+        write!(f, "linked {} imports", linked.len())?;
+        if !linked.is_empty() {
+            write!(f, ": {}", linked.join(", "))?;
+        }
+        if !self.unresolved.is_empty() {
+            let unresolved: Vec<String> = self.unresolved.iter().map(|i| i.to_string()).collect();
+            write!(f, "; unresolved: {}", unresolved.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Policy describing which imports a module may have
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportPolicy {
+    /// Reject modules with unknown imports before instantiation
+    pub deny_unknown: bool,
+    
+    /// Allowed WASI namespaces
+    pub wasi_namespaces: BTreeSet<String>,
+    
+    /// Registered host items as (module, name)
+    pub host_imports: BTreeSet<(String, String)>,
+    
+    /// Operator rules every import must also pass, even with `deny_unknown` off
+    pub rules: ImportRules,
+}
+
+impl Default for ImportPolicy {
+    fn default() -> Self {
+        let mut policy = Self {
+            deny_unknown: true,
+            wasi_namespaces: DEFAULT_WASI_NAMESPACES.iter().map(|ns| ns.to_string()).collect(),
+            host_imports: BTreeSet::new(),
+            rules: ImportRules::default(),
+        };
+        // Memory, result streaming, service calls, settings, secrets, DNS, HTTP, timers, checkpoints, progress, allocation failures, logging, and children provided by the runtime linker
+        policy.host_imports.insert(("env".to_string(), "memory".to_string()));
+        policy.host_imports.insert((
+            crate::runtime::STREAM_IMPORT_MODULE.to_string(),
+            crate::runtime::STREAM_EMIT_FUNCTION.to_string(),
+        ));
+        policy.host_imports.insert((
+            crate::runtime::SERVICE_IMPORT_MODULE.to_string(),
+            crate::runtime::SERVICE_CALL_FUNCTION.to_string(),
+        ));
+        policy.host_imports.insert((
+            crate::runtime::CONFIG_IMPORT_MODULE.to_string(),
+            crate::runtime::CONFIG_GET_FUNCTION.to_string(),
+        ));
+        policy.host_imports.insert((
+            crate::runtime::SECRETS_IMPORT_MODULE.to_string(),
+            crate::runtime::SECRETS_GET_FUNCTION.to_string(),
+        ));
+        policy.host_imports.insert((
+            crate::runtime::DNS_IMPORT_MODULE.to_string(),
+            crate::runtime::DNS_RESOLVE_FUNCTION.to_string(),
+        ));
+        policy.host_imports.insert((
+            crate::runtime::HTTP_IMPORT_MODULE.to_string(),
+            crate::runtime::HTTP_FETCH_FUNCTION.to_string(),
+        ));
+        for function in [crate::runtime::TIMER_SET_FUNCTION, crate::runtime::TIMER_CANCEL_FUNCTION] {
+            policy.host_imports.insert((crate::runtime::TIMER_IMPORT_MODULE.to_string(), function.to_string()));
+        }
+        policy.host_imports.insert((
+            crate::runtime::CHECKPOINT_IMPORT_MODULE.to_string(),
+            crate::runtime::CHECKPOINT_FUNCTION.to_string(),
+        ));
+        policy.host_imports.insert((
+            crate::runtime::PROGRESS_IMPORT_MODULE.to_string(),
+            crate::runtime::PROGRESS_REPORT_FUNCTION.to_string(),
+        ));
+        policy.host_imports.insert((
+            crate::runtime::ALLOC_IMPORT_MODULE.to_string(),
+            crate::runtime::ALLOC_FAILED_FUNCTION.to_string(),
+        ));
+        for function in [
+            crate::runtime::LOG_WRITE_FUNCTION,
+            crate::runtime::LOG_CALL_ID_FUNCTION,
+            crate::runtime::LOG_DIAGNOSTIC_FUNCTION,
+        ] {
+            policy.host_imports.insert((crate::runtime::LOG_IMPORT_MODULE.to_string(), function.to_string()));
+        }
+        for function in [
+            crate::runtime::NN_LOAD_FUNCTION,
+            crate::runtime::NN_LOAD_BY_NAME_FUNCTION,
+            crate::runtime::NN_INIT_EXECUTION_CONTEXT_FUNCTION,
+            crate::runtime::NN_SET_INPUT_FUNCTION,
+            crate::runtime::NN_COMPUTE_FUNCTION,
+            crate::runtime::NN_GET_OUTPUT_FUNCTION,
+        ] {
+            policy.host_imports.insert((crate::runtime::NN_IMPORT_MODULE.to_string(), function.to_string()));
+        }
+        for function in [
+            crate::runtime::CHILD_SPAWN_FUNCTION,
+            crate::runtime::CHILD_CALL_FUNCTION,
+            crate::runtime::CHILD_KILL_FUNCTION,
+        ] {
+            policy.host_imports.insert((crate::runtime::CHILD_IMPORT_MODULE.to_string(), function.to_string()));
+        }
+        policy
+    }
+}
+
+impl ImportPolicy {
+    /// Allow an additional WASI namespace
+    pub fn allow_wasi_namespace(mut self, namespace: &str) -> Self {
+        self.wasi_namespaces.insert(namespace.to_string());
+        self
+    }
+    
+    /// Allow the standard host functions in [`crate::runtime::stdlib::STDLIB_IMPORT_MODULE`]
+    pub fn allow_host_stdlib(mut self) -> Self {
+        for function in crate::runtime::stdlib::STDLIB_FUNCTIONS {
+            self.register_host_import(crate::runtime::stdlib::STDLIB_IMPORT_MODULE, function);
+        }
+        self
+    }
+    
+    /// Register a host-provided import
+    pub fn register_host_import(&mut self, module: &str, name: &str) {
+        self.host_imports.insert((module.to_string(), name.to_string()));
+    }
+    
+    /// Allow a host-provided import
+    pub fn allow_host_import(mut self, module: &str, name: &str) -> Self {
+        self.register_host_import(module, name);
+        self
+    }
+    
+    /// Restrict imports further with operator rules
+    pub fn with_rules(mut self, rules: ImportRules) -> Self {
+        self.rules = rules;
+        self
+    }
+    
+    /// Check whether a single import is provided
+    ///
+    /// Imports of the host world under their WIT names are known when their core names are.
+    pub fn is_known(&self, import: &ModuleImport) -> bool {
+        let (module, name) = crate::runtime::wit::core_import(&import.module, &import.name)
+            .unwrap_or((&import.module, &import.name));
+        self.wasi_namespaces.contains(module)
+            || self.host_imports.contains(&(module.to_string(), name.to_string()))
+    }
+    
+    /// Check whether an import may be linked
+    ///
+    /// With `deny_unknown` off every import the host defines may be linked.
+    pub fn allows_link(&self, import: &ModuleImport) -> bool {
+        !self.deny_unknown || self.is_known(import)
+    }
+    
+    /// Validate imports and report the unknown ones
+    pub fn validate(&self, imports: &[ModuleImport]) -> ImportReport {
+        ImportReport {
+            unknown: imports.iter()
+                .filter(|import| !self.is_known(import))
+                .cloned()
+                .collect(),
+        }
+    }
+    
+    /// Check imports against the operator rules, failing with every rule they break
+    pub fn check_rules(&self, imports: &[ModuleImport]) -> Result<()> {
+        self.rules.check(imports)
+    }
+    
+    /// Validate imports, failing with a structured report if any are unknown
+    pub fn check(&self, imports: &[ModuleImport]) -> Result<()> {
+        if !self.deny_unknown {
+            return Ok(());
+        }
+        
+        let report = self.validate(imports);
+        if report.is_clean() {
+            Ok(())
+        } else {
+            Err(Error::UnknownImports { report })
+        }
+    }
+}
@@ -2,11 +2,21 @@
 
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
 pub mod audit;
+pub mod audit_store;
 pub mod capabilities;
 pub mod resource_limits;
 pub mod audit_impl;
+pub mod imports;
+pub mod redaction;
+pub mod secrets;
+pub mod dns;
+#[cfg(feature = "http-client")]
+pub mod http;
+pub mod extensions;
+pub mod hardening;
 
 /// Host specification for network access
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -52,6 +62,72 @@ impl PortRange {
     }
 }
 
+/// Names a guest may have the host resolve
+///
+/// Resolution happens on the host, through [`crate::runtime::DNS_IMPORT_MODULE`].
+/// Addresses are pinned until the call that resolved them returns, and only
+/// pinned addresses may be connected to, so a DNS server can't rebind an
+/// allowed name to another address between the lookup and the connection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsPolicy {
+    /// Domains that may be resolved; `*.example.com` matches any subdomain of `example.com`
+    pub allowed_domains: Vec<String>,
+    
+    /// Ports connections to resolved addresses may use (`None` allows any)
+    pub ports: Option<PortRange>,
+    
+    /// Most names resolved per minute (`None` is unlimited)
+    pub max_resolutions_per_minute: Option<u32>,
+}
+
+impl DnsPolicy {
+    /// Create a policy allowing no domains
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Allow resolving `domain`, which may start with `*.`
+    pub fn allow_domain(mut self, domain: impl Into<String>) -> Self {
+        self.allowed_domains.push(domain.into());
+        self
+    }
+    
+    /// Only allow connections on `ports`
+    pub fn ports(mut self, ports: PortRange) -> Self {
+        self.ports = Some(ports);
+        self
+    }
+    
+    /// Allow at most `resolutions` names to be resolved per minute
+    pub fn max_resolutions_per_minute(mut self, resolutions: u32) -> Self {
+        self.max_resolutions_per_minute = Some(resolutions);
+        self
+    }
+    
+    /// Check whether `name` may be resolved
+    ///
+    /// Names are compared case-insensitively and without a trailing dot. A
+    /// wildcard matches subdomains at any depth but not the domain itself.
+    pub fn allows_domain(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        if name.is_empty() {
+            return false;
+        }
+        self.allowed_domains.iter().any(|domain| {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            match domain.strip_prefix("*.") {
+                Some(parent) => name.strip_suffix(parent).is_some_and(|prefix| prefix.len() > 1 && prefix.ends_with('.')),
+                None => name == domain,
+            }
+        })
+    }
+    
+    /// Check whether connections to `port` are allowed
+    pub fn allows_port(&self, port: u16) -> bool {
+        self.ports.as_ref().map_or(true, |range| range.contains(port))
+    }
+}
+
 /// Network access capabilities
 #[derive(Debug, Clone, PartialEq)]
 pub enum NetworkCapability {
@@ -67,6 +143,9 @@ pub enum NetworkCapability {
     /// Specific ports only
     AllowedPorts(Vec<PortRange>),
     
+    /// Addresses of allowed domains, resolved by the host (see [`DnsPolicy`])
+    AllowedDomains(DnsPolicy),
+    
     /// Full network access
     Full,
 }
@@ -130,6 +209,35 @@ impl Default for EnvironmentCapability {
     }
 }
 
+/// Exports the host may call in an instance
+///
+/// Lets a deployment block exports such as debugging hooks without
+/// rebuilding the module. Calls to blocked exports fail before they reach the
+/// guest and are recorded in [`crate::WasmSandbox::function_call_audit_logger`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CallableFunctions {
+    /// Every export may be called
+    #[default]
+    All,
+    
+    /// Only the listed exports may be called
+    Allowlist(Vec<String>),
+    
+    /// Every export but the listed ones may be called
+    Denylist(Vec<String>),
+}
+
+impl CallableFunctions {
+    /// Whether the host may call `function_name`
+    pub fn allows(&self, function_name: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Allowlist(names) => names.iter().any(|name| name == function_name),
+            Self::Denylist(names) => !names.iter().any(|name| name == function_name),
+        }
+    }
+}
+
 /// Process creation capability
 #[derive(Debug, Clone, PartialEq)]
 pub enum ProcessCapability {
@@ -184,6 +292,126 @@ impl Default for RandomCapability {
     }
 }
 
+/// Secrets access capability
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum SecretsCapability {
+    /// No secrets may be requested
+    #[default]
+    None,
+    
+    /// Only the named secrets may be requested
+    Allowlist(Vec<String>),
+}
+
+impl SecretsCapability {
+    /// Check whether a named secret is granted
+    pub fn allows(&self, name: &str) -> bool {
+        match self {
+            Self::None => false,
+            Self::Allowlist(names) => names.iter().any(|granted| granted == name),
+        }
+    }
+}
+
+/// Default largest tensor a guest may exchange with a model, in bytes
+pub const DEFAULT_MAX_TENSOR_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Machine learning inference capability
+///
+/// Guests can only run host-registered models, through the WASI-NN imports
+/// (see [`crate::runtime::wasi_nn`]). Like secrets, this is always enforced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MlCapability {
+    /// Names of the host-registered models the guest may load
+    pub allowed_models: Vec<String>,
+    
+    /// Largest tensor the guest may pass to or read from a model, in bytes
+    pub max_tensor_bytes: u64,
+    
+    /// Total time the guest's inferences may take, or `None` for no limit
+    pub time_budget: Option<Duration>,
+}
+
+impl Default for MlCapability {
+    fn default() -> Self {
+        Self {
+            allowed_models: Vec::new(),
+            max_tensor_bytes: DEFAULT_MAX_TENSOR_BYTES,
+            time_budget: None,
+        }
+    }
+}
+
+impl MlCapability {
+    /// Allow the named models with default limits
+    pub fn models<I, S>(models: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            allowed_models: models.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+    
+    /// Check whether a model may be loaded
+    pub fn allows(&self, model: &str) -> bool {
+        self.allowed_models.iter().any(|allowed| allowed == model)
+    }
+}
+
+/// Default share of the parent's memory and fuel limits a child instance gets, in percent
+pub const DEFAULT_CHILD_BUDGET_PERCENT: u8 = 25;
+
+/// Capability to start child instances, a sandboxed alternative to spawning processes
+///
+/// Guests can only start modules the host registered for children, through
+/// [`crate::runtime::CHILD_IMPORT_MODULE`]. A child runs with its parent's
+/// capabilities, minus process creation and children of its own, and with
+/// `budget_percent` of the parent's memory and fuel limits. Children are
+/// removed with their parent. Like secrets, this is always enforced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChildCapability {
+    /// Names of the registered child modules the guest may start
+    pub allowed_modules: Vec<String>,
+    
+    /// Most children the guest may have running at once
+    pub max_children: usize,
+    
+    /// Share of the parent's memory and fuel limits each child gets, in percent
+    pub budget_percent: u8,
+}
+
+impl Default for ChildCapability {
+    fn default() -> Self {
+        Self {
+            allowed_modules: Vec::new(),
+            max_children: 4,
+            budget_percent: DEFAULT_CHILD_BUDGET_PERCENT,
+        }
+    }
+}
+
+impl ChildCapability {
+    /// Allow starting the named modules with default limits
+    pub fn modules<I, S>(modules: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            allowed_modules: modules.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+    
+    /// Check whether a module may be started
+    pub fn allows(&self, module: &str) -> bool {
+        self.allowed_modules.iter().any(|allowed| allowed == module)
+    }
+}
+
 /// Custom capability type
 #[derive(Debug, Clone, PartialEq)]
 pub enum CustomCapability {
@@ -207,6 +435,101 @@ pub enum CustomCapability {
     StringList(Vec<String>),
 }
 
+/// Enforcement mode for capability checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnforcementMode {
+    /// Violations are denied
+    Enforce,
+    
+    /// Violations are logged as audit events but allowed to proceed
+    ///
+    /// Only domains checked when the guest acts can audit. The
+    /// [`STATIC_DOMAINS`] are applied when the instance is created and are
+    /// always enforced.
+    Audit,
+}
+
+impl Default for EnforcementMode {
+    fn default() -> Self {
+        Self::Enforce
+    }
+}
+
+impl std::str::FromStr for EnforcementMode {
+    type Err = crate::error::Error;
+    
+    /// Parse `enforce` or `audit`, as manifests give them
+    fn from_str(mode: &str) -> crate::error::Result<Self> {
+        match mode.to_ascii_lowercase().as_str() {
+            "enforce" => Ok(Self::Enforce),
+            "audit" => Ok(Self::Audit),
+            _ => Err(crate::error::Error::InvalidInput {
+                field: "enforcement".to_string(),
+                reason: format!("unknown enforcement mode `{}`", mode),
+                suggestion: Some("Use `enforce` or `audit`".to_string()),
+            }),
+        }
+    }
+}
+
+/// Domains applied once when an instance is created (preopened directories,
+/// the environment, clocks and randomness), which have no per-call check that
+/// [`EnforcementMode::Audit`] could let through
+pub const STATIC_DOMAINS: &[&str] = &["filesystem", "environment", "process", "time", "random"];
+
+/// Per-domain enforcement configuration
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CapabilityEnforcement {
+    /// Mode used for domains without an explicit override
+    pub default: EnforcementMode,
+    
+    /// Per-domain overrides (e.g. "network", "filesystem")
+    pub domains: HashMap<String, EnforcementMode>,
+}
+
+impl CapabilityEnforcement {
+    /// Get the enforcement mode for a domain
+    ///
+    /// The [`STATIC_DOMAINS`] are always enforced.
+    pub fn mode_for(&self, domain: &str) -> EnforcementMode {
+        let domain = canonical_domain(domain);
+        if STATIC_DOMAINS.contains(&domain) {
+            return EnforcementMode::Enforce;
+        }
+        self.domains
+            .get(domain)
+            .copied()
+            .unwrap_or(self.default)
+    }
+    
+    /// Reject audit overrides for domains that can't honor them
+    pub fn validate(&self) -> crate::error::Result<()> {
+        let mut audited: Vec<&str> = self.domains.iter()
+            .filter(|(domain, mode)| **mode == EnforcementMode::Audit && STATIC_DOMAINS.contains(&domain.as_str()))
+            .map(|(domain, _)| domain.as_str())
+            .collect();
+        if audited.is_empty() {
+            return Ok(());
+        }
+        audited.sort_unstable();
+        Err(crate::error::Error::config_error(
+            format!("Audit mode is not supported for {}, which are applied when the instance is created", audited.join(", ")),
+            Some("Only audit domains checked per call, such as network, host and services".to_string()),
+        ))
+    }
+}
+
+/// Map domain aliases to their canonical names
+pub(crate) fn canonical_domain(domain: &str) -> &str {
+    match domain {
+        "fs" => "filesystem",
+        "env" => "environment",
+        "proc" => "process",
+        "rand" => "random",
+        other => other,
+    }
+}
+
 /// Security capabilities for the sandbox
 #[derive(Debug, Clone, PartialEq)]
 pub struct Capabilities {
@@ -228,8 +551,20 @@ pub struct Capabilities {
     /// Random number generation capability
     pub random: RandomCapability,
     
+    /// Named secrets the guest may request from the host
+    pub secrets: SecretsCapability,
+    
+    /// Host-registered models the guest may run inference with
+    pub ml: MlCapability,
+    
+    /// Child instances the guest may start
+    pub children: ChildCapability,
+    
     /// Custom capabilities map
     pub custom: HashMap<String, CustomCapability>,
+    
+    /// How capability violations are handled
+    pub enforcement: CapabilityEnforcement,
 }
 
 impl Capabilities {
@@ -242,7 +577,11 @@ impl Capabilities {
             process: ProcessCapability::None,
             time: TimeCapability::ReadOnly,
             random: RandomCapability::PseudoOnly,
+            secrets: SecretsCapability::None,
+            ml: MlCapability::default(),
+            children: ChildCapability::default(),
             custom: HashMap::new(),
+            enforcement: CapabilityEnforcement::default(),
         }
     }
     
@@ -265,7 +604,11 @@ impl Capabilities {
             process: ProcessCapability::None,
             time: TimeCapability::ReadOnly,
             random: RandomCapability::Full,
+            secrets: SecretsCapability::None,
+            ml: MlCapability::default(),
+            children: ChildCapability::default(),
             custom: HashMap::new(),
+            enforcement: CapabilityEnforcement::default(),
         }
     }
     
@@ -278,6 +621,154 @@ impl Capabilities {
     pub fn get_custom(&self, name: &str) -> Option<&CustomCapability> {
         self.custom.get(name)
     }
+    
+    /// Set the enforcement mode for all domains
+    ///
+    /// In [`EnforcementMode::Audit`] mode, violations are recorded as audit events
+    /// but allowed to proceed, which makes it possible to profile what a plugin
+    /// actually needs before locking down a production policy. The
+    /// [`STATIC_DOMAINS`] stay enforced.
+    pub fn enforcement(mut self, mode: EnforcementMode) -> Self {
+        self.enforcement.default = mode;
+        self
+    }
+    
+    /// Set the enforcement mode for a single domain
+    ///
+    /// Instances refuse to start with [`EnforcementMode::Audit`] set for one of
+    /// the [`STATIC_DOMAINS`].
+    pub fn domain_enforcement(mut self, domain: &str, mode: EnforcementMode) -> Self {
+        self.enforcement.domains.insert(canonical_domain(domain).to_string(), mode);
+        self
+    }
+    
+    /// Domains in which these capabilities allow more than `ceiling`
+    ///
+    /// Empty when the capabilities only narrow the ceiling. Lists are compared
+    /// entry by entry, so an allowlist is within a ceiling only if the ceiling
+    /// names every entry, and directories must lie inside a ceiling directory.
+    pub fn exceeds(&self, ceiling: &Capabilities) -> Vec<&'static str> {
+        let mut exceeded = Vec::new();
+        let mut check = |domain, within: bool| {
+            if !within {
+                exceeded.push(domain);
+            }
+        };
+        check("network", network_within(&self.network, &ceiling.network));
+        check("filesystem", filesystem_within(&self.filesystem, &ceiling.filesystem));
+        check("environment", environment_within(&self.environment, &ceiling.environment));
+        check("process", match (&self.process, &ceiling.process) {
+            (ProcessCapability::None, _) | (_, ProcessCapability::Full) => true,
+            (ProcessCapability::AllowedCommands(commands), ProcessCapability::AllowedCommands(allowed)) => {
+                all_in(commands, allowed)
+            }
+            _ => false,
+        });
+        check("time", self.time == TimeCapability::ReadOnly || ceiling.time == TimeCapability::Full);
+        check("random", random_rank(&self.random) <= random_rank(&ceiling.random));
+        check("secrets", match (&self.secrets, &ceiling.secrets) {
+            (SecretsCapability::None, _) => true,
+            (SecretsCapability::Allowlist(names), SecretsCapability::Allowlist(allowed)) => all_in(names, allowed),
+            _ => false,
+        });
+        check("ml", all_in(&self.ml.allowed_models, &ceiling.ml.allowed_models)
+            && self.ml.max_tensor_bytes <= ceiling.ml.max_tensor_bytes
+            && at_most(self.ml.time_budget, ceiling.ml.time_budget));
+        check("children", all_in(&self.children.allowed_modules, &ceiling.children.allowed_modules)
+            && self.children.max_children <= ceiling.children.max_children
+            && self.children.budget_percent <= ceiling.children.budget_percent);
+        check("custom", self.custom.iter().all(|(name, capability)| {
+            custom_within(capability, ceiling.custom.get(name))
+        }));
+        
+        let domains = ["network", "filesystem", "environment", "process", "time", "random"];
+        check("enforcement", domains.into_iter()
+            .chain(self.enforcement.domains.keys().map(String::as_str))
+            .all(|domain| {
+                self.enforcement.mode_for(domain) == EnforcementMode::Enforce
+                    || ceiling.enforcement.mode_for(domain) == EnforcementMode::Audit
+            }));
+        exceeded
+    }
+}
+
+fn all_in(items: &[String], allowed: &[String]) -> bool {
+    items.iter().all(|item| allowed.contains(item))
+}
+
+/// Whether an optional limit is at most another, `None` being unlimited
+fn at_most<T: PartialOrd>(limit: Option<T>, ceiling: Option<T>) -> bool {
+    match (limit, ceiling) {
+        (_, None) => true,
+        (Some(limit), Some(ceiling)) => limit <= ceiling,
+        (None, Some(_)) => false,
+    }
+}
+
+fn network_within(network: &NetworkCapability, ceiling: &NetworkCapability) -> bool {
+    match (network, ceiling) {
+        (NetworkCapability::None, _) | (_, NetworkCapability::Full) => true,
+        (NetworkCapability::Loopback, NetworkCapability::Loopback) => true,
+        (NetworkCapability::AllowedHosts(hosts), NetworkCapability::AllowedHosts(allowed)) => {
+            hosts.iter().all(|host| allowed.contains(host))
+        }
+        (NetworkCapability::AllowedPorts(ports), NetworkCapability::AllowedPorts(allowed)) => {
+            ports.iter().all(|range| allowed.iter().any(|allowed| allowed.start <= range.start && range.end <= allowed.end))
+        }
+        (NetworkCapability::AllowedDomains(policy), NetworkCapability::AllowedDomains(allowed)) => {
+            all_in(&policy.allowed_domains, &allowed.allowed_domains)
+                && match (&policy.ports, &allowed.ports) {
+                    (_, None) => true,
+                    (Some(ports), Some(allowed)) => allowed.start <= ports.start && ports.end <= allowed.end,
+                    (None, Some(_)) => false,
+                }
+                && at_most(policy.max_resolutions_per_minute, allowed.max_resolutions_per_minute)
+        }
+        _ => false,
+    }
+}
+
+fn filesystem_within(filesystem: &FilesystemCapability, ceiling: &FilesystemCapability) -> bool {
+    let inside = |dir: &PathBuf, dirs: &[PathBuf]| dirs.iter().any(|allowed| dir.starts_with(allowed));
+    filesystem.readable_dirs.iter().all(|dir| inside(dir, &ceiling.readable_dirs) || inside(dir, &ceiling.writable_dirs))
+        && filesystem.writable_dirs.iter().all(|dir| inside(dir, &ceiling.writable_dirs))
+        && at_most(filesystem.max_file_size, ceiling.max_file_size)
+        && (!filesystem.allow_create || ceiling.allow_create)
+        && (!filesystem.allow_delete || ceiling.allow_delete)
+}
+
+fn environment_within(environment: &EnvironmentCapability, ceiling: &EnvironmentCapability) -> bool {
+    match (environment, ceiling) {
+        (EnvironmentCapability::None, _) | (_, EnvironmentCapability::Full) => true,
+        (EnvironmentCapability::Allowlist(vars), EnvironmentCapability::Allowlist(allowed)) => all_in(vars, allowed),
+        (EnvironmentCapability::Allowlist(vars), EnvironmentCapability::Denylist(denied)) => {
+            !vars.iter().any(|var| denied.contains(var))
+        }
+        (EnvironmentCapability::Denylist(denied), EnvironmentCapability::Denylist(ceiling_denied)) => {
+            all_in(ceiling_denied, denied)
+        }
+        _ => false,
+    }
+}
+
+fn random_rank(random: &RandomCapability) -> u8 {
+    match random {
+        RandomCapability::None => 0,
+        RandomCapability::PseudoOnly => 1,
+        RandomCapability::Full => 2,
+    }
+}
+
+fn custom_within(capability: &CustomCapability, ceiling: Option<&CustomCapability>) -> bool {
+    match (capability, ceiling) {
+        (CustomCapability::Boolean(false), _) => true,
+        (CustomCapability::StringList(items), Some(CustomCapability::StringList(allowed))) => all_in(items, allowed),
+        (CustomCapability::Numeric { value, .. }, Some(CustomCapability::Numeric { value: limit, min, .. })) => {
+            min <= value && value <= limit
+        }
+        (capability, Some(ceiling)) => capability == ceiling,
+        (_, None) => false,
+    }
 }
 
 impl Default for Capabilities {
@@ -290,16 +781,24 @@ impl Default for Capabilities {
 #[derive(Debug, Clone)]
 pub struct MemoryLimits {
     /// Maximum memory pages (64KB each)
-    pub max_memory_pages: u32,
+    ///
+    /// Pages are counted in `u64` so limits for 64-bit memories can exceed 4GB.
+    pub max_memory_pages: u64,
     
     /// Reserved memory pages
-    pub reserved_memory_pages: u32,
+    pub reserved_memory_pages: u64,
     
     /// Growth rate limiting
     pub max_growth_rate: Option<u32>,
     
-    /// Maximum memory addresses
+    /// Maximum number of tables
     pub max_tables: u32,
+    
+    /// Maximum number of elements in any one table
+    pub max_table_elements: u64,
+    
+    /// Pages to grow memory to at instantiation, avoiding memory.grow stalls on the first call
+    pub pre_grow_pages: Option<u64>,
 }
 
 impl Default for MemoryLimits {
@@ -309,6 +808,8 @@ impl Default for MemoryLimits {
             reserved_memory_pages: 16, // 1MB (16 * 64KB)
             max_growth_rate: Some(10),
             max_tables: 1,
+            max_table_elements: 100_000,
+            pre_grow_pages: None,
         }
     }
 }
@@ -353,6 +854,9 @@ pub struct IoLimits {
     
     /// Maximum total write bytes
     pub max_total_write_bytes: Option<u64>,
+    
+    /// Maximum bytes held in the instance's scratch directory
+    pub max_scratch_bytes: Option<u64>,
 }
 
 impl Default for IoLimits {
@@ -363,6 +867,7 @@ impl Default for IoLimits {
             max_write_bytes_per_second: Some(1024 * 1024), // 1MB/s
             max_total_read_bytes: Some(10 * 1024 * 1024), // 10MB
             max_total_write_bytes: Some(5 * 1024 * 1024), // 5MB
+            max_scratch_bytes: Some(64 * 1024 * 1024), // 64MB
         }
     }
 }
@@ -375,6 +880,9 @@ pub struct TimeLimits {
     
     /// Maximum idle time in milliseconds
     pub max_idle_time_ms: Option<u64>,
+    
+    /// Maximum number of timers the guest may have outstanding
+    pub max_timers: usize,
 }
 
 impl Default for TimeLimits {
@@ -382,6 +890,7 @@ impl Default for TimeLimits {
         Self {
             max_total_time_ms: 30000, // 30 seconds
             max_idle_time_ms: Some(5000), // 5 seconds
+            max_timers: crate::runtime::timers::DEFAULT_MAX_TIMERS,
         }
     }
 }
@@ -403,6 +912,12 @@ pub struct ResourceLimits {
     
     /// Wasmtime fuel limits (instruction counting)
     pub fuel: Option<u64>,
+    
+    /// How fuel is charged for different classes of work
+    pub fuel_schedule: FuelSchedule,
+    
+    /// Maximum number of host objects the guest may hold handles to
+    pub max_handles: usize,
 }
 
 impl Default for ResourceLimits {
@@ -413,6 +928,84 @@ impl Default for ResourceLimits {
             io: IoLimits::default(),
             time: TimeLimits::default(),
             fuel: Some(10_000_000), // 10M instructions by default
+            fuel_schedule: FuelSchedule::default(),
+            max_handles: crate::runtime::handles::DEFAULT_MAX_HANDLES,
         }
     }
 }
+
+/// Fuel cost schedule
+///
+/// Wasmtime charges one unit of fuel per executed instruction. A schedule
+/// weights that baseline and adds charges for memory growth and guest calls,
+/// so that fuel better approximates the real cost of a workload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuelSchedule {
+    /// Fuel charged per unit of instruction fuel consumed by the runtime
+    pub instruction_weight: u64,
+    
+    /// Additional fuel charged per 64KB page of memory growth
+    pub memory_grow_per_page: u64,
+    
+    /// Additional fuel charged per guest function call made by the host
+    pub call_cost: u64,
+}
+
+impl FuelSchedule {
+    /// Every instruction costs one unit of fuel and nothing else is charged
+    pub fn uniform() -> Self {
+        Self {
+            instruction_weight: 1,
+            memory_grow_per_page: 0,
+            call_cost: 0,
+        }
+    }
+    
+    /// Preset for workloads dominated by calls and buffer growth
+    pub fn io_heavy() -> Self {
+        Self {
+            instruction_weight: 1,
+            memory_grow_per_page: 10_000,
+            call_cost: 5_000,
+        }
+    }
+    
+    /// Preset for workloads dominated by instruction execution
+    pub fn compute_heavy() -> Self {
+        Self {
+            instruction_weight: 2,
+            memory_grow_per_page: 1_000,
+            call_cost: 100,
+        }
+    }
+    
+    /// Get a preset by name ("uniform", "io-heavy" or "compute-heavy")
+    pub fn preset(name: &str) -> crate::error::Result<Self> {
+        match name {
+            "uniform" => Ok(Self::uniform()),
+            "io-heavy" => Ok(Self::io_heavy()),
+            "compute-heavy" => Ok(Self::compute_heavy()),
+            _ => Err(crate::error::SandboxError::config_error(
+                format!("Unknown fuel schedule: {}", name),
+                Some("Use one of: uniform, io-heavy, compute-heavy".to_string()),
+            )),
+        }
+    }
+    
+    /// Calculate the extra fuel to charge after a guest call
+    ///
+    /// `consumed` is the instruction fuel the runtime charged for the call and
+    /// `grown_pages` the number of memory pages the guest grew by.
+    pub fn extra_cost(&self, consumed: u64, grown_pages: u64) -> u64 {
+        consumed
+            .saturating_mul(self.instruction_weight.saturating_sub(1))
+            .saturating_add(grown_pages.saturating_mul(self.memory_grow_per_page))
+            .saturating_add(self.call_cost)
+    }
+}
+
+impl Default for FuelSchedule {
+    fn default() -> Self {
+        Self::uniform()
+    }
+}
@@ -0,0 +1,143 @@
+//! Redaction of guest trap and error messages before they leave the sandbox
+
+use regex::Regex;
+
+use crate::error::{Error, Result, SandboxError};
+
+/// Patterns used by [`RedactionPolicy::standard`]
+const STANDARD_PATTERNS: &[&str] = &[
+    // Unix absolute paths with at least two components
+    r"(?:/[A-Za-z0-9._~@+-]+){2,}/?",
+    // Windows drive paths
+    r"[A-Za-z]:\\(?:[^\\\s:]+\\?)+",
+    // Long hex strings such as addresses, keys, or digests embedded in the module
+    r"\b(?:0x)?[0-9a-fA-F]{16,}\b",
+];
+
+/// Rewrites error messages so they don't leak host paths or embedded data
+///
+/// Patterns are replaced wherever they match. If an allowlist is configured,
+/// only message lines matching an allowlisted pattern are kept at all; other
+/// lines are replaced entirely.
+#[derive(Debug, Clone)]
+pub struct RedactionPolicy {
+    /// Patterns whose matches are replaced
+    pub patterns: Vec<Regex>,
+    
+    /// Lines kept when non-empty; all other lines are replaced
+    pub allowlist: Vec<Regex>,
+    
+    /// Replacement text
+    pub replacement: String,
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        Self {
+            patterns: Vec::new(),
+            allowlist: Vec::new(),
+            replacement: "[redacted]".to_string(),
+        }
+    }
+}
+
+impl RedactionPolicy {
+    /// Create a policy that redacts nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Create a policy that redacts host paths and long hex strings
+    pub fn standard() -> Self {
+        Self {
+            patterns: STANDARD_PATTERNS.iter()
+                .map(|pattern| Regex::new(pattern).expect("standard redaction pattern is valid"))
+                .collect(),
+            ..Self::default()
+        }
+    }
+    
+    /// Redact matches of an additional pattern
+    pub fn pattern(mut self, pattern: &str) -> Result<Self> {
+        self.patterns.push(compile(pattern)?);
+        Ok(self)
+    }
+    
+    /// Keep message lines matching a pattern, replacing all others
+    pub fn allow(mut self, pattern: &str) -> Result<Self> {
+        self.allowlist.push(compile(pattern)?);
+        Ok(self)
+    }
+    
+    /// Set the replacement text
+    pub fn replacement(mut self, replacement: &str) -> Self {
+        self.replacement = replacement.to_string();
+        self
+    }
+    
+    /// Check whether the policy changes anything
+    pub fn is_active(&self) -> bool {
+        !self.patterns.is_empty() || !self.allowlist.is_empty()
+    }
+    
+    /// Redact a message
+    pub fn redact_str(&self, message: &str) -> String {
+        if !self.is_active() {
+            return message.to_string();
+        }
+        
+        message.lines()
+            .map(|line| {
+                if !self.allowlist.is_empty() && !self.allowlist.iter().any(|allowed| allowed.is_match(line)) {
+                    return self.replacement.clone();
+                }
+                self.patterns.iter().fold(line.to_string(), |line, pattern| {
+                    pattern.replace_all(&line, self.replacement.as_str()).into_owned()
+                })
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+    
+    /// Redact the guest-controlled messages in an error
+    pub fn redact_error(&self, error: &SandboxError) -> SandboxError {
+        let redact = |message: &String| self.redact_str(message);
+        match error {
+            SandboxError::FunctionCall { function_name, reason } => SandboxError::FunctionCall {
+                function_name: function_name.clone(),
+                reason: redact(reason),
+            },
+            SandboxError::InvalidResult { function_name, errors } => SandboxError::InvalidResult {
+                function_name: function_name.clone(),
+                errors: errors.iter().map(redact).collect(),
+            },
+            SandboxError::WasmRuntime { function, instance_id, message } => SandboxError::WasmRuntime {
+                function: function.clone(),
+                instance_id: *instance_id,
+                message: redact(message),
+            },
+            SandboxError::Instance { operation, instance_id, reason } => SandboxError::Instance {
+                operation: operation.clone(),
+                instance_id: *instance_id,
+                reason: redact(reason),
+            },
+            SandboxError::InstanceCreation { reason, instance_id } => SandboxError::InstanceCreation {
+                reason: redact(reason),
+                instance_id: *instance_id,
+            },
+            SandboxError::Generic { message } => SandboxError::Generic { message: redact(message) },
+            SandboxError::Compilation { message } => SandboxError::Compilation { message: redact(message) },
+            SandboxError::ModuleLoad { message } => SandboxError::ModuleLoad { message: redact(message) },
+            other => other.clone(),
+        }
+    }
+}
+
+/// Compile a redaction pattern
+fn compile(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| Error::InvalidInput {
+        field: "redaction pattern".to_string(),
+        reason: e.to_string(),
+        suggestion: Some("Patterns use Rust regex syntax".to_string()),
+    })
+}
@@ -13,7 +13,7 @@ use crate::security::{
 #[derive(Debug, Clone)]
 pub struct MemoryResourceTracker {
     /// Maximum memory pages
-    pub max_memory_pages: u32,
+    pub max_memory_pages: u64,
     
     /// Current memory pages
     current_pages: Arc<AtomicU64>,
@@ -57,18 +57,18 @@ impl MemoryResourceTracker {
         
         Self {
             max_memory_pages: limits.max_memory_pages,
-            current_pages: Arc::new(AtomicU64::new(limits.reserved_memory_pages as u64)),
-            peak_pages: Arc::new(AtomicU64::new(limits.reserved_memory_pages as u64)),
+            current_pages: Arc::new(AtomicU64::new(limits.reserved_memory_pages)),
+            peak_pages: Arc::new(AtomicU64::new(limits.reserved_memory_pages)),
             growth_tracker: Arc::new(Mutex::new(growth_tracker)),
         }
     }
     
     /// Check if memory allocation is allowed
-    pub fn check_allocation(&self, pages: u32) -> Result<()> {
+    pub fn check_allocation(&self, pages: u64) -> Result<()> {
         let current = self.current_pages.load(Ordering::Acquire);
-        let requested = current + pages as u64;
+        let requested = current + pages;
         
-        if requested > self.max_memory_pages as u64 {
+        if requested > self.max_memory_pages {
             return Err(Error::ResourceLimit {
                 message: format!("Memory allocation of {} pages would exceed limit of {} pages", 
                     pages, self.max_memory_pages)
@@ -85,7 +85,7 @@ impl MemoryResourceTracker {
             tracker.growth_events.retain(|(time, _)| *time >= cutoff);
             
             // Add current growth
-            tracker.growth_events.push((now, pages as u64));
+            tracker.growth_events.push((now, pages));
             
             // Calculate total growth in window
             let total_growth: u64 = tracker.growth_events.iter().map(|(_, size)| *size).sum();
@@ -105,8 +105,8 @@ impl MemoryResourceTracker {
     }
     
     /// Update memory usage
-    pub fn update(&self, pages: u32) {
-        let current = self.current_pages.fetch_add(pages as u64, Ordering::AcqRel) + pages as u64;
+    pub fn update(&self, pages: u64) {
+        let current = self.current_pages.fetch_add(pages, Ordering::AcqRel) + pages;
         let mut peak = self.peak_pages.load(Ordering::Acquire);
         
         while current > peak {
@@ -366,12 +366,12 @@ impl IoResourceTracker {
         let total = self.total_read.fetch_add(bytes, Ordering::AcqRel) + bytes;
         
         // Check total limit
-        if let Some(limit) = self.max_total_read_bytes {
-            if total > limit {
-                return Err(Error::ResourceLimit {
-                    message: format!("Total read limit of {} bytes exceeded", limit)
-                });
-            }
+        if let Some(limit) = self.max_total_read_bytes
+            && total > limit
+        {
+            return Err(Error::ResourceLimit {
+                message: format!("Total read limit of {} bytes exceeded", limit)
+            });
         }
         
         // Check rate limit
@@ -405,12 +405,12 @@ impl IoResourceTracker {
         let total = self.total_write.fetch_add(bytes, Ordering::AcqRel) + bytes;
         
         // Check total limit
-        if let Some(limit) = self.max_total_write_bytes {
-            if total > limit {
-                return Err(Error::ResourceLimit {
-                    message: format!("Total write limit of {} bytes exceeded", limit)
-                });
-            }
+        if let Some(limit) = self.max_total_write_bytes
+            && total > limit
+        {
+            return Err(Error::ResourceLimit {
+                message: format!("Total write limit of {} bytes exceeded", limit)
+            });
         }
         
         // Check rate limit
@@ -598,12 +598,12 @@ impl ResourceLimitManager {
         self.cpu.check_time_limit()?;
         
         // Check fuel limits
-        if let Some(fuel) = &self.fuel {
-            if fuel.load(Ordering::Acquire) == 0 {
-                return Err(Error::ResourceLimit {
-                    message: "Fuel limit exceeded".to_string()
-                });
-            }
+        if let Some(fuel) = &self.fuel
+            && fuel.load(Ordering::Acquire) == 0
+        {
+            return Err(Error::ResourceLimit {
+                message: "Fuel limit exceeded".to_string()
+            });
         }
         
         Ok(())
@@ -0,0 +1,306 @@
+//! Secrets provisioned to guests on request
+//!
+//! Secrets never appear in a guest's environment or filesystem. A guest asks
+//! for a secret by name through [`crate::runtime::SECRETS_IMPORT_MODULE`]; the
+//! host checks the name against the instance's [`SecretsCapability`], fetches
+//! the value from the configured [`SecretsProvider`], and copies it straight
+//! into guest memory. Every request is audited, and the host-side copy is
+//! zeroized as soon as it has been handed over.
+//!
+//! Secrets access is always enforced: [`crate::EnforcementMode::Audit`] does
+//! not release secrets an instance is not granted.
+
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use crate::error::{Error, Result, SecurityContext};
+use crate::runtime::SecretResolver;
+use crate::security::audit::{AuditEventType, AuditLogger};
+use crate::security::capabilities::ActiveCapabilities;
+use crate::security::{Capabilities, SecretsCapability};
+use crate::InstanceId;
+
+/// Capability name reported when a secret is not granted
+const SECRETS_CAPABILITY: &str = "secrets";
+
+/// Secret bytes that are zeroized when dropped
+pub struct SecretValue {
+    bytes: Vec<u8>,
+}
+
+impl SecretValue {
+    /// Wrap secret bytes
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        Self { bytes: bytes.into() }
+    }
+    
+    /// Borrow the secret bytes
+    pub fn expose(&self) -> &[u8] {
+        &self.bytes
+    }
+    
+    /// Length of the secret in bytes
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+    
+    /// Check whether the secret is empty
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl From<String> for SecretValue {
+    fn from(value: String) -> Self {
+        Self::new(value.into_bytes())
+    }
+}
+
+impl Drop for SecretValue {
+    fn drop(&mut self) {
+        zeroize(&mut self.bytes);
+    }
+}
+
+impl fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretValue([REDACTED; {} bytes])", self.bytes.len())
+    }
+}
+
+/// Overwrite a buffer with zeros in a way the optimizer cannot elide
+fn zeroize(bytes: &mut Vec<u8>) {
+    // Zero spare capacity too, in case the buffer was shrunk or reallocated in place
+    let capacity = bytes.capacity();
+    let ptr = bytes.as_mut_ptr();
+    for offset in 0..capacity {
+        // SAFETY: `offset` is within the allocation owned by `bytes`
+        unsafe { std::ptr::write_volatile(ptr.add(offset), 0) };
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    bytes.clear();
+}
+
+/// Source of secret values on the host
+pub trait SecretsProvider: Send + Sync {
+    /// Fetch a secret by name, or `None` if the provider has no such secret
+    fn get(&self, name: &str) -> Result<Option<SecretValue>>;
+}
+
+/// Reads secrets from host environment variables
+///
+/// A secret `db.password` is read from `<prefix>DB_PASSWORD`: names are
+/// upper-cased and `.` and `-` become `_`.
+#[derive(Debug, Clone, Default)]
+pub struct EnvSecretsProvider {
+    prefix: String,
+}
+
+impl EnvSecretsProvider {
+    /// Create a provider reading variables that start with `prefix`
+    pub fn new(prefix: &str) -> Self {
+        Self { prefix: prefix.to_string() }
+    }
+    
+    /// Environment variable holding a secret
+    pub fn variable_name(&self, name: &str) -> String {
+        let name: String = name.chars()
+            .map(|c| if c == '.' || c == '-' { '_' } else { c.to_ascii_uppercase() })
+            .collect();
+        format!("{}{}", self.prefix, name)
+    }
+}
+
+impl SecretsProvider for EnvSecretsProvider {
+    fn get(&self, name: &str) -> Result<Option<SecretValue>> {
+        Ok(std::env::var_os(self.variable_name(name))
+            .map(|value| SecretValue::new(value.into_encoded_bytes())))
+    }
+}
+
+/// Reads secrets from files in a directory, one file per secret
+///
+/// This matches how orchestrators such as Kubernetes and Docker mount secrets.
+/// Names that would escape the directory are rejected.
+#[derive(Debug, Clone)]
+pub struct FileSecretsProvider {
+    directory: PathBuf,
+}
+
+impl FileSecretsProvider {
+    /// Create a provider reading files in `directory`
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self { directory: directory.into() }
+    }
+}
+
+impl SecretsProvider for FileSecretsProvider {
+    fn get(&self, name: &str) -> Result<Option<SecretValue>> {
+        let mut components = Path::new(name).components();
+        if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+            return Err(Error::InvalidInput {
+                field: "secret name".to_string(),
+                reason: format!("'{}' is not a plain file name", name),
+                suggestion: Some("Use a name without path separators".to_string()),
+            });
+        }
+        
+        match std::fs::read(self.directory.join(name)) {
+            Ok(bytes) => Ok(Some(SecretValue::new(bytes))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::Io(e)),
+        }
+    }
+}
+
+/// Closure fetching a secret by name
+pub type SecretFetcher = Box<dyn Fn(&str) -> Result<Option<SecretValue>> + Send + Sync>;
+
+/// Adapter for external secret managers such as Vault or a cloud KMS
+///
+/// Wraps a closure that fetches a secret with the host's own client, so the
+/// sandbox does not depend on any particular secret manager SDK.
+pub struct CallbackSecretsProvider {
+    fetch: SecretFetcher,
+}
+
+impl CallbackSecretsProvider {
+    /// Create a provider calling `fetch` for each request
+    pub fn new<F>(fetch: F) -> Self
+    where
+        F: Fn(&str) -> Result<Option<SecretValue>> + Send + Sync + 'static,
+    {
+        Self { fetch: Box::new(fetch) }
+    }
+}
+
+impl SecretsProvider for CallbackSecretsProvider {
+    fn get(&self, name: &str) -> Result<Option<SecretValue>> {
+        (self.fetch)(name)
+    }
+}
+
+impl fmt::Debug for CallbackSecretsProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackSecretsProvider").finish_non_exhaustive()
+    }
+}
+
+/// Releases secrets to instances according to their capabilities
+///
+/// Every request is recorded in the audit log by name, whether or not it is
+/// allowed. Secret values are never logged.
+pub struct SecretStore {
+    provider: RwLock<Option<Arc<dyn SecretsProvider>>>,
+    audit: AuditLogger,
+}
+
+impl SecretStore {
+    /// Create a store with no provider
+    pub fn new() -> Self {
+        Self {
+            provider: RwLock::new(None),
+            audit: AuditLogger::new(1000),
+        }
+    }
+    
+    /// Record requests in an existing audit logger
+    pub fn with_audit_logger(mut self, logger: AuditLogger) -> Self {
+        self.audit = logger;
+        self
+    }
+    
+    /// Get the audit logger recording secret requests
+    pub fn audit_logger(&self) -> &AuditLogger {
+        &self.audit
+    }
+    
+    /// Set the provider secrets are fetched from
+    pub fn set_provider(&self, provider: Arc<dyn SecretsProvider>) {
+        *self.provider.write().unwrap() = Some(provider);
+    }
+    
+    /// Fetch a secret on behalf of an instance
+    pub fn fetch(&self, instance_id: InstanceId, capabilities: &Capabilities, name: &str) -> Result<SecretValue> {
+        if !capabilities.secrets.allows(name) {
+            self.record(instance_id, name, false);
+            return Err(Error::SecurityViolation {
+                violation: format!("Instance is not granted secret {}", name),
+                instance_id: Some(instance_id.0),
+                context: SecurityContext {
+                    attempted_operation: format!("read secret {}", name),
+                    required_capability: SECRETS_CAPABILITY.to_string(),
+                    available_capabilities: match &capabilities.secrets {
+                        SecretsCapability::None => Vec::new(),
+                        SecretsCapability::Allowlist(names) => names.clone(),
+                    },
+                },
+            });
+        }
+        self.record(instance_id, name, true);
+        
+        let Some(provider) = self.provider.read().unwrap().clone() else {
+            return Err(Error::UnsupportedOperation {
+                message: "No secrets provider is configured".to_string(),
+            });
+        };
+        let secret = provider.get(name)?.ok_or_else(|| Error::NotFound {
+            resource_type: "secret".to_string(),
+            identifier: name.to_string(),
+        })?;
+        Ok(secret)
+    }
+    
+    /// Record a request in the audit log
+    fn record(&self, instance_id: InstanceId, name: &str, allowed: bool) {
+        let event = AuditEventType::SecretAccess {
+            instance_id: instance_id.to_string(),
+            name: name.to_string(),
+            allowed,
+        };
+        if allowed {
+            self.audit.info(event, &format!("Granted request for secret {}", name));
+        } else {
+            self.audit.warning(event, &format!("Denied request for secret {}", name));
+        }
+    }
+}
+
+impl Default for SecretStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for SecretStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretStore")
+            .field("has_provider", &self.provider.read().unwrap().is_some())
+            .finish()
+    }
+}
+
+/// Resolves one instance's secret requests through a shared store
+pub struct InstanceSecrets {
+    store: Arc<SecretStore>,
+    instance_id: InstanceId,
+    capabilities: ActiveCapabilities,
+}
+
+impl InstanceSecrets {
+    /// Create a resolver for requests made by `instance_id`
+    pub fn new(store: Arc<SecretStore>, instance_id: InstanceId, capabilities: ActiveCapabilities) -> Self {
+        Self {
+            store,
+            instance_id,
+            capabilities,
+        }
+    }
+}
+
+impl SecretResolver for InstanceSecrets {
+    fn resolve(&self, name: &str) -> Result<SecretValue> {
+        self.store.fetch(self.instance_id, &self.capabilities.current(), name)
+    }
+}
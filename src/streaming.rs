@@ -1,7 +1,11 @@
 //! Streaming execution APIs for large datasets and batch operations
 
+use std::future::Future;
+use std::marker::PhantomData;
 use std::pin::Pin;
+use std::task::{Context, Poll};
 use futures::{Stream, StreamExt};
+use serde::de::DeserializeOwned;
 use serde_json::Value;
 use async_trait::async_trait;
 
@@ -218,6 +222,85 @@ impl StreamingExecution for StreamingExecutor {
     }
 }
 
+/// Partial results buffered before a streaming guest blocks in `emit`
+pub const RESULT_STREAM_BUFFER: usize = 16;
+
+/// Partial results pushed by a guest function, ending when the function returns
+///
+/// Each item is one `emit` call deserialized from JSON. If the function traps,
+/// the error is yielded as the last item. Dropping the stream signals the guest
+/// to stop at its next `emit`.
+pub struct ResultStream<R> {
+    receiver: tokio::sync::mpsc::Receiver<Vec<u8>>,
+    call: Option<tokio::task::JoinHandle<Result<()>>>,
+    function_name: String,
+    completed: bool,
+    _result: PhantomData<fn() -> R>,
+}
+
+impl<R> ResultStream<R> {
+    /// Create a stream over a receiver fed by the guest call `call`
+    pub fn new(
+        function_name: &str,
+        receiver: tokio::sync::mpsc::Receiver<Vec<u8>>,
+        call: tokio::task::JoinHandle<Result<()>>,
+    ) -> Self {
+        Self {
+            receiver,
+            call: Some(call),
+            function_name: function_name.to_string(),
+            completed: false,
+            _result: PhantomData,
+        }
+    }
+    
+    /// Whether the guest function returned successfully (the completion marker)
+    pub fn is_complete(&self) -> bool {
+        self.completed
+    }
+}
+
+impl<R: DeserializeOwned> Stream for ResultStream<R> {
+    type Item = Result<R>;
+    
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        
+        match this.receiver.poll_recv(cx) {
+            Poll::Ready(Some(partial)) => {
+                return Poll::Ready(Some(serde_json::from_slice(&partial).map_err(|e| SandboxError::FunctionCall {
+                    function_name: this.function_name.clone(),
+                    reason: format!("Failed to deserialize partial result: {}", e),
+                })));
+            }
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(None) => {}
+        }
+        
+        // All partial results are drained; report how the call ended
+        let Some(call) = this.call.as_mut() else {
+            return Poll::Ready(None);
+        };
+        let outcome = match Pin::new(call).poll(cx) {
+            Poll::Ready(outcome) => outcome,
+            Poll::Pending => return Poll::Pending,
+        };
+        this.call = None;
+        
+        match outcome {
+            Ok(Ok(())) => {
+                this.completed = true;
+                Poll::Ready(None)
+            }
+            Ok(Err(e)) => Poll::Ready(Some(Err(e))),
+            Err(e) => Poll::Ready(Some(Err(SandboxError::FunctionCall {
+                function_name: this.function_name.clone(),
+                reason: format!("Streaming call panicked or was cancelled: {}", e),
+            }))),
+        }
+    }
+}
+
 /// Builder for streaming configuration
 #[derive(Debug)]
 pub struct StreamingConfigBuilder {
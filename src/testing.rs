@@ -0,0 +1,575 @@
+//! Test harness for plugin authors
+//!
+//! [`MockHost`] runs a module against scripted host functions and records every
+//! host call the guest makes, so plugin logic can be unit tested without setting
+//! up a full sandbox configuration.
+//!
+//! ```rust,no_run
+//! use wasm_sandbox::runtime::HostValue;
+//! use wasm_sandbox::testing::MockHost;
+//!
+//! # fn main() -> wasm_sandbox::Result<()> {
+//! let wasm_bytes = std::fs::read("plugin.wasm")?;
+//! let plugin = MockHost::new()
+//!     .returns("host", "get_threshold", vec![HostValue::I32(10)])
+//!     .on("host", "log", |_args| Ok(vec![]))
+//!     .instantiate(&wasm_bytes)?;
+//!
+//! let result = plugin.call("check", &[HostValue::I32(12)])?;
+//! assert_eq!(result, vec![HostValue::I32(1)]);
+//! plugin.assert_called("host", "log");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`TempSandboxFs`] gives integration tests a throwaway directory tree built
+//! from an [`FsFixture`], mounts it into an instance, and checks what the
+//! guest did to it afterwards:
+//!
+//! ```rust,no_run
+//! use wasm_sandbox::testing::FsFixture;
+//! use wasm_sandbox::{InstanceConfig, WasmSandbox};
+//!
+//! # async fn check(wasm_bytes: &[u8]) -> wasm_sandbox::Result<()> {
+//! let fs = FsFixture::new()
+//!     .readable("input")
+//!     .file("input/data.csv", "a,b\n1,2\n")
+//!     .writable("output")
+//!     .build()?;
+//!
+//! let mut sandbox = WasmSandbox::new()?;
+//! let module_id = sandbox.load_module(wasm_bytes)?;
+//! let instance_id = sandbox.create_instance(module_id, Some(fs.mount(InstanceConfig::default())))?;
+//! let _: () = sandbox.call_function(instance_id, "convert", ()).await?;
+//!
+//! fs.assert_created("output/data.json");
+//! fs.assert_unchanged("input");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::runtime::environment::EnvironmentLayer;
+use crate::runtime::{HostFunctions, HostValue, RuntimeConfig, WasmInstance, WasmRuntime};
+use crate::runtime::wasmtime::WasmtimeRuntime;
+use crate::security::{Capabilities, ResourceLimits};
+use crate::InstanceConfig;
+
+/// Scripted implementation of a host function
+pub type MockHandler = Arc<dyn Fn(&[HostValue]) -> Result<Vec<HostValue>> + Send + Sync>;
+
+/// A host call made by the guest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostCall {
+    /// Import module
+    pub module: String,
+    
+    /// Import name
+    pub name: String,
+    
+    /// Arguments passed by the guest
+    pub args: Vec<HostValue>,
+    
+    /// Values returned to the guest, or `None` if the handler failed
+    pub results: Option<Vec<HostValue>>,
+}
+
+/// Builder for running a module against scripted host functions
+///
+/// Only the scripted imports are mocked; WASI and the runtime's own imports
+/// behave as they do in a sandbox with the same capabilities.
+pub struct MockHost {
+    handlers: HashMap<(String, String), MockHandler>,
+    capabilities: Capabilities,
+    resource_limits: ResourceLimits,
+    runtime_config: RuntimeConfig,
+}
+
+impl MockHost {
+    /// Create a mock host with minimal capabilities and default limits
+    pub fn new() -> Self {
+        Self {
+            handlers: HashMap::new(),
+            capabilities: Capabilities::minimal(),
+            resource_limits: ResourceLimits::default(),
+            runtime_config: RuntimeConfig::default(),
+        }
+    }
+    
+    /// Set the capabilities the module runs with
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+    
+    /// Set the resource limits the module runs with
+    pub fn resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.resource_limits = limits;
+        self
+    }
+    
+    /// Set the runtime configuration
+    pub fn runtime_config(mut self, config: RuntimeConfig) -> Self {
+        self.runtime_config = config;
+        self
+    }
+    
+    /// Handle `module.name` with a closure
+    pub fn on<F>(mut self, module: &str, name: &str, handler: F) -> Self
+    where
+        F: Fn(&[HostValue]) -> Result<Vec<HostValue>> + Send + Sync + 'static,
+    {
+        self.handlers.insert((module.to_string(), name.to_string()), Arc::new(handler));
+        self
+    }
+    
+    /// Return the same values from every call to `module.name`
+    pub fn returns(self, module: &str, name: &str, values: Vec<HostValue>) -> Self {
+        self.on(module, name, move |_| Ok(values.clone()))
+    }
+    
+    /// Return each response in turn from calls to `module.name`, repeating the last
+    pub fn returns_sequence(self, module: &str, name: &str, responses: Vec<Vec<HostValue>>) -> Self {
+        let next = Mutex::new(0usize);
+        self.on(module, name, move |_| {
+            let mut next = next.lock().unwrap();
+            let response = responses.get(*next).or(responses.last()).cloned().unwrap_or_default();
+            *next += 1;
+            Ok(response)
+        })
+    }
+    
+    /// Trap the guest whenever it calls `module.name`
+    pub fn fails(self, module: &str, name: &str, message: &str) -> Self {
+        let message = message.to_string();
+        self.on(module, name, move |_| Err(Error::Generic { message: message.clone() }))
+    }
+    
+    /// Compile and instantiate a module against the scripted host
+    pub fn instantiate(&self, wasm_bytes: &[u8]) -> Result<MockInstance> {
+        let runtime = WasmtimeRuntime::new(&self.runtime_config)?;
+        let module = runtime.load_module(wasm_bytes)?;
+        
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let host = Arc::new(ScriptedHost {
+            handlers: self.handlers.clone(),
+            calls: calls.clone(),
+        });
+        let instance = runtime.create_instance_with_host_functions(
+            module.as_ref(),
+            self.resource_limits.clone(),
+            self.capabilities.clone(),
+            host,
+        )?;
+        
+        Ok(MockInstance {
+            instance,
+            calls,
+            _runtime: runtime,
+        })
+    }
+}
+
+impl Default for MockHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Dispatches guest imports to scripted handlers and records each call
+struct ScriptedHost {
+    handlers: HashMap<(String, String), MockHandler>,
+    calls: Arc<Mutex<Vec<HostCall>>>,
+}
+
+impl HostFunctions for ScriptedHost {
+    fn provides(&self, module: &str, name: &str) -> bool {
+        self.handlers.contains_key(&(module.to_string(), name.to_string()))
+    }
+    
+    fn call(&self, module: &str, name: &str, args: &[HostValue]) -> Result<Vec<HostValue>> {
+        let handler = self.handlers.get(&(module.to_string(), name.to_string()))
+            .ok_or_else(|| Error::NotFound {
+                resource_type: "host function".to_string(),
+                identifier: format!("{}.{}", module, name),
+            })?;
+        let result = handler(args);
+        
+        self.calls.lock().unwrap().push(HostCall {
+            module: module.to_string(),
+            name: name.to_string(),
+            args: args.to_vec(),
+            results: result.as_ref().ok().cloned(),
+        });
+        result
+    }
+}
+
+/// A module instantiated by [`MockHost`]
+pub struct MockInstance {
+    instance: Box<dyn WasmInstance>,
+    calls: Arc<Mutex<Vec<HostCall>>>,
+    _runtime: WasmtimeRuntime,
+}
+
+impl MockInstance {
+    /// Call an export with numeric arguments
+    pub fn call(&self, function_name: &str, args: &[HostValue]) -> Result<Vec<HostValue>> {
+        self.instance.call_values(function_name, args)
+    }
+    
+    /// Call an export using the guest data ABI
+    pub fn call_raw(&self, function_name: &str, input: &[u8]) -> Result<Vec<u8>> {
+        self.instance.call_raw(function_name, input)
+    }
+    
+    /// The underlying instance
+    pub fn instance(&self) -> &dyn WasmInstance {
+        self.instance.as_ref()
+    }
+    
+    /// Host calls made so far, in order
+    pub fn calls(&self) -> Vec<HostCall> {
+        self.calls.lock().unwrap().clone()
+    }
+    
+    /// Host calls made to `module.name`
+    pub fn calls_to(&self, module: &str, name: &str) -> Vec<HostCall> {
+        self.calls.lock().unwrap().iter()
+            .filter(|call| call.module == module && call.name == name)
+            .cloned()
+            .collect()
+    }
+    
+    /// Number of calls made to `module.name`
+    pub fn call_count(&self, module: &str, name: &str) -> usize {
+        self.calls_to(module, name).len()
+    }
+    
+    /// Forget the recorded calls
+    pub fn clear_calls(&self) {
+        self.calls.lock().unwrap().clear();
+    }
+    
+    /// Panic unless the guest called `module.name`
+    #[track_caller]
+    pub fn assert_called(&self, module: &str, name: &str) {
+        if self.call_count(module, name) == 0 {
+            panic!("expected a call to {}.{}, recorded calls: {:?}", module, name, self.calls());
+        }
+    }
+    
+    /// Panic if the guest called `module.name`
+    #[track_caller]
+    pub fn assert_not_called(&self, module: &str, name: &str) {
+        let calls = self.calls_to(module, name);
+        if !calls.is_empty() {
+            panic!("expected no calls to {}.{}, recorded: {:?}", module, name, calls);
+        }
+    }
+}
+
+/// Declarative description of a directory tree for [`TempSandboxFs`]
+///
+/// Paths are relative to the tree's root. Each mount is a top-level directory
+/// the guest sees at the same name under `/`, e.g. `output` at `/output`.
+/// Fixtures also load from YAML or JSON:
+///
+/// ```yaml
+/// readable: [input]
+/// writable: [output]
+/// files:
+///   input/data.csv: "a,b\n1,2\n"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FsFixture {
+    /// Directories the guest may read
+    #[serde(default)]
+    pub readable: Vec<String>,
+    
+    /// Directories the guest may read and write
+    #[serde(default)]
+    pub writable: Vec<String>,
+    
+    /// File contents by path
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+}
+
+impl FsFixture {
+    /// Create an empty fixture
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Parse a fixture from YAML (or JSON, which is valid YAML)
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        serde_yaml::from_str(content).map_err(|e| Error::InvalidInput {
+            field: "filesystem fixture".to_string(),
+            reason: e.to_string(),
+            suggestion: None,
+        })
+    }
+    
+    /// Mount a directory the guest may read
+    pub fn readable(mut self, dir: &str) -> Self {
+        self.readable.push(dir.to_string());
+        self
+    }
+    
+    /// Mount a directory the guest may read and write
+    pub fn writable(mut self, dir: &str) -> Self {
+        self.writable.push(dir.to_string());
+        self
+    }
+    
+    /// Add a file
+    pub fn file(mut self, path: &str, contents: impl Into<String>) -> Self {
+        self.files.insert(path.to_string(), contents.into());
+        self
+    }
+    
+    /// Write the tree into a fresh temporary directory
+    pub fn build(&self) -> Result<TempSandboxFs> {
+        let root = crate::utils::temp_dir()?;
+        let mut mounts = Vec::new();
+        for (dir, writable) in self.readable.iter().map(|dir| (dir, false)).chain(self.writable.iter().map(|dir| (dir, true))) {
+            let relative = fixture_path(dir)?;
+            if relative.components().count() != 1 {
+                return Err(Error::InvalidInput {
+                    field: "filesystem fixture".to_string(),
+                    reason: format!("mount `{}` is not a top-level directory", dir),
+                    suggestion: Some("Mount directories directly under the fixture root, e.g. `output`".to_string()),
+                });
+            }
+            crate::utils::ensure_dir_exists(&root.path().join(&relative))?;
+            mounts.push((relative, writable));
+        }
+        for (path, contents) in &self.files {
+            let host_path = root.path().join(fixture_path(path)?);
+            if let Some(parent) = host_path.parent() {
+                crate::utils::ensure_dir_exists(parent)?;
+            }
+            fs::write(&host_path, contents).map_err(|e| Error::Filesystem {
+                operation: "build_filesystem_fixture".to_string(),
+                path: host_path.clone(),
+                reason: e.to_string(),
+            })?;
+        }
+        
+        let baseline = snapshot(root.path())?;
+        Ok(TempSandboxFs { root, mounts, baseline })
+    }
+}
+
+/// Check that a fixture path stays inside the root
+fn fixture_path(path: &str) -> Result<PathBuf> {
+    let relative = PathBuf::from(path);
+    if path.is_empty() || !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+        return Err(Error::InvalidInput {
+            field: "filesystem fixture".to_string(),
+            reason: format!("path `{}` must be relative and stay inside the fixture", path),
+            suggestion: None,
+        });
+    }
+    Ok(relative)
+}
+
+/// Contents of every file under `root`, by path relative to it
+fn snapshot(root: &Path) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+    let mut files = BTreeMap::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let read_error = |path: &Path, e: std::io::Error| Error::Filesystem {
+            operation: "snapshot_filesystem_fixture".to_string(),
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        };
+        for entry in fs::read_dir(&dir).map_err(|e| read_error(&dir, e))? {
+            let path = entry.map_err(|e| read_error(&dir, e))?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                let contents = fs::read(&path).map_err(|e| read_error(&path, e))?;
+                files.insert(path.strip_prefix(root).unwrap().to_path_buf(), contents);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Files that differ from the fixture, by path relative to its root
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FsChanges {
+    /// Files that did not exist in the fixture
+    pub created: Vec<PathBuf>,
+    
+    /// Files whose contents changed
+    pub modified: Vec<PathBuf>,
+    
+    /// Files that no longer exist
+    pub deleted: Vec<PathBuf>,
+}
+
+impl FsChanges {
+    /// Check whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.modified.is_empty() && self.deleted.is_empty()
+    }
+    
+    /// Changes at `path` or beneath it
+    pub fn under(&self, path: impl AsRef<Path>) -> FsChanges {
+        let path = path.as_ref();
+        let filter = |paths: &[PathBuf]| paths.iter().filter(|changed| changed.starts_with(path)).cloned().collect();
+        FsChanges {
+            created: filter(&self.created),
+            modified: filter(&self.modified),
+            deleted: filter(&self.deleted),
+        }
+    }
+}
+
+/// A throwaway directory tree mounted into instances, removed when dropped
+///
+/// Readable mounts are preopened read-only, so guest writes to them fail.
+#[derive(Debug)]
+pub struct TempSandboxFs {
+    root: tempfile::TempDir,
+    mounts: Vec<(PathBuf, bool)>,
+    baseline: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl TempSandboxFs {
+    /// Root of the tree on the host
+    pub fn root(&self) -> &Path {
+        self.root.path()
+    }
+    
+    /// Host path of a path in the tree
+    pub fn path(&self, relative: impl AsRef<Path>) -> PathBuf {
+        self.root.path().join(relative)
+    }
+    
+    /// Read a file in the tree as text
+    pub fn read(&self, relative: impl AsRef<Path>) -> Result<String> {
+        let path = self.path(relative);
+        fs::read_to_string(&path).map_err(|e| Error::Filesystem {
+            operation: "read_filesystem_fixture".to_string(),
+            path,
+            reason: e.to_string(),
+        })
+    }
+    
+    /// Grant the mounts in `capabilities`
+    pub fn capabilities(&self, mut capabilities: Capabilities) -> Capabilities {
+        let filesystem = &mut capabilities.filesystem;
+        for (dir, writable) in &self.mounts {
+            filesystem.readable_dirs.push(self.path(dir));
+            if *writable {
+                filesystem.writable_dirs.push(self.path(dir));
+                filesystem.allow_create = true;
+                filesystem.allow_delete = true;
+            }
+        }
+        capabilities
+    }
+    
+    /// Environment layer preopening each mount at its guest path
+    pub fn environment_layer(&self) -> EnvironmentLayer {
+        self.mounts.iter().fold(EnvironmentLayer::new(), |layer, (dir, writable)| {
+            let guest_path = format!("/{}", dir.display());
+            match writable {
+                true => layer.directory(self.path(dir), &guest_path),
+                false => layer.read_only_directory(self.path(dir), &guest_path),
+            }
+        })
+    }
+    
+    /// Mount the tree into an instance configuration
+    ///
+    /// Mounts are preopened after anything else in the configuration's
+    /// environment layer, readable mounts first, each in the order it was
+    /// added. Without other preopens the first mount is file descriptor 3.
+    pub fn mount(&self, mut config: InstanceConfig) -> InstanceConfig {
+        config.capabilities = self.capabilities(config.capabilities);
+        config.environment_layer = Some(match config.environment_layer.take() {
+            Some(layer) => layer.merge(self.environment_layer()),
+            None => self.environment_layer(),
+        });
+        config
+    }
+    
+    /// Files that differ from the fixture
+    pub fn changes(&self) -> Result<FsChanges> {
+        let current = snapshot(self.root.path())?;
+        let mut changes = FsChanges::default();
+        for (path, contents) in &current {
+            match self.baseline.get(path) {
+                None => changes.created.push(path.clone()),
+                Some(original) if original != contents => changes.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        changes.deleted = self.baseline.keys()
+            .filter(|path| !current.contains_key(*path))
+            .cloned()
+            .collect();
+        Ok(changes)
+    }
+    
+    /// Panic unless the guest created `relative`
+    #[track_caller]
+    pub fn assert_created(&self, relative: impl AsRef<Path>) {
+        self.assert_change(relative.as_ref(), "created", |changes| &changes.created);
+    }
+    
+    /// Panic unless the guest changed the contents of `relative`
+    #[track_caller]
+    pub fn assert_modified(&self, relative: impl AsRef<Path>) {
+        self.assert_change(relative.as_ref(), "modified", |changes| &changes.modified);
+    }
+    
+    /// Panic unless the guest deleted `relative`
+    #[track_caller]
+    pub fn assert_deleted(&self, relative: impl AsRef<Path>) {
+        self.assert_change(relative.as_ref(), "deleted", |changes| &changes.deleted);
+    }
+    
+    /// Panic if anything at `relative` or beneath it changed
+    #[track_caller]
+    pub fn assert_unchanged(&self, relative: impl AsRef<Path>) {
+        let changes = self.current_changes().under(relative.as_ref());
+        if !changes.is_empty() {
+            panic!("expected {} to be unchanged, found {:?}", relative.as_ref().display(), changes);
+        }
+    }
+    
+    /// Panic unless `relative` holds `expected`
+    #[track_caller]
+    pub fn assert_contents(&self, relative: impl AsRef<Path>, expected: &str) {
+        match self.read(relative.as_ref()) {
+            Ok(contents) => assert_eq!(contents, expected, "contents of {}", relative.as_ref().display()),
+            Err(e) => panic!("expected {} to hold {:?}: {}", relative.as_ref().display(), expected, e),
+        }
+    }
+    
+    #[track_caller]
+    fn assert_change(&self, relative: &Path, kind: &str, select: impl Fn(&FsChanges) -> &Vec<PathBuf>) {
+        let changes = self.current_changes();
+        if !select(&changes).iter().any(|path| path == relative) {
+            panic!("expected {} to be {}, found {:?}", relative.display(), kind, changes);
+        }
+    }
+    
+    #[track_caller]
+    fn current_changes(&self) -> FsChanges {
+        self.changes().unwrap_or_else(|e| panic!("failed to read the fixture: {}", e))
+    }
+}
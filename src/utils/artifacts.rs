@@ -0,0 +1,273 @@
+//! Collection of files written by guests into their writable directories
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::error::{Error, Result};
+
+/// State of a guest's writable directories, taken when the instance is created
+#[derive(Debug, Clone, Default)]
+pub struct WorkspaceSnapshot {
+    /// Writable directory roots
+    roots: Vec<PathBuf>,
+    
+    /// Files that existed at snapshot time with their modification time and size
+    files: HashMap<PathBuf, (Option<SystemTime>, u64)>,
+}
+
+impl WorkspaceSnapshot {
+    /// Snapshot the given writable directories
+    pub fn capture(roots: &[PathBuf]) -> Self {
+        let mut files = HashMap::new();
+        for root in roots {
+            for (path, metadata) in walk_files(root) {
+                files.insert(path, (metadata.modified().ok(), metadata.len()));
+            }
+        }
+        
+        Self {
+            roots: roots.to_vec(),
+            files,
+        }
+    }
+    
+    /// Writable directory roots covered by this snapshot
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+    
+    /// Find files created or modified since the snapshot
+    fn changed_files(&self) -> Vec<(PathBuf, PathBuf, fs::Metadata, bool)> {
+        let mut changed = Vec::new();
+        for root in &self.roots {
+            for (path, metadata) in walk_files(root) {
+                let created = match self.files.get(&path) {
+                    None => true,
+                    Some((modified, len)) => {
+                        if *modified == metadata.modified().ok() && *len == metadata.len() {
+                            continue;
+                        }
+                        false
+                    }
+                };
+                let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                changed.push((path, relative, metadata, created));
+            }
+        }
+        changed
+    }
+}
+
+/// Options for collecting guest outputs
+#[derive(Debug, Clone)]
+pub struct OutputCollection {
+    /// Glob patterns matched against paths relative to the writable dir (`*`, `?`, `**`)
+    pub patterns: Vec<String>,
+    
+    /// Maximum size of a single collected file
+    pub max_file_size: Option<u64>,
+    
+    /// Maximum total size of all collected files
+    pub max_total_size: Option<u64>,
+    
+    /// Read file contents into memory
+    pub read_contents: bool,
+    
+    /// Delete files the guest created once they are collected
+    pub clean: bool,
+}
+
+impl Default for OutputCollection {
+    fn default() -> Self {
+        Self {
+            patterns: vec!["**".to_string()],
+            max_file_size: None,
+            max_total_size: Some(100 * 1024 * 1024), // 100MB
+            read_contents: true,
+            clean: false,
+        }
+    }
+}
+
+impl OutputCollection {
+    /// Collect files matching the given patterns
+    pub fn new(patterns: &[&str]) -> Self {
+        Self {
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            ..Self::default()
+        }
+    }
+    
+    /// Set the maximum size of a single file
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+    
+    /// Set the maximum total size of collected files
+    pub fn max_total_size(mut self, bytes: u64) -> Self {
+        self.max_total_size = Some(bytes);
+        self
+    }
+    
+    /// Return paths only; open files with [`CollectedOutput::open`] to stream them
+    pub fn paths_only(mut self) -> Self {
+        self.read_contents = false;
+        self
+    }
+    
+    /// Delete guest-created files after collecting them
+    pub fn clean(mut self) -> Self {
+        self.clean = true;
+        self
+    }
+}
+
+/// A file written by the guest
+#[derive(Debug, Clone)]
+pub struct CollectedOutput {
+    /// Absolute path on the host
+    pub path: PathBuf,
+    
+    /// Path relative to the writable directory
+    pub relative_path: PathBuf,
+    
+    /// File size in bytes
+    pub size: u64,
+    
+    /// Whether the guest created the file (as opposed to modifying an existing one)
+    pub created: bool,
+    
+    /// File contents, if requested
+    pub contents: Option<Vec<u8>>,
+}
+
+impl CollectedOutput {
+    /// Open the file for streaming
+    pub fn open(&self) -> Result<fs::File> {
+        fs::File::open(&self.path).map_err(|e| Error::Filesystem {
+            operation: "open_output".to_string(),
+            path: self.path.clone(),
+            reason: e.to_string(),
+        })
+    }
+}
+
+/// Collect files created or modified since the snapshot
+pub fn collect_outputs(snapshot: &WorkspaceSnapshot, options: &OutputCollection) -> Result<Vec<CollectedOutput>> {
+    let mut outputs = Vec::new();
+    let mut total_size = 0u64;
+    
+    for (path, relative, metadata, created) in snapshot.changed_files() {
+        let relative_str = relative.to_string_lossy().replace('\\', "/");
+        if !options.patterns.iter().any(|pattern| glob_match(pattern, &relative_str)) {
+            continue;
+        }
+        
+        let size = metadata.len();
+        if let Some(max) = options.max_file_size
+            && size > max
+        {
+            return Err(Error::ResourceLimit {
+                message: format!("Output {} is {} bytes, exceeding the {} byte file limit", relative_str, size, max),
+            });
+        }
+        
+        total_size += size;
+        if let Some(max) = options.max_total_size
+            && total_size > max
+        {
+            return Err(Error::ResourceLimit {
+                message: format!("Outputs exceed the {} byte total limit", max),
+            });
+        }
+        
+        let contents = if options.read_contents {
+            Some(fs::read(&path).map_err(|e| Error::Filesystem {
+                operation: "read_output".to_string(),
+                path: path.clone(),
+                reason: e.to_string(),
+            })?)
+        } else {
+            None
+        };
+        
+        outputs.push(CollectedOutput {
+            path,
+            relative_path: relative,
+            size,
+            created,
+            contents,
+        });
+    }
+    
+    if options.clean {
+        for output in outputs.iter().filter(|output| output.created) {
+            fs::remove_file(&output.path).map_err(|e| Error::Filesystem {
+                operation: "clean_output".to_string(),
+                path: output.path.clone(),
+                reason: e.to_string(),
+            })?;
+        }
+    }
+    
+    outputs.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    Ok(outputs)
+}
+
+/// Recursively list regular files under `root` without following symlinks
+pub(crate) fn walk_files(root: &Path) -> Vec<(PathBuf, fs::Metadata)> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = fs::symlink_metadata(entry.path()) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else if metadata.is_file() {
+                files.push((entry.path(), metadata));
+            }
+        }
+    }
+    
+    files
+}
+
+/// Match a path against a glob pattern supporting `*`, `?`, and `**`
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    glob_match_from(&pattern, &path)
+}
+
+fn glob_match_from(pattern: &[char], path: &[char]) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            // `**/` may match zero directories
+            let rest = &pattern[2..];
+            if rest.first() == Some(&'/') && glob_match_from(&rest[1..], path) {
+                return true;
+            }
+            (0..=path.len()).any(|i| glob_match_from(rest, &path[i..]))
+        }
+        Some('*') => {
+            let rest = &pattern[1..];
+            (0..=path.len())
+                .take_while(|&i| i == 0 || path[i - 1] != '/')
+                .any(|i| glob_match_from(rest, &path[i..]))
+        }
+        Some('?') => {
+            matches!(path.first(), Some(c) if *c != '/') && glob_match_from(&pattern[1..], &path[1..])
+        }
+        Some(c) => path.first() == Some(c) && glob_match_from(&pattern[1..], &path[1..]),
+    }
+}
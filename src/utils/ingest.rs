@@ -0,0 +1,274 @@
+//! Staging input files for instances
+//!
+//! An instance configured with an [`InboxConfig`] gets a private inbox
+//! directory preopened at [`InboxConfig::guest_path`]. The host stages files
+//! into it with [`crate::WasmSandbox::stage_file`] or
+//! [`crate::WasmSandbox::stage_path`] instead of copying them into a readable
+//! directory by hand: each file is size-checked, passed through the sandbox's
+//! [`IngestHook`]s (virus scanning, format conversion, redaction) before the
+//! guest can see it, hashed, and recorded both in the inbox's list of
+//! [`StagedFile`]s and in the sandbox's ingestion audit log.
+//!
+//! The guest sees copies, so nothing it does to the inbox reaches the
+//! originals. The inbox is deleted with the instance.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result, SecurityContext};
+use crate::security::audit::{AuditEventType, AuditLogger};
+use crate::InstanceId;
+
+/// Guest path inboxes are preopened at by default
+pub const DEFAULT_INBOX_GUEST_PATH: &str = "/input";
+
+/// Where an instance's inbox appears and how much it may hold
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InboxConfig {
+    /// Absolute guest path the inbox is preopened at
+    pub guest_path: String,
+    
+    /// Largest file that may be staged, before and after hooks run
+    pub max_file_bytes: Option<u64>,
+    
+    /// Most bytes the inbox may hold in total
+    pub max_total_bytes: Option<u64>,
+}
+
+impl Default for InboxConfig {
+    fn default() -> Self {
+        Self {
+            guest_path: DEFAULT_INBOX_GUEST_PATH.to_string(),
+            max_file_bytes: None,
+            max_total_bytes: None,
+        }
+    }
+}
+
+impl InboxConfig {
+    /// Preopen the inbox at `guest_path` instead of `/input`
+    pub fn guest_path(mut self, guest_path: &str) -> Self {
+        self.guest_path = guest_path.to_string();
+        self
+    }
+    
+    /// Refuse files larger than `bytes`
+    pub fn max_file_bytes(mut self, bytes: u64) -> Self {
+        self.max_file_bytes = Some(bytes);
+        self
+    }
+    
+    /// Refuse files that would take the inbox over `bytes`
+    pub fn max_total_bytes(mut self, bytes: u64) -> Self {
+        self.max_total_bytes = Some(bytes);
+        self
+    }
+}
+
+/// What an [`IngestHook`] decided about a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IngestDecision {
+    /// Stage the file as it is
+    Accept,
+    
+    /// Stage these contents instead
+    Replace(Vec<u8>),
+    
+    /// Refuse the file, giving the reason
+    Reject(String),
+}
+
+/// Callback run on every file before it is staged, given its name and contents
+///
+/// Hooks run in the order they were registered, each seeing the contents the
+/// previous one accepted or produced.
+pub type IngestHook = Arc<dyn Fn(&str, &[u8]) -> IngestDecision + Send + Sync>;
+
+/// A file staged into an inbox
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StagedFile {
+    /// File name within the inbox
+    pub name: String,
+    
+    /// Path the guest opens it at
+    pub guest_path: String,
+    
+    /// Size of the staged contents
+    pub size: u64,
+    
+    /// SHA-256 of the staged contents, in hex
+    pub sha256: String,
+    
+    /// SHA-256 of the contents as submitted, in hex; differs from `sha256` if a hook replaced them
+    pub source_sha256: String,
+    
+    /// When the file was staged
+    pub staged_at: SystemTime,
+}
+
+impl StagedFile {
+    /// Whether a hook replaced the submitted contents
+    pub fn is_transformed(&self) -> bool {
+        self.sha256 != self.source_sha256
+    }
+}
+
+/// An instance's inbox on the host
+#[derive(Debug)]
+pub struct Inbox {
+    instance_id: InstanceId,
+    path: PathBuf,
+    config: InboxConfig,
+    files: Mutex<Vec<StagedFile>>,
+}
+
+impl Inbox {
+    /// Create an empty inbox for `instance_id`
+    pub(crate) fn create(instance_id: InstanceId, config: InboxConfig) -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("wasm-sandbox-inbox-{}", instance_id));
+        fs::create_dir_all(&path).map_err(|e| Error::Filesystem {
+            operation: "create_inbox".to_string(),
+            path: path.clone(),
+            reason: e.to_string(),
+        })?;
+        Ok(Self {
+            instance_id,
+            path,
+            config,
+            files: Mutex::new(Vec::new()),
+        })
+    }
+    
+    /// Directory on the host
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    /// Files currently staged, in the order they were staged
+    pub fn files(&self) -> Vec<StagedFile> {
+        self.files.lock().unwrap().clone()
+    }
+    
+    /// Bytes held by the staged files
+    pub fn usage(&self) -> u64 {
+        self.files.lock().unwrap().iter().map(|file| file.size).sum()
+    }
+    
+    /// Stage `contents` as `name`, replacing any file of that name
+    ///
+    /// Every decision, including refusals, is recorded in `audit`.
+    pub(crate) fn stage(&self, name: &str, contents: Vec<u8>, hooks: &[IngestHook], audit: &AuditLogger) -> Result<StagedFile> {
+        let source_sha256 = sha256_hex(&contents);
+        let staged = self.admit(name, contents, hooks).and_then(|contents| self.store(name, &contents, &source_sha256));
+        
+        let event = AuditEventType::FileStaged {
+            instance_id: self.instance_id.to_string(),
+            name: name.to_string(),
+            sha256: source_sha256.clone(),
+            accepted: staged.is_ok(),
+        };
+        match &staged {
+            Ok(file) => audit.info(event, &format!("Staged {} ({} bytes) for instance {}", name, file.size, self.instance_id)),
+            Err(e) => audit.warning(event, &format!("Refused to stage {} for instance {}: {}", name, self.instance_id, e)),
+        }
+        staged
+    }
+    
+    /// Remove a staged file, returning whether it was there
+    pub(crate) fn remove(&self, name: &str) -> bool {
+        let mut files = self.files.lock().unwrap();
+        let Some(index) = files.iter().position(|file| file.name == name) else {
+            return false;
+        };
+        files.remove(index);
+        let _ = fs::remove_file(self.path.join(name));
+        true
+    }
+    
+    /// Check a file's name and size and run the hooks over it
+    fn admit(&self, name: &str, mut contents: Vec<u8>, hooks: &[IngestHook]) -> Result<Vec<u8>> {
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+            return Err(Error::InvalidInput {
+                field: "name".to_string(),
+                reason: format!("{:?} is not a plain file name", name),
+                suggestion: Some("Stage files under a name without directory components".to_string()),
+            });
+        }
+        self.check_size(name, contents.len() as u64)?;
+        for hook in hooks {
+            match hook(name, &contents) {
+                IngestDecision::Accept => {}
+                IngestDecision::Replace(replacement) => contents = replacement,
+                IngestDecision::Reject(reason) => {
+                    return Err(Error::SecurityViolation {
+                        violation: format!("File {} was rejected: {}", name, reason),
+                        instance_id: Some(self.instance_id.as_uuid()),
+                        context: SecurityContext {
+                            attempted_operation: "stage file".to_string(),
+                            required_capability: "ingest hook approval".to_string(),
+                            available_capabilities: Vec::new(),
+                        },
+                    });
+                }
+            }
+        }
+        self.check_size(name, contents.len() as u64)?;
+        Ok(contents)
+    }
+    
+    /// Fail if a file of `size` bytes would exceed the inbox's limits
+    fn check_size(&self, name: &str, size: u64) -> Result<()> {
+        if let Some(max) = self.config.max_file_bytes.filter(|max| size > *max) {
+            return Err(Error::ResourceLimit {
+                message: format!("File {} is {} bytes, over the limit of {} bytes", name, size, max),
+            });
+        }
+        let others = self.files.lock().unwrap().iter()
+            .filter(|file| file.name != name)
+            .map(|file| file.size)
+            .sum::<u64>();
+        if let Some(max) = self.config.max_total_bytes.filter(|max| others + size > *max) {
+            return Err(Error::ResourceLimit {
+                message: format!("Staging {} would take the inbox to {} bytes, over its limit of {} bytes", name, others + size, max),
+            });
+        }
+        Ok(())
+    }
+    
+    /// Write a file into the inbox and record it
+    fn store(&self, name: &str, contents: &[u8], source_sha256: &str) -> Result<StagedFile> {
+        let path = self.path.join(name);
+        fs::write(&path, contents).map_err(|e| Error::Filesystem {
+            operation: "stage_file".to_string(),
+            path,
+            reason: e.to_string(),
+        })?;
+        let file = StagedFile {
+            name: name.to_string(),
+            guest_path: format!("{}/{}", self.config.guest_path.trim_end_matches('/'), name),
+            size: contents.len() as u64,
+            sha256: sha256_hex(contents),
+            source_sha256: source_sha256.to_string(),
+            staged_at: SystemTime::now(),
+        };
+        let mut files = self.files.lock().unwrap();
+        files.retain(|staged| staged.name != name);
+        files.push(file.clone());
+        Ok(file)
+    }
+}
+
+impl Drop for Inbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
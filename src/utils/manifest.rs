@@ -3,18 +3,32 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::fs;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 use crate::error::{Error, Result, SandboxError};
+use crate::security::imports::{ImportPattern, ImportPolicy, ImportRules};
 use crate::security::{
-    Capabilities, NetworkCapability, FilesystemCapability, 
-    EnvironmentCapability, ProcessCapability, PortRange, HostSpec
+    Capabilities, NetworkCapability, DnsPolicy, FilesystemCapability, 
+    EnvironmentCapability, ProcessCapability, PortRange, HostSpec, ResourceLimits,
+    CapabilityEnforcement, EnforcementMode, STATIC_DOMAINS, canonical_domain,
 };
-use crate::runtime::RuntimeConfig;
+use crate::runtime::{RuntimeConfig, DEFAULT_ASYNC_YIELD_FUEL};
+use crate::runtime::engine::EngineSelection;
+use crate::runtime::environment::EnvironmentLayer;
+use crate::runtime::result_cache::ResultCacheConfig;
+use crate::InstanceConfig;
+
+/// Current manifest schema version
+pub const MANIFEST_SCHEMA_VERSION: u32 = 2;
 
 /// Sandbox manifest format
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SandboxManifest {
+    /// Manifest schema version (manifests without a version header are version 1)
+    #[serde(default = "legacy_schema_version")]
+    pub schema_version: u32,
+    
     /// Name of the application
     pub name: String,
     
@@ -24,6 +38,10 @@ pub struct SandboxManifest {
     /// Description of the application
     pub description: Option<String>,
     
+    /// Manifests merged underneath this one, relative to this file
+    #[serde(default)]
+    pub include: Vec<String>,
+    
     /// Runtime configuration
     #[serde(default)]
     pub runtime: ManifestRuntime,
@@ -35,12 +53,20 @@ pub struct SandboxManifest {
     /// Resource limits
     #[serde(default)]
     pub resource_limits: ManifestResourceLimits,
+    
+    /// Fixture environment composed into instances
+    #[serde(default)]
+    pub environment_layer: ManifestEnvironmentLayer,
+    
+    /// Pure functions and the cache holding their results
+    #[serde(default)]
+    pub result_cache: ManifestResultCache,
 }
 
 /// Runtime configuration in manifest
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ManifestRuntime {
-    /// WebAssembly runtime engine
+    /// WebAssembly runtime engine: `auto`, or the engine to prefer
     pub engine: String,
     
     /// Whether to enable debugging
@@ -60,6 +86,10 @@ fn default_true() -> bool {
     true
 }
 
+fn legacy_schema_version() -> u32 {
+    1
+}
+
 fn default_threads() -> usize {
     num_cpus::get()
 }
@@ -89,6 +119,14 @@ pub struct ManifestNetworkCapabilities {
     /// Allowed ports
     #[serde(default)]
     pub allowed_ports: Vec<String>,
+    
+    /// Domains the host resolves for the guest, such as `*.example.com`
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    
+    /// Most domain names resolved per minute
+    #[serde(default)]
+    pub max_resolutions_per_minute: Option<u32>,
 }
 
 impl Default for ManifestNetworkCapabilities {
@@ -97,6 +135,8 @@ impl Default for ManifestNetworkCapabilities {
             mode: "none".to_string(),
             allowed_hosts: Vec::new(),
             allowed_ports: Vec::new(),
+            allowed_domains: Vec::new(),
+            max_resolutions_per_minute: None,
         }
     }
 }
@@ -178,6 +218,30 @@ impl Default for ManifestProcessCapabilities {
     }
 }
 
+/// Import rules in manifest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ManifestImportCapabilities {
+    /// Namespaces modules may import from
+    #[serde(default)]
+    pub allowed_namespaces: Vec<String>,
+    
+    /// Functions modules may import outside the allowed namespaces, as `module::name`
+    #[serde(default)]
+    pub allowed_functions: Vec<ImportPattern>,
+    
+    /// Imports refused even if allowed, such as `wasi_snapshot_preview1::proc_*`
+    #[serde(default)]
+    pub denied_functions: Vec<ImportPattern>,
+    
+    /// Most imports a module may have
+    #[serde(default)]
+    pub max_imports: Option<usize>,
+    
+    /// Most imports a module may have from each namespace
+    #[serde(default)]
+    pub max_namespace_imports: HashMap<String, usize>,
+}
+
 /// Capabilities in manifest
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ManifestCapabilities {
@@ -197,6 +261,10 @@ pub struct ManifestCapabilities {
     #[serde(default)]
     pub process: ManifestProcessCapabilities,
     
+    /// Import rules
+    #[serde(default)]
+    pub imports: ManifestImportCapabilities,
+    
     /// Time capabilities
     #[serde(default)]
     pub time_mode: String,
@@ -208,6 +276,10 @@ pub struct ManifestCapabilities {
     /// Custom capabilities
     #[serde(default)]
     pub custom: HashMap<String, String>,
+    
+    /// Enforcement mode (`enforce` or `audit`) by domain, with `default` for the rest
+    #[serde(default)]
+    pub enforcement: HashMap<String, String>,
 }
 
 impl Default for ManifestCapabilities {
@@ -217,13 +289,74 @@ impl Default for ManifestCapabilities {
             filesystem: ManifestFilesystemCapabilities::default(),
             environment: ManifestEnvironmentCapabilities::default(),
             process: ManifestProcessCapabilities::default(),
+            imports: ManifestImportCapabilities::default(),
             time_mode: "readonly".to_string(),
             random_mode: "pseudo".to_string(),
             custom: HashMap::new(),
+            enforcement: HashMap::new(),
         }
     }
 }
 
+/// Fixture file in manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestVirtualFile {
+    /// Absolute guest path
+    pub path: String,
+    
+    /// Inline file contents
+    #[serde(default)]
+    pub contents: Option<String>,
+    
+    /// Host file to copy contents from (relative to the working directory)
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+/// Stub socket in manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestStubSocket {
+    /// Endpoint address as `host:port`
+    pub address: String,
+    
+    /// Canned response
+    #[serde(default)]
+    pub response: String,
+}
+
+/// Environment layer in manifest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ManifestEnvironmentLayer {
+    /// Fixture files
+    #[serde(default)]
+    pub files: Vec<ManifestVirtualFile>,
+    
+    /// Environment variables
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    
+    /// Stub socket endpoints
+    #[serde(default)]
+    pub stub_sockets: Vec<ManifestStubSocket>,
+}
+
+/// Result cache in manifest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ManifestResultCache {
+    /// Exports whose results depend only on their parameters
+    #[serde(default)]
+    pub pure_functions: Vec<String>,
+    
+    /// Maximum number of cached results
+    pub max_entries: Option<usize>,
+    
+    /// Maximum combined size of cached results
+    pub max_size: Option<String>,
+    
+    /// How long a cached result stays valid
+    pub ttl: Option<String>,
+}
+
 /// Memory limits in manifest
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ManifestMemoryLimits {
@@ -277,6 +410,10 @@ pub struct ManifestIoLimits {
     
     /// Maximum open files
     pub max_open_files: Option<u32>,
+    
+    /// Maximum size of the scratch directory
+    #[serde(default)]
+    pub max_scratch_bytes: Option<String>,
 }
 
 impl Default for ManifestIoLimits {
@@ -285,6 +422,7 @@ impl Default for ManifestIoLimits {
             max_read_bytes: Some("10MB".to_string()),
             max_write_bytes: Some("5MB".to_string()),
             max_open_files: Some(10),
+            max_scratch_bytes: None,
         }
     }
 }
@@ -316,30 +454,119 @@ impl Default for ManifestResourceLimits {
 }
 
 impl SandboxManifest {
-    /// Load a manifest from a file
+    /// Load a manifest from a file, resolving includes
     pub fn from_path(path: &Path) -> Result<Self> {
-        let content = fs::read_to_string(path)
-            .map_err(|e| Error::Filesystem { 
-                operation: "read_manifest".to_string(), 
-                path: path.to_path_buf(),
-                reason: e.to_string() 
-            })?;
+        Ok(Self::resolve(path, false)?.manifest)
+    }
+    
+    /// Load a manifest from a file, resolving includes and rejecting unknown keys
+    pub fn from_path_strict(path: &Path) -> Result<Self> {
+        Ok(Self::resolve(path, true)?.manifest)
+    }
+    
+    /// Load a manifest from a file and report what was merged from its includes
+    pub fn resolve(path: &Path, strict: bool) -> Result<ResolvedManifest> {
+        let mut resolver = IncludeResolver { strict, stack: Vec::new(), sources: Vec::new() };
+        let value = resolver.load(path)?;
         
-        Self::from_str(&content)
+        Ok(ResolvedManifest {
+            manifest: Self::from_value(migrate(value.clone())?)?,
+            sources: resolver.sources,
+            value,
+        })
     }
     
-    /// Load a manifest from a string
+    /// Load a manifest from a string (includes are resolved relative to the working directory)
     pub fn from_str(content: &str) -> Result<Self> {
-        // Try to parse as TOML first
-        if let Ok(manifest) = toml::from_str::<SandboxManifest>(content) {
-            return Ok(manifest);
+        let mut resolver = IncludeResolver { strict: false, stack: Vec::new(), sources: Vec::new() };
+        let value = resolver.merge_includes(parse_value(content)?, Path::new("."))?;
+        Self::from_value(migrate(value)?)
+    }
+    
+    /// Load a manifest from a string, rejecting unknown or misspelled keys
+    pub fn from_str_strict(content: &str) -> Result<Self> {
+        check_issues(Self::validate_str(content)?)?;
+        
+        let mut resolver = IncludeResolver { strict: true, stack: Vec::new(), sources: Vec::new() };
+        let value = resolver.merge_includes(parse_value(content)?, Path::new("."))?;
+        Self::from_value(migrate(value)?)
+    }
+    
+    /// Check a manifest for unknown keys and unsupported schema versions
+    pub fn validate_str(content: &str) -> Result<Vec<ManifestIssue>> {
+        let value = parse_value(content)?;
+        let mut issues = Vec::new();
+        
+        let version = schema_version_of(&value);
+        if version > MANIFEST_SCHEMA_VERSION {
+            issues.push(ManifestIssue {
+                path: "schema_version".to_string(),
+                line: find_key_line(content, &["schema_version"]),
+                message: format!("schema version {} is newer than supported version {}", version, MANIFEST_SCHEMA_VERSION),
+                suggestion: Some("Upgrade wasm-sandbox to load this manifest".to_string()),
+            });
+        }
+        
+        if let Some(engine) = value.pointer("/runtime/engine").and_then(|engine| engine.as_str())
+            && let Err(e) = engine.parse::<EngineSelection>()
+        {
+            issues.push(ManifestIssue {
+                path: "runtime.engine".to_string(),
+                line: find_key_line(content, &["runtime", "engine"]),
+                message: e.to_string(),
+                suggestion: Some("Use `auto`, `wasmtime`, or `wasmer`".to_string()),
+            });
         }
         
-        // Try to parse as JSON
-        serde_json::from_str::<SandboxManifest>(content)
+        if let Some(modes) = value.pointer("/capabilities/enforcement").and_then(|modes| modes.as_object()) {
+            for (domain, mode) in modes {
+                let parsed = mode.as_str().map(|mode| mode.parse::<EnforcementMode>());
+                if !matches!(parsed, Some(Ok(_))) {
+                    issues.push(ManifestIssue {
+                        path: format!("capabilities.enforcement.{}", domain),
+                        line: find_key_line(content, &["capabilities", "enforcement", domain]),
+                        message: format!("invalid enforcement mode {} for {}", mode, domain),
+                        suggestion: Some("Use `enforce` or `audit`".to_string()),
+                    });
+                } else if matches!(parsed, Some(Ok(EnforcementMode::Audit))) && STATIC_DOMAINS.contains(&canonical_domain(domain)) {
+                    issues.push(ManifestIssue {
+                        path: format!("capabilities.enforcement.{}", domain),
+                        line: find_key_line(content, &["capabilities", "enforcement", domain]),
+                        message: format!("{} is applied when the instance is created and can't be audited", domain),
+                        suggestion: Some("Use `enforce`".to_string()),
+                    });
+                }
+            }
+        }
+        
+        let template = serde_json::to_value(Self::template())?;
+        collect_unknown_keys(content, &value, &template, &mut Vec::new(), &mut issues);
+        
+        Ok(issues)
+    }
+    
+    /// Manifest with every known key, used as the schema for strict validation
+    fn template() -> Self {
+        Self {
+            schema_version: MANIFEST_SCHEMA_VERSION,
+            name: String::new(),
+            version: String::new(),
+            description: None,
+            include: Vec::new(),
+            runtime: ManifestRuntime::default(),
+            capabilities: ManifestCapabilities::default(),
+            resource_limits: ManifestResourceLimits::default(),
+            environment_layer: ManifestEnvironmentLayer::default(),
+            result_cache: ManifestResultCache::default(),
+        }
+    }
+    
+    /// Deserialize a migrated manifest value
+    fn from_value(value: serde_json::Value) -> Result<Self> {
+        serde_json::from_value::<SandboxManifest>(value)
             .map_err(|e| SandboxError::Configuration {
                 message: format!("Failed to parse manifest: {}", e),
-                suggestion: Some("Check manifest syntax - supports both TOML and JSON".to_string()),
+                suggestion: Some("Check manifest syntax - supports TOML, JSON, and YAML".to_string()),
                 field: Some("manifest".to_string()),
             })
     }
@@ -354,7 +581,128 @@ impl SandboxManifest {
             compilation_threads: self.runtime.compilation_threads,
             cache_modules: self.runtime.cache_modules,
             cache_directory: None,
+            import_policy: ImportPolicy::default().with_rules(self.to_import_rules()),
+            pooling: None,
+            enable_memory64: true,
+            enable_gc: false,
+            compatibility: Default::default(),
+            compilation: Default::default(),
+            async_yield_fuel: Some(DEFAULT_ASYNC_YIELD_FUEL),
+            profiling: Default::default(),
+            engine: self.runtime.engine.parse().unwrap_or_else(|e| {
+                log::warn!("Ignoring manifest runtime engine: {}", e);
+                EngineSelection::Auto
+            }),
+        }
+    }
+    
+    /// Convert to import rules
+    pub fn to_import_rules(&self) -> ImportRules {
+        let imports = &self.capabilities.imports;
+        ImportRules {
+            allowed_namespaces: imports.allowed_namespaces.iter().cloned().collect(),
+            allowed_functions: imports.allowed_functions.clone(),
+            denied: imports.denied_functions.clone(),
+            max_imports: imports.max_imports,
+            max_namespace_imports: imports.max_namespace_imports.iter()
+                .map(|(namespace, limit)| (namespace.clone(), *limit))
+                .collect(),
+        }
+    }
+    
+    /// Convert to resource limits
+    pub fn to_resource_limits(&self) -> Result<ResourceLimits> {
+        let mut limits = ResourceLimits::default();
+        let page_size = 64 * 1024;
+        
+        let memory = &self.resource_limits.memory;
+        if let Some(max_memory) = &memory.max_memory {
+            limits.memory.max_memory_pages = parse_size(max_memory)? / page_size;
+        }
+        if let Some(reserved_memory) = &memory.reserved_memory {
+            limits.memory.reserved_memory_pages = parse_size(reserved_memory)? / page_size;
+        }
+        
+        let cpu = &self.resource_limits.cpu;
+        if let Some(max_execution_time) = &cpu.max_execution_time {
+            limits.cpu.max_execution_time_ms = parse_duration_ms(max_execution_time)?;
+        }
+        limits.cpu.cpu_usage_percentage = cpu.cpu_usage_percentage;
+        limits.cpu.max_threads = cpu.max_threads;
+        
+        let io = &self.resource_limits.io;
+        if let Some(max_read_bytes) = &io.max_read_bytes {
+            limits.io.max_total_read_bytes = Some(parse_size(max_read_bytes)?);
+        }
+        if let Some(max_write_bytes) = &io.max_write_bytes {
+            limits.io.max_total_write_bytes = Some(parse_size(max_write_bytes)?);
+        }
+        if let Some(max_open_files) = io.max_open_files {
+            limits.io.max_open_files = max_open_files;
+        }
+        if let Some(max_scratch_bytes) = &io.max_scratch_bytes {
+            limits.io.max_scratch_bytes = Some(parse_size(max_scratch_bytes)?);
+        }
+        
+        Ok(limits)
+    }
+    
+    /// Convert to instance configuration
+    pub fn to_instance_config(&self) -> Result<InstanceConfig> {
+        Ok(InstanceConfig {
+            resource_limits: self.to_resource_limits()?,
+            capabilities: self.to_capabilities()?,
+            enable_debug: self.runtime.debug,
+            environment_layer: Some(self.to_environment_layer()?).filter(|layer| !layer.is_empty()),
+            pure_functions: self.result_cache.pure_functions.iter().cloned().collect(),
+            ..InstanceConfig::default()
+        })
+    }
+    
+    /// Convert to result cache limits
+    pub fn to_result_cache_config(&self) -> Result<ResultCacheConfig> {
+        let mut config = ResultCacheConfig::default();
+        if let Some(max_entries) = self.result_cache.max_entries {
+            config.max_entries = max_entries;
+        }
+        if let Some(max_size) = &self.result_cache.max_size {
+            config.max_bytes = parse_size(max_size)? as usize;
+        }
+        if let Some(ttl) = &self.result_cache.ttl {
+            config.ttl = Some(Duration::from_millis(parse_duration_ms(ttl)?));
         }
+        Ok(config)
+    }
+    
+    /// Convert to an environment layer, reading `source` files from the host
+    pub fn to_environment_layer(&self) -> Result<EnvironmentLayer> {
+        let mut layer = EnvironmentLayer::new();
+        
+        for file in &self.environment_layer.files {
+            let contents = match (&file.contents, &file.source) {
+                (Some(contents), None) => contents.clone().into_bytes(),
+                (None, Some(source)) => fs::read(source).map_err(|e| Error::Filesystem {
+                    operation: "read_fixture".to_string(),
+                    path: PathBuf::from(source),
+                    reason: e.to_string(),
+                })?,
+                _ => return Err(SandboxError::config_error(
+                    format!("Fixture file {} must set exactly one of `contents` or `source`", file.path),
+                    Some("Inline small fixtures with `contents`; reference larger ones with `source`".to_string()),
+                )),
+            };
+            layer = layer.file(&file.path, contents);
+        }
+        
+        for (key, value) in &self.environment_layer.env {
+            layer = layer.env(key, value);
+        }
+        
+        for socket in &self.environment_layer.stub_sockets {
+            layer = layer.stub_socket(&socket.address, socket.response.as_bytes());
+        }
+        
+        Ok(layer)
     }
     
     /// Convert to capabilities
@@ -428,12 +776,31 @@ impl SandboxManifest {
                 
                 NetworkCapability::AllowedPorts(ports)
             },
+            "allowed_domains" => NetworkCapability::AllowedDomains(DnsPolicy {
+                allowed_domains: self.capabilities.network.allowed_domains.clone(),
+                ports: None,
+                max_resolutions_per_minute: self.capabilities.network.max_resolutions_per_minute,
+            }),
             "full" => NetworkCapability::Full,
             _ => {
                 return Err(SandboxError::config_error(format!("Invalid network mode: {}", self.capabilities.network.mode), None));
             }
         };
         
+        // Parse enforcement modes, `default` applying to domains not listed
+        let mut enforcement = CapabilityEnforcement::default();
+        for (domain, mode) in &self.capabilities.enforcement {
+            let mode = mode.parse::<EnforcementMode>()
+                .map_err(|_| SandboxError::config_error(format!("Invalid enforcement mode for {}: {}", domain, mode), None))?;
+            match domain.as_str() {
+                "default" => enforcement.default = mode,
+                domain => {
+                    enforcement.domains.insert(canonical_domain(domain).to_string(), mode);
+                }
+            }
+        }
+        enforcement.validate()?;
+        
         // Parse filesystem capabilities
         let filesystem = FilesystemCapability {
             readable_dirs: self.capabilities.filesystem.readable_dirs.iter()
@@ -492,7 +859,11 @@ impl SandboxManifest {
                 "full" => crate::security::RandomCapability::Full,
                 _ => crate::security::RandomCapability::PseudoOnly,
             },
+            secrets: crate::security::SecretsCapability::None,
+            ml: crate::security::MlCapability::default(),
+            children: crate::security::ChildCapability::default(),
             custom: HashMap::new(), // Custom capabilities are not supported in the manifest yet
+            enforcement,
         })
     }
 }
@@ -534,4 +905,342 @@ fn parse_size(size: &str) -> Result<u64> {
     Ok((num * multiplier as f64) as u64)
 }
 
+/// Parse a duration string (e.g. "10s", "500ms") into milliseconds
+fn parse_duration_ms(duration: &str) -> Result<u64> {
+    let duration = duration.trim();
+    let split = duration.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(duration.len());
+    let (num_str, suffix) = duration.split_at(split);
+    
+    let num: f64 = num_str.parse()
+        .map_err(|_| SandboxError::config_error(format!("Invalid duration: {}", duration), None))?;
+    
+    let multiplier = match suffix.trim().to_lowercase().as_str() {
+        "ms" => 1.0,
+        "" | "s" => 1000.0,
+        "m" | "min" => 60_000.0,
+        "h" => 3_600_000.0,
+        _ => return Err(SandboxError::config_error(format!("Invalid duration suffix: {}", suffix), None)),
+    };
+    
+    Ok((num * multiplier) as u64)
+}
+
+/// A problem found while validating a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestIssue {
+    /// Dotted path of the offending key
+    pub path: String,
+    
+    /// Line number (1-based) where the key appears, if it could be located
+    pub line: Option<usize>,
+    
+    /// Description of the problem
+    pub message: String,
+    
+    /// Suggested fix
+    pub suggestion: Option<String>,
+}
+
+impl std::fmt::Display for ManifestIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " ({})", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// A manifest with its includes merged in
+#[derive(Debug, Clone)]
+pub struct ResolvedManifest {
+    /// The merged manifest
+    pub manifest: SandboxManifest,
+    
+    /// Files that contributed, in merge order (later files override earlier ones)
+    pub sources: Vec<PathBuf>,
+    
+    /// Merged manifest before schema migration
+    pub value: serde_json::Value,
+}
+
+impl ResolvedManifest {
+    /// Dump the merged manifest as YAML, prefixed with the files it came from
+    pub fn dump(&self) -> Result<String> {
+        let mut output = String::new();
+        for source in &self.sources {
+            output.push_str(&format!("# source: {}\n", source.display()));
+        }
+        
+        let yaml = serde_yaml::to_string(&self.value)
+            .map_err(|e| SandboxError::Serialization {
+                format: "yaml".to_string(),
+                operation: "serialize".to_string(),
+                reason: e.to_string(),
+            })?;
+        output.push_str(&yaml);
+        
+        Ok(output)
+    }
+}
+
+/// Loads manifests and merges their includes, detecting cycles
+struct IncludeResolver {
+    /// Validate each file strictly
+    strict: bool,
+    
+    /// Files currently being loaded
+    stack: Vec<PathBuf>,
+    
+    /// Files loaded so far, in merge order
+    sources: Vec<PathBuf>,
+}
 
+impl IncludeResolver {
+    /// Load a manifest file and merge its includes underneath it
+    fn load(&mut self, path: &Path) -> Result<serde_json::Value> {
+        let path = path.canonicalize()
+            .map_err(|e| Error::Filesystem { 
+                operation: "read_manifest".to_string(), 
+                path: path.to_path_buf(),
+                reason: e.to_string() 
+            })?;
+        
+        if let Some(position) = self.stack.iter().position(|p| *p == path) {
+            let cycle: Vec<String> = self.stack[position..].iter()
+                .chain(std::iter::once(&path))
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(SandboxError::Configuration {
+                message: format!("Manifest include cycle: {}", cycle.join(" -> ")),
+                suggestion: Some("Remove one of the includes in the cycle".to_string()),
+                field: Some("include".to_string()),
+            });
+        }
+        
+        let content = fs::read_to_string(&path)
+            .map_err(|e| Error::Filesystem { 
+                operation: "read_manifest".to_string(), 
+                path: path.clone(),
+                reason: e.to_string() 
+            })?;
+        
+        if self.strict {
+            check_issues(SandboxManifest::validate_str(&content)?)?;
+        }
+        
+        let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.stack.push(path.clone());
+        let merged = self.merge_includes(parse_value(&content)?, &base_dir);
+        self.stack.pop();
+        
+        self.sources.push(path);
+        merged
+    }
+    
+    /// Merge the includes listed in `value` underneath it
+    fn merge_includes(&mut self, mut value: serde_json::Value, base_dir: &Path) -> Result<serde_json::Value> {
+        let includes: Vec<String> = match value.as_object_mut().and_then(|table| table.remove("include")) {
+            Some(serde_json::Value::String(include)) => vec![include],
+            Some(include) => serde_json::from_value(include)?,
+            None => Vec::new(),
+        };
+        
+        let mut merged = serde_json::Value::Object(serde_json::Map::new());
+        for include in includes {
+            let included = self.load(&base_dir.join(include))?;
+            merge_values(&mut merged, included);
+        }
+        merge_values(&mut merged, value);
+        
+        Ok(merged)
+    }
+}
+
+/// Deep-merge `overlay` into `base`; tables merge key by key, everything else is replaced
+fn merge_values(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Turn validation issues into an error
+fn check_issues(issues: Vec<ManifestIssue>) -> Result<()> {
+    match issues.first() {
+        Some(first) => {
+            let report: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+            Err(SandboxError::Configuration {
+                message: format!("Invalid manifest: {}", report.join("; ")),
+                suggestion: first.suggestion.clone(),
+                field: Some(first.path.clone()),
+            })
+        }
+        None => Ok(()),
+    }
+}
+
+/// Parse manifest content (TOML, JSON, or YAML) into a generic value
+fn parse_value(content: &str) -> Result<serde_json::Value> {
+    // Try to parse as TOML first
+    if let Ok(table) = toml::from_str::<toml::Table>(content) {
+        return Ok(serde_json::to_value(table)?);
+    }
+    
+    // Try to parse as JSON
+    let json_error = match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+    
+    // Try to parse as YAML (only mappings are manifests)
+    match serde_yaml::from_str::<serde_json::Value>(content) {
+        Ok(value) if value.is_object() => Ok(value),
+        _ => Err(SandboxError::Configuration {
+            message: format!("Failed to parse manifest: {}", json_error),
+            suggestion: Some("Check manifest syntax - supports TOML, JSON, and YAML".to_string()),
+            field: Some("manifest".to_string()),
+        }),
+    }
+}
+
+/// Read the schema version of a manifest value
+fn schema_version_of(value: &serde_json::Value) -> u32 {
+    value.get("schema_version")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32)
+        .unwrap_or_else(legacy_schema_version)
+}
+
+/// Migrate a manifest value to the current schema version
+fn migrate(mut value: serde_json::Value) -> Result<serde_json::Value> {
+    let version = schema_version_of(&value);
+    if version > MANIFEST_SCHEMA_VERSION {
+        return Err(SandboxError::Configuration {
+            message: format!("Manifest schema version {} is newer than supported version {}", version, MANIFEST_SCHEMA_VERSION),
+            suggestion: Some("Upgrade wasm-sandbox to load this manifest".to_string()),
+            field: Some("schema_version".to_string()),
+        });
+    }
+    
+    // Version 1 manifests predate the version header; their keys are unchanged in version 2
+    if let Some(table) = value.as_object_mut() {
+        table.insert("schema_version".to_string(), MANIFEST_SCHEMA_VERSION.into());
+    }
+    
+    Ok(value)
+}
+
+/// Collect keys in `value` that don't exist in `template`
+fn collect_unknown_keys(
+    content: &str,
+    value: &serde_json::Value,
+    template: &serde_json::Value,
+    path: &mut Vec<String>,
+    issues: &mut Vec<ManifestIssue>,
+) {
+    let (Some(table), Some(known)) = (value.as_object(), template.as_object()) else {
+        return;
+    };
+    
+    // Empty template tables (e.g. `custom`) accept arbitrary keys
+    if known.is_empty() {
+        return;
+    }
+    
+    for (key, child) in table {
+        path.push(key.clone());
+        match known.get(key) {
+            Some(known_child) => collect_unknown_keys(content, child, known_child, path, issues),
+            None => {
+                let segments: Vec<&str> = path.iter().map(String::as_str).collect();
+                issues.push(ManifestIssue {
+                    path: path.join("."),
+                    line: find_key_line(content, &segments),
+                    message: format!("unknown key `{}`", path.join(".")),
+                    suggestion: closest_key(key, known.keys())
+                        .map(|candidate| format!("did you mean `{}`?", candidate)),
+                });
+            }
+        }
+        path.pop();
+    }
+}
+
+/// Find the line where a dotted key path is defined
+fn find_key_line(content: &str, path: &[&str]) -> Option<usize> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut start = 0;
+    let mut found = None;
+    
+    for segment in path {
+        let index = (start..lines.len()).find(|&i| line_defines_key(lines[i], segment))?;
+        found = Some(index + 1);
+        start = index;
+    }
+    
+    found
+}
+
+/// Check whether a TOML or JSON line defines `key`
+fn line_defines_key(line: &str, key: &str) -> bool {
+    let line = line.trim();
+    
+    // JSON: "key": ...
+    if line.contains(&format!("\"{}\"", key)) && line.contains(':') {
+        return true;
+    }
+    
+    // TOML table header: [a.key] or [key]
+    if let Some(header) = line.strip_prefix('[').and_then(|l| l.split(']').next()) {
+        return header.trim_start_matches('[').split('.').any(|part| part.trim() == key);
+    }
+    
+    // YAML mapping entry: key: ... or - key: ...
+    if let Some((candidate, _)) = line.trim_start_matches("- ").split_once(':')
+        && candidate.trim().trim_matches('"') == key
+    {
+        return true;
+    }
+    
+    // TOML assignment: key = ... or key.sub = ...
+    line.split(['=', '.']).next().map(|k| k.trim().trim_matches('"')) == Some(key)
+}
+
+/// Find the known key closest to a misspelled one
+fn closest_key<'a>(key: &str, known: impl Iterator<Item = &'a String>) -> Option<&'a String> {
+    known
+        .map(|candidate| (edit_distance(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2.max(key.len() / 3))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            current.push((previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    
+    previous[b.len()]
+}
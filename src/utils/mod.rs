@@ -50,3 +50,9 @@ pub fn random_string(len: usize) -> String {
 
 pub mod manifest;
 pub mod logging;
+pub mod artifacts;
+pub mod version;
+pub mod module_diff;
+pub mod scratch;
+pub mod provenance;
+pub mod ingest;
@@ -0,0 +1,410 @@
+//! Comparing two builds of a module before upgrading to the new one
+//!
+//! [`ModuleDiff::compare`] lists what changed between the interface of an old
+//! and a new build of a core module or component: exports and imports that
+//! appeared, disappeared, or changed type, limits of exported and imported
+//! memories and tables, functions of component interfaces, and metadata
+//! custom sections. Each change says whether it can break a host or caller
+//! written against the old build, so hot reloads and plugin registry updates
+//! can be refused when they would.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use wasmtime::component::types::ComponentItem;
+use wasmtime::component::{Component, Type};
+use wasmtime::{Engine, ExternType, Module};
+
+use crate::error::{Error, Result};
+use crate::plugins::{BreakingChange, BreakingChangeType, CompatibilityReport};
+use crate::runtime::abi::custom_section_names;
+
+/// What changed about an item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChangeKind {
+    /// The new build exports an item the old one didn't
+    ExportAdded,
+    
+    /// The new build no longer exports an item
+    ExportRemoved,
+    
+    /// An export has a different type
+    ExportChanged,
+    
+    /// The new build needs an import the old one didn't
+    ImportAdded,
+    
+    /// The new build no longer needs an import
+    ImportRemoved,
+    
+    /// An import has a different type
+    ImportChanged,
+    
+    /// An exported or imported memory has different limits
+    MemoryLimitsChanged,
+    
+    /// An exported or imported table has different limits
+    TableLimitsChanged,
+    
+    /// The new build carries a custom section the old one didn't
+    MetadataAdded,
+    
+    /// The new build no longer carries a custom section
+    MetadataRemoved,
+}
+
+/// One difference between two builds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleChange {
+    /// What changed
+    pub kind: ChangeKind,
+    
+    /// The item, such as `export run`, `import env::log`, or `export wasi:cli/run#run`
+    pub item: String,
+    
+    /// The item's type in the old build
+    pub old: Option<String>,
+    
+    /// The item's type in the new build
+    pub new: Option<String>,
+    
+    /// Whether hosts or callers of the old build may break
+    pub breaking: bool,
+}
+
+impl fmt::Display for ModuleChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {}", self.kind, self.item)?;
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, ": {} -> {}", old, new),
+            (Some(old), None) => write!(f, ": was {}", old),
+            (None, Some(new)) => write!(f, ": {}", new),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Interface differences between an old and a new build
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleDiff {
+    /// Whether the builds are components rather than core modules
+    pub is_component: bool,
+    
+    /// Every difference, exports first, then imports, then metadata
+    pub changes: Vec<ModuleChange>,
+}
+
+impl ModuleDiff {
+    /// Compare two builds, given as binary or text
+    pub fn compare(old_bytes: &[u8], new_bytes: &[u8]) -> Result<Self> {
+        let engine = Engine::default();
+        let old = Surface::load(&engine, old_bytes, "old")?;
+        let new = Surface::load(&engine, new_bytes, "new")?;
+        if old.is_component != new.is_component {
+            return Err(Error::ModuleLoad {
+                message: "Cannot compare a core module with a component".to_string(),
+            });
+        }
+        
+        let mut changes = Vec::new();
+        diff_items(&old.exports, &new.exports, Direction::Export, &mut changes);
+        diff_items(&old.imports, &new.imports, Direction::Import, &mut changes);
+        for name in new.custom_sections.iter().filter(|name| !old.custom_sections.contains(name)) {
+            changes.push(ModuleChange {
+                kind: ChangeKind::MetadataAdded,
+                item: format!("custom section {}", name),
+                old: None,
+                new: None,
+                breaking: false,
+            });
+        }
+        for name in old.custom_sections.iter().filter(|name| !new.custom_sections.contains(name)) {
+            changes.push(ModuleChange {
+                kind: ChangeKind::MetadataRemoved,
+                item: format!("custom section {}", name),
+                old: None,
+                new: None,
+                breaking: false,
+            });
+        }
+        
+        Ok(Self {
+            is_component: new.is_component,
+            changes,
+        })
+    }
+    
+    /// Whether the builds have the same interface
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+    
+    /// Whether the new build can replace the old one without breaking anything
+    pub fn is_compatible(&self) -> bool {
+        !self.changes.iter().any(|change| change.breaking)
+    }
+    
+    /// Changes that may break hosts or callers of the old build
+    pub fn breaking_changes(&self) -> impl Iterator<Item = &ModuleChange> {
+        self.changes.iter().filter(|change| change.breaking)
+    }
+    
+    /// Summarize the diff for a hot reload or registry update decision
+    pub fn compatibility_report(&self) -> CompatibilityReport {
+        let breaking_changes = self.breaking_changes()
+            .map(|change| {
+                let (change_type, migration) = match change.kind {
+                    ChangeKind::ExportRemoved => (BreakingChangeType::EntryPointRemoved, "Stop calling the removed export or keep it in the new build"),
+                    ChangeKind::ExportChanged => (BreakingChangeType::FunctionSignature, "Update callers to the new signature"),
+                    ChangeKind::ImportAdded | ChangeKind::ImportChanged => (BreakingChangeType::DependencyChange, "Provide the import from the host before upgrading"),
+                    _ => (BreakingChangeType::DependencyChange, "Check the new limits against the host configuration"),
+                };
+                BreakingChange {
+                    change_type,
+                    description: change.to_string(),
+                    affected_items: vec![change.item.clone()],
+                    migration_steps: vec![migration.to_string()],
+                }
+            })
+            .collect::<Vec<_>>();
+        let warnings = self.changes.iter()
+            .filter(|change| !change.breaking)
+            .map(|change| change.to_string())
+            .collect();
+        let recommendations = if breaking_changes.is_empty() {
+            Vec::new()
+        } else {
+            vec!["Restart instances with the new build instead of hot reloading".to_string()]
+        };
+        
+        CompatibilityReport {
+            is_compatible: breaking_changes.is_empty(),
+            breaking_changes,
+            warnings,
+            recommendations,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Export,
+    Import,
+}
+
+/// Minimum and maximum size of a memory or table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Limits {
+    minimum: u64,
+    maximum: Option<u64>,
+}
+
+impl Limits {
+    /// Whether `new` accepts less than `self`: a larger minimum if `check_minimum`, or a smaller maximum
+    fn narrowed_by(&self, new: &Limits, check_minimum: bool) -> bool {
+        let maximum_shrank = match (self.maximum, new.maximum) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(old), Some(new)) => new < old,
+        };
+        maximum_shrank || (check_minimum && new.minimum > self.minimum)
+    }
+}
+
+impl fmt::Display for Limits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.maximum {
+            Some(maximum) => write!(f, "{}..{}", self.minimum, maximum),
+            None => write!(f, "{}..", self.minimum),
+        }
+    }
+}
+
+/// Type of an export or import, as far as compatibility goes
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ItemType {
+    /// Memory, with everything but its limits in `kind`
+    Memory { kind: String, limits: Limits },
+    
+    /// Table, with everything but its limits in `kind`
+    Table { kind: String, limits: Limits },
+    
+    /// Component function, compared by its interface types
+    ComponentFunc { params: Vec<(String, Type)>, results: Vec<Type> },
+    
+    /// Anything else, compared by its description
+    Other(String),
+}
+
+impl fmt::Display for ItemType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Memory { kind, limits } | Self::Table { kind, limits } => write!(f, "{} {}", kind, limits),
+            Self::ComponentFunc { params, results } => {
+                let params = params.iter()
+                    .map(|(name, ty)| format!("{}: {}", name, kind_name(ty)))
+                    .collect::<Vec<_>>();
+                let results = results.iter().map(kind_name).collect::<Vec<_>>();
+                write!(f, "func({}) -> ({})", params.join(", "), results.join(", "))
+            }
+            Self::Other(description) => f.write_str(description),
+        }
+    }
+}
+
+/// The parts of a build other code depends on
+struct Surface {
+    is_component: bool,
+    exports: BTreeMap<String, ItemType>,
+    imports: BTreeMap<String, ItemType>,
+    custom_sections: Vec<String>,
+}
+
+impl Surface {
+    fn load(engine: &Engine, bytes: &[u8], which: &str) -> Result<Self> {
+        let mut surface = Self {
+            is_component: false,
+            exports: BTreeMap::new(),
+            imports: BTreeMap::new(),
+            custom_sections: custom_section_names(bytes),
+        };
+        
+        let module_error = match Module::new(engine, bytes) {
+            Ok(module) => {
+                for export in module.exports() {
+                    surface.exports.insert(export.name().to_string(), core_item(&export.ty()));
+                }
+                for import in module.imports() {
+                    surface.imports.insert(format!("{}::{}", import.module(), import.name()), core_item(&import.ty()));
+                }
+                return Ok(surface);
+            }
+            Err(e) => e,
+        };
+        
+        let component = Component::new(engine, bytes).map_err(|_| Error::ModuleLoad {
+            message: format!("Failed to load the {} build: {}", which, module_error),
+        })?;
+        surface.is_component = true;
+        let ty = component.component_type();
+        for (name, item) in ty.exports(engine) {
+            component_items(engine, name.to_string(), item, &mut surface.exports);
+        }
+        for (name, item) in ty.imports(engine) {
+            component_items(engine, name.to_string(), item, &mut surface.imports);
+        }
+        Ok(surface)
+    }
+}
+
+fn core_item(ty: &ExternType) -> ItemType {
+    if let Some(func) = ty.func() {
+        let params = func.params().map(|ty| ty.to_string()).collect::<Vec<_>>();
+        let results = func.results().map(|ty| ty.to_string()).collect::<Vec<_>>();
+        ItemType::Other(format!("func({}) -> ({})", params.join(", "), results.join(", ")))
+    } else if let Some(global) = ty.global() {
+        let mutability = if global.mutability() == wasmtime::Mutability::Var { "mut " } else { "" };
+        ItemType::Other(format!("global {}{}", mutability, global.content()))
+    } else if let Some(memory) = ty.memory() {
+        let mut kind = if memory.is_64() { "memory64" } else { "memory" }.to_string();
+        if memory.is_shared() {
+            kind.push_str(" shared");
+        }
+        ItemType::Memory {
+            kind,
+            limits: Limits { minimum: memory.minimum(), maximum: memory.maximum() },
+        }
+    } else if let Some(table) = ty.table() {
+        ItemType::Table {
+            kind: format!("table {}", table.element()),
+            limits: Limits { minimum: table.minimum(), maximum: table.maximum() },
+        }
+    } else {
+        ItemType::Other(kind_name(ty))
+    }
+}
+
+/// Flatten a component item into `items`, descending into interfaces as `interface#item`
+fn component_items(engine: &Engine, path: String, item: ComponentItem, items: &mut BTreeMap<String, ItemType>) {
+    match item {
+        ComponentItem::ComponentInstance(instance) => {
+            for (name, item) in instance.exports(engine) {
+                component_items(engine, format!("{}#{}", path, name), item, items);
+            }
+        }
+        ComponentItem::ComponentFunc(func) => {
+            let params = func.params().map(|(name, ty)| (name.to_string(), ty)).collect();
+            let results = func.results().collect();
+            items.insert(path, ItemType::ComponentFunc { params, results });
+        }
+        ComponentItem::Type(ty) => {
+            items.insert(path, ItemType::Other(format!("type {}", kind_name(&ty))));
+        }
+        other => {
+            items.insert(path, ItemType::Other(kind_name(&other)));
+        }
+    }
+}
+
+/// Lowercase variant name of a type or item, such as `u32`, `record`, or `module`
+fn kind_name(value: &impl fmt::Debug) -> String {
+    let debug = format!("{:?}", value);
+    debug.split(['(', ' ', '{']).next().unwrap_or_default().to_lowercase()
+}
+
+fn diff_items(
+    old: &BTreeMap<String, ItemType>,
+    new: &BTreeMap<String, ItemType>,
+    direction: Direction,
+    changes: &mut Vec<ModuleChange>,
+) {
+    let (prefix, added, removed, changed) = match direction {
+        Direction::Export => ("export", ChangeKind::ExportAdded, ChangeKind::ExportRemoved, ChangeKind::ExportChanged),
+        Direction::Import => ("import", ChangeKind::ImportAdded, ChangeKind::ImportRemoved, ChangeKind::ImportChanged),
+    };
+    
+    for (name, old_ty) in old {
+        let item = format!("{} {}", prefix, name);
+        let Some(new_ty) = new.get(name) else {
+            changes.push(ModuleChange {
+                kind: removed,
+                item,
+                old: Some(old_ty.to_string()),
+                new: None,
+                // Callers of a removed export break; a dropped import only frees the host
+                breaking: direction == Direction::Export,
+            });
+            continue;
+        };
+        if old_ty == new_ty {
+            continue;
+        }
+        
+        let (kind, breaking) = match (old_ty, new_ty) {
+            (ItemType::Memory { kind: old_kind, limits: old_limits }, ItemType::Memory { kind: new_kind, limits: new_limits })
+                if old_kind == new_kind => (ChangeKind::MemoryLimitsChanged, old_limits.narrowed_by(new_limits, direction == Direction::Import)),
+            (ItemType::Table { kind: old_kind, limits: old_limits }, ItemType::Table { kind: new_kind, limits: new_limits })
+                if old_kind == new_kind => (ChangeKind::TableLimitsChanged, old_limits.narrowed_by(new_limits, direction == Direction::Import)),
+            _ => (changed, true),
+        };
+        changes.push(ModuleChange {
+            kind,
+            item,
+            old: Some(old_ty.to_string()),
+            new: Some(new_ty.to_string()),
+            breaking,
+        });
+    }
+    
+    for (name, new_ty) in new.iter().filter(|(name, _)| !old.contains_key(*name)) {
+        changes.push(ModuleChange {
+            kind: added,
+            item: format!("{} {}", prefix, name),
+            old: None,
+            new: Some(new_ty.to_string()),
+            // A new import has to be provided by hosts that only knew the old build
+            breaking: direction == Direction::Import,
+        });
+    }
+}
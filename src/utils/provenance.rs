@@ -0,0 +1,264 @@
+//! Build provenance embedded in modules
+//!
+//! A [`Provenance`] records where a module came from: the source repository
+//! and commit, the versions of the tools that built it, and the dependencies
+//! compiled into it. [`Provenance::embed`] stores it in the
+//! [`PROVENANCE_SECTION`] custom section, which the compiler does when
+//! [`CompilerOptions::provenance`](crate::compiler::CompilerOptions::provenance)
+//! is set, and loaded modules report it through
+//! [`WasmModule::metadata`](crate::runtime::WasmModule::metadata). A
+//! [`ProvenancePolicy`] in the sandbox configuration refuses to load modules
+//! that were not built from approved repositories.
+//!
+//! Provenance is a claim made by whoever built the module. It keeps honest
+//! builds honest; it does not prove anything about a module from an untrusted
+//! source unless the module is also signed.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result, SecurityContext};
+use crate::runtime::abi::custom_sections;
+
+/// Name of the custom section provenance is stored in
+pub const PROVENANCE_SECTION: &str = "wasm-sandbox.provenance";
+
+/// A dependency compiled into a module
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SbomComponent {
+    /// Package name
+    pub name: String,
+    
+    /// Package version
+    pub version: String,
+    
+    /// Where the package came from, e.g. a registry or git URL
+    #[serde(default)]
+    pub source: Option<String>,
+    
+    /// Checksum of the package, if the source provides one
+    #[serde(default)]
+    pub checksum: Option<String>,
+}
+
+impl SbomComponent {
+    /// Every package listed in a `Cargo.lock`
+    pub fn from_cargo_lock(contents: &str) -> Result<Vec<Self>> {
+        #[derive(Deserialize)]
+        struct Lockfile {
+            #[serde(default)]
+            package: Vec<SbomComponent>,
+        }
+        let lockfile: Lockfile = toml::from_str(contents).map_err(|e| Error::InvalidInput {
+            field: "Cargo.lock".to_string(),
+            reason: e.to_string(),
+            suggestion: None,
+        })?;
+        Ok(lockfile.package)
+    }
+}
+
+/// Where a module was built from and what went into it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Source repository URL
+    #[serde(default)]
+    pub source_repo: Option<String>,
+    
+    /// Commit the module was built from
+    #[serde(default)]
+    pub commit: Option<String>,
+    
+    /// Versions of the tools that built the module, by tool name
+    #[serde(default)]
+    pub build_tools: BTreeMap<String, String>,
+    
+    /// Dependencies compiled into the module
+    #[serde(default)]
+    pub dependencies: Vec<SbomComponent>,
+}
+
+impl Provenance {
+    /// Create empty provenance
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Set the source repository URL
+    pub fn source_repo(mut self, url: &str) -> Self {
+        self.source_repo = Some(url.to_string());
+        self
+    }
+    
+    /// Set the commit
+    pub fn commit(mut self, commit: &str) -> Self {
+        self.commit = Some(commit.to_string());
+        self
+    }
+    
+    /// Record a build tool's version
+    pub fn build_tool(mut self, tool: &str, version: &str) -> Self {
+        self.build_tools.insert(tool.to_string(), version.to_string());
+        self
+    }
+    
+    /// Add a dependency
+    pub fn dependency(mut self, component: SbomComponent) -> Self {
+        self.dependencies.push(component);
+        self
+    }
+    
+    /// Gather provenance for a Cargo project
+    ///
+    /// The repository and commit come from `git`, tool versions from `rustc`
+    /// and `cargo`, and dependencies from the project's `Cargo.lock`. Anything
+    /// that can't be determined is left out.
+    pub fn collect(project_path: &Path) -> Result<Self> {
+        let run = |program: &str, args: &[&str]| {
+            Command::new(program)
+                .args(args)
+                .current_dir(project_path)
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        
+        let mut provenance = Self {
+            source_repo: run("git", &["remote", "get-url", "origin"]),
+            commit: run("git", &["rev-parse", "HEAD"]),
+            ..Self::default()
+        };
+        for tool in ["rustc", "cargo"] {
+            if let Some(version) = run(tool, &["--version"]) {
+                provenance.build_tools.insert(tool.to_string(), version);
+            }
+        }
+        if let Ok(lockfile) = std::fs::read_to_string(project_path.join("Cargo.lock")) {
+            provenance.dependencies = SbomComponent::from_cargo_lock(&lockfile)?;
+        }
+        Ok(provenance)
+    }
+    
+    /// Store the provenance in a binary module, replacing any already there
+    pub fn embed(&self, wasm_bytes: &[u8]) -> Result<Vec<u8>> {
+        if !wasm_bytes.starts_with(b"\0asm") || wasm_bytes.len() < 8 {
+            return Err(Error::InvalidInput {
+                field: "wasm_bytes".to_string(),
+                reason: "provenance can only be embedded in binary modules".to_string(),
+                suggestion: Some("Convert text-format modules to binary first".to_string()),
+            });
+        }
+        
+        let mut embedded = Vec::with_capacity(wasm_bytes.len());
+        let mut copied = 0;
+        for section in custom_sections(wasm_bytes).into_iter().filter(|section| section.name == PROVENANCE_SECTION) {
+            embedded.extend_from_slice(&wasm_bytes[copied..section.range.start]);
+            copied = section.range.end;
+        }
+        embedded.extend_from_slice(&wasm_bytes[copied..]);
+        
+        let mut contents = Vec::new();
+        write_leb_u32(&mut contents, PROVENANCE_SECTION.len() as u32);
+        contents.extend_from_slice(PROVENANCE_SECTION.as_bytes());
+        contents.extend_from_slice(&serde_json::to_vec(self)?);
+        embedded.push(0);
+        write_leb_u32(&mut embedded, contents.len() as u32);
+        embedded.extend_from_slice(&contents);
+        Ok(embedded)
+    }
+    
+    /// Read the provenance embedded in a binary module, if any
+    pub fn read(wasm_bytes: &[u8]) -> Result<Option<Self>> {
+        let Some(section) = custom_sections(wasm_bytes).into_iter().rfind(|section| section.name == PROVENANCE_SECTION) else {
+            return Ok(None);
+        };
+        serde_json::from_slice(section.payload).map(Some).map_err(|e| Error::InvalidInput {
+            field: PROVENANCE_SECTION.to_string(),
+            reason: format!("malformed provenance: {}", e),
+            suggestion: None,
+        })
+    }
+}
+
+/// Which modules a sandbox will load, judged by their provenance
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProvenancePolicy {
+    /// Repositories modules must be built from; empty allows any repository
+    pub approved_repos: Vec<String>,
+    
+    /// Refuse modules whose provenance doesn't name a commit
+    pub require_commit: bool,
+}
+
+impl ProvenancePolicy {
+    /// Create a policy that only requires modules to carry provenance
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Approve a repository
+    ///
+    /// URLs are compared ignoring a trailing `/` or `.git`.
+    pub fn approve_repo(mut self, url: &str) -> Self {
+        self.approved_repos.push(url.to_string());
+        self
+    }
+    
+    /// Refuse modules whose provenance doesn't name a commit
+    pub fn require_commit(mut self) -> Self {
+        self.require_commit = true;
+        self
+    }
+    
+    /// Check a module's provenance against the policy
+    pub fn check(&self, provenance: Option<&Provenance>) -> Result<()> {
+        let repo = provenance.and_then(|provenance| provenance.source_repo.as_deref());
+        let refusal = match provenance {
+            None => Some("the module carries no provenance".to_string()),
+            Some(provenance) if self.require_commit && provenance.commit.is_none() => {
+                Some("the module's provenance names no commit".to_string())
+            }
+            Some(_) if !self.approved_repos.is_empty() => match repo {
+                None => Some("the module's provenance names no source repository".to_string()),
+                Some(repo) if !self.approved_repos.iter().any(|approved| same_repo(approved, repo)) => {
+                    Some(format!("the module was built from {}, which is not approved", repo))
+                }
+                Some(_) => None,
+            },
+            Some(_) => None,
+        };
+        match refusal {
+            None => Ok(()),
+            Some(violation) => Err(Error::SecurityViolation {
+                violation,
+                instance_id: None,
+                context: SecurityContext {
+                    attempted_operation: "load module".to_string(),
+                    required_capability: "approved provenance".to_string(),
+                    available_capabilities: self.approved_repos.clone(),
+                },
+            }),
+        }
+    }
+}
+
+fn same_repo(a: &str, b: &str) -> bool {
+    let normalize = |url: &str| url.trim_end_matches('/').trim_end_matches(".git").to_string();
+    normalize(a) == normalize(b)
+}
+
+fn write_leb_u32(bytes: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
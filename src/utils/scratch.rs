@@ -0,0 +1,145 @@
+//! Private scratch directories for instances
+//!
+//! An instance configured with a [`ScratchConfig`] gets an empty host
+//! directory of its own, preopened read-write at [`ScratchConfig::guest_path`].
+//! Its size is checked against [`crate::security::IoLimits::max_scratch_bytes`]
+//! after every call, and it is deleted with the instance unless it was kept,
+//! either explicitly or because a call failed with `keep_on_failure` set.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::utils::artifacts::walk_files;
+use crate::InstanceId;
+
+/// Guest path scratch directories are preopened at by default
+pub const DEFAULT_SCRATCH_GUEST_PATH: &str = "/scratch";
+
+/// Where an instance's scratch directory appears and what happens to it afterwards
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScratchConfig {
+    /// Absolute guest path the directory is preopened at
+    pub guest_path: String,
+    
+    /// Keep the directory after the instance is removed if any call failed
+    pub keep_on_failure: bool,
+}
+
+impl Default for ScratchConfig {
+    fn default() -> Self {
+        Self {
+            guest_path: DEFAULT_SCRATCH_GUEST_PATH.to_string(),
+            keep_on_failure: false,
+        }
+    }
+}
+
+impl ScratchConfig {
+    /// Preopen the directory at `guest_path` instead of `/scratch`
+    pub fn guest_path(mut self, guest_path: &str) -> Self {
+        self.guest_path = guest_path.to_string();
+        self
+    }
+    
+    /// Keep the directory for debugging when a call fails
+    pub fn keep_on_failure(mut self, keep: bool) -> Self {
+        self.keep_on_failure = keep;
+        self
+    }
+}
+
+/// An instance's scratch directory on the host
+#[derive(Debug)]
+pub struct ScratchSpace {
+    path: PathBuf,
+    quota: Option<u64>,
+    kept: AtomicBool,
+}
+
+impl ScratchSpace {
+    /// Create an empty directory for `instance_id` holding at most `quota` bytes
+    pub(crate) fn create(instance_id: InstanceId, quota: Option<u64>) -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("wasm-sandbox-scratch-{}", instance_id));
+        fs::create_dir_all(&path).map_err(|e| Error::Filesystem {
+            operation: "create_scratch_space".to_string(),
+            path: path.clone(),
+            reason: e.to_string(),
+        })?;
+        Ok(Self {
+            path,
+            quota,
+            kept: AtomicBool::new(false),
+        })
+    }
+    
+    /// Directory on the host
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    /// Most bytes the directory may hold
+    pub fn quota(&self) -> Option<u64> {
+        self.quota
+    }
+    
+    /// Bytes currently held by files in the directory
+    pub fn usage(&self) -> u64 {
+        walk_files(&self.path).iter().map(|(_, metadata)| metadata.len()).sum()
+    }
+    
+    /// Fail if the directory holds more than its quota
+    pub(crate) fn check_quota(&self) -> Result<()> {
+        let Some(quota) = self.quota else {
+            return Ok(());
+        };
+        let usage = self.usage();
+        if usage > quota {
+            return Err(Error::ResourceLimit {
+                message: format!("Scratch space holds {} bytes, over its quota of {} bytes", usage, quota),
+            });
+        }
+        Ok(())
+    }
+    
+    /// Leave the directory on disk when the instance is removed
+    pub fn keep(&self) {
+        self.kept.store(true, Ordering::SeqCst);
+    }
+    
+    /// Whether the directory will be left on disk
+    pub fn is_kept(&self) -> bool {
+        self.kept.load(Ordering::SeqCst)
+    }
+    
+    /// Copy the directory's files under `destination`, returning the bytes copied
+    pub fn export(&self, destination: &Path) -> Result<u64> {
+        let mut copied = 0;
+        for (source, _) in walk_files(&self.path) {
+            let relative = source.strip_prefix(&self.path).unwrap_or(&source);
+            let target = destination.join(relative);
+            if let Some(parent) = target.parent() {
+                crate::utils::ensure_dir_exists(parent)?;
+            }
+            copied += fs::copy(&source, &target).map_err(|e| Error::Filesystem {
+                operation: "export_scratch_space".to_string(),
+                path: target.clone(),
+                reason: e.to_string(),
+            })?;
+        }
+        Ok(copied)
+    }
+}
+
+impl Drop for ScratchSpace {
+    fn drop(&mut self) {
+        if self.is_kept() {
+            log::info!("Keeping scratch space {}", self.path.display());
+        } else {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
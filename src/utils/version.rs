@@ -0,0 +1,201 @@
+//! Semver-style versions and ranges for plugin API compatibility
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// A `major.minor.patch` version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ApiVersion {
+    /// Major version
+    pub major: u32,
+    
+    /// Minor version
+    pub minor: u32,
+    
+    /// Patch version
+    pub patch: u32,
+}
+
+impl ApiVersion {
+    /// Create a version
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self { major, minor, patch }
+    }
+    
+    /// Decode a version exported by a guest as `(major << 16) | (minor << 8) | patch`
+    pub fn from_packed(packed: u32) -> Self {
+        Self::new(packed >> 16, (packed >> 8) & 0xff, packed & 0xff)
+    }
+    
+    /// Encode the version as `(major << 16) | (minor << 8) | patch`
+    pub fn to_packed(&self) -> u32 {
+        (self.major << 16) | ((self.minor & 0xff) << 8) | (self.patch & 0xff)
+    }
+    
+    /// Check whether a caret requirement on `self` accepts `other`
+    ///
+    /// Versions are compatible when the leftmost non-zero component matches
+    /// and `other` is not older than `self`.
+    pub fn is_compatible_with(&self, other: &ApiVersion) -> bool {
+        VersionRange::caret(*self).contains(other)
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for ApiVersion {
+    type Err = Error;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        let (version, _) = parse_partial(s)?;
+        Ok(version)
+    }
+}
+
+/// Comparison operator in a version range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// A set of versions, written like Cargo requirements (`^1.2`, `~1.2.3`, `>=1.0, <2.0`, `*`)
+///
+/// A bare version (`1.2`) is a caret requirement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionRange {
+    /// Comparators that must all hold
+    comparators: Vec<(Op, ApiVersion)>,
+    
+    /// Original requirement text
+    text: String,
+}
+
+impl VersionRange {
+    /// Accept any version
+    pub fn any() -> Self {
+        Self { comparators: Vec::new(), text: "*".to_string() }
+    }
+    
+    /// Accept exactly `version`
+    pub fn exact(version: ApiVersion) -> Self {
+        Self { comparators: vec![(Op::Eq, version)], text: format!("={}", version) }
+    }
+    
+    /// Accept semver-compatible versions not older than `version`
+    pub fn caret(version: ApiVersion) -> Self {
+        let upper = if version.major > 0 {
+            ApiVersion::new(version.major + 1, 0, 0)
+        } else if version.minor > 0 {
+            ApiVersion::new(0, version.minor + 1, 0)
+        } else {
+            ApiVersion::new(0, 0, version.patch + 1)
+        };
+        Self {
+            comparators: vec![(Op::Ge, version), (Op::Lt, upper)],
+            text: format!("^{}", version),
+        }
+    }
+    
+    /// Check whether the range contains `version`
+    pub fn contains(&self, version: &ApiVersion) -> bool {
+        self.comparators.iter().all(|(op, bound)| match op {
+            Op::Eq => version == bound,
+            Op::Gt => version > bound,
+            Op::Ge => version >= bound,
+            Op::Lt => version < bound,
+            Op::Le => version <= bound,
+        })
+    }
+}
+
+impl fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl FromStr for VersionRange {
+    type Err = Error;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        let text = s.trim();
+        let mut comparators = Vec::new();
+        
+        for part in text.split(',').map(str::trim) {
+            if part == "*" || part.is_empty() {
+                continue;
+            }
+            
+            let (op, rest) = ["<=", ">=", "<", ">", "=", "^", "~"].iter()
+                .find_map(|op| part.strip_prefix(op).map(|rest| (*op, rest.trim())))
+                .unwrap_or(("^", part));
+            let (version, components) = parse_partial(rest)?;
+            
+            match op {
+                "<=" => comparators.push((Op::Le, version)),
+                ">=" => comparators.push((Op::Ge, version)),
+                "<" => comparators.push((Op::Lt, version)),
+                ">" => comparators.push((Op::Gt, version)),
+                "=" => comparators.push((Op::Eq, version)),
+                "~" => {
+                    // ~1 allows any 1.x; ~1.2 and ~1.2.3 allow patch updates only
+                    let upper = if components == 1 {
+                        ApiVersion::new(version.major + 1, 0, 0)
+                    } else {
+                        ApiVersion::new(version.major, version.minor + 1, 0)
+                    };
+                    comparators.push((Op::Ge, version));
+                    comparators.push((Op::Lt, upper));
+                }
+                _ => {
+                    // Partial caret versions (^1, ^0.2) are bounded at the last given component
+                    let upper = match components {
+                        1 => ApiVersion::new(version.major + 1, 0, 0),
+                        2 if version.major == 0 => ApiVersion::new(0, version.minor + 1, 0),
+                        _ => {
+                            comparators.extend(VersionRange::caret(version).comparators);
+                            continue;
+                        }
+                    };
+                    comparators.push((Op::Ge, version));
+                    comparators.push((Op::Lt, upper));
+                }
+            }
+        }
+        
+        Ok(Self { comparators, text: text.to_string() })
+    }
+}
+
+/// Parse `major[.minor[.patch]]`, returning the version and how many components were given
+fn parse_partial(s: &str) -> Result<(ApiVersion, usize)> {
+    let invalid = || Error::InvalidInput {
+        field: "version".to_string(),
+        reason: format!("invalid version `{}`", s),
+        suggestion: Some("Use major[.minor[.patch]], e.g. 1.2.0".to_string()),
+    };
+    
+    let parts: Vec<&str> = s.trim().split('.').collect();
+    if parts.is_empty() || parts.len() > 3 {
+        return Err(invalid());
+    }
+    
+    let mut numbers = [0u32; 3];
+    for (slot, part) in numbers.iter_mut().zip(&parts) {
+        *slot = part.parse().map_err(|_| invalid())?;
+    }
+    
+    Ok((ApiVersion::new(numbers[0], numbers[1], numbers[2]), parts.len()))
+}
@@ -8,6 +8,7 @@ use crate::error::{Error, Result};
 use crate::wrappers::{WrapperGenerator, WrapperSpec, ApplicationType};
 use crate::templates::HTTP_SERVER_TEMPLATE;
 use crate::compiler::{CompilerOptions, BuildProfile, OptimizationLevel};
+use crate::compiler::optimize::{PostOptimize, WasmOptLevel};
 use crate::compiler::{CargoCompiler, Compiler};
 
 /// HTTP Server wrapper configuration
@@ -203,6 +204,7 @@ impl WrapperGenerator for HttpServerGenerator {
             target: "wasm32-wasi".to_string(),
             opt_level: OptimizationLevel::Speed,
             profile: BuildProfile::Release,
+            post_optimize: Some(PostOptimize::new().level(WasmOptLevel::O3)),
             ..CompilerOptions::default()
         };
         
@@ -0,0 +1,133 @@
+//! Tests for guest ABI detection and ABI-specific call marshalling
+
+mod common;
+
+use serde_json::{json, Value};
+use wasm_sandbox::runtime::abi::custom_section_names;
+use wasm_sandbox::{AbiKind, WasmSandbox};
+
+/// Guest data ABI module echoing its input
+const SANDBOX_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param i32) (result i32)
+    i32.const 1024)
+  (func (export "echo") (param i32 i32) (result i64)
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get 0)) (i64.const 32))
+      (i64.extend_i32_u (local.get 1)))))
+"#;
+
+/// Module with plain numeric exports
+const CUSTOM_MODULE: &str = r#"
+(module
+  (func (export "mul") (param i64 i64) (result i64)
+    (i64.mul (local.get 0) (local.get 1)))
+  (func (export "half") (param f64) (result f64)
+    (f64.div (local.get 0) (f64.const 2)))
+  (func (export "divmod") (param i32 i32) (result i32 i32)
+    (i32.div_s (local.get 0) (local.get 1))
+    (i32.rem_s (local.get 0) (local.get 1))))
+"#;
+
+/// Module shaped like wasm-bindgen output for `fn(&str) -> String` exports
+const BINDGEN_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $heap (mut i32) (i32.const 1024))
+  (global $stack (mut i32) (i32.const 512))
+  (func (export "__wbindgen_malloc") (param i32 i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $heap))
+    (global.set $heap (i32.add (global.get $heap) (local.get 0)))
+    (local.get $ptr))
+  (func (export "__wbindgen_free") (param i32 i32 i32))
+  (func (export "__wbindgen_add_to_stack_pointer") (param i32) (result i32)
+    (global.set $stack (i32.add (global.get $stack) (local.get 0)))
+    (global.get $stack))
+  ;; Multi-value form
+  (func (export "echo") (param i32 i32) (result i32 i32)
+    (local.get 0)
+    (local.get 1))
+  ;; Return-pointer form
+  (func (export "echo_retptr") (param i32 i32 i32)
+    (i32.store (local.get 0) (local.get 1))
+    (i32.store offset=4 (local.get 0) (local.get 2))))
+"#;
+
+const WASI_MODULE: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func $exit (param i32)))
+  (func (export "square") (param i32) (result i32)
+    (i32.mul (local.get 0) (local.get 0))))
+"#;
+
+fn instantiate(module: &str) -> (WasmSandbox, AbiKind, wasm_sandbox::InstanceId) {
+    let (sandbox, instance_id) = common::instantiate(module, None);
+    let abi = sandbox.module_abi(sandbox.get_instance(instance_id).unwrap().module_id).unwrap();
+    (sandbox, abi, instance_id)
+}
+
+#[tokio::test]
+async fn test_sandbox_abi_passes_json_through_memory() {
+    let (sandbox, abi, instance_id) = instantiate(SANDBOX_MODULE);
+    assert_eq!(abi, AbiKind::Sandbox);
+    
+    let input = json!({ "name": "widget", "tags": ["a", "b"] });
+    let output: Value = sandbox.call_function(instance_id, "echo", input.clone()).await.unwrap();
+    assert_eq!(output, input);
+}
+
+#[tokio::test]
+async fn test_custom_abi_uses_numeric_arguments() {
+    let (sandbox, abi, instance_id) = instantiate(CUSTOM_MODULE);
+    assert_eq!(abi, AbiKind::Custom);
+    
+    // Small integers are widened to the export's i64 and f64 parameters
+    let product: i64 = sandbox.call_function(instance_id, "mul", (6, 7)).await.unwrap();
+    assert_eq!(product, 42);
+    let half: f64 = sandbox.call_function(instance_id, "half", 5).await.unwrap();
+    assert_eq!(half, 2.5);
+    let divmod: (i32, i32) = sandbox.call_function(instance_id, "divmod", (17, 5)).await.unwrap();
+    assert_eq!(divmod, (3, 2));
+    
+    let result: wasm_sandbox::Result<i64> = sandbox.call_function(instance_id, "mul", ("six", 7)).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_wasi_module_detected() {
+    let (sandbox, abi, instance_id) = instantiate(WASI_MODULE);
+    assert_eq!(abi, AbiKind::Wasi);
+    
+    let square: i32 = sandbox.call_function(instance_id, "square", [9]).await.unwrap();
+    assert_eq!(square, 81);
+}
+
+#[tokio::test]
+async fn test_wasm_bindgen_string_exports() {
+    let (sandbox, abi, instance_id) = instantiate(BINDGEN_MODULE);
+    assert_eq!(abi, AbiKind::WasmBindgen);
+    
+    let input = json!({ "greeting": "hello" });
+    let direct: Value = sandbox.call_function(instance_id, "echo", input.clone()).await.unwrap();
+    assert_eq!(direct, input);
+    let retptr: Value = sandbox.call_function(instance_id, "echo_retptr", input.clone()).await.unwrap();
+    assert_eq!(retptr, input);
+}
+
+#[test]
+fn test_custom_sections_are_sniffed() {
+    let name = b"__wasm_bindgen_unstable";
+    let mut module = b"\0asm\x01\0\0\0".to_vec();
+    module.extend_from_slice(&[0, (name.len() + 3) as u8, name.len() as u8]);
+    module.extend_from_slice(name);
+    module.extend_from_slice(&[1, 2]);
+    
+    assert_eq!(custom_section_names(&module), vec!["__wasm_bindgen_unstable".to_string()]);
+    assert!(custom_section_names(SANDBOX_MODULE.as_bytes()).is_empty());
+    
+    let sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(&module).unwrap();
+    assert_eq!(sandbox.module_abi(module_id).unwrap(), AbiKind::WasmBindgen);
+}
@@ -0,0 +1,78 @@
+//! Tests for guest ABI and plugin API version handshakes
+
+use wasm_sandbox::{
+    ApiCompatibility, ApiVersion, SandboxConfig, SandboxError, VersionRange, WasmSandbox,
+};
+use wasm_sandbox::plugins::version_satisfies;
+
+fn versioned_module(abi: i32, api: ApiVersion) -> String {
+    format!(r#"
+(module
+  (global (export "__abi_version") i32 (i32.const {}))
+  (func (export "__api_version") (result i32) i32.const {}))
+"#, abi, api.to_packed())
+}
+
+fn sandbox_with(compatibility: ApiCompatibility) -> WasmSandbox {
+    let mut config = SandboxConfig::default();
+    config.runtime.compatibility = compatibility;
+    WasmSandbox::with_config(config).expect("Failed to create sandbox")
+}
+
+#[test]
+fn test_version_ranges() {
+    let range: VersionRange = "^1.2".parse().unwrap();
+    assert!(range.contains(&ApiVersion::new(1, 2, 0)));
+    assert!(range.contains(&ApiVersion::new(1, 9, 3)));
+    assert!(!range.contains(&ApiVersion::new(1, 1, 9)));
+    assert!(!range.contains(&ApiVersion::new(2, 0, 0)));
+    
+    let range: VersionRange = ">=0.3, <0.5".parse().unwrap();
+    assert!(range.contains(&ApiVersion::new(0, 4, 7)));
+    assert!(!range.contains(&ApiVersion::new(0, 5, 0)));
+    
+    assert!(version_satisfies("0.2.5", "~0.2").unwrap());
+    assert!(!version_satisfies("0.3.0", "0.2").unwrap());
+    assert!(version_satisfies("1.0", "*").unwrap());
+    assert!(version_satisfies("not-a-version", "*").is_err());
+}
+
+#[test]
+fn test_compatible_guest_instantiates() {
+    let mut sandbox = sandbox_with(ApiCompatibility::default()
+        .abi("1".parse().unwrap())
+        .api("^2.1".parse().unwrap()));
+    let module = versioned_module(1, ApiVersion::new(2, 3, 0));
+    let module_id = sandbox.load_module(module.as_bytes()).unwrap();
+    
+    assert!(sandbox.create_instance(module_id, None).is_ok());
+}
+
+#[test]
+fn test_incompatible_guest_fails_fast() {
+    let mut sandbox = sandbox_with(ApiCompatibility::default().api("^2.1".parse().unwrap()));
+    let module = versioned_module(1, ApiVersion::new(3, 0, 0));
+    let module_id = sandbox.load_module(module.as_bytes()).unwrap();
+    
+    match sandbox.create_instance(module_id, None) {
+        Err(SandboxError::IncompatibleVersion { export, found, supported }) => {
+            assert_eq!(export, "__api_version");
+            assert_eq!(found.as_deref(), Some("3.0.0"));
+            assert_eq!(supported, "^2.1");
+        }
+        other => panic!("expected version error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_missing_version_export_when_required() {
+    let mut sandbox = sandbox_with(ApiCompatibility::default()
+        .abi("1".parse().unwrap())
+        .require_exports());
+    let module_id = sandbox.load_module(b"(module)").unwrap();
+    
+    assert!(matches!(
+        sandbox.create_instance(module_id, None),
+        Err(SandboxError::IncompatibleVersion { found: None, .. })
+    ));
+}
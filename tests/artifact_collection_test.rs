@@ -0,0 +1,49 @@
+//! Tests for collecting files written by guests
+
+use wasm_sandbox::{WasmSandbox, InstanceConfig};
+use wasm_sandbox::security::Capabilities;
+use wasm_sandbox::utils::artifacts::{glob_match, OutputCollection};
+
+const TEST_MODULE: &[u8] = include_bytes!("../fixtures/test_module.wasm");
+
+#[test]
+fn test_glob_match() {
+    assert!(glob_match("*.csv", "report.csv"));
+    assert!(!glob_match("*.csv", "out/report.csv"));
+    assert!(glob_match("**/*.csv", "report.csv"));
+    assert!(glob_match("**/*.csv", "out/deep/report.csv"));
+    assert!(glob_match("out/?.txt", "out/a.txt"));
+}
+
+#[test]
+fn test_collect_outputs_returns_only_new_files_and_cleans() {
+    let workspace = tempfile::tempdir().unwrap();
+    std::fs::write(workspace.path().join("input.csv"), "existing").unwrap();
+
+    let mut capabilities = Capabilities::minimal();
+    capabilities.filesystem.writable_dirs = vec![workspace.path().to_path_buf()];
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(TEST_MODULE).unwrap();
+    let instance_id = sandbox.create_instance(module_id, Some(InstanceConfig {
+        capabilities,
+        ..InstanceConfig::default()
+    })).unwrap();
+
+    // Files written after instance creation stand in for guest output
+    std::fs::create_dir(workspace.path().join("out")).unwrap();
+    std::fs::write(workspace.path().join("out/result.csv"), "a,b").unwrap();
+    std::fs::write(workspace.path().join("out/log.txt"), "done").unwrap();
+
+    let outputs = sandbox.collect_outputs_with(
+        instance_id,
+        &OutputCollection::new(&["**/*.csv"]).clean(),
+    ).unwrap();
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].relative_path, std::path::Path::new("out/result.csv"));
+    assert_eq!(outputs[0].contents.as_deref(), Some(&b"a,b"[..]));
+    assert!(!workspace.path().join("out/result.csv").exists());
+    assert!(workspace.path().join("input.csv").exists());
+
+    let too_large = sandbox.collect_outputs_with(instance_id, &OutputCollection::new(&["**"]).max_file_size(1));
+    assert!(too_large.is_err());
+}
@@ -0,0 +1,91 @@
+//! Tests for guest calls that yield to the executor while they run
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use wasm_sandbox::runtime::RuntimeConfig;
+use wasm_sandbox::{InstanceConfig, InstanceId, SandboxConfig, WasmSandbox};
+
+// `count` loops `n` times before returning `n`; `spin` never returns on its own
+const WORKER_MODULE: &str = r#"
+(module
+  (func (export "double") (param i32) (result i32)
+    (i32.mul (local.get 0) (i32.const 2)))
+  (func (export "count") (param $n i32) (result i32)
+    (local $i i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (local.get $i))
+  (func (export "spin") (param i32) (result i32)
+    (loop $forever (br $forever))
+    (i32.const 0)))
+"#;
+
+fn sandbox_with_instance(runtime: RuntimeConfig) -> (WasmSandbox, InstanceId) {
+    let mut sandbox = WasmSandbox::with_config(SandboxConfig {
+        runtime,
+        ..SandboxConfig::default()
+    }).expect("Failed to create sandbox");
+    let mut config = InstanceConfig::default();
+    config.resource_limits.fuel = Some(u64::MAX / 2);
+    let instance_id = common::create_instance(&mut sandbox, WORKER_MODULE, Some(config));
+    (sandbox, instance_id)
+}
+
+/// When a task that keeps yielding finishes, relative to a long call started alongside it
+async fn ticker_and_long_call(sandbox: &WasmSandbox, instance_id: InstanceId) -> (Instant, Instant) {
+    let ticker = async {
+        for _ in 0..3 {
+            tokio::task::yield_now().await;
+        }
+        Instant::now()
+    };
+    let call = async {
+        let value: i32 = sandbox.call_function(instance_id, "count", (10_000_000,)).await.unwrap();
+        assert_eq!(value, 10_000_000);
+        Instant::now()
+    };
+    tokio::join!(ticker, call)
+}
+
+#[tokio::test]
+async fn test_long_call_lets_other_tasks_run() {
+    let (sandbox, instance_id) = sandbox_with_instance(RuntimeConfig::default());
+    let (ticked, finished) = ticker_and_long_call(&sandbox, instance_id).await;
+    assert!(ticked < finished);
+}
+
+#[tokio::test]
+async fn test_calls_without_yield_interval_run_to_completion() {
+    let (sandbox, instance_id) = sandbox_with_instance(RuntimeConfig {
+        async_yield_fuel: None,
+        ..RuntimeConfig::default()
+    });
+    let (ticked, finished) = ticker_and_long_call(&sandbox, instance_id).await;
+    assert!(finished < ticked);
+}
+
+#[tokio::test]
+async fn test_concurrent_calls_to_one_instance_take_turns() {
+    let (sandbox, instance_id) = sandbox_with_instance(RuntimeConfig::default());
+    let (counted, doubled) = tokio::join!(
+        sandbox.call_function::<_, i32>(instance_id, "count", (1_000_000,)),
+        sandbox.call_function::<_, i32>(instance_id, "double", (21,)),
+    );
+    assert_eq!(counted.unwrap(), 1_000_000);
+    assert_eq!(doubled.unwrap(), 42);
+}
+
+#[tokio::test]
+async fn test_dropped_call_leaves_instance_usable() {
+    let (sandbox, instance_id) = sandbox_with_instance(RuntimeConfig::default());
+    let spin = sandbox.call_function::<_, i32>(instance_id, "spin", (0,));
+    assert!(tokio::time::timeout(Duration::from_millis(50), spin).await.is_err());
+    
+    let value: i32 = sandbox.call_function(instance_id, "double", (4,)).await.unwrap();
+    assert_eq!(value, 8);
+}
@@ -0,0 +1,68 @@
+//! Tests for async host functions on RPC channels and guest imports
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use wasm_sandbox::communication::{AsyncRpcChannelExt, RpcChannelExt};
+use wasm_sandbox::communication::channels::MessageChannel;
+use wasm_sandbox::communication::rpc::JsonRpcChannel;
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::{HostNamespace, WasmSandbox};
+
+// `quote` forwards to an async host function and adds one to its answer
+const QUOTE_MODULE: &str = r#"
+(module
+  (import "acme.pricing" "lookup" (func $lookup (param i64) (result i64)))
+  (func (export "quote") (param $sku i64) (result i64)
+    (i64.add (call $lookup (local.get $sku)) (i64.const 1))))
+"#;
+
+fn rpc_channel() -> JsonRpcChannel {
+    JsonRpcChannel::new(Arc::new(MessageChannel::new("test", 8)))
+}
+
+#[tokio::test]
+async fn test_async_host_function_is_awaited() {
+    let mut channel = rpc_channel();
+    channel.register_async_host_function("lookup", |key: String| async move {
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        Ok(format!("value-for-{key}"))
+    }).unwrap();
+
+    let result: String = channel.call_host_function("lookup", "user").await.unwrap();
+    assert_eq!(result, "value-for-user");
+}
+
+#[tokio::test]
+async fn test_sync_host_function_dispatches_through_async_path() {
+    let mut channel = rpc_channel();
+    channel.register_host_function("add", |(a, b): (i32, i32)| Ok(a + b)).unwrap();
+
+    let result: i32 = channel.call_host_function("add", &(2, 3)).await.unwrap();
+    assert_eq!(result, 5);
+
+    let missing: wasm_sandbox::Result<i32> = channel.call_host_function("missing", &()).await;
+    assert!(missing.is_err());
+}
+
+#[tokio::test]
+async fn test_guest_import_awaits_async_host_function() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.register_host_namespace(HostNamespace::new("acme.pricing")
+        .ungated()
+        .async_function("lookup", |args| async move {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            match args.as_slice() {
+                [HostValue::I64(sku)] => Ok(vec![HostValue::I64(sku * 100)]),
+                _ => unreachable!(),
+            }
+        }))
+        .unwrap();
+    let instance_id = common::create_instance(&mut sandbox, QUOTE_MODULE, None);
+
+    let instance = &sandbox.get_instance(instance_id).unwrap().instance;
+    let result = instance.call_values_async("quote", &[HostValue::I64(7)]).await.unwrap();
+    assert_eq!(result, vec![HostValue::I64(701)]);
+}
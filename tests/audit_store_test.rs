@@ -0,0 +1,125 @@
+//! Tests for the persistent audit store
+#![cfg(feature = "sqlite-audit")]
+
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use wasm_sandbox::security::audit::{AuditEventType, AuditLogger, AuditSeverity};
+use wasm_sandbox::{
+    AuditEventKind, AuditStore, AuditStoreConfig, CallableFunctions, Error, InstanceConfig, InstanceId, SandboxConfig,
+    WasmSandbox,
+};
+
+const ECHO_MODULE: &str = r#"
+(module
+  (func (export "echo") (param i32) (result i32) (local.get 0)))
+"#;
+
+fn instantiate(sandbox: &mut WasmSandbox) -> InstanceId {
+    let config = InstanceConfig::builder()
+        .callable_functions(CallableFunctions::Allowlist(vec!["echo".to_string()]))
+        .build()
+        .unwrap();
+    common::create_instance(sandbox, ECHO_MODULE, Some(config))
+}
+
+fn custom(data: &str) -> AuditEventType {
+    AuditEventType::Custom { event_type: "test".to_string(), data: data.to_string() }
+}
+
+#[tokio::test]
+async fn test_sandbox_persists_and_queries_audit_events() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit.db");
+    let start = SystemTime::now() - Duration::from_secs(1);
+    let mut sandbox = WasmSandbox::with_config(SandboxConfig {
+        audit_store: Some(AuditStoreConfig::new(&path)),
+        ..SandboxConfig::default()
+    }).unwrap();
+    let first = instantiate(&mut sandbox);
+    let second = instantiate(&mut sandbox);
+    
+    for instance_id in [first, first, second] {
+        assert!(sandbox.call_function::<_, i32>(instance_id, "hidden", ()).await.is_err());
+    }
+    let _: i32 = sandbox.call_function(first, "echo", 1).await.unwrap();
+    
+    let store = sandbox.audit_store().unwrap();
+    let denied = store.query().instance(first).kind(AuditEventKind::FunctionCallDenied).since(start).fetch().unwrap();
+    assert_eq!(denied.len(), 2);
+    assert!(denied.iter().all(|event| event.event_type.instance_id() == Some(first.to_string().as_str())));
+    assert_eq!(store.query().kind(AuditEventKind::FunctionCallDenied).limit(1).fetch().unwrap().len(), 1);
+    assert_eq!(store.query().min_severity(AuditSeverity::Error).fetch().unwrap().len(), 0);
+    assert!(store.query().until(start).fetch().unwrap().is_empty());
+    drop(sandbox);
+    
+    // The events outlive the sandbox
+    let store = AuditStore::open(AuditStoreConfig::new(&path)).unwrap();
+    let denied = store.query().kind(AuditEventKind::FunctionCallDenied).fetch().unwrap();
+    assert_eq!(denied.len(), 3);
+    assert!(matches!(&denied[2].event_type, AuditEventType::FunctionCallDenied { function_name, .. } if function_name == "hidden"));
+}
+
+#[test]
+fn test_retention_and_rotation() {
+    let dir = tempfile::tempdir().unwrap();
+    let pruned = Arc::new(AuditStore::open(AuditStoreConfig::new(dir.path().join("pruned.db")).max_events(3)).unwrap());
+    let path = dir.path().join("rotated.db");
+    let rotated = Arc::new(AuditStore::open(AuditStoreConfig::new(&path).rotate_after(2, 1)).unwrap());
+    for store in [&pruned, &rotated] {
+        let logger = AuditLogger::new(100).with_store(store.clone());
+        for n in 0..5 {
+            logger.info(custom(&n.to_string()), "tick");
+        }
+    }
+    
+    // Only the newest events are kept
+    let kept: Vec<_> = pruned.query().fetch().unwrap().into_iter().map(|event| event.event_type).collect();
+    assert_eq!(kept, [custom("2"), custom("3"), custom("4")]);
+    
+    // Each rotation replaces the one before it
+    assert_eq!(rotated.query().fetch().unwrap().len(), 1);
+    assert!(dir.path().join("rotated.db.1").exists());
+    assert!(!dir.path().join("rotated.db.2").exists());
+    let previous = AuditStore::open(AuditStoreConfig::new(dir.path().join("rotated.db.1"))).unwrap();
+    let previous: Vec<_> = previous.query().fetch().unwrap().into_iter().map(|event| event.event_type).collect();
+    assert_eq!(previous, [custom("2"), custom("3")]);
+}
+
+#[test]
+fn test_export_as_jsonl_and_csv() {
+    let dir = tempfile::tempdir().unwrap();
+    let store = Arc::new(AuditStore::open(AuditStoreConfig::new(dir.path().join("audit.db"))).unwrap());
+    let logger = AuditLogger::new(100).with_store(store.clone());
+    logger.warning(custom("first"), "plain");
+    logger.error(custom("second"), "needs, \"quoting\"");
+    
+    let mut jsonl = Vec::new();
+    assert_eq!(store.query().export_jsonl(&mut jsonl).unwrap(), 2);
+    let lines: Vec<serde_json::Value> = String::from_utf8(jsonl).unwrap().lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines[1]["message"], "needs, \"quoting\"");
+    
+    let mut csv = Vec::new();
+    assert_eq!(store.query().min_severity(AuditSeverity::Error).export_csv(&mut csv).unwrap(), 1);
+    let csv = String::from_utf8(csv).unwrap();
+    let mut rows = csv.lines();
+    assert_eq!(rows.next(), Some("timestamp,severity,kind,instance_id,call_id,message,details"));
+    let row = rows.next().unwrap();
+    assert!(row.contains(",error,Custom,,,\"needs, \"\"quoting\"\"\","), "{}", row);
+    assert_eq!(rows.next(), None);
+}
+
+#[test]
+fn test_invalid_store_settings_are_rejected() {
+    for config in [AuditStoreConfig::new("audit.db").max_events(0), AuditStoreConfig::new("")] {
+        let result = WasmSandbox::with_config(SandboxConfig {
+            audit_store: Some(config),
+            ..SandboxConfig::default()
+        });
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
+    }
+}
@@ -0,0 +1,105 @@
+//! Tests for passing binary payloads to and from guests without JSON
+
+mod common;
+
+use serde::{Deserialize, Serialize};
+use wasm_sandbox::{Bytes, Error, InstanceId};
+
+/// Data ABI module whose exports invert or echo their input
+const BYTES_MODULE: &str = r#"
+(module
+  (memory (export "memory") 4)
+  (func (export "alloc") (param i32) (result i32)
+    i32.const 1024)
+  ;; Complement every byte into the output area
+  (func (export "invert") (param $ptr i32) (param $len i32) (result i64)
+    (local $i i32)
+    (block $done
+      (loop $each
+        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+        (i32.store8 (i32.add (i32.const 131072) (local.get $i))
+          (i32.xor (i32.load8_u (i32.add (local.get $ptr) (local.get $i))) (i32.const 255)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $each)))
+    (i64.or (i64.shl (i64.const 131072) (i64.const 32)) (i64.extend_i32_u (local.get $len))))
+  (func (export "echo") (param i32 i32) (result i64)
+    (i64.or (i64.shl (i64.extend_i32_u (local.get 0)) (i64.const 32)) (i64.extend_i32_u (local.get 1)))))
+"#;
+
+/// Module shaped like wasm-bindgen output for a `fn(&[u8]) -> Vec<u8>` export
+const BINDGEN_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $heap (mut i32) (i32.const 1024))
+  (func (export "__wbindgen_malloc") (param i32 i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $heap))
+    (global.set $heap (i32.add (global.get $heap) (local.get 0)))
+    (local.get $ptr))
+  (func (export "__wbindgen_free") (param i32 i32 i32))
+  (func (export "echo") (param i32 i32) (result i32 i32)
+    (local.get 0)
+    (local.get 1)))
+"#;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Upload {
+    name: String,
+    data: Bytes,
+}
+
+#[tokio::test]
+async fn test_bytes_pass_through_unchanged() {
+    let (sandbox, instance_id) = common::instantiate(BYTES_MODULE, None);
+    
+    // Every byte value, which isn't valid UTF-8
+    let input: Vec<u8> = (0..=255).collect();
+    let output = sandbox.call_function_bytes(instance_id, "invert", &input).await.unwrap();
+    assert_eq!(output, input.iter().map(|byte| !byte).collect::<Vec<u8>>());
+    
+    let large: Vec<u8> = (0..100_000u32).map(|i| (i * 7) as u8).collect();
+    assert_eq!(sandbox.call_function_bytes(instance_id, "echo", &large).await.unwrap(), large);
+    assert!(sandbox.call_function_bytes(instance_id, "echo", &[]).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_msgpack_calls_keep_binary_fields_compact() {
+    let (sandbox, instance_id) = common::instantiate(BYTES_MODULE, None);
+    let upload = Upload { name: "photo.png".to_string(), data: Bytes((0..50_000u32).map(|i| i as u8).collect()) };
+    
+    let echoed: Upload = sandbox.call_function_msgpack(instance_id, "echo", &upload).await.unwrap();
+    assert_eq!(echoed, upload);
+    
+    // Binary fields cost a few bytes of framing, where JSON spends up to four bytes per byte
+    let raw = sandbox.call_function_bytes(instance_id, "echo", &rmp_serde::to_vec_named(&upload).unwrap()).await.unwrap();
+    assert!(raw.len() < upload.data.len() + 32, "{} bytes", raw.len());
+    let json = serde_json::to_vec(&upload).unwrap();
+    assert!(json.len() > 3 * upload.data.len());
+    assert_eq!(serde_json::from_slice::<Upload>(&json).unwrap(), upload);
+    
+    // Output that isn't MessagePack of the expected type is reported
+    let error = sandbox.call_function_msgpack::<_, Upload>(instance_id, "echo", "text").await.unwrap_err();
+    assert!(error.to_string().contains("Failed to deserialize function result"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_wasm_bindgen_exports_take_bytes() {
+    let (sandbox, instance_id) = common::instantiate(BINDGEN_MODULE, None);
+    
+    let input = [0xff, 0xfe, 0x00, 0x80, b'a'];
+    assert_eq!(sandbox.call_function_bytes(instance_id, "echo", &input).await.unwrap(), input);
+    let echoed: Vec<Bytes> = sandbox.call_function_msgpack(instance_id, "echo", &[Bytes::from(&input[..])]).await.unwrap();
+    assert_eq!(echoed[0].as_ref(), input);
+}
+
+#[tokio::test]
+async fn test_numeric_exports_refuse_bytes() {
+    let (sandbox, instance_id) = common::instantiate(r#"(module (func (export "square") (param i32) (result i32) (i32.mul (local.get 0) (local.get 0))))"#, None);
+    
+    let error = sandbox.call_function_bytes(instance_id, "square", b"7").await.unwrap_err();
+    assert!(matches!(&error, Error::UnsupportedOperation { message } if message.contains("numeric exports")), "{}", error);
+    
+    let error = sandbox.call_function_bytes(InstanceId::new(), "square", b"7").await.unwrap_err();
+    assert!(matches!(error, Error::NotFound { .. }));
+    assert_eq!(Vec::from(Bytes::from(vec![1, 2])), [1, 2]);
+}
@@ -0,0 +1,87 @@
+//! Tests for exporting and importing compiled module caches
+
+use wasm_sandbox::{ArtifactTarget, CacheBundle, Error, WasmSandbox};
+
+const ADD_MODULE: &str = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))))
+"#;
+
+fn exported_bundle(dir: &tempfile::TempDir) -> std::path::PathBuf {
+    let sandbox = WasmSandbox::new().unwrap();
+    sandbox.load_module(ADD_MODULE.as_bytes()).unwrap();
+    sandbox.load_module(ADD_MODULE.as_bytes()).unwrap();
+    
+    let path = dir.path().join("modules.cache");
+    assert_eq!(sandbox.export_cache(&path).unwrap(), 1, "identical modules are exported once");
+    path
+}
+
+#[tokio::test]
+async fn test_imported_artifacts_replace_compilation() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = exported_bundle(&dir);
+    
+    let mut sandbox = WasmSandbox::new().unwrap();
+    assert_eq!(sandbox.import_cache(&path).unwrap(), 1);
+    let module_id = sandbox.load_module(ADD_MODULE.as_bytes()).unwrap();
+    
+    let metrics = sandbox.runtime().get_metrics();
+    assert_eq!(metrics.compiled_modules, 0);
+    assert_eq!(metrics.cache_hit_rate, Some(1.0));
+    
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    let sum: i32 = sandbox.call_function(instance_id, "add", (2, 3)).await.unwrap();
+    assert_eq!(sum, 5);
+}
+
+#[test]
+fn test_bundle_for_another_target_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = exported_bundle(&dir);
+    let mut bundle = CacheBundle::read(&path).unwrap();
+    bundle.target.settings.enable_fuel = !bundle.target.settings.enable_fuel;
+    bundle.write(&path).unwrap();
+    
+    let sandbox = WasmSandbox::new().unwrap();
+    assert!(matches!(sandbox.import_cache(&path), Err(Error::Module { .. })));
+    sandbox.load_module(ADD_MODULE.as_bytes()).unwrap();
+    assert_eq!(sandbox.runtime().get_metrics().compiled_modules, 1);
+}
+
+#[test]
+fn test_corrupt_bundles_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = exported_bundle(&dir);
+    let sandbox = WasmSandbox::new().unwrap();
+    
+    let mut bytes = std::fs::read(&path).unwrap();
+    *bytes.last_mut().unwrap() ^= 0xff;
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(sandbox.import_cache(&path), Err(Error::InvalidInput { .. })));
+    
+    std::fs::write(&path, b"not a bundle").unwrap();
+    assert!(matches!(sandbox.import_cache(&path), Err(Error::InvalidInput { .. })));
+}
+
+#[test]
+fn test_hosts_may_have_extra_cpu_features() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = CacheBundle::read(&exported_bundle(&dir)).unwrap().target;
+    
+    let mut host = target.clone();
+    host.cpu_features.push("future-extension".to_string());
+    assert!(target.check_compatible(&host).is_ok());
+    
+    let mut needs_more = target.clone();
+    needs_more.cpu_features.push("future-extension".to_string());
+    let error = needs_more.check_compatible(&target).unwrap_err();
+    assert!(error.to_string().contains("future-extension"), "unexpected error: {}", error);
+    
+    let other_platform = ArtifactTarget {
+        arch: "riscv32".to_string(),
+        ..target.clone()
+    };
+    assert!(other_platform.check_compatible(&target).is_err());
+}
@@ -0,0 +1,86 @@
+//! Tests for structured diagnostics guests attach to their results
+
+mod common;
+
+use wasm_sandbox::runtime::diagnostics::MAX_CALL_DIAGNOSTICS;
+use wasm_sandbox::{DiagnosticKind, GuestErrorCode, InstanceId, WasmSandbox};
+
+// `process` reports a warning and a skipped record; `flood` reports `n` warnings;
+// `malformed` returns the result of reporting an unknown kind
+const DIAGNOSTICS_MODULE: &str = r#"
+(module
+  (import "sandbox_log" "diagnostic" (func $diagnostic (param i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "{\"kind\":\"warning\",\"message\":\"input truncated\"}")
+  (data (i32.const 100) "{\"kind\":\"skipped_record\",\"message\":\"bad date\",\"details\":{\"row\":12}}")
+  (data (i32.const 200) "{\"kind\":\"unknown\"}")
+  (func (export "process") (param $records i32) (result i32)
+    (drop (call $diagnostic (i32.const 0) (i32.const 46)))
+    (drop (call $diagnostic (i32.const 100) (i32.const 67)))
+    (i32.sub (local.get $records) (i32.const 1)))
+  (func (export "flood") (param $n i32) (result i32)
+    (local $i i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (drop (call $diagnostic (i32.const 0) (i32.const 46)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (local.get $n))
+  (func (export "malformed") (param i32) (result i32)
+    (call $diagnostic (i32.const 200) (i32.const 18)))
+  (func (export "quiet") (param $x i32) (result i32)
+    (local.get $x)))
+"#;
+
+fn instance() -> (WasmSandbox, InstanceId) {
+    common::instantiate(DIAGNOSTICS_MODULE, None)
+}
+
+#[tokio::test]
+async fn test_report_carries_the_calls_diagnostics() {
+    let (sandbox, instance_id) = instance();
+    let processed: i32 = sandbox.call_function(instance_id, "process", 10).await.unwrap();
+    assert_eq!(processed, 9);
+    
+    let diagnostics = sandbox.last_invocation_report(instance_id).unwrap().diagnostics;
+    assert_eq!(diagnostics.entries.len(), 2);
+    let warning = diagnostics.warnings().next().unwrap();
+    assert_eq!(warning.message, "input truncated");
+    assert!(warning.details.is_none());
+    let skipped = diagnostics.skipped_records().next().unwrap();
+    assert_eq!(skipped.message, "bad date");
+    assert_eq!(skipped.details, Some(serde_json::json!({"row": 12})));
+    assert_eq!(diagnostics.partial_failures().count(), 0);
+}
+
+#[tokio::test]
+async fn test_diagnostics_belong_to_one_call() {
+    let (sandbox, instance_id) = instance();
+    let _: i32 = sandbox.call_function(instance_id, "process", 1).await.unwrap();
+    let _: i32 = sandbox.call_function(instance_id, "quiet", 1).await.unwrap();
+    
+    let report = sandbox.last_invocation_report(instance_id).unwrap();
+    assert_eq!(report.function_name, "quiet");
+    assert!(report.diagnostics.is_empty());
+}
+
+#[tokio::test]
+async fn test_diagnostics_beyond_the_limit_are_counted() {
+    let (sandbox, instance_id) = instance();
+    let flood = MAX_CALL_DIAGNOSTICS as i32 + 5;
+    let _: i32 = sandbox.call_function(instance_id, "flood", flood).await.unwrap();
+    
+    let diagnostics = sandbox.last_invocation_report(instance_id).unwrap().diagnostics;
+    assert_eq!(diagnostics.entries.len(), MAX_CALL_DIAGNOSTICS);
+    assert_eq!(diagnostics.dropped, 5);
+    assert!(diagnostics.entries.iter().all(|diagnostic| diagnostic.kind == DiagnosticKind::Warning));
+}
+
+#[tokio::test]
+async fn test_malformed_diagnostics_are_rejected() {
+    let (sandbox, instance_id) = instance();
+    let code: i32 = sandbox.call_function(instance_id, "malformed", 0).await.unwrap();
+    assert_eq!(code as i64, GuestErrorCode::InvalidInput.code());
+    assert!(sandbox.last_invocation_report(instance_id).unwrap().diagnostics.is_empty());
+}
@@ -0,0 +1,175 @@
+//! Tests for queueing concurrent calls to an instance by priority
+
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::{
+    CallPriority, CallQueueConfig, CallQueueMetrics, Error, HostNamespace, InstanceConfig, InstanceId,
+    OverflowPolicy, WasmSandbox,
+};
+
+// `hold` blocks in the host until released; `record` logs its argument in the host
+const QUEUE_MODULE: &str = r#"
+(module
+  (import "test.queue" "hold" (func $hold (param i32) (result i32)))
+  (import "test.queue" "record" (func $record (param i32) (result i32)))
+  (func (export "hold") (param $x i32) (result i32)
+    (call $hold (local.get $x)))
+  (func (export "record") (param $x i32) (result i32)
+    (call $record (local.get $x))))
+"#;
+
+struct Harness {
+    sandbox: Arc<WasmSandbox>,
+    instance_id: InstanceId,
+    release: mpsc::Sender<()>,
+    recorded: Arc<Mutex<Vec<i32>>>,
+}
+
+fn harness(queue: CallQueueConfig) -> Harness {
+    let (release, released) = mpsc::channel::<()>();
+    let released = Mutex::new(released);
+    let recorded = Arc::new(Mutex::new(Vec::new()));
+    let log = recorded.clone();
+    
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.register_host_namespace(
+        HostNamespace::new("test.queue")
+            .ungated()
+            .function("hold", move |args| {
+                released.lock().unwrap().recv().unwrap();
+                Ok(args.to_vec())
+            })
+            .function("record", move |args| {
+                if let [HostValue::I32(x)] = args {
+                    log.lock().unwrap().push(*x);
+                }
+                Ok(args.to_vec())
+            }),
+    ).unwrap();
+    let module_id = sandbox.load_module(QUEUE_MODULE.as_bytes()).expect("Failed to load module");
+    let config = InstanceConfig {
+        call_queue: Some(queue),
+        ..InstanceConfig::default()
+    };
+    let instance_id = sandbox.create_instance(module_id, Some(config)).expect("Failed to create instance");
+    
+    Harness {
+        sandbox: Arc::new(sandbox),
+        instance_id,
+        release,
+        recorded,
+    }
+}
+
+impl Harness {
+    fn call(&self, function_name: &'static str, x: i32, priority: CallPriority) -> tokio::task::JoinHandle<wasm_sandbox::Result<i32>> {
+        let sandbox = self.sandbox.clone();
+        let instance_id = self.instance_id;
+        tokio::spawn(async move {
+            sandbox.call_function_with_priority(instance_id, function_name, x, priority).await
+        })
+    }
+    
+    fn metrics(&self) -> CallQueueMetrics {
+        self.sandbox.call_queue_metrics(self.instance_id).unwrap()
+    }
+    
+    /// Wait until the queue looks the way `done` wants
+    async fn wait_for(&self, done: impl Fn(&CallQueueMetrics) -> bool) {
+        for _ in 0..500 {
+            if done(&self.metrics()) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        panic!("queue never settled: {:?}", self.metrics());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_waiting_calls_run_by_priority() {
+    let harness = harness(CallQueueConfig::default());
+    let holder = harness.call("hold", 0, CallPriority::Normal);
+    harness.wait_for(|metrics| metrics.running).await;
+    
+    let mut waiters = Vec::new();
+    for (x, priority) in [(1, CallPriority::Low), (2, CallPriority::Normal), (3, CallPriority::High), (4, CallPriority::Normal)] {
+        waiters.push(harness.call("record", x, priority));
+        harness.wait_for(|metrics| metrics.depth() == x as usize).await;
+    }
+    assert_eq!(harness.metrics().waiting, [1, 2, 1]);
+    
+    harness.release.send(()).unwrap();
+    assert_eq!(holder.await.unwrap().unwrap(), 0);
+    for waiter in waiters {
+        waiter.await.unwrap().unwrap();
+    }
+    assert_eq!(*harness.recorded.lock().unwrap(), vec![3, 2, 4, 1]);
+    
+    let metrics = harness.metrics();
+    assert_eq!((metrics.depth(), metrics.running, metrics.peak_depth, metrics.queued), (0, false, 4, 4));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_full_queue_rejects_calls() {
+    let harness = harness(CallQueueConfig::default().max_depth(1));
+    let holder = harness.call("hold", 0, CallPriority::Normal);
+    harness.wait_for(|metrics| metrics.running).await;
+    let waiter = harness.call("record", 1, CallPriority::Low);
+    harness.wait_for(|metrics| metrics.depth() == 1).await;
+    
+    let rejected = harness.call("record", 2, CallPriority::High).await.unwrap();
+    assert!(matches!(rejected, Err(Error::ResourceLimit { .. })));
+    
+    harness.release.send(()).unwrap();
+    holder.await.unwrap().unwrap();
+    waiter.await.unwrap().unwrap();
+    assert_eq!(*harness.recorded.lock().unwrap(), vec![1]);
+    assert_eq!(harness.metrics().rejected, 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_full_queue_sheds_lower_priority_calls() {
+    let harness = harness(CallQueueConfig::default().max_depth(1).overflow(OverflowPolicy::ShedLowest));
+    let holder = harness.call("hold", 0, CallPriority::Normal);
+    harness.wait_for(|metrics| metrics.running).await;
+    let low = harness.call("record", 1, CallPriority::Low);
+    harness.wait_for(|metrics| metrics.depth() == 1).await;
+    
+    let high = harness.call("record", 2, CallPriority::High);
+    assert!(matches!(low.await.unwrap(), Err(Error::ResourceLimit { .. })));
+    
+    // A call no more urgent than the waiter is refused instead
+    let normal = harness.call("record", 3, CallPriority::Normal).await.unwrap();
+    assert!(normal.is_err());
+    
+    harness.release.send(()).unwrap();
+    holder.await.unwrap().unwrap();
+    high.await.unwrap().unwrap();
+    assert_eq!(*harness.recorded.lock().unwrap(), vec![2]);
+    let metrics = harness.metrics();
+    assert_eq!((metrics.shed, metrics.rejected), (1, 1));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_waits_time_out_per_priority() {
+    let harness = harness(CallQueueConfig::default().timeout(CallPriority::Low, Duration::from_millis(20)));
+    let holder = harness.call("hold", 0, CallPriority::Normal);
+    harness.wait_for(|metrics| metrics.running).await;
+    
+    let low = harness.call("record", 1, CallPriority::Low);
+    let normal = harness.call("record", 2, CallPriority::Normal);
+    assert!(matches!(low.await.unwrap(), Err(Error::Timeout { .. })));
+    
+    harness.release.send(()).unwrap();
+    holder.await.unwrap().unwrap();
+    normal.await.unwrap().unwrap();
+    assert_eq!(*harness.recorded.lock().unwrap(), vec![2]);
+    assert_eq!(harness.metrics().timed_out, 1);
+    
+    let unqueued = WasmSandbox::new().unwrap();
+    assert!(unqueued.call_queue_metrics(harness.instance_id).is_none());
+}
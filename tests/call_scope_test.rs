@@ -0,0 +1,102 @@
+//! Tests for scoped groups of concurrent calls
+
+use std::time::{Duration, Instant};
+
+use wasm_sandbox::{Error, InstanceConfig, InstanceId, WasmSandbox};
+
+// `spin` never returns on its own
+const WORKER_MODULE: &str = r#"
+(module
+  (func (export "double") (param i32) (result i32)
+    (i32.mul (local.get 0) (i32.const 2)))
+  (func (export "spin") (param i32) (result i32)
+    (loop $forever (br $forever))
+    (i32.const 0)))
+"#;
+
+fn sandbox_with_instances(count: usize) -> (WasmSandbox, Vec<InstanceId>) {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(WORKER_MODULE.as_bytes()).expect("Failed to load module");
+    
+    // With unbounded fuel, only an interrupt can stop `spin`
+    let mut config = InstanceConfig::default();
+    config.resource_limits.fuel = Some(u64::MAX);
+    let instances = (0..count)
+        .map(|_| sandbox.create_instance(module_id, Some(config.clone())).expect("Failed to create instance"))
+        .collect();
+    (sandbox, instances)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_join_all_returns_results_in_spawn_order() {
+    let (sandbox, instances) = sandbox_with_instances(3);
+    let mut scope = sandbox.scope::<i32>();
+    for (value, instance_id) in instances.iter().enumerate() {
+        scope.spawn(*instance_id, "double", (value as i32,)).unwrap();
+    }
+    scope.spawn(instances[0], "double", (10,)).unwrap();
+    assert_eq!(scope.len(), 4);
+    
+    let results: Vec<i32> = scope.join_all().await.into_iter().map(|result| result.unwrap()).collect();
+    assert_eq!(results, vec![0, 2, 4, 20]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_deadline_interrupts_running_guests() {
+    let (sandbox, instances) = sandbox_with_instances(2);
+    let started = Instant::now();
+    let mut scope = sandbox.scope::<i32>().with_deadline(Duration::from_millis(200));
+    let quick = scope.spawn(instances[0], "double", (21,)).unwrap();
+    scope.spawn(instances[0], "spin", (0,)).unwrap();
+    scope.spawn(instances[1], "spin", (0,)).unwrap();
+    
+    let mut timed_out = 0;
+    while let Some((id, result)) = scope.join_next().await {
+        match result {
+            Ok(value) => assert_eq!((id, value), (quick, 42)),
+            Err(Error::Timeout { .. }) => timed_out += 1,
+            Err(e) => panic!("unexpected error {}", e),
+        }
+    }
+    assert_eq!(timed_out, 2);
+    assert!(scope.is_ended());
+    assert!(started.elapsed() < Duration::from_secs(5));
+    drop(scope);
+    
+    // Interrupted instances keep working
+    let value: i32 = sandbox.call_function(instances[1], "double", (4,)).await.unwrap();
+    assert_eq!(value, 8);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cancel_stops_calls_and_rejects_new_ones() {
+    let (sandbox, instances) = sandbox_with_instances(1);
+    let mut scope = sandbox.scope::<i32>();
+    scope.spawn(instances[0], "spin", (0,)).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    scope.cancel();
+    scope.spawn(instances[0], "double", (1,)).unwrap();
+    
+    for result in scope.join_all().await {
+        match result {
+            Err(Error::FunctionCall { reason, .. }) => assert!(reason.contains("cancelled")),
+            other => panic!("expected cancellation, got {:?}", other),
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_dropping_scope_interrupts_guests() {
+    let (sandbox, instances) = sandbox_with_instances(1);
+    {
+        let mut scope = sandbox.scope::<i32>();
+        scope.spawn(instances[0], "spin", (0,)).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(scope.spawn(InstanceId::new(), "double", (1,)).is_err());
+    }
+    
+    // The instance is free again once the spinning call has been interrupted
+    let call = sandbox.call_function::<_, i32>(instances[0], "double", (5,));
+    let value = tokio::time::timeout(Duration::from_secs(5), call).await.expect("guest was not interrupted");
+    assert_eq!(value.unwrap(), 10);
+}
@@ -0,0 +1,79 @@
+//! Tests for restricting which exports the host may call
+
+mod common;
+
+use wasm_sandbox::security::audit::AuditEventType;
+use wasm_sandbox::{CallableFunctions, Error, InstanceConfig, InstanceId, WasmSandbox};
+
+const MODULE: &str = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1)))
+  (func (export "debug_dump_all") (result i32) (i32.const 42)))
+"#;
+
+fn instance(sandbox: &mut WasmSandbox, callable_functions: CallableFunctions) -> InstanceId {
+    let config = InstanceConfig::builder().callable_functions(callable_functions).build().unwrap();
+    common::create_instance(sandbox, MODULE, Some(config))
+}
+
+#[tokio::test]
+async fn test_denylisted_functions_are_refused_and_audited() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let instance_id = instance(&mut sandbox, CallableFunctions::Denylist(vec!["debug_dump_all".to_string()]));
+    
+    let sum: i32 = sandbox.call_function(instance_id, "add", (2, 3)).await.unwrap();
+    assert_eq!(sum, 5);
+    let err = sandbox.call_function::<_, i32>(instance_id, "debug_dump_all", ()).await.unwrap_err();
+    assert!(matches!(err, Error::SecurityViolation { .. }), "{}", err);
+    
+    let events = sandbox.function_call_audit_logger().get_events();
+    assert_eq!(events.len(), 1);
+    match &events[0].event_type {
+        AuditEventType::FunctionCallDenied { instance_id: denied, function_name } => {
+            assert_eq!(*denied, instance_id.to_string());
+            assert_eq!(function_name, "debug_dump_all");
+        }
+        other => panic!("unexpected event {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_allowlist_refuses_everything_else() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let instance_id = instance(&mut sandbox, CallableFunctions::Allowlist(vec!["add".to_string()]));
+    
+    let sum: i32 = sandbox.call_function(instance_id, "add", (1, 1)).await.unwrap();
+    assert_eq!(sum, 2);
+    assert!(sandbox.call_function::<_, i32>(instance_id, "debug_dump_all", ()).await.is_err());
+    
+    // Names missing from the module are refused before the lookup
+    let err = sandbox.call_function::<_, i32>(instance_id, "missing", ()).await.unwrap_err();
+    assert!(matches!(err, Error::SecurityViolation { .. }), "{}", err);
+    assert_eq!(sandbox.function_call_audit_logger().get_events().len(), 2);
+}
+
+#[tokio::test]
+async fn test_scoped_and_streaming_calls_are_checked() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let instance_id = instance(&mut sandbox, CallableFunctions::Denylist(vec!["debug_dump_all".to_string()]));
+    
+    let mut scope = sandbox.scope::<i32>();
+    assert!(scope.spawn(instance_id, "debug_dump_all", ()).is_err());
+    drop(scope);
+    assert!(sandbox.call_function_streaming::<_, i32>(instance_id, "debug_dump_all", ()).await.is_err());
+    assert!(sandbox.call_function_spilled(instance_id, "debug_dump_all", ()).is_err());
+    assert_eq!(sandbox.function_call_audit_logger().get_events().len(), 3);
+}
+
+#[tokio::test]
+async fn test_every_function_is_callable_by_default() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let instance_id = instance(&mut sandbox, CallableFunctions::default());
+    
+    let value: i32 = sandbox.call_function(instance_id, "debug_dump_all", ()).await.unwrap();
+    assert_eq!(value, 42);
+    assert!(sandbox.function_call_audit_logger().get_events().is_empty());
+    assert!(CallableFunctions::All.allows("anything"));
+    assert!(!CallableFunctions::Allowlist(Vec::new()).allows("add"));
+}
@@ -0,0 +1,127 @@
+//! Tests for capability explanations, capability diffs, and enforcement modes
+
+mod common;
+
+use wasm_sandbox::{Error, InstanceConfig, WasmSandbox};
+use wasm_sandbox::security::{
+    Capabilities, NetworkCapability, EnvironmentCapability, EnforcementMode,
+};
+use wasm_sandbox::security::audit::AuditLogger;
+use wasm_sandbox::security::capabilities::{
+    CapabilityManager, CapabilityDecision, CapabilityChange,
+};
+
+fn manager_for(capabilities: &Capabilities) -> CapabilityManager {
+    CapabilityManager::new(
+        capabilities.network.clone(),
+        capabilities.filesystem.clone(),
+        capabilities.environment.clone(),
+        capabilities.process.clone(),
+        capabilities.time.clone(),
+        capabilities.random.clone(),
+    )
+}
+
+#[test]
+fn test_explain_allowed_operation() {
+    let mut capabilities = Capabilities::minimal();
+    capabilities.network = NetworkCapability::Loopback;
+
+    let explanation = manager_for(&capabilities).explain("network", "connect", &["localhost", "8080"]);
+
+    assert_eq!(explanation.decision, CapabilityDecision::Allowed);
+    assert!(explanation.changes.is_empty());
+    assert!(explanation.reason.is_empty());
+}
+
+#[test]
+fn test_explain_denied_network_suggests_host() {
+    let capabilities = Capabilities::minimal();
+
+    let explanation = manager_for(&capabilities).explain("network", "connect", &["api.example.com", "443", "secure"]);
+
+    assert_eq!(explanation.decision, CapabilityDecision::Denied);
+    assert_eq!(explanation.changes.len(), 1);
+    assert!(matches!(
+        &explanation.changes[0],
+        CapabilityChange::AllowHost(spec) if spec.host == "api.example.com" && spec.secure
+    ));
+}
+
+#[test]
+fn test_explain_changes_allow_operation_when_applied() {
+    let mut capabilities = Capabilities::minimal();
+    capabilities.environment = EnvironmentCapability::Allowlist(vec!["PATH".to_string()]);
+
+    let explanation = manager_for(&capabilities).explain("env", "get", &["HOME"]);
+    assert!(!explanation.is_allowed());
+
+    explanation.apply_to(&mut capabilities);
+    let explanation = manager_for(&capabilities).explain("env", "get", &["HOME"]);
+    assert!(explanation.is_allowed());
+}
+
+#[test]
+fn test_explain_malformed_request_has_no_changes() {
+    let capabilities = Capabilities::minimal();
+
+    let explanation = manager_for(&capabilities).explain("network", "connect", &["localhost"]);
+
+    assert_eq!(explanation.decision, CapabilityDecision::Denied);
+    assert!(explanation.changes.is_empty());
+}
+
+#[test]
+fn test_audit_mode_allows_and_logs_violation() {
+    let capabilities = Capabilities::minimal()
+        .domain_enforcement("network", EnforcementMode::Audit);
+    let logger = AuditLogger::new(10);
+    let manager = CapabilityManager::from_capabilities(&capabilities)
+        .with_audit_logger(logger.clone());
+
+    assert!(manager.check("network", "connect", &["example.com", "80"]).is_ok());
+    assert!(manager.check("env", "get", &["HOME"]).is_err());
+    assert_eq!(logger.get_events().len(), 2);
+}
+
+/// Check that a domain applied at instance creation can't be audited
+fn assert_not_auditable(domain: &str, operation: &str, params: &[&str]) {
+    // Sandbox-wide audit mode leaves the domain enforced
+    let capabilities = Capabilities::minimal().enforcement(EnforcementMode::Audit);
+    assert_eq!(capabilities.enforcement.mode_for(domain), EnforcementMode::Enforce);
+    assert!(CapabilityManager::from_capabilities(&capabilities).check(domain, operation, params).is_err());
+
+    // Asking to audit it specifically is refused
+    let config = InstanceConfig {
+        capabilities: Capabilities::minimal().domain_enforcement(domain, EnforcementMode::Audit),
+        ..InstanceConfig::default()
+    };
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let result = common::try_create_instance(&mut sandbox, "(module)", Some(config));
+    assert!(matches!(result, Err(Error::Configuration { .. })), "{:?}", result);
+}
+
+#[test]
+fn test_filesystem_cannot_be_audited() {
+    assert_not_auditable("filesystem", "read", &["/etc/passwd"]);
+}
+
+#[test]
+fn test_environment_cannot_be_audited() {
+    assert_not_auditable("env", "get", &["HOME"]);
+}
+
+#[test]
+fn test_process_cannot_be_audited() {
+    assert_not_auditable("process", "exec", &["sh"]);
+}
+
+#[test]
+fn test_time_cannot_be_audited() {
+    assert_not_auditable("time", "set", &[]);
+}
+
+#[test]
+fn test_random_cannot_be_audited() {
+    assert_not_auditable("random", "secure", &[]);
+}
@@ -0,0 +1,125 @@
+//! Tests for capability extensions
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::security::audit::AuditEventType;
+use wasm_sandbox::security::{Capabilities, CustomCapability};
+use wasm_sandbox::{
+    CapabilityExtension, Error, ExtensionCall, HostNamespace, InstanceConfig, InstanceId, Result, WasmSandbox,
+};
+
+const SHOP_MODULE: &str = r#"
+(module
+  (import "payments" "charge" (func $charge (param i64) (result i64)))
+  (func (export "buy") (param $cents i64) (result i64)
+    (call $charge (local.get $cents))))
+"#;
+
+/// Lets instances charge up to a per-call limit in cents
+struct Payments;
+
+impl CapabilityExtension for Payments {
+    type Grant = i64;
+    
+    fn namespace(&self) -> &str {
+        "payments"
+    }
+    
+    fn functions(&self) -> Vec<String> {
+        vec!["charge".to_string()]
+    }
+    
+    fn verify(&self, capability: &CustomCapability) -> Result<i64> {
+        match capability {
+            CustomCapability::Numeric { value, .. } if *value >= 0 => Ok(*value),
+            other => Err(Error::InvalidInput {
+                field: "payments".to_string(),
+                reason: format!("expected a non-negative limit, got {:?}", other),
+                suggestion: None,
+            }),
+        }
+    }
+    
+    fn call(&self, call: &ExtensionCall<'_, i64>, args: &[HostValue]) -> Result<Vec<HostValue>> {
+        match args {
+            [HostValue::I64(cents)] if cents <= call.grant => Ok(vec![HostValue::I64(*cents)]),
+            _ => Err(Error::ResourceLimit { message: "charge exceeds the limit".to_string() }),
+        }
+    }
+}
+
+fn with_limit(limit: Option<CustomCapability>) -> InstanceConfig {
+    let mut capabilities = Capabilities::minimal();
+    if let Some(limit) = limit {
+        capabilities.add_custom("payments", limit);
+    }
+    InstanceConfig {
+        capabilities,
+        ..InstanceConfig::default()
+    }
+}
+
+fn limit(cents: i64) -> Option<CustomCapability> {
+    Some(CustomCapability::Numeric { value: cents, min: 0, max: i64::MAX })
+}
+
+fn shop(sandbox: &mut WasmSandbox, config: InstanceConfig) -> Result<InstanceId> {
+    let module_id = sandbox.load_module(SHOP_MODULE.as_bytes())?;
+    sandbox.create_instance(module_id, Some(config))
+}
+
+fn buy(sandbox: &WasmSandbox, instance_id: InstanceId, cents: i64) -> Result<Vec<HostValue>> {
+    sandbox.get_instance(instance_id).unwrap().instance.call_values("buy", &[HostValue::I64(cents)])
+}
+
+fn audited_calls(sandbox: &WasmSandbox) -> Vec<bool> {
+    sandbox.capability_audit_logger().get_events().into_iter()
+        .filter_map(|event| match event.event_type {
+            AuditEventType::ExtensionCall { allowed, .. } => Some(allowed),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_granted_instances_call_within_their_grant() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.register_capability_extension(Payments).unwrap();
+    assert_eq!(sandbox.capability_extensions().namespaces(), vec!["payments"]);
+    
+    let instance_id = shop(&mut sandbox, with_limit(limit(500))).unwrap();
+    assert_eq!(buy(&sandbox, instance_id, 250).unwrap(), vec![HostValue::I64(250)]);
+    assert!(buy(&sandbox, instance_id, 501).is_err());
+    assert_eq!(audited_calls(&sandbox), vec![true, true]);
+}
+
+#[test]
+fn test_calls_without_a_grant_are_denied_and_audited() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.register_capability_extension(Payments).unwrap();
+    
+    let instance_id = shop(&mut sandbox, with_limit(None)).unwrap();
+    let error = buy(&sandbox, instance_id, 1).unwrap_err();
+    assert!(error.to_string().contains("payments.charge"), "{}", error);
+    assert_eq!(audited_calls(&sandbox), vec![false]);
+}
+
+#[test]
+fn test_invalid_grants_are_rejected_at_creation() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.register_capability_extension(Payments).unwrap();
+    
+    let result = shop(&mut sandbox, with_limit(Some(CustomCapability::String("lots".to_string()))));
+    assert!(matches!(result, Err(Error::Configuration { .. })));
+    assert!(shop(&mut sandbox, with_limit(limit(-1))).is_err());
+}
+
+#[test]
+fn test_namespace_can_only_be_claimed_once() {
+    let sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.register_capability_extension(Payments).unwrap();
+    assert!(sandbox.register_capability_extension(Payments).is_err());
+    
+    // The extension's functions are a host namespace other providers can't take over
+    let takeover = HostNamespace::new("payments").function("charge", |args| Ok(args.to_vec()));
+    assert!(sandbox.register_host_namespace(takeover).is_err());
+}
@@ -0,0 +1,89 @@
+//! Tests for capabilities granted and revoked while instances run
+
+mod common;
+
+use std::time::Duration;
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::security::audit::AuditEventType;
+use wasm_sandbox::security::capabilities::ActiveCapabilities;
+use wasm_sandbox::security::Capabilities;
+use wasm_sandbox::{CapabilityChange, Error, HostNamespace, InstanceId, WasmSandbox};
+
+const PLUGIN_MODULE: &str = r#"
+(module
+  (import "acme.migrate" "export_rows" (func $export_rows (param i64) (result i64)))
+  (func (export "migrate") (param $rows i64) (result i64)
+    (call $export_rows (local.get $rows))))
+"#;
+
+fn sandbox_with_plugin() -> (WasmSandbox, InstanceId) {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.register_host_namespaces([HostNamespace::new("acme.migrate")
+        .function("export_rows", |args| Ok(args.to_vec()))])
+        .unwrap();
+    let instance_id = common::create_instance(&mut sandbox, PLUGIN_MODULE, None);
+    (sandbox, instance_id)
+}
+
+fn migrate(sandbox: &WasmSandbox, instance_id: InstanceId) -> wasm_sandbox::Result<Vec<HostValue>> {
+    sandbox.get_instance(instance_id).unwrap().instance.call_values("migrate", &[HostValue::I64(7)])
+}
+
+#[test]
+fn test_grant_applies_until_revoked() {
+    let (sandbox, instance_id) = sandbox_with_plugin();
+    assert!(migrate(&sandbox, instance_id).is_err());
+    
+    let grant = sandbox.grant(instance_id, CapabilityChange::AllowHostNamespace("acme.migrate".to_string()), None).unwrap();
+    assert_eq!(migrate(&sandbox, instance_id).unwrap(), vec![HostValue::I64(7)]);
+    assert_eq!(sandbox.capability_grants(instance_id).len(), 1);
+    
+    sandbox.revoke(instance_id, grant).unwrap();
+    assert!(migrate(&sandbox, instance_id).is_err());
+    assert!(sandbox.capability_grants(instance_id).is_empty());
+    assert!(matches!(sandbox.revoke(instance_id, grant), Err(Error::NotFound { .. })));
+    
+    let events: Vec<_> = sandbox.capability_audit_logger().get_events().into_iter().map(|event| event.event_type).collect();
+    assert!(matches!(&events[0], AuditEventType::CapabilityGranted { ttl_ms: None, .. }));
+    assert!(matches!(&events[1], AuditEventType::CapabilityRevoked { expired: false, .. }));
+}
+
+#[test]
+fn test_grant_lapses_after_its_ttl() {
+    let (sandbox, instance_id) = sandbox_with_plugin();
+    sandbox.grant(
+        instance_id,
+        CapabilityChange::AllowHostNamespace("acme.*".to_string()),
+        Some(Duration::from_millis(50)),
+    ).unwrap();
+    assert!(migrate(&sandbox, instance_id).is_ok());
+    
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(migrate(&sandbox, instance_id).is_err());
+    assert!(sandbox.capability_grants(instance_id).is_empty());
+    
+    let expired = sandbox.capability_audit_logger().get_events().into_iter()
+        .filter(|event| matches!(event.event_type, AuditEventType::CapabilityRevoked { expired: true, .. }))
+        .count();
+    assert_eq!(expired, 1);
+}
+
+#[test]
+fn test_grants_apply_over_function_overlays() {
+    let active = ActiveCapabilities::new(Capabilities::minimal());
+    active.grant(CapabilityChange::AllowSecret("db-password".to_string()), None);
+    assert!(active.current().secrets.allows("db-password"));
+    
+    let _scope = active.enter("migrate", Capabilities::minimal());
+    assert!(active.current().secrets.allows("db-password"));
+    assert!(!active.current().secrets.allows("api-key"));
+}
+
+#[test]
+fn test_grant_to_unknown_instance_fails() {
+    let (sandbox, _) = sandbox_with_plugin();
+    let result = sandbox.grant(InstanceId::new(), CapabilityChange::AllowModel("classifier".to_string()), None);
+    assert!(matches!(result, Err(Error::NotFound { .. })));
+    assert!(sandbox.capability_audit_logger().get_events().is_empty());
+}
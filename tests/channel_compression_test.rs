@@ -0,0 +1,133 @@
+//! Tests for transparent compression on IPC channels
+#![cfg(unix)]
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use wasm_sandbox::communication::{
+    CommunicationChannel, CompressionCodec, CompressionConfig, IpcChannel, IpcChannelConfig, IpcListener,
+    IpcRpcChannel, RpcChannelExt,
+};
+
+fn config(compression: Option<CompressionConfig>) -> IpcChannelConfig {
+    IpcChannelConfig {
+        timeout: Duration::from_secs(5),
+        compression,
+        ..IpcChannelConfig::default()
+    }
+}
+
+/// A JSON document large enough to be worth compressing
+fn document() -> String {
+    let rows: Vec<String> = (0..500)
+        .map(|i| format!(r#"{{"id":{},"name":"plugin-{}","enabled":true}}"#, i, i % 7))
+        .collect();
+    format!("[{}]", rows.join(","))
+}
+
+/// Wait for the peer's codec announcement to arrive
+fn negotiated(channel: &IpcChannel) -> Option<CompressionCodec> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while channel.compression_codec().is_none() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    channel.compression_codec()
+}
+
+#[test]
+fn test_large_messages_are_compressed() {
+    let (a, b) = IpcChannel::pair(config(Some(CompressionConfig::default()))).unwrap();
+    assert_eq!(negotiated(&a), Some(CompressionCodec::Zstd));
+    
+    let document = document();
+    a.send_to_guest(document.as_bytes()).unwrap();
+    a.send_to_guest(b"small").unwrap();
+    assert_eq!(b.receive_from_guest().unwrap(), document.as_bytes());
+    assert_eq!(b.receive_from_guest().unwrap(), b"small");
+    
+    let sent = a.compression_stats();
+    assert_eq!(sent.messages_compressed, 1);
+    assert_eq!(sent.raw_bytes_sent, document.len() as u64 + 5);
+    assert!(sent.send_ratio() < 0.5, "ratio {}", sent.send_ratio());
+    
+    let received = b.compression_stats();
+    assert_eq!(received.messages_decompressed, 1);
+    assert_eq!(received.raw_bytes_received, sent.raw_bytes_sent);
+    assert_eq!(received.wire_bytes_received, sent.wire_bytes_sent);
+}
+
+#[test]
+fn test_codec_is_negotiated_per_channel() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sandbox.sock");
+    let listener = IpcListener::bind(&path, config(Some(CompressionConfig::default()))).unwrap();
+    let client = std::thread::spawn({
+        let path = path.clone();
+        move || {
+            let gzip_only = CompressionConfig::default().codecs([CompressionCodec::Gzip]);
+            let channel = IpcChannel::connect(&path, config(Some(gzip_only))).unwrap();
+            assert_eq!(negotiated(&channel), Some(CompressionCodec::Gzip));
+            let message = channel.receive_from_guest().unwrap();
+            channel.send_to_guest(&message).unwrap();
+            channel.receive_from_guest().ok();
+        }
+    });
+    
+    let server = listener.accept().unwrap();
+    assert_eq!(negotiated(&server), Some(CompressionCodec::Gzip));
+    let document = document();
+    server.send_to_guest(document.as_bytes()).unwrap();
+    assert_eq!(server.receive_from_guest().unwrap(), document.as_bytes());
+    assert_eq!(server.compression_stats().messages_decompressed, 1);
+    server.close().unwrap();
+    client.join().unwrap();
+}
+
+#[test]
+fn test_one_sided_compression_sends_plain_messages() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sandbox.sock");
+    let listener = IpcListener::bind(&path, config(Some(CompressionConfig::default()))).unwrap();
+    let client = std::thread::spawn({
+        let path = path.clone();
+        move || {
+            let channel = IpcChannel::connect(&path, config(None)).unwrap();
+            let message = channel.receive_from_guest().unwrap();
+            channel.send_to_guest(&message).unwrap();
+            assert_eq!(channel.compression_codec(), None);
+            channel.receive_from_guest().ok();
+        }
+    });
+    
+    let server = listener.accept().unwrap();
+    let document = document();
+    server.send_to_guest(document.as_bytes()).unwrap();
+    assert_eq!(server.receive_from_guest().unwrap(), document.as_bytes());
+    
+    assert_eq!(server.compression_codec(), None);
+    let stats = server.compression_stats();
+    assert_eq!(stats.messages_compressed, 0);
+    assert_eq!(stats.wire_bytes_sent, stats.raw_bytes_sent);
+    server.close().unwrap();
+    client.join().unwrap();
+}
+
+#[test]
+fn test_rpc_payloads_are_compressed() {
+    let compression = CompressionConfig::default().min_size(1024);
+    let (a, b) = IpcChannel::pair(config(Some(compression))).unwrap();
+    let (a, b) = (Arc::new(a), Arc::new(b));
+    negotiated(&a);
+    negotiated(&b);
+    let supervisor = IpcRpcChannel::new(a.clone()).unwrap();
+    let mut sandbox = IpcRpcChannel::new(b.clone()).unwrap();
+    sandbox
+        .register_host_function("echo", |document: String| Ok(document))
+        .unwrap();
+    
+    let document = document();
+    let echoed: String = supervisor.call_guest_function("echo", &document).unwrap();
+    assert_eq!(echoed, document);
+    assert_eq!(a.compression_stats().messages_compressed, 1);
+    assert_eq!(a.compression_stats().messages_decompressed, 1);
+}
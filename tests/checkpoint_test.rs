@@ -0,0 +1,107 @@
+//! Tests for checkpoints requested by guests
+
+mod common;
+
+use wasm_sandbox::runtime::CHECKPOINT_IMPORT_MODULE;
+use wasm_sandbox::{Checkpoint, CheckpointConfig, Error, GuestErrorCode, InstanceConfig, InstanceId, WasmSandbox};
+
+/// `step` advances the job's progress in memory and in a global, then checkpoints
+const JOB_MODULE: &str = r#"
+(module
+  (import "sandbox_checkpoint" "checkpoint" (func $checkpoint (result i64)))
+  (memory (export "memory") 1)
+  (global $steps (export "steps") (mut i32) (i32.const 0))
+  (func (export "step") (result i64)
+    (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (i32.const 1)))
+    (global.set $steps (i32.add (global.get $steps) (i32.const 1)))
+    (call $checkpoint))
+  (func (export "progress") (result i32) (i32.load (i32.const 0)))
+  (func (export "step_count") (result i32) (global.get $steps)))
+"#;
+
+fn checkpointed_job(sandbox: &mut WasmSandbox, checkpoints: Option<CheckpointConfig>) -> InstanceId {
+    let instance_config = InstanceConfig { checkpoints, ..InstanceConfig::default() };
+    common::create_instance(sandbox, JOB_MODULE, Some(instance_config))
+}
+
+#[tokio::test]
+async fn test_guest_checkpoints_get_increasing_ids() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let instance_id = checkpointed_job(&mut sandbox, Some(CheckpointConfig::new(dir.path()).keep(2)));
+    
+    for expected in 1..=3 {
+        let checkpoint_id: i64 = sandbox.call_function(instance_id, "step", ()).await.unwrap();
+        assert_eq!(checkpoint_id, expected);
+    }
+    
+    let latest = sandbox.last_checkpoint(instance_id).unwrap();
+    assert_eq!(latest.checkpoint_id, 3);
+    assert_eq!(latest.instance_id, instance_id);
+    assert_eq!(latest.memory_bytes, 65536);
+    assert_eq!(Checkpoint::read(&latest.path).unwrap(), latest);
+    
+    // Only the newest two are kept
+    let ids: Vec<_> = Checkpoint::list(dir.path()).unwrap().iter().map(|checkpoint| checkpoint.checkpoint_id).collect();
+    assert_eq!(ids, [2, 3]);
+    
+    sandbox.remove_instance(instance_id);
+    assert!(sandbox.last_checkpoint(instance_id).is_none());
+}
+
+#[tokio::test]
+async fn test_job_resumes_from_its_latest_checkpoint_after_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    {
+        let mut sandbox = WasmSandbox::new().unwrap();
+        let instance_id = checkpointed_job(&mut sandbox, Some(CheckpointConfig::new(dir.path())));
+        for _ in 0..3 {
+            let _: i64 = sandbox.call_function(instance_id, "step", ()).await.unwrap();
+        }
+    }
+    
+    // A new sandbox stands in for the restarted host
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(JOB_MODULE.as_bytes()).unwrap();
+    let latest = Checkpoint::latest(dir.path()).unwrap().unwrap();
+    let instance_config = InstanceConfig {
+        checkpoints: Some(CheckpointConfig::new(dir.path())),
+        ..InstanceConfig::default()
+    };
+    let instance_id = sandbox.restore_checkpoint(module_id, &latest.path, Some(instance_config)).unwrap();
+    
+    let progress: i32 = sandbox.call_function(instance_id, "progress", ()).await.unwrap();
+    let steps: i32 = sandbox.call_function(instance_id, "step_count", ()).await.unwrap();
+    assert_eq!((progress, steps), (3, 3));
+    let checkpoint_id: i64 = sandbox.call_function(instance_id, "step", ()).await.unwrap();
+    assert_eq!(checkpoint_id, 4);
+}
+
+#[tokio::test]
+async fn test_checkpoints_are_unavailable_unless_enabled() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let instance_id = checkpointed_job(&mut sandbox, None);
+    let result: i64 = sandbox.call_function(instance_id, "step", ()).await.unwrap();
+    assert_eq!(result, GuestErrorCode::Unavailable.code());
+    assert!(sandbox.last_checkpoint(instance_id).is_none());
+    assert!(JOB_MODULE.contains(CHECKPOINT_IMPORT_MODULE));
+}
+
+#[tokio::test]
+async fn test_restore_rejects_other_modules_and_corrupt_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let instance_id = checkpointed_job(&mut sandbox, Some(CheckpointConfig::new(dir.path())));
+    let _: i64 = sandbox.call_function(instance_id, "step", ()).await.unwrap();
+    let latest = sandbox.last_checkpoint(instance_id).unwrap();
+    
+    let other = sandbox.load_module(br#"(module (memory (export "memory") 1))"#).unwrap();
+    let err = sandbox.restore_checkpoint(other, &latest.path, None).unwrap_err();
+    assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+    
+    let corrupt = dir.path().join("corrupt.ckpt");
+    std::fs::write(&corrupt, b"not a checkpoint").unwrap();
+    assert!(Checkpoint::read(&corrupt).is_err());
+    let module_id = sandbox.load_module(JOB_MODULE.as_bytes()).unwrap();
+    assert!(sandbox.restore_checkpoint(module_id, &corrupt, None).is_err());
+}
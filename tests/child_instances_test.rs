@@ -0,0 +1,138 @@
+//! Tests for child instances started by guests
+
+mod common;
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::{ChildCapability, InstanceConfig, InstanceId, WasmSandbox};
+
+// `spawn` starts the child module named at `name`, `call_child` calls its `echo`
+// export with "hello", and `kill` stops a child
+const PARENT_MODULE: &str = r#"
+(module
+  (import "sandbox_child" "spawn" (func $spawn (param i32 i32) (result i64)))
+  (import "sandbox_child" "call" (func $call (param i32 i32 i32 i32 i32) (result i64)))
+  (import "sandbox_child" "kill" (func $kill (param i32) (result i32)))
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (data (i32.const 0) "echo")
+  (data (i32.const 16) "large")
+  (data (i32.const 32) "hello")
+  
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  
+  (func (export "spawn") (param $name i32) (param $len i32) (result i64)
+    (call $spawn (local.get $name) (local.get $len)))
+  
+  (func (export "call_child") (param $child i32) (result i64)
+    (call $call (local.get $child) (i32.const 0) (i32.const 4) (i32.const 32) (i32.const 5)))
+  
+  (func (export "kill") (param $child i32) (result i32)
+    (call $kill (local.get $child))))
+"#;
+
+// Returns its input through the guest data ABI
+const ECHO_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (func (export "echo") (param $ptr i32) (param $len i32) (result i64)
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+      (i64.extend_i32_u (local.get $len)))))
+"#;
+
+// Needs 8 pages of memory to start
+const LARGE_MODULE: &str = r#"(module (memory (export "memory") 8))"#;
+
+fn parent(children: ChildCapability) -> (WasmSandbox, InstanceId) {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.register_child_module("echo", ECHO_MODULE.as_bytes()).unwrap();
+    sandbox.register_child_module("large", LARGE_MODULE.as_bytes()).unwrap();
+    
+    let mut config = InstanceConfig::default();
+    config.capabilities.children = children;
+    config.resource_limits.memory.max_memory_pages = 16;
+    let instance_id = common::create_instance(&mut sandbox, PARENT_MODULE, Some(config));
+    (sandbox, instance_id)
+}
+
+fn call(sandbox: &WasmSandbox, instance_id: InstanceId, function: &str, args: &[HostValue]) -> i64 {
+    match sandbox.get_instance(instance_id).unwrap().instance.call_values(function, args).unwrap()[..] {
+        [HostValue::I64(value)] => value,
+        [HostValue::I32(value)] => i64::from(value),
+        ref other => panic!("unexpected results {:?}", other),
+    }
+}
+
+fn spawn(sandbox: &WasmSandbox, instance_id: InstanceId, name: &str) -> i64 {
+    let offset = if name == "echo" { 0 } else { 16 };
+    call(sandbox, instance_id, "spawn", &[HostValue::I32(offset), HostValue::I32(name.len() as i32)])
+}
+
+#[test]
+fn test_guest_calls_child_and_children_go_with_parent() {
+    let (mut sandbox, instance_id) = parent(ChildCapability::modules(["echo"]));
+    
+    let child = spawn(&sandbox, instance_id, "echo");
+    assert_eq!(child, 1);
+    let packed = call(&sandbox, instance_id, "call_child", &[HostValue::I32(child as i32)]);
+    assert!(packed > 0, "call failed with {}", packed);
+    let output = sandbox.get_instance(instance_id).unwrap().instance
+        .read_memory_at((packed >> 32) as usize, (packed & 0xffff_ffff) as usize)
+        .unwrap();
+    assert_eq!(output, b"hello");
+    assert_eq!(sandbox.child_registry().child_count(instance_id), 1);
+    assert_eq!(sandbox.child_registry().module_names(), vec!["echo", "large"]);
+    
+    drop(sandbox.remove_instance(instance_id));
+    assert_eq!(sandbox.child_registry().child_count(instance_id), 0);
+}
+
+#[test]
+fn test_only_granted_registered_modules_start() {
+    let (sandbox, instance_id) = parent(ChildCapability::modules(["echo", "missing"]));
+    
+    // Permission denied, then not found
+    assert_eq!(spawn(&sandbox, instance_id, "large"), -1);
+    assert_eq!(call(&sandbox, instance_id, "spawn", &[HostValue::I32(32), HostValue::I32(5)]), -1);
+    sandbox.child_registry().unregister("echo");
+    assert_eq!(spawn(&sandbox, instance_id, "echo"), -4);
+    assert_eq!(sandbox.child_registry().child_count(instance_id), 0);
+}
+
+#[test]
+fn test_child_count_is_limited_and_kill_frees_a_slot() {
+    let (sandbox, instance_id) = parent(ChildCapability {
+        max_children: 1,
+        ..ChildCapability::modules(["echo"])
+    });
+    
+    assert_eq!(spawn(&sandbox, instance_id, "echo"), 1);
+    assert_eq!(spawn(&sandbox, instance_id, "echo"), -2);
+    assert_eq!(call(&sandbox, instance_id, "kill", &[HostValue::I32(1)]), 0);
+    assert_eq!(call(&sandbox, instance_id, "kill", &[HostValue::I32(1)]), -4);
+    assert_eq!(call(&sandbox, instance_id, "call_child", &[HostValue::I32(1)]), -4);
+    assert_eq!(spawn(&sandbox, instance_id, "echo"), 1);
+}
+
+#[test]
+fn test_children_get_a_share_of_the_parent_budget() {
+    // A quarter of 16 pages is too little for the large module; half is enough
+    let (sandbox, instance_id) = parent(ChildCapability::modules(["large"]));
+    assert!(spawn(&sandbox, instance_id, "large") < 0);
+    
+    let (sandbox, instance_id) = parent(ChildCapability {
+        budget_percent: 50,
+        ..ChildCapability::modules(["large"])
+    });
+    assert_eq!(spawn(&sandbox, instance_id, "large"), 1);
+}
@@ -0,0 +1,24 @@
+//! Setup shared by the integration tests
+//!
+//! Each test binary declares `mod common;` and uses only some of the helpers.
+#![allow(dead_code)]
+
+use wasm_sandbox::{InstanceConfig, InstanceId, WasmSandbox};
+
+/// Load a module into `sandbox` and create an instance of it
+pub fn try_create_instance(sandbox: &mut WasmSandbox, module: &str, config: Option<InstanceConfig>) -> wasm_sandbox::Result<InstanceId> {
+    let module_id = sandbox.load_module(module.as_bytes())?;
+    sandbox.create_instance(module_id, config)
+}
+
+/// Load a module into `sandbox` and create an instance of it, panicking on failure
+pub fn create_instance(sandbox: &mut WasmSandbox, module: &str, config: Option<InstanceConfig>) -> InstanceId {
+    try_create_instance(sandbox, module, config).expect("Failed to create instance")
+}
+
+/// Create a default sandbox holding one instance of a module
+pub fn instantiate(module: &str, config: Option<InstanceConfig>) -> (WasmSandbox, InstanceId) {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let instance_id = create_instance(&mut sandbox, module, config);
+    (sandbox, instance_id)
+}
@@ -0,0 +1,94 @@
+//! Tests for compiling modules on dedicated threads or in a child process
+
+use std::time::{Duration, Instant};
+
+use wasm_sandbox::runtime::compilation::{serve_compile_request, EngineSettings};
+use wasm_sandbox::runtime::RuntimeConfig;
+use wasm_sandbox::{CompilationIsolation, Error, SandboxConfig, SubprocessCompiler, WasmSandbox};
+
+const ADD_MODULE: &str = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))))
+"#;
+
+fn sandbox(compilation: CompilationIsolation) -> WasmSandbox {
+    WasmSandbox::with_config(SandboxConfig {
+        runtime: RuntimeConfig {
+            compilation,
+            ..RuntimeConfig::default()
+        },
+        ..SandboxConfig::default()
+    }).expect("Failed to create sandbox")
+}
+
+/// A compiler process that ignores its request and runs `script`
+#[cfg(unix)]
+fn shell_compiler(script: &str) -> SubprocessCompiler {
+    SubprocessCompiler::new("sh").arg("-c").arg(format!("cat > /dev/null; {}", script))
+}
+
+#[tokio::test]
+async fn test_thread_pool_compiles_modules() {
+    let mut sandbox = sandbox(CompilationIsolation::ThreadPool {
+        threads: 2,
+        timeout: Some(Duration::from_secs(30)),
+    });
+    let module_id = sandbox.load_module(ADD_MODULE.as_bytes()).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    let sum: i32 = sandbox.call_function(instance_id, "add", (2, 3)).await.unwrap();
+    assert_eq!(sum, 5);
+    
+    assert!(sandbox.load_module(b"not a module").is_err());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_subprocess_artifact_is_loaded() {
+    // Serve a request the way a compiler process would, keeping the artifact
+    let settings = serde_json::to_vec(&EngineSettings::from(&RuntimeConfig::default())).unwrap();
+    let mut request = (settings.len() as u32).to_le_bytes().to_vec();
+    request.extend_from_slice(&settings);
+    request.extend_from_slice(ADD_MODULE.as_bytes());
+    let mut artifact = Vec::new();
+    serve_compile_request(request.as_slice(), &mut artifact).unwrap();
+    
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("add.cwasm");
+    std::fs::write(&path, &artifact).unwrap();
+    
+    let mut sandbox = sandbox(CompilationIsolation::Subprocess(shell_compiler(&format!("cat '{}'", path.display()))));
+    let module_id = sandbox.load_module(ADD_MODULE.as_bytes()).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    let sum: i32 = sandbox.call_function(instance_id, "add", (20, 22)).await.unwrap();
+    assert_eq!(sum, 42);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_subprocess_failures_are_contained() {
+    let crashing = sandbox(CompilationIsolation::Subprocess(shell_compiler("echo 'compiler bug' >&2; exit 3")));
+    match crashing.load_module(ADD_MODULE.as_bytes()) {
+        Err(Error::Compilation { message }) => assert!(message.contains("compiler bug")),
+        other => panic!("expected a compilation error, got {:?}", other.map(|_| ())),
+    }
+    
+    // Output that isn't an artifact for this engine is rejected
+    let garbage = sandbox(CompilationIsolation::Subprocess(shell_compiler("echo garbage")));
+    assert!(matches!(garbage.load_module(ADD_MODULE.as_bytes()), Err(Error::Compilation { .. })));
+    
+    let missing = sandbox(CompilationIsolation::Subprocess(SubprocessCompiler::new("/nonexistent/compiler")));
+    assert!(matches!(missing.load_module(ADD_MODULE.as_bytes()), Err(Error::Compilation { .. })));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_stalled_compiler_process_is_killed() {
+    let compiler = shell_compiler("sleep 30").timeout(Duration::from_millis(200));
+    let sandbox = sandbox(CompilationIsolation::Subprocess(compiler));
+    
+    let started = Instant::now();
+    let result = sandbox.load_module(ADD_MODULE.as_bytes());
+    assert!(matches!(result, Err(Error::Timeout { .. })));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,110 @@
+//! Tests for reloading a sandbox's configuration while it runs
+
+use std::time::Duration;
+
+use wasm_sandbox::runtime::RuntimeConfig;
+use wasm_sandbox::{FuelBudget, InstanceConfig, RedactionPolicy, ResultCacheConfig, SandboxConfig, WasmSandbox};
+
+/// `burn` loops `n` times
+const BURN_MODULE: &str = r#"
+(module
+  (func (export "burn") (param $n i32) (result i32)
+    (loop $again
+      (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+      (br_if $again (local.get $n)))
+    (i32.const 1)))
+"#;
+
+fn sandbox_with_fuel(fuel: u64) -> WasmSandbox {
+    let mut config = SandboxConfig::default();
+    config.default_instance_config.resource_limits.fuel = Some(fuel);
+    WasmSandbox::with_config(config).unwrap()
+}
+
+#[test]
+fn test_policies_apply_and_the_runtime_is_deferred() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let unchanged = sandbox.apply_config(sandbox.config().clone()).unwrap();
+    assert!(unchanged.is_empty(), "{}", unchanged);
+    
+    let mut config = sandbox.config().clone();
+    config.redaction = RedactionPolicy::standard();
+    config.result_cache = ResultCacheConfig::default().max_entries(4);
+    config.runtime = RuntimeConfig { debug_info: !config.runtime.debug_info, ..config.runtime };
+    let diff = sandbox.apply_config(config).unwrap();
+    
+    assert!(diff.is_applied("redaction") && diff.is_applied("result_cache"), "{}", diff);
+    assert!(diff.is_deferred("runtime"), "{}", diff);
+    assert!(diff.to_string().contains("deferred runtime:"), "{}", diff);
+    assert!(sandbox.config().redaction.is_active());
+    assert_eq!(sandbox.result_cache().config().max_entries, 4);
+    assert_eq!(sandbox.config().runtime.debug_info, SandboxConfig::default().runtime.debug_info);
+}
+
+#[tokio::test]
+async fn test_raised_fuel_reaches_running_instances_on_the_old_defaults() {
+    let mut sandbox = sandbox_with_fuel(20_000);
+    let module_id = sandbox.load_module(BURN_MODULE.as_bytes()).unwrap();
+    let defaulted = sandbox.create_instance(module_id, None).unwrap();
+    let mut custom_config = InstanceConfig::default();
+    custom_config.resource_limits.fuel = Some(30_000);
+    let custom = sandbox.create_instance(module_id, Some(custom_config)).unwrap();
+    
+    assert!(sandbox.call_function::<_, i32>(defaulted, "burn", (50_000,)).await.is_err());
+    
+    let mut config = sandbox.config().clone();
+    config.default_instance_config.resource_limits.fuel = Some(10_000_000);
+    let diff = sandbox.apply_config(config).unwrap();
+    assert!(diff.is_applied("default_instance_config"), "{}", diff);
+    assert!(diff.to_string().contains("raised on 1 running instances"), "{}", diff);
+    
+    let done: i32 = sandbox.call_function(defaulted, "burn", (50_000,)).await.unwrap();
+    assert_eq!(done, 1);
+    assert_eq!(sandbox.get_instance(defaulted).unwrap().config.resource_limits.fuel, Some(10_000_000));
+    assert_eq!(sandbox.get_instance(custom).unwrap().config.resource_limits.fuel, Some(30_000));
+    
+    // Taking fuel away from a running call isn't safe
+    let mut config = sandbox.config().clone();
+    config.default_instance_config.resource_limits.fuel = Some(1_000);
+    let diff = sandbox.apply_config(config).unwrap();
+    assert!(diff.is_deferred("default_instance_config.resource_limits.fuel"), "{}", diff);
+    assert_eq!(sandbox.get_instance(defaulted).unwrap().config.resource_limits.fuel, Some(10_000_000));
+    let fresh = sandbox.create_instance(module_id, None).unwrap();
+    assert_eq!(sandbox.get_instance(fresh).unwrap().config.resource_limits.fuel, Some(1_000));
+}
+
+#[test]
+fn test_time_limits_update_running_instances() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(BURN_MODULE.as_bytes()).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    
+    let mut config = sandbox.config().clone();
+    config.default_instance_config.resource_limits.time.max_idle_time_ms = Some(60_000);
+    config.default_instance_config.resource_limits.time.max_timers += 1;
+    let diff = sandbox.apply_config(config).unwrap();
+    
+    assert!(diff.to_string().contains("updated on 1 running instances"), "{}", diff);
+    assert!(diff.is_deferred("default_instance_config.resource_limits.time.max_timers"), "{}", diff);
+    let time = &sandbox.get_instance(instance_id).unwrap().config.resource_limits.time;
+    assert_eq!(time.max_idle_time_ms, Some(60_000));
+}
+
+#[test]
+fn test_invalid_configs_change_nothing() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let mut config = sandbox.config().clone();
+    config.redaction = RedactionPolicy::standard();
+    config.runtime.enable_fuel = false;
+    config.fuel_budget = Some(FuelBudget::new(1_000, Duration::from_secs(1)));
+    
+    assert!(sandbox.apply_config(config).is_err());
+    assert!(!sandbox.config().redaction.is_active());
+    
+    // A budget can't be introduced into a running sandbox
+    let mut config = sandbox.config().clone();
+    config.fuel_budget = Some(FuelBudget::new(1_000, Duration::from_secs(1)));
+    let diff = sandbox.apply_config(config).unwrap();
+    assert!(diff.is_deferred("fuel_budget"), "{}", diff);
+    assert!(sandbox.config().fuel_budget.is_none());
+}
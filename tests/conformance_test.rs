@@ -0,0 +1,111 @@
+//! Tests for the guest ABI conformance suite
+
+use wasm_sandbox::conformance::{CheckOutcome, ConformanceSuite};
+use wasm_sandbox::security::ResourceLimits;
+
+/// Bump allocator shared by the test modules, growing memory as needed
+const ALLOCATOR: &str = r#"
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (func $alloc (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (local.get $ptr) (local.get $len)))
+    (block $done
+      (loop $grow
+        (br_if $done (i32.le_u (global.get $next) (i32.mul (memory.size) (i32.const 65536))))
+        (if (i32.eq (memory.grow (i32.const 1)) (i32.const -1)) (then unreachable))
+        (br $grow)))
+    (local.get $ptr))
+"#;
+
+const ECHO: &str = r#"
+  (func (export "conformance_echo") (param $ptr i32) (param $len i32) (result i64)
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+      (i64.extend_i32_u (local.get $len))))
+"#;
+
+fn module(parts: &[&str]) -> Vec<u8> {
+    format!("(module {})", parts.concat()).into_bytes()
+}
+
+#[test]
+fn test_conforming_module_passes_every_check() {
+    let fail = r#"(func (export "conformance_fail") (param i32 i32) (result i64) unreachable)"#;
+    let report = ConformanceSuite::new().run(&module(&[ALLOCATOR, ECHO, fail])).unwrap();
+    
+    assert!(report.passed(), "{}", report);
+    assert_eq!(report.checks.len(), 5);
+    assert!(report.checks.iter().all(|check| check.outcome == CheckOutcome::Passed));
+    
+    let output = report.to_string();
+    assert!(output.starts_with("running 5 checks\ncheck abi::memory_export ... ok\n"));
+    assert!(output.contains("check result: ok. 5 passed; 0 failed; 0 ignored"));
+    report.assert_passed();
+}
+
+#[test]
+fn test_missing_test_exports_are_ignored() {
+    let report = ConformanceSuite::new().run(&module(&[ALLOCATOR])).unwrap();
+    
+    assert!(report.passed());
+    assert_eq!(report.outcome("abi::allocator"), Some(&CheckOutcome::Passed));
+    for name in ["serialization::round_trip", "errors::propagation", "payload::large"] {
+        assert!(matches!(report.outcome(name), Some(CheckOutcome::Ignored(_))), "{}", name);
+    }
+    assert!(report.to_string().contains("2 passed; 0 failed; 3 ignored"));
+    
+    // Without an allocator the module can't receive input at all
+    let report = ConformanceSuite::new().run(br#"(module (memory (export "memory") 1))"#).unwrap();
+    assert!(matches!(report.outcome("abi::allocator"), Some(CheckOutcome::Failed(reason)) if reason.contains("alloc")));
+}
+
+#[test]
+fn test_broken_allocator_and_echo_fail() {
+    // Every allocation lands at the same address, and the echo drops the last byte
+    let broken = r#"
+      (memory (export "memory") 1)
+      (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+      (func (export "conformance_echo") (param $ptr i32) (param $len i32) (result i64)
+        (i64.or
+          (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+          (i64.extend_i32_u (i32.sub (local.get $len) (i32.const 1)))))
+    "#;
+    let report = ConformanceSuite::new().run(&module(&[broken])).unwrap();
+    
+    assert!(!report.passed());
+    assert!(matches!(report.outcome("abi::allocator"), Some(CheckOutcome::Failed(reason)) if reason.contains("overlap")));
+    assert!(matches!(report.outcome("serialization::round_trip"), Some(CheckOutcome::Failed(_))));
+    let output = report.to_string();
+    assert!(output.contains("check abi::allocator ... FAILED"));
+    assert!(output.contains("failures:\n    abi::allocator: allocations at 1024 and 1024 overlap"));
+    assert!(output.contains("check result: FAILED."));
+    
+    let panic = std::panic::catch_unwind(|| report.assert_passed()).unwrap_err();
+    assert!(panic.downcast_ref::<String>().unwrap().contains("does not conform"));
+}
+
+#[test]
+fn test_error_and_payload_checks_use_configured_exports_and_limits() {
+    // The "error" export succeeds, and memory can't hold the large payload
+    let succeeds = r#"(func (export "maybe_fail") (param $ptr i32) (param $len i32) (result i64) (i64.const 0))"#;
+    let mut limits = ResourceLimits::default();
+    limits.memory.max_memory_pages = 4;
+    let report = ConformanceSuite::new()
+        .error_export("maybe_fail")
+        .large_payload_bytes(1024 * 1024)
+        .resource_limits(limits)
+        .run(&module(&[ALLOCATOR, ECHO, succeeds]))
+        .unwrap();
+    
+    assert_eq!(report.outcome("serialization::round_trip"), Some(&CheckOutcome::Passed));
+    assert!(matches!(report.outcome("errors::propagation"), Some(CheckOutcome::Failed(reason)) if reason.contains("instead of failing")));
+    assert!(matches!(report.outcome("payload::large"), Some(CheckOutcome::Failed(reason)) if reason.contains("1048576 bytes")));
+    
+    let report = ConformanceSuite::new()
+        .large_payload_bytes(128 * 1024)
+        .run(&module(&[ALLOCATOR, ECHO]))
+        .unwrap();
+    assert_eq!(report.outcome("payload::large"), Some(&CheckOutcome::Passed));
+}
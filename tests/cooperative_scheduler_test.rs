@@ -0,0 +1,115 @@
+//! Tests for time-sliced scheduling of many instances on one thread
+
+use wasm_sandbox::runtime::scheduler::DEFAULT_FUEL_SLICE;
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::{CooperativeScheduler, Error, ResourceKind, SchedulerConfig};
+
+// `spin` counts down from its argument and returns how many iterations it ran
+const SPIN_MODULE: &str = r#"
+(module
+  (func (export "spin") (param $n i64) (result i64)
+    (local $i i64)
+    (block $done
+      (loop $next
+        (br_if $done (i64.ge_u (local.get $i) (local.get $n)))
+        (local.set $i (i64.add (local.get $i) (i64.const 1)))
+        (br $next)))
+    (local.get $i))
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))))
+"#;
+
+fn scheduler(config: SchedulerConfig, instances: usize) -> (CooperativeScheduler, Vec<wasm_sandbox::InstanceId>) {
+    let mut scheduler = CooperativeScheduler::new(config).expect("Failed to create scheduler");
+    let module_id = scheduler.load_module(SPIN_MODULE.as_bytes()).expect("Failed to load module");
+    let instances = (0..instances)
+        .map(|_| scheduler.create_instance(module_id).expect("Failed to create instance"))
+        .collect();
+    (scheduler, instances)
+}
+
+#[test]
+fn test_calls_complete_with_results() {
+    let (mut scheduler, instances) = scheduler(SchedulerConfig::default(), 1);
+    
+    let sum = scheduler.call(instances[0], "add", &[HostValue::I32(2), HostValue::I32(3)]).unwrap();
+    assert_eq!(sum, vec![HostValue::I32(5)]);
+    
+    // Small integers are widened to the export's i64 parameter
+    let spun = scheduler.call(instances[0], "spin", &[HostValue::I32(1000)]).unwrap();
+    assert_eq!(spun, vec![HostValue::I64(1000)]);
+    assert!(scheduler.call(instances[0], "missing", &[]).is_err());
+}
+
+#[test]
+fn test_turns_interleave_instances_fairly() {
+    let (mut scheduler, instances) = scheduler(SchedulerConfig::default(), 3);
+    let long = [
+        scheduler.spawn(instances[0], "spin", &[HostValue::I64(1_000_000)]).unwrap(),
+        scheduler.spawn(instances[1], "spin", &[HostValue::I64(1_000_000)]).unwrap(),
+    ];
+    let short = scheduler.spawn(instances[2], "spin", &[HostValue::I64(10)]).unwrap();
+    
+    // The short call is not starved by the long ones ahead of it
+    assert_eq!(scheduler.run_turn(), 2);
+    assert_eq!(scheduler.take_result(short).unwrap().unwrap(), vec![HostValue::I64(10)]);
+    assert!(scheduler.take_result(long[0]).is_none());
+    
+    for _ in 0..10 {
+        scheduler.run_turn();
+    }
+    let first = scheduler.metrics(instances[0]).unwrap();
+    let second = scheduler.metrics(instances[1]).unwrap();
+    assert_eq!(first.slices, 11);
+    assert_eq!(first.slices, second.slices);
+    assert_eq!(scheduler.metrics(instances[2]).unwrap().slices, 1);
+    
+    scheduler.run_until_idle();
+    for task in long {
+        assert_eq!(scheduler.take_result(task).unwrap().unwrap(), vec![HostValue::I64(1_000_000)]);
+    }
+    let first = scheduler.metrics(instances[0]).unwrap();
+    assert_eq!(first.completed_calls, 1);
+    assert!(first.fuel_consumed > 1_000_000);
+    assert!(first.slices >= first.fuel_consumed / DEFAULT_FUEL_SLICE);
+    assert!(first.max_wait <= first.total_wait);
+}
+
+#[test]
+fn test_calls_to_one_instance_run_in_order() {
+    let (mut scheduler, instances) = scheduler(SchedulerConfig::default(), 1);
+    let first = scheduler.spawn(instances[0], "spin", &[HostValue::I64(100_000)]).unwrap();
+    let second = scheduler.spawn(instances[0], "add", &[HostValue::I32(1), HostValue::I32(1)]).unwrap();
+    assert_eq!(scheduler.metrics(instances[0]).unwrap().queued_calls, 2);
+    
+    scheduler.run_turn();
+    assert_eq!(scheduler.metrics(instances[0]).unwrap().queued_calls, 1);
+    assert!(scheduler.take_result(second).is_none());
+    
+    scheduler.run_until_idle();
+    assert!(scheduler.take_result(first).unwrap().is_ok());
+    assert!(scheduler.take_result(second).unwrap().is_ok());
+}
+
+#[test]
+fn test_call_budget_and_removal() {
+    let config = SchedulerConfig {
+        fuel_slice: 1_000,
+        max_fuel_per_call: Some(50_000),
+    };
+    let (mut scheduler, instances) = scheduler(config, 2);
+    
+    match scheduler.call(instances[0], "spin", &[HostValue::I64(1_000_000)]) {
+        Err(Error::ResourceExhausted { kind: ResourceKind::Fuel, limit, .. }) => assert_eq!(limit, 50_000),
+        other => panic!("expected fuel exhaustion, got {:?}", other),
+    }
+    
+    let task = scheduler.spawn(instances[1], "spin", &[HostValue::I64(10_000)]).unwrap();
+    scheduler.run_turn();
+    assert!(scheduler.remove_instance(instances[1]));
+    assert!(scheduler.take_result(task).unwrap().is_err());
+    assert_eq!(scheduler.instance_count(), 1);
+    assert_eq!(scheduler.run_turn(), 0);
+    
+    assert!(CooperativeScheduler::new(SchedulerConfig { fuel_slice: 0, ..SchedulerConfig::default() }).is_err());
+}
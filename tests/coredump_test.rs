@@ -0,0 +1,93 @@
+//! Tests for coredumps of trapped guest calls
+
+mod common;
+
+use wasm_sandbox::{CoredumpConfig, InstanceConfig, InstanceId, SandboxConfig, SandboxError, WasmSandbox};
+
+// `crash` stores its argument, then traps
+const CRASHING_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $calls (export "calls") (mut i32) (i32.const 0))
+  (func (export "crash") (param $x i32) (result i32)
+    (global.set $calls (i32.add (global.get $calls) (i32.const 1)))
+    (i32.store (i32.const 0) (local.get $x))
+    unreachable))
+"#;
+
+fn instance(coredumps: CoredumpConfig, enable_debug: bool) -> (WasmSandbox, InstanceId) {
+    let mut sandbox = WasmSandbox::with_config(SandboxConfig {
+        coredumps,
+        ..SandboxConfig::default()
+    }).unwrap();
+    let config = InstanceConfig {
+        enable_debug,
+        ..InstanceConfig::default()
+    };
+    let instance_id = common::create_instance(&mut sandbox, CRASHING_MODULE, Some(config));
+    (sandbox, instance_id)
+}
+
+fn call_error(result: wasm_sandbox::Result<i32>) -> String {
+    match result {
+        Err(SandboxError::FunctionCall { reason, .. }) => reason,
+        other => panic!("expected a failed call, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn traps_in_debug_instances_write_a_coredump() {
+    let directory = tempfile::tempdir().unwrap();
+    let (sandbox, instance_id) = instance(CoredumpConfig::default().directory(directory.path()), true);
+    let reason = call_error(sandbox.call_function(instance_id, "crash", 7).await);
+    
+    let coredump = sandbox.last_coredump(instance_id).unwrap();
+    assert!(reason.contains(&coredump.path.display().to_string()));
+    assert_eq!(coredump.function_name, "crash");
+    assert_eq!(coredump.call_id, sandbox.last_call_id(instance_id));
+    assert!(coredump.path.starts_with(directory.path()));
+    
+    // A Wasm module whose first section is the `core` custom section
+    let bytes = std::fs::read(&coredump.path).unwrap();
+    assert_eq!(bytes.len() as u64, coredump.size);
+    assert!(bytes.starts_with(b"\0asm"));
+    assert_eq!(&bytes[8..9], &[0]);
+    assert!(bytes.windows(4).any(|window| window == b"core"));
+}
+
+#[tokio::test]
+async fn other_instances_write_no_coredump() {
+    let directory = tempfile::tempdir().unwrap();
+    let (sandbox, instance_id) = instance(CoredumpConfig::default().directory(directory.path()), false);
+    let reason = call_error(sandbox.call_function(instance_id, "crash", 7).await);
+    
+    assert!(!reason.contains("coredump"));
+    assert!(reason.contains("unreachable"));
+    assert!(sandbox.last_coredump(instance_id).is_none());
+    assert!(!directory.path().exists() || std::fs::read_dir(directory.path()).unwrap().next().is_none());
+}
+
+#[tokio::test]
+async fn coredumps_over_the_cap_are_skipped() {
+    let directory = tempfile::tempdir().unwrap();
+    let config = CoredumpConfig::default().directory(directory.path()).max_bytes(1024);
+    let (sandbox, instance_id) = instance(config, true);
+    let reason = call_error(sandbox.call_function(instance_id, "crash", 7).await);
+    
+    assert!(!reason.contains("coredump"));
+    assert!(sandbox.last_coredump(instance_id).is_none());
+}
+
+#[tokio::test]
+async fn each_trap_gets_its_own_coredump() {
+    let directory = tempfile::tempdir().unwrap();
+    let (sandbox, instance_id) = instance(CoredumpConfig::default().directory(directory.path()), true);
+    call_error(sandbox.call_function(instance_id, "crash", 1).await);
+    let first = sandbox.last_coredump(instance_id).unwrap();
+    call_error(sandbox.call_function(instance_id, "crash", 2).await);
+    let second = sandbox.last_coredump(instance_id).unwrap();
+    
+    assert_ne!(first.path, second.path);
+    assert!(first.path.exists() && second.path.exists());
+    assert_ne!(std::fs::read(&first.path).unwrap(), std::fs::read(&second.path).unwrap());
+}
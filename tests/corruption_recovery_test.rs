@@ -0,0 +1,96 @@
+//! Tests for recreating instances whose calls trap as if memory were corrupted
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use wasm_sandbox::{InstanceConfig, InstanceId, RecoveryNotice, RecoveryPolicy, WasmSandbox};
+
+// `process` counts its calls in memory and "corrupts" itself from the second
+// call on; `crash` always reads out of bounds and `abort` hits `unreachable`
+const FLAKY_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "process") (param $x i32) (result i32)
+    (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (i32.const 1)))
+    (if (i32.gt_u (i32.load (i32.const 0)) (i32.const 1))
+      (then (drop (i32.load (i32.const 0x7fffffff)))))
+    (i32.add (local.get $x) (i32.load (i32.const 0))))
+  (func (export "crash") (param $x i32) (result i32)
+    (i32.load (i32.const 0x7fffffff)))
+  (func (export "abort") (param $x i32) (result i32)
+    unreachable))
+"#;
+
+fn instantiate(sandbox: &mut WasmSandbox, recovery: Option<RecoveryPolicy>) -> InstanceId {
+    let config = InstanceConfig {
+        recovery,
+        ..InstanceConfig::default()
+    };
+    common::create_instance(sandbox, FLAKY_MODULE, Some(config))
+}
+
+#[tokio::test]
+async fn test_corrupted_instance_is_recreated_and_retried() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let notices = Arc::new(Mutex::new(Vec::<RecoveryNotice>::new()));
+    let recorded = notices.clone();
+    sandbox.on_recovery(move |notice| recorded.lock().unwrap().push(notice.clone()));
+    let instance_id = instantiate(&mut sandbox, Some(RecoveryPolicy::default()));
+    
+    let first: i32 = sandbox.call_function(instance_id, "process", 10).await.unwrap();
+    assert_eq!(first, 11);
+    
+    // The second call traps, and is answered by a fresh instance whose counter starts over
+    let second: i32 = sandbox.call_function(instance_id, "process", 20).await.unwrap();
+    assert_eq!(second, 21);
+    
+    let notices = notices.lock().unwrap();
+    assert_eq!(notices.len(), 1);
+    assert_eq!((notices[0].instance_id, notices[0].function_name.as_str(), notices[0].recoveries), (instance_id, "process", 1));
+    assert!(notices[0].error.contains("out of bounds memory access"), "{}", notices[0].error);
+    
+    let metrics = sandbox.recovery_metrics();
+    assert_eq!((metrics.recoveries, metrics.retries_succeeded, metrics.retries_failed), (1, 1, 0));
+}
+
+#[tokio::test]
+async fn test_instances_without_a_policy_keep_failing() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let instance_id = instantiate(&mut sandbox, None);
+    
+    let _: i32 = sandbox.call_function(instance_id, "process", 10).await.unwrap();
+    for _ in 0..2 {
+        let result: wasm_sandbox::Result<i32> = sandbox.call_function(instance_id, "process", 10).await;
+        assert!(result.is_err());
+    }
+    assert_eq!(sandbox.recovery_metrics().recoveries, 0);
+}
+
+#[tokio::test]
+async fn test_only_matching_traps_are_retried() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let default_policy = instantiate(&mut sandbox, Some(RecoveryPolicy::default()));
+    let result: wasm_sandbox::Result<i32> = sandbox.call_function(default_policy, "abort", 0).await;
+    assert!(result.is_err());
+    assert_eq!(sandbox.recovery_metrics().recoveries, 0);
+    
+    let extended = instantiate(&mut sandbox, Some(RecoveryPolicy::default().signature("unreachable")));
+    let result: wasm_sandbox::Result<i32> = sandbox.call_function(extended, "abort", 0).await;
+    assert!(result.is_err());
+    let metrics = sandbox.recovery_metrics();
+    assert_eq!((metrics.recoveries, metrics.retries_failed), (1, 1));
+}
+
+#[tokio::test]
+async fn test_recoveries_are_capped() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let instance_id = instantiate(&mut sandbox, Some(RecoveryPolicy::default().max_recoveries(Some(1))));
+    
+    for _ in 0..3 {
+        let result: wasm_sandbox::Result<i32> = sandbox.call_function(instance_id, "crash", 0).await;
+        assert!(result.is_err());
+    }
+    let metrics = sandbox.recovery_metrics();
+    assert_eq!((metrics.recoveries, metrics.retries_failed, metrics.exhausted), (1, 1, 2));
+}
@@ -0,0 +1,145 @@
+//! Tests for the operator debug console
+
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use wasm_sandbox::observability::console::{ConsoleClient, ConsoleRequest, ConsoleResponse, DebugConsole};
+use wasm_sandbox::security::audit::AuditEventType;
+use wasm_sandbox::{CapabilityChange, InstanceId, WasmSandbox};
+
+const TOKEN: &str = "s3cret-token";
+
+const ADD_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))))
+"#;
+
+fn sandbox_with_instances(count: usize) -> (WasmSandbox, Vec<InstanceId>) {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(ADD_MODULE.as_bytes()).unwrap();
+    let instance_ids = (0..count).map(|_| sandbox.create_instance(module_id, None).unwrap()).collect();
+    (sandbox, instance_ids)
+}
+
+/// Serve the console on a loopback port while `client` runs against its address
+async fn with_console<F, Fut>(sandbox: &WasmSandbox, client: F)
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let console = DebugConsole::new(TOKEN);
+    tokio::select! {
+        result = console.serve_tcp(sandbox, listener) => panic!("console stopped: {:?}", result),
+        () = client(addr) => {}
+    }
+}
+
+#[tokio::test]
+async fn test_operator_lists_and_inspects_instances() {
+    let (sandbox, instance_ids) = sandbox_with_instances(2);
+    let first = instance_ids[0];
+    let _: i32 = sandbox.call_function(first, "add", (2, 3)).await.unwrap();
+    
+    with_console(&sandbox, |addr| async move {
+        let mut client = ConsoleClient::connect_tcp(addr, TOKEN).await.unwrap();
+        
+        let instances = client.list_instances().await.unwrap();
+        assert_eq!(instances.len(), 2);
+        let mut listed: Vec<_> = instances.iter().map(|instance| instance.instance_id).collect();
+        let mut expected = instance_ids.clone();
+        listed.sort_by_key(InstanceId::as_uuid);
+        expected.sort_by_key(InstanceId::as_uuid);
+        assert_eq!(listed, expected);
+        assert!(instances.iter().all(|instance| instance.memory_bytes == 65536 && !instance.paused && !instance.failing));
+        
+        let details = client.instance(first).await.unwrap();
+        assert_eq!(details.summary.instance_id, first);
+        assert!(details.config.contains("resource_limits"), "{}", details.config);
+        assert!(details.capabilities.contains("network"), "{}", details.capabilities);
+        
+        let err = client.instance(InstanceId::new()).await.unwrap_err();
+        assert!(err.to_string().contains("No instance"), "{}", err);
+    }).await;
+}
+
+#[tokio::test]
+async fn test_connections_must_authenticate() {
+    let (sandbox, _) = sandbox_with_instances(1);
+    
+    with_console(&sandbox, |addr| async move {
+        let err = ConsoleClient::connect_tcp(addr.as_str(), "wrong").await.err().unwrap();
+        assert!(err.to_string().contains("Invalid token"), "{}", err);
+        
+        // Requests before authenticating are refused and the connection closed
+        let mut stream = tokio::net::TcpStream::connect(addr.as_str()).await.unwrap();
+        stream.write_all(b"{\"command\":\"list_instances\"}\n").await.unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        let response: ConsoleResponse = serde_json::from_str(&line).unwrap();
+        assert!(matches!(response, ConsoleResponse::Error { message } if message == "Authenticate first"));
+        line.clear();
+        assert_eq!(reader.read_line(&mut line).await.unwrap(), 0);
+    }).await;
+}
+
+#[tokio::test]
+async fn test_pause_resume_and_terminate() {
+    let (sandbox, instance_ids) = sandbox_with_instances(1);
+    let instance_id = instance_ids[0];
+    
+    with_console(&sandbox, |addr| async {
+        let mut client = ConsoleClient::connect_tcp(addr, TOKEN).await.unwrap();
+        client.pause(instance_id).await.unwrap();
+        assert!(sandbox.is_paused(instance_id));
+        assert!(client.list_instances().await.unwrap()[0].paused);
+        
+        // Calls wait while the instance is paused
+        let call = sandbox.call_function::<_, i32>(instance_id, "add", (1, 1));
+        tokio::pin!(call);
+        assert!(tokio::time::timeout(Duration::from_millis(100), &mut call).await.is_err());
+        client.resume(instance_id).await.unwrap();
+        assert_eq!(call.await.unwrap(), 2);
+        
+        client.terminate(instance_id).await.unwrap();
+        assert!(client.list_instances().await.unwrap()[0].terminated);
+    }).await;
+    
+    let err = sandbox.call_function::<_, i32>(instance_id, "add", (1, 1)).await.unwrap_err();
+    assert!(err.to_string().contains("terminated"), "{}", err);
+    assert_eq!(sandbox.terminated_instances(), [instance_id]);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_audit_entries_over_a_unix_socket() {
+    let (sandbox, instance_ids) = sandbox_with_instances(2);
+    sandbox.grant(instance_ids[0], CapabilityChange::AllowCreate, None).unwrap();
+    sandbox.grant(instance_ids[1], CapabilityChange::AllowDelete, None).unwrap();
+    
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("console.sock");
+    let listener = tokio::net::UnixListener::bind(&path).unwrap();
+    let console = DebugConsole::new(TOKEN);
+    tokio::select! {
+        result = console.serve_unix(&sandbox, listener) => panic!("console stopped: {:?}", result),
+        () = async {
+            let mut client = ConsoleClient::connect_unix(&path, TOKEN).await.unwrap();
+            assert_eq!(client.audit(None, 10).await.unwrap().len(), 2);
+            assert_eq!(client.audit(None, 1).await.unwrap().len(), 1);
+            
+            let events = client.audit(Some(instance_ids[1]), 10).await.unwrap();
+            assert_eq!(events.len(), 1);
+            assert!(matches!(&events[0].event_type, AuditEventType::CapabilityGranted { instance_id, .. } if *instance_id == instance_ids[1].to_string()));
+            
+            let response = client.request(&ConsoleRequest::Audit { instance_id: None, limit: None }).await.unwrap();
+            assert!(matches!(response, ConsoleResponse::Audit { events } if events.len() == 2));
+        } => {}
+    }
+}
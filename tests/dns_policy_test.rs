@@ -0,0 +1,186 @@
+//! Tests for host-side DNS resolution under a DNS policy
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::security::audit::{AuditEventType, AuditLogger};
+use wasm_sandbox::security::dns::{DnsResolver, NameResolver};
+use wasm_sandbox::security::{Capabilities, EnforcementMode, NetworkCapability, PortRange};
+use wasm_sandbox::{DnsPolicy, Error, GuestErrorCode, InstanceConfig, InstanceId, StaticResolver, WasmSandbox};
+
+const DNS_MODULE: &str = r#"
+(module
+  (import "sandbox_dns" "resolve" (func $resolve (param i32 i32) (result i64)))
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 4096))
+  (data (i32.const 0) "api.example.com")
+  (data (i32.const 32) "evil.test")
+  (data (i32.const 64) "gone.example.com")
+  
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  
+  (func (export "allowed") (result i64) (call $resolve (i32.const 0) (i32.const 15)))
+  (func (export "denied") (result i64) (call $resolve (i32.const 32) (i32.const 9)))
+  (func (export "unknown") (result i64) (call $resolve (i32.const 64) (i32.const 16))))
+"#;
+
+/// Resolves every name to 10.0.0.N, with N going up on each lookup, like a rebinding DNS server
+#[derive(Default)]
+struct RebindingResolver {
+    lookups: AtomicU8,
+}
+
+impl NameResolver for RebindingResolver {
+    fn lookup(&self, _name: &str) -> wasm_sandbox::Result<Vec<IpAddr>> {
+        let n = self.lookups.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, n))])
+    }
+}
+
+fn domains(policy: DnsPolicy) -> Capabilities {
+    Capabilities {
+        network: NetworkCapability::AllowedDomains(policy),
+        ..Capabilities::minimal()
+    }
+}
+
+fn call(sandbox: &WasmSandbox, instance_id: InstanceId, function_name: &str) -> i64 {
+    match sandbox.get_instance(instance_id).unwrap().instance.call_values(function_name, &[]).unwrap().as_slice() {
+        [HostValue::I64(value)] => *value,
+        other => panic!("unexpected results {:?}", other),
+    }
+}
+
+#[test]
+fn test_wildcard_domains() {
+    let policy = DnsPolicy::new().allow_domain("*.example.com").allow_domain("api.test");
+    assert!(policy.allows_domain("a.example.com"));
+    assert!(policy.allows_domain("a.b.Example.COM."));
+    assert!(!policy.allows_domain("example.com"));
+    assert!(!policy.allows_domain("badexample.com"));
+    assert!(policy.allows_domain("API.test"));
+    assert!(!policy.allows_domain("www.api.test"));
+    assert!(!policy.allows_domain(""));
+}
+
+#[test]
+fn test_guest_resolves_allowed_names_through_the_host() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let address = IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34));
+    sandbox.set_name_resolver(StaticResolver::new().host("api.example.com", [address]));
+    let module_id = sandbox.load_module(DNS_MODULE.as_bytes()).unwrap();
+    let mut instance_config = InstanceConfig::default();
+    instance_config.capabilities.network = NetworkCapability::AllowedDomains(
+        DnsPolicy::new().allow_domain("*.example.com").ports(PortRange::single(443)),
+    );
+    let instance_id = sandbox.create_instance(module_id, Some(instance_config)).unwrap();
+    
+    let packed = call(&sandbox, instance_id, "allowed") as u64;
+    let bytes = sandbox.get_instance(instance_id).unwrap().instance
+        .read_memory_at((packed >> 32) as usize, (packed & 0xffff_ffff) as usize)
+        .unwrap();
+    let resolved = Ipv6Addr::from(<[u8; 16]>::try_from(bytes.as_slice()).unwrap());
+    assert_eq!(resolved.to_ipv4_mapped(), Some(Ipv4Addr::new(93, 184, 216, 34)));
+    
+    assert_eq!(call(&sandbox, instance_id, "denied"), GuestErrorCode::PermissionDenied.code());
+    assert_eq!(call(&sandbox, instance_id, "unknown"), GuestErrorCode::NotFound.code());
+    
+    // Only the pinned address on an allowed port may be connected to
+    assert!(sandbox.is_connection_allowed(instance_id, SocketAddr::new(address, 443)));
+    assert!(!sandbox.is_connection_allowed(instance_id, SocketAddr::new(address, 80)));
+    assert!(!sandbox.is_connection_allowed(instance_id, "10.0.0.1:443".parse().unwrap()));
+    sandbox.dns_resolver().release(instance_id);
+    assert!(!sandbox.is_connection_allowed(instance_id, SocketAddr::new(address, 443)));
+}
+
+#[test]
+fn test_addresses_are_pinned_until_released() {
+    let resolver = DnsResolver::new();
+    resolver.set_resolver(std::sync::Arc::new(RebindingResolver::default()));
+    let capabilities = domains(DnsPolicy::new().allow_domain("api.example.com"));
+    let instance_id = InstanceId::new();
+    
+    let first = resolver.resolve(instance_id, &capabilities, "api.example.com").unwrap();
+    assert_eq!(first, [IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))]);
+    // A rebinding answer can't replace the pinned address during the call
+    assert_eq!(resolver.resolve(instance_id, &capabilities, "API.example.com.").unwrap(), first);
+    assert!(resolver.is_pinned(instance_id, first[0]));
+    assert_eq!(resolver.pinned(instance_id).len(), 1);
+    
+    resolver.release(instance_id);
+    assert!(!resolver.is_pinned(instance_id, first[0]));
+    let second = resolver.resolve(instance_id, &capabilities, "api.example.com").unwrap();
+    assert_eq!(second, [IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]);
+}
+
+#[test]
+fn test_resolutions_are_limited_per_minute() {
+    let resolver = DnsResolver::new();
+    resolver.set_resolver(std::sync::Arc::new(RebindingResolver::default()));
+    let capabilities = domains(DnsPolicy::new().allow_domain("*.example.com").max_resolutions_per_minute(2));
+    let instance_id = InstanceId::new();
+    
+    resolver.resolve(instance_id, &capabilities, "a.example.com").unwrap();
+    resolver.resolve(instance_id, &capabilities, "b.example.com").unwrap();
+    // Pinned names don't count against the quota
+    resolver.resolve(instance_id, &capabilities, "a.example.com").unwrap();
+    let err = resolver.resolve(instance_id, &capabilities, "c.example.com").unwrap_err();
+    assert!(matches!(err, Error::ResourceExhausted { limit: 2, .. }), "{}", err);
+    assert_eq!(GuestErrorCode::from(&err), GuestErrorCode::QuotaExceeded);
+    
+    // Other instances have their own quota
+    assert!(resolver.resolve(InstanceId::new(), &capabilities, "c.example.com").is_ok());
+    
+    let denied = resolver.audit_logger().get_events().into_iter()
+        .filter(|event| matches!(&event.event_type, wasm_sandbox::security::audit::AuditEventType::DnsResolution { allowed: false, .. }))
+        .count();
+    assert_eq!(denied, 1);
+}
+
+#[test]
+fn test_only_successful_resolutions_count_against_the_quota() {
+    let resolver = DnsResolver::new();
+    let address = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    resolver.set_resolver(std::sync::Arc::new(StaticResolver::new().host("a.example.com", [address])));
+    let capabilities = domains(DnsPolicy::new().allow_domain("*.example.com").max_resolutions_per_minute(1));
+    let instance_id = InstanceId::new();
+    
+    // Names that don't resolve give back their place in the quota
+    for _ in 0..3 {
+        let err = resolver.resolve(instance_id, &capabilities, "gone.example.com").unwrap_err();
+        assert!(matches!(err, Error::NotFound { .. }), "{}", err);
+    }
+    assert_eq!(resolver.resolve(instance_id, &capabilities, "a.example.com").unwrap(), [address]);
+    
+    // The successful resolution used it up
+    let err = resolver.resolve(instance_id, &capabilities, "gone.example.com").unwrap_err();
+    assert!(matches!(err, Error::ResourceExhausted { limit: 1, .. }), "{}", err);
+}
+
+#[test]
+fn test_audit_mode_logs_and_allows_denied_names_and_connections() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let address = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 7));
+    sandbox.set_name_resolver(StaticResolver::new().host("evil.test", [address]));
+    let module_id = sandbox.load_module(DNS_MODULE.as_bytes()).unwrap();
+    let instance_config = InstanceConfig {
+        capabilities: domains(DnsPolicy::new().allow_domain("*.example.com"))
+            .domain_enforcement("network", EnforcementMode::Audit),
+        ..InstanceConfig::default()
+    };
+    let instance_id = sandbox.create_instance(module_id, Some(instance_config)).unwrap();
+    
+    assert!(call(&sandbox, instance_id, "denied") > 0);
+    assert!(sandbox.is_connection_allowed(instance_id, "10.0.0.1:443".parse().unwrap()));
+    
+    let violations = |logger: &AuditLogger| logger.get_events().into_iter()
+        .filter(|event| matches!(&event.event_type, AuditEventType::CapabilityViolation { domain, .. } if domain == "network"))
+        .count();
+    assert_eq!(violations(sandbox.dns_resolver().audit_logger()), 1);
+    assert_eq!(violations(sandbox.capability_audit_logger()), 1);
+}
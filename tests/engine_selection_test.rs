@@ -0,0 +1,71 @@
+//! Tests for choosing the engine a runtime is built on
+
+use wasm_sandbox::runtime::{create_runtime, RuntimeConfig};
+use wasm_sandbox::{EngineSelection, RuntimeEngine, SandboxManifest, WasmSandbox};
+
+const ADD_MODULE: &str = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))))
+"#;
+
+fn engine_for(selection: EngineSelection) -> wasm_sandbox::Result<RuntimeEngine> {
+    create_runtime(&RuntimeConfig::default().engine(selection)).map(|runtime| runtime.engine())
+}
+
+#[test]
+fn test_default_and_explicit_selection() {
+    assert_eq!(RuntimeEngine::available()[0], RuntimeEngine::Wasmtime);
+    assert_eq!(engine_for(EngineSelection::Auto).unwrap(), RuntimeEngine::Wasmtime);
+    assert_eq!(engine_for(EngineSelection::Require(RuntimeEngine::Wasmtime)).unwrap(), RuntimeEngine::Wasmtime);
+    
+    if RuntimeEngine::Wasmer.is_available() {
+        assert_eq!(engine_for(EngineSelection::Prefer(RuntimeEngine::Wasmer)).unwrap(), RuntimeEngine::Wasmer);
+        assert_eq!(engine_for(EngineSelection::Require(RuntimeEngine::Wasmer)).unwrap(), RuntimeEngine::Wasmer);
+    } else {
+        // An unavailable preference falls back; a requirement doesn't
+        assert_eq!(engine_for(EngineSelection::Prefer(RuntimeEngine::Wasmer)).unwrap(), RuntimeEngine::Wasmtime);
+        let err = engine_for(EngineSelection::Require(RuntimeEngine::Wasmer)).unwrap_err();
+        assert!(err.to_string().contains("wasmer-runtime"), "{}", err);
+    }
+}
+
+#[test]
+fn test_prefer_cached_picks_engine_that_loads_the_bundle() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("modules.cache");
+    let sandbox = WasmSandbox::new().unwrap();
+    sandbox.load_module(ADD_MODULE.as_bytes()).unwrap();
+    sandbox.export_cache(&path).unwrap();
+    
+    assert_eq!(engine_for(EngineSelection::PreferCached(path)).unwrap(), RuntimeEngine::Wasmtime);
+    
+    // A missing or corrupt bundle is ignored
+    let missing = dir.path().join("missing.cache");
+    assert_eq!(engine_for(EngineSelection::PreferCached(missing)).unwrap(), RuntimeEngine::Wasmtime);
+    let corrupt = dir.path().join("corrupt.cache");
+    std::fs::write(&corrupt, b"not a bundle").unwrap();
+    assert_eq!(engine_for(EngineSelection::PreferCached(corrupt)).unwrap(), RuntimeEngine::Wasmtime);
+}
+
+#[test]
+fn test_selection_parses_manifest_names() {
+    assert_eq!("auto".parse::<EngineSelection>().unwrap(), EngineSelection::Auto);
+    assert_eq!("Wasmer".parse::<EngineSelection>().unwrap(), EngineSelection::Prefer(RuntimeEngine::Wasmer));
+    assert!("v8".parse::<EngineSelection>().is_err());
+    
+    let manifest = r#"
+name = "plugin"
+version = "1.0.0"
+
+[runtime]
+engine = "v8"
+"#;
+    let issues = SandboxManifest::validate_str(manifest).unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].path, "runtime.engine");
+    assert_eq!(issues[0].line, Some(6));
+    
+    let manifest = SandboxManifest::from_str(&manifest.replace("v8", "wasmer")).unwrap();
+    assert_eq!(manifest.to_runtime_config().engine, EngineSelection::Prefer(RuntimeEngine::Wasmer));
+}
@@ -0,0 +1,70 @@
+//! Tests for environment layers composed into instances
+
+use wasm_sandbox::{EnvironmentLayer, InstanceConfig, SandboxManifest, WasmSandbox};
+
+const TEST_MODULE: &[u8] = include_bytes!("../fixtures/test_module.wasm");
+
+#[test]
+fn test_materialize_places_files_under_guest_dirs() {
+    let layer = EnvironmentLayer::new()
+        .file("/fixtures/input.json", "{\"n\": 1}")
+        .file("/config.toml", "debug = true");
+    
+    let materialized = layer.materialize().expect("Failed to materialize layer");
+    
+    let guests: Vec<&str> = materialized.preopens().iter().map(|dir| dir.guest_path.as_str()).collect();
+    assert_eq!(guests, vec!["/", "/fixtures"]);
+    assert_eq!(
+        std::fs::read_to_string(materialized.root().join("fixtures/input.json")).unwrap(),
+        "{\"n\": 1}"
+    );
+}
+
+#[test]
+fn test_materialize_rejects_escaping_paths() {
+    assert!(EnvironmentLayer::new().file("relative.txt", "x").materialize().is_err());
+    assert!(EnvironmentLayer::new().file("/fixtures/../../etc/passwd", "x").materialize().is_err());
+}
+
+#[test]
+fn test_manifest_environment_layer() {
+    let manifest = SandboxManifest::from_str_strict(r#"
+schema_version = 2
+name = "fixture-test"
+version = "1.0.0"
+
+[environment_layer.env]
+API_URL = "http://stub.local"
+
+[[environment_layer.files]]
+path = "/fixtures/data.csv"
+contents = "a,b\n1,2\n"
+
+[[environment_layer.stub_sockets]]
+address = "stub.local:80"
+response = "HTTP/1.1 200 OK\r\n\r\n"
+"#).expect("Failed to parse manifest");
+
+    let layer = manifest.to_instance_config().unwrap().environment_layer.expect("layer is set");
+    assert_eq!(layer.env.get("API_URL").map(String::as_str), Some("http://stub.local"));
+    assert_eq!(layer.files[0].contents, b"a,b\n1,2\n");
+    assert_eq!(layer.stub_response("stub.local:80"), Some(&b"HTTP/1.1 200 OK\r\n\r\n"[..]));
+    assert_eq!(layer.stub_response("other.local:80"), None);
+}
+
+#[test]
+fn test_instance_with_environment_layer() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(TEST_MODULE).expect("Failed to load module");
+    
+    let config = InstanceConfig {
+        environment_layer: Some(EnvironmentLayer::new()
+            .file("/fixtures/input.txt", "hello")
+            .env("MODE", "test")),
+        ..InstanceConfig::default()
+    };
+    
+    let instance_id = sandbox.create_instance(module_id, Some(config))
+        .expect("Failed to create instance with environment layer");
+    assert!(sandbox.get_instance(instance_id).is_some());
+}
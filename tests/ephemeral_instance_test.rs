@@ -0,0 +1,102 @@
+//! Tests for request-scoped instances
+
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+
+use futures::FutureExt;
+use wasm_sandbox::runtime::ModuleId;
+use wasm_sandbox::WasmSandbox;
+
+const COUNTER_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "set") (param $value i32)
+    (i32.store (i32.const 64) (local.get $value)))
+  (func (export "get") (result i32)
+    (i32.load (i32.const 64))))
+"#;
+
+fn sandbox() -> (WasmSandbox, ModuleId) {
+    let sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(COUNTER_MODULE.as_bytes()).unwrap();
+    (sandbox, module_id)
+}
+
+#[tokio::test]
+async fn test_instance_is_removed_after_the_closure() {
+    let (mut sandbox, module_id) = sandbox();
+    
+    let (instance_id, value) = sandbox.with_ephemeral_instance(module_id, None, async |instance| {
+        instance.call_function::<_, ()>("set", 5).await.unwrap();
+        (instance.id(), instance.call_function::<_, i32>("get", ()).await.unwrap())
+    })
+    .await
+    .unwrap();
+    assert_eq!(value, 5);
+    assert!(sandbox.get_instance(instance_id).is_none());
+    
+    // The next request starts from a clean instance
+    let value = sandbox.with_ephemeral_instance(module_id, None, async |instance| {
+        instance.call_function::<_, i32>("get", ()).await.unwrap()
+    })
+    .await
+    .unwrap();
+    assert_eq!(value, 0);
+    
+    let metrics = sandbox.ephemeral_metrics();
+    assert_eq!((metrics.created, metrics.torn_down, metrics.cancelled, metrics.panicked), (2, 2, 0, 0));
+    assert!(metrics.max_creation_time >= metrics.mean_creation_time());
+    assert!(metrics.mean_creation_time() > Duration::ZERO);
+}
+
+#[tokio::test]
+async fn test_instance_is_removed_when_the_closure_panics_or_is_cancelled() {
+    let (mut sandbox, module_id) = sandbox();
+    
+    let panicked = AssertUnwindSafe(sandbox.with_ephemeral_instance(module_id, None, async |_| -> () {
+        panic!("request failed");
+    }))
+    .catch_unwind()
+    .await;
+    assert!(panicked.is_err());
+    assert!(sandbox.instance_ids().is_empty());
+    
+    let cancelled = tokio::time::timeout(
+        Duration::from_millis(20),
+        sandbox.with_ephemeral_instance(module_id, None, async |_| {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        }),
+    )
+    .await;
+    assert!(cancelled.is_err());
+    assert!(sandbox.instance_ids().is_empty());
+    
+    let metrics = sandbox.ephemeral_metrics();
+    assert_eq!((metrics.created, metrics.torn_down, metrics.cancelled, metrics.panicked), (2, 2, 1, 1));
+}
+
+#[tokio::test]
+async fn test_fork_starts_from_the_template_and_leaves_it_alone() {
+    let (mut sandbox, module_id) = sandbox();
+    let template = sandbox.create_instance(module_id, None).unwrap();
+    sandbox.call_function::<_, ()>(template, "set", 42).await.unwrap();
+    
+    let value = sandbox.with_ephemeral_fork(template, async |instance| {
+        let value: i32 = instance.call_function("get", ()).await.unwrap();
+        instance.call_function::<_, ()>("set", 7).await.unwrap();
+        value
+    })
+    .await
+    .unwrap();
+    assert_eq!(value, 42);
+    assert_eq!(sandbox.instance_ids(), vec![template]);
+    assert_eq!(sandbox.call_function::<_, i32>(template, "get", ()).await.unwrap(), 42);
+}
+
+#[tokio::test]
+async fn test_creation_failure_runs_nothing() {
+    let (mut sandbox, _) = sandbox();
+    let result = sandbox.with_ephemeral_instance(ModuleId::new(), None, async |_| unreachable!()).await;
+    assert!(result.is_err());
+    assert_eq!(sandbox.ephemeral_metrics().created, 0);
+}
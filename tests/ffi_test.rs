@@ -0,0 +1,119 @@
+//! Tests for the C ABI behind the generated Python bindings
+
+#![cfg(feature = "ffi")]
+
+use std::ffi::{c_char, CStr, CString};
+
+use wasm_sandbox::ffi::*;
+
+const ADD_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))))
+"#;
+
+fn last_error() -> Option<String> {
+    let error = get_last_error();
+    (!error.is_null()).then(|| unsafe { CStr::from_ptr(error) }.to_string_lossy().into_owned())
+}
+
+/// Call a function, returning its JSON result or the error
+unsafe fn call(sandbox: *mut FfiSandbox, function: &str, params: &str) -> Result<String, String> {
+    let function = CString::new(function).unwrap();
+    let params = CString::new(params).unwrap();
+    let result = unsafe { sandbox_call_function(sandbox, function.as_ptr(), params.as_ptr()) };
+    if result.is_null() {
+        return Err(last_error().expect("a failed call sets the last error"));
+    }
+    let json = unsafe { CStr::from_ptr(get_result_json(result)) }.to_str().unwrap().to_string();
+    unsafe { free_result(result) };
+    Ok(json)
+}
+
+unsafe fn load(sandbox: *mut FfiSandbox, wasm: &[u8]) -> *mut c_char {
+    unsafe { sandbox_load_module(sandbox, wasm.as_ptr().cast(), wasm.len()) }
+}
+
+#[test]
+fn test_modules_are_loaded_and_called_through_handles() {
+    let sandbox = sandbox_new();
+    assert!(!sandbox.is_null());
+    unsafe {
+        let module_id = load(sandbox, ADD_MODULE.as_bytes());
+        assert!(!module_id.is_null());
+        assert_eq!(CStr::from_ptr(module_id).to_bytes().len(), 36);
+        sandbox_free_string(module_id);
+        
+        assert_eq!(call(sandbox, "add", "[2, 3]"), Ok("5".to_string()));
+        assert!(last_error().is_none());
+        
+        // A later load replaces the instance calls go to
+        let module_id = load(sandbox, br#"(module (func (export "seven") (param i32) (result i32) (i32.const 7)))"#);
+        sandbox_free_string(module_id);
+        assert_eq!(call(sandbox, "seven", "0"), Ok("7".to_string()));
+        assert!(call(sandbox, "add", "[2, 3]").is_err());
+        
+        sandbox_free(sandbox);
+        sandbox_free(std::ptr::null_mut());
+    }
+}
+
+#[test]
+fn test_failures_are_reported_through_the_last_error() {
+    unsafe {
+        let path = CString::new("module.wasm").unwrap();
+        assert_eq!(sandbox_load_from_source(std::ptr::null_mut(), path.as_ptr()), -1);
+        assert!(last_error().unwrap().contains("sandbox is NULL"));
+        assert!(get_result_json(std::ptr::null_mut()).is_null());
+        
+        let sandbox = sandbox_new();
+        assert!(call(sandbox, "add", "[]").unwrap_err().contains("No module is loaded"));
+        assert!(load(sandbox, b"not a module").is_null());
+        assert!(last_error().is_some());
+        
+        sandbox_free_string(load(sandbox, ADD_MODULE.as_bytes()));
+        assert!(call(sandbox, "add", "[1,").is_err());
+        assert!(call(sandbox, "missing", "[]").is_err());
+        let unknown_source = CString::new("program.cobol").unwrap();
+        assert_eq!(sandbox_load_from_source(sandbox, unknown_source.as_ptr()), -1);
+        
+        // Failures leave the loaded module in place, and success clears the error
+        assert_eq!(call(sandbox, "add", "[20, 22]"), Ok("42".to_string()));
+        assert!(last_error().is_none());
+        sandbox_free(sandbox);
+    }
+}
+
+#[test]
+fn test_builder_builds_a_sandbox_from_a_source_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("add.wasm");
+    std::fs::write(&path, wat::parse_str(ADD_MODULE).unwrap()).unwrap();
+    let path = CString::new(path.to_str().unwrap()).unwrap();
+    
+    unsafe {
+        let builder = sandbox_builder_new();
+        assert!(sandbox_builder_build(builder).is_null());
+        assert!(last_error().unwrap().contains("No source file"));
+        
+        sandbox_builder_source(builder, path.as_ptr());
+        sandbox_builder_timeout(builder, 5_000);
+        sandbox_builder_memory_limit(builder, 4 << 20);
+        sandbox_builder_file_access(builder, 0);
+        sandbox_builder_network(builder, 0);
+        let sandbox = sandbox_builder_build(builder);
+        assert!(!sandbox.is_null(), "{:?}", last_error());
+        assert_eq!(call(sandbox, "add", "[40, 2]"), Ok("42".to_string()));
+        
+        // The builder outlives what it built
+        let again = sandbox_builder_build(builder);
+        assert!(!again.is_null());
+        sandbox_builder_free(builder);
+        sandbox_free(again);
+        sandbox_free(sandbox);
+        
+        sandbox_builder_memory_limit(std::ptr::null_mut(), 0);
+        assert!(last_error().unwrap().contains("builder is NULL"));
+    }
+}
@@ -0,0 +1,117 @@
+//! Tests for staging input files into instance inboxes
+
+mod common;
+
+use std::path::PathBuf;
+
+use wasm_sandbox::security::audit::AuditEventType;
+use wasm_sandbox::{InboxConfig, IngestDecision, InstanceConfig, InstanceId, SandboxError, WasmSandbox};
+
+/// Reads the first four bytes of `in.txt` in the first preopened directory, or returns minus the WASI errno
+const INBOX_READER: &str = r#"
+    (module
+        (import "wasi_snapshot_preview1" "path_open"
+            (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_read"
+            (func $fd_read (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "in.txt")
+        (func (export "read") (param i32) (result i32)
+            (local $errno i32)
+            (local.set $errno
+                (call $path_open (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 6)
+                    (i32.const 0) (i64.const 0x1fffffff) (i64.const 0x1fffffff) (i32.const 0) (i32.const 16)))
+            (if (local.get $errno) (then (return (i32.sub (i32.const 0) (local.get $errno)))))
+            (i32.store (i32.const 32) (i32.const 1024))
+            (i32.store (i32.const 36) (i32.const 4))
+            (local.set $errno (call $fd_read (i32.load (i32.const 16)) (i32.const 32) (i32.const 1) (i32.const 48)))
+            (if (local.get $errno) (then (return (i32.sub (i32.const 0) (local.get $errno)))))
+            (i32.load (i32.const 1024))))
+"#;
+
+fn inbox_instance(inbox: InboxConfig) -> (WasmSandbox, InstanceId) {
+    let config = InstanceConfig {
+        inbox: Some(inbox),
+        ..InstanceConfig::default()
+    };
+    common::instantiate(INBOX_READER, Some(config))
+}
+
+#[tokio::test]
+async fn guest_reads_staged_files_and_the_inbox_goes_with_the_instance() {
+    let (mut sandbox, instance_id) = inbox_instance(InboxConfig::default());
+    let staged = sandbox.stage_file(instance_id, "in.txt", b"ABCDEFGH".to_vec()).unwrap();
+    assert_eq!((staged.guest_path.as_str(), staged.size), ("/input/in.txt", 8));
+    assert_eq!(staged.sha256.len(), 64);
+    assert!(!staged.is_transformed());
+    
+    let value: i32 = sandbox.call_function(instance_id, "read", 0).await.unwrap();
+    assert_eq!(value.to_le_bytes(), *b"ABCD");
+    
+    let path: PathBuf = sandbox.inbox(instance_id).unwrap().path().to_path_buf();
+    assert_eq!(sandbox.staged_files(instance_id), vec![staged]);
+    sandbox.remove_instance(instance_id);
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn hooks_transform_and_reject_files_and_are_audited() {
+    let (sandbox, instance_id) = inbox_instance(InboxConfig::default());
+    sandbox.on_ingest(|name, contents| match name.ends_with(".exe") {
+        true => IngestDecision::Reject("executables are not accepted".to_string()),
+        false => IngestDecision::Replace(contents.to_ascii_uppercase()),
+    });
+    
+    let staged = sandbox.stage_file(instance_id, "in.txt", b"wxyz".to_vec()).unwrap();
+    assert!(staged.is_transformed());
+    let value: i32 = sandbox.call_function(instance_id, "read", 0).await.unwrap();
+    assert_eq!(value.to_le_bytes(), *b"WXYZ");
+    
+    let rejected = sandbox.stage_file(instance_id, "tool.exe", b"MZ".to_vec());
+    assert!(matches!(rejected, Err(SandboxError::SecurityViolation { .. })));
+    assert!(!sandbox.inbox(instance_id).unwrap().path().join("tool.exe").exists());
+    
+    let decisions: Vec<_> = sandbox.ingestion_audit_logger().get_events().into_iter()
+        .filter_map(|event| match event.event_type {
+            AuditEventType::FileStaged { name, accepted, .. } => Some((name, accepted)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(decisions, vec![("in.txt".to_string(), true), ("tool.exe".to_string(), false)]);
+}
+
+#[test]
+fn staging_enforces_names_and_size_limits() {
+    let (sandbox, instance_id) = inbox_instance(InboxConfig::default().max_file_bytes(10).max_total_bytes(16));
+    assert!(matches!(
+        sandbox.stage_file(instance_id, "../escape.txt", b"x".to_vec()),
+        Err(SandboxError::InvalidInput { .. })
+    ));
+    assert!(matches!(
+        sandbox.stage_file(instance_id, "big.bin", vec![0; 11]),
+        Err(SandboxError::ResourceLimit { .. })
+    ));
+    
+    sandbox.stage_file(instance_id, "a.bin", vec![0; 10]).unwrap();
+    assert!(matches!(
+        sandbox.stage_file(instance_id, "b.bin", vec![0; 10]),
+        Err(SandboxError::ResourceLimit { .. })
+    ));
+    // Replacing a file only counts its new size
+    sandbox.stage_file(instance_id, "a.bin", vec![0; 6]).unwrap();
+    sandbox.stage_file(instance_id, "b.bin", vec![0; 10]).unwrap();
+    assert_eq!(sandbox.inbox(instance_id).unwrap().usage(), 16);
+    
+    assert!(sandbox.unstage_file(instance_id, "a.bin"));
+    assert!(!sandbox.unstage_file(instance_id, "a.bin"));
+    assert_eq!(sandbox.inbox(instance_id).unwrap().usage(), 10);
+}
+
+#[test]
+fn staging_needs_an_inbox() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(INBOX_READER.as_bytes()).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    assert!(sandbox.inbox(instance_id).is_none());
+    assert!(sandbox.stage_file(instance_id, "in.txt", b"data".to_vec()).is_err());
+}
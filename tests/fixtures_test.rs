@@ -0,0 +1,115 @@
+//! Tests for declarative golden fixtures
+
+use serde_json::json;
+use wasm_sandbox::conformance::CheckOutcome;
+use wasm_sandbox::fixtures::{FixtureCase, SandboxTestSuite};
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::{HostNamespace, WasmSandbox};
+
+/// `echo` returns its input; `charge` calls the gated billing namespace first
+const PLUGIN_MODULE: &str = r#"
+(module
+  (import "acme.billing" "charge" (func $charge (param i64) (result i64)))
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (local.get $ptr) (local.get $len)))
+    (local.get $ptr))
+  (func $echo (export "echo") (param $ptr i32) (param $len i32) (result i64)
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+      (i64.extend_i32_u (local.get $len))))
+  (func (export "charge") (param $ptr i32) (param $len i32) (result i64)
+    (drop (call $charge (i64.const 1)))
+    (call $echo (local.get $ptr) (local.get $len))))
+"#;
+
+fn write(dir: &std::path::Path, name: &str, content: &str) {
+    std::fs::write(dir.join(name), content).unwrap();
+}
+
+#[tokio::test]
+async fn test_golden_outputs_pass() {
+    let dir = tempfile::tempdir().unwrap();
+    write(dir.path(), "a_single.json", r#"{"function": "echo", "input": {"name": "Ada"}, "expected": {"name": "Ada"}}"#);
+    write(dir.path(), "b_list.yaml", "- function: echo\n  input: [1, 2]\n  expected: [1, 2]\n- name: strings\n  function: echo\n  input: hi\n  expected: hi\n");
+    write(dir.path(), "notes.txt", "not a fixture");
+    
+    let suite = SandboxTestSuite::from_dir(dir.path()).unwrap();
+    let names: Vec<_> = suite.cases().iter().map(|case| case.name.as_str()).collect();
+    assert_eq!(names, ["a_single", "b_list#0", "strings"]);
+    
+    let mut sandbox = WasmSandbox::new().unwrap();
+    sandbox.register_host_namespace(HostNamespace::new("acme.billing").function("charge", |args| Ok(args.to_vec()))).unwrap();
+    let module_id = sandbox.load_module(PLUGIN_MODULE.as_bytes()).unwrap();
+    let report = suite.run_with(&mut sandbox, module_id).await.unwrap();
+    
+    assert!(report.passed(), "{}", report);
+    assert!(report.to_string().contains("fixture result: ok. 3 passed; 0 failed"));
+    assert!(sandbox.instance_ids().is_empty());
+    report.assert_passed();
+}
+
+#[tokio::test]
+async fn test_mismatches_report_a_diff() {
+    let suite = SandboxTestSuite::new()
+        .case(FixtureCase::new("wrong", "echo", json!({"a": [1, 2], "b": true})).expect(json!({"a": [1, 3], "c": true})))
+        .case(FixtureCase::new("missing", "nope", json!(null)).expect(json!(null)))
+        .case(FixtureCase::new("no_error", "echo", json!(1)).expect_error("boom"));
+    
+    let mut sandbox = WasmSandbox::new().unwrap();
+    sandbox.register_host_namespace(HostNamespace::new("acme.billing").function("charge", |args| Ok(args.to_vec()))).unwrap();
+    let module_id = sandbox.load_module(PLUGIN_MODULE.as_bytes()).unwrap();
+    let report = suite.run_with(&mut sandbox, module_id).await.unwrap();
+    
+    assert!(!report.passed());
+    assert_eq!(report.failures().count(), 3);
+    let Some(CheckOutcome::Failed(reason)) = report.outcome("wrong") else { panic!("{}", report) };
+    assert_eq!(reason, "$.a[1]: expected 3, got 2\n$.c: missing, expected true\n$.b: unexpected true");
+    assert!(matches!(report.outcome("missing"), Some(CheckOutcome::Failed(reason)) if reason.contains("`nope` failed")));
+    
+    let output = report.to_string();
+    assert!(output.contains("fixture wrong ... FAILED"), "{}", output);
+    assert!(output.contains("        $.a[1]: expected 3, got 2"), "{}", output);
+    assert!(output.contains("fixture result: FAILED. 0 passed; 3 failed"), "{}", output);
+}
+
+#[tokio::test]
+async fn test_capability_denials_are_checked() {
+    let expected = FixtureCase::new("denied", "charge", json!({}))
+        .expect_error("acme.billing")
+        .expect_denial("not granted host namespace acme.billing");
+    let unexpected = FixtureCase::new("unexpected", "charge", json!({})).expect_error("acme.billing");
+    let missing = FixtureCase::new("missing", "echo", json!({})).expect(json!({})).expect_denial("acme.billing");
+    let suite = SandboxTestSuite::new().case(expected).case(unexpected).case(missing);
+    
+    let mut sandbox = WasmSandbox::new().unwrap();
+    sandbox.register_host_namespace(
+        HostNamespace::new("acme.billing").function("charge", |args| match args {
+            [HostValue::I64(cents)] => Ok(vec![HostValue::I64(*cents)]),
+            _ => unreachable!(),
+        }),
+    ).unwrap();
+    let module_id = sandbox.load_module(PLUGIN_MODULE.as_bytes()).unwrap();
+    let report = suite.run_with(&mut sandbox, module_id).await.unwrap();
+    
+    assert_eq!(report.outcome("denied"), Some(&CheckOutcome::Passed), "{}", report);
+    assert!(matches!(report.outcome("unexpected"), Some(CheckOutcome::Failed(reason)) if reason.starts_with("unexpected denial: Instance is not granted")));
+    assert!(matches!(report.outcome("missing"), Some(CheckOutcome::Failed(reason)) if reason.contains("expected a denial matching \"acme.billing\"")));
+}
+
+#[test]
+fn test_malformed_fixtures_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    write(dir.path(), "broken.yaml", "- input: 1\n  expected: 1\n");
+    let err = SandboxTestSuite::from_dir(dir.path()).unwrap_err();
+    assert!(err.to_string().contains("broken.yaml"), "{}", err);
+    
+    let dir = tempfile::tempdir().unwrap();
+    write(dir.path(), "broken.json", "{\"function\": ");
+    assert!(SandboxTestSuite::from_dir(dir.path()).is_err());
+    
+    assert!(SandboxTestSuite::from_dir(dir.path().join("missing")).is_err());
+}
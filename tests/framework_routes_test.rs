@@ -0,0 +1,119 @@
+//! Tests for mounting guest HTTP handlers in axum and actix-web services
+
+use std::sync::Arc;
+
+use wasm_sandbox::{HttpBudget, HttpRequest, RoutePolicy, SandboxRoute, WasmSandbox};
+
+// Answers every request with the response JSON stored at offset 0
+fn handler_module(response: &str) -> String {
+    format!(
+        r#"
+(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (data (i32.const 0) "{}")
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (func (export "on_http_request") (param $ptr i32) (param $len i32) (result i64)
+    (i64.const {})))
+"#,
+        response.replace('"', "\\\""),
+        response.len()
+    )
+}
+
+#[cfg(any(feature = "axum", feature = "actix"))]
+fn route(response: &str, policy: RoutePolicy) -> SandboxRoute {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(handler_module(response).as_bytes()).unwrap();
+    let instances = policy.instantiate(&mut sandbox, module_id).unwrap();
+    SandboxRoute::new(Arc::new(sandbox), instances, policy.budget)
+}
+
+#[tokio::test]
+async fn test_route_policy_creates_replicas_within_budget() {
+    let policy = RoutePolicy::new()
+        .replicas(3)
+        .budget(HttpBudget::default().max_request_bytes(4));
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(handler_module(r#"{"status":200}"#).as_bytes()).unwrap();
+    let instances = policy.instantiate(&mut sandbox, module_id).unwrap();
+    assert_eq!(instances.len(), 3);
+    
+    let route = SandboxRoute::new(Arc::new(sandbox), instances, policy.budget);
+    assert_eq!(route.budget().max_request_bytes, 4);
+    assert_eq!(route.handle(HttpRequest::new("GET", "/")).await.status, 200);
+    let response = route.handle(HttpRequest::new("POST", "/").body(vec![0; 5])).await;
+    assert_eq!(response.status, 413);
+}
+
+#[cfg(feature = "axum")]
+#[tokio::test]
+async fn test_axum_router_serves_mounted_routes() {
+    use axum::body::Body;
+    use axum::http::Request;
+    use axum::Router;
+    use tower::ServiceExt;
+    use wasm_sandbox::frameworks::axum::SandboxRouterExt;
+    
+    let app = Router::new()
+        .sandbox_route("/plugins/hello", route(
+            r#"{"status":201,"headers":[["x-served-by","guest"]],"body":[104,105]}"#,
+            RoutePolicy::new(),
+        ))
+        .sandbox_route("/small", route(
+            r#"{"status":200}"#,
+            RoutePolicy::new().budget(HttpBudget::default().max_request_bytes(2)),
+        ));
+    
+    let request = Request::post("/plugins/hello/world").body(Body::from("{}")).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), 201);
+    assert_eq!(response.headers()["x-served-by"], "guest");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], b"hi");
+    
+    let request = Request::post("/small").body(Body::from("abc")).unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), 413);
+    let request = Request::get("/elsewhere").body(Body::empty()).unwrap();
+    assert_eq!(app.oneshot(request).await.unwrap().status(), 404);
+}
+
+#[cfg(feature = "actix")]
+#[tokio::test]
+async fn test_actix_middleware_serves_mounted_routes() {
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+    use wasm_sandbox::frameworks::actix::SandboxMiddleware;
+    
+    let middleware = SandboxMiddleware::new()
+        .mount("/plugins", route(r#"{"status":200,"body":[49]}"#, RoutePolicy::new()))
+        .mount("/plugins/hello", route(r#"{"status":201,"body":[50]}"#, RoutePolicy::new()))
+        .mount("/small", route(
+            r#"{"status":200}"#,
+            RoutePolicy::new().budget(HttpBudget::default().max_request_bytes(2)),
+        ));
+    let app = init_service(
+        App::new()
+            .wrap(middleware)
+            .route("/app", web::get().to(|| async { HttpResponse::Ok().body("app") })),
+    )
+    .await;
+    
+    // The longest mount wins, matching whole path segments
+    let response = call_service(&app, TestRequest::get().uri("/plugins/hello/world").to_request()).await;
+    assert_eq!(response.status(), 201);
+    assert_eq!(&read_body(response).await[..], b"2");
+    let response = call_service(&app, TestRequest::get().uri("/plugins/helloworld").to_request()).await;
+    assert_eq!(&read_body(response).await[..], b"1");
+    
+    let request = TestRequest::post().uri("/small").set_payload("abc").to_request();
+    assert_eq!(call_service(&app, request).await.status(), 413);
+    
+    // Requests no mount serves reach the app
+    let response = call_service(&app, TestRequest::get().uri("/app").to_request()).await;
+    assert_eq!(&read_body(response).await[..], b"app");
+}
@@ -0,0 +1,116 @@
+//! Tests for the sandbox-wide fuel budget
+
+mod common;
+
+use std::time::Duration;
+
+use wasm_sandbox::runtime::RuntimeConfig;
+use wasm_sandbox::{Error, FuelBudget, InstanceConfig, InstanceId, ResourceKind, SandboxConfig, WasmSandbox};
+
+// `spin` burns fuel in proportion to its argument
+const SPIN_MODULE: &str = r#"
+(module
+  (func (export "spin") (param $n i32) (result i32)
+    (block $done
+      (loop $again
+        (br_if $done (i32.eqz (local.get $n)))
+        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+        (br $again)))
+    (local.get $n)))
+"#;
+
+fn sandbox(budget: FuelBudget) -> WasmSandbox {
+    WasmSandbox::with_config(SandboxConfig {
+        fuel_budget: Some(budget),
+        ..SandboxConfig::default()
+    }).expect("Failed to create sandbox")
+}
+
+fn instantiate(sandbox: &mut WasmSandbox, fuel_weight: u32) -> wasm_sandbox::Result<InstanceId> {
+    let config = InstanceConfig {
+        fuel_weight,
+        ..InstanceConfig::default()
+    };
+    common::try_create_instance(sandbox, SPIN_MODULE, Some(config))
+}
+
+async fn spin(sandbox: &WasmSandbox, instance_id: InstanceId, n: i32) -> wasm_sandbox::Result<i32> {
+    sandbox.call_function(instance_id, "spin", n).await
+}
+
+#[tokio::test]
+async fn test_instance_is_throttled_after_its_share() {
+    let mut sandbox = sandbox(FuelBudget::new(10_000, Duration::from_secs(60)));
+    let instance_id = instantiate(&mut sandbox, 1).unwrap();
+    assert_eq!(sandbox.fuel_balance(instance_id), Some(10_000));
+    
+    assert_eq!(spin(&sandbox, instance_id, 5_000).await.unwrap(), 0);
+    let balance = sandbox.fuel_balance(instance_id).unwrap();
+    assert!(balance < 0, "a call larger than the share leaves the bucket in debt: {}", balance);
+    
+    match spin(&sandbox, instance_id, 1).await {
+        Err(Error::ResourceExhausted { kind: ResourceKind::Fuel, limit, .. }) => assert_eq!(limit, 10_000),
+        other => panic!("expected the fuel budget to be exhausted, got {:?}", other),
+    }
+    
+    let metrics = sandbox.fuel_budget_metrics();
+    assert_eq!(metrics.throttled_calls, 1);
+    assert_eq!(metrics.fuel_charged as i64, 10_000 - balance);
+}
+
+#[tokio::test]
+async fn test_budget_is_shared_by_weight() {
+    let mut sandbox = sandbox(FuelBudget::new(40_000, Duration::from_secs(60)));
+    let heavy = instantiate(&mut sandbox, 3).unwrap();
+    let light = instantiate(&mut sandbox, 1).unwrap();
+    
+    // The light instance runs out while the heavy one can still afford the same work
+    while spin(&sandbox, light, 1_000).await.is_ok() {}
+    let balance = sandbox.fuel_balance(light).unwrap();
+    assert!(balance <= 0);
+    
+    let work = 10_000 - balance;
+    assert!(sandbox.fuel_balance(heavy).unwrap() > work);
+    spin(&sandbox, heavy, 1_000).await.unwrap();
+    
+    // Removing an instance returns its share to the rest
+    sandbox.remove_instance(heavy);
+    assert_eq!(sandbox.fuel_balance(heavy), None);
+    let newcomer = instantiate(&mut sandbox, 1).unwrap();
+    assert_eq!(sandbox.fuel_balance(newcomer), Some(20_000));
+}
+
+#[tokio::test]
+async fn test_bucket_refills_over_the_window() {
+    let mut sandbox = sandbox(FuelBudget::new(10_000, Duration::from_millis(100)));
+    let instance_id = instantiate(&mut sandbox, 1).unwrap();
+    
+    while spin(&sandbox, instance_id, 1_000).await.is_ok() {}
+    assert!(spin(&sandbox, instance_id, 1).await.is_err());
+    
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(sandbox.fuel_balance(instance_id), Some(10_000));
+    assert!(spin(&sandbox, instance_id, 1).await.is_ok());
+}
+
+#[test]
+fn test_invalid_budgets_are_rejected() {
+    let without_fuel = WasmSandbox::with_config(SandboxConfig {
+        runtime: RuntimeConfig {
+            enable_fuel: false,
+            ..RuntimeConfig::default()
+        },
+        fuel_budget: Some(FuelBudget::new(10_000, Duration::from_secs(1))),
+        ..SandboxConfig::default()
+    });
+    assert!(matches!(without_fuel, Err(Error::Configuration { .. })));
+    
+    let empty = WasmSandbox::with_config(SandboxConfig {
+        fuel_budget: Some(FuelBudget::new(0, Duration::from_secs(1))),
+        ..SandboxConfig::default()
+    });
+    assert!(matches!(empty, Err(Error::Configuration { .. })));
+    
+    let mut sandbox = sandbox(FuelBudget::new(10_000, Duration::from_secs(1)));
+    assert!(matches!(instantiate(&mut sandbox, 0), Err(Error::Configuration { .. })));
+}
@@ -0,0 +1,126 @@
+//! Tests for topping up calls that run low on fuel
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use wasm_sandbox::runtime::RuntimeConfig;
+use wasm_sandbox::security::ResourceLimits;
+use wasm_sandbox::{
+    Error, FuelBudget, FuelClass, FuelRefillPolicy, InstanceConfig, InstanceId, SandboxConfig, WasmSandbox,
+};
+
+// `spin` burns fuel in proportion to its argument
+const SPIN_MODULE: &str = r#"
+(module
+  (func (export "spin") (param $n i32) (result i32)
+    (block $done
+      (loop $again
+        (br_if $done (i32.eqz (local.get $n)))
+        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+        (br $again)))
+    (local.get $n)))
+"#;
+
+// Enough for a few yields, far less than a long spin needs
+const INSTANCE_FUEL: u64 = 300_000;
+
+fn sandbox(policy: FuelRefillPolicy, budget: Option<FuelBudget>) -> WasmSandbox {
+    WasmSandbox::with_config(SandboxConfig {
+        fuel_refill: Some(policy),
+        fuel_budget: budget,
+        ..SandboxConfig::default()
+    }).expect("Failed to create sandbox")
+}
+
+fn instantiate(sandbox: &mut WasmSandbox, fuel_class: FuelClass) -> InstanceId {
+    let config = InstanceConfig {
+        resource_limits: ResourceLimits {
+            fuel: Some(INSTANCE_FUEL),
+            ..ResourceLimits::default()
+        },
+        fuel_class,
+        ..InstanceConfig::default()
+    };
+    common::create_instance(sandbox, SPIN_MODULE, Some(config))
+}
+
+#[tokio::test]
+async fn test_interactive_call_is_topped_up() {
+    let mut sandbox = sandbox(FuelRefillPolicy::default().increment(250_000), None);
+    let instance_id = instantiate(&mut sandbox, FuelClass::Interactive);
+    
+    let result: i32 = sandbox.call_function(instance_id, "spin", 1_000_000).await.unwrap();
+    assert_eq!(result, 0);
+    
+    let metrics = sandbox.fuel_refill_metrics();
+    assert!(metrics.refills > 0);
+    assert_eq!(metrics.fuel_added, metrics.refills * 250_000);
+    assert_eq!(metrics.capped, 0);
+}
+
+#[tokio::test]
+async fn test_top_ups_stop_at_the_per_call_cap() {
+    let policy = FuelRefillPolicy::default().increment(250_000).max_per_call(500_000);
+    let mut sandbox = sandbox(policy, None);
+    let instance_id = instantiate(&mut sandbox, FuelClass::Interactive);
+    
+    let result = sandbox.call_function::<_, i32>(instance_id, "spin", 10_000_000).await;
+    assert!(result.is_err(), "the call should run out once the cap is reached");
+    
+    let metrics = sandbox.fuel_refill_metrics();
+    assert_eq!(metrics.fuel_added, 500_000);
+    assert!(metrics.capped > 0);
+    
+    // Batch instances get nothing without a budget to pay from
+    let batch = instantiate(&mut sandbox, FuelClass::Batch);
+    assert!(sandbox.call_function::<_, i32>(batch, "spin", 1_000_000).await.is_err());
+    assert_eq!(sandbox.fuel_refill_metrics().fuel_added, 500_000);
+}
+
+#[tokio::test]
+async fn test_batch_call_waits_for_the_budget() {
+    let budget = FuelBudget::new(1_000_000, Duration::from_millis(200));
+    let policy = FuelRefillPolicy::default().increment(250_000).retry_interval(Duration::from_millis(5));
+    let mut sandbox = sandbox(policy, Some(budget));
+    let instance_id = instantiate(&mut sandbox, FuelClass::Batch);
+    
+    // Needs several windows' worth of fuel, so has to be suspended along the way
+    let started = Instant::now();
+    let result: i32 = sandbox.call_function(instance_id, "spin", 500_000).await.unwrap();
+    assert_eq!(result, 0);
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    
+    let metrics = sandbox.fuel_refill_metrics();
+    assert!(metrics.refills > 0);
+    assert!(metrics.suspensions > 0);
+}
+
+#[test]
+fn test_invalid_policies_are_rejected() {
+    let without_fuel = WasmSandbox::with_config(SandboxConfig {
+        runtime: RuntimeConfig {
+            enable_fuel: false,
+            ..RuntimeConfig::default()
+        },
+        fuel_refill: Some(FuelRefillPolicy::default()),
+        ..SandboxConfig::default()
+    });
+    assert!(matches!(without_fuel, Err(Error::Configuration { .. })));
+    
+    let without_yields = WasmSandbox::with_config(SandboxConfig {
+        runtime: RuntimeConfig {
+            async_yield_fuel: None,
+            ..RuntimeConfig::default()
+        },
+        fuel_refill: Some(FuelRefillPolicy::default()),
+        ..SandboxConfig::default()
+    });
+    assert!(matches!(without_yields, Err(Error::Configuration { .. })));
+    
+    let too_small = WasmSandbox::with_config(SandboxConfig {
+        fuel_refill: Some(FuelRefillPolicy::default().increment(100_000)),
+        ..SandboxConfig::default()
+    });
+    assert!(matches!(too_small, Err(Error::Configuration { .. })));
+}
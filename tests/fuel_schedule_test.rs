@@ -0,0 +1,84 @@
+//! Tests for fuel cost schedules
+
+mod common;
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::security::ResourceLimits;
+use wasm_sandbox::{Error, FuelSchedule, InstanceConfig, InstanceId, WasmSandbox};
+
+#[test]
+fn test_fuel_schedule_presets() {
+    assert_eq!(FuelSchedule::preset("uniform").unwrap(), FuelSchedule::uniform());
+    assert_eq!(FuelSchedule::preset("io-heavy").unwrap(), FuelSchedule::io_heavy());
+    assert_eq!(FuelSchedule::preset("compute-heavy").unwrap(), FuelSchedule::compute_heavy());
+    assert!(FuelSchedule::preset("unknown").is_err());
+}
+
+#[test]
+fn test_fuel_schedule_extra_cost() {
+    assert_eq!(FuelSchedule::uniform().extra_cost(1_000, 4), 0);
+
+    let schedule = FuelSchedule::io_heavy();
+    assert_eq!(schedule.extra_cost(1_000, 2), 2 * 10_000 + 5_000);
+
+    let schedule = FuelSchedule::compute_heavy();
+    assert_eq!(schedule.extra_cost(1_000, 0), 1_000 + 100);
+}
+
+// `spin` runs a loop `n` times; `grow` grows memory by a page
+const SPIN_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1 4)
+  (func (export "spin") (param $n i32) (result i32)
+    (loop $again
+      (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+      (br_if $again (i32.gt_s (local.get $n) (i32.const 0))))
+    (local.get $n))
+  (func (export "grow") (result i32)
+    (memory.grow (i32.const 1))))
+"#;
+
+fn instantiate(fuel: u64, fuel_schedule: FuelSchedule) -> (WasmSandbox, InstanceId) {
+    let config = InstanceConfig {
+        resource_limits: ResourceLimits { fuel: Some(fuel), fuel_schedule, ..ResourceLimits::default() },
+        ..InstanceConfig::default()
+    };
+    common::instantiate(SPIN_MODULE, Some(config))
+}
+
+fn assert_out_of_fuel(err: Error) {
+    assert!(err.to_string().contains("fuel"), "unexpected error: {}", err);
+}
+
+#[tokio::test]
+async fn test_call_cost_stops_the_call_that_exceeds_the_budget() {
+    let schedule = FuelSchedule { call_cost: 3_000, ..FuelSchedule::uniform() };
+    let (sandbox, instance_id) = instantiate(10_000, schedule);
+
+    for _ in 0..3 {
+        sandbox.call_values(instance_id, "spin", &[HostValue::I32(1)]).await.unwrap();
+    }
+    let err = sandbox.call_values(instance_id, "spin", &[HostValue::I32(1)]).await.unwrap_err();
+    assert_out_of_fuel(err);
+}
+
+#[tokio::test]
+async fn test_weighted_instructions_stop_the_call_that_exceeds_the_budget() {
+    let schedule = FuelSchedule { instruction_weight: 100, ..FuelSchedule::uniform() };
+    let (sandbox, instance_id) = instantiate(10_000, schedule);
+
+    // Five hundred iterations fit the budget unweighted, but not at a hundred times the cost
+    sandbox.call_values(instance_id, "spin", &[HostValue::I32(5)]).await.unwrap();
+    let err = sandbox.call_values(instance_id, "spin", &[HostValue::I32(500)]).await.unwrap_err();
+    assert_out_of_fuel(err);
+}
+
+#[tokio::test]
+async fn test_memory_growth_stops_the_call_that_exceeds_the_budget() {
+    let schedule = FuelSchedule { memory_grow_per_page: 6_000, ..FuelSchedule::uniform() };
+    let (sandbox, instance_id) = instantiate(10_000, schedule);
+
+    sandbox.call_values(instance_id, "grow", &[]).await.unwrap();
+    let err = sandbox.call_values(instance_id, "grow", &[]).await.unwrap_err();
+    assert_out_of_fuel(err);
+}
@@ -0,0 +1,35 @@
+//! Tests for per-function capability overlays
+
+use wasm_sandbox::security::{Capabilities, NetworkCapability};
+use wasm_sandbox::security::capabilities::ActiveCapabilities;
+
+#[test]
+fn test_overlay_applies_only_within_scope() {
+    let active = ActiveCapabilities::new(Capabilities::minimal());
+    let mut overlay = Capabilities::minimal();
+    overlay.network = NetworkCapability::Loopback;
+
+    {
+        let _scope = active.enter("fetch", overlay);
+        assert_eq!(active.current().network, NetworkCapability::Loopback);
+        assert_eq!(active.active_function().as_deref(), Some("fetch"));
+        assert!(active.manager().network.is_host_allowed("localhost", 80, false));
+    }
+
+    assert_eq!(active.current().network, NetworkCapability::None);
+    assert!(active.active_function().is_none());
+}
+
+#[test]
+fn test_nested_scopes_restore_previous_overlay() {
+    let active = ActiveCapabilities::new(Capabilities::minimal());
+    let mut outer = Capabilities::minimal();
+    outer.network = NetworkCapability::Loopback;
+
+    let _outer_scope = active.enter("outer", outer);
+    {
+        let _inner_scope = active.enter("inner", Capabilities::minimal());
+        assert_eq!(active.current().network, NetworkCapability::None);
+    }
+    assert_eq!(active.active_function().as_deref(), Some("outer"));
+}
@@ -0,0 +1,68 @@
+//! Tests for memory and table growth hooks
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use wasm_sandbox::{GrowthDecision, InstanceId, WasmSandbox};
+
+// `grow` and `grow_table` return the previous size, or -1 if growth was refused
+const GROWING_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (table 1 funcref)
+  (func (export "grow") (param i32) (result i32)
+    (memory.grow (local.get 0)))
+  (func (export "grow_table") (param i32) (result i32)
+    (table.grow (ref.null func) (local.get 0))))
+"#;
+
+fn call(sandbox: &WasmSandbox, instance_id: InstanceId, function_name: &str, delta: i32) -> i32 {
+    sandbox.get_instance(instance_id).unwrap().instance
+        .call_simple_function(function_name, &[delta])
+        .expect("call failed")
+}
+
+#[test]
+fn test_memory_growth_is_reported() {
+    let (sandbox, instance_id) = common::instantiate(GROWING_MODULE, None);
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    sandbox.on_memory_grow(move |instance_id, from, to| {
+        recorded.lock().unwrap().push((instance_id, from, to));
+        GrowthDecision::Allow
+    });
+    
+    assert_eq!(call(&sandbox, instance_id, "grow", 2), 1);
+    assert_eq!(call(&sandbox, instance_id, "grow", 1), 3);
+    assert_eq!(*events.lock().unwrap(), vec![(instance_id, 1, 3), (instance_id, 3, 4)]);
+    assert_eq!(sandbox.memory_pages(instance_id).unwrap().current, 4);
+}
+
+#[test]
+fn test_hook_can_veto_memory_growth() {
+    let (sandbox, instance_id) = common::instantiate(GROWING_MODULE, None);
+    sandbox.on_memory_grow(|_, _, to| if to > 2 { GrowthDecision::Deny } else { GrowthDecision::Allow });
+    
+    assert_eq!(call(&sandbox, instance_id, "grow", 1), 1);
+    assert_eq!(call(&sandbox, instance_id, "grow", 1), -1);
+    assert_eq!(sandbox.memory_pages(instance_id).unwrap().current, 2);
+}
+
+#[test]
+fn test_table_growth_hooks() {
+    let (sandbox, instance_id) = common::instantiate(GROWING_MODULE, None);
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    sandbox.on_table_grow(move |_, from, to| {
+        recorded.lock().unwrap().push((from, to));
+        GrowthDecision::Allow
+    });
+    sandbox.on_table_grow(|_, _, to| if to > 8 { GrowthDecision::Deny } else { GrowthDecision::Allow });
+    
+    assert_eq!(call(&sandbox, instance_id, "grow_table", 3), 1);
+    assert_eq!(call(&sandbox, instance_id, "grow_table", 10), -1);
+    
+    // Every hook sees the request, even one another hook denies
+    assert_eq!(*events.lock().unwrap(), vec![(1, 4), (4, 14)]);
+}
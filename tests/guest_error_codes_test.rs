@@ -0,0 +1,113 @@
+//! Tests for the error codes host imports return to guests
+
+use wasm_sandbox::communication::broker::SERVICE_GRANT_CAPABILITY;
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::security::{Capabilities, CustomCapability};
+use wasm_sandbox::{Error, GuestErrorCode, InstanceConfig, InstanceId, ServiceQuota, WasmSandbox};
+
+// `rpc_status` calls `echo.echo` and `config_status` reads `missing`, both returning the raw status
+const PROBE_MODULE: &str = r#"
+(module
+  (import "sandbox_rpc" "call" (func $call (param i32 i32 i32 i32 i32 i32) (result i64)))
+  (import "sandbox_config" "get" (func $get (param i32 i32) (result i64)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "echo")
+  (data (i32.const 16) "missing")
+  
+  (func (export "alloc") (param i32) (result i32)
+    i32.const 1024)
+  
+  (func (export "echo") (param $ptr i32) (param $len i32) (result i64)
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+      (i64.extend_i32_u (local.get $len))))
+  
+  (func (export "rpc_status") (result i64)
+    (call $call (i32.const 0) (i32.const 4) (i32.const 0) (i32.const 4) (i32.const 0) (i32.const 4)))
+  
+  (func (export "config_status") (result i64)
+    (call $get (i32.const 16) (i32.const 7))))
+"#;
+
+fn setup(services: &[&str], expose: bool) -> (WasmSandbox, InstanceId) {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(PROBE_MODULE.as_bytes()).expect("Failed to load module");
+    
+    let mut capabilities = Capabilities::minimal();
+    capabilities.add_custom(
+        SERVICE_GRANT_CAPABILITY,
+        CustomCapability::StringList(services.iter().map(|s| s.to_string()).collect()),
+    );
+    let config = InstanceConfig {
+        capabilities,
+        ..InstanceConfig::default()
+    };
+    let caller = sandbox.create_instance(module_id, Some(config)).expect("Failed to create caller");
+    
+    if expose {
+        let provider = sandbox.create_instance(module_id, None).expect("Failed to create provider");
+        sandbox.expose_service(provider, "echo", &["echo"]).expect("Failed to expose service");
+    }
+    (sandbox, caller)
+}
+
+fn status(sandbox: &WasmSandbox, instance_id: InstanceId, function_name: &str) -> Option<GuestErrorCode> {
+    let results = sandbox.get_instance(instance_id).unwrap().instance
+        .call_values(function_name, &[])
+        .expect("probe failed");
+    match results.as_slice() {
+        [HostValue::I64(code)] => GuestErrorCode::from_code(*code),
+        other => panic!("unexpected results {:?}", other),
+    }
+}
+
+#[test]
+fn test_service_failures_map_to_codes() {
+    let (sandbox, caller) = setup(&["echo"], false);
+    assert_eq!(status(&sandbox, caller, "rpc_status"), Some(GuestErrorCode::NotFound));
+    
+    let (sandbox, caller) = setup(&["billing"], true);
+    assert_eq!(status(&sandbox, caller, "rpc_status"), Some(GuestErrorCode::PermissionDenied));
+    
+    let (sandbox, caller) = setup(&["echo"], true);
+    sandbox.service_broker().set_quota("echo", ServiceQuota {
+        max_calls: Some(1),
+        ..ServiceQuota::default()
+    });
+    assert_eq!(status(&sandbox, caller, "rpc_status"), None);
+    assert_eq!(status(&sandbox, caller, "rpc_status"), Some(GuestErrorCode::QuotaExceeded));
+}
+
+#[test]
+fn test_missing_setting_is_not_found() {
+    let (sandbox, caller) = setup(&[], false);
+    assert_eq!(status(&sandbox, caller, "config_status"), Some(GuestErrorCode::NotFound));
+}
+
+#[test]
+fn test_codes_are_stable() {
+    let expected = [
+        ("permission_denied", -1),
+        ("quota_exceeded", -2),
+        ("failed", -3),
+        ("not_found", -4),
+        ("throttled", -5),
+        ("invalid_input", -6),
+        ("unavailable", -7),
+        ("timeout", -8),
+    ];
+    for (code, (name, value)) in GuestErrorCode::ALL.into_iter().zip(expected) {
+        assert_eq!(code.name(), name);
+        assert_eq!(code.code(), value);
+        assert_eq!(GuestErrorCode::from_code(value), Some(code));
+    }
+    assert_eq!(GuestErrorCode::from_code(0), None);
+    
+    let error = Error::InvalidInput {
+        field: "payload".to_string(),
+        reason: "empty".to_string(),
+        suggestion: None,
+    };
+    assert_eq!(GuestErrorCode::from(&error), GuestErrorCode::InvalidInput);
+    assert_eq!(GuestErrorCode::from(&Error::Generic { message: "boom".to_string() }), GuestErrorCode::Failed);
+}
@@ -0,0 +1,100 @@
+//! Tests for guest logging and per-call ids
+
+mod common;
+
+use wasm_sandbox::security::audit::{AuditEventType, AuditLogger};
+use wasm_sandbox::{current_call_id, CallId, GuestErrorCode, HostNamespace, InstanceId, WasmSandbox};
+
+// `log` writes a fixed line at the given level; `call_id` returns the low half of the guest-visible call id
+const LOG_MODULE: &str = r#"
+(module
+  (import "sandbox_log" "write" (func $write (param i32 i32 i32) (result i32)))
+  (import "sandbox_log" "call_id" (func $call_id (result i64)))
+  (import "test.audit" "touch" (func $touch (param i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "hello from guest")
+  (func (export "log") (param $level i32) (result i32)
+    (call $write (local.get $level) (i32.const 0) (i32.const 16)))
+  (func (export "call_id") (param $x i32) (result i32)
+    (i32.wrap_i64 (call $call_id)))
+  (func (export "touch") (param $x i32) (result i32)
+    (call $touch (local.get $x))))
+"#;
+
+fn instantiate(audit: AuditLogger) -> (WasmSandbox, InstanceId) {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.register_host_namespace(
+        HostNamespace::new("test.audit")
+            .ungated()
+            .function("touch", move |args| {
+                audit.info(
+                    AuditEventType::Custom { event_type: "touch".to_string(), data: String::new() },
+                    "guest touched the host",
+                );
+                Ok(args.to_vec())
+            }),
+    ).unwrap();
+    let instance_id = common::create_instance(&mut sandbox, LOG_MODULE, None);
+    (sandbox, instance_id)
+}
+
+#[tokio::test]
+async fn test_guest_log_records_are_tagged_with_call_id() {
+    let (sandbox, instance_id) = instantiate(AuditLogger::new(16));
+    
+    let code: i32 = sandbox.call_function(instance_id, "log", 3).await.unwrap();
+    assert_eq!(code, 0);
+    let first = sandbox.last_call_id(instance_id).expect("call should have an id");
+    
+    let _: i32 = sandbox.call_function(instance_id, "log", 1).await.unwrap();
+    let second = sandbox.last_call_id(instance_id).unwrap();
+    assert_ne!(first, second);
+    
+    let records = sandbox.guest_log().records_for_call(first);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].message, "hello from guest");
+    assert_eq!(records[0].level, log::Level::Info);
+    assert_eq!(records[0].instance_id, instance_id);
+    assert_eq!(records[0].function_name.as_deref(), Some("log"));
+    
+    let records = sandbox.guest_log().records_for_call(second);
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].level, log::Level::Error);
+}
+
+#[tokio::test]
+async fn test_guest_sees_its_call_id() {
+    let (sandbox, instance_id) = instantiate(AuditLogger::new(16));
+    
+    let seen: i32 = sandbox.call_function(instance_id, "call_id", 0).await.unwrap();
+    let call_id = sandbox.last_call_id(instance_id).unwrap();
+    assert_eq!(seen, call_id.as_u64() as i32);
+    assert_eq!(call_id.to_string().len(), 16);
+}
+
+#[tokio::test]
+async fn test_invalid_level_is_rejected() {
+    let (sandbox, instance_id) = instantiate(AuditLogger::new(16));
+    
+    let code: i32 = sandbox.call_function(instance_id, "log", 9).await.unwrap();
+    assert_eq!(code as i64, GuestErrorCode::InvalidInput.code());
+    assert!(sandbox.guest_log().records().is_empty());
+}
+
+#[tokio::test]
+async fn test_audit_events_carry_call_id() {
+    let audit = AuditLogger::new(16);
+    let (sandbox, instance_id) = instantiate(audit.clone());
+    
+    audit.info(AuditEventType::Custom { event_type: "outside".to_string(), data: String::new() }, "no call running");
+    assert_eq!(current_call_id(), None);
+    
+    let _: i32 = sandbox.call_function(instance_id, "touch", 1).await.unwrap();
+    let call_id: CallId = sandbox.last_call_id(instance_id).unwrap();
+    
+    let events = audit.get_events();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].call_id, None);
+    assert_eq!(events[1].call_id, Some(call_id));
+    assert_eq!(current_call_id(), None);
+}
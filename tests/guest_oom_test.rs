@@ -0,0 +1,95 @@
+//! Tests for guests reporting failed allocations through `sandbox_alloc`
+
+mod common;
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::{Error, InstanceConfig, ResourceKind};
+
+// `alloc` reports a failure and returns null for inputs over 64 bytes;
+// `aborts` reports a failure then traps as a Rust guest's OOM handler would;
+// `recovers` reports a failure then carries on; `crashes` traps unreported
+const OOM_MODULE: &str = r#"
+(module
+  (import "sandbox_alloc" "failed" (func $failed (param i64) (result i32)))
+  (memory (export "memory") 1 2)
+  (func (export "alloc") (param $len i32) (result i32)
+    (if (i32.gt_u (local.get $len) (i32.const 64))
+      (then
+        (drop (call $failed (i64.extend_i32_u (local.get $len))))
+        (return (i32.const 0))))
+    (i32.const 1024))
+  (func $echo (export "echo") (param i32 i32) (result i64)
+    (i64.or (i64.shl (i64.extend_i32_u (local.get 0)) (i64.const 32)) (i64.extend_i32_u (local.get 1))))
+  (func (export "aborts") (param i32 i32) (result i64)
+    (drop (call $failed (i64.const 1048576)))
+    unreachable)
+  (func (export "recovers") (param i32 i32) (result i64)
+    (drop (call $failed (i64.const 1048576)))
+    (call $echo (local.get 0) (local.get 1)))
+  (func (export "crashes") (param i32 i32) (result i64)
+    unreachable))
+"#;
+
+fn assert_out_of_memory(err: Error, requested: u64) {
+    match err {
+        Error::ResourceExhausted { kind: ResourceKind::Memory, used, suggestion, .. } => {
+            assert!(used >= requested, "used {} < {}", used, requested);
+            assert!(suggestion.unwrap().contains(&requested.to_string()));
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_failed_input_allocation_is_resource_exhausted() {
+    let (sandbox, instance_id) = common::instantiate(OOM_MODULE, Some(InstanceConfig::default()));
+    
+    let echoed: String = sandbox.call_function(instance_id, "echo", "short").await.unwrap();
+    assert_eq!(echoed, "short");
+    
+    let params = "x".repeat(100);
+    let err = sandbox.call_function::<_, String>(instance_id, "echo", params.clone()).await.unwrap_err();
+    assert_out_of_memory(err, 102);
+    let err = sandbox.call_function_bytes(instance_id, "echo", params.as_bytes()).await.unwrap_err();
+    assert_out_of_memory(err, 100);
+    
+    // The instance is still usable
+    let echoed: String = sandbox.call_function(instance_id, "echo", "again").await.unwrap();
+    assert_eq!(echoed, "again");
+}
+
+#[tokio::test]
+async fn test_abort_after_reported_failure_is_resource_exhausted() {
+    let (sandbox, instance_id) = common::instantiate(OOM_MODULE, Some(InstanceConfig::default()));
+    let err = sandbox.call_function::<_, String>(instance_id, "aborts", "x").await.unwrap_err();
+    assert_out_of_memory(err, 1 << 20);
+}
+
+#[tokio::test]
+async fn test_recovered_failure_and_plain_traps_are_unaffected() {
+    let (sandbox, instance_id) = common::instantiate(OOM_MODULE, Some(InstanceConfig::default()));
+    
+    let echoed: String = sandbox.call_function(instance_id, "recovers", "fine").await.unwrap();
+    assert_eq!(echoed, "fine");
+    
+    // The earlier report does not leak into a later unrelated trap
+    let err = sandbox.call_function::<_, String>(instance_id, "crashes", "x").await.unwrap_err();
+    assert!(!matches!(err, Error::ResourceExhausted { .. }), "{}", err);
+}
+
+#[test]
+fn test_direct_calls_do_not_inherit_earlier_failures() {
+    let (sandbox, instance_id) = common::instantiate(OOM_MODULE, Some(InstanceConfig::default()));
+    let instance = &sandbox.get_instance(instance_id).unwrap().instance;
+    let args = [HostValue::I32(0), HostValue::I32(0)];
+    
+    // The limit comes from the memory's declared maximum of two pages
+    match instance.call_values("aborts", &args).unwrap_err() {
+        Error::ResourceExhausted { kind: ResourceKind::Memory, limit, .. } => assert_eq!(limit, 2 * 65536),
+        other => panic!("unexpected error: {}", other),
+    }
+    
+    assert!(instance.call_values("recovers", &args).is_ok());
+    let err = instance.call_values("crashes", &args).unwrap_err();
+    assert!(!matches!(err, Error::ResourceExhausted { .. }), "{}", err);
+}
@@ -0,0 +1,141 @@
+//! Tests for opaque handles to host objects
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::{Error, HandleTable, HostNamespace, InstanceConfig, InstanceId, WasmSandbox};
+
+const DB_MODULE: &str = r#"
+(module
+  (import "acme.db" "connect" (func $connect (result i32)))
+  (import "acme.db" "query" (func $query (param i32) (result i64)))
+  (import "acme.db" "close" (func $close (param i32)))
+  (import "acme.files" "open" (func $open (result i32)))
+  (func (export "connect") (result i32) (call $connect))
+  (func (export "query") (param i32) (result i64) (call $query (local.get 0)))
+  (func (export "close") (param i32) (call $close (local.get 0)))
+  (func (export "open") (result i32) (call $open)))
+"#;
+
+/// A host object that counts queries and records when it is dropped
+struct Connection {
+    queries: Mutex<i64>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.dropped.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+struct File;
+
+fn handle(args: &[HostValue]) -> u32 {
+    match args {
+        [HostValue::I32(handle)] => *handle as u32,
+        other => panic!("unexpected arguments {:?}", other),
+    }
+}
+
+fn sandbox(dropped: Arc<AtomicUsize>) -> WasmSandbox {
+    let sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let db = HostNamespace::new("acme.db")
+        .ungated()
+        .function_with_context("connect", move |context, _| {
+            let connection = Connection {
+                queries: Mutex::new(0),
+                dropped: dropped.clone(),
+            };
+            Ok(vec![HostValue::I32(context.handles.insert(connection)? as i32)])
+        })
+        .function_with_context("query", |context, args| {
+            let connection = context.handles.get::<Connection>(handle(args))?;
+            let mut queries = connection.queries.lock().unwrap();
+            *queries += 1;
+            Ok(vec![HostValue::I64(*queries)])
+        })
+        .function_with_context("close", |context, args| {
+            context.handles.remove::<Connection>(handle(args))?;
+            Ok(Vec::new())
+        });
+    let files = HostNamespace::new("acme.files")
+        .ungated()
+        .function_with_context("open", |context, _| Ok(vec![HostValue::I32(context.handles.insert(File)? as i32)]));
+    sandbox.register_host_namespaces([db, files]).unwrap();
+    sandbox
+}
+
+fn instantiate(sandbox: &mut WasmSandbox, max_handles: usize) -> InstanceId {
+    let mut config = InstanceConfig::default();
+    config.resource_limits.max_handles = max_handles;
+    common::create_instance(sandbox, DB_MODULE, Some(config))
+}
+
+fn call(sandbox: &WasmSandbox, instance_id: InstanceId, function_name: &str, args: &[HostValue]) -> wasm_sandbox::Result<Vec<HostValue>> {
+    sandbox.get_instance(instance_id).unwrap().instance.call_values(function_name, args)
+}
+
+#[test]
+fn test_guests_use_handles_across_calls() {
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let mut sandbox = sandbox(dropped.clone());
+    let instance_id = instantiate(&mut sandbox, 8);
+    
+    let connection = call(&sandbox, instance_id, "connect", &[]).unwrap()[0];
+    call(&sandbox, instance_id, "query", &[connection]).unwrap();
+    assert_eq!(call(&sandbox, instance_id, "query", &[connection]).unwrap(), vec![HostValue::I64(2)]);
+    
+    call(&sandbox, instance_id, "close", &[connection]).unwrap();
+    assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    assert!(call(&sandbox, instance_id, "query", &[connection]).is_err());
+    assert!(sandbox.get_instance(instance_id).unwrap().handles.is_empty());
+}
+
+#[test]
+fn test_handles_are_type_checked_and_per_instance() {
+    let mut sandbox = sandbox(Arc::new(AtomicUsize::new(0)));
+    let owner = instantiate(&mut sandbox, 8);
+    let other = instantiate(&mut sandbox, 8);
+    
+    let file = call(&sandbox, owner, "open", &[]).unwrap()[0];
+    let err = call(&sandbox, owner, "query", &[file]).unwrap_err();
+    assert!(err.to_string().contains("File"), "{}", err);
+    
+    // Another instance's handle means nothing here
+    let connection = call(&sandbox, owner, "connect", &[]).unwrap()[0];
+    assert!(call(&sandbox, other, "query", &[connection]).is_err());
+    assert!(call(&sandbox, owner, "query", &[connection]).is_ok());
+}
+
+#[test]
+fn test_handles_are_capped_and_closed_with_the_instance() {
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let mut sandbox = sandbox(dropped.clone());
+    let instance_id = instantiate(&mut sandbox, 2);
+    
+    call(&sandbox, instance_id, "connect", &[]).unwrap();
+    call(&sandbox, instance_id, "connect", &[]).unwrap();
+    let err = call(&sandbox, instance_id, "connect", &[]).unwrap_err();
+    assert!(err.to_string().contains("handles"), "{}", err);
+    assert_eq!(dropped.load(Ordering::SeqCst), 1, "the rejected connection is dropped");
+    
+    sandbox.remove_instance(instance_id);
+    assert_eq!(dropped.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_wrong_type_leaves_handle_open() {
+    let table = HandleTable::new(4);
+    let file = table.insert(File).unwrap();
+    
+    assert!(matches!(table.remove::<Connection>(file), Err(Error::InvalidInput { .. })));
+    assert_eq!(table.len(), 1);
+    assert!(table.type_of(file).unwrap().ends_with("File"));
+    assert!(table.remove::<File>(file).is_ok());
+    assert!(matches!(table.get::<File>(file), Err(Error::NotFound { .. })));
+    assert!(!table.close(file));
+}
@@ -0,0 +1,123 @@
+//! Tests for the health check and Prometheus metrics endpoints
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use wasm_sandbox::observability::http::{render_metrics, HealthEndpoints, METRICS_CONTENT_TYPE};
+use wasm_sandbox::{HttpRequest, InstanceId, WasmSandbox};
+
+// `check` traps when its argument is zero
+const CHECK_MODULE: &str = r#"
+(module
+  (func (export "check") (param i32) (result i32)
+    (if (i32.eqz (local.get 0)) (then unreachable))
+    (local.get 0)))
+"#;
+
+fn sandbox_with(count: usize) -> (WasmSandbox, Vec<InstanceId>) {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(CHECK_MODULE.as_bytes()).unwrap();
+    let instances = (0..count).map(|_| sandbox.create_instance(module_id, None).unwrap()).collect();
+    (sandbox, instances)
+}
+
+async fn check(sandbox: &WasmSandbox, instance_id: InstanceId, value: i32) -> bool {
+    sandbox.call_function::<_, i32>(instance_id, "check", value).await.is_ok()
+}
+
+#[tokio::test]
+async fn test_health_tracks_failing_instances() {
+    let (mut sandbox, instances) = sandbox_with(2);
+    assert!(check(&sandbox, instances[0], 1).await);
+    assert!(!check(&sandbox, instances[1], 0).await);
+    
+    let health = sandbox.health();
+    assert_eq!(health.instances, 2);
+    assert_eq!(health.failing_instances, vec![instances[1]]);
+    
+    // A successful call clears the failure, as does removing the instance
+    assert!(check(&sandbox, instances[1], 1).await);
+    assert!(sandbox.health().failing_instances.is_empty());
+    assert!(!check(&sandbox, instances[0], 0).await);
+    sandbox.remove_instance(instances[0]);
+    assert!(sandbox.health().failing_instances.is_empty());
+}
+
+#[tokio::test]
+async fn test_healthz_reports_unhealthy_past_the_limit() {
+    let (sandbox, instances) = sandbox_with(2);
+    let endpoints = HealthEndpoints::new().max_failing_instances(1);
+    let healthz = HttpRequest::new("GET", "/healthz");
+    
+    let response = endpoints.handle(&sandbox, &healthz);
+    assert_eq!(response.status, 200);
+    let body: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["instances"], 2);
+    
+    for instance_id in &instances {
+        assert!(!check(&sandbox, *instance_id, 0).await);
+    }
+    let response = endpoints.handle(&sandbox, &healthz);
+    assert_eq!(response.status, 503);
+    let body: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+    assert_eq!(body["failing_instances"].as_array().unwrap().len(), 2);
+    
+    // Without a limit failing instances don't make the sandbox unhealthy
+    assert_eq!(HealthEndpoints::new().handle(&sandbox, &healthz).status, 200);
+    assert_eq!(endpoints.handle(&sandbox, &HttpRequest::new("GET", "/other")).status, 404);
+    assert_eq!(endpoints.handle(&sandbox, &HttpRequest::new("POST", "/healthz")).status, 405);
+}
+
+#[tokio::test]
+async fn test_metrics_use_the_prometheus_text_format() {
+    let (sandbox, instances) = sandbox_with(1);
+    assert!(check(&sandbox, instances[0], 1).await);
+    assert!(!check(&sandbox, instances[0], 0).await);
+    
+    let response = HealthEndpoints::new().handle(&sandbox, &HttpRequest::new("GET", "/metrics"));
+    assert_eq!(response.status, 200);
+    assert!(response.headers.contains(&("content-type".to_string(), METRICS_CONTENT_TYPE.to_string())));
+    
+    let text = String::from_utf8(response.body).unwrap();
+    assert_eq!(text.lines().count(), render_metrics(&sandbox.health()).lines().count());
+    for line in text.lines().filter(|line| !line.starts_with('#')) {
+        let (name, value) = line.rsplit_once(' ').unwrap();
+        assert!(name.starts_with("wasm_sandbox_"), "unexpected sample {}", line);
+        value.parse::<f64>().unwrap_or_else(|_| panic!("unparsable value in {}", line));
+    }
+    assert!(text.contains("# TYPE wasm_sandbox_calls_total counter\nwasm_sandbox_calls_total 2\n"));
+    assert!(text.contains("wasm_sandbox_failed_calls_total 1\n"));
+    assert!(text.contains("wasm_sandbox_instances 1\n"));
+    assert!(text.contains("wasm_sandbox_failing_instances 1\n"));
+    assert!(text.contains("wasm_sandbox_call_latency_seconds{quantile=\"0.99\"}"));
+    assert!(text.contains("wasm_sandbox_module_calls_total{module="));
+}
+
+#[tokio::test]
+async fn test_listener_serves_both_endpoints() {
+    let (sandbox, _) = sandbox_with(1);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let endpoints = HealthEndpoints::new();
+    
+    let get = |path: &'static str| async move {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(format!("GET {} HTTP/1.1\r\nhost: localhost\r\n\r\n", path).as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    };
+    let requests = async {
+        (get("/healthz").await, get("/metrics").await)
+    };
+    let (healthz, metrics) = tokio::select! {
+        responses = requests => responses,
+        result = endpoints.serve(&sandbox, listener) => panic!("listener stopped: {:?}", result),
+    };
+    
+    assert!(healthz.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(healthz.contains(r#""status":"ok""#));
+    assert!(metrics.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(metrics.contains("wasm_sandbox_instances 1\n"));
+}
@@ -0,0 +1,134 @@
+//! Tests for hibernating idle instances to disk
+
+mod common;
+
+use std::time::Duration;
+
+use wasm_sandbox::{Error, HibernationConfig, InstanceConfig, InstanceId, SandboxConfig, WasmSandbox};
+
+// `bump` counts its calls in memory, so the count only survives if memory does
+const COUNTER_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "bump") (param $by i32) (result i32)
+    (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (local.get $by)))
+    (i32.load (i32.const 0))))
+"#;
+
+// `tick` counts its calls in an exported mutable global instead
+const GLOBAL_COUNTER_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $count (export "count") (mut i32) (i32.const 0))
+  (func (export "tick") (result i32)
+    (global.set $count (i32.add (global.get $count) (i32.const 1)))
+    (global.get $count)))
+"#;
+
+fn sandbox(dir: &tempfile::TempDir) -> WasmSandbox {
+    WasmSandbox::with_config(SandboxConfig {
+        hibernation: Some(HibernationConfig::new(dir.path())),
+        ..SandboxConfig::default()
+    }).expect("Failed to create sandbox")
+}
+
+fn instantiate(sandbox: &mut WasmSandbox, max_idle_time_ms: Option<u64>) -> InstanceId {
+    let mut config = InstanceConfig::default();
+    config.resource_limits.time.max_idle_time_ms = max_idle_time_ms;
+    common::create_instance(sandbox, COUNTER_MODULE, Some(config))
+}
+
+async fn bump(sandbox: &WasmSandbox, instance_id: InstanceId) -> i32 {
+    sandbox.call_function(instance_id, "bump", 1).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_idle_instance_hibernates_and_wakes_on_call() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sandbox = sandbox(&dir);
+    let instance_id = instantiate(&mut sandbox, Some(20));
+    assert_eq!(bump(&sandbox, instance_id).await, 1);
+    assert!(sandbox.hibernate_idle_instances().is_empty());
+    
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(sandbox.hibernate_idle_instances(), vec![instance_id]);
+    assert!(sandbox.is_hibernated(instance_id));
+    assert_eq!(sandbox.total_memory_usage(), 0);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    
+    // The next call restores the instance with its memory intact
+    assert_eq!(bump(&sandbox, instance_id).await, 2);
+    assert!(!sandbox.is_hibernated(instance_id));
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    
+    let metrics = sandbox.hibernation_metrics();
+    assert_eq!((metrics.hibernations, metrics.restores, metrics.failures), (1, 1, 0));
+    assert_eq!(metrics.bytes_freed, 65536);
+    assert!(metrics.max_restore_latency <= metrics.restore_time);
+}
+
+#[tokio::test]
+async fn test_waking_restores_globals_and_fuel() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sandbox = sandbox(&dir);
+    let mut config = InstanceConfig::default();
+    config.resource_limits.fuel = Some(1_000_000);
+    let instance_id = common::create_instance(&mut sandbox, GLOBAL_COUNTER_MODULE, Some(config));
+    let tick = |sandbox: &WasmSandbox| sandbox.get_instance(instance_id).unwrap().instance.call_simple_function("tick", &[]);
+    tick(&sandbox).unwrap();
+    assert_eq!(tick(&sandbox).unwrap(), 2);
+    let used = sandbox.get_instance(instance_id).unwrap().instance.fuel_usage().unwrap();
+    assert!(used > 0);
+    
+    sandbox.hibernate_instance(instance_id).unwrap();
+    // Pinning wakes the instance without calling it
+    sandbox.pin_instance(instance_id).unwrap();
+    assert!(!sandbox.is_hibernated(instance_id));
+    assert_eq!(sandbox.get_instance(instance_id).unwrap().instance.fuel_usage(), Some(used));
+    assert_eq!(tick(&sandbox).unwrap(), 3);
+}
+
+#[tokio::test]
+async fn test_pinned_instances_stay_in_memory() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sandbox = sandbox(&dir);
+    let pinned = instantiate(&mut sandbox, Some(0));
+    let unbounded = instantiate(&mut sandbox, None);
+    assert_eq!(bump(&sandbox, pinned).await, 1);
+    
+    sandbox.pin_instance(pinned).unwrap();
+    assert!(sandbox.hibernate_idle_instances().is_empty());
+    assert!(!sandbox.is_hibernated(unbounded));
+    
+    // Pinning a hibernated instance restores it
+    sandbox.unpin_instance(pinned);
+    assert_eq!(sandbox.hibernate_idle_instances(), vec![pinned]);
+    sandbox.pin_instance(pinned).unwrap();
+    assert!(!sandbox.is_hibernated(pinned));
+    assert_eq!(bump(&sandbox, pinned).await, 2);
+}
+
+#[tokio::test]
+async fn test_removing_a_hibernated_instance_deletes_its_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut sandbox = sandbox(&dir);
+    let instance_id = instantiate(&mut sandbox, None);
+    assert_eq!(bump(&sandbox, instance_id).await, 1);
+    
+    sandbox.hibernate_instance(instance_id).unwrap();
+    let direct = sandbox.get_instance(instance_id).unwrap().instance.call_simple_function("bump", &[1]);
+    assert!(matches!(direct, Err(Error::Instance { .. })));
+    
+    sandbox.remove_instance(instance_id);
+    assert!(!sandbox.is_hibernated(instance_id));
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_hibernation_requires_configuration() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let instance_id = instantiate(&mut sandbox, Some(0));
+    
+    assert!(sandbox.hibernate_idle_instances().is_empty());
+    assert!(matches!(sandbox.hibernate_instance(instance_id), Err(Error::Configuration { .. })));
+}
@@ -0,0 +1,137 @@
+//! Tests for OS-level hardening of helper processes
+
+use wasm_sandbox::{HardeningMeasure, HostHardening};
+
+#[cfg(target_os = "linux")]
+use std::process::Command;
+#[cfg(target_os = "linux")]
+use wasm_sandbox::runtime::compilation::{serve_compile_request, EngineSettings};
+#[cfg(target_os = "linux")]
+use wasm_sandbox::runtime::RuntimeConfig;
+#[cfg(target_os = "linux")]
+use wasm_sandbox::{CompilationIsolation, Error, SandboxConfig, SubprocessCompiler, WasmSandbox};
+
+/// Set in the child process a test re-runs itself in
+#[cfg(target_os = "linux")]
+const CHILD_ENV: &str = "WASM_SANDBOX_HARDENING_CHILD";
+
+/// Re-run `test` alone in a child process, since hardening can't be undone
+#[cfg(target_os = "linux")]
+fn run_in_child(test: &str) {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", test, "--nocapture", "--test-threads=1"])
+        .env(CHILD_ENV, "1")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}{}", stdout, String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("1 passed"), "{}", stdout);
+}
+
+#[test]
+fn test_defaults_and_builders() {
+    let hardening = HostHardening::new();
+    assert!(hardening.no_new_privileges && hardening.seccomp && hardening.landlock);
+    assert!(!hardening.has_limits() && !hardening.require_all);
+    assert_eq!(serde_json::from_str::<HostHardening>("{}").unwrap(), hardening);
+    
+    let hardening = HostHardening::none().max_open_files(32).allow_read("/usr/lib").require_all(true);
+    assert!(!hardening.no_new_privileges && !hardening.seccomp && !hardening.landlock);
+    assert!(hardening.has_limits());
+    assert_eq!(hardening.readable_paths, [std::path::PathBuf::from("/usr/lib")]);
+    
+    let json = serde_json::to_string(&hardening).unwrap();
+    assert_eq!(serde_json::from_str::<HostHardening>(&json).unwrap(), hardening);
+    assert_eq!(HardeningMeasure::NoNewPrivileges.to_string(), "no_new_privileges");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_compiler_process_starts_hardened() {
+    let script = "cat > /dev/null; grep -E '^(NoNewPrivs|Seccomp):' /proc/self/status >&2; \
+                  echo files $(ulimit -n) memory $(ulimit -v) >&2; exit 1";
+    let compiler = SubprocessCompiler::new("sh").arg("-c").arg(script)
+        .hardening(HostHardening::new().max_open_files(64).max_memory_bytes(4 << 30));
+    let sandbox = WasmSandbox::with_config(SandboxConfig {
+        runtime: RuntimeConfig {
+            compilation: CompilationIsolation::Subprocess(compiler),
+            ..RuntimeConfig::default()
+        },
+        ..SandboxConfig::default()
+    }).unwrap();
+    
+    match sandbox.load_module(b"(module)") {
+        Err(Error::Compilation { message }) => {
+            assert!(message.contains("NoNewPrivs:\t1"), "{}", message);
+            assert!(message.contains("Seccomp:\t2"), "{}", message);
+            assert!(message.contains("files 64 memory 4194304"), "{}", message);
+        }
+        other => panic!("expected a compilation error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_apply_denies_network_and_filesystem() {
+    if std::env::var_os(CHILD_ENV).is_none() {
+        return run_in_child("test_apply_denies_network_and_filesystem");
+    }
+    let allowed = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+    let denied = concat!(env!("CARGO_MANIFEST_DIR"), "/src/lib.rs");
+    
+    let report = HostHardening::new().allow_read(allowed).apply().unwrap();
+    assert!(report.applied.contains(&HardeningMeasure::NoNewPrivileges));
+    assert!(report.applied.contains(&HardeningMeasure::Seccomp));
+    
+    let err = std::net::TcpListener::bind("127.0.0.1:0").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    assert!(std::fs::read(allowed).is_ok());
+    if report.applied.contains(&HardeningMeasure::Landlock) {
+        assert!(std::fs::read(denied).is_err());
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_apply_denies_io_uring_and_exec() {
+    if std::env::var_os(CHILD_ENV).is_none() {
+        return run_in_child("test_apply_denies_io_uring_and_exec");
+    }
+    let report = HostHardening::none().seccomp(true).apply().unwrap();
+    assert!(report.applied.contains(&HardeningMeasure::Seccomp));
+    
+    // `struct io_uring_params` is 120 bytes
+    let mut params = [0u8; 120];
+    // SAFETY: `params` is large enough for the kernel to fill in
+    let fd = unsafe { libc::syscall(libc::SYS_io_uring_setup, 8u32, params.as_mut_ptr()) };
+    assert_eq!(fd, -1);
+    assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EPERM));
+    for syscall in [libc::SYS_io_uring_enter, libc::SYS_io_uring_register] {
+        // SAFETY: the filter rejects the call before the kernel looks at its arguments
+        assert_eq!(unsafe { libc::syscall(syscall, -1, 0, 0, 0, 0, 0) }, -1);
+        assert_eq!(std::io::Error::last_os_error().raw_os_error(), Some(libc::EPERM));
+    }
+    
+    let err = Command::new("true").status().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_worker_compiles_after_hardening_itself() {
+    if std::env::var_os(CHILD_ENV).is_none() {
+        return run_in_child("test_worker_compiles_after_hardening_itself");
+    }
+    // The header the host sends with a hardened compiler
+    let mut header = serde_json::to_value(EngineSettings::from(&RuntimeConfig::default())).unwrap();
+    header["hardening"] = serde_json::to_value(HostHardening::new().max_open_files(64)).unwrap();
+    let header = serde_json::to_vec(&header).unwrap();
+    let mut request = (header.len() as u32).to_le_bytes().to_vec();
+    request.extend_from_slice(&header);
+    request.extend_from_slice(br#"(module (func (export "f") (result i32) (i32.const 7)))"#);
+    
+    let mut artifact = Vec::new();
+    serve_compile_request(request.as_slice(), &mut artifact).unwrap();
+    assert!(!artifact.is_empty());
+    assert!(std::net::UdpSocket::bind("127.0.0.1:0").is_err());
+}
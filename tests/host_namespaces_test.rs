@@ -0,0 +1,113 @@
+//! Tests for namespaced host functions
+
+mod common;
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::security::{Capabilities, CustomCapability};
+use wasm_sandbox::{Error, HostNamespace, InstanceConfig, InstanceId, WasmSandbox, HOST_NAMESPACE_CAPABILITY};
+
+// `charge` forwards to the billing namespace, `log` to the ungated logging one
+const PLUGIN_MODULE: &str = r#"
+(module
+  (import "acme.billing" "charge" (func $charge (param i64) (result i64)))
+  (import "host.log" "write" (func $write (param i32) (result i32)))
+  (func (export "charge") (param $cents i64) (result i64)
+    (call $charge (local.get $cents)))
+  (func (export "log") (param $level i32) (result i32)
+    (call $write (local.get $level))))
+"#;
+
+fn billing(provider: &str) -> HostNamespace {
+    HostNamespace::new("acme.billing")
+        .provider(provider)
+        .function("charge", |args| match args {
+            [HostValue::I64(cents)] => Ok(vec![HostValue::I64(cents * 2)]),
+            _ => unreachable!(),
+        })
+}
+
+fn logging() -> HostNamespace {
+    HostNamespace::new("host.log")
+        .ungated()
+        .function("write", |args| Ok(args.to_vec()))
+}
+
+fn granted(namespaces: &[&str]) -> InstanceConfig {
+    let mut capabilities = Capabilities::minimal();
+    capabilities.add_custom(
+        HOST_NAMESPACE_CAPABILITY,
+        CustomCapability::StringList(namespaces.iter().map(|s| s.to_string()).collect()),
+    );
+    InstanceConfig {
+        capabilities,
+        ..InstanceConfig::default()
+    }
+}
+
+fn instantiate(sandbox: &mut WasmSandbox, config: InstanceConfig) -> InstanceId {
+    common::create_instance(sandbox, PLUGIN_MODULE, Some(config))
+}
+
+fn call(sandbox: &WasmSandbox, instance_id: InstanceId, function_name: &str, arg: HostValue) -> wasm_sandbox::Result<Vec<HostValue>> {
+    sandbox.get_instance(instance_id).unwrap().instance.call_values(function_name, &[arg])
+}
+
+#[test]
+fn test_granted_namespaces_are_callable() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.register_host_namespaces([billing("billing-plugin"), logging()]).unwrap();
+    
+    let exact = instantiate(&mut sandbox, granted(&["acme.billing"]));
+    assert_eq!(call(&sandbox, exact, "charge", HostValue::I64(21)).unwrap(), vec![HostValue::I64(42)]);
+    
+    // A prefix grant covers every namespace below it
+    let prefixed = instantiate(&mut sandbox, granted(&["acme.*"]));
+    assert_eq!(call(&sandbox, prefixed, "charge", HostValue::I64(5)).unwrap(), vec![HostValue::I64(10)]);
+}
+
+#[test]
+fn test_ungranted_namespaces_are_denied() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.register_host_namespaces([billing("billing-plugin"), logging()]).unwrap();
+    
+    let instance_id = instantiate(&mut sandbox, granted(&["acme.billingx", "acm.*"]));
+    let err = call(&sandbox, instance_id, "charge", HostValue::I64(1)).unwrap_err();
+    assert!(err.to_string().contains("acme.billing"), "{}", err);
+    
+    // Ungated namespaces need no grant
+    assert_eq!(call(&sandbox, instance_id, "log", HostValue::I32(3)).unwrap(), vec![HostValue::I32(3)]);
+}
+
+#[test]
+fn test_conflicting_providers_are_rejected() {
+    let sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.register_host_namespace(billing("billing-plugin")).unwrap();
+    
+    let err = sandbox.register_host_namespace(billing("rival-plugin")).unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("acme.billing.charge"), "{}", message);
+    assert!(message.contains("rival-plugin") && message.contains("billing-plugin"), "{}", message);
+    
+    // Bulk registration is all or nothing
+    let refunds = HostNamespace::new("acme.refunds").function("refund", |_| Ok(Vec::new()));
+    assert!(sandbox.register_host_namespaces([refunds, billing("rival-plugin")]).is_err());
+    assert_eq!(sandbox.host_functions().functions(), vec!["acme.billing.charge".to_string()]);
+    
+    // Once the owner is gone the name is free again
+    assert_eq!(sandbox.host_functions().unregister_provider("billing-plugin"), 1);
+    sandbox.register_host_namespace(billing("rival-plugin")).unwrap();
+    assert_eq!(sandbox.host_functions().provider_of("acme.billing.charge").as_deref(), Some("rival-plugin"));
+}
+
+#[test]
+fn test_reserved_and_invalid_names_are_rejected() {
+    let sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    for namespace in ["env", "wasi_snapshot_preview1", "sandbox_rpc", "acme..billing", "acme billing", ""] {
+        let result = sandbox.register_host_namespace(HostNamespace::new(namespace).function("f", |_| Ok(Vec::new())));
+        assert!(matches!(result, Err(Error::Configuration { .. } | Error::InvalidInput { .. })), "{}", namespace);
+    }
+    
+    let dotted = HostNamespace::new("acme").function("billing.charge", |_| Ok(Vec::new()));
+    assert!(matches!(sandbox.register_host_namespace(dotted), Err(Error::InvalidInput { .. })));
+    assert!(sandbox.host_functions().is_empty());
+}
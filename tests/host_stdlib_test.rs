@@ -0,0 +1,120 @@
+//! Tests for the standard host functions
+
+mod common;
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::security::imports::ImportPolicy;
+use wasm_sandbox::{
+    GuestErrorCode, InstanceConfig, InstanceId, RandomCapability, SandboxConfig, SandboxError, WasmSandbox,
+};
+
+const STDLIB_MODULE: &str = r#"
+(module
+  (import "sandbox_std" "monotonic_now" (func $now (result i64)))
+  (import "sandbox_std" "random_fill" (func $random (param i32 i32) (result i32)))
+  (import "sandbox_std" "uuid_v4" (func $uuid (param i32) (result i32)))
+  (import "sandbox_std" "sha256" (func $sha256 (param i32 i32 i32) (result i32)))
+  (import "sandbox_std" "base64_encode" (func $encode (param i32 i32) (result i64)))
+  (import "sandbox_std" "base64_decode" (func $decode (param i32 i32) (result i64)))
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 4096))
+  (data (i32.const 0) "abc")
+  (data (i32.const 16) "aGVsbG8=")
+  (data (i32.const 32) "not base64!")
+  
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  
+  (func (export "now") (result i64) (call $now))
+  (func (export "random") (result i32) (call $random (i32.const 256) (i32.const 32)))
+  (func (export "uuid") (result i32) (call $uuid (i32.const 512)))
+  (func (export "sha256") (result i32) (call $sha256 (i32.const 0) (i32.const 3) (i32.const 1024)))
+  (func (export "encode") (result i64) (call $encode (i32.const 0) (i32.const 3)))
+  (func (export "decode") (result i64) (call $decode (i32.const 16) (i32.const 8)))
+  (func (export "decode_invalid") (result i64) (call $decode (i32.const 32) (i32.const 11))))
+"#;
+
+fn instantiate(random: RandomCapability) -> (WasmSandbox, InstanceId) {
+    let mut config = SandboxConfig::default();
+    config.runtime.import_policy = ImportPolicy::default().allow_host_stdlib();
+    let mut sandbox = WasmSandbox::with_config(config).expect("Failed to create sandbox");
+    
+    let mut instance_config = InstanceConfig::default();
+    instance_config.capabilities.random = random;
+    let instance_id = common::create_instance(&mut sandbox, STDLIB_MODULE, Some(instance_config));
+    (sandbox, instance_id)
+}
+
+fn call(sandbox: &WasmSandbox, instance_id: InstanceId, function_name: &str) -> i64 {
+    let results = sandbox.get_instance(instance_id).unwrap().instance
+        .call_values(function_name, &[])
+        .expect("call failed");
+    match results.as_slice() {
+        [HostValue::I32(value)] => *value as i64,
+        [HostValue::I64(value)] => *value,
+        other => panic!("unexpected results {:?}", other),
+    }
+}
+
+fn read(sandbox: &WasmSandbox, instance_id: InstanceId, offset: usize, len: usize) -> Vec<u8> {
+    sandbox.get_instance(instance_id).unwrap().instance.read_memory_at(offset, len).unwrap()
+}
+
+fn read_packed(sandbox: &WasmSandbox, instance_id: InstanceId, packed: i64) -> Vec<u8> {
+    let packed = packed as u64;
+    read(sandbox, instance_id, (packed >> 32) as usize, (packed & 0xffff_ffff) as usize)
+}
+
+#[test]
+fn test_stdlib_is_opt_in() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(STDLIB_MODULE.as_bytes()).expect("Failed to load module");
+    
+    match sandbox.create_instance(module_id, None) {
+        Err(SandboxError::UnknownImports { report }) => assert_eq!(report.unknown.len(), 6),
+        other => panic!("expected unknown imports error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_hash_and_base64() {
+    let (sandbox, instance_id) = instantiate(RandomCapability::PseudoOnly);
+    
+    assert_eq!(call(&sandbox, instance_id, "sha256"), 0);
+    let digest = read(&sandbox, instance_id, 1024, 32);
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    assert_eq!(hex, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    
+    let encoded = call(&sandbox, instance_id, "encode");
+    assert_eq!(read_packed(&sandbox, instance_id, encoded), b"YWJj");
+    let decoded = call(&sandbox, instance_id, "decode");
+    assert_eq!(read_packed(&sandbox, instance_id, decoded), b"hello");
+    assert_eq!(call(&sandbox, instance_id, "decode_invalid"), GuestErrorCode::InvalidInput.code());
+}
+
+#[test]
+fn test_clock_is_monotonic() {
+    let (sandbox, instance_id) = instantiate(RandomCapability::PseudoOnly);
+    
+    let first = call(&sandbox, instance_id, "now");
+    let second = call(&sandbox, instance_id, "now");
+    assert!(first >= 0 && second >= first);
+}
+
+#[test]
+fn test_randomness_requires_capability() {
+    let (sandbox, instance_id) = instantiate(RandomCapability::Full);
+    assert_eq!(call(&sandbox, instance_id, "random"), 0);
+    assert_ne!(read(&sandbox, instance_id, 256, 32), vec![0; 32]);
+    assert_eq!(call(&sandbox, instance_id, "uuid"), 0);
+    let uuid = read(&sandbox, instance_id, 512, 16);
+    assert_eq!(uuid[6] >> 4, 4);
+    
+    let (sandbox, instance_id) = instantiate(RandomCapability::None);
+    assert_eq!(call(&sandbox, instance_id, "random"), GuestErrorCode::PermissionDenied.code());
+    assert_eq!(call(&sandbox, instance_id, "uuid"), GuestErrorCode::PermissionDenied.code());
+    assert_eq!(read(&sandbox, instance_id, 512, 16), vec![0; 16]);
+}
@@ -0,0 +1,296 @@
+//! Tests for guests' HTTP requests sent by the host under an egress policy
+
+#![cfg(feature = "http-client")]
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use base64::Engine as _;
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls;
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::security::http::{HttpRequest, HttpResponse};
+use wasm_sandbox::security::{Capabilities, HostSpec, NetworkCapability, PortRange};
+use wasm_sandbox::{
+    AuditEventKind, Error, GuestErrorCode, HttpEgressPolicy, HttpHostPolicy, HttpProxy, HttpRetryPolicy,
+    InstanceConfig, InstanceId, WasmSandbox,
+};
+
+const CA: &str = include_str!("../fixtures/tls/ca.pem");
+const OTHER_CA: &str = include_str!("../fixtures/tls/other-ca.pem");
+const SERVER_CERT: &str = include_str!("../fixtures/tls/server.pem");
+const SERVER_KEY: &str = include_str!("../fixtures/tls/server.key");
+const CLIENT_IDENTITY: &str = include_str!("../fixtures/tls/client-identity.pem");
+
+/// `fetch` forwards its input to the host; `denied` requests a host outside the capability
+const FETCH_MODULE: &str = r#"
+(module
+  (import "sandbox_http" "fetch" (func $fetch (param i32 i32) (result i64)))
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 4096))
+  (data (i32.const 0) "{\"url\":\"http://evil.test/\"}")
+  
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  
+  (func (export "fetch") (param $ptr i32) (param $len i32) (result i64)
+    (call $fetch (local.get $ptr) (local.get $len)))
+  (func (export "denied") (result i64) (call $fetch (i32.const 0) (i32.const 27))))
+"#;
+
+/// A local server answering each request with the response `respond` gives for its attempt number
+struct TestServer {
+    port: u16,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl TestServer {
+    async fn start(respond: fn(usize) -> Option<&'static str>) -> Self {
+        Self::start_with(respond, None).await
+    }
+    
+    async fn start_tls(respond: fn(usize) -> Option<&'static str>) -> Self {
+        let roots = {
+            let mut roots = rustls::RootCertStore::empty();
+            roots.add(CertificateDer::from_pem_slice(CA.as_bytes()).unwrap()).unwrap();
+            Arc::new(roots)
+        };
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let client_verifier = rustls::server::WebPkiClientVerifier::builder_with_provider(roots, provider.clone())
+            .build()
+            .unwrap();
+        let config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(
+                vec![CertificateDer::from_pem_slice(SERVER_CERT.as_bytes()).unwrap()],
+                PrivateKeyDer::from_pem_slice(SERVER_KEY.as_bytes()).unwrap(),
+            )
+            .unwrap();
+        Self::start_with(respond, Some(tokio_rustls::TlsAcceptor::from(Arc::new(config)))).await
+    }
+    
+    async fn start_with(respond: fn(usize) -> Option<&'static str>, tls: Option<tokio_rustls::TlsAcceptor>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let recorded = recorded.clone();
+                let tls = tls.clone();
+                tokio::spawn(async move {
+                    match tls {
+                        Some(acceptor) => {
+                            if let Ok(stream) = acceptor.accept(stream).await {
+                                handle(stream, recorded, respond).await;
+                            }
+                        }
+                        None => handle(stream, recorded, respond).await,
+                    }
+                });
+            }
+        });
+        Self { port, requests }
+    }
+    
+    fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Record one request's head and answer it, or hang if `respond` gives nothing
+async fn handle(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    recorded: Arc<Mutex<Vec<String>>>,
+    respond: fn(usize) -> Option<&'static str>,
+) {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+    let attempt = {
+        let mut recorded = recorded.lock().unwrap();
+        recorded.push(String::from_utf8_lossy(&request).to_ascii_lowercase());
+        recorded.len()
+    };
+    match respond(attempt) {
+        Some(response) => {
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        }
+        None => tokio::time::sleep(Duration::from_secs(30)).await,
+    }
+}
+
+fn loopback() -> Capabilities {
+    Capabilities {
+        network: NetworkCapability::Loopback,
+        ..Capabilities::minimal()
+    }
+}
+
+fn get(url: &str) -> HttpRequest {
+    HttpRequest {
+        method: "GET".to_string(),
+        url: url.to_string(),
+        headers: BTreeMap::new(),
+        body: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn test_guest_requests_go_through_the_authenticated_proxy() {
+    let proxy = TestServer::start(|_| Some("HTTP/1.1 200 OK\r\nContent-Length: 2\r\nX-Served-By: proxy\r\n\r\nok")).await;
+    let mut sandbox = WasmSandbox::new().unwrap();
+    sandbox.set_http_egress_policy(
+        &HttpEgressPolicy::new().proxy(HttpProxy::new(&format!("http://127.0.0.1:{}", proxy.port)).basic_auth("egress", "s3cret")),
+    ).unwrap();
+    let module_id = sandbox.load_module(FETCH_MODULE.as_bytes()).unwrap();
+    let mut instance_config = InstanceConfig::default();
+    instance_config.capabilities.network = NetworkCapability::AllowedHosts(vec![HostSpec {
+        host: "api.example.com".to_string(),
+        ports: Some(PortRange::single(80)),
+        secure: false,
+    }]);
+    let instance_id = sandbox.create_instance(module_id, Some(instance_config)).unwrap();
+    
+    let mut request = get("http://api.example.com/data");
+    request.method = "POST".to_string();
+    request.headers.insert("x-trace".to_string(), "7".to_string());
+    request.body = b"hi".to_vec();
+    let response: HttpResponse = sandbox.call_function(instance_id, "fetch", request).await.unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, b"ok");
+    assert_eq!(response.headers["x-served-by"], "proxy");
+    
+    let requests = proxy.requests();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].starts_with("post http://api.example.com/data http/1.1"), "{}", requests[0]);
+    let credentials = base64::engine::general_purpose::STANDARD.encode("egress:s3cret").to_ascii_lowercase();
+    assert!(requests[0].contains(&format!("proxy-authorization: basic {}", credentials)), "{}", requests[0]);
+    assert!(requests[0].contains("x-trace: 7"), "{}", requests[0]);
+    
+    // Hosts outside the capability never reach the proxy
+    let denied = sandbox.get_instance(instance_id).unwrap().instance.call_values("denied", &[]).unwrap();
+    assert_eq!(denied, [HostValue::I64(GuestErrorCode::PermissionDenied.code())]);
+    assert_eq!(proxy.requests().len(), 1);
+    
+    let audited = sandbox.http_egress().audit_logger().get_events().into_iter()
+        .filter(|event| event.event_type.kind() == AuditEventKind::HttpRequest)
+        .count();
+    assert_eq!(audited, 2);
+}
+
+#[tokio::test]
+async fn test_per_host_retries_and_timeouts() {
+    let flaky = TestServer::start(flaky_response).await;
+    let hanging = TestServer::start(|_| None).await;
+    
+    let sandbox = WasmSandbox::new().unwrap();
+    let egress = sandbox.http_egress();
+    let instance_id = InstanceId::new();
+    let url = |server: &TestServer| format!("http://127.0.0.1:{}/", server.port);
+    
+    // Without retries the 503 is returned as is
+    let response = egress.send(instance_id, &loopback(), get(&url(&flaky))).await.unwrap();
+    assert_eq!(response.status, 503);
+    
+    egress.set_policy(
+        &HttpEgressPolicy::new()
+            .timeout(Duration::from_millis(200))
+            .host("127.0.0.1", HttpHostPolicy::new().retry(HttpRetryPolicy::new(3, Duration::from_millis(10)))),
+    ).unwrap();
+    let flaky = TestServer::start(flaky_response).await;
+    let response = egress.send(instance_id, &loopback(), get(&url(&flaky))).await.unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(flaky.requests().len(), 2);
+    
+    let err = egress.send(instance_id, &loopback(), get(&url(&hanging))).await.unwrap_err();
+    assert!(matches!(err, Error::Timeout { duration, .. } if duration == Duration::from_millis(200)), "{}", err);
+    assert_eq!(hanging.requests().len(), 3);
+    
+    let denied = egress.send(instance_id, &Capabilities::minimal(), get(&url(&hanging))).await.unwrap_err();
+    assert!(matches!(denied, Error::SecurityViolation { .. }), "{}", denied);
+}
+
+#[tokio::test]
+async fn test_only_idempotent_requests_are_retried() {
+    let sandbox = WasmSandbox::new().unwrap();
+    let egress = sandbox.http_egress();
+    let instance_id = InstanceId::new();
+    let post = |server: &TestServer| HttpRequest {
+        method: "POST".to_string(),
+        ..get(&format!("http://127.0.0.1:{}/", server.port))
+    };
+    let retry = HttpRetryPolicy::new(3, Duration::from_millis(10));
+    
+    egress.set_policy(&HttpEgressPolicy::new().retry(retry)).unwrap();
+    let flaky = TestServer::start(flaky_response).await;
+    let response = egress.send(instance_id, &loopback(), post(&flaky)).await.unwrap();
+    assert_eq!(response.status, 503);
+    assert_eq!(flaky.requests().len(), 1);
+    
+    // Hosts that deduplicate requests can opt in
+    egress.set_policy(&HttpEgressPolicy::new().retry(retry.retry_non_idempotent())).unwrap();
+    let flaky = TestServer::start(flaky_response).await;
+    let response = egress.send(instance_id, &loopback(), post(&flaky)).await.unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(flaky.requests().len(), 2);
+}
+
+/// 503 for the first request, then 200
+fn flaky_response(attempt: usize) -> Option<&'static str> {
+    match attempt {
+        1 => Some("HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n"),
+        _ => Some("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"),
+    }
+}
+
+#[tokio::test]
+async fn test_pinned_roots_and_client_certificates() {
+    let server = TestServer::start_tls(|_| Some("HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\npinned")).await;
+    let url = format!("https://localhost:{}/", server.port);
+    let sandbox = WasmSandbox::new().unwrap();
+    let egress = sandbox.http_egress();
+    let instance_id = InstanceId::new();
+    
+    let pinned = HttpHostPolicy::new().pin_root_pem(CA).client_identity_pem(CLIENT_IDENTITY);
+    egress.set_policy(&HttpEgressPolicy::new().host("localhost", pinned)).unwrap();
+    let response = egress.send(instance_id, &loopback(), get(&url)).await.unwrap();
+    assert_eq!(response.body, b"pinned");
+    
+    // The public roots don't include the test CA, and another pinned CA doesn't vouch for the server
+    for policy in [
+        HttpEgressPolicy::new(),
+        HttpEgressPolicy::new().host("localhost", HttpHostPolicy::new().pin_root_pem(OTHER_CA).client_identity_pem(CLIENT_IDENTITY)),
+    ] {
+        egress.set_policy(&policy).unwrap();
+        let err = egress.send(instance_id, &loopback(), get(&url)).await.unwrap_err();
+        assert!(matches!(err, Error::Network { .. }), "{}", err);
+    }
+    
+    // The server requires a client certificate
+    egress.set_policy(&HttpEgressPolicy::new().host("localhost", HttpHostPolicy::new().pin_root_pem(CA))).unwrap();
+    assert!(egress.send(instance_id, &loopback(), get(&url)).await.is_err());
+    assert_eq!(server.requests().len(), 1);
+    
+    // A policy that doesn't parse leaves the current one in place
+    assert!(egress.set_policy(&HttpEgressPolicy::new().host("localhost", HttpHostPolicy::new().pin_root_pem("nope"))).is_err());
+    assert!(egress.set_policy(&HttpEgressPolicy::new().proxy(HttpProxy::new("not a url"))).is_err());
+}
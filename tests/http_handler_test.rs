@@ -0,0 +1,156 @@
+//! Tests for serving HTTP requests from guest handlers
+
+mod common;
+
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use wasm_sandbox::{HttpBudget, HttpRequest, HttpRouter, InstanceConfig, InstanceId, WasmSandbox};
+
+// Answers every request with the response JSON stored at offset 0
+fn handler_module(response: &str) -> String {
+    format!(
+        r#"
+(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (data (i32.const 0) "{}")
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (func (export "on_http_request") (param $ptr i32) (param $len i32) (result i64)
+    (i64.const {})))
+"#,
+        response.replace('"', "\\\""),
+        response.len()
+    )
+}
+
+// Never returns from the handler
+const SPINNING_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (func (export "on_http_request") (param $ptr i32) (param $len i32) (result i64)
+    (loop $forever (br $forever))
+    (i64.const 0)))
+"#;
+
+fn instance(sandbox: &mut WasmSandbox, module: &str) -> InstanceId {
+    let mut config = InstanceConfig::default();
+    // Enough fuel that only the route's timeout stops a spinning handler
+    config.resource_limits.fuel = Some(u64::MAX / 2);
+    common::create_instance(sandbox, module, Some(config))
+}
+
+#[tokio::test]
+async fn test_requests_are_routed_by_longest_prefix() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let root = instance(&mut sandbox, &handler_module(r#"{"status":200,"body":[114]}"#));
+    let first = instance(&mut sandbox, &handler_module(r#"{"status":200,"body":[49]}"#));
+    let second = instance(&mut sandbox, &handler_module(r#"{"status":201,"body":[50]}"#));
+    let router = HttpRouter::new()
+        .route("/", [root], HttpBudget::default())
+        .route("/api/", [first, second], HttpBudget::default());
+    
+    let response = sandbox.handle_http(&router, HttpRequest::new("GET", "/index.html")).await;
+    assert_eq!((response.status, response.body), (200, b"r".to_vec()));
+    let response = sandbox.handle_http(&router, HttpRequest::new("GET", "/apiary")).await;
+    assert_eq!(response.body, b"r");
+    
+    // Requests on a route take turns between its instances
+    let bodies: Vec<_> = futures::future::join_all((0..3).map(|_| {
+        sandbox.handle_http(&router, HttpRequest::new("POST", "/api/users?id=1").body("{}"))
+    }))
+    .await
+    .into_iter()
+    .map(|response| (response.status, response.body))
+    .collect();
+    assert_eq!(bodies, vec![(200, b"1".to_vec()), (201, b"2".to_vec()), (200, b"1".to_vec())]);
+    
+    let response = sandbox.handle_http(&HttpRouter::new(), HttpRequest::new("GET", "/")).await;
+    assert_eq!(response.status, 404);
+}
+
+#[tokio::test]
+async fn test_budget_limits_requests_responses_and_time() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let handler = instance(&mut sandbox, &handler_module(r#"{"status":200,"body":[1,2,3,4]}"#));
+    let spinning = instance(&mut sandbox, SPINNING_MODULE);
+    let budget = HttpBudget::default()
+        .timeout(Duration::from_millis(200))
+        .max_request_bytes(8)
+        .max_response_bytes(3);
+    let router = HttpRouter::new()
+        .route("/small", [handler], budget)
+        .route("/slow", [spinning], budget);
+    
+    let response = sandbox.handle_http(&router, HttpRequest::new("POST", "/small").body(vec![0; 9])).await;
+    assert_eq!(response.status, 413);
+    let response = sandbox.handle_http(&router, HttpRequest::new("POST", "/small").body(vec![0; 8])).await;
+    assert_eq!(response.status, 502);
+    let response = sandbox.handle_http(&router, HttpRequest::new("GET", "/slow")).await;
+    assert_eq!(response.status, 504);
+}
+
+#[tokio::test]
+async fn test_listener_serves_guest_responses() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let handler = instance(
+        &mut sandbox,
+        &handler_module(r#"{"status":200,"headers":[["x-served-by","guest"]],"body":[104,105]}"#),
+    );
+    let router = HttpRouter::new().route("/", [handler], HttpBudget::default());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    
+    let client = async {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(b"POST /hello HTTP/1.1\r\nHost: test\r\nTransfer-Encoding: chunked\r\n\r\n2\r\n{}\r\n0\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    };
+    let response = tokio::select! {
+        response = client => response,
+        result = sandbox.serve_http(listener, &router, 4) => panic!("server stopped: {:?}", result),
+    };
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.contains("x-served-by: guest\r\n"));
+    assert!(response.contains("content-length: 2\r\n"));
+    assert!(response.ends_with("\r\n\r\nhi"));
+}
+
+#[tokio::test]
+async fn test_listener_rejects_malformed_and_oversized_requests() {
+    let sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let router = HttpRouter::new();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    
+    let client = async {
+        let mut responses = Vec::new();
+        for request in [&b"NONSENSE\r\n\r\n"[..], b"POST / HTTP/1.1\r\nHost: test\r\nContent-Length: 5\r\n\r\nhello"] {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            stream.write_all(request).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            responses.push(response);
+        }
+        responses
+    };
+    let responses = tokio::select! {
+        responses = client => responses,
+        result = sandbox.serve_http(listener, &router, 4) => panic!("server stopped: {:?}", result),
+    };
+    assert!(responses[0].starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(responses[1].starts_with("HTTP/1.1 413 "), "{}", responses[1]);
+}
@@ -0,0 +1,110 @@
+//! Tests for pre-instantiation import validation
+
+use wasm_sandbox::{SandboxConfig, SandboxManifest, WasmSandbox, SandboxError};
+use wasm_sandbox::security::imports::{
+    ImportKind, ImportPattern, ImportPolicy, ImportRules, ImportViolation, ModuleImport,
+};
+
+const UNKNOWN_IMPORT_MODULE: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+  (import "acme" "charge" (func (param i32) (result i32)))
+  (import "env" "memory" (memory 1)))
+"#;
+
+#[test]
+fn test_policy_reports_each_unknown_import() {
+    let policy = ImportPolicy::default().allow_host_import("host", "log");
+    let imports = vec![
+        ModuleImport::new("wasi_snapshot_preview1", "fd_write", ImportKind::Function),
+        ModuleImport::new("host", "log", ImportKind::Function),
+        ModuleImport::new("acme", "charge", ImportKind::Function),
+        ModuleImport::new("acme", "limit", ImportKind::Global),
+    ];
+
+    let report = policy.validate(&imports);
+    assert_eq!(report.unknown.len(), 2);
+    assert_eq!(report.unknown[0].name, "charge");
+    assert_eq!(report.unknown[1].kind, ImportKind::Global);
+}
+
+#[test]
+fn test_instance_creation_rejects_unknown_imports() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(UNKNOWN_IMPORT_MODULE.as_bytes())
+        .expect("Failed to load module");
+
+    match sandbox.create_instance(module_id, None) {
+        Err(SandboxError::UnknownImports { report }) => {
+            assert_eq!(report.unknown, vec![
+                ModuleImport::new("acme", "charge", ImportKind::Function),
+            ]);
+        }
+        other => panic!("expected unknown imports error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_rules_report_each_violation() {
+    let rules = ImportRules::new()
+        .allow_namespace("wasi_snapshot_preview1")
+        .allow_function("sandbox_log::*".parse().unwrap())
+        .deny("wasi_snapshot_preview1::proc_*".parse().unwrap())
+        .max_namespace_imports("wasi_snapshot_preview1", 2);
+    let imports = vec![
+        ModuleImport::new("wasi_snapshot_preview1", "fd_write", ImportKind::Function),
+        ModuleImport::new("wasi_snapshot_preview1", "proc_exit", ImportKind::Function),
+        ModuleImport::new("wasm-sandbox:host/sandbox-log", "call-id", ImportKind::Function),
+        ModuleImport::new("wasi_snapshot_preview1", "fd_read", ImportKind::Function),
+        ModuleImport::new("acme", "charge", ImportKind::Function),
+    ];
+    
+    let violations = rules.violations(&imports);
+    assert_eq!(violations, vec![
+        ImportViolation::Denied { import: imports[1].clone(), pattern: "wasi_snapshot_preview1::proc_*".parse().unwrap() },
+        ImportViolation::NotAllowed(imports[4].clone()),
+        ImportViolation::TooMany { namespace: Some("wasi_snapshot_preview1".to_string()), limit: 2, found: 3 },
+    ]);
+    assert!(ImportRules::new().max_imports(5).violations(&imports).is_empty());
+    assert!(ImportRules::new().is_empty());
+    
+    for pattern in ["proc_exit", "::proc_exit", "wasi::", "wasi::*_exit"] {
+        assert!(pattern.parse::<ImportPattern>().is_err(), "{}", pattern);
+    }
+}
+
+#[test]
+fn test_manifest_import_rules_refuse_modules() {
+    let manifest = SandboxManifest::from_str_strict(r#"
+name = "strict"
+version = "1.0.0"
+
+[capabilities.imports]
+denied_functions = ["wasi_snapshot_preview1::proc_exit"]
+max_imports = 10
+max_namespace_imports = { env = 1 }
+"#).unwrap();
+    let mut sandbox = WasmSandbox::with_config(SandboxConfig {
+        runtime: manifest.to_runtime_config(),
+        ..SandboxConfig::default()
+    }).unwrap();
+    
+    // Rule violations are reported ahead of unknown imports
+    let module_id = sandbox.load_module(UNKNOWN_IMPORT_MODULE.as_bytes()).unwrap();
+    match sandbox.create_instance(module_id, None) {
+        Err(SandboxError::ForbiddenImports { violations }) => {
+            assert_eq!(violations.len(), 1);
+            assert!(violations[0].to_string().contains("wasi_snapshot_preview1::proc_exit"));
+        }
+        other => panic!("expected forbidden imports error, got {:?}", other.map(|_| ())),
+    }
+    
+    let result = SandboxManifest::from_str(r#"
+name = "typo"
+version = "1.0.0"
+
+[capabilities.imports]
+denied_functions = ["proc_exit"]
+"#);
+    assert!(result.is_err());
+}
@@ -0,0 +1,64 @@
+//! Tests for linking only the imports a module uses
+
+use wasm_sandbox::security::imports::{ImportKind, ModuleImport};
+use wasm_sandbox::{SandboxConfig, SandboxError, WasmSandbox};
+
+const WASI_MODULE: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+  (import "sandbox_config" "get" (func (param i32 i32) (result i64)))
+  (memory (export "memory") 1))
+"#;
+
+const UNKNOWN_IMPORT_MODULE: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+  (import "acme" "charge" (func (param i32) (result i32))))
+"#;
+
+#[test]
+fn test_only_imported_functions_are_linked() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(WASI_MODULE.as_bytes()).expect("Failed to load module");
+    let instance_id = sandbox.create_instance(module_id, None).expect("Failed to create instance");
+    
+    let report = sandbox.link_report(instance_id).unwrap();
+    assert!(report.is_complete());
+    assert_eq!(report.linked, vec![
+        ModuleImport::new("wasi_snapshot_preview1", "fd_write", ImportKind::Function),
+        ModuleImport::new("wasi_snapshot_preview1", "proc_exit", ImportKind::Function),
+        ModuleImport::new("sandbox_config", "get", ImportKind::Function),
+    ]);
+    assert!(!report.is_linked("wasi_snapshot_preview1", "path_open"));
+    assert!(!report.is_linked("sandbox_rpc", "call"));
+    assert!(report.to_string().starts_with("linked 3 imports"));
+}
+
+#[test]
+fn test_module_without_imports_links_nothing() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(b"(module (func (export \"noop\")))").expect("Failed to load module");
+    let instance_id = sandbox.create_instance(module_id, None).expect("Failed to create instance");
+    
+    let report = sandbox.link_report(instance_id).unwrap();
+    assert!(report.linked.is_empty());
+    assert_eq!(report.to_string(), "linked 0 imports");
+}
+
+#[test]
+fn test_undefined_imports_fail_with_report() {
+    let mut config = SandboxConfig::default();
+    config.runtime.import_policy.deny_unknown = false;
+    let mut sandbox = WasmSandbox::with_config(config).expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(UNKNOWN_IMPORT_MODULE.as_bytes()).expect("Failed to load module");
+    
+    match sandbox.create_instance(module_id, None) {
+        Err(SandboxError::UnknownImports { report }) => {
+            assert_eq!(report.unknown, vec![
+                ModuleImport::new("acme", "charge", ImportKind::Function),
+            ]);
+        }
+        other => panic!("expected unknown imports error, got {:?}", other.map(|_| ())),
+    }
+}
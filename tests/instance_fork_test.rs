@@ -0,0 +1,74 @@
+//! Tests for forking running instances
+
+mod common;
+
+use wasm_sandbox::{Error, InstanceConfig, InstanceId, WasmSandbox};
+
+// `set` stores a value in memory and `bump` increments an exported global
+const COUNTER_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $count (export "count") (mut i32) (i32.const 0))
+  (func (export "set") (param $value i32)
+    (i32.store (i32.const 64) (local.get $value)))
+  (func (export "get") (result i32)
+    (i32.load (i32.const 64)))
+  (func (export "bump") (result i32)
+    (global.set $count (i32.add (global.get $count) (i32.const 1)))
+    (global.get $count)))
+"#;
+
+fn template() -> (WasmSandbox, InstanceId) {
+    common::instantiate(COUNTER_MODULE, None)
+}
+
+#[tokio::test]
+async fn test_fork_starts_from_the_template_state() {
+    let (mut sandbox, template) = template();
+    sandbox.call_function::<_, ()>(template, "set", 1234).await.unwrap();
+    let _: i32 = sandbox.call_function(template, "bump", ()).await.unwrap();
+    let _: i32 = sandbox.call_function(template, "bump", ()).await.unwrap();
+    
+    let fork = sandbox.fork_instance(template).unwrap();
+    assert_ne!(fork, template);
+    let value: i32 = sandbox.call_function(fork, "get", ()).await.unwrap();
+    assert_eq!(value, 1234);
+    let count: i32 = sandbox.call_function(fork, "bump", ()).await.unwrap();
+    assert_eq!(count, 3);
+}
+
+#[tokio::test]
+async fn test_fork_and_template_change_independently() {
+    let (mut sandbox, template) = template();
+    sandbox.call_function::<_, ()>(template, "set", 7).await.unwrap();
+    let fork = sandbox.fork_instance(template).unwrap();
+    
+    sandbox.call_function::<_, ()>(fork, "set", 8).await.unwrap();
+    let _: i32 = sandbox.call_function(fork, "bump", ()).await.unwrap();
+    assert_eq!(sandbox.call_function::<_, i32>(template, "get", ()).await.unwrap(), 7);
+    assert_eq!(sandbox.call_function::<_, i32>(template, "bump", ()).await.unwrap(), 1);
+    
+    sandbox.call_function::<_, ()>(template, "set", 9).await.unwrap();
+    assert_eq!(sandbox.call_function::<_, i32>(fork, "get", ()).await.unwrap(), 8);
+}
+
+#[tokio::test]
+async fn test_fork_keeps_the_template_config() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(COUNTER_MODULE.as_bytes()).unwrap();
+    let mut config = InstanceConfig::default();
+    config.resource_limits.memory.max_memory_pages = 3;
+    let template = sandbox.create_instance(module_id, Some(config)).unwrap();
+    
+    let fork = sandbox.fork_instance(template).unwrap();
+    let fork = sandbox.get_instance(fork).unwrap();
+    assert_eq!(fork.module_id, module_id);
+    assert_eq!(fork.config.resource_limits.memory.max_memory_pages, 3);
+}
+
+#[test]
+fn test_unknown_instance_cannot_be_forked() {
+    let (mut sandbox, _) = template();
+    let result = sandbox.fork_instance(InstanceId::new());
+    assert!(matches!(result, Err(Error::NotFound { .. })));
+}
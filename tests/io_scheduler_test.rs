@@ -0,0 +1,166 @@
+//! Tests for sharing the host disk fairly between instances
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use wasm_sandbox::observability::http::render_metrics;
+use wasm_sandbox::{InstanceConfig, InstanceId, IoBudget, ScratchConfig, SandboxConfig, WasmSandbox};
+
+/// `write` opens `out.bin` in the scratch directory and writes `count` chunks of
+/// `size` bytes, returning the first failing errno; `print` writes to stdout
+const WRITER_MODULE: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "path_open"
+    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_close" (func $fd_close (param i32) (result i32)))
+  (memory (export "memory") 2)
+  (data (i32.const 0) "out.bin")
+  (func (export "write") (param $count i32) (param $size i32) (result i32)
+    (local $fd i32) (local $errno i32)
+    (local.set $errno (call $path_open (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 7)
+      (i32.const 9) (i64.const 66) (i64.const 66) (i32.const 0) (i32.const 32)))
+    (if (local.get $errno) (then (return (local.get $errno))))
+    (local.set $fd (i32.load (i32.const 32)))
+    (i32.store (i32.const 16) (i32.const 1024))
+    (i32.store (i32.const 20) (local.get $size))
+    (block $done
+      (loop $next
+        (br_if $done (i32.eqz (local.get $count)))
+        (local.set $errno (call $fd_write (local.get $fd) (i32.const 16) (i32.const 1) (i32.const 40)))
+        (br_if $done (local.get $errno))
+        (local.set $count (i32.sub (local.get $count) (i32.const 1)))
+        (br $next)))
+    (drop (call $fd_close (local.get $fd)))
+    (local.get $errno))
+  (func (export "print") (result i32)
+    (i32.store (i32.const 16) (i32.const 1024))
+    (i32.store (i32.const 20) (i32.const 0))
+    (call $fd_write (i32.const 1) (i32.const 16) (i32.const 1) (i32.const 40))))
+"#;
+
+const EAGAIN: i32 = 6;
+
+fn sandbox_with_budget(budget: Option<IoBudget>) -> WasmSandbox {
+    WasmSandbox::with_config(SandboxConfig {
+        io_budget: budget,
+        ..SandboxConfig::default()
+    }).unwrap()
+}
+
+fn writer(sandbox: &mut WasmSandbox, io_weight: u32) -> InstanceId {
+    let config = InstanceConfig {
+        scratch: Some(ScratchConfig::default()),
+        io_weight,
+        ..InstanceConfig::default()
+    };
+    common::create_instance(sandbox, WRITER_MODULE, Some(config))
+}
+
+#[tokio::test]
+async fn test_writes_are_paced_to_the_instance_cap() {
+    let mut sandbox = sandbox_with_budget(Some(IoBudget::new(16 * 1024 * 1024, 10_000).burst(Duration::ZERO)));
+    let module_id = sandbox.load_module(WRITER_MODULE.as_bytes()).unwrap();
+    let mut config = InstanceConfig {
+        scratch: Some(ScratchConfig::default()),
+        ..InstanceConfig::default()
+    };
+    config.resource_limits.io.max_write_bytes_per_second = Some(256 * 1024);
+    let instance_id = sandbox.create_instance(module_id, Some(config)).unwrap();
+    
+    // Alone on the disk the instance could write much faster, but its own cap still applies
+    let started = Instant::now();
+    let errno: i32 = sandbox.call_function(instance_id, "write", (8, 16 * 1024)).await.unwrap();
+    assert_eq!(errno, 0);
+    assert!(started.elapsed() >= Duration::from_millis(350), "{:?}", started.elapsed());
+    
+    let metrics = sandbox.instance_io_metrics(instance_id).unwrap();
+    assert_eq!(metrics.operations, 8);
+    assert_eq!(metrics.bytes, 8 * 16 * 1024);
+    assert_eq!(metrics.delayed_operations, 7);
+    assert_eq!(metrics.starved_operations, 0);
+    
+    // The standard streams aren't disk I/O
+    let errno: i32 = sandbox.call_function(instance_id, "print", ()).await.unwrap();
+    assert_eq!(errno, 0);
+    assert_eq!(sandbox.io_metrics().operations, 8);
+}
+
+#[tokio::test]
+async fn test_busy_instances_share_the_disk_by_weight() {
+    let mut sandbox = sandbox_with_budget(Some(IoBudget::new(256 * 1024, 10_000).burst(Duration::ZERO)));
+    let heavy = writer(&mut sandbox, 3);
+    let light = writer(&mut sandbox, 1);
+    
+    let started = Instant::now();
+    let write = |instance_id| {
+        let sandbox = &sandbox;
+        async move {
+            let errno: i32 = sandbox.call_function(instance_id, "write", (8, 8 * 1024)).await.unwrap();
+            assert_eq!(errno, 0);
+            started.elapsed()
+        }
+    };
+    let (heavy_took, light_took) = tokio::join!(write(heavy), write(light));
+    assert!(heavy_took < light_took, "{:?} vs {:?}", heavy_took, light_took);
+    
+    // The light instance's quarter share makes it wait past the starvation threshold
+    let health = sandbox.health();
+    let (heavy_io, light_io) = (health.instance_io[&heavy], health.instance_io[&light]);
+    assert!(heavy_io.wait_time < light_io.wait_time, "{:?} vs {:?}", heavy_io, light_io);
+    assert_eq!(heavy_io.starved_operations, 0);
+    assert!(light_io.starved_operations > 0);
+    assert_eq!(health.io.starved_operations, light_io.starved_operations);
+    
+    let rendered = render_metrics(&health);
+    let line = format!("wasm_sandbox_instance_io_starved_operations_total{{instance=\"{}\"}} {}", light, light_io.starved_operations);
+    assert!(rendered.contains(&line), "{}", rendered);
+    assert!(rendered.contains("wasm_sandbox_io_operations_total 16"), "{}", rendered);
+}
+
+#[tokio::test]
+async fn test_operations_that_would_wait_too_long_fail_with_eagain() {
+    let budget = IoBudget::new(40_960, 1_000).burst(Duration::ZERO).max_delay(Duration::from_millis(200));
+    let mut sandbox = sandbox_with_budget(Some(budget));
+    let instance_id = writer(&mut sandbox, 1);
+    
+    // One second's worth of the disk leaves the next write with too long a wait
+    let errno: i32 = sandbox.call_function(instance_id, "write", (1, 40_960)).await.unwrap();
+    assert_eq!(errno, 0);
+    let errno: i32 = sandbox.call_function(instance_id, "write", (1, 4096)).await.unwrap();
+    assert_eq!(errno, EAGAIN);
+    
+    let metrics = sandbox.instance_io_metrics(instance_id).unwrap();
+    assert_eq!((metrics.operations, metrics.refused_operations), (1, 1));
+    
+    sandbox.remove_instance(instance_id);
+    assert!(sandbox.instance_io_metrics(instance_id).is_none());
+    assert_eq!(sandbox.io_metrics().refused_operations, 1);
+}
+
+#[tokio::test]
+async fn test_budgets_are_validated_and_optional() {
+    assert!(WasmSandbox::with_config(SandboxConfig {
+        io_budget: Some(IoBudget::new(0, 100)),
+        ..SandboxConfig::default()
+    }).is_err());
+    
+    let mut sandbox = sandbox_with_budget(Some(IoBudget::new(1024 * 1024, 100)));
+    let module_id = sandbox.load_module(WRITER_MODULE.as_bytes()).unwrap();
+    let config = InstanceConfig { io_weight: 0, ..InstanceConfig::default() };
+    assert!(sandbox.create_instance(module_id, Some(config)).is_err());
+    
+    let mut config = sandbox.config().clone();
+    config.io_budget = None;
+    let diff = sandbox.apply_config(config).unwrap();
+    assert!(diff.is_deferred("io_budget"), "{}", diff);
+    
+    let mut sandbox = sandbox_with_budget(None);
+    let instance_id = writer(&mut sandbox, 1);
+    let errno: i32 = sandbox.call_function(instance_id, "write", (8, 16 * 1024)).await.unwrap();
+    assert_eq!(errno, 0);
+    assert_eq!(sandbox.io_metrics().operations, 0);
+    assert!(sandbox.instance_io_metrics(instance_id).is_none());
+    assert!(sandbox.health().instance_io.is_empty());
+}
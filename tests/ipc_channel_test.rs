@@ -0,0 +1,89 @@
+//! Tests for the unix domain socket IPC channel
+#![cfg(unix)]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use wasm_sandbox::communication::{
+    CommunicationChannel, IpcChannel, IpcChannelConfig, IpcListener, IpcRpcChannel, RpcChannelExt,
+};
+
+fn config() -> IpcChannelConfig {
+    IpcChannelConfig {
+        timeout: Duration::from_secs(5),
+        ..IpcChannelConfig::default()
+    }
+}
+
+#[test]
+fn test_messages_cross_the_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sandbox.sock");
+    let listener = IpcListener::bind(&path, config()).expect("Failed to bind");
+    
+    let client = std::thread::spawn({
+        let path = path.clone();
+        move || {
+            let channel = IpcChannel::connect(&path, config()).expect("Failed to connect");
+            let request = channel.receive_from_guest().unwrap();
+            channel.send_to_guest(&[request.as_slice(), b" pong"].concat()).unwrap();
+        }
+    });
+    
+    let server = listener.accept().expect("Failed to accept");
+    server.send_to_guest(b"ping").unwrap();
+    assert_eq!(server.receive_from_guest().unwrap(), b"ping pong");
+    client.join().unwrap();
+    
+    // The peer hung up after replying
+    assert!(server.receive_from_guest().is_err());
+    assert!(!server.has_messages());
+}
+
+#[test]
+fn test_receive_times_out_and_close_rejects_sends() {
+    let (a, _b) = IpcChannel::pair(IpcChannelConfig {
+        timeout: Duration::from_millis(50),
+        ..IpcChannelConfig::default()
+    })
+    .unwrap();
+    
+    assert!(a.receive_from_guest().is_err());
+    a.close().unwrap();
+    assert!(a.send_to_guest(b"late").is_err());
+}
+
+#[test]
+fn test_oversized_frames_are_rejected() {
+    let small = IpcChannelConfig {
+        max_message_size: 8,
+        ..config()
+    };
+    let (a, b) = IpcChannel::pair(small).unwrap();
+    
+    assert!(a.send_to_guest(&[0u8; 16]).is_err());
+    a.send_to_guest(b"fits").unwrap();
+    assert_eq!(b.receive_from_guest().unwrap(), b"fits");
+}
+
+#[test]
+fn test_rpc_in_both_directions() {
+    let (a, b) = IpcChannel::pair(config()).unwrap();
+    let mut supervisor = IpcRpcChannel::new(Arc::new(a)).unwrap();
+    let mut sandbox = IpcRpcChannel::new(Arc::new(b)).unwrap();
+    
+    sandbox
+        .register_host_function("add", |(x, y): (i32, i32)| Ok(x + y))
+        .unwrap();
+    supervisor
+        .register_host_function("greet", |name: String| Ok(format!("hello {}", name)))
+        .unwrap();
+    
+    let sum: i32 = supervisor.call_guest_function("add", &(2, 3)).unwrap();
+    assert_eq!(sum, 5);
+    let greeting: String = sandbox.call_guest_function("greet", "sandbox").unwrap();
+    assert_eq!(greeting, "hello sandbox");
+    
+    let missing: wasm_sandbox::Result<i32> = supervisor.call_guest_function("missing", &());
+    assert!(missing.unwrap_err().to_string().contains("not registered"));
+}
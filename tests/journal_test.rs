@@ -0,0 +1,171 @@
+//! Tests for the sandbox event journal and its sinks
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use wasm_sandbox::security::audit::AuditEventType;
+use wasm_sandbox::{
+    CallableFunctions, FileJournalSink, FuelBudget, InstanceConfig, JournalCursor, JournalEntry, JournalEvent,
+    JournalRetention, SandboxConfig, TracingJournalSink, WasmSandbox, WebhookJournalSink,
+};
+
+// `spin` burns fuel in proportion to its argument
+const SPIN_MODULE: &str = r#"
+(module
+  (func (export "spin") (param $n i32) (result i32)
+    (block $done
+      (loop $again
+        (br_if $done (i32.eqz (local.get $n)))
+        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+        (br $again)))
+    (local.get $n)))
+"#;
+
+fn kinds(entries: &[JournalEntry]) -> Vec<&'static str> {
+    entries.iter().map(|entry| match &entry.event {
+        JournalEvent::ModuleLoaded { .. } => "module_loaded",
+        JournalEvent::InstanceCreated { .. } => "instance_created",
+        JournalEvent::InstanceRemoved { .. } => "instance_removed",
+        JournalEvent::Audit { .. } => "audit",
+        JournalEvent::ResourceViolation { .. } => "resource_violation",
+        JournalEvent::CallCompleted { .. } => "call_completed",
+        JournalEvent::Custom { .. } => "custom",
+    }).collect()
+}
+
+#[tokio::test]
+async fn test_lifecycle_and_calls_are_journaled_in_order() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(SPIN_MODULE.as_bytes()).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    let _: i32 = sandbox.call_function(instance_id, "spin", 10).await.unwrap();
+    sandbox.remove_instance(instance_id);
+    
+    let entries = sandbox.journal().entries();
+    assert_eq!(kinds(&entries), ["module_loaded", "instance_created", "call_completed", "instance_removed"]);
+    assert!(entries.windows(2).all(|pair| pair[0].sequence + 1 == pair[1].sequence));
+    match &entries[2].event {
+        JournalEvent::CallCompleted { instance_id: called, function_name, error, .. } => {
+            assert_eq!(*called, instance_id.to_string());
+            assert_eq!(function_name, "spin");
+            assert!(error.is_none());
+        }
+        other => panic!("unexpected event {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_audit_records_and_resource_violations_are_journaled() {
+    let mut sandbox = WasmSandbox::with_config(SandboxConfig {
+        fuel_budget: Some(FuelBudget::new(10_000, Duration::from_secs(60))),
+        ..SandboxConfig::default()
+    }).unwrap();
+    let module_id = sandbox.load_module(SPIN_MODULE.as_bytes()).unwrap();
+    let config = InstanceConfig::builder()
+        .callable_functions(CallableFunctions::Allowlist(vec!["spin".to_string()]))
+        .build()
+        .unwrap();
+    let instance_id = sandbox.create_instance(module_id, Some(config)).unwrap();
+    
+    let mut cursor = sandbox.journal().cursor();
+    assert!(sandbox.call_function::<_, i32>(instance_id, "debug_dump_all", ()).await.is_err());
+    let _: i32 = sandbox.call_function(instance_id, "spin", 20_000).await.unwrap();
+    assert!(sandbox.call_function::<_, i32>(instance_id, "spin", 1).await.is_err());
+    
+    let entries = sandbox.journal().read(&mut cursor, 100);
+    assert_eq!(kinds(&entries), ["audit", "call_completed", "resource_violation", "call_completed"]);
+    match &entries[0].event {
+        JournalEvent::Audit { record } => {
+            assert!(matches!(record.event_type, AuditEventType::FunctionCallDenied { .. }));
+        }
+        other => panic!("unexpected event {:?}", other),
+    }
+    match (&entries[2].event, &entries[3].event) {
+        (JournalEvent::ResourceViolation { call_id, .. }, JournalEvent::CallCompleted { call_id: completed, error, .. }) => {
+            assert_eq!(call_id, completed);
+            assert!(error.is_some());
+        }
+        other => panic!("unexpected events {:?}", other),
+    }
+    assert_eq!(cursor.position(), entries[3].sequence + 1);
+}
+
+#[tokio::test]
+async fn test_cursors_retention_and_replayed_sinks() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let journal = sandbox.journal().clone();
+    for n in 0..5 {
+        journal.record(JournalEvent::Custom { event_type: "tick".to_string(), data: n.into() });
+    }
+    
+    // A cursor reads in batches and resumes where it stopped
+    let mut cursor = JournalCursor::start();
+    assert_eq!(journal.read(&mut cursor, 2).len(), 2);
+    let rest = journal.read(&mut cursor, 10);
+    assert_eq!(rest.iter().map(|entry| entry.sequence).collect::<Vec<_>>(), [2, 3, 4]);
+    assert!(journal.read(&mut cursor, 10).is_empty());
+    
+    // A sink added late is replayed what it missed, then gets new entries as JSON lines
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("journal.jsonl");
+    assert_eq!(journal.add_sink_from(Arc::new(FileJournalSink::new(&path).unwrap()), JournalCursor::at(3)), 2);
+    journal.add_sink(Arc::new(TracingJournalSink));
+    journal.record(JournalEvent::Custom { event_type: "tock".to_string(), data: 5.into() });
+    journal.flush().unwrap();
+    let lines: Vec<JournalEntry> = std::fs::read_to_string(&path).unwrap().lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.iter().map(|entry| entry.sequence).collect::<Vec<_>>(), [3, 4, 5]);
+    
+    // Tightening retention drops the oldest entries; sequence numbers keep counting
+    let mut config = sandbox.config().clone();
+    config.journal_retention = JournalRetention { max_entries: 2, max_age: None };
+    let diff = sandbox.apply_config(config).unwrap();
+    assert!(diff.is_applied("journal_retention"), "{}", diff);
+    let kept: Vec<_> = journal.entries().iter().map(|entry| entry.sequence).collect();
+    assert_eq!(kept, [4, 5]);
+    assert_eq!(journal.metrics().dropped, 4);
+    assert_eq!(journal.metrics().sink_failures, 0);
+}
+
+#[tokio::test]
+async fn test_webhook_sink_posts_each_entry() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let (bodies, received) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+            bodies.send(body).unwrap();
+        }
+    });
+    
+    assert!(WebhookJournalSink::new("https://collector.example/events").is_err());
+    let sink = Arc::new(WebhookJournalSink::new(&format!("http://{}/events", address)).unwrap());
+    let sandbox = WasmSandbox::new().unwrap();
+    sandbox.journal().add_sink(sink.clone());
+    sandbox.load_module(SPIN_MODULE.as_bytes()).unwrap();
+    sandbox.journal().flush().unwrap();
+    
+    let body = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    let entry: JournalEntry = serde_json::from_slice(&body).unwrap();
+    assert_eq!(entry.sequence, 0);
+    assert!(matches!(entry.event, JournalEvent::ModuleLoaded { .. }));
+    assert_eq!(sink.failures(), 0);
+}
@@ -0,0 +1,203 @@
+//! Tests for the JSON-RPC management API
+
+#![cfg(feature = "management-api")]
+
+use base64::Engine;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::RwLock;
+use wasm_sandbox::{ManagementServer, RpcAuthenticator, RpcError, TokenAuthenticator, WasmSandbox};
+
+const TOKEN: &str = "s3cret-token";
+
+const ADD_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))))
+"#;
+
+/// A client speaking newline-delimited JSON-RPC
+struct Client<S> {
+    stream: BufReader<S>,
+    next_id: u64,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Client<S> {
+    fn new(stream: S) -> Self {
+        Self { stream: BufReader::new(stream), next_id: 0 }
+    }
+    
+    async fn send(&mut self, line: &str) -> Option<Value> {
+        self.stream.get_mut().write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+        let mut reply = String::new();
+        match self.stream.read_line(&mut reply).await.unwrap() {
+            0 => None,
+            _ => Some(serde_json::from_str(&reply).unwrap()),
+        }
+    }
+    
+    /// Call a method, returning its result or error object
+    async fn call(&mut self, method: &str, params: Value) -> Result<Value, Value> {
+        self.next_id += 1;
+        let request = json!({ "jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params });
+        let reply = self.send(&request.to_string()).await.expect("connection closed");
+        assert_eq!(reply["id"], self.next_id);
+        match reply.get("error") {
+            Some(error) => Err(error.clone()),
+            None => Ok(reply["result"].clone()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_orchestrator_drives_a_sandbox_over_a_stream() {
+    let sandbox = RwLock::new(WasmSandbox::new().unwrap());
+    let server = ManagementServer::new();
+    let (client_side, server_side) = tokio::io::duplex(1 << 20);
+    let (reader, writer) = tokio::io::split(server_side);
+    
+    let client = async move {
+        let mut client = Client::new(client_side);
+        let module = client.call("load_module", json!({ "wat": ADD_MODULE })).await.unwrap();
+        let instance = client.call("create_instance", json!({ "module_id": module["module_id"] })).await.unwrap();
+        let instance_id = instance["instance_id"].clone();
+        let sum = client.call("call_function", json!({ "instance_id": instance_id, "function": "add", "params": [2, 3] })).await;
+        assert_eq!(sum, Ok(json!(5)));
+        
+        // Binary modules travel as base64
+        let wasm = base64::engine::general_purpose::STANDARD.encode(wat::parse_str(ADD_MODULE).unwrap());
+        let module = client.call("load_module", json!({ "wasm": wasm })).await.unwrap();
+        client.call("create_instance", json!({ "module_id": module["module_id"] })).await.unwrap();
+        
+        let instances = client.call("list_instances", Value::Null).await.unwrap();
+        assert_eq!(instances.as_array().unwrap().len(), 2);
+        assert_eq!(client.call("health", json!({})).await.unwrap()["instances"], 2);
+        let metrics = client.call("metrics", json!({})).await.unwrap();
+        assert!(metrics.as_str().unwrap().contains("wasm_sandbox_instances 2"), "{}", metrics);
+        
+        for method in ["pause_instance", "resume_instance", "terminate_instance"] {
+            assert_eq!(client.call(method, json!({ "instance_id": instance_id })).await, Ok(Value::Null), "{}", method);
+        }
+        let error = client.call("call_function", json!({ "instance_id": instance_id, "function": "add", "params": [1, 1] })).await.unwrap_err();
+        assert_eq!(error["code"], RpcError::SANDBOX_ERROR);
+        assert_eq!(client.call("remove_instance", json!({ "instance_id": instance_id })).await, Ok(json!(true)));
+        assert_eq!(client.call("remove_instance", json!({ "instance_id": instance_id })).await, Ok(json!(false)));
+        
+        // Garbage gets a parse error and the connection stays usable
+        let reply = client.send("{not json").await.unwrap();
+        assert_eq!(reply["error"]["code"], RpcError::PARSE_ERROR);
+        assert_eq!(reply["id"], Value::Null);
+        assert!(client.call("list_instances", Value::Null).await.is_ok());
+    };
+    let (served, ()) = tokio::join!(server.serve(&sandbox, reader, writer), client);
+    served.unwrap();
+    assert_eq!(sandbox.read().await.instance_ids().len(), 1);
+}
+
+#[tokio::test]
+async fn test_protocol_errors_follow_the_spec() {
+    let sandbox = RwLock::new(WasmSandbox::new().unwrap());
+    let server = ManagementServer::new();
+    let code = |reply: Option<Value>| reply.unwrap()["error"]["code"].clone();
+    
+    let reply = server.handle(&sandbox, json!({ "id": 1, "method": "list_instances" })).await;
+    assert_eq!(code(reply), RpcError::INVALID_REQUEST);
+    let reply = server.handle(&sandbox, json!({ "jsonrpc": "2.0", "id": 2, "method": "reboot" })).await;
+    assert_eq!(code(reply), RpcError::METHOD_NOT_FOUND);
+    let reply = server.handle(&sandbox, json!({ "jsonrpc": "2.0", "id": 3, "method": "create_instance", "params": ["x"] })).await;
+    assert_eq!(code(reply), RpcError::INVALID_PARAMS);
+    let reply = server.handle(&sandbox, json!({ "jsonrpc": "2.0", "id": 4, "method": "create_instance", "params": { "module_id": "nope" } })).await;
+    assert_eq!(code(reply), RpcError::INVALID_PARAMS);
+    let reply = server.handle(&sandbox, json!({ "jsonrpc": "2.0", "id": 5, "method": "load_module", "params": { "wat": "(module" } })).await;
+    assert_eq!(code(reply), RpcError::SANDBOX_ERROR);
+    
+    // Notifications run but aren't answered
+    let reply = server.handle(&sandbox, json!({ "jsonrpc": "2.0", "method": "load_module", "params": { "wat": ADD_MODULE } })).await;
+    assert!(reply.is_none());
+    
+    // Batches are answered in order, leaving out notifications
+    let batch = json!([
+        { "jsonrpc": "2.0", "id": "a", "method": "list_instances" },
+        { "jsonrpc": "2.0", "method": "health" },
+        42,
+        { "jsonrpc": "2.0", "id": "b", "method": "reboot" },
+    ]);
+    let replies = server.handle(&sandbox, batch).await.unwrap();
+    let ids: Vec<&Value> = replies.as_array().unwrap().iter().map(|reply| &reply["id"]).collect();
+    assert_eq!(ids, [&json!("a"), &Value::Null, &json!("b")]);
+    assert_eq!(replies[0]["result"], json!([]));
+    assert_eq!(replies[1]["error"]["code"], RpcError::INVALID_REQUEST);
+    assert_eq!(code(server.handle(&sandbox, json!([])).await), RpcError::INVALID_REQUEST);
+}
+
+#[tokio::test]
+async fn test_tcp_connections_must_authenticate() {
+    let sandbox = RwLock::new(WasmSandbox::new().unwrap());
+    let server = ManagementServer::new().with_authenticator(TokenAuthenticator::new(TOKEN));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    
+    let client = async {
+        let mut client = Client::new(TcpStream::connect(addr).await.unwrap());
+        let error = client.call("list_instances", Value::Null).await.unwrap_err();
+        assert_eq!(error["code"], RpcError::UNAUTHENTICATED);
+        let error = client.call("authenticate", json!({ "token": "guess" })).await.unwrap_err();
+        assert_eq!(error["message"], "Invalid credentials");
+        
+        // Refused credentials end the connection
+        let request = json!({ "jsonrpc": "2.0", "id": 9, "method": "list_instances" }).to_string();
+        assert!(client.send(&request).await.is_none());
+        
+        let mut client = Client::new(TcpStream::connect(addr).await.unwrap());
+        let authenticated = client.call("authenticate", json!({ "token": TOKEN })).await.unwrap();
+        assert_eq!(authenticated["principal"], "token");
+        assert_eq!(client.call("list_instances", Value::Null).await, Ok(json!([])));
+    };
+    tokio::select! {
+        result = server.serve_tcp(&sandbox, listener) => panic!("server stopped: {:?}", result),
+        () = client => {}
+    }
+}
+
+/// Admits two principals; `observer` may only read
+struct Roles;
+
+impl RpcAuthenticator for Roles {
+    fn authenticate(&self, credentials: &Value) -> Option<String> {
+        let user = credentials.get("user")?.as_str()?;
+        ["admin", "observer"].contains(&user).then(|| user.to_string())
+    }
+    
+    fn authorize(&self, principal: &str, method: &str) -> bool {
+        principal == "admin" || matches!(method, "list_instances" | "health" | "metrics")
+    }
+}
+
+#[tokio::test]
+async fn test_authorization_hook_limits_methods() {
+    let sandbox = RwLock::new(WasmSandbox::new().unwrap());
+    let server = ManagementServer::new().with_authenticator(Roles);
+    let (client_side, server_side) = tokio::io::duplex(1 << 16);
+    let (reader, writer) = tokio::io::split(server_side);
+    
+    let client = async move {
+        let mut client = Client::new(client_side);
+        client.call("authenticate", json!({ "user": "observer" })).await.unwrap();
+        assert!(client.call("health", Value::Null).await.is_ok());
+        let error = client.call("load_module", json!({ "wat": ADD_MODULE })).await.unwrap_err();
+        assert_eq!(error["code"], RpcError::FORBIDDEN);
+        assert_eq!(error["message"], "observer may not call load_module");
+        
+        // Authenticating again switches principals
+        client.call("authenticate", json!({ "user": "admin" })).await.unwrap();
+        assert!(client.call("load_module", json!({ "wat": ADD_MODULE })).await.is_ok());
+    };
+    let (served, ()) = tokio::join!(server.serve(&sandbox, reader, writer), client);
+    served.unwrap();
+    
+    // In-process requests are trusted
+    let reply = server.handle(&sandbox, json!({ "jsonrpc": "2.0", "id": 1, "method": "list_instances" })).await.unwrap();
+    assert_eq!(reply["result"], json!([]));
+}
@@ -0,0 +1,108 @@
+//! Tests for manifest schema versioning and strict validation
+
+use wasm_sandbox::{EnforcementMode, SandboxManifest};
+use wasm_sandbox::utils::manifest::MANIFEST_SCHEMA_VERSION;
+
+const LEGACY_MANIFEST: &str = r#"
+name = "plugin"
+version = "1.0.0"
+
+[capabilities.network]
+mode = "loopback"
+"#;
+
+const MISSPELLED_MANIFEST: &str = r#"
+schema_version = 2
+name = "plugin"
+version = "1.0.0"
+
+[capabilities.netwrk]
+mode = "loopback"
+"#;
+
+#[test]
+fn test_legacy_manifest_is_migrated() {
+    let manifest = SandboxManifest::from_str_strict(LEGACY_MANIFEST).unwrap();
+    assert_eq!(manifest.schema_version, MANIFEST_SCHEMA_VERSION);
+    assert_eq!(manifest.capabilities.network.mode, "loopback");
+}
+
+#[test]
+fn test_strict_mode_reports_misspelled_key_with_line() {
+    // Lenient parsing silently ignores the typo
+    assert!(SandboxManifest::from_str(MISSPELLED_MANIFEST).is_ok());
+
+    let issues = SandboxManifest::validate_str(MISSPELLED_MANIFEST).unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].path, "capabilities.netwrk");
+    assert_eq!(issues[0].line, Some(6));
+    assert_eq!(issues[0].suggestion.as_deref(), Some("did you mean `network`?"));
+
+    assert!(SandboxManifest::from_str_strict(MISSPELLED_MANIFEST).is_err());
+}
+
+#[test]
+fn test_newer_schema_version_is_rejected() {
+    let manifest = r#"{"schema_version": 99, "name": "plugin", "version": "1.0.0"}"#;
+    assert!(SandboxManifest::from_str(manifest).is_err());
+}
+
+#[test]
+fn test_yaml_manifest_merges_includes() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("base.yaml"), r#"
+name: base
+version: "0.1.0"
+capabilities:
+  network:
+    mode: loopback
+  time_mode: full
+"#).unwrap();
+    std::fs::write(dir.path().join("plugin.yaml"), r#"
+include: [base.yaml]
+name: plugin
+version: "1.0.0"
+capabilities:
+  time_mode: readonly
+"#).unwrap();
+
+    let resolved = SandboxManifest::resolve(&dir.path().join("plugin.yaml"), true).unwrap();
+    assert_eq!(resolved.manifest.name, "plugin");
+    assert_eq!(resolved.manifest.capabilities.network.mode, "loopback");
+    assert_eq!(resolved.manifest.capabilities.time_mode, "readonly");
+    assert_eq!(resolved.sources.len(), 2);
+    assert!(resolved.dump().unwrap().contains("base.yaml"));
+}
+
+#[test]
+fn test_include_cycle_is_detected() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.yaml"), "include: [b.yaml]\nname: a\nversion: \"1\"\n").unwrap();
+    std::fs::write(dir.path().join("b.yaml"), "include: [a.yaml]\nname: b\nversion: \"1\"\n").unwrap();
+
+    let error = SandboxManifest::from_path(&dir.path().join("a.yaml")).unwrap_err();
+    assert!(error.to_string().contains("cycle"));
+}
+
+#[test]
+fn test_enforcement_modes_are_parsed() {
+    let manifest = r#"
+name = "plugin"
+version = "1.0.0"
+
+[capabilities.enforcement]
+default = "audit"
+fs = "enforce"
+"#;
+    assert!(SandboxManifest::validate_str(manifest).unwrap().is_empty());
+    let capabilities = SandboxManifest::from_str(manifest).unwrap().to_capabilities().unwrap();
+    assert_eq!(capabilities.enforcement.mode_for("network"), EnforcementMode::Audit);
+    assert_eq!(capabilities.enforcement.mode_for("filesystem"), EnforcementMode::Enforce);
+
+    let invalid = manifest.replace("\"enforce\"", "\"warn\"");
+    let issues = SandboxManifest::validate_str(&invalid).unwrap();
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].path, "capabilities.enforcement.fs");
+    assert_eq!(issues[0].line, Some(7));
+    assert!(SandboxManifest::from_str(&invalid).unwrap().to_capabilities().is_err());
+}
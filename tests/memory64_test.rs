@@ -0,0 +1,79 @@
+//! Tests for guests with 64-bit linear memory
+
+mod common;
+
+use wasm_sandbox::runtime::{HostValue, RuntimeConfig};
+use wasm_sandbox::{Error, InstanceConfig, InstanceId, SandboxConfig, WasmSandbox};
+
+const MEMORY64_MODULE: &str = r#"
+(module
+  (memory (export "memory") i64 2)
+  (func (export "grow") (param $pages i64) (result i64)
+    (memory.grow (local.get $pages)))
+  (func (export "roundtrip") (param $addr i64) (param $value i32) (result i32)
+    (i32.store (local.get $addr) (local.get $value))
+    (i32.load (local.get $addr))))
+"#;
+
+fn instantiate(sandbox: &mut WasmSandbox, module: &str, max_memory_pages: u64) -> wasm_sandbox::Result<InstanceId> {
+    let mut config = InstanceConfig::default();
+    config.resource_limits.memory.max_memory_pages = max_memory_pages;
+    common::try_create_instance(sandbox, module, Some(config))
+}
+
+fn call(sandbox: &WasmSandbox, instance_id: InstanceId, function_name: &str, args: &[HostValue]) -> HostValue {
+    sandbox.get_instance(instance_id).unwrap().instance.call_values(function_name, args).unwrap()[0]
+}
+
+#[test]
+fn test_memory64_guest_runs_with_u64_usage() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let instance_id = instantiate(&mut sandbox, MEMORY64_MODULE, 1 << 33).unwrap();
+    
+    let value = call(&sandbox, instance_id, "roundtrip", &[HostValue::I64(65536 + 8), HostValue::I32(42)]);
+    assert_eq!(value, HostValue::I32(42));
+    
+    assert_eq!(call(&sandbox, instance_id, "grow", &[HostValue::I64(3)]), HostValue::I64(2));
+    let instance = &sandbox.get_instance(instance_id).unwrap().instance;
+    assert_eq!(instance.memory_usage(), 5 * 65536u64);
+    assert_eq!(sandbox.memory_pages(instance_id).unwrap().current, 5);
+    assert_eq!(sandbox.total_memory_usage(), 5 * 65536);
+}
+
+#[test]
+fn test_growth_past_the_page_limit_is_refused() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let instance_id = instantiate(&mut sandbox, MEMORY64_MODULE, 4).unwrap();
+    
+    assert_eq!(call(&sandbox, instance_id, "grow", &[HostValue::I64(2)]), HostValue::I64(2));
+    assert_eq!(call(&sandbox, instance_id, "grow", &[HostValue::I64(1)]), HostValue::I64(-1));
+    assert_eq!(sandbox.memory_pages(instance_id).unwrap().current, 4);
+}
+
+#[test]
+fn test_initial_memory_over_the_limit_is_rejected() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let result = instantiate(&mut sandbox, "(module (memory i64 8))", 4);
+    match result {
+        Err(Error::Configuration { message, .. }) => assert!(message.contains("8 memory pages"), "{}", message),
+        other => panic!("expected a configuration error, got {:?}", other.map(|_| ())),
+    }
+    
+    // 32-bit memories are held to the same limit
+    assert!(instantiate(&mut sandbox, "(module (memory 8))", 4).is_err());
+    assert!(instantiate(&mut sandbox, "(module (memory 4))", 4).is_ok());
+}
+
+#[test]
+fn test_memory64_can_be_disabled() {
+    let sandbox = WasmSandbox::with_config(SandboxConfig {
+        runtime: RuntimeConfig {
+            enable_memory64: false,
+            ..RuntimeConfig::default()
+        },
+        ..SandboxConfig::default()
+    }).expect("Failed to create sandbox");
+    
+    assert!(matches!(sandbox.load_module(MEMORY64_MODULE.as_bytes()), Err(Error::Configuration { .. })));
+    assert!(sandbox.load_module(b"(module (memory 1))").is_ok());
+}
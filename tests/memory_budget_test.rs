@@ -0,0 +1,124 @@
+//! Tests for the global memory budget and instance eviction
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use wasm_sandbox::{InstanceConfig, InstanceId, MemoryBudget, SandboxConfig, WasmSandbox};
+
+const COUNTER_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  
+  (func (export "set") (param i32) (result i32)
+    (i32.store (i32.const 0) (local.get 0))
+    (local.get 0))
+  
+  (func (export "get") (result i32)
+    (i32.load (i32.const 0))))
+"#;
+
+// Counts calls in an exported mutable global
+const GLOBAL_COUNTER_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $count (export "count") (mut i32) (i32.const 0))
+  
+  (func (export "bump") (result i32)
+    (global.set $count (i32.add (global.get $count) (i32.const 1)))
+    (global.get $count)))
+"#;
+
+/// Budget that fits one single-page instance
+fn one_instance_budget() -> MemoryBudget {
+    MemoryBudget::new(100 * 1024).min_idle(Duration::ZERO)
+}
+
+fn sandbox_with_budget(budget: MemoryBudget) -> WasmSandbox {
+    WasmSandbox::with_config(SandboxConfig {
+        memory_budget: Some(budget),
+        ..SandboxConfig::default()
+    }).expect("Failed to create sandbox")
+}
+
+#[test]
+fn test_lru_instance_is_dropped_over_budget() {
+    let mut sandbox = sandbox_with_budget(one_instance_budget());
+    let notices: Arc<Mutex<Vec<InstanceId>>> = Arc::new(Mutex::new(Vec::new()));
+    let seen = notices.clone();
+    sandbox.on_eviction(move |notice| seen.lock().unwrap().push(notice.instance_id));
+    
+    let module_id = sandbox.load_module(COUNTER_MODULE.as_bytes()).expect("Failed to load module");
+    let first = sandbox.create_instance(module_id, None).expect("Failed to create instance");
+    let second = sandbox.create_instance(module_id, None).expect("Failed to create instance");
+    
+    assert_eq!(*notices.lock().unwrap(), vec![first]);
+    assert!(sandbox.get_instance(first).is_none());
+    assert!(sandbox.get_instance(second).is_some());
+    assert!(sandbox.evicted_instances().is_empty());
+    
+    let metrics = sandbox.eviction_metrics();
+    assert_eq!(metrics.evictions, 1);
+    assert_eq!(metrics.dropped, 1);
+    assert_eq!(metrics.bytes_reclaimed, 65536);
+    assert!(sandbox.total_memory_usage() <= 100 * 1024);
+}
+
+#[test]
+fn test_snapshotted_instance_restores_memory() {
+    let snapshots = tempfile::tempdir().expect("Failed to create snapshot dir");
+    let mut sandbox = sandbox_with_budget(one_instance_budget().snapshot_to(snapshots.path()));
+    
+    let module_id = sandbox.load_module(COUNTER_MODULE.as_bytes()).expect("Failed to load module");
+    let first = sandbox.create_instance(module_id, None).expect("Failed to create instance");
+    sandbox.get_instance(first).unwrap().instance.call_simple_function("set", &[42]).unwrap();
+    
+    let second = sandbox.create_instance(module_id, None).expect("Failed to create instance");
+    assert_eq!(sandbox.evicted_instances(), vec![first]);
+    assert_eq!(sandbox.eviction_metrics().snapshotted, 1);
+    
+    sandbox.remove_instance(second);
+    sandbox.restore_instance(first).expect("Failed to restore instance");
+    
+    let value = sandbox.get_instance(first).unwrap().instance.call_simple_function("get", &[]).unwrap();
+    assert_eq!(value, 42);
+    assert!(sandbox.evicted_instances().is_empty());
+    assert_eq!(sandbox.eviction_metrics().restored, 1);
+    assert_eq!(std::fs::read_dir(snapshots.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_snapshotted_instance_keeps_globals_and_fuel() {
+    let snapshots = tempfile::tempdir().expect("Failed to create snapshot dir");
+    let mut sandbox = sandbox_with_budget(one_instance_budget().snapshot_to(snapshots.path()));
+    let mut config = InstanceConfig::default();
+    config.resource_limits.fuel = Some(1_000_000);
+    
+    let module_id = sandbox.load_module(GLOBAL_COUNTER_MODULE.as_bytes()).expect("Failed to load module");
+    let first = sandbox.create_instance(module_id, Some(config)).expect("Failed to create instance");
+    let instance = &sandbox.get_instance(first).unwrap().instance;
+    instance.call_simple_function("bump", &[]).unwrap();
+    assert_eq!(instance.call_simple_function("bump", &[]).unwrap(), 2);
+    let used = instance.fuel_usage().unwrap();
+    assert!(used > 0);
+    
+    let second = sandbox.create_instance(module_id, None).expect("Failed to create instance");
+    assert_eq!(sandbox.evicted_instances(), vec![first]);
+    sandbox.remove_instance(second);
+    sandbox.restore_instance(first).expect("Failed to restore instance");
+    
+    let instance = &sandbox.get_instance(first).unwrap().instance;
+    assert_eq!(instance.fuel_usage(), Some(used));
+    assert_eq!(instance.call_simple_function("bump", &[]).unwrap(), 3);
+}
+
+#[test]
+fn test_recently_used_instances_are_kept() {
+    let mut sandbox = sandbox_with_budget(MemoryBudget::new(100 * 1024));
+    
+    let module_id = sandbox.load_module(COUNTER_MODULE.as_bytes()).expect("Failed to load module");
+    sandbox.create_instance(module_id, None).expect("Failed to create instance");
+    sandbox.create_instance(module_id, None).expect("Failed to create instance");
+    
+    assert_eq!(sandbox.instance_ids().len(), 2);
+    assert_eq!(sandbox.eviction_metrics().evictions, 0);
+}
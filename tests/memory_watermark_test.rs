@@ -0,0 +1,113 @@
+//! Tests for per-call memory watermarks and out-of-memory prediction
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use wasm_sandbox::{GrowthDecision, InstanceConfig, InstanceId, OomPrediction, OomWarning, WasmSandbox};
+
+// `grow` adds up to `pages` pages one at a time, returning how many it got
+const GROWING_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "grow") (param $pages i32) (result i32)
+    (local $grown i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $grown) (local.get $pages)))
+        (br_if $done (i32.eq (memory.grow (i32.const 1)) (i32.const -1)))
+        (local.set $grown (i32.add (local.get $grown) (i32.const 1)))
+        (br $next)))
+    (local.get $grown)))
+"#;
+
+fn instance(oom_prediction: Option<OomPrediction>) -> (WasmSandbox, InstanceId) {
+    let mut config = InstanceConfig {
+        oom_prediction,
+        ..InstanceConfig::default()
+    };
+    config.resource_limits.memory.max_memory_pages = 32;
+    common::instantiate(GROWING_MODULE, Some(config))
+}
+
+fn record_warnings(sandbox: &WasmSandbox, decision: GrowthDecision) -> Arc<Mutex<Vec<OomWarning>>> {
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let recorded = warnings.clone();
+    sandbox.on_oom_warning(move |warning| {
+        recorded.lock().unwrap().push(warning.clone());
+        decision
+    });
+    warnings
+}
+
+#[tokio::test]
+async fn test_report_records_the_calls_high_watermark() {
+    let (sandbox, instance_id) = instance(None);
+    assert!(sandbox.last_invocation_report(instance_id).is_none());
+    
+    let grown: i32 = sandbox.call_function(instance_id, "grow", 3).await.unwrap();
+    assert_eq!(grown, 3);
+    let report = sandbox.last_invocation_report(instance_id).unwrap();
+    assert_eq!(report.function_name, "grow");
+    assert_eq!(Some(report.call_id), sandbox.last_call_id(instance_id));
+    assert_eq!((report.start_pages, report.peak_pages, report.end_pages), (1, 4, 4));
+    assert!(!report.oom_predicted);
+    
+    let _: i32 = sandbox.call_function(instance_id, "grow", 0).await.unwrap();
+    let report = sandbox.last_invocation_report(instance_id).unwrap();
+    assert_eq!((report.start_pages, report.peak_pages, report.end_pages), (4, 4, 4));
+}
+
+#[tokio::test]
+async fn test_predicted_oom_can_stop_growth_early() {
+    let (sandbox, instance_id) = instance(Some(OomPrediction::default()));
+    let warnings = record_warnings(&sandbox, GrowthDecision::Deny);
+    
+    // The second growth projects the call's rate a second ahead, far past 32 pages
+    let grown: i32 = sandbox.call_function(instance_id, "grow", 20).await.unwrap();
+    assert_eq!(grown, 1);
+    
+    let warnings = warnings.lock().unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].instance_id, instance_id);
+    assert_eq!(warnings[0].function_name, "grow");
+    assert_eq!((warnings[0].current_pages, warnings[0].limit_pages), (3, 32));
+    assert!(warnings[0].projected_pages > 32);
+    
+    let report = sandbox.last_invocation_report(instance_id).unwrap();
+    assert!(report.oom_predicted);
+    assert_eq!(report.peak_pages, 2);
+}
+
+#[tokio::test]
+async fn test_warning_is_raised_once_per_call() {
+    let (sandbox, instance_id) = instance(Some(OomPrediction::default().min_growths(5)));
+    let warnings = record_warnings(&sandbox, GrowthDecision::Allow);
+    
+    let grown: i32 = sandbox.call_function(instance_id, "grow", 20).await.unwrap();
+    assert_eq!(grown, 20);
+    assert_eq!(warnings.lock().unwrap().len(), 1);
+    assert_eq!(warnings.lock().unwrap()[0].current_pages, 6);
+    
+    let _: i32 = sandbox.call_function(instance_id, "grow", 4).await.unwrap();
+    assert_eq!(warnings.lock().unwrap().len(), 1, "four growths are below min_growths");
+    assert_eq!(sandbox.last_invocation_report(instance_id).unwrap().peak_pages, 25);
+}
+
+#[tokio::test]
+async fn test_calls_are_not_predicted_without_a_configuration() {
+    let (sandbox, instance_id) = instance(None);
+    let warnings = record_warnings(&sandbox, GrowthDecision::Deny);
+    
+    let grown: i32 = sandbox.call_function(instance_id, "grow", 20).await.unwrap();
+    assert_eq!(grown, 20);
+    assert!(warnings.lock().unwrap().is_empty());
+    
+    // A zero horizon only projects the current size, which is within the limit
+    let (sandbox, instance_id) = instance(Some(OomPrediction::default().horizon(Duration::ZERO)));
+    let warnings = record_warnings(&sandbox, GrowthDecision::Deny);
+    let grown: i32 = sandbox.call_function(instance_id, "grow", 20).await.unwrap();
+    assert_eq!(grown, 20);
+    assert!(warnings.lock().unwrap().is_empty());
+}
@@ -0,0 +1,79 @@
+//! Tests for the plugin author test harness
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::security::{Capabilities, EnvironmentCapability};
+use wasm_sandbox::testing::MockHost;
+
+const PLUGIN_MODULE: &str = r#"
+(module
+  (import "host" "get_threshold" (func $get_threshold (result i32)))
+  (import "host" "log" (func $log (param i32)))
+  (memory (export "memory") 1)
+  
+  (func (export "check") (param i32) (result i32)
+    (call $log (local.get 0))
+    (i32.gt_s (local.get 0) (call $get_threshold)))
+  
+  (func (export "check_twice") (param i32) (result i32 i32)
+    (i32.gt_s (local.get 0) (call $get_threshold))
+    (i32.gt_s (local.get 0) (call $get_threshold))))
+"#;
+
+#[test]
+fn test_scripted_responses_and_recorded_calls() {
+    let plugin = MockHost::new()
+        .returns("host", "get_threshold", vec![HostValue::I32(10)])
+        .on("host", "log", |_| Ok(vec![]))
+        .instantiate(PLUGIN_MODULE.as_bytes())
+        .expect("Failed to instantiate plugin");
+    
+    assert_eq!(plugin.call("check", &[HostValue::I32(12)]).unwrap(), vec![HostValue::I32(1)]);
+    assert_eq!(plugin.call("check", &[HostValue::I32(3)]).unwrap(), vec![HostValue::I32(0)]);
+    
+    plugin.assert_called("host", "log");
+    let logged: Vec<_> = plugin.calls_to("host", "log").into_iter().map(|call| call.args).collect();
+    assert_eq!(logged, vec![vec![HostValue::I32(12)], vec![HostValue::I32(3)]]);
+    assert_eq!(plugin.call_count("host", "get_threshold"), 2);
+    
+    plugin.clear_calls();
+    assert!(plugin.calls().is_empty());
+}
+
+#[test]
+fn test_response_sequence() {
+    let plugin = MockHost::new()
+        .returns_sequence("host", "get_threshold", vec![vec![HostValue::I32(0)], vec![HostValue::I32(100)]])
+        .on("host", "log", |_| Ok(vec![]))
+        .instantiate(PLUGIN_MODULE.as_bytes())
+        .expect("Failed to instantiate plugin");
+    
+    let results = plugin.call("check_twice", &[HostValue::I32(50)]).unwrap();
+    assert_eq!(results, vec![HostValue::I32(1), HostValue::I32(0)]);
+    plugin.assert_not_called("host", "log");
+}
+
+#[test]
+fn test_failing_host_function_traps_guest() {
+    let plugin = MockHost::new()
+        .fails("host", "get_threshold", "backend unavailable")
+        .on("host", "log", |_| Ok(vec![]))
+        .instantiate(PLUGIN_MODULE.as_bytes())
+        .expect("Failed to instantiate plugin");
+    
+    let err = plugin.call("check", &[HostValue::I32(1)]).unwrap_err();
+    assert!(err.to_string().contains("backend unavailable"));
+    assert_eq!(plugin.calls_to("host", "get_threshold")[0].results, None);
+}
+
+#[test]
+fn test_unscripted_imports_are_rejected() {
+    let result = MockHost::new()
+        .returns("host", "get_threshold", vec![HostValue::I32(10)])
+        .capabilities(Capabilities {
+            environment: EnvironmentCapability::None,
+            ..Capabilities::minimal()
+        })
+        .instantiate(PLUGIN_MODULE.as_bytes());
+    
+    assert!(result.is_err());
+}
@@ -0,0 +1,60 @@
+//! Tests for per-module default instance configurations
+
+use wasm_sandbox::{Error, InstanceConfig, ResourceKind, WasmSandbox};
+
+/// Data ABI module whose export returns its input
+const ECHO_MODULE: &str = r#"
+(module
+  (memory (export "memory") 4)
+  (func (export "alloc") (param i32) (result i32)
+    i32.const 1024)
+  (func (export "echo") (param i32 i32) (result i64)
+    (i64.or (i64.shl (i64.extend_i32_u (local.get 0)) (i64.const 32)) (i64.extend_i32_u (local.get 1)))))
+"#;
+
+fn strict() -> InstanceConfig {
+    InstanceConfig::builder().max_param_bytes(16).build().unwrap()
+}
+
+async fn echoes(sandbox: &WasmSandbox, instance_id: wasm_sandbox::InstanceId) -> bool {
+    match sandbox.call_function::<_, String>(instance_id, "echo", "x".repeat(64)).await {
+        Ok(_) => true,
+        Err(Error::ResourceExhausted { kind: ResourceKind::ParamBytes, .. }) => false,
+        Err(other) => panic!("unexpected error: {}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_instances_default_to_their_module_config() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let strict_module = sandbox.load_module_with_defaults(ECHO_MODULE.as_bytes(), strict()).unwrap();
+    let plain_module = sandbox.load_module(ECHO_MODULE.as_bytes()).unwrap();
+    
+    let strict_instance = sandbox.create_instance(strict_module, None).unwrap();
+    let plain_instance = sandbox.create_instance(plain_module, None).unwrap();
+    assert!(!echoes(&sandbox, strict_instance).await);
+    assert!(echoes(&sandbox, plain_instance).await);
+    
+    // An explicit configuration still wins
+    let explicit = sandbox.create_instance(strict_module, Some(InstanceConfig::default())).unwrap();
+    assert!(echoes(&sandbox, explicit).await);
+}
+
+#[tokio::test]
+async fn test_module_defaults_can_be_replaced_and_cleared() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(ECHO_MODULE.as_bytes()).unwrap();
+    assert_eq!(sandbox.module_defaults(module_id).max_param_bytes, None);
+    
+    sandbox.set_module_defaults(module_id, strict()).unwrap();
+    assert_eq!(sandbox.module_defaults(module_id).max_param_bytes, Some(16));
+    let before = sandbox.create_instance(module_id, None).unwrap();
+    
+    assert!(sandbox.clear_module_defaults(module_id).is_some());
+    let after = sandbox.create_instance(module_id, None).unwrap();
+    assert!(!echoes(&sandbox, before).await, "existing instances keep their configuration");
+    assert!(echoes(&sandbox, after).await);
+    
+    let unknown = WasmSandbox::new().unwrap().load_module(ECHO_MODULE.as_bytes()).unwrap();
+    assert!(sandbox.set_module_defaults(unknown, strict()).is_err());
+}
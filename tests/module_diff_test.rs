@@ -0,0 +1,122 @@
+//! Tests for comparing two builds of a module
+
+use wasm_sandbox::{ChangeKind, ModuleDiff};
+
+const OLD: &str = r#"
+    (module
+        (import "env" "log" (func (param i32 i32)))
+        (memory (export "memory") 1 16)
+        (func (export "add") (param i32 i32) (result i32)
+            local.get 0
+            local.get 1
+            i32.add)
+        (func (export "legacy") (result i32)
+            i32.const 0))
+"#;
+
+#[test]
+fn identical_builds_have_no_changes() {
+    let diff = ModuleDiff::compare(OLD.as_bytes(), OLD.as_bytes()).unwrap();
+    assert!(diff.is_empty());
+    assert!(diff.is_compatible());
+    assert!(!diff.is_component);
+}
+
+#[test]
+fn added_exports_are_compatible_and_removed_ones_break() {
+    let new = r#"
+        (module
+            (import "env" "log" (func (param i32 i32)))
+            (memory (export "memory") 1 16)
+            (func (export "add") (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                i32.add)
+            (func (export "sub") (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                i32.sub))
+    "#;
+    let diff = ModuleDiff::compare(OLD.as_bytes(), new.as_bytes()).unwrap();
+    
+    let kinds: Vec<_> = diff.changes.iter().map(|change| (change.kind, change.item.as_str(), change.breaking)).collect();
+    assert_eq!(kinds, vec![
+        (ChangeKind::ExportRemoved, "export legacy", true),
+        (ChangeKind::ExportAdded, "export sub", false),
+    ]);
+    
+    let report = diff.compatibility_report();
+    assert!(!report.is_compatible);
+    assert_eq!(report.breaking_changes.len(), 1);
+    assert_eq!(report.breaking_changes[0].affected_items, vec!["export legacy".to_string()]);
+    assert_eq!(report.warnings.len(), 1);
+}
+
+#[test]
+fn signature_import_and_memory_changes_are_reported() {
+    let new = r#"
+        (module
+            (import "env" "log" (func (param i32 i32 i32)))
+            (import "env" "now" (func (result i64)))
+            (memory (export "memory") 2 8)
+            (func (export "add") (param i64 i64) (result i64)
+                local.get 0
+                local.get 1
+                i64.add)
+            (func (export "legacy") (result i32)
+                i32.const 0))
+    "#;
+    let diff = ModuleDiff::compare(OLD.as_bytes(), new.as_bytes()).unwrap();
+    
+    let add = diff.changes.iter().find(|change| change.item == "export add").unwrap();
+    assert_eq!(add.kind, ChangeKind::ExportChanged);
+    assert_eq!(add.old.as_deref(), Some("func(i32, i32) -> (i32)"));
+    assert_eq!(add.new.as_deref(), Some("func(i64, i64) -> (i64)"));
+    
+    let memory = diff.changes.iter().find(|change| change.item == "export memory").unwrap();
+    assert_eq!(memory.kind, ChangeKind::MemoryLimitsChanged);
+    assert_eq!(memory.old.as_deref(), Some("memory 1..16"));
+    assert_eq!(memory.new.as_deref(), Some("memory 2..8"));
+    assert!(memory.breaking, "a smaller maximum can't hold what the old build could");
+    
+    let log = diff.changes.iter().find(|change| change.item == "import env::log").unwrap();
+    assert_eq!(log.kind, ChangeKind::ImportChanged);
+    let now = diff.changes.iter().find(|change| change.item == "import env::now").unwrap();
+    assert_eq!(now.kind, ChangeKind::ImportAdded);
+    assert!(now.breaking);
+    assert!(!diff.is_compatible());
+}
+
+#[test]
+fn component_interface_changes_are_reported() {
+    let old = r#"
+        (component
+            (core module $m
+                (func (export "run") (param i32) (result i32) local.get 0))
+            (core instance $i (instantiate $m))
+            (func $run (param "x" u32) (result u32) (canon lift (core func $i "run")))
+            (instance $api (export "run" (func $run)))
+            (export "example:plugin/api" (instance $api)))
+    "#;
+    let new = r#"
+        (component
+            (core module $m
+                (func (export "run") (param i32) (result i32) local.get 0))
+            (core instance $i (instantiate $m))
+            (func $run (param "x" s32) (result s32) (canon lift (core func $i "run")))
+            (instance $api (export "run" (func $run)))
+            (export "example:plugin/api" (instance $api)))
+    "#;
+    let diff = ModuleDiff::compare(old.as_bytes(), new.as_bytes()).unwrap();
+    
+    assert!(diff.is_component);
+    assert_eq!(diff.changes.len(), 1);
+    let change = &diff.changes[0];
+    assert_eq!(change.kind, ChangeKind::ExportChanged);
+    assert_eq!(change.item, "export example:plugin/api#run");
+    assert_eq!(change.old.as_deref(), Some("func(x: u32) -> (u32)"));
+    assert_eq!(change.new.as_deref(), Some("func(x: s32) -> (s32)"));
+    assert!(!diff.is_compatible());
+    
+    assert!(ModuleDiff::compare(OLD.as_bytes(), new.as_bytes()).is_err());
+}
@@ -0,0 +1,91 @@
+//! Tests for typed calls to exports with mixed parameter and multi-value result types
+
+mod common;
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::Error;
+
+const NUMERIC_MODULE: &str = r#"
+(module
+  (func (export "divmod") (param i64 i64) (result i64 i64)
+    (i64.div_s (local.get 0) (local.get 1))
+    (i64.rem_s (local.get 0) (local.get 1)))
+  (func (export "mix") (param i32 f32 f64) (result f64 i32)
+    (f64.add (f64.promote_f32 (local.get 1)) (local.get 2))
+    (i32.mul (local.get 0) (i32.const 2)))
+  (func (export "lanes") (param v128 v128) (result v128)
+    (i32x4.add (local.get 0) (local.get 1)))
+  (func (export "first_lane") (param v128) (result i32)
+    (i32x4.extract_lane 0 (local.get 0)))
+  (func (export "splat") (param i32) (result v128)
+    (i32x4.splat (local.get 0)))
+  (func (export "nothing")))
+"#;
+
+fn lanes(values: [u32; 4]) -> u128 {
+    values.iter().rev().fold(0u128, |bits, lane| (bits << 32) | *lane as u128)
+}
+
+#[tokio::test]
+async fn test_typed_calls_return_every_result() {
+    let (sandbox, instance_id) = common::instantiate(NUMERIC_MODULE, None);
+    
+    let (quotient, remainder): (i64, i64) = sandbox.call_typed(instance_id, "divmod", (-17i64, 5i64)).await.unwrap();
+    assert_eq!((quotient, remainder), (-3, -2));
+    let (sum, doubled): (f64, i32) = sandbox.call_typed(instance_id, "mix", (21, 0.5f32, 0.25)).await.unwrap();
+    assert_eq!((sum, doubled), (0.75, 42));
+    sandbox.call_typed::<_, ()>(instance_id, "nothing", ()).await.unwrap();
+    
+    // The untyped form keeps the exact result types
+    let results = sandbox.call_values(instance_id, "divmod", &[HostValue::I32(9), HostValue::I64(4)]).await.unwrap();
+    assert_eq!(results, [HostValue::I64(2), HostValue::I64(1)]);
+    
+    // JSON calls see multi-value results as arrays
+    let results: (i64, i64) = sandbox.call_function(instance_id, "divmod", (9, 4)).await.unwrap();
+    assert_eq!(results, (2, 1));
+}
+
+#[tokio::test]
+async fn test_v128_values_pass_both_ways() {
+    let (sandbox, instance_id) = common::instantiate(NUMERIC_MODULE, None);
+    
+    let sum: u128 = sandbox.call_typed(instance_id, "lanes", (lanes([1, 2, 3, 4]), lanes([10, 20, 30, u32::MAX]))).await.unwrap();
+    assert_eq!(sum, lanes([11, 22, 33, 3]));
+    let first: i32 = sandbox.call_typed(instance_id, "first_lane", lanes([7, 0, 0, 0])).await.unwrap();
+    assert_eq!(first, 7);
+    
+    // JSON can't hold 128 bits, so vectors come back as hex strings
+    let splat: String = sandbox.call_function(instance_id, "splat", 1).await.unwrap();
+    assert_eq!(splat, "0x00000001000000010000000100000001");
+}
+
+#[tokio::test]
+async fn test_signature_mismatches_are_explained() {
+    let (sandbox, instance_id) = common::instantiate(NUMERIC_MODULE, None);
+    
+    let error = sandbox.call_typed::<_, (i64, i64)>(instance_id, "divmod", (1.5f64, 2i64)).await.unwrap_err();
+    assert!(matches!(&error, Error::FunctionCall { .. }));
+    assert!(error.to_string().contains("Argument 1 is f64 but the export is (i64, i64) -> (i64, i64)"), "{}", error);
+    
+    let error = sandbox.call_typed::<_, (i64, i64)>(instance_id, "divmod", 1i64).await.unwrap_err();
+    assert!(error.to_string().contains("was passed 1 arguments"), "{}", error);
+    
+    let error = sandbox.call_typed::<_, i64>(instance_id, "divmod", (1i64, 2i64)).await.unwrap_err();
+    assert!(error.to_string().contains("Export returns (i64, i64) but the call expects (i64)"), "{}", error);
+    
+    let error = sandbox.call_typed::<_, (f64, f64)>(instance_id, "mix", (1, 1.0f32, 1.0)).await.unwrap_err();
+    assert!(error.to_string().contains("returns (f64, i32) but the call expects (f64, f64)"), "{}", error);
+}
+
+#[test]
+fn test_host_value_conversions() {
+    assert_eq!(HostValue::from(7i64), HostValue::I64(7));
+    assert_eq!(HostValue::from(1u128 << 100), HostValue::V128(1 << 100));
+    assert_eq!(i32::try_from(HostValue::I32(3)).unwrap(), 3);
+    assert_eq!(f32::try_from(HostValue::F32(0.5)).unwrap(), 0.5);
+    
+    let error = i64::try_from(HostValue::F64(1.0)).unwrap_err();
+    assert!(error.to_string().contains("Expected i64, got f64"), "{}", error);
+    assert_eq!(HostValue::ExternRef(None).type_name(), "externref");
+    assert_eq!(HostValue::V128(0).type_name(), "v128");
+}
@@ -0,0 +1,132 @@
+//! Tests for sandboxes nested inside a sandbox
+
+use std::time::Duration;
+
+use wasm_sandbox::{
+    CapabilityChange, Error, FuelBudget, InstanceConfig, MemoryBudget, NestedSandboxConfig, NetworkCapability,
+    SandboxConfig, ScratchConfig, WasmSandbox,
+};
+use wasm_sandbox::security::{Capabilities, HostSpec};
+
+const ADD_MODULE: &str = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))))
+"#;
+
+fn host(name: &str) -> HostSpec {
+    HostSpec { host: name.to_string(), ports: None, secure: true }
+}
+
+fn hosts(names: &[&str]) -> NetworkCapability {
+    NetworkCapability::AllowedHosts(names.iter().map(|name| host(name)).collect())
+}
+
+fn with_network(network: NetworkCapability) -> Capabilities {
+    Capabilities {
+        network,
+        ..Capabilities::minimal()
+    }
+}
+
+#[tokio::test]
+async fn test_nested_sandbox_runs_instances() {
+    let mut parent = WasmSandbox::new().expect("Failed to create sandbox");
+    let id = parent.create_nested(NestedSandboxConfig::new(Capabilities::minimal())).unwrap();
+    assert_eq!(parent.nested_ids(), vec![id]);
+    
+    let nested = parent.nested_mut(id).unwrap();
+    let module_id = nested.load_module(ADD_MODULE.as_bytes()).unwrap();
+    let instance_id = nested.create_instance(module_id, None).unwrap();
+    let sum: i32 = nested.call_function(instance_id, "add", (2, 3)).await.unwrap();
+    assert_eq!(sum, 5);
+    
+    // Sandboxes nest to any depth
+    let inner = nested.create_nested(NestedSandboxConfig::new(Capabilities::minimal())).unwrap();
+    assert!(nested.nested(inner).is_some());
+    
+    assert!(parent.remove_nested(id));
+    assert!(parent.nested(id).is_none());
+    assert!(!parent.remove_nested(id));
+}
+
+#[test]
+fn test_capabilities_only_narrow() {
+    let ceiling = with_network(hosts(&["api.example.com", "cdn.example.com"]));
+    let mut parent = WasmSandbox::with_config(SandboxConfig {
+        capability_ceiling: Some(ceiling.clone()),
+        ..SandboxConfig::default()
+    }).expect("Failed to create sandbox");
+    
+    match parent.create_nested(NestedSandboxConfig::new(with_network(NetworkCapability::Full))) {
+        Err(Error::SecurityViolation { violation, .. }) => assert!(violation.contains("network")),
+        other => panic!("expected a wider nested sandbox to be refused, got {:?}", other.map(|_| ())),
+    }
+    
+    let id = parent.create_nested(NestedSandboxConfig::new(with_network(hosts(&["api.example.com"])))).unwrap();
+    let nested = parent.nested_mut(id).unwrap();
+    assert!(nested.create_nested(NestedSandboxConfig::new(ceiling.clone())).is_err());
+    
+    // Instances and grants in the nested sandbox stay under its ceiling
+    let module_id = nested.load_module(ADD_MODULE.as_bytes()).unwrap();
+    let wider = InstanceConfig {
+        capabilities: ceiling,
+        ..InstanceConfig::default()
+    };
+    assert!(matches!(nested.create_instance(module_id, Some(wider)), Err(Error::SecurityViolation { .. })));
+    
+    let instance_id = nested.create_instance(module_id, None).unwrap();
+    assert!(nested.grant(instance_id, CapabilityChange::AllowHost(host("cdn.example.com")), None).is_err());
+    assert!(nested.grant(instance_id, CapabilityChange::AllowHost(host("api.example.com")), None).is_ok());
+}
+
+#[test]
+fn test_budgets_are_carved_from_the_parent() {
+    let mut parent = WasmSandbox::with_config(SandboxConfig {
+        memory_budget: Some(MemoryBudget::new(10 * 1024 * 1024)),
+        fuel_budget: Some(FuelBudget::new(1_000_000, Duration::from_secs(1))),
+        ..SandboxConfig::default()
+    }).expect("Failed to create sandbox");
+    
+    // Nested sandboxes must take a share of each budget the parent has
+    assert!(matches!(
+        parent.create_nested(NestedSandboxConfig::new(Capabilities::minimal()).fuel(100_000)),
+        Err(Error::Configuration { .. })
+    ));
+    
+    let share = || NestedSandboxConfig::new(Capabilities::minimal()).memory(4 * 1024 * 1024).fuel(400_000);
+    let first = parent.create_nested(share()).unwrap();
+    let second = parent.create_nested(share()).unwrap();
+    assert!(matches!(parent.create_nested(share()), Err(Error::ResourceExhausted { .. })));
+    
+    // The parent's instances share what is left
+    let module_id = parent.load_module(ADD_MODULE.as_bytes()).unwrap();
+    let instance_id = parent.create_instance(module_id, None).unwrap();
+    assert_eq!(parent.fuel_balance(instance_id), Some(200_000));
+    
+    // Removing a nested sandbox returns its share
+    assert!(parent.remove_nested(first));
+    assert!(parent.create_nested(share()).is_ok());
+    assert!(parent.nested(second).is_some());
+}
+
+#[test]
+fn test_dropping_the_parent_tears_down_nested_instances() {
+    let mut parent = WasmSandbox::new().expect("Failed to create sandbox");
+    let id = parent.create_nested(NestedSandboxConfig::new(Capabilities::minimal())).unwrap();
+    let nested = parent.nested_mut(id).unwrap();
+    let inner = nested.create_nested(NestedSandboxConfig::new(Capabilities::minimal())).unwrap();
+    let innermost = nested.nested_mut(inner).unwrap();
+    
+    let module_id = innermost.load_module(ADD_MODULE.as_bytes()).unwrap();
+    let config = InstanceConfig {
+        scratch: Some(ScratchConfig::default()),
+        ..InstanceConfig::default()
+    };
+    let instance_id = innermost.create_instance(module_id, Some(config)).unwrap();
+    let scratch = innermost.scratch_space(instance_id).unwrap().path().to_path_buf();
+    assert!(scratch.exists());
+    
+    drop(parent);
+    assert!(!scratch.exists());
+}
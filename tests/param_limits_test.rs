@@ -0,0 +1,65 @@
+//! Tests for limiting and spilling call parameters
+
+mod common;
+
+use wasm_sandbox::{Error, InstanceConfig, InstanceId, ResourceKind, WasmSandbox};
+
+/// Data ABI module whose exports return their input
+const ECHO_MODULE: &str = r#"
+(module
+  (memory (export "memory") 4)
+  (func (export "alloc") (param i32) (result i32)
+    i32.const 1024)
+  (func $echo (export "echo") (param i32 i32) (result i64)
+    (i64.or (i64.shl (i64.extend_i32_u (local.get 0)) (i64.const 32)) (i64.extend_i32_u (local.get 1))))
+  (func (export "upload") (param i32 i32) (result i64)
+    (call $echo (local.get 0) (local.get 1))))
+"#;
+
+fn instantiate(config: InstanceConfig) -> (WasmSandbox, InstanceId) {
+    common::instantiate(ECHO_MODULE, Some(config))
+}
+
+#[tokio::test]
+async fn test_large_parameters_are_written_into_guest_memory() {
+    let config = InstanceConfig {
+        max_inline_param_bytes: Some(64),
+        ..InstanceConfig::default()
+    };
+    let (sandbox, instance_id) = instantiate(config);
+    
+    let small = vec![1u32, 2, 3];
+    let echoed: Vec<u32> = sandbox.call_function(instance_id, "echo", small.clone()).await.unwrap();
+    assert_eq!(echoed, small);
+    
+    // Spilled parameters reach the guest unchanged
+    let large: Vec<u32> = (0..10_000).collect();
+    let echoed: Vec<u32> = sandbox.call_function(instance_id, "echo", large.clone()).await.unwrap();
+    assert_eq!(echoed, large);
+}
+
+#[tokio::test]
+async fn test_oversized_parameters_fail_before_the_guest_runs() {
+    let config = InstanceConfig::builder()
+        .max_param_bytes(32)
+        .function_param_limit("upload", 1024)
+        .build()
+        .unwrap();
+    let (sandbox, instance_id) = instantiate(config);
+    
+    let params = "x".repeat(100);
+    let err = sandbox.call_function::<_, String>(instance_id, "echo", params.clone()).await.unwrap_err();
+    match err {
+        Error::ResourceExhausted { kind: ResourceKind::ParamBytes, limit, used, .. } => {
+            assert_eq!(limit, 32);
+            assert!(used > 32);
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+    let err = sandbox.call_function_bytes(instance_id, "echo", params.as_bytes()).await.unwrap_err();
+    assert!(matches!(err, Error::ResourceExhausted { kind: ResourceKind::ParamBytes, .. }), "{}", err);
+    
+    // The export's own limit applies in place of the instance-wide one
+    let echoed: String = sandbox.call_function(instance_id, "upload", params.clone()).await.unwrap();
+    assert_eq!(echoed, params);
+}
@@ -0,0 +1,220 @@
+//! Tests for packaging plugins as `.wsbx` bundles
+
+use std::collections::HashMap;
+
+use serde_json::json;
+use wasm_sandbox::bundle::BUNDLE_MAGIC;
+use wasm_sandbox::config::AdvancedCapabilities;
+use wasm_sandbox::fixtures::FixtureCase;
+use wasm_sandbox::plugins::PluginQuery;
+use wasm_sandbox::{Bundle, Ed25519PublicKey, Ed25519Signer, Error, HmacKey, PluginManifest, PluginRegistry, Result};
+
+/// `echo` returns its JSON input
+const ECHO_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (local.get $ptr) (local.get $len)))
+    (local.get $ptr))
+  (func (export "echo") (param $ptr i32) (param $len i32) (result i64)
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+      (i64.extend_i32_u (local.get $len)))))
+"#;
+
+const POLICY: &str = r#"
+name = "echo"
+version = "1.0.0"
+
+[resource_limits.memory]
+max_memory = "32MB"
+"#;
+
+fn manifest(id: &str) -> PluginManifest {
+    PluginManifest {
+        id: id.to_string(),
+        name: "Echo".to_string(),
+        version: "1.0.0".to_string(),
+        description: "Returns its input".to_string(),
+        permissions: AdvancedCapabilities::default(),
+        entry_points: Vec::new(),
+        dependencies: Vec::new(),
+        metadata: HashMap::new(),
+        min_sandbox_version: "0.1.0".to_string(),
+        author: Some("Ada".to_string()),
+        license: Some("MIT".to_string()),
+        repository: None,
+    }
+}
+
+fn echo_bundle() -> Bundle {
+    Bundle::new(manifest("echo"), ECHO_MODULE.as_bytes().to_vec())
+        .with_readme("# Echo\n")
+        .with_metadata("categories", json!(["examples"]))
+        .with_fixture(FixtureCase::new("object", "echo", json!({"a": 1})).expect(json!({"a": 1})))
+        .with_policy(POLICY)
+        .unwrap()
+}
+
+#[derive(Default)]
+struct MemoryRegistry {
+    plugins: HashMap<String, (PluginManifest, Vec<u8>)>,
+}
+
+impl PluginRegistry for MemoryRegistry {
+    fn register_plugin(&mut self, manifest: PluginManifest, wasm_bytes: Vec<u8>) -> Result<()> {
+        self.plugins.insert(manifest.id.clone(), (manifest, wasm_bytes));
+        Ok(())
+    }
+    
+    fn unregister_plugin(&mut self, plugin_id: &str) -> Result<()> {
+        self.plugins.remove(plugin_id);
+        Ok(())
+    }
+    
+    fn get_manifest(&self, plugin_id: &str) -> Result<&PluginManifest> {
+        self.plugins.get(plugin_id).map(|(manifest, _)| manifest).ok_or_else(|| Error::NotFound {
+            resource_type: "plugin".to_string(),
+            identifier: plugin_id.to_string(),
+        })
+    }
+    
+    fn list_plugins(&self) -> Vec<&PluginManifest> {
+        self.plugins.values().map(|(manifest, _)| manifest).collect()
+    }
+    
+    fn search_plugins(&self, _query: &PluginQuery) -> Vec<&PluginManifest> {
+        self.list_plugins()
+    }
+    
+    fn get_dependencies(&self, _plugin_id: &str) -> Result<Vec<&PluginManifest>> {
+        Ok(Vec::new())
+    }
+}
+
+#[test]
+fn test_pack_and_unpack_roundtrip() {
+    let mut bundle = echo_bundle();
+    bundle.sign(&HmacKey::new("publisher", "secret")).unwrap();
+    let packed = bundle.pack().unwrap();
+    assert!(packed.starts_with(BUNDLE_MAGIC));
+    assert_eq!(bundle.pack().unwrap(), packed);
+    
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("echo.wsbx");
+    bundle.write(&path).unwrap();
+    let unpacked = Bundle::read(&path).unwrap();
+    
+    assert_eq!(unpacked.module, bundle.module);
+    assert_eq!(unpacked.manifest.id, "echo");
+    assert_eq!(unpacked.readme.as_deref(), Some("# Echo\n"));
+    assert_eq!(unpacked.metadata["categories"], json!(["examples"]));
+    assert_eq!(unpacked.fixtures, bundle.fixtures);
+    assert_eq!(unpacked.default_policy().unwrap().unwrap().name, "echo");
+    assert_eq!(unpacked.signature, bundle.signature);
+    assert_eq!(unpacked.digest().unwrap(), bundle.digest().unwrap());
+}
+
+#[test]
+fn test_tampering_and_untrusted_signatures_are_rejected() {
+    let key = HmacKey::new("publisher", "secret");
+    let mut bundle = echo_bundle();
+    assert!(matches!(bundle.verify(&key), Err(Error::SecurityViolation { .. })));
+    
+    bundle.sign(&key).unwrap();
+    bundle.verify(&key).unwrap();
+    let err = bundle.verify(&HmacKey::new("publisher", "other")).unwrap_err();
+    assert!(err.to_string().contains("does not match"), "{}", err);
+    assert!(bundle.verify(&HmacKey::new("someone-else", "secret")).is_err());
+    
+    // Changing the contents after signing breaks the signature
+    let mut edited = bundle.clone();
+    edited.manifest.version = "1.0.1".to_string();
+    assert!(Bundle::unpack(&edited.pack().unwrap()).unwrap().verify(&key).is_err());
+    
+    // Changing the bytes of an entry breaks its digest
+    let mut packed = bundle.pack().unwrap();
+    let last = packed.len() - 1;
+    packed[last] ^= 1;
+    let err = Bundle::unpack(&packed).unwrap_err();
+    assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+    assert!(Bundle::unpack(b"WSBXPLUGnope").is_err());
+    
+    assert!(echo_bundle().with_policy("include = [\"base.toml\"]\nname = \"x\"\nversion = \"1\"").is_err());
+}
+
+#[test]
+fn test_signature_survives_map_ordering() {
+    let mut manifest = manifest("echo");
+    for i in 0..16 {
+        manifest.metadata.insert(format!("key-{}", i), json!(i));
+    }
+    let mut bundle = Bundle::new(manifest, ECHO_MODULE.as_bytes().to_vec());
+    for i in 0..16 {
+        bundle = bundle.with_metadata(&format!("tag-{}", i), json!({"b": i, "a": [i]}));
+    }
+    
+    let (signer, _) = Ed25519Signer::generate("publisher").unwrap();
+    bundle.sign(&signer).unwrap();
+    
+    // Unpacking rebuilds the maps with different iteration orders
+    let unpacked = Bundle::unpack(&bundle.pack().unwrap()).unwrap();
+    assert_eq!(unpacked.manifest.metadata.len(), 16);
+    assert_eq!(unpacked.metadata.len(), 16);
+    unpacked.verify(&signer.public_key()).unwrap();
+    let repacked = Bundle::unpack(&unpacked.pack().unwrap()).unwrap();
+    repacked.verify(&signer.public_key()).unwrap();
+}
+
+#[test]
+fn test_ed25519_signatures() {
+    let (signer, pkcs8) = Ed25519Signer::generate("publisher").unwrap();
+    let public_key = signer.public_key();
+    assert_eq!(public_key.as_bytes().len(), 32);
+    
+    let mut bundle = echo_bundle();
+    bundle.sign(&Ed25519Signer::from_pkcs8("publisher", &pkcs8).unwrap()).unwrap();
+    bundle.verify(&Ed25519PublicKey::new("publisher", public_key.as_bytes())).unwrap();
+    
+    let (other, _) = Ed25519Signer::generate("publisher").unwrap();
+    let err = bundle.verify(&other.public_key()).unwrap_err();
+    assert!(err.to_string().contains("does not match"), "{}", err);
+    assert!(bundle.verify(&HmacKey::new("publisher", "secret")).is_err());
+    
+    let mut edited = bundle.clone();
+    edited.readme = Some("# Not echo\n".to_string());
+    assert!(edited.verify(&public_key).is_err());
+    assert!(Ed25519Signer::from_pkcs8("publisher", b"not a key").is_err());
+}
+
+#[test]
+fn test_registry_accepts_only_trusted_bundles() {
+    let key = HmacKey::new("publisher", "secret");
+    let mut registry = MemoryRegistry::default();
+    
+    let err = registry.register_bundle(echo_bundle(), &key).unwrap_err();
+    assert!(matches!(err, Error::SecurityViolation { .. }), "{}", err);
+    assert!(registry.list_plugins().is_empty());
+    
+    let mut bundle = echo_bundle();
+    bundle.sign(&key).unwrap();
+    let bundle = Bundle::unpack(&bundle.pack().unwrap()).unwrap();
+    registry.register_bundle(bundle, &key).unwrap();
+    assert_eq!(registry.get_manifest("echo").unwrap().author.as_deref(), Some("Ada"));
+    assert_eq!(registry.plugins["echo"].1, ECHO_MODULE.as_bytes());
+}
+
+#[tokio::test]
+async fn test_bundled_fixtures_run_against_the_module() {
+    let bundle = Bundle::unpack(&echo_bundle().pack().unwrap()).unwrap();
+    let report = bundle.run_fixtures().await.unwrap();
+    assert!(report.passed(), "{}", report);
+    
+    let broken = bundle.with_fixture(FixtureCase::new("wrong", "echo", json!(1)).expect(json!(2)));
+    let report = broken.run_fixtures().await.unwrap();
+    assert!(!report.passed());
+    assert_eq!(report.failures().count(), 1);
+}
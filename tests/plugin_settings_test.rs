@@ -0,0 +1,97 @@
+//! Tests for guest-visible plugin settings
+
+use serde_json::json;
+use wasm_sandbox::{InstanceConfig, InstanceId, PluginSettings, WasmSandbox};
+
+const SETTINGS_MODULE: &str = r#"
+(module
+  (import "sandbox_config" "get" (func $get (param i32 i32) (result i64)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "retries")
+  (data (i32.const 16) "missing")
+  (global $updates (mut i32) (i32.const 0))
+  
+  (func (export "alloc") (param i32) (result i32)
+    i32.const 1024)
+  
+  ;; Read a single-digit setting, or -1 if it is missing
+  (func $digit (param $key i32) (param $len i32) (result i32)
+    (local $packed i64)
+    (local.set $packed (call $get (local.get $key) (local.get $len)))
+    (if (i64.lt_s (local.get $packed) (i64.const 0))
+      (then (return (i32.const -1))))
+    (i32.sub
+      (i32.load8_u (i32.wrap_i64 (i64.shr_u (local.get $packed) (i64.const 32))))
+      (i32.const 48)))
+  
+  (func (export "retries") (result i32)
+    (call $digit (i32.const 0) (i32.const 7)))
+  
+  (func (export "missing") (result i32)
+    (call $digit (i32.const 16) (i32.const 7)))
+  
+  (func (export "on_config_update")
+    (global.set $updates (i32.add (global.get $updates) (i32.const 1))))
+  
+  (func (export "updates") (result i32)
+    (global.get $updates)))
+"#;
+
+fn sandbox_with_settings(settings: PluginSettings) -> (WasmSandbox, wasm_sandbox::Result<InstanceId>) {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(SETTINGS_MODULE.as_bytes()).expect("Failed to load module");
+    let config = InstanceConfig {
+        settings: Some(settings),
+        ..InstanceConfig::default()
+    };
+    let instance_id = sandbox.create_instance(module_id, Some(config));
+    (sandbox, instance_id)
+}
+
+fn call(sandbox: &WasmSandbox, instance_id: InstanceId, function: &str) -> i32 {
+    sandbox.get_instance(instance_id).unwrap().instance.call_simple_function(function, &[]).unwrap()
+}
+
+#[test]
+fn test_guest_reads_settings() {
+    let (sandbox, instance_id) = sandbox_with_settings(PluginSettings::new(json!({ "retries": 3 })));
+    let instance_id = instance_id.expect("Failed to create instance");
+    
+    assert_eq!(call(&sandbox, instance_id, "retries"), 3);
+    assert_eq!(call(&sandbox, instance_id, "missing"), -1);
+}
+
+#[test]
+fn test_update_notifies_guest() {
+    let (mut sandbox, instance_id) = sandbox_with_settings(PluginSettings::new(json!({ "retries": 3 })));
+    let instance_id = instance_id.expect("Failed to create instance");
+    
+    sandbox.update_settings(instance_id, json!({ "retries": 5 })).expect("Failed to update settings");
+    
+    assert_eq!(call(&sandbox, instance_id, "retries"), 5);
+    assert_eq!(call(&sandbox, instance_id, "updates"), 1);
+}
+
+#[test]
+fn test_settings_size_cap() {
+    let (_, instance_id) = sandbox_with_settings(PluginSettings::new(json!({ "retries": 3 })).max_bytes(4));
+    assert!(instance_id.is_err());
+    
+    let (mut sandbox, instance_id) = sandbox_with_settings(PluginSettings::new(json!({ "retries": 3 })).max_bytes(32));
+    let instance_id = instance_id.expect("Failed to create instance");
+    let oversized = json!({ "retries": 4, "padding": "x".repeat(64) });
+    assert!(sandbox.update_settings(instance_id, oversized).is_err());
+    
+    assert_eq!(call(&sandbox, instance_id, "retries"), 3);
+    assert_eq!(call(&sandbox, instance_id, "updates"), 0);
+}
+
+#[test]
+fn test_dotted_key_lookup() {
+    let settings = PluginSettings::new(json!({ "servers": [{ "host": "a.local" }], "retry": { "max": 2 } }));
+    
+    assert_eq!(settings.get("retry.max"), Some(&json!(2)));
+    assert_eq!(settings.get("servers.0.host"), Some(&json!("a.local")));
+    assert_eq!(settings.get("servers.1.host"), None);
+    assert_eq!(settings.get(""), Some(&settings.document));
+}
@@ -0,0 +1,110 @@
+//! Tests for shrinking compiled modules after compilation
+
+use wasm_sandbox::compiler::optimize::{optimize, strip_custom_sections, PostOptimize, WasmOptLevel};
+use wasm_sandbox::runtime::abi::{custom_section_names, WASM_BINDGEN_CUSTOM_SECTION};
+use wasm_sandbox::utils::provenance::{Provenance, PROVENANCE_SECTION};
+use wasm_sandbox::{Error, WasmSandbox};
+
+/// A module exporting `f`, which returns 7
+const SEVEN: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f,
+    0x03, 0x02, 0x01, 0x00,
+    0x07, 0x05, 0x01, 0x01, b'f', 0x00, 0x00,
+    0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x07, 0x0b,
+];
+
+fn custom_section(name: &str, payload: &[u8]) -> Vec<u8> {
+    let mut section = vec![0, (1 + name.len() + payload.len()) as u8, name.len() as u8];
+    section.extend_from_slice(name.as_bytes());
+    section.extend_from_slice(payload);
+    section
+}
+
+/// [`SEVEN`] with debug info, producers, names and provenance sections
+fn module_with_sections() -> Vec<u8> {
+    let mut module = SEVEN.to_vec();
+    module.extend(custom_section(".debug_info", &[0xaa; 64]));
+    module.extend(custom_section("producers", b"rustc 1.88"));
+    module.extend(custom_section("name", &[0]));
+    Provenance::new().embed(&module).unwrap()
+}
+
+#[test]
+fn test_strip_keeps_required_and_requested_sections() {
+    let mut module = module_with_sections();
+    module.extend(custom_section(WASM_BINDGEN_CUSTOM_SECTION, b"{}"));
+    let (stripped, removed) = strip_custom_sections(&module, &["name".to_string()]);
+    
+    assert_eq!(removed, [".debug_info", "producers"]);
+    assert_eq!(
+        custom_section_names(&stripped),
+        ["name", PROVENANCE_SECTION, WASM_BINDGEN_CUSTOM_SECTION],
+    );
+    assert!(Provenance::read(&stripped).unwrap().is_some());
+    
+    let (bare, _) = strip_custom_sections(SEVEN, &[]);
+    assert_eq!(bare, SEVEN);
+}
+
+#[tokio::test]
+async fn test_optimized_module_reports_savings_and_still_runs() {
+    let module = module_with_sections();
+    let (optimized, report) = optimize(&module, &PostOptimize::new().without_wasm_opt()).unwrap();
+    
+    assert!(!report.wasm_opt);
+    assert_eq!(report.original_bytes, module.len());
+    assert_eq!(report.optimized_bytes, optimized.len());
+    assert_eq!(report.stripped_sections, [".debug_info", "producers", "name"]);
+    assert!(report.saved_bytes() > 64 && report.saved_ratio() > 0.2, "{}", report);
+    assert!(report.to_string().contains("% smaller"), "{}", report);
+    
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(&optimized).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    let seven: i32 = sandbox.call_function(instance_id, "f", ()).await.unwrap();
+    assert_eq!(seven, 7);
+    
+    // Already compiled modules pass straight through to the optimizer
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("plugin.wasm");
+    std::fs::write(&path, &module).unwrap();
+    let (from_source, _) = wasm_sandbox::compile_source_to_wasm_optimized(
+        path.to_str().unwrap(),
+        &PostOptimize::new().without_wasm_opt(),
+    ).await.unwrap();
+    assert_eq!(from_source, optimized);
+}
+
+#[test]
+fn test_missing_wasm_opt_is_skipped_unless_required() {
+    let options = PostOptimize::new().wasm_opt("/nonexistent/wasm-opt");
+    let (_, report) = optimize(SEVEN, &options).unwrap();
+    assert!(!report.wasm_opt);
+    
+    let err = optimize(SEVEN, &options.require_wasm_opt(true)).unwrap_err();
+    assert!(matches!(err, Error::Compilation { .. }), "{}", err);
+    
+    let err = optimize(b"(module)", &PostOptimize::new()).unwrap_err();
+    assert!(matches!(err, Error::InvalidInput { .. }), "{}", err);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_wasm_opt_runs_at_the_configured_level() {
+    use std::os::unix::fs::PermissionsExt;
+    
+    // Stands in for wasm-opt: accepts only -Os and appends a custom section
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("wasm-opt");
+    std::fs::write(&script, "#!/bin/sh\n[ \"$1\" = -Os ] || exit 3\ncp \"$2\" \"$4\"\nprintf '\\000\\002\\001x' >> \"$4\"\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    
+    let options = PostOptimize::new().wasm_opt(&script).level(WasmOptLevel::Os).keep_section("x");
+    let (optimized, report) = optimize(SEVEN, &options).unwrap();
+    assert!(report.wasm_opt);
+    assert_eq!(custom_section_names(&optimized), ["x"]);
+    
+    let err = optimize(SEVEN, &options.level(WasmOptLevel::Oz)).unwrap_err();
+    assert!(err.to_string().contains("wasm-opt failed"), "{}", err);
+}
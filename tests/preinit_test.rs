@@ -0,0 +1,144 @@
+//! Tests for Wizer-style module preinitialization
+
+use wasm_sandbox::compiler::preinit::{preinitialize, Preinit, DEFAULT_INIT_FUNCTION};
+use wasm_sandbox::compiler::CompilerOptions;
+use wasm_sandbox::{Error, WasmSandbox};
+
+/// Squares table built by initialization, with a count of how often it ran
+const TABLE_MODULE: &str = r#"
+(module
+  (memory 1)
+  (global $runs (mut i32) (i32.const 0))
+  (global $scale (mut i64) (i64.const 1))
+  (global $ratio (mut f64) (f64.const 0))
+  (global $base i32 (i32.const 1024))
+  (func (export "wizer.initialize")
+    (local $i i32)
+    (global.set $runs (i32.add (global.get $runs) (i32.const 1)))
+    (global.set $scale (i64.const -7))
+    (global.set $ratio (f64.const 0.5))
+    (block $done
+      (loop $fill
+        (br_if $done (i32.ge_u (local.get $i) (i32.const 100)))
+        (i32.store
+          (i32.add (global.get $base) (i32.mul (local.get $i) (i32.const 4)))
+          (i32.mul (local.get $i) (local.get $i)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $fill)))
+    ;; A second run of state far away, so the snapshot needs two segments
+    (i32.store (i32.const 60000) (i32.const 42)))
+  (func (export "square") (param i32) (result i32)
+    (i32.load (i32.add (global.get $base) (i32.mul (local.get 0) (i32.const 4)))))
+  (func (export "runs") (param i32) (result i32) (global.get $runs))
+  (func (export "scale") (param i32) (result i64) (global.get $scale))
+  (func (export "far") (param i32) (result i32) (i32.load (i32.const 60000))))
+"#;
+
+async fn call<R>(wasm_bytes: &[u8], function: &str) -> R
+where
+    R: serde::de::DeserializeOwned + 'static,
+{
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(wasm_bytes).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    sandbox.call_function(instance_id, function, 0).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_preinitialized_module_starts_from_initialized_state() {
+    let (wasm, report) = preinitialize(TABLE_MODULE.as_bytes(), &Preinit::new()).unwrap();
+    
+    // Initialization ran once, at preinitialization
+    assert_eq!(call::<i32>(&wasm, "runs").await, 1);
+    assert_eq!(call::<i32>(&wasm, "square").await, 0);
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(&wasm).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    let square: i32 = sandbox.call_function(instance_id, "square", 99).await.unwrap();
+    assert_eq!(square, 99 * 99);
+    assert_eq!(call::<i64>(&wasm, "scale").await, -7);
+    assert_eq!(call::<i32>(&wasm, "far").await, 42);
+    
+    assert_eq!(report.memory_pages, 1);
+    assert_eq!(report.data_segments, 2);
+    assert_eq!(report.globals, 3);
+    assert!(!report.removed_start);
+    
+    // The initialization export is gone
+    let module_id = sandbox.load_module(&wasm).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    assert!(sandbox.call_function::<_, ()>(instance_id, DEFAULT_INIT_FUNCTION, ()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_start_function_is_folded_into_the_snapshot() {
+    let wat = r#"
+(module
+  (memory 1 4)
+  (global $ready (mut i32) (i32.const 0))
+  (data $greeting "hello")
+  (func $start
+    (memory.grow (i32.const 1))
+    drop
+    (i32.store (i32.const 70000) (i32.const 9)))
+  (start $start)
+  (func (export "setup")
+    (memory.init $greeting (i32.const 16) (i32.const 0) (i32.const 5))
+    (global.set $ready (i32.const 1)))
+  (func (export "pages") (param i32) (result i32) (memory.size))
+  (func (export "late") (param i32) (result i32) (i32.load (i32.const 70000)))
+  (func (export "first") (param i32) (result i32) (i32.load8_u (i32.const 16)))
+  (func (export "copy_again") (param i32) (result i32)
+    (memory.init $greeting (i32.const 32) (i32.const 0) (i32.const 5))
+    (i32.load8_u (i32.const 36))))
+"#;
+    let options = Preinit::new().init_function("setup").keep_init_export();
+    let (wasm, report) = preinitialize(wat.as_bytes(), &options).unwrap();
+    assert!(report.removed_start);
+    assert_eq!(report.memory_pages, 2);
+    
+    // Growth by the start function is kept, and it isn't run again
+    assert_eq!(call::<i32>(&wasm, "pages").await, 2);
+    assert_eq!(call::<i32>(&wasm, "late").await, 9);
+    assert_eq!(call::<i32>(&wasm, "first").await, i32::from(b'h'));
+    
+    // Passive segments still work
+    assert_eq!(call::<i32>(&wasm, "copy_again").await, i32::from(b'o'));
+    
+    // The kept initialization export can run again
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(&wasm).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    sandbox.call_function::<_, ()>(instance_id, "setup", ()).await.unwrap();
+}
+
+#[test]
+fn test_unsupported_modules_are_rejected() {
+    let no_init = r#"(module (memory 1) (func (export "run")))"#;
+    let result = preinitialize(no_init.as_bytes(), &Preinit::new());
+    assert!(matches!(result, Err(Error::NotFound { identifier, .. }) if identifier == DEFAULT_INIT_FUNCTION));
+    
+    let imported_memory = r#"(module (import "env" "memory" (memory 1)) (func (export "wizer.initialize")))"#;
+    let result = preinitialize(imported_memory.as_bytes(), &Preinit::new());
+    assert!(matches!(result, Err(Error::InvalidInput { .. })));
+    
+    let trapping = r#"(module (memory 1) (func (export "wizer.initialize") unreachable))"#;
+    let result = preinitialize(trapping.as_bytes(), &Preinit::new());
+    assert!(matches!(result, Err(Error::FunctionCall { .. })));
+    
+    assert!(preinitialize(b"not a module", &Preinit::new()).is_err());
+}
+
+#[test]
+fn test_compiler_options_preinitialize_on_request() {
+    assert!(CompilerOptions::default().preinitialize.is_none());
+    let options = CompilerOptions::default().with_preinitialize(Preinit::new().init_function("init"));
+    assert_eq!(options.preinitialize.unwrap().init_function, "init");
+    
+    // Modules without memory are preinitialized too
+    let wat = r#"(module (global (export "g") (mut i32) (i32.const 0)) (func (export "wizer.initialize") (global.set 0 (i32.const 5))))"#;
+    let (wasm, report) = preinitialize(wat.as_bytes(), &Preinit::new()).unwrap();
+    assert_eq!(report, wasm_sandbox::compiler::preinit::PreinitReport { globals: 1, ..Default::default() });
+    assert_eq!(report.to_string(), "0 pages snapshotted into 0 data segments of 0 bytes, 1 globals");
+    WasmSandbox::new().unwrap().load_module(&wasm).unwrap();
+}
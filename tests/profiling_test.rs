@@ -0,0 +1,105 @@
+//! Tests for sampling guest calls and native profiler support
+
+mod common;
+
+use std::time::Duration;
+
+use wasm_sandbox::runtime::RuntimeConfig;
+use wasm_sandbox::{FoldedStacks, InstanceConfig, InstanceId, ProfilingStrategy, SamplingConfig, SandboxConfig, WasmSandbox};
+
+/// `run` spends ten times as long in `hot` as in `cold`
+const BURN_MODULE: &str = r#"
+(module
+  (func $burn (param $n i32)
+    (loop $again
+      (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+      (br_if $again (local.get $n))))
+  (func $hot (call $burn (i32.const 20000000)))
+  (func $cold (call $burn (i32.const 2000000)))
+  (func $run (export "run") (result i32)
+    (call $hot)
+    (call $cold)
+    (i32.const 1))
+  (func $quick (export "quick") (result i32) (i32.const 2)))
+"#;
+
+fn profiled_instance(sandbox: &mut WasmSandbox, sampling: Option<SamplingConfig>) -> InstanceId {
+    let mut instance_config = InstanceConfig::default();
+    instance_config.resource_limits.fuel = Some(1_000_000_000);
+    instance_config.profiling = sampling;
+    common::create_instance(sandbox, BURN_MODULE, Some(instance_config))
+}
+
+/// Sum the counts of the folded lines whose stack ends in `function`
+fn count_ending_in(folded: &str, function: &str) -> u64 {
+    folded.lines()
+        .filter_map(|line| line.rsplit_once(' '))
+        .filter(|(stack, _)| stack.ends_with(&format!(";{}", function)))
+        .map(|(_, count)| count.parse::<u64>().unwrap())
+        .sum()
+}
+
+#[test]
+fn test_folded_stack_format() {
+    let mut stacks = FoldedStacks::default();
+    assert!(stacks.is_empty());
+    stacks.add("run;hot;burn", 300);
+    stacks.add("run;hot;burn", 200);
+    stacks.add("run;cold;burn", 0);
+    
+    assert_eq!(stacks.total_samples(), 3);
+    assert_eq!(stacks.total_fuel(), 500);
+    assert_eq!(stacks.folded(), "run;cold;burn 1\nrun;hot;burn 2\n");
+    assert_eq!(stacks.folded_fuel(), "run;hot;burn 500\n");
+    assert_eq!(SamplingConfig::new().interval(Duration::from_millis(5)).interval, Duration::from_millis(5));
+}
+
+#[tokio::test]
+async fn test_profile_charges_fuel_to_the_hot_function() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let instance_id = profiled_instance(&mut sandbox, Some(SamplingConfig::new()));
+    let _: i32 = sandbox.call_function(instance_id, "run", ()).await.unwrap();
+    
+    let profile = sandbox.last_profile(instance_id).unwrap();
+    assert_eq!(profile.instance_id, instance_id);
+    assert_eq!(profile.function_name, "run");
+    assert!(profile.call_id.is_some());
+    assert!(profile.stacks.total_samples() > 0);
+    
+    let folded = profile.stacks.folded_fuel();
+    assert!(folded.lines().all(|line| line.starts_with("run;")), "{}", folded);
+    let hot = count_ending_in(&folded, "hot;burn");
+    let cold = count_ending_in(&folded, "cold;burn");
+    assert!(hot > cold, "{}", folded);
+}
+
+#[tokio::test]
+async fn test_each_call_replaces_the_profile() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let unprofiled = profiled_instance(&mut sandbox, None);
+    let _: i32 = sandbox.call_function(unprofiled, "quick", ()).await.unwrap();
+    assert!(sandbox.last_profile(unprofiled).is_none());
+    
+    let instance_id = profiled_instance(&mut sandbox, Some(SamplingConfig::new().interval(Duration::from_millis(2))));
+    let _: i32 = sandbox.call_function(instance_id, "run", ()).await.unwrap();
+    let _: i32 = sandbox.call_function(instance_id, "quick", ()).await.unwrap();
+    let profile = sandbox.last_profile(instance_id).unwrap();
+    assert_eq!(profile.function_name, "quick");
+    assert!(profile.duration < Duration::from_secs(1));
+    
+    sandbox.remove_instance(instance_id);
+    assert!(sandbox.last_profile(instance_id).is_none());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_perf_map_names_guest_functions() {
+    let sandbox = WasmSandbox::with_config(SandboxConfig {
+        runtime: RuntimeConfig::default().profiling(ProfilingStrategy::PerfMap),
+        ..SandboxConfig::default()
+    }).unwrap();
+    sandbox.load_module(BURN_MODULE.as_bytes()).unwrap();
+    
+    let map = std::fs::read_to_string(format!("/tmp/perf-{}.map", std::process::id())).unwrap();
+    assert!(map.contains("hot"), "{}", map);
+}
@@ -0,0 +1,111 @@
+//! Tests for guest progress reports and cancellation through them
+
+mod common;
+
+use wasm_sandbox::{CallProgress, GuestErrorCode, ProgressUpdate};
+
+// `steps` reports each of `n` steps and returns `n`; `until_cancelled` reports
+// until told to abort and returns how many reports it made; `report` returns
+// the raw result of one report of `percent` with a message of `len` bytes
+const PROGRESS_MODULE: &str = r#"
+(module
+  (import "sandbox_progress" "report" (func $report (param i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "indexing")
+  (func (export "steps") (param $n i32) (result i32)
+    (local $i i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br_if $done (call $report
+          (i32.div_s (i32.mul (local.get $i) (i32.const 100)) (local.get $n))
+          (i32.const 0) (i32.const 8)))
+        (br $next)))
+    (local.get $i))
+  (func (export "until_cancelled") (param $x i32) (result i32)
+    (local $i i32)
+    (block $done
+      (loop $next
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br_if $done (call $report (i32.rem_u (local.get $i) (i32.const 100)) (i32.const 0) (i32.const 8)))
+        (br $next)))
+    (local.get $i))
+  (func (export "report") (param $percent i32) (param $len i32) (result i32)
+    (call $report (local.get $percent) (i32.const 0) (local.get $len))))
+"#;
+
+#[tokio::test]
+async fn test_progress_handle_follows_guest_reports() {
+    let (sandbox, instance_id) = common::instantiate(PROGRESS_MODULE, None);
+    let progress = CallProgress::new();
+    let mut updates = progress.subscribe();
+    assert_eq!(progress.current(), ProgressUpdate::default());
+    
+    let steps: i32 = sandbox.call_function_with_progress(instance_id, "steps", 4, &progress).await.unwrap();
+    assert_eq!(steps, 4);
+    assert!(updates.has_changed().unwrap());
+    assert_eq!(*updates.borrow_and_update(), ProgressUpdate { percent: 100, message: "indexing".to_string() });
+    assert!(!progress.is_cancelled());
+    
+    // Calls without a handle report into the void and carry on
+    let steps: i32 = sandbox.call_function(instance_id, "steps", 3).await.unwrap();
+    assert_eq!(steps, 3);
+    assert!(!updates.has_changed().unwrap());
+}
+
+#[tokio::test]
+async fn test_cancelled_call_aborts_at_next_report() {
+    let (sandbox, instance_id) = common::instantiate(PROGRESS_MODULE, None);
+    let progress = CallProgress::new();
+    progress.cancel();
+    
+    let err = sandbox.call_function_with_progress::<_, i32>(instance_id, "steps", 10, &progress).await.unwrap_err();
+    assert!(err.to_string().contains("cancelled"), "{}", err);
+    // The guest stopped at its first report
+    assert_eq!(progress.current().percent, 10);
+    
+    // Cancellation belongs to the handle, not the instance
+    let steps: i32 = sandbox.call_function_with_progress(instance_id, "steps", 2, &CallProgress::new()).await.unwrap();
+    assert_eq!(steps, 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_progress_watcher_cancels_running_call() {
+    let (sandbox, instance_id) = common::instantiate(PROGRESS_MODULE, None);
+    let progress = CallProgress::new();
+    let mut updates = progress.subscribe();
+    let watcher = progress.clone();
+    tokio::spawn(async move {
+        while updates.changed().await.is_ok() {
+            if updates.borrow_and_update().percent >= 50 {
+                watcher.cancel();
+                break;
+            }
+        }
+    });
+    
+    let result = sandbox.call_function_with_progress::<_, i32>(instance_id, "until_cancelled", 0, &progress).await;
+    assert!(result.is_err());
+    assert!(progress.is_cancelled());
+    assert!(progress.current().percent >= 50);
+}
+
+#[tokio::test]
+async fn test_invalid_reports_are_rejected() {
+    let (sandbox, instance_id) = common::instantiate(PROGRESS_MODULE, None);
+    let progress = CallProgress::new();
+    let invalid = GuestErrorCode::InvalidInput.code() as i32;
+    
+    let code: i32 = sandbox.call_function_with_progress(instance_id, "report", (101, 8), &progress).await.unwrap();
+    assert_eq!(code, invalid);
+    let code: i32 = sandbox.call_function_with_progress(instance_id, "report", (-1, 8), &progress).await.unwrap();
+    assert_eq!(code, invalid);
+    let code: i32 = sandbox.call_function_with_progress(instance_id, "report", (50, 4096), &progress).await.unwrap();
+    assert_eq!(code, invalid);
+    assert_eq!(progress.current(), ProgressUpdate::default());
+    
+    let code: i32 = sandbox.call_function_with_progress(instance_id, "report", (50, 5), &progress).await.unwrap();
+    assert_eq!(code, 0);
+    assert_eq!(progress.current(), ProgressUpdate { percent: 50, message: "index".to_string() });
+}
@@ -0,0 +1,94 @@
+//! Tests for module provenance metadata
+
+use wasm_sandbox::{
+    Error, Provenance, ProvenancePolicy, SandboxConfig, SbomComponent, WasmSandbox, PROVENANCE_SECTION,
+};
+
+const TEST_MODULE: &[u8] = include_bytes!("../fixtures/test_module.wasm");
+
+const CARGO_LOCK: &str = r#"
+version = 3
+
+[[package]]
+name = "plugin"
+version = "0.1.0"
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "ddc6f9cc94d67c0e21aaf7eda3a010fd3af78ebf6e096aa6e2e13c79749cce4f"
+"#;
+
+fn provenance() -> Provenance {
+    Provenance::new()
+        .source_repo("https://github.com/acme/plugin.git")
+        .commit("0123456789abcdef")
+        .build_tool("rustc", "rustc 1.85.0")
+        .dependency(SbomComponent {
+            name: "serde".to_string(),
+            version: "1.0.200".to_string(),
+            source: None,
+            checksum: None,
+        })
+}
+
+fn sandbox(policy: ProvenancePolicy) -> WasmSandbox {
+    WasmSandbox::with_config(SandboxConfig {
+        provenance_policy: Some(policy),
+        ..SandboxConfig::default()
+    })
+    .expect("Failed to create sandbox")
+}
+
+#[test]
+fn test_embedded_provenance_is_reported_by_loaded_modules() {
+    let embedded = provenance().embed(TEST_MODULE).unwrap();
+    assert_eq!(Provenance::read(&embedded).unwrap(), Some(provenance()));
+    assert_eq!(Provenance::read(TEST_MODULE).unwrap(), None);
+    
+    let sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(&embedded).unwrap();
+    let metadata = sandbox.module_metadata(module_id).unwrap();
+    assert_eq!(metadata.provenance, Some(provenance()));
+    assert!(metadata.custom_sections.iter().any(|name| name == PROVENANCE_SECTION));
+}
+
+#[test]
+fn test_embedding_again_replaces_the_provenance() {
+    let first = provenance().embed(TEST_MODULE).unwrap();
+    let second = Provenance::new().commit("fedcba").embed(&first).unwrap();
+    
+    assert_eq!(Provenance::read(&second).unwrap(), Some(Provenance::new().commit("fedcba")));
+    let sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let metadata = sandbox.module_metadata(sandbox.load_module(&second).unwrap()).unwrap();
+    assert_eq!(metadata.custom_sections.iter().filter(|name| *name == PROVENANCE_SECTION).count(), 1);
+    
+    assert!(provenance().embed(b"(module)").is_err());
+}
+
+#[test]
+fn test_policy_only_loads_modules_from_approved_repos() {
+    let policy = ProvenancePolicy::new().approve_repo("https://github.com/acme/plugin/").require_commit();
+    let sandbox = sandbox(policy);
+    
+    assert!(sandbox.load_module(&provenance().embed(TEST_MODULE).unwrap()).is_ok());
+    
+    let unapproved = provenance().source_repo("https://github.com/mallory/plugin").embed(TEST_MODULE).unwrap();
+    assert!(matches!(sandbox.load_module(&unapproved), Err(Error::SecurityViolation { .. })));
+    let uncommitted = Provenance::new().source_repo("https://github.com/acme/plugin").embed(TEST_MODULE).unwrap();
+    assert!(matches!(sandbox.load_module(&uncommitted), Err(Error::SecurityViolation { .. })));
+    assert!(matches!(sandbox.load_module(TEST_MODULE), Err(Error::SecurityViolation { .. })));
+}
+
+#[test]
+fn test_sbom_is_read_from_cargo_lock() {
+    let components = SbomComponent::from_cargo_lock(CARGO_LOCK).unwrap();
+    assert_eq!(components.len(), 2);
+    assert_eq!((components[0].name.as_str(), components[0].source.as_deref()), ("plugin", None));
+    assert_eq!(components[1].version, "1.0.200");
+    assert!(components[1].source.as_deref().unwrap().starts_with("registry+"));
+    assert!(components[1].checksum.is_some());
+    
+    assert!(SbomComponent::from_cargo_lock("[[package]]\nname = 1").is_err());
+}
@@ -0,0 +1,56 @@
+//! Tests for redacting guest error messages
+
+use wasm_sandbox::{RedactionPolicy, SandboxConfig, SandboxError, WasmSandbox};
+
+const TRAPPING_MODULE: &str = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    unreachable))
+"#;
+
+#[test]
+fn test_standard_policy_redacts_paths_and_hex() {
+    let policy = RedactionPolicy::standard();
+    
+    let redacted = policy.redact_str("failed to open /home/builder/secrets/key.pem at 0xdeadbeefcafebabe1234");
+    
+    assert!(!redacted.contains("/home/builder"));
+    assert!(!redacted.contains("deadbeef"));
+    assert!(redacted.starts_with("failed to open [redacted]"));
+}
+
+#[test]
+fn test_allowlist_keeps_only_matching_lines() {
+    let policy = RedactionPolicy::new()
+        .allow(r"^wasm trap:").unwrap()
+        .replacement("<hidden>");
+    
+    let redacted = policy.redact_str("wasm trap: unreachable\ninternal detail: token=abc");
+    
+    assert_eq!(redacted, "wasm trap: unreachable\n<hidden>");
+}
+
+#[test]
+fn test_invalid_pattern_is_rejected() {
+    assert!(RedactionPolicy::new().pattern("(unclosed").is_err());
+}
+
+#[tokio::test]
+async fn test_call_errors_are_redacted_with_raw_error_kept() {
+    let config = SandboxConfig {
+        redaction: RedactionPolicy::new().pattern("unreachable").unwrap(),
+        ..SandboxConfig::default()
+    };
+    let mut sandbox = WasmSandbox::with_config(config).unwrap();
+    let module_id = sandbox.load_module(TRAPPING_MODULE.as_bytes()).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    
+    let error = sandbox.call_function::<_, i32>(instance_id, "add", (1, 2)).await.unwrap_err();
+    assert!(!error.to_string().contains("unreachable"));
+    assert!(error.to_string().contains("[redacted]"));
+    
+    match sandbox.last_raw_error(instance_id) {
+        Some(SandboxError::FunctionCall { reason, .. }) => assert!(reason.contains("unreachable")),
+        other => panic!("expected raw function call error, got {:?}", other),
+    }
+}
@@ -0,0 +1,145 @@
+//! Tests for guests using externref and GC types
+
+mod common;
+
+use wasm_sandbox::runtime::{HostValue, RuntimeConfig};
+use wasm_sandbox::{Error, HostNamespace, InstanceId, SandboxConfig, WasmSandbox};
+
+// Files are handed to the guest as externrefs, which it keeps in a table
+const FILES_MODULE: &str = r#"
+(module
+  (import "acme.files" "open" (func $open (param i32) (result externref)))
+  (import "acme.files" "size" (func $size (param externref) (result i64)))
+  (table $files 4 externref)
+  (func (export "open") (param $slot i32) (param $size i32)
+    (table.set $files (local.get $slot) (call $open (local.get $size))))
+  (func (export "size") (param $slot i32) (result i64)
+    (call $size (table.get $files (local.get $slot))))
+  (func (export "take") (param $slot i32) (result externref)
+    (table.get $files (local.get $slot)))
+  (func (export "echo") (param externref) (result externref)
+    (local.get 0))
+  (func (export "is_null") (param externref) (result i32)
+    (ref.is_null (local.get 0))))
+"#;
+
+const GC_MODULE: &str = r#"
+(module
+  (type $point (struct (field $x i32) (field $y i32)))
+  (func (export "sum") (param $x i32) (param $y i32) (result i32)
+    (local $p (ref $point))
+    (local.set $p (struct.new $point (local.get $x) (local.get $y)))
+    (i32.add (struct.get $point $x (local.get $p)) (struct.get $point $y (local.get $p)))))
+"#;
+
+struct File {
+    size: i64,
+}
+
+fn files_sandbox() -> (WasmSandbox, InstanceId) {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let files = HostNamespace::new("acme.files")
+        .ungated()
+        .function_with_context("open", |context, args| {
+            let [HostValue::I32(size)] = args else {
+                panic!("unexpected arguments {:?}", args);
+            };
+            Ok(vec![HostValue::ExternRef(Some(context.handles.insert(File { size: *size as i64 })?))])
+        })
+        .function_with_context("size", |context, args| match args {
+            [HostValue::ExternRef(Some(handle))] => Ok(vec![HostValue::I64(context.handles.get::<File>(*handle)?.size)]),
+            [HostValue::ExternRef(None)] => Ok(vec![HostValue::I64(-1)]),
+            other => panic!("unexpected arguments {:?}", other),
+        });
+    sandbox.register_host_namespaces([files]).unwrap();
+    let instance_id = common::create_instance(&mut sandbox, FILES_MODULE, None);
+    (sandbox, instance_id)
+}
+
+fn call(sandbox: &WasmSandbox, instance_id: InstanceId, function_name: &str, args: &[HostValue]) -> wasm_sandbox::Result<Vec<HostValue>> {
+    sandbox.get_instance(instance_id).unwrap().instance.call_values(function_name, args)
+}
+
+#[test]
+fn test_host_handles_pass_through_guest_externrefs() {
+    let (sandbox, instance_id) = files_sandbox();
+    
+    call(&sandbox, instance_id, "open", &[HostValue::I32(0), HostValue::I32(512)]).unwrap();
+    call(&sandbox, instance_id, "open", &[HostValue::I32(1), HostValue::I32(2048)]).unwrap();
+    assert_eq!(call(&sandbox, instance_id, "size", &[HostValue::I32(0)]).unwrap(), [HostValue::I64(512)]);
+    assert_eq!(call(&sandbox, instance_id, "size", &[HostValue::I32(1)]).unwrap(), [HostValue::I64(2048)]);
+    assert_eq!(call(&sandbox, instance_id, "size", &[HostValue::I32(2)]).unwrap(), [HostValue::I64(-1)]);
+    
+    // The externref the guest holds is the handle in the instance's table
+    let [HostValue::ExternRef(Some(handle))] = call(&sandbox, instance_id, "take", &[HostValue::I32(1)]).unwrap()[..] else {
+        panic!("expected a handle");
+    };
+    let handles = &sandbox.get_instance(instance_id).unwrap().handles;
+    assert_eq!(handles.get::<File>(handle).unwrap().size, 2048);
+    assert!(handles.close(handle));
+    assert!(call(&sandbox, instance_id, "size", &[HostValue::I32(1)]).is_err());
+}
+
+#[tokio::test]
+async fn test_externref_arguments_and_results() {
+    let (sandbox, instance_id) = files_sandbox();
+    
+    assert_eq!(call(&sandbox, instance_id, "echo", &[HostValue::ExternRef(Some(7))]).unwrap(), [HostValue::ExternRef(Some(7))]);
+    assert_eq!(call(&sandbox, instance_id, "echo", &[HostValue::ExternRef(None)]).unwrap(), [HostValue::ExternRef(None)]);
+    assert_eq!(call(&sandbox, instance_id, "is_null", &[HostValue::ExternRef(None)]).unwrap(), [HostValue::I32(1)]);
+    
+    // Through JSON, externrefs are handle numbers or null
+    let echoed: Option<u32> = sandbox.call_function(instance_id, "echo", 9).await.unwrap();
+    assert_eq!(echoed, Some(9));
+    let echoed: Option<u32> = sandbox.call_function(instance_id, "echo", [()]).await.unwrap();
+    assert_eq!(echoed, None);
+}
+
+#[tokio::test]
+async fn test_gc_types_need_gc_enabled() {
+    let sandbox = WasmSandbox::new().unwrap();
+    match sandbox.load_module(GC_MODULE.as_bytes()) {
+        Err(Error::Configuration { suggestion, .. }) => assert!(suggestion.unwrap().contains("enable_gc")),
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+    
+    let mut sandbox = WasmSandbox::with_config(SandboxConfig {
+        runtime: RuntimeConfig {
+            enable_gc: true,
+            ..RuntimeConfig::default()
+        },
+        ..SandboxConfig::default()
+    }).unwrap();
+    let module_id = sandbox.load_module(GC_MODULE.as_bytes()).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    let sum: i32 = sandbox.call_function(instance_id, "sum", (40, 2)).await.unwrap();
+    assert_eq!(sum, 42);
+}
+
+#[tokio::test]
+async fn test_externref_globals_survive_snapshot_and_restore() {
+    let module = r#"
+(module
+  (global $current (export "current") (mut externref) (ref.null extern))
+  (func (export "set") (param externref)
+    (global.set $current (local.get 0)))
+  (func (export "get") (result externref)
+    (global.get $current)))
+"#;
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(module.as_bytes()).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    call(&sandbox, instance_id, "set", &[HostValue::ExternRef(Some(5))]).unwrap();
+    
+    let instance = &sandbox.get_instance(instance_id).unwrap().instance;
+    let globals = instance.mutable_globals().unwrap();
+    assert_eq!(globals, [("current".to_string(), HostValue::ExternRef(Some(5)))]);
+    
+    // Restoring on top of a changed value puts the snapshot back
+    call(&sandbox, instance_id, "set", &[HostValue::ExternRef(None)]).unwrap();
+    instance.restore_globals(&globals).unwrap();
+    assert_eq!(call(&sandbox, instance_id, "get", &[]).unwrap(), [HostValue::ExternRef(Some(5))]);
+    
+    let fork_id = sandbox.fork_instance(instance_id).unwrap();
+    assert_eq!(call(&sandbox, fork_id, "get", &[]).unwrap(), [HostValue::ExternRef(Some(5))]);
+}
@@ -0,0 +1,113 @@
+//! Tests for session recording and replay
+
+use std::sync::Arc;
+
+use wasm_sandbox::replay::{Divergence, Recorder, Replayer, SessionEvent, SessionRecording};
+use wasm_sandbox::runtime::{HostFunctions, HostValue};
+use wasm_sandbox::Result;
+
+/// Module reading randomness, the clock, and a host function
+fn plugin_module(process_body: &str) -> String {
+    format!(r#"
+(module
+  (import "wasi_snapshot_preview1" "random_get" (func $random_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "clock_time_get" (func $clock (param i32 i64 i32) (result i32)))
+  (import "host" "scale" (func $scale (param i32) (result i32)))
+  (memory (export "memory") 1)
+  
+  (func (export "roll") (result i32)
+    (drop (call $random_get (i32.const 0) (i32.const 4)))
+    (i32.load (i32.const 0)))
+  
+  (func (export "now") (result i64)
+    (drop (call $clock (i32.const 0) (i64.const 1) (i32.const 8)))
+    (i64.load (i32.const 8)))
+  
+  (func (export "process") (param i32) (result i32)
+    {}))
+"#, process_body)
+}
+
+/// Host function doubling its argument
+struct Doubler;
+
+impl HostFunctions for Doubler {
+    fn provides(&self, module: &str, name: &str) -> bool {
+        module == "host" && name == "scale"
+    }
+    
+    fn call(&self, _module: &str, _name: &str, args: &[HostValue]) -> Result<Vec<HostValue>> {
+        match args {
+            [HostValue::I32(value)] => Ok(vec![HostValue::I32(value * 2)]),
+            _ => Ok(vec![HostValue::I32(0)]),
+        }
+    }
+}
+
+fn record(process_body: &str) -> SessionRecording {
+    let mut session = Recorder::new()
+        .host_functions(Arc::new(Doubler))
+        .start(plugin_module(process_body).as_bytes())
+        .expect("Failed to start recording");
+    
+    session.call("roll", &[]).unwrap();
+    session.call("now", &[]).unwrap();
+    assert_eq!(session.call("process", &[HostValue::I32(21)]).unwrap(), vec![HostValue::I32(42)]);
+    session.finish()
+}
+
+const ORIGINAL: &str = "(call $scale (local.get 0))";
+
+#[test]
+fn test_recording_captures_host_calls_and_entropy() {
+    let recording = record(ORIGINAL);
+    
+    assert_eq!(recording.calls().count(), 3);
+    let host_calls: Vec<_> = recording.host_calls().collect();
+    assert_eq!(host_calls.len(), 1);
+    assert_eq!(host_calls[0].args, vec![HostValue::I32(21)]);
+    assert!(recording.events.iter().any(|e| matches!(&e.event, SessionEvent::Random { bytes } if bytes.len() == 4)));
+    assert!(recording.events.iter().any(|e| matches!(e.event, SessionEvent::Clock { clock_id: 0, .. })));
+}
+
+#[test]
+fn test_replay_of_same_module_is_identical() {
+    let recording = record(ORIGINAL);
+    
+    let file = tempfile::NamedTempFile::new().unwrap();
+    recording.save(file.path()).expect("Failed to save recording");
+    let loaded = SessionRecording::load(file.path()).expect("Failed to load recording");
+    assert_eq!(loaded, recording);
+    
+    let report = Replayer::new(loaded).replay(plugin_module(ORIGINAL).as_bytes()).unwrap();
+    assert_eq!(report.calls, 3);
+    assert!(report.is_identical(), "{:?}", report.divergences);
+}
+
+#[test]
+fn test_replay_detects_changed_results() {
+    let recording = record(ORIGINAL);
+    
+    let upgraded = plugin_module("(i32.add (call $scale (local.get 0)) (i32.const 1))");
+    let report = Replayer::new(recording).replay(upgraded.as_bytes()).unwrap();
+    
+    assert_eq!(report.divergences, vec![Divergence::Outcome {
+        call: 2,
+        function: "process".to_string(),
+        expected: Ok(vec![HostValue::I32(42)]),
+        actual: Ok(vec![HostValue::I32(43)]),
+    }]);
+}
+
+#[test]
+fn test_replay_detects_changed_host_calls() {
+    let recording = record(ORIGINAL);
+    
+    let upgraded = plugin_module("(call $scale (i32.add (local.get 0) (i32.const 1)))");
+    let report = Replayer::new(recording).replay(upgraded.as_bytes()).unwrap();
+    
+    assert!(matches!(
+        &report.divergences[..],
+        [Divergence::HostCall { actual, .. }] if actual.2 == vec![HostValue::I32(22)]
+    ));
+}
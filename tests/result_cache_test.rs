@@ -0,0 +1,119 @@
+//! Tests for memoized results of pure functions
+
+mod common;
+
+use std::time::Duration;
+
+use wasm_sandbox::{InstanceConfig, InstanceId, ResultCacheConfig, SandboxConfig, SandboxManifest, WasmSandbox};
+
+// `score` and `impure` bump the exported `calls` counter on every execution
+const SCORING_MODULE: &str = r#"
+(module
+  (global $calls (export "calls") (mut i32) (i32.const 0))
+  (func (export "score") (param i32) (result i32)
+    (global.set $calls (i32.add (global.get $calls) (i32.const 1)))
+    (i32.mul (local.get 0) (i32.const 10)))
+  (func (export "impure") (param i32) (result i32)
+    (global.set $calls (i32.add (global.get $calls) (i32.const 1)))
+    (global.get $calls)))
+"#;
+
+fn sandbox(cache: ResultCacheConfig) -> WasmSandbox {
+    WasmSandbox::with_config(SandboxConfig {
+        result_cache: cache,
+        ..SandboxConfig::default()
+    }).expect("Failed to create sandbox")
+}
+
+fn instantiate(sandbox: &mut WasmSandbox) -> InstanceId {
+    let config = InstanceConfig::builder().pure_function("score").build().unwrap();
+    common::create_instance(sandbox, SCORING_MODULE, Some(config))
+}
+
+fn executions(sandbox: &WasmSandbox, instance_id: InstanceId) -> i32 {
+    sandbox.get_instance(instance_id).unwrap().instance.exported_i32("calls").unwrap()
+}
+
+#[tokio::test]
+async fn test_hits_skip_execution() {
+    let mut sandbox = sandbox(ResultCacheConfig::default());
+    let instance_id = instantiate(&mut sandbox);
+    
+    for _ in 0..3 {
+        let score: i32 = sandbox.call_function(instance_id, "score", (4,)).await.unwrap();
+        assert_eq!(score, 40);
+    }
+    let score: i32 = sandbox.call_function(instance_id, "score", (5,)).await.unwrap();
+    assert_eq!(score, 50);
+    assert_eq!(executions(&sandbox, instance_id), 2);
+    
+    // Functions not marked pure always run
+    let first: i32 = sandbox.call_function(instance_id, "impure", (1,)).await.unwrap();
+    let second: i32 = sandbox.call_function(instance_id, "impure", (1,)).await.unwrap();
+    assert_ne!(first, second);
+    
+    let metrics = sandbox.result_cache_metrics();
+    assert_eq!((metrics.hits, metrics.misses, metrics.entries), (2, 2, 2));
+    assert_eq!(metrics.hit_rate(), 0.5);
+}
+
+#[tokio::test]
+async fn test_results_are_shared_per_module_version() {
+    let mut sandbox = sandbox(ResultCacheConfig::default());
+    let first = instantiate(&mut sandbox);
+    let second = instantiate(&mut sandbox);
+    
+    let _: i32 = sandbox.call_function(first, "score", (7,)).await.unwrap();
+    let score: i32 = sandbox.call_function(second, "score", (7,)).await.unwrap();
+    assert_eq!(score, 70);
+    assert_eq!(executions(&sandbox, second), 0);
+    
+    // A different module version never sees the cached result
+    let changed = SCORING_MODULE.replace("i32.const 10", "i32.const 11");
+    let module_id = sandbox.load_module(changed.as_bytes()).unwrap();
+    let config = InstanceConfig::builder().pure_function("score").build().unwrap();
+    let third = sandbox.create_instance(module_id, Some(config)).unwrap();
+    let score: i32 = sandbox.call_function(third, "score", (7,)).await.unwrap();
+    assert_eq!(score, 77);
+}
+
+#[tokio::test]
+async fn test_ttl_and_size_limits() {
+    let mut sandbox = sandbox(ResultCacheConfig::default().max_entries(2).ttl(Duration::from_millis(50)));
+    let instance_id = instantiate(&mut sandbox);
+    
+    for value in [1, 2, 3] {
+        let _: i32 = sandbox.call_function(instance_id, "score", (value,)).await.unwrap();
+    }
+    let metrics = sandbox.result_cache_metrics();
+    assert_eq!((metrics.entries, metrics.evictions), (2, 1));
+    
+    // The least recently used result was evicted
+    let _: i32 = sandbox.call_function(instance_id, "score", (1,)).await.unwrap();
+    assert_eq!(executions(&sandbox, instance_id), 4);
+    
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    let _: i32 = sandbox.call_function(instance_id, "score", (3,)).await.unwrap();
+    assert_eq!(executions(&sandbox, instance_id), 5);
+    assert_eq!(sandbox.result_cache_metrics().expirations, 1);
+}
+
+#[test]
+fn test_pure_functions_from_manifest() {
+    let manifest = SandboxManifest::from_str_strict(r#"
+name = "analytics"
+version = "1.0.0"
+
+[result_cache]
+pure_functions = ["score"]
+max_entries = 100
+max_size = "1MB"
+ttl = "5m"
+"#).unwrap();
+
+    assert!(manifest.to_instance_config().unwrap().pure_functions.contains("score"));
+    let cache = manifest.to_result_cache_config().unwrap();
+    assert_eq!(cache.max_entries, 100);
+    assert_eq!(cache.max_bytes, 1024 * 1024);
+    assert_eq!(cache.ttl, Some(Duration::from_secs(300)));
+}
@@ -0,0 +1,84 @@
+//! Tests for reading large guest results in chunks
+
+mod common;
+
+use wasm_sandbox::{InstanceConfig, InstanceId, WasmSandbox};
+
+/// Number of elements in the array `zeros` returns
+const ZEROS: usize = 100_000;
+
+const LARGE_RESULT_MODULE: &str = r#"
+(module
+  (memory (export "memory") 4)
+  (func (export "alloc") (param i32) (result i32)
+    i32.const 0)
+  
+  ;; Write "[0,0,...,0]" with 100000 elements at offset 4096
+  (func (export "zeros") (param i32 i32) (result i64)
+    (local $i i32)
+    (i32.store8 (i32.const 4096) (i32.const 91))
+    (block $done
+      (loop $fill
+        (br_if $done (i32.ge_u (local.get $i) (i32.const 100000)))
+        (i32.store8 (i32.add (i32.const 4097) (i32.mul (local.get $i) (i32.const 2))) (i32.const 48))
+        (i32.store8 (i32.add (i32.const 4098) (i32.mul (local.get $i) (i32.const 2))) (i32.const 44))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $fill)))
+    ;; Replace the trailing comma with "]"
+    (i32.store8 (i32.const 204096) (i32.const 93))
+    (i64.or (i64.shl (i64.const 4096) (i64.const 32)) (i64.const 200001)))
+  
+  ;; Claims an output far beyond the end of memory
+  (func (export "bogus") (param i32 i32) (result i64)
+    (i64.or (i64.shl (i64.const 4096) (i64.const 32)) (i64.const 0x7fffffff))))
+"#;
+
+fn instantiate(max_inline_result_bytes: Option<usize>) -> (WasmSandbox, InstanceId) {
+    let config = InstanceConfig {
+        max_inline_result_bytes,
+        ..InstanceConfig::default()
+    };
+    common::instantiate(LARGE_RESULT_MODULE, Some(config))
+}
+
+#[tokio::test]
+async fn test_large_result_is_reassembled() {
+    let (sandbox, instance_id) = instantiate(Some(1024));
+    
+    let zeros: Vec<u8> = sandbox.call_function(instance_id, "zeros", ()).await.unwrap();
+    assert_eq!(zeros.len(), ZEROS);
+    assert!(zeros.iter().all(|&z| z == 0));
+}
+
+#[tokio::test]
+async fn test_inline_results_still_work() {
+    let (sandbox, instance_id) = instantiate(None);
+    
+    let zeros: Vec<u8> = sandbox.call_function(instance_id, "zeros", ()).await.unwrap();
+    assert_eq!(zeros.len(), ZEROS);
+}
+
+#[test]
+fn test_caller_can_stream_chunks() {
+    let (sandbox, instance_id) = instantiate(Some(64 * 1024));
+    
+    let output = sandbox.call_function_spilled(instance_id, "zeros", ()).unwrap();
+    assert_eq!(output.len(), 2 * ZEROS + 1);
+    assert!(output.is_spilled());
+    
+    let chunks: Vec<Vec<u8>> = output.collect::<wasm_sandbox::Result<_>>().unwrap();
+    assert_eq!(chunks.len(), 4);
+    assert!(chunks[..3].iter().all(|chunk| chunk.len() == 64 * 1024));
+    
+    let json = chunks.concat();
+    assert!(json.starts_with(b"[0,0,") && json.ends_with(b",0]"));
+}
+
+#[tokio::test]
+async fn test_out_of_bounds_output_is_rejected() {
+    let (sandbox, instance_id) = instantiate(Some(1024));
+    
+    let result: wasm_sandbox::Result<Vec<u8>> = sandbox.call_function(instance_id, "bogus", ()).await;
+    assert!(result.unwrap_err().to_string().contains("outside guest memory"));
+    assert!(sandbox.call_function_spilled(instance_id, "bogus", ()).is_err());
+}
@@ -0,0 +1,103 @@
+//! Tests for streaming partial results from guest functions
+
+use futures::StreamExt;
+use wasm_sandbox::{ResultStream, WasmSandbox};
+
+const STREAMING_MODULE: &str = r#"
+(module
+  (import "sandbox_stream" "emit" (func $emit (param i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "123")
+  
+  (func (export "alloc") (param i32) (result i32)
+    i32.const 1024)
+  
+  (func (export "count")
+    (drop (call $emit (i32.const 0) (i32.const 1)))
+    (drop (call $emit (i32.const 1) (i32.const 1)))
+    (drop (call $emit (i32.const 2) (i32.const 1))))
+  
+  (func (export "echo") (param i32 i32)
+    (drop (call $emit (local.get 0) (local.get 1))))
+  
+  (func (export "fail")
+    (drop (call $emit (i32.const 0) (i32.const 1)))
+    unreachable)
+  
+  (func (export "forever")
+    (loop $again
+      (br_if $again (i32.eqz (call $emit (i32.const 0) (i32.const 1)))))))
+"#;
+
+fn sandbox_with_module() -> (WasmSandbox, wasm_sandbox::InstanceId) {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(STREAMING_MODULE.as_bytes()).expect("Failed to load module");
+    let instance_id = sandbox.create_instance(module_id, None).expect("Failed to create instance");
+    (sandbox, instance_id)
+}
+
+#[tokio::test]
+async fn test_stream_yields_partials_then_completes() {
+    let (sandbox, instance_id) = sandbox_with_module();
+    
+    let mut stream: ResultStream<i32> = sandbox
+        .call_function_streaming(instance_id, "count", ())
+        .await
+        .expect("Failed to start stream");
+    
+    let mut results = Vec::new();
+    while let Some(item) = stream.next().await {
+        results.push(item.expect("partial result"));
+    }
+    
+    assert_eq!(results, vec![1, 2, 3]);
+    assert!(stream.is_complete());
+}
+
+#[tokio::test]
+async fn test_stream_passes_parameters() {
+    let (sandbox, instance_id) = sandbox_with_module();
+    
+    let stream: ResultStream<Vec<i32>> = sandbox
+        .call_function_streaming(instance_id, "echo", vec![4, 5])
+        .await
+        .expect("Failed to start stream");
+    
+    let results: Vec<_> = stream.map(|item| item.unwrap()).collect().await;
+    assert_eq!(results, vec![vec![4, 5]]);
+}
+
+#[tokio::test]
+async fn test_stream_reports_trap_as_last_item() {
+    let (sandbox, instance_id) = sandbox_with_module();
+    
+    let mut stream: ResultStream<i32> = sandbox
+        .call_function_streaming(instance_id, "fail", ())
+        .await
+        .expect("Failed to start stream");
+    
+    assert_eq!(stream.next().await.unwrap().unwrap(), 1);
+    assert!(stream.next().await.unwrap().is_err());
+    assert!(stream.next().await.is_none());
+    assert!(!stream.is_complete());
+}
+
+#[tokio::test]
+async fn test_dropping_stream_stops_guest() {
+    let (sandbox, instance_id) = sandbox_with_module();
+    
+    let mut stream: ResultStream<i32> = sandbox
+        .call_function_streaming(instance_id, "forever", ())
+        .await
+        .expect("Failed to start stream");
+    assert_eq!(stream.next().await.unwrap().unwrap(), 1);
+    drop(stream);
+    
+    // The guest returns once `emit` reports the consumer is gone, releasing the instance
+    let stream: ResultStream<i32> = sandbox
+        .call_function_streaming(instance_id, "count", ())
+        .await
+        .expect("Failed to start stream");
+    let results: Vec<_> = stream.map(|item| item.unwrap()).collect().await;
+    assert_eq!(results, vec![1, 2, 3]);
+}
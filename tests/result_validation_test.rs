@@ -0,0 +1,122 @@
+//! Tests for validating guest results before they are deserialized
+
+mod common;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use wasm_sandbox::observability::http::render_metrics;
+use wasm_sandbox::runtime::ModuleId;
+use wasm_sandbox::{Error, InstanceConfig, InstanceId, ResultValidator, WasmSandbox};
+
+/// `user` returns a well-formed user; `broken_user` returns one whose age is a string
+const USER_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (data (i32.const 1024) "{\"name\":\"ada\",\"age\":36}")
+  (data (i32.const 2048) "{\"name\":\"ada\",\"age\":\"old\"}")
+  (func (export "alloc") (param i32) (result i32)
+    i32.const 0)
+  (func (export "user") (param i32 i32) (result i64)
+    (i64.or (i64.shl (i64.const 1024) (i64.const 32)) (i64.const 23)))
+  (func (export "broken_user") (param i32 i32) (result i64)
+    (i64.or (i64.shl (i64.const 2048) (i64.const 32)) (i64.const 26))))
+"#;
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct User {
+    name: String,
+    age: u32,
+}
+
+fn user_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" },
+            "age": { "type": "integer", "minimum": 0 }
+        },
+        "required": ["name", "age"]
+    })
+}
+
+fn instantiate(max_inline_result_bytes: Option<usize>) -> (WasmSandbox, ModuleId, InstanceId) {
+    let config = InstanceConfig { max_inline_result_bytes, ..InstanceConfig::default() };
+    let (sandbox, instance_id) = common::instantiate(USER_MODULE, Some(config));
+    let module_id = sandbox.get_instance(instance_id).unwrap().module_id;
+    (sandbox, module_id, instance_id)
+}
+
+#[tokio::test]
+async fn test_schema_rejects_malformed_results_with_a_descriptive_error() {
+    let (sandbox, module_id, instance_id) = instantiate(None);
+    let validator = ResultValidator::json_schema(&user_schema()).unwrap();
+    sandbox.set_result_validator(module_id, "user", validator.clone()).unwrap();
+    sandbox.set_result_validator(module_id, "broken_user", validator).unwrap();
+    
+    let user: Value = sandbox.call_function(instance_id, "user", ()).await.unwrap();
+    assert_eq!(user["age"], 36);
+    
+    // Deserializing into a loose type would have hidden the problem
+    let err = sandbox.call_function::<_, Value>(instance_id, "broken_user", ()).await.unwrap_err();
+    match &err {
+        Error::InvalidResult { function_name, errors } => {
+            assert_eq!(function_name, "broken_user");
+            assert!(errors.iter().any(|error| error.starts_with("/age")), "{:?}", errors);
+        }
+        other => panic!("unexpected error {}", other),
+    }
+    
+    let metrics = &sandbox.result_validation_metrics()[&module_id];
+    assert_eq!((metrics.validated, metrics.failed), (2, 1));
+    assert_eq!(metrics.failures_by_function["broken_user"], 1);
+}
+
+#[tokio::test]
+async fn test_typed_validators_check_spilled_and_inline_results() {
+    for max_inline_result_bytes in [Some(8), None] {
+        let (sandbox, module_id, instance_id) = instantiate(max_inline_result_bytes);
+        sandbox.set_result_validator(module_id, "broken_user", ResultValidator::typed::<User>()).unwrap();
+        sandbox.set_result_validator(module_id, "user", ResultValidator::typed::<User>()).unwrap();
+        
+        let user: Value = sandbox.call_function(instance_id, "user", ()).await.unwrap();
+        assert_eq!(user["name"], "ada");
+        let err = sandbox.call_function::<_, Value>(instance_id, "broken_user", ()).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidResult { .. }), "{}", err);
+    }
+}
+
+#[tokio::test]
+async fn test_scoped_calls_are_validated_until_the_validator_is_removed() {
+    let (sandbox, module_id, instance_id) = instantiate(None);
+    let validator = ResultValidator::custom("adults only", |result| match result["age"].as_u64() {
+        Some(age) if age >= 18 => Ok(()),
+        _ => Err(vec!["age must be a number of at least 18".to_string()]),
+    });
+    sandbox.set_result_validator(module_id, "broken_user", validator).unwrap();
+    
+    let mut scope = sandbox.scope::<Value>();
+    scope.spawn(instance_id, "broken_user", ()).unwrap();
+    let (_, result) = scope.join_next().await.unwrap();
+    assert!(matches!(result, Err(Error::InvalidResult { .. })));
+    drop(scope);
+    
+    assert!(sandbox.remove_result_validator(module_id, "broken_user"));
+    assert!(!sandbox.remove_result_validator(module_id, "broken_user"));
+    let user: Value = sandbox.call_function(instance_id, "broken_user", ()).await.unwrap();
+    assert_eq!(user["age"], "old");
+}
+
+#[tokio::test]
+async fn test_invalid_schemas_and_unknown_modules_are_refused() {
+    let (sandbox, module_id, instance_id) = instantiate(None);
+    assert!(ResultValidator::json_schema(&json!({ "type": "no such type" })).is_err());
+    let validator = ResultValidator::typed::<User>();
+    assert!(sandbox.set_result_validator(ModuleId::new(), "user", validator.clone()).is_err());
+    
+    sandbox.set_result_validator(module_id, "broken_user", validator).unwrap();
+    let _ = sandbox.call_function::<_, Value>(instance_id, "broken_user", ()).await;
+    let rendered = render_metrics(&sandbox.health());
+    let line = format!("wasm_sandbox_module_invalid_results_total{{module=\"{}\"}} 1", module_id);
+    assert!(rendered.contains(&line), "{}", rendered);
+}
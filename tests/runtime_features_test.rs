@@ -0,0 +1,74 @@
+//! Tests for runtime feature introspection
+
+use wasm_sandbox::runtime::component::ComponentRuntime;
+use wasm_sandbox::runtime::{RuntimeConfig, WasmRuntime};
+use wasm_sandbox::security::{Capabilities, ResourceLimits};
+use wasm_sandbox::{
+    Error, HibernationConfig, MemoryBudget, PoolingConfig, RuntimeFeature, RuntimeFeatures,
+    SandboxConfig, WasmSandbox,
+};
+
+#[test]
+fn test_wasmtime_reports_its_features() {
+    let sandbox = WasmSandbox::new().unwrap();
+    let features = sandbox.runtime().features();
+    
+    assert_eq!(features.backend, "wasmtime");
+    assert_eq!(features.supported(), [
+        RuntimeFeature::Fuel,
+        RuntimeFeature::Epochs,
+        RuntimeFeature::Memory64,
+        RuntimeFeature::Pooling,
+        RuntimeFeature::Snapshots,
+        RuntimeFeature::Gc,
+    ]);
+    assert!(!features.supports(RuntimeFeature::Threads));
+    assert!(features.require(RuntimeFeature::Fuel).is_ok());
+}
+
+#[test]
+fn test_missing_features_fail_consistently() {
+    let features = RuntimeFeatures::none("custom");
+    assert!(features.supported().is_empty());
+    
+    for feature in RuntimeFeature::ALL {
+        match features.require(feature).unwrap_err() {
+            Error::Unsupported { operation, context, suggestion } => {
+                assert_eq!(operation, format!("`{}` on the custom runtime", feature.name()));
+                assert_eq!(context, "custom");
+                assert!(suggestion.unwrap().contains(feature.name()));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+}
+
+#[test]
+fn test_component_runtime_supports_components_only() {
+    let runtime = ComponentRuntime::new(Capabilities::minimal(), ResourceLimits::default()).unwrap();
+    let features = runtime.features();
+    
+    assert_eq!(features.backend, "wasmtime-component");
+    assert_eq!(features.supported(), [RuntimeFeature::Components]);
+    assert_eq!(features, RuntimeFeatures { components: true, ..RuntimeFeatures::none("wasmtime-component") });
+}
+
+#[test]
+fn test_sandbox_accepts_configurations_its_backend_supports() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = SandboxConfig {
+        runtime: RuntimeConfig {
+            enable_memory64: true,
+            pooling: Some(PoolingConfig::default()),
+            ..RuntimeConfig::default()
+        },
+        hibernation: Some(HibernationConfig::new(dir.path())),
+        memory_budget: Some(MemoryBudget::new(64 * 1024 * 1024)),
+        ..SandboxConfig::default()
+    };
+    let mut sandbox = WasmSandbox::with_config(config).unwrap();
+    
+    let module_id = sandbox.load_module(br#"(module (memory (export "memory") 1))"#).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    assert!(sandbox.fork_instance(instance_id).is_ok());
+}
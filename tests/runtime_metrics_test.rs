@@ -0,0 +1,115 @@
+//! Tests for latency percentiles, fuel rates and per-module runtime metrics
+
+use std::time::{Duration, Instant};
+
+use wasm_sandbox::{DecayingRate, InstanceConfig, LatencyWindow, WasmSandbox};
+
+const ADD_MODULE: &str = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    i32.add)
+  (func (export "fail") (param i32) (result i32)
+    unreachable))
+"#;
+
+const ECHO_MODULE: &str = r#"
+(module
+  (func (export "echo") (param i32) (result i32)
+    local.get 0))
+"#;
+
+#[test]
+fn latency_windows_report_nearest_rank_percentiles() {
+    let mut window = LatencyWindow::new(100);
+    for ms in 1..=100 {
+        window.record(Duration::from_millis(ms));
+    }
+    let summary = window.summary();
+    assert_eq!(summary.count, 100);
+    assert_eq!(summary.p50, Duration::from_millis(50));
+    assert_eq!(summary.p95, Duration::from_millis(95));
+    assert_eq!(summary.p99, Duration::from_millis(99));
+    assert_eq!(summary.max, Duration::from_millis(100));
+    
+    // Old samples are evicted, but still counted
+    for _ in 0..100 {
+        window.record(Duration::from_millis(1));
+    }
+    let summary = window.summary();
+    assert_eq!(summary.count, 200);
+    assert_eq!(summary.max, Duration::from_millis(1));
+    assert_eq!(LatencyWindow::default().summary().p99, Duration::ZERO);
+}
+
+#[test]
+fn decaying_rates_halve_every_half_life() {
+    let start = Instant::now();
+    let mut rate = DecayingRate::new(Duration::from_secs(10));
+    assert_eq!(rate.rate(start), 0.0);
+    
+    rate.record(1_000.0, start);
+    let initial = rate.rate(start);
+    let later = rate.rate(start + Duration::from_secs(10));
+    assert!((later - initial / 2.0).abs() < 1e-9);
+    
+    // A steady rate converges on its true value
+    let mut steady = DecayingRate::new(Duration::from_secs(1));
+    for tick in 1..=1_000 {
+        steady.record(10.0, start + Duration::from_millis(10 * tick));
+    }
+    let per_second = steady.rate(start + Duration::from_secs(10));
+    assert!((per_second - 1_000.0).abs() < 50.0, "rate was {}", per_second);
+}
+
+#[tokio::test]
+async fn detailed_metrics_break_calls_down_by_module() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let add_module = sandbox.load_module(ADD_MODULE.as_bytes()).unwrap();
+    let echo_module = sandbox.load_module(ECHO_MODULE.as_bytes()).unwrap();
+    let adder = sandbox.create_instance(add_module, None).unwrap();
+    sandbox.create_instance(add_module, None).unwrap();
+    let echo = sandbox.create_instance(echo_module, None).unwrap();
+    
+    for i in 0..3 {
+        let sum: i32 = sandbox.call_function(adder, "add", (i, 1)).await.unwrap();
+        assert_eq!(sum, i + 1);
+    }
+    assert!(sandbox.call_function::<_, i32>(adder, "fail", 0).await.is_err());
+    let _: i32 = sandbox.call_function(echo, "echo", 5).await.unwrap();
+    
+    let detailed = sandbox.runtime().get_metrics_detailed();
+    assert_eq!(detailed.runtime.compiled_modules, 2);
+    assert_eq!(detailed.totals.calls, 5);
+    assert_eq!(detailed.totals.failed_calls, 1);
+    assert_eq!(detailed.totals.instantiations, 3);
+    assert_eq!(detailed.totals.call_latency.count, 5);
+    assert!(detailed.totals.call_latency.p50 <= detailed.totals.call_latency.p99);
+    assert!(detailed.totals.instantiation_latency.max > Duration::ZERO);
+    
+    let add = &detailed.modules[&add_module];
+    assert_eq!((add.calls, add.failed_calls, add.instantiations), (4, 1, 2));
+    let echo = &detailed.modules[&echo_module];
+    assert_eq!((echo.calls, echo.failed_calls, echo.instantiations), (1, 0, 1));
+}
+
+#[tokio::test]
+async fn fuel_burned_by_calls_feeds_the_fuel_rate() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(ADD_MODULE.as_bytes()).unwrap();
+    let mut config = InstanceConfig::default();
+    config.resource_limits.fuel = Some(1_000_000);
+    let instance_id = sandbox.create_instance(module_id, Some(config)).unwrap();
+    assert!(sandbox.runtime().get_metrics().fuel_consumption_rate.is_none());
+    
+    for i in 0..10 {
+        let _: i32 = sandbox.call_function(instance_id, "add", (i, i)).await.unwrap();
+    }
+    
+    let detailed = sandbox.runtime().get_metrics_detailed();
+    assert!(detailed.totals.fuel_consumed > 0);
+    assert!(detailed.totals.fuel_rate > 0.0);
+    assert_eq!(detailed.modules[&module_id].fuel_consumed, detailed.totals.fuel_consumed);
+    assert!(detailed.runtime.fuel_consumption_rate.unwrap() > 0.0);
+}
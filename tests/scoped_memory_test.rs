@@ -0,0 +1,66 @@
+//! Tests for scoped access to instance memory
+
+mod common;
+
+use wasm_sandbox::runtime::{HostValue, WasmMemoryExt};
+use wasm_sandbox::WasmSandbox;
+
+const MEMORY_MODULE: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (data (i32.const 16) "hello")
+  (func (export "load") (param $offset i32) (result i32)
+    (i32.load8_u (local.get $offset)))
+  (func (export "grow") (param $pages i32) (result i32)
+    (memory.grow (local.get $pages))))
+"#;
+
+#[test]
+fn test_with_memory_reads_guest_data() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let instance_id = common::create_instance(&mut sandbox, MEMORY_MODULE, None);
+    let instance = &sandbox.get_instance(instance_id).unwrap().instance;
+    
+    let greeting = instance.with_memory(|memory| memory[16..21].to_vec()).unwrap();
+    assert_eq!(greeting, b"hello");
+    assert_eq!(instance.with_memory(|memory| memory.len()).unwrap(), 65536);
+}
+
+#[test]
+fn test_with_memory_mut_writes_are_seen_by_the_guest() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let instance_id = common::create_instance(&mut sandbox, MEMORY_MODULE, None);
+    let instance = &sandbox.get_instance(instance_id).unwrap().instance;
+    
+    let previous = instance.with_memory_mut(|memory| std::mem::replace(&mut memory[100], 42)).unwrap();
+    assert_eq!(previous, 0);
+    assert_eq!(instance.call_values("load", &[HostValue::I32(100)]).unwrap(), vec![HostValue::I32(42)]);
+}
+
+#[test]
+fn test_access_after_growth_sees_the_new_memory() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let instance_id = common::create_instance(&mut sandbox, MEMORY_MODULE, None);
+    let instance = &sandbox.get_instance(instance_id).unwrap().instance;
+    
+    instance.call_values("grow", &[HostValue::I32(2)]).unwrap();
+    let (len, greeting) = instance.with_memory(|memory| (memory.len(), memory[16..21].to_vec())).unwrap();
+    assert_eq!(len, 3 * 65536);
+    assert_eq!(greeting, b"hello");
+    
+    // Bytes past the old end are writable without any stale pointer to fix up
+    instance.with_memory_mut(|memory| memory[2 * 65536] = 7).unwrap();
+    assert_eq!(instance.call_values("load", &[HostValue::I32(2 * 65536)]).unwrap(), vec![HostValue::I32(7)]);
+}
+
+#[test]
+fn test_modules_without_memory_are_rejected() {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let instance_id = common::create_instance(&mut sandbox, "(module)", None);
+    let instance = &sandbox.get_instance(instance_id).unwrap().instance;
+    
+    let mut called = false;
+    assert!(instance.with_memory(|_| called = true).is_err());
+    assert!(instance.with_memory_mut(|_| called = true).is_err());
+    assert!(!called);
+}
@@ -0,0 +1,92 @@
+//! Tests for per-instance scratch directories
+
+use wasm_sandbox::{InstanceConfig, SandboxError, SandboxManifest, ScratchConfig, WasmSandbox};
+
+/// Writes `len` zero bytes to `out.bin` in the first preopened directory, returning the WASI errno
+const SCRATCH_WRITER: &str = r#"
+    (module
+        (import "wasi_snapshot_preview1" "path_open"
+            (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_write"
+            (func $fd_write (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 0) "out.bin")
+        (func (export "write") (param $len i32) (result i32)
+            (local $errno i32)
+            (local.set $errno
+                (call $path_open (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 7)
+                    (i32.const 1) (i64.const 0x1fffffff) (i64.const 0x1fffffff) (i32.const 0) (i32.const 16)))
+            (if (local.get $errno) (then (return (local.get $errno))))
+            (i32.store (i32.const 32) (i32.const 1024))
+            (i32.store (i32.const 36) (local.get $len))
+            (call $fd_write (i32.load (i32.const 16)) (i32.const 32) (i32.const 1) (i32.const 48))))
+"#;
+
+fn scratch_instance(max_scratch_bytes: Option<u64>, scratch: ScratchConfig) -> (WasmSandbox, wasm_sandbox::InstanceId) {
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let module_id = sandbox.load_module(SCRATCH_WRITER.as_bytes()).unwrap();
+    let mut config = InstanceConfig {
+        scratch: Some(scratch),
+        ..InstanceConfig::default()
+    };
+    config.resource_limits.io.max_scratch_bytes = max_scratch_bytes;
+    let instance_id = sandbox.create_instance(module_id, Some(config)).unwrap();
+    (sandbox, instance_id)
+}
+
+#[tokio::test]
+async fn guest_writes_land_in_scratch_and_are_removed_with_the_instance() {
+    let (mut sandbox, instance_id) = scratch_instance(Some(1024), ScratchConfig::default());
+    let path = sandbox.scratch_space(instance_id).unwrap().path().to_path_buf();
+    
+    let errno: i32 = sandbox.call_function(instance_id, "write", 100).await.unwrap();
+    assert_eq!(errno, 0);
+    assert_eq!(std::fs::read(path.join("out.bin")).unwrap(), vec![0; 100]);
+    assert_eq!(sandbox.scratch_space(instance_id).unwrap().usage(), 100);
+    
+    drop(sandbox.remove_instance(instance_id));
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn exceeding_the_quota_fails_the_call_and_keeps_the_workspace_on_request() {
+    let (mut sandbox, instance_id) = scratch_instance(Some(256), ScratchConfig::default().keep_on_failure(true));
+    let path = sandbox.scratch_space(instance_id).unwrap().path().to_path_buf();
+    
+    let error = sandbox.call_function::<_, i32>(instance_id, "write", 512).await.unwrap_err();
+    assert!(matches!(error, SandboxError::ResourceLimit { .. }), "unexpected error: {}", error);
+    assert!(sandbox.scratch_space(instance_id).unwrap().is_kept());
+    
+    drop(sandbox.remove_instance(instance_id));
+    assert_eq!(std::fs::metadata(path.join("out.bin")).unwrap().len(), 512);
+    std::fs::remove_dir_all(&path).unwrap();
+}
+
+#[tokio::test]
+async fn workspace_can_be_exported() {
+    let (sandbox, instance_id) = scratch_instance(None, ScratchConfig::default());
+    let _: i32 = sandbox.call_function(instance_id, "write", 64).await.unwrap();
+    
+    let destination = tempfile::tempdir().unwrap();
+    let copied = sandbox.scratch_space(instance_id).unwrap().export(destination.path()).unwrap();
+    assert_eq!(copied, 64);
+    assert_eq!(std::fs::read(destination.path().join("out.bin")).unwrap().len(), 64);
+    
+    let outputs = sandbox.collect_outputs(instance_id, &["*.bin"]).unwrap();
+    assert_eq!(outputs.len(), 1);
+}
+
+#[test]
+fn manifest_sets_the_scratch_quota() {
+    let manifest = SandboxManifest::from_str_strict(r#"
+schema_version = 2
+name = "scratch-test"
+version = "1.0.0"
+
+[resource_limits.io]
+max_scratch_bytes = "2MB"
+"#).expect("Failed to parse manifest");
+
+    let config = manifest.to_instance_config().unwrap();
+    assert_eq!(config.resource_limits.io.max_scratch_bytes, Some(2 * 1024 * 1024));
+}
@@ -0,0 +1,125 @@
+//! Tests for secrets provisioned to guests through a host import
+
+mod common;
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::security::audit::AuditEventType;
+use wasm_sandbox::security::secrets::{
+    CallbackSecretsProvider, EnvSecretsProvider, FileSecretsProvider, SecretValue, SecretsProvider,
+};
+use wasm_sandbox::{GuestErrorCode, InstanceConfig, InstanceId, SecretsCapability, WasmSandbox};
+
+const SECRETS_MODULE: &str = r#"
+(module
+  (import "sandbox_secrets" "get" (func $get (param i32 i32) (result i64)))
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 4096))
+  (data (i32.const 0) "api_key")
+  (data (i32.const 16) "db_password")
+  (data (i32.const 32) "missing")
+  
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  
+  (func (export "api_key") (result i64) (call $get (i32.const 0) (i32.const 7)))
+  (func (export "db_password") (result i64) (call $get (i32.const 16) (i32.const 11)))
+  (func (export "missing") (result i64) (call $get (i32.const 32) (i32.const 7))))
+"#;
+
+fn provider() -> CallbackSecretsProvider {
+    CallbackSecretsProvider::new(|name| {
+        Ok(match name {
+            "api_key" => Some(SecretValue::new("s3cr3t")),
+            "db_password" => Some(SecretValue::new("hunter2")),
+            _ => None,
+        })
+    })
+}
+
+fn instantiate(sandbox: &mut WasmSandbox, granted: &[&str]) -> InstanceId {
+    let mut instance_config = InstanceConfig::default();
+    instance_config.capabilities.secrets =
+        SecretsCapability::Allowlist(granted.iter().map(|name| name.to_string()).collect());
+    common::create_instance(sandbox, SECRETS_MODULE, Some(instance_config))
+}
+
+fn call(sandbox: &WasmSandbox, instance_id: InstanceId, function_name: &str) -> i64 {
+    match sandbox.get_instance(instance_id).unwrap().instance.call_values(function_name, &[]).unwrap().as_slice() {
+        [HostValue::I64(value)] => *value,
+        other => panic!("unexpected results {:?}", other),
+    }
+}
+
+#[test]
+fn test_granted_secret_is_copied_to_guest() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.set_secrets_provider(provider());
+    let instance_id = instantiate(&mut sandbox, &["api_key", "missing"]);
+    
+    let packed = call(&sandbox, instance_id, "api_key") as u64;
+    let value = sandbox.get_instance(instance_id).unwrap().instance
+        .read_memory_at((packed >> 32) as usize, (packed & 0xffff_ffff) as usize)
+        .unwrap();
+    assert_eq!(value, b"s3cr3t");
+    
+    assert_eq!(call(&sandbox, instance_id, "missing"), GuestErrorCode::NotFound.code());
+}
+
+#[test]
+fn test_requests_are_checked_and_audited() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.set_secrets_provider(provider());
+    let instance_id = instantiate(&mut sandbox, &["api_key"]);
+    
+    assert_eq!(call(&sandbox, instance_id, "db_password"), GuestErrorCode::PermissionDenied.code());
+    assert!(call(&sandbox, instance_id, "api_key") > 0);
+    
+    let requests: Vec<_> = sandbox.secret_store().audit_logger().get_events().into_iter()
+        .filter_map(|event| match event.event_type {
+            AuditEventType::SecretAccess { name, allowed, .. } => Some((name, allowed)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(requests, vec![("db_password".to_string(), false), ("api_key".to_string(), true)]);
+}
+
+#[test]
+fn test_secrets_need_capability_and_provider() {
+    // Without a provider, granted secrets are unavailable
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let instance_id = instantiate(&mut sandbox, &["api_key"]);
+    assert_eq!(call(&sandbox, instance_id, "api_key"), GuestErrorCode::Unavailable.code());
+    
+    // Instances are granted no secrets by default
+    sandbox.set_secrets_provider(provider());
+    let module_id = sandbox.load_module(SECRETS_MODULE.as_bytes()).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    assert_eq!(call(&sandbox, instance_id, "api_key"), GuestErrorCode::PermissionDenied.code());
+}
+
+#[test]
+fn test_env_and_file_providers() {
+    let env = EnvSecretsProvider::new("SECRETS_TEST_");
+    assert_eq!(env.variable_name("db.pass-word"), "SECRETS_TEST_DB_PASS_WORD");
+    assert!(env.get("absent").unwrap().is_none());
+    
+    // `path` maps to the PATH variable when there is no prefix
+    let path = std::env::var_os("PATH").unwrap();
+    let secret = EnvSecretsProvider::default().get("path").unwrap().unwrap();
+    assert_eq!(secret.expose(), path.as_encoded_bytes());
+    
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("token"), "from-file").unwrap();
+    let files = FileSecretsProvider::new(dir.path());
+    let secret = files.get("token").unwrap().unwrap();
+    assert_eq!(secret.expose(), b"from-file");
+    assert!(files.get("absent").unwrap().is_none());
+    assert!(files.get("../token").is_err());
+    assert!(files.get("nested/token").is_err());
+    
+    // Values never show up in debug output
+    assert!(!format!("{:?}", secret).contains("from-file"));
+}
@@ -0,0 +1,131 @@
+//! Tests for brokered calls between instances
+
+use wasm_sandbox::{InstanceConfig, InstanceId, ServiceQuota, WasmSandbox};
+use wasm_sandbox::communication::broker::SERVICE_GRANT_CAPABILITY;
+use wasm_sandbox::security::{Capabilities, CustomCapability};
+use wasm_sandbox::security::audit::AuditEventType;
+
+// `echo` returns its input; `relay` calls the `echo` service and maps error codes to text
+const SERVICE_MODULE: &str = r#"
+(module
+  (import "sandbox_rpc" "call" (func $call (param i32 i32 i32 i32 i32 i32) (result i64)))
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (data (i32.const 0) "echo")
+  (data (i32.const 16) "denied")
+  (data (i32.const 32) "quota")
+  (data (i32.const 48) "failed")
+  
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  
+  (func (export "echo") (param $ptr i32) (param $len i32) (result i64)
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+      (i64.extend_i32_u (local.get $len))))
+  
+  (func (export "relay") (param $ptr i32) (param $len i32) (result i64)
+    (local $r i64)
+    (local.set $r (call $call
+      (i32.const 0) (i32.const 4)
+      (i32.const 0) (i32.const 4)
+      (local.get $ptr) (local.get $len)))
+    (if (result i64) (i64.ge_s (local.get $r) (i64.const 0))
+      (then (local.get $r))
+      (else
+        (if (result i64) (i64.eq (local.get $r) (i64.const -1))
+          (then (i64.const 0x1000000006))
+          (else
+            (if (result i64) (i64.eq (local.get $r) (i64.const -2))
+              (then (i64.const 0x2000000005))
+              (else (i64.const 0x3000000006)))))))))
+"#;
+
+fn granted(services: &[&str]) -> InstanceConfig {
+    let mut capabilities = Capabilities::minimal();
+    capabilities.add_custom(
+        SERVICE_GRANT_CAPABILITY,
+        CustomCapability::StringList(services.iter().map(|s| s.to_string()).collect()),
+    );
+    InstanceConfig {
+        capabilities,
+        ..InstanceConfig::default()
+    }
+}
+
+fn setup(caller_config: InstanceConfig) -> (WasmSandbox, InstanceId, InstanceId) {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module_id = sandbox.load_module(SERVICE_MODULE.as_bytes()).expect("Failed to load module");
+    let provider = sandbox.create_instance(module_id, None).expect("Failed to create provider");
+    let caller = sandbox.create_instance(module_id, Some(caller_config)).expect("Failed to create caller");
+    sandbox.expose_service(provider, "echo", &["echo"]).expect("Failed to expose service");
+    (sandbox, provider, caller)
+}
+
+fn relay(sandbox: &WasmSandbox, caller: InstanceId, payload: &[u8]) -> Vec<u8> {
+    sandbox.get_instance(caller).unwrap().instance
+        .call_raw("relay", payload)
+        .expect("relay failed")
+}
+
+#[test]
+fn test_granted_call_reaches_provider() {
+    let (sandbox, _provider, caller) = setup(granted(&["echo"]));
+    
+    assert_eq!(relay(&sandbox, caller, b"hello"), b"hello");
+    assert_eq!(sandbox.service_broker().calls_made(caller, "echo"), 1);
+    
+    let events = sandbox.service_broker().audit_logger().get_events();
+    assert!(events.iter().any(|event| matches!(
+        &event.event_type,
+        AuditEventType::ServiceCall { service, allowed: true, .. } if service == "echo"
+    )));
+}
+
+#[test]
+fn test_ungranted_call_is_denied_and_audited() {
+    let (sandbox, _provider, caller) = setup(granted(&["billing"]));
+    
+    assert_eq!(relay(&sandbox, caller, b"hello"), b"denied");
+    
+    let events = sandbox.service_broker().audit_logger().get_events();
+    assert!(events.iter().any(|event| matches!(
+        &event.event_type,
+        AuditEventType::ServiceCall { allowed: false, .. }
+    )));
+}
+
+#[test]
+fn test_quota_limits_calls() {
+    let (sandbox, _provider, caller) = setup(granted(&["echo.echo"]));
+    sandbox.service_broker().set_quota("echo", ServiceQuota {
+        max_calls: Some(1),
+        ..ServiceQuota::default()
+    });
+    
+    assert_eq!(relay(&sandbox, caller, b"one"), b"one");
+    assert_eq!(relay(&sandbox, caller, b"two"), b"quota");
+}
+
+#[test]
+fn test_removed_provider_withdraws_service() {
+    let (mut sandbox, provider, caller) = setup(granted(&["*"]));
+    
+    sandbox.remove_instance(provider);
+    
+    assert!(sandbox.service_broker().services().is_empty());
+    assert_eq!(relay(&sandbox, caller, b"hello"), b"failed");
+}
+
+#[test]
+fn test_instance_cannot_call_itself() {
+    let (sandbox, _provider, caller) = setup(granted(&["*"]));
+    sandbox.expose_service(caller, "self", &["echo"]).unwrap();
+    
+    let broker = sandbox.service_broker();
+    let capabilities = sandbox.get_instance(caller).unwrap().config.capabilities.clone();
+    assert!(broker.call(caller, &capabilities, "self", "echo", b"x").is_err());
+}
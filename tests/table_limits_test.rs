@@ -0,0 +1,68 @@
+//! Tests for limits on tables and their elements
+
+mod common;
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::{Error, InstanceConfig, InstanceId, ResourceKind, WasmSandbox};
+
+const GROWABLE_TABLE: &str = r#"
+(module
+  (table $t 10 funcref)
+  (func (export "grow") (param $elements i32) (result i32)
+    (table.grow $t (ref.null func) (local.get $elements))))
+"#;
+
+fn instantiate(sandbox: &mut WasmSandbox, module: &str, max_tables: u32, max_table_elements: u64) -> wasm_sandbox::Result<InstanceId> {
+    let mut config = InstanceConfig::default();
+    config.resource_limits.memory.max_tables = max_tables;
+    config.resource_limits.memory.max_table_elements = max_table_elements;
+    common::try_create_instance(sandbox, module, Some(config))
+}
+
+fn grow(sandbox: &WasmSandbox, instance_id: InstanceId, elements: i32) -> wasm_sandbox::Result<Vec<HostValue>> {
+    sandbox.get_instance(instance_id).unwrap().instance.call_values("grow", &[HostValue::I32(elements)])
+}
+
+#[test]
+fn test_table_grows_within_limit() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let instance_id = instantiate(&mut sandbox, GROWABLE_TABLE, 1, 100).unwrap();
+    
+    assert_eq!(grow(&sandbox, instance_id, 90).unwrap(), vec![HostValue::I32(10)]);
+}
+
+#[test]
+fn test_table_growth_past_limit_is_resource_exhausted() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let instance_id = instantiate(&mut sandbox, GROWABLE_TABLE, 1, 100).unwrap();
+    
+    match grow(&sandbox, instance_id, 1_000_000) {
+        Err(Error::ResourceExhausted { kind: ResourceKind::Table, limit, used, .. }) => {
+            assert_eq!(limit, 100);
+            assert_eq!(used, 1_000_010);
+        }
+        other => panic!("expected a table limit error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_oversized_initial_table_is_rejected() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module = r#"(module (table 5000 funcref))"#;
+    
+    let result = instantiate(&mut sandbox, module, 1, 1000);
+    assert!(matches!(result, Err(Error::ResourceExhausted { kind: ResourceKind::Table, limit: 1000, used: 5000, .. })));
+    
+    assert!(instantiate(&mut sandbox, module, 1, 10_000).is_ok());
+}
+
+#[test]
+fn test_too_many_tables_are_rejected() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let module = r#"(module (table 1 funcref) (table 1 funcref) (table 1 externref))"#;
+    
+    let result = instantiate(&mut sandbox, module, 2, 100);
+    assert!(matches!(result, Err(Error::ResourceExhausted { kind: ResourceKind::Table, limit: 2, used: 3, .. })));
+    
+    assert!(instantiate(&mut sandbox, module, 3, 100).is_ok());
+}
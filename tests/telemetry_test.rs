@@ -0,0 +1,123 @@
+//! Tests for sampling and hashing of journaled telemetry
+
+mod common;
+
+use wasm_sandbox::observability::telemetry::hash_value;
+use wasm_sandbox::security::audit::AuditEventType;
+use wasm_sandbox::{
+    CallableFunctions, Error, InstanceConfig, InstanceId, JournalEvent, SandboxConfig, TelemetryConfig,
+    TenantTelemetry, WasmSandbox,
+};
+
+const ECHO_MODULE: &str = r#"
+(module
+  (func (export "echo") (param i32) (result i32) (local.get 0)))
+"#;
+
+fn sandbox(telemetry: TelemetryConfig) -> WasmSandbox {
+    WasmSandbox::with_config(SandboxConfig {
+        telemetry,
+        ..SandboxConfig::default()
+    }).unwrap()
+}
+
+fn instantiate(sandbox: &mut WasmSandbox, tenant: Option<&str>) -> InstanceId {
+    let mut config = InstanceConfig::builder()
+        .callable_functions(CallableFunctions::Allowlist(vec!["echo".to_string()]));
+    if let Some(tenant) = tenant {
+        config = config.tenant(tenant);
+    }
+    common::create_instance(sandbox, ECHO_MODULE, Some(config.build().unwrap()))
+}
+
+fn count(sandbox: &WasmSandbox, kind: &str, instance_id: InstanceId) -> usize {
+    let instance_id = instance_id.to_string();
+    sandbox.journal().entries().iter()
+        .filter(|entry| entry.event.kind() == kind && entry.event.instance_id() == Some(instance_id.as_str()))
+        .count()
+}
+
+#[tokio::test]
+async fn test_sampling_drops_events_by_kind_and_tenant() {
+    let mut sandbox = sandbox(TelemetryConfig::new()
+        .sample("call_completed", 0.0)
+        .tenant("acme", TenantTelemetry::new().sample("call_completed", 1.0)));
+    let plain = instantiate(&mut sandbox, None);
+    let acme = instantiate(&mut sandbox, Some("acme"));
+    
+    for n in 0..3 {
+        let _: i32 = sandbox.call_function(plain, "echo", n).await.unwrap();
+        let _: i32 = sandbox.call_function(acme, "echo", n).await.unwrap();
+    }
+    assert_eq!(count(&sandbox, "call_completed", plain), 0);
+    assert_eq!(count(&sandbox, "call_completed", acme), 3);
+    assert_eq!(count(&sandbox, "instance_created", plain), 1);
+    assert_eq!(sandbox.journal().metrics().sampled_out, 3);
+    
+    // Events the host records itself are sampled too
+    let journal = sandbox.journal();
+    assert!(journal.record(JournalEvent::Custom { event_type: "tick".to_string(), data: 1.into() }).is_some());
+    journal.set_telemetry(TelemetryConfig::new().sample("custom", 0.0));
+    assert!(journal.record(JournalEvent::Custom { event_type: "tick".to_string(), data: 2.into() }).is_none());
+}
+
+#[tokio::test]
+async fn test_hashed_fields_replace_parameters() {
+    let mut sandbox = sandbox(TelemetryConfig::new()
+        .hash_field("function_name")
+        .hash_salt("pepper")
+        .tenant("internal", TenantTelemetry::new().hashed_fields(Vec::<String>::new())));
+    let hashed = instantiate(&mut sandbox, None);
+    let internal = instantiate(&mut sandbox, Some("internal"));
+    
+    let _: i32 = sandbox.call_function(hashed, "echo", 1).await.unwrap();
+    assert!(sandbox.call_function::<_, i32>(hashed, "secret_export", ()).await.is_err());
+    let _: i32 = sandbox.call_function(internal, "echo", 1).await.unwrap();
+    
+    let expected = hash_value("pepper", "echo");
+    assert!(expected.starts_with("sha256:"));
+    assert_ne!(expected, hash_value("", "echo"));
+    let function_names: Vec<(String, String)> = sandbox.journal().entries().into_iter()
+        .filter_map(|entry| match entry.event {
+            JournalEvent::CallCompleted { instance_id, function_name, .. } => Some((instance_id, function_name)),
+            JournalEvent::Audit { record } => match record.event_type {
+                AuditEventType::FunctionCallDenied { instance_id, function_name } => Some((instance_id, function_name)),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    assert_eq!(function_names, [
+        (hashed.to_string(), expected),
+        (hashed.to_string(), hash_value("pepper", "secret_export")),
+        (internal.to_string(), "echo".to_string()),
+    ]);
+    
+    // The audit log the host reads keeps the raw values
+    let audit = sandbox.function_call_audit_logger().get_events();
+    assert!(matches!(&audit[0].event_type, AuditEventType::FunctionCallDenied { function_name, .. } if function_name == "secret_export"));
+}
+
+#[tokio::test]
+async fn test_telemetry_is_validated_and_reloadable() {
+    let config = SandboxConfig {
+        telemetry: TelemetryConfig::new().tenant("acme", TenantTelemetry::new().sample("audit", 1.5)),
+        ..SandboxConfig::default()
+    };
+    match WasmSandbox::with_config(config) {
+        Err(Error::InvalidInput { field, .. }) => assert_eq!(field, "telemetry.tenants.acme.sampling.audit"),
+        other => panic!("unexpected result {:?}", other.map(|_| ())),
+    }
+    
+    let mut sandbox = sandbox(TelemetryConfig::default());
+    let instance_id = instantiate(&mut sandbox, None);
+    let _: i32 = sandbox.call_function(instance_id, "echo", 1).await.unwrap();
+    
+    let mut config = sandbox.config().clone();
+    config.telemetry = TelemetryConfig::new().sample("call_completed", 0.0);
+    let diff = sandbox.apply_config(config).unwrap();
+    assert!(diff.is_applied("telemetry"), "{}", diff);
+    assert_eq!(sandbox.journal().telemetry().sampling["call_completed"], 0.0);
+    let _: i32 = sandbox.call_function(instance_id, "echo", 2).await.unwrap();
+    assert_eq!(count(&sandbox, "call_completed", instance_id), 1);
+}
@@ -0,0 +1,139 @@
+//! Tests for mounting throwaway directory trees into instances
+
+mod common;
+
+use wasm_sandbox::testing::{FsFixture, TempSandboxFs};
+use wasm_sandbox::{InstanceConfig, InstanceId, WasmSandbox};
+
+/// `write` creates `out.txt` holding "done" and `remove` deletes `old.txt`, both
+/// in the preopened directory with the given descriptor, returning the WASI errno
+const FS_MODULE: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "path_open"
+    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "path_unlink_file"
+    (func $path_unlink_file (param i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "out.txt")
+  (data (i32.const 8) "old.txt")
+  (data (i32.const 64) "done")
+  (func (export "write") (param $dir i32) (result i32)
+    (local $errno i32)
+    (local.set $errno
+      (call $path_open (local.get $dir) (i32.const 0) (i32.const 0) (i32.const 7)
+        (i32.const 1) (i64.const 0x1fffffff) (i64.const 0x1fffffff) (i32.const 0) (i32.const 16)))
+    (if (local.get $errno) (then (return (local.get $errno))))
+    (i32.store (i32.const 32) (i32.const 64))
+    (i32.store (i32.const 36) (i32.const 4))
+    (call $fd_write (i32.load (i32.const 16)) (i32.const 32) (i32.const 1) (i32.const 48)))
+  (func (export "remove") (param $dir i32) (result i32)
+    (call $path_unlink_file (local.get $dir) (i32.const 8) (i32.const 7))))
+"#;
+
+/// Descriptors of the readable `input` and writable `output` mounts
+const INPUT_FD: i32 = 3;
+const OUTPUT_FD: i32 = 4;
+
+/// WASI `perm` errno
+const ERRNO_PERM: i32 = 63;
+
+fn fixture() -> TempSandboxFs {
+    FsFixture::new()
+        .readable("input")
+        .file("input/data.txt", "1,2,3")
+        .writable("output")
+        .file("output/old.txt", "stale")
+        .build()
+        .unwrap()
+}
+
+fn instantiate(fs: &TempSandboxFs) -> (WasmSandbox, InstanceId) {
+    common::instantiate(FS_MODULE, Some(fs.mount(InstanceConfig::default())))
+}
+
+#[tokio::test]
+async fn test_guest_changes_are_reported() {
+    let fs = fixture();
+    let (sandbox, instance_id) = instantiate(&fs);
+    assert!(fs.changes().unwrap().is_empty());
+    
+    let errno: i32 = sandbox.call_function(instance_id, "write", OUTPUT_FD).await.unwrap();
+    assert_eq!(errno, 0);
+    let errno: i32 = sandbox.call_function(instance_id, "remove", OUTPUT_FD).await.unwrap();
+    assert_eq!(errno, 0);
+    
+    fs.assert_created("output/out.txt");
+    fs.assert_contents("output/out.txt", "done");
+    fs.assert_deleted("output/old.txt");
+    fs.assert_unchanged("input");
+    
+    // Changes made outside the guest count too
+    std::fs::write(fs.path("input/data.txt"), "4,5,6").unwrap();
+    fs.assert_modified("input/data.txt");
+    let changes = fs.changes().unwrap();
+    assert_eq!(changes.under("input").modified.len(), 1);
+    assert_eq!(changes.created.len() + changes.deleted.len(), 2);
+}
+
+#[tokio::test]
+async fn test_readable_mounts_reject_guest_writes() {
+    let fs = FsFixture::new()
+        .readable("input")
+        .file("input/old.txt", "kept")
+        .writable("output")
+        .build()
+        .unwrap();
+    let (sandbox, instance_id) = instantiate(&fs);
+    
+    let errno: i32 = sandbox.call_function(instance_id, "write", INPUT_FD).await.unwrap();
+    assert_eq!(errno, ERRNO_PERM);
+    let errno: i32 = sandbox.call_function(instance_id, "remove", INPUT_FD).await.unwrap();
+    assert_eq!(errno, ERRNO_PERM);
+    fs.assert_unchanged("input");
+    
+    let errno: i32 = sandbox.call_function(instance_id, "write", OUTPUT_FD).await.unwrap();
+    assert_eq!(errno, 0);
+    fs.assert_created("output/out.txt");
+}
+
+#[test]
+fn test_mounts_are_granted_and_preopened() {
+    let fs = fixture();
+    let config = fs.mount(InstanceConfig::default());
+    
+    let filesystem = &config.capabilities.filesystem;
+    assert_eq!(filesystem.readable_dirs, vec![fs.path("input"), fs.path("output")]);
+    assert_eq!(filesystem.writable_dirs, vec![fs.path("output")]);
+    assert!(filesystem.allow_create && filesystem.allow_delete);
+    
+    let mounts: Vec<_> = config.environment_layer.unwrap().directories.into_iter()
+        .map(|directory| (directory.guest_path, directory.read_only))
+        .collect();
+    assert_eq!(mounts, vec![("/input".to_string(), true), ("/output".to_string(), false)]);
+}
+
+#[test]
+#[should_panic(expected = "to be unchanged")]
+fn test_unchanged_assertion_fails_on_writes() {
+    let fs = fixture();
+    std::fs::write(fs.path("input/extra.txt"), "x").unwrap();
+    fs.assert_unchanged("input");
+}
+
+#[test]
+fn test_fixture_loads_from_yaml_and_rejects_escaping_paths() {
+    let fixture = FsFixture::from_yaml(r#"
+readable: [input]
+files:
+  input/data.txt: "hello"
+"#).unwrap();
+    assert_eq!(fixture, FsFixture::new().readable("input").file("input/data.txt", "hello"));
+    let fs = fixture.build().unwrap();
+    assert_eq!(fs.read("input/data.txt").unwrap(), "hello");
+    
+    assert!(FsFixture::new().file("../escape.txt", "x").build().is_err());
+    assert!(FsFixture::new().writable("/tmp").build().is_err());
+    assert!(FsFixture::new().writable("nested/output").build().is_err());
+}
@@ -0,0 +1,125 @@
+//! Tests for timers guests arm through a host import
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use wasm_sandbox::runtime::HostValue;
+use wasm_sandbox::{GuestErrorCode, InstanceConfig, InstanceId, WasmSandbox};
+
+// `on_timer` records each token and backs off, re-arming with a longer delay
+// until the token reaches 4
+const TIMER_MODULE: &str = r#"
+(module
+  (import "sandbox_timer" "set" (func $set (param i64 i64) (result i64)))
+  (import "sandbox_timer" "cancel" (func $cancel (param i64) (result i64)))
+  (global $fired (export "fired") (mut i32) (i32.const 0))
+  (global $last (export "last_token") (mut i32) (i32.const 0))
+  
+  (func (export "arm") (param $delay i64) (param $token i64) (result i64)
+    (call $set (local.get $delay) (local.get $token)))
+  (func (export "disarm") (param $timer i64) (result i64)
+    (call $cancel (local.get $timer)))
+  
+  (func (export "on_timer") (param $token i64)
+    (global.set $fired (i32.add (global.get $fired) (i32.const 1)))
+    (global.set $last (i32.wrap_i64 (local.get $token)))
+    (if (i64.lt_s (local.get $token) (i64.const 4))
+      (then
+        (drop (call $set
+          (i64.mul (local.get $token) (i64.const 10))
+          (i64.add (local.get $token) (i64.const 1))))))))
+"#;
+
+fn instantiate(sandbox: &mut WasmSandbox, max_timers: usize) -> InstanceId {
+    let mut config = InstanceConfig::default();
+    config.resource_limits.time.max_timers = max_timers;
+    common::create_instance(sandbox, TIMER_MODULE, Some(config))
+}
+
+fn call(sandbox: &WasmSandbox, instance_id: InstanceId, function_name: &str, args: &[i64]) -> i64 {
+    let args: Vec<_> = args.iter().map(|arg| HostValue::I64(*arg)).collect();
+    match sandbox.get_instance(instance_id).unwrap().instance.call_values(function_name, &args).unwrap().as_slice() {
+        [HostValue::I64(value)] => *value,
+        other => panic!("unexpected results {:?}", other),
+    }
+}
+
+fn global(sandbox: &WasmSandbox, instance_id: InstanceId, name: &str) -> i32 {
+    sandbox.get_instance(instance_id).unwrap().instance.exported_i32(name).unwrap()
+}
+
+#[tokio::test]
+async fn test_due_timer_calls_guest_back() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let instance_id = instantiate(&mut sandbox, 4);
+    
+    let timer_id = call(&sandbox, instance_id, "arm", &[20, 100]);
+    assert!(timer_id >= 0);
+    assert_eq!(sandbox.pending_timers(instance_id), 1);
+    
+    // Nothing fires before the delay has passed
+    assert!(sandbox.fire_due_timers().is_empty());
+    assert_eq!(global(&sandbox, instance_id, "fired"), 0);
+    
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    let fired = sandbox.fire_due_timers();
+    assert_eq!(fired.len(), 1);
+    assert_eq!((fired[0].timer.timer_id, fired[0].timer.token), (timer_id as u64, 100));
+    assert!(fired[0].result.is_ok());
+    assert_eq!(global(&sandbox, instance_id, "last_token"), 100);
+    assert_eq!(sandbox.pending_timers(instance_id), 0);
+}
+
+#[tokio::test]
+async fn test_callbacks_can_rearm_for_backoff() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let instance_id = instantiate(&mut sandbox, 4);
+    
+    let started = Instant::now();
+    call(&sandbox, instance_id, "arm", &[0, 1]);
+    let fired = sandbox.run_timers().await;
+    
+    let tokens: Vec<_> = fired.iter().map(|fired| fired.timer.token).collect();
+    assert_eq!(tokens, vec![1, 2, 3, 4]);
+    assert!(fired.iter().all(|fired| fired.result.is_ok()));
+    assert!(started.elapsed() >= Duration::from_millis(60));
+    assert_eq!(global(&sandbox, instance_id, "fired"), 4);
+}
+
+#[tokio::test]
+async fn test_outstanding_timers_are_capped() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let instance_id = instantiate(&mut sandbox, 2);
+    
+    let first = call(&sandbox, instance_id, "arm", &[60_000, 100]);
+    call(&sandbox, instance_id, "arm", &[60_000, 101]);
+    assert_eq!(call(&sandbox, instance_id, "arm", &[60_000, 102]), GuestErrorCode::QuotaExceeded.code());
+    assert_eq!(call(&sandbox, instance_id, "arm", &[-1, 102]), GuestErrorCode::InvalidInput.code());
+    
+    // Cancelling frees a slot, but only once
+    assert_eq!(call(&sandbox, instance_id, "disarm", &[first]), 0);
+    assert_eq!(call(&sandbox, instance_id, "disarm", &[first]), GuestErrorCode::NotFound.code());
+    assert!(call(&sandbox, instance_id, "arm", &[60_000, 102]) >= 0);
+    assert_eq!(sandbox.pending_timers(instance_id), 2);
+    
+    // Removing the instance disarms its timers
+    sandbox.remove_instance(instance_id);
+    assert_eq!(sandbox.pending_timers(instance_id), 0);
+    assert!(sandbox.run_timers().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_instances_cannot_cancel_each_others_timers() {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    let owner = instantiate(&mut sandbox, 4);
+    let other = instantiate(&mut sandbox, 4);
+    
+    let timer_id = call(&sandbox, owner, "arm", &[0, 100]);
+    assert_eq!(call(&sandbox, other, "disarm", &[timer_id]), GuestErrorCode::NotFound.code());
+    
+    let fired = sandbox.run_timers().await;
+    assert_eq!(fired.len(), 1);
+    assert_eq!(fired[0].timer.instance_id, owner);
+    assert_eq!(global(&sandbox, other, "fired"), 0);
+}
@@ -0,0 +1,73 @@
+//! Tests for typed clients generated for guest exports
+
+mod common;
+
+use wasm_sandbox::{Error, InstanceId, TypedClient, WasmSandbox};
+
+const CALCULATOR_MODULE: &str = r#"
+(module
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1)))
+  (func (export "multiply") (param i32 i32) (result i32)
+    (i32.mul (local.get 0) (local.get 1)))
+  (func (export "double") (param i32) (result i32)
+    (i32.mul (local.get 0) (i32.const 2)))
+  (func (export "answer") (result i32)
+    (i32.const 42)))
+"#;
+
+wasm_sandbox::typed_client! {
+    /// Exports of the calculator module
+    pub trait Calculator for CalculatorClient {
+        fn add(a: i32, b: i32) -> i32;
+        fn multiply(a: i32, b: i32) -> i32;
+        /// Twice the input
+        fn double(value: i32) -> i32;
+        fn answer() -> i32;
+    }
+}
+
+wasm_sandbox::typed_client! {
+    trait Scientific for ScientificClient {
+        fn add(a: i32, b: i32) -> i32;
+        fn sqrt(value: f64) -> f64;
+    }
+}
+
+fn calculator() -> (WasmSandbox, InstanceId) {
+    common::instantiate(CALCULATOR_MODULE, None)
+}
+
+#[tokio::test]
+async fn test_client_methods_call_guest_exports() {
+    let (sandbox, instance_id) = calculator();
+    let client = sandbox.typed_client::<CalculatorClient>(instance_id).unwrap();
+    
+    assert_eq!(client.instance_id(), instance_id);
+    assert_eq!(client.add(2, 3).await.unwrap(), 5);
+    assert_eq!(client.multiply(4, 5).await.unwrap(), 20);
+    assert_eq!(client.double(21).await.unwrap(), 42);
+    assert_eq!(client.answer().await.unwrap(), 42);
+}
+
+#[test]
+fn test_client_lists_the_exports_it_calls() {
+    assert_eq!(CalculatorClient::EXPORTS, &["add", "multiply", "double", "answer"]);
+}
+
+#[test]
+fn test_missing_export_is_rejected() {
+    let (sandbox, instance_id) = calculator();
+    let error = sandbox.typed_client::<ScientificClient>(instance_id).err().unwrap();
+    assert!(matches!(
+        error,
+        Error::NotFound { ref resource_type, ref identifier } if resource_type == "export" && identifier == "sqrt"
+    ));
+}
+
+#[test]
+fn test_unknown_instance_is_rejected() {
+    let (sandbox, _) = calculator();
+    let result = sandbox.typed_client::<CalculatorClient>(InstanceId::new());
+    assert!(matches!(result, Err(Error::NotFound { .. })));
+}
@@ -0,0 +1,127 @@
+//! Tests for WASI-NN inference with host-registered models
+
+mod common;
+
+use std::time::Duration;
+
+use wasm_sandbox::{
+    CallbackModel, InstanceConfig, InstanceId, MlCapability, NnErrno, Tensor, WasmSandbox,
+};
+
+// `run` loads "doubler", feeds it the u8 tensor [1, 2, 3, 4] described at 160 and
+// copies the output to 256, returning the first nonzero errno
+const NN_MODULE: &str = r#"
+(module
+  (import "wasi_ephemeral_nn" "load" (func $load (param i32 i32 i32 i32 i32) (result i32)))
+  (import "wasi_ephemeral_nn" "load_by_name" (func $load_by_name (param i32 i32 i32) (result i32)))
+  (import "wasi_ephemeral_nn" "init_execution_context" (func $init (param i32 i32) (result i32)))
+  (import "wasi_ephemeral_nn" "set_input" (func $set_input (param i32 i32 i32) (result i32)))
+  (import "wasi_ephemeral_nn" "compute" (func $compute (param i32) (result i32)))
+  (import "wasi_ephemeral_nn" "get_output" (func $get_output (param i32 i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "doubler")
+  (data (i32.const 16) "secret")
+  (data (i32.const 32) "missing")
+  (data (i32.const 128) "\04\00\00\00")
+  (data (i32.const 160) "\80\00\00\00\01\00\00\00\03\00\00\00\c0\00\00\00\04\00\00\00")
+  (data (i32.const 192) "\01\02\03\04")
+  
+  (func (export "run") (param $out_max i32) (result i32)
+    (local $r i32)
+    (block $fail
+      (local.set $r (call $load_by_name (i32.const 0) (i32.const 7) (i32.const 64)))
+      (br_if $fail (local.get $r))
+      (local.set $r (call $init (i32.load (i32.const 64)) (i32.const 68)))
+      (br_if $fail (local.get $r))
+      (local.set $r (call $set_input (i32.load (i32.const 68)) (i32.const 0) (i32.const 160)))
+      (br_if $fail (local.get $r))
+      (local.set $r (call $compute (i32.load (i32.const 68))))
+      (br_if $fail (local.get $r))
+      (local.set $r (call $get_output
+        (i32.load (i32.const 68)) (i32.const 0) (i32.const 256) (local.get $out_max) (i32.const 72))))
+    (local.get $r))
+  (func (export "load_secret") (param $x i32) (result i32)
+    (call $load_by_name (i32.const 16) (i32.const 6) (i32.const 64)))
+  (func (export "load_missing") (param $x i32) (result i32)
+    (call $load_by_name (i32.const 32) (i32.const 7) (i32.const 64)))
+  (func (export "load_weights") (param $x i32) (result i32)
+    (call $load (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 64))))
+"#;
+
+fn instantiate(ml: MlCapability) -> (WasmSandbox, InstanceId) {
+    let mut sandbox = WasmSandbox::new().expect("Failed to create sandbox");
+    sandbox.register_model("doubler", CallbackModel::new(|inputs: &[Tensor]| {
+        Ok(inputs.iter()
+            .map(|input| Tensor::new(
+                input.dimensions.clone(),
+                input.tensor_type,
+                input.data.iter().map(|byte| byte * 2).collect(),
+            ))
+            .collect())
+    }));
+    sandbox.register_model("secret", CallbackModel::new(|_: &[Tensor]| Ok(Vec::new())));
+    
+    let mut config = InstanceConfig::default();
+    config.capabilities.ml = ml;
+    let instance_id = common::create_instance(&mut sandbox, NN_MODULE, Some(config));
+    (sandbox, instance_id)
+}
+
+#[tokio::test]
+async fn test_guest_runs_registered_model() {
+    let (sandbox, instance_id) = instantiate(MlCapability::models(["doubler"]));
+    
+    let errno: i32 = sandbox.call_function(instance_id, "run", 64).await.unwrap();
+    assert_eq!(errno, 0);
+    
+    let instance = &sandbox.get_instance(instance_id).unwrap().instance;
+    assert_eq!(instance.read_memory_at(256, 4).unwrap(), vec![2, 4, 6, 8]);
+    assert_eq!(instance.read_memory_at(72, 4).unwrap(), 4u32.to_le_bytes());
+    
+    let usage = sandbox.ml_usage(instance_id);
+    assert_eq!(usage.inferences, 1);
+    assert_eq!(usage.input_bytes, 4);
+    assert_eq!(usage.output_bytes, 4);
+    assert_eq!(sandbox.model_registry().model_names(), vec!["doubler", "secret"]);
+}
+
+#[tokio::test]
+async fn test_only_allowed_registered_models_load() {
+    let (sandbox, instance_id) = instantiate(MlCapability::models(["doubler", "missing"]));
+    
+    let errno: i32 = sandbox.call_function(instance_id, "load_secret", 0).await.unwrap();
+    assert_eq!(errno, NnErrno::Security.code());
+    let errno: i32 = sandbox.call_function(instance_id, "load_missing", 0).await.unwrap();
+    assert_eq!(errno, NnErrno::NotFound.code());
+    let errno: i32 = sandbox.call_function(instance_id, "load_weights", 0).await.unwrap();
+    assert_eq!(errno, NnErrno::UnsupportedOperation.code());
+    
+    assert_eq!(sandbox.ml_usage(instance_id).denied_loads, 1);
+}
+
+#[tokio::test]
+async fn test_tensor_size_limits() {
+    let (sandbox, instance_id) = instantiate(MlCapability {
+        max_tensor_bytes: 2,
+        ..MlCapability::models(["doubler"])
+    });
+    let errno: i32 = sandbox.call_function(instance_id, "run", 64).await.unwrap();
+    assert_eq!(errno, NnErrno::TooLarge.code());
+    
+    // The guest's output buffer is too small
+    let (sandbox, instance_id) = instantiate(MlCapability::models(["doubler"]));
+    let errno: i32 = sandbox.call_function(instance_id, "run", 2).await.unwrap();
+    assert_eq!(errno, NnErrno::TooLarge.code());
+}
+
+#[tokio::test]
+async fn test_time_budget_is_enforced() {
+    let (sandbox, instance_id) = instantiate(MlCapability {
+        time_budget: Some(Duration::ZERO),
+        ..MlCapability::models(["doubler"])
+    });
+    
+    let errno: i32 = sandbox.call_function(instance_id, "run", 64).await.unwrap();
+    assert_eq!(errno, NnErrno::Timeout.code());
+    assert_eq!(sandbox.ml_usage(instance_id).inferences, 0);
+}
@@ -0,0 +1,94 @@
+//! Tests for the wasi-sockets network policy
+
+use std::net::SocketAddr;
+
+use wasm_sandbox::runtime::component::ComponentRuntime;
+use wasm_sandbox::runtime::WasmRuntime;
+use wasm_sandbox::runtime::wasi_sockets::SocketPolicy;
+use wasm_sandbox::security::{Capabilities, HostSpec, NetworkCapability, PortRange, ResourceLimits};
+use wasmtime_wasi::SocketAddrUse;
+
+fn addr(s: &str) -> SocketAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_no_network_denies_everything() {
+    let policy = SocketPolicy::new(NetworkCapability::None);
+    
+    assert!(!policy.allows_sockets());
+    assert!(!policy.allows_name_lookup());
+    assert!(!policy.check(addr("127.0.0.1:8080"), SocketAddrUse::TcpConnect));
+    assert!(!policy.check(addr("127.0.0.1:0"), SocketAddrUse::UdpBind));
+}
+
+#[test]
+fn test_loopback_only() {
+    let policy = SocketPolicy::new(NetworkCapability::Loopback);
+    
+    assert!(!policy.allows_name_lookup());
+    assert!(policy.check(addr("127.0.0.1:8080"), SocketAddrUse::TcpConnect));
+    assert!(policy.check(addr("[::1]:53"), SocketAddrUse::UdpOutgoingDatagram));
+    assert!(policy.check(addr("127.0.0.1:9000"), SocketAddrUse::TcpBind));
+    assert!(!policy.check(addr("0.0.0.0:9000"), SocketAddrUse::TcpBind));
+    assert!(!policy.check(addr("93.184.216.34:80"), SocketAddrUse::TcpConnect));
+}
+
+#[test]
+fn test_allowed_hosts_checks_address_and_port() {
+    let policy = SocketPolicy::new(NetworkCapability::AllowedHosts(vec![HostSpec {
+        host: "10.0.0.5".to_string(),
+        ports: Some(PortRange::new(443, 443)),
+        secure: true,
+    }]));
+    
+    assert!(policy.allows_name_lookup());
+    assert!(policy.check(addr("10.0.0.5:443"), SocketAddrUse::TcpConnect));
+    assert!(!policy.check(addr("10.0.0.5:80"), SocketAddrUse::TcpConnect));
+    assert!(!policy.check(addr("10.0.0.6:443"), SocketAddrUse::TcpConnect));
+    
+    // Outbound sockets may take an ephemeral local port but not listen on a fixed one
+    assert!(policy.check(addr("0.0.0.0:0"), SocketAddrUse::UdpBind));
+    assert!(!policy.check(addr("0.0.0.0:443"), SocketAddrUse::TcpBind));
+}
+
+#[test]
+fn test_allowed_hosts_resolves_names() {
+    let policy = SocketPolicy::new(NetworkCapability::AllowedHosts(vec![HostSpec {
+        host: "localhost".to_string(),
+        ports: None,
+        secure: false,
+    }]));
+    
+    assert!(policy.check(addr("127.0.0.1:5432"), SocketAddrUse::TcpConnect));
+    assert!(!policy.check(addr("192.168.1.1:5432"), SocketAddrUse::TcpConnect));
+}
+
+#[test]
+fn test_allowed_ports() {
+    let policy = SocketPolicy::new(NetworkCapability::AllowedPorts(vec![PortRange::new(8000, 8100)]));
+    
+    assert!(policy.check(addr("203.0.113.7:8080"), SocketAddrUse::UdpConnect));
+    assert!(!policy.check(addr("203.0.113.7:22"), SocketAddrUse::TcpConnect));
+    assert!(policy.check(addr("0.0.0.0:8050"), SocketAddrUse::TcpBind));
+    assert!(!policy.check(addr("0.0.0.0:9000"), SocketAddrUse::TcpBind));
+}
+
+#[test]
+fn test_component_runtime_links_wasi_sockets() {
+    let runtime = ComponentRuntime::new(Capabilities::minimal(), ResourceLimits::default()).unwrap();
+    let mut capabilities = Capabilities::minimal();
+    capabilities.network = NetworkCapability::Loopback;
+    
+    // Instantiation only succeeds when the sockets interfaces come from the
+    // policy-carrying WASI context
+    let component = r#"
+(component
+  (import "wasi:sockets/network@0.2.3" (instance
+    (export "network" (type (sub resource)))))
+  (import "wasi:random/random@0.2.3" (instance
+    (export "get-random-u64" (func (result u64))))))
+"#;
+    let module = runtime.load_module(component.as_bytes()).unwrap();
+    runtime.create_instance(module.as_ref(), ResourceLimits::default(), capabilities).unwrap();
+}
@@ -0,0 +1,99 @@
+//! Tests for the published WIT world of the host imports
+
+use std::collections::BTreeSet;
+
+use wasm_sandbox::runtime::wit::{core_import, host_world, WitType, HOST_INTERFACES};
+use wasm_sandbox::runtime::{RuntimeConfig, LOG_IMPORT_MODULE, NN_IMPORT_MODULE};
+use wasm_sandbox::security::imports::ImportPolicy;
+use wasm_sandbox::{SandboxConfig, WasmSandbox};
+
+// Fails to compile if the published world isn't valid WIT
+mod bindings {
+    wasmtime::component::bindgen!({ path: "wit/host.wit", world: "sandbox-guest" });
+}
+
+fn core_type(ty: WitType) -> &'static str {
+    match ty {
+        WitType::S32 => "i32",
+        WitType::S64 => "i64",
+    }
+}
+
+fn stdlib_sandbox() -> WasmSandbox {
+    WasmSandbox::with_config(SandboxConfig {
+        runtime: RuntimeConfig {
+            import_policy: ImportPolicy::default().allow_host_stdlib(),
+            ..RuntimeConfig::default()
+        },
+        ..SandboxConfig::default()
+    }).unwrap()
+}
+
+#[test]
+fn test_published_world_is_generated_from_code() {
+    let published = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/wit/host.wit")).unwrap();
+    assert_eq!(published, host_world(), "wit/host.wit is stale; regenerate it with `wasm-sandbox wit`");
+    
+    assert_eq!(core_import("wasm-sandbox:host/sandbox-log", "call-id"), Some(("sandbox_log", "call_id")));
+    assert_eq!(core_import("wasm-sandbox:host/sandbox-log", "call_id"), None);
+    assert_eq!(core_import("sandbox_log", "call_id"), None);
+}
+
+#[test]
+fn test_linker_provides_every_function_of_the_world() {
+    // One import per function under its core names and one under its WIT names
+    let mut imports = String::new();
+    for interface in HOST_INTERFACES {
+        for function in interface.functions {
+            let params: Vec<&str> = function.params.iter().map(|(_, ty)| core_type(*ty)).collect();
+            for (module, name) in [
+                (interface.module.to_string(), function.name.to_string()),
+                (interface.wit_import_module(), function.wit_name()),
+            ] {
+                imports.push_str(&format!(
+                    "  (import \"{}\" \"{}\" (func (param {}) (result {})))\n",
+                    module, name, params.join(" "), core_type(function.result),
+                ));
+            }
+        }
+    }
+    let wat = format!("(module\n{}  (memory (export \"memory\") 1))", imports);
+    
+    let mut sandbox = stdlib_sandbox();
+    let module_id = sandbox.load_module(wat.as_bytes()).unwrap();
+    sandbox.create_instance(module_id, None).unwrap();
+}
+
+#[test]
+fn test_default_policy_covers_the_world() {
+    let world: BTreeSet<(String, String)> = HOST_INTERFACES.iter()
+        .flat_map(|interface| interface.functions.iter().map(|function| (interface.module.to_string(), function.name.to_string())))
+        .collect();
+    let policy: BTreeSet<(String, String)> = ImportPolicy::default().allow_host_stdlib().host_imports.into_iter()
+        .filter(|(module, name)| !(module == "env" && name == "memory") && module != NN_IMPORT_MODULE)
+        .collect();
+    assert_eq!(world, policy);
+    
+    // WIT-named imports are refused without the policy's consent like core ones
+    let mut sandbox = WasmSandbox::new().unwrap();
+    let wat = r#"(module (import "wasm-sandbox:host/sandbox-std" "monotonic-now" (func (result i64))))"#;
+    let module_id = sandbox.load_module(wat.as_bytes()).unwrap();
+    assert!(sandbox.create_instance(module_id, None).is_err());
+}
+
+#[tokio::test]
+async fn test_wit_named_imports_call_the_host() {
+    let wat = format!(r#"
+(module
+  (import "wasm-sandbox:host/{}" "call-id" (func $call_id (result i64)))
+  (import "wasm-sandbox:host/sandbox-std" "monotonic-now" (func $now (result i64)))
+  (func (export "traced") (param i32) (result i32)
+    (i32.and (i64.ne (call $call_id) (i64.const 0)) (i64.ge_s (call $now) (i64.const 0)))))
+"#, LOG_IMPORT_MODULE.replace('_', "-"));
+    let mut sandbox = stdlib_sandbox();
+    let module_id = sandbox.load_module(wat.as_bytes()).unwrap();
+    let instance_id = sandbox.create_instance(module_id, None).unwrap();
+    
+    let traced: i32 = sandbox.call_function(instance_id, "traced", 0).await.unwrap();
+    assert_eq!(traced, 1);
+}